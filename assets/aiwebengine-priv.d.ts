@@ -72,6 +72,36 @@ interface UserStorage {
    * userStorage.removeUserRole("user123", "Editor");
    */
   removeUserRole(userId: string, role: string): void;
+
+  /**
+   * Get the custom profile fields of the caller, or of any user for administrators
+   * @param userId - User ID (defaults to the calling user)
+   * @returns JSON string of the profile: { userId, fields, updatedAt }
+   * @throws If another user's profile is requested without administrator privileges
+   * @example
+   * const profile = JSON.parse(userStorage.getUserProfile());
+   * const locale = profile.fields.locale ?? "en";
+   */
+  getUserProfile(userId?: string): string;
+
+  /**
+   * Change custom profile fields of the caller, or of any user for administrators.
+   * Fields set to null are removed. The result must match auth.profile_schema;
+   * fields marked readOnly in the schema can only be changed by administrators.
+   * @param fields - Fields to set or remove
+   * @param userId - User ID (defaults to the calling user)
+   * @returns JSON string of the updated profile
+   * @throws If no profile schema is configured, the profile would not match it, or permission is denied
+   * @example
+   * userStorage.updateUserProfile({ locale: "fi", theme: null });
+   */
+  updateUserProfile(fields: Record<string, unknown>, userId?: string): string;
+
+  /**
+   * Get the JSON Schema of the custom profile fields
+   * @returns JSON string of the schema, or null if profiles are not configured
+   */
+  getProfileSchema(): string | null;
 }
 
 /**
@@ -150,6 +180,104 @@ interface Console {
    */
   listLogsForUri(uri: string): string;
 
+  /**
+   * Query log entries with filters and cursor pagination, newest first
+   * (requires ViewLogs capability)
+   * @param options - Filters and paging; all fields are optional
+   * @returns JSON string of a LogPage, or an error message starting with "Error:"
+   * @example
+   * let page = JSON.parse(console.queryLogs({ levels: ["ERROR"], search: "timeout" }));
+   * while (page.nextCursor) {
+   *   page = JSON.parse(console.queryLogs({ levels: ["ERROR"], search: "timeout", cursor: page.nextCursor }));
+   * }
+   */
+  queryLogs(options?: LogQueryOptions): string;
+
+  /**
+   * Statement statistics collected by the query log, which is enabled with
+   * repository.query_log_enabled (requires ViewLogs capability)
+   * @param options - Filtering and size of the report
+   * @returns JSON string of a QueryStatsReport, or an error message starting with "Error:"
+   * @example
+   * const report = JSON.parse(console.queryStats({ slowOnly: true, limit: 10 }));
+   * for (const s of report.statements) {
+   *   console.log(`${s.totalMs}ms over ${s.calls} calls: ${s.fingerprint}`);
+   * }
+   */
+  queryStats(options?: QueryStatsOptions): string;
+
+  /**
+   * Clear the query log statistics (requires DeleteLogs capability)
+   * @returns Result message, or an error message starting with "Error:"
+   */
+  resetQueryStats(): string;
+
+  /**
+   * Per-tenant usage and the configured tenancy.quotas (requires ViewLogs
+   * capability). Counters are kept in memory since the server started.
+   * @param tenant - Report only this tenant
+   * @returns JSON string of a TenantUsageReport, or an error message starting with "Error:"
+   * @example
+   * const { tenants } = JSON.parse(console.tenantUsage());
+   * const busiest = tenants.sort((a, b) => b.requestsThisMinute - a.requestsThisMinute)[0];
+   */
+  tenantUsage(tenant?: string): string;
+
+  /**
+   * Monthly LLM token usage and estimated cost per script and user, with the
+   * [javascript.llm.budgets] limits (requires ViewLogs capability)
+   * @returns JSON string of an LlmUsageReport, or an error message starting with "Error:"
+   * @example
+   * const { scripts } = JSON.parse(console.llmUsage({ month: "2026-10" }));
+   * const overBudget = scripts.filter((s) => s.overSoftLimit);
+   */
+  llmUsage(options?: { month?: string; scriptUri?: string; userId?: string }): string;
+
+  /**
+   * Webhook deliveries of every script, newest first (requires ViewLogs
+   * capability)
+   * @returns JSON string of WebhookDelivery[], or an error message starting with "Error:"
+   * @example
+   * const failed = JSON.parse(console.webhookDeliveries({ status: "failed" }));
+   */
+  webhookDeliveries(options?: {
+    status?: "pending" | "delivered" | "failed";
+    scriptUri?: string;
+    limit?: number;
+  }): string;
+
+  /**
+   * Background tasks of every script, newest first (requires ViewLogs
+   * capability)
+   * @returns JSON string of ScriptTask[], or an error message starting with "Error:"
+   * @example
+   * const failed = JSON.parse(console.scriptTasks({ status: "failed" }));
+   */
+  scriptTasks(options?: {
+    status?: "pending" | "running" | "completed" | "failed";
+    scriptUri?: string;
+    limit?: number;
+  }): string;
+
+  /**
+   * Task queue depth per script: pending, running, completed and failed
+   * tasks and the oldest due task not yet running (requires ViewLogs
+   * capability)
+   * @returns JSON string of TaskQueueStats[], or an error message starting with "Error:"
+   */
+  taskQueueStats(): string;
+
+  /**
+   * Sent and failed emails of every script, newest first (requires
+   * ViewLogs capability)
+   * @returns JSON string of EmailLogEntry[], or an error message starting with "Error:"
+   */
+  emailLog(options?: {
+    status?: "sent" | "failed";
+    scriptUri?: string;
+    limit?: number;
+  }): string;
+
   /**
    * Prune old log entries (requires ViewLogs capability)
    * @returns Prune operation result message
@@ -160,6 +288,199 @@ interface Console {
   pruneLogs(): string;
 }
 
+/**
+ * Options for console.queryLogs()
+ */
+interface LogQueryOptions {
+  /** Only entries written by this script */
+  scriptUri?: string;
+  /** Only entries with one of these levels (e.g. "ERROR", "WARN") */
+  levels?: string[];
+  /** Inclusive lower bound: milliseconds since the epoch or RFC 3339 string */
+  since?: number | string;
+  /** Exclusive upper bound: milliseconds since the epoch or RFC 3339 string */
+  until?: number | string;
+  /** Case-insensitive text the message must contain */
+  search?: string;
+  /** Structured fields the entry's data must contain, e.g. { userId: 42 } */
+  data?: Record<string, any>;
+  /** Only entries written while handling this request (see LogRecord.context) */
+  requestId?: string;
+  /** nextCursor of the previous page */
+  cursor?: string;
+  /** Page size (default 100, max 1000) */
+  limit?: number;
+}
+
+/**
+ * Log entry returned by console.queryLogs()
+ */
+interface LogRecord {
+  id: string;
+  scriptUri: string;
+  level: string;
+  message: string;
+  /** Object logged with the message, e.g. console.log("login", { userId }) */
+  data?: any;
+  /** Captured automatically: requestId, handler, kind, method, path */
+  context?: {
+    requestId?: string;
+    handler?: string;
+    kind?: string;
+    method?: string;
+    path?: string;
+  } | null;
+  /** RFC 3339 timestamp */
+  timestamp: string;
+  /** Cursor continuing the query after this entry */
+  cursor: string;
+}
+
+/**
+ * Page of log entries returned by console.queryLogs()
+ */
+interface LogPage {
+  entries: LogRecord[];
+  /** Present when more entries match the query */
+  nextCursor?: string | null;
+}
+
+/**
+ * Options for console.queryStats()
+ */
+interface QueryStatsOptions {
+  /** Only statements with at least one call over the slow threshold */
+  slowOnly?: boolean;
+  /** Maximum statements and slow occurrences returned (default 50, max 500) */
+  limit?: number;
+}
+
+/**
+ * Aggregate for statements sharing a fingerprint (literals replaced with ?)
+ */
+interface QueryStatement {
+  fingerprint: string;
+  calls: number;
+  slowCalls: number;
+  totalMs: number;
+  avgMs: number;
+  maxMs: number;
+  rowsReturned: number;
+  rowsAffected: number;
+  /** Handlers that issued the statement, most frequent first */
+  handlers: { handler: string; calls: number }[];
+}
+
+/**
+ * One statement that exceeded the slow threshold
+ */
+interface SlowQuery {
+  fingerprint: string;
+  durationMs: number;
+  rowsReturned: number;
+  rowsAffected: number;
+  handler?: string;
+  requestId?: string;
+  /** RFC 3339 timestamp */
+  timestamp: string;
+}
+
+/**
+ * Report returned by console.queryStats()
+ */
+interface QueryStatsReport {
+  /** False when repository.query_log_enabled is off; the lists are then empty */
+  enabled: boolean;
+  slowThresholdMs: number;
+  /** RFC 3339 time collection started or was last reset */
+  since: string | null;
+  /** Statements not aggregated because too many distinct fingerprints were seen */
+  untrackedCalls: number;
+  /** Highest total time first */
+  statements: QueryStatement[];
+  /** Newest first */
+  recentSlow: SlowQuery[];
+}
+
+/**
+ * Usage of one tenant in console.tenantUsage()
+ */
+interface TenantUsage {
+  tenant: string;
+  /** Handler requests in the current one-minute window */
+  requestsThisMinute: number;
+  /** Handler execution time in the current one-minute window */
+  executionMsThisMinute: number;
+  totalRequests: number;
+  totalExecutionMs: number;
+  /** Requests and stream connections rejected with HTTP 429 */
+  throttledRequests: number;
+  streamConnections: number;
+  /** Live sharedStorage bytes across all scripts */
+  storageBytes: number;
+}
+
+/**
+ * Report returned by console.tenantUsage()
+ */
+interface TenantUsageReport {
+  /** Whether tenancy.enabled is set; without it nothing is counted */
+  enabled: boolean;
+  /** Configured per-tenant limits; 0 means unlimited */
+  quotas: {
+    requestsPerMinute: number;
+    executionMsPerMinute: number;
+    maxStreamConnections: number;
+    storageBytes: number;
+  };
+  /** Sorted by tenant ID */
+  tenants: TenantUsage[];
+}
+
+/**
+ * Usage of one script, user, provider and model in console.llmUsage()
+ */
+interface LlmUsageEntry {
+  scriptUri: string;
+  /** null for calls made without a signed-in user */
+  userId: string | null;
+  provider: string;
+  model: string;
+  calls: number;
+  inputTokens: number;
+  outputTokens: number;
+  /** Estimated from [javascript.llm.prices], in USD */
+  cost: number;
+}
+
+/**
+ * Month-to-date totals of a script or user against its budget
+ */
+interface LlmBudgetStatus {
+  /** Script URI or user ID */
+  id: string;
+  calls: number;
+  inputTokens: number;
+  outputTokens: number;
+  cost: number;
+  softLimit: number | null;
+  hardLimit: number | null;
+  overSoftLimit: boolean;
+  /** Further calls are refused until the month ends */
+  overHardLimit: boolean;
+}
+
+/**
+ * Report returned by console.llmUsage()
+ */
+interface LlmUsageReport {
+  /** YYYY-MM */
+  month: string;
+  entries: LlmUsageEntry[];
+  scripts: LlmBudgetStatus[];
+  users: LlmBudgetStatus[];
+}
+
 // ============================================================================
 // Route Registry API (Privileged Scripts Only)
 // ============================================================================
@@ -188,6 +509,36 @@ interface RouteIntrospectionEntry {
 
   /** Tags associated with the registered route */
   tags: string[];
+
+  /** Hosts the route is limited to; empty when it is served on any host */
+  hosts?: string[];
+
+  /** Rate limit registered with the route, if any */
+  rateLimit?: RouteRateLimit;
+
+  /** Idempotency-Key handling registered with the route, if any */
+  idempotency?: RouteIdempotency;
+
+  /** Execution timeout registered with the route, if any */
+  timeoutMs?: number;
+}
+
+/**
+ * Parsed entry from JSON returned by admin.routeConflicts()
+ */
+interface RouteConflict {
+  /** Host the route is registered for; null when it is served on any host */
+  host: string | null;
+
+  method: string;
+
+  path: string;
+
+  /** URIs of the scripts registering the route, the winner first */
+  scripts: string[];
+
+  /** URI of the script whose handler serves the route */
+  winner: string;
 }
 
 /**
@@ -197,6 +548,9 @@ interface StreamIntrospectionEntry {
   /** Registered stream path */
   path: string;
 
+  /** Host the stream is limited to, or null when it is served on any host */
+  host: string | null;
+
   /** URI of the script that registered the stream */
   script_uri: string;
 }
@@ -404,11 +758,272 @@ interface ScriptMetadata {
   /** Whether script has privileged access */
   privileged: boolean;
 
+  /** Whether script is a protected system script */
+  system: boolean;
+
   /** Whether script has been initialized */
   initialized: boolean;
 
   /** Initialization error message if any */
   initError?: string;
+
+  /** Human-readable summary of the script */
+  description: string | null;
+
+  /** Lowercase tags used to categorize the script */
+  tags: string[];
+
+  /** Highest migration version applied with db.migrate, or null */
+  schemaVersion: number | null;
+
+  /** Timeout of the script's route handlers in milliseconds, or null for the configured timeout */
+  executionTimeoutMs: number | null;
+
+  /** Memory limit of the script's executions in bytes, or null for the configured limit */
+  memoryLimitBytes: number | null;
+}
+
+/**
+ * Script description and tags
+ */
+interface ScriptLabels {
+  /** Human-readable summary (null clears it) */
+  description?: string | null;
+
+  /** Tags: letters, digits and - _ . : / (normalized to lowercase, max 20) */
+  tags?: string[] | null;
+}
+
+/**
+ * Options for scriptStorage.upsertScript()
+ */
+interface UpsertScriptOptions extends ScriptLabels {
+  /**
+   * Source map (JSON, at most 10 MB) for a script bundled or minified before
+   * upload. Stack frames in error reports and logs are translated through it
+   * while the script keeps this content. null removes the stored map.
+   */
+  sourceMap?: string | null;
+
+  /** Overwrite a protected system script (admin only) */
+  force?: boolean;
+}
+
+/**
+ * Staging slot of a script, from scriptStorage.stageScript() and friends
+ */
+interface ScriptStaging {
+  uri: string;
+
+  /** Version waiting to be promoted */
+  stagedContent: string | null;
+
+  stagedAt: string | null;
+
+  /** User who staged the version */
+  stagedBy: string | null;
+
+  /** Why the staged version's init() failed; promotion is refused */
+  stagedInitError: string | null;
+
+  /** Routes the staged version registers, as "METHOD path" */
+  stagedRoutes: string[];
+
+  /** Version replaced by the last promotion or rollback */
+  previousContent: string | null;
+
+  promotedAt: string | null;
+}
+
+/**
+ * Request a dry run executes against, for scriptStorage.dryRun()
+ */
+interface DryRunRequest {
+  /** Name of the handler function to call */
+  handler: string;
+
+  /** HTTP method (default GET) */
+  method?: string;
+
+  /** Request path (default /); a query string in it is merged into query */
+  path?: string;
+
+  headers?: Record<string, string>;
+
+  query?: Record<string, string>;
+
+  /** Route parameters, as a pattern like /users/:id would extract them */
+  params?: Record<string, string>;
+
+  /** Raw request body; url-encoded bodies also fill request.form */
+  body?: string;
+}
+
+/**
+ * Outcome of scriptStorage.dryRun()
+ */
+interface DryRunResult {
+  /** The handler's response; absent when the run failed */
+  response?: {
+    status: number;
+    contentType: string | null;
+    headers: Record<string, string>;
+    /** Body as text, when it is UTF-8 */
+    body?: string;
+    /** Body as base64, when it is not UTF-8 */
+    bodyBase64?: string;
+  };
+
+  /** Why the run failed */
+  error?: string;
+
+  /** The exception the script threw, with source-mapped positions */
+  exception?: {
+    name?: string;
+    message: string;
+    stack?: string;
+    fileName?: string;
+    lineNumber?: number;
+    columnNumber?: number;
+  };
+
+  /** Log messages written during the run; they are not stored */
+  logs: { level: string; message: string; data?: unknown; timestamp: string }[];
+
+  /** Whether log messages beyond the capture limit were dropped */
+  logsTruncated: boolean;
+
+  durationMs: number;
+}
+
+/**
+ * Outcome of scriptStorage.lintScript()
+ */
+interface ScriptLintReport {
+  /** Whether the script parses */
+  valid: boolean;
+
+  /** Where the script fails to parse */
+  error?: { message: string; line: number; column: number };
+
+  /** Findings of the rules configured in [javascript.lint] */
+  warnings: {
+    /** "unused-handler", "missing-init" or "forbidden-global" */
+    rule: string;
+    message: string;
+    line: number;
+    column: number;
+  }[];
+}
+
+/**
+ * Storage used by a script, as returned by scriptStorage.getStorageUsage()
+ */
+interface ScriptStorageUsage {
+  scriptUri: string;
+
+  /** Bytes of live sharedStorage keys and values */
+  propertiesBytes: number;
+
+  /** Bytes of asset content */
+  assetBytes: number;
+
+  usedBytes: number;
+
+  /** Effective quota in bytes, or null when unlimited */
+  quotaBytes: number | null;
+
+  /** Per-script override of the configured default, or null */
+  quotaOverride: number | null;
+}
+
+/**
+ * Usage of one route, as reported by scriptStorage.getRouteUsage()
+ */
+interface RouteUsage {
+  /** Registered pattern, e.g. "/items/:id" */
+  route: string;
+  method: string;
+  requests: number;
+
+  /** Requests answered with a 5xx status */
+  errors: number;
+  statusCodes: { status: number; count: number }[];
+
+  /** Latencies in milliseconds, or null when the route got no requests */
+  avgMs: number | null;
+  p50Ms: number | null;
+  p95Ms: number | null;
+  maxMs: number | null;
+
+  /** RFC 3339 timestamp, or null */
+  lastRequestAt: string | null;
+}
+
+/**
+ * Route usage of a script, as returned by scriptStorage.getRouteUsage()
+ */
+interface RouteUsageReport {
+  scriptUri: string;
+
+  /** First day reported (YYYY-MM-DD, UTC) */
+  since: string;
+  days: number;
+
+  /** Most requested first; registered routes without requests last */
+  routes: RouteUsage[];
+}
+
+/**
+ * Resource usage of one script, as reported by scriptStorage.getResourceUsage()
+ */
+interface ScriptResourceUsage {
+  scriptUri: string;
+  executions: number;
+
+  /** Milliseconds the script's executions ran */
+  cpuMs: number;
+
+  /** Average milliseconds per execution, or null without executions */
+  avgCpuMs: number | null;
+
+  /** Most heap an execution had allocated when it finished */
+  peakMemoryBytes: number;
+
+  /** Executions interrupted by the execution timeout */
+  timeouts: number;
+
+  /** Bytes sent and received by fetch() and webhook deliveries */
+  outboundBytes: number;
+
+  /** RFC 3339 timestamp, or null */
+  lastExecutionAt: string | null;
+}
+
+/**
+ * Script resource usage, as returned by scriptStorage.getResourceUsage()
+ */
+interface ScriptResourceReport {
+  /** First day reported (YYYY-MM-DD, UTC) */
+  since: string;
+  days: number;
+
+  /** Most execution time first */
+  scripts: ScriptResourceUsage[];
+}
+
+/**
+ * Filter for listing scripts
+ */
+interface ScriptListFilter {
+  /** Only scripts carrying this tag */
+  tag?: string;
+
+  /** Only scripts carrying all of these tags */
+  tags?: string[];
+
+  /** Case-insensitive match against uri, name and description */
+  search?: string;
 }
 
 /**
@@ -418,11 +1033,13 @@ interface ScriptMetadata {
 interface ScriptStorage {
   /**
    * List all scripts with metadata (requires ReadScripts capability)
+   * @param filter - Optional tag and text filter
    * @returns JSON string array of script metadata
    * @example
    * const scripts = JSON.parse(scriptStorage.listScripts());
+   * const apis = JSON.parse(scriptStorage.listScripts({ tag: "api" }));
    */
-  listScripts(): string;
+  listScripts(filter?: ScriptListFilter): string;
 
   /**
    * Get script content by name (requires ReadScripts capability)
@@ -455,20 +1072,249 @@ interface ScriptStorage {
    * Create or update a script (requires WriteScripts capability)
    * @param scriptName - Script name/URI
    * @param content - Script content
-   * @returns Result message
+   * @param options - Optional description, tags and source map; omitted keys keep their current value
+   * @returns Result message; scripts that do not parse are rejected with
+   * "Error: Syntax error at line L, column C: ..."
    * @example
    * scriptStorage.upsertScript("my-script", "function init() { ... }");
+   * scriptStorage.upsertScript("my-script", content, { tags: ["api", "billing"] });
+   * scriptStorage.upsertScript("bundle.js", bundle, { sourceMap: bundleMap });
+   */
+  upsertScript(scriptName: string, content: string, options?: UpsertScriptOptions): string;
+
+  /**
+   * Run an unsaved script body as scriptName and call one of its handlers
+   * with a described request, without saving the script (requires
+   * WriteScripts capability and permission to modify the script). Database
+   * writes are rolled back when the run ends, logs are captured instead of
+   * stored, and stream, dispatcher and scheduler calls and fetch() requests
+   * other than GET and HEAD are refused.
+   * @param scriptName - Script name/URI the body runs as
+   * @param content - Script content to run
+   * @param request - Handler and request to call it with
+   * @returns JSON string of a DryRunResult, or an error message
+   * @example
+   * const result = JSON.parse(scriptStorage.dryRun("my-script", draft, {
+   *   handler: "createOrder",
+   *   method: "POST",
+   *   path: "/orders",
+   *   headers: { "content-type": "application/json" },
+   *   body: JSON.stringify({ item: "book" }),
+   * }));
    */
-  upsertScript(scriptName: string, content: string): string;
+  dryRun(scriptName: string, content: string, request: DryRunRequest): string;
 
   /**
-   * Delete a script (requires ownership or admin privileges)
+   * Check that a script body parses and lint it, without saving it
+   * @param scriptName - Script name/URI; its extension selects TypeScript or JSX
+   * @param content - Script content to check
+   * @returns JSON string of a ScriptLintReport
+   * @example
+   * const report = JSON.parse(scriptStorage.lintScript("my-script", draft));
+   * report.warnings.forEach((w) => console.warn(`${w.line}:${w.column} ${w.message}`));
+   */
+  lintScript(scriptName: string, content: string): string;
+
+  /**
+   * Get the description and tags of a script (requires ReadScripts capability)
+   * @param scriptName - Script name/URI
+   * @returns JSON string with { description, tags } or null if not found
+   * @example
+   * const labels = JSON.parse(scriptStorage.getScriptLabels("my-script"));
+   */
+  getScriptLabels(scriptName: string): string | null;
+
+  /**
+   * Update the description and tags of a script without changing its content
+   * (requires WriteScripts capability and ownership or admin privileges)
+   * @param scriptName - Script name/URI
+   * @param labels - Keys to change; omitted keys keep their current value
+   * @returns JSON string with the stored { description, tags }, or an "Error: ..." message
+   * @example
+   * scriptStorage.setScriptLabels("my-script", { description: "Billing API" });
+   */
+  setScriptLabels(scriptName: string, labels: ScriptLabels): string;
+
+  /**
+   * Get the bytes a script stores in shared storage and assets, and its quota
+   * (requires ReadScripts capability)
+   * @param scriptName - Script name/URI
+   * @returns JSON string with a ScriptStorageUsage object, or null
+   * @example
+   * const usage = JSON.parse(scriptStorage.getStorageUsage("my-script"));
+   */
+  getStorageUsage(scriptName: string): string | null;
+
+  /**
+   * Get request counts, status codes and p50/p95 latency of a script's
+   * routes (requires ReadScripts capability)
+   * @param scriptName - Script name/URI
+   * @param options - days: days to report, ending today (default 7, at most 366)
+   * @returns JSON string with a RouteUsageReport object, or an "Error: ..." message
+   * @example
+   * const usage = JSON.parse(scriptStorage.getRouteUsage("my-script", { days: 30 }));
+   */
+  getRouteUsage(scriptName: string, options?: { days?: number }): string;
+
+  /**
+   * Get execution time, peak memory, timeouts and outbound bytes per script
+   * (requires ReadScripts capability)
+   * @param options - days: days to report, ending today (default 7, at most 366);
+   *   uri: only this script; limit: most scripts reported (default 50)
+   * @returns JSON string with a ScriptResourceReport object, or an "Error: ..." message
+   * @example
+   * const usage = JSON.parse(scriptStorage.getResourceUsage({ days: 1, limit: 10 }));
+   */
+  getResourceUsage(options?: { days?: number; uri?: string; limit?: number }): string;
+
+  /**
+   * Override the storage quota of a script (admin only). Writes to
+   * sharedStorage and assetStorage that would exceed the quota fail.
+   * @param scriptName - Script name/URI
+   * @param quotaBytes - Quota in bytes, 0 for unlimited, or null to use the
+   * configured default (repository.default_storage_quota_bytes)
+   * @returns True if successful
+   * @example
+   * scriptStorage.setStorageQuota("uploads", 500 * 1024 * 1024);
+   */
+  setStorageQuota(scriptName: string, quotaBytes: number | null): boolean;
+
+  /**
+   * Override the timeout of a script's route handlers (admin only), e.g. for
+   * a known-slow report generator
+   * @param scriptName - Script name/URI
+   * @param timeoutMs - Timeout in milliseconds, at most
+   * javascript.max_script_execution_timeout_ms, or null to use the configured
+   * timeout (javascript.execution_timeout_ms)
+   * @returns True if successful
+   * @example
+   * scriptStorage.setExecutionTimeout("reports", 30000);
+   */
+  setExecutionTimeout(scriptName: string, timeoutMs: number | null): boolean;
+
+  /**
+   * Override the memory limit of every execution of a script (admin only).
+   * An execution that exceeds it is terminated and a FATAL entry is written
+   * to the script's log.
+   * @param scriptName - Script name/URI
+   * @param limitBytes - Limit in bytes, at least 1 MB and at most
+   * javascript.max_heap_bytes, or null to use the configured limit
+   * (javascript.max_memory_bytes)
+   * @returns True if successful
+   * @example
+   * scriptStorage.setMemoryLimit("image-report", 64 * 1024 * 1024);
+   */
+  setMemoryLimit(scriptName: string, limitBytes: number | null): boolean;
+
+  /**
+   * Get a script's capability manifest (security.script_manifests)
+   * @param scriptName - Script name/URI
+   * @returns JSON { uri, requested, approved, approvedBy, approvedAt, pending }
+   * or null if the script doesn't exist
+   */
+  getScriptCapabilities(scriptName: string): string | null;
+
+  /**
+   * Replace the sandbox capabilities a script requests (requires ownership or
+   * admin privileges). New capabilities wait for an administrator's approval;
+   * approvals of capabilities no longer requested are dropped.
+   * @param scriptName - Script name/URI
+   * @param capabilities - Any of "fetch", "secrets", "graphql", "streams", "db",
+   * "email", "notify", "llm", "tasks"
+   * @returns JSON manifest, or an "Error: ..." string
+   * @example
+   * scriptStorage.requestScriptCapabilities("reports", ["db", "fetch"]);
+   */
+  requestScriptCapabilities(
+    scriptName: string,
+    capabilities: Array<
+      | "fetch"
+      | "secrets"
+      | "graphql"
+      | "streams"
+      | "db"
+      | "email"
+      | "notify"
+      | "llm"
+      | "tasks"
+    >,
+  ): string;
+
+  /**
+   * Delete a script (requires ownership or admin privileges). The script, its
+   * assets and tables move to the trash and can be restored until the
+   * retention period (repository.trash_retention_days) has passed.
    * @param scriptName - Script name/URI
+   * @param force - Delete a protected system script
    * @returns True if deleted, false if failed
    * @example
    * scriptStorage.deleteScript("old-script");
    */
-  deleteScript(scriptName: string): boolean;
+  deleteScript(scriptName: string, force?: boolean): boolean;
+
+  /**
+   * List deleted scripts that can still be restored (admin only)
+   * @returns JSON array of { uri, name, owners, assetCount, deletedAt } or "Error: ..." message
+   * @example
+   * const trashed = JSON.parse(scriptStorage.listTrashedScripts());
+   */
+  listTrashedScripts(): string;
+
+  /**
+   * Restore a deleted script with its owners, collaborators, tables and the
+   * assets deleted with it (admin only). Fails if the URI is in use again.
+   * @param scriptName - Script name/URI
+   * @returns Result message
+   * @example
+   * scriptStorage.restoreScript("old-script");
+   */
+  restoreScript(scriptName: string): string;
+
+  /**
+   * Stage a new version of a script without replacing the live one. Its
+   * init() runs in a sandbox; the outcome is kept with the staging slot.
+   * Try the staged content with dryRun() before promoting it.
+   * @param scriptName - URI of an existing script
+   * @param content - The new version
+   * @returns JSON ScriptStaging, or "Error: ..." message
+   * @example
+   * const slot = JSON.parse(scriptStorage.stageScript("https://example.com/blog", source));
+   * if (slot.stagedInitError) console.error(slot.stagedInitError);
+   */
+  stageScript(scriptName: string, content: string): string;
+
+  /**
+   * Make the staged version live on every instance, keeping the replaced
+   * version for rollbackScript(). Refused when the staged init() failed.
+   * @returns JSON ScriptStaging, or "Error: ..." message
+   */
+  promoteStagedScript(scriptName: string): string;
+
+  /**
+   * Make the version replaced by the last promotion or rollback live again
+   * @returns JSON ScriptStaging, or "Error: ..." message
+   */
+  rollbackScript(scriptName: string): string;
+
+  /**
+   * Drop the staged version of a script
+   * @returns JSON ScriptStaging, or "Error: ..." message
+   */
+  discardStagedScript(scriptName: string): string;
+
+  /**
+   * @returns JSON ScriptStaging, "null" when nothing was ever staged, or
+   * "Error: ..." message
+   */
+  getScriptStaging(scriptName: string): string;
+
+  /**
+   * Permanently remove trash entries older than the retention period (admin only)
+   * @returns JSON string { scripts, assets, tables } with purged counts, or "Error: ..." message
+   * @example
+   * const summary = JSON.parse(scriptStorage.purgeTrash());
+   */
+  purgeTrash(): string;
 
   /**
    * Set privileged status for a script (admin only)
@@ -480,6 +1326,18 @@ interface ScriptStorage {
    */
   setScriptPrivileged(scriptName: string, privileged: boolean): boolean;
 
+  /**
+   * Mark a script as a system script, which can only be overwritten or
+   * deleted with force, or lift the protection (admin only). Bootstrapped
+   * feature scripts are system scripts.
+   * @param scriptName - Script name/URI
+   * @param system - Whether the script should be protected
+   * @returns True if successful
+   * @example
+   * scriptStorage.setScriptSystem("billing", true);
+   */
+  setScriptSystem(scriptName: string, system: boolean): boolean;
+
   /**
    * Check if current user can manage script privileges (admin capability check)
    * @returns True if user has admin capability
@@ -516,6 +1374,38 @@ interface ScriptStorage {
    * scriptStorage.removeScriptOwner("my-script", "user123");
    */
   removeScriptOwner(scriptName: string, userId: string): string;
+
+  /**
+   * Get list of collaborator user IDs for a script. Collaborators may edit
+   * the script but cannot delete it or manage its owners and collaborators.
+   * @param scriptName - Script name/URI
+   * @returns JSON string array of collaborator user IDs
+   * @example
+   * const collaborators = JSON.parse(scriptStorage.getScriptCollaborators("my-script"));
+   */
+  getScriptCollaborators(scriptName: string): string;
+
+  /**
+   * Add a collaborator to a script (requires current ownership or admin).
+   * "group:<name>" makes every member of a user group a collaborator.
+   * @param scriptName - Script name/URI
+   * @param userId - User ID, or "group:<name>", to add as collaborator
+   * @returns Result message
+   * @example
+   * scriptStorage.addScriptCollaborator("my-script", "user456");
+   * scriptStorage.addScriptCollaborator("my-script", "group:support");
+   */
+  addScriptCollaborator(scriptName: string, userId: string): string;
+
+  /**
+   * Remove a collaborator from a script (requires current ownership or admin)
+   * @param scriptName - Script name/URI
+   * @param userId - User ID to remove
+   * @returns Result message
+   * @example
+   * scriptStorage.removeScriptCollaborator("my-script", "user456");
+   */
+  removeScriptCollaborator(scriptName: string, userId: string): string;
 }
 
 // ============================================================================
@@ -560,6 +1450,7 @@ interface AssetStorage {
    * @param name - Asset name/identifier
    * @param mimetype - MIME type (e.g., "image/svg+xml", "text/css")
    * @param contentBase64 - Base64-encoded asset content
+   * @param options - Optional response headers (omit to keep the current ones)
    * @returns Success message or error message
    * @example
    * const base64Content = btoa("<svg>...</svg>");
@@ -570,6 +1461,7 @@ interface AssetStorage {
     name: string,
     mimetype: string,
     contentBase64: string,
+    options?: Pick<AssetOptions, "headers">,
   ): string;
 
   /**
@@ -581,6 +1473,271 @@ interface AssetStorage {
    * assetStorage.deleteAssetForUri("my-script", "old-logo.svg");
    */
   deleteAssetForUri(uri: string, name: string): string;
+
+  /**
+   * List deleted assets of a specific script URI that can still be restored
+   * @param uri - Script URI that owned the assets
+   * @returns JSON array of { uri, scriptUri, name, mimetype, size, deletedAt } or error message
+   * @example
+   * const trashed = JSON.parse(assetStorage.listTrashedAssetsForUri("my-script"));
+   */
+  listTrashedAssetsForUri(uri: string): string;
+
+  /**
+   * Restore a deleted asset of a specific script URI (requires DeleteAssets capability)
+   * @param uri - Script URI that owns the asset
+   * @param name - Asset name/identifier to restore
+   * @returns Success message or error message
+   * @example
+   * assetStorage.restoreAssetForUri("my-script", "old-logo.svg");
+   */
+  restoreAssetForUri(uri: string, name: string): string;
+}
+
+// ============================================================================
+// Admin Operations API (Privileged Scripts Only)
+// ============================================================================
+
+/**
+ * Maintenance mode state. While enabled, script routes answer 503 with
+ * Retry-After to everyone but administrators.
+ */
+interface MaintenanceStatus {
+  enabled: boolean;
+  /** Shown to clients in the 503 response */
+  message?: string;
+  retryAfterSeconds: number;
+  /** When maintenance mode was switched on (RFC 3339) */
+  since?: string;
+}
+
+/**
+ * Action taken or queued by a threat response policy
+ * (`[security.threat_response]`)
+ */
+interface ThreatAction {
+  id: string;
+  policy: string;
+  action: "block_ip" | "quarantine_script" | "alert";
+  /** IP address or script URI acted on */
+  subject: string;
+  /** Threat indicator the policy acts on, e.g. "brute_force_authentication" */
+  indicator: string;
+  description: string;
+  status: "pending" | "active" | "dismissed" | "lifted";
+  /** How long the action lasts once active (0 = until lifted) */
+  durationSecs: number;
+  expiresAt?: string;
+  createdAt: string;
+  decidedBy?: string;
+  decidedAt?: string;
+}
+
+/**
+ * Operational actions for administrators (requires admin). Each action is
+ * applied on every server instance. Errors are returned as strings starting
+ * with "Error:".
+ */
+interface Admin {
+  /**
+   * Drop a script's registrations and run its init() again
+   * @param uri - Script URI
+   * @returns Result message
+   * @example
+   * admin.reloadScript("https://example.com/blog");
+   */
+  reloadScript(uri: string): string;
+
+  /**
+   * Rebuild the GraphQL schema from the current registrations
+   * @returns Result message
+   */
+  rebuildGraphQLSchema(): string;
+
+  /**
+   * Clear the route index and the bytecode and response caches
+   * @returns JSON { responses } with the number of cached responses dropped here
+   */
+  clearCaches(): string;
+
+  /**
+   * Close the connections of one stream path, or of every stream. The streams
+   * stay registered, so clients reconnect.
+   * @param path - Stream path; omit to drain every stream
+   * @returns Number of connections closed on this instance, as a string
+   */
+  drainStreams(path?: string): string;
+
+  /**
+   * Switch maintenance mode on or off
+   * @param options.retryAfterSeconds - Retry-After of the 503 responses (default 300)
+   * @returns JSON MaintenanceStatus
+   * @example
+   * admin.setMaintenanceMode({ enabled: true, message: "Back at 14:00" });
+   */
+  setMaintenanceMode(options: {
+    enabled: boolean;
+    message?: string;
+    retryAfterSeconds?: number;
+  }): string;
+
+  /**
+   * @returns JSON MaintenanceStatus
+   */
+  maintenanceStatus(): string;
+
+  /**
+   * Build, enabled features and loaded scripts of the instance running the
+   * script, as served at `/engine/version`
+   * @returns JSON VersionInfo
+   */
+  versionInfo(): string;
+
+  /**
+   * Routes registered by more than one initialized script. The script
+   * created first serves the route; the others are shadowed.
+   * @returns JSON array of RouteConflict
+   */
+  routeConflicts(): string;
+
+  /**
+   * Actions taken or queued by threat response policies, newest first
+   * @param options.limit - Most actions returned (default 100, at most 500)
+   * @returns JSON array of ThreatAction
+   * @example
+   * const pending = JSON.parse(admin.threatActions({ status: "pending" }));
+   */
+  threatActions(options?: {
+    status?: "pending" | "active" | "dismissed" | "lifted";
+    limit?: number;
+  }): string;
+
+  /**
+   * Approve or dismiss a pending action, or lift an active one
+   * @param id - Threat action ID
+   * @returns JSON ThreatAction after the decision
+   */
+  reviewThreatAction(id: string, decision: "approve" | "dismiss" | "lift"): string;
+
+  /**
+   * Release a script quarantined after repeated timeouts or panics, once it
+   * is fixed. Quarantines are announced to subscribers of the
+   * "system.script_quarantined" event with { scriptUri, actionId, reason,
+   * quarantinedAt }.
+   * @param uri - Script URI
+   * @returns JSON array of the lifted ThreatAction entries, or an "Error: ..."
+   *   message when the script is not quarantined
+   */
+  releaseScript(uri: string): string;
+
+  /**
+   * Unlock an account locked after failed sign-ins and forget its failures
+   * @param userId - User ID of the account
+   * @returns "true" if the account had failed sign-ins, "false" otherwise
+   */
+  unlockAccount(userId: string): string;
+
+  /**
+   * User groups with their roles and members
+   * @returns JSON array of { name, description, roles, members, createdAt, updatedAt }
+   */
+  userGroups(): string;
+
+  /**
+   * Create a user group. Members get the group's roles when they sign in.
+   * @param name - Group name: lowercase letters, digits, "-", "_" or "."
+   * @param options - Description and roles ("Editor", "Administrator")
+   * @returns JSON of the created group
+   * @example
+   * admin.createUserGroup("support", { description: "Support team", roles: ["Editor"] });
+   */
+  createUserGroup(
+    name: string,
+    options?: { description?: string | null; roles?: string[] },
+  ): string;
+
+  /**
+   * Change the description or roles of a user group; options not given are
+   * kept. Members get changed roles on their next sign-in.
+   * @param name - Group name
+   * @param options - Description and roles ("Editor", "Administrator")
+   * @returns JSON of the updated group
+   */
+  updateUserGroup(
+    name: string,
+    options: { description?: string | null; roles?: string[] },
+  ): string;
+
+  /**
+   * Delete a user group with its memberships and script collaborations
+   * @param name - Group name
+   * @returns "true" if the group existed, "false" otherwise
+   */
+  deleteUserGroup(name: string): string;
+
+  /**
+   * Add a user to a group
+   * @param name - Group name
+   * @param userId - User ID
+   * @returns "true" if the user was added, "false" if already a member
+   */
+  addUserGroupMember(name: string, userId: string): string;
+
+  /**
+   * Remove a user from a group
+   * @param name - Group name
+   * @param userId - User ID
+   * @returns "true" if the user was a member, "false" otherwise
+   */
+  removeUserGroupMember(name: string, userId: string): string;
+
+  /**
+   * Everything kept about a user as one JSON document: the account and
+   * profile, session metadata, properties, secret names, push subscriptions,
+   * groups, script ownerships and collaborations, usage, security records,
+   * logs whose data has `userId` set to the user, email log entries and rows
+   * of script tables with a text `user_id` column. The export is recorded
+   * in the user data audit trail.
+   * @param userId - User ID
+   * @returns JSON of the export
+   */
+  exportUserData(userId: string): string;
+
+  /**
+   * Delete a user's account, sessions and data in one transaction.
+   * "anonymize" keeps usage, threat, log, email log and script table rows,
+   * replacing the user ID with a pseudonym ("deleted-<hash>"); "purge"
+   * deletes them too. Scripts the user owned are kept.
+   * @param userId - User ID
+   * @param mode - "anonymize" or "purge"
+   * @returns JSON of the audit trail entry: { id, subject, action,
+   *   requestedBy, summary, createdAt }, summary counting rows per section
+   */
+  deleteUserData(userId: string, mode: "anonymize" | "purge"): string;
+
+  /**
+   * Audit trail of user data exports and erasures, newest first. Entries
+   * name the user by pseudonym.
+   * @param userId - Only entries of this user
+   * @param limit - Most entries to return (default 100, at most 500)
+   * @returns JSON array of { id, subject, action, requestedBy, summary, createdAt }
+   */
+  userDataRequests(userId?: string, limit?: number): string;
+
+  /**
+   * Scripts whose capability manifest has requests waiting for approval
+   * @returns JSON array of { uri, requested, approved, approvedBy, approvedAt, pending }
+   */
+  pendingScriptCapabilities(): string;
+
+  /**
+   * Approve requested capabilities of a script; approvals not listed are
+   * revoked
+   * @param uri - Script URI
+   * @param capabilities - Capabilities to approve, all requested ones if omitted
+   * @returns JSON manifest after the approval
+   */
+  approveScriptCapabilities(uri: string, capabilities?: string[]): string;
 }
 
 // ============================================================================
@@ -589,3 +1746,4 @@ interface AssetStorage {
 
 declare var userStorage: UserStorage;
 declare var scriptStorage: ScriptStorage;
+declare var admin: Admin;
@@ -10,6 +10,17 @@
  * IMPORTANT: Every script MUST export an init() function that registers routes,
  * GraphQL resolvers, or other initialization logic.
  *
+ * init(), route handlers, GraphQL resolvers, MCP tools and prompts, and
+ * scheduled, event, notification and task handlers may be `async` functions.
+ * The promise they return is awaited, running its pending jobs, before the
+ * result is used; a rejection fails the call like a thrown error, and a
+ * promise that can never settle fails it too. While the promise is pending,
+ * fetchAsync() requests run concurrently and setTimeout() timers fire, so
+ * `await Promise.all([fetchAsync(a), fetchAsync(b)])` waits for the slower
+ * request only. Waiting counts against the execution timeout, and requests
+ * and timers still pending when the result is ready are dropped. fetch()
+ * and the other host functions stay synchronous.
+ *
  * @example
  * // Minimal script structure
  * function myHandler(context) {
@@ -47,7 +58,7 @@
  *   console.log("Script initialized successfully");
  * }
  */
-declare function init(context?: HandlerContext): void;
+declare function init(context?: HandlerContext): void | Promise<void>;
 
 // ============================================================================
 // HTTP Request and Response Types
@@ -75,10 +86,14 @@ interface HttpRequest {
   /** Form data from POST requests as key-value pairs */
   form: Record<string, string>;
 
-  /** Raw request body as string */
+  /** Raw request body as string; null for multipart requests */
   body: string;
 
-  /** Uploaded files from multipart form data */
+  /**
+   * Uploaded files from multipart form data. Files larger than the
+   * server's upload_spool_threshold_bytes are written to a temporary file,
+   * deleted after the request, and carry its path instead of data.
+   */
   files: Array<{
     /** Form field name */
     field: string;
@@ -86,14 +101,72 @@ interface HttpRequest {
     filename?: string;
     /** MIME content type (if provided) */
     contentType?: string;
-    /** Base64-encoded file data */
-    data: string;
+    /** Base64-encoded file data of files kept in memory */
+    data?: string;
+    /** Temporary file holding the content of a spooled file */
+    path?: string;
     /** File size in bytes */
     size: number;
+    /** Hex-encoded SHA-256 of the content */
+    sha256: string;
+    /**
+     * Read the content, from disk when spooled
+     * @returns Base64-encoded content, or a string starting with "Error: "
+     */
+    read(): string;
+    /**
+     * Read the content as bytes, from disk when spooled
+     * @throws When the content cannot be read
+     */
+    bytes(): Uint8Array;
+    /**
+     * Store the file as an asset of the current script under `name`, with
+     * its content type. Subject to the same permissions and size limit as
+     * assetStorage.upsertAsset.
+     * @returns The result message of assetStorage.upsertAsset
+     */
+    saveAsAsset(name: string): string;
   }>;
 
   /** Authentication context (available when user is authenticated) */
   auth?: AuthContext;
+
+  /**
+   * Tenant of the request and the branding configured for it under
+   * `tenancy.overrides`; absent when neither applies
+   */
+  tenant?: RequestTenant;
+
+  /**
+   * Whether the Accept header accepts a media type, following q-values
+   * and wildcards. A missing Accept header accepts everything.
+   * @param type - Media type, or json, html, csv, text or xml
+   * @example
+   * if (!context.request.accepts("json")) return ResponseBuilder.error(406, "JSON only");
+   */
+  accepts(type: string): boolean;
+
+  /**
+   * The type the Accept header prefers; earlier types win ties
+   * @param types - Media types or shorthands (json, html, csv, text, xml)
+   * @returns One of `types`, or null when none is acceptable
+   */
+  preferredType(types: string[]): string | null;
+}
+
+/**
+ * Tenant information available in request.tenant
+ */
+interface RequestTenant {
+  /** Tenant ID, or null when tenancy is disabled */
+  id: string | null;
+
+  /** Branding for pages rendered for this tenant; unset fields are null */
+  branding: {
+    name: string | null;
+    logoUrl: string | null;
+    primaryColor: string | null;
+  };
 }
 
 /**
@@ -131,6 +204,27 @@ interface AuthContext {
     isAuthenticated: boolean;
   } | null;
 
+  /**
+   * Custom profile fields of the user, read on first access, or null if not
+   * authenticated. Fields are defined by the auth.profile_schema setting.
+   * @example
+   * const locale = req.auth.profile?.locale ?? "en";
+   */
+  profile: Record<string, unknown> | null;
+
+  /**
+   * Names of the user's groups, read on first access; empty if not
+   * authenticated
+   */
+  groups: string[];
+
+  /**
+   * Whether the user is a member of a group
+   * @example
+   * if (!req.auth.inGroup("support")) return { status: 403, body: "Forbidden" };
+   */
+  inGroup(name: string): boolean;
+
   /**
    * Asserts that the request is authenticated, returning the user object.
    * Throws an error if the request is not authenticated.
@@ -184,10 +278,16 @@ interface HandlerContext {
     | "graphqlMutation"
     | "graphqlSubscription"
     | "scheduledJob"
+    | "notification"
+    | "event"
+    | "task"
     | "mcpTool";
 
   /** Additional metadata */
   metadata?: Record<string, any>;
+
+  /** Invocation details, such as `meta.event` for event handlers */
+  meta?: Record<string, any>;
 }
 
 // ============================================================================
@@ -205,8 +305,43 @@ interface RouteRegistry {
    * @param method - HTTP method (GET, POST, PUT, DELETE, etc.). Registering
    *   GET automatically serves HEAD requests too, running the same handler
    *   and returning its headers with an empty body. Register HEAD explicitly
-   *   to override this with custom behavior.
+   *   to override this with custom behavior. OPTIONS requests to a path are
+   *   answered with 204 and an `Allow` header listing the methods registered
+   *   for it, unless OPTIONS is registered too; 405 responses carry the same
+   *   header.
    * @param metadata - Optional OpenAPI metadata (summary, description, tags, parameters, requestBody)
+   *   and `host`: a host name or list of them the route is limited to. Requests
+   *   are matched against the routes of their Host header first and fall back
+   *   to routes registered without a host. `rateLimit` caps how often the
+   *   route may be called, per client IP (default), per signed-in user or for
+   *   all callers together; requests over the limit get HTTP 429 with
+   *   Retry-After before the handler runs. Enforced when the server enables
+   *   rate limiting. `idempotency` lets clients retry safely: a request with
+   *   an `Idempotency-Key` header runs once, and retries with the same key
+   *   get the stored response (marked `Idempotent-Replayed: true`) without
+   *   running the handler again. Server errors are not stored. `uploads`
+   *   validates multipart files before the handler runs: larger files get
+   *   HTTP 413, files of other content types 415 and too many files 400.
+   *   `schema` declares JSON Schemas for the body, query string and path
+   *   parameters; requests that do not match get HTTP 400 listing every
+   *   violation under `error.context.violations`, and the schemas describe
+   *   the route in the OpenAPI document unless `parameters` or
+   *   `requestBody` are given. `cache` keeps the handler's 200 responses to
+   *   anonymous GET requests in memory for `ttlSeconds` and answers later
+   *   anonymous GET and HEAD requests with the same path, query string and
+   *   `vary` header values without running the handler (`X-Cache: HIT`).
+   *   Responses setting cookies or `Cache-Control: private` or `no-store`
+   *   are not cached; cache.purgeRoute drops cached responses early. An
+   *   expired response is still served for `staleWhileRevalidateSeconds`
+   *   (`X-Cache: STALE`) while the handler refreshes it in the background.
+   *   `auth: true` admits only signed-in users: anonymous requests get
+   *   HTTP 401, or a redirect to sign-in for browser GET requests.
+   *   `auth: { groups }` further requires membership in one of the groups
+   *   (HTTP 403 otherwise); administrators may use every route.
+   *   `timeoutMs` gives the route's handler its own execution timeout in
+   *   place of the script's or the server's, so a slow export route does not
+   *   need a longer timeout everywhere; it may be at most the server's
+   *   `javascript.max_script_execution_timeout_ms`.
    * @returns Registration result message
    * @example
    * routeRegistry.registerRoute("/api/users", "listUsers", "GET");
@@ -238,6 +373,33 @@ interface RouteRegistry {
    *     }
    *   })
    * });
+   * routeRegistry.registerRoute("/", "shopHome", "GET", { host: "shop.example.com" });
+   * routeRegistry.registerRoute("/api/orders", "createOrder", "POST", {
+   *   rateLimit: { requestsPerMinute: 30, per: "user", burst: 5 },
+   *   idempotency: true,
+   * });
+   * routeRegistry.registerRoute("/api/avatar", "uploadAvatar", "POST", {
+   *   uploads: { maxFileSize: 2 * 1024 * 1024, maxFiles: 1, allowedTypes: ["image/*"] },
+   * });
+   * routeRegistry.registerRoute("/products/:id", "showProduct", "GET", {
+   *   cache: { ttlSeconds: 60, vary: ["Accept-Language"], staleWhileRevalidateSeconds: 300 },
+   * });
+   * routeRegistry.registerRoute("/reports/export", "exportReport", "GET", {
+   *   timeoutMs: 20000,
+   * });
+   * routeRegistry.registerRoute("/support/tickets", "listTickets", "GET", {
+   *   auth: { groups: ["support"] },
+   * });
+   * routeRegistry.registerRoute("/api/projects/:id/tasks", "createTask", "POST", {
+   *   schema: {
+   *     params: { type: "object", properties: { id: { type: "integer" } } },
+   *     body: {
+   *       type: "object",
+   *       required: ["title"],
+   *       properties: { title: { type: "string", minLength: 1 } },
+   *     },
+   *   },
+   * });
    */
   registerRoute(
     path: string,
@@ -249,6 +411,14 @@ interface RouteRegistry {
       tags?: string[];
       parameters?: string; // JSON string of OpenAPI parameters array
       requestBody?: string; // JSON string of OpenAPI requestBody object
+      host?: string | string[];
+      rateLimit?: RouteRateLimit;
+      idempotency?: boolean | RouteIdempotency;
+      uploads?: RouteUploads;
+      schema?: RouteSchema;
+      cache?: RouteCache;
+      auth?: boolean | RouteAuth;
+      timeoutMs?: number;
     },
   ): string;
 
@@ -256,27 +426,108 @@ interface RouteRegistry {
    * Register a Server-Sent Events (SSE) stream endpoint
    * @param path - URL path for the stream (must start with /)
    * @param customizationFunction - Optional name of a function that returns connection filter criteria
+   * @param options - Optional host the stream is limited to; connections on
+   *   that host use it instead of a stream registered for any host
    * @returns Registration result message
    * @example
    * routeRegistry.registerStreamRoute("/events/notifications");
    * routeRegistry.registerStreamRoute("/events/chat", "chatCustomizer");
+   * routeRegistry.registerStreamRoute("/events/orders", undefined, {
+   *   host: "shop.example.com",
+   * });
    */
-  registerStreamRoute(path: string, customizationFunction?: string): string;
+  registerStreamRoute(
+    path: string,
+    customizationFunction?: string,
+    options?: StreamRouteOptions,
+  ): string;
 
   /**
-   * Register a static asset route
+   * Register a static asset route. With the imageTransforms option, PNG,
+   * JPEG, GIF and WebP assets accept the w (width) and format query
+   * parameters from the listed values, e.g. "/images/hero.jpg?w=400&format=webp".
    * @param httpPath - HTTP path where asset will be served (e.g., "/styles/main.css")
    * @param assetName - Name of the asset in the asset storage (e.g., "main.css")
+   * @param options - Optional response headers, versioning and host for this path
    * @returns Registration result message
    * @example
    * routeRegistry.registerAssetRoute("/styles/main.css", "main.css");
+   * routeRegistry.registerAssetRoute("/styles/main.css", "main.css", {
+   *   headers: { "Cache-Control": "public, max-age=3600" },
+   * });
+   * routeRegistry.registerAssetRoute("/images/hero.jpg", "hero.jpg", {
+   *   imageTransforms: { widths: [400, 800], formats: ["webp", "avif"] },
+   * });
+   */
+  registerAssetRoute(
+    httpPath: string,
+    assetName: string,
+    options?: AssetRouteOptions,
+  ): string;
+
+  /**
+   * Mount a whole directory of assets, such as an SPA build, under a URL
+   * prefix. A request for `prefix` + rest serves the asset named
+   * `assetNamePrefix` + rest; the prefix itself and paths ending in "/"
+   * serve the index asset. Asset routes registered with registerAssetRoute
+   * take precedence, and longer prefixes over shorter ones.
+   * @param prefix - URL prefix (must start with /), e.g. "/app/"
+   * @param assetNamePrefix - Prefix of the asset names, e.g. "app/"
+   * @param options - Index and fallback assets, response headers, host and caching
+   * @returns Registration result message
+   * @example
+   * // app/index.html at /app/, app/assets/main.js at /app/assets/main.js,
+   * // and index.html for client-side routes such as /app/settings
+   * routeRegistry.registerAssetPrefix("/app/", "app/", { fallback: "index.html" });
+   */
+  registerAssetPrefix(
+    prefix: string,
+    assetNamePrefix: string,
+    options?: AssetPrefixOptions,
+  ): string;
+
+  /**
+   * Register a resumable upload endpoint speaking the tus 1.0 protocol
+   * (creation, termination and expiration extensions), for clients such as
+   * tus-js-client. Clients POST to the path to create an upload, PATCH its
+   * bytes to the returned URL and resume from the offset HEAD reports after
+   * a dropped connection. Unfinished uploads expire after 24 hours.
+   *
+   * A completed upload is stored as an asset named `asset` + its file name
+   * (from the "filename" metadata), and/or passed to the `onComplete`
+   * handler as `context.request.files[0]`, with the upload metadata as
+   * `context.request.form` and the upload ID as `context.request.params.id`.
+   * A handler response with status 400 or above is returned to the client.
+   * @param path - URL path of the endpoint (must start with /)
+   * @param options - Where completed uploads go, size limit and host
+   * @returns Registration result message
+   * @example
+   * routeRegistry.registerUploadRoute("/uploads", {
+   *   onComplete: "videoUploaded",
+   *   maxSize: 2 * 1024 * 1024 * 1024,
+   * });
+   * routeRegistry.registerUploadRoute("/avatars", { asset: "avatars/" });
+   */
+  registerUploadRoute(path: string, options: UploadRouteOptions): string;
+
+  /**
+   * Resolve the current URL of an asset route. Versioned routes return the
+   * content-hashed URL (e.g. "/static/app.3f2a9c1b7d4e.js"), which is served
+   * with immutable caching; other routes return the path unchanged.
+   * @param httpPath - Path the asset route was registered at
+   * @param options - Host the route was registered for, if any
+   * @returns The URL to reference, or an error message starting with "Error:"
+   * @example
+   * routeRegistry.registerAssetRoute("/static/app.js", "app.js", { versioned: true });
+   * const src = routeRegistry.resolveAssetUrl("/static/app.js");
    */
-  registerAssetRoute(httpPath: string, assetName: string): string;
+  resolveAssetUrl(httpPath: string, options?: StreamRouteOptions): string;
 
   /**
    * Broadcast a message to all connections on a stream
    * @param path - Stream path
    * @param data - Data to send (will be JSON serialized)
+   * @param options - Host the stream was registered for, if any
    * @returns Broadcast result message
    * @example
    * routeRegistry.sendStreamMessage("/events/notifications", {
@@ -284,7 +535,11 @@ interface RouteRegistry {
    *   message: "New update available"
    * });
    */
-  sendStreamMessage(path: string, data: any): string;
+  sendStreamMessage(
+    path: string,
+    data: any,
+    options?: StreamRouteOptions,
+  ): string;
 
   /**
    * Send a message to filtered connections based on metadata
@@ -292,6 +547,7 @@ interface RouteRegistry {
    * @param data - Data to send (will be JSON serialized)
    * @param filterJson - JSON filter criteria for connection metadata
    * @param matchMode - Optional filter matching mode. Defaults to "subset".
+   * @param options - Host the stream was registered for, if any
    * @returns Broadcast result message
    * @example
    * routeRegistry.sendStreamMessageFiltered(
@@ -306,6 +562,7 @@ interface RouteRegistry {
     data: any,
     filterJson: string,
     matchMode?: "subset" | "overlap",
+    options?: StreamRouteOptions,
   ): string;
 }
 
@@ -334,6 +591,204 @@ interface AssetMetadata {
 
   /** Last update timestamp */
   updated_at: string;
+
+  /** Custom response headers sent when the asset is served */
+  headers: Record<string, string>;
+}
+
+/**
+ * Options for storing an asset
+ */
+interface AssetOptions {
+  /** Display name (defaults to the asset URI) */
+  name?: string;
+
+  /**
+   * Extra response headers applied when the asset is served through an asset
+   * route, e.g. Cache-Control, Content-Disposition or CORS headers. Omit to
+   * keep the current headers, pass null to clear them. Content-Type comes from
+   * the mimetype and cannot be overridden.
+   */
+  headers?: Record<string, string> | null;
+}
+
+/**
+ * Options for registering an asset route
+ */
+interface AssetRouteOptions {
+  /** Response headers for this path; they override the asset's own headers */
+  headers?: Record<string, string>;
+  /**
+   * Also serve content-hashed URLs (see resolveAssetUrl) with immutable
+   * caching. The stable path keeps pointing at the latest content and
+   * defaults to "Cache-Control: no-cache".
+   */
+  versioned?: boolean;
+  /**
+   * Serve this path only on the given host. Requests on that host use it
+   * instead of a route registered for any host at the same path.
+   */
+  host?: string;
+  /**
+   * Keep responses in server memory instead of reading the asset on every
+   * request. Changing or deleting one of the script's assets drops them.
+   */
+  cache?: RouteCache;
+  /**
+   * Only serve the path at signed URLs from assetStorage.signUrl; other
+   * requests get 403
+   */
+  private?: boolean;
+  /**
+   * Cache-Control and surrogate keys for CDNs; cannot be combined with
+   * private. Storing or deleting the asset purges it from the configured CDN.
+   */
+  cachePolicy?: AssetCachePolicy;
+  /**
+   * Image renditions served from the query string: w from widths (pixels,
+   * up to 4096) and format from formats (png, jpeg, gif, webp or avif).
+   * Other transform parameters, and other values, answer 400. Without this
+   * option transform parameters are ignored and the asset is served as
+   * stored.
+   */
+  imageTransforms?: { widths?: number[]; formats?: string[] };
+}
+
+/**
+ * Options for routeRegistry.registerAssetPrefix
+ */
+interface AssetPrefixOptions {
+  /** Response headers for every path under the prefix */
+  headers?: Record<string, string>;
+  /**
+   * Asset served for the prefix itself and paths ending in "/", relative to
+   * the asset name prefix (default "index.html")
+   */
+  index?: string;
+  /**
+   * Asset served, relative to the asset name prefix, when the requested one
+   * doesn't exist; lets client-side routed SPAs handle their own paths.
+   * Without it such paths answer 404.
+   */
+  fallback?: string;
+  /** Serve the prefix only on the given host */
+  host?: string;
+  /** Keep responses in server memory instead of reading assets on every request */
+  cache?: RouteCache;
+  /** Only serve the paths under the prefix at signed URLs from assetStorage.signUrl */
+  private?: boolean;
+  /** Cache-Control and surrogate keys for CDNs; cannot be combined with private */
+  cachePolicy?: AssetCachePolicy;
+}
+
+/**
+ * Rate limit registered with a route
+ */
+interface RouteRateLimit {
+  /** Sustained number of requests allowed per minute */
+  requestsPerMinute: number;
+  /** Who shares the budget: each client IP (default), each signed-in user
+   * (anonymous callers are limited per IP) or all callers of the route */
+  per?: "ip" | "user" | "route";
+  /** Requests allowed in a burst; defaults to requestsPerMinute */
+  burst?: number;
+}
+
+/**
+ * Idempotency-Key handling registered with a route
+ */
+interface RouteIdempotency {
+  /** How long responses are replayed to retries; defaults to 24 hours */
+  ttlSeconds?: number;
+}
+
+/**
+ * Response caching of a route
+ */
+interface RouteCache {
+  /** Seconds a response is served from the cache */
+  ttlSeconds: number;
+  /** Request headers whose values select different cached responses */
+  vary?: string[];
+  /**
+   * Seconds an expired response is still served while it is refreshed in
+   * the background; defaults to the server's configured window
+   */
+  staleWhileRevalidateSeconds?: number;
+}
+
+/**
+ * Sign-in requirement of a route
+ */
+interface RouteAuth {
+  /** Groups whose members may use the route; any signed-in user if omitted */
+  groups?: string[];
+}
+
+/**
+ * CDN caching of an asset route or prefix; at least one field is required
+ */
+interface AssetCachePolicy {
+  /** Seconds browsers keep a response (Cache-Control max-age) */
+  maxAge?: number;
+  /** Seconds CDNs and other shared caches keep a response (s-maxage) */
+  sMaxAge?: number;
+  /**
+   * Keys sent in the surrogate key header next to the asset's own key, so
+   * one purge can drop every response of the route (at most 16)
+   */
+  surrogateKeys?: string[];
+}
+
+/**
+ * Options of a resumable upload endpoint; onComplete, asset or both are
+ * required
+ */
+interface UploadRouteOptions {
+  /** Name of the handler called with each completed upload */
+  onComplete?: string;
+  /** Asset name prefix completed uploads are stored under */
+  asset?: string;
+  /** Largest accepted upload in bytes; defaults to max_upload_request_bytes */
+  maxSize?: number;
+  /** Host the endpoint is limited to */
+  host?: string;
+}
+
+/**
+ * Upload rules registered with a route
+ */
+interface RouteUploads {
+  /** Largest accepted file or form field in bytes */
+  maxFileSize?: number;
+  /** Most files accepted in one request */
+  maxFiles?: number;
+  /** Accepted content types, such as "application/pdf" or "image/*" */
+  allowedTypes?: string[];
+}
+
+/**
+ * JSON Schemas a route's requests are validated against
+ *
+ * Query strings, path parameters and form fields are strings; properties
+ * declared as "integer", "number" or "boolean" are converted before
+ * validation. Schemas may only `$ref` their own definitions.
+ */
+interface RouteSchema {
+  /** Schema for the JSON body, or for the fields of form posts */
+  body?: object;
+  /** Object schema for the query string parameters */
+  query?: object;
+  /** Object schema for the path parameters */
+  params?: object;
+}
+
+/**
+ * Host selection for stream routes and host-scoped asset routes
+ */
+interface StreamRouteOptions {
+  /** Host name (e.g. "shop.example.com"); omit for routes on any host */
+  host?: string;
 }
 
 /**
@@ -360,15 +815,25 @@ interface AssetStorage {
   fetchAsset(name: string): string;
 
   /**
-   * Create or update an asset owned by this script
+   * Create or update an asset owned by this script. Content counts against
+   * the script's storage quota, shared with sharedStorage.
    * @param name - Asset name/URI
    * @param mimetype - MIME type (e.g., "image/png", "text/css")
    * @param contentBase64 - Base64-encoded content
+   * @param options - Optional display name and response headers
    * @returns Operation result message
    * @example
    * assetStorage.upsertAsset("logo.svg", "image/svg+xml", base64Content);
+   * assetStorage.upsertAsset("report.pdf", "application/pdf", base64Pdf, {
+   *   headers: { "Content-Disposition": 'attachment; filename="report.pdf"' },
+   * });
    */
-  upsertAsset(name: string, mimetype: string, contentBase64: string): string;
+  upsertAsset(
+    name: string,
+    mimetype: string,
+    contentBase64: string,
+    options?: AssetOptions,
+  ): string;
 
   /**
    * Delete an asset owned by this script
@@ -378,6 +843,37 @@ interface AssetStorage {
    * assetStorage.deleteAsset("old-logo.svg");
    */
   deleteAsset(name: string): string;
+
+  /**
+   * List this script's deleted assets that can still be restored
+   * (requires DeleteAssets capability)
+   * @returns JSON array of { uri, scriptUri, name, mimetype, size, deletedAt } or "Error: ..." message
+   * @example
+   * const trashed = JSON.parse(assetStorage.listTrashedAssets());
+   */
+  listTrashedAssets(): string;
+
+  /**
+   * Restore a deleted asset of this script (requires DeleteAssets capability)
+   * @param name - Asset name/URI
+   * @returns Operation result message
+   * @example
+   * assetStorage.restoreAsset("old-logo.svg");
+   */
+  restoreAsset(name: string): string;
+
+  /**
+   * A time-limited URL of an asset of this script served by a private asset
+   * route or prefix (requires ReadAssets capability). Anyone holding the
+   * URL can read the asset until it expires.
+   * @param name - Asset name
+   * @param ttlSeconds - Seconds the URL stays valid (default 3600, max 7 days)
+   * @returns The path with the expiry time and signature in its query, or "Error: ..." message
+   * @example
+   * routeRegistry.registerAssetRoute("/reports/q1.pdf", "q1.pdf", { private: true });
+   * const url = assetStorage.signUrl("q1.pdf", 600);
+   */
+  signUrl(name: string, ttlSeconds?: number): string;
 }
 
 // ============================================================================
@@ -385,7 +881,8 @@ interface AssetStorage {
 // ============================================================================
 
 /**
- * Shared storage (script-scoped, persistent key-value store)
+ * Shared storage (script-scoped, persistent key-value store).
+ * With tenancy enabled, keys are additionally scoped to the request's tenant.
  */
 interface SharedStorage {
   /**
@@ -398,13 +895,27 @@ interface SharedStorage {
   getItem(key: string): string | null;
 
   /**
-   * Set a value in shared storage
+   * Set a value in shared storage. Keys and values count against the
+   * script's storage quota, shared with its assets.
    * @param key - Storage key
    * @param value - Value to store
+   * @param options - Optional expiry; without ttlSeconds the item never
+   * expires, and setting an item replaces any previous TTL
    * @example
    * sharedStorage.setItem("pageViews", "42");
+   * sharedStorage.setItem("cache:weather", json, { ttlSeconds: 300 });
+   */
+  setItem(key: string, value: string, options?: SharedStorageSetOptions): void;
+
+  /**
+   * Reset the TTL of an existing item to ttlSeconds from now
+   * @param key - Storage key
+   * @param ttlSeconds - New time to live in seconds
+   * @returns false if the item does not exist or has already expired
+   * @example
+   * sharedStorage.touch("session:abc", 1800);
    */
-  setItem(key: string, value: string): void;
+  touch(key: string, ttlSeconds: number): boolean;
 
   /**
    * Remove a key from shared storage
@@ -422,6 +933,72 @@ interface SharedStorage {
   clear(): void;
 }
 
+/**
+ * Options for sharedStorage.setItem()
+ */
+interface SharedStorageSetOptions {
+  /** Seconds until the item expires and reads return null */
+  ttlSeconds?: number;
+}
+
+/**
+ * Cache for hot lookups, kept in server memory and shared between servers
+ * through Redis when configured. Values are script-scoped and, with tenancy
+ * enabled, tenant-scoped like sharedStorage. Entries may be evicted at any
+ * time, so always be ready to recompute a value.
+ */
+interface Cache {
+  /**
+   * Get a cached value
+   * @param key - Cache key
+   * @returns Cached value or null if missing or expired
+   * @example
+   * const cached = cache.get("user:" + id);
+   */
+  get(key: string): string | null;
+
+  /**
+   * Cache a value, replacing any previous value and TTL
+   * @param key - Cache key, at most 512 bytes
+   * @param value - Value to cache
+   * @param options - Optional expiry; without ttlSeconds the server's
+   * default TTL applies
+   * @returns "Value cached" or an error message starting with "Error:"
+   * @example
+   * cache.set("user:" + id, JSON.stringify(user), { ttlSeconds: 60 });
+   */
+  set(key: string, value: string, options?: CacheSetOptions): string;
+
+  /**
+   * Remove a cached value
+   * @param key - Cache key
+   * @returns true if the key was cached
+   * @example
+   * cache.delete("user:" + id);
+   */
+  delete(key: string): boolean;
+
+  /**
+   * Drop the cached responses of one of this script's routes registered
+   * with the `cache` option, on every server
+   * @param path - Request path, such as "/products/42", or a prefix ending
+   * in "/*" such as "/products/*"
+   * @returns Number of responses dropped on this server, or an error
+   * message starting with "Error:"
+   * @example
+   * cache.purgeRoute("/products/" + id);
+   */
+  purgeRoute(path: string): string;
+}
+
+/**
+ * Options for cache.set()
+ */
+interface CacheSetOptions {
+  /** Seconds until the value expires, up to the server's maximum */
+  ttlSeconds?: number;
+}
+
 /**
  * Personal storage (user-scoped, requires authentication)
  */
@@ -702,6 +1279,34 @@ interface GraphQLRegistry {
   ): string;
 }
 
+/**
+ * Error a GraphQL resolver throws to report an expected failure to the client.
+ *
+ * The message and extensions are sent to the client as they are. `code` has
+ * to be SCREAMING_SNAKE_CASE; without a valid one the error gets
+ * `BAD_USER_INPUT`. Any other error a resolver throws is masked as
+ * "Internal server error" with an `errorId` that is also logged.
+ * @example
+ * function orderResolver(context) {
+ *   const order = findOrder(context.args.id);
+ *   if (!order) {
+ *     throw new GraphQLError("Order not found", { code: "NOT_FOUND" });
+ *   }
+ *   return order;
+ * }
+ */
+declare class GraphQLError extends Error {
+  /**
+   * @param message - Message shown to the client
+   * @param extensions - Error extensions, or just the error code
+   */
+  constructor(
+    message: string,
+    extensions?: string | { code?: string; [key: string]: unknown },
+  );
+  readonly extensions: { code?: string; [key: string]: unknown };
+}
+
 // ============================================================================
 // MCP (Model Context Protocol) Registry API
 // ============================================================================
@@ -788,7 +1393,9 @@ interface McpTool {
  *
  * The MCP Client implements the Model Context Protocol to connect to external
  * MCP servers (like GitHub Copilot MCP) and use their tools. Authentication
- * is handled via secrets stored in the environment.
+ * is handled via secrets stored in the environment. With script manifests
+ * enabled, McpClient needs the "fetch" capability and its calls the
+ * "secrets" capability.
  *
  * IMPORTANT: The McpClient uses a low-level API with static methods. For easier
  * usage, wrap it in a class as shown in scripts/examples/github_mcp_issues.js
@@ -946,6 +1553,10 @@ interface FetchResponse {
 
 /**
  * HTTP client with secret injection support
+ *
+ * Calls made while handling an HTTP request send its `X-Request-Id` and a
+ * W3C `traceparent` header, unless the options set them, so upstream logs
+ * can be correlated with the request.
  * @param url - URL to fetch (supports {{SECRET_NAME}} syntax for secret injection)
  * @param options - Fetch options
  * @returns Fetch response as JSON string
@@ -966,41 +1577,454 @@ interface FetchResponse {
  */
 declare function fetch(url: string, options?: FetchOptions): string;
 
-// ============================================================================
-// Database API (Script-Scoped Table Management)
-// ============================================================================
+/**
+ * fetch() that runs the request concurrently with the script, for async
+ * handlers. At most 64 requests and timers may be pending at a time.
+ * @param url - URL to fetch (supports {{SECRET_NAME}} syntax for secret injection)
+ * @param options - Fetch options
+ * @returns Promise of the fetch response as JSON string; rejected when the
+ * request fails
+ * @example
+ * async function handler(context) {
+ *   const [users, orders] = await Promise.all([
+ *     fetchAsync("https://api.example.com/users"),
+ *     fetchAsync("https://api.example.com/orders"),
+ *   ]);
+ *   return ResponseBuilder.json({
+ *     users: JSON.parse(JSON.parse(users).body),
+ *     orders: JSON.parse(JSON.parse(orders).body),
+ *   });
+ * }
+ */
+declare function fetchAsync(url: string, options?: FetchOptions): Promise<string>;
 
 /**
- * Database interface for script-scoped table management and operations.
- * Each script can create and manage its own tables with automatic namespacing.
+ * Run `callback` with `args` after `delay` milliseconds, while an async
+ * handler's promise is pending. An exception thrown by the callback fails
+ * the call like one thrown by the handler.
+ * @param callback - Function to run
+ * @param delay - Milliseconds to wait (default 0)
+ * @param args - Arguments for the callback
+ * @returns Timer id for clearTimeout()
+ * @example
+ * async function handler(context) {
+ *   await new Promise((resolve) => setTimeout(resolve, 100));
+ *   return ResponseBuilder.text("done");
+ * }
  */
-interface Database {
+declare function setTimeout(callback: (...args: any[]) => void, delay?: number, ...args: any[]): number;
+
+/**
+ * Cancel a timer started with setTimeout(); unknown ids are ignored
+ * @param id - Timer id
+ */
+declare function clearTimeout(id: number): void;
+
+/** Options of webhooks.send */
+interface WebhookSendOptions {
+  /** Extra request headers; values may use {{secret:name}} like fetch */
+  headers?: Record<string, string>;
+  /** Script secret to sign the body with (X-Webhook-Signature) */
+  secret?: string;
+  /** Defaults to application/json for objects and text/plain for strings */
+  contentType?: string;
+  /** Defaults to max_attempts of [javascript.webhooks] */
+  maxAttempts?: number;
+}
+
+/** A queued webhook and how its delivery went */
+interface WebhookDelivery {
+  id: string;
+  scriptUri: string;
+  url: string;
+  status: "pending" | "delivered" | "failed";
+  attempts: number;
+  maxAttempts: number;
+  /** When the next attempt is due, while pending */
+  nextAttemptAt: string;
+  /** HTTP status of the last attempt, if it got a response */
+  lastStatusCode: number | null;
+  lastError: string | null;
+  createdAt: string;
+  updatedAt: string;
+  deliveredAt: string | null;
+}
+
+/**
+ * Outgoing webhooks delivered in the background and retried with backoff
+ * until the target answers 2xx. Every request carries X-Webhook-Id (the same
+ * across retries), X-Webhook-Timestamp and X-Webhook-Attempt; with a secret,
+ * X-Webhook-Signature is "sha256=" and the hex HMAC-SHA256 of
+ * "{timestamp}.{body}".
+ */
+interface Webhooks {
   /**
-   * Create a new table for this script
-   * @param tableName - Logical table name (will be prefixed with script namespace)
-   * @returns JSON string with result: {success: boolean, tableName: string, physicalName: string} or {error: string}
+   * Queue a delivery; inside a transaction it is sent only if the
+   * transaction commits
+   * @returns JSON string of the WebhookDelivery, or a string starting with
+   *   "Error: "
    * @example
-   * const result = JSON.parse(database.createTable("users"));
-   * // {success: true, tableName: "users", physicalName: "script_myapp_users"}
+   * const { id } = JSON.parse(
+   *   webhooks.send("https://hooks.example.com/orders", { orderId: 42 }, { secret: "hook_secret" }),
+   * );
    */
-  createTable(tableName: string): string;
+  send(url: string, payload: unknown, options?: WebhookSendOptions): string;
+  /** @returns JSON string of a delivery of this script, or "null" */
+  get(id: string): string;
+  /** @returns JSON string of this script's deliveries, newest first */
+  list(options?: { status?: "pending" | "delivered" | "failed"; limit?: number }): string;
+}
+
+declare var webhooks: Webhooks;
+
+// ============================================================================
+// Tasks API (Background Work)
+// ============================================================================
 
+/**
+ * Options for tasks.enqueue
+ */
+interface TaskEnqueueOptions {
+  /** Higher runs first, from -1000 to 1000 (default 0) */
+  priority?: number;
   /**
-   * Drop a table owned by this script
-   * @param tableName - Table name to drop
-   * @returns JSON string with result: {success: boolean, tableName: string, dropped: boolean} or {error: string}
-   * @example
-   * const result = JSON.parse(database.dropTable("old_data"));
+   * Run no earlier than this duration from now, such as "90s", "15m",
+   * "24h" or "1d12h" (units ms, s, m, h and d)
    */
-  dropTable(tableName: string): string;
-
+  runAfter?: string;
+  /** Run no earlier than this many milliseconds from now */
+  delayMs?: number;
   /**
-   * Add an integer column to a table
-   * @param tableName - Table name
-   * @param columnName - Column name
-   * @param nullable - Whether column can be NULL (default: true)
-   * @param defaultValue - Default value (optional)
-   * @returns JSON string with result: {success: boolean, column: string} or {error: string}
+   * Run no earlier than this UTC ISO timestamp. Tasks may be delayed by at
+   * most 30 days; use only one of runAfter, delayMs and runAt.
+   */
+  runAt?: string;
+  /** Attempts before the task fails (server default 3) */
+  maxAttempts?: number;
+  /**
+   * Queue only if this script has no task with this key yet (at most 255
+   * characters). Keys are remembered as long as their task is kept.
+   */
+  idempotencyKey?: string;
+}
+
+/**
+ * A queued task and how it went
+ */
+interface ScriptTask {
+  id: string;
+  scriptUri: string;
+  handlerName: string;
+  payload: unknown;
+  priority: number;
+  status: "pending" | "running" | "completed" | "failed";
+  attempts: number;
+  maxAttempts: number;
+  /** When the next attempt is due while pending */
+  runAt: string;
+  lastError: string | null;
+  createdAt: string;
+  updatedAt: string;
+  completedAt: string | null;
+  idempotencyKey: string | null;
+}
+
+/**
+ * Background work of this script. A queued task runs the named handler
+ * of this script on a server worker, with `{ id, payload, priority,
+ * attempt, maxAttempts }` in `context.meta.task`. Handlers that throw are
+ * retried with growing delays, and a task whose server stopped runs again,
+ * so make handlers safe to run more than once.
+ */
+interface Tasks {
+  /**
+   * Queue a task; inside a transaction it is queued only if the
+   * transaction commits. With an idempotencyKey this script already used,
+   * the task queued with it is returned with `duplicate: true` instead.
+   * @returns JSON string of the ScriptTask with `duplicate`, or a string
+   *   starting with "Error: "
+   * @example
+   * tasks.enqueue("buildReport", { reportId: 42 }, { priority: 10 });
+   * tasks.enqueue("sendReminder", { orderId }, {
+   *   runAfter: "24h",
+   *   idempotencyKey: `reminder-${orderId}`,
+   * });
+   * function buildReport(context) {
+   *   const { payload } = context.meta.task;
+   *   // slow work
+   * }
+   */
+  enqueue(handlerName: string, payload?: unknown, options?: TaskEnqueueOptions): string;
+  /** @returns JSON string of a task of this script, or "null" */
+  get(id: string): string;
+  /** @returns JSON string of this script's tasks, newest first */
+  list(options?: {
+    status?: "pending" | "running" | "completed" | "failed";
+    limit?: number;
+  }): string;
+}
+
+declare var tasks: Tasks;
+
+// ============================================================================
+// Email API
+// ============================================================================
+
+/**
+ * An attachment of email.send: its content, or a script asset
+ */
+interface EmailAttachment {
+  filename: string;
+  /** Text, or base64 with encoding "base64" */
+  content?: string;
+  encoding?: "utf8" | "base64";
+  /** Name of a script asset to attach instead of content */
+  asset?: string;
+  /** Guessed from the file name or the asset when not given */
+  contentType?: string;
+}
+
+/**
+ * A message of email.send. Addresses may include a name, such as
+ * "Ann <ann@example.com>".
+ */
+interface EmailMessage {
+  to: string | string[];
+  cc?: string | string[];
+  bcc?: string | string[];
+  replyTo?: string;
+  subject: string;
+  html?: string;
+  text?: string;
+  /** Script asset rendered as the HTML body, like templates.render */
+  template?: string;
+  /** Script asset rendered as the plain text body */
+  textTemplate?: string;
+  /** Data the templates are rendered with */
+  data?: Record<string, unknown>;
+  attachments?: EmailAttachment[];
+}
+
+/**
+ * An entry of the email delivery log
+ */
+interface EmailLogEntry {
+  id: string;
+  scriptUri: string;
+  /** Addresses of to, cc and bcc */
+  recipients: string[];
+  subject: string;
+  status: "sent" | "failed";
+  error: string | null;
+  messageId: string | null;
+  createdAt: string;
+}
+
+/**
+ * Outgoing email from the server's configured sender. Each script may send
+ * a limited number of messages per UTC day.
+ */
+interface Email {
+  /**
+   * Send a message
+   * @returns JSON string of the EmailLogEntry, or a string starting with
+   *   "Error: "
+   * @example
+   * email.send({
+   *   to: user.email,
+   *   subject: "Welcome",
+   *   template: "welcome.hbs",
+   *   data: { name: user.name },
+   * });
+   */
+  send(message: EmailMessage): string;
+  /** @returns JSON string of this script's sent and failed emails, newest first */
+  list(options?: { status?: "sent" | "failed"; limit?: number }): string;
+}
+
+declare var email: Email;
+
+// ============================================================================
+// Notify API (Web Push and SMS)
+// ============================================================================
+
+/**
+ * A browser push subscription, as returned by PushSubscription.toJSON()
+ */
+interface PushSubscriptionInput {
+  endpoint: string;
+  keys: { p256dh: string; auth: string };
+}
+
+/**
+ * A stored push subscription of a user
+ */
+interface StoredPushSubscription {
+  id: string;
+  scriptUri: string;
+  userId: string;
+  endpoint: string;
+  p256dh: string;
+  auth: string;
+  createdAt: string;
+  updatedAt: string;
+}
+
+/**
+ * Result of notify.push. Subscriptions the push service reports gone are
+ * removed and counted in `removed`.
+ */
+interface PushResult {
+  sent: number;
+  failed: number;
+  removed: number;
+  errors: string[];
+}
+
+/**
+ * Web push to subscribed browsers and text messages through the server's
+ * SMS provider
+ */
+interface Notify {
+  /**
+   * The server's VAPID public key, for the applicationServerKey of
+   * PushManager.subscribe
+   * @returns base64url key, or a string starting with "Error: "
+   */
+  vapidPublicKey(): string;
+  /**
+   * Store a browser subscription for the signed-in user
+   * @returns JSON string of the StoredPushSubscription, or a string
+   *   starting with "Error: "
+   */
+  subscribe(subscription: PushSubscriptionInput): string;
+  /** @returns "true" when a subscription with this ID or endpoint was removed */
+  unsubscribe(endpointOrId: string): string;
+  /**
+   * Send a payload to every subscription of a user, or to one subscription.
+   * Strings are sent as they are, other values as JSON; at most 3993 bytes.
+   * @returns JSON string of the PushResult, or a string starting with
+   *   "Error: "
+   * @example
+   * notify.push({ userId: order.userId }, { title: "Order shipped", url: "/orders" });
+   */
+  push(
+    target: { userId: string } | { subscriptionId: string },
+    payload: unknown,
+    options?: {
+      /** Seconds the push service keeps an undelivered message */
+      ttl?: number;
+      urgency?: "very-low" | "low" | "normal" | "high";
+      /** Replaces an undelivered message with the same topic */
+      topic?: string;
+    },
+  ): string;
+  /**
+   * Send a text message to an E.164 number such as "+358401234567"
+   * @returns JSON string of { to, provider, messageId }, or a string
+   *   starting with "Error: "
+   */
+  sms(to: string, message: string): string;
+}
+
+declare var notify: Notify;
+
+// ============================================================================
+// Events API (Between Scripts)
+// ============================================================================
+
+/**
+ * An event as passed to subscribed handlers in `context.meta.event`
+ */
+interface ScriptEvent {
+  id: string;
+  topic: string;
+  payload: unknown;
+  /** URI of the publishing script */
+  source: string;
+  /** RFC 3339 timestamp */
+  publishedAt: string;
+  /** 1, plus one for each event handler that published along the way */
+  depth: number;
+}
+
+/**
+ * Events between scripts. Handlers run in the background, each with the
+ * event in `context.meta.event`; a handler that throws has the event
+ * logged to its script's log instead of failing the publisher.
+ */
+interface Events {
+  /**
+   * Run the handlers subscribed to a topic. Handlers may publish events in
+   * turn, up to 8 deep.
+   * @param topic - Dot-separated segments of lowercase letters, digits,
+   *   underscores and hyphens, e.g. "orders.created"
+   * @param options - `broadcast: true` also delivers the event to the
+   *   subscribers on other server instances; such events are limited to
+   *   about 8000 bytes
+   * @returns JSON string `{id, topic, subscribers, broadcast}` with the
+   *   number of handlers on this instance, or a string starting with
+   *   "Error: "
+   * @example
+   * events.publish("orders.created", { orderId: 42 });
+   */
+  publish(topic: string, payload?: unknown, options?: { broadcast?: boolean }): string;
+
+  /**
+   * Run a handler for every event published on a topic. Only available in
+   * init(); subscriptions are dropped when the script is re-initialized or
+   * deleted.
+   * @returns JSON string `{topic, handler, added}`, or a string starting
+   *   with "Error: "
+   * @example
+   * function init() {
+   *   events.subscribe("orders.created", "onOrderCreated");
+   * }
+   * function onOrderCreated(context) {
+   *   const { payload } = context.meta.event;
+   *   console.log("new order " + payload.orderId);
+   * }
+   */
+  subscribe(topic: string, handlerName: string): string;
+}
+
+declare var events: Events;
+
+// ============================================================================
+// Database API (Script-Scoped Table Management)
+// ============================================================================
+
+/**
+ * Database interface for script-scoped table management and operations.
+ * Each script can create and manage its own tables with automatic namespacing.
+ */
+interface Database {
+  /**
+   * Create a new table for this script
+   * @param tableName - Logical table name (will be prefixed with script namespace)
+   * @returns JSON string with result: {success: boolean, tableName: string, physicalName: string} or {error: string}
+   * @example
+   * const result = JSON.parse(database.createTable("users"));
+   * // {success: true, tableName: "users", physicalName: "script_myapp_users"}
+   */
+  createTable(tableName: string): string;
+
+  /**
+   * Drop a table owned by this script
+   * @param tableName - Table name to drop
+   * @returns JSON string with result: {success: boolean, tableName: string, dropped: boolean} or {error: string}
+   * @example
+   * const result = JSON.parse(database.dropTable("old_data"));
+   */
+  dropTable(tableName: string): string;
+
+  /**
+   * Add an integer column to a table
+   * @param tableName - Table name
+   * @param columnName - Column name
+   * @param nullable - Whether column can be NULL (default: true)
+   * @param defaultValue - Default value (optional)
+   * @returns JSON string with result: {success: boolean, column: string} or {error: string}
    * @example
    * database.addIntegerColumn("users", "age", true);
    * database.addIntegerColumn("products", "stock", false, "0");
@@ -1371,23 +2395,131 @@ interface Database {
   checkDatabaseHealth(): string;
 }
 
+// ============================================================================
+// SQL Query API
+// ============================================================================
+
+/**
+ * Limits for a single db.query statement
+ */
+interface SqlQueryOptions {
+  /** Maximum rows returned (default 1000, max 10000) */
+  maxRows?: number;
+  /** Statement timeout in milliseconds (default 5000, max 30000) */
+  timeoutMs?: number;
+}
+
+/**
+ * Parameterized SQL against the script's own Postgres schema.
+ *
+ * Each script gets a private schema: tables it creates are invisible to other
+ * scripts, and engine tables cannot be read. Values must be passed as bound
+ * parameters (`$1`, `$2`, ...) rather than concatenated into the SQL text.
+ * Requires the database management capability.
+ *
+ * When the server runs with tenancy enabled, every table gets a `tenant_id`
+ * column and a row-level security policy: a request only sees and writes
+ * rows of its tenant, and code without a tenant (init, scheduled jobs) only
+ * rows without one. Turning row level security off is rejected.
+ */
+interface SqlDatabase {
+  /**
+   * Run one SELECT, INSERT, UPDATE, DELETE or WITH statement, or DDL on
+   * tables, indexes, views and sequences.
+   *
+   * Parameters are bound as text, bigint, double precision, boolean or null;
+   * arrays and objects are bound as jsonb. Cast where the column type differs,
+   * e.g. `$1::timestamptz`.
+   *
+   * @param sql - A single SQL statement with `$n` placeholders
+   * @param params - Values for the placeholders, in order
+   * @param options - Row and time limits
+   * @returns JSON string `{rows, rowCount, truncated}` or {error: string}
+   * @example
+   * db.query("CREATE TABLE IF NOT EXISTS notes (id SERIAL PRIMARY KEY, body TEXT)");
+   * db.query("INSERT INTO notes (body) VALUES ($1)", [body]);
+   * const { rows } = JSON.parse(
+   *   db.query("SELECT * FROM notes WHERE id > $1 ORDER BY id", [lastId], { maxRows: 50 })
+   * );
+   */
+  query(sql: string, params?: unknown[] | null, options?: SqlQueryOptions): string;
+
+  /**
+   * Apply pending schema migrations, typically from init().
+   *
+   * Migrations already applied are skipped, so the full list can be declared
+   * on every init. Each migration runs in its own transaction; editing one
+   * that was already applied is an error, so add a new version instead.
+   *
+   * @param migrations - Versioned statements, applied in ascending version order
+   * @returns JSON string `{applied, version}` or {error: string}
+   * @example
+   * function init() {
+   *   db.migrate([
+   *     { version: 1, up: "CREATE TABLE notes (id SERIAL PRIMARY KEY, body TEXT)" },
+   *     { version: 2, up: ["ALTER TABLE notes ADD COLUMN done BOOLEAN", "CREATE INDEX ON notes (done)"] },
+   *   ]);
+   * }
+   */
+  migrate(migrations: SqlMigration[]): string;
+
+  /**
+   * Run a handler whenever a database NOTIFY arrives on a channel, typically
+   * registered from init(). Subscriptions are dropped when the script is
+   * re-initialized or deleted, so declare them on every init.
+   *
+   * Every server instance delivers the notification to its own handler, so
+   * make handlers idempotent when running several instances. The handler
+   * receives the channel and payload in `context.metadata.notification`.
+   *
+   * @param channel - Lowercase channel name (letters, digits, underscores);
+   *   engine channels such as script_upserted are reserved
+   * @param handlerName - Name of the handler function to call
+   * @returns JSON string `{channel, handler, added}` or {error: string}
+   * @example
+   * function init() {
+   *   db.listen("orders_changed", "onOrdersChanged");
+   * }
+   * function onOrdersChanged(context) {
+   *   const { payload } = context.metadata.notification;
+   *   console.log("order changed", JSON.parse(payload));
+   * }
+   */
+  listen(channel: string, handlerName: string): string;
+}
+
+/**
+ * A versioned schema change for db.migrate
+ */
+interface SqlMigration {
+  /** Positive integer; migrations are applied in ascending order */
+  version: number;
+  /** One statement or several, run in one transaction */
+  up: string | string[];
+}
+
 // ============================================================================
 // Console API
 // ============================================================================
 
 /**
  * Console logging interface
- * Note: Privileged scripts have additional methods available (listLogs, listLogsForUri, pruneLogs)
+ * Note: Privileged scripts have additional methods available (listLogs, listLogsForUri, queryLogs, pruneLogs)
  * defined in aiwebengine-priv.d.ts
  */
 interface Console {
   /**
    * Write a log message
+   *
+   * A single object argument, or an object passed after the message, is
+   * stored as structured data that can be filtered with console.queryLogs.
+   * The request ID and handler are attached to every entry automatically.
    * @param message - Message to log (multiple arguments will be concatenated)
    * @param optionalParams - Additional parameters to log
    * @example
    * console.log("Request received:", req.path);
    * console.log("User:", user.id, user.name);
+   * console.log("Order placed", { orderId: order.id, total: order.total });
    */
   log(message?: any, ...optionalParams: any[]): void;
 
@@ -1507,6 +2639,679 @@ interface Convert {
   atob(data: string): string;
 }
 
+/**
+ * Options for markdown.render
+ */
+interface MarkdownRenderOptions {
+  /** GitHub-style tables (default: true) */
+  tables?: boolean;
+  /** ~~strikethrough~~ (default: false) */
+  strikethrough?: boolean;
+  /** "- [x] done" task list items (default: false) */
+  taskLists?: boolean;
+  /** [^1] footnotes (default: false) */
+  footnotes?: boolean;
+  /** Curly quotes, dashes and ellipses (default: false) */
+  smartPunctuation?: boolean;
+  /**
+   * Keep raw HTML in the text instead of escaping it (default: false).
+   * Only for trusted text: the HTML is not sanitized.
+   */
+  allowHtml?: boolean;
+}
+
+/**
+ * Markdown rendering
+ */
+interface Markdown {
+  /**
+   * Render CommonMark markdown to HTML that is safe to embed in a page.
+   * Raw HTML is escaped, and links and images with unsafe URLs (such as
+   * javascript:) are rendered as their text only.
+   * @param text - Markdown text (at most 1MB)
+   * @param options - Extensions to enable
+   * @returns HTML string, or a string starting with "Error: "
+   * @example
+   * const html = markdown.render(sharedStorage.getItem("docs/intro"), { taskLists: true });
+   */
+  render(text: string, options?: MarkdownRenderOptions): string;
+}
+
+/**
+ * Allowlists for html.sanitize. Lists that are not set keep the defaults,
+ * which cover common formatting, links, images and tables.
+ */
+interface HtmlSanitizePolicy {
+  /** Elements to keep; other elements are removed, keeping their content */
+  tags?: string[];
+  /** Attributes to keep by element name; "*" lists attributes kept on every element */
+  attributes?: Record<string, string[]>;
+  /** URL schemes allowed in links and images; relative URLs are always allowed */
+  urlSchemes?: string[];
+  /**
+   * rel set on every link (default: "noopener noreferrer"); "" leaves links
+   * as they are. Ignored when rel is an allowed attribute of links.
+   */
+  linkRel?: string;
+}
+
+/**
+ * HTML utilities
+ */
+interface Html {
+  /**
+   * Remove elements, attributes and URLs the policy does not allow, so
+   * user content can be embedded in a page. script and style elements,
+   * comments and event handler attributes are always removed.
+   * @param input - HTML to clean (at most 1MB)
+   * @param policy - Allowlists replacing the defaults
+   * @returns Sanitized HTML, or a string starting with "Error: "
+   * @example
+   * const safe = html.sanitize(comment.body, { tags: ["p", "b", "i", "a"] });
+   */
+  sanitize(input: string, policy?: HtmlSanitizePolicy): string;
+}
+
+/**
+ * Options of pdf.fromHtml
+ */
+interface PdfOptions {
+  /** Paper size (default "a4") */
+  pageSize?: "a4" | "letter" | "legal";
+  /** Rotate the page to landscape */
+  landscape?: boolean;
+  /** Page margin in points (default 50) */
+  margin?: number;
+  /** Body text size in points, 4-72 (default 11); headings scale from it */
+  fontSize?: number;
+  /** Document title; defaults to the HTML <title> */
+  title?: string;
+}
+
+/**
+ * PDF generation
+ */
+interface PdfGenerator {
+  /**
+   * Render HTML to a PDF document. Supports headings, paragraphs, line
+   * breaks, b/strong, i/em, code, lists, blockquote, pre, hr and tables,
+   * with align attributes and text-align styles. Other CSS and images are
+   * ignored. Input size, page count and rendering time are limited by the
+   * server's [javascript.pdf] settings.
+   * @param html - Document to render
+   * @param options - Page setup
+   * @returns The PDF as base64, or a string starting with "Error: "
+   * @example
+   * const invoice = pdf.fromHtml(templates.render("invoice.hbs", order), { title: "Invoice" });
+   * assetStorage.upsertAsset("invoices/42.pdf", invoice, "application/pdf");
+   * return { status: 200, bodyBase64: invoice, contentType: "application/pdf" };
+   */
+  fromHtml(html: string, options?: PdfOptions): string;
+}
+
+/**
+ * Organizer or attendee of a calendar event
+ */
+interface CalendarPerson {
+  email: string;
+  name?: string;
+  /** Whether a reply is requested from the attendee */
+  rsvp?: boolean;
+  /** Participation status: "NEEDS-ACTION", "ACCEPTED", "DECLINED", "TENTATIVE" or "DELEGATED" */
+  response?: string;
+}
+
+/**
+ * Event of ical.build and ical.parse. start and end are "YYYY-MM-DD"
+ * dates for all-day events (the end date is exclusive), ISO 8601
+ * timestamps with an offset, or local "YYYY-MM-DDTHH:MM:SS" times in
+ * timeZone. Local times without a timeZone are floating.
+ */
+interface CalendarEvent {
+  /** Unique identifier; generated by ical.build when missing */
+  uid?: string;
+  summary?: string;
+  description?: string;
+  location?: string;
+  url?: string;
+  start: string;
+  end?: string;
+  /** IANA time zone, e.g. "Europe/Helsinki" */
+  timeZone?: string;
+  /** "TENTATIVE", "CONFIRMED" or "CANCELLED" */
+  status?: string;
+  /** Revision of the event; increase it when sending an updated invite */
+  sequence?: number;
+  /** Recurrence rule, e.g. "FREQ=WEEKLY;COUNT=4" */
+  recurrence?: string;
+  organizer?: CalendarPerson;
+  attendees?: CalendarPerson[];
+}
+
+/**
+ * Options of ical.build
+ */
+interface IcalOptions {
+  /** Scheduling method, e.g. "REQUEST" for an invite or "CANCEL" */
+  method?: string;
+  /** Calendar name shown by calendar applications */
+  name?: string;
+  /** Product identifier, "-//aiwebengine//EN" by default */
+  prodId?: string;
+}
+
+/**
+ * iCalendar (.ics) documents
+ */
+interface Ical {
+  /**
+   * Write events as an iCalendar document. Times with a timeZone are
+   * converted to UTC, except in recurring events, which keep the zone so
+   * recurrences follow its daylight saving changes.
+   * @param events - Events to write (at most 1000)
+   * @param options - Calendar properties
+   * @returns The document, or a string starting with "Error: "
+   * @example
+   * const invite = ical.build([{
+   *   summary: "Planning", start: "2026-03-02T10:00", end: "2026-03-02T11:00",
+   *   timeZone: "Europe/Helsinki",
+   *   organizer: { email: "host@example.com" },
+   *   attendees: [{ email: "guest@example.com", rsvp: true }]
+   * }], { method: "REQUEST" });
+   * return { status: 200, body: invite, contentType: "text/calendar; method=REQUEST" };
+   */
+  build(events: CalendarEvent[], options?: IcalOptions): string;
+
+  /**
+   * Read the events of an iCalendar document. Times in IANA time zones
+   * are returned with their offset and timeZone; times in other zones are
+   * returned as local times with the zone's name as timeZone. An event
+   * with a duration gets the end it implies.
+   * @param text - Document to read (at most 1MB)
+   * @returns JSON of { method, name, events: CalendarEvent[] }, or a
+   *   string starting with "Error: "
+   */
+  parse(text: string): string;
+}
+
+/**
+ * Handlebars templates stored as assets of the script
+ */
+interface Templates {
+  /**
+   * Render one of the script's assets as a Handlebars template.
+   * {{value}} output is HTML-escaped; {{{value}}} inserts it as is.
+   * Compiled templates are cached until the asset changes.
+   * @param name - Asset name of the template
+   * @param data - Data the template reads
+   * @returns Rendered text, or a string starting with "Error: "
+   * @example
+   * const page = templates.render("templates/post.hbs", { title: post.title, comments });
+   */
+  render(name: string, data?: any): string;
+}
+
+/** A variable a prompt template uses */
+interface PromptVariable {
+  name: string;
+  description?: string;
+  /** Rendering fails when a required variable is missing (default false) */
+  required?: boolean;
+}
+
+/** What prompts.save stores */
+interface PromptDefinition {
+  /** Handlebars template; output is not HTML-escaped */
+  template: string;
+  description?: string;
+  variables?: PromptVariable[];
+}
+
+/** One version of a saved prompt template */
+interface PromptTemplate {
+  name: string;
+  version: number;
+  description: string;
+  template: string;
+  variables: PromptVariable[];
+  /** Script that saved this version */
+  scriptUri: string;
+  createdAt: string;
+}
+
+/**
+ * Versioned prompt templates shared by all scripts. The latest version of
+ * each is also offered through MCP prompts/list and prompts/get, unless a
+ * script registered an MCP prompt of the same name.
+ */
+interface Prompts {
+  /**
+   * Save a template as the next version of the prompt `name`. Saving the
+   * same definition as the latest version returns that version.
+   * Requires the WriteScripts capability.
+   * @param name - Lowercase letters, digits, "_", "-" and "."
+   * @returns JSON of the saved PromptTemplate, or a string starting with "Error: "
+   * @example
+   * prompts.save("summarize", {
+   *   template: "Summarize in {{words}} words:\n\n{{text}}",
+   *   variables: [{ name: "text", required: true }, { name: "words" }],
+   * });
+   */
+  save(name: string, definition: PromptDefinition): string;
+  /**
+   * The prompt at `version`, or its latest version
+   * @returns JSON of a PromptTemplate or null, or a string starting with "Error: "
+   */
+  get(name: string, version?: number): string;
+  /**
+   * The latest version of every prompt
+   * @returns JSON array of PromptTemplate, or a string starting with "Error: "
+   */
+  list(): string;
+  /**
+   * Fill in the prompt at `version`, or its latest version
+   * @returns Rendered text, or a string starting with "Error: "
+   * @example
+   * const prompt = prompts.render("summarize", { text: article.body, words: 50 });
+   */
+  render(name: string, vars?: Record<string, any>, version?: number): string;
+}
+
+/**
+ * Options of images.transform. The crop is applied first, then the resize.
+ */
+interface ImageTransformOptions {
+  /** Output width in pixels (1-4096) */
+  width?: number;
+  /** Output height in pixels (1-4096) */
+  height?: number;
+  /**
+   * With both width and height: "contain" (default) fits within the box,
+   * "cover" fills it and crops the overflow, "fill" stretches to it
+   */
+  fit?: "contain" | "cover" | "fill";
+  /** Region of the source image to keep, in source pixels */
+  crop?: { x: number; y: number; width: number; height: number };
+  /** Output format; defaults to the source format. WebP output is lossless. */
+  format?: "png" | "jpeg" | "gif" | "webp" | "avif";
+  /** Quality of JPEG and AVIF output (1-100, default 80) */
+  quality?: number;
+}
+
+/**
+ * Image transformations of the script's assets
+ */
+interface Images {
+  /**
+   * Crop, resize and convert one of the script's PNG, JPEG, GIF or WebP
+   * assets. Results are cached until the asset changes.
+   * @param name - Asset name of the image
+   * @param options - Transform to apply
+   * @returns JSON with contentType and bodyBase64, or a string starting
+   *   with "Error: "
+   * @example
+   * const thumb = JSON.parse(images.transform("hero.jpg", { width: 400, format: "webp" }));
+   * return { status: 200, ...thumb };
+   */
+  transform(name: string, options?: ImageTransformOptions): string;
+}
+
+/**
+ * Translations stored as the script's JSON assets "i18n/<locale>.json",
+ * e.g. "i18n/fi-FI.json". Keys may be nested objects addressed with dots,
+ * values may contain {name} placeholders, and a value may be an object of
+ * plural forms ("zero", "one", "other") chosen by the count parameter.
+ *
+ * Locales are tried in the request's Accept-Language order, each followed
+ * by its language without the region, then the server's default locale.
+ */
+interface I18n {
+  /**
+   * Translate a key
+   * @param key - Translation key, e.g. "nav.home"
+   * @param params - Values of the {name} placeholders; count also selects
+   *   the plural form
+   * @returns The translation, or the key itself when no locale has one
+   * @example
+   * i18n.t("cart.items", { count: 3 }); // "3 items"
+   */
+  t(key: string, params?: Record<string, any>): string;
+
+  /**
+   * Locale translations are taken from first: the first locale of the
+   * fallback chain that has a bundle
+   */
+  locale(): string;
+
+  /**
+   * Prefer a locale over the request's languages for the rest of the
+   * execution, e.g. one stored in the user's profile
+   * @returns "Locale set" or a string starting with "Error: "
+   */
+  setLocale(locale: string): string;
+}
+
+/**
+ * Parameters of llm.complete
+ */
+interface LlmCompletionParams {
+  /** Model name; must be allowed for the script in [javascript.llm] */
+  model: string;
+  /** System prompt, placed before the messages */
+  system?: string;
+  /** Conversation so far */
+  messages?: { role: "system" | "user" | "assistant"; content: string }[];
+  /** User message appended after the messages */
+  prompt?: string;
+  /** Longest completion in tokens; defaults to 1024 */
+  maxTokens?: number;
+  /** Sampling temperature, 0-2 (Anthropic caps it at 1) */
+  temperature?: number;
+  /** Sequences that end the completion */
+  stop?: string[];
+  /**
+   * Registered MCP tools the model may call: true for all of them, or their
+   * names. The engine runs the calls with the caller's permissions and
+   * sends the results back until the model answers. Not supported by
+   * llm.stream.
+   */
+  tools?: boolean | string[];
+  /** Most rounds of tool calls before the completion fails; defaults to 5, at most 20 */
+  maxToolRounds?: number;
+}
+
+/** A tool call run while completing */
+interface LlmToolCall {
+  name: string;
+  arguments: any;
+  /** What the tool returned; null when it failed */
+  result: any;
+  error?: string;
+}
+
+/**
+ * Result of llm.complete
+ */
+interface LlmCompletion {
+  provider: string;
+  /** Model that answered, as reported by the provider */
+  model: string;
+  text: string;
+  /** Why generation stopped, such as "stop", "length" or "end_turn" */
+  finishReason: string | null;
+  /** Summed over every round when tools were called */
+  usage: { inputTokens: number | null; outputTokens: number | null };
+  /** Tool calls run while completing, in order; absent when there were none */
+  toolCalls?: LlmToolCall[];
+  /** Flagged prompt and output when moderation is set to "flag" */
+  moderation?: { input?: ModerationResult; output?: ModerationResult };
+}
+
+/**
+ * Completions from the LLM providers configured in [javascript.llm].
+ * API keys are read from the script or user secret the provider names
+ * ("openai_api_key" and "anthropic_api_key" for the built-in providers).
+ */
+interface Llm {
+  /**
+   * Run a chat completion
+   * @param provider - "openai", "anthropic" or a configured provider name
+   * @returns JSON string of an LlmCompletion, or a string starting with
+   *   "Error: "
+   * @example
+   * const result = llm.complete("anthropic", {
+   *   model: "claude-haiku-4-5",
+   *   system: "Answer in one sentence.",
+   *   prompt: "What is a webhook?"
+   * });
+   * if (result.startsWith("Error: ")) return ResponseBuilder.error(502, result);
+   * const completion = JSON.parse(result);
+   */
+  complete(provider: string, params: LlmCompletionParams): string;
+  /**
+   * Run a chat completion in the background and send its text to the
+   * connections of a registered stream as it is produced. Every message is
+   * JSON with the returned id: { type: "llm.delta", id, text } for each
+   * piece of text, then { type: "llm.done", id, completion } with an
+   * LlmCompletion or { type: "llm.error", id, error }. Requires the
+   * ManageStreams capability.
+   * @returns JSON string { id, path }, or a string starting with "Error: "
+   * @example
+   * const started = llm.stream(
+   *   { provider: "openai", model: "gpt-4o-mini", prompt: question },
+   *   "/chat/events"
+   * );
+   */
+  stream(
+    params: LlmCompletionParams & { provider: string },
+    streamPath: string,
+  ): string;
+}
+
+interface EmbeddingOptions {
+  /** Defaults to embedding_provider of [javascript.llm] */
+  provider?: string;
+  /** Defaults to embedding_model; other models must be allowed like chat models */
+  model?: string;
+  /** Length of the returned vectors, for models that can shorten them */
+  dimensions?: number;
+}
+
+interface EmbeddingResult {
+  provider: string;
+  model: string;
+  /** One vector per input text, in input order */
+  embeddings: number[][];
+  usage: { inputTokens: number | null; outputTokens: null };
+}
+
+/** Embeddings from the configured LLM providers */
+interface Embeddings {
+  /**
+   * Embed a text or up to 256 texts
+   * @returns JSON string of an EmbeddingResult, or a string starting with
+   *   "Error: "
+   */
+  create(input: string | string[], options?: EmbeddingOptions): string;
+}
+
+/** Outcome of moderation.check */
+interface ModerationResult {
+  flagged: boolean;
+  /** Categories the text was flagged for, sorted */
+  categories: string[];
+  /** Score per category reported by the provider, from 0 to 1 */
+  scores?: Record<string, number>;
+}
+
+/**
+ * Content moderation configured in [javascript.llm.moderation]: a provider's
+ * moderation API and/or local patterns per category
+ */
+interface Moderation {
+  /**
+   * Check a text of up to 256 KiB
+   * @param options.action - "audit" logs flagged text, "block" also returns an
+   *   error; without it the result is only returned
+   * @returns JSON string of a ModerationResult, or a string starting with
+   *   "Error: "
+   */
+  check(
+    text: string,
+    options?: { action?: "off" | "audit" | "flag" | "block" },
+  ): string;
+}
+
+/** A document in a vector collection */
+interface VectorDocument {
+  /** Unique within the collection; storing it again replaces the document */
+  id: string;
+  /** Embedded with embeddings.create when no embedding is given */
+  text?: string;
+  /** JSON object vectors.search can filter on */
+  metadata?: Record<string, unknown>;
+  embedding?: number[];
+}
+
+interface VectorSearchOptions {
+  /** 1-100, default 10 */
+  limit?: number;
+  /** Matches to skip, usually nextOffset of the previous page */
+  offset?: number;
+  /** Only documents whose metadata contains these keys and values */
+  filter?: Record<string, unknown>;
+  /** Only matches scoring at least this much */
+  minScore?: number;
+}
+
+interface VectorMatch {
+  id: string;
+  text: string | null;
+  metadata: Record<string, unknown>;
+  /** Cosine similarity to the query, from -1 to 1 */
+  score: number;
+}
+
+interface VectorSearchPage {
+  /** Best match first */
+  matches: VectorMatch[];
+  /**
+   * Offset of the next page, or null on the last one. Pages reach at most
+   * 1000 matches deep.
+   */
+  nextOffset: number | null;
+}
+
+/**
+ * Vector collections of this script, stored with pgvector in the script's
+ * database schema. A collection is created by the first upsert and keeps
+ * the vector length of that call. Requires the ManageScriptDatabase
+ * capability; failures return JSON with an "error" field.
+ */
+interface Vectors {
+  /**
+   * Store up to 256 documents
+   * @param options - embedding options for documents given as text
+   * @returns JSON string: { stored: number }
+   * @example
+   * vectors.upsert("docs", [
+   *   { id: "intro", text: "aiwebengine runs JavaScript handlers", metadata: { lang: "en" } }
+   * ]);
+   */
+  upsert(
+    collection: string,
+    documents: VectorDocument[],
+    options?: EmbeddingOptions,
+  ): string;
+  /**
+   * Find a page of the documents closest to a query, best first. A text
+   * query is embedded with the default embedding model.
+   * @returns JSON string of a VectorSearchPage
+   * @example
+   * const question = "What runs handlers?";
+   * const page = JSON.parse(vectors.search("docs", question, 3, { lang: "en" }));
+   * if (page.nextOffset !== null) {
+   *   const next = JSON.parse(vectors.search("docs", question, 3, { lang: "en" }, page.nextOffset));
+   * }
+   */
+  search(
+    collection: string,
+    query: string | number[],
+    options?: VectorSearchOptions,
+  ): string;
+  /**
+   * Find the k documents closest to a query whose metadata contains the
+   * filter, skipping the first offset matches
+   * @returns JSON string of a VectorSearchPage
+   */
+  search(
+    collection: string,
+    query: string | number[],
+    k: number,
+    filter?: Record<string, unknown> | null,
+    offset?: number,
+  ): string;
+  /** @returns JSON string: { deleted: number } */
+  delete(collection: string, ids: string[]): string;
+  /** @returns JSON string: { dropped: boolean } */
+  drop(collection: string): string;
+}
+
+/**
+ * Rule for one field checked by validate.object. Missing and null fields
+ * only fail when required; the other checks apply to the values they fit.
+ */
+interface FieldRule {
+  type?:
+    | "string"
+    | "number"
+    | "integer"
+    | "boolean"
+    | "object"
+    | "array"
+    | "email"
+    | "url"
+    | "uuid";
+  required?: boolean;
+  /** Shortest string (in characters) or array */
+  minLength?: number;
+  /** Longest string (in characters) or array */
+  maxLength?: number;
+  /** Smallest number */
+  min?: number;
+  /** Largest number */
+  max?: number;
+  /** Accepted values */
+  enum?: any[];
+  /** Rules for the fields of a nested object (implies type "object") */
+  fields?: Record<string, FieldRule>;
+  /** Rule for every item of an array (implies type "array") */
+  items?: FieldRule;
+}
+
+/**
+ * Input validation implemented in the engine
+ */
+interface Validate {
+  /** Whether value is an email address */
+  email(value: any): boolean;
+  /** Whether value is an http or https URL */
+  url(value: any): boolean;
+  /** Whether value is a UUID in its hyphenated form */
+  uuid(value: any): boolean;
+  /**
+   * Whether a string's character count or an array's length is within
+   * the bounds
+   */
+  length(value: any, min?: number, max?: number): boolean;
+  /** Whether value equals one of the allowed values */
+  oneOf(value: any, allowed: any[]): boolean;
+  /**
+   * Check an object against field rules
+   * @returns JSON string of { valid, errors: [{ path, message }] }, or a
+   *   string starting with "Error: " when the rules are invalid
+   * @example
+   * const result = JSON.parse(validate.object(JSON.parse(request.body), {
+   *   email: { type: "email", required: true },
+   *   role: { enum: ["admin", "user"] },
+   *   address: { fields: { city: { type: "string", required: true } } }
+   * }));
+   * if (!result.valid) return ResponseBuilder.json({ errors: result.errors }, 400);
+   */
+  object(value: any, rules: Record<string, FieldRule>): string;
+  /** Query parameter value; throws when it is missing or empty */
+  requireQueryParam(context: HandlerContext, name: string): string;
+  /** Path parameter value; throws when it is missing or empty */
+  requirePathParam(context: HandlerContext, name: string): string;
+  /** The string itself; throws when it is not a string within the limits */
+  validateString(
+    value: any,
+    options?: { minLength?: number; maxLength?: number; pattern?: RegExp },
+  ): string;
+  /** The value as a number; throws when it is not one within the limits */
+  validateNumber(value: any, options?: { min?: number; max?: number }): number;
+}
+
 // ============================================================================
 // Global Objects
 // ============================================================================
@@ -1514,15 +3319,30 @@ interface Convert {
 declare var routeRegistry: RouteRegistry;
 declare var assetStorage: AssetStorage;
 declare var sharedStorage: SharedStorage;
+declare var cache: Cache;
 declare var personalStorage: PersonalStorage;
 declare var secretStorage: SecretStorage;
 declare var schedulerService: SchedulerService;
 declare var graphQLRegistry: GraphQLRegistry;
 declare var mcpRegistry: McpRegistry;
 declare var database: Database;
+declare var db: SqlDatabase;
 declare var console: Console;
 declare var dispatcher: MessageDispatcher;
 declare var convert: Convert;
+declare var markdown: Markdown;
+declare var html: Html;
+declare var pdf: PdfGenerator;
+declare var ical: Ical;
+declare var templates: Templates;
+declare var prompts: Prompts;
+declare var images: Images;
+declare var i18n: I18n;
+declare var validate: Validate;
+declare var llm: Llm;
+declare var embeddings: Embeddings;
+declare var moderation: Moderation;
+declare var vectors: Vectors;
 
 // ============================================================================
 // Response Builder Helpers
@@ -1588,6 +3408,29 @@ declare var ResponseBuilder: {
    * return ResponseBuilder.redirect("/login");
    */
   redirect(location: string): HttpResponse;
+
+  /**
+   * Respond with the representation the request's Accept header prefers,
+   * with its Content-Type and Vary: Accept set. Keys are shorthands (json,
+   * html, csv, text, xml) or media types; earlier keys win ties. Values are
+   * the body or a function returning it; non-string JSON bodies are
+   * serialized. Responds 406 when no representation is acceptable.
+   * @param request - context.request
+   * @param representations - Body, or body function, per type
+   * @param status - HTTP status code (default: 200)
+   * @returns HTTP response object
+   * @example
+   * return ResponseBuilder.negotiate(context.request, {
+   *   json: () => orders,
+   *   html: () => templates.render("orders.hbs", { orders }),
+   *   csv: () => orders.map((o) => o.id + "," + o.total).join("\n"),
+   * });
+   */
+  negotiate(
+    request: HttpRequest,
+    representations: Record<string, any>,
+    status?: number,
+  ): HttpResponse;
 };
 
 // ============================================================================
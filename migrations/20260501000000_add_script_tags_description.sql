@@ -0,0 +1,9 @@
+-- Add categorization metadata to scripts
+-- Tags are free-form labels used for filtering in listScripts and the editor;
+-- description is a short human-readable summary of what the script does
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS description TEXT;
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+
+-- GIN index supports tag containment filters (tags @> ARRAY['...'])
+CREATE INDEX IF NOT EXISTS idx_scripts_tags ON scripts USING GIN (tags);
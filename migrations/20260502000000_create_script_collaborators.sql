@@ -0,0 +1,19 @@
+-- Create script_collaborators junction table
+-- Collaborators may edit a script's content and metadata but, unlike owners,
+-- cannot delete the script or manage its owners and collaborators.
+-- Collaborators are added/removed by script owners or administrators.
+
+CREATE TABLE IF NOT EXISTS script_collaborators (
+    script_uri TEXT NOT NULL REFERENCES scripts(uri) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (script_uri, user_id)
+);
+
+-- Index for finding all scripts a user collaborates on
+CREATE INDEX IF NOT EXISTS idx_script_collaborators_user_id ON script_collaborators(user_id);
+
+COMMENT ON TABLE script_collaborators IS 'Junction table of users allowed to edit a script without owning it. Administrators can edit any script regardless of ownership.';
+COMMENT ON COLUMN script_collaborators.script_uri IS 'Reference to the script URI';
+COMMENT ON COLUMN script_collaborators.user_id IS 'User ID from the users table (TEXT format, not UUID)';
+COMMENT ON COLUMN script_collaborators.created_at IS 'When this collaborator was added';
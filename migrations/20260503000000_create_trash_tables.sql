@@ -0,0 +1,40 @@
+-- Trash for soft-deleted scripts and assets
+-- deleteScript/deleteAsset move rows here instead of dropping them. Rows can be
+-- restored until they are older than the configured retention
+-- (repository.trash_retention_days), after which a scheduled job purges them.
+
+CREATE TABLE IF NOT EXISTS script_trash (
+    uri TEXT PRIMARY KEY,
+    content TEXT NOT NULL,
+    name TEXT,
+    privileged BOOLEAN NOT NULL DEFAULT FALSE,
+    description TEXT,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    owners TEXT[] NOT NULL DEFAULT '{}',
+    collaborators TEXT[] NOT NULL DEFAULT '{}',
+    -- script_tables rows of the script; the physical tables are kept until purge
+    script_tables JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_trash_deleted_at ON script_trash(deleted_at);
+
+CREATE TABLE IF NOT EXISTS asset_trash (
+    uri TEXT PRIMARY KEY,
+    script_uri TEXT NOT NULL,
+    mimetype TEXT NOT NULL,
+    content BYTEA NOT NULL,
+    name TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- TRUE when the asset was trashed because its script was deleted; such
+    -- assets are restored together with the script
+    deleted_with_script BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS idx_asset_trash_deleted_at ON asset_trash(deleted_at);
+CREATE INDEX IF NOT EXISTS idx_asset_trash_script_uri ON asset_trash(script_uri);
+
+COMMENT ON TABLE script_trash IS 'Soft-deleted scripts with the ownership and table metadata needed to restore them';
+COMMENT ON TABLE asset_trash IS 'Soft-deleted assets awaiting restore or purge';
@@ -0,0 +1,8 @@
+-- Custom response headers for assets
+-- Headers (e.g. Cache-Control, Content-Disposition, CORS) are stored with the
+-- asset and applied when it is served through a registered asset route.
+
+ALTER TABLE assets ADD COLUMN IF NOT EXISTS headers JSONB NOT NULL DEFAULT '{}';
+ALTER TABLE asset_trash ADD COLUMN IF NOT EXISTS headers JSONB NOT NULL DEFAULT '{}';
+
+COMMENT ON COLUMN assets.headers IS 'Extra HTTP response headers (name -> value) sent when the asset is served';
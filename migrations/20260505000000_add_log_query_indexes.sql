@@ -0,0 +1,10 @@
+-- Indexes for paginated log queries
+-- Log pages are read newest first with (created_at, id) keyset cursors,
+-- optionally narrowed to one script or one level.
+
+CREATE INDEX IF NOT EXISTS idx_logs_created_at_id ON logs(created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_logs_script_uri_created_at_id ON logs(script_uri, created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_logs_log_level_created_at_id ON logs(log_level, created_at DESC, id DESC);
+
+-- Superseded by the keyset indexes above
+DROP INDEX IF EXISTS idx_logs_script_uri_created_at;
@@ -0,0 +1,12 @@
+-- Structured log payloads
+-- `data` holds the object passed to console.log and friends, `context` the
+-- request/handler context captured automatically when the entry was written.
+
+ALTER TABLE logs ADD COLUMN IF NOT EXISTS data JSONB;
+ALTER TABLE logs ADD COLUMN IF NOT EXISTS context JSONB;
+
+-- Containment queries on structured fields (data @> '{"userId": 42}')
+CREATE INDEX IF NOT EXISTS idx_logs_data ON logs USING GIN (data jsonb_path_ops);
+
+-- All entries written while handling one request
+CREATE INDEX IF NOT EXISTS idx_logs_context_request_id ON logs ((context->>'requestId'));
@@ -0,0 +1,9 @@
+-- Expiring keys in shared storage
+-- Items with an expires_at in the past are treated as missing and removed
+-- lazily on read and by the background expiry sweep.
+
+ALTER TABLE script_properties ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_script_properties_expires_at
+    ON script_properties(expires_at)
+    WHERE expires_at IS NOT NULL;
@@ -0,0 +1,7 @@
+-- Per-script storage quota override
+-- Shared storage items and assets of a script count against its quota. NULL
+-- uses the configured default (repository.default_storage_quota_bytes) and
+-- 0 means unlimited.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS storage_quota_bytes BIGINT
+    CHECK (storage_quota_bytes >= 0);
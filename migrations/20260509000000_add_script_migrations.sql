@@ -0,0 +1,13 @@
+-- Migrations applied by scripts to their private schema (db.migrate)
+-- One row per applied version; the checksum of the statements detects a
+-- migration that was edited after it ran. Rows outlive a deleted script so
+-- a restore from the trash keeps its schema state, and are removed when the
+-- trash entry is purged.
+
+CREATE TABLE IF NOT EXISTS script_migrations (
+    script_uri TEXT NOT NULL,
+    version BIGINT NOT NULL CHECK (version > 0),
+    checksum TEXT NOT NULL,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (script_uri, version)
+);
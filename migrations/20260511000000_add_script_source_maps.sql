@@ -0,0 +1,9 @@
+-- Source maps uploaded alongside scripts
+-- Scripts bundled or minified outside the server can be uploaded with a
+-- source map. Stack frames in error reports and the script log view are
+-- translated through it. The map applies only while the script's content
+-- hashes to source_map_content_hash, so a map left over from an older
+-- version of the script is ignored.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS source_map TEXT;
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS source_map_content_hash TEXT;
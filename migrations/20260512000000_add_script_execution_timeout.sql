@@ -0,0 +1,7 @@
+-- Per-script execution timeout override
+-- Replaces javascript.execution_timeout_ms for the script's route handlers,
+-- up to javascript.max_script_execution_timeout_ms. NULL uses the configured
+-- timeout.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS execution_timeout_ms BIGINT
+    CHECK (execution_timeout_ms > 0);
@@ -0,0 +1,14 @@
+-- Versioned prompt templates saved with prompts.save. Saving a changed
+-- template adds a version; prompts.render and the MCP prompts/get method use
+-- the latest version unless one is named.
+
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    name TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    template TEXT NOT NULL,
+    variables JSONB NOT NULL DEFAULT '[]',
+    script_uri TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (name, version)
+);
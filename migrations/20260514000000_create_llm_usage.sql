@@ -0,0 +1,20 @@
+-- Tokens and estimated cost of llm.* calls, summed per calendar month (UTC),
+-- script, user, provider and model. Monthly LLM budgets are checked against
+-- these totals. user_id is '' for calls made without a signed-in user.
+
+CREATE TABLE IF NOT EXISTS llm_usage (
+    month DATE NOT NULL,
+    script_uri TEXT NOT NULL,
+    user_id TEXT NOT NULL DEFAULT '',
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    calls BIGINT NOT NULL DEFAULT 0,
+    input_tokens BIGINT NOT NULL DEFAULT 0,
+    output_tokens BIGINT NOT NULL DEFAULT 0,
+    cost DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (month, script_uri, user_id, provider, model)
+);
+
+CREATE INDEX IF NOT EXISTS idx_llm_usage_month_user
+    ON llm_usage(month, user_id);
@@ -0,0 +1,36 @@
+-- Outgoing webhooks queued by webhooks.send. A background worker posts each
+-- pending delivery when next_attempt_at passes, retrying failures with
+-- backoff until it is delivered or has used max_attempts. locked_until keeps
+-- other workers away from a delivery while an attempt runs. secret_name names
+-- the script secret the body is signed with; the secret itself is not stored.
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY,
+    script_uri TEXT NOT NULL,
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    headers JSONB NOT NULL DEFAULT '{}',
+    secret_name TEXT,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMPTZ,
+    last_status_code INTEGER,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+    ON webhook_deliveries(next_attempt_at)
+    WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_script
+    ON webhook_deliveries(script_uri, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_status_updated
+    ON webhook_deliveries(status, updated_at);
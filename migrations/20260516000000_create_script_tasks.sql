@@ -0,0 +1,32 @@
+-- Background tasks queued by tasks.enqueue. Workers run due pending tasks
+-- by priority, highest first, retrying failures with backoff until a task
+-- completes or has used max_attempts. locked_until hides a running task
+-- from other workers; a task whose worker stopped runs again after it.
+
+CREATE TABLE IF NOT EXISTS script_tasks (
+    id UUID PRIMARY KEY,
+    script_uri TEXT NOT NULL,
+    handler_name TEXT NOT NULL,
+    payload JSONB NOT NULL DEFAULT 'null',
+    priority INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'completed', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_tasks_due
+    ON script_tasks(priority DESC, run_at)
+    WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS idx_script_tasks_script
+    ON script_tasks(script_uri, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_script_tasks_status_updated
+    ON script_tasks(status, updated_at);
@@ -0,0 +1,9 @@
+-- Idempotency keys of tasks.enqueue: a script queues at most one task per
+-- key while that task is kept, so "only once per order" needs no lookup
+-- table of its own.
+
+ALTER TABLE script_tasks ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_script_tasks_idempotency_key
+    ON script_tasks(script_uri, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
@@ -0,0 +1,20 @@
+-- Delivery log of email.send. Every send attempt is recorded, whether the
+-- provider accepted it or not; the entries of a script's current UTC day
+-- count against its daily limit.
+
+CREATE TABLE IF NOT EXISTS email_log (
+    id UUID PRIMARY KEY,
+    script_uri TEXT NOT NULL,
+    recipients TEXT[] NOT NULL,
+    subject TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('sent', 'failed')),
+    error TEXT,
+    message_id TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_log_script
+    ON email_log(script_uri, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_email_log_created
+    ON email_log(created_at);
@@ -0,0 +1,21 @@
+-- Requests served by script routes, per day (UTC), script, route pattern and
+-- method. status_counts maps response statuses to request counts;
+-- latency_buckets counts requests per bucket of route_usage::LATENCY_BUCKETS_MS
+-- so percentiles can be estimated over any range of days.
+
+CREATE TABLE IF NOT EXISTS route_usage (
+    day DATE NOT NULL,
+    script_uri TEXT NOT NULL,
+    route TEXT NOT NULL,
+    method TEXT NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    total_ms DOUBLE PRECISION NOT NULL DEFAULT 0,
+    max_ms DOUBLE PRECISION NOT NULL DEFAULT 0,
+    status_counts JSONB NOT NULL DEFAULT '{}',
+    latency_buckets BIGINT[] NOT NULL DEFAULT '{}',
+    last_request_at TIMESTAMPTZ,
+    PRIMARY KEY (day, script_uri, route, method)
+);
+
+CREATE INDEX IF NOT EXISTS idx_route_usage_script
+    ON route_usage(script_uri, day);
@@ -0,0 +1,24 @@
+-- Usage records written by the table sink of [metering]. Each row is the
+-- quantity of one metric used by a tenant, user or script over
+-- [period_start, period_end); storage_bytes rows are samples taken at
+-- period_end. IDs stay the same when a batch is retried, so downstream
+-- billing can read rows by period without double counting.
+
+CREATE TABLE IF NOT EXISTS usage_records (
+    id UUID PRIMARY KEY,
+    metric TEXT NOT NULL,
+    quantity BIGINT NOT NULL,
+    unit TEXT NOT NULL,
+    tenant TEXT,
+    user_id TEXT,
+    script_uri TEXT,
+    period_start TIMESTAMPTZ NOT NULL,
+    period_end TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_period
+    ON usage_records(period_end);
+
+CREATE INDEX IF NOT EXISTS idx_usage_records_tenant
+    ON usage_records(tenant, period_end);
@@ -0,0 +1,24 @@
+-- Actions taken by [security.threat_response] policies. Actions of policies
+-- with require_review wait as 'pending' until an administrator approves or
+-- dismisses them; active actions end at expires_at or when lifted.
+
+CREATE TABLE IF NOT EXISTS threat_actions (
+    id UUID PRIMARY KEY,
+    policy TEXT NOT NULL,
+    action TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    indicator TEXT NOT NULL,
+    description TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'active', 'dismissed', 'lifted')),
+    duration_secs BIGINT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    decided_by TEXT,
+    decided_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_threat_actions_status
+    ON threat_actions(status, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_threat_actions_subject
+    ON threat_actions(action, subject);
@@ -0,0 +1,10 @@
+-- Failed sign-in counters kept by [auth.lockout]. An account is locked while
+-- locked_until is in the future; unlocking an account deletes its row.
+
+CREATE TABLE IF NOT EXISTS account_lockouts (
+    user_id TEXT PRIMARY KEY REFERENCES users(user_id) ON DELETE CASCADE,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    window_started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
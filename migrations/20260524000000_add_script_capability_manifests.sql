@@ -0,0 +1,9 @@
+-- Per-script capability manifests ([security.script_manifests])
+-- A script requests sandbox capabilities (fetch, secrets, graphql, streams,
+-- db); an administrator approves them. While manifests are enforced, a
+-- script only gets the approved capabilities it still requests.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS requested_capabilities TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS approved_capabilities TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS capabilities_approved_by TEXT;
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS capabilities_approved_at TIMESTAMPTZ;
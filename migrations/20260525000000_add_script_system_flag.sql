@@ -0,0 +1,13 @@
+-- Protected system scripts
+-- System scripts (the bootstrapped feature scripts) can only be overwritten
+-- or deleted by administrators passing an explicit force flag.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS system BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE scripts SET system = TRUE
+WHERE uri IN (
+    'https://example.com/core',
+    'https://example.com/cli',
+    'https://example.com/admin',
+    'https://example.com/auth'
+);
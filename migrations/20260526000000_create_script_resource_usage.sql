@@ -0,0 +1,19 @@
+-- Resources used by scripts, per day (UTC) and script. cpu_ms sums the time
+-- the script's runtime ran; peak_memory_bytes is the most heap an execution
+-- had allocated when it finished; outbound_bytes sums the bytes sent and
+-- received by fetch() and webhook deliveries.
+
+CREATE TABLE IF NOT EXISTS script_resource_usage (
+    day DATE NOT NULL,
+    script_uri TEXT NOT NULL,
+    executions BIGINT NOT NULL DEFAULT 0,
+    cpu_ms DOUBLE PRECISION NOT NULL DEFAULT 0,
+    peak_memory_bytes BIGINT NOT NULL DEFAULT 0,
+    timeouts BIGINT NOT NULL DEFAULT 0,
+    outbound_bytes BIGINT NOT NULL DEFAULT 0,
+    last_execution_at TIMESTAMPTZ,
+    PRIMARY KEY (day, script_uri)
+);
+
+CREATE INDEX IF NOT EXISTS idx_script_resource_usage_day
+    ON script_resource_usage(day);
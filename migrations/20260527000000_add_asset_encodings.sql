@@ -0,0 +1,10 @@
+-- Precompressed asset variants
+-- gzip and brotli representations of compressible assets, computed when an
+-- asset is stored. NULL means not computed (the asset is compressed on the
+-- fly); an empty value means compression did not pay off.
+
+ALTER TABLE assets ADD COLUMN IF NOT EXISTS content_gzip BYTEA;
+ALTER TABLE assets ADD COLUMN IF NOT EXISTS content_br BYTEA;
+
+COMMENT ON COLUMN assets.content_gzip IS 'gzip variant of content; empty when not smaller, NULL when not computed';
+COMMENT ON COLUMN assets.content_br IS 'brotli variant of content; empty when not smaller, NULL when not computed';
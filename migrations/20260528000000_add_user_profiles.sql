@@ -0,0 +1,8 @@
+-- Custom user profile fields
+-- Fields described by the JSON Schema in auth.profile_schema, edited through
+-- the updateUserProfile GraphQL mutation and read by scripts as
+-- request.auth.profile.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS profile JSONB NOT NULL DEFAULT '{}'::JSONB;
+
+COMMENT ON COLUMN users.profile IS 'Custom profile fields validated against auth.profile_schema';
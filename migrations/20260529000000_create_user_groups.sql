@@ -0,0 +1,28 @@
+-- User groups (teams). Members of a group get the group's roles on their next
+-- sign-in, may use routes registered with auth: { groups: [...] } naming the
+-- group, and may edit scripts the group collaborates on (script_collaborators
+-- rows with user_id 'group:<name>').
+
+CREATE TABLE IF NOT EXISTS user_groups (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    is_editor BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS user_group_members (
+    group_name TEXT NOT NULL REFERENCES user_groups(name) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (group_name, user_id)
+);
+
+-- Index for finding the groups of a user
+CREATE INDEX IF NOT EXISTS idx_user_group_members_user_id ON user_group_members(user_id);
+
+COMMENT ON TABLE user_groups IS 'Named groups of users, managed by administrators';
+COMMENT ON COLUMN user_groups.is_admin IS 'Members get the Administrator role';
+COMMENT ON COLUMN user_groups.is_editor IS 'Members get the Editor role';
+COMMENT ON TABLE user_group_members IS 'Junction table of group memberships';
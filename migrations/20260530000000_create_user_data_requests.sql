@@ -0,0 +1,22 @@
+-- Audit trail of user data exports and erasures (data subject access and
+-- erasure requests). The user is identified by the same pseudonym erasures
+-- leave in anonymized rows, so the trail itself keeps no user ID.
+
+CREATE TABLE IF NOT EXISTS user_data_requests (
+    id UUID PRIMARY KEY,
+    subject TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('export', 'anonymize', 'purge')),
+    requested_by TEXT NOT NULL,
+    summary JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_data_requests_created
+    ON user_data_requests(created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_user_data_requests_subject
+    ON user_data_requests(subject);
+
+COMMENT ON TABLE user_data_requests IS 'Exports and erasures of the data kept about a user';
+COMMENT ON COLUMN user_data_requests.subject IS 'Pseudonym of the user (deleted-<hash>)';
+COMMENT ON COLUMN user_data_requests.summary IS 'Rows exported or erased per section';
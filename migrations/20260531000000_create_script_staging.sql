@@ -0,0 +1,15 @@
+-- Staging slots of scripts (blue/green deployment)
+-- A staged version waits next to the live one until it is promoted;
+-- previous_content keeps the version the last promotion or rollback
+-- replaced, so it can be swapped back.
+
+CREATE TABLE IF NOT EXISTS script_staging (
+    script_uri TEXT PRIMARY KEY REFERENCES scripts(uri) ON DELETE CASCADE,
+    staged_content TEXT,
+    staged_at TIMESTAMPTZ,
+    staged_by TEXT,
+    staged_init_error TEXT,
+    staged_routes TEXT[] NOT NULL DEFAULT '{}',
+    previous_content TEXT,
+    promoted_at TIMESTAMPTZ
+);
@@ -0,0 +1,6 @@
+-- Per-script memory limit override
+-- Replaces javascript.max_memory_bytes for every execution of the script,
+-- up to javascript.max_heap_bytes. NULL uses the configured limit.
+
+ALTER TABLE scripts ADD COLUMN IF NOT EXISTS memory_limit_bytes BIGINT
+    CHECK (memory_limit_bytes > 0);
@@ -0,0 +1,7 @@
+-- Full scripts row of a trashed script
+-- Restoring a script brings back every column of the row it was deleted
+-- with (quota, timeout, memory limit, source map, capability manifest, system
+-- flag, ...). NULL for scripts trashed before this column existed; those are
+-- restored from the individual columns.
+
+ALTER TABLE script_trash ADD COLUMN IF NOT EXISTS script_row JSONB;
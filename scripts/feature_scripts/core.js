@@ -139,6 +139,23 @@ function scriptUpdatesCustomizer(context) {
   return {};
 }
 
+// Lint warnings for a script that was just saved, or an empty list when
+// linting is unavailable
+function scriptLintWarnings(uri, content) {
+  if (
+    typeof scriptStorage === "undefined" ||
+    typeof scriptStorage.lintScript !== "function"
+  ) {
+    return [];
+  }
+  try {
+    return JSON.parse(scriptStorage.lintScript(uri, content)).warnings || [];
+  } catch (error) {
+    console.warn(`Script lint failed for ${uri}: ${error.message}`);
+    return [];
+  }
+}
+
 // Helper function to broadcast script update messages
 // Message metadata in the JSON object will be used for filtering.
 // The default match mode is "subset", where connection criteria must be present
@@ -184,10 +201,12 @@ function upsert_script_handler(context) {
     // Extract parameters from form data (for POST requests)
     let uri = null;
     let content = null;
+    let sourceMap = null;
 
     if (req.form) {
       uri = req.form.uri;
       content = req.form.content;
+      sourceMap = req.form.sourceMap || null;
     }
 
     // Fallback to query parameters if form data is not available
@@ -229,11 +248,14 @@ function upsert_script_handler(context) {
         : null;
     const action = existingScript ? "updated" : "inserted";
 
-    // Call the upsertScript function
+    // Call the upsertScript function, storing the source map uploaded with
+    // an externally bundled script
     const result =
       typeof scriptStorage !== "undefined" &&
       typeof scriptStorage.upsertScript === "function"
-        ? scriptStorage.upsertScript(uri, content)
+        ? sourceMap
+          ? scriptStorage.upsertScript(uri, content, { sourceMap: sourceMap })
+          : scriptStorage.upsertScript(uri, content)
         : "Error: scriptStorage.upsertScript not available";
 
     // Check if the result indicates an error
@@ -242,7 +264,7 @@ function upsert_script_handler(context) {
     if (!result || result.startsWith("Error:")) {
       console.error(`Script upsert failed: ${result}`);
       return {
-        status: 500,
+        status: result && result.startsWith("Error: Syntax error") ? 400 : 500,
         body: JSON.stringify({
           error: "Failed to upsert script",
           details: result || "Unknown error",
@@ -267,6 +289,7 @@ function upsert_script_handler(context) {
         message: "Script upserted successfully",
         uri: uri,
         contentLength: content.length,
+        warnings: scriptLintWarnings(uri, content),
         timestamp: new Date().toISOString(),
       }),
       contentType: "application/json",
@@ -285,6 +308,60 @@ function upsert_script_handler(context) {
   }
 }
 
+// Script preview endpoint: runs an unsaved script body against a request
+// described by the caller without persisting any of its writes
+function dry_run_script_handler(context) {
+  const req = getRequest(context);
+  const badRequest = (error) => ({
+    status: 400,
+    body: JSON.stringify({
+      error: error,
+      timestamp: new Date().toISOString(),
+    }),
+    contentType: "application/json",
+  });
+
+  let payload;
+  try {
+    payload = JSON.parse(req.body || "{}");
+  } catch (error) {
+    return badRequest("Request body must be JSON: " + error.message);
+  }
+
+  if (!payload.uri) {
+    return badRequest("Missing required parameter: uri");
+  }
+  if (!payload.content) {
+    return badRequest("Missing required parameter: content");
+  }
+  if (!payload.request || !payload.request.handler) {
+    return badRequest("Missing required parameter: request.handler");
+  }
+
+  const result = scriptStorage.dryRun(
+    payload.uri,
+    payload.content,
+    payload.request,
+  );
+  if (!result || result.startsWith("Error:")) {
+    return {
+      status: result && /permission/i.test(result) ? 403 : 400,
+      body: JSON.stringify({
+        error: "Dry run failed",
+        details: result || "Unknown error",
+        timestamp: new Date().toISOString(),
+      }),
+      contentType: "application/json",
+    };
+  }
+
+  return {
+    status: 200,
+    body: result,
+    contentType: "application/json",
+  };
+}
+
 // Script deletion endpoint
 function delete_script_handler(context) {
   const req = getRequest(context);
@@ -485,10 +562,14 @@ function scriptsQuery(context) {
   const req = getRequest(context);
   const args = getArgs(context);
   try {
+    const filter = {};
+    if (args && args.tag) filter.tag = args.tag;
+    if (args && args.search) filter.search = args.search;
+
     const scriptsJson =
       typeof scriptStorage !== "undefined" &&
       typeof scriptStorage.listScripts === "function"
-        ? scriptStorage.listScripts()
+        ? scriptStorage.listScripts(filter)
         : "[]";
 
     const scriptMetadata = JSON.parse(scriptsJson);
@@ -514,6 +595,9 @@ function scriptsQuery(context) {
         uri: meta.uri,
         chars: meta.size || 0,
         owners: owners,
+        description: meta.description || null,
+        tags: meta.tags || [],
+        system: meta.system === true,
       };
     });
 
@@ -550,6 +634,31 @@ function scriptQuery(context) {
       console.warn(`Failed to get owners for script ${args.uri}: ${e.message}`);
     }
 
+    let collaborators = [];
+    try {
+      if (
+        typeof scriptStorage !== "undefined" &&
+        typeof scriptStorage.getScriptCollaborators === "function"
+      ) {
+        collaborators = JSON.parse(
+          scriptStorage.getScriptCollaborators(args.uri),
+        );
+      }
+    } catch (e) {
+      console.warn(
+        `Failed to get collaborators for script ${args.uri}: ${e.message}`,
+      );
+    }
+
+    let labels = { description: null, tags: [] };
+    if (
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.getScriptLabels === "function"
+    ) {
+      const labelsJson = scriptStorage.getScriptLabels(args.uri);
+      if (labelsJson) labels = JSON.parse(labelsJson);
+    }
+
     // getScript returns null if script not found
     if (content !== null && content !== undefined) {
       return JSON.stringify({
@@ -558,6 +667,9 @@ function scriptQuery(context) {
         contentLength: content.length,
         logs: logs,
         owners: owners,
+        collaborators: collaborators,
+        description: labels.description,
+        tags: labels.tags,
       });
     } else {
       // Return null if script doesn't exist
@@ -567,6 +679,9 @@ function scriptQuery(context) {
         contentLength: 0,
         logs: logs,
         owners: owners,
+        collaborators: collaborators,
+        description: null,
+        tags: [],
       });
     }
   } catch (error) {
@@ -577,6 +692,9 @@ function scriptQuery(context) {
       contentLength: 0,
       logs: [],
       owners: [],
+      collaborators: [],
+      description: null,
+      tags: [],
     });
   }
 }
@@ -642,10 +760,17 @@ function upsertScriptMutation(context) {
         : null;
     const action = existingScript ? "updated" : "inserted";
 
+    // Only pass options the caller supplied so omitted ones keep their value
+    const options = {};
+    if (args.description !== undefined) options.description = args.description;
+    if (args.tags !== undefined) options.tags = args.tags;
+    if (args.sourceMap !== undefined) options.sourceMap = args.sourceMap;
+    if (args.force === true) options.force = true;
+
     const result =
       typeof scriptStorage !== "undefined" &&
       typeof scriptStorage.upsertScript === "function"
-        ? scriptStorage.upsertScript(args.uri, args.content)
+        ? scriptStorage.upsertScript(args.uri, args.content, options)
         : "Error: scriptStorage.upsertScript not available";
 
     // Check if the result indicates an error (Rust returns string for both success and errors)
@@ -675,6 +800,7 @@ function upsertScriptMutation(context) {
       uri: args.uri,
       chars: args.content.length,
       success: true,
+      warnings: scriptLintWarnings(args.uri, args.content),
     });
   } catch (error) {
     console.error(`Script upsert mutation failed: ${error.message}`);
@@ -687,6 +813,50 @@ function upsertScriptMutation(context) {
   }
 }
 
+function setScriptLabelsMutation(context) {
+  const req = getRequest(context);
+  const args = getArgs(context);
+  try {
+    const options = {};
+    if (args.description !== undefined) options.description = args.description;
+    if (args.tags !== undefined) options.tags = args.tags;
+
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.setScriptLabels === "function"
+        ? scriptStorage.setScriptLabels(args.uri, options)
+        : "Error: scriptStorage.setScriptLabels not available";
+
+    if (!result || result.startsWith("Error")) {
+      return JSON.stringify({
+        message: result || "Unknown error",
+        uri: args.uri,
+        description: null,
+        tags: [],
+        success: false,
+      });
+    }
+
+    const labels = JSON.parse(result);
+    return JSON.stringify({
+      message: `Labels updated for ${args.uri}`,
+      uri: args.uri,
+      description: labels.description,
+      tags: labels.tags,
+      success: true,
+    });
+  } catch (error) {
+    console.error(`Set script labels mutation failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: Failed to set script labels: ${error.message}`,
+      uri: args ? args.uri : null,
+      description: null,
+      tags: [],
+      success: false,
+    });
+  }
+}
+
 function deleteScriptMutation(context) {
   const req = getRequest(context);
   const args = getArgs(context);
@@ -694,9 +864,10 @@ function deleteScriptMutation(context) {
     const result =
       typeof scriptStorage !== "undefined" &&
       typeof scriptStorage.deleteScript === "function"
-        ? scriptStorage.deleteScript(args.uri)
+        ? scriptStorage.deleteScript(args.uri, args.force === true)
         : false;
-    // deleteScript now returns boolean: true if deleted, false if not found
+    // deleteScript returns boolean: true if deleted, false if not found or
+    // a system script without force
 
     if (result) {
       // Broadcast the script removal
@@ -712,7 +883,7 @@ function deleteScriptMutation(context) {
       });
     } else {
       return JSON.stringify({
-        message: `Script not found: ${args.uri}`,
+        message: `Script not found or protected: ${args.uri}`,
         uri: args.uri,
         success: false,
       });
@@ -813,194 +984,1065 @@ function removeScriptOwnerMutation(context) {
   }
 }
 
-// OpenAPI specification endpoint
-function openapiSpec(context) {
-  try {
-    const spec = routeRegistry.generateOpenApi();
-    return {
-      status: 200,
-      body: spec,
-      contentType: "application/json",
-    };
-  } catch (error) {
-    console.error("Error generating OpenAPI spec: " + error.message);
-    return {
-      status: 500,
-      body: JSON.stringify({
-        error: "Failed to generate OpenAPI specification",
-      }),
-      contentType: "application/json",
-    };
-  }
-}
-
-// MCP Tool Handlers for File Operations
-
-// Read file handler - fetches content of a script
-function readFileHandler(context) {
+function addScriptCollaboratorMutation(context) {
   const args = getArgs(context);
-  const uri = args.uri;
-
-  if (!uri) {
-    return JSON.stringify({
-      error: "Missing required parameter: uri",
-    });
-  }
-
   try {
-    const content =
+    const result =
       typeof scriptStorage !== "undefined" &&
-      typeof scriptStorage.getScript === "function"
-        ? scriptStorage.getScript(uri)
-        : null;
+      typeof scriptStorage.addScriptCollaborator === "function"
+        ? scriptStorage.addScriptCollaborator(args.uri, args.userId)
+        : "Error: scriptStorage.addScriptCollaborator not available";
 
-    if (content !== null && content !== undefined) {
-      return JSON.stringify({
-        uri: uri,
-        content: content,
-        size: content.length,
-        timestamp: new Date().toISOString(),
-      });
-    } else {
+    if (typeof result === "string" && result.startsWith("Error:")) {
+      console.error(`Add script collaborator mutation failed: ${result}`);
       return JSON.stringify({
-        error: `File not found: ${uri}`,
+        message: result,
+        uri: args.uri,
+        userId: args.userId,
+        success: false,
       });
     }
-  } catch (error) {
-    console.error(`MCP read_file error: ${error.message}`);
-    return JSON.stringify({
-      error: `Failed to read file: ${error.message}`,
-    });
-  }
-}
-
-// Write/edit file handler - creates or updates a script
-function writeFileHandler(context) {
-  const args = getArgs(context);
-  const uri = args.uri;
-  const content = args.content;
 
-  if (!uri) {
+    console.log(
+      `Collaborator added via GraphQL: ${args.userId} to script ${args.uri}`,
+    );
     return JSON.stringify({
-      error: "Missing required parameter: uri",
+      message: result,
+      uri: args.uri,
+      userId: args.userId,
+      success: true,
     });
-  }
-
-  if (content === undefined || content === null) {
+  } catch (error) {
+    console.error(`Add script collaborator mutation failed: ${error.message}`);
     return JSON.stringify({
-      error: "Missing required parameter: content",
+      message: `Error: Failed to add collaborator: ${error.message}`,
+      uri: args.uri,
+      userId: args.userId,
+      success: false,
     });
   }
+}
 
+function removeScriptCollaboratorMutation(context) {
+  const args = getArgs(context);
   try {
-    const existingScript =
-      typeof scriptStorage !== "undefined" &&
-      typeof scriptStorage.getScript === "function"
-        ? scriptStorage.getScript(uri)
-        : null;
-    const action = existingScript ? "updated" : "created";
-
     const result =
       typeof scriptStorage !== "undefined" &&
-      typeof scriptStorage.upsertScript === "function"
-        ? scriptStorage.upsertScript(uri, content)
-        : "Error: scriptStorage.upsertScript not available";
+      typeof scriptStorage.removeScriptCollaborator === "function"
+        ? scriptStorage.removeScriptCollaborator(args.uri, args.userId)
+        : "Error: scriptStorage.removeScriptCollaborator not available";
 
-    // Check if the result indicates an error (Rust returns string for both success and errors)
-    if (!result || result.startsWith("Error:")) {
-      console.error(`MCP write_file failed: ${result}`);
+    if (typeof result === "string" && result.startsWith("Error:")) {
+      console.error(`Remove script collaborator mutation failed: ${result}`);
       return JSON.stringify({
-        error: `Failed to write file: ${result || "Unknown error"}`,
+        message: result,
+        uri: args.uri,
+        userId: args.userId,
+        success: false,
       });
     }
 
-    broadcastScriptUpdate(uri, action === "created" ? "inserted" : "updated", {
-      contentLength: content.length,
-      via: "mcp",
-    });
-
-    console.log(`MCP ${action} file: ${uri} (${content.length} chars)`);
-
+    console.log(
+      `Collaborator removed via GraphQL: ${args.userId} from script ${args.uri}`,
+    );
     return JSON.stringify({
+      message: result,
+      uri: args.uri,
+      userId: args.userId,
       success: true,
-      action: action,
-      uri: uri,
-      size: content.length,
-      timestamp: new Date().toISOString(),
     });
   } catch (error) {
-    console.error(`MCP write_file error: ${error.message}`);
+    console.error(
+      `Remove script collaborator mutation failed: ${error.message}`,
+    );
     return JSON.stringify({
-      error: `Failed to write file: ${error.message}`,
+      message: `Error: Failed to remove collaborator: ${error.message}`,
+      uri: args.uri,
+      userId: args.userId,
+      success: false,
     });
   }
 }
 
-// Create file handler - creates a new script (fails if exists)
-function createFileHandler(context) {
-  const args = getArgs(context);
-  const uri = args.uri;
-  const content = args.content || "";
-
-  if (!uri) {
-    return JSON.stringify({
-      error: "Missing required parameter: uri",
-    });
-  }
-
+function trashedScriptsQuery(context) {
   try {
-    const existingScript =
-      typeof scriptStorage !== "undefined" &&
-      typeof scriptStorage.getScript === "function"
-        ? scriptStorage.getScript(uri)
-        : null;
-
-    if (existingScript !== null && existingScript !== undefined) {
-      return JSON.stringify({
-        error: `File already exists: ${uri}`,
-      });
-    }
-
     const result =
       typeof scriptStorage !== "undefined" &&
-      typeof scriptStorage.upsertScript === "function"
-        ? scriptStorage.upsertScript(uri, content)
-        : "Error: scriptStorage.upsertScript not available";
-
-    // Check if the result indicates an error (Rust returns string for both success and errors)
-    if (!result || result.startsWith("Error:")) {
-      console.error(`MCP create_file failed: ${result}`);
-      return JSON.stringify({
-        error: `Failed to create file: ${result || "Unknown error"}`,
-      });
+      typeof scriptStorage.listTrashedScripts === "function"
+        ? scriptStorage.listTrashedScripts()
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Trashed scripts query failed: ${result}`);
+      return JSON.stringify([]);
     }
-
-    broadcastScriptUpdate(uri, "inserted", {
-      contentLength: content.length,
-      via: "mcp",
-    });
-
-    console.log(`MCP created file: ${uri} (${content.length} chars)`);
-
-    return JSON.stringify({
-      success: true,
-      uri: uri,
-      size: content.length,
-      timestamp: new Date().toISOString(),
-    });
+    return result;
   } catch (error) {
-    console.error(`MCP create_file error: ${error.message}`);
-    return JSON.stringify({
-      error: `Failed to create file: ${error.message}`,
-    });
+    console.error(`Trashed scripts query failed: ${error.message}`);
+    return JSON.stringify([]);
   }
 }
 
-// List files handler - lists all scripts or filters by pattern
-function listFilesHandler(context) {
+function trashedAssetsQuery(context) {
   const args = getArgs(context);
-  const pattern = args.pattern || null;
-
+  try {
+    const result =
+      typeof assetStorage !== "undefined" &&
+      typeof assetStorage.listTrashedAssetsForUri === "function"
+        ? assetStorage.listTrashedAssetsForUri(args.scriptUri)
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Trashed assets query failed: ${result}`);
+      return JSON.stringify([]);
+    }
+    return result;
+  } catch (error) {
+    console.error(`Trashed assets query failed: ${error.message}`);
+    return JSON.stringify([]);
+  }
+}
+
+function logsQuery(context) {
+  const args = getArgs(context);
+  try {
+    const result =
+      typeof console.queryLogs === "function"
+        ? console.queryLogs({
+            scriptUri: args.scriptUri,
+            levels: args.levels,
+            since: args.since,
+            until: args.until,
+            search: args.search,
+            // Structured field filter, passed as a JSON object string
+            data: args.data ? JSON.parse(args.data) : undefined,
+            requestId: args.requestId,
+            cursor: args.after,
+            limit: args.limit,
+          })
+        : '{"entries":[]}';
+    if (result.startsWith("Error:")) {
+      console.error(`Logs query failed: ${result}`);
+      return JSON.stringify([]);
+    }
+    // Each entry carries its own cursor; pass the last one as `after`.
+    // Structured fields are returned as JSON strings.
+    const entries = JSON.parse(result).entries.map((entry) => ({
+      ...entry,
+      data: entry.data == null ? null : JSON.stringify(entry.data),
+      context: entry.context == null ? null : JSON.stringify(entry.context),
+    }));
+    return JSON.stringify(entries);
+  } catch (error) {
+    console.error(`Logs query failed: ${error.message}`);
+    return JSON.stringify([]);
+  }
+}
+
+function queryStatsQuery(context) {
+  const args = getArgs(context);
+  const empty = {
+    enabled: false,
+    slowThresholdMs: 0,
+    since: null,
+    untrackedCalls: 0,
+    statements: [],
+    recentSlow: [],
+  };
+  try {
+    const result =
+      typeof console.queryStats === "function"
+        ? console.queryStats({ slowOnly: args.slowOnly, limit: args.limit })
+        : JSON.stringify(empty);
+    if (result.startsWith("Error:")) {
+      console.error(`Query stats failed: ${result}`);
+      return JSON.stringify(empty);
+    }
+    return result;
+  } catch (error) {
+    console.error(`Query stats failed: ${error.message}`);
+    return JSON.stringify(empty);
+  }
+}
+
+function tenantUsageQuery(context) {
+  const args = getArgs(context);
+  const empty = { enabled: false, quotas: null, tenants: [] };
+  try {
+    const result =
+      typeof console.tenantUsage === "function"
+        ? console.tenantUsage(args.tenant)
+        : JSON.stringify(empty);
+    if (result.startsWith("Error:")) {
+      console.error(`Tenant usage failed: ${result}`);
+      return JSON.stringify(empty);
+    }
+    return result;
+  } catch (error) {
+    console.error(`Tenant usage failed: ${error.message}`);
+    return JSON.stringify(empty);
+  }
+}
+
+function llmUsageQuery(context) {
+  const args = getArgs(context);
+  const empty = { month: "", entries: [], scripts: [], users: [] };
+  const options = {};
+  if (args.month) options.month = args.month;
+  if (args.scriptUri) options.scriptUri = args.scriptUri;
+  if (args.userId) options.userId = args.userId;
+  try {
+    const result =
+      typeof console.llmUsage === "function"
+        ? console.llmUsage(options)
+        : JSON.stringify(empty);
+    if (result.startsWith("Error:")) {
+      console.error(`LLM usage failed: ${result}`);
+      return JSON.stringify(empty);
+    }
+    return result;
+  } catch (error) {
+    console.error(`LLM usage failed: ${error.message}`);
+    return JSON.stringify(empty);
+  }
+}
+
+function routeUsageQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.days !== undefined && args.days !== null) options.days = args.days;
+  try {
+    const result = scriptStorage.getRouteUsage(args.uri, options);
+    if (result.startsWith("Error:")) {
+      console.error(`Route usage failed: ${result}`);
+      return JSON.stringify(null);
+    }
+    return result;
+  } catch (error) {
+    console.error(`Route usage failed: ${error.message}`);
+    return JSON.stringify(null);
+  }
+}
+
+function scriptResourceUsageQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.days !== undefined && args.days !== null) options.days = args.days;
+  if (args.uri) options.uri = args.uri;
+  if (args.limit) options.limit = args.limit;
+  try {
+    const result = scriptStorage.getResourceUsage(options);
+    if (result.startsWith("Error:")) {
+      console.error(`Script resource usage failed: ${result}`);
+      return JSON.stringify(null);
+    }
+    return result;
+  } catch (error) {
+    console.error(`Script resource usage failed: ${error.message}`);
+    return JSON.stringify(null);
+  }
+}
+
+function webhookDeliveriesQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.status) options.status = args.status;
+  if (args.scriptUri) options.scriptUri = args.scriptUri;
+  if (args.limit) options.limit = args.limit;
+  try {
+    const result =
+      typeof console.webhookDeliveries === "function"
+        ? console.webhookDeliveries(options)
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Webhook deliveries failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Webhook deliveries failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function scriptTasksQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.status) options.status = args.status;
+  if (args.scriptUri) options.scriptUri = args.scriptUri;
+  if (args.limit) options.limit = args.limit;
+  try {
+    const result =
+      typeof console.scriptTasks === "function"
+        ? console.scriptTasks(options)
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Script tasks failed: ${result}`);
+      return "[]";
+    }
+    // Payloads are arbitrary JSON, exposed to GraphQL as strings
+    return JSON.stringify(
+      JSON.parse(result).map((task) =>
+        Object.assign({}, task, { payload: JSON.stringify(task.payload) }),
+      ),
+    );
+  } catch (error) {
+    console.error(`Script tasks failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function taskQueueStatsQuery(context) {
+  try {
+    const result =
+      typeof console.taskQueueStats === "function"
+        ? console.taskQueueStats()
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Task queue stats failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Task queue stats failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function emailLogQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.status) options.status = args.status;
+  if (args.scriptUri) options.scriptUri = args.scriptUri;
+  if (args.limit) options.limit = args.limit;
+  try {
+    const result =
+      typeof console.emailLog === "function" ? console.emailLog(options) : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Email log failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Email log failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function scriptStagingQuery(context) {
+  const args = getArgs(context);
+  try {
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.getScriptStaging === "function"
+        ? scriptStorage.getScriptStaging(args.uri)
+        : "null";
+    if (result.startsWith("Error")) {
+      console.error(`Script staging query failed: ${result}`);
+      return null;
+    }
+    return result;
+  } catch (error) {
+    console.error(`Script staging query failed: ${error.message}`);
+    return null;
+  }
+}
+
+// Shared by the staging mutations: call scriptStorage[operation](...args)
+// and answer with the staging slot it returns
+function scriptStagingMutation(operation, uri, ...rest) {
+  try {
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage[operation] === "function"
+        ? scriptStorage[operation](uri, ...rest)
+        : `Error: scriptStorage.${operation} not available`;
+
+    if (result.startsWith("Error")) {
+      console.error(`${operation} failed: ${result}`);
+      return JSON.stringify({ message: result, uri, success: false });
+    }
+
+    if (operation === "promoteStagedScript" || operation === "rollbackScript") {
+      broadcastScriptUpdate(uri, "updated", { via: "graphql" });
+    }
+    return JSON.stringify({
+      message: `${operation} succeeded for ${uri}`,
+      uri,
+      success: true,
+      staging: JSON.parse(result),
+    });
+  } catch (error) {
+    console.error(`${operation} failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: ${error.message}`,
+      uri,
+      success: false,
+    });
+  }
+}
+
+function stageScriptMutation(context) {
+  const args = getArgs(context);
+  return scriptStagingMutation("stageScript", args.uri, args.content);
+}
+
+function promoteStagedScriptMutation(context) {
+  const args = getArgs(context);
+  return scriptStagingMutation("promoteStagedScript", args.uri);
+}
+
+function rollbackScriptMutation(context) {
+  const args = getArgs(context);
+  return scriptStagingMutation("rollbackScript", args.uri);
+}
+
+function discardStagedScriptMutation(context) {
+  const args = getArgs(context);
+  return scriptStagingMutation("discardStagedScript", args.uri);
+}
+
+function restoreScriptMutation(context) {
+  const args = getArgs(context);
+  try {
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.restoreScript === "function"
+        ? scriptStorage.restoreScript(args.uri)
+        : "Error: scriptStorage.restoreScript not available";
+
+    if (typeof result === "string" && result.startsWith("Error")) {
+      console.error(`Restore script mutation failed: ${result}`);
+      return JSON.stringify({
+        message: result,
+        uri: args.uri,
+        success: false,
+      });
+    }
+
+    broadcastScriptUpdate(args.uri, "inserted", {
+      via: "graphql",
+    });
+
+    console.log(`Script restored via GraphQL: ${args.uri}`);
+    return JSON.stringify({
+      message: result,
+      uri: args.uri,
+      success: true,
+    });
+  } catch (error) {
+    console.error(`Restore script mutation failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: Failed to restore script: ${error.message}`,
+      uri: args.uri,
+      success: false,
+    });
+  }
+}
+
+function restoreAssetMutation(context) {
+  const args = getArgs(context);
+  try {
+    const result =
+      typeof assetStorage !== "undefined" &&
+      typeof assetStorage.restoreAssetForUri === "function"
+        ? assetStorage.restoreAssetForUri(args.scriptUri, args.uri)
+        : "Error: assetStorage.restoreAssetForUri not available";
+
+    const success = !(typeof result === "string" && result.startsWith("Error"));
+    if (!success) {
+      console.error(`Restore asset mutation failed: ${result}`);
+    }
+    return JSON.stringify({
+      message: result,
+      uri: args.uri,
+      success,
+    });
+  } catch (error) {
+    console.error(`Restore asset mutation failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: Failed to restore asset: ${error.message}`,
+      uri: args.uri,
+      success: false,
+    });
+  }
+}
+
+// Admin operations: run admin.<name>(...args) and report the outcome.
+// `fields` turns a successful result into extra response fields.
+function runAdminOperation(name, args, fields) {
+  try {
+    const result =
+      typeof admin !== "undefined" && typeof admin[name] === "function"
+        ? admin[name](...args)
+        : `Error: admin.${name} not available`;
+
+    if (typeof result === "string" && result.startsWith("Error")) {
+      console.error(`Admin operation ${name} failed: ${result}`);
+      return JSON.stringify({ message: result, success: false });
+    }
+    console.log(`Admin operation ${name} completed via GraphQL`);
+    return JSON.stringify({
+      message: `${name} completed`,
+      success: true,
+      ...(fields ? fields(result) : { message: result }),
+    });
+  } catch (error) {
+    console.error(`Admin operation ${name} failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: ${name} failed: ${error.message}`,
+      success: false,
+    });
+  }
+}
+
+function reloadScriptMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation("reloadScript", [args.uri]);
+}
+
+function rebuildGraphQLSchemaMutation() {
+  return runAdminOperation("rebuildGraphQLSchema", []);
+}
+
+function clearCachesMutation() {
+  return runAdminOperation("clearCaches", [], (result) => JSON.parse(result));
+}
+
+function drainStreamsMutation(context) {
+  const args = getArgs(context);
+  const params = args.path ? [args.path] : [];
+  return runAdminOperation("drainStreams", params, (result) => ({
+    drained: Number(result),
+  }));
+}
+
+function setMaintenanceModeMutation(context) {
+  const args = getArgs(context);
+  const options = { enabled: args.enabled === true };
+  if (args.message) options.message = args.message;
+  if (args.retryAfterSeconds != null) {
+    options.retryAfterSeconds = args.retryAfterSeconds;
+  }
+  return runAdminOperation("setMaintenanceMode", [options], (result) => ({
+    maintenance: JSON.parse(result),
+  }));
+}
+
+function threatActionsQuery(context) {
+  const args = getArgs(context);
+  const options = {};
+  if (args.status) options.status = args.status;
+  if (args.limit) options.limit = args.limit;
+  try {
+    const result =
+      typeof admin !== "undefined" && typeof admin.threatActions === "function"
+        ? admin.threatActions(options)
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Threat actions failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Threat actions failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function reviewThreatActionMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "reviewThreatAction",
+    [args.id, args.decision],
+    (result) => ({ action: JSON.parse(result) }),
+  );
+}
+
+function releaseScriptMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation("releaseScript", [args.uri], (result) => ({
+    actions: JSON.parse(result),
+  }));
+}
+
+function unlockAccountMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation("unlockAccount", [args.userId], (result) => ({
+    unlocked: result === "true",
+  }));
+}
+
+function userGroupsQuery() {
+  try {
+    const result =
+      typeof admin !== "undefined" && typeof admin.userGroups === "function"
+        ? admin.userGroups()
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`User groups failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`User groups failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+// Only the options given are passed on, so updates keep the others
+function userGroupOptions(args) {
+  const options = {};
+  if ("description" in args) options.description = args.description;
+  if (Array.isArray(args.roles)) options.roles = args.roles;
+  return options;
+}
+
+function createUserGroupMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "createUserGroup",
+    [args.name, userGroupOptions(args)],
+    (result) => ({ group: JSON.parse(result) }),
+  );
+}
+
+function updateUserGroupMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "updateUserGroup",
+    [args.name, userGroupOptions(args)],
+    (result) => ({ group: JSON.parse(result) }),
+  );
+}
+
+function deleteUserGroupMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation("deleteUserGroup", [args.name], (result) => ({
+    changed: result === "true",
+  }));
+}
+
+function addUserGroupMemberMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "addUserGroupMember",
+    [args.name, args.userId],
+    (result) => ({ changed: result === "true" }),
+  );
+}
+
+function removeUserGroupMemberMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "removeUserGroupMember",
+    [args.name, args.userId],
+    (result) => ({ changed: result === "true" }),
+  );
+}
+
+// Export documents and per-section row counts travel as JSON text
+function userDataRequestResponse(request) {
+  return { ...request, summary: JSON.stringify(request.summary) };
+}
+
+function userDataRequestsQuery(context) {
+  const args = getArgs(context);
+  try {
+    const result =
+      typeof admin !== "undefined" &&
+      typeof admin.userDataRequests === "function"
+        ? admin.userDataRequests(args.userId ?? "", args.limit ?? 100)
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`User data requests failed: ${result}`);
+      return "[]";
+    }
+    return JSON.stringify(JSON.parse(result).map(userDataRequestResponse));
+  } catch (error) {
+    console.error(`User data requests failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function exportUserDataMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation("exportUserData", [args.userId], (result) => ({
+    data: result,
+  }));
+}
+
+function deleteUserDataMutation(context) {
+  const args = getArgs(context);
+  return runAdminOperation(
+    "deleteUserData",
+    [args.userId, args.mode],
+    (result) => ({ request: userDataRequestResponse(JSON.parse(result)) }),
+  );
+}
+
+// Custom profile fields travel as JSON text; schema is the JSON Schema they
+// must match, for clients rendering profile forms
+function userProfileResponse(profileJson) {
+  const profile = JSON.parse(profileJson);
+  const schema = userStorage.getProfileSchema();
+  return {
+    userId: profile.userId,
+    fields: JSON.stringify(profile.fields),
+    schema: schema ?? null,
+    updatedAt: profile.updatedAt,
+  };
+}
+
+function userProfileQuery(context) {
+  const args = getArgs(context);
+  try {
+    if (
+      typeof userStorage === "undefined" ||
+      typeof userStorage.getUserProfile !== "function"
+    ) {
+      return JSON.stringify(null);
+    }
+    const profileJson = args.userId
+      ? userStorage.getUserProfile(args.userId)
+      : userStorage.getUserProfile();
+    return JSON.stringify(userProfileResponse(profileJson));
+  } catch (error) {
+    console.error(`User profile query failed: ${error.message}`);
+    return JSON.stringify(null);
+  }
+}
+
+function updateUserProfileMutation(context) {
+  const args = getArgs(context);
+  try {
+    if (
+      typeof userStorage === "undefined" ||
+      typeof userStorage.updateUserProfile !== "function"
+    ) {
+      throw new Error("userStorage.updateUserProfile not available");
+    }
+    const fields = JSON.parse(args.fields);
+    const profileJson = args.userId
+      ? userStorage.updateUserProfile(fields, args.userId)
+      : userStorage.updateUserProfile(fields);
+    return JSON.stringify({
+      message: "Profile updated",
+      success: true,
+      profile: userProfileResponse(profileJson),
+    });
+  } catch (error) {
+    console.error(`Update user profile mutation failed: ${error.message}`);
+    return JSON.stringify({
+      message: `Error: Failed to update profile: ${error.message}`,
+      success: false,
+    });
+  }
+}
+
+function pendingScriptCapabilitiesQuery() {
+  try {
+    const result =
+      typeof admin !== "undefined" &&
+      typeof admin.pendingScriptCapabilities === "function"
+        ? admin.pendingScriptCapabilities()
+        : "[]";
+    if (result.startsWith("Error:")) {
+      console.error(`Pending script capabilities failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Pending script capabilities failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+function approveScriptCapabilitiesMutation(context) {
+  const args = getArgs(context);
+  const params = Array.isArray(args.capabilities)
+    ? [args.uri, args.capabilities]
+    : [args.uri];
+  return runAdminOperation("approveScriptCapabilities", params, (result) => ({
+    manifest: JSON.parse(result),
+  }));
+}
+
+function maintenanceStatusQuery() {
+  const disabled = JSON.stringify({ enabled: false, retryAfterSeconds: 0 });
+  try {
+    const result =
+      typeof admin !== "undefined" ? admin.maintenanceStatus() : disabled;
+    if (result.startsWith("Error:")) {
+      console.error(`Maintenance status failed: ${result}`);
+      return disabled;
+    }
+    return result;
+  } catch (error) {
+    console.error(`Maintenance status failed: ${error.message}`);
+    return disabled;
+  }
+}
+
+function engineVersionQuery() {
+  try {
+    if (typeof admin === "undefined") {
+      return null;
+    }
+    const result = admin.versionInfo();
+    if (result.startsWith("Error:")) {
+      console.error(`Version info failed: ${result}`);
+      return null;
+    }
+    const info = JSON.parse(result);
+    return JSON.stringify({
+      instanceId: info.instance_id,
+      version: info.version,
+      gitCommit: info.git_commit,
+      gitCommitTimestamp: info.git_commit_timestamp,
+      buildTimestamp: info.build_timestamp,
+      profile: info.profile,
+      features: info.features,
+      scripts: {
+        total: info.scripts.total,
+        initialized: info.scripts.initialized,
+        failed: info.scripts.failed,
+        routes: info.scripts.routes,
+        scheduledJobs: info.scripts.scheduled_jobs,
+      },
+    });
+  } catch (error) {
+    console.error(`Version info failed: ${error.message}`);
+    return null;
+  }
+}
+
+function routeConflictsQuery() {
+  try {
+    if (typeof admin === "undefined") {
+      return "[]";
+    }
+    const result = admin.routeConflicts();
+    if (result.startsWith("Error:")) {
+      console.error(`Route conflicts failed: ${result}`);
+      return "[]";
+    }
+    return result;
+  } catch (error) {
+    console.error(`Route conflicts failed: ${error.message}`);
+    return "[]";
+  }
+}
+
+// Scheduled job: permanently remove trash entries past the retention period
+function purgeTrashJob(context) {
+  if (
+    typeof scriptStorage === "undefined" ||
+    typeof scriptStorage.purgeTrash !== "function"
+  ) {
+    return;
+  }
+  const result = scriptStorage.purgeTrash();
+  if (result.startsWith("Error")) {
+    console.error(`Trash purge failed: ${result}`);
+    return;
+  }
+  const summary = JSON.parse(result);
+  if (summary.scripts > 0 || summary.assets > 0) {
+    console.log(
+      `Purged ${summary.scripts} scripts, ${summary.assets} assets and ${summary.tables} tables from trash`,
+    );
+  }
+}
+
+// OpenAPI specification endpoint
+function openapiSpec(context) {
+  try {
+    const spec = routeRegistry.generateOpenApi();
+    return {
+      status: 200,
+      body: spec,
+      contentType: "application/json",
+    };
+  } catch (error) {
+    console.error("Error generating OpenAPI spec: " + error.message);
+    return {
+      status: 500,
+      body: JSON.stringify({
+        error: "Failed to generate OpenAPI specification",
+      }),
+      contentType: "application/json",
+    };
+  }
+}
+
+// MCP Tool Handlers for File Operations
+
+// Read file handler - fetches content of a script
+function readFileHandler(context) {
+  const args = getArgs(context);
+  const uri = args.uri;
+
+  if (!uri) {
+    return JSON.stringify({
+      error: "Missing required parameter: uri",
+    });
+  }
+
+  try {
+    const content =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.getScript === "function"
+        ? scriptStorage.getScript(uri)
+        : null;
+
+    if (content !== null && content !== undefined) {
+      return JSON.stringify({
+        uri: uri,
+        content: content,
+        size: content.length,
+        timestamp: new Date().toISOString(),
+      });
+    } else {
+      return JSON.stringify({
+        error: `File not found: ${uri}`,
+      });
+    }
+  } catch (error) {
+    console.error(`MCP read_file error: ${error.message}`);
+    return JSON.stringify({
+      error: `Failed to read file: ${error.message}`,
+    });
+  }
+}
+
+// Write/edit file handler - creates or updates a script
+function writeFileHandler(context) {
+  const args = getArgs(context);
+  const uri = args.uri;
+  const content = args.content;
+
+  if (!uri) {
+    return JSON.stringify({
+      error: "Missing required parameter: uri",
+    });
+  }
+
+  if (content === undefined || content === null) {
+    return JSON.stringify({
+      error: "Missing required parameter: content",
+    });
+  }
+
+  try {
+    const existingScript =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.getScript === "function"
+        ? scriptStorage.getScript(uri)
+        : null;
+    const action = existingScript ? "updated" : "created";
+
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.upsertScript === "function"
+        ? scriptStorage.upsertScript(uri, content)
+        : "Error: scriptStorage.upsertScript not available";
+
+    // Check if the result indicates an error (Rust returns string for both success and errors)
+    if (!result || result.startsWith("Error:")) {
+      console.error(`MCP write_file failed: ${result}`);
+      return JSON.stringify({
+        error: `Failed to write file: ${result || "Unknown error"}`,
+      });
+    }
+
+    broadcastScriptUpdate(uri, action === "created" ? "inserted" : "updated", {
+      contentLength: content.length,
+      via: "mcp",
+    });
+
+    console.log(`MCP ${action} file: ${uri} (${content.length} chars)`);
+
+    return JSON.stringify({
+      success: true,
+      action: action,
+      uri: uri,
+      size: content.length,
+      warnings: scriptLintWarnings(uri, content),
+      timestamp: new Date().toISOString(),
+    });
+  } catch (error) {
+    console.error(`MCP write_file error: ${error.message}`);
+    return JSON.stringify({
+      error: `Failed to write file: ${error.message}`,
+    });
+  }
+}
+
+// Create file handler - creates a new script (fails if exists)
+function createFileHandler(context) {
+  const args = getArgs(context);
+  const uri = args.uri;
+  const content = args.content || "";
+
+  if (!uri) {
+    return JSON.stringify({
+      error: "Missing required parameter: uri",
+    });
+  }
+
+  try {
+    const existingScript =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.getScript === "function"
+        ? scriptStorage.getScript(uri)
+        : null;
+
+    if (existingScript !== null && existingScript !== undefined) {
+      return JSON.stringify({
+        error: `File already exists: ${uri}`,
+      });
+    }
+
+    const result =
+      typeof scriptStorage !== "undefined" &&
+      typeof scriptStorage.upsertScript === "function"
+        ? scriptStorage.upsertScript(uri, content)
+        : "Error: scriptStorage.upsertScript not available";
+
+    // Check if the result indicates an error (Rust returns string for both success and errors)
+    if (!result || result.startsWith("Error:")) {
+      console.error(`MCP create_file failed: ${result}`);
+      return JSON.stringify({
+        error: `Failed to create file: ${result || "Unknown error"}`,
+      });
+    }
+
+    broadcastScriptUpdate(uri, "inserted", {
+      contentLength: content.length,
+      via: "mcp",
+    });
+
+    console.log(`MCP created file: ${uri} (${content.length} chars)`);
+
+    return JSON.stringify({
+      success: true,
+      uri: uri,
+      size: content.length,
+      warnings: scriptLintWarnings(uri, content),
+      timestamp: new Date().toISOString(),
+    });
+  } catch (error) {
+    console.error(`MCP create_file error: ${error.message}`);
+    return JSON.stringify({
+      error: `Failed to create file: ${error.message}`,
+    });
+  }
+}
+
+// List files handler - lists all scripts or filters by pattern
+function listFilesHandler(context) {
+  const args = getArgs(context);
+  const pattern = args.pattern || null;
+
   try {
     const scriptsJson =
       typeof scriptStorage !== "undefined" &&
@@ -1217,7 +2259,19 @@ function init(context) {
       "POST",
       {
         summary: "Create or update script",
-        description: "Upsert a script by URI",
+        description:
+          "Upsert a script by URI; an optional sourceMap field stores a source map for bundled or minified scripts",
+        tags: ["Scripts"],
+      },
+    );
+    routeRegistry.registerRoute(
+      "/dry_run_script",
+      "dry_run_script_handler",
+      "POST",
+      {
+        summary: "Preview script",
+        description:
+          "Run an unsaved script body against a described request (handler, method, path, headers, query, params, body); returns the response and captured logs without persisting writes",
         tags: ["Scripts"],
       },
     );
@@ -1267,13 +2321,13 @@ function init(context) {
     // Register GraphQL queries (authenticated - used by clients and tests)
     graphQLRegistry.registerQuery(
       "scripts",
-      "type ScriptInfo { uri: String!, chars: Int!, owners: [String!]! } type Query { scripts: [ScriptInfo!]! }",
+      "type ScriptInfo { uri: String!, chars: Int!, owners: [String!]!, description: String, tags: [String!]!, system: Boolean! } type Query { scripts(tag: String, search: String): [ScriptInfo!]! }",
       "scriptsQuery",
       "external",
     );
     graphQLRegistry.registerQuery(
       "script",
-      "type ScriptDetail { uri: String!, content: String!, contentLength: Int!, logs: [String!]!, owners: [String!]! } type Query { script(uri: String!): ScriptDetail }",
+      "type ScriptDetail { uri: String!, content: String!, contentLength: Int!, logs: [String!]!, owners: [String!]!, collaborators: [String!]!, description: String, tags: [String!]! } type Query { script(uri: String!): ScriptDetail }",
       "scriptQuery",
       "external",
     );
@@ -1290,16 +2344,149 @@ function init(context) {
       "external",
     );
 
+    graphQLRegistry.registerQuery(
+      "trashedScripts",
+      "type TrashedScript { uri: String!, name: String, owners: [String!]!, assetCount: Int!, deletedAt: String! } type Query { trashedScripts: [TrashedScript!]! }",
+      "trashedScriptsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "scriptStaging",
+      "type ScriptStaging { uri: String!, stagedContent: String, stagedAt: String, stagedBy: String, stagedInitError: String, stagedRoutes: [String!]!, previousContent: String, promotedAt: String } type Query { scriptStaging(uri: String!): ScriptStaging }",
+      "scriptStagingQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "trashedAssets",
+      "type TrashedAsset { uri: String!, scriptUri: String!, name: String, mimetype: String!, size: Int!, deletedAt: String! } type Query { trashedAssets(scriptUri: String!): [TrashedAsset!]! }",
+      "trashedAssetsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "logs",
+      "type LogRecord { id: String!, scriptUri: String!, level: String!, message: String!, data: String, context: String, timestamp: String!, cursor: String! } type Query { logs(scriptUri: String, levels: [String!], since: String, until: String, search: String, data: String, requestId: String, after: String, limit: Int): [LogRecord!]! }",
+      "logsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "queryStats",
+      "type QueryStatementHandler { handler: String!, calls: Int! } type QueryStatement { fingerprint: String!, calls: Int!, slowCalls: Int!, totalMs: Float!, avgMs: Float!, maxMs: Float!, rowsReturned: Int!, rowsAffected: Int!, handlers: [QueryStatementHandler!]! } type SlowQuery { fingerprint: String!, durationMs: Float!, rowsReturned: Int!, rowsAffected: Int!, handler: String, requestId: String, timestamp: String! } type QueryStatsReport { enabled: Boolean!, slowThresholdMs: Int!, since: String, untrackedCalls: Int!, statements: [QueryStatement!]!, recentSlow: [SlowQuery!]! } type Query { queryStats(slowOnly: Boolean, limit: Int): QueryStatsReport! }",
+      "queryStatsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "tenantUsage",
+      "type TenantQuotas { requestsPerMinute: Float!, executionMsPerMinute: Float!, maxStreamConnections: Float!, storageBytes: Float! } type TenantUsage { tenant: String!, requestsThisMinute: Int!, executionMsThisMinute: Float!, totalRequests: Float!, totalExecutionMs: Float!, throttledRequests: Float!, streamConnections: Int!, storageBytes: Float! } type TenantUsageReport { enabled: Boolean!, quotas: TenantQuotas, tenants: [TenantUsage!]! } type Query { tenantUsage(tenant: String): TenantUsageReport! }",
+      "tenantUsageQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "llmUsage",
+      "type LlmUsageEntry { scriptUri: String!, userId: String, provider: String!, model: String!, calls: Float!, inputTokens: Float!, outputTokens: Float!, cost: Float! } type LlmBudgetStatus { id: String!, calls: Float!, inputTokens: Float!, outputTokens: Float!, cost: Float!, softLimit: Float, hardLimit: Float, overSoftLimit: Boolean!, overHardLimit: Boolean! } type LlmUsageReport { month: String!, entries: [LlmUsageEntry!]!, scripts: [LlmBudgetStatus!]!, users: [LlmBudgetStatus!]! } type Query { llmUsage(month: String, scriptUri: String, userId: String): LlmUsageReport! }",
+      "llmUsageQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "userProfile",
+      "type UserProfile { userId: String!, fields: String!, schema: String, updatedAt: String! } type Query { userProfile(userId: String): UserProfile }",
+      "userProfileQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "userGroups",
+      "type UserGroup { name: String!, description: String, roles: [String!]!, members: [String!]!, createdAt: String!, updatedAt: String! } type Query { userGroups: [UserGroup!]! }",
+      "userGroupsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "routeUsage",
+      "type RouteStatusCount { status: Int!, count: Float! } type RouteUsage { route: String!, method: String!, requests: Float!, errors: Float!, statusCodes: [RouteStatusCount!]!, avgMs: Float, p50Ms: Float, p95Ms: Float, maxMs: Float, lastRequestAt: String } type RouteUsageReport { scriptUri: String!, since: String!, days: Int!, routes: [RouteUsage!]! } type Query { routeUsage(uri: String!, days: Int): RouteUsageReport }",
+      "routeUsageQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "scriptResourceUsage",
+      "type ScriptResourceUsage { scriptUri: String!, executions: Float!, cpuMs: Float!, avgCpuMs: Float, peakMemoryBytes: Float!, timeouts: Float!, outboundBytes: Float!, lastExecutionAt: String } type ScriptResourceReport { since: String!, days: Int!, scripts: [ScriptResourceUsage!]! } type Query { scriptResourceUsage(days: Int, uri: String, limit: Int): ScriptResourceReport }",
+      "scriptResourceUsageQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "webhookDeliveries",
+      "type WebhookDelivery { id: String!, scriptUri: String!, url: String!, status: String!, attempts: Int!, maxAttempts: Int!, nextAttemptAt: String!, lastStatusCode: Int, lastError: String, createdAt: String!, updatedAt: String!, deliveredAt: String } type Query { webhookDeliveries(status: String, scriptUri: String, limit: Int): [WebhookDelivery!]! }",
+      "webhookDeliveriesQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "scriptTasks",
+      "type ScriptTask { id: String!, scriptUri: String!, handlerName: String!, payload: String!, priority: Int!, status: String!, attempts: Int!, maxAttempts: Int!, runAt: String!, lastError: String, createdAt: String!, updatedAt: String!, completedAt: String, idempotencyKey: String } type Query { scriptTasks(status: String, scriptUri: String, limit: Int): [ScriptTask!]! }",
+      "scriptTasksQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "taskQueueStats",
+      "type TaskQueueStats { scriptUri: String!, pending: Int!, running: Int!, completed: Int!, failed: Int!, oldestDueAt: String } type Query { taskQueueStats: [TaskQueueStats!]! }",
+      "taskQueueStatsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "emailLog",
+      "type EmailLogEntry { id: String!, scriptUri: String!, recipients: [String!]!, subject: String!, status: String!, error: String, messageId: String, createdAt: String! } type Query { emailLog(status: String, scriptUri: String, limit: Int): [EmailLogEntry!]! }",
+      "emailLogQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "userDataRequests",
+      "type UserDataRequest { id: String!, subject: String!, action: String!, requestedBy: String!, summary: String!, createdAt: String! } type Query { userDataRequests(userId: String, limit: Int): [UserDataRequest!]! }",
+      "userDataRequestsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "threatActions",
+      "type ThreatAction { id: String!, policy: String!, action: String!, subject: String!, indicator: String!, description: String!, status: String!, durationSecs: Float!, expiresAt: String, createdAt: String!, decidedBy: String, decidedAt: String } type Query { threatActions(status: String, limit: Int): [ThreatAction!]! }",
+      "threatActionsQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "pendingScriptCapabilities",
+      "type ScriptManifest { uri: String!, requested: [String!]!, approved: [String!]!, approvedBy: String, approvedAt: String, pending: [String!]! } type Query { pendingScriptCapabilities: [ScriptManifest!]! }",
+      "pendingScriptCapabilitiesQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "maintenanceStatus",
+      "type MaintenanceStatus { enabled: Boolean!, message: String, retryAfterSeconds: Int!, since: String } type Query { maintenanceStatus: MaintenanceStatus! }",
+      "maintenanceStatusQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "engineVersion",
+      "type LoadedScripts { total: Int!, initialized: Int!, failed: Int!, routes: Int!, scheduledJobs: Int! } type EngineVersion { instanceId: String!, version: String!, gitCommit: String!, gitCommitTimestamp: String!, buildTimestamp: String!, profile: String!, features: [String!]!, scripts: LoadedScripts! } type Query { engineVersion: EngineVersion }",
+      "engineVersionQuery",
+      "external",
+    );
+    graphQLRegistry.registerQuery(
+      "routeConflicts",
+      "type RouteConflict { host: String, method: String!, path: String!, scripts: [String!]!, winner: String! } type Query { routeConflicts: [RouteConflict!]! }",
+      "routeConflictsQuery",
+      "external",
+    );
+
     // Register GraphQL mutations (authenticated - used by clients and tests)
     graphQLRegistry.registerMutation(
       "upsertScript",
-      "type UpsertScriptResponse { message: String!, uri: String!, chars: Int!, success: Boolean! } type Mutation { upsertScript(uri: String!, content: String!): UpsertScriptResponse! }",
+      "type ScriptLintWarning { rule: String!, message: String!, line: Int!, column: Int! } type UpsertScriptResponse { message: String!, uri: String!, chars: Int!, success: Boolean!, warnings: [ScriptLintWarning!] } type Mutation { upsertScript(uri: String!, content: String!, description: String, tags: [String!], sourceMap: String, force: Boolean): UpsertScriptResponse! }",
       "upsertScriptMutation",
       "external",
     );
+    graphQLRegistry.registerMutation(
+      "setScriptLabels",
+      "type ScriptLabelsResponse { message: String!, uri: String!, description: String, tags: [String!]!, success: Boolean! } type Mutation { setScriptLabels(uri: String!, description: String, tags: [String!]): ScriptLabelsResponse! }",
+      "setScriptLabelsMutation",
+      "external",
+    );
     graphQLRegistry.registerMutation(
       "deleteScript",
-      "type DeleteScriptResponse { message: String!, uri: String!, success: Boolean! } type Mutation { deleteScript(uri: String!): DeleteScriptResponse! }",
+      "type DeleteScriptResponse { message: String!, uri: String!, success: Boolean! } type Mutation { deleteScript(uri: String!, force: Boolean): DeleteScriptResponse! }",
       "deleteScriptMutation",
       "external",
     );
@@ -1315,6 +2502,158 @@ function init(context) {
       "removeScriptOwnerMutation",
       "external",
     );
+    graphQLRegistry.registerMutation(
+      "addScriptCollaborator",
+      "type OwnershipResponse { message: String!, uri: String!, userId: String!, success: Boolean! } type Mutation { addScriptCollaborator(uri: String!, userId: String!): OwnershipResponse! }",
+      "addScriptCollaboratorMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "removeScriptCollaborator",
+      "type OwnershipResponse { message: String!, uri: String!, userId: String!, success: Boolean! } type Mutation { removeScriptCollaborator(uri: String!, userId: String!): OwnershipResponse! }",
+      "removeScriptCollaboratorMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "restoreScript",
+      "type RestoreScriptResponse { message: String!, uri: String!, success: Boolean! } type Mutation { restoreScript(uri: String!): RestoreScriptResponse! }",
+      "restoreScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "stageScript",
+      "type ScriptStaging { uri: String!, stagedContent: String, stagedAt: String, stagedBy: String, stagedInitError: String, stagedRoutes: [String!]!, previousContent: String, promotedAt: String } type ScriptStagingResponse { message: String!, uri: String!, success: Boolean!, staging: ScriptStaging } type Mutation { stageScript(uri: String!, content: String!): ScriptStagingResponse! }",
+      "stageScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "promoteStagedScript",
+      "type ScriptStaging { uri: String!, stagedContent: String, stagedAt: String, stagedBy: String, stagedInitError: String, stagedRoutes: [String!]!, previousContent: String, promotedAt: String } type ScriptStagingResponse { message: String!, uri: String!, success: Boolean!, staging: ScriptStaging } type Mutation { promoteStagedScript(uri: String!): ScriptStagingResponse! }",
+      "promoteStagedScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "rollbackScript",
+      "type ScriptStaging { uri: String!, stagedContent: String, stagedAt: String, stagedBy: String, stagedInitError: String, stagedRoutes: [String!]!, previousContent: String, promotedAt: String } type ScriptStagingResponse { message: String!, uri: String!, success: Boolean!, staging: ScriptStaging } type Mutation { rollbackScript(uri: String!): ScriptStagingResponse! }",
+      "rollbackScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "discardStagedScript",
+      "type ScriptStaging { uri: String!, stagedContent: String, stagedAt: String, stagedBy: String, stagedInitError: String, stagedRoutes: [String!]!, previousContent: String, promotedAt: String } type ScriptStagingResponse { message: String!, uri: String!, success: Boolean!, staging: ScriptStaging } type Mutation { discardStagedScript(uri: String!): ScriptStagingResponse! }",
+      "discardStagedScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "restoreAsset",
+      "type RestoreAssetResponse { message: String!, uri: String!, success: Boolean! } type Mutation { restoreAsset(scriptUri: String!, uri: String!): RestoreAssetResponse! }",
+      "restoreAssetMutation",
+      "external",
+    );
+
+    // Admin operations, applied on every server instance
+    graphQLRegistry.registerMutation(
+      "reloadScript",
+      "type AdminOperationResponse { message: String!, success: Boolean! } type Mutation { reloadScript(uri: String!): AdminOperationResponse! }",
+      "reloadScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "rebuildGraphQLSchema",
+      "type AdminOperationResponse { message: String!, success: Boolean! } type Mutation { rebuildGraphQLSchema: AdminOperationResponse! }",
+      "rebuildGraphQLSchemaMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "clearCaches",
+      "type ClearCachesResponse { message: String!, success: Boolean!, responses: Int } type Mutation { clearCaches: ClearCachesResponse! }",
+      "clearCachesMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "drainStreams",
+      "type DrainStreamsResponse { message: String!, success: Boolean!, drained: Int } type Mutation { drainStreams(path: String): DrainStreamsResponse! }",
+      "drainStreamsMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "setMaintenanceMode",
+      "type MaintenanceStatus { enabled: Boolean!, message: String, retryAfterSeconds: Int!, since: String } type MaintenanceModeResponse { message: String!, success: Boolean!, maintenance: MaintenanceStatus } type Mutation { setMaintenanceMode(enabled: Boolean!, message: String, retryAfterSeconds: Int): MaintenanceModeResponse! }",
+      "setMaintenanceModeMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "reviewThreatAction",
+      "type ThreatAction { id: String!, policy: String!, action: String!, subject: String!, indicator: String!, description: String!, status: String!, durationSecs: Float!, expiresAt: String, createdAt: String!, decidedBy: String, decidedAt: String } type ReviewThreatActionResponse { message: String!, success: Boolean!, action: ThreatAction } type Mutation { reviewThreatAction(id: String!, decision: String!): ReviewThreatActionResponse! }",
+      "reviewThreatActionMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "releaseScript",
+      "type ThreatAction { id: String!, policy: String!, action: String!, subject: String!, indicator: String!, description: String!, status: String!, durationSecs: Float!, expiresAt: String, createdAt: String!, decidedBy: String, decidedAt: String } type ReleaseScriptResponse { message: String!, success: Boolean!, actions: [ThreatAction!] } type Mutation { releaseScript(uri: String!): ReleaseScriptResponse! }",
+      "releaseScriptMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "unlockAccount",
+      "type UnlockAccountResponse { message: String!, success: Boolean!, unlocked: Boolean } type Mutation { unlockAccount(userId: String!): UnlockAccountResponse! }",
+      "unlockAccountMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "updateUserProfile",
+      "type UserProfile { userId: String!, fields: String!, schema: String, updatedAt: String! } type UpdateUserProfileResponse { message: String!, success: Boolean!, profile: UserProfile } type Mutation { updateUserProfile(fields: String!, userId: String): UpdateUserProfileResponse! }",
+      "updateUserProfileMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "createUserGroup",
+      "type UserGroup { name: String!, description: String, roles: [String!]!, members: [String!]!, createdAt: String!, updatedAt: String! } type UserGroupResponse { message: String!, success: Boolean!, group: UserGroup } type Mutation { createUserGroup(name: String!, description: String, roles: [String!]): UserGroupResponse! }",
+      "createUserGroupMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "updateUserGroup",
+      "type UserGroup { name: String!, description: String, roles: [String!]!, members: [String!]!, createdAt: String!, updatedAt: String! } type UserGroupResponse { message: String!, success: Boolean!, group: UserGroup } type Mutation { updateUserGroup(name: String!, description: String, roles: [String!]): UserGroupResponse! }",
+      "updateUserGroupMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "deleteUserGroup",
+      "type UserGroupChangeResponse { message: String!, success: Boolean!, changed: Boolean } type Mutation { deleteUserGroup(name: String!): UserGroupChangeResponse! }",
+      "deleteUserGroupMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "addUserGroupMember",
+      "type UserGroupChangeResponse { message: String!, success: Boolean!, changed: Boolean } type Mutation { addUserGroupMember(name: String!, userId: String!): UserGroupChangeResponse! }",
+      "addUserGroupMemberMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "removeUserGroupMember",
+      "type UserGroupChangeResponse { message: String!, success: Boolean!, changed: Boolean } type Mutation { removeUserGroupMember(name: String!, userId: String!): UserGroupChangeResponse! }",
+      "removeUserGroupMemberMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "exportUserData",
+      "type ExportUserDataResponse { message: String!, success: Boolean!, data: String } type Mutation { exportUserData(userId: String!): ExportUserDataResponse! }",
+      "exportUserDataMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "deleteUserData",
+      "type UserDataRequest { id: String!, subject: String!, action: String!, requestedBy: String!, summary: String!, createdAt: String! } type DeleteUserDataResponse { message: String!, success: Boolean!, request: UserDataRequest } type Mutation { deleteUserData(userId: String!, mode: String!): DeleteUserDataResponse! }",
+      "deleteUserDataMutation",
+      "external",
+    );
+    graphQLRegistry.registerMutation(
+      "approveScriptCapabilities",
+      "type ScriptManifest { uri: String!, requested: [String!]!, approved: [String!]!, approvedBy: String, approvedAt: String, pending: [String!]! } type ApproveScriptCapabilitiesResponse { message: String!, success: Boolean!, manifest: ScriptManifest } type Mutation { approveScriptCapabilities(uri: String!, capabilities: [String!]): ApproveScriptCapabilitiesResponse! }",
+      "approveScriptCapabilitiesMutation",
+      "external",
+    );
 
     if (typeof schedulerService !== "undefined") {
       const oneMinuteFromNow = new Date(Date.now() + 60 * 1000).toISOString();
@@ -1329,6 +2668,11 @@ function init(context) {
         intervalMinutes: 2,
         name: "core-server-heartbeat",
       });
+      schedulerService.registerRecurring({
+        handler: "purgeTrashJob",
+        intervalMinutes: 60,
+        name: "core-trash-purge",
+      });
     } else {
       console.warn("schedulerService unavailable; skipping background jobs");
     }
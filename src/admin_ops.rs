@@ -0,0 +1,359 @@
+//! Operational actions for administrators (`admin.*` and the admin GraphQL
+//! mutations).
+//!
+//! Operators can reload a script, rebuild the GraphQL schema, clear the
+//! route index and the bytecode and response caches, drain stream
+//! connections and switch maintenance mode on and off without database
+//! access or restarts. Each action is applied on this instance at once and
+//! sent over the `admin_operation` database notification channel to every
+//! other instance.
+//!
+//! In maintenance mode script routes answer 503 with `Retry-After` to
+//! everyone but administrators. Registered assets, streams and the built-in
+//! endpoints keep working, so the admin UI and GraphQL stay reachable.
+//! Maintenance mode is held in memory: an instance started while it is on
+//! starts outside it until it is switched on again.
+
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+/// Database notification channel carrying admin operations
+pub const ADMIN_CHANNEL: &str = "admin_operation";
+
+/// Retry-After sent during maintenance when the operator gave none
+pub const DEFAULT_RETRY_AFTER_SECONDS: u64 = 300;
+
+/// Longest maintenance message
+pub const MAX_MAINTENANCE_MESSAGE_LENGTH: usize = 500;
+
+/// An action applied on every instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AdminOperation {
+    ReloadScript { uri: String },
+    RebuildSchema,
+    ClearCaches,
+    DrainStreams { path: Option<String> },
+    SetMaintenance(MaintenanceMode),
+}
+
+/// Operation sent over `ADMIN_CHANNEL`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminMessage {
+    pub operation: AdminOperation,
+    pub server_id: String,
+}
+
+/// Maintenance mode state
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+    /// Shown to clients in the 503 response
+    pub message: Option<String>,
+    pub retry_after_seconds: u64,
+    /// When maintenance mode was switched on (RFC 3339)
+    pub since: Option<String>,
+}
+
+/// What clearing the caches dropped on this instance
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearedCaches {
+    pub responses: usize,
+}
+
+static MAINTENANCE: OnceLock<Mutex<MaintenanceMode>> = OnceLock::new();
+
+fn lock_maintenance() -> MutexGuard<'static, MaintenanceMode> {
+    match MAINTENANCE.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Maintenance mode mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Current maintenance mode state
+pub fn maintenance() -> MaintenanceMode {
+    lock_maintenance().clone()
+}
+
+/// Options of `admin.setMaintenanceMode` and the GraphQL mutation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct MaintenanceOptions {
+    pub enabled: bool,
+    pub message: Option<String>,
+    pub retry_after_seconds: Option<u64>,
+}
+
+/// Build the maintenance state to switch to
+pub fn maintenance_mode(options: MaintenanceOptions) -> Result<MaintenanceMode, String> {
+    if !options.enabled {
+        return Ok(MaintenanceMode::default());
+    }
+    let message = options
+        .message
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty());
+    if message
+        .as_ref()
+        .is_some_and(|message| message.chars().count() > MAX_MAINTENANCE_MESSAGE_LENGTH)
+    {
+        return Err(format!(
+            "Maintenance message is longer than {} characters",
+            MAX_MAINTENANCE_MESSAGE_LENGTH
+        ));
+    }
+    Ok(MaintenanceMode {
+        enabled: true,
+        message,
+        retry_after_seconds: options
+            .retry_after_seconds
+            .unwrap_or(DEFAULT_RETRY_AFTER_SECONDS),
+        since: Some(chrono::Utc::now().to_rfc3339()),
+    })
+}
+
+/// Reload a script on every instance: its registrations are dropped, its
+/// `init()` runs again and the schema and route index are rebuilt
+pub fn reload_script(uri: &str) -> Result<(), String> {
+    if crate::repository::fetch_script(uri).is_none() {
+        return Err(format!("Script '{}' not found", uri));
+    }
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            let uri = uri.to_string();
+            handle.spawn(async move {
+                let _ = reload_local_script(&uri).await;
+            });
+        }
+        Err(_) => return Err("No runtime to reload the script on".to_string()),
+    }
+    broadcast(AdminOperation::ReloadScript {
+        uri: uri.to_string(),
+    });
+    Ok(())
+}
+
+/// Rebuild the GraphQL schema on every instance
+pub fn rebuild_schema() -> Result<(), String> {
+    crate::graphql::rebuild_schema()
+        .map_err(|e| format!("Failed to rebuild GraphQL schema: {:?}", e))?;
+    broadcast(AdminOperation::RebuildSchema);
+    Ok(())
+}
+
+/// Clear the route index and the bytecode and response caches on every
+/// instance
+pub fn clear_caches() -> ClearedCaches {
+    let cleared = clear_local_caches();
+    broadcast(AdminOperation::ClearCaches);
+    cleared
+}
+
+/// Close the stream connections of one path, or of every stream, on every
+/// instance. Clients reconnect to their streams on their own. Returns how
+/// many were closed on this instance.
+pub fn drain_streams(path: Option<&str>) -> Result<usize, String> {
+    if path.is_some_and(|path| !path.starts_with('/')) {
+        return Err("Path must start with '/'".to_string());
+    }
+    let drained = crate::stream_registry::get_global_registry().drain_connections(path)?;
+    broadcast(AdminOperation::DrainStreams {
+        path: path.map(str::to_string),
+    });
+    Ok(drained)
+}
+
+/// Switch maintenance mode on every instance
+pub fn set_maintenance(mode: MaintenanceMode) {
+    apply_maintenance(mode.clone());
+    broadcast(AdminOperation::SetMaintenance(mode));
+}
+
+fn apply_maintenance(mode: MaintenanceMode) {
+    if mode.enabled {
+        info!("Maintenance mode switched on");
+    } else {
+        info!("Maintenance mode switched off");
+    }
+    *lock_maintenance() = mode;
+}
+
+fn clear_local_caches() -> ClearedCaches {
+    crate::route_index::invalidate();
+    crate::bytecode::clear();
+    let cleared = ClearedCaches {
+        responses: crate::response_cache::purge_all(),
+    };
+    info!(
+        "Cleared route index, bytecode cache and {} cached responses",
+        cleared.responses
+    );
+    cleared
+}
+
+/// Reload a script on this instance only. Returns how long its `init()`
+/// took, or why it or rebuilding the schema failed.
+pub async fn reload_local_script(uri: &str) -> Result<u64, String> {
+    crate::graphql::clear_script_graphql_registrations(uri);
+    crate::mcp::clear_script_mcp_registrations(uri);
+    crate::bytecode::invalidate(uri);
+
+    let initializer = crate::script_init::ScriptInitializer::new(10_000);
+    let reloaded = match initializer.initialize_script(uri, false).await {
+        Ok(result) if result.success => {
+            info!("Script '{}' reloaded in {}ms", uri, result.duration_ms);
+            Ok(result.duration_ms)
+        }
+        Ok(result) => {
+            let e = result.error.unwrap_or_default();
+            warn!("Script '{}' reload failed: {}", uri, e);
+            Err(e)
+        }
+        Err(e) => {
+            error!("Failed to reload script '{}': {}", uri, e);
+            Err(e)
+        }
+    };
+
+    let rebuilt = crate::graphql::rebuild_schema().map_err(|e| {
+        error!(
+            "Failed to rebuild GraphQL schema after reloading '{}': {:?}",
+            uri, e
+        );
+        format!("Failed to rebuild GraphQL schema: {:?}", e)
+    });
+    crate::route_index::invalidate();
+    crate::response_cache::purge_script(uri);
+    reloaded.and_then(|duration_ms| rebuilt.map(|_| duration_ms))
+}
+
+/// Apply an operation received from another instance
+pub async fn apply_remote(message: &AdminMessage) {
+    info!(
+        "Applying admin operation {:?} from server {}",
+        message.operation, message.server_id
+    );
+    match &message.operation {
+        AdminOperation::ReloadScript { uri } => {
+            let _ = reload_local_script(uri).await;
+        }
+        AdminOperation::RebuildSchema => {
+            if let Err(e) = crate::graphql::rebuild_schema() {
+                error!("Failed to rebuild GraphQL schema: {:?}", e);
+            }
+        }
+        AdminOperation::ClearCaches => {
+            clear_local_caches();
+        }
+        AdminOperation::DrainStreams { path } => {
+            if let Err(e) =
+                crate::stream_registry::get_global_registry().drain_connections(path.as_deref())
+            {
+                error!("Failed to drain stream connections: {}", e);
+            }
+        }
+        AdminOperation::SetMaintenance(mode) => apply_maintenance(mode.clone()),
+    }
+}
+
+fn broadcast(operation: AdminOperation) {
+    let (Some(db), Some(server_id)) = (
+        crate::database::get_global_database(),
+        crate::notifications::get_server_id(),
+    ) else {
+        return;
+    };
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let message = match serde_json::to_string(&AdminMessage {
+        operation,
+        server_id,
+    }) {
+        Ok(message) => message,
+        Err(e) => {
+            error!("Failed to serialize admin operation: {}", e);
+            return;
+        }
+    };
+    handle.spawn(async move {
+        if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(ADMIN_CHANNEL)
+            .bind(&message)
+            .execute(db.pool())
+            .await
+        {
+            error!("Failed to broadcast admin operation: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_mode_options() {
+        let mode = maintenance_mode(MaintenanceOptions {
+            enabled: true,
+            message: Some("  Upgrading  ".to_string()),
+            retry_after_seconds: None,
+        })
+        .unwrap();
+        assert!(mode.enabled);
+        assert_eq!(mode.message.as_deref(), Some("Upgrading"));
+        assert_eq!(mode.retry_after_seconds, DEFAULT_RETRY_AFTER_SECONDS);
+        assert!(mode.since.is_some());
+
+        assert_eq!(
+            maintenance_mode(MaintenanceOptions {
+                enabled: false,
+                message: Some("ignored".to_string()),
+                retry_after_seconds: Some(5),
+            })
+            .unwrap(),
+            MaintenanceMode::default()
+        );
+        assert!(
+            maintenance_mode(MaintenanceOptions {
+                enabled: true,
+                message: Some("x".repeat(501)),
+                retry_after_seconds: None,
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_admin_message_serialization() {
+        let message = AdminMessage {
+            operation: AdminOperation::DrainStreams {
+                path: Some("/events".to_string()),
+            },
+            server_id: "server-1".to_string(),
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert!(json.contains(r#""op":"drain_streams""#));
+        let parsed: AdminMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.operation, message.operation);
+
+        let mode = maintenance_mode(MaintenanceOptions {
+            enabled: true,
+            message: None,
+            retry_after_seconds: Some(60),
+        })
+        .unwrap();
+        let json = serde_json::to_string(&AdminOperation::SetMaintenance(mode.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_str::<AdminOperation>(&json).unwrap(),
+            AdminOperation::SetMaintenance(mode)
+        );
+    }
+}
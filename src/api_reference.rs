@@ -0,0 +1,754 @@
+//! Sandbox API reference
+//!
+//! Builds a machine-readable description of the JavaScript globals exposed to
+//! scripts (routeRegistry, sharedStorage, fetch, …) from the bundled
+//! TypeScript definitions, so the `.d.ts` files stay the single source of
+//! truth. Served at `GET /engine/docs/api` as JSON, or as the merged
+//! TypeScript definitions with `?format=ts` for editor autocomplete.
+//!
+//! The parser understands the subset of TypeScript the definition files use:
+//! top-level `interface` blocks, `declare var` / `declare function`
+//! statements, and JSDoc comments with `@param`, `@returns` and `@example`.
+//! Privileged definitions (`aiwebengine-priv.d.ts`) are merged into the
+//! public ones the same way TypeScript declaration merging would.
+
+use std::sync::OnceLock;
+
+use axum::extract::Query;
+use axum::http::{HeaderValue, header};
+use axum::response::{IntoResponse, Json, Response};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Definitions available to every script
+const PUBLIC_DEFINITIONS: &str = include_str!("../assets/aiwebengine.d.ts");
+
+/// Definitions only available to privileged scripts
+const PRIVILEGED_DEFINITIONS: &str = include_str!("../assets/aiwebengine-priv.d.ts");
+
+static API_REFERENCE: OnceLock<ApiReference> = OnceLock::new();
+
+/// Machine-readable description of the sandbox API
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiReference {
+    /// Engine version the reference was generated for
+    pub version: String,
+    /// Globals available to scripts
+    pub globals: Vec<ApiGlobal>,
+    /// Interfaces referenced by the globals
+    pub types: Vec<ApiType>,
+}
+
+/// A global variable or function available in the sandbox
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiGlobal {
+    /// Global name, e.g. `routeRegistry`
+    pub name: String,
+    /// "object" or "function"
+    pub kind: String,
+    /// Interface name for objects typed by an interface, e.g. `RouteRegistry`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_name: Option<String>,
+    /// Full declaration for functions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Description from the JSDoc comment
+    pub description: String,
+    /// Documented parameters of functions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<ApiParam>,
+    /// Description of the return value of functions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+    /// Usage examples
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<String>,
+    /// Members of objects declared with an inline type
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<ApiMember>,
+    /// Only available to privileged scripts
+    pub privileged: bool,
+}
+
+/// An interface declared in the type definitions
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiType {
+    /// Interface name
+    pub name: String,
+    /// Description from the JSDoc comment
+    pub description: String,
+    /// Methods and properties
+    pub members: Vec<ApiMember>,
+    /// Only available to privileged scripts
+    pub privileged: bool,
+}
+
+/// A method or property of an interface
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiMember {
+    /// Member name
+    pub name: String,
+    /// "method" or "property"
+    pub kind: String,
+    /// Declaration as written in the type definitions
+    pub signature: String,
+    /// Description from the JSDoc comment
+    pub description: String,
+    /// Documented parameters
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<ApiParam>,
+    /// Description of the return value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub returns: Option<String>,
+    /// Usage examples
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<String>,
+    /// Only available to privileged scripts
+    pub privileged: bool,
+}
+
+/// A documented parameter
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiParam {
+    /// Parameter name
+    pub name: String,
+    /// Parameter description
+    pub description: String,
+}
+
+/// Parsed JSDoc comment
+#[derive(Debug, Default, Clone)]
+struct DocComment {
+    description: String,
+    params: Vec<ApiParam>,
+    returns: Option<String>,
+    examples: Vec<String>,
+}
+
+impl DocComment {
+    fn parse(raw: &str) -> Self {
+        let mut doc = DocComment::default();
+        let mut description = Vec::new();
+        // The tag currently being continued by untagged lines
+        let mut current: Option<&str> = None;
+        let mut example: Vec<&str> = Vec::new();
+
+        let flush_example = |example: &mut Vec<&str>, doc: &mut DocComment| {
+            let text = example.join("\n").trim().to_string();
+            if !text.is_empty() {
+                doc.examples.push(text);
+            }
+            example.clear();
+        };
+
+        for line in raw.lines() {
+            // Keep indentation inside examples, only drop the comment gutter
+            let line = line.trim_start();
+            let line = line.strip_prefix("*").unwrap_or(line);
+            let line = line.strip_prefix(' ').unwrap_or(line);
+            let trimmed = line.trim();
+
+            if let Some(rest) = trimmed.strip_prefix('@') {
+                if current == Some("example") {
+                    flush_example(&mut example, &mut doc);
+                }
+                let (tag, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let value = value.trim();
+                match tag {
+                    "param" => {
+                        let (name, description) = value.split_once(" - ").unwrap_or((value, ""));
+                        doc.params.push(ApiParam {
+                            name: name.trim().to_string(),
+                            description: description.trim().to_string(),
+                        });
+                        current = Some("param");
+                    }
+                    "returns" | "return" => {
+                        doc.returns = Some(value.to_string());
+                        current = Some("returns");
+                    }
+                    "example" => {
+                        if !value.is_empty() {
+                            example.push(value);
+                        }
+                        current = Some("example");
+                    }
+                    _ => current = Some("other"),
+                }
+                continue;
+            }
+
+            match current {
+                None => description.push(trimmed),
+                Some("example") => example.push(line.trim_end()),
+                Some("param") if !trimmed.is_empty() => {
+                    if let Some(param) = doc.params.last_mut() {
+                        param.description.push(' ');
+                        param.description.push_str(trimmed);
+                    }
+                }
+                Some("returns") if !trimmed.is_empty() => {
+                    if let Some(returns) = doc.returns.as_mut() {
+                        returns.push(' ');
+                        returns.push_str(trimmed);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if current == Some("example") {
+            flush_example(&mut example, &mut doc);
+        }
+
+        doc.description = description
+            .join(" ")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        doc
+    }
+}
+
+/// What the statement being accumulated at top level declares
+#[derive(Debug)]
+enum Container {
+    Interface(String),
+    InlineGlobal(String),
+    /// `declare module` / `declare namespace` blocks are not part of the API
+    Skipped,
+}
+
+#[derive(Debug, Default)]
+struct ParsedDefinitions {
+    globals: Vec<ApiGlobal>,
+    types: Vec<ApiType>,
+}
+
+/// Remove a trailing `//` comment, ignoring `//` inside string literals
+fn strip_line_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut prev = '\0';
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q && prev != '\\' => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' || c == '`' => quote = Some(c),
+            None if c == '/' && prev == '/' => return &line[..idx - 1],
+            None => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+/// Net change in `{}`/`()` nesting for a line of code
+fn depth_delta(code: &str) -> (i32, i32) {
+    let mut braces = 0;
+    let mut parens = 0;
+    let mut quote: Option<char> = None;
+    for c in code.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' | '`' => quote = Some(c),
+                '{' => braces += 1,
+                '}' => braces -= 1,
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ => {}
+            },
+        }
+    }
+    (braces, parens)
+}
+
+/// Collapse a multi-line declaration into one line
+fn normalize_signature(statement: &str) -> String {
+    let joined = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    let joined = joined.replace("( ", "(").replace(" )", ")");
+    let joined = joined.replace(", )", ")").replace(",)", ")");
+    joined.trim_end_matches(';').trim().to_string()
+}
+
+/// Name of a member declaration such as `registerRoute(path: string): string`
+fn member_name(signature: &str) -> Option<(String, bool)> {
+    let name: String = signature
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        .collect();
+    if name.is_empty() {
+        return None;
+    }
+    let rest = signature[name.len()..].trim_start();
+    let rest = rest.strip_prefix('?').unwrap_or(rest);
+    let is_method = rest.starts_with('(') || rest.starts_with('<');
+    Some((name, is_method))
+}
+
+fn build_member(statement: &str, doc: DocComment, privileged: bool) -> Option<ApiMember> {
+    let signature = normalize_signature(statement);
+    let (name, is_method) = member_name(&signature)?;
+    Some(ApiMember {
+        name,
+        kind: if is_method { "method" } else { "property" }.to_string(),
+        signature,
+        description: doc.description,
+        params: doc.params,
+        returns: doc.returns,
+        examples: doc.examples,
+        privileged,
+    })
+}
+
+/// Parse one definitions file
+fn parse_definitions(source: &str, privileged: bool) -> ParsedDefinitions {
+    let mut parsed = ParsedDefinitions::default();
+
+    let mut in_comment = false;
+    let mut comment = String::new();
+    let mut pending_doc: Option<DocComment> = None;
+
+    let mut container: Option<Container> = None;
+    let mut container_doc = DocComment::default();
+    let mut members: Vec<ApiMember> = Vec::new();
+    let mut depth: i32 = 0;
+
+    // Statement spanning several lines (a member inside a container, or a
+    // top-level `declare function`)
+    let mut statement = String::new();
+    let mut statement_doc: Option<DocComment> = None;
+    let mut statement_depth: (i32, i32) = (0, 0);
+
+    for raw_line in source.lines() {
+        let line = raw_line.trim();
+
+        if in_comment {
+            if let Some(end) = raw_line.find("*/") {
+                comment.push_str(&raw_line[..end]);
+                in_comment = false;
+                pending_doc = Some(DocComment::parse(&comment));
+            } else {
+                comment.push_str(raw_line);
+                comment.push('\n');
+            }
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("/**") {
+            comment.clear();
+            if let Some(end) = rest.find("*/") {
+                pending_doc = Some(DocComment::parse(&rest[..end]));
+            } else {
+                comment.push_str(rest);
+                comment.push('\n');
+                in_comment = true;
+            }
+            continue;
+        }
+
+        let code = strip_line_comment(line).trim();
+        if code.is_empty() {
+            continue;
+        }
+        let (brace_delta, paren_delta) = depth_delta(code);
+
+        // Continue a multi-line statement until its brackets balance and it
+        // ends with `;`
+        if !statement.is_empty() || (depth == 1 && container.is_some()) {
+            statement.push(' ');
+            statement.push_str(code);
+            if statement_doc.is_none() {
+                statement_doc = pending_doc.take();
+            }
+            statement_depth.0 += brace_delta;
+            statement_depth.1 += paren_delta;
+
+            let closes_container = depth == 1 && statement_depth.0 < 0;
+            if closes_container {
+                // The container's closing `}` rather than a member
+                statement.clear();
+                statement_doc = None;
+                statement_depth = (0, 0);
+            } else if statement_depth == (0, 0) && (code.ends_with(';') || code.ends_with(',')) {
+                let doc = statement_doc.take().unwrap_or_default();
+                let text = std::mem::take(&mut statement);
+                statement_depth = (0, 0);
+                if depth == 0 {
+                    push_declared_function(&mut parsed, &text, doc, privileged);
+                } else if !matches!(container, Some(Container::Skipped))
+                    && let Some(member) = build_member(&text, doc, privileged)
+                {
+                    members.push(member);
+                }
+                continue;
+            } else {
+                continue;
+            }
+        }
+
+        if depth == 0 {
+            let doc = pending_doc.take().unwrap_or_default();
+            if let Some(rest) = code.strip_prefix("interface ") {
+                let name: String = rest
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect();
+                container = Some(Container::Interface(name));
+                container_doc = doc;
+            } else if let Some(rest) = code.strip_prefix("declare var ") {
+                let (name, type_name) = rest.split_once(':').unwrap_or((rest, ""));
+                let name = name.trim().to_string();
+                let type_name = type_name.trim().trim_end_matches(';').trim();
+                if type_name == "{" || type_name.starts_with('{') {
+                    container = Some(Container::InlineGlobal(name));
+                    container_doc = doc;
+                } else {
+                    parsed.globals.push(ApiGlobal {
+                        name,
+                        kind: "object".to_string(),
+                        type_name: Some(type_name.to_string()),
+                        signature: None,
+                        description: doc.description,
+                        params: Vec::new(),
+                        returns: None,
+                        examples: doc.examples,
+                        members: Vec::new(),
+                        privileged,
+                    });
+                }
+            } else if code.starts_with("declare function ") {
+                if paren_delta == 0 && code.ends_with(';') {
+                    push_declared_function(&mut parsed, code, doc, privileged);
+                } else {
+                    statement = code.to_string();
+                    statement_doc = Some(doc);
+                    statement_depth = (brace_delta, paren_delta);
+                }
+                continue;
+            } else if brace_delta > 0 {
+                container = Some(Container::Skipped);
+            }
+        }
+
+        depth += brace_delta;
+        if depth == 0
+            && let Some(finished) = container.take()
+        {
+            let doc = std::mem::take(&mut container_doc);
+            let finished_members = std::mem::take(&mut members);
+            match finished {
+                Container::Interface(name) => parsed.types.push(ApiType {
+                    name,
+                    description: doc.description,
+                    members: finished_members,
+                    privileged,
+                }),
+                Container::InlineGlobal(name) => parsed.globals.push(ApiGlobal {
+                    name,
+                    kind: "object".to_string(),
+                    type_name: None,
+                    signature: None,
+                    description: doc.description,
+                    params: Vec::new(),
+                    returns: None,
+                    examples: doc.examples,
+                    members: finished_members,
+                    privileged,
+                }),
+                Container::Skipped => {}
+            }
+        }
+    }
+
+    parsed
+}
+
+fn push_declared_function(
+    parsed: &mut ParsedDefinitions,
+    statement: &str,
+    doc: DocComment,
+    privileged: bool,
+) {
+    let signature = normalize_signature(statement);
+    let declaration = signature.trim_start_matches("declare function ").trim();
+    let Some((name, _)) = member_name(declaration) else {
+        return;
+    };
+    parsed.globals.push(ApiGlobal {
+        name,
+        kind: "function".to_string(),
+        type_name: None,
+        signature: Some(declaration.to_string()),
+        description: doc.description,
+        params: doc.params,
+        returns: doc.returns,
+        examples: doc.examples,
+        members: Vec::new(),
+        privileged,
+    });
+}
+
+/// Merge privileged definitions into the public ones. Interfaces declared in
+/// both files gain the privileged members; globals declared in both keep the
+/// public declaration.
+fn merge(public: ParsedDefinitions, privileged: ParsedDefinitions) -> ApiReference {
+    let mut reference = ApiReference {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        globals: public.globals,
+        types: public.types,
+    };
+
+    for ty in privileged.types {
+        match reference.types.iter_mut().find(|t| t.name == ty.name) {
+            Some(existing) => {
+                for member in ty.members {
+                    // Redeclared members replace the public declaration
+                    existing.members.retain(|m| m.signature != member.signature);
+                    existing.members.push(member);
+                }
+            }
+            None => reference.types.push(ty),
+        }
+    }
+    for global in privileged.globals {
+        if !reference.globals.iter().any(|g| g.name == global.name) {
+            reference.globals.push(global);
+        }
+    }
+
+    reference
+}
+
+/// Build a reference from public and privileged definition sources
+pub fn build_reference(public: &str, privileged: &str) -> ApiReference {
+    merge(
+        parse_definitions(public, false),
+        parse_definitions(privileged, true),
+    )
+}
+
+/// The sandbox API reference, generated on first use from the bundled definitions
+pub fn api_reference() -> &'static ApiReference {
+    API_REFERENCE.get_or_init(|| build_reference(PUBLIC_DEFINITIONS, PRIVILEGED_DEFINITIONS))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiReferenceParams {
+    /// "json" (default) or "ts"
+    pub format: Option<String>,
+}
+
+/// Sandbox JavaScript API reference
+#[utoipa::path(
+    get,
+    path = "/engine/docs/api",
+    tags = ["Documentation"],
+    params(
+        ("format" = Option<String>, Query, description = "Response format: \"json\" (default) or \"ts\" for the merged TypeScript definitions")
+    ),
+    responses(
+        (status = 200, description = "API reference for the script sandbox", body = ApiReference),
+        (status = 400, description = "Unknown format", body = crate::openapi_schemas::ErrorResponse),
+    )
+)]
+pub async fn api_reference_handler(Query(params): Query<ApiReferenceParams>) -> Response {
+    let mut response = match params.format.as_deref().unwrap_or("json") {
+        "json" => Json(api_reference()).into_response(),
+        "ts" => {
+            let mut response =
+                format!("{}\n{}", PUBLIC_DEFINITIONS, PRIVILEGED_DEFINITIONS).into_response();
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            );
+            response
+        }
+        other => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Bad Request",
+                    "message": format!("Unknown format '{}', expected 'json' or 'ts'", other),
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    response.headers_mut().insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        HeaderValue::from_static("*"),
+    );
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=3600"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+/**
+ * Route registry
+ */
+interface RouteRegistry {
+  /**
+   * Register an HTTP route handler
+   * @param path - URL path pattern
+   * @param method - HTTP method. Registering
+   *   GET also serves HEAD.
+   * @returns Registration result message
+   * @example
+   * routeRegistry.registerRoute("/api", "handler", "GET");
+   */
+  registerRoute(
+    path: string,
+    method: string,
+    metadata?: {
+      summary?: string; // short text
+    },
+  ): string;
+
+  /** Number of routes */
+  count: number;
+}
+
+declare module "react/jsx-runtime" {
+  export function jsx(tag: string): string;
+}
+
+declare var routeRegistry: RouteRegistry;
+
+/**
+ * Builder
+ */
+declare var ResponseBuilder: {
+  /** Create a JSON response */
+  json(data: any, status?: number): HttpResponse;
+};
+
+/**
+ * Fetch a URL
+ * @param url - Target URL
+ */
+declare function fetch(
+  url: string,
+  options?: FetchOptions,
+): string;
+"#;
+
+    #[test]
+    fn test_parse_interface_members() {
+        let parsed = parse_definitions(SAMPLE, false);
+        assert_eq!(parsed.types.len(), 1);
+
+        let registry = &parsed.types[0];
+        assert_eq!(registry.name, "RouteRegistry");
+        assert_eq!(registry.description, "Route registry");
+        assert_eq!(registry.members.len(), 2);
+
+        let register = &registry.members[0];
+        assert_eq!(register.name, "registerRoute");
+        assert_eq!(register.kind, "method");
+        assert!(
+            register
+                .signature
+                .starts_with("registerRoute(path: string,")
+        );
+        assert!(register.signature.ends_with("): string"));
+        assert_eq!(register.params.len(), 2);
+        assert_eq!(
+            register.params[1].description,
+            "HTTP method. Registering GET also serves HEAD."
+        );
+        assert_eq!(
+            register.returns.as_deref(),
+            Some("Registration result message")
+        );
+        assert_eq!(register.examples.len(), 1);
+
+        let count = &registry.members[1];
+        assert_eq!(count.name, "count");
+        assert_eq!(count.kind, "property");
+        assert_eq!(count.description, "Number of routes");
+    }
+
+    #[test]
+    fn test_parse_globals() {
+        let parsed = parse_definitions(SAMPLE, false);
+        let names: Vec<_> = parsed.globals.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["routeRegistry", "ResponseBuilder", "fetch"]);
+
+        assert_eq!(
+            parsed.globals[0].type_name.as_deref(),
+            Some("RouteRegistry")
+        );
+        assert_eq!(parsed.globals[1].members.len(), 1);
+        assert_eq!(parsed.globals[1].members[0].name, "json");
+        assert_eq!(parsed.globals[2].kind, "function");
+        assert_eq!(parsed.globals[2].params.len(), 1);
+        assert_eq!(
+            parsed.globals[2].signature.as_deref(),
+            Some("fetch(url: string, options?: FetchOptions): string")
+        );
+    }
+
+    #[test]
+    fn test_privileged_definitions_merge() {
+        let privileged = r#"
+interface RouteRegistry {
+  /** List all routes */
+  listRoutes(): string;
+}
+declare var scriptStorage: ScriptStorage;
+"#;
+        let reference = build_reference(SAMPLE, privileged);
+        let registry = reference
+            .types
+            .iter()
+            .find(|t| t.name == "RouteRegistry")
+            .expect("RouteRegistry type");
+        assert!(!registry.privileged);
+        let list = registry
+            .members
+            .iter()
+            .find(|m| m.name == "listRoutes")
+            .expect("merged member");
+        assert!(list.privileged);
+
+        let storage = reference
+            .globals
+            .iter()
+            .find(|g| g.name == "scriptStorage")
+            .expect("privileged global");
+        assert!(storage.privileged);
+    }
+
+    #[test]
+    fn test_bundled_reference_is_complete() {
+        let reference = api_reference();
+        for expected in ["routeRegistry", "sharedStorage", "fetch", "scriptStorage"] {
+            assert!(
+                reference.globals.iter().any(|g| g.name == expected),
+                "missing global {}",
+                expected
+            );
+        }
+
+        // Every interface-typed global must resolve to a parsed interface
+        for global in &reference.globals {
+            if let Some(type_name) = &global.type_name {
+                let ty = reference.types.iter().find(|t| &t.name == type_name);
+                assert!(
+                    ty.is_some_and(|t| !t.members.is_empty()),
+                    "global {} has unresolved type {}",
+                    global.name,
+                    type_name
+                );
+            }
+        }
+    }
+}
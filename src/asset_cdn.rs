@@ -0,0 +1,310 @@
+//! CDN caching of asset routes.
+//!
+//! Asset routes and prefixes can carry a cache policy, which sets the
+//! `Cache-Control` of their responses and the surrogate keys CDNs index the
+//! cached responses by:
+//!
+//! ```javascript
+//! routeRegistry.registerAssetPrefix("/static", "static/", {
+//!     cachePolicy: { maxAge: 60, sMaxAge: 86400, surrogateKeys: ["static"] },
+//! });
+//! ```
+//!
+//! Every asset response also carries a key of its own asset. When an asset
+//! is stored or deleted, a purge request for the asset's key, the keys of
+//! the routes serving it and their paths is sent to the CDN API configured
+//! in `[cdn]`, so CDNs can cache for long without serving stale assets.
+//! Paths under a prefix fallback are only reached through the keys.
+
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::config::CdnConfig;
+
+/// Most surrogate keys one route may declare
+pub const MAX_SURROGATE_KEYS: usize = 16;
+
+/// Longest surrogate key
+pub const MAX_SURROGATE_KEY_LENGTH: usize = 128;
+
+/// Placeholder replaced with the surrogate keys in purge requests
+const KEYS_PLACEHOLDER: &str = "{keys}";
+
+/// Placeholder replaced with the URL paths in purge request bodies
+const PATHS_PLACEHOLDER: &str = "{paths}";
+
+static SETTINGS: OnceLock<RwLock<CdnConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<CdnConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the CDN configuration. Called once at server startup.
+pub fn configure(config: &CdnConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> CdnConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Cache policy of an asset route (`cachePolicy` option)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// How long browsers keep a response, in seconds
+    pub max_age: Option<u64>,
+    /// How long CDNs and other shared caches keep a response, in seconds
+    pub s_maxage: Option<u64>,
+    /// Keys sent in the surrogate key header, which purges can target
+    pub surrogate_keys: Vec<String>,
+}
+
+impl CachePolicy {
+    /// Check the options given to `registerAssetRoute` or `registerAssetPrefix`
+    pub fn new(
+        max_age: Option<u64>,
+        s_maxage: Option<u64>,
+        surrogate_keys: Vec<String>,
+    ) -> Result<Self, String> {
+        if max_age.is_none() && s_maxage.is_none() && surrogate_keys.is_empty() {
+            return Err("cachePolicy must set maxAge, sMaxAge or surrogateKeys".to_string());
+        }
+        if surrogate_keys.len() > MAX_SURROGATE_KEYS {
+            return Err(format!(
+                "cachePolicy.surrogateKeys must not list more than {} keys",
+                MAX_SURROGATE_KEYS
+            ));
+        }
+        for key in &surrogate_keys {
+            if key.is_empty()
+                || key.len() > MAX_SURROGATE_KEY_LENGTH
+                || !key.bytes().all(|b| b.is_ascii_graphic() && b != b',')
+            {
+                return Err(format!(
+                    "cachePolicy.surrogateKeys entry '{}' must be 1-{} printable ASCII characters without spaces or commas",
+                    key, MAX_SURROGATE_KEY_LENGTH
+                ));
+            }
+        }
+        Ok(Self {
+            max_age,
+            s_maxage,
+            surrogate_keys,
+        })
+    }
+
+    /// The `Cache-Control` of responses, if the policy sets lifetimes.
+    /// Browsers revalidate every time when only `s_maxage` is set.
+    pub fn cache_control(&self) -> Option<String> {
+        match (self.max_age, self.s_maxage) {
+            (None, None) => None,
+            (max_age, None) => Some(format!("public, max-age={}", max_age.unwrap_or_default())),
+            (max_age, Some(s_maxage)) => Some(format!(
+                "public, max-age={}, s-maxage={}",
+                max_age.unwrap_or_default(),
+                s_maxage
+            )),
+        }
+    }
+}
+
+/// Surrogate key of one asset, the same on every route serving it
+pub fn asset_key(script_uri: &str, asset_name: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(script_uri.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(asset_name.as_bytes());
+    format!("asset-{}", &hex::encode(hasher.finalize())[..16])
+}
+
+/// The surrogate key header of a response of `asset_name`: sent when purging
+/// is configured or the route has surrogate keys of its own
+pub fn surrogate_key_header(
+    policy: Option<&CachePolicy>,
+    script_uri: &str,
+    asset_name: &str,
+) -> Option<(String, String)> {
+    let config = current_settings();
+    let route_keys = policy.map(|policy| policy.surrogate_keys.as_slice());
+    if !config.enabled && route_keys.is_none_or(<[String]>::is_empty) {
+        return None;
+    }
+    let mut keys = vec![asset_key(script_uri, asset_name)];
+    keys.extend(route_keys.unwrap_or_default().iter().cloned());
+    Some((
+        config.surrogate_key_header.to_ascii_lowercase(),
+        keys.join(&config.surrogate_key_separator),
+    ))
+}
+
+/// Replace the placeholders of a purge request template
+fn render(template: &str, keys: &[String], paths: &[String], separator: &str) -> String {
+    template
+        .replace(KEYS_PLACEHOLDER, &keys.join(separator))
+        .replace(PATHS_PLACEHOLDER, &paths.join(separator))
+}
+
+/// The body of a purge request: `{keys}` and `{paths}` become JSON arrays
+fn render_body(template: &str, keys: &[String], paths: &[String]) -> String {
+    let json = |values: &[String]| serde_json::to_string(values).unwrap_or_default();
+    template
+        .replace(KEYS_PLACEHOLDER, &json(keys))
+        .replace(PATHS_PLACEHOLDER, &json(paths))
+}
+
+/// Shared connection-pooled client; the purge URL comes from the
+/// configuration, so it is not restricted like `fetch` targets
+fn shared_client() -> Result<&'static reqwest::blocking::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::blocking::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Send one purge request to the CDN API
+fn send_purge(config: &CdnConfig, keys: &[String], paths: &[String]) -> Result<(), String> {
+    let url = config
+        .purge_url
+        .as_deref()
+        .ok_or("cdn.purge_url is not set")?;
+    let method = reqwest::Method::from_bytes(config.purge_method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid cdn.purge_method '{}'", config.purge_method))?;
+    let mut request = shared_client()?
+        .request(method, url)
+        .timeout(Duration::from_millis(config.timeout_ms));
+    for (name, value) in &config.purge_headers {
+        request = request.header(
+            name.as_str(),
+            render(value, keys, paths, &config.surrogate_key_separator),
+        );
+    }
+    if !config.purge_body.is_empty() {
+        if !config
+            .purge_headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-type"))
+        {
+            request = request.header("Content-Type", "application/json");
+        }
+        request = request.body(render_body(&config.purge_body, keys, paths));
+    }
+    let response = request.send().map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status().as_u16()))
+    }
+}
+
+/// Purge the cached responses of `asset_name` of `script_uri` from the CDN
+/// in the background. Does nothing unless purging is configured.
+pub fn purge_asset(script_uri: &str, asset_name: &str) {
+    let config = current_settings();
+    if !config.enabled || config.purge_url.is_none() {
+        return;
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let mut keys = vec![asset_key(script_uri, asset_name)];
+    let mut paths = Vec::new();
+    for (path, policy) in
+        crate::asset_registry::get_global_registry().paths_serving(script_uri, asset_name)
+    {
+        paths.push(path);
+        if let Some(policy) = policy {
+            keys.extend(policy.surrogate_keys);
+        }
+    }
+    keys.sort();
+    keys.dedup();
+    paths.sort();
+    paths.dedup();
+    let (script_uri, asset_name) = (script_uri.to_string(), asset_name.to_string());
+    runtime.spawn_blocking(move || match send_purge(&config, &keys, &paths) {
+        Ok(()) => debug!(
+            "Purged asset '{}' of {} from the CDN ({} keys, {} paths)",
+            asset_name,
+            script_uri,
+            keys.len(),
+            paths.len()
+        ),
+        Err(e) => warn!(
+            "Failed to purge asset '{}' of {} from the CDN: {}",
+            asset_name, script_uri, e
+        ),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_policy() {
+        let policy = CachePolicy::new(Some(60), Some(86400), vec!["static".to_string()]).unwrap();
+        assert_eq!(
+            policy.cache_control().as_deref(),
+            Some("public, max-age=60, s-maxage=86400")
+        );
+        let policy = CachePolicy::new(None, Some(600), Vec::new()).unwrap();
+        assert_eq!(
+            policy.cache_control().as_deref(),
+            Some("public, max-age=0, s-maxage=600")
+        );
+        let policy = CachePolicy::new(Some(60), None, Vec::new()).unwrap();
+        assert_eq!(
+            policy.cache_control().as_deref(),
+            Some("public, max-age=60")
+        );
+        let policy = CachePolicy::new(None, None, vec!["docs".to_string()]).unwrap();
+        assert_eq!(policy.cache_control(), None);
+
+        assert!(CachePolicy::new(None, None, Vec::new()).is_err());
+        for key in ["", "two words", "a,b", "ä"] {
+            assert!(
+                CachePolicy::new(None, None, vec![key.to_string()]).is_err(),
+                "{}",
+                key
+            );
+        }
+        let too_many = (0..=MAX_SURROGATE_KEYS).map(|i| i.to_string()).collect();
+        assert!(CachePolicy::new(None, None, too_many).is_err());
+    }
+
+    #[test]
+    fn test_asset_key() {
+        let key = asset_key("script1", "app.css");
+        assert!(key.starts_with("asset-"));
+        assert_eq!(key.len(), "asset-".len() + 16);
+        assert_eq!(key, asset_key("script1", "app.css"));
+        assert_ne!(key, asset_key("script2", "app.css"));
+        assert_ne!(key, asset_key("script1", "app.js"));
+    }
+
+    #[test]
+    fn test_render() {
+        let keys = vec!["asset-1".to_string(), "static".to_string()];
+        let paths = vec!["/static/app.css".to_string()];
+        assert_eq!(render("{keys}", &keys, &paths, " "), "asset-1 static");
+        assert_eq!(
+            render_body(r#"{"tags": {keys}, "files": {paths}}"#, &keys, &paths),
+            r#"{"tags": ["asset-1","static"], "files": ["/static/app.css"]}"#
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Compressed representations of assets.
+//!
+//! When an asset of a compressible type (text, JavaScript, JSON, SVG, ...)
+//! is stored, its gzip and brotli variants are computed and stored next to
+//! it. Asset responses use the variant the request's `Accept-Encoding`
+//! prefers, so popular assets are compressed once instead of on every
+//! request. A variant that is not at least a tenth smaller than the asset is
+//! stored empty, which records that the asset is served as it is.
+//!
+//! Assets stored before this existed, or restored from the trash, have no
+//! variants; they are compressed on the fly instead.
+//!
+//! `performance.enable_compression` turns this off, and
+//! `performance.compression_level` (1-9) sets the gzip level and brotli
+//! quality.
+
+use std::io::Write as _;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::config::PerformanceConfig;
+
+/// Assets smaller than this are not worth compressing
+pub const MIN_COMPRESSIBLE_BYTES: usize = 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static LEVEL: AtomicU32 = AtomicU32::new(6);
+
+/// Apply the compression settings of the configuration
+pub fn configure(config: &PerformanceConfig) {
+    ENABLED.store(config.enable_compression, Ordering::Relaxed);
+    LEVEL.store(config.compression_level.clamp(1, 9), Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn level() -> u32 {
+    LEVEL.load(Ordering::Relaxed)
+}
+
+/// A content coding assets are stored and served in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// Name in `Accept-Encoding` and `Content-Encoding`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Stored variants of an asset; None when the asset is not compressible
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variants {
+    pub gzip: Option<Vec<u8>>,
+    pub brotli: Option<Vec<u8>>,
+}
+
+/// Whether content of `mimetype` shrinks when compressed. Images other than
+/// SVG, audio, video, archives and WOFF fonts are compressed already.
+pub fn is_compressible(mimetype: &str) -> bool {
+    let essence = mimetype
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/javascript"
+                | "application/x-javascript"
+                | "application/ecmascript"
+                | "application/json"
+                | "application/ld+json"
+                | "application/xml"
+                | "application/wasm"
+                | "application/graphql"
+                | "application/vnd.ms-fontobject"
+                | "font/ttf"
+                | "font/otf"
+        )
+}
+
+/// Whether a response with this type and size should be compressed
+pub fn should_compress(mimetype: &str, size: usize) -> bool {
+    enabled() && size >= MIN_COMPRESSIBLE_BYTES && is_compressible(mimetype)
+}
+
+/// The encoding to answer a request with, from its `Accept-Encoding`.
+/// Brotli wins over gzip when the client weighs them the same.
+pub fn negotiate(accept_encoding: Option<&str>) -> Option<Encoding> {
+    if !enabled() {
+        return None;
+    }
+    let mut brotli = None;
+    let mut gzip = None;
+    let mut any = None;
+    for coding in accept_encoding?.split(',') {
+        let mut parts = coding.split(';');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match name.as_str() {
+            "br" => brotli = Some(quality),
+            "gzip" | "x-gzip" => gzip = Some(quality),
+            "*" => any = Some(quality),
+            _ => {}
+        }
+    }
+    let brotli = brotli.or(any).unwrap_or(0.0);
+    let gzip = gzip.or(any).unwrap_or(0.0);
+    if brotli > 0.0 && brotli >= gzip {
+        Some(Encoding::Brotli)
+    } else if gzip > 0.0 {
+        Some(Encoding::Gzip)
+    } else {
+        None
+    }
+}
+
+/// Compress `content` with `encoding` at the configured level
+pub fn compress(content: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    let level = level();
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+            encoder.write_all(content)?;
+            encoder.finish()
+        }
+        Encoding::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, level, 22);
+            encoder.write_all(content)?;
+            Ok(encoder.into_inner())
+        }
+    }
+}
+
+/// Whether a compressed representation is small enough to be worth serving
+fn pays_off(compressed: usize, original: usize) -> bool {
+    compressed * 10 <= original * 9
+}
+
+/// Compress `content` with `encoding`; an empty variant when compression
+/// does not pay off
+fn variant(content: &[u8], encoding: Encoding) -> Option<Vec<u8>> {
+    match compress(content, encoding) {
+        Ok(compressed) if pays_off(compressed.len(), content.len()) => Some(compressed),
+        Ok(_) => Some(Vec::new()),
+        Err(e) => {
+            tracing::warn!("Failed to {} compress asset: {}", encoding.as_str(), e);
+            None
+        }
+    }
+}
+
+/// The variants to store with an asset. CPU heavy; call on the blocking
+/// pool.
+pub fn precompress(mimetype: &str, content: &[u8]) -> Variants {
+    if !should_compress(mimetype, content.len()) {
+        return Variants::default();
+    }
+    Variants {
+        gzip: variant(content, Encoding::Gzip),
+        brotli: variant(content, Encoding::Brotli),
+    }
+}
+
+/// Compress a response body on the blocking pool. The body comes back as
+/// it was, with no encoding, when compression fails or does not pay off.
+pub async fn compress_response(
+    content: Vec<u8>,
+    encoding: Encoding,
+) -> (Vec<u8>, Option<Encoding>) {
+    let content = Arc::new(content);
+    let input = Arc::clone(&content);
+    let compressed = tokio::task::spawn_blocking(move || compress(&input, encoding)).await;
+    match compressed {
+        Ok(Ok(compressed)) if pays_off(compressed.len(), content.len()) => {
+            (compressed, Some(encoding))
+        }
+        _ => (
+            Arc::try_unwrap(content).unwrap_or_else(|content| content.to_vec()),
+            None,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_is_compressible() {
+        for mimetype in [
+            "text/html; charset=utf-8",
+            "application/javascript",
+            "application/manifest+json",
+            "image/svg+xml",
+            "application/wasm",
+        ] {
+            assert!(is_compressible(mimetype), "{}", mimetype);
+        }
+        for mimetype in ["image/png", "video/mp4", "application/zip", "font/woff2"] {
+            assert!(!is_compressible(mimetype), "{}", mimetype);
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None), None);
+        assert_eq!(negotiate(Some("gzip, deflate, br")), Some(Encoding::Brotli));
+        assert_eq!(negotiate(Some("gzip")), Some(Encoding::Gzip));
+        assert_eq!(negotiate(Some("br;q=0.5, gzip")), Some(Encoding::Gzip));
+        assert_eq!(negotiate(Some("br;q=0, gzip;q=0")), None);
+        assert_eq!(negotiate(Some("*")), Some(Encoding::Brotli));
+        assert_eq!(negotiate(Some("identity")), None);
+    }
+
+    #[test]
+    fn test_precompress() {
+        let content = "body { color: red; }\n".repeat(200).into_bytes();
+        let variants = precompress("text/css", &content);
+
+        let gzip = variants.gzip.unwrap();
+        assert!(!gzip.is_empty() && gzip.len() < content.len());
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(gzip.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, content);
+
+        let brotli = variants.brotli.unwrap();
+        assert!(!brotli.is_empty() && brotli.len() < content.len());
+        let mut decoded = Vec::new();
+        brotli::Decompressor::new(brotli.as_slice(), 4096)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, content);
+
+        // Small or already compressed assets get no variants
+        assert_eq!(precompress("text/css", b"a{}"), Variants::default());
+        assert_eq!(precompress("image/png", &content), Variants::default());
+    }
+}
@@ -3,6 +3,13 @@
 /// This module manages runtime registration of public HTTP paths to asset names.
 /// Assets are stored by name in the repository, and scripts can register them to
 /// specific HTTP paths using routeRegistry.registerAssetRoute() in their init() functions.
+///
+/// Whole directories of assets (e.g. an SPA build uploaded as `app/index.html`,
+/// `app/assets/main.js`, ...) are mounted with routeRegistry.registerAssetPrefix():
+/// every path under the URL prefix maps to the asset named by the asset name
+/// prefix followed by the rest of the path. Exact asset paths take precedence
+/// over prefixes, and longer prefixes over shorter ones.
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex, OnceLock};
 use tracing::{debug, info, warn};
@@ -15,6 +22,115 @@ pub fn get_global_registry() -> &'static AssetRegistry {
     GLOBAL_ASSET_REGISTRY.get_or_init(AssetRegistry::new)
 }
 
+/// Maximum number of custom response headers on an asset or asset route
+pub const MAX_ASSET_HEADERS: usize = 32;
+
+/// Headers the server derives from the stored asset itself and which must not
+/// be overridden by custom asset headers
+const RESERVED_ASSET_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "content-encoding",
+    "transfer-encoding",
+    "connection",
+    "set-cookie",
+];
+
+/// Validate custom response headers attached to an asset or asset route
+pub fn validate_asset_headers(headers: &HashMap<String, String>) -> Result<(), String> {
+    if headers.len() > MAX_ASSET_HEADERS {
+        return Err(format!(
+            "Too many headers (max {} per asset)",
+            MAX_ASSET_HEADERS
+        ));
+    }
+    for (name, value) in headers {
+        let header_name = axum::http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}'", name))?;
+        if RESERVED_ASSET_HEADERS.contains(&header_name.as_str()) {
+            return Err(format!(
+                "Header '{}' is set by the server and cannot be customized",
+                name
+            ));
+        }
+        axum::http::HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value for header '{}'", name))?;
+    }
+    Ok(())
+}
+
+/// Number of hex characters of the content hash embedded in versioned URLs
+pub const ASSET_HASH_LENGTH: usize = 12;
+
+/// Cache-Control sent for content-hashed URLs: their content never changes
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Short content hash identifying one version of an asset
+pub fn content_hash(content: &[u8]) -> String {
+    let digest = hex::encode(Sha256::digest(content));
+    digest[..ASSET_HASH_LENGTH].to_string()
+}
+
+/// Build the versioned URL of a path by inserting the hash before the file
+/// extension: `/static/app.js` -> `/static/app.<hash>.js`
+pub fn versioned_path(path: &str, hash: &str) -> String {
+    let (dir, file) = path.rsplit_once('/').unwrap_or(("", path));
+    let file = match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}.{}.{}", stem, hash, extension),
+        _ => format!("{}.{}", file, hash),
+    };
+    format!("{}/{}", dir, file)
+}
+
+/// Split a possibly versioned request path into `(stable path, hash)`
+/// candidates, i.e. the inverse of [`versioned_path`]
+fn versioned_path_candidates(path: &str) -> Vec<(String, String)> {
+    let Some((dir, file)) = path.rsplit_once('/') else {
+        return Vec::new();
+    };
+    let is_hash =
+        |part: &str| part.len() == ASSET_HASH_LENGTH && part.bytes().all(|b| b.is_ascii_hexdigit());
+    let parts: Vec<&str> = file.split('.').collect();
+    let mut candidates = Vec::new();
+    // `<stem>.<hash>.<extension>`
+    if parts.len() >= 3 && is_hash(parts[parts.len() - 2]) {
+        let mut stable = parts.clone();
+        let hash = stable.remove(parts.len() - 2);
+        candidates.push((format!("{}/{}", dir, stable.join(".")), hash.to_string()));
+    }
+    // `<file>.<hash>` for paths without an extension
+    if parts.len() >= 2 && is_hash(parts[parts.len() - 1]) {
+        let stable = parts[..parts.len() - 1].join(".");
+        candidates.push((
+            format!("{}/{}", dir, stable),
+            parts[parts.len() - 1].to_string(),
+        ));
+    }
+    candidates
+}
+
+/// Options for registering an asset path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetRouteOptions {
+    /// Response headers for this path; they override the asset's own headers
+    pub headers: HashMap<String, String>,
+    /// Also serve the asset at content-hashed URLs with immutable caching
+    pub versioned: bool,
+    /// Normalized host name the path is served on; None serves it on any
+    /// host that doesn't register the same path itself
+    pub host: Option<String>,
+    /// Keeps responses in the response cache instead of reading the asset
+    /// on every request
+    pub cache: Option<crate::response_cache::RouteCache>,
+    /// Only serve the path at signed URLs (see [`crate::signed_urls`])
+    pub private: bool,
+    /// CDN caching of the path (see [`crate::asset_cdn`])
+    pub cache_policy: Option<crate::asset_cdn::CachePolicy>,
+    /// Image transforms served from the query string; None serves the
+    /// asset as stored
+    pub image_transforms: Option<crate::image_transform::TransformPolicy>,
+}
+
 /// Stores registration information for a public asset path
 #[derive(Debug, Clone)]
 pub struct AssetPathRegistration {
@@ -22,6 +138,126 @@ pub struct AssetPathRegistration {
     pub asset_name: String,
     /// The script URI that registered this path
     pub script_uri: String,
+    /// Response headers for this path; they override the asset's own headers
+    pub headers: HashMap<String, String>,
+    /// Whether content-hashed URLs of this path are served (see [`versioned_path`])
+    pub versioned: bool,
+    /// Response caching of the path
+    pub cache: Option<crate::response_cache::RouteCache>,
+    /// Whether the path was resolved under an asset prefix rather than
+    /// registered itself
+    pub from_prefix: bool,
+    /// Asset served instead when `asset_name` does not exist; set for paths
+    /// under asset prefixes with a fallback (client-side routed SPAs)
+    pub fallback_asset: Option<String>,
+    /// Whether requests need a signed URL (see [`crate::signed_urls`])
+    pub private: bool,
+    /// CDN caching of the path
+    pub cache_policy: Option<crate::asset_cdn::CachePolicy>,
+    /// Image transforms served from the query string
+    pub image_transforms: Option<crate::image_transform::TransformPolicy>,
+}
+
+/// Default asset served for a directory path under an asset prefix
+pub const DEFAULT_INDEX_ASSET: &str = "index.html";
+
+/// Options for registering an asset prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetPrefixOptions {
+    /// Response headers for every path under the prefix
+    pub headers: HashMap<String, String>,
+    /// Normalized host name the prefix is served on; None serves it on any host
+    pub host: Option<String>,
+    /// Response caching of the paths under the prefix
+    pub cache: Option<crate::response_cache::RouteCache>,
+    /// Asset name, relative to the asset name prefix, served for the prefix
+    /// itself and for paths ending in `/`
+    pub index: String,
+    /// Asset name, relative to the asset name prefix, served for paths whose
+    /// asset does not exist; None answers them with 404
+    pub fallback: Option<String>,
+    /// Only serve the paths under the prefix at signed URLs
+    pub private: bool,
+    /// CDN caching of the paths under the prefix
+    pub cache_policy: Option<crate::asset_cdn::CachePolicy>,
+}
+
+impl Default for AssetPrefixOptions {
+    fn default() -> Self {
+        Self {
+            headers: HashMap::new(),
+            host: None,
+            cache: None,
+            index: DEFAULT_INDEX_ASSET.to_string(),
+            fallback: None,
+            private: false,
+            cache_policy: None,
+        }
+    }
+}
+
+/// Stores registration information for an asset prefix
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetPrefixRegistration {
+    /// Prefix of the names of the assets served under the URL prefix
+    pub asset_name_prefix: String,
+    /// The script URI that registered this prefix
+    pub script_uri: String,
+    /// Response headers for every path under the prefix
+    pub headers: HashMap<String, String>,
+    /// Response caching of the paths under the prefix
+    pub cache: Option<crate::response_cache::RouteCache>,
+    /// Asset served for directory paths, relative to the asset name prefix
+    pub index: String,
+    /// Asset served for paths without an asset, relative to the asset name prefix
+    pub fallback: Option<String>,
+    /// Whether requests need a signed URL
+    pub private: bool,
+    /// CDN caching of the paths under the prefix
+    pub cache_policy: Option<crate::asset_cdn::CachePolicy>,
+}
+
+impl AssetPrefixRegistration {
+    /// The registration serving the part of a request path after the prefix,
+    /// or None when it would leave the asset name prefix
+    fn resolve(&self, rest: &str) -> Option<AssetPathRegistration> {
+        if rest
+            .split('/')
+            .any(|segment| segment == ".." || segment == ".")
+            || rest.contains('\\')
+        {
+            return None;
+        }
+        let asset_name = if rest.is_empty() || rest.ends_with('/') {
+            format!("{}{}{}", self.asset_name_prefix, rest, self.index)
+        } else {
+            format!("{}{}", self.asset_name_prefix, rest)
+        };
+        Some(AssetPathRegistration {
+            asset_name,
+            script_uri: self.script_uri.clone(),
+            headers: self.headers.clone(),
+            versioned: false,
+            cache: self.cache.clone(),
+            from_prefix: true,
+            fallback_asset: self
+                .fallback
+                .as_ref()
+                .map(|fallback| format!("{}{}", self.asset_name_prefix, fallback)),
+            private: self.private,
+            cache_policy: self.cache_policy.clone(),
+            image_transforms: None,
+        })
+    }
+}
+
+/// Normalize a URL prefix to end with `/`
+pub fn normalize_prefix(prefix: &str) -> String {
+    if prefix.ends_with('/') {
+        prefix.to_string()
+    } else {
+        format!("{}/", prefix)
+    }
 }
 
 /// Registry for managing public asset path registrations
@@ -29,6 +265,8 @@ pub struct AssetPathRegistration {
 pub struct AssetRegistry {
     /// Map of HTTP path -> asset registration
     paths: Arc<Mutex<HashMap<String, AssetPathRegistration>>>,
+    /// Map of HTTP path prefix (ending in `/`) -> asset prefix registration
+    prefixes: Arc<Mutex<HashMap<String, AssetPrefixRegistration>>>,
 }
 
 impl AssetRegistry {
@@ -36,6 +274,7 @@ impl AssetRegistry {
     pub fn new() -> Self {
         Self {
             paths: Arc::new(Mutex::new(HashMap::new())),
+            prefixes: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -51,10 +290,44 @@ impl AssetRegistry {
         asset_name: &str,
         script_uri: &str,
     ) -> Result<(), String> {
+        self.register_path_with_options(path, asset_name, script_uri, AssetRouteOptions::default())
+    }
+
+    /// Register a public path for an asset with custom response headers
+    /// (e.g. Cache-Control) applied on top of the asset's stored headers and,
+    /// for versioned routes, content-hashed URLs
+    pub fn register_path_with_options(
+        &self,
+        path: &str,
+        asset_name: &str,
+        script_uri: &str,
+        options: AssetRouteOptions,
+    ) -> Result<(), String> {
+        validate_asset_headers(&options.headers)?;
+        let AssetRouteOptions {
+            headers,
+            versioned,
+            host,
+            cache,
+            private,
+            cache_policy,
+            image_transforms,
+        } = options;
+        let key = crate::route_index::host_scoped_path(host.as_deref(), path);
+        let path = key.as_str();
+
         match self.paths.lock() {
             Ok(mut paths) => {
                 if let Some(existing) = paths.get(path) {
-                    if existing.asset_name != asset_name || existing.script_uri != script_uri {
+                    if existing.asset_name != asset_name
+                        || existing.script_uri != script_uri
+                        || existing.headers != headers
+                        || existing.versioned != versioned
+                        || existing.cache != cache
+                        || existing.private != private
+                        || existing.cache_policy != cache_policy
+                        || existing.image_transforms != image_transforms
+                    {
                         warn!(
                             "Overwriting asset path '{}': was {} from {}, now {} from {}",
                             path, existing.asset_name, existing.script_uri, asset_name, script_uri
@@ -78,6 +351,14 @@ impl AssetRegistry {
                     AssetPathRegistration {
                         asset_name: asset_name.to_string(),
                         script_uri: script_uri.to_string(),
+                        headers,
+                        versioned,
+                        cache,
+                        from_prefix: false,
+                        fallback_asset: None,
+                        private,
+                        cache_policy,
+                        image_transforms,
                     },
                 );
                 Ok(())
@@ -90,6 +371,130 @@ impl AssetRegistry {
         }
     }
 
+    /// Mount every asset whose name starts with `asset_name_prefix` under the
+    /// URL `prefix`: `/app/` with `app/` serves asset `app/assets/main.js` at
+    /// `/app/assets/main.js`
+    pub fn register_prefix(
+        &self,
+        prefix: &str,
+        asset_name_prefix: &str,
+        script_uri: &str,
+        options: AssetPrefixOptions,
+    ) -> Result<(), String> {
+        validate_asset_headers(&options.headers)?;
+        let AssetPrefixOptions {
+            headers,
+            host,
+            cache,
+            index,
+            fallback,
+            private,
+            cache_policy,
+        } = options;
+        let key = crate::route_index::host_scoped_path(host.as_deref(), &normalize_prefix(prefix));
+        let registration = AssetPrefixRegistration {
+            asset_name_prefix: asset_name_prefix.to_string(),
+            script_uri: script_uri.to_string(),
+            headers,
+            cache,
+            index,
+            fallback,
+            private,
+            cache_policy,
+        };
+
+        match self.prefixes.lock() {
+            Ok(mut prefixes) => {
+                match prefixes.get(&key) {
+                    Some(existing) if *existing == registration => {
+                        debug!(
+                            "Asset prefix '{}' already registered to {} from {}",
+                            key, asset_name_prefix, script_uri
+                        );
+                        return Ok(());
+                    }
+                    Some(existing) => warn!(
+                        "Overwriting asset prefix '{}': was {} from {}, now {} from {}",
+                        key,
+                        existing.asset_name_prefix,
+                        existing.script_uri,
+                        asset_name_prefix,
+                        script_uri
+                    ),
+                    None => {}
+                }
+                info!(
+                    "Registering asset prefix '{}' -> assets '{}*' (from script '{}')",
+                    key, asset_name_prefix, script_uri
+                );
+                prefixes.insert(key, registration);
+                Ok(())
+            }
+            Err(e) => {
+                let err_msg = format!("Failed to lock asset registry: {}", e);
+                warn!("{}", err_msg);
+                Err(err_msg)
+            }
+        }
+    }
+
+    /// Find the longest asset prefix containing a registry key and map the
+    /// key to an asset under it. The prefix without its trailing `/` matches
+    /// too and serves the index asset.
+    fn resolve_prefix(&self, key: &str) -> Option<AssetPathRegistration> {
+        let prefixes = match self.prefixes.lock() {
+            Ok(prefixes) => prefixes,
+            Err(e) => {
+                warn!("Failed to lock asset registry for prefix lookup: {}", e);
+                return None;
+            }
+        };
+        prefixes
+            .iter()
+            .filter_map(|(prefix, registration)| {
+                let rest = key
+                    .strip_prefix(prefix.as_str())
+                    .or_else(|| (key == &prefix[..prefix.len() - 1]).then_some(""))?;
+                Some((prefix.len(), registration, rest))
+            })
+            .max_by_key(|(length, _, _)| *length)
+            .and_then(|(_, registration, rest)| registration.resolve(rest))
+    }
+
+    /// Unregister an asset prefix
+    pub fn unregister_prefix(&self, prefix: &str) -> bool {
+        match self.prefixes.lock() {
+            Ok(mut prefixes) => {
+                let existed = prefixes.remove(&normalize_prefix(prefix)).is_some();
+                if existed {
+                    info!("Unregistered asset prefix: {}", prefix);
+                }
+                existed
+            }
+            Err(e) => {
+                warn!("Failed to lock asset registry for unregistration: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Get all asset prefix registrations with their details
+    pub fn get_all_prefix_registrations(&self) -> Vec<(String, AssetPrefixRegistration)> {
+        match self.prefixes.lock() {
+            Ok(prefixes) => prefixes
+                .iter()
+                .map(|(prefix, reg)| (prefix.clone(), reg.clone()))
+                .collect(),
+            Err(e) => {
+                warn!(
+                    "Failed to lock asset registry for getting prefix registrations: {}",
+                    e
+                );
+                Vec::new()
+            }
+        }
+    }
+
     /// Get the asset name for a given HTTP path
     pub fn get_asset_name(&self, path: &str) -> Option<String> {
         match self.paths.lock() {
@@ -112,6 +517,161 @@ impl AssetRegistry {
         }
     }
 
+    /// Resolve a content-hashed URL of a versioned route to its registration
+    /// and the requested hash. The caller checks the hash against the asset's
+    /// current content.
+    pub fn resolve_versioned_path(&self, path: &str) -> Option<(AssetPathRegistration, String)> {
+        let candidates = versioned_path_candidates(path);
+        if candidates.is_empty() {
+            return None;
+        }
+        match self.paths.lock() {
+            Ok(paths) => candidates.into_iter().find_map(|(stable, hash)| {
+                paths
+                    .get(&stable)
+                    .filter(|reg| reg.versioned)
+                    .map(|reg| (reg.clone(), hash))
+            }),
+            Err(e) => {
+                warn!("Failed to lock asset registry for versioned lookup: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Find the registration serving a request for `path` on `host`: the
+    /// host's own registration first, then the one for any host, and asset
+    /// prefixes after exact paths. The second value is the requested hash
+    /// when a content-hashed URL of a versioned route matched.
+    pub fn resolve_request(
+        &self,
+        host: Option<&str>,
+        path: &str,
+    ) -> Option<(AssetPathRegistration, Option<String>)> {
+        let keys = crate::route_index::request_path_keys(host, path);
+        keys.iter()
+            .find_map(|key| match self.get_asset_registration(key) {
+                Some(registration) => Some((registration, None)),
+                None => self
+                    .resolve_versioned_path(key)
+                    .map(|(registration, hash)| (registration, Some(hash))),
+            })
+            .or_else(|| {
+                keys.iter()
+                    .find_map(|key| self.resolve_prefix(key))
+                    .map(|registration| (registration, None))
+            })
+    }
+
+    /// The URL path signed URLs of a private asset of `script_uri` point at:
+    /// a private path registered for the asset, else the asset's path under
+    /// the private prefix with the longest matching asset name prefix. Host
+    /// scoped registrations give the path on their host.
+    pub fn private_path(&self, script_uri: &str, asset_name: &str) -> Option<String> {
+        let exact = match self.paths.lock() {
+            Ok(paths) => paths
+                .iter()
+                .filter(|(_, reg)| {
+                    reg.private && reg.script_uri == script_uri && reg.asset_name == asset_name
+                })
+                .map(|(key, _)| {
+                    crate::route_index::split_host_scoped_path(key)
+                        .1
+                        .to_string()
+                })
+                .min(),
+            Err(e) => {
+                warn!(
+                    "Failed to lock asset registry for private path lookup: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        if exact.is_some() {
+            return exact;
+        }
+
+        let prefixes = match self.prefixes.lock() {
+            Ok(prefixes) => prefixes,
+            Err(e) => {
+                warn!(
+                    "Failed to lock asset registry for private path lookup: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        prefixes
+            .iter()
+            .filter(|(_, reg)| reg.private && reg.script_uri == script_uri)
+            .filter_map(|(key, reg)| {
+                let rest = asset_name.strip_prefix(reg.asset_name_prefix.as_str())?;
+                let prefix = crate::route_index::split_host_scoped_path(key).1;
+                Some((reg.asset_name_prefix.len(), format!("{}{}", prefix, rest)))
+            })
+            .max_by(|(a_len, a_path), (b_len, b_path)| a_len.cmp(b_len).then(b_path.cmp(a_path)))
+            .map(|(_, path)| path)
+    }
+
+    /// The URL paths serving `asset_name` of `script_uri` with their cache
+    /// policies, for purging CDN caches when the asset changes: paths
+    /// registered for the asset and its paths under matching prefixes,
+    /// including the directory paths of index assets
+    pub fn paths_serving(
+        &self,
+        script_uri: &str,
+        asset_name: &str,
+    ) -> Vec<(String, Option<crate::asset_cdn::CachePolicy>)> {
+        let mut serving: Vec<(String, Option<crate::asset_cdn::CachePolicy>)> =
+            match self.paths.lock() {
+                Ok(paths) => paths
+                    .iter()
+                    .filter(|(_, reg)| reg.script_uri == script_uri && reg.asset_name == asset_name)
+                    .map(|(key, reg)| {
+                        (
+                            crate::route_index::split_host_scoped_path(key)
+                                .1
+                                .to_string(),
+                            reg.cache_policy.clone(),
+                        )
+                    })
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to lock asset registry for purge lookup: {}", e);
+                    Vec::new()
+                }
+            };
+
+        match self.prefixes.lock() {
+            Ok(prefixes) => {
+                for (key, reg) in prefixes
+                    .iter()
+                    .filter(|(_, reg)| reg.script_uri == script_uri)
+                {
+                    let Some(rest) = asset_name.strip_prefix(reg.asset_name_prefix.as_str()) else {
+                        continue;
+                    };
+                    let prefix = crate::route_index::split_host_scoped_path(key).1;
+                    serving.push((format!("{}{}", prefix, rest), reg.cache_policy.clone()));
+                    if let Some(dir) = rest.strip_suffix(reg.index.as_str())
+                        && (dir.is_empty() || dir.ends_with('/'))
+                    {
+                        serving.push((format!("{}{}", prefix, dir), reg.cache_policy.clone()));
+                        if dir.is_empty() {
+                            serving.push((
+                                prefix.trim_end_matches('/').to_string(),
+                                reg.cache_policy.clone(),
+                            ));
+                        }
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to lock asset registry for purge lookup: {}", e),
+        }
+        serving
+    }
+
     /// Check if a path is registered
     pub fn is_path_registered(&self, path: &str) -> bool {
         match self.paths.lock() {
@@ -182,6 +742,12 @@ impl AssetRegistry {
                 warn!("Failed to lock asset registry for clearing: {}", e);
             }
         }
+        match self.prefixes.lock() {
+            Ok(mut prefixes) => prefixes.clear(),
+            Err(e) => {
+                warn!("Failed to lock asset registry for clearing: {}", e);
+            }
+        }
     }
 
     /// Get all registrations for a specific script
@@ -291,4 +857,320 @@ mod tests {
         assert_eq!(css_reg.1.asset_name, "app.css");
         assert_eq!(css_reg.1.script_uri, "script2");
     }
+
+    #[test]
+    fn test_register_path_with_headers() {
+        let registry = AssetRegistry::new();
+        let headers = HashMap::from([(
+            "Cache-Control".to_string(),
+            "public, max-age=3600".to_string(),
+        )]);
+
+        registry
+            .register_path_with_options(
+                "/app.css",
+                "app.css",
+                "script1",
+                AssetRouteOptions {
+                    headers: headers.clone(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let registration = registry.get_asset_registration("/app.css").unwrap();
+        assert_eq!(registration.headers, headers);
+
+        // Re-registering with different headers replaces them
+        registry
+            .register_path("/app.css", "app.css", "script1")
+            .unwrap();
+        let registration = registry.get_asset_registration("/app.css").unwrap();
+        assert!(registration.headers.is_empty());
+    }
+
+    #[test]
+    fn test_validate_asset_headers() {
+        let ok = HashMap::from([
+            ("Cache-Control".to_string(), "no-cache".to_string()),
+            (
+                "Content-Disposition".to_string(),
+                "attachment; filename=\"report.pdf\"".to_string(),
+            ),
+            ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+        ]);
+        assert!(validate_asset_headers(&ok).is_ok());
+
+        let reserved = HashMap::from([("Content-Type".to_string(), "text/html".to_string())]);
+        assert!(validate_asset_headers(&reserved).is_err());
+
+        let bad_name = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(validate_asset_headers(&bad_name).is_err());
+
+        let bad_value = HashMap::from([("X-Test".to_string(), "line\nbreak".to_string())]);
+        assert!(validate_asset_headers(&bad_value).is_err());
+
+        let registry = AssetRegistry::new();
+        assert!(
+            registry
+                .register_path_with_options(
+                    "/x",
+                    "x",
+                    "script1",
+                    AssetRouteOptions {
+                        headers: reserved,
+                        ..Default::default()
+                    },
+                )
+                .is_err()
+        );
+        assert!(!registry.is_path_registered("/x"));
+    }
+
+    #[test]
+    fn test_versioned_path() {
+        assert_eq!(
+            versioned_path("/static/app.js", "0123456789ab"),
+            "/static/app.0123456789ab.js"
+        );
+        assert_eq!(
+            versioned_path("/static/app.min.js", "0123456789ab"),
+            "/static/app.min.0123456789ab.js"
+        );
+        assert_eq!(
+            versioned_path("/static/LICENSE", "0123456789ab"),
+            "/static/LICENSE.0123456789ab"
+        );
+        assert_eq!(
+            versioned_path("/.well-known", "0123456789ab"),
+            "/.well-known.0123456789ab"
+        );
+        assert_eq!(content_hash(b"hello").len(), ASSET_HASH_LENGTH);
+        assert_ne!(content_hash(b"hello"), content_hash(b"hello!"));
+    }
+
+    #[test]
+    fn test_resolve_versioned_path() {
+        let registry = AssetRegistry::new();
+        let versioned = AssetRouteOptions {
+            versioned: true,
+            ..Default::default()
+        };
+        registry
+            .register_path_with_options("/static/app.js", "app.js", "script1", versioned.clone())
+            .unwrap();
+        registry
+            .register_path_with_options("/static/LICENSE", "LICENSE", "script1", versioned)
+            .unwrap();
+        registry
+            .register_path("/static/plain.css", "plain.css", "script1")
+            .unwrap();
+
+        for stable in ["/static/app.js", "/static/LICENSE"] {
+            let hashed = versioned_path(stable, "0123456789ab");
+            let (registration, hash) = registry.resolve_versioned_path(&hashed).unwrap();
+            assert!(registration.versioned);
+            assert_eq!(hash, "0123456789ab");
+            assert!(registry.is_path_registered(stable));
+        }
+
+        // Only versioned routes answer hashed URLs
+        assert!(
+            registry
+                .resolve_versioned_path("/static/plain.0123456789ab.css")
+                .is_none()
+        );
+        assert!(registry.resolve_versioned_path("/static/app.js").is_none());
+        assert!(
+            registry
+                .resolve_versioned_path("/static/app.notahash.js")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_host_scoped_asset_paths() {
+        let registry = AssetRegistry::new();
+        for (host, asset) in [("a.example.com", "a.ico"), ("b.example.com", "b.ico")] {
+            registry
+                .register_path_with_options(
+                    "/favicon.ico",
+                    asset,
+                    "script1",
+                    AssetRouteOptions {
+                        host: Some(host.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        registry
+            .register_path("/favicon.ico", "default.ico", "script2")
+            .unwrap();
+
+        let asset_for = |host: Option<&str>| {
+            registry
+                .resolve_request(host, "/favicon.ico")
+                .map(|(registration, _)| registration.asset_name)
+        };
+        assert_eq!(asset_for(Some("a.example.com")).as_deref(), Some("a.ico"));
+        assert_eq!(
+            asset_for(Some("B.example.com:8080")).as_deref(),
+            Some("b.ico")
+        );
+        assert_eq!(
+            asset_for(Some("c.example.com")).as_deref(),
+            Some("default.ico")
+        );
+        assert_eq!(asset_for(None).as_deref(), Some("default.ico"));
+        assert_eq!(registry.get_paths_for_script("script1").len(), 2);
+    }
+
+    #[test]
+    fn test_asset_prefixes() {
+        let registry = AssetRegistry::new();
+        registry
+            .register_prefix("/app", "app/", "script1", AssetPrefixOptions::default())
+            .unwrap();
+        registry
+            .register_prefix(
+                "/app/docs/",
+                "docs/",
+                "script2",
+                AssetPrefixOptions {
+                    fallback: Some("404.html".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        registry
+            .register_path("/app/favicon.ico", "favicon.ico", "script3")
+            .unwrap();
+
+        let resolve = |path: &str| {
+            registry
+                .resolve_request(None, path)
+                .map(|(registration, _)| (registration.asset_name, registration.fallback_asset))
+        };
+        assert_eq!(
+            resolve("/app/assets/main.js"),
+            Some(("app/assets/main.js".to_string(), None))
+        );
+        assert_eq!(resolve("/app/"), Some(("app/index.html".to_string(), None)));
+        assert_eq!(resolve("/app"), Some(("app/index.html".to_string(), None)));
+        assert_eq!(
+            resolve("/app/nested/"),
+            Some(("app/nested/index.html".to_string(), None))
+        );
+
+        // Longer prefixes and exact paths win
+        assert_eq!(
+            resolve("/app/docs/guide.html"),
+            Some((
+                "docs/guide.html".to_string(),
+                Some("docs/404.html".to_string())
+            ))
+        );
+        assert_eq!(
+            resolve("/app/favicon.ico"),
+            Some(("favicon.ico".to_string(), None))
+        );
+
+        // Paths can't climb out of the asset name prefix
+        assert_eq!(resolve("/app/../secret.txt"), None);
+        assert_eq!(resolve("/application.js"), None);
+
+        assert!(registry.unregister_prefix("/app/"));
+        assert_eq!(resolve("/app/assets/main.js"), None);
+    }
+
+    #[test]
+    fn test_private_paths() {
+        let registry = AssetRegistry::new();
+        let private = AssetRouteOptions {
+            private: true,
+            ..Default::default()
+        };
+        registry
+            .register_path_with_options("/reports/q1.pdf", "q1.pdf", "script1", private)
+            .unwrap();
+        registry
+            .register_path("/public/q1.pdf", "q1.pdf", "script1")
+            .unwrap();
+        registry
+            .register_prefix(
+                "/files",
+                "files/",
+                "script1",
+                AssetPrefixOptions {
+                    private: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let (registration, _) = registry.resolve_request(None, "/reports/q1.pdf").unwrap();
+        assert!(registration.private);
+        let (registration, _) = registry.resolve_request(None, "/files/a/b.txt").unwrap();
+        assert!(registration.private);
+        let (registration, _) = registry.resolve_request(None, "/public/q1.pdf").unwrap();
+        assert!(!registration.private);
+
+        assert_eq!(
+            registry.private_path("script1", "q1.pdf").as_deref(),
+            Some("/reports/q1.pdf")
+        );
+        assert_eq!(
+            registry.private_path("script1", "files/a/b.txt").as_deref(),
+            Some("/files/a/b.txt")
+        );
+        assert_eq!(registry.private_path("script2", "q1.pdf"), None);
+        assert_eq!(registry.private_path("script1", "other.pdf"), None);
+    }
+
+    #[test]
+    fn test_paths_serving() {
+        let registry = AssetRegistry::new();
+        let policy =
+            crate::asset_cdn::CachePolicy::new(Some(60), None, vec!["site".to_string()]).unwrap();
+        registry
+            .register_path("/home.html", "site/index.html", "script1")
+            .unwrap();
+        registry
+            .register_prefix(
+                "/site",
+                "site/",
+                "script1",
+                AssetPrefixOptions {
+                    cache_policy: Some(policy.clone()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        registry
+            .register_prefix("/other", "site/", "script2", AssetPrefixOptions::default())
+            .unwrap();
+
+        let mut serving = registry.paths_serving("script1", "site/index.html");
+        serving.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            serving,
+            vec![
+                ("/home.html".to_string(), None),
+                ("/site".to_string(), Some(policy.clone())),
+                ("/site/".to_string(), Some(policy.clone())),
+                ("/site/index.html".to_string(), Some(policy.clone())),
+            ]
+        );
+        assert_eq!(
+            registry
+                .paths_serving("script1", "site/docs/index.html")
+                .len(),
+            2
+        );
+        assert_eq!(
+            registry.paths_serving("script1", "site/app.js"),
+            vec![("/site/app.js".to_string(), Some(policy))]
+        );
+        assert!(registry.paths_serving("script1", "other.js").is_empty());
+    }
 }
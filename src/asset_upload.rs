@@ -0,0 +1,451 @@
+//! Direct asset uploads, without base64 through JavaScript.
+//!
+//! `POST /engine/assets/upload` takes a `multipart/form-data` body with one
+//! file and these fields:
+//!
+//! - `scriptUri` (required): the script the asset belongs to
+//! - `name`: the asset name; the uploaded file name when missing
+//!
+//! The `uploadAsset(scriptUri, file, name)` GraphQL mutation does the same
+//! through the GraphQL multipart request spec. Both check the same
+//! permissions as `assetStorage.upsertAssetForUri` (the `WriteAssets`
+//! capability, administrators, or owning the script), reject files larger
+//! than `repository.max_asset_size_bytes` or of a type outside
+//! `repository.allowed_asset_types`, and store the asset through
+//! [`repository::upsert_asset_async`].
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use async_graphql::ErrorExtensions;
+use async_graphql::dynamic::{Field, FieldFuture, InputValue, TypeRef};
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::RepositoryConfig;
+use crate::parsers::{self, UploadLimits};
+use crate::repository::{self, Repository as _};
+use crate::security::{Capability, UserContext};
+
+/// Path of the upload endpoint
+pub const UPLOAD_PATH: &str = "/engine/assets/upload";
+
+/// Type of the `uploadAsset` mutation's result, in the SDL form scripts use
+pub const GRAPHQL_SDL: &str = "type UploadedAsset { name: String! scriptUri: String! mimetype: String! size: Int! sha256: String! }";
+
+/// Longest asset name
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Limits of uploads
+#[derive(Debug, Clone)]
+struct Settings {
+    limits: UploadLimits,
+    /// Content types accepted as `type/subtype`, `type/*` or `*/*`; empty
+    /// accepts any
+    allowed_types: Vec<String>,
+}
+
+impl Settings {
+    fn from_config(config: &RepositoryConfig) -> Self {
+        let mut limits = UploadLimits::from_config(config);
+        limits.max_file_bytes = limits.max_file_bytes.min(config.max_asset_size_bytes);
+        Self {
+            limits,
+            allowed_types: config.allowed_asset_types.clone(),
+        }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<Settings>> = OnceLock::new();
+
+fn settings_lock() -> &'static RwLock<Settings> {
+    SETTINGS.get_or_init(|| RwLock::new(Settings::from_config(&RepositoryConfig::default())))
+}
+
+fn settings() -> Settings {
+    settings_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Apply the upload limits of the configuration
+pub fn configure(config: &RepositoryConfig) {
+    *settings_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Settings::from_config(config);
+}
+
+/// Largest accepted file, for the GraphQL multipart parser
+pub fn max_file_bytes() -> usize {
+    settings().limits.max_file_bytes
+}
+
+/// A stored upload
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedAsset {
+    pub name: String,
+    pub script_uri: String,
+    pub mimetype: String,
+    pub size: usize,
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+}
+
+/// Why an upload was refused
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl UploadError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// The `code` extension of the GraphQL error
+    fn code(&self) -> &'static str {
+        match self.status {
+            StatusCode::FORBIDDEN => "FORBIDDEN",
+            StatusCode::PAYLOAD_TOO_LARGE => "PAYLOAD_TOO_LARGE",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "UNSUPPORTED_MEDIA_TYPE",
+            status if status.is_server_error() => "INTERNAL_SERVER_ERROR",
+            _ => "BAD_USER_INPUT",
+        }
+    }
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        (
+            self.status,
+            axum::Json(serde_json::json!({ "error": self.message })),
+        )
+            .into_response()
+    }
+}
+
+/// Check an asset name the way `assetStorage.upsertAssetForUri` does
+fn validate_name(name: &str) -> Result<(), UploadError> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Err(UploadError::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Invalid asset name: must be 1-{} characters",
+                MAX_NAME_LENGTH
+            ),
+        ));
+    }
+    if name.contains("..") || name.contains('\\') {
+        return Err(UploadError::new(
+            StatusCode::BAD_REQUEST,
+            "Invalid asset name: path traversal not allowed",
+        ));
+    }
+    Ok(())
+}
+
+/// The MIME type an upload is stored with: the declared one, or a guess from
+/// the name when the client sent none or a generic one
+fn resolve_mimetype(name: &str, declared: Option<&str>) -> String {
+    let declared = declared
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| !value.is_empty() && value != "application/octet-stream");
+    declared.unwrap_or_else(|| {
+        mime_guess::from_path(name)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string()
+    })
+}
+
+fn check_type(mimetype: &str, allowed_types: &[String]) -> Result<(), UploadError> {
+    let essence = mimetype.split(';').next().unwrap_or_default().trim();
+    if allowed_types.is_empty()
+        || allowed_types
+            .iter()
+            .any(|allowed| parsers::content_type_matches(allowed, essence))
+    {
+        Ok(())
+    } else {
+        Err(UploadError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("Asset type '{}' is not allowed", essence),
+        ))
+    }
+}
+
+async fn check_permission(user: &UserContext, script_uri: &str) -> Result<(), UploadError> {
+    if user.has_capability(&Capability::WriteAssets)
+        || user.has_capability(&Capability::DeleteScripts)
+    {
+        return Ok(());
+    }
+    let owns_script = match &user.user_id {
+        Some(user_id) => repository::get_repository()
+            .user_owns_script(script_uri, user_id)
+            .await
+            .unwrap_or(false),
+        None => false,
+    };
+    if owns_script {
+        Ok(())
+    } else {
+        Err(UploadError::new(StatusCode::FORBIDDEN, "Access denied"))
+    }
+}
+
+/// Validate an upload and store it as an asset of `script_uri`. Headers of
+/// an existing asset with the same name are kept.
+pub async fn store(
+    user: &UserContext,
+    script_uri: &str,
+    name: &str,
+    content_type: Option<&str>,
+    content: Vec<u8>,
+) -> Result<UploadedAsset, UploadError> {
+    check_permission(user, script_uri).await?;
+    validate_name(name)?;
+
+    let settings = settings();
+    if content.len() > settings.limits.max_file_bytes {
+        return Err(UploadError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Asset too large (max {} bytes)",
+                settings.limits.max_file_bytes
+            ),
+        ));
+    }
+    let mimetype = resolve_mimetype(name, content_type);
+    check_type(&mimetype, &settings.allowed_types)?;
+
+    let headers = repository::fetch_asset_async(script_uri, name)
+        .await
+        .map(|existing| existing.headers)
+        .unwrap_or_default();
+    let uploaded = UploadedAsset {
+        name: name.to_string(),
+        script_uri: script_uri.to_string(),
+        mimetype: mimetype.clone(),
+        size: content.len(),
+        sha256: hex::encode(Sha256::digest(&content)),
+    };
+    let now = std::time::SystemTime::now();
+    repository::upsert_asset_async(repository::Asset {
+        uri: name.to_string(),
+        name: Some(name.to_string()),
+        mimetype,
+        content,
+        created_at: now,
+        updated_at: now,
+        script_uri: script_uri.to_string(),
+        headers,
+    })
+    .await
+    .map_err(|e| {
+        warn!("Failed to store uploaded asset '{}': {}", name, e);
+        UploadError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Error storing asset: {}", e),
+        )
+    })?;
+
+    info!(
+        user_id = ?user.user_id,
+        script_uri = %script_uri,
+        asset = %name,
+        size = uploaded.size,
+        "Asset uploaded"
+    );
+    Ok(uploaded)
+}
+
+/// `POST /engine/assets/upload`. With authentication enabled the route sits
+/// behind the required authentication middleware; without it uploads run
+/// with administrator rights, like GraphQL resolvers.
+pub async fn upload_handler(req: Request<Body>) -> Response {
+    let user = req
+        .extensions()
+        .get::<crate::auth::AuthUser>()
+        .map(crate::auth::AuthUser::user_context)
+        .unwrap_or_else(|| UserContext::admin("asset-upload".to_string()));
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if !content_type
+        .as_deref()
+        .is_some_and(|ct| ct.starts_with("multipart/form-data"))
+    {
+        return UploadError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Expected a multipart/form-data body",
+        )
+        .into_response();
+    }
+
+    let limits = settings().limits;
+    let (fields, mut files) =
+        match parsers::parse_form_data(content_type.as_deref(), req.into_body(), &limits).await {
+            Ok(form) => form,
+            Err(status) => {
+                return UploadError::new(status, "Invalid or oversized multipart body")
+                    .into_response();
+            }
+        };
+    match upload_form(&user, &fields, &mut files).await {
+        Ok(uploaded) => (StatusCode::CREATED, axum::Json(uploaded)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn upload_form(
+    user: &UserContext,
+    fields: &HashMap<String, String>,
+    files: &mut Vec<parsers::UploadedFile>,
+) -> Result<UploadedAsset, UploadError> {
+    let Some(script_uri) = fields.get("scriptUri").filter(|uri| !uri.is_empty()) else {
+        return Err(UploadError::new(
+            StatusCode::BAD_REQUEST,
+            "Missing scriptUri field",
+        ));
+    };
+    if files.len() != 1 {
+        return Err(UploadError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Expected exactly one file, got {}", files.len()),
+        ));
+    }
+    let file = files.remove(0);
+    let name = fields
+        .get("name")
+        .filter(|name| !name.is_empty())
+        .or(file.filename.as_ref())
+        .cloned()
+        .unwrap_or_default();
+    let content = file
+        .read()
+        .map_err(|e| {
+            UploadError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read upload: {}", e),
+            )
+        })?
+        .into_owned();
+    store(
+        user,
+        script_uri,
+        &name,
+        file.content_type.as_deref(),
+        content,
+    )
+    .await
+}
+
+/// The `uploadAsset(scriptUri: String!, file: Upload!, name: String): UploadedAsset!`
+/// mutation
+pub fn graphql_field() -> Field {
+    Field::new("uploadAsset", TypeRef::named_nn("UploadedAsset"), |ctx| {
+        FieldFuture::new(async move {
+            let user = crate::js_engine::graphql_resolver_user(
+                ctx.data::<crate::auth::JsAuthContext>().ok(),
+            );
+            let script_uri = ctx.args.try_get("scriptUri")?.string()?.to_string();
+            let name = match ctx.args.get("name") {
+                Some(name) => Some(name.string()?.to_string()),
+                None => None,
+            };
+            let mut upload = ctx.args.try_get("file")?.upload()?.value(ctx.ctx)?;
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut upload.content, &mut content)?;
+
+            let name = name.unwrap_or(upload.filename);
+            let uploaded = store(
+                &user,
+                &script_uri,
+                &name,
+                upload.content_type.as_deref(),
+                content,
+            )
+            .await
+            .map_err(|e| {
+                let code = e.code();
+                async_graphql::Error::new(e.message)
+                    .extend_with(|_, values| values.set("code", code))
+            })?;
+            Ok(Some(async_graphql::Value::from_json(
+                serde_json::to_value(uploaded)?,
+            )?))
+        })
+    })
+    .argument(InputValue::new(
+        "scriptUri",
+        TypeRef::named_nn(TypeRef::STRING),
+    ))
+    .argument(InputValue::new("file", TypeRef::named_nn(TypeRef::UPLOAD)))
+    .argument(InputValue::new("name", TypeRef::named(TypeRef::STRING)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("logo.png").is_ok());
+        assert!(validate_name("app/main.js").is_ok());
+        for name in ["", "../secret", "a\\b", &"x".repeat(MAX_NAME_LENGTH + 1)] {
+            assert_eq!(
+                validate_name(name).unwrap_err().status,
+                StatusCode::BAD_REQUEST
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_mimetype() {
+        assert_eq!(resolve_mimetype("logo.png", Some("Image/PNG")), "image/png");
+        assert_eq!(
+            resolve_mimetype("logo.png", Some("application/octet-stream")),
+            "image/png"
+        );
+        assert_eq!(resolve_mimetype("style.css", None), "text/css");
+        assert_eq!(resolve_mimetype("blob", None), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_check_type() {
+        assert!(check_type("application/zip", &[]).is_ok());
+
+        let allowed = vec!["image/*".to_string(), "text/css".to_string()];
+        assert!(check_type("image/png", &allowed).is_ok());
+        assert!(check_type("text/css; charset=utf-8", &allowed).is_ok());
+        assert_eq!(
+            check_type("application/zip", &allowed).unwrap_err().status,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(
+            UploadError::new(StatusCode::FORBIDDEN, "Access denied").code(),
+            "FORBIDDEN"
+        );
+        assert_eq!(
+            UploadError::new(StatusCode::BAD_REQUEST, "Missing scriptUri field").code(),
+            "BAD_USER_INPUT"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// CAPTCHA Verification
+// Challenges sign-ins from IP addresses with repeated recent failures
+//
+// Failed OAuth callbacks are counted per client IP on each server. Once an IP
+// reaches `failure_threshold` failures within `failure_window_secs`, the login
+// page renders a Turnstile or hCaptcha widget and /auth/login/{provider} only
+// starts a sign-in whose CAPTCHA response the provider confirms. A successful
+// sign-in clears the IP's failures. The client IP comes from the
+// X-Forwarded-For or X-Real-IP header set by the reverse proxy.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use super::config::{CaptchaConfig, CaptchaProvider};
+use super::error::AuthError;
+
+/// Most IP addresses whose failures are tracked at once
+const MAX_TRACKED_IPS: usize = 100_000;
+
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+/// Verification result returned by Turnstile and hCaptcha
+#[derive(Debug, Deserialize)]
+struct VerifyResponse {
+    success: bool,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+/// Tracks failed sign-ins and verifies CAPTCHA responses
+pub struct CaptchaGuard {
+    config: CaptchaConfig,
+    http_client: reqwest::Client,
+    failures: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl CaptchaGuard {
+    /// Create a guard from the CAPTCHA configuration
+    pub fn new(config: CaptchaConfig) -> Result<Self, AuthError> {
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| AuthError::ConfigError(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            config,
+            http_client,
+            failures: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.config.failure_window_secs)
+    }
+
+    fn lock_failures(&self) -> std::sync::MutexGuard<'_, HashMap<String, VecDeque<Instant>>> {
+        self.failures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether sign-ins from this IP address must pass a CAPTCHA
+    pub fn is_required(&self, ip_addr: &str) -> bool {
+        if self.config.failure_threshold == 0 {
+            return true;
+        }
+        let window = self.window();
+        let failures = self.lock_failures();
+        failures.get(ip_addr).is_some_and(|attempts| {
+            attempts
+                .iter()
+                .filter(|attempt| attempt.elapsed() < window)
+                .count()
+                >= self.config.failure_threshold as usize
+        })
+    }
+
+    /// Count a failed sign-in from this IP address. Clients whose address
+    /// is unknown are not tracked, so they don't share one CAPTCHA
+    /// requirement.
+    pub fn record_failure(&self, ip_addr: &str) {
+        if ip_addr == "unknown" {
+            return;
+        }
+        let window = self.window();
+        let mut failures = self.lock_failures();
+
+        if failures.len() >= MAX_TRACKED_IPS && !failures.contains_key(ip_addr) {
+            failures.retain(|_, attempts| {
+                attempts
+                    .back()
+                    .is_some_and(|attempt| attempt.elapsed() < window)
+            });
+            if failures.len() >= MAX_TRACKED_IPS {
+                tracing::warn!("CAPTCHA failure tracking is full; not tracking {}", ip_addr);
+                return;
+            }
+        }
+
+        let attempts = failures.entry(ip_addr.to_string()).or_default();
+        while attempts
+            .front()
+            .is_some_and(|attempt| attempt.elapsed() >= window)
+        {
+            attempts.pop_front();
+        }
+        attempts.push_back(Instant::now());
+        // Only the last `failure_threshold` attempts decide whether a
+        // CAPTCHA is required
+        while attempts.len() > (self.config.failure_threshold as usize).max(1) {
+            attempts.pop_front();
+        }
+    }
+
+    /// Forget the failures of an IP address after a successful sign-in
+    pub fn clear_failures(&self, ip_addr: &str) {
+        self.lock_failures().remove(ip_addr);
+    }
+
+    /// Verify a CAPTCHA response with the provider
+    pub async fn verify(&self, response: &str, ip_addr: &str) -> Result<(), AuthError> {
+        if response.is_empty() {
+            return Err(AuthError::CaptchaRequired);
+        }
+
+        let mut form = vec![
+            ("secret", self.config.secret_key.as_str()),
+            ("response", response),
+            ("sitekey", self.config.site_key.as_str()),
+        ];
+        if ip_addr != "unknown" {
+            form.push(("remoteip", ip_addr));
+        }
+
+        let url = match self.config.provider {
+            CaptchaProvider::Turnstile => TURNSTILE_VERIFY_URL,
+            CaptchaProvider::Hcaptcha => HCAPTCHA_VERIFY_URL,
+        };
+        let result: VerifyResponse = self
+            .http_client
+            .post(url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if result.success {
+            Ok(())
+        } else {
+            Err(AuthError::CaptchaFailed(if result.error_codes.is_empty() {
+                "rejected by provider".to_string()
+            } else {
+                result.error_codes.join(", ")
+            }))
+        }
+    }
+
+    /// Script tag and widget placeholder for the login page
+    pub fn widget_html(&self) -> String {
+        let (script, class) = match self.config.provider {
+            CaptchaProvider::Turnstile => (
+                "https://challenges.cloudflare.com/turnstile/v0/api.js",
+                "cf-turnstile",
+            ),
+            CaptchaProvider::Hcaptcha => ("https://js.hcaptcha.com/1/api.js", "h-captcha"),
+        };
+        format!(
+            r#"<script src="{}" async defer></script>
+                                <div class="{} mb-3" data-sitekey="{}"></div>"#,
+            script,
+            class,
+            html_escape::encode_double_quoted_attribute(&self.config.site_key)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(failure_threshold: u32) -> CaptchaGuard {
+        CaptchaGuard::new(CaptchaConfig {
+            enabled: true,
+            site_key: "site-key".to_string(),
+            secret_key: "secret-key".to_string(),
+            failure_threshold,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_required_after_threshold() {
+        let guard = guard(3);
+        assert!(!guard.is_required("203.0.113.5"));
+
+        guard.record_failure("203.0.113.5");
+        guard.record_failure("203.0.113.5");
+        assert!(!guard.is_required("203.0.113.5"));
+
+        guard.record_failure("203.0.113.5");
+        assert!(guard.is_required("203.0.113.5"));
+        assert!(!guard.is_required("203.0.113.6"));
+
+        guard.clear_failures("203.0.113.5");
+        assert!(!guard.is_required("203.0.113.5"));
+
+        for _ in 0..3 {
+            guard.record_failure("unknown");
+        }
+        assert!(!guard.is_required("unknown"));
+    }
+
+    #[test]
+    fn test_zero_threshold_always_requires() {
+        assert!(guard(0).is_required("203.0.113.5"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_is_rejected() {
+        assert!(matches!(
+            guard(3).verify("", "203.0.113.5").await,
+            Err(AuthError::CaptchaRequired)
+        ));
+    }
+
+    #[test]
+    fn test_widget_html() {
+        let html = guard(3).widget_html();
+        assert!(html.contains(r#"class="cf-turnstile mb-3""#));
+        assert!(html.contains(r#"data-sitekey="site-key""#));
+    }
+}
@@ -40,6 +40,29 @@ pub struct AuthConfig {
     /// Use this to set up the first administrator who can then grant roles to others
     #[serde(default)]
     pub bootstrap_admins: Vec<String>,
+
+    /// Bootstrap editor emails - users with these emails automatically get
+    /// the Editor role when their account is created
+    #[serde(default)]
+    pub bootstrap_editors: Vec<String>,
+
+    /// Roles given to new users whose email matches a pattern, e.g. everyone
+    /// signing in with a company address
+    #[serde(default)]
+    pub bootstrap_roles: Vec<BootstrapRoleRule>,
+
+    /// CAPTCHA challenge on sign-in after repeated failures
+    #[serde(default)]
+    pub captcha: CaptchaConfig,
+
+    /// Per-account lockout after repeated failed sign-ins
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+
+    /// JSON Schema of the custom user profile fields; without one profiles
+    /// can't be changed
+    #[serde(default)]
+    pub profile_schema: Option<serde_json::Value>,
 }
 
 impl AuthConfig {
@@ -106,6 +129,14 @@ impl AuthConfig {
         // Validate provider configs
         self.providers.validate()?;
 
+        self.captcha.validate()?;
+
+        self.lockout.validate()?;
+
+        for (index, rule) in self.bootstrap_roles.iter().enumerate() {
+            rule.validate(index)?;
+        }
+
         Ok(())
     }
 
@@ -135,10 +166,80 @@ impl Default for AuthConfig {
             providers: ProvidersConfig::default(),
             enabled: true,
             bootstrap_admins: Vec::new(),
+            bootstrap_editors: Vec::new(),
+            bootstrap_roles: Vec::new(),
+            captcha: CaptchaConfig::default(),
+            lockout: LockoutConfig::default(),
+            profile_schema: None,
         }
     }
 }
 
+/// Roles given to new users whose email matches `pattern`
+/// (`[[auth.bootstrap_roles]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapRoleRule {
+    /// Email address, case-insensitive; `*` matches any characters and a
+    /// pattern starting with `@` matches every address of the domain
+    pub pattern: String,
+
+    /// Roles to grant: "Editor" and/or "Administrator"
+    pub roles: Vec<crate::user_repository::UserRole>,
+}
+
+impl BootstrapRoleRule {
+    fn validate(&self, index: usize) -> Result<(), AuthError> {
+        if self.pattern.trim().is_empty() {
+            return Err(AuthError::InvalidConfig {
+                key: format!("bootstrap_roles[{}].pattern", index),
+                reason: "cannot be empty".to_string(),
+            });
+        }
+
+        if self.roles.is_empty() {
+            return Err(AuthError::InvalidConfig {
+                key: format!("bootstrap_roles[{}].roles", index),
+                reason: "must list at least one role".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether the rule applies to `email`
+    pub fn matches(&self, email: &str) -> bool {
+        let pattern = self.pattern.trim().to_lowercase();
+        let pattern = match pattern.strip_prefix('@') {
+            Some(domain) => format!("*@{}", domain),
+            None => pattern,
+        };
+        wildcard_match(pattern.as_bytes(), email.trim().to_lowercase().as_bytes())
+    }
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
 /// Cookie configuration for session management
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CookieConfig {
@@ -200,6 +301,172 @@ pub enum SameSitePolicy {
     None,
 }
 
+/// CAPTCHA configuration for the sign-in endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptchaConfig {
+    /// Off by default
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+
+    #[serde(default = "default_captcha_provider")]
+    pub provider: CaptchaProvider,
+
+    /// Public site key rendered into the sign-in page
+    #[serde(default)]
+    pub site_key: String,
+
+    /// Secret key used to verify responses; set it through the
+    /// APP_AUTH__CAPTCHA__SECRET_KEY environment variable
+    #[serde(default)]
+    pub secret_key: String,
+
+    /// Failed sign-ins from one IP address after which a CAPTCHA is required
+    /// (0 = always required)
+    #[serde(default = "default_captcha_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Window in which failed sign-ins are counted, in seconds
+    #[serde(default = "default_captcha_failure_window")]
+    pub failure_window_secs: u64,
+
+    /// Longest a verification request may take, in milliseconds
+    #[serde(default = "default_captcha_timeout")]
+    pub timeout_ms: u64,
+}
+
+impl CaptchaConfig {
+    fn validate(&self) -> Result<(), AuthError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.site_key.is_empty() {
+            return Err(AuthError::InvalidConfig {
+                key: "captcha.site_key".to_string(),
+                reason: "cannot be empty when CAPTCHA is enabled".to_string(),
+            });
+        }
+
+        if self.secret_key.is_empty() {
+            return Err(AuthError::InvalidConfig {
+                key: "captcha.secret_key".to_string(),
+                reason: "cannot be empty when CAPTCHA is enabled".to_string(),
+            });
+        }
+
+        if self.failure_window_secs == 0 {
+            return Err(AuthError::InvalidConfig {
+                key: "captcha.failure_window_secs".to_string(),
+                reason: "must be at least 1 second".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CaptchaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: default_captcha_provider(),
+            site_key: String::new(),
+            secret_key: String::new(),
+            failure_threshold: default_captcha_failure_threshold(),
+            failure_window_secs: default_captcha_failure_window(),
+            timeout_ms: default_captcha_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaProvider {
+    /// Cloudflare Turnstile
+    Turnstile,
+    Hcaptcha,
+}
+
+/// Account lockout configuration for the sign-in endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockoutConfig {
+    /// Off by default
+    #[serde(default = "default_false")]
+    pub enabled: bool,
+
+    /// Failed sign-ins of one account after which it is locked
+    #[serde(default = "default_lockout_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Window in which failed sign-ins are counted, in seconds
+    #[serde(default = "default_lockout_failure_window")]
+    pub failure_window_secs: u64,
+
+    /// How long a locked account stays locked, in seconds
+    #[serde(default = "default_lockout_duration")]
+    pub lockout_duration_secs: u64,
+
+    /// Delay added to the response of the first failed sign-in, in
+    /// milliseconds; it doubles with each further failure in the window
+    #[serde(default = "default_lockout_delay_base")]
+    pub delay_base_ms: u64,
+
+    /// Longest delay added to a failed sign-in, in milliseconds
+    #[serde(default = "default_lockout_max_delay")]
+    pub max_delay_ms: u64,
+}
+
+impl LockoutConfig {
+    fn validate(&self) -> Result<(), AuthError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.failure_threshold == 0 {
+            return Err(AuthError::InvalidConfig {
+                key: "lockout.failure_threshold".to_string(),
+                reason: "must be at least 1".to_string(),
+            });
+        }
+
+        if self.failure_window_secs == 0 {
+            return Err(AuthError::InvalidConfig {
+                key: "lockout.failure_window_secs".to_string(),
+                reason: "must be at least 1 second".to_string(),
+            });
+        }
+
+        if self.lockout_duration_secs == 0 {
+            return Err(AuthError::InvalidConfig {
+                key: "lockout.lockout_duration_secs".to_string(),
+                reason: "must be at least 1 second".to_string(),
+            });
+        }
+
+        if self.max_delay_ms < self.delay_base_ms {
+            return Err(AuthError::InvalidConfig {
+                key: "lockout.max_delay_ms".to_string(),
+                reason: "must be greater than or equal to delay_base_ms".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: default_lockout_failure_threshold(),
+            failure_window_secs: default_lockout_failure_window(),
+            lockout_duration_secs: default_lockout_duration(),
+            delay_base_ms: default_lockout_delay_base(),
+            max_delay_ms: default_lockout_max_delay(),
+        }
+    }
+}
+
 /// OAuth2 providers configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProvidersConfig {
@@ -392,6 +659,42 @@ fn default_cookie_path() -> String {
     "/".to_string()
 }
 
+fn default_captcha_provider() -> CaptchaProvider {
+    CaptchaProvider::Turnstile
+}
+
+fn default_captcha_failure_threshold() -> u32 {
+    3
+}
+
+fn default_captcha_failure_window() -> u64 {
+    900 // 15 minutes
+}
+
+fn default_captcha_timeout() -> u64 {
+    5000
+}
+
+fn default_lockout_failure_threshold() -> u32 {
+    5
+}
+
+fn default_lockout_failure_window() -> u64 {
+    900 // 15 minutes
+}
+
+fn default_lockout_duration() -> u64 {
+    900 // 15 minutes
+}
+
+fn default_lockout_delay_base() -> u64 {
+    500
+}
+
+fn default_lockout_max_delay() -> u64 {
+    8000
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -407,9 +710,82 @@ mod tests {
             providers: ProvidersConfig::default(),
             enabled: true,
             bootstrap_admins: Vec::new(),
+            bootstrap_editors: Vec::new(),
+            bootstrap_roles: Vec::new(),
+            captcha: CaptchaConfig::default(),
+            lockout: LockoutConfig::default(),
+            profile_schema: None,
+        };
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_bootstrap_role_rules() {
+        let rule = |pattern: &str| BootstrapRoleRule {
+            pattern: pattern.to_string(),
+            roles: vec![crate::user_repository::UserRole::Editor],
+        };
+
+        assert!(rule("alice@example.com").matches("Alice@Example.com"));
+        assert!(!rule("alice@example.com").matches("bob@example.com"));
+        assert!(rule("@example.com").matches("bob@example.com"));
+        assert!(!rule("@example.com").matches("bob@example.com.evil.org"));
+        assert!(!rule("@example.com").matches("bob@notexample.com"));
+        assert!(rule("*@*.example.com").matches("bob@eu.example.com"));
+        assert!(!rule("*@*.example.com").matches("bob@example.com"));
+        assert!(rule("dev-*@example.com").matches("dev-alice@example.com"));
+        assert!(rule("*").matches("anyone@anywhere.org"));
+
+        let mut config = AuthConfig {
+            jwt_secret: "a".repeat(32),
+            ..Default::default()
+        };
+        config.bootstrap_roles.push(rule("@example.com"));
+        assert!(config.validate().is_ok());
+
+        config.bootstrap_roles.push(rule(" "));
+        assert!(config.validate().is_err());
+
+        config.bootstrap_roles[1] = BootstrapRoleRule {
+            pattern: "@example.org".to_string(),
+            roles: Vec::new(),
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_captcha_validation() {
+        let mut config = AuthConfig {
+            jwt_secret: "a".repeat(32),
+            ..Default::default()
         };
+        config.captcha.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.captcha.site_key = "site".to_string();
+        config.captcha.secret_key = "secret".to_string();
+        assert!(config.validate().is_ok());
+
+        config.captcha.failure_window_secs = 0;
+        assert!(config.validate().is_err());
+    }
 
+    #[test]
+    fn test_lockout_validation() {
+        let mut config = AuthConfig {
+            jwt_secret: "a".repeat(32),
+            ..Default::default()
+        };
+        config.lockout.enabled = true;
         assert!(config.validate().is_ok());
+
+        config.lockout.failure_threshold = 0;
+        assert!(config.validate().is_err());
+
+        config.lockout.failure_threshold = 5;
+        config.lockout.max_delay_ms = config.lockout.delay_base_ms - 1;
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -79,6 +79,15 @@ pub enum AuthError {
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
 
+    #[error("CAPTCHA verification required")]
+    CaptchaRequired,
+
+    #[error("CAPTCHA verification failed: {0}")]
+    CaptchaFailed(String),
+
+    #[error("Account locked until {0}")]
+    AccountLocked(chrono::DateTime<chrono::Utc>),
+
     #[error("Authentication required")]
     AuthenticationRequired,
 
@@ -130,7 +139,10 @@ impl AuthError {
             | AuthError::InvalidSessionCookie
             | AuthError::AuthenticationRequired => 401,
 
-            AuthError::InsufficientPermissions => 403,
+            AuthError::InsufficientPermissions
+            | AuthError::CaptchaRequired
+            | AuthError::CaptchaFailed(_)
+            | AuthError::AccountLocked(_) => 403,
 
             AuthError::RateLimitExceeded => 429,
 
@@ -159,6 +171,11 @@ mod tests {
         assert_eq!(AuthError::AuthenticationRequired.status_code(), 401);
         assert_eq!(AuthError::InsufficientPermissions.status_code(), 403);
         assert_eq!(AuthError::RateLimitExceeded.status_code(), 429);
+        assert_eq!(AuthError::CaptchaRequired.status_code(), 403);
+        assert_eq!(
+            AuthError::AccountLocked(chrono::Utc::now()).status_code(),
+            403
+        );
         assert_eq!(
             AuthError::ConfigError("test".to_string()).status_code(),
             500
@@ -68,6 +68,35 @@ impl JsAuthContext {
         }
     }
 
+    /// Custom profile fields of the user (see
+    /// [`crate::user_repository::UserProfile`]); None when anonymous or the
+    /// profile can't be read
+    pub fn profile(&self) -> Option<serde_json::Map<String, serde_json::Value>> {
+        if !self.is_authenticated {
+            return None;
+        }
+        let user_id = self.user_id.as_deref()?;
+        match crate::user_repository::get_user_profile(user_id) {
+            Ok(profile) => Some(profile.fields),
+            Err(e) => {
+                debug!("No profile for user {}: {}", user_id, e);
+                None
+            }
+        }
+    }
+
+    /// Names of the groups the user is a member of; empty when anonymous or
+    /// the groups can't be read
+    pub fn groups(&self) -> Vec<String> {
+        let Some(user_id) = self.user_id.as_deref().filter(|_| self.is_authenticated) else {
+            return Vec::new();
+        };
+        crate::user_repository::get_user_groups(user_id).unwrap_or_else(|e| {
+            debug!("No groups for user {}: {}", user_id, e);
+            Vec::new()
+        })
+    }
+
     /// Convert to UserContext for security checks
     pub fn to_user_context(&self) -> UserContext {
         if self.is_authenticated {
@@ -91,6 +120,9 @@ impl JsAuthContext {
 /// - `auth.userName` - User display name or null
 /// - `auth.provider` - OAuth provider name or null
 /// - `auth.user` - Complete user object or null
+/// - `auth.profile` - Custom profile fields, read on first access, or null
+/// - `auth.groups` - Names of the user's groups, read on first access
+/// - `auth.inGroup(name)` - Whether the user is a member of a group
 /// - `auth.requireAuth()` - Throw error if not authenticated
 pub struct AuthJsApi {
     #[allow(dead_code)]
@@ -185,6 +217,25 @@ impl AuthJsApi {
         // Set the implementation function
         auth_obj.set("__requireAuthImpl", require_auth_fn)?;
 
+        // Profiles are read from the repository, so only when a handler
+        // uses them
+        let profile_ctx = auth_context.clone();
+        let profile_fn = Function::new(
+            ctx.clone(),
+            move |_ctx: Ctx<'_>| -> JsResult<Option<String>> {
+                Ok(profile_ctx
+                    .profile()
+                    .and_then(|fields| serde_json::to_string(&fields).ok()))
+            },
+        )?;
+        auth_obj.set("__profileImpl", profile_fn)?;
+
+        let groups_ctx = auth_context.clone();
+        let groups_fn = Function::new(ctx.clone(), move |_ctx: Ctx<'_>| -> Vec<String> {
+            groups_ctx.groups()
+        })?;
+        auth_obj.set("__groupsImpl", groups_fn)?;
+
         // Now wrap in functions that parse JSON
         // We need to create a temporary global to run the eval, then remove it
         ctx.globals().set("__tempAuth", auth_obj.clone())?;
@@ -198,6 +249,30 @@ impl AuthJsApi {
                     throw new Error('Authentication required. Please login to access this resource.');
                 }
             };
+            (function(auth) {
+                let profile;
+                Object.defineProperty(auth, 'profile', {
+                    get: function() {
+                        if (profile === undefined) {
+                            const json = auth.__profileImpl();
+                            profile = json == null ? null : JSON.parse(json);
+                        }
+                        return profile;
+                    },
+                });
+                let groups;
+                Object.defineProperty(auth, 'groups', {
+                    get: function() {
+                        if (groups === undefined) {
+                            groups = auth.__groupsImpl();
+                        }
+                        return groups;
+                    },
+                });
+                auth.inGroup = function(name) {
+                    return this.groups.includes(name);
+                };
+            })(__tempAuth);
             "#
         )?;
 
@@ -318,6 +393,14 @@ mod tests {
             // Test userId is null
             let user_id_is_null: bool = ctx.eval("req.auth.userId === null").unwrap();
             assert!(user_id_is_null);
+
+            // Anonymous users have no profile and no groups
+            let profile_is_null: bool = ctx.eval("req.auth.profile === null").unwrap();
+            assert!(profile_is_null);
+            let group_count: i32 = ctx.eval("req.auth.groups.length").unwrap();
+            assert_eq!(group_count, 0);
+            let in_group: bool = ctx.eval("req.auth.inGroup('support')").unwrap();
+            assert!(!in_group);
         });
     }
 
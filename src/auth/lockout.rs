@@ -0,0 +1,186 @@
+// Account Lockout
+// Locks an account after repeated failed sign-ins
+//
+// Complements the per-IP rate limiter and CAPTCHA: failures are counted per
+// account, so guessing spread over many addresses still ends in a lockout.
+// Each failed sign-in within `failure_window_secs` is answered after a delay
+// that starts at `delay_base_ms` and doubles with each further failure, up to
+// `max_delay_ms`. The failure that reaches `failure_threshold` locks the
+// account for `lockout_duration_secs`, or until an administrator unlocks it.
+// The state is kept in the account_lockouts table, so every server enforces
+// the same lockout.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::config::LockoutConfig;
+use super::error::AuthError;
+use crate::user_repository::{self, AccountLockout};
+
+/// Enforces the account lockout configuration
+pub struct LockoutPolicy {
+    config: LockoutConfig,
+}
+
+impl LockoutPolicy {
+    /// Create a policy from the lockout configuration
+    pub fn new(config: LockoutConfig) -> Self {
+        Self { config }
+    }
+
+    /// The lockout state of an account after one more failed sign-in at `now`
+    pub fn next_state(
+        &self,
+        user_id: &str,
+        current: Option<AccountLockout>,
+        now: DateTime<Utc>,
+    ) -> AccountLockout {
+        let window = chrono::Duration::seconds(self.config.failure_window_secs as i64);
+        // An expired lockout starts a new window
+        let (failed_attempts, window_started_at) = match current {
+            Some(current)
+                if now - current.window_started_at < window
+                    && current.locked_until.is_none_or(|until| until > now) =>
+            {
+                (
+                    current.failed_attempts.saturating_add(1),
+                    current.window_started_at,
+                )
+            }
+            _ => (1, now),
+        };
+
+        let locked_until = (failed_attempts >= self.config.failure_threshold)
+            .then(|| now + chrono::Duration::seconds(self.config.lockout_duration_secs as i64));
+
+        AccountLockout {
+            user_id: user_id.to_string(),
+            failed_attempts,
+            window_started_at,
+            locked_until,
+        }
+    }
+
+    /// How long to delay the answer to the `failed_attempts`th failed sign-in
+    pub fn delay(&self, failed_attempts: u32) -> Duration {
+        if failed_attempts == 0 {
+            return Duration::ZERO;
+        }
+        let factor = 1u64.checked_shl(failed_attempts - 1).unwrap_or(u64::MAX);
+        Duration::from_millis(
+            self.config
+                .delay_base_ms
+                .saturating_mul(factor)
+                .min(self.config.max_delay_ms),
+        )
+    }
+
+    /// Fail with `AccountLocked` while the account is locked. A lockout that
+    /// can't be read does not block the sign-in.
+    pub async fn check(&self, user_id: &str) -> Result<(), AuthError> {
+        match user_repository::get_account_lockout(user_id).await {
+            Ok(Some(AccountLockout {
+                locked_until: Some(until),
+                ..
+            })) if until > Utc::now() => Err(AuthError::AccountLocked(until)),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Failed to read lockout of account {}: {}", user_id, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Count a failed sign-in of an account and return its new state
+    pub async fn record_failure(&self, user_id: &str) -> Result<AccountLockout, AuthError> {
+        user_repository::update_account_lockout(user_id, |current| {
+            self.next_state(user_id, current, Utc::now())
+        })
+        .await
+        .map_err(|e| AuthError::Internal(format!("Failed to record failed sign-in: {}", e)))
+    }
+
+    /// Forget the failed sign-ins of an account after a successful sign-in
+    pub async fn clear(&self, user_id: &str) {
+        if let Err(e) = user_repository::clear_account_lockout_async(user_id).await {
+            tracing::warn!("Failed to clear lockout of account {}: {}", user_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy::new(LockoutConfig {
+            enabled: true,
+            failure_threshold: 3,
+            failure_window_secs: 900,
+            lockout_duration_secs: 600,
+            delay_base_ms: 500,
+            max_delay_ms: 4000,
+        })
+    }
+
+    #[test]
+    fn test_locks_at_threshold() {
+        let policy = policy();
+        let now = Utc::now();
+
+        let first = policy.next_state("user-1", None, now);
+        assert_eq!(first.failed_attempts, 1);
+        assert!(first.locked_until.is_none());
+
+        let second = policy.next_state("user-1", Some(first), now);
+        assert!(second.locked_until.is_none());
+
+        let third = policy.next_state("user-1", Some(second), now);
+        assert_eq!(third.failed_attempts, 3);
+        assert_eq!(
+            third.locked_until,
+            Some(now + chrono::Duration::seconds(600))
+        );
+    }
+
+    #[test]
+    fn test_window_and_expired_lockout_restart_count() {
+        let policy = policy();
+        let now = Utc::now();
+
+        let stale = AccountLockout {
+            user_id: "user-1".to_string(),
+            failed_attempts: 2,
+            window_started_at: now - chrono::Duration::seconds(901),
+            locked_until: None,
+        };
+        assert_eq!(
+            policy
+                .next_state("user-1", Some(stale), now)
+                .failed_attempts,
+            1
+        );
+
+        let expired = AccountLockout {
+            user_id: "user-1".to_string(),
+            failed_attempts: 3,
+            window_started_at: now - chrono::Duration::seconds(60),
+            locked_until: Some(now - chrono::Duration::seconds(1)),
+        };
+        let next = policy.next_state("user-1", Some(expired), now);
+        assert_eq!(next.failed_attempts, 1);
+        assert!(next.locked_until.is_none());
+    }
+
+    #[test]
+    fn test_progressive_delay() {
+        let policy = policy();
+        assert_eq!(policy.delay(0), Duration::ZERO);
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(2), Duration::from_millis(1000));
+        assert_eq!(policy.delay(3), Duration::from_millis(2000));
+        assert_eq!(policy.delay(5), Duration::from_millis(4000));
+        assert_eq!(policy.delay(200), Duration::from_millis(4000));
+    }
+}
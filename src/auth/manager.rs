@@ -3,8 +3,8 @@
 /// Central orchestrator for authentication operations, coordinating providers,
 /// sessions, and security infrastructure.
 use crate::auth::{
-    AuthError, AuthSecurityContext, AuthSessionManager, OAuth2Provider, OAuth2ProviderConfig,
-    OAuth2TokenResponse, OAuth2UserInfo, ProviderFactory,
+    AuthError, AuthSecurityContext, AuthSessionManager, CaptchaGuard, LockoutPolicy,
+    OAuth2Provider, OAuth2ProviderConfig, OAuth2TokenResponse, OAuth2UserInfo, ProviderFactory,
 };
 use chrono::Utc;
 use std::collections::HashMap;
@@ -86,6 +86,8 @@ pub struct AuthManager {
     session_manager: Arc<AuthSessionManager>,
     security_context: Arc<AuthSecurityContext>,
     api_key: Option<String>,
+    captcha: Option<Arc<CaptchaGuard>>,
+    lockout: Option<Arc<LockoutPolicy>>,
 }
 
 const SESSION_REFRESH_WINDOW_SECONDS: i64 = 300;
@@ -104,9 +106,105 @@ impl AuthManager {
             session_manager,
             security_context,
             api_key,
+            captcha: None,
+            lockout: None,
         }
     }
 
+    /// Require a CAPTCHA for sign-ins from IP addresses with repeated
+    /// failures
+    pub fn with_captcha(mut self, captcha: CaptchaGuard) -> Self {
+        self.captcha = Some(Arc::new(captcha));
+        self
+    }
+
+    /// CAPTCHA guard, if CAPTCHA is enabled
+    pub fn captcha(&self) -> Option<Arc<CaptchaGuard>> {
+        self.captcha.clone()
+    }
+
+    /// Check the CAPTCHA response of a sign-in from `ip_addr`, when one is
+    /// required. A rejected response counts as a failed sign-in.
+    pub async fn check_captcha(
+        &self,
+        ip_addr: &str,
+        response: Option<&str>,
+    ) -> Result<(), AuthError> {
+        let Some(captcha) = &self.captcha else {
+            return Ok(());
+        };
+        if !captcha.is_required(ip_addr) {
+            return Ok(());
+        }
+
+        let result = match response {
+            Some(response) => captcha.verify(response, ip_addr).await,
+            None => Err(AuthError::CaptchaRequired),
+        };
+        if let Err(e) = &result
+            && !matches!(e, AuthError::CaptchaRequired)
+        {
+            captcha.record_failure(ip_addr);
+            self.security_context
+                .log_auth_failure("captcha", &e.to_string(), Some(ip_addr))
+                .await;
+        }
+        result
+    }
+
+    /// Lock accounts after repeated failed sign-ins
+    pub fn with_lockout(mut self, lockout: LockoutPolicy) -> Self {
+        self.lockout = Some(Arc::new(lockout));
+        self
+    }
+
+    /// Refuse the sign-in of a locked account, when lockout is enabled
+    pub async fn check_account_lockout(
+        &self,
+        user_id: &str,
+        provider_name: &str,
+        ip_addr: &str,
+    ) -> Result<(), AuthError> {
+        let Some(lockout) = &self.lockout else {
+            return Ok(());
+        };
+        let result = lockout.check(user_id).await;
+        if result.is_err() {
+            self.security_context
+                .log_locked_account_attempt(user_id, provider_name, Some(ip_addr))
+                .await;
+        }
+        result
+    }
+
+    /// Count a failed sign-in of a known account. The failure that reaches
+    /// the threshold locks the account, and the response is delayed longer
+    /// with each failure.
+    pub async fn record_account_failure(&self, user_id: &str, ip_addr: &str) {
+        let Some(lockout) = &self.lockout else {
+            return;
+        };
+        let state = match lockout.record_failure(user_id).await {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::warn!("{}", e);
+                return;
+            }
+        };
+        if let Some(locked_until) = state.locked_until {
+            tracing::warn!(
+                "Account {} locked until {} after {} failed sign-ins",
+                user_id,
+                locked_until,
+                state.failed_attempts
+            );
+            self.security_context
+                .log_account_locked(user_id, state.failed_attempts, locked_until, Some(ip_addr))
+                .await;
+        }
+        tokio::time::sleep(lockout.delay(state.failed_attempts)).await;
+    }
+
     /// Register an OAuth2 provider
     pub fn register_provider(
         &mut self,
@@ -223,6 +321,28 @@ impl AuthManager {
         state: &str,
         ip_addr: &str,
         user_agent: &str,
+    ) -> Result<String, AuthError> {
+        let result = self
+            .complete_callback(provider_name, code, state, ip_addr, user_agent)
+            .await;
+
+        // Repeated failures make the IP address solve a CAPTCHA
+        if let Some(captcha) = &self.captcha {
+            match &result {
+                Ok(_) => captcha.clear_failures(ip_addr),
+                Err(_) => captcha.record_failure(ip_addr),
+            }
+        }
+        result
+    }
+
+    async fn complete_callback(
+        &self,
+        provider_name: &str,
+        code: &str,
+        state: &str,
+        ip_addr: &str,
+        user_agent: &str,
     ) -> Result<String, AuthError> {
         // Validate CSRF state
         if !self
@@ -282,11 +402,33 @@ impl AuthManager {
                 e
             })?;
 
+        // Locked accounts can't sign in. Accounts are only known once the
+        // provider has identified the user, so first sign-ins are never locked.
+        let existing_user_id = if self.lockout.is_some() {
+            crate::user_repository::find_user_by_provider_async(
+                provider_name,
+                &user_info.provider_user_id,
+            )
+            .await
+            .ok()
+            .flatten()
+            .map(|user| user.id)
+        } else {
+            None
+        };
+        if let Some(user_id) = &existing_user_id {
+            self.check_account_lockout(user_id, provider_name, ip_addr)
+                .await?;
+        }
+
         // Verify email if required
         if !user_info.email_verified {
             self.security_context
                 .log_auth_failure(provider_name, "Email not verified", Some(ip_addr))
                 .await;
+            if let Some(user_id) = &existing_user_id {
+                self.record_account_failure(user_id, ip_addr).await;
+            }
             return Err(AuthError::ProviderError(
                 "Email not verified by provider".to_string(),
             ));
@@ -313,15 +455,22 @@ impl AuthManager {
                 AuthError::Internal("User not found after creation".to_string())
             })?;
 
+        // Roles of the user's groups add to their own
+        let group_roles = crate::user_repository::get_group_roles_async(&user_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to get group roles of {}: {}", user_id, e);
+                Vec::new()
+            });
+        let has_role = |role: &crate::user_repository::UserRole| {
+            user.roles.contains(role) || group_roles.contains(role)
+        };
+
         // Check if user has Administrator role
-        let is_admin = user
-            .roles
-            .contains(&crate::user_repository::UserRole::Administrator);
+        let is_admin = has_role(&crate::user_repository::UserRole::Administrator);
 
         // Check if user has Editor role
-        let is_editor = user
-            .roles
-            .contains(&crate::user_repository::UserRole::Editor);
+        let is_editor = has_role(&crate::user_repository::UserRole::Editor);
 
         // Create session with correct admin and editor status
         let session_token = self
@@ -345,6 +494,10 @@ impl AuthManager {
             .log_auth_success(&user_id, provider_name, Some(ip_addr))
             .await;
 
+        if let Some(lockout) = &self.lockout {
+            lockout.clear(&user_id).await;
+        }
+
         Ok(session_token.token)
     }
 
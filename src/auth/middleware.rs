@@ -56,6 +56,55 @@ impl AuthUser {
             name,
         }
     }
+
+    /// The user as seen by scripts (`context.request.auth`)
+    pub fn js_auth_context(&self) -> crate::auth::JsAuthContext {
+        crate::auth::JsAuthContext::authenticated(
+            self.user_id.clone(),
+            self.email.clone(),
+            self.name.clone(),
+            self.provider.clone(),
+            self.is_admin,
+            self.is_editor,
+        )
+    }
+
+    /// Security context the user's requests run scripts with
+    pub fn user_context(&self) -> crate::security::UserContext {
+        if self.is_admin {
+            crate::security::UserContext::admin(self.user_id.clone())
+        } else if self.is_editor {
+            crate::security::UserContext::editor(self.user_id.clone())
+        } else {
+            crate::security::UserContext::authenticated(self.user_id.clone())
+        }
+    }
+}
+
+/// Sign-in requirement of a script route (`auth` option of
+/// `routeRegistry.registerRoute`). Checked before the handler runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RouteAuth {
+    /// Groups whose members may use the route; empty admits any signed-in
+    /// user. Administrators may use every route.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+impl RouteAuth {
+    /// Whether a signed-in user may use the route
+    pub async fn allows(&self, user: &AuthUser) -> bool {
+        if self.groups.is_empty() || user.is_admin {
+            return true;
+        }
+        match crate::user_repository::get_user_groups_async(&user.user_id).await {
+            Ok(groups) => groups.iter().any(|group| self.groups.contains(group)),
+            Err(e) => {
+                tracing::warn!("Failed to get groups of {}: {}", user.user_id, e);
+                false
+            }
+        }
+    }
 }
 
 /// Extract session token from request cookies or Authorization header
@@ -517,6 +566,28 @@ mod tests {
         assert_eq!(ua, "Mozilla/5.0 Test Browser");
     }
 
+    #[tokio::test]
+    async fn test_route_auth_without_groups() {
+        let user = |is_admin| {
+            AuthUser::new(
+                "user-1".to_string(),
+                "google".to_string(),
+                "token".to_string(),
+                is_admin,
+                false,
+                None,
+                None,
+            )
+        };
+        assert!(RouteAuth::default().allows(&user(false)).await);
+
+        // Administrators pass group requirements without a lookup
+        let support = RouteAuth {
+            groups: vec!["support".to_string()],
+        };
+        assert!(support.allows(&user(true)).await);
+    }
+
     #[test]
     fn test_extract_user_agent_missing() {
         let req = Request::builder().body(Body::empty()).unwrap();
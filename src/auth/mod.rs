@@ -1,10 +1,12 @@
 // Authentication Module
 // Provides OAuth2/OIDC authentication with session management
 
+pub mod captcha;
 pub mod client_registration;
 pub mod config;
 pub mod error;
 pub mod js_api;
+pub mod lockout;
 pub mod manager;
 pub mod mcp_middleware;
 pub mod metadata;
@@ -15,13 +17,18 @@ pub mod routes;
 pub mod security;
 pub mod session;
 
+pub use captcha::CaptchaGuard;
 pub use client_registration::{
     ClientRegistrationManager, ClientRegistrationRequest, ClientRegistrationResponse,
     RegisteredClient, RegisteredClientMetadata,
 };
-pub use config::{AuthConfig, CookieConfig, ProviderConfig, ProvidersConfig, SameSitePolicy};
+pub use config::{
+    AuthConfig, BootstrapRoleRule, CaptchaConfig, CaptchaProvider, CookieConfig, LockoutConfig,
+    ProviderConfig, ProvidersConfig, SameSitePolicy,
+};
 pub use error::AuthError;
 pub use js_api::{AuthJsApi, JsAuthContext};
+pub use lockout::LockoutPolicy;
 pub use manager::{AuthManager, AuthManagerConfig, AuthenticatedUser, CookieSameSite};
 pub use mcp_middleware::{
     McpAuthSession, mcp_auth_middleware, mcp_require_admin_middleware,
@@ -29,7 +36,7 @@ pub use mcp_middleware::{
 };
 pub use metadata::{AuthorizationServerMetadata, MetadataConfig};
 pub use middleware::{
-    AuthUser, AuthenticatedUser as AuthUserExtractor, optional_auth_middleware,
+    AuthUser, AuthenticatedUser as AuthUserExtractor, RouteAuth, optional_auth_middleware,
     redirect_to_login_middleware, require_editor_or_admin_middleware, required_auth_middleware,
 };
 pub use pkce::{PkcePair, generate_code_challenge, generate_code_verifier};
@@ -41,6 +41,14 @@ pub struct LoginParams {
     /// Optional redirect URL after successful login
     #[allow(dead_code)]
     redirect: Option<String>,
+
+    /// CAPTCHA response set by the Turnstile widget
+    #[serde(rename = "cf-turnstile-response")]
+    turnstile_response: Option<String>,
+
+    /// CAPTCHA response set by the hCaptcha widget
+    #[serde(rename = "h-captcha-response")]
+    hcaptcha_response: Option<String>,
 }
 
 /// Logout parameters
@@ -158,11 +166,64 @@ pub struct LoginPageParams {
 pub async fn login_page(
     State(auth_manager): State<Arc<AuthManager>>,
     Query(params): Query<LoginPageParams>,
+    headers: HeaderMap,
 ) -> Html<String> {
-    let providers = auth_manager.list_providers();
     let redirect_param = params.redirect.unwrap_or_else(|| "/".to_string());
     let encoded_redirect = urlencoding::encode(&redirect_param);
 
+    // After repeated failures from this address the providers are buttons
+    // of a form that carries the CAPTCHA response
+    let ip_addr = get_client_ip(&headers);
+    let captcha = auth_manager
+        .captcha()
+        .filter(|captcha| captcha.is_required(&ip_addr));
+
+    let mut sorted_providers = auth_manager.list_providers();
+    sorted_providers.sort();
+    let provider_links = sorted_providers
+        .iter()
+        .map(|p| {
+            let label = match p.as_str() {
+                "google" => "Sign in with Google",
+                "microsoft" => "Sign in with Microsoft",
+                "apple" => "Sign in with Apple",
+                _ => "Sign in",
+            };
+            if captcha.is_some() {
+                format!(
+                    r#"<button type="submit" formaction="/auth/login/{}" class="btn btn-block provider-btn provider-{} mb-2">{}</button>"#,
+                    p.to_lowercase(),
+                    p.to_lowercase(),
+                    label
+                )
+            } else {
+                format!(
+                    r#"<a href="/auth/login/{}?redirect={}" class="btn btn-block provider-btn provider-{} mb-2">{}</a>"#,
+                    p.to_lowercase(),
+                    encoded_redirect,
+                    p.to_lowercase(),
+                    label
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n                                ");
+
+    let providers_html = match &captcha {
+        Some(captcha) => format!(
+            r#"<p class="text-muted mb-3">Complete the challenge below to continue.</p>
+                                <form method="get">
+                                <input type="hidden" name="redirect" value="{}">
+                                {}
+                                {}
+                                </form>"#,
+            html_escape::encode_double_quoted_attribute(&redirect_param),
+            captcha.widget_html(),
+            provider_links
+        ),
+        None => provider_links,
+    };
+
     let html = format!(
         r#"<!DOCTYPE html>
 <html lang="en">
@@ -193,26 +254,7 @@ pub async fn login_page(
     </div>
 </body>
 </html>"#,
-        {
-            let mut sorted_providers = providers.clone();
-            sorted_providers.sort();
-            sorted_providers
-                .iter()
-                .map(|p| format!(
-                    r#"<a href="/auth/login/{}?redirect={}" class="btn btn-block provider-btn provider-{} mb-2">{}</a>"#,
-                    p.to_lowercase(),
-                    encoded_redirect,
-                    p.to_lowercase(),
-                    match p.as_str() {
-                        "google" => "Sign in with Google",
-                        "microsoft" => "Sign in with Microsoft",
-                        "apple" => "Sign in with Apple",
-                        _ => "Sign in",
-                    }
-                ))
-                .collect::<Vec<_>>()
-                .join("\n                                ")
-        }
+        providers_html
     );
 
     Html(html)
@@ -225,10 +267,12 @@ pub async fn login_page(
     tags = ["Authentication"],
     params(
         ("provider" = String, Path, description = "OAuth provider name (google, microsoft, apple)"),
-        ("redirect" = Option<String>, Query, description = "Redirect URL after successful login")
+        ("redirect" = Option<String>, Query, description = "Redirect URL after successful login"),
+        ("cf-turnstile-response" = Option<String>, Query, description = "Turnstile response, when a CAPTCHA is required"),
+        ("h-captcha-response" = Option<String>, Query, description = "hCaptcha response, when a CAPTCHA is required")
     ),
     responses(
-        (status = 302, description = "Redirect to OAuth provider for authentication"),
+        (status = 302, description = "Redirect to OAuth provider for authentication, or back to the login page when a CAPTCHA is required"),
         (status = 400, description = "Invalid request", body = crate::openapi_schemas::ErrorResponse),
     )
 )]
@@ -240,6 +284,21 @@ pub async fn start_login(
 ) -> Result<Redirect, ErrorResponse> {
     let ip_addr = get_client_ip(&headers);
 
+    // Send addresses that must solve a CAPTCHA back to the login page, which
+    // shows the challenge
+    let captcha_response = params
+        .turnstile_response
+        .as_deref()
+        .or(params.hcaptcha_response.as_deref());
+    if let Err(e) = auth_manager.check_captcha(&ip_addr, captcha_response).await {
+        tracing::info!("Sign-in from {} needs a CAPTCHA: {}", ip_addr, e);
+        let redirect = params.redirect.as_deref().unwrap_or("/");
+        return Ok(Redirect::to(&format!(
+            "/auth/login?redirect={}",
+            urlencoding::encode(redirect)
+        )));
+    }
+
     // Generate authorization URL with or without redirect
     let (auth_url, _state) = if let Some(ref redirect_url) = params.redirect {
         tracing::info!("Starting login with redirect URL: {}", redirect_url);
@@ -1042,6 +1101,25 @@ pub async fn oauth2_token(
             .into_response();
     }
 
+    let ip_addr = extract_client_ip_from_headers(&headers);
+    let user_agent = extract_user_agent_from_headers(&headers);
+
+    // Locked accounts can't exchange codes issued before they were locked
+    if let Err(e) = oauth2_state
+        .auth_manager
+        .check_account_lockout(&code_data.user_id, "oauth2", &ip_addr)
+        .await
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "invalid_grant".to_string(),
+                message: e.to_string(),
+            }),
+        )
+            .into_response();
+    }
+
     // Verify PKCE code_verifier if code_challenge was provided
     if let Some(ref challenge) = code_data.code_challenge {
         match params.code_verifier.as_ref() {
@@ -1059,6 +1137,10 @@ pub async fn oauth2_token(
                     };
 
                 if &computed_challenge != challenge {
+                    oauth2_state
+                        .auth_manager
+                        .record_account_failure(&code_data.user_id, &ip_addr)
+                        .await;
                     return (
                         StatusCode::BAD_REQUEST,
                         Json(ErrorResponse {
@@ -1083,9 +1165,6 @@ pub async fn oauth2_token(
     }
 
     // Create a session for the user
-    let ip_addr = extract_client_ip_from_headers(&headers);
-    let user_agent = extract_user_agent_from_headers(&headers);
-
     // Get user info from the user repository to get email, name, is_admin, is_editor
     // For now, we'll use defaults since we don't have direct access to user repo here
     // In production, pass user repository or fetch this info during code storage
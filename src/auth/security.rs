@@ -177,6 +177,52 @@ impl AuthSecurityContext {
         self.auditor.log_event(event).await;
     }
 
+    /// Log a sign-in refused because the account is locked
+    pub async fn log_locked_account_attempt(
+        &self,
+        user_id: &str,
+        provider: &str,
+        ip_addr: Option<&str>,
+    ) {
+        let mut event = SecurityEvent::new(
+            SecurityEventType::AuthenticationFailure,
+            SecuritySeverity::Medium,
+            Some(user_id.to_string()),
+        )
+        .with_detail("provider", provider)
+        .with_error("Account locked".to_string());
+
+        if let Some(ip) = ip_addr {
+            event = event.with_detail("ip_address", ip);
+        }
+
+        self.auditor.log_event(event).await;
+    }
+
+    /// Log an account being locked after repeated failed sign-ins
+    pub async fn log_account_locked(
+        &self,
+        user_id: &str,
+        failed_attempts: u32,
+        locked_until: chrono::DateTime<chrono::Utc>,
+        ip_addr: Option<&str>,
+    ) {
+        let mut event = SecurityEvent::new(
+            SecurityEventType::AuthenticationFailure,
+            SecuritySeverity::High,
+            Some(user_id.to_string()),
+        )
+        .with_action("account_locked".to_string())
+        .with_detail("failed_attempts", failed_attempts)
+        .with_detail("locked_until", locked_until.to_rfc3339());
+
+        if let Some(ip) = ip_addr {
+            event = event.with_detail("ip_address", ip);
+        }
+
+        self.auditor.log_event(event).await;
+    }
+
     /// Log suspicious activity
     pub async fn log_suspicious_activity(&self, description: &str, user_id: Option<&str>) {
         self.auditor
@@ -0,0 +1,191 @@
+//! What this instance is running, served at `/engine/version` and by the
+//! `engineVersion` GraphQL query.
+//!
+//! Build details (crate version, git commit, timestamps) are compiled in by
+//! `build.rs`. Features are the optional subsystems switched on in the
+//! configuration, and the script counts describe what this instance has
+//! loaded, so operators can compare instances behind a load balancer.
+
+use std::sync::{OnceLock, RwLock};
+
+use serde::Serialize;
+
+use crate::config::AppConfig;
+use crate::repository::Repository as _;
+
+static FEATURES: OnceLock<RwLock<Vec<&'static str>>> = OnceLock::new();
+
+fn features() -> &'static RwLock<Vec<&'static str>> {
+    FEATURES.get_or_init(Default::default)
+}
+
+/// Record the features enabled in `config`. Called once at server startup.
+pub fn configure(config: &AppConfig) {
+    let enabled = enabled_features(config);
+    match features().write() {
+        Ok(mut guard) => *guard = enabled,
+        Err(poisoned) => *poisoned.into_inner() = enabled,
+    }
+}
+
+fn current_features() -> Vec<&'static str> {
+    match features().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Names of the optional subsystems enabled in `config`, sorted
+fn enabled_features(config: &AppConfig) -> Vec<&'static str> {
+    let security = &config.security;
+    let mut enabled: Vec<&'static str> = [
+        (
+            "auth",
+            config.auth.as_ref().is_some_and(|auth| auth.enabled),
+        ),
+        ("tenancy", config.tenancy.enabled),
+        ("metering", config.metering.enabled),
+        ("cdn", config.cdn.enabled),
+        ("debugger", config.javascript.enable_debugger),
+        (
+            "compilation_cache",
+            config.javascript.enable_compilation_cache,
+        ),
+        ("compression", config.performance.enable_compression),
+        ("response_cache", config.performance.enable_response_cache),
+        ("metrics", config.performance.enable_metrics),
+        ("audit_export", security.audit_export.enabled),
+        ("threat_response", security.threat_response.enabled),
+        ("script_manifests", security.script_manifests.enabled),
+        ("script_quarantine", security.script_quarantine.enabled),
+        (
+            "secret_encryption",
+            security.secret_encryption_key.is_some(),
+        ),
+        ("development_mode", security.development_mode),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect();
+    enabled.sort_unstable();
+    enabled
+}
+
+/// Build details compiled into the binary
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildDetails {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub git_commit_timestamp: &'static str,
+    pub build_timestamp: &'static str,
+    /// `debug` or `release`
+    pub profile: &'static str,
+}
+
+pub fn build_details() -> BuildDetails {
+    BuildDetails {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("VERGEN_GIT_SHA").unwrap_or(""),
+        git_commit_timestamp: option_env!("VERGEN_GIT_COMMIT_TIMESTAMP").unwrap_or(""),
+        build_timestamp: option_env!("VERGEN_BUILD_TIMESTAMP").unwrap_or(""),
+        profile: if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        },
+    }
+}
+
+/// Scripts loaded by this instance
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScriptCounts {
+    pub total: usize,
+    /// Scripts whose `init()` succeeded
+    pub initialized: usize,
+    /// Scripts whose `init()` failed
+    pub failed: usize,
+    /// Routes registered by initialized scripts
+    pub routes: usize,
+    /// Scheduled jobs registered on this instance
+    pub scheduled_jobs: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionInfo {
+    pub instance_id: String,
+    #[serde(flatten)]
+    pub build: BuildDetails,
+    pub features: Vec<&'static str>,
+    pub scripts: ScriptCounts,
+}
+
+fn count_scripts(metadata: &[crate::repository::ScriptMetadata]) -> ScriptCounts {
+    let mut counts = ScriptCounts {
+        total: metadata.len(),
+        ..Default::default()
+    };
+    for script in metadata {
+        if script.initialized {
+            counts.initialized += 1;
+            counts.routes += script.registrations.len();
+        } else if script.init_error.is_some() {
+            counts.failed += 1;
+        }
+    }
+    counts
+}
+
+/// Everything reported by `/engine/version`
+pub async fn collect() -> Result<VersionInfo, String> {
+    let metadata = match crate::repository::get_repository_opt() {
+        Some(repo) => repo
+            .get_all_script_metadata()
+            .await
+            .map_err(|e| format!("Failed to list scripts: {}", e))?,
+        None => Vec::new(),
+    };
+    let mut scripts = count_scripts(&metadata);
+    scripts.scheduled_jobs = crate::scheduler::get_scheduler()
+        .get_job_counts()
+        .values()
+        .sum();
+
+    Ok(VersionInfo {
+        instance_id: crate::notifications::get_server_id().unwrap_or_else(|| "unknown".to_string()),
+        build: build_details(),
+        features: current_features(),
+        scripts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled_features() {
+        let mut config = AppConfig::default();
+        config.auth = None;
+        config.tenancy.enabled = true;
+        config.javascript.enable_compilation_cache = false;
+        config.performance.enable_compression = true;
+        config.performance.enable_response_cache = false;
+        config.performance.enable_metrics = false;
+        config.security.secret_encryption_key = Some("key".to_string());
+
+        let enabled = enabled_features(&config);
+        assert!(enabled.contains(&"tenancy"));
+        assert!(enabled.contains(&"compression"));
+        assert!(enabled.contains(&"secret_encryption"));
+        assert!(!enabled.contains(&"auth"));
+        assert!(!enabled.contains(&"compilation_cache"));
+        assert!(enabled.is_sorted());
+    }
+
+    #[test]
+    fn test_build_details() {
+        let details = build_details();
+        assert_eq!(details.version, env!("CARGO_PKG_VERSION"));
+        assert!(["debug", "release"].contains(&details.profile));
+    }
+}
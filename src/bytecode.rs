@@ -81,18 +81,42 @@ pub fn eval_program(ctx: &Ctx<'_>, cache_key: &str, code: &str) -> Result<(), rq
 
     debug!(uri = cache_key, "Bytecode cache miss; compiling");
     let bytecode = compile_to_bytecode(ctx, code, cache_key)?;
+    store(cache_key, source_hash, bytecode.clone());
 
+    eval_bytecode(ctx, &bytecode)
+}
+
+/// Compile global-script `code` into the cache under `cache_key` without
+/// executing it, so the next [`eval_program`] with identical source skips
+/// parsing. Returns false when the cache already held this source.
+pub fn precompile(ctx: &Ctx<'_>, cache_key: &str, code: &str) -> Result<bool, rquickjs::Error> {
+    let source_hash = hash_source(code);
+
+    let cached = cache().lock().ok().is_some_and(|guard| {
+        guard
+            .get(cache_key)
+            .is_some_and(|entry| entry.source_hash == source_hash)
+    });
+    if cached {
+        return Ok(false);
+    }
+
+    debug!(uri = cache_key, "Precompiling bytecode");
+    let bytecode = compile_to_bytecode(ctx, code, cache_key)?;
+    store(cache_key, source_hash, bytecode);
+    Ok(true)
+}
+
+fn store(cache_key: &str, source_hash: String, bytecode: Vec<u8>) {
     if let Ok(mut guard) = cache().lock() {
         guard.insert(
             cache_key.to_string(),
             CachedBytecode {
                 source_hash,
-                bytecode: bytecode.clone(),
+                bytecode,
             },
         );
     }
-
-    eval_bytecode(ctx, &bytecode)
 }
 
 /// Compile global-script source to serialized QuickJS bytecode without running
@@ -227,6 +251,27 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_precompile_fills_cache_without_running() {
+        let src = "var RAN = true; function ping(){ return 'pong'; }";
+        {
+            let (_rt, ctx) = fresh();
+            ctx.with(|ctx| {
+                assert!(precompile(&ctx, "s5", src).unwrap());
+                assert!(!precompile(&ctx, "s5", src).unwrap(), "already cached");
+                let ran: Option<bool> = ctx.globals().get("RAN").unwrap();
+                assert_eq!(ran, None, "precompiling must not execute the script");
+            });
+        }
+
+        let (_rt, ctx) = fresh();
+        ctx.with(|ctx| {
+            eval_program(&ctx, "s5", src).unwrap();
+            let f: Function = ctx.globals().get("ping").unwrap();
+            assert_eq!(f.call::<_, String>(()).unwrap(), "pong");
+        });
+    }
+
     #[test]
     fn test_compile_error_surfaces() {
         clear();
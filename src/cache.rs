@@ -0,0 +1,316 @@
+//! Script cache (`cache.get`, `cache.set`, `cache.delete`).
+//!
+//! Values live in an in-process LRU keyed by script and key, so repeated
+//! lookups never leave the server. With `redis_url` configured, Redis is
+//! the shared tier: writes and deletes go to both, reads try memory first
+//! and keep what they load from Redis in memory for at most
+//! `local_ttl_seconds`, which bounds how stale another server's view can
+//! be. The cache is best effort: Redis errors are logged and the call
+//! carries on with memory alone.
+
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use tracing::{debug, warn};
+
+use crate::config::CacheConfig;
+
+/// Longest key accepted, in bytes
+pub const MAX_KEY_LENGTH: usize = 512;
+
+/// Redis connections kept open for reuse
+const MAX_IDLE_REDIS_CONNECTIONS: usize = 8;
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+}
+
+/// Clients of the Redis tier and their idle connections
+#[derive(Default)]
+struct RedisTier {
+    client: Option<redis::Client>,
+    idle: Vec<redis::Connection>,
+}
+
+static SETTINGS: OnceLock<RwLock<CacheConfig>> = OnceLock::new();
+static MEMORY: OnceLock<Mutex<LruCache<String, Entry>>> = OnceLock::new();
+static REDIS: OnceLock<Mutex<RedisTier>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<CacheConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+fn current_settings() -> CacheConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn capacity(max_entries: usize) -> NonZeroUsize {
+    NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN)
+}
+
+fn lock_memory() -> MutexGuard<'static, LruCache<String, Entry>> {
+    let memory = MEMORY
+        .get_or_init(|| Mutex::new(LruCache::new(capacity(CacheConfig::default().max_entries))));
+    match memory.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Script cache mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+fn lock_redis() -> MutexGuard<'static, RedisTier> {
+    match REDIS.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Apply the cache configuration. Called once at server startup.
+pub fn configure(config: &CacheConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+    lock_memory().resize(capacity(config.max_entries));
+
+    let client = config
+        .redis_url
+        .as_deref()
+        .and_then(|url| match redis::Client::open(url) {
+            Ok(client) => Some(client),
+            Err(e) => {
+                warn!("Script cache Redis tier disabled: {}", e);
+                None
+            }
+        });
+    *lock_redis() = RedisTier {
+        client,
+        idle: Vec::new(),
+    };
+}
+
+/// Key of a script's value in both tiers. The URI's length keeps keys of
+/// different scripts apart however their URIs and keys are split.
+fn entry_key(config: &CacheConfig, script_uri: &str, key: &str) -> String {
+    format!(
+        "{}{}:{}:{}",
+        config.redis_key_prefix,
+        script_uri.len(),
+        script_uri,
+        key
+    )
+}
+
+/// Run `command` on a Redis connection; `None` without a Redis tier or
+/// when the command fails
+fn with_redis<T>(
+    config: &CacheConfig,
+    command: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+) -> Option<T> {
+    let (client, idle) = {
+        let mut tier = lock_redis();
+        let client = tier.client.clone()?;
+        let idle = tier.idle.pop();
+        (client, idle)
+    };
+    let timeout = Duration::from_millis(config.redis_timeout_ms);
+    let connection = match idle {
+        Some(connection) => Ok(connection),
+        None => client.get_connection_with_timeout(timeout).and_then(|c| {
+            c.set_read_timeout(Some(timeout))?;
+            c.set_write_timeout(Some(timeout))?;
+            Ok(c)
+        }),
+    };
+    let mut connection = match connection {
+        Ok(connection) => connection,
+        Err(e) => {
+            warn!("Script cache could not connect to Redis: {}", e);
+            return None;
+        }
+    };
+    match command(&mut connection) {
+        Ok(result) => {
+            let mut tier = lock_redis();
+            if tier.idle.len() < MAX_IDLE_REDIS_CONNECTIONS {
+                tier.idle.push(connection);
+            }
+            Some(result)
+        }
+        // The connection may be broken; it is dropped
+        Err(e) => {
+            warn!("Script cache Redis command failed: {}", e);
+            None
+        }
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.trim().is_empty() {
+        return Err("Key cannot be empty".to_string());
+    }
+    if key.len() > MAX_KEY_LENGTH {
+        return Err(format!("Key must not exceed {} bytes", MAX_KEY_LENGTH));
+    }
+    Ok(())
+}
+
+/// The cached value of `key` for `script_uri`
+pub fn get(script_uri: &str, key: &str) -> Result<Option<String>, String> {
+    validate_key(key)?;
+    let config = current_settings();
+    let entry_key = entry_key(&config, script_uri, key);
+    {
+        let mut memory = lock_memory();
+        match memory.get(&entry_key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                return Ok(Some(entry.value.clone()));
+            }
+            Some(_) => {
+                memory.pop(&entry_key);
+            }
+            None => {}
+        }
+    }
+
+    let loaded = with_redis(&config, |connection| {
+        redis::pipe()
+            .cmd("GET")
+            .arg(&entry_key)
+            .cmd("PTTL")
+            .arg(&entry_key)
+            .query::<(Option<String>, i64)>(connection)
+    });
+    let Some((Some(value), remaining_ms)) = loaded else {
+        return Ok(None);
+    };
+    // PTTL is negative for keys without an expiry, which cache.set never
+    // writes
+    let local_ttl = Duration::from_secs(config.local_ttl_seconds)
+        .min(Duration::from_millis(remaining_ms.max(0) as u64));
+    if !local_ttl.is_zero() {
+        lock_memory().put(
+            entry_key,
+            Entry {
+                value: value.clone(),
+                expires_at: Instant::now() + local_ttl,
+            },
+        );
+    }
+    Ok(Some(value))
+}
+
+/// Cache `value` as `key` of `script_uri` for `ttl_seconds`, or the
+/// configured default
+pub fn set(
+    script_uri: &str,
+    key: &str,
+    value: &str,
+    ttl_seconds: Option<f64>,
+) -> Result<(), String> {
+    validate_key(key)?;
+    let config = current_settings();
+    if value.len() > config.max_value_bytes {
+        return Err(format!(
+            "Value must not exceed {} bytes",
+            config.max_value_bytes
+        ));
+    }
+    let ttl = match ttl_seconds {
+        None => Duration::from_secs(config.default_ttl_seconds),
+        Some(seconds) if seconds.is_finite() && seconds > 0.0 => {
+            if seconds > config.max_ttl_seconds as f64 {
+                return Err(format!(
+                    "ttlSeconds must be at most {}",
+                    config.max_ttl_seconds
+                ));
+            }
+            Duration::from_secs_f64(seconds)
+        }
+        Some(_) => return Err("ttlSeconds must be a positive number".to_string()),
+    };
+
+    let entry_key = entry_key(&config, script_uri, key);
+    let mut local_ttl = ttl;
+    if with_redis(&config, |connection| {
+        redis::cmd("SET")
+            .arg(&entry_key)
+            .arg(value)
+            .arg("PX")
+            .arg(ttl.as_millis().max(1) as u64)
+            .query::<()>(connection)
+    })
+    .is_some()
+    {
+        local_ttl = local_ttl.min(Duration::from_secs(config.local_ttl_seconds));
+    }
+    lock_memory().put(
+        entry_key,
+        Entry {
+            value: value.to_string(),
+            expires_at: Instant::now() + local_ttl,
+        },
+    );
+    debug!(script_uri = %script_uri, key = %key, "Cached value");
+    Ok(())
+}
+
+/// Remove `key` of `script_uri` from both tiers; whether it was cached
+pub fn delete(script_uri: &str, key: &str) -> Result<bool, String> {
+    validate_key(key)?;
+    let config = current_settings();
+    let entry_key = entry_key(&config, script_uri, key);
+    let in_memory = lock_memory()
+        .pop(&entry_key)
+        .is_some_and(|entry| entry.expires_at > Instant::now());
+    let in_redis = with_redis(&config, |connection| {
+        redis::cmd("DEL").arg(&entry_key).query::<u64>(connection)
+    })
+    .is_some_and(|removed| removed > 0);
+    Ok(in_memory || in_redis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_delete_and_ttl() {
+        let script_uri = "https://example.com/cache-unit";
+        set(script_uri, "greeting", "hello", None).unwrap();
+        assert_eq!(
+            get(script_uri, "greeting").unwrap().as_deref(),
+            Some("hello")
+        );
+        assert_eq!(get("https://example.com/other", "greeting").unwrap(), None);
+        assert!(delete(script_uri, "greeting").unwrap());
+        assert!(!delete(script_uri, "greeting").unwrap());
+
+        set(script_uri, "short", "lived", Some(0.001)).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(get(script_uri, "short").unwrap(), None);
+
+        assert_eq!(
+            set(script_uri, "bad", "x", Some(-1.0)).unwrap_err(),
+            "ttlSeconds must be a positive number"
+        );
+        assert_eq!(get(script_uri, " ").unwrap_err(), "Key cannot be empty");
+    }
+
+    #[test]
+    fn test_entry_keys_do_not_collide() {
+        let config = CacheConfig::default();
+        assert_ne!(
+            entry_key(&config, "a:b", "c"),
+            entry_key(&config, "a", "b:c")
+        );
+    }
+}
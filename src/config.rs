@@ -4,7 +4,7 @@ use figment::{
     providers::{Env, Format, Serialized, Toml, Yaml},
 };
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, path::PathBuf, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, time::Duration};
 
 /// Application configuration with comprehensive settings for all components
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -30,6 +30,26 @@ pub struct AppConfig {
     /// Authentication configuration (optional)
     #[serde(default)]
     pub auth: Option<crate::auth::AuthConfig>,
+
+    /// Row-level tenancy for script data
+    #[serde(default)]
+    pub tenancy: TenancyConfig,
+
+    /// Usage records for billing
+    #[serde(default)]
+    pub metering: MeteringConfig,
+
+    /// CDN surrogate keys and purging of asset routes
+    #[serde(default)]
+    pub cdn: CdnConfig,
+
+    /// Dependency checks of the health endpoint
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    /// Scripts loaded from a local directory during development
+    #[serde(default)]
+    pub dev_watch: DevWatchConfig,
 }
 
 /// Server-specific configuration
@@ -61,6 +81,82 @@ pub struct ServerConfig {
 
     /// Shutdown timeout in seconds
     pub shutdown_timeout_secs: u64,
+
+    /// GraphQL over HTTP (`/graphql`)
+    #[serde(default)]
+    pub graphql: GraphQLHttpConfig,
+
+    /// GraphQL subscriptions over WebSocket (`/graphql/ws`)
+    #[serde(default)]
+    pub graphql_ws: GraphQLWsConfig,
+
+    /// What happens to requests for script routes whose path is not
+    /// normalized (trailing or duplicate slashes, needless percent-encoding):
+    /// "strict" (default), "redirect" or "rewrite"
+    #[serde(default)]
+    pub path_normalization: crate::path_normalization::PathNormalization,
+}
+
+/// Limits of `/graphql` requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphQLHttpConfig {
+    /// Operations one batched POST (a JSON array of requests) may carry;
+    /// 0 disables batching
+    pub max_batch_size: usize,
+
+    /// Persisted query documents kept in memory, looked up by the SHA-256
+    /// hash requests name in `extensions.persistedQuery`; 0 disables
+    /// persisted queries
+    pub max_persisted_queries: usize,
+}
+
+impl Default for GraphQLHttpConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 10,
+            max_persisted_queries: 1000,
+        }
+    }
+}
+
+/// Timing, limits and buffering of `/graphql/ws` connections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphQLWsConfig {
+    /// Seconds between pings sent to the client; 0 disables them
+    pub keep_alive_interval_secs: u64,
+
+    /// Seconds a client has to send `connection_init` after connecting;
+    /// 0 waits forever
+    pub connection_init_timeout_secs: u64,
+
+    /// Subscriptions one connection may run at once
+    pub max_subscriptions_per_connection: usize,
+
+    /// Subscriptions one signed-in user may run at once across connections;
+    /// 0 = no limit
+    pub max_subscriptions_per_user: usize,
+
+    /// Messages buffered for a client that hasn't read them yet
+    pub send_buffer_messages: usize,
+
+    /// Milliseconds a full buffer is waited on before the client is closed
+    /// as a slow consumer
+    pub slow_consumer_timeout_ms: u64,
+}
+
+impl Default for GraphQLWsConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive_interval_secs: 30,
+            connection_init_timeout_secs: 10,
+            max_subscriptions_per_connection: 20,
+            max_subscriptions_per_user: 100,
+            send_buffer_messages: 256,
+            slow_consumer_timeout_ms: 5000,
+        }
+    }
 }
 
 /// Logging configuration
@@ -97,9 +193,23 @@ pub struct JavaScriptConfig {
     /// Maximum memory usage per script in bytes
     pub max_memory_bytes: usize,
 
-    /// Maximum number of concurrent script executions
+    /// Highest memory limit an administrator may set for a single script
+    /// (`scriptStorage.setMemoryLimit`), in bytes
+    #[serde(default = "default_max_heap_bytes")]
+    pub max_heap_bytes: usize,
+
+    /// Maximum number of request script executions queued or running at
+    /// once; requests beyond it are answered with 503
     pub max_concurrent_executions: usize,
 
+    /// Worker threads executing request scripts; 0 uses two per CPU core
+    #[serde(default)]
+    pub worker_threads: usize,
+
+    /// Runtimes the worker threads keep between executions
+    #[serde(default)]
+    pub runtime_pool: RuntimePoolConfig,
+
     /// Enable script compilation caching
     pub enable_compilation_cache: bool,
 
@@ -116,15 +226,660 @@ pub struct JavaScriptConfig {
     /// Init function timeout in milliseconds (defaults to execution_timeout_ms if not set)
     pub init_timeout_ms: Option<u64>,
 
+    /// Highest execution timeout an administrator may set for a single
+    /// script (`scriptStorage.setExecutionTimeout`) or a route may register
+    /// (`registerRoute` `timeoutMs`)
+    #[serde(default = "default_max_script_execution_timeout_ms")]
+    pub max_script_execution_timeout_ms: u64,
+
     /// Fail server startup if any script init fails
     #[serde(default)]
     pub fail_startup_on_init_error: bool,
+
+    /// Include the exception's stack trace and location in script error
+    /// responses. Meant for development; logs always carry them.
+    #[serde(default)]
+    pub expose_error_details: bool,
+
+    /// Serve the script debugger WebSocket (`/engine/debugger`) to
+    /// administrators. Meant for development.
+    #[serde(default)]
+    pub enable_debugger: bool,
+
+    /// Lint rules run when scripts are saved
+    #[serde(default)]
+    pub lint: ScriptLintConfig,
+
+    /// Limits of `pdf.fromHtml`
+    #[serde(default)]
+    pub pdf: PdfConfig,
+
+    /// Providers and models available to `llm.complete`
+    #[serde(default)]
+    pub llm: LlmConfig,
+
+    /// Delivery and retries of `webhooks.send`
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+
+    /// Workers and retries of `tasks.enqueue`
+    #[serde(default)]
+    pub tasks: TaskQueueConfig,
+
+    /// Provider and quotas of `email.send`
+    #[serde(default)]
+    pub email: EmailConfig,
+
+    /// Web push keys and the SMS provider of `notify`
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Memory and Redis tiers of `cache`
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    /// Locale `i18n.t` falls back to when none of the request's
+    /// `Accept-Language` locales has a translation
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+}
+
+fn default_locale() -> String {
+    crate::i18n::DEFAULT_LOCALE.to_string()
 }
 
 fn default_enable_init_functions() -> bool {
     true
 }
 
+fn default_max_script_execution_timeout_ms() -> u64 {
+    60_000
+}
+
+fn default_max_heap_bytes() -> usize {
+    256 * 1024 * 1024
+}
+
+/// QuickJS runtimes kept by script worker threads between executions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimePoolConfig {
+    /// `shared`: one runtime per worker for every script; `per_script`: one
+    /// per script on each worker
+    pub reuse: crate::runtime_pool::RuntimeReuse,
+
+    /// Executions a runtime serves before it is replaced
+    pub max_reuses: u32,
+
+    /// A runtime holding more memory than this after garbage collection is
+    /// replaced, in bytes
+    pub max_retained_memory_bytes: usize,
+
+    /// Runtimes one worker keeps with `reuse = "per_script"`
+    pub max_runtimes_per_worker: usize,
+
+    /// Create a runtime on every worker at startup
+    pub prewarm: bool,
+}
+
+impl Default for RuntimePoolConfig {
+    fn default() -> Self {
+        Self {
+            reuse: Default::default(),
+            max_reuses: 1000,
+            max_retained_memory_bytes: 8 * 1024 * 1024,
+            max_runtimes_per_worker: 8,
+            prewarm: true,
+        }
+    }
+}
+
+/// Lint rules run when scripts are saved. Findings are returned to the
+/// caller as warnings and never block the save; syntax errors always do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptLintConfig {
+    /// Warn about top-level functions that are neither called nor named in
+    /// a registration
+    pub unused_handlers: bool,
+
+    /// Warn about scripts without an init() function to register handlers
+    pub missing_init: bool,
+
+    /// Globals scripts should not use
+    pub forbidden_globals: Vec<String>,
+}
+
+impl Default for ScriptLintConfig {
+    fn default() -> Self {
+        Self {
+            unused_handlers: true,
+            missing_init: true,
+            forbidden_globals: vec!["eval".to_string(), "Function".to_string()],
+        }
+    }
+}
+
+/// Size and time budget of PDF rendering (`pdf.fromHtml`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfConfig {
+    /// Largest HTML input accepted, in bytes
+    pub max_html_bytes: usize,
+
+    /// Most pages one document may have
+    pub max_pages: usize,
+
+    /// Longest one rendering may take, in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for PdfConfig {
+    fn default() -> Self {
+        Self {
+            max_html_bytes: 2 * 1024 * 1024,
+            max_pages: 100,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Delivery and retries of outgoing webhooks (`webhooks.send`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Attempts a delivery gets when the script sets no `maxAttempts`
+    pub max_attempts: u32,
+
+    /// Most attempts a script may ask for
+    pub max_attempts_limit: u32,
+
+    /// Delay before the first retry, in milliseconds; each further retry
+    /// waits twice as long
+    pub retry_delay_ms: u64,
+
+    /// Longest delay between retries, in milliseconds
+    pub max_retry_delay_ms: u64,
+
+    /// Longest one attempt may take, in milliseconds
+    pub timeout_ms: u64,
+
+    /// Largest payload accepted, in bytes
+    pub max_payload_bytes: usize,
+
+    /// Hours delivered and failed deliveries are kept for inspection
+    pub retention_hours: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            max_attempts_limit: 20,
+            retry_delay_ms: 10_000,
+            max_retry_delay_ms: 60 * 60 * 1000,
+            timeout_ms: 10_000,
+            max_payload_bytes: 1024 * 1024,
+            retention_hours: 7 * 24,
+        }
+    }
+}
+
+/// Background tasks queued by scripts (`tasks.enqueue`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskQueueConfig {
+    /// Tasks one server instance runs at the same time
+    pub concurrency: usize,
+
+    /// Attempts a task gets when the script sets no `maxAttempts`
+    pub max_attempts: u32,
+
+    /// Most attempts a script may ask for
+    pub max_attempts_limit: u32,
+
+    /// Delay before the first retry, in milliseconds; each further retry
+    /// waits twice as long
+    pub retry_delay_ms: u64,
+
+    /// Longest delay between retries, in milliseconds
+    pub max_retry_delay_ms: u64,
+
+    /// How long a running task is hidden from other workers, in
+    /// milliseconds. A task whose worker stopped is run again after this;
+    /// keep it above the handler execution timeout.
+    pub visibility_timeout_ms: u64,
+
+    /// Largest payload accepted, in bytes
+    pub max_payload_bytes: usize,
+
+    /// Hours completed and failed tasks are kept for inspection
+    pub retention_hours: u64,
+}
+
+impl Default for TaskQueueConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_attempts: 3,
+            max_attempts_limit: 20,
+            retry_delay_ms: 5_000,
+            max_retry_delay_ms: 60 * 60 * 1000,
+            visibility_timeout_ms: 5 * 60 * 1000,
+            max_payload_bytes: 256 * 1024,
+            retention_hours: 7 * 24,
+        }
+    }
+}
+
+/// Outgoing email of scripts (`email.send`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// Where messages go; `email.send` fails while disabled
+    pub provider: EmailProvider,
+
+    /// Sender of every message, such as "Example <noreply@example.com>"
+    pub from: String,
+
+    /// SMTP server host
+    pub smtp_host: String,
+
+    /// SMTP server port
+    pub smtp_port: u16,
+
+    /// How the SMTP connection is encrypted
+    pub smtp_tls: SmtpTls,
+
+    /// SMTP user name; none sends without authentication
+    pub smtp_username: Option<String>,
+
+    /// Name of the sending script's secret holding the SMTP password
+    pub smtp_password_secret: Option<String>,
+
+    /// Longest one send may take, in milliseconds
+    pub timeout_ms: u64,
+
+    /// Most recipients (to, cc and bcc together) of one message
+    pub max_recipients: usize,
+
+    /// Largest message accepted, attachments included, in bytes
+    pub max_message_bytes: usize,
+
+    /// Messages each script may send per UTC day (0 = unlimited)
+    pub daily_limit: u32,
+
+    /// Daily limits of particular scripts by URI, replacing `daily_limit`
+    pub script_daily_limits: HashMap<String, u32>,
+
+    /// Hours entries of the delivery log are kept
+    pub retention_hours: u64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            provider: EmailProvider::Disabled,
+            from: String::new(),
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_tls: SmtpTls::Starttls,
+            smtp_username: None,
+            smtp_password_secret: None,
+            timeout_ms: 10_000,
+            max_recipients: 50,
+            max_message_bytes: 10 * 1024 * 1024,
+            daily_limit: 200,
+            script_daily_limits: HashMap::new(),
+            retention_hours: 30 * 24,
+        }
+    }
+}
+
+/// Where `email.send` delivers messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailProvider {
+    Disabled,
+    Smtp,
+    /// Messages are logged and recorded as sent without being delivered,
+    /// for development
+    Log,
+}
+
+/// Encryption of the SMTP connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS, usually on port 587
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// No encryption; only for servers on a trusted network
+    None,
+}
+
+/// Web push and SMS notifications of scripts (`notify.push`, `notify.sms`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Contact of the push sender in VAPID claims, such as
+    /// "mailto:admin@example.com"
+    pub vapid_subject: String,
+
+    /// Base64url P-256 private key signing push requests; its public key
+    /// is the `applicationServerKey` of browser subscriptions. Push is
+    /// disabled while not set.
+    pub vapid_private_key: Option<String>,
+
+    /// Seconds a push service keeps an undelivered message when the script
+    /// sets no `ttl`
+    pub push_ttl_seconds: u32,
+
+    /// Most push subscriptions a user may have per script
+    pub max_subscriptions_per_user: i64,
+
+    /// Longest one push or SMS request may take, in milliseconds
+    pub timeout_ms: u64,
+
+    /// Where `notify.sms` sends text messages
+    pub sms: SmsConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            vapid_subject: String::new(),
+            vapid_private_key: None,
+            push_ttl_seconds: 24 * 60 * 60,
+            max_subscriptions_per_user: 20,
+            timeout_ms: 10_000,
+            sms: SmsConfig::default(),
+        }
+    }
+}
+
+/// SMS provider of `notify.sms`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmsConfig {
+    /// `notify.sms` fails while disabled
+    pub provider: SmsProviderKind,
+
+    /// Sender number or alphanumeric sender ID
+    pub from: String,
+
+    /// Twilio account SID
+    pub account_sid: Option<String>,
+
+    /// Endpoint the `http` provider posts `{ to, from, body }` to
+    pub api_url: Option<String>,
+
+    /// Name of the sending script's secret holding the Twilio auth token
+    /// or the `http` provider's bearer token
+    pub credentials_secret: Option<String>,
+
+    /// Longest message accepted, in characters
+    pub max_length: usize,
+}
+
+impl Default for SmsConfig {
+    fn default() -> Self {
+        Self {
+            provider: SmsProviderKind::Disabled,
+            from: String::new(),
+            account_sid: None,
+            api_url: None,
+            credentials_secret: None,
+            max_length: 1600,
+        }
+    }
+}
+
+/// SMS providers `notify.sms` can use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmsProviderKind {
+    Disabled,
+    Twilio,
+    /// A JSON API of your own or a provider's, posted `{ to, from, body }`
+    Http,
+    /// Messages are logged without being sent, for development
+    Log,
+}
+
+/// Script cache of `cache.get` and `cache.set`: an in-process LRU, with
+/// Redis shared by all servers when `redis_url` is set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Most entries kept in memory; the least recently used are evicted
+    pub max_entries: usize,
+
+    /// Largest value accepted, in bytes
+    pub max_value_bytes: usize,
+
+    /// TTL of values set without `ttlSeconds`
+    pub default_ttl_seconds: u64,
+
+    /// Longest TTL a script may set
+    pub max_ttl_seconds: u64,
+
+    /// Redis server shared by the cluster, such as "redis://cache:6379/0".
+    /// Only the memory tier is used while not set.
+    pub redis_url: Option<String>,
+
+    /// Prefix of the keys written to Redis
+    pub redis_key_prefix: String,
+
+    /// Seconds a value from Redis is also served from memory; changes made
+    /// on other servers show up after at most this long
+    pub local_ttl_seconds: u64,
+
+    /// Longest a Redis command may take, in milliseconds
+    pub redis_timeout_ms: u64,
+
+    /// Most route responses kept by the response cache of routes
+    /// registered with the `cache` option
+    pub max_responses: usize,
+
+    /// Largest route response body the response cache keeps, in bytes
+    pub max_response_bytes: usize,
+
+    /// Seconds an expired cached route or asset response is still served
+    /// while it is refreshed in the background, for routes that set no
+    /// `staleWhileRevalidateSeconds`. 0 refreshes in the request instead.
+    pub stale_while_revalidate_seconds: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_value_bytes: 1024 * 1024,
+            default_ttl_seconds: 300,
+            max_ttl_seconds: 24 * 60 * 60,
+            redis_url: None,
+            redis_key_prefix: "aiwebengine:cache:".to_string(),
+            local_ttl_seconds: 5,
+            redis_timeout_ms: 500,
+            max_responses: 1000,
+            max_response_bytes: 1024 * 1024,
+            stale_while_revalidate_seconds: 0,
+        }
+    }
+}
+
+/// LLM providers and the models scripts may use with `llm.complete`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// Providers by name. `openai` and `anthropic` are built in; entries
+    /// here add providers or replace the built-in ones.
+    pub providers: HashMap<String, LlmProviderConfig>,
+
+    /// Models every script may use, such as "gpt-4o-mini" or "claude-*".
+    /// Empty means no script may call `llm.complete`.
+    pub allowed_models: Vec<String>,
+
+    /// Models allowed per script URI, replacing `allowed_models` for that
+    /// script
+    pub script_models: HashMap<String, Vec<String>>,
+
+    /// Longest one completion may take, in milliseconds
+    pub timeout_ms: u64,
+
+    /// Largest `maxTokens` a script may request
+    pub max_output_tokens: u32,
+
+    /// Provider `embeddings.create` uses when a script names none
+    pub embedding_provider: String,
+
+    /// Model `embeddings.create` uses when a script names none. Scripts may
+    /// always use it; other embedding models must be allowed like chat
+    /// models.
+    pub embedding_model: String,
+
+    /// Prices by model name or prefix pattern ending in `*`, used to
+    /// estimate the cost of calls. The longest matching pattern applies;
+    /// models without a price cost nothing.
+    pub prices: HashMap<String, LlmPrice>,
+
+    /// Monthly cost limits
+    pub budgets: LlmBudgetConfig,
+
+    /// Content moderation of `moderation.check` and of LLM calls
+    pub moderation: ModerationConfig,
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LlmPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Monthly cost limits of LLM calls, in USD of estimated cost
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmBudgetConfig {
+    /// Limits of each script
+    pub script: LlmBudgetLimits,
+
+    /// Limits of each signed-in user, across all scripts
+    pub user: LlmBudgetLimits,
+
+    /// Limits per script URI, replacing `script` for that script
+    pub scripts: HashMap<String, LlmBudgetLimits>,
+}
+
+/// A soft limit logs a warning for each call made past it; a hard limit
+/// refuses calls until the month ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmBudgetLimits {
+    pub soft: Option<f64>,
+    pub hard: Option<f64>,
+}
+
+/// How text is moderated and what happens to flagged LLM prompts and
+/// outputs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    /// LLM provider whose moderation API checks text, such as `openai` or
+    /// an `openai-compatible` server running a local model. Empty checks
+    /// only `patterns`.
+    pub provider: String,
+
+    /// Moderation model of the provider
+    pub model: String,
+
+    /// Regular expressions by category, checked locally; text matching one
+    /// is flagged for its category
+    pub patterns: HashMap<String, Vec<String>>,
+
+    /// What happens when the user messages of an `llm.*` call are flagged
+    pub input: ModerationAction,
+
+    /// What happens when the text an `llm.*` call generates is flagged
+    pub output: ModerationAction,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            provider: String::new(),
+            model: "omni-moderation-latest".to_string(),
+            patterns: HashMap::new(),
+            input: ModerationAction::Off,
+            output: ModerationAction::Off,
+        }
+    }
+}
+
+/// Policy applied to flagged text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Not checked
+    #[default]
+    Off,
+    /// Flagged text is logged and let through
+    Audit,
+    /// Flagged text is logged and the result tells the script why
+    Flag,
+    /// Flagged text is logged and the call fails
+    Block,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            providers: HashMap::new(),
+            allowed_models: Vec::new(),
+            script_models: HashMap::new(),
+            timeout_ms: 60_000,
+            max_output_tokens: 4096,
+            embedding_provider: "openai".to_string(),
+            embedding_model: "text-embedding-3-small".to_string(),
+            prices: HashMap::new(),
+            budgets: LlmBudgetConfig::default(),
+            moderation: ModerationConfig::default(),
+        }
+    }
+}
+
+/// API an LLM provider speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LlmApi {
+    /// OpenAI chat completions
+    Openai,
+    /// Anthropic messages
+    Anthropic,
+    /// Chat completions of a local or self-hosted server, such as Ollama
+    /// or vLLM
+    OpenaiCompatible,
+}
+
+/// One LLM provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LlmProviderConfig {
+    pub api: LlmApi,
+
+    /// API base URL; required for `openai-compatible` providers
+    #[serde(default)]
+    pub base_url: Option<String>,
+
+    /// Name of the script or user secret holding the API key; none sends
+    /// no credentials
+    #[serde(default)]
+    pub api_key_secret: Option<String>,
+}
+
 /// Repository configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepositoryConfig {
@@ -139,6 +894,12 @@ pub struct RepositoryConfig {
     /// Maximum asset size in bytes
     pub max_asset_size_bytes: usize,
 
+    /// Content types accepted by the asset upload endpoint and the
+    /// `uploadAsset` mutation, as `type/subtype`, `type/*` or `*/*`; empty
+    /// accepts any
+    #[serde(default)]
+    pub allowed_asset_types: Vec<String>,
+
     /// Maximum number of log messages per script
     pub max_log_messages_per_script: usize,
 
@@ -150,6 +911,138 @@ pub struct RepositoryConfig {
 
     /// Maximum upload file size in bytes
     pub max_upload_size_bytes: usize,
+
+    /// Maximum size of a multipart request in bytes, all files and fields
+    /// together
+    #[serde(default = "default_max_upload_request_bytes")]
+    pub max_upload_request_bytes: usize,
+
+    /// Uploaded files larger than this many bytes are written to a temporary
+    /// file instead of being kept in memory
+    #[serde(default = "default_upload_spool_threshold_bytes")]
+    pub upload_spool_threshold_bytes: usize,
+
+    /// Directory of spooled uploads; a directory under the system temporary
+    /// directory when not set
+    #[serde(default)]
+    pub upload_spool_dir: Option<String>,
+
+    /// Days deleted scripts and assets stay in the trash before being purged
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u64,
+
+    /// Bytes each script may store in shared storage and assets unless it has
+    /// its own quota (0 = unlimited)
+    #[serde(default = "default_storage_quota_bytes")]
+    pub default_storage_quota_bytes: u64,
+
+    /// Connection strings of read replicas. Reads that tolerate replication
+    /// lag (log and trash listings) are spread over them; writes,
+    /// transactions and advisory locks always use database_url.
+    #[serde(default)]
+    pub read_replica_urls: Vec<String>,
+
+    /// Where lag-tolerant reads go: "replicas" (default) or "primary"
+    #[serde(default)]
+    pub read_routing: crate::database::ReadRouting,
+
+    /// Maximum connections per pool (primary and each replica)
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+
+    /// Connections each pool keeps open even when idle
+    #[serde(default)]
+    pub min_connections: u32,
+
+    /// How long a query waits for a free connection before failing
+    #[serde(default = "default_acquire_timeout_ms")]
+    pub acquire_timeout_ms: u64,
+
+    /// Seconds an idle connection above min_connections is kept (0 = forever)
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+
+    /// Server-side statement_timeout for every connection (0 = none)
+    #[serde(default)]
+    pub statement_timeout_ms: u64,
+
+    /// Record every statement's fingerprint, duration and row count for the
+    /// queryStats report. Off by default: it adds a tracing event per query.
+    #[serde(default)]
+    pub query_log_enabled: bool,
+
+    /// Statements at least this slow are counted as slow in the query log
+    #[serde(default = "default_slow_query_threshold_ms")]
+    pub slow_query_threshold_ms: u64,
+
+    /// Count requests, status codes and latency per script route
+    #[serde(default = "default_route_usage_enabled")]
+    pub route_usage_enabled: bool,
+
+    /// Seconds between writes of route usage counts and script resource
+    /// usage to the database
+    #[serde(default = "default_route_usage_flush_interval_secs")]
+    pub route_usage_flush_interval_secs: u64,
+
+    /// Days of route usage kept (0 = forever)
+    #[serde(default = "default_route_usage_retention_days")]
+    pub route_usage_retention_days: u64,
+
+    /// Sum execution time, memory, timeouts and outbound bytes per script
+    #[serde(default = "default_script_resources_enabled")]
+    pub script_resources_enabled: bool,
+
+    /// Days of script resource usage kept (0 = forever)
+    #[serde(default = "default_route_usage_retention_days")]
+    pub script_resources_retention_days: u64,
+}
+
+fn default_max_upload_request_bytes() -> usize {
+    50 * 1024 * 1024 // 50MB
+}
+
+fn default_upload_spool_threshold_bytes() -> usize {
+    1024 * 1024 // 1MB
+}
+
+fn default_trash_retention_days() -> u64 {
+    crate::repository::DEFAULT_TRASH_RETENTION_DAYS
+}
+
+fn default_storage_quota_bytes() -> u64 {
+    crate::repository::DEFAULT_STORAGE_QUOTA_BYTES
+}
+
+fn default_max_connections() -> u32 {
+    5
+}
+
+fn default_acquire_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_idle_timeout_secs() -> u64 {
+    600
+}
+
+fn default_slow_query_threshold_ms() -> u64 {
+    100
+}
+
+fn default_route_usage_enabled() -> bool {
+    true
+}
+
+fn default_route_usage_flush_interval_secs() -> u64 {
+    60
+}
+
+fn default_route_usage_retention_days() -> u64 {
+    90
+}
+
+fn default_script_resources_enabled() -> bool {
+    true
 }
 
 /// Security configuration
@@ -182,6 +1075,11 @@ pub struct SecurityConfig {
     /// Rate limit: requests per minute per IP
     pub rate_limit_per_minute: u32,
 
+    /// Rate limits applied to requests whose path matches a pattern,
+    /// enforced before any script runs
+    #[serde(default)]
+    pub rate_limit_rules: Vec<RateLimitRuleConfig>,
+
     /// Enable security headers
     pub enable_security_headers: bool,
 
@@ -209,15 +1107,320 @@ pub struct SecurityConfig {
     /// Example (env): APP_SECURITY__API_KEY
     #[serde(default)]
     pub api_key: Option<String>,
+
+    /// Optional key signing the URLs of private assets. Without it a random
+    /// key is generated at startup and signed URLs stop working on restart.
+    /// Example (env): APP_SECURITY__ASSET_URL_KEY
+    #[serde(default)]
+    pub asset_url_key: Option<String>,
+
+    /// Export of security audit events to a SIEM
+    #[serde(default)]
+    pub audit_export: AuditExportConfig,
+
+    /// Automated responses to detected threats
+    #[serde(default)]
+    pub threat_response: ThreatResponseConfig,
+
+    /// Per-script capability manifests
+    #[serde(default)]
+    pub script_manifests: ScriptManifestsConfig,
+
+    /// Quarantine of scripts that keep timing out or panicking
+    #[serde(default)]
+    pub script_quarantine: ScriptQuarantineConfig,
+}
+
+/// A rate limit on requests matching a path pattern
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitRuleConfig {
+    /// Path pattern: an exact path, `/prefix/*`, or a pattern with `:param`
+    /// segments
+    pub path: String,
+
+    /// HTTP methods the rule applies to; empty means all methods
+    #[serde(default)]
+    pub methods: Vec<String>,
+
+    /// Who shares a budget: `ip`, `user` or `route`
+    #[serde(default)]
+    pub per: crate::rate_limit_rules::RateLimitScope,
+
+    /// Sustained number of requests allowed per minute
+    pub requests_per_minute: u32,
+
+    /// Requests allowed in a burst; defaults to `requests_per_minute`
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+/// Exporters security audit events are sent to; see
+/// [`crate::security::audit_export`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditExportConfig {
+    /// Off by default
+    pub enabled: bool,
+
+    /// Most events buffered for export; the oldest are dropped when full
+    pub buffer_size: usize,
+
+    /// Most events per exporter write
+    pub batch_size: usize,
+
+    /// Longest an event waits before its batch is written, in milliseconds
+    pub flush_interval_ms: u64,
+
+    /// Retries of a failed batch per exporter before it is dropped
+    pub max_retries: u32,
+
+    /// Delay before the first retry, doubled for each further retry
+    pub retry_delay_ms: u64,
+
+    /// RFC 5424 syslog over UDP or TCP
+    pub syslog: Option<SyslogExportConfig>,
+
+    /// Batches posted over HTTPS, e.g. to a Splunk HTTP Event Collector or
+    /// a Logstash HTTP input
+    pub https: Option<HttpsExportConfig>,
+
+    /// Newline-delimited JSON appended to a local file
+    pub file: Option<FileExportConfig>,
+}
+
+impl Default for AuditExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            buffer_size: 10_000,
+            batch_size: 100,
+            flush_interval_ms: 1000,
+            max_retries: 5,
+            retry_delay_ms: 1000,
+            syslog: None,
+            https: None,
+            file: None,
+        }
+    }
+}
+
+/// Syslog exporter of audit events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyslogExportConfig {
+    /// `host:port` of the syslog receiver
+    pub address: String,
+
+    pub protocol: SyslogProtocol,
+
+    /// Syslog facility number (13 = log audit)
+    pub facility: u8,
+
+    /// APP-NAME of every message
+    pub app_name: String,
+}
+
+impl Default for SyslogExportConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1:514".to_string(),
+            protocol: SyslogProtocol::Udp,
+            facility: 13,
+            app_name: "aiwebengine".to_string(),
+        }
+    }
+}
+
+/// Transport of the syslog exporter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogProtocol {
+    Udp,
+    /// Octet-counted framing (RFC 6587)
+    Tcp,
+}
+
+/// HTTPS batch exporter of audit events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpsExportConfig {
+    pub url: String,
+
+    pub format: HttpsExportFormat,
+
+    /// Authorization header value, such as "Splunk <token>" or
+    /// "Bearer <token>"
+    pub authorization: Option<String>,
+
+    /// Longest one request may take, in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for HttpsExportConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            format: HttpsExportFormat::Ndjson,
+            authorization: None,
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+/// Body format of the HTTPS exporter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpsExportFormat {
+    /// One JSON event per line
+    Ndjson,
+    /// Splunk HTTP Event Collector events
+    SplunkHec,
+}
+
+/// File exporter of audit events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileExportConfig {
+    pub path: PathBuf,
+
+    /// Size at which the file is renamed to `<path>.1` and a new one is
+    /// started (0 = never)
+    pub max_bytes: u64,
+}
+
+impl Default for FileExportConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("logs/audit.ndjson"),
+            max_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+/// Policies acting on the indicators threat detection reports; see
+/// [`crate::security::threat_response`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreatResponseConfig {
+    /// Off by default
+    pub enabled: bool,
+
+    /// IP addresses, user IDs and script URIs no policy acts on
+    pub allowlist: Vec<String>,
+
+    pub policies: Vec<ThreatPolicyConfig>,
+}
+
+impl Default for ThreatResponseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowlist: Vec::new(),
+            policies: vec![
+                ThreatPolicyConfig {
+                    name: "brute_force".to_string(),
+                    indicator: "brute_force_authentication".to_string(),
+                    action: ThreatActionKind::BlockIp,
+                    duration_secs: 900,
+                    require_review: false,
+                },
+                ThreatPolicyConfig {
+                    name: "suspicious_script_write".to_string(),
+                    indicator: "suspicious_script_write".to_string(),
+                    action: ThreatActionKind::QuarantineScript,
+                    duration_secs: 0,
+                    require_review: true,
+                },
+            ],
+        }
+    }
+}
+
+/// One threat response policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreatPolicyConfig {
+    pub name: String,
+
+    /// Threat indicator type the policy acts on, such as
+    /// "brute_force_authentication"
+    pub indicator: String,
+
+    pub action: ThreatActionKind,
+
+    /// How long the action lasts once active, in seconds (0 = until lifted)
+    #[serde(default)]
+    pub duration_secs: u64,
+
+    /// Queue the action for an administrator instead of applying it
+    #[serde(default)]
+    pub require_review: bool,
+}
+
+/// What a threat response policy does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreatActionKind {
+    /// Answer every request from the event's IP address with 403
+    BlockIp,
+    /// Stop serving the routes of the event's script
+    QuarantineScript,
+    /// Only log an alert
+    Alert,
+}
+
+/// Capability manifests limiting the sandbox features of each script; see
+/// [`crate::security::script_manifests`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptManifestsConfig {
+    /// Off by default: every script gets every sandbox feature
+    pub enabled: bool,
+
+    /// Privileged scripts get every sandbox feature without a manifest
+    pub exempt_privileged: bool,
+}
+
+impl Default for ScriptManifestsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            exempt_privileged: true,
+        }
+    }
+}
+
+/// Quarantine of scripts whose executions keep timing out or panicking; see
+/// [`crate::security::script_quarantine`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptQuarantineConfig {
+    pub enabled: bool,
+
+    /// Timeouts and panics that quarantine a script
+    pub failure_threshold: u32,
+
+    /// Seconds within which the failures must happen
+    pub failure_window_secs: u64,
+}
+
+impl Default for ScriptQuarantineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: 5,
+            failure_window_secs: 600,
+        }
+    }
 }
 
 /// Performance configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceConfig {
-    /// Enable response compression
+    /// Enable response compression: gzip and brotli variants of assets are
+    /// stored with them and served when clients accept them
     pub enable_compression: bool,
 
-    /// Compression level (1-9)
+    /// Compression level (1-9), the gzip level and brotli quality
     pub compression_level: u32,
 
     /// Enable response caching
@@ -226,17 +1429,257 @@ pub struct PerformanceConfig {
     /// Response cache TTL in seconds
     pub response_cache_ttl_secs: u64,
 
-    /// Maximum number of cached responses
-    pub max_cached_responses: usize,
+    /// Maximum number of cached responses
+    pub max_cached_responses: usize,
+
+    /// Worker thread pool size
+    pub worker_threads: Option<usize>,
+
+    /// Enable metrics collection
+    pub enable_metrics: bool,
+
+    /// Metrics collection interval in seconds
+    pub metrics_interval_secs: u64,
+}
+
+/// Tenancy configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenancyConfig {
+    /// Scope db.query rows and sharedStorage keys by the tenant of each
+    /// request, so scripts serving several customers cannot mix their data
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Where the tenant comes from: "host" (default), "email_domain" or
+    /// "user_id"
+    #[serde(default)]
+    pub source: crate::tenancy::TenantSource,
+
+    /// Limits applied to each tenant
+    #[serde(default)]
+    pub quotas: TenantQuotaConfig,
+
+    /// Settings overridden for single tenants, keyed by tenant ID or host
+    /// name (`[tenancy.overrides."shop.example.com"]`). Host keys apply even
+    /// when tenancy is disabled.
+    #[serde(default)]
+    pub overrides: HashMap<String, TenantOverrideConfig>,
+}
+
+/// Usage records (requests, script execution time, storage, LLM tokens) sent
+/// to a sink for billing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeteringConfig {
+    /// Off by default
+    pub enabled: bool,
+
+    /// Where records go
+    pub sink: MeteringSinkKind,
+
+    /// Endpoint the `webhook` sink posts `{ records }` batches to
+    pub webhook_url: Option<String>,
+
+    /// Key of the `X-Metering-Signature` HMAC of webhook batches
+    pub webhook_secret: Option<String>,
+
+    /// Seconds of usage summed into one record
+    pub flush_interval_secs: u64,
+
+    /// Seconds between samples of stored bytes
+    pub storage_sample_interval_secs: u64,
+
+    /// Most records per webhook request
+    pub batch_size: usize,
+
+    /// Longest one webhook request may take, in milliseconds
+    pub timeout_ms: u64,
+
+    /// Days the `table` sink keeps records (0 = forever)
+    pub retention_days: u64,
+}
+
+impl Default for MeteringConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sink: MeteringSinkKind::Table,
+            webhook_url: None,
+            webhook_secret: None,
+            flush_interval_secs: 60,
+            storage_sample_interval_secs: 60 * 60,
+            batch_size: 500,
+            timeout_ms: 10_000,
+            retention_days: 400,
+        }
+    }
+}
+
+/// Sinks metering records can go to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeteringSinkKind {
+    /// The `usage_records` table
+    Table,
+    /// JSON batches posted to `webhook_url`
+    Webhook,
+}
+
+/// CDN integration of asset routes: surrogate key headers on asset responses
+/// and purge requests sent when assets are stored or deleted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CdnConfig {
+    /// Send purge requests and surrogate keys of every asset response. Off
+    /// by default; routes with their own surrogate keys send them either way.
+    pub enabled: bool,
+
+    /// CDN API endpoint purge requests go to
+    pub purge_url: Option<String>,
+
+    /// HTTP method of purge requests
+    pub purge_method: String,
+
+    /// Headers of purge requests, such as the API token. `{keys}` in a value
+    /// is replaced with the surrogate keys joined by `surrogate_key_separator`.
+    pub purge_headers: HashMap<String, String>,
+
+    /// Body of purge requests. `{keys}` and `{paths}` are replaced with JSON
+    /// arrays of the surrogate keys and URL paths to purge; empty sends no body.
+    pub purge_body: String,
+
+    /// Response header carrying the surrogate keys (`Surrogate-Key` for
+    /// Fastly, `Cache-Tag` for Cloudflare)
+    pub surrogate_key_header: String,
+
+    /// Separator of the surrogate keys in headers (a space for Fastly, a
+    /// comma for Cloudflare)
+    pub surrogate_key_separator: String,
+
+    /// Longest one purge request may take, in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for CdnConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            purge_url: None,
+            purge_method: "POST".to_string(),
+            purge_headers: HashMap::new(),
+            purge_body: r#"{"surrogate_keys": {keys}, "paths": {paths}}"#.to_string(),
+            surrogate_key_header: "Surrogate-Key".to_string(),
+            surrogate_key_separator: " ".to_string(),
+            timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Dependency checks run by `/health`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Longest one check may take before it counts as failed, in milliseconds
+    pub timeout_ms: u64,
+
+    /// URL requested to check outbound connectivity; no check when unset
+    pub outbound_probe_url: Option<String>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 2_000,
+            outbound_probe_url: None,
+        }
+    }
+}
+
+/// Scripts loaded from a local directory and reloaded when their files
+/// change; only allowed in development mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevWatchConfig {
+    pub enabled: bool,
+
+    /// Directory holding the script files
+    pub directory: String,
+
+    /// Prepended to a file's path relative to `directory` to form its
+    /// script URI
+    pub uri_prefix: String,
+
+    /// How long to wait for further changes after a file changes before
+    /// reloading, in milliseconds
+    pub debounce_ms: u64,
+}
+
+impl Default for DevWatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "scripts".to_string(),
+            uri_prefix: "https://example.com/".to_string(),
+            debounce_ms: 200,
+        }
+    }
+}
+
+/// Settings a tenant or host can override; unset fields use the server-wide
+/// configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantOverrideConfig {
+    /// Script handler timeout, replacing `javascript.execution_timeout_ms`
+    #[serde(default)]
+    pub execution_timeout_ms: Option<u64>,
+
+    /// Origins allowed to call script routes cross-origin ("*" for any)
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Require a signed-in user for every script route
+    #[serde(default)]
+    pub require_authentication: bool,
+
+    /// Branding passed to handlers as `request.tenant.branding`
+    #[serde(default)]
+    pub branding: TenantBrandingConfig,
+}
+
+/// Branding of a tenant's pages, such as the GraphiQL and docs pages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantBrandingConfig {
+    /// Product name shown in page titles and headers
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// URL of the logo image
+    #[serde(default)]
+    pub logo_url: Option<String>,
+
+    /// Primary color as a CSS color value
+    #[serde(default)]
+    pub primary_color: Option<String>,
+}
 
-    /// Worker thread pool size
-    pub worker_threads: Option<usize>,
+/// Per-tenant limits; 0 disables a limit. Requests over a limit get HTTP 429.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantQuotaConfig {
+    /// Script handler requests per minute
+    #[serde(default)]
+    pub requests_per_minute: u64,
 
-    /// Enable metrics collection
-    pub enable_metrics: bool,
+    /// Milliseconds of handler execution per minute
+    #[serde(default)]
+    pub execution_ms_per_minute: u64,
 
-    /// Metrics collection interval in seconds
-    pub metrics_interval_secs: u64,
+    /// Concurrent stream (SSE) connections
+    #[serde(default)]
+    pub max_stream_connections: u64,
+
+    /// Bytes of sharedStorage keys and values, across all scripts
+    #[serde(default)]
+    pub storage_bytes: u64,
 }
 
 impl Default for ServerConfig {
@@ -250,6 +1693,9 @@ impl Default for ServerConfig {
             max_connections: 10000,
             graceful_shutdown: true,
             shutdown_timeout_secs: 30,
+            graphql: GraphQLHttpConfig::default(),
+            graphql_ws: GraphQLWsConfig::default(),
+            path_normalization: Default::default(),
         }
     }
 }
@@ -301,13 +1747,28 @@ impl Default for JavaScriptConfig {
         Self {
             execution_timeout_ms: 5000,
             max_memory_bytes: 10 * 1024 * 1024, // 10MB
+            max_heap_bytes: default_max_heap_bytes(),
             max_concurrent_executions: 100,
+            worker_threads: 0,
+            runtime_pool: RuntimePoolConfig::default(),
             enable_compilation_cache: true,
             max_cached_scripts: 1000,
             stack_size_bytes: 1024 * 1024, // 1MB
             enable_init_functions: true,
             init_timeout_ms: None, // Use execution_timeout_ms by default
+            max_script_execution_timeout_ms: default_max_script_execution_timeout_ms(),
             fail_startup_on_init_error: false,
+            expose_error_details: false,
+            enable_debugger: false,
+            lint: ScriptLintConfig::default(),
+            pdf: PdfConfig::default(),
+            llm: LlmConfig::default(),
+            webhooks: WebhookConfig::default(),
+            tasks: TaskQueueConfig::default(),
+            email: EmailConfig::default(),
+            notify: NotifyConfig::default(),
+            cache: CacheConfig::default(),
+            default_locale: default_locale(),
         }
     }
 }
@@ -319,10 +1780,30 @@ impl Default for RepositoryConfig {
                 .to_string(),
             max_script_size_bytes: 1024 * 1024,     // 1MB
             max_asset_size_bytes: 10 * 1024 * 1024, // 10MB
+            allowed_asset_types: Vec::new(),
             max_log_messages_per_script: 100,
             log_retention_hours: 24,
             auto_prune_logs: true,
             max_upload_size_bytes: 10 * 1024 * 1024, // 10MB
+            max_upload_request_bytes: default_max_upload_request_bytes(),
+            upload_spool_threshold_bytes: default_upload_spool_threshold_bytes(),
+            upload_spool_dir: None,
+            trash_retention_days: crate::repository::DEFAULT_TRASH_RETENTION_DAYS,
+            default_storage_quota_bytes: crate::repository::DEFAULT_STORAGE_QUOTA_BYTES,
+            read_replica_urls: Vec::new(),
+            read_routing: crate::database::ReadRouting::default(),
+            max_connections: default_max_connections(),
+            min_connections: 0,
+            acquire_timeout_ms: default_acquire_timeout_ms(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+            statement_timeout_ms: 0,
+            query_log_enabled: false,
+            slow_query_threshold_ms: default_slow_query_threshold_ms(),
+            route_usage_enabled: default_route_usage_enabled(),
+            route_usage_flush_interval_secs: default_route_usage_flush_interval_secs(),
+            route_usage_retention_days: default_route_usage_retention_days(),
+            script_resources_enabled: default_script_resources_enabled(),
+            script_resources_retention_days: default_route_usage_retention_days(),
         }
     }
 }
@@ -336,6 +1817,7 @@ impl Default for SecurityConfig {
             enable_csrf: false,
             enable_rate_limiting: true,
             rate_limit_per_minute: 100,
+            rate_limit_rules: Vec::new(),
             enable_security_headers: true,
             content_security_policy: Some(
                 "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'".to_string()
@@ -346,6 +1828,11 @@ impl Default for SecurityConfig {
             session_encryption_key: None,
             secret_encryption_key: None,
             api_key: None,
+            asset_url_key: None,
+            audit_export: AuditExportConfig::default(),
+            threat_response: ThreatResponseConfig::default(),
+            script_manifests: ScriptManifestsConfig::default(),
+            script_quarantine: ScriptQuarantineConfig::default(),
         }
     }
 }
@@ -498,6 +1985,10 @@ impl AppConfig {
             anyhow::bail!("Max connections must be > 0");
         }
 
+        if self.health.timeout_ms == 0 {
+            anyhow::bail!("health.timeout_ms must be > 0");
+        }
+
         // Validate logging configuration
         match self.logging.level.as_str() {
             "trace" | "debug" | "info" | "warn" | "error" => {}
@@ -524,19 +2015,373 @@ impl AppConfig {
             anyhow::bail!("JavaScript execution timeout must be > 0");
         }
 
+        if self.javascript.max_script_execution_timeout_ms == 0 {
+            anyhow::bail!("JavaScript max script execution timeout must be > 0");
+        }
+
         if self.javascript.max_memory_bytes == 0 {
             anyhow::bail!("JavaScript max memory must be > 0");
         }
 
+        if self.javascript.max_heap_bytes < self.javascript.max_memory_bytes {
+            anyhow::bail!("JavaScript max heap must be at least the max memory");
+        }
+
         if self.javascript.max_concurrent_executions == 0 {
             anyhow::bail!("JavaScript max concurrent executions must be > 0");
         }
 
-        // PostgreSQL is the only supported storage backend - no validation needed
+        let runtime_pool = &self.javascript.runtime_pool;
+        if runtime_pool.max_reuses == 0 || runtime_pool.max_runtimes_per_worker == 0 {
+            anyhow::bail!("JavaScript runtime pool max reuses and runtimes per worker must be > 0");
+        }
+
+        if self.javascript.pdf.max_html_bytes == 0
+            || self.javascript.pdf.max_pages == 0
+            || self.javascript.pdf.timeout_ms == 0
+        {
+            anyhow::bail!("JavaScript PDF limits must be > 0");
+        }
+
+        let llm = &self.javascript.llm;
+        if llm.timeout_ms == 0 || llm.max_output_tokens == 0 {
+            anyhow::bail!("JavaScript LLM timeout and max output tokens must be > 0");
+        }
+        for (name, provider) in &llm.providers {
+            match &provider.base_url {
+                Some(base_url) => {
+                    let url = url::Url::parse(base_url).map_err(|e| {
+                        anyhow::anyhow!("LLM provider '{}' has an invalid base_url: {}", name, e)
+                    })?;
+                    if !matches!(url.scheme(), "http" | "https") {
+                        anyhow::bail!("LLM provider '{}' base_url must use http or https", name);
+                    }
+                }
+                None if provider.api == LlmApi::OpenaiCompatible => {
+                    anyhow::bail!("LLM provider '{}' needs a base_url", name);
+                }
+                None => {}
+            }
+        }
+        if llm.embedding_model.trim().is_empty() {
+            anyhow::bail!("JavaScript LLM embedding model must not be empty");
+        }
+        let embedding_api = match llm.providers.get(&llm.embedding_provider) {
+            Some(provider) => Some(provider.api),
+            None if llm.embedding_provider == "openai" => Some(LlmApi::Openai),
+            None if llm.embedding_provider == "anthropic" => Some(LlmApi::Anthropic),
+            None => None,
+        };
+        match embedding_api {
+            Some(LlmApi::Anthropic) => anyhow::bail!(
+                "LLM embedding provider '{}' has no embeddings API",
+                llm.embedding_provider
+            ),
+            None => anyhow::bail!(
+                "LLM embedding provider '{}' is not configured",
+                llm.embedding_provider
+            ),
+            Some(_) => {}
+        }
+
+        for (model, price) in &llm.prices {
+            if [price.input, price.output]
+                .iter()
+                .any(|value| !value.is_finite() || *value < 0.0)
+            {
+                anyhow::bail!("LLM price of '{}' must not be negative", model);
+            }
+        }
+        let budgets = &llm.budgets;
+        for (name, limits) in [("script", &budgets.script), ("user", &budgets.user)]
+            .into_iter()
+            .chain(
+                budgets
+                    .scripts
+                    .iter()
+                    .map(|(uri, limits)| (uri.as_str(), limits)),
+            )
+        {
+            if [limits.soft, limits.hard]
+                .iter()
+                .flatten()
+                .any(|limit| !limit.is_finite() || *limit <= 0.0)
+            {
+                anyhow::bail!("LLM budget limits of '{}' must be > 0", name);
+            }
+            if let (Some(soft), Some(hard)) = (limits.soft, limits.hard)
+                && soft > hard
+            {
+                anyhow::bail!(
+                    "LLM soft budget limit of '{}' must not exceed its hard limit",
+                    name
+                );
+            }
+        }
+
+        let moderation = &llm.moderation;
+        if !moderation.provider.is_empty() {
+            let moderation_api = match llm.providers.get(&moderation.provider) {
+                Some(provider) => Some(provider.api),
+                None if moderation.provider == "openai" => Some(LlmApi::Openai),
+                None if moderation.provider == "anthropic" => Some(LlmApi::Anthropic),
+                None => None,
+            };
+            match moderation_api {
+                Some(LlmApi::Anthropic) => anyhow::bail!(
+                    "LLM moderation provider '{}' has no moderation API",
+                    moderation.provider
+                ),
+                None => anyhow::bail!(
+                    "LLM moderation provider '{}' is not configured",
+                    moderation.provider
+                ),
+                Some(_) => {}
+            }
+            if moderation.model.trim().is_empty() {
+                anyhow::bail!("LLM moderation model must not be empty");
+            }
+        }
+        for (category, patterns) in &moderation.patterns {
+            for pattern in patterns {
+                regex::Regex::new(pattern).map_err(|e| {
+                    anyhow::anyhow!("LLM moderation pattern of '{}' is invalid: {}", category, e)
+                })?;
+            }
+        }
+        if moderation.provider.is_empty()
+            && moderation.patterns.is_empty()
+            && (moderation.input != ModerationAction::Off
+                || moderation.output != ModerationAction::Off)
+        {
+            anyhow::bail!("LLM moderation needs a provider or patterns");
+        }
+
+        let webhooks = &self.javascript.webhooks;
+        if webhooks.max_attempts == 0
+            || webhooks.max_attempts > webhooks.max_attempts_limit
+            || webhooks.timeout_ms == 0
+            || webhooks.max_payload_bytes == 0
+        {
+            anyhow::bail!(
+                "JavaScript webhook limits must be > 0 and max_attempts must not exceed max_attempts_limit"
+            );
+        }
+        if webhooks.retry_delay_ms == 0 || webhooks.retry_delay_ms > webhooks.max_retry_delay_ms {
+            anyhow::bail!(
+                "JavaScript webhook retry_delay_ms must be > 0 and not exceed max_retry_delay_ms"
+            );
+        }
+
+        let tasks = &self.javascript.tasks;
+        if tasks.concurrency == 0
+            || tasks.max_attempts == 0
+            || tasks.max_attempts > tasks.max_attempts_limit
+            || tasks.max_payload_bytes == 0
+        {
+            anyhow::bail!(
+                "JavaScript task queue limits must be > 0 and max_attempts must not exceed max_attempts_limit"
+            );
+        }
+        if tasks.retry_delay_ms == 0 || tasks.retry_delay_ms > tasks.max_retry_delay_ms {
+            anyhow::bail!(
+                "JavaScript task queue retry_delay_ms must be > 0 and not exceed max_retry_delay_ms"
+            );
+        }
+        if tasks.visibility_timeout_ms <= self.javascript.execution_timeout_ms {
+            anyhow::bail!(
+                "JavaScript task queue visibility_timeout_ms must exceed execution_timeout_ms"
+            );
+        }
+
+        let email = &self.javascript.email;
+        if email.timeout_ms == 0 || email.max_recipients == 0 || email.max_message_bytes == 0 {
+            anyhow::bail!("JavaScript email limits must be > 0");
+        }
+        if email.provider != EmailProvider::Disabled
+            && email.from.parse::<lettre::message::Mailbox>().is_err()
+        {
+            anyhow::bail!(
+                "JavaScript email from must be an address such as \"Example <noreply@example.com>\""
+            );
+        }
+        if email.provider == EmailProvider::Smtp
+            && (email.smtp_host.trim().is_empty() || email.smtp_port == 0)
+        {
+            anyhow::bail!("JavaScript email smtp_host and smtp_port must be set for provider smtp");
+        }
+
+        let notify = &self.javascript.notify;
+        if notify.timeout_ms == 0 || notify.max_subscriptions_per_user <= 0 {
+            anyhow::bail!("JavaScript notify limits must be > 0");
+        }
+        if let Some(key) = &notify.vapid_private_key {
+            if let Err(e) = crate::notify::vapid_public_key(key) {
+                anyhow::bail!("JavaScript notify vapid_private_key is invalid: {}", e);
+            }
+            if !notify.vapid_subject.starts_with("mailto:")
+                && !notify.vapid_subject.starts_with("https://")
+            {
+                anyhow::bail!(
+                    "JavaScript notify vapid_subject must be a mailto: or https: URL when vapid_private_key is set"
+                );
+            }
+        }
+        let sms = &notify.sms;
+        let sms_missing = match sms.provider {
+            SmsProviderKind::Twilio => {
+                sms.account_sid.is_none() || sms.credentials_secret.is_none()
+            }
+            SmsProviderKind::Http => sms.api_url.is_none(),
+            SmsProviderKind::Disabled | SmsProviderKind::Log => false,
+        };
+        if sms_missing || (sms.provider != SmsProviderKind::Disabled && sms.from.trim().is_empty())
+        {
+            anyhow::bail!(
+                "JavaScript notify.sms needs from, and account_sid and credentials_secret for twilio or api_url for http"
+            );
+        }
+        if sms.max_length == 0 {
+            anyhow::bail!("JavaScript notify.sms max_length must be > 0");
+        }
+
+        let cache = &self.javascript.cache;
+        if cache.max_entries == 0
+            || cache.max_value_bytes == 0
+            || cache.default_ttl_seconds == 0
+            || cache.redis_timeout_ms == 0
+            || cache.max_responses == 0
+            || cache.max_response_bytes == 0
+        {
+            anyhow::bail!("JavaScript cache limits must be > 0");
+        }
+        if cache.default_ttl_seconds > cache.max_ttl_seconds {
+            anyhow::bail!("JavaScript cache default_ttl_seconds must not exceed max_ttl_seconds");
+        }
+        if let Some(url) = &cache.redis_url
+            && let Err(e) = redis::Client::open(url.as_str())
+        {
+            anyhow::bail!("JavaScript cache redis_url is invalid: {}", e);
+        }
+
+        if self.javascript.default_locale.trim().is_empty() {
+            anyhow::bail!("JavaScript default locale must not be empty");
+        }
+
+        // PostgreSQL is the only supported storage backend
         // Connection string is required and already enforced by type system
+        if self.repository.max_connections == 0 {
+            anyhow::bail!("Database max connections must be > 0");
+        }
+
+        if self.repository.min_connections > self.repository.max_connections {
+            anyhow::bail!("Database min connections cannot exceed max connections");
+        }
+
+        if self.repository.acquire_timeout_ms == 0 {
+            anyhow::bail!("Database acquire timeout must be > 0");
+        }
+
+        if self.repository.route_usage_flush_interval_secs == 0 {
+            anyhow::bail!("Route usage flush interval must be > 0");
+        }
+
+        if self.repository.max_upload_size_bytes == 0
+            || self.repository.max_upload_request_bytes < self.repository.max_upload_size_bytes
+        {
+            anyhow::bail!(
+                "Max upload size must be > 0 and max upload request size at least as large"
+            );
+        }
+
+        let audit_export = &self.security.audit_export;
+        if audit_export.enabled {
+            if audit_export.buffer_size == 0 || audit_export.batch_size == 0 {
+                anyhow::bail!("Audit export buffer and batch sizes must be > 0");
+            }
+            if let Some(https) = &audit_export.https
+                && !https.url.starts_with("https://")
+                && !https.url.starts_with("http://")
+            {
+                anyhow::bail!("Audit export https.url must be an http(s) URL");
+            }
+            if let Some(syslog) = &audit_export.syslog
+                && syslog.facility > 23
+            {
+                anyhow::bail!("Audit export syslog facility must be between 0 and 23");
+            }
+        }
+
+        let threat_response = &self.security.threat_response;
+        if threat_response.enabled {
+            let mut names = std::collections::HashSet::new();
+            for policy in &threat_response.policies {
+                if policy.name.trim().is_empty() || policy.indicator.trim().is_empty() {
+                    anyhow::bail!("Threat response policies need a name and an indicator");
+                }
+                if !names.insert(policy.name.as_str()) {
+                    anyhow::bail!("Duplicate threat response policy: '{}'", policy.name);
+                }
+            }
+        }
+
+        let graphql_ws = &self.server.graphql_ws;
+        if graphql_ws.max_subscriptions_per_connection == 0 || graphql_ws.send_buffer_messages == 0
+        {
+            anyhow::bail!(
+                "server.graphql_ws max_subscriptions_per_connection and send_buffer_messages must be > 0"
+            );
+        }
+
+        let script_quarantine = &self.security.script_quarantine;
+        if script_quarantine.enabled
+            && (script_quarantine.failure_threshold == 0
+                || script_quarantine.failure_window_secs == 0)
+        {
+            anyhow::bail!(
+                "Script quarantine failure_threshold and failure_window_secs must be > 0"
+            );
+        }
+
+        if self.metering.enabled {
+            if self.metering.flush_interval_secs == 0
+                || self.metering.storage_sample_interval_secs == 0
+            {
+                anyhow::bail!("Metering flush and storage sample intervals must be > 0");
+            }
+            if self.metering.sink == MeteringSinkKind::Webhook {
+                let url = self.metering.webhook_url.as_deref().unwrap_or_default();
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    anyhow::bail!("Metering webhook sink requires an http(s) webhook_url");
+                }
+                if self.metering.batch_size == 0 {
+                    anyhow::bail!("Metering batch size must be > 0");
+                }
+            }
+        }
+
+        for (key, overrides) in &self.tenancy.overrides {
+            if crate::tenancy::normalize_tenant_id(key).is_none() {
+                anyhow::bail!("Invalid tenancy override key: '{}'", key);
+            }
+            if overrides.execution_timeout_ms == Some(0) {
+                anyhow::bail!("Tenancy override '{}': execution timeout must be > 0", key);
+            }
+        }
 
         // Validate security configuration
         // Note: rate_limit_per_minute of 0 means disabled, which is allowed
+        for rule in &self.security.rate_limit_rules {
+            if !rule.path.starts_with('/') {
+                anyhow::bail!("Rate limit rule path must start with '/': '{}'", rule.path);
+            }
+            if rule.requests_per_minute == 0 {
+                anyhow::bail!(
+                    "Rate limit rule '{}': requests per minute must be > 0",
+                    rule.path
+                );
+            }
+        }
 
         if self.security.max_request_body_bytes == 0 {
             anyhow::bail!("Max request body size must be > 0");
@@ -580,6 +2425,11 @@ impl AppConfig {
             }
         }
 
+        // Anyone able to write to the watched directory can change scripts
+        if self.dev_watch.enabled && !self.security.development_mode {
+            anyhow::bail!("dev_watch.enabled requires security.development_mode");
+        }
+
         // Validate performance configuration
         if self.performance.compression_level < 1 || self.performance.compression_level > 9 {
             anyhow::bail!("Compression level must be between 1 and 9");
@@ -708,6 +2558,16 @@ mod tests {
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn test_dev_watch_requires_development_mode() {
+        let mut config = AppConfig::default();
+        config.dev_watch.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.security.development_mode = true;
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_server_address() {
         let config = AppConfig::default();
@@ -749,6 +2609,11 @@ mod tests {
         config.javascript.max_memory_bytes = 0;
         assert!(config.validate().is_err());
 
+        // A script's memory limit may not be capped below the default
+        config = AppConfig::default();
+        config.javascript.max_heap_bytes = config.javascript.max_memory_bytes - 1;
+        assert!(config.validate().is_err());
+
         // Test timeout edge cases - only zero timeout should fail
         config = AppConfig::default();
         config.javascript.execution_timeout_ms = 1;
@@ -758,6 +2623,35 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_tenant_override_validation() {
+        let mut config = AppConfig::default();
+        config.tenancy.overrides.insert(
+            "shop.example.com".to_string(),
+            TenantOverrideConfig {
+                execution_timeout_ms: Some(500),
+                ..Default::default()
+            },
+        );
+        assert!(config.validate().is_ok());
+
+        config.tenancy.overrides.insert(
+            "other.example.com".to_string(),
+            TenantOverrideConfig {
+                execution_timeout_ms: Some(0),
+                ..Default::default()
+            },
+        );
+        assert!(config.validate().is_err());
+
+        config = AppConfig::default();
+        config
+            .tenancy
+            .overrides
+            .insert("bad/key".to_string(), TenantOverrideConfig::default());
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_backward_compatibility() {
         let config = AppConfig::default();
@@ -790,6 +2684,153 @@ mod tests {
         assert_eq!(config.server.host, "127.0.0.1"); // Should keep other defaults
     }
 
+    #[test]
+    fn test_llm_validation() {
+        let mut config = AppConfig::default();
+        config.javascript.llm.providers.insert(
+            "local".to_string(),
+            LlmProviderConfig {
+                api: LlmApi::OpenaiCompatible,
+                base_url: None,
+                api_key_secret: None,
+            },
+        );
+        assert!(config.validate().is_err());
+
+        config
+            .javascript
+            .llm
+            .providers
+            .get_mut("local")
+            .unwrap()
+            .base_url = Some("ftp://localhost/v1".to_string());
+        assert!(config.validate().is_err());
+
+        config
+            .javascript
+            .llm
+            .providers
+            .get_mut("local")
+            .unwrap()
+            .base_url = Some("http://localhost:11434/v1".to_string());
+        assert!(config.validate().is_ok());
+
+        config.javascript.llm.embedding_provider = "anthropic".to_string();
+        assert!(config.validate().is_err());
+        config.javascript.llm.embedding_provider = "local".to_string();
+        assert!(config.validate().is_ok());
+
+        config.javascript.llm.budgets.script = LlmBudgetLimits {
+            soft: Some(20.0),
+            hard: Some(50.0),
+        };
+        config.javascript.llm.prices.insert(
+            "gpt-4o-mini*".to_string(),
+            LlmPrice {
+                input: 0.15,
+                output: 0.6,
+            },
+        );
+        assert!(config.validate().is_ok());
+        config.javascript.llm.budgets.scripts.insert(
+            "https://example.com/chat".to_string(),
+            LlmBudgetLimits {
+                soft: Some(5.0),
+                hard: Some(1.0),
+            },
+        );
+        assert!(config.validate().is_err());
+        config.javascript.llm.budgets.scripts.clear();
+        config.javascript.llm.budgets.user.hard = Some(0.0);
+        assert!(config.validate().is_err());
+        config.javascript.llm.budgets.user.hard = None;
+
+        config.javascript.llm.moderation.output = ModerationAction::Block;
+        assert!(config.validate().is_err());
+        config
+            .javascript
+            .llm
+            .moderation
+            .patterns
+            .insert("secrets".to_string(), vec!["(unclosed".to_string()]);
+        assert!(config.validate().is_err());
+        config.javascript.llm.moderation.patterns.insert(
+            "secrets".to_string(),
+            vec![r"sk-[A-Za-z0-9]{20,}".to_string()],
+        );
+        assert!(config.validate().is_ok());
+        config.javascript.llm.moderation.provider = "anthropic".to_string();
+        assert!(config.validate().is_err());
+        config.javascript.llm.moderation.provider = "openai".to_string();
+        assert!(config.validate().is_ok());
+
+        config.javascript.llm.max_output_tokens = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_webhook_validation() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+        config.javascript.webhooks.max_attempts = 30;
+        assert!(config.validate().is_err());
+        config.javascript.webhooks.max_attempts = 8;
+        config.javascript.webhooks.retry_delay_ms =
+            config.javascript.webhooks.max_retry_delay_ms + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_task_queue_validation() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+        config.javascript.tasks.concurrency = 0;
+        assert!(config.validate().is_err());
+        config.javascript.tasks.concurrency = 4;
+        config.javascript.tasks.visibility_timeout_ms = config.javascript.execution_timeout_ms;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_email_validation() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+        config.javascript.email.provider = EmailProvider::Smtp;
+        config.javascript.email.from = "Example <noreply@example.com>".to_string();
+        assert!(config.validate().is_err());
+        config.javascript.email.smtp_host = "smtp.example.com".to_string();
+        assert!(config.validate().is_ok());
+        config.javascript.email.from = "not an address".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_notify_validation() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+        config.javascript.notify.vapid_private_key = Some("not-a-key".to_string());
+        assert!(config.validate().is_err());
+        config.javascript.notify.vapid_private_key = None;
+        config.javascript.notify.sms.provider = SmsProviderKind::Twilio;
+        config.javascript.notify.sms.from = "+15550100".to_string();
+        assert!(config.validate().is_err());
+        config.javascript.notify.sms.account_sid = Some("AC123".to_string());
+        config.javascript.notify.sms.credentials_secret = Some("twilio_token".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_cache_validation() {
+        let mut config = AppConfig::default();
+        config.javascript.cache.default_ttl_seconds = 2 * config.javascript.cache.max_ttl_seconds;
+        assert!(config.validate().is_err());
+        config.javascript.cache.default_ttl_seconds = 60;
+        config.javascript.cache.redis_url = Some("not a url".to_string());
+        assert!(config.validate().is_err());
+        config.javascript.cache.redis_url = Some("redis://cache:6379/0".to_string());
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_security_validation() {
         let mut config = AppConfig::default();
@@ -804,6 +2845,21 @@ mod tests {
 
         config.security.rate_limit_per_minute = 100;
         assert!(config.validate().is_ok());
+
+        config.security.rate_limit_rules = vec![RateLimitRuleConfig {
+            path: "/api/*".to_string(),
+            methods: vec!["POST".to_string()],
+            per: crate::rate_limit_rules::RateLimitScope::Ip,
+            requests_per_minute: 0,
+            burst: None,
+        }];
+        assert!(config.validate().is_err());
+
+        config.security.rate_limit_rules[0].requests_per_minute = 60;
+        assert!(config.validate().is_ok());
+
+        config.security.rate_limit_rules[0].path = "api/*".to_string();
+        assert!(config.validate().is_err());
     }
 
     #[test]
@@ -837,6 +2893,24 @@ mod tests {
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn test_database_pool_validation() {
+        let mut config = AppConfig::default();
+
+        config.repository.max_connections = 0;
+        assert!(config.validate().is_err());
+
+        config.repository.max_connections = 2;
+        config.repository.min_connections = 3;
+        assert!(config.validate().is_err());
+
+        config.repository.min_connections = 2;
+        assert!(config.validate().is_ok());
+
+        config.repository.acquire_timeout_ms = 0;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_log_level_validation() {
         let mut config = AppConfig::default();
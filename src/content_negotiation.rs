@@ -0,0 +1,151 @@
+//! `Accept` header parsing and content negotiation.
+//!
+//! Backs `request.accepts(type)`, `request.preferredType(types)` and
+//! `ResponseBuilder.negotiate`. Types may be full media types or the
+//! shorthands `json`, `html`, `csv`, `text` and `xml`.
+//!
+//! A type's quality is that of the most specific media range matching it,
+//! so `text/*;q=0.5, text/html` prefers HTML over CSV. A missing or empty
+//! `Accept` header accepts everything.
+
+/// A media range from an `Accept` header
+#[derive(Debug, Clone, PartialEq)]
+struct MediaRange {
+    /// Type, or `*`
+    kind: String,
+    /// Subtype, or `*`
+    subtype: String,
+    quality: f32,
+}
+
+impl MediaRange {
+    /// How specifically the range matches `kind/subtype`, if it does: 2 for
+    /// an exact match, 1 for `kind/*` and 0 for `*/*`
+    fn specificity(&self, kind: &str, subtype: &str) -> Option<u8> {
+        match (self.kind.as_str(), self.subtype.as_str()) {
+            ("*", _) => Some(0),
+            (range_kind, "*") if range_kind == kind => Some(1),
+            (range_kind, range_subtype) if range_kind == kind && range_subtype == subtype => {
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The full media type of a shorthand, or `name` itself
+pub fn media_type(name: &str) -> String {
+    let name = name.trim().to_ascii_lowercase();
+    match name.as_str() {
+        "json" => "application/json".to_string(),
+        "html" => "text/html".to_string(),
+        "csv" => "text/csv".to_string(),
+        "text" => "text/plain".to_string(),
+        "xml" => "application/xml".to_string(),
+        _ => name,
+    }
+}
+
+fn parse_accept(header: &str) -> Vec<MediaRange> {
+    header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let (kind, subtype) = parts.next()?.trim().split_once('/')?;
+            let quality = parts
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+                .map_or(1.0, |quality| quality.clamp(0.0, 1.0));
+            Some(MediaRange {
+                kind: kind.trim().to_ascii_lowercase(),
+                subtype: subtype.trim().to_ascii_lowercase(),
+                quality,
+            })
+        })
+        .collect()
+}
+
+/// Quality the `Accept` header gives `media_type`; 0 when unacceptable
+fn quality(ranges: &[MediaRange], media_type: &str) -> f32 {
+    if ranges.is_empty() {
+        return 1.0;
+    }
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    let Some((kind, subtype)) = essence.split_once('/') else {
+        return 0.0;
+    };
+    ranges
+        .iter()
+        .filter_map(|range| Some((range.specificity(kind, subtype)?, range.quality)))
+        .max_by_key(|(specificity, _)| *specificity)
+        .map_or(0.0, |(_, quality)| quality)
+}
+
+/// Whether a request with this `Accept` header accepts `media_type`
+pub fn accepts(accept: Option<&str>, media_type_or_shorthand: &str) -> bool {
+    let ranges = parse_accept(accept.unwrap_or_default());
+    quality(&ranges, &media_type(media_type_or_shorthand)) > 0.0
+}
+
+/// Index of the type in `available` the `Accept` header prefers, earlier
+/// types winning ties, or `None` when it accepts none of them
+pub fn preferred(accept: Option<&str>, available: &[String]) -> Option<usize> {
+    let ranges = parse_accept(accept.unwrap_or_default());
+    let mut best: Option<(usize, f32)> = None;
+    for (index, name) in available.iter().enumerate() {
+        let quality = quality(&ranges, &media_type(name));
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((index, quality));
+        }
+    }
+    best.map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_accepts() {
+        let browser = Some("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8");
+        assert!(accepts(browser, "html"));
+        assert!(accepts(browser, "application/json"));
+        assert!(accepts(None, "csv"));
+        assert!(accepts(Some(""), "csv"));
+
+        let json_only = Some("application/json");
+        assert!(accepts(json_only, "json"));
+        assert!(!accepts(json_only, "html"));
+        assert!(!accepts(Some("text/*, text/csv;q=0"), "csv"));
+        assert!(accepts(
+            Some("text/*, text/csv;q=0"),
+            "text/plain; charset=utf-8"
+        ));
+    }
+
+    #[test]
+    fn test_preferred() {
+        let available = types(&["json", "html", "csv"]);
+        assert_eq!(preferred(None, &available), Some(0));
+        assert_eq!(preferred(Some("text/html,*/*;q=0.8"), &available), Some(1));
+        assert_eq!(
+            preferred(Some("text/*;q=0.5, text/csv"), &available),
+            Some(2)
+        );
+        // Equal qualities keep the handler's order
+        assert_eq!(preferred(Some("text/*"), &available), Some(1));
+        assert_eq!(preferred(Some("image/png"), &available), None);
+        assert_eq!(
+            preferred(
+                Some("application/*;q=0.2, */*;q=0.1"),
+                &types(&["text/csv", "json"])
+            ),
+            Some(1)
+        );
+    }
+}
@@ -1,6 +1,12 @@
 use base64::Engine;
+use chrono::{
+    DateTime, LocalResult, NaiveDate, NaiveDateTime, SecondsFormat, TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
 use handlebars::Handlebars;
-use pulldown_cmark::{Options, Parser, html};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd, html};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 /// Maximum size for markdown input (1MB)
 const MAX_MARKDOWN_SIZE: usize = 1_000_000;
@@ -8,6 +14,12 @@ const MAX_MARKDOWN_SIZE: usize = 1_000_000;
 /// Maximum size for Handlebars template input (1MB)
 const MAX_TEMPLATE_SIZE: usize = 1_000_000;
 
+/// Maximum size for HTML sanitization input (1MB)
+const MAX_HTML_SIZE: usize = 1_000_000;
+
+/// Elements removed with their content whatever the policy allows
+const ALWAYS_REMOVED_TAGS: [&str; 2] = ["script", "style"];
+
 /// Convert markdown string to HTML
 ///
 /// This function parses markdown using pulldown_cmark with the same options
@@ -54,6 +66,206 @@ pub fn convert_markdown_to_html(markdown: &str) -> Result<String, String> {
     Ok(html_output)
 }
 
+/// Options of `markdown.render`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct MarkdownOptions {
+    /// GitHub-style tables
+    pub tables: bool,
+    /// `~~strikethrough~~`
+    pub strikethrough: bool,
+    /// `- [x] done` task list items
+    pub task_lists: bool,
+    /// `[^1]` footnotes
+    pub footnotes: bool,
+    /// Curly quotes, dashes and ellipses
+    pub smart_punctuation: bool,
+    /// Keep raw HTML in the input instead of escaping it. Only for trusted
+    /// input: the HTML is not sanitized.
+    pub allow_html: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            strikethrough: false,
+            task_lists: false,
+            footnotes: false,
+            smart_punctuation: false,
+            allow_html: false,
+        }
+    }
+}
+
+impl MarkdownOptions {
+    fn parser_options(&self) -> Options {
+        let mut options = Options::empty();
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.task_lists);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_SMART_PUNCTUATION, self.smart_punctuation);
+        options
+    }
+}
+
+/// Whether a link or image URL is safe to render: relative, or with an
+/// http, https, mailto or tel scheme
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters in schemes
+    let cleaned: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect();
+    let Some((scheme, _)) = cleaned.split_once(':') else {
+        return true;
+    };
+    if scheme.contains(['/', '?', '#']) {
+        return true;
+    }
+    matches!(
+        scheme.to_ascii_lowercase().as_str(),
+        "http" | "https" | "mailto" | "tel"
+    )
+}
+
+/// Render CommonMark markdown to HTML that is safe to embed in a page
+///
+/// Raw HTML in the input is escaped unless `options.allow_html` is set, and
+/// links and images with unsafe URLs (such as `javascript:`) are rendered as
+/// their text only.
+///
+/// # Errors
+/// * Returns error if markdown exceeds 1MB size limit
+pub fn render_markdown(markdown: &str, options: &MarkdownOptions) -> Result<String, String> {
+    if markdown.len() > MAX_MARKDOWN_SIZE {
+        return Err(format!(
+            "Markdown input too large: {} bytes (max: {} bytes / 1MB)",
+            markdown.len(),
+            MAX_MARKDOWN_SIZE
+        ));
+    }
+
+    // Whether each open link and image is rendered
+    let mut links_kept = Vec::new();
+    let mut images_kept = Vec::new();
+    let events =
+        Parser::new_ext(markdown, options.parser_options()).filter_map(|event| match event {
+            Event::Html(raw) | Event::InlineHtml(raw) if !options.allow_html => {
+                Some(Event::Text(raw))
+            }
+            Event::Start(Tag::Link { ref dest_url, .. }) => {
+                let keep = is_safe_url(dest_url);
+                links_kept.push(keep);
+                keep.then_some(event)
+            }
+            Event::End(TagEnd::Link) => links_kept.pop().unwrap_or(true).then_some(event),
+            Event::Start(Tag::Image { ref dest_url, .. }) => {
+                let keep = is_safe_url(dest_url);
+                images_kept.push(keep);
+                keep.then_some(event)
+            }
+            Event::End(TagEnd::Image) => images_kept.pop().unwrap_or(true).then_some(event),
+            event => Some(event),
+        });
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events);
+    Ok(html_output)
+}
+
+/// Allowlists of `html.sanitize`. Lists that are not set keep the default
+/// allowlists, which cover common formatting, links, images and tables.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct HtmlPolicy {
+    /// Elements to keep; other elements are removed, keeping their content
+    pub tags: Option<Vec<String>>,
+    /// Attributes to keep by element name; `"*"` lists the attributes kept
+    /// on every element
+    pub attributes: Option<HashMap<String, Vec<String>>>,
+    /// URL schemes allowed in links and images; relative URLs are always
+    /// allowed
+    pub url_schemes: Option<Vec<String>>,
+    /// `rel` set on every link, `"noopener noreferrer"` by default; an empty
+    /// string leaves links as they are. Ignored when `rel` is an allowed
+    /// attribute of links.
+    pub link_rel: Option<String>,
+}
+
+/// Remove elements, attributes and URLs that `policy` does not allow from
+/// `input`, so it can be embedded in a page without script injection.
+/// `<script>` and `<style>` elements are always removed with their content,
+/// as are comments and event handler attributes not explicitly allowed.
+///
+/// # Errors
+/// * Returns error if input exceeds 1MB size limit
+/// * Returns error if the policy allows `script` or `style` elements
+pub fn sanitize_html(input: &str, policy: &HtmlPolicy) -> Result<String, String> {
+    if input.len() > MAX_HTML_SIZE {
+        return Err(format!(
+            "HTML input too large: {} bytes (max: {} bytes / 1MB)",
+            input.len(),
+            MAX_HTML_SIZE
+        ));
+    }
+
+    let lowercase = |names: &[String]| -> Vec<String> {
+        names
+            .iter()
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect()
+    };
+    let tags = policy.tags.as_deref().map(lowercase);
+    let attributes: Option<Vec<(String, Vec<String>)>> = policy.attributes.as_ref().map(|map| {
+        map.iter()
+            .map(|(tag, names)| (tag.trim().to_ascii_lowercase(), lowercase(names)))
+            .collect()
+    });
+    let url_schemes = policy.url_schemes.as_deref().map(lowercase);
+
+    for tag in ALWAYS_REMOVED_TAGS {
+        let in_tags = tags.iter().flatten().any(|name| name == tag);
+        let in_attributes = attributes.iter().flatten().any(|(name, _)| name == tag);
+        if in_tags || in_attributes {
+            return Err(format!("<{}> elements cannot be allowed", tag));
+        }
+    }
+
+    let mut builder = ammonia::Builder::default();
+    if let Some(tags) = &tags {
+        builder.tags(tags.iter().map(String::as_str).collect());
+    }
+    let mut rel_allowed = false;
+    if let Some(attributes) = &attributes {
+        let mut generic = HashSet::new();
+        let mut by_tag = HashMap::new();
+        for (tag, names) in attributes {
+            let names: HashSet<&str> = names.iter().map(String::as_str).collect();
+            rel_allowed |= (tag == "*" || tag == "a") && names.contains("rel");
+            if tag == "*" {
+                generic.extend(names);
+            } else {
+                by_tag.insert(tag.as_str(), names);
+            }
+        }
+        builder.generic_attributes(generic).tag_attributes(by_tag);
+    }
+    if let Some(url_schemes) = &url_schemes {
+        builder.url_schemes(url_schemes.iter().map(String::as_str).collect());
+    }
+    let link_rel = match policy.link_rel.as_deref() {
+        _ if rel_allowed => None,
+        Some("") => None,
+        Some(rel) => Some(rel),
+        None => Some("noopener noreferrer"),
+    };
+    builder.link_rel(link_rel);
+
+    Ok(builder.clean(input).to_string())
+}
+
 /// Render Handlebars template with data
 ///
 /// This function compiles and renders a Handlebars template with the provided data object.
@@ -134,6 +346,732 @@ pub fn convert_atob(input: &str) -> Result<String, String> {
     String::from_utf8(decoded).map_err(|e| format!("Decoded data is not valid UTF-8: {}", e))
 }
 
+/// Maximum size for iCalendar input (1MB)
+const MAX_ICALENDAR_SIZE: usize = 1_000_000;
+
+/// Maximum number of events in a built iCalendar document
+const MAX_ICALENDAR_EVENTS: usize = 1000;
+
+/// Longest iCalendar content line in octets; longer lines are folded
+const ICALENDAR_LINE_OCTETS: usize = 75;
+
+/// Methods of an iCalendar document sent as a scheduling message
+const ICALENDAR_METHODS: [&str; 8] = [
+    "PUBLISH",
+    "REQUEST",
+    "REPLY",
+    "ADD",
+    "CANCEL",
+    "REFRESH",
+    "COUNTER",
+    "DECLINECOUNTER",
+];
+
+/// Statuses of an event
+const ICALENDAR_EVENT_STATUSES: [&str; 3] = ["TENTATIVE", "CONFIRMED", "CANCELLED"];
+
+/// Participation statuses of an attendee
+const ICALENDAR_PARTICIPATION_STATUSES: [&str; 5] = [
+    "NEEDS-ACTION",
+    "ACCEPTED",
+    "DECLINED",
+    "TENTATIVE",
+    "DELEGATED",
+];
+
+/// An event of `ical.build` and `ical.parse`.
+///
+/// `start` and `end` are `YYYY-MM-DD` dates for all-day events (the end
+/// date is exclusive), RFC 3339 timestamps, or local `YYYY-MM-DDTHH:MM:SS`
+/// times in `timeZone`. Local times without a time zone are floating: the
+/// same wall-clock time wherever the calendar is opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CalendarEvent {
+    /// Unique identifier; generated when building an event without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub start: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    /// IANA time zone of the event, e.g. `Europe/Helsinki`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// `TENTATIVE`, `CONFIRMED` or `CANCELLED`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Revision of the event; increase it when sending an updated invite
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>,
+    /// Recurrence rule, e.g. `FREQ=WEEKLY;COUNT=4`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organizer: Option<CalendarPerson>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attendees: Vec<CalendarPerson>,
+}
+
+/// Organizer or attendee of a [`CalendarEvent`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CalendarPerson {
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Whether a reply is requested from the attendee
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub rsvp: bool,
+    /// Participation status, e.g. `ACCEPTED` or `NEEDS-ACTION`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+}
+
+/// Options of `ical.build`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct IcalOptions {
+    /// Scheduling method, e.g. `REQUEST` for an invite or `CANCEL`
+    pub method: Option<String>,
+    /// Calendar name shown by calendar applications
+    pub name: Option<String>,
+    /// Product identifier, `-//aiwebengine//EN` by default
+    pub prod_id: Option<String>,
+}
+
+/// Calendar read by `ical.parse`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedCalendar {
+    pub method: Option<String>,
+    pub name: Option<String>,
+    pub events: Vec<CalendarEvent>,
+}
+
+/// A `start` or `end` of an event to be written
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EventTime {
+    Date(NaiveDate),
+    Utc(DateTime<Utc>),
+    Zoned(NaiveDateTime, Tz),
+    Floating(NaiveDateTime),
+}
+
+impl EventTime {
+    /// Interpret `value` for an event in `time_zone`. Times of recurring
+    /// events stay in their time zone so the recurrences follow its
+    /// daylight saving changes; other times are converted to UTC.
+    fn resolve(value: &str, time_zone: Option<Tz>, recurring: bool) -> Result<Self, String> {
+        let value = value.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(Self::Date(date));
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+            let time = time.with_timezone(&Utc);
+            return Ok(match time_zone {
+                Some(tz) if recurring => Self::Zoned(time.with_timezone(&tz).naive_local(), tz),
+                _ => Self::Utc(time),
+            });
+        }
+        let local = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .ok_or_else(|| format!("'{}' is not a date or date-time", value))?;
+        match time_zone {
+            None => Ok(Self::Floating(local)),
+            Some(tz) if recurring => Ok(Self::Zoned(local, tz)),
+            Some(tz) => match tz.from_local_datetime(&local) {
+                LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => {
+                    Ok(Self::Utc(time.with_timezone(&Utc)))
+                }
+                LocalResult::None => Err(format!("'{}' does not exist in {}", value, tz.name())),
+            },
+        }
+    }
+
+    /// Whether `self` is before `other`, when the two are comparable
+    fn is_before(&self, other: &Self) -> Option<bool> {
+        match (self, other) {
+            (Self::Date(a), Self::Date(b)) => Some(a < b),
+            (Self::Utc(a), Self::Utc(b)) => Some(a < b),
+            (Self::Zoned(a, _), Self::Zoned(b, _)) | (Self::Floating(a), Self::Floating(b)) => {
+                Some(a < b)
+            }
+            _ => None,
+        }
+    }
+
+    /// `name` property line of the time
+    fn property(&self, name: &str) -> String {
+        const DATE_TIME: &str = "%Y%m%dT%H%M%S";
+        match self {
+            Self::Date(date) => format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d")),
+            Self::Utc(time) => format!("{}:{}Z", name, time.format(DATE_TIME)),
+            Self::Zoned(time, tz) => {
+                format!("{};TZID={}:{}", name, tz.name(), time.format(DATE_TIME))
+            }
+            Self::Floating(time) => format!("{}:{}", name, time.format(DATE_TIME)),
+        }
+    }
+}
+
+/// Escape a TEXT value
+fn escape_ical_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A parameter value, quoted when it contains separators. Quotes and
+/// control characters cannot be represented and are dropped.
+fn ical_param_value(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| *c != '"' && !c.is_control())
+        .collect();
+    if value.contains([':', ';', ',']) {
+        format!("\"{}\"", value)
+    } else {
+        value
+    }
+}
+
+/// Append `line` to `output`, folded to lines of at most 75 octets
+fn push_folded(output: &mut String, line: &str) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > ICALENDAR_LINE_OCTETS {
+            output.push_str("\r\n ");
+            octets = 1;
+        }
+        output.push(c);
+        octets += c.len_utf8();
+    }
+    output.push_str("\r\n");
+}
+
+/// One of `allowed`, matched case-insensitively
+fn ical_keyword(value: &str, allowed: &[&str], what: &str) -> Result<String, String> {
+    let value = value.trim().to_ascii_uppercase();
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(format!(
+            "{} must be one of {}, got '{}'",
+            what,
+            allowed.join(", "),
+            value
+        ))
+    }
+}
+
+/// ORGANIZER or ATTENDEE property line of `person`
+fn ical_person(name: &str, person: &CalendarPerson) -> Result<String, String> {
+    let email = person.email.trim();
+    if !email.contains('@') || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(format!("'{}' is not an email address", email));
+    }
+    let mut line = name.to_string();
+    if let Some(cn) = &person.name {
+        line.push_str(";CN=");
+        line.push_str(&ical_param_value(cn));
+    }
+    if let Some(response) = &person.response {
+        line.push_str(";PARTSTAT=");
+        line.push_str(&ical_keyword(
+            response,
+            &ICALENDAR_PARTICIPATION_STATUSES,
+            "response",
+        )?);
+    }
+    if person.rsvp {
+        line.push_str(";RSVP=TRUE");
+    }
+    line.push_str(":mailto:");
+    line.push_str(email);
+    Ok(line)
+}
+
+/// Content lines of one event
+fn ical_event_lines(event: &CalendarEvent, now: DateTime<Utc>) -> Result<Vec<String>, String> {
+    let time_zone = event
+        .time_zone
+        .as_deref()
+        .map(|name| {
+            name.trim()
+                .parse::<Tz>()
+                .map_err(|_| format!("Unknown time zone '{}'", name))
+        })
+        .transpose()?;
+    let recurrence = event
+        .recurrence
+        .as_deref()
+        .map(|rule| {
+            let rule = rule.trim();
+            let rule = rule
+                .strip_prefix("RRULE:")
+                .unwrap_or(rule)
+                .to_ascii_uppercase();
+            let valid = rule.split(';').any(|part| part.starts_with("FREQ="))
+                && !rule.chars().any(|c| c.is_control() || c.is_whitespace());
+            if valid {
+                Ok(rule)
+            } else {
+                Err(format!("'{}' is not a recurrence rule", rule))
+            }
+        })
+        .transpose()?;
+    if event.start.trim().is_empty() {
+        return Err("start is required".to_string());
+    }
+    let start = EventTime::resolve(&event.start, time_zone, recurrence.is_some())?;
+    let end = event
+        .end
+        .as_deref()
+        .map(|end| EventTime::resolve(end, time_zone, recurrence.is_some()))
+        .transpose()?;
+    if let Some(end) = &end {
+        match start.is_before(end) {
+            Some(true) => {}
+            Some(false) => return Err("end must be after start".to_string()),
+            None if matches!(start, EventTime::Date(_)) || matches!(end, EventTime::Date(_)) => {
+                return Err("start and end must both be dates or both date-times".to_string());
+            }
+            None => {}
+        }
+    }
+
+    let uid = event
+        .uid
+        .clone()
+        .unwrap_or_else(|| format!("{}@aiwebengine", uuid::Uuid::new_v4()));
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", escape_ical_text(&uid)),
+        format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")),
+        start.property("DTSTART"),
+    ];
+    if let Some(end) = end {
+        lines.push(end.property("DTEND"));
+    }
+    if let Some(rule) = recurrence {
+        lines.push(format!("RRULE:{}", rule));
+    }
+    for (name, value) in [
+        ("SUMMARY", &event.summary),
+        ("DESCRIPTION", &event.description),
+        ("LOCATION", &event.location),
+    ] {
+        if let Some(value) = value {
+            lines.push(format!("{}:{}", name, escape_ical_text(value)));
+        }
+    }
+    if let Some(url) = &event.url {
+        if url.chars().any(|c| c.is_control() || c.is_whitespace()) {
+            return Err(format!("'{}' is not a URL", url.trim()));
+        }
+        lines.push(format!("URL:{}", url));
+    }
+    if let Some(status) = &event.status {
+        let status = ical_keyword(status, &ICALENDAR_EVENT_STATUSES, "status")?;
+        lines.push(format!("STATUS:{}", status));
+    }
+    if let Some(sequence) = event.sequence {
+        lines.push(format!("SEQUENCE:{}", sequence));
+    }
+    if let Some(organizer) = &event.organizer {
+        lines.push(ical_person("ORGANIZER", organizer)?);
+    }
+    for attendee in &event.attendees {
+        lines.push(ical_person("ATTENDEE", attendee)?);
+    }
+    lines.push("END:VEVENT".to_string());
+    Ok(lines)
+}
+
+/// Write `events` as an iCalendar (RFC 5545) document
+///
+/// Event times with a `timeZone` are converted to UTC, except in recurring
+/// events, which keep the time zone's name so that every recurrence falls
+/// on the same wall-clock time across daylight saving changes.
+///
+/// # Errors
+/// * Returns error if there are more than 1000 events
+/// * Returns error if an event has no valid `start`, an unknown time zone,
+///   or an invalid status, recurrence rule or email address
+pub fn build_icalendar(events: &[CalendarEvent], options: &IcalOptions) -> Result<String, String> {
+    build_icalendar_at(events, options, Utc::now())
+}
+
+fn build_icalendar_at(
+    events: &[CalendarEvent],
+    options: &IcalOptions,
+    now: DateTime<Utc>,
+) -> Result<String, String> {
+    if events.len() > MAX_ICALENDAR_EVENTS {
+        return Err(format!(
+            "Too many events: {} (max: {})",
+            events.len(),
+            MAX_ICALENDAR_EVENTS
+        ));
+    }
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:{}",
+            escape_ical_text(options.prod_id.as_deref().unwrap_or("-//aiwebengine//EN"))
+        ),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    if let Some(method) = &options.method {
+        lines.push(format!(
+            "METHOD:{}",
+            ical_keyword(method, &ICALENDAR_METHODS, "method")?
+        ));
+    }
+    if let Some(name) = &options.name {
+        lines.push(format!("X-WR-CALNAME:{}", escape_ical_text(name)));
+    }
+    for (index, event) in events.iter().enumerate() {
+        lines
+            .extend(ical_event_lines(event, now).map_err(|e| format!("events[{}]: {}", index, e))?);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut output = String::new();
+    for line in &lines {
+        push_folded(&mut output, line);
+    }
+    Ok(output)
+}
+
+/// Undo the escaping of a TEXT value
+fn unescape_ical_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// A content line split into its upper-cased name, parameters and value
+struct ContentLine {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn parse(line: &str) -> Option<Self> {
+        // Separators inside quoted parameter values do not count
+        let mut in_quotes = false;
+        let mut separators = Vec::new();
+        let mut value_start = None;
+        for (index, c) in line.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => separators.push(index),
+                ':' if !in_quotes => {
+                    value_start = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let value_start = value_start?;
+        let mut bounds = vec![0];
+        bounds.extend(separators.iter().map(|index| index + 1));
+        let mut ends = separators.clone();
+        ends.push(value_start);
+
+        let mut segments = bounds
+            .iter()
+            .zip(&ends)
+            .map(|(start, end)| &line[*start..*end]);
+        let name = segments.next()?.trim().to_ascii_uppercase();
+        if name.is_empty() {
+            return None;
+        }
+        let params = segments
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| {
+                (
+                    key.trim().to_ascii_uppercase(),
+                    value.trim().trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+        Some(Self {
+            name,
+            params,
+            value: line[value_start + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse a DATE or DATE-TIME value into the form of [`CalendarEvent`] times,
+/// with the time zone it was given in
+fn parse_ical_time(line: &ContentLine) -> Result<(String, Option<String>), String> {
+    let value = line.value.trim();
+    let is_date = line
+        .param("VALUE")
+        .is_some_and(|kind| kind.eq_ignore_ascii_case("DATE"))
+        || (value.len() == 8 && value.bytes().all(|b| b.is_ascii_digit()));
+    if is_date {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map_err(|_| format!("Invalid date '{}'", value))?;
+        return Ok((date.format("%Y-%m-%d").to_string(), None));
+    }
+    let utc = value.strip_suffix(['Z', 'z']);
+    let local = NaiveDateTime::parse_from_str(utc.unwrap_or(value), "%Y%m%dT%H%M%S")
+        .map_err(|_| format!("Invalid date-time '{}'", value))?;
+    if utc.is_some() {
+        return Ok((
+            local.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true),
+            None,
+        ));
+    }
+    let Some(tzid) = line.param("TZID") else {
+        return Ok((local.format("%Y-%m-%dT%H:%M:%S").to_string(), None));
+    };
+    // Times in IANA time zones get their offset; other zones, such as the
+    // Windows names some clients use, are kept as local times
+    let time = tzid
+        .trim_start_matches('/')
+        .parse::<Tz>()
+        .ok()
+        .and_then(|tz| tz.from_local_datetime(&local).earliest());
+    Ok(match time {
+        Some(time) => (
+            time.to_rfc3339_opts(SecondsFormat::Secs, false),
+            Some(time.timezone().name().to_string()),
+        ),
+        None => (
+            local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            Some(tzid.to_string()),
+        ),
+    })
+}
+
+/// Parse a DURATION value such as `PT1H30M` or `P1D`
+fn parse_ical_duration(value: &str) -> Option<TimeDelta> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P')?;
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            unit => {
+                let amount: i64 = std::mem::take(&mut number).parse().ok()?;
+                let unit_seconds = match (unit, in_time) {
+                    ('W', false) => 7 * 86_400,
+                    ('D', false) => 86_400,
+                    ('H', true) => 3_600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                seconds = seconds.checked_add(amount.checked_mul(unit_seconds)?)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    TimeDelta::try_seconds(if negative { -seconds } else { seconds })
+}
+
+/// `start` moved by `duration`, in the same form
+fn add_ical_duration(start: &str, duration: TimeDelta) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(start, "%Y-%m-%d") {
+        return Some(
+            date.checked_add_signed(duration)?
+                .format("%Y-%m-%d")
+                .to_string(),
+        );
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(start) {
+        let use_z = start.ends_with('Z');
+        return Some(
+            time.checked_add_signed(duration)?
+                .to_rfc3339_opts(SecondsFormat::Secs, use_z),
+        );
+    }
+    let local = NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M:%S").ok()?;
+    Some(
+        local
+            .checked_add_signed(duration)?
+            .format("%Y-%m-%dT%H:%M:%S")
+            .to_string(),
+    )
+}
+
+/// Organizer or attendee of an ORGANIZER or ATTENDEE line
+fn parse_ical_person(line: &ContentLine) -> CalendarPerson {
+    let value = line.value.trim();
+    let email = match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    };
+    CalendarPerson {
+        email: email.to_string(),
+        name: line.param("CN").map(str::to_string),
+        rsvp: line
+            .param("RSVP")
+            .is_some_and(|rsvp| rsvp.eq_ignore_ascii_case("TRUE")),
+        response: line.param("PARTSTAT").map(str::to_ascii_uppercase),
+    }
+}
+
+/// Read the events of an iCalendar (RFC 5545) document
+///
+/// Event times are returned like [`CalendarEvent`] times: dates, UTC
+/// timestamps, timestamps with the offset of their IANA time zone, or local
+/// times. An event with a duration instead of an end gets the end it
+/// implies. Alarms, to-dos and unknown properties are ignored.
+///
+/// # Errors
+/// * Returns error if input exceeds 1MB size limit
+/// * Returns error if the input is not an iCalendar document, or has
+///   malformed lines, dates or unbalanced components
+pub fn parse_icalendar(text: &str) -> Result<ParsedCalendar, String> {
+    if text.len() > MAX_ICALENDAR_SIZE {
+        return Err(format!(
+            "iCalendar input too large: {} bytes (max: {} bytes / 1MB)",
+            text.len(),
+            MAX_ICALENDAR_SIZE
+        ));
+    }
+
+    // Unfold: a line starting with a space or tab continues the previous one
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ => lines.push((index + 1, raw.to_string())),
+        }
+    }
+    lines.retain(|(_, line)| !line.trim().is_empty());
+    let starts_calendar = lines
+        .first()
+        .is_some_and(|(_, line)| line.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR"));
+    if !starts_calendar {
+        return Err("Input is not an iCalendar document".to_string());
+    }
+
+    let mut calendar = ParsedCalendar::default();
+    let mut components: Vec<String> = Vec::new();
+    let mut event = CalendarEvent::default();
+    let mut duration = None;
+    for (number, line) in lines {
+        let line =
+            ContentLine::parse(&line).ok_or_else(|| format!("Line {} is not valid", number))?;
+        let at_line = |e: String| format!("Line {}: {}", number, e);
+        match line.name.as_str() {
+            "BEGIN" => {
+                let component = line.value.trim().to_ascii_uppercase();
+                if component == "VEVENT" {
+                    event = CalendarEvent::default();
+                    duration = None;
+                }
+                components.push(component);
+            }
+            "END" => {
+                let component = line.value.trim().to_ascii_uppercase();
+                if components.pop().as_deref() != Some(component.as_str()) {
+                    return Err(at_line(format!("Unexpected END:{}", component)));
+                }
+                if component == "VEVENT" {
+                    let mut finished = std::mem::take(&mut event);
+                    if let (None, Some(duration)) = (&finished.end, duration) {
+                        finished.end = add_ical_duration(&finished.start, duration);
+                    }
+                    calendar.events.push(finished);
+                }
+            }
+            _ => match components.last().map(String::as_str) {
+                Some("VCALENDAR") => match line.name.as_str() {
+                    "METHOD" => calendar.method = Some(line.value.trim().to_ascii_uppercase()),
+                    "X-WR-CALNAME" => calendar.name = Some(unescape_ical_text(&line.value)),
+                    _ => {}
+                },
+                Some("VEVENT") => match line.name.as_str() {
+                    "UID" => event.uid = Some(unescape_ical_text(&line.value)),
+                    "SUMMARY" => event.summary = Some(unescape_ical_text(&line.value)),
+                    "DESCRIPTION" => event.description = Some(unescape_ical_text(&line.value)),
+                    "LOCATION" => event.location = Some(unescape_ical_text(&line.value)),
+                    "URL" => event.url = Some(line.value.trim().to_string()),
+                    "DTSTART" => {
+                        let (start, time_zone) = parse_ical_time(&line).map_err(at_line)?;
+                        event.start = start;
+                        event.time_zone = time_zone;
+                    }
+                    "DTEND" => event.end = Some(parse_ical_time(&line).map_err(at_line)?.0),
+                    "DURATION" => {
+                        duration = Some(parse_ical_duration(&line.value).ok_or_else(|| {
+                            at_line(format!("Invalid duration '{}'", line.value.trim()))
+                        })?);
+                    }
+                    "STATUS" => event.status = Some(line.value.trim().to_ascii_uppercase()),
+                    "SEQUENCE" => event.sequence = line.value.trim().parse().ok(),
+                    "RRULE" => event.recurrence = Some(line.value.trim().to_string()),
+                    "ORGANIZER" => event.organizer = Some(parse_ical_person(&line)),
+                    "ATTENDEE" => event.attendees.push(parse_ical_person(&line)),
+                    _ => {}
+                },
+                _ => {}
+            },
+        }
+    }
+
+    if let Some(component) = components.last() {
+        return Err(format!("BEGIN:{} is never ended", component));
+    }
+    Ok(calendar)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,6 +1181,301 @@ mod tests {
         assert!(html.contains("<a href=\"https://example.com\">Example</a>"));
     }
 
+    #[test]
+    fn test_render_markdown_defaults() {
+        let options = MarkdownOptions::default();
+        let html = render_markdown("| A | B |\n|---|---|\n| 1 | 2 |\n\n~~old~~", &options).unwrap();
+        assert!(html.contains("<td>1</td>"));
+        assert!(html.contains("~~old~~"), "strikethrough is opt-in");
+        assert_eq!(render_markdown("", &options).unwrap(), "");
+
+        let options: MarkdownOptions =
+            serde_json::from_str(r#"{"tables": false, "strikethrough": true}"#).unwrap();
+        let html = render_markdown("| A |\n|---|\n\n~~old~~", &options).unwrap();
+        assert!(!html.contains("<table>"));
+        assert!(html.contains("<del>old</del>"));
+        assert!(serde_json::from_str::<MarkdownOptions>(r#"{"tabels": true}"#).is_err());
+    }
+
+    #[test]
+    fn test_render_markdown_sanitizes() {
+        let options = MarkdownOptions::default();
+        let html = render_markdown(
+            "<script>alert(1)</script>\n\nHi <b onclick=\"x()\">there</b>",
+            &options,
+        )
+        .unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b "));
+        assert!(html.contains("&lt;script&gt;"));
+
+        let html = render_markdown(
+            "[bad](javascript:alert(1)) [also bad](JaVaScRiPt:x) ![img](data:text/html,x) \
+             [good](https://example.com) [local](/docs/intro#top)",
+            &options,
+        )
+        .unwrap();
+        assert!(!html.to_lowercase().contains("script:"));
+        assert!(!html.contains("data:"));
+        assert!(html.contains("bad also bad"));
+        assert!(html.contains("<a href=\"https://example.com\">good</a>"));
+        assert!(html.contains("<a href=\"/docs/intro#top\">local</a>"));
+        assert!(!is_safe_url(" java\tscript:alert(1)"));
+        assert!(is_safe_url("mailto:team@example.com"));
+        assert!(is_safe_url("page?next=a:b"));
+
+        let trusted = MarkdownOptions {
+            allow_html: true,
+            ..MarkdownOptions::default()
+        };
+        let html = render_markdown("Hi <b>there</b>", &trusted).unwrap();
+        assert!(html.contains("<b>there</b>"));
+    }
+
+    #[test]
+    fn test_sanitize_html_default_policy() {
+        let policy = HtmlPolicy::default();
+        let html = sanitize_html(
+            r#"<p onclick="steal()">Hi <b>there</b><script>alert(1)</script></p><!-- note -->
+<a href="javascript:alert(1)">x</a> <a href="https://example.com">y</a><iframe src="/x"></iframe>"#,
+            &policy,
+        )
+        .unwrap();
+        assert!(html.contains("<p>Hi <b>there</b></p>"), "{}", html);
+        assert!(
+            !html.contains("script") && !html.contains("alert"),
+            "{}",
+            html
+        );
+        assert!(
+            !html.contains("note") && !html.contains("iframe"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains("<a rel=\"noopener noreferrer\">x</a>"),
+            "{}",
+            html
+        );
+        assert!(
+            html.contains(r#"<a href="https://example.com" rel="noopener noreferrer">y</a>"#),
+            "{}",
+            html
+        );
+    }
+
+    #[test]
+    fn test_sanitize_html_custom_policy() {
+        let policy: HtmlPolicy = serde_json::from_str(
+            r#"{
+                "tags": ["P", "a", "span"],
+                "attributes": {"a": ["href", "rel"], "*": ["class"]},
+                "urlSchemes": ["https"]
+            }"#,
+        )
+        .unwrap();
+        let html = sanitize_html(
+            r#"<p class="lead"><b>bold</b> <span title="t">s</span> <a href="http://a" rel="me">a</a></p>"#,
+            &policy,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            r#"<p class="lead">bold <span>s</span> <a rel="me">a</a></p>"#
+        );
+
+        let allows_script = HtmlPolicy {
+            tags: Some(vec!["script".to_string()]),
+            ..HtmlPolicy::default()
+        };
+        assert_eq!(
+            sanitize_html("<script></script>", &allows_script).unwrap_err(),
+            "<script> elements cannot be allowed"
+        );
+        assert!(serde_json::from_str::<HtmlPolicy>(r#"{"tag": ["p"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_build_icalendar() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let events = vec![
+            CalendarEvent {
+                uid: Some("a@example.com".to_string()),
+                summary: Some("Review; notes, etc.".to_string()),
+                start: "2026-07-01T10:00".to_string(),
+                end: Some("2026-07-01T11:00".to_string()),
+                time_zone: Some("Europe/Helsinki".to_string()),
+                organizer: Some(CalendarPerson {
+                    email: "host@example.com".to_string(),
+                    name: Some("Host: Team".to_string()),
+                    ..CalendarPerson::default()
+                }),
+                ..CalendarEvent::default()
+            },
+            CalendarEvent {
+                uid: Some("b@example.com".to_string()),
+                start: "2026-03-27T09:00".to_string(),
+                time_zone: Some("Europe/Helsinki".to_string()),
+                recurrence: Some("freq=weekly;count=2".to_string()),
+                ..CalendarEvent::default()
+            },
+            CalendarEvent {
+                uid: Some("c@example.com".to_string()),
+                description: Some("x".repeat(100)),
+                start: "2026-12-24".to_string(),
+                end: Some("2026-12-27".to_string()),
+                ..CalendarEvent::default()
+            },
+        ];
+        let options = IcalOptions {
+            method: Some("publish".to_string()),
+            ..IcalOptions::default()
+        };
+        let text = build_icalendar_at(&events, &options, now).unwrap();
+
+        assert!(text.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(text.contains("\r\nMETHOD:PUBLISH\r\n"));
+        assert!(text.contains("\r\nDTSTAMP:20260101T120000Z\r\n"));
+        // Summer time, UTC+3
+        assert!(text.contains("\r\nDTSTART:20260701T070000Z\r\nDTEND:20260701T080000Z\r\n"));
+        assert!(text.contains("\r\nSUMMARY:Review\\; notes\\, etc.\r\n"));
+        assert!(text.contains("\r\nORGANIZER;CN=\"Host: Team\":mailto:host@example.com\r\n"));
+        assert!(text.contains(
+            "\r\nDTSTART;TZID=Europe/Helsinki:20260327T090000\r\nRRULE:FREQ=WEEKLY;COUNT=2\r\n"
+        ));
+        assert!(text.contains("\r\nDTSTART;VALUE=DATE:20261224\r\nDTEND;VALUE=DATE:20261227\r\n"));
+        assert!(text.lines().all(|line| line.len() <= 75));
+        assert!(text.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+
+        let invalid =
+            |event: CalendarEvent| build_icalendar_at(&[event], &options, now).unwrap_err();
+        assert_eq!(
+            invalid(CalendarEvent::default()),
+            "events[0]: start is required"
+        );
+        assert_eq!(
+            invalid(CalendarEvent {
+                start: "2026-01-02".to_string(),
+                end: Some("2026-01-01".to_string()),
+                ..CalendarEvent::default()
+            }),
+            "events[0]: end must be after start"
+        );
+        assert_eq!(
+            invalid(CalendarEvent {
+                start: "2026-03-29T03:30".to_string(),
+                time_zone: Some("Europe/Helsinki".to_string()),
+                ..CalendarEvent::default()
+            }),
+            "events[0]: '2026-03-29T03:30' does not exist in Europe/Helsinki"
+        );
+        assert_eq!(
+            invalid(CalendarEvent {
+                start: "2026-01-01".to_string(),
+                time_zone: Some("Mars/Olympus".to_string()),
+                ..CalendarEvent::default()
+            }),
+            "events[0]: Unknown time zone 'Mars/Olympus'"
+        );
+    }
+
+    #[test]
+    fn test_parse_icalendar() {
+        let text = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            METHOD:REQUEST\r\n\
+            X-WR-CALNAME:Team\r\n\
+            BEGIN:VTIMEZONE\r\n\
+            TZID:Europe/Helsinki\r\n\
+            BEGIN:STANDARD\r\n\
+            DTSTART:19701025T040000\r\n\
+            END:STANDARD\r\n\
+            END:VTIMEZONE\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:1@example.com\r\n\
+            SUMMARY:Line one\\nline two\\, with a lo\r\n \
+            ng folded tail\r\n\
+            DTSTART;TZID=Europe/Helsinki:20260115T090000\r\n\
+            DURATION:PT1H30M\r\n\
+            ORGANIZER;CN=\"Doe; Jane\":MAILTO:jane@example.com\r\n\
+            ATTENDEE;PARTSTAT=accepted;RSVP=TRUE:mailto:ada@example.com\r\n\
+            BEGIN:VALARM\r\n\
+            DESCRIPTION:Reminder\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:2@example.com\r\n\
+            DTSTART;VALUE=DATE:20260201\r\n\
+            DURATION:P2D\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;TZID=FLE Standard Time:20260301T080000\r\n\
+            DTEND:20260301T090000Z\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let calendar = parse_icalendar(text).unwrap();
+        assert_eq!(calendar.method.as_deref(), Some("REQUEST"));
+        assert_eq!(calendar.name.as_deref(), Some("Team"));
+        assert_eq!(calendar.events.len(), 3);
+
+        let meeting = &calendar.events[0];
+        assert_eq!(
+            meeting.summary.as_deref(),
+            Some("Line one\nline two, with a long folded tail")
+        );
+        assert_eq!(meeting.description, None);
+        assert_eq!(meeting.start, "2026-01-15T09:00:00+02:00");
+        assert_eq!(meeting.end.as_deref(), Some("2026-01-15T10:30:00+02:00"));
+        assert_eq!(meeting.time_zone.as_deref(), Some("Europe/Helsinki"));
+        let organizer = meeting.organizer.as_ref().unwrap();
+        assert_eq!(organizer.email, "jane@example.com");
+        assert_eq!(organizer.name.as_deref(), Some("Doe; Jane"));
+        assert_eq!(meeting.attendees[0].response.as_deref(), Some("ACCEPTED"));
+        assert!(meeting.attendees[0].rsvp);
+
+        assert_eq!(calendar.events[1].start, "2026-02-01");
+        assert_eq!(calendar.events[1].end.as_deref(), Some("2026-02-03"));
+
+        let windows_zone = &calendar.events[2];
+        assert_eq!(windows_zone.start, "2026-03-01T08:00:00");
+        assert_eq!(windows_zone.time_zone.as_deref(), Some("FLE Standard Time"));
+        assert_eq!(windows_zone.end.as_deref(), Some("2026-03-01T09:00:00Z"));
+
+        assert_eq!(
+            parse_icalendar("BEGIN:VCALENDAR\nBEGIN:VEVENT\nEND:VCALENDAR\n").unwrap_err(),
+            "Line 3: Unexpected END:VCALENDAR"
+        );
+        assert_eq!(
+            parse_icalendar("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:tomorrow\n").unwrap_err(),
+            "Line 3: Invalid date-time 'tomorrow'"
+        );
+    }
+
+    #[test]
+    fn test_icalendar_round_trip() {
+        let event = CalendarEvent {
+            uid: Some("r@example.com".to_string()),
+            summary: Some("Weekly sync".to_string()),
+            description: Some("Agenda:\n1. Status, risks; next steps\\done".to_string()),
+            start: "2026-03-27T09:00:00+02:00".to_string(),
+            end: Some("2026-03-27T10:00:00+02:00".to_string()),
+            time_zone: Some("Europe/Helsinki".to_string()),
+            recurrence: Some("FREQ=WEEKLY;COUNT=3".to_string()),
+            status: Some("CONFIRMED".to_string()),
+            sequence: Some(2),
+            attendees: vec![CalendarPerson {
+                email: "ada@example.com".to_string(),
+                name: Some("Ada".to_string()),
+                rsvp: true,
+                response: Some("NEEDS-ACTION".to_string()),
+            }],
+            ..CalendarEvent::default()
+        };
+        let text = build_icalendar(std::slice::from_ref(&event), &IcalOptions::default()).unwrap();
+        let parsed = parse_icalendar(&text).unwrap();
+        assert_eq!(parsed.events, vec![event]);
+    }
+
     #[test]
     fn test_render_handlebars_simple() {
         let template = "Hello {{name}}!";
@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
 use sqlx::{Postgres, Transaction};
 use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tracing::{info, warn};
@@ -22,6 +24,8 @@ pub struct TransactionState {
     _start_time: Instant,
     /// Whether the transaction has been finalized (committed or rolled back)
     finalized: bool,
+    /// Whether the transaction is a sandbox whose writes are always discarded
+    sandbox: bool,
 }
 
 impl TransactionState {
@@ -36,6 +40,7 @@ impl TransactionState {
             deadline,
             _start_time: start_time,
             finalized: false,
+            sandbox: false,
         }
     }
 
@@ -102,6 +107,24 @@ impl Drop for TransactionGuard {
     }
 }
 
+/// RAII guard discarding a sandbox transaction on drop, see
+/// [`Database::begin_sandbox_transaction`]
+#[derive(Debug)]
+pub struct SandboxGuard {
+    _private: (),
+}
+
+impl Drop for SandboxGuard {
+    fn drop(&mut self) {
+        if let Err(e) = Database::discard_sandbox_transaction() {
+            warn!("Failed to discard sandbox transaction: {}", e);
+        }
+    }
+}
+
+/// Savepoint a sandbox transaction returns to when it is rolled back
+const SANDBOX_SAVEPOINT: &str = "sandbox_start";
+
 /// Safe wrapper around either a transaction or a connection pool
 ///
 /// This type provides a safe abstraction for executing queries within or outside
@@ -156,6 +179,121 @@ pub fn get_current_executor(pool: &PgPool) -> TransactionExecutor<'_> {
     TransactionExecutor::Pool(pool)
 }
 
+/// Get an executor for a read that tolerates replication lag
+///
+/// Inside an active transaction the read stays on the transaction so it sees
+/// the transaction's own writes. Otherwise it goes to a read replica of the
+/// global database when replicas are configured, and to `pool` when not.
+/// Writes and advisory locks must keep using `get_current_executor`.
+pub fn get_current_read_executor(pool: &PgPool) -> TransactionExecutor<'_> {
+    if get_current_transaction_active() {
+        return get_current_executor(pool);
+    }
+
+    match GLOBAL_DATABASE.get().and_then(|db| db.replica_pool()) {
+        Some(replica) => TransactionExecutor::Pool(replica),
+        None => TransactionExecutor::Pool(pool),
+    }
+}
+
+/// Connection acquisition statistics of the primary pool
+///
+/// Recorded for acquisitions made through `acquire` and `begin`: handler
+/// transactions, script table operations and script SQL. Queries run directly
+/// on the pool acquire inside sqlx and are not timed individually.
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    acquisitions: AtomicU64,
+    timeouts: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// Point-in-time view of `PoolMetrics`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PoolMetricsSnapshot {
+    /// Successful acquisitions
+    pub acquisitions: u64,
+    /// Acquisitions that gave up after the acquire timeout
+    pub timeouts: u64,
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+impl PoolMetrics {
+    fn record(&self, wait: Duration, timed_out: bool) {
+        if timed_out {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let micros = wait.as_micros().min(u64::MAX as u128) as u64;
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> PoolMetricsSnapshot {
+        let acquisitions = self.acquisitions.load(Ordering::Relaxed);
+        let total_wait_micros = self.total_wait_micros.load(Ordering::Relaxed);
+        PoolMetricsSnapshot {
+            acquisitions,
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            avg_wait_ms: if acquisitions == 0 {
+                0.0
+            } else {
+                total_wait_micros as f64 / acquisitions as f64 / 1000.0
+            },
+            max_wait_ms: self.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+static POOL_METRICS: PoolMetrics = PoolMetrics {
+    acquisitions: AtomicU64::new(0),
+    timeouts: AtomicU64::new(0),
+    total_wait_micros: AtomicU64::new(0),
+    max_wait_micros: AtomicU64::new(0),
+};
+
+/// Acquisition statistics of the primary pool
+pub fn pool_metrics() -> PoolMetricsSnapshot {
+    POOL_METRICS.snapshot()
+}
+
+/// Acquire a connection from `pool`, recording the wait in the pool metrics
+pub async fn acquire(pool: &PgPool) -> Result<PoolConnection<Postgres>, sqlx::Error> {
+    let started = Instant::now();
+    let result = pool.acquire().await;
+    POOL_METRICS.record(
+        started.elapsed(),
+        matches!(result, Err(sqlx::Error::PoolTimedOut)),
+    );
+    result
+}
+
+/// Begin a transaction on `pool`, recording the connection wait in the pool
+/// metrics
+pub async fn begin(pool: &PgPool) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let started = Instant::now();
+    let result = pool.begin().await;
+    POOL_METRICS.record(
+        started.elapsed(),
+        matches!(result, Err(sqlx::Error::PoolTimedOut)),
+    );
+    result
+}
+
+/// Where reads that tolerate replication lag are sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadRouting {
+    /// Spread reads over the configured replicas (primary if there are none)
+    #[default]
+    Replicas,
+    /// Send every query to the primary, ignoring configured replicas
+    Primary,
+}
+
 /// Global database instance
 ///
 /// This is initialized once during server startup and provides
@@ -177,47 +315,115 @@ pub fn initialize_global_database(database: Arc<Database>) -> bool {
     GLOBAL_DATABASE.set(database).is_ok()
 }
 
+/// Hide the password of a connection string for logging
+fn redact_connection_string(connection_string: &str) -> String {
+    if let Some(at_pos) = connection_string.find('@') {
+        let before_at = &connection_string[..at_pos];
+        let after_at = &connection_string[at_pos..];
+        if let Some(colon_pos) = before_at.rfind(':') {
+            return format!("{}:****{}", &before_at[..colon_pos], after_at);
+        }
+    }
+    connection_string.to_string()
+}
+
 /// Database connection pool manager
+///
+/// Holds the primary pool, used for writes, transactions and advisory locks,
+/// and optional read replica pools for reads that tolerate replication lag.
 pub struct Database {
     pool: PgPool,
+    replicas: Vec<PgPool>,
+    read_routing: ReadRouting,
+    next_replica: AtomicUsize,
 }
 
 impl Database {
     /// Create a new database instance from an existing pool (useful for testing)
     pub fn from_pool(pool: PgPool) -> Self {
-        Self { pool }
+        Self::with_replicas(pool, Vec::new(), ReadRouting::default())
+    }
+
+    /// Create a database instance from a primary pool and read replica pools
+    pub fn with_replicas(pool: PgPool, replicas: Vec<PgPool>, read_routing: ReadRouting) -> Self {
+        Self {
+            pool,
+            replicas,
+            read_routing,
+            next_replica: AtomicUsize::new(0),
+        }
     }
 
     /// Create a new database connection pool
+    ///
+    /// Replicas that cannot be reached at startup are skipped with a warning
+    /// rather than failing startup; their reads go to the remaining replicas
+    /// or to the primary.
     pub async fn new(config: &RepositoryConfig) -> Result<Self> {
         let connection_string = &config.connection_string;
 
-        // Log connection attempt (hide password)
-        let safe_conn_string = if let Some(at_pos) = connection_string.find('@') {
-            let before_at = &connection_string[..at_pos];
-            let after_at = &connection_string[at_pos..];
-            if let Some(colon_pos) = before_at.rfind(':') {
-                format!("{}:****{}", &before_at[..colon_pos], after_at)
-            } else {
-                connection_string.clone()
-            }
-        } else {
-            connection_string.clone()
-        };
+        info!(
+            "Attempting to connect to database: {}",
+            redact_connection_string(connection_string)
+        );
 
-        info!("Attempting to connect to database: {}", safe_conn_string);
-
-        let pool = PgPoolOptions::new()
-            .max_connections(5) // Default pool size
-            .acquire_timeout(Duration::from_millis(5000)) // Increased for tests
-            .connect(connection_string)
+        let pool = Self::pool_options(config)
+            .connect_with(Self::connect_options(config, connection_string)?)
             .await
             .context("Failed to connect to database")?;
 
         info!("✓ Database connection established successfully");
-        info!("✓ Connection pool created with max 5 connections");
+        info!(
+            "✓ Connection pool created with {}-{} connections",
+            config.min_connections, config.max_connections
+        );
+
+        let mut replicas = Vec::new();
+        for replica_url in &config.read_replica_urls {
+            let safe_url = redact_connection_string(replica_url);
+            match Self::pool_options(config)
+                .connect_with(Self::connect_options(config, replica_url)?)
+                .await
+            {
+                Ok(replica) => {
+                    info!("✓ Read replica connected: {}", safe_url);
+                    replicas.push(replica);
+                }
+                Err(e) => warn!("Skipping unreachable read replica {}: {}", safe_url, e),
+            }
+        }
+        if !replicas.is_empty() && config.read_routing == ReadRouting::Primary {
+            info!("Read routing is set to primary; replicas will not serve reads");
+        }
+
+        Ok(Self::with_replicas(pool, replicas, config.read_routing))
+    }
 
-        Ok(Self { pool })
+    /// Pool sizing and timeouts from the repository configuration
+    fn pool_options(config: &RepositoryConfig) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections.min(config.max_connections))
+            .acquire_timeout(Duration::from_millis(config.acquire_timeout_ms))
+            .idle_timeout(
+                (config.idle_timeout_secs > 0)
+                    .then(|| Duration::from_secs(config.idle_timeout_secs)),
+            )
+    }
+
+    /// Connection options for `url`, with the configured statement timeout as
+    /// the session default so `SET LOCAL` overrides reset back to it
+    fn connect_options(config: &RepositoryConfig, url: &str) -> Result<PgConnectOptions> {
+        let options: PgConnectOptions = url
+            .parse()
+            .with_context(|| format!("Invalid database URL: {}", redact_connection_string(url)))?;
+        if config.statement_timeout_ms == 0 {
+            return Ok(options);
+        }
+        Ok(options.options([(
+            "statement_timeout",
+            format!("{}ms", config.statement_timeout_ms),
+        )]))
     }
 
     /// Run database migrations
@@ -233,11 +439,31 @@ impl Database {
         Ok(())
     }
 
-    /// Get a reference to the connection pool
+    /// Get a reference to the primary connection pool
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
 
+    /// Connected read replica pools
+    pub fn replicas(&self) -> &[PgPool] {
+        &self.replicas
+    }
+
+    /// Next replica pool for a lag-tolerant read (round robin), or `None` when
+    /// reads should go to the primary
+    pub fn replica_pool(&self) -> Option<&PgPool> {
+        if self.read_routing == ReadRouting::Primary || self.replicas.is_empty() {
+            return None;
+        }
+        let index = self.next_replica.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        Some(&self.replicas[index])
+    }
+
+    /// Pool for a lag-tolerant read: a replica when available, else the primary
+    pub fn read_pool(&self) -> &PgPool {
+        self.replica_pool().unwrap_or(&self.pool)
+    }
+
     /// Check database health
     pub async fn health_check(&self) -> Result<()> {
         sqlx::query("SELECT 1")
@@ -289,6 +515,9 @@ impl Database {
     /// Gracefully close the database connection pool
     pub async fn close(self) {
         info!("Closing database connection pool...");
+        for replica in &self.replicas {
+            replica.close().await;
+        }
         self.pool.close().await;
         info!("Database connection pool closed");
     }
@@ -346,7 +575,7 @@ impl Database {
                 let pool = db.pool.clone();
 
                 let tx = run_blocking(async {
-                    pool.begin()
+                    begin(&pool)
                         .await
                         .map_err(|e| format!("Failed to begin transaction: {}", e))
                 })?;
@@ -362,6 +591,95 @@ impl Database {
         })
     }
 
+    /// Begin a sandbox transaction: writes made in it are visible to reads
+    /// on this thread but are discarded when the returned guard is dropped.
+    /// Committing a sandbox does nothing and rolling it back returns it to
+    /// where it started. Fails when a transaction is already active.
+    pub fn begin_sandbox_transaction() -> Result<SandboxGuard, String> {
+        fn run_blocking<F, R>(future: F) -> R
+        where
+            F: std::future::Future<Output = R>,
+        {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => tokio::task::block_in_place(move || handle.block_on(future)),
+                Err(_) => tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to create temporary runtime")
+                    .block_on(future),
+            }
+        }
+
+        CURRENT_TRANSACTION.with(|tx_cell| {
+            let mut tx_option = tx_cell.borrow_mut();
+            if tx_option.as_ref().is_some_and(|state| state.is_active()) {
+                return Err("A transaction is already active".to_string());
+            }
+
+            let db = get_global_database().ok_or("Database not initialized")?;
+            let pool = db.pool.clone();
+
+            let tx = run_blocking(async {
+                let mut tx = begin(&pool)
+                    .await
+                    .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+                sqlx::query(sqlx::AssertSqlSafe(format!(
+                    "SAVEPOINT {}",
+                    SANDBOX_SAVEPOINT
+                )))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to create savepoint: {}", e))?;
+                Ok::<_, String>(tx)
+            })?;
+
+            // Convert to 'static lifetime like begin_transaction; the guard
+            // rolls it back
+            let tx_static: Transaction<'static, Postgres> = unsafe { std::mem::transmute(tx) };
+
+            let mut state = TransactionState::new(tx_static, None);
+            state.sandbox = true;
+            *tx_option = Some(state);
+
+            Ok(SandboxGuard { _private: () })
+        })
+    }
+
+    /// Roll back the sandbox transaction of this thread, whatever savepoints
+    /// are still open in it
+    fn discard_sandbox_transaction() -> Result<(), String> {
+        fn run_blocking<F, R>(future: F) -> R
+        where
+            F: std::future::Future<Output = R>,
+        {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => tokio::task::block_in_place(move || handle.block_on(future)),
+                Err(_) => tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to create temporary runtime")
+                    .block_on(future),
+            }
+        }
+
+        let state = CURRENT_TRANSACTION.with(|tx_cell| {
+            let mut tx_option = tx_cell.borrow_mut();
+            if tx_option.as_ref().is_some_and(|state| state.sandbox) {
+                tx_option.take()
+            } else {
+                None
+            }
+        });
+        let Some(tx) = state.and_then(|mut state| state.transaction.take()) else {
+            return Ok(());
+        };
+        run_blocking(async {
+            tx.rollback()
+                .await
+                .map_err(|e| format!("Failed to rollback transaction: {}", e))
+        })
+    }
+
     /// Commit the current transaction
     ///
     /// If savepoints are active, this will release the most recent savepoint.
@@ -407,6 +725,9 @@ impl Database {
                     .map_err(|e| format!("Failed to release savepoint: {}", e))?;
                     Ok::<(), String>(())
                 })?;
+            } else if state.sandbox {
+                // Writes in a sandbox are never committed; they are discarded
+                // when the sandbox ends
             } else {
                 // Commit the entire transaction
                 let tx = state
@@ -455,7 +776,12 @@ impl Database {
                 .as_mut()
                 .ok_or("No active transaction to rollback")?;
 
-            if let Some(savepoint_name) = state.savepoint_stack.pop() {
+            // A sandbox rolls back to where it started instead of ending
+            let savepoint = state
+                .savepoint_stack
+                .pop()
+                .or_else(|| state.sandbox.then(|| SANDBOX_SAVEPOINT.to_string()));
+            if let Some(savepoint_name) = savepoint {
                 // Rollback to the savepoint
                 let tx_ref = state
                     .transaction
@@ -695,10 +1021,30 @@ mod tests {
             connection_string: database_url,
             max_script_size_bytes: 1024 * 1024,
             max_asset_size_bytes: 10 * 1024 * 1024,
+            allowed_asset_types: Vec::new(),
             max_log_messages_per_script: 100,
             log_retention_hours: 24,
             auto_prune_logs: true,
             max_upload_size_bytes: 10 * 1024 * 1024,
+            max_upload_request_bytes: 50 * 1024 * 1024,
+            upload_spool_threshold_bytes: 1024 * 1024,
+            upload_spool_dir: None,
+            trash_retention_days: 30,
+            default_storage_quota_bytes: 100 * 1024 * 1024,
+            read_replica_urls: Vec::new(),
+            read_routing: ReadRouting::Replicas,
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout_ms: 5000,
+            idle_timeout_secs: 600,
+            statement_timeout_ms: 0,
+            query_log_enabled: false,
+            slow_query_threshold_ms: 100,
+            route_usage_enabled: true,
+            route_usage_flush_interval_secs: 60,
+            route_usage_retention_days: 90,
+            script_resources_enabled: true,
+            script_resources_retention_days: 90,
         };
 
         // Try to connect with a short timeout to avoid hanging
@@ -1144,4 +1490,87 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_redact_connection_string() {
+        assert_eq!(
+            redact_connection_string("postgresql://user:secret@db:5432/app"),
+            "postgresql://user:****@db:5432/app"
+        );
+        assert_eq!(
+            redact_connection_string("postgresql://db:5432/app"),
+            "postgresql://db:5432/app"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replica_routing() {
+        // Lazy pools never connect, so no database is needed
+        let lazy_pool = |url: &str| PgPoolOptions::new().connect_lazy(url).unwrap();
+        let primary = lazy_pool("postgresql://primary:5432/app");
+        let replicas = vec![
+            lazy_pool("postgresql://replica1:5432/app"),
+            lazy_pool("postgresql://replica2:5432/app"),
+        ];
+
+        let db = Database::from_pool(primary.clone());
+        assert!(db.replica_pool().is_none());
+
+        let db = Database::with_replicas(primary.clone(), replicas.clone(), ReadRouting::Replicas);
+        let first = db.read_pool().connect_options();
+        let second = db.read_pool().connect_options();
+        let third = db.read_pool().connect_options();
+        assert_eq!(first.get_host(), "replica1");
+        assert_eq!(second.get_host(), "replica2");
+        assert_eq!(third.get_host(), "replica1");
+        assert_eq!(db.pool().connect_options().get_host(), "primary");
+
+        let db = Database::with_replicas(primary, replicas, ReadRouting::Primary);
+        assert!(db.replica_pool().is_none());
+        assert_eq!(db.read_pool().connect_options().get_host(), "primary");
+    }
+
+    #[test]
+    fn test_read_routing_config() {
+        let routing: ReadRouting = serde_json::from_str("\"primary\"").unwrap();
+        assert_eq!(routing, ReadRouting::Primary);
+        assert_eq!(ReadRouting::default(), ReadRouting::Replicas);
+    }
+
+    #[test]
+    fn test_pool_metrics_snapshot() {
+        let metrics = PoolMetrics::default();
+        assert_eq!(metrics.snapshot().avg_wait_ms, 0.0);
+
+        metrics.record(Duration::from_millis(2), false);
+        metrics.record(Duration::from_millis(4), false);
+        metrics.record(Duration::from_secs(5), true);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.acquisitions, 2);
+        assert_eq!(snapshot.timeouts, 1);
+        assert_eq!(snapshot.avg_wait_ms, 3.0);
+        assert_eq!(snapshot.max_wait_ms, 4.0);
+    }
+
+    #[test]
+    fn test_pool_options_from_config() {
+        let config = RepositoryConfig {
+            max_connections: 12,
+            min_connections: 3,
+            acquire_timeout_ms: 250,
+            idle_timeout_secs: 0,
+            statement_timeout_ms: 1500,
+            ..RepositoryConfig::default()
+        };
+
+        let options = Database::pool_options(&config);
+        assert_eq!(options.get_max_connections(), 12);
+        assert_eq!(options.get_min_connections(), 3);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_millis(250));
+        assert_eq!(options.get_idle_timeout(), None);
+
+        let connect = Database::connect_options(&config, "postgresql://u:p@db:5432/app").unwrap();
+        assert_eq!(connect.get_options(), Some("-c statement_timeout=1500ms"));
+    }
 }
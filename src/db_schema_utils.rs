@@ -12,6 +12,13 @@ pub const MAX_COLUMNS_PER_TABLE: usize = 50;
 /// Maximum length for table and column names
 pub const MAX_IDENTIFIER_LENGTH: usize = 63; // PostgreSQL limit
 
+/// Maximum length of vectors in a vector collection (pgvector limit)
+pub const MAX_VECTOR_DIMENSIONS: usize = 16_000;
+
+/// Maximum length of vectors pgvector can put in an HNSW index; longer
+/// collections are searched without one
+pub const MAX_INDEXED_VECTOR_DIMENSIONS: usize = 2_000;
+
 /// Error types for database schema operations
 #[derive(Debug, thiserror::Error)]
 pub enum SchemaError {
@@ -44,6 +51,12 @@ pub enum SchemaError {
 
     #[error("Referenced table '{0}' not found")]
     ReferencedTableNotFound(String),
+
+    #[error("SQL statement not allowed: {0}")]
+    ForbiddenSql(String),
+
+    #[error("Invalid vector: {0}")]
+    InvalidVector(String),
 }
 
 /// Supported column types for script-created tables
@@ -213,6 +226,320 @@ pub fn quote_identifier(name: &str) -> String {
     format!("\"{}\"", name.replace('"', "\"\""))
 }
 
+/// Generates the name of a script's private schema, which is also the name
+/// of the role its `db.query` statements run as.
+/// Format: scriptdb_{hash}, where hash is the first 16 characters of SHA256(script_uri)
+pub fn generate_script_schema_name(script_uri: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(script_uri.as_bytes());
+    let hash_hex = hex::encode(hasher.finalize());
+
+    format!("scriptdb_{}", &hash_hex[..16])
+}
+
+/// Generates the table name of a script's vector collection inside the
+/// script's private schema
+/// Format: vectors_{collection}
+pub fn generate_vector_table_name(collection: &str) -> Result<String, SchemaError> {
+    validate_identifier(collection)?;
+    let table_name = format!("vectors_{}", collection);
+    validate_identifier(&table_name)?;
+    Ok(table_name)
+}
+
+/// Formats a vector as a pgvector text literal such as `[0.1,0.2]`
+pub fn vector_literal(values: &[f32]) -> Result<String, SchemaError> {
+    if values.is_empty() || values.len() > MAX_VECTOR_DIMENSIONS {
+        return Err(SchemaError::InvalidVector(format!(
+            "must have between 1 and {} dimensions, got {}",
+            MAX_VECTOR_DIMENSIONS,
+            values.len()
+        )));
+    }
+    if values.iter().any(|value| !value.is_finite()) {
+        return Err(SchemaError::InvalidVector(
+            "values must be finite numbers".to_string(),
+        ));
+    }
+    let values: Vec<String> = values.iter().map(f32::to_string).collect();
+    Ok(format!("[{}]", values.join(",")))
+}
+
+/// Statements scripts may run in their private schema
+const SCRIPT_SQL_STATEMENTS: &[&str] = &[
+    "select", "insert", "update", "delete", "with", "values", "table", "create", "alter", "drop",
+    "truncate",
+];
+
+/// Object kinds scripts may create, alter and drop
+const SCRIPT_SQL_OBJECTS: &[&str] = &["table", "index", "view", "sequence"];
+
+/// Functions that could switch the role or search path, run SQL built from
+/// strings, or reach outside the script's schema
+const SCRIPT_SQL_FORBIDDEN_FUNCTIONS: &[&str] = &[
+    "set_config",
+    "query_to_xml",
+    "query_to_xmlschema",
+    "query_to_xml_and_xmlschema",
+    "cursor_to_xml",
+    "cursor_to_xmlschema",
+    "pg_notify",
+    "pg_cancel_backend",
+    "pg_terminate_backend",
+    "pg_reload_conf",
+];
+
+/// Function name prefixes forbidden for the same reasons
+const SCRIPT_SQL_FORBIDDEN_PREFIXES: &[&str] =
+    &["dblink", "lo_", "pg_advisory", "pg_read_", "pg_ls_"];
+
+/// Validates a statement passed to `db.query`: exactly one statement, either
+/// DML or DDL on tables, indexes, views and sequences, that does not call
+/// functions able to escape the script's role or schema
+pub fn validate_script_sql(sql: &str) -> Result<(), SchemaError> {
+    let words = sql_words(sql)?;
+    let forbidden = |reason: &str| Err(SchemaError::ForbiddenSql(reason.to_string()));
+
+    let Some(first) = words.first() else {
+        return forbidden("empty statement");
+    };
+    if !SCRIPT_SQL_STATEMENTS.contains(&first.as_str()) {
+        return Err(SchemaError::ForbiddenSql(format!(
+            "{} statements are not supported",
+            first.to_uppercase()
+        )));
+    }
+
+    if matches!(first.as_str(), "create" | "alter" | "drop") {
+        let object = words[1..]
+            .iter()
+            .find(|word| {
+                !matches!(
+                    word.as_str(),
+                    "or" | "replace"
+                        | "unique"
+                        | "temp"
+                        | "temporary"
+                        | "unlogged"
+                        | "materialized"
+                )
+            })
+            .map(String::as_str)
+            .unwrap_or("");
+        if !SCRIPT_SQL_OBJECTS.contains(&object) {
+            return Err(SchemaError::ForbiddenSql(format!(
+                "{} {} is not supported",
+                first.to_uppercase(),
+                object.to_uppercase()
+            )));
+        }
+    }
+
+    for word in &words {
+        if SCRIPT_SQL_FORBIDDEN_FUNCTIONS.contains(&word.as_str())
+            || SCRIPT_SQL_FORBIDDEN_PREFIXES
+                .iter()
+                .any(|prefix| word.starts_with(prefix))
+        {
+            return Err(SchemaError::ForbiddenSql(format!(
+                "'{}' may not be used",
+                word
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Extra check for `db.query` statements when tenancy is enabled: row-level
+/// security is managed by the engine, and temporary tables would outlive the
+/// transaction on a pooled connection shared by all tenants
+pub fn validate_tenant_script_sql(sql: &str) -> Result<(), SchemaError> {
+    let words = sql_words(sql)?;
+    if words
+        .windows(3)
+        .any(|w| w[0] == "row" && w[1] == "level" && w[2] == "security")
+    {
+        return Err(SchemaError::ForbiddenSql(
+            "row-level security is managed by the engine when tenancy is enabled".to_string(),
+        ));
+    }
+    if words.first().is_some_and(|first| first == "create")
+        && words
+            .iter()
+            .take_while(|word| word.as_str() != "table")
+            .any(|word| word == "temp" || word == "temporary")
+    {
+        return Err(SchemaError::ForbiddenSql(
+            "temporary tables are not supported when tenancy is enabled".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Lowercased keywords and identifiers (quoted ones included) of a single SQL
+/// statement, skipping string literals and comments
+fn sql_words(sql: &str) -> Result<Vec<String>, SchemaError> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut words: Vec<String> = Vec::new();
+    let mut statement_ended = false;
+    let mut i = 0;
+
+    let unterminated =
+        |what: &str| Err(SchemaError::ForbiddenSql(format!("unterminated {}", what)));
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            loop {
+                if i + 1 >= chars.len() {
+                    return unterminated("comment");
+                }
+                if chars[i] == '/' && chars[i + 1] == '*' {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars[i + 1] == '/' {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            continue;
+        }
+
+        if statement_ended {
+            return Err(SchemaError::ForbiddenSql(
+                "only one statement may be run at a time".to_string(),
+            ));
+        }
+
+        match c {
+            ';' => {
+                statement_ended = true;
+                i += 1;
+            }
+            '\'' => {
+                // A one-letter word right before the quote is a string prefix;
+                // E'...' strings also accept backslash escapes
+                let escapes = words
+                    .last()
+                    .is_some_and(|w| w == "e" && i > 0 && chars[i - 1].eq_ignore_ascii_case(&'e'));
+                if words
+                    .last()
+                    .is_some_and(|w| w.len() == 1 && i > 0 && chars[i - 1].is_alphabetic())
+                {
+                    if words.last().is_some_and(|w| w == "u") {
+                        return Err(SchemaError::ForbiddenSql(
+                            "Unicode escape strings are not supported".to_string(),
+                        ));
+                    }
+                    words.pop();
+                }
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return unterminated("string literal"),
+                        Some('\\') if escapes => i += 2,
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => i += 2,
+                        Some('\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(_) => i += 1,
+                    }
+                }
+            }
+            '"' => {
+                let mut word = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return unterminated("quoted identifier"),
+                        Some('"') if chars.get(i + 1) == Some(&'"') => {
+                            word.push('"');
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&ch) => {
+                            word.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                words.push(word.to_lowercase());
+            }
+            '&' if words.last().is_some_and(|w| w == "u")
+                && matches!(next, Some('\'') | Some('"')) =>
+            {
+                return Err(SchemaError::ForbiddenSql(
+                    "Unicode escape strings are not supported".to_string(),
+                ));
+            }
+            '$' if next.is_some_and(|n| n == '$' || n.is_alphabetic() || n == '_') => {
+                // Dollar-quoted string: $tag$ ... $tag$
+                let tag_end = match chars[i + 1..].iter().position(|&ch| ch == '$') {
+                    Some(offset) => i + 1 + offset,
+                    None => return unterminated("dollar-quoted string"),
+                };
+                let tag: String = chars[i..=tag_end].iter().collect();
+                if !tag[1..tag.len() - 1]
+                    .chars()
+                    .all(|ch| ch.is_alphanumeric() || ch == '_')
+                {
+                    return Err(SchemaError::ForbiddenSql(format!(
+                        "invalid dollar quote {}",
+                        tag
+                    )));
+                }
+                let tag: Vec<char> = tag.chars().collect();
+                i = tag_end + 1;
+                loop {
+                    if i + tag.len() > chars.len() {
+                        return unterminated("dollar-quoted string");
+                    }
+                    if chars[i..i + tag.len()] == tag[..] {
+                        i += tag.len();
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                words.push(chars[start..i].iter().collect::<String>().to_lowercase());
+            }
+            _ => i += 1,
+        }
+    }
+
+    Ok(words)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -274,6 +601,84 @@ mod tests {
         assert_ne!(physical, physical3);
     }
 
+    #[test]
+    fn test_generate_script_schema_name() {
+        let schema = generate_script_schema_name("https://example.com/myscript");
+        assert!(schema.starts_with("scriptdb_"));
+        assert_eq!(schema.len(), "scriptdb_".len() + 16);
+        assert!(validate_identifier(&schema).is_ok());
+        assert_eq!(
+            schema,
+            generate_script_schema_name("https://example.com/myscript")
+        );
+        assert_ne!(
+            schema,
+            generate_script_schema_name("https://example.com/other")
+        );
+    }
+
+    #[test]
+    fn test_vector_helpers() {
+        assert_eq!(generate_vector_table_name("docs").unwrap(), "vectors_docs");
+        assert!(generate_vector_table_name("Docs").is_err());
+        assert!(generate_vector_table_name(&"a".repeat(60)).is_err());
+
+        assert_eq!(vector_literal(&[0.5, -1.0, 2.0]).unwrap(), "[0.5,-1,2]");
+        assert!(vector_literal(&[]).is_err());
+        assert!(vector_literal(&[f32::NAN]).is_err());
+    }
+
+    #[test]
+    fn test_validate_script_sql_allows_dml_and_table_ddl() {
+        assert!(validate_script_sql("SELECT * FROM notes WHERE id = $1").is_ok());
+        assert!(validate_script_sql("insert into notes (body) values ($1) returning id;").is_ok());
+        assert!(
+            validate_script_sql("WITH recent AS (SELECT 1) UPDATE notes SET body = 'a;b'").is_ok()
+        );
+        assert!(validate_script_sql("CREATE TABLE IF NOT EXISTS notes (id SERIAL)").is_ok());
+        assert!(validate_script_sql("create unique index notes_body on notes (body)").is_ok());
+        assert!(validate_script_sql("CREATE OR REPLACE VIEW v AS SELECT 1").is_ok());
+        assert!(validate_script_sql("DROP TABLE notes -- done").is_ok());
+        // Forbidden names inside literals and comments are just text
+        assert!(validate_script_sql("SELECT 'set_config' /* pg_notify */").is_ok());
+        assert!(validate_script_sql("SELECT $tag$ reset role; $tag$").is_ok());
+    }
+
+    #[test]
+    fn test_validate_script_sql_rejects_escapes() {
+        assert!(validate_script_sql("").is_err());
+        assert!(validate_script_sql("RESET ROLE").is_err());
+        assert!(validate_script_sql("SET search_path TO public").is_err());
+        assert!(validate_script_sql("COMMIT").is_err());
+        assert!(validate_script_sql("DO $$ BEGIN END $$").is_err());
+        assert!(validate_script_sql("SELECT 1; DROP TABLE notes").is_err());
+        assert!(validate_script_sql("CREATE FUNCTION f() RETURNS int AS 'select 1'").is_err());
+        assert!(validate_script_sql("ALTER ROLE someone SUPERUSER").is_err());
+        assert!(validate_script_sql("SELECT set_config('role', 'admin', false)").is_err());
+        assert!(
+            validate_script_sql("SELECT pg_catalog.\"set_config\"('role', 'a', true)").is_err()
+        );
+        assert!(validate_script_sql("SELECT query_to_xml('select 1', true, false, '')").is_err());
+        assert!(validate_script_sql("SELECT lo_import('/etc/passwd')").is_err());
+        assert!(validate_script_sql("SELECT U&'\\0041'").is_err());
+        assert!(validate_script_sql("SELECT E'it\\'s; reset role'").is_ok());
+        assert!(validate_script_sql("SELECT 'unterminated").is_err());
+    }
+
+    #[test]
+    fn test_validate_tenant_script_sql() {
+        assert!(validate_tenant_script_sql("SELECT * FROM notes WHERE id = $1").is_ok());
+        assert!(validate_tenant_script_sql("CREATE TABLE notes (body TEXT)").is_ok());
+        assert!(
+            validate_tenant_script_sql("ALTER TABLE notes NO FORCE ROW LEVEL SECURITY").is_err()
+        );
+        assert!(
+            validate_tenant_script_sql("alter table notes disable row level security").is_err()
+        );
+        assert!(validate_tenant_script_sql("CREATE TEMP TABLE scratch (x INT)").is_err());
+        assert!(validate_tenant_script_sql("SELECT 'row level security'").is_ok());
+    }
+
     #[test]
     fn test_validate_default_value_integer() {
         assert!(validate_default_value(&ColumnType::Integer, "42").is_ok());
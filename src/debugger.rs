@@ -0,0 +1,838 @@
+//! Breakpoint debugger for script route handlers.
+//!
+//! The QuickJS build the engine embeds has no debugger hooks, so breakpoints
+//! work by instrumentation instead: while a debugger is attached, a script it
+//! is interested in executes with a call to `__debugHook(line, variables)`
+//! inserted before each statement ([`instrument`]). The calls share the line
+//! of the statement they precede, so line numbers and stack traces are
+//! unchanged. The hook pauses the executing thread when its line has a
+//! breakpoint, when the script reaches a `debugger` statement, or when the
+//! debugger asked to step, and reports the variables in scope and the stack.
+//!
+//! Debuggers attach over the `/engine/debugger` WebSocket, which is only
+//! served when `javascript.enable_debugger` is set and only to
+//! administrators. Messages are JSON, in the style of the Chrome DevTools
+//! protocol:
+//!
+//! - `{"id": 1, "method": "Debugger.setBreakpoints", "params": {"scriptUri": "https://example.com/api", "lines": [3, 7]}}`
+//!   replaces the breakpoints of a script; an empty list removes them
+//! - `{"id": 2, "method": "Debugger.getBreakpoints"}`
+//! - `{"id": 3, "method": "Debugger.resume", "params": {"pauseId": 1}}`
+//! - `{"id": 4, "method": "Debugger.stepInto", "params": {"pauseId": 1}}`
+//!   resumes and pauses again at the next statement executed
+//!
+//! Commands are answered with `{"id", "result"}` or `{"id", "error"}`. The
+//! server sends `Debugger.paused` and `Debugger.resumed` events. Closing the
+//! connection resumes every execution it paused. An execution stays paused
+//! for at most [`MAX_PAUSE`] in total, and its time limit is extended by the
+//! time it spent paused.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError, mpsc};
+use std::time::{Duration, Instant};
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use futures_util::{SinkExt, StreamExt};
+use oxc::allocator::{Allocator, ArenaVec};
+use oxc::ast::ast::Statement;
+use oxc::ast_visit::{Visit, walk};
+use oxc::parser::Parser;
+use oxc::semantic::{ScopeFlags, ScopeId, Scoping, SemanticBuilder, SymbolFlags};
+use oxc::span::GetSpan;
+use rquickjs::{Ctx, Function, Object, Value, function::Opt};
+use serde_json::{Value as JsonValue, json};
+use tokio::sync::mpsc as async_mpsc;
+use tracing::{info, warn};
+
+/// Longest total time one execution may spend paused
+pub const MAX_PAUSE: Duration = Duration::from_secs(300);
+
+/// Global the instrumented code calls before each statement
+const HOOK_NAME: &str = "__debugHook";
+
+/// Longest variable preview sent to the debugger, in characters
+const MAX_PREVIEW_CHARS: usize = 1000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Source of session and pause ids
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Enable or disable the debugger. Called at startup.
+pub fn configure(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the debugger is enabled
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// How to continue a paused execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    Continue,
+    Step,
+}
+
+/// An attached debugger
+struct Session {
+    /// Breakpoint lines by script URI
+    breakpoints: HashMap<String, BTreeSet<u32>>,
+    events: async_mpsc::UnboundedSender<String>,
+}
+
+/// A paused execution, waiting for its session to resume it
+struct Pause {
+    session_id: u64,
+    resume: mpsc::Sender<Resume>,
+}
+
+#[derive(Default)]
+struct Registry {
+    sessions: HashMap<u64, Session>,
+    pauses: HashMap<u64, Pause>,
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+fn attach(events: async_mpsc::UnboundedSender<String>) -> u64 {
+    let session_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    registry().sessions.insert(
+        session_id,
+        Session {
+            breakpoints: HashMap::new(),
+            events,
+        },
+    );
+    session_id
+}
+
+/// Remove a session. Dropping its pauses' senders resumes the executions.
+fn detach(session_id: u64) {
+    let mut registry = registry();
+    registry.sessions.remove(&session_id);
+    registry
+        .pauses
+        .retain(|_, pause| pause.session_id != session_id);
+}
+
+fn send_event(session_id: u64, event: JsonValue) {
+    if let Some(session) = registry().sessions.get(&session_id) {
+        let _ = session.events.send(event.to_string());
+    }
+}
+
+/// Whether an execution of `content` as `script_uri` should be instrumented:
+/// an attached debugger has breakpoints in it, or it has a `debugger`
+/// statement.
+pub fn should_instrument(script_uri: &str, content: &str) -> bool {
+    if !is_enabled() {
+        return false;
+    }
+    let registry = registry();
+    registry.sessions.values().any(|session| {
+        session
+            .breakpoints
+            .get(script_uri)
+            .is_some_and(|lines| !lines.is_empty())
+    }) || (!registry.sessions.is_empty() && content.contains("debugger"))
+}
+
+/// Extra time an execution starting now may spend paused in the debugger
+pub fn pause_allowance() -> Duration {
+    if is_enabled() && !registry().sessions.is_empty() {
+        MAX_PAUSE
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// The instrumented execution running on this thread
+struct Execution {
+    script_uri: String,
+    /// Time spent paused so far
+    paused: Duration,
+    /// Session that asked to pause at the next statement
+    stepping: Option<u64>,
+}
+
+thread_local! {
+    static EXECUTION: RefCell<Option<Execution>> = const { RefCell::new(None) };
+}
+
+/// Marks the current thread as running an instrumented execution of
+/// `script_uri` until dropped
+pub struct ExecutionGuard {
+    previous: Option<Execution>,
+}
+
+impl ExecutionGuard {
+    pub fn enter(script_uri: &str) -> Self {
+        let previous = EXECUTION.with(|execution| {
+            execution.borrow_mut().replace(Execution {
+                script_uri: script_uri.to_string(),
+                paused: Duration::ZERO,
+                stepping: None,
+            })
+        });
+        Self { previous }
+    }
+}
+
+impl Drop for ExecutionGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        EXECUTION.with(|execution| *execution.borrow_mut() = previous);
+    }
+}
+
+/// Time the execution on this thread has spent paused; its deadlines are
+/// extended by this much
+pub fn paused_time() -> Duration {
+    EXECUTION.with(|execution| {
+        execution
+            .borrow()
+            .as_ref()
+            .map_or(Duration::ZERO, |execution| execution.paused)
+    })
+}
+
+/// Install the `__debugHook` global instrumented code calls
+pub fn install_hook(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    let hook = Function::new(
+        ctx.clone(),
+        |line: u32, variables: Object<'_>, debugger_statement: Opt<bool>| {
+            on_statement(line, &variables, debugger_statement.0.unwrap_or(false));
+        },
+    )?;
+    ctx.globals().set(HOOK_NAME, hook)
+}
+
+/// Called before each statement of an instrumented script
+fn on_statement(line: u32, variables: &Object<'_>, debugger_statement: bool) {
+    let Some((script_uri, paused, stepping)) = EXECUTION.with(|execution| {
+        let mut execution = execution.borrow_mut();
+        execution.as_mut().map(|execution| {
+            (
+                execution.script_uri.clone(),
+                execution.paused,
+                execution.stepping.take(),
+            )
+        })
+    }) else {
+        return;
+    };
+    if paused >= MAX_PAUSE {
+        return;
+    }
+
+    let target = {
+        let registry = registry();
+        stepping
+            .filter(|session_id| registry.sessions.contains_key(session_id))
+            .map(|session_id| (session_id, "step"))
+            .or_else(|| {
+                registry
+                    .sessions
+                    .iter()
+                    .find(|(_, session)| {
+                        session
+                            .breakpoints
+                            .get(&script_uri)
+                            .is_some_and(|lines| lines.contains(&line))
+                    })
+                    .map(|(session_id, _)| (*session_id, "breakpoint"))
+            })
+            .or_else(|| {
+                debugger_statement
+                    .then(|| registry.sessions.keys().min().copied())
+                    .flatten()
+                    .map(|session_id| (session_id, "debuggerStatement"))
+            })
+    };
+    let Some((session_id, reason)) = target else {
+        return;
+    };
+
+    // Collect what the debugger shows before taking the registry lock again;
+    // the getters run script code
+    let ctx = variables.ctx();
+    let variables = preview_variables(ctx, variables);
+    let stack = current_stack(ctx);
+
+    let pause_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let (resume_tx, resume_rx) = mpsc::channel();
+    {
+        let mut registry = registry();
+        let Some(session) = registry.sessions.get(&session_id) else {
+            return;
+        };
+        let event = json!({
+            "method": "Debugger.paused",
+            "params": {
+                "pauseId": pause_id,
+                "scriptUri": script_uri,
+                "line": line,
+                "reason": reason,
+                "variables": variables,
+                "stack": stack,
+            }
+        });
+        if session.events.send(event.to_string()).is_err() {
+            return;
+        }
+        registry.pauses.insert(
+            pause_id,
+            Pause {
+                session_id,
+                resume: resume_tx,
+            },
+        );
+    }
+
+    let started = Instant::now();
+    let resume = resume_rx
+        .recv_timeout(MAX_PAUSE - paused)
+        .unwrap_or(Resume::Continue);
+    registry().pauses.remove(&pause_id);
+    send_event(
+        session_id,
+        json!({"method": "Debugger.resumed", "params": {"pauseId": pause_id}}),
+    );
+
+    EXECUTION.with(|execution| {
+        if let Some(execution) = execution.borrow_mut().as_mut() {
+            execution.paused += started.elapsed();
+            if resume == Resume::Step {
+                execution.stepping = Some(session_id);
+            }
+        }
+    });
+}
+
+/// Preview each variable through its getter. Variables still in their
+/// temporal dead zone throw, and show as unavailable.
+fn preview_variables<'js>(
+    ctx: &Ctx<'js>,
+    variables: &Object<'js>,
+) -> serde_json::Map<String, JsonValue> {
+    let mut previews = serde_json::Map::new();
+    for (name, getter) in variables.props::<String, Function>().flatten() {
+        let preview = match getter.call::<_, Value>(()) {
+            Ok(value) => preview_value(ctx, value),
+            Err(_) => {
+                let _ = ctx.catch();
+                "<unavailable>".to_string()
+            }
+        };
+        previews.insert(name, JsonValue::String(preview));
+    }
+    previews
+}
+
+fn preview_value<'js>(ctx: &Ctx<'js>, value: Value<'js>) -> String {
+    if value.is_undefined() {
+        return "undefined".to_string();
+    }
+    if value.is_function() {
+        return "[function]".to_string();
+    }
+    let preview = match ctx.json_stringify(value.clone()) {
+        Ok(Some(json)) => json.to_string().unwrap_or_default(),
+        Ok(None) => format!("[{}]", value.type_name()),
+        Err(_) => {
+            let _ = ctx.catch();
+            format!("[{}]", value.type_name())
+        }
+    };
+    if preview.chars().count() > MAX_PREVIEW_CHARS {
+        let truncated: String = preview.chars().take(MAX_PREVIEW_CHARS).collect();
+        format!("{}…", truncated)
+    } else {
+        preview
+    }
+}
+
+/// Script frames of the current call stack, innermost first
+fn current_stack(ctx: &Ctx<'_>) -> Vec<String> {
+    let stack = match ctx.eval::<String, _>("new Error().stack") {
+        Ok(stack) => stack,
+        Err(_) => {
+            let _ = ctx.catch();
+            return Vec::new();
+        }
+    };
+    stack
+        .lines()
+        .map(str::trim)
+        .filter(|frame| {
+            !frame.is_empty() && !frame.contains("(native)") && !frame.contains("<eval>")
+        })
+        .map(|frame| frame.trim_start_matches("at ").to_string())
+        .collect()
+}
+
+/// Rewrite `content` so each statement is preceded by a call to the debug
+/// hook on the same line, passing getters for the variables in scope
+pub fn instrument(script_uri: &str, content: &str) -> Result<String, String> {
+    let allocator = Allocator::default();
+    let source_type = crate::transpiler::get_source_type(script_uri);
+    let parsed = Parser::new(&allocator, content, source_type).parse();
+    if let Some(error) = parsed.diagnostics.first() {
+        return Err(format!("Parse error: {}", error));
+    }
+    let scoping = SemanticBuilder::new()
+        .build(&parsed.program)
+        .semantic
+        .into_scoping();
+
+    let mut collector = HookCollector {
+        scoping: &scoping,
+        scopes: Vec::new(),
+        line_starts: std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+            .collect(),
+        hooks: Vec::new(),
+    };
+    collector.visit_program(&parsed.program);
+
+    let mut hooks = collector.hooks;
+    hooks.sort_by_key(|(offset, _)| *offset);
+    let mut instrumented = String::with_capacity(content.len() + hooks.len() * 48);
+    let mut copied = 0;
+    for (offset, hook) in hooks {
+        instrumented.push_str(&content[copied..offset]);
+        instrumented.push_str(&hook);
+        copied = offset;
+    }
+    instrumented.push_str(&content[copied..]);
+    Ok(instrumented)
+}
+
+/// Collects the hook call to insert before each statement
+struct HookCollector<'s> {
+    scoping: &'s Scoping,
+    /// Scopes enclosing the statement being visited, innermost last
+    scopes: Vec<ScopeId>,
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+    /// Insertion offset and hook call
+    hooks: Vec<(usize, String)>,
+}
+
+impl HookCollector<'_> {
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    /// Variables declared before `offset` and visible from the current scope,
+    /// innermost first
+    fn variables_at(&self, offset: u32) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        let mut variables = Vec::new();
+        let mut scope = self.scopes.last().copied();
+        while let Some(scope_id) = scope {
+            for symbol_id in self.scoping.get_bindings(scope_id).values() {
+                let flags = self.scoping.symbol_flags(*symbol_id);
+                let name = self.scoping.symbol_name(*symbol_id);
+                if flags.intersects(SymbolFlags::Variable | SymbolFlags::CatchVariable)
+                    && !flags.contains(SymbolFlags::Ambient)
+                    && self.scoping.symbol_span(*symbol_id).start < offset
+                    && seen.insert(name)
+                {
+                    variables.push(name);
+                }
+            }
+            scope = self.scoping.scope_parent_id(scope_id);
+        }
+        variables.sort_unstable();
+        variables
+    }
+}
+
+impl<'a> Visit<'a> for HookCollector<'_> {
+    fn enter_scope(&mut self, _flags: ScopeFlags, scope_id: &std::cell::Cell<Option<ScopeId>>) {
+        if let Some(scope_id) = scope_id.get() {
+            self.scopes.push(scope_id);
+        }
+    }
+
+    fn leave_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn visit_statements(&mut self, statements: &ArenaVec<'a, Statement<'a>>) {
+        for statement in statements {
+            let debugger_statement = matches!(statement, Statement::DebuggerStatement(_));
+            let hookable = match statement {
+                Statement::VariableDeclaration(declaration) => !declaration.declare,
+                Statement::ExpressionStatement(_)
+                | Statement::ReturnStatement(_)
+                | Statement::IfStatement(_)
+                | Statement::ForStatement(_)
+                | Statement::ForInStatement(_)
+                | Statement::ForOfStatement(_)
+                | Statement::WhileStatement(_)
+                | Statement::DoWhileStatement(_)
+                | Statement::ThrowStatement(_)
+                | Statement::TryStatement(_)
+                | Statement::SwitchStatement(_)
+                | Statement::BlockStatement(_)
+                | Statement::BreakStatement(_)
+                | Statement::ContinueStatement(_)
+                | Statement::LabeledStatement(_)
+                | Statement::DebuggerStatement(_) => true,
+                _ => false,
+            };
+            if hookable {
+                let start = statement.span().start;
+                let getters: Vec<String> = self
+                    .variables_at(start)
+                    .into_iter()
+                    .map(|name| format!("{name}: () => {name}"))
+                    .collect();
+                self.hooks.push((
+                    start as usize,
+                    format!(
+                        "{}({}, {{{}}}, {}); ",
+                        HOOK_NAME,
+                        self.line_of(start as usize),
+                        getters.join(", "),
+                        debugger_statement
+                    ),
+                ));
+            }
+        }
+        walk::walk_statements(self, statements);
+    }
+}
+
+/// Handle a debugger command and return its result
+fn handle_command(session_id: u64, method: &str, params: &JsonValue) -> Result<JsonValue, String> {
+    match method {
+        "Debugger.setBreakpoints" => {
+            let script_uri = params
+                .get("scriptUri")
+                .and_then(JsonValue::as_str)
+                .ok_or("scriptUri is required")?;
+            let lines: BTreeSet<u32> = params
+                .get("lines")
+                .and_then(JsonValue::as_array)
+                .ok_or("lines must be an array")?
+                .iter()
+                .map(|line| {
+                    line.as_u64()
+                        .and_then(|line| u32::try_from(line).ok())
+                        .filter(|line| *line > 0)
+                        .ok_or("lines must be positive integers")
+                })
+                .collect::<Result<_, _>>()?;
+            let mut registry = registry();
+            let session = registry
+                .sessions
+                .get_mut(&session_id)
+                .ok_or("Session is closed")?;
+            if lines.is_empty() {
+                session.breakpoints.remove(script_uri);
+            } else {
+                session
+                    .breakpoints
+                    .insert(script_uri.to_string(), lines.clone());
+            }
+            Ok(json!({"scriptUri": script_uri, "lines": lines}))
+        }
+        "Debugger.getBreakpoints" => {
+            let registry = registry();
+            let session = registry
+                .sessions
+                .get(&session_id)
+                .ok_or("Session is closed")?;
+            Ok(json!({"breakpoints": session.breakpoints}))
+        }
+        "Debugger.resume" | "Debugger.stepInto" => {
+            let pause_id = params
+                .get("pauseId")
+                .and_then(JsonValue::as_u64)
+                .ok_or("pauseId is required")?;
+            let pause = {
+                let mut registry = registry();
+                match registry.pauses.get(&pause_id) {
+                    Some(pause) if pause.session_id == session_id => {
+                        registry.pauses.remove(&pause_id)
+                    }
+                    _ => None,
+                }
+            }
+            .ok_or_else(|| format!("No paused execution {}", pause_id))?;
+            let resume = if method == "Debugger.stepInto" {
+                Resume::Step
+            } else {
+                Resume::Continue
+            };
+            let _ = pause.resume.send(resume);
+            Ok(json!({}))
+        }
+        _ => Err(format!("Unknown method {}", method)),
+    }
+}
+
+fn reply(message: &str, session_id: u64) -> JsonValue {
+    let request: JsonValue = match serde_json::from_str(message) {
+        Ok(request) => request,
+        Err(e) => return json!({"error": {"message": format!("Invalid message: {}", e)}}),
+    };
+    let id = request.get("id").cloned().unwrap_or(JsonValue::Null);
+    let method = request
+        .get("method")
+        .and_then(JsonValue::as_str)
+        .unwrap_or_default();
+    let params = request.get("params").cloned().unwrap_or(JsonValue::Null);
+    match handle_command(session_id, method, &params) {
+        Ok(result) => json!({"id": id, "result": result}),
+        Err(message) => json!({"id": id, "error": {"message": message}}),
+    }
+}
+
+/// `GET /engine/debugger`: attach a debugger (administrators only)
+pub async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    req: axum::http::Request<axum::body::Body>,
+) -> Response {
+    if !is_enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(user) = req
+        .extensions()
+        .get::<crate::auth::AuthUser>()
+        .filter(|user| user.is_admin)
+        .cloned()
+    else {
+        return (
+            StatusCode::FORBIDDEN,
+            "The debugger is only available to administrators",
+        )
+            .into_response();
+    };
+    ws.on_upgrade(move |socket| run_session(socket, user.user_id))
+}
+
+async fn run_session(socket: WebSocket, user_id: String) {
+    let (mut sender, mut receiver) = socket.split();
+    let (events_tx, mut events_rx) = async_mpsc::unbounded_channel();
+    let session_id = attach(events_tx);
+    info!(session_id, user_id = %user_id, "Debugger attached");
+
+    loop {
+        tokio::select! {
+            event = events_rx.recv() => {
+                let Some(event) = event else { break };
+                if sender.send(Message::Text(event.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let response = reply(&text, session_id);
+                    if sender
+                        .send(Message::Text(response.to_string().into()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    warn!(session_id, "Debugger connection error: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    detach(session_id);
+    info!(session_id, user_id = %user_id, "Debugger detached");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Once;
+    use tokio::runtime::Runtime;
+
+    static DB_INIT: Once = Once::new();
+
+    fn get_runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to create Tokio runtime")
+        })
+    }
+
+    fn setup_db() {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        DB_INIT.call_once(|| {
+            get_runtime().block_on(async {
+                let pool = sqlx::PgPool::connect_lazy(&url).expect("Failed to create pool");
+                let db = std::sync::Arc::new(crate::database::Database::from_pool(pool.clone()));
+                let _ = crate::database::initialize_global_database(db);
+                let server_id = crate::notifications::generate_server_id();
+                let _ = crate::notifications::initialize_server_id(server_id.clone());
+                let repo = crate::repository::PostgresRepository::new(pool, server_id);
+                let _ = crate::repository::initialize_repository(repo);
+            });
+        });
+    }
+
+    fn should_skip_db_tests() -> bool {
+        std::env::var("DATABASE_URL").is_err()
+    }
+
+    #[test]
+    fn test_instrument_keeps_lines_and_scopes() {
+        let source = "const limit = 10;\n\
+                      function handler(context) {\n\
+                      \x20   let total = 0;\n\
+                      \x20   for (let i = 0; i < limit; i++) {\n\
+                      \x20       total += i;\n\
+                      \x20   }\n\
+                      \x20   return total;\n\
+                      }\n";
+        let instrumented = instrument("test.js", source).unwrap();
+        assert_eq!(instrumented.lines().count(), source.lines().count());
+
+        let lines: Vec<&str> = instrumented.lines().collect();
+        assert!(lines[0].starts_with("__debugHook(1, {}, false); const limit"));
+        assert!(lines[2].contains(
+            "__debugHook(3, {context: () => context, limit: () => limit}, false); let total"
+        ));
+        assert!(lines[4].contains(
+            "__debugHook(5, {context: () => context, i: () => i, limit: () => limit, total: () => total}, false); total += i"
+        ));
+        assert!(lines[6].contains("__debugHook(7, "));
+    }
+
+    #[test]
+    fn test_instrument_marks_debugger_statements() {
+        let instrumented =
+            instrument("test.ts", "function f(x: number) {\n    debugger;\n}\n").unwrap();
+        assert!(instrumented.contains("__debugHook(2, {x: () => x}, true); debugger;"));
+        assert!(instrument("broken.js", "function (").is_err());
+    }
+
+    #[test]
+    fn test_commands_manage_breakpoints() {
+        let (events, _events_rx) = async_mpsc::unbounded_channel();
+        let session_id = attach(events);
+
+        let result = handle_command(
+            session_id,
+            "Debugger.setBreakpoints",
+            &json!({"scriptUri": "debugger-test", "lines": [7, 3]}),
+        )
+        .unwrap();
+        assert_eq!(result["lines"], json!([3, 7]));
+        let breakpoints =
+            handle_command(session_id, "Debugger.getBreakpoints", &JsonValue::Null).unwrap();
+        assert_eq!(breakpoints["breakpoints"]["debugger-test"], json!([3, 7]));
+
+        assert!(
+            handle_command(
+                session_id,
+                "Debugger.setBreakpoints",
+                &json!({"scriptUri": "debugger-test", "lines": [0]}),
+            )
+            .is_err()
+        );
+        assert!(
+            handle_command(session_id, "Debugger.resume", &json!({"pauseId": 999999})).is_err()
+        );
+        assert!(handle_command(session_id, "Debugger.evaluate", &JsonValue::Null).is_err());
+
+        detach(session_id);
+        assert!(handle_command(session_id, "Debugger.getBreakpoints", &JsonValue::Null).is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_pauses_execution() {
+        if should_skip_db_tests() {
+            return;
+        }
+        setup_db();
+        configure(true);
+        let (events, mut events_rx) = async_mpsc::unbounded_channel();
+        let session_id = attach(events);
+        let script_uri = "debugger-pause-test.js";
+        handle_command(
+            session_id,
+            "Debugger.setBreakpoints",
+            &json!({"scriptUri": script_uri, "lines": [3]}),
+        )
+        .unwrap();
+
+        let worker = std::thread::spawn(move || {
+            let _guard = get_runtime().enter();
+            let source = "function handler(context) {\n\
+                          \x20   const name = context.request.query.name;\n\
+                          \x20   return { status: 200, body: 'hello ' + name };\n\
+                          }\n";
+            let params = crate::js_engine::RequestExecutionParams {
+                script_uri: script_uri.to_string(),
+                handler_name: "handler".to_string(),
+                path: "/hello".to_string(),
+                method: "GET".to_string(),
+                query_params: Some(HashMap::from([("name".to_string(), "world".to_string())])),
+                form_data: None,
+                raw_body: None,
+                headers: HashMap::new(),
+                user_context: crate::security::UserContext::admin("test".to_string()),
+                auth_context: None,
+                route_params: None,
+                uploaded_files: None,
+                timeout_ms: Some(2000),
+                tenant: None,
+            };
+            crate::js_engine::execute_script_source_for_request(params, source)
+        });
+
+        let paused: JsonValue =
+            serde_json::from_str(&events_rx.blocking_recv().expect("paused event")).unwrap();
+        assert_eq!(paused["method"], "Debugger.paused");
+        assert_eq!(paused["params"]["line"], 3);
+        assert_eq!(paused["params"]["reason"], "breakpoint");
+        assert_eq!(paused["params"]["variables"]["name"], "\"world\"");
+        assert!(
+            paused["params"]["stack"][0]
+                .as_str()
+                .unwrap()
+                .starts_with("handler")
+        );
+
+        // Stay paused past the execution time limit before resuming
+        std::thread::sleep(Duration::from_millis(2500));
+        let pause_id = paused["params"]["pauseId"].clone();
+        handle_command(session_id, "Debugger.resume", &json!({"pauseId": pause_id})).unwrap();
+        let response = worker.join().unwrap().expect("handler should finish");
+        assert_eq!(response.body, b"hello world");
+
+        detach(session_id);
+        configure(false);
+    }
+}
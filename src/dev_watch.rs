@@ -0,0 +1,190 @@
+//! Scripts edited on disk during local development (`[dev_watch]`).
+//!
+//! When enabled, the script files under `directory` are upserted when the
+//! server starts and again whenever they change, so scripts can be edited in
+//! an editor instead of the web UI. Every upsert runs the script's `init()`
+//! again and rebuilds the GraphQL schema, and the outcome is logged.
+//!
+//! The URI of a file's script is `uri_prefix` followed by the file's path
+//! relative to `directory`: with the defaults, `scripts/blog/posts.js` is
+//! `https://example.com/blog/posts`. `.js` is dropped from the URI while
+//! `.ts`, `.tsx` and `.jsx` are kept, so those scripts are transpiled.
+//! Hidden files, such as editor swap files, are ignored, and deleting a file
+//! leaves its script in place.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// The notify crate; crate::notify sends push notifications
+use ::notify::{RecursiveMode, Watcher};
+use tracing::{error, info, warn};
+
+use crate::config::DevWatchConfig;
+use crate::repository::{self, Repository as _};
+
+const SCRIPT_EXTENSIONS: [&str; 4] = ["js", "ts", "tsx", "jsx"];
+
+/// URI of the script in `path`, a file under `directory`; None for files
+/// that are not scripts
+fn script_uri(directory: &Path, path: &Path, uri_prefix: &str) -> Option<String> {
+    let relative = path.strip_prefix(directory).ok()?;
+    let extension = relative.extension()?.to_str()?;
+    if !SCRIPT_EXTENSIONS.contains(&extension) {
+        return None;
+    }
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        let part = component.as_os_str().to_str()?;
+        if part.starts_with('.') {
+            return None;
+        }
+        parts.push(part);
+    }
+    let mut name = parts.join("/");
+    if extension == "js" {
+        name.truncate(name.len() - ".js".len());
+    }
+    Some(format!("{}/{}", uri_prefix.trim_end_matches('/'), name))
+}
+
+/// Files under `directory` and its subdirectories, sorted
+fn files_under(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Upsert and reload the script in `path` if its content changed
+async fn sync_file(settings: &DevWatchConfig, directory: &Path, path: &Path) {
+    let Some(uri) = script_uri(directory, path, &settings.uri_prefix) else {
+        return;
+    };
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("{} removed; script {} is kept", path.display(), uri);
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    // Editors often write a file several times per save
+    if let Ok(Some(stored)) = repository::get_repository().get_script(&uri).await
+        && stored == content
+    {
+        return;
+    }
+    if let Err(e) = repository::upsert_script_async(&uri, &content).await {
+        error!("✗ Failed to upsert {} from {}: {}", uri, path.display(), e);
+        return;
+    }
+    match crate::admin_ops::reload_local_script(&uri).await {
+        Ok(duration_ms) => info!(
+            "✓ Reloaded {} from {} ({}ms)",
+            uri,
+            path.display(),
+            duration_ms
+        ),
+        Err(e) => error!("✗ Failed to reload {} from {}: {}", uri, path.display(), e),
+    }
+}
+
+fn collect_paths(event: ::notify::Result<::notify::Event>, paths: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) if !event.kind.is_access() => paths.extend(event.paths),
+        Ok(_) => {}
+        Err(e) => warn!("Script directory watch error: {}", e),
+    }
+}
+
+async fn watch(settings: DevWatchConfig) -> Result<(), String> {
+    let directory = std::fs::canonicalize(&settings.directory)
+        .map_err(|e| format!("Cannot watch {}: {}", settings.directory, e))?;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher =
+        ::notify::recommended_watcher(move |event: ::notify::Result<::notify::Event>| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| format!("Cannot watch {}: {}", directory.display(), e))?;
+    watcher
+        .watch(&directory, RecursiveMode::Recursive)
+        .map_err(|e| format!("Cannot watch {}: {}", directory.display(), e))?;
+    info!("Watching {} for script changes", directory.display());
+
+    let listed = directory.clone();
+    let files = tokio::task::spawn_blocking(move || files_under(&listed))
+        .await
+        .unwrap_or_default();
+    for path in files {
+        sync_file(&settings, &directory, &path).await;
+    }
+
+    let debounce = Duration::from_millis(settings.debounce_ms);
+    while let Some(event) = rx.recv().await {
+        let mut changed = BTreeSet::new();
+        collect_paths(event, &mut changed);
+        // Let the rest of a save arrive before reloading
+        tokio::time::sleep(debounce).await;
+        while let Ok(event) = rx.try_recv() {
+            collect_paths(event, &mut changed);
+        }
+        for path in changed {
+            sync_file(&settings, &directory, &path).await;
+        }
+    }
+    Ok(())
+}
+
+/// Upsert the scripts in the configured directory and keep reloading them
+/// as they change. Does nothing unless `[dev_watch]` is enabled.
+pub fn spawn_watcher(config: &DevWatchConfig) {
+    if !config.enabled {
+        return;
+    }
+    let settings = config.clone();
+    tokio::spawn(async move {
+        if let Err(e) = watch(settings).await {
+            error!("{}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_uri() {
+        let directory = Path::new("/work/scripts");
+        let uri = |path: &str| script_uri(directory, Path::new(path), "https://example.com/");
+        assert_eq!(
+            uri("/work/scripts/blog/posts.js").as_deref(),
+            Some("https://example.com/blog/posts")
+        );
+        assert_eq!(
+            uri("/work/scripts/app.ts").as_deref(),
+            Some("https://example.com/app.ts")
+        );
+        assert_eq!(uri("/work/scripts/notes.md"), None);
+        assert_eq!(uri("/work/scripts/.posts.js.swp"), None);
+        assert_eq!(uri("/work/scripts/.hidden/posts.js"), None);
+        assert_eq!(uri("/work/other/posts.js"), None);
+    }
+}
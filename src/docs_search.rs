@@ -0,0 +1,644 @@
+//! Documentation search
+//!
+//! Indexes the markdown files under the docs directory together with
+//! script-registered routes and external GraphQL operations, and serves ranked
+//! snippets for the docs UI at `GET /engine/docs/search`.
+//!
+//! What a search covers depends on the caller (see [`SearchAccess`]):
+//! anonymous callers search the public pages only, signed-in users also the
+//! administrator and contributor guides, and only editors and administrators
+//! find registered routes, the scripts behind them and GraphQL operations.
+//!
+//! The markdown indexes are built once on first use (docs ship with the
+//! binary's deployment and do not change at runtime). Registrations change
+//! whenever a script is saved, so they are indexed per query; there are few
+//! enough of them that this is cheaper than tracking invalidation.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use axum::extract::Query;
+use axum::response::{IntoResponse, Json};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::openapi_schemas::{DocsSearchHit, DocsSearchResponse};
+use crate::repository::Repository as _;
+
+/// Directory scanned for markdown documentation when `AIWEBENGINE_DOCS_DIR` is unset
+const DEFAULT_DOCS_DIR: &str = "docs";
+
+/// URL prefix under which the docs UI serves markdown pages
+const DOCS_URL_PREFIX: &str = "/engine/docs/";
+
+/// Docs directories about running and developing the engine rather than
+/// writing scripts; anonymous callers do not find their pages
+const INTERNAL_DOCS_DIRS: &[&str] = &["engine-administrators", "engine-contributors"];
+
+/// Default and maximum number of results returned per query
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+/// Approximate snippet length in characters
+const SNIPPET_CHARS: usize = 160;
+
+/// Title matches count this many times a body match
+const TITLE_BOOST: f64 = 3.0;
+
+static DOCS_INDEXES: OnceLock<DocsIndexes> = OnceLock::new();
+
+/// What a search may return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchAccess {
+    /// Public documentation pages
+    Public,
+    /// Every documentation page
+    Docs,
+    /// Every documentation page and script registrations
+    Full,
+}
+
+impl SearchAccess {
+    /// Access of the caller: anonymous callers get public pages, signed-in
+    /// users every page, and editors and administrators registrations too.
+    /// With authentication disabled everyone gets full access.
+    pub fn for_user(auth_user: Option<&crate::auth::AuthUser>, auth_enabled: bool) -> Self {
+        match auth_user {
+            _ if !auth_enabled => Self::Full,
+            Some(user) if user.is_admin || user.is_editor => Self::Full,
+            Some(_) => Self::Docs,
+            None => Self::Public,
+        }
+    }
+}
+
+/// Kind of indexed document, reported to the UI for grouping results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocKind {
+    /// Markdown page from the docs directory
+    Doc,
+    /// Script-registered HTTP route
+    Route,
+    /// Script-registered GraphQL operation
+    GraphQL,
+}
+
+impl DocKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Doc => "doc",
+            Self::Route => "route",
+            Self::GraphQL => "graphql",
+        }
+    }
+}
+
+/// A single searchable document
+#[derive(Debug, Clone)]
+pub struct IndexedDocument {
+    pub kind: DocKind,
+    pub title: String,
+    pub url: String,
+    pub text: String,
+    /// Found by anonymous callers
+    pub public: bool,
+}
+
+/// Inverted index over a set of documents
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: Vec<IndexedDocument>,
+    /// term -> (document index, weighted term frequency)
+    postings: BTreeMap<String, Vec<(usize, f64)>>,
+}
+
+impl SearchIndex {
+    /// Build an index; title terms are weighted by [`TITLE_BOOST`]
+    pub fn build(documents: Vec<IndexedDocument>) -> Self {
+        let mut postings: BTreeMap<String, Vec<(usize, f64)>> = BTreeMap::new();
+
+        for (doc_idx, doc) in documents.iter().enumerate() {
+            let mut frequencies: HashMap<String, f64> = HashMap::new();
+            for term in tokenize(&doc.title) {
+                *frequencies.entry(term).or_default() += TITLE_BOOST;
+            }
+            for term in tokenize(&doc.text) {
+                *frequencies.entry(term).or_default() += 1.0;
+            }
+            for (term, frequency) in frequencies {
+                postings.entry(term).or_default().push((doc_idx, frequency));
+            }
+        }
+
+        Self {
+            documents,
+            postings,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Score documents against the query terms (tf-idf with a damped term
+    /// frequency). The last term also matches as a prefix so results update
+    /// while the user is still typing.
+    fn score(&self, terms: &[String]) -> Vec<(usize, f64)> {
+        let total_docs = self.documents.len() as f64;
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+
+        for (position, term) in terms.iter().enumerate() {
+            let is_last = position + 1 == terms.len();
+            let mut matched: HashMap<usize, f64> = HashMap::new();
+
+            if let Some(postings) = self.postings.get(term) {
+                for &(doc_idx, frequency) in postings {
+                    matched.insert(doc_idx, frequency);
+                }
+            }
+            if is_last && term.len() >= 2 {
+                for (key, postings) in self
+                    .postings
+                    .range(term.clone()..)
+                    .take_while(|(key, _)| key.starts_with(term.as_str()))
+                {
+                    if key == term {
+                        continue;
+                    }
+                    for &(doc_idx, frequency) in postings {
+                        // Prefix hits rank below whole-word hits
+                        let entry = matched.entry(doc_idx).or_default();
+                        *entry = entry.max(frequency * 0.5);
+                    }
+                }
+            }
+
+            if matched.is_empty() {
+                continue;
+            }
+            let idf = (1.0 + total_docs / matched.len() as f64).ln();
+            for (doc_idx, frequency) in matched {
+                *scores.entry(doc_idx).or_default() += idf * (1.0 + frequency.ln());
+            }
+        }
+
+        let mut ranked: Vec<(usize, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        ranked
+    }
+
+    /// Search the index, returning all matches ranked by score
+    pub fn search(&self, query: &str) -> Vec<DocsSearchHit> {
+        let terms = tokenize(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        self.score(&terms)
+            .into_iter()
+            .filter_map(|(doc_idx, score)| {
+                let doc = self.documents.get(doc_idx)?;
+                Some(DocsSearchHit {
+                    kind: doc.kind.as_str().to_string(),
+                    title: doc.title.clone(),
+                    url: doc.url.clone(),
+                    snippet: make_snippet(&doc.text, &terms),
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Split text into lowercase alphanumeric terms of at least two characters
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 2)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Cut a window of roughly [`SNIPPET_CHARS`] around the first query term found
+/// in the text, falling back to the beginning of the text
+pub fn make_snippet(text: &str, terms: &[String]) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lower = collapsed.to_lowercase();
+
+    // Lowercasing can change byte lengths for some scripts; only trust the
+    // match position when it maps back onto the original string
+    let match_start = terms
+        .iter()
+        .filter_map(|term| lower.find(term.as_str()))
+        .min()
+        .filter(|&idx| lower.len() == collapsed.len() && collapsed.is_char_boundary(idx))
+        .unwrap_or(0);
+
+    let chars_before = collapsed[..match_start].chars().count();
+    let start_char = chars_before.saturating_sub(SNIPPET_CHARS / 4);
+    let snippet: String = collapsed
+        .chars()
+        .skip(start_char)
+        .take(SNIPPET_CHARS)
+        .collect();
+
+    let mut result = String::new();
+    if start_char > 0 {
+        result.push('…');
+    }
+    result.push_str(snippet.trim());
+    if start_char + SNIPPET_CHARS < collapsed.chars().count() {
+        result.push('…');
+    }
+    result
+}
+
+/// Strip the markdown syntax that would otherwise show up in snippets
+fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    for line in markdown.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.chars().all(|c| c == '-' || c == '|' || c == ' ') {
+            continue;
+        }
+        let line = line.trim_start_matches(['#', '>', '-', '*', ' ']);
+        for c in line.chars() {
+            if !matches!(c, '`' | '*' | '_' | '[' | ']' | '|') {
+                text.push(c);
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// First level-one heading, or the file stem when the page has none
+fn markdown_title(markdown: &str, path: &Path) -> String {
+    markdown
+        .lines()
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+}
+
+fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Skipping docs directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_markdown_files(&path, files);
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("md") {
+            files.push(path);
+        }
+    }
+}
+
+/// Load every markdown page under `docs_dir` as an indexable document
+pub fn load_markdown_documents(docs_dir: &Path) -> Vec<IndexedDocument> {
+    let mut files = Vec::new();
+    collect_markdown_files(docs_dir, &mut files);
+    files.sort();
+
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let markdown = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Failed to read docs page {}: {}", path.display(), e);
+                    return None;
+                }
+            };
+            let relative = path.strip_prefix(docs_dir).unwrap_or(&path);
+            let public = !relative.components().next().is_some_and(|dir| {
+                INTERNAL_DOCS_DIRS.contains(&dir.as_os_str().to_string_lossy().as_ref())
+            });
+            let slug = relative
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/");
+            Some(IndexedDocument {
+                kind: DocKind::Doc,
+                title: markdown_title(&markdown, &path),
+                url: format!("{}{}", DOCS_URL_PREFIX, slug),
+                text: markdown_to_text(&markdown),
+                public,
+            })
+        })
+        .collect()
+}
+
+fn docs_dir() -> PathBuf {
+    std::env::var("AIWEBENGINE_DOCS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_DOCS_DIR))
+}
+
+/// Markdown indexes of the public pages and of every page
+struct DocsIndexes {
+    public: SearchIndex,
+    all: SearchIndex,
+}
+
+/// The markdown index `access` searches, built on first use
+fn docs_index(access: SearchAccess) -> &'static SearchIndex {
+    let indexes = DOCS_INDEXES.get_or_init(|| {
+        let dir = docs_dir();
+        let documents = load_markdown_documents(&dir);
+        let public = documents.iter().filter(|doc| doc.public).cloned().collect();
+        let indexes = DocsIndexes {
+            public: SearchIndex::build(public),
+            all: SearchIndex::build(documents),
+        };
+        debug!(
+            "Built docs search index from {}: {} pages, {} public",
+            dir.display(),
+            indexes.all.len(),
+            indexes.public.len()
+        );
+        indexes
+    });
+    match access {
+        SearchAccess::Public => &indexes.public,
+        SearchAccess::Docs | SearchAccess::Full => &indexes.all,
+    }
+}
+
+/// Routes and external GraphQL operations currently registered by scripts
+async fn registration_documents() -> Vec<IndexedDocument> {
+    let mut documents = Vec::new();
+
+    let metadata = match crate::repository::get_repository_opt() {
+        Some(repo) => repo.get_all_script_metadata().await,
+        None => Ok(Vec::new()),
+    };
+    match metadata {
+        Ok(metadata) => {
+            for script in metadata.iter().filter(|script| script.initialized) {
+                for ((path, method), route) in &script.registrations {
+                    let mut text = vec![
+                        route.summary.clone().unwrap_or_default(),
+                        route.description.clone().unwrap_or_default(),
+                        route.tags.join(" "),
+                        route.handler_name.clone(),
+                        script.uri.clone(),
+                    ];
+                    text.retain(|part| !part.is_empty());
+                    documents.push(IndexedDocument {
+                        kind: DocKind::Route,
+                        title: format!("{} {}", method, path),
+                        url: path.clone(),
+                        text: text.join("\n"),
+                        public: false,
+                    });
+                }
+            }
+        }
+        Err(e) => warn!("Docs search could not read route registrations: {}", e),
+    }
+
+    // Only external operations are reachable over /graphql, so only those are
+    // worth documenting (and engine-internal ones should not be advertised)
+    let registry = crate::graphql::get_registry();
+    if let Ok(registry) = registry.read() {
+        let operations = [
+            ("query", registry.get_queries()),
+            ("mutation", registry.get_mutations()),
+            ("subscription", registry.get_subscriptions()),
+        ];
+        for (operation_kind, ops) in operations {
+            for (name, op) in ops {
+                if op.visibility != crate::graphql::OperationVisibility::External {
+                    continue;
+                }
+                documents.push(IndexedDocument {
+                    kind: DocKind::GraphQL,
+                    title: format!("{} {}", operation_kind, name),
+                    url: "/graphql".to_string(),
+                    text: format!("{}\n{}", op.sdl, op.script_uri),
+                    public: false,
+                });
+            }
+        }
+    }
+
+    documents
+}
+
+/// Search the docs pages and, with full access, live registrations, merged
+/// by score
+pub async fn search(query: &str, limit: usize, access: SearchAccess) -> DocsSearchResponse {
+    let mut results = docs_index(access).search(query);
+    if access == SearchAccess::Full {
+        results.extend(SearchIndex::build(registration_documents().await).search(query));
+    }
+
+    // Ranks from the two indexes are comparable: both use the same scoring,
+    // only the idf base differs by corpus size
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let total = results.len();
+    results.truncate(limit);
+
+    DocsSearchResponse {
+        query: query.to_string(),
+        total,
+        results,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocsSearchParams {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+/// Documentation search endpoint
+#[utoipa::path(
+    get,
+    path = "/engine/docs/search",
+    tags = ["Documentation"],
+    params(
+        ("q" = String, Query, description = "Search terms; the last term also matches as a prefix"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of results (default 10, max 50)")
+    ),
+    responses(
+        (status = 200, description = "Ranked search results. Anonymous callers get public documentation pages only; routes, scripts and GraphQL operations are returned to editors and administrators.", body = crate::openapi_schemas::DocsSearchResponse),
+    )
+)]
+pub async fn search_handler(
+    Query(params): Query<DocsSearchParams>,
+    req: axum::extract::Request,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let access = SearchAccess::for_user(
+        req.extensions().get::<crate::auth::AuthUser>(),
+        crate::security::is_auth_enabled(),
+    );
+    Json(search(params.q.trim(), limit, access).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(title: &str, text: &str) -> IndexedDocument {
+        IndexedDocument {
+            kind: DocKind::Doc,
+            title: title.to_string(),
+            url: format!("/engine/docs/{}", title.to_lowercase()),
+            text: text.to_string(),
+            public: true,
+        }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Hello, routeRegistry.registerRoute()! a"),
+            vec!["hello", "routeregistry", "registerroute"]
+        );
+        assert!(tokenize("  - ").is_empty());
+    }
+
+    #[test]
+    fn test_search_ranks_title_matches_first() {
+        let index = SearchIndex::build(vec![
+            doc("Configuration", "Secrets are mentioned once here."),
+            doc("Secrets", "How to store secrets for scripts."),
+            doc("Streams", "Nothing relevant."),
+        ]);
+
+        let hits = index.search("secrets");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].title, "Secrets");
+        assert_eq!(hits[1].title, "Configuration");
+    }
+
+    #[test]
+    fn test_search_matches_last_term_as_prefix() {
+        let index = SearchIndex::build(vec![
+            doc("Scheduler", "Register recurring jobs."),
+            doc("Routes", "Register HTTP handlers."),
+        ]);
+
+        let hits = index.search("recur");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Scheduler");
+
+        // Only the last term is a prefix; earlier terms must match whole words
+        assert!(
+            index
+                .search("recur jobs")
+                .iter()
+                .all(|h| h.title == "Scheduler")
+        );
+        assert!(index.search("").is_empty());
+    }
+
+    #[test]
+    fn test_snippet_centers_on_match() {
+        let text = format!("{} needle {}", "lorem ".repeat(100), "ipsum ".repeat(100));
+        let snippet = make_snippet(&text, &["needle".to_string()]);
+        assert!(snippet.contains("needle"));
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.ends_with('…'));
+
+        let short = make_snippet("short text", &["missing".to_string()]);
+        assert_eq!(short, "short text");
+    }
+
+    #[test]
+    fn test_markdown_helpers() {
+        let markdown =
+            "# Getting Started\n\n```bash\ncargo run\n```\n\nUse `routeRegistry` **now**.";
+        assert_eq!(
+            markdown_title(markdown, Path::new("01-start.md")),
+            "Getting Started"
+        );
+        assert_eq!(markdown_title("no heading", Path::new("notes.md")), "notes");
+
+        let text = markdown_to_text(markdown);
+        assert!(text.contains("Use routeRegistry now."));
+        assert!(!text.contains("```"));
+    }
+
+    #[test]
+    fn test_internal_guides_are_not_public() {
+        let dir = std::env::temp_dir().join(format!("docs-search-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("engine-administrators")).unwrap();
+        std::fs::create_dir_all(dir.join("solution-developers")).unwrap();
+        std::fs::write(dir.join("INDEX.md"), "# Index").unwrap();
+        std::fs::write(dir.join("engine-administrators/SECRETS.md"), "# Secrets").unwrap();
+        std::fs::write(dir.join("solution-developers/ROUTES.md"), "# Routes").unwrap();
+
+        let documents = load_markdown_documents(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        let public: Vec<_> = documents
+            .iter()
+            .map(|doc| (doc.url.as_str(), doc.public))
+            .collect();
+        assert_eq!(
+            public,
+            vec![
+                ("/engine/docs/INDEX", true),
+                ("/engine/docs/engine-administrators/SECRETS", false),
+                ("/engine/docs/solution-developers/ROUTES", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_access() {
+        let user = |is_admin, is_editor| {
+            crate::auth::AuthUser::new(
+                "user-1".to_string(),
+                "google".to_string(),
+                "session".to_string(),
+                is_admin,
+                is_editor,
+                None,
+                None,
+            )
+        };
+
+        assert_eq!(SearchAccess::for_user(None, true), SearchAccess::Public);
+        assert_eq!(
+            SearchAccess::for_user(Some(&user(false, false)), true),
+            SearchAccess::Docs
+        );
+        assert_eq!(
+            SearchAccess::for_user(Some(&user(false, true)), true),
+            SearchAccess::Full
+        );
+        assert_eq!(
+            SearchAccess::for_user(Some(&user(true, false)), true),
+            SearchAccess::Full
+        );
+        // Without authentication every caller is trusted
+        assert_eq!(SearchAccess::for_user(None, false), SearchAccess::Full);
+    }
+}
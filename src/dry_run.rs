@@ -0,0 +1,380 @@
+//! Dry runs of scripts that have not been saved.
+//!
+//! The editor previews a handler by running the script body being edited
+//! against a request it describes (`scriptStorage.dryRun`). [`execute`] calls
+//! the handler the way a real request would, except that:
+//!
+//! - Database writes happen in a sandbox transaction that is rolled back when
+//!   the run ends, so the handler reads its own writes but nothing persists.
+//! - Log messages are captured and returned with the response instead of
+//!   being written to the log table.
+//! - Side effects a transaction cannot undo are refused with an error to the
+//!   script: stream, subscription and dispatcher messages, scheduler changes
+//!   and outbound requests other than GET and HEAD.
+//! - The handler runs without the capabilities to change scripts, assets,
+//!   streams or GraphQL registrations.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::js_engine::{self, JsHttpResponse, RequestExecutionParams};
+use crate::script_errors::JsError;
+use crate::security::{Capability, UserContext};
+
+/// Most log messages kept from one dry run
+const MAX_CAPTURED_LOGS: usize = 1000;
+
+/// Capabilities a dry run executes without
+const WITHHELD_CAPABILITIES: [Capability; 7] = [
+    Capability::WriteScripts,
+    Capability::DeleteScripts,
+    Capability::WriteAssets,
+    Capability::DeleteAssets,
+    Capability::DeleteLogs,
+    Capability::ManageStreams,
+    Capability::ManageGraphQL,
+];
+
+/// Request a dry run executes against (`scriptStorage.dryRun` argument)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunRequest {
+    /// Name of the handler function to call
+    pub handler: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Request path; a query string in it is merged into `query`
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    /// Route parameters, as a pattern like `/users/:id` would extract them
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+/// A log message written during a dry run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedLog {
+    pub level: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Response returned by a dry-run handler
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub headers: HashMap<String, String>,
+    /// Body as text, when it is UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Body as base64, when it is not UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl From<JsHttpResponse> for DryRunResponse {
+    fn from(response: JsHttpResponse) -> Self {
+        let (body, body_base64) = match String::from_utf8(response.body) {
+            Ok(text) => (Some(text), None),
+            Err(e) => (
+                None,
+                Some(base64::Engine::encode(
+                    &base64::engine::general_purpose::STANDARD,
+                    e.into_bytes(),
+                )),
+            ),
+        };
+        Self {
+            status: response.status,
+            content_type: response.content_type,
+            headers: response.headers,
+            body,
+            body_base64,
+        }
+    }
+}
+
+/// Outcome of a dry run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResult {
+    /// The handler's response; absent when the run failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<DryRunResponse>,
+    /// Why the run failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The exception the script threw, with source-mapped positions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception: Option<JsError>,
+    pub logs: Vec<CapturedLog>,
+    /// Whether log messages beyond the capture limit were dropped
+    pub logs_truncated: bool,
+    pub duration_ms: u64,
+}
+
+impl DryRunResult {
+    fn failed(error: String, start: Instant) -> Self {
+        Self {
+            response: None,
+            error: Some(error),
+            exception: None,
+            logs: Vec::new(),
+            logs_truncated: false,
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Capture {
+    logs: Vec<CapturedLog>,
+    truncated: bool,
+}
+
+thread_local! {
+    static ACTIVE_CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// Marks the current thread as running a dry run until dropped
+struct CaptureGuard;
+
+impl CaptureGuard {
+    fn start() -> Self {
+        ACTIVE_CAPTURE.with(|capture| *capture.borrow_mut() = Some(Capture::default()));
+        Self
+    }
+
+    fn finish(self) -> Capture {
+        ACTIVE_CAPTURE
+            .with(|capture| capture.borrow_mut().take())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        ACTIVE_CAPTURE.with(|capture| *capture.borrow_mut() = None);
+    }
+}
+
+/// Whether a dry run is executing on the current thread
+pub fn is_active() -> bool {
+    ACTIVE_CAPTURE.with(|capture| capture.borrow().is_some())
+}
+
+/// Refuse `operation` while a dry run is executing on the current thread
+pub fn ensure_allowed(operation: &str) -> Result<(), String> {
+    if is_active() {
+        return Err(format!("{} is disabled in dry runs", operation));
+    }
+    Ok(())
+}
+
+/// Keep a log message written during a dry run. Returns false when no dry
+/// run is executing and the message should be stored as usual.
+pub fn capture_log(level: &str, message: &str, data: Option<&serde_json::Value>) -> bool {
+    ACTIVE_CAPTURE.with(|capture| {
+        let mut capture = capture.borrow_mut();
+        let Some(capture) = capture.as_mut() else {
+            return false;
+        };
+        if capture.logs.len() >= MAX_CAPTURED_LOGS {
+            capture.truncated = true;
+        } else {
+            capture.logs.push(CapturedLog {
+                level: level.to_string(),
+                message: message.to_string(),
+                data: data.cloned(),
+                timestamp: chrono::Utc::now(),
+            });
+        }
+        true
+    })
+}
+
+/// `user_context` without the capabilities a dry run executes without
+pub fn restricted_user_context(mut user_context: UserContext) -> UserContext {
+    for capability in &WITHHELD_CAPABILITIES {
+        user_context.capabilities.remove(capability);
+    }
+    user_context
+}
+
+impl DryRunRequest {
+    fn into_params(
+        self,
+        script_uri: &str,
+        user_context: UserContext,
+        auth_context: Option<crate::auth::JsAuthContext>,
+    ) -> RequestExecutionParams {
+        let (path, mut query) = match self.path.split_once('?') {
+            Some((path, query)) => (
+                path.to_string(),
+                serde_urlencoded::from_str::<HashMap<String, String>>(query).unwrap_or_default(),
+            ),
+            None => (self.path, HashMap::new()),
+        };
+        query.extend(self.query);
+
+        // Header names arrive lowercased on real requests
+        let headers: HashMap<String, String> = self
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect();
+        let form_data = match (headers.get("content-type"), &self.body) {
+            (Some(content_type), Some(body))
+                if content_type.starts_with("application/x-www-form-urlencoded") =>
+            {
+                serde_urlencoded::from_str(body).unwrap_or_default()
+            }
+            _ => HashMap::new(),
+        };
+
+        let tenant = crate::tenancy::current_tenant();
+        let overrides = crate::tenancy::overrides_for(
+            tenant.as_deref(),
+            headers.get("host").map(String::as_str),
+        );
+
+        RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: self.handler,
+            path,
+            method: self.method.to_ascii_uppercase(),
+            query_params: Some(query),
+            form_data: Some(form_data),
+            raw_body: self.body,
+            headers,
+            user_context: restricted_user_context(user_context),
+            auth_context,
+            route_params: Some(self.params),
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: crate::tenancy::request_tenant_json(tenant.as_deref(), overrides.as_ref()),
+        }
+    }
+}
+
+/// Run `content` as the script at `script_uri`, calling its handler with
+/// `request` on behalf of the user in `user_context` and `auth_context`
+pub fn execute(
+    script_uri: &str,
+    content: &str,
+    request: DryRunRequest,
+    user_context: UserContext,
+    auth_context: Option<crate::auth::JsAuthContext>,
+) -> DryRunResult {
+    let start = Instant::now();
+    if is_active() {
+        return DryRunResult::failed("Dry runs cannot be nested".to_string(), start);
+    }
+    let sandbox = match crate::database::Database::begin_sandbox_transaction() {
+        Ok(sandbox) => sandbox,
+        Err(e) => return DryRunResult::failed(format!("Cannot start dry run: {}", e), start),
+    };
+
+    let capture = CaptureGuard::start();
+    let params = request.into_params(script_uri, user_context, auth_context);
+    let outcome = js_engine::execute_script_source_for_request(params, content);
+    let Capture { logs, truncated } = capture.finish();
+    drop(sandbox);
+
+    let (response, error, exception) = match outcome {
+        Ok(response) => (Some(response.into()), None, None),
+        Err(failure) => (
+            None,
+            Some(failure.message),
+            failure.exception.map(|exception| *exception),
+        ),
+    };
+    DryRunResult {
+        response,
+        error,
+        exception,
+        logs,
+        logs_truncated: truncated,
+        duration_ms: start.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_log_only_while_active() {
+        assert!(!capture_log("INFO", "outside", None));
+        assert!(ensure_allowed("fetch").is_ok());
+
+        let guard = CaptureGuard::start();
+        assert!(capture_log("INFO", "inside", None));
+        assert_eq!(
+            ensure_allowed("fetch"),
+            Err("fetch is disabled in dry runs".to_string())
+        );
+        let capture = guard.finish();
+        assert_eq!(capture.logs.len(), 1);
+        assert_eq!(capture.logs[0].message, "inside");
+        assert!(!is_active());
+    }
+
+    #[test]
+    fn test_request_into_params() {
+        let request: DryRunRequest = serde_json::from_str(
+            r#"{
+                "handler": "submit",
+                "method": "post",
+                "path": "/orders?page=2",
+                "headers": {"Content-Type": "application/x-www-form-urlencoded"},
+                "query": {"sort": "asc"},
+                "body": "item=book&qty=3"
+            }"#,
+        )
+        .unwrap();
+        let params = request.into_params(
+            "dry-run-test",
+            UserContext::admin("editor".to_string()),
+            None,
+        );
+        assert_eq!(params.method, "POST");
+        assert_eq!(params.path, "/orders");
+        let query = params.query_params.unwrap();
+        assert_eq!(query.get("page").map(String::as_str), Some("2"));
+        assert_eq!(query.get("sort").map(String::as_str), Some("asc"));
+        assert_eq!(
+            params.form_data.unwrap().get("qty").map(String::as_str),
+            Some("3")
+        );
+        assert!(params.headers.contains_key("content-type"));
+        assert!(
+            !params
+                .user_context
+                .has_capability(&Capability::WriteScripts)
+        );
+        assert!(params.user_context.has_capability(&Capability::ViewLogs));
+    }
+}
@@ -0,0 +1,589 @@
+//! Outgoing email of scripts (`email.send`).
+//!
+//! `email.send({ to, subject, html, text, attachments })` sends a message
+//! from the configured sender (`[javascript.email]`) and returns its entry
+//! in the delivery log. `provider = "smtp"` delivers it to the SMTP server;
+//! the password is read from the sending script's secret named by
+//! `smtp_password_secret`, so scripts never see it. `provider = "log"` only
+//! logs messages, for development.
+//!
+//! Instead of `html` or `text`, a message may name Handlebars templates
+//! stored as script assets (`template`, `textTemplate`), rendered with
+//! `data` like `templates.render`. Attachments carry their content as text
+//! or base64, or name a script asset.
+//!
+//! Every send the provider accepted or refused is recorded in the
+//! `email_log` table. A script may send `daily_limit` messages per UTC day
+//! (`script_daily_limits` overrides it per script); sends past the limit
+//! are refused. Scripts see their own log with `email.list()`,
+//! administrators every script's with the `emailLog` GraphQL query.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment as AttachmentPart, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::{EmailConfig, EmailProvider, SmtpTls};
+use crate::repository::{self, Repository as _};
+
+/// Longest accepted subject
+const MAX_SUBJECT_LENGTH: usize = 998;
+
+/// Most entries `email.list` and the GraphQL view return
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+/// How often log entries past their retention are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+static SETTINGS: OnceLock<RwLock<EmailConfig>> = OnceLock::new();
+
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn settings() -> &'static RwLock<EmailConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the email configuration. Called once at server startup.
+pub fn configure(config: &EmailConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> EmailConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Outcome of a send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailStatus {
+    /// Accepted by the provider
+    Sent,
+    /// Refused by the provider or not reachable
+    Failed,
+}
+
+impl EmailStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailStatus::Sent => "sent",
+            EmailStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sent" => Some(EmailStatus::Sent),
+            "failed" => Some(EmailStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the delivery log
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailLogEntry {
+    pub id: Uuid,
+    pub script_uri: String,
+    /// Addresses of to, cc and bcc
+    pub recipients: Vec<String>,
+    pub subject: String,
+    pub status: EmailStatus,
+    pub error: Option<String>,
+    /// Message-ID header of the message
+    pub message_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One address or a list of them
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Recipients {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for Recipients {
+    fn default() -> Self {
+        Recipients::Many(Vec::new())
+    }
+}
+
+impl Recipients {
+    fn addresses(&self) -> &[String] {
+        match self {
+            Recipients::One(address) => std::slice::from_ref(address),
+            Recipients::Many(addresses) => addresses,
+        }
+    }
+}
+
+/// How the content of an attachment is given
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+/// An attachment of `email.send`: its `content`, or the script asset
+/// named by `asset`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct Attachment {
+    pub filename: String,
+    pub content: Option<String>,
+    pub encoding: AttachmentEncoding,
+    pub asset: Option<String>,
+    /// Guessed from the file name or the asset when not given
+    pub content_type: Option<String>,
+}
+
+/// The message of `email.send`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct SendRequest {
+    pub to: Recipients,
+    pub cc: Recipients,
+    pub bcc: Recipients,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub html: Option<String>,
+    pub text: Option<String>,
+    /// Script asset rendered as the HTML body
+    pub template: Option<String>,
+    /// Script asset rendered as the plain text body
+    pub text_template: Option<String>,
+    /// Data the templates are rendered with
+    pub data: Option<Value>,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Filter of `email.list` and the GraphQL view
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListOptions {
+    pub status: Option<EmailStatus>,
+    pub script_uri: Option<String>,
+    pub limit: Option<i64>,
+}
+
+fn parse_mailbox(field: &str, address: &str) -> Result<Mailbox, String> {
+    address
+        .parse::<Mailbox>()
+        .map_err(|e| format!("Invalid {} address '{}': {}", field, address, e))
+}
+
+/// Messages `script_uri` may send per day; None when unlimited
+fn daily_limit(config: &EmailConfig, script_uri: &str) -> Option<u32> {
+    let limit = config
+        .script_daily_limits
+        .get(script_uri)
+        .copied()
+        .unwrap_or(config.daily_limit);
+    (limit > 0).then_some(limit)
+}
+
+/// The HTML or plain text body: given inline or rendered from a template
+fn body(
+    script_uri: &str,
+    field: &str,
+    inline: &Option<String>,
+    template: &Option<String>,
+    data: &Value,
+) -> Result<Option<String>, String> {
+    match (inline, template) {
+        (Some(_), Some(_)) => Err(format!("Use either {} or its template, not both", field)),
+        (Some(content), None) => Ok(Some(content.clone())),
+        (None, Some(name)) => crate::templates::render(script_uri, name, data).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+fn attachment_part(script_uri: &str, attachment: &Attachment) -> Result<SinglePart, String> {
+    if attachment.filename.trim().is_empty() {
+        return Err("Attachments need a filename".to_string());
+    }
+    let (content, asset_type) = match (&attachment.content, &attachment.asset) {
+        (Some(content), None) => match attachment.encoding {
+            AttachmentEncoding::Utf8 => (content.as_bytes().to_vec(), None),
+            AttachmentEncoding::Base64 => (
+                base64::engine::general_purpose::STANDARD
+                    .decode(content)
+                    .map_err(|e| {
+                        format!(
+                            "Attachment '{}' is not valid base64: {}",
+                            attachment.filename, e
+                        )
+                    })?,
+                None,
+            ),
+        },
+        (None, Some(name)) => {
+            let asset = repository::fetch_asset(script_uri, name)
+                .ok_or_else(|| format!("Asset '{}' not found", name))?;
+            (asset.content, Some(asset.mimetype))
+        }
+        _ => {
+            return Err(format!(
+                "Attachment '{}' needs either content or asset",
+                attachment.filename
+            ));
+        }
+    };
+    let content_type = attachment
+        .content_type
+        .clone()
+        .or(asset_type)
+        .unwrap_or_else(|| {
+            mime_guess::from_path(&attachment.filename)
+                .first_or_octet_stream()
+                .to_string()
+        });
+    let content_type = ContentType::parse(&content_type)
+        .map_err(|_| format!("Invalid content type '{}'", content_type))?;
+    Ok(AttachmentPart::new(attachment.filename.clone()).body(content, content_type))
+}
+
+/// Build the message of `request` and the addresses it goes to
+fn build_message(
+    config: &EmailConfig,
+    script_uri: &str,
+    id: Uuid,
+    request: &SendRequest,
+) -> Result<(Message, Vec<String>), String> {
+    let subject = request.subject.trim();
+    if subject.is_empty() {
+        return Err("email.send requires a subject".to_string());
+    }
+    if subject.len() > MAX_SUBJECT_LENGTH || subject.contains(['\r', '\n']) {
+        return Err(format!(
+            "subject must be one line of at most {} characters",
+            MAX_SUBJECT_LENGTH
+        ));
+    }
+    let from = parse_mailbox("from", &config.from)?;
+    let mut builder = Message::builder()
+        .message_id(Some(format!("<{}@{}>", id, from.email.domain())))
+        .from(from)
+        .subject(subject);
+
+    let mut recipients = Vec::new();
+    for (field, addresses) in [
+        ("to", &request.to),
+        ("cc", &request.cc),
+        ("bcc", &request.bcc),
+    ] {
+        for address in addresses.addresses() {
+            let mailbox = parse_mailbox(field, address)?;
+            recipients.push(mailbox.email.to_string());
+            builder = match field {
+                "to" => builder.to(mailbox),
+                "cc" => builder.cc(mailbox),
+                _ => builder.bcc(mailbox),
+            };
+        }
+    }
+    if request.to.addresses().is_empty() {
+        return Err("email.send requires at least one to address".to_string());
+    }
+    if recipients.len() > config.max_recipients {
+        return Err(format!(
+            "A message may have at most {} recipients",
+            config.max_recipients
+        ));
+    }
+    if let Some(reply_to) = &request.reply_to {
+        builder = builder.reply_to(parse_mailbox("replyTo", reply_to)?);
+    }
+
+    let data = request
+        .data
+        .clone()
+        .unwrap_or_else(|| Value::Object(Default::default()));
+    let html = body(script_uri, "html", &request.html, &request.template, &data)?;
+    let text = body(
+        script_uri,
+        "text",
+        &request.text,
+        &request.text_template,
+        &data,
+    )?;
+    let message = match (text, html, request.attachments.is_empty()) {
+        (None, None, _) => {
+            return Err("email.send requires html, text or a template".to_string());
+        }
+        (Some(text), None, true) => builder.singlepart(SinglePart::plain(text)),
+        (None, Some(html), true) => builder.singlepart(SinglePart::html(html)),
+        (Some(text), Some(html), true) => {
+            builder.multipart(MultiPart::alternative_plain_html(text, html))
+        }
+        (text, html, false) => {
+            let mut mixed = match (text, html) {
+                (Some(text), Some(html)) => {
+                    MultiPart::mixed().multipart(MultiPart::alternative_plain_html(text, html))
+                }
+                (Some(text), None) => MultiPart::mixed().singlepart(SinglePart::plain(text)),
+                (None, Some(html)) => MultiPart::mixed().singlepart(SinglePart::html(html)),
+                (None, None) => unreachable!("matched above"),
+            };
+            for attachment in &request.attachments {
+                mixed = mixed.singlepart(attachment_part(script_uri, attachment)?);
+            }
+            builder.multipart(mixed)
+        }
+    }
+    .map_err(|e| format!("Invalid message: {}", e))?;
+
+    if message.formatted().len() > config.max_message_bytes {
+        return Err(format!(
+            "Message must not exceed {} bytes",
+            config.max_message_bytes
+        ));
+    }
+    Ok((message, recipients))
+}
+
+/// Hand `message` to the SMTP server
+fn send_smtp(config: &EmailConfig, script_uri: &str, message: &Message) -> Result<(), String> {
+    let host = config.smtp_host.as_str();
+    let mut transport = match config.smtp_tls {
+        SmtpTls::Starttls => SmtpTransport::starttls_relay(host).map_err(|e| e.to_string())?,
+        SmtpTls::Tls => SmtpTransport::relay(host).map_err(|e| e.to_string())?,
+        SmtpTls::None => SmtpTransport::builder_dangerous(host),
+    }
+    .port(config.smtp_port)
+    .timeout(Some(Duration::from_millis(config.timeout_ms)));
+    if let Some(username) = &config.smtp_username {
+        let password = match &config.smtp_password_secret {
+            Some(secret) => repository::resolve_secret_db(script_uri, secret, None)
+                .ok_or_else(|| format!("Secret '{}' is not set", secret))?,
+            None => String::new(),
+        };
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+    transport
+        .build()
+        .send(message)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Send `request` from `script_uri` and record it in the delivery log
+pub fn send(script_uri: &str, request: &SendRequest) -> Result<EmailLogEntry, String> {
+    let config = current_settings();
+    if config.provider == EmailProvider::Disabled {
+        return Err("Email is not configured on this server".to_string());
+    }
+    let id = Uuid::new_v4();
+    let (message, recipients) = build_message(&config, script_uri, id, request)?;
+
+    if let Some(limit) = daily_limit(&config, script_uri) {
+        let today = Utc::now()
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+            .and_utc();
+        let sent = repository::count_emails_since(script_uri, today).map_err(|e| e.to_string())?;
+        if sent >= i64::from(limit) {
+            return Err(format!(
+                "Daily email limit ({}) reached for this script",
+                limit
+            ));
+        }
+    }
+
+    let result = match config.provider {
+        EmailProvider::Smtp => send_smtp(&config, script_uri, &message),
+        EmailProvider::Log => {
+            info!(
+                script_uri = %script_uri,
+                id = %id,
+                recipients = ?recipients,
+                "Email (log provider, not delivered): {}",
+                request.subject.trim()
+            );
+            Ok(())
+        }
+        EmailProvider::Disabled => unreachable!("checked above"),
+    };
+    let entry = EmailLogEntry {
+        id,
+        script_uri: script_uri.to_string(),
+        recipients,
+        subject: request.subject.trim().to_string(),
+        status: if result.is_ok() {
+            EmailStatus::Sent
+        } else {
+            EmailStatus::Failed
+        },
+        error: result.as_ref().err().cloned(),
+        message_id: message.headers().get_raw("Message-ID").map(str::to_string),
+        created_at: Utc::now(),
+    };
+    if let Err(e) = repository::insert_email_log(&entry) {
+        warn!(id = %id, "Failed to record email: {}", e);
+    }
+    match result {
+        Ok(()) => {
+            debug!(script_uri = %script_uri, id = %id, "Sent email");
+            Ok(entry)
+        }
+        Err(error) => {
+            warn!(script_uri = %script_uri, id = %id, "Failed to send email: {}", error);
+            Err(format!("Failed to send email: {}", error))
+        }
+    }
+}
+
+/// Log entries matching `options`, newest first
+pub fn list(options: &ListOptions) -> Result<Vec<EmailLogEntry>, String> {
+    let limit = options.limit.unwrap_or(100);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return Err(format!("limit must be between 1 and {}", MAX_LIST_LIMIT));
+    }
+    repository::list_email_log(options.status, options.script_uri.as_deref(), limit)
+        .map_err(|e| e.to_string())
+}
+
+/// Start the background task that deletes log entries past their
+/// retention. Only the first call starts it.
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let config = current_settings();
+            let before = Utc::now() - chrono::Duration::hours(config.retention_hours as i64);
+            match repository::get_repository().purge_email_log(before).await {
+                Ok(0) => {}
+                Ok(count) => debug!("Removed {} email log entries", count),
+                Err(e) => warn!("Failed to purge email log: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            provider: EmailProvider::Log,
+            from: "Example <noreply@example.com>".to_string(),
+            max_recipients: 2,
+            ..EmailConfig::default()
+        }
+    }
+
+    fn build(request: Value) -> Result<(Message, Vec<String>), String> {
+        let request: SendRequest = serde_json::from_value(request).unwrap();
+        build_message(
+            &config(),
+            "https://example.com/mail",
+            Uuid::new_v4(),
+            &request,
+        )
+    }
+
+    #[test]
+    fn test_build_message() {
+        let (message, recipients) = build(json!({
+            "to": "Ann <ann@example.com>",
+            "bcc": ["audit@example.com"],
+            "subject": "Welcome",
+            "text": "Hello",
+            "html": "<p>Hello</p>",
+            "attachments": [{ "filename": "a.txt", "content": "aGk=", "encoding": "base64" }]
+        }))
+        .unwrap();
+        assert_eq!(recipients, vec!["ann@example.com", "audit@example.com"]);
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("Subject: Welcome"));
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("filename=\"a.txt\""));
+        assert!(!formatted.contains("audit@example.com"));
+    }
+
+    #[test]
+    fn test_build_message_validation() {
+        let error = |request: Value| build(request).unwrap_err();
+        assert_eq!(
+            error(json!({ "to": "a@example.com", "text": "x" })),
+            "email.send requires a subject"
+        );
+        assert_eq!(
+            error(json!({ "to": "a@example.com", "subject": "Hi" })),
+            "email.send requires html, text or a template"
+        );
+        assert_eq!(
+            error(json!({ "subject": "Hi", "text": "x" })),
+            "email.send requires at least one to address"
+        );
+        assert!(
+            error(json!({ "to": "nobody", "subject": "Hi", "text": "x" }))
+                .starts_with("Invalid to address 'nobody'")
+        );
+        assert_eq!(
+            error(json!({
+                "to": ["a@example.com", "b@example.com"],
+                "cc": "c@example.com",
+                "subject": "Hi",
+                "text": "x"
+            })),
+            "A message may have at most 2 recipients"
+        );
+        assert_eq!(
+            error(json!({
+                "to": "a@example.com",
+                "subject": "Hi",
+                "html": "<p>x</p>",
+                "template": "welcome.hbs"
+            })),
+            "Use either html or its template, not both"
+        );
+        assert_eq!(
+            error(json!({
+                "to": "a@example.com",
+                "subject": "Hi",
+                "text": "x",
+                "attachments": [{ "filename": "a.txt" }]
+            })),
+            "Attachment 'a.txt' needs either content or asset"
+        );
+        assert!(serde_json::from_value::<SendRequest>(json!({ "body": "x" })).is_err());
+    }
+
+    #[test]
+    fn test_daily_limit() {
+        let mut config = config();
+        config.daily_limit = 10;
+        config
+            .script_daily_limits
+            .insert("https://example.com/unlimited".to_string(), 0);
+        assert_eq!(daily_limit(&config, "https://example.com/mail"), Some(10));
+        assert_eq!(daily_limit(&config, "https://example.com/unlimited"), None);
+    }
+}
@@ -13,6 +13,8 @@ pub enum ErrorCode {
     MethodNotAllowed,
     Conflict,
     UnprocessableEntity,
+    PayloadTooLarge,
+    UnsupportedMediaType,
     TooManyRequests,
 
     // Server errors (5xx)
@@ -132,6 +134,8 @@ impl ErrorResponseBuilder {
             ErrorCode::MethodNotAllowed => 405,
             ErrorCode::Conflict => 409,
             ErrorCode::UnprocessableEntity => 422,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::UnsupportedMediaType => 415,
             ErrorCode::TooManyRequests => 429,
             ErrorCode::InternalServerError => 500,
             ErrorCode::NotImplemented => 501,
@@ -153,6 +157,21 @@ impl ErrorResponseBuilder {
 pub mod errors {
     use super::*;
 
+    pub fn unauthorized(path: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::Unauthorized, "Authentication required")
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn forbidden(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::Forbidden, "Forbidden")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
     pub fn not_found(path: &str, request_id: &str) -> ErrorResponse {
         ErrorResponseBuilder::new(ErrorCode::NotFound, "Resource not found")
             .path(path)
@@ -176,6 +195,23 @@ pub mod errors {
             .build()
     }
 
+    pub fn script_exception(
+        path: &str,
+        summary: &str,
+        exception: Option<serde_json::Value>,
+        request_id: &str,
+    ) -> ErrorResponse {
+        let mut builder =
+            ErrorResponseBuilder::new(ErrorCode::ScriptExecutionFailed, "Script execution failed")
+                .details(summary)
+                .path(path)
+                .request_id(request_id);
+        if let Some(exception) = exception {
+            builder = builder.context("exception", exception);
+        }
+        builder.build()
+    }
+
     pub fn script_timeout(path: &str, request_id: &str) -> ErrorResponse {
         ErrorResponseBuilder::new(ErrorCode::ScriptTimeout, "Script execution timeout")
             .path(path)
@@ -183,6 +219,73 @@ pub mod errors {
             .build()
     }
 
+    pub fn bad_request(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::BadRequest, "Bad request")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn schema_validation_failed(
+        path: &str,
+        violations: &[crate::request_schema::SchemaViolation],
+        request_id: &str,
+    ) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::ValidationError, "Request validation failed")
+            .details(format!(
+                "{} violation(s) of the route's request schema",
+                violations.len()
+            ))
+            .path(path)
+            .request_id(request_id)
+            .context(
+                "violations",
+                serde_json::to_value(violations).unwrap_or_default(),
+            )
+            .build()
+    }
+
+    pub fn conflict(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::Conflict, "Conflict")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn unprocessable_entity(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::UnprocessableEntity, "Unprocessable entity")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn unsupported_media_type(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::UnsupportedMediaType, "Unsupported media type")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn too_many_requests(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::TooManyRequests, "Too many requests")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
+    pub fn service_unavailable(path: &str, reason: &str, request_id: &str) -> ErrorResponse {
+        ErrorResponseBuilder::new(ErrorCode::ServiceUnavailable, "Service unavailable")
+            .details(reason)
+            .path(path)
+            .request_id(request_id)
+            .build()
+    }
+
     pub fn internal_server_error(path: &str, error: &str, request_id: &str) -> ErrorResponse {
         ErrorResponseBuilder::new(ErrorCode::InternalServerError, "Internal server error")
             .details(error)
@@ -41,6 +41,9 @@ pub enum AppError {
     #[error("Asset not found: {name}")]
     AssetNotFound { name: String },
 
+    #[error("Storage quota exceeded: {message}")]
+    QuotaExceeded { message: String },
+
     // JavaScript execution errors
     #[error("JavaScript execution failed: {message}")]
     JsExecution { message: String },
@@ -135,6 +138,7 @@ impl AppError {
             AppError::RateLimitExceeded => 429,
             AppError::ScriptNotFound { .. } => 404,
             AppError::AssetNotFound { .. } => 404,
+            AppError::QuotaExceeded { .. } => 413,
 
             // Server errors (5xx)
             AppError::Config { .. } => 500,
@@ -214,6 +218,16 @@ impl AppError {
                 .request_id(request_id)
                 .build();
             }
+            AppError::QuotaExceeded { message } => {
+                return ErrorResponseBuilder::new(
+                    ErrorCode::PayloadTooLarge,
+                    format!("Storage quota exceeded: {}", message),
+                )
+                .path(path)
+                .method(method)
+                .request_id(request_id)
+                .build();
+            }
             AppError::JsTimeout { timeout_ms } => {
                 return ErrorResponseBuilder::new(
                     ErrorCode::ScriptTimeout,
@@ -310,6 +324,17 @@ impl From<crate::repository::RepositoryError> for AppError {
                 field: "data".to_string(),
                 reason: msg,
             },
+            crate::repository::RepositoryError::QuotaExceeded(message) => {
+                AppError::QuotaExceeded { message }
+            }
+            crate::repository::RepositoryError::ProtectedScript(uri) => {
+                AppError::AuthorizationFailed {
+                    message: format!(
+                        "{} is a system script; only administrators can change it with force",
+                        uri
+                    ),
+                }
+            }
         }
     }
 }
@@ -422,6 +447,13 @@ mod tests {
             400
         );
         assert_eq!(AppError::RateLimitExceeded.status_code(), 429);
+        assert_eq!(
+            AppError::QuotaExceeded {
+                message: "test".to_string(),
+            }
+            .status_code(),
+            413
+        );
         assert_eq!(AppError::JsTimeout { timeout_ms: 5000 }.status_code(), 504);
         assert_eq!(
             AppError::Internal {
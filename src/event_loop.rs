@@ -0,0 +1,307 @@
+//! Host operations that settle script promises: `fetchAsync()` requests and
+//! `setTimeout()` timers.
+//!
+//! Every sandboxed execution opens a [`Scope`] on its thread. A script
+//! starts an operation through a host function, which returns the id the
+//! script keeps its promise or timer callback under. Requests run on
+//! threads of their own, so the requests of one execution run at the same
+//! time, and timers wait in the scope until they are due.
+//!
+//! While a handler's promise is pending and the runtime has no job left,
+//! the engine calls [`next_wake`] to wait for the next request to finish or
+//! timer to fire and settles it in the script. When nothing is left that
+//! could settle the promise, the promise never settles; when the
+//! execution's deadline passes first, the execution has timed out.
+//! Operations still pending when the execution ends are dropped.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Requests and timers one execution may have pending at a time
+pub const MAX_PENDING_OPERATIONS: usize = 64;
+
+/// Outcome of a host request: its result for the script, or the message
+/// its promise is rejected with
+pub type Outcome = Result<String, String>;
+
+/// What a wait in [`next_wake`] ended with
+#[derive(Debug, PartialEq)]
+pub enum Wake {
+    /// A request finished
+    Completed { id: u32, outcome: Outcome },
+    /// A timer is due
+    TimerFired { id: u32 },
+}
+
+struct EventLoop {
+    scope_id: u64,
+    deadline: Instant,
+    timed_out: Arc<AtomicBool>,
+    next_id: u32,
+    /// Pending timers with when they are due
+    timers: Vec<(Instant, u32)>,
+    in_flight: usize,
+    sender: Sender<(u32, Outcome)>,
+    receiver: Receiver<(u32, Outcome)>,
+}
+
+impl EventLoop {
+    fn allocate_id(&mut self) -> Result<u32, String> {
+        if self.timers.len() + self.in_flight >= MAX_PENDING_OPERATIONS {
+            return Err(format!(
+                "At most {} requests and timers may be pending at a time",
+                MAX_PENDING_OPERATIONS
+            ));
+        }
+        self.next_id = self.next_id.wrapping_add(1);
+        Ok(self.next_id)
+    }
+
+    /// Wait for the next request to finish or timer to fire; None when
+    /// nothing is pending
+    fn wait(&mut self) -> Result<Option<Wake>, String> {
+        loop {
+            if self.in_flight == 0 && self.timers.is_empty() {
+                return Ok(None);
+            }
+            // Time spent paused in the debugger does not count against the
+            // deadline
+            let deadline = self.deadline + crate::debugger::paused_time();
+            let next_timer = self
+                .timers
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (due, _))| *due)
+                .map(|(index, (due, _))| (index, *due));
+            let now = Instant::now();
+            if let Some((index, due)) = next_timer
+                && due <= now
+            {
+                let (_, id) = self.timers.remove(index);
+                return Ok(Some(Wake::TimerFired { id }));
+            }
+            if now >= deadline {
+                self.timed_out.store(true, Ordering::Relaxed);
+                return Err("Script execution timed out".to_string());
+            }
+
+            let until = next_timer.map_or(deadline, |(_, due)| due.min(deadline));
+            let timeout = until.saturating_duration_since(now);
+            if self.in_flight == 0 {
+                std::thread::sleep(timeout);
+                continue;
+            }
+            match self.receiver.recv_timeout(timeout) {
+                Ok((id, outcome)) => {
+                    self.in_flight -= 1;
+                    return Ok(Some(Wake::Completed { id, outcome }));
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                // The loop keeps a sender of its own
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("Host operations stopped".to_string());
+                }
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Event loops of the executions running on this thread, innermost last
+    static LOOPS: RefCell<Vec<EventLoop>> = const { RefCell::new(Vec::new()) };
+}
+
+static NEXT_SCOPE: AtomicU64 = AtomicU64::new(0);
+
+/// The event loop of one execution, closed when dropped
+#[derive(Debug)]
+pub struct Scope {
+    id: u64,
+}
+
+/// Open the event loop of an execution that must finish by `deadline`;
+/// `timed_out` is set when a wait reaches it
+pub fn enter(deadline: Instant, timed_out: Arc<AtomicBool>) -> Scope {
+    let id = NEXT_SCOPE.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::channel();
+    LOOPS.with(|loops| {
+        loops.borrow_mut().push(EventLoop {
+            scope_id: id,
+            deadline,
+            timed_out,
+            next_id: 0,
+            timers: Vec::new(),
+            in_flight: 0,
+            sender,
+            receiver,
+        })
+    });
+    Scope { id }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        LOOPS.with(|loops| {
+            loops
+                .borrow_mut()
+                .retain(|event_loop| event_loop.scope_id != self.id)
+        });
+    }
+}
+
+fn with_current<T>(f: impl FnOnce(&mut EventLoop) -> Result<T, String>) -> Result<T, String> {
+    LOOPS.with(|loops| match loops.borrow_mut().last_mut() {
+        Some(event_loop) => f(event_loop),
+        None => Err("Not available outside a script execution".to_string()),
+    })
+}
+
+/// Run `job` on a thread of its own; its outcome settles the operation with
+/// the returned id
+pub fn spawn(job: impl FnOnce() -> Outcome + Send + 'static) -> Result<u32, String> {
+    with_current(|event_loop| {
+        let id = event_loop.allocate_id()?;
+        let sender = event_loop.sender.clone();
+        std::thread::Builder::new()
+            .name("script-host-operation".to_string())
+            .spawn(move || {
+                // The execution may have ended; its outcome is then dropped
+                let _ = sender.send((id, job()));
+            })
+            .map_err(|e| format!("Failed to start host operation: {}", e))?;
+        event_loop.in_flight += 1;
+        Ok(id)
+    })
+}
+
+/// Start a timer due in `delay`
+pub fn start_timer(delay: Duration) -> Result<u32, String> {
+    with_current(|event_loop| {
+        let id = event_loop.allocate_id()?;
+        event_loop.timers.push((Instant::now() + delay, id));
+        Ok(id)
+    })
+}
+
+/// Cancel a pending timer; unknown ids are ignored
+pub fn cancel_timer(id: u32) {
+    let _ = with_current(|event_loop| {
+        event_loop.timers.retain(|(_, timer)| *timer != id);
+        Ok(())
+    });
+}
+
+/// Wait for the next host operation of the current execution to finish.
+/// None when nothing is pending, or outside an execution; an error when the
+/// execution's deadline passes first.
+pub fn next_wake() -> Result<Option<Wake>, String> {
+    LOOPS.with(|loops| match loops.borrow_mut().last_mut() {
+        Some(event_loop) => event_loop.wait(),
+        None => Ok(None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(timeout: Duration) -> (Scope, Arc<AtomicBool>) {
+        let timed_out = Arc::new(AtomicBool::new(false));
+        (
+            enter(Instant::now() + timeout, timed_out.clone()),
+            timed_out,
+        )
+    }
+
+    #[test]
+    fn test_timers_fire_in_due_order() {
+        let (_scope, _) = scope(Duration::from_secs(5));
+        let late = start_timer(Duration::from_millis(40)).unwrap();
+        let early = start_timer(Duration::from_millis(10)).unwrap();
+        let cancelled = start_timer(Duration::from_millis(20)).unwrap();
+        cancel_timer(cancelled);
+
+        assert_eq!(next_wake(), Ok(Some(Wake::TimerFired { id: early })));
+        assert_eq!(next_wake(), Ok(Some(Wake::TimerFired { id: late })));
+        assert_eq!(next_wake(), Ok(None));
+    }
+
+    #[test]
+    fn test_requests_run_concurrently() {
+        let (_scope, _) = scope(Duration::from_secs(5));
+        let started = Instant::now();
+        let slow = spawn(|| {
+            std::thread::sleep(Duration::from_millis(200));
+            Ok("slow".to_string())
+        })
+        .unwrap();
+        let failing = spawn(|| {
+            std::thread::sleep(Duration::from_millis(200));
+            Err("refused".to_string())
+        })
+        .unwrap();
+
+        let mut wakes = vec![next_wake().unwrap(), next_wake().unwrap()];
+        wakes.sort_by_key(|wake| match wake {
+            Some(Wake::Completed { id, .. }) => *id,
+            _ => 0,
+        });
+        assert_eq!(
+            wakes,
+            vec![
+                Some(Wake::Completed {
+                    id: slow,
+                    outcome: Ok("slow".to_string())
+                }),
+                Some(Wake::Completed {
+                    id: failing,
+                    outcome: Err("refused".to_string())
+                }),
+            ]
+        );
+        assert!(
+            started.elapsed() < Duration::from_millis(390),
+            "requests should not run one after another: {:?}",
+            started.elapsed()
+        );
+        assert_eq!(next_wake(), Ok(None));
+    }
+
+    #[test]
+    fn test_wait_stops_at_deadline() {
+        let (_scope, timed_out) = scope(Duration::from_millis(50));
+        start_timer(Duration::from_secs(60)).unwrap();
+        let started = Instant::now();
+        assert!(next_wake().is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(timed_out.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_pending_operations_are_limited() {
+        let (_scope, _) = scope(Duration::from_secs(5));
+        for _ in 0..MAX_PENDING_OPERATIONS {
+            start_timer(Duration::from_secs(1)).unwrap();
+        }
+        assert!(start_timer(Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_operations_need_an_execution() {
+        assert!(start_timer(Duration::ZERO).is_err());
+        assert_eq!(next_wake(), Ok(None));
+
+        // A nested execution has its own operations
+        let (outer, _) = scope(Duration::from_secs(5));
+        start_timer(Duration::ZERO).unwrap();
+        {
+            let (_inner, _) = scope(Duration::from_secs(5));
+            assert_eq!(next_wake(), Ok(None));
+        }
+        assert!(matches!(next_wake(), Ok(Some(Wake::TimerFired { .. }))));
+        drop(outer);
+    }
+}
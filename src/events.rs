@@ -0,0 +1,475 @@
+//! Events between scripts (`events.publish` / `events.subscribe`).
+//!
+//! A script subscribes a handler to a topic from `init()` with
+//! `events.subscribe(topic, handlerName)`. `events.publish(topic, payload)`
+//! returns at once and runs every subscribed handler on its own blocking
+//! thread, like `db.listen` handlers, with the event in
+//! `context.meta.event`. Subscriptions are dropped when their script is
+//! re-initialized or deleted.
+//!
+//! Events stay on the publishing server instance unless published with
+//! `{ broadcast: true }`, which also sends them over the `script_event`
+//! database notification channel to the subscribers of every other
+//! instance. Notifications carry at most 8000 bytes, which limits the
+//! payload of broadcast events.
+//!
+//! A handler that throws does not affect the publisher or other
+//! subscribers. The event is dead-lettered instead: logged as an error to
+//! the server log and the subscriber's script log with its topic, ID,
+//! publisher and payload.
+//!
+//! Handlers may publish events themselves. Each event carries its depth in
+//! such a chain, and publishing beyond `MAX_EVENT_DEPTH` fails so that
+//! scripts reacting to each other cannot loop forever.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::repository;
+
+/// Database notification channel carrying broadcast events
+pub const EVENT_CHANNEL: &str = "script_event";
+
+/// Longest topic name
+pub const MAX_TOPIC_LENGTH: usize = 128;
+
+/// Largest serialized payload of an event
+pub const MAX_EVENT_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Largest serialized broadcast event; Postgres rejects longer notifications
+pub const MAX_BROADCAST_EVENT_BYTES: usize = 7999;
+
+/// Topics one script may subscribe to
+pub const MAX_TOPICS_PER_SCRIPT: usize = 50;
+
+/// Longest chain of handlers publishing events for each other
+pub const MAX_EVENT_DEPTH: u32 = 8;
+
+/// Longest payload excerpt written to the log of a dead-lettered event
+const DEAD_LETTER_PAYLOAD_PREVIEW: usize = 1024;
+
+/// Script handler subscribed to a topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSubscription {
+    pub script_uri: String,
+    pub handler_name: String,
+}
+
+/// An event as delivered to handlers, and as broadcast to other instances
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub id: String,
+    pub topic: String,
+    pub payload: Value,
+    /// URI of the publishing script
+    pub source: String,
+    pub published_at: String,
+    /// 1 for events published outside event handlers, one more for each
+    /// handler in a chain
+    pub depth: u32,
+}
+
+/// Options of `events.publish`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct PublishOptions {
+    /// Also deliver the event to subscribers on other server instances
+    pub broadcast: bool,
+}
+
+/// Result of `events.publish`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishResult {
+    pub id: String,
+    pub topic: String,
+    /// Handlers the event was dispatched to on this instance
+    pub subscribers: usize,
+    pub broadcast: bool,
+}
+
+/// Broadcast event as sent over `EVENT_CHANNEL`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventMessage {
+    pub event: Event,
+    pub server_id: String,
+}
+
+/// Subscriptions registered with events.subscribe, by topic
+static SUBSCRIPTIONS: OnceLock<Mutex<HashMap<String, Vec<EventSubscription>>>> = OnceLock::new();
+
+thread_local! {
+    /// Depth of the event whose handler runs on this thread, 0 outside
+    /// event handlers
+    static CURRENT_DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+fn lock_subscriptions() -> std::sync::MutexGuard<'static, HashMap<String, Vec<EventSubscription>>> {
+    match SUBSCRIPTIONS.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Event subscription registry mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Marks the current thread as running a handler of an event with `depth`
+/// until dropped
+pub struct DepthGuard {
+    previous: u32,
+}
+
+/// Enter the handler of an event with `depth` on this thread
+pub fn enter_handler(depth: u32) -> DepthGuard {
+    DepthGuard {
+        previous: CURRENT_DEPTH.with(|current| current.replace(depth)),
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        CURRENT_DEPTH.with(|current| current.set(self.previous));
+    }
+}
+
+/// Check that `topic` is made of dot-separated segments of lowercase
+/// letters, digits, underscores and hyphens, such as `orders.created`
+pub fn validate_topic(topic: &str) -> Result<(), String> {
+    let valid = !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LENGTH
+        && topic.split('.').all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-'))
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid topic '{}': use dot-separated segments of lowercase letters, digits, underscores and hyphens (max {} characters)",
+            topic, MAX_TOPIC_LENGTH
+        ))
+    }
+}
+
+/// Subscribe a script handler to a topic. Returns false when the same
+/// handler was already subscribed.
+pub fn subscribe(script_uri: &str, topic: &str, handler_name: &str) -> Result<bool, String> {
+    validate_topic(topic)?;
+    if handler_name.is_empty() {
+        return Err("Handler name cannot be empty".to_string());
+    }
+
+    let mut subscriptions = lock_subscriptions();
+    let subscription = EventSubscription {
+        script_uri: script_uri.to_string(),
+        handler_name: handler_name.to_string(),
+    };
+    if subscriptions
+        .get(topic)
+        .is_some_and(|topic_subscriptions| topic_subscriptions.contains(&subscription))
+    {
+        return Ok(false);
+    }
+    let script_topic_count = subscriptions
+        .iter()
+        .filter(|(name, topic_subscriptions)| {
+            name.as_str() != topic
+                && topic_subscriptions
+                    .iter()
+                    .any(|s| s.script_uri == script_uri)
+        })
+        .count();
+    if script_topic_count >= MAX_TOPICS_PER_SCRIPT {
+        return Err(format!(
+            "A script may subscribe to at most {} topics",
+            MAX_TOPICS_PER_SCRIPT
+        ));
+    }
+
+    subscriptions
+        .entry(topic.to_string())
+        .or_default()
+        .push(subscription);
+    Ok(true)
+}
+
+/// Remove every subscription of a script, returning how many were removed
+pub fn clear_script_subscriptions(script_uri: &str) -> usize {
+    let mut subscriptions = lock_subscriptions();
+    let mut removed = 0;
+    subscriptions.retain(|_, topic_subscriptions| {
+        let count = topic_subscriptions.len();
+        topic_subscriptions.retain(|s| s.script_uri != script_uri);
+        removed += count - topic_subscriptions.len();
+        !topic_subscriptions.is_empty()
+    });
+    removed
+}
+
+/// Handlers subscribed to `topic`
+pub fn topic_subscriptions(topic: &str) -> Vec<EventSubscription> {
+    lock_subscriptions().get(topic).cloned().unwrap_or_default()
+}
+
+/// Publish an event from `script_uri` and dispatch it to the subscribers
+/// on this instance, and with `broadcast` to those on other instances
+pub fn publish(
+    script_uri: &str,
+    topic: &str,
+    payload: Value,
+    options: &PublishOptions,
+) -> Result<PublishResult, String> {
+    validate_topic(topic)?;
+    let payload_bytes = serde_json::to_vec(&payload)
+        .map_err(|e| format!("Failed to serialize payload: {}", e))?
+        .len();
+    if payload_bytes > MAX_EVENT_PAYLOAD_BYTES {
+        return Err(format!(
+            "Event payload must not exceed {} bytes",
+            MAX_EVENT_PAYLOAD_BYTES
+        ));
+    }
+    let depth = CURRENT_DEPTH.with(Cell::get) + 1;
+    if depth > MAX_EVENT_DEPTH {
+        return Err(format!(
+            "Event '{}' not published: event handlers may publish at most {} events deep",
+            topic, MAX_EVENT_DEPTH
+        ));
+    }
+
+    let event = Event {
+        id: Uuid::new_v4().to_string(),
+        topic: topic.to_string(),
+        payload,
+        source: script_uri.to_string(),
+        published_at: chrono::Utc::now().to_rfc3339(),
+        depth,
+    };
+
+    let broadcast = options.broadcast && broadcast(&event)?;
+    let subscribers = dispatch(&event);
+    debug!(
+        "Published event '{}' ({}) from {} to {} local subscriber(s)",
+        event.topic, event.id, script_uri, subscribers
+    );
+    Ok(PublishResult {
+        id: event.id,
+        topic: event.topic,
+        subscribers,
+        broadcast,
+    })
+}
+
+/// Send `event` to the other server instances. Returns false when there is
+/// no database to carry it.
+fn broadcast(event: &Event) -> Result<bool, String> {
+    let (Some(db), Some(server_id)) = (
+        crate::database::get_global_database(),
+        crate::notifications::get_server_id(),
+    ) else {
+        return Ok(false);
+    };
+    let message = serde_json::to_string(&EventMessage {
+        event: event.clone(),
+        server_id,
+    })
+    .map_err(|e| format!("Failed to serialize event: {}", e))?;
+    if message.len() > MAX_BROADCAST_EVENT_BYTES {
+        return Err(format!(
+            "Broadcast events must not exceed {} bytes including their payload",
+            MAX_BROADCAST_EVENT_BYTES
+        ));
+    }
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return Ok(false);
+    };
+
+    let topic = event.topic.clone();
+    handle.spawn(async move {
+        if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(EVENT_CHANNEL)
+            .bind(&message)
+            .execute(db.pool())
+            .await
+        {
+            error!("Failed to broadcast event '{}': {}", topic, e);
+        }
+    });
+    Ok(true)
+}
+
+/// Run the handlers subscribed to the topic of `event` on this instance,
+/// returning how many there are
+pub fn dispatch(event: &Event) -> usize {
+    let subscriptions = topic_subscriptions(&event.topic);
+    let count = subscriptions.len();
+    let handle = tokio::runtime::Handle::try_current().ok();
+
+    for subscription in subscriptions {
+        let event = event.clone();
+        match &handle {
+            Some(handle) => {
+                handle.spawn(async move {
+                    let handler = subscription.clone();
+                    let handler_event = event.clone();
+                    let execution =
+                        tokio::task::spawn_blocking(move || run_handler(&handler, &handler_event))
+                            .await;
+                    let failure = match execution {
+                        Ok(result) => result.err(),
+                        Err(join_err) => Some(format!("handler panicked: {}", join_err)),
+                    };
+                    if let Some(err) = failure {
+                        dead_letter(&subscription, &event, &err);
+                    }
+                });
+            }
+            // Outside the server runtime (tools and tests) handlers run
+            // in place
+            None => {
+                if let Err(err) = run_handler(&subscription, &event) {
+                    dead_letter(&subscription, &event, &err);
+                }
+            }
+        }
+    }
+    count
+}
+
+fn run_handler(subscription: &EventSubscription, event: &Event) -> Result<(), String> {
+    let _depth = enter_handler(event.depth);
+    crate::js_engine::execute_event_handler(
+        &subscription.script_uri,
+        &subscription.handler_name,
+        event,
+    )
+}
+
+/// Record an event a handler failed to process
+fn dead_letter(subscription: &EventSubscription, event: &Event, err: &str) {
+    let payload = event.payload.to_string();
+    let preview = match payload.char_indices().nth(DEAD_LETTER_PAYLOAD_PREVIEW) {
+        Some((end, _)) => format!("{}…", &payload[..end]),
+        None => payload,
+    };
+    warn!(
+        script = %subscription.script_uri,
+        handler = %subscription.handler_name,
+        topic = %event.topic,
+        event_id = %event.id,
+        source = %event.source,
+        error = %err,
+        "Event handler failed; event dead-lettered"
+    );
+    if repository::get_repository_opt().is_some() {
+        repository::insert_log_message(
+            &subscription.script_uri,
+            &format!(
+                "event handler '{}' failed for '{}' (id {}, from {}): {}; payload: {}",
+                subscription.handler_name, event.topic, event.id, event.source, err, preview
+            ),
+            "ERROR",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_topic() {
+        assert!(validate_topic("orders.created").is_ok());
+        assert!(validate_topic("user-profile.updated_v2").is_ok());
+        assert!(validate_topic("single").is_ok());
+        assert!(validate_topic("").is_err());
+        assert!(validate_topic("Orders.created").is_err());
+        assert!(validate_topic("orders..created").is_err());
+        assert!(validate_topic(".orders").is_err());
+        assert!(validate_topic("orders.*").is_err());
+        assert!(validate_topic(&"a".repeat(MAX_TOPIC_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_subscription_registry() {
+        let uri = "https://example.com/events-registry-test";
+        let other = "https://example.com/events-registry-other";
+
+        assert_eq!(subscribe(uri, "registry-test.a", "onA"), Ok(true));
+        assert_eq!(subscribe(uri, "registry-test.a", "onA"), Ok(false));
+        assert_eq!(subscribe(other, "registry-test.a", "onA"), Ok(true));
+        assert!(subscribe(uri, "registry-test.a", "").is_err());
+        assert_eq!(topic_subscriptions("registry-test.a").len(), 2);
+
+        assert_eq!(clear_script_subscriptions(uri), 1);
+        assert_eq!(
+            topic_subscriptions("registry-test.a"),
+            vec![EventSubscription {
+                script_uri: other.to_string(),
+                handler_name: "onA".to_string(),
+            }]
+        );
+        assert_eq!(clear_script_subscriptions(other), 1);
+        assert!(topic_subscriptions("registry-test.a").is_empty());
+
+        for i in 0..MAX_TOPICS_PER_SCRIPT {
+            subscribe(uri, &format!("registry-test.t{}", i), "on").unwrap();
+        }
+        assert!(subscribe(uri, "registry-test.extra", "on").is_err());
+        assert_eq!(clear_script_subscriptions(uri), MAX_TOPICS_PER_SCRIPT);
+    }
+
+    #[test]
+    fn test_publish_limits() {
+        let uri = "https://example.com/events-publish-test";
+        let options = PublishOptions::default();
+
+        let result = publish(uri, "publish-test.none", json!({ "n": 1 }), &options).unwrap();
+        assert_eq!(result.subscribers, 0);
+        assert!(!result.broadcast);
+        assert!(Uuid::parse_str(&result.id).is_ok());
+
+        let large = json!("x".repeat(MAX_EVENT_PAYLOAD_BYTES));
+        assert!(publish(uri, "publish-test.none", large, &options).is_err());
+        assert!(publish(uri, "Bad Topic", Value::Null, &options).is_err());
+
+        let _depth = enter_handler(MAX_EVENT_DEPTH - 1);
+        assert!(publish(uri, "publish-test.none", Value::Null, &options).is_ok());
+        {
+            let _inner = enter_handler(MAX_EVENT_DEPTH);
+            assert!(publish(uri, "publish-test.none", Value::Null, &options).is_err());
+        }
+        assert!(publish(uri, "publish-test.none", Value::Null, &options).is_ok());
+    }
+
+    #[test]
+    fn test_event_message_serialization() {
+        let message = EventMessage {
+            event: Event {
+                id: "e1".to_string(),
+                topic: "orders.created".to_string(),
+                payload: json!({ "orderId": 42 }),
+                source: "https://example.com/orders".to_string(),
+                published_at: "2026-01-01T00:00:00+00:00".to_string(),
+                depth: 1,
+            },
+            server_id: "server-a".to_string(),
+        };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["event"]["publishedAt"], "2026-01-01T00:00:00+00:00");
+        let parsed: EventMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.event, message.event);
+        assert_eq!(parsed.server_id, "server-a");
+    }
+}
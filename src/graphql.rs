@@ -12,6 +12,7 @@ static SDL_FIELD_REGEX: OnceLock<regex::Regex> = OnceLock::new();
 /// Static regex for detecting the `type` keyword in SDL fragments
 static SDL_HAS_TYPE_REGEX: OnceLock<regex::Regex> = OnceLock::new();
 
+use crate::graphql_errors::ResolverError;
 use crate::js_engine::{GraphqlOperationKind, GraphqlResolverExecutionParams};
 
 /// Visibility level for GraphQL operations
@@ -570,13 +571,20 @@ fn extract_arguments(sdl: &str, field_name: &str) -> Vec<(String, TypeRef)> {
                     "Float!" => TypeRef::named_nn(TypeRef::FLOAT),
                     "Float" => TypeRef::named(TypeRef::FLOAT),
                     s if s.starts_with('[') && s.contains(']') => {
-                        // Handle array types like [String!]! or [String]
+                        // Handle array types like [String!]! or [String]. Unlike
+                        // return types, nullability matters here: a nullable
+                        // list argument may be omitted by the caller.
                         let inner_type = s.trim_matches(|c| c == '[' || c == ']' || c == '!');
                         debug!(
                             "Detected array argument type with inner type: '{}'",
                             inner_type
                         );
-                        TypeRef::named_nn_list_nn(inner_type)
+                        match (s.contains("!]"), s.ends_with("]!")) {
+                            (true, true) => TypeRef::named_nn_list_nn(inner_type),
+                            (true, false) => TypeRef::named_nn_list(inner_type),
+                            (false, true) => TypeRef::named_list_nn(inner_type),
+                            (false, false) => TypeRef::named_list(inner_type),
+                        }
                     }
                     _ => {
                         // Check if it's a custom type
@@ -707,13 +715,15 @@ pub fn build_schema_with_context(
     let has_queries = !queries.is_empty();
     let has_mutations = !mutations.is_empty();
     let has_subscriptions = !subscriptions.is_empty();
+    // The built-in uploadAsset mutation is only offered to HTTP clients
+    let has_upload = context == SchemaContext::External;
 
     // Drop the guard so we don't have borrowing issues
     drop(registry_guard);
 
     let mut builder = Schema::build(
         "Query",
-        if has_mutations {
+        if has_mutations || has_upload {
             Some("Mutation")
         } else {
             None
@@ -837,10 +847,7 @@ pub fn build_schema_with_context(
                             Ok(Some(async_graphql::Value::String(result)))
                         }
                     }
-                    Err(e) => {
-                        error!("GraphQL resolver error for {}::{}: {}", uri, func, e);
-                        Ok(Some(async_graphql::Value::String(format!("Error: {}", e))))
-                    }
+                    Err(e) => Err(e.into_graphql_error(&uri, &func)),
                 }
             })
         });
@@ -937,10 +944,7 @@ pub fn build_schema_with_context(
                                 Ok(Some(async_graphql::Value::String(result)))
                             }
                         }
-                        Err(e) => {
-                            error!("GraphQL resolver error for {}::{}: {}", uri, func, e);
-                            Ok(Some(async_graphql::Value::String(format!("Error: {}", e))))
-                        }
+                        Err(e) => Err(e.into_graphql_error(&uri, &func)),
                     }
                 })
             });
@@ -952,7 +956,15 @@ pub fn build_schema_with_context(
 
             mutation_builder = mutation_builder.field(mutation_field);
         }
-    } else {
+    }
+
+    if has_upload {
+        mutation_builder = mutation_builder.field(crate::asset_upload::graphql_field());
+        for (_, upload_type) in parse_types_from_sdl(crate::asset_upload::GRAPHQL_SDL) {
+            builder = builder.register(upload_type);
+        }
+        builder = builder.enable_uploading();
+    } else if !has_mutations {
         // Add a placeholder mutation if no mutations are registered
         mutation_builder = mutation_builder.field(Field::new(
             "placeholder",
@@ -1028,14 +1040,11 @@ pub fn build_schema_with_context(
                             ) {
                                 Ok(criteria) => criteria,
                                 Err(e) => {
-                                    error!(
+                                    return Err(ResolverError::from(format!(
                                         "Subscription '{}' customization failed: {}",
                                         subscription_name, e
-                                    );
-                                    return Err(async_graphql::Error::new(format!(
-                                        "Subscription setup failed: {}",
-                                        e
-                                    )));
+                                    ))
+                                    .into_graphql_error(&uri, &func));
                                 }
                             };
 
@@ -1051,14 +1060,11 @@ pub fn build_schema_with_context(
                         {
                             Ok(connection) => connection,
                             Err(e) => {
-                                error!(
+                                return Err(ResolverError::from(format!(
                                     "Failed to create connection for subscription '{}': {}",
                                     subscription_name, e
-                                );
-                                return Err(async_graphql::Error::new(format!(
-                                    "Subscription connection failed: {}",
-                                    e
-                                )));
+                                ))
+                                .into_graphql_error(&uri, &func));
                             }
                         };
 
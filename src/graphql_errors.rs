@@ -0,0 +1,199 @@
+//! Errors of GraphQL resolvers as clients see them.
+//!
+//! Resolvers report expected failures by throwing a `GraphQLError`:
+//!
+//! ```javascript
+//! throw new GraphQLError("Order not found", { code: "NOT_FOUND", orderId: id });
+//! ```
+//!
+//! Its message and extensions reach the client as they are. The `code`
+//! extension has to be SCREAMING_SNAKE_CASE; errors without a valid code get
+//! `BAD_USER_INPUT`.
+//!
+//! Every other failure (an uncaught exception, a timeout, a missing resolver
+//! function) is masked: the client sees "Internal server error" with the
+//! extensions `{ code: "INTERNAL_SERVER_ERROR", errorId }`, and the full
+//! error is logged with the same `errorId`, so a report from a client can be
+//! matched with the log. With `javascript.expose_error_details` the original
+//! message is sent instead of the generic one.
+
+use async_graphql::ErrorExtensions;
+use serde_json::{Map, Value};
+use tracing::error;
+
+use crate::script_errors::{self, JsError};
+
+/// Name of the JavaScript error class whose errors reach clients
+pub const CLIENT_ERROR_NAME: &str = "GraphQLError";
+
+/// Code of client errors thrown without a valid one
+pub const DEFAULT_CLIENT_CODE: &str = "BAD_USER_INPUT";
+
+/// Code of masked errors
+pub const INTERNAL_ERROR_CODE: &str = "INTERNAL_SERVER_ERROR";
+
+/// Message of masked errors
+const INTERNAL_ERROR_MESSAGE: &str = "Internal server error";
+
+/// Longest client error message and code
+const MAX_MESSAGE_LENGTH: usize = 1000;
+const MAX_CODE_LENGTH: usize = 64;
+
+/// Why a resolver failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolverError {
+    /// Thrown by the script as a `GraphQLError`; safe to show to clients
+    Client {
+        message: String,
+        extensions: Map<String, Value>,
+    },
+    /// Anything else; the details stay in the logs
+    Internal(String),
+}
+
+impl ResolverError {
+    /// Take the pending exception off the context
+    pub fn capture(ctx: &rquickjs::Ctx<'_>, error: &rquickjs::Error) -> Self {
+        let exception = ctx.catch();
+        if let Some(object) = exception.as_object()
+            && object.get::<_, String>("name").ok().as_deref() == Some(CLIENT_ERROR_NAME)
+        {
+            let message = object.get::<_, String>("message").unwrap_or_default();
+            let extensions = object
+                .get::<_, rquickjs::Value>("extensions")
+                .ok()
+                .and_then(|value| ctx.json_stringify(value).ok().flatten())
+                .and_then(|json| json.to_string().ok())
+                .and_then(|json| serde_json::from_str(&json).ok());
+            return Self::client(message, extensions);
+        }
+
+        // Put the exception back for JsError to describe it
+        if matches!(error, rquickjs::Error::Exception) {
+            let _ = ctx.throw(exception);
+        }
+        Self::Internal(format!(
+            "JavaScript execution error: {}",
+            JsError::capture(ctx, error)
+        ))
+    }
+
+    /// An error for the client, with a valid `code` extension
+    pub fn client(message: impl Into<String>, extensions: Option<Value>) -> Self {
+        let mut extensions = match extensions {
+            Some(Value::Object(extensions)) => extensions,
+            _ => Map::new(),
+        };
+        if !extensions
+            .get("code")
+            .and_then(Value::as_str)
+            .is_some_and(is_valid_code)
+        {
+            extensions.insert("code".to_string(), DEFAULT_CLIENT_CODE.into());
+        }
+        Self::Client {
+            message: message.into().chars().take(MAX_MESSAGE_LENGTH).collect(),
+            extensions,
+        }
+    }
+
+    /// The error the client gets for a failed resolver of a script. Masked
+    /// errors are logged here.
+    pub fn into_graphql_error(self, script_uri: &str, resolver: &str) -> async_graphql::Error {
+        match self {
+            ResolverError::Client {
+                message,
+                extensions,
+            } => async_graphql::Error::new(message).extend_with(|_, values| {
+                for (name, value) in extensions {
+                    if let Ok(value) = async_graphql::Value::from_json(value) {
+                        values.set(name, value);
+                    }
+                }
+            }),
+            ResolverError::Internal(details) => {
+                let error_id = uuid::Uuid::new_v4().to_string();
+                error!(
+                    "GraphQL resolver error {} in {}::{}: {}",
+                    error_id, script_uri, resolver, details
+                );
+                let message = if script_errors::error_details_exposed() {
+                    details
+                } else {
+                    INTERNAL_ERROR_MESSAGE.to_string()
+                };
+                async_graphql::Error::new(message).extend_with(|_, values| {
+                    values.set("code", INTERNAL_ERROR_CODE);
+                    values.set("errorId", error_id);
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolverError::Client { message, .. } => write!(f, "{}", message),
+            ResolverError::Internal(details) => write!(f, "{}", details),
+        }
+    }
+}
+
+impl From<String> for ResolverError {
+    fn from(details: String) -> Self {
+        ResolverError::Internal(details)
+    }
+}
+
+fn is_valid_code(code: &str) -> bool {
+    !code.is_empty()
+        && code.len() <= MAX_CODE_LENGTH
+        && code != INTERNAL_ERROR_CODE
+        && code
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_client_error_codes() {
+        let error = ResolverError::client("Order not found", Some(json!({ "code": "NOT_FOUND" })));
+        assert_eq!(
+            error,
+            ResolverError::Client {
+                message: "Order not found".to_string(),
+                extensions: json!({ "code": "NOT_FOUND" }).as_object().cloned().unwrap(),
+            }
+        );
+
+        // Invalid or reserved codes are replaced
+        for code in [json!("not-found"), json!(INTERNAL_ERROR_CODE), json!(42)] {
+            let ResolverError::Client { extensions, .. } =
+                ResolverError::client("Nope", Some(json!({ "code": code, "field": "id" })))
+            else {
+                unreachable!();
+            };
+            assert_eq!(extensions["code"], DEFAULT_CLIENT_CODE);
+            assert_eq!(extensions["field"], "id");
+        }
+    }
+
+    #[test]
+    fn test_internal_errors_are_masked() {
+        let error = ResolverError::Internal("database password rejected".to_string())
+            .into_graphql_error("https://example.com/orders", "ordersResolver");
+        assert_eq!(error.message, INTERNAL_ERROR_MESSAGE);
+
+        let extensions = error.extensions.unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from(INTERNAL_ERROR_CODE))
+        );
+        assert!(extensions.get("errorId").is_some());
+    }
+}
@@ -0,0 +1,232 @@
+//! Persisted queries of `/graphql` requests.
+//!
+//! A client may send a request, or an operation of a batch, with the
+//! `persistedQuery` extension instead of its query document:
+//!
+//! ```json
+//! { "extensions": { "persistedQuery": { "version": 1, "sha256Hash": "…" } } }
+//! ```
+//!
+//! The hash is the hex SHA-256 of the document. A request carrying both the
+//! document and its hash stores the document under the hash; later requests
+//! send only the hash and run the stored document. An unknown hash is
+//! answered with a `PersistedQueryNotFound` error (code
+//! `PERSISTED_QUERY_NOT_FOUND`), after which the client sends the document
+//! again. Each operation of a batch is resolved on its own, so one miss does
+//! not fail the rest of the batch.
+//!
+//! Documents are kept in memory, up to `server.graphql.max_persisted_queries`
+//! of them with the least recently used dropped first; 0 turns persisted
+//! queries off and answers every hash with `PersistedQueryNotSupported`.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use async_graphql::ServerError;
+use lru::LruCache;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::config::GraphQLHttpConfig;
+
+/// Name of the request extension naming a persisted query
+pub const EXTENSION_NAME: &str = "persistedQuery";
+
+/// Version of the persisted query extension understood
+const SUPPORTED_VERSION: u32 = 1;
+
+/// Longest query document stored
+const MAX_QUERY_LENGTH: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedQuery {
+    version: u32,
+    sha256_hash: String,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static QUERIES: OnceLock<Mutex<LruCache<String, String>>> = OnceLock::new();
+
+fn capacity(max_queries: usize) -> NonZeroUsize {
+    NonZeroUsize::new(max_queries).unwrap_or(NonZeroUsize::MIN)
+}
+
+fn lock_queries() -> MutexGuard<'static, LruCache<String, String>> {
+    let queries = QUERIES.get_or_init(|| {
+        Mutex::new(LruCache::new(capacity(
+            GraphQLHttpConfig::default().max_persisted_queries,
+        )))
+    });
+    match queries.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Persisted query store mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Apply the `/graphql` configuration. Called once at server startup.
+pub fn configure(config: &GraphQLHttpConfig) {
+    ENABLED.store(config.max_persisted_queries > 0, Ordering::Relaxed);
+    lock_queries().resize(capacity(config.max_persisted_queries));
+}
+
+/// Hex SHA-256 of a query document, the hash clients name it by
+pub fn query_hash(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
+
+/// Store a query document, returning the hash it is stored under
+pub fn store(query: &str) -> String {
+    let hash = query_hash(query);
+    lock_queries().put(hash.clone(), query.to_string());
+    hash
+}
+
+fn error(message: &str, code: &str) -> ServerError {
+    let mut error = ServerError::new(message, None);
+    let mut values = async_graphql::ErrorExtensionValues::default();
+    values.set("code", code);
+    error.extensions = Some(values);
+    error
+}
+
+/// Resolve the persisted query a request names: fill in its stored
+/// document, or store the document it carries. Requests without the
+/// extension are left as they are.
+pub fn resolve(request: &mut async_graphql::Request) -> Result<(), ServerError> {
+    let Some(value) = request.extensions.remove(EXTENSION_NAME) else {
+        return Ok(());
+    };
+    if !ENABLED.load(Ordering::Relaxed) {
+        return Err(error(
+            "PersistedQueryNotSupported",
+            "PERSISTED_QUERY_NOT_SUPPORTED",
+        ));
+    }
+    let persisted: PersistedQuery = async_graphql::from_value(value).map_err(|_| {
+        error(
+            "Invalid persistedQuery extension: expected version and sha256Hash",
+            "BAD_REQUEST",
+        )
+    })?;
+    if persisted.version != SUPPORTED_VERSION {
+        return Err(error(
+            &format!(
+                "Persisted query version {} is not supported, use {}",
+                persisted.version, SUPPORTED_VERSION
+            ),
+            "BAD_REQUEST",
+        ));
+    }
+    let hash = persisted.sha256_hash.to_ascii_lowercase();
+
+    if request.query.is_empty() {
+        return match lock_queries().get(&hash) {
+            Some(query) => {
+                request.query = query.clone();
+                Ok(())
+            }
+            None => Err(error("PersistedQueryNotFound", "PERSISTED_QUERY_NOT_FOUND")),
+        };
+    }
+    if query_hash(&request.query) != hash {
+        return Err(error("provided sha does not match query", "BAD_REQUEST"));
+    }
+    // Longer documents still run; they are only not kept
+    if request.query.len() <= MAX_QUERY_LENGTH {
+        lock_queries().put(hash, request.query.clone());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: serde_json::Value) -> async_graphql::Request {
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn persisted(hash: &str) -> serde_json::Value {
+        serde_json::json!({ "persistedQuery": { "version": 1, "sha256Hash": hash } })
+    }
+
+    fn code(error: &ServerError) -> Option<&async_graphql::Value> {
+        error
+            .extensions
+            .as_ref()
+            .and_then(|values| values.get("code"))
+    }
+
+    #[test]
+    fn test_query_hash() {
+        assert_eq!(query_hash("{ a }"), hex::encode(Sha256::digest(b"{ a }")));
+        assert_eq!(query_hash("{ a }").len(), 64);
+    }
+
+    #[test]
+    fn test_stored_query_is_resolved() {
+        let query = "{ persistedStoredTest }";
+        let hash = store(query);
+        let mut hit = request(serde_json::json!({ "extensions": persisted(&hash) }));
+        resolve(&mut hit).expect("A stored hash should resolve");
+        assert_eq!(hit.query, query);
+        assert!(!hit.extensions.contains_key(EXTENSION_NAME));
+    }
+
+    #[test]
+    fn test_query_sent_with_its_hash_is_stored() {
+        let query = "{ persistedRegisterTest }";
+        let hash = query_hash(query);
+        let mut first = request(serde_json::json!({
+            "query": query,
+            "extensions": persisted(&hash),
+        }));
+        resolve(&mut first).expect("A matching hash should be accepted");
+
+        let mut later = request(serde_json::json!({ "extensions": persisted(&hash) }));
+        resolve(&mut later).expect("The document should have been stored");
+        assert_eq!(later.query, query);
+    }
+
+    #[test]
+    fn test_unknown_hash_is_not_found() {
+        let mut miss = request(serde_json::json!({
+            "extensions": persisted(&query_hash("{ persistedMissTest }")),
+        }));
+        let error = resolve(&mut miss).expect_err("An unknown hash should fail");
+        assert_eq!(error.message, "PersistedQueryNotFound");
+        assert_eq!(
+            code(&error),
+            Some(&async_graphql::Value::from("PERSISTED_QUERY_NOT_FOUND"))
+        );
+    }
+
+    #[test]
+    fn test_mismatched_and_invalid_extensions_are_rejected() {
+        let mut mismatch = request(serde_json::json!({
+            "query": "{ a }",
+            "extensions": persisted(&query_hash("{ b }")),
+        }));
+        assert!(resolve(&mut mismatch).is_err());
+
+        let mut old = request(serde_json::json!({
+            "extensions": { "persistedQuery": { "version": 2, "sha256Hash": "00" } },
+        }));
+        assert!(resolve(&mut old).is_err());
+
+        let mut invalid = request(serde_json::json!({
+            "extensions": { "persistedQuery": "abc" },
+        }));
+        assert!(resolve(&mut invalid).is_err());
+
+        let mut plain = request(serde_json::json!({ "query": "{ a }" }));
+        assert!(resolve(&mut plain).is_ok());
+        assert_eq!(plain.query, "{ a }");
+    }
+}
@@ -0,0 +1,529 @@
+//! GraphQL over Server-Sent Events Protocol Implementation
+//!
+//! Implements the graphql-sse protocol on `/graphql/sse`, so standard client
+//! libraries work without adapters.
+//! Protocol specification: https://github.com/enisdenjo/graphql-sse/blob/master/PROTOCOL.md
+//!
+//! Distinct connections mode: every GET (`query`, `variables`,
+//! `operationName` and `extensions` in the query string) or POST (JSON body)
+//! without a stream token opens its own event stream. Each result is sent
+//! as a `next` event carrying the execution result, followed by one
+//! `complete` event when the operation ends. Queries and mutations produce a
+//! single `next` event.
+//!
+//! Single connection mode:
+//! - `PUT` reserves a stream and answers 201 with its token as plain text
+//! - `GET` with the token in the `x-graphql-event-stream-token` header (or
+//!   the `token` query parameter) opens the stream; a second attempt
+//!   answers 409
+//! - `POST` with the token and `extensions.operationId` in the body starts an
+//!   operation and answers 202; its results arrive on the stream as `next`
+//!   events of `{ "id", "payload" }` and end with a `complete` event of
+//!   `{ "id" }`
+//! - `DELETE` with the token and an `operationId` query parameter stops an
+//!   operation
+//!
+//! A reservation belongs to the user that made it. Closing the stream drops
+//! the reservation and stops its operations; reservations whose stream is
+//! never opened expire after a minute.
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Method, Request, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::auth::{AuthUser, JsAuthContext};
+
+/// Header carrying the token of a reserved stream
+pub const TOKEN_HEADER: &str = "x-graphql-event-stream-token";
+
+/// Most reserved streams at once
+const MAX_RESERVATIONS: usize = 10_000;
+
+/// How long a reservation waits for its stream to be opened
+const RESERVATION_TTL: Duration = Duration::from_secs(60);
+
+/// Events buffered for a single connection stream; operations wait while
+/// the buffer is full
+const STREAM_BUFFER_EVENTS: usize = 256;
+
+/// A stream reserved for single connection mode
+struct Reservation {
+    user_id: Option<String>,
+    reserved_at: Instant,
+    sender: mpsc::Sender<Event>,
+    /// Taken when the stream is opened
+    receiver: Option<mpsc::Receiver<Event>>,
+    operations: HashMap<String, JoinHandle<()>>,
+}
+
+static RESERVATIONS: OnceLock<Mutex<HashMap<String, Reservation>>> = OnceLock::new();
+
+fn lock_reservations() -> MutexGuard<'static, HashMap<String, Reservation>> {
+    RESERVATIONS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// GraphQL request parameters of the protocol
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestParams {
+    query: String,
+    #[serde(default)]
+    variables: Option<Value>,
+    #[serde(default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    extensions: Option<Value>,
+}
+
+impl RequestParams {
+    /// Parse the parameters of a GET request
+    fn from_query_string(query_string: &str) -> Result<Self, String> {
+        let mut query = None;
+        let mut variables = None;
+        let mut operation_name = None;
+        let mut extensions = None;
+
+        for (key, value) in url::form_urlencoded::parse(query_string.as_bytes()) {
+            match key.as_ref() {
+                "query" => query = Some(value.into_owned()),
+                "variables" if !value.is_empty() => {
+                    variables = Some(
+                        serde_json::from_str(&value)
+                            .map_err(|e| format!("Invalid variables: {}", e))?,
+                    );
+                }
+                "operationName" if !value.is_empty() => operation_name = Some(value.into_owned()),
+                "extensions" if !value.is_empty() => {
+                    extensions = Some(
+                        serde_json::from_str(&value)
+                            .map_err(|e| format!("Invalid extensions: {}", e))?,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            query: query.ok_or("Missing query parameter")?,
+            variables,
+            operation_name,
+            extensions,
+        })
+    }
+
+    /// Parse the JSON body of a POST request
+    fn from_body(body: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(body).map_err(|e| format!("Invalid JSON: {}", e))
+    }
+
+    /// The operation id of a single connection mode request
+    fn operation_id(&self) -> Option<String> {
+        self.extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("operationId"))
+            .and_then(Value::as_str)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string)
+    }
+
+    fn into_graphql_request(self) -> async_graphql::Request {
+        let mut request = async_graphql::Request::new(self.query);
+        if let Some(variables) = self.variables {
+            request = request.variables(async_graphql::Variables::from_json(variables));
+        }
+        if let Some(operation_name) = self.operation_name {
+            request = request.operation_name(operation_name);
+        }
+        request
+    }
+}
+
+fn plain_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        message.into(),
+    )
+        .into_response()
+}
+
+fn stream_token(headers: &HeaderMap, query_string: Option<&str>) -> Option<String> {
+    headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| {
+            url::form_urlencoded::parse(query_string.unwrap_or("").as_bytes())
+                .find(|(key, _)| key == "token")
+                .map(|(_, value)| value.into_owned())
+        })
+        .filter(|token| !token.is_empty())
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"))
+}
+
+fn js_auth_context(auth_user: Option<&AuthUser>) -> JsAuthContext {
+    match auth_user {
+        Some(user) => JsAuthContext::authenticated(
+            user.user_id.clone(),
+            user.email.clone(),
+            user.name.clone(),
+            user.provider.clone(),
+            user.is_admin,
+            user.is_editor,
+        ),
+        None => JsAuthContext::anonymous(),
+    }
+}
+
+fn event(name: &str, data: &Value) -> Event {
+    Event::default().event(name).data(data.to_string())
+}
+
+/// Handle a request to `/graphql/sse` in either mode
+pub async fn handle(req: Request<Body>, max_request_body: usize) -> Response {
+    // Extract authentication context before consuming the request
+    let auth_user = req.extensions().get::<AuthUser>().cloned();
+    let user_id = auth_user.as_ref().map(|user| user.user_id.clone());
+
+    let (parts, body) = req.into_parts();
+    let query_string = parts.uri.query();
+    let token = stream_token(&parts.headers, query_string);
+    debug!(
+        "GraphQL SSE {} request for URI: {}",
+        parts.method, parts.uri
+    );
+
+    match (&parts.method, token) {
+        (&Method::PUT, _) => match reserve(user_id) {
+            Ok(token) => plain_response(StatusCode::CREATED, token),
+            Err(message) => plain_response(StatusCode::SERVICE_UNAVAILABLE, message),
+        },
+        (&Method::DELETE, Some(token)) => {
+            let operation_id = url::form_urlencoded::parse(query_string.unwrap_or("").as_bytes())
+                .find(|(key, _)| key == "operationId")
+                .map(|(_, value)| value.into_owned());
+            let Some(operation_id) = operation_id else {
+                return plain_response(StatusCode::BAD_REQUEST, "Missing operationId parameter");
+            };
+            match stop_operation(&token, user_id.as_deref(), &operation_id) {
+                Ok(()) => plain_response(StatusCode::OK, ""),
+                Err((status, message)) => plain_response(status, message),
+            }
+        }
+        (&Method::DELETE, None) => plain_response(StatusCode::BAD_REQUEST, "Missing stream token"),
+        (&Method::GET, Some(token)) => open_stream(token, user_id.as_deref()),
+        (&Method::POST, Some(token)) if accepts_event_stream(&parts.headers) => {
+            open_stream(token, user_id.as_deref())
+        }
+        (&Method::GET, None) => {
+            match RequestParams::from_query_string(query_string.unwrap_or("")) {
+                Ok(params) => distinct_stream(params, auth_user.as_ref()),
+                Err(message) => plain_response(StatusCode::BAD_REQUEST, message),
+            }
+        }
+        (&Method::POST, token) => {
+            let body_bytes = match axum::body::to_bytes(body, max_request_body).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("GraphQL SSE: Failed to read request body: {}", e);
+                    return plain_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "Request body too large or unreadable",
+                    );
+                }
+            };
+            let params = match RequestParams::from_body(&body_bytes) {
+                Ok(params) => params,
+                Err(message) => return plain_response(StatusCode::BAD_REQUEST, message),
+            };
+            match token {
+                Some(token) => match start_operation(&token, auth_user.as_ref(), params) {
+                    Ok(()) => plain_response(StatusCode::ACCEPTED, ""),
+                    Err((status, message)) => plain_response(status, message),
+                },
+                None => distinct_stream(params, auth_user.as_ref()),
+            }
+        }
+        _ => plain_response(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
+
+/// Run one operation on its own event stream (distinct connections mode)
+fn distinct_stream(params: RequestParams, auth_user: Option<&AuthUser>) -> Response {
+    let schema = match crate::graphql::get_schema() {
+        Ok(schema) => schema,
+        Err(e) => {
+            error!("GraphQL SSE: Failed to get schema: {:?}", e);
+            return plain_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Schema error: {:?}", e),
+            );
+        }
+    };
+    let request = params
+        .into_graphql_request()
+        .data(js_auth_context(auth_user));
+
+    let events = async_stream::stream! {
+        let mut results = Box::pin(schema.execute_stream(request));
+        while let Some(response) = results.next().await {
+            match serde_json::to_value(&response) {
+                Ok(payload) => yield Ok::<Event, Infallible>(event("next", &payload)),
+                Err(e) => error!("Failed to serialize response: {}", e),
+            }
+        }
+        yield Ok(Event::default().event("complete").data(""));
+    };
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Reserve a stream for single connection mode, returning its token
+fn reserve(user_id: Option<String>) -> Result<String, &'static str> {
+    let mut reservations = lock_reservations();
+    if reservations.len() >= MAX_RESERVATIONS {
+        reservations.retain(|_, reservation| {
+            reservation.receiver.is_none() || reservation.reserved_at.elapsed() < RESERVATION_TTL
+        });
+        if reservations.len() >= MAX_RESERVATIONS {
+            warn!("GraphQL SSE: Too many reserved streams");
+            return Err("Too many reserved streams");
+        }
+    }
+
+    let token = uuid::Uuid::new_v4().to_string();
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER_EVENTS);
+    reservations.insert(
+        token.clone(),
+        Reservation {
+            user_id,
+            reserved_at: Instant::now(),
+            sender,
+            receiver: Some(receiver),
+            operations: HashMap::new(),
+        },
+    );
+    Ok(token)
+}
+
+/// Find the reservation of a token if it belongs to the user
+fn owned_reservation<'a>(
+    reservations: &'a mut HashMap<String, Reservation>,
+    token: &str,
+    user_id: Option<&str>,
+) -> Result<&'a mut Reservation, (StatusCode, &'static str)> {
+    reservations
+        .get_mut(token)
+        .filter(|reservation| reservation.user_id.as_deref() == user_id)
+        .filter(|reservation| {
+            reservation.receiver.is_none() || reservation.reserved_at.elapsed() < RESERVATION_TTL
+        })
+        .ok_or((StatusCode::NOT_FOUND, "Stream not found"))
+}
+
+/// Take the receiving end of a reserved stream
+fn take_receiver(
+    token: &str,
+    user_id: Option<&str>,
+) -> Result<mpsc::Receiver<Event>, (StatusCode, &'static str)> {
+    let mut reservations = lock_reservations();
+    owned_reservation(&mut reservations, token, user_id)?
+        .receiver
+        .take()
+        .ok_or((StatusCode::CONFLICT, "Stream already open"))
+}
+
+/// Drop a reservation and stop its operations
+fn release(token: &str) {
+    if let Some(reservation) = lock_reservations().remove(token) {
+        for handle in reservation.operations.into_values() {
+            handle.abort();
+        }
+    }
+}
+
+/// Releases the reservation when its stream is closed
+struct StreamGuard(String);
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        debug!("GraphQL SSE stream {} closed", self.0);
+        release(&self.0);
+    }
+}
+
+/// Open the event stream of a reservation (single connection mode)
+fn open_stream(token: String, user_id: Option<&str>) -> Response {
+    let mut receiver = match take_receiver(&token, user_id) {
+        Ok(receiver) => receiver,
+        Err((status, message)) => return plain_response(status, message),
+    };
+    info!("GraphQL SSE stream {} opened", token);
+
+    let guard = StreamGuard(token);
+    let events = async_stream::stream! {
+        let _guard = guard;
+        while let Some(event) = receiver.recv().await {
+            yield Ok::<Event, Infallible>(event);
+        }
+    };
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Start an operation whose results go to a reserved stream
+fn start_operation(
+    token: &str,
+    auth_user: Option<&AuthUser>,
+    params: RequestParams,
+) -> Result<(), (StatusCode, &'static str)> {
+    let Some(operation_id) = params.operation_id() else {
+        return Err((StatusCode::BAD_REQUEST, "Missing extensions.operationId"));
+    };
+    let schema = crate::graphql::get_schema().map_err(|e| {
+        error!("GraphQL SSE: Failed to get schema: {:?}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Schema error")
+    })?;
+    let request = params
+        .into_graphql_request()
+        .data(js_auth_context(auth_user));
+
+    // Spawned while holding the lock so the task can't finish and forget
+    // itself before it is registered
+    let mut reservations = lock_reservations();
+    let reservation = owned_reservation(
+        &mut reservations,
+        token,
+        auth_user.map(|user| user.user_id.as_str()),
+    )?;
+    if reservation.operations.contains_key(&operation_id) {
+        return Err((StatusCode::CONFLICT, "Operation already running"));
+    }
+
+    let sender = reservation.sender.clone();
+    let stream_token = token.to_string();
+    let id = operation_id.clone();
+    let handle = tokio::spawn(async move {
+        debug!("Starting GraphQL SSE operation {} on {}", id, stream_token);
+        let mut results = Box::pin(schema.execute_stream(request));
+        while let Some(response) = results.next().await {
+            let payload = match serde_json::to_value(&response) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    error!("Failed to serialize response: {}", e);
+                    continue;
+                }
+            };
+            let next = event("next", &json!({ "id": id, "payload": payload }));
+            if sender.send(next).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender.send(event("complete", &json!({ "id": id }))).await;
+        if let Some(reservation) = lock_reservations().get_mut(&stream_token) {
+            reservation.operations.remove(&id);
+        }
+    });
+    reservation.operations.insert(operation_id, handle);
+    Ok(())
+}
+
+/// Stop an operation of a reserved stream at the client's request
+fn stop_operation(
+    token: &str,
+    user_id: Option<&str>,
+    operation_id: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    let mut reservations = lock_reservations();
+    let reservation = owned_reservation(&mut reservations, token, user_id)?;
+    if let Some(handle) = reservation.operations.remove(operation_id) {
+        debug!(
+            "Stopping GraphQL SSE operation {} on {}",
+            operation_id, token
+        );
+        handle.abort();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_params_from_query_string() {
+        let params = RequestParams::from_query_string(
+            "query=subscription%20%7B%20ticks%20%7D&variables=%7B%22n%22%3A1%7D&extensions=%7B%22operationId%22%3A%22op-1%22%7D",
+        )
+        .unwrap();
+        assert_eq!(params.query, "subscription { ticks }");
+        assert_eq!(params.variables, Some(json!({ "n": 1 })));
+        assert_eq!(params.operation_id().as_deref(), Some("op-1"));
+
+        assert!(RequestParams::from_query_string("variables=%7B%7D").is_err());
+        assert!(RequestParams::from_query_string("query=x&variables=nope").is_err());
+    }
+
+    #[test]
+    fn test_stream_token_from_header_or_query() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            stream_token(&headers, Some("token=abc")).as_deref(),
+            Some("abc")
+        );
+        assert_eq!(stream_token(&headers, Some("query=x")), None);
+
+        headers.insert(TOKEN_HEADER, "def".parse().unwrap());
+        assert_eq!(
+            stream_token(&headers, Some("token=abc")).as_deref(),
+            Some("def")
+        );
+    }
+
+    #[test]
+    fn test_reservation_lifecycle() {
+        let token = reserve(Some("user-1".to_string())).unwrap();
+
+        // Only the user that reserved the stream can open it, and only once
+        assert_eq!(
+            take_receiver(&token, Some("user-2")).unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        assert!(take_receiver(&token, Some("user-1")).is_ok());
+        assert_eq!(
+            take_receiver(&token, Some("user-1")).unwrap_err().0,
+            StatusCode::CONFLICT
+        );
+
+        // Closing the stream drops the reservation
+        drop(StreamGuard(token.clone()));
+        assert_eq!(
+            take_receiver(&token, Some("user-1")).unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -9,22 +9,54 @@
 //! - Authentication via connection_init payload
 //! - Automatic keep-alive with ping/pong
 //! - Graceful error handling and cleanup
-
-use axum::extract::ws::{Message, WebSocket};
+//!
+//! Browsers can't set headers on WebSocket requests, so clients that keep
+//! their session token outside cookies send it in the `connection_init`
+//! payload instead, as `{ "Authorization": "Bearer <token>" }`, nested under
+//! `headers`, or as `token`. While authentication is enabled a connection
+//! without a valid session from either place is closed with 4403 Forbidden;
+//! subscribing before the connection is acknowledged closes it with 4401
+//! Unauthorized. Ping interval and the time a client has to initialise the
+//! connection come from `[server.graphql_ws]`.
+//!
+//! Each connection may run `max_subscriptions_per_connection` subscriptions
+//! and each signed-in user `max_subscriptions_per_user` across connections;
+//! further subscribe messages get an error. Messages wait for the client in
+//! a buffer of `send_buffer_messages`. A client that leaves it full for
+//! `slow_consumer_timeout_ms` is closed with 1008, so a slow reader can't
+//! make the server buffer without bound.
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::mpsc::error::{SendTimeoutError, TrySendError};
+use tokio::sync::{Mutex, mpsc, watch};
 use tokio::time::{Duration, interval};
 use tracing::{debug, error, info, warn};
 
-/// Maximum number of concurrent subscriptions per WebSocket connection
-const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 20;
-
-/// Keep-alive ping interval (30 seconds)
-const PING_INTERVAL_SECS: u64 = 30;
+use crate::config::GraphQLWsConfig;
+
+/// How long queued messages and the close frame may take to send when the
+/// connection ends
+const SEND_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Close codes of the graphql-transport-ws protocol
+pub mod close_code {
+    /// A subscribe message arrived before the connection was acknowledged
+    pub const UNAUTHORIZED: u16 = 4401;
+    /// The connection_init payload did not authenticate the client
+    pub const FORBIDDEN: u16 = 4403;
+    /// No connection_init within the configured timeout
+    pub const INIT_TIMEOUT: u16 = 4408;
+    /// A second connection_init on the same connection
+    pub const TOO_MANY_INIT_REQUESTS: u16 = 4429;
+    /// Policy violation (RFC 6455): the client did not read its messages
+    /// fast enough
+    pub const SLOW_CONSUMER: u16 = 1008;
+}
 
 /// Message types in the graphql-transport-ws protocol
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -134,21 +166,130 @@ impl ProtocolMessage {
     }
 }
 
+/// Ends a connection with a close code; the first close wins
+#[derive(Clone)]
+struct Closer(Arc<watch::Sender<Option<(u16, &'static str)>>>);
+
+impl Closer {
+    fn close(&self, code: u16, reason: &'static str) {
+        self.0.send_if_modified(|close| {
+            if close.is_some() {
+                return false;
+            }
+            *close = Some((code, reason));
+            true
+        });
+    }
+}
+
+/// Queue a message from the connection's own loop, which must not wait for
+/// the client. Returns false when the connection has to end.
+fn queue(tx: &mpsc::Sender<ProtocolMessage>, closer: &Closer, message: ProtocolMessage) -> bool {
+    match tx.try_send(message) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            warn!("GraphQL WebSocket client is not reading its messages; closing");
+            closer.close(close_code::SLOW_CONSUMER, "Slow consumer");
+            false
+        }
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+/// Subscriptions of each signed-in user across connections
+static USER_SUBSCRIPTIONS: OnceLock<std::sync::Mutex<HashMap<String, usize>>> = OnceLock::new();
+
+fn lock_user_subscriptions() -> std::sync::MutexGuard<'static, HashMap<String, usize>> {
+    USER_SUBSCRIPTIONS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// One subscription counted against a user's limit until dropped
+struct UserSubscription {
+    user_id: String,
+}
+
+impl UserSubscription {
+    /// Count a subscription of a user, or `None` when the user already has
+    /// `max` (0 = no limit)
+    fn acquire(user_id: &str, max: usize) -> Option<Self> {
+        let mut counts = lock_user_subscriptions();
+        let count = counts.entry(user_id.to_string()).or_default();
+        if max > 0 && *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(Self {
+            user_id: user_id.to_string(),
+        })
+    }
+}
+
+impl Drop for UserSubscription {
+    fn drop(&mut self) {
+        let mut counts = lock_user_subscriptions();
+        if let Some(count) = counts.get_mut(&self.user_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                counts.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+/// What a connection needs to authenticate a session token sent in its
+/// connection_init payload
+pub struct ConnectionAuth {
+    pub auth_manager: Arc<crate::auth::AuthManager>,
+    pub ip_addr: String,
+    pub user_agent: String,
+}
+
+/// Session token of a connection_init payload: an `Authorization` bearer
+/// value, top-level or under `headers`, or a plain `token`
+pub fn session_token_from_payload(payload: Option<&Value>) -> Option<String> {
+    let payload = payload?;
+    let authorization = |value: &Value| {
+        ["Authorization", "authorization"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(Value::as_str))
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string())
+    };
+    authorization(payload)
+        .or_else(|| payload.get("headers").and_then(authorization))
+        .or_else(|| {
+            payload
+                .get("token")
+                .and_then(Value::as_str)
+                .map(|token| token.trim().to_string())
+        })
+        .filter(|token| !token.is_empty())
+}
+
 /// Subscription state tracker
 struct SubscriptionState {
     /// Active subscriptions mapped by ID
     subscriptions: HashMap<String, tokio::task::JoinHandle<()>>,
+    max_subscriptions: usize,
 }
 
 impl SubscriptionState {
-    fn new() -> Self {
+    fn new(max_subscriptions: usize) -> Self {
         Self {
             subscriptions: HashMap::new(),
+            max_subscriptions,
         }
     }
 
+    fn is_full(&self) -> bool {
+        self.subscriptions.len() >= self.max_subscriptions
+    }
+
     fn add(&mut self, id: String, handle: tokio::task::JoinHandle<()>) -> bool {
-        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+        if self.is_full() {
             return false;
         }
         self.subscriptions.insert(id, handle);
@@ -167,10 +308,14 @@ impl SubscriptionState {
     }
 }
 
-/// WebSocket connection handler for GraphQL subscriptions
+/// WebSocket connection handler for GraphQL subscriptions. `auth_user` is
+/// the user authenticated by the upgrade request; with `auth` set, a
+/// connection without one must authenticate in its connection_init payload.
 pub async fn handle_websocket_connection(
     socket: WebSocket,
-    auth_user: Option<crate::auth::AuthUser>,
+    mut auth_user: Option<crate::auth::AuthUser>,
+    auth: Option<ConnectionAuth>,
+    config: GraphQLWsConfig,
 ) {
     info!(
         "New GraphQL WebSocket connection - authenticated: {}",
@@ -178,37 +323,103 @@ pub async fn handle_websocket_connection(
     );
 
     let (mut ws_sender, mut ws_receiver) = socket.split();
-    let subscription_state = Arc::new(Mutex::new(SubscriptionState::new()));
+    let subscription_state = Arc::new(Mutex::new(SubscriptionState::new(
+        config.max_subscriptions_per_connection,
+    )));
     let mut connection_initialized = false;
+    let slow_consumer_timeout = Duration::from_millis(config.slow_consumer_timeout_ms);
 
-    // Create channel for sending messages to WebSocket
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<ProtocolMessage>();
+    // Create bounded channel for sending messages to WebSocket
+    let (tx, mut rx) = mpsc::channel::<ProtocolMessage>(config.send_buffer_messages.max(1));
+    let (close_tx, mut close_rx) = watch::channel(None);
+    let closer = Closer(Arc::new(close_tx));
+    let mut connection_closed = closer.0.subscribe();
 
-    // Spawn task to send messages from channel to WebSocket
+    // Spawn task to send messages from channel to WebSocket; a close
+    // request goes ahead of queued messages
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&msg)
-                && ws_sender.send(Message::Text(json.into())).await.is_err()
-            {
-                break;
+        loop {
+            let msg = tokio::select! {
+                biased;
+                _ = close_rx.changed() => {
+                    let close = *close_rx.borrow();
+                    if let Some((code, reason)) = close {
+                        let _ = ws_sender
+                            .send(Message::Close(Some(CloseFrame {
+                                code,
+                                reason: reason.into(),
+                            })))
+                            .await;
+                    }
+                    break;
+                }
+                msg = rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
+            let Ok(json) = serde_json::to_string(&msg) else {
+                continue;
+            };
+            tokio::select! {
+                biased;
+                // The client isn't reading; give up on the pending message
+                _ = close_rx.changed() => break,
+                sent = ws_sender.send(Message::Text(json.into())) => {
+                    if sent.is_err() {
+                        break;
+                    }
+                }
             }
         }
     });
 
     // Spawn keep-alive ping task
     let ping_tx = tx.clone();
+    let keep_alive_interval_secs = config.keep_alive_interval_secs;
     let ping_task = tokio::spawn(async move {
-        let mut ping_interval = interval(Duration::from_secs(PING_INTERVAL_SECS));
+        if keep_alive_interval_secs == 0 {
+            return;
+        }
+        let mut ping_interval = interval(Duration::from_secs(keep_alive_interval_secs));
+        // The first tick completes at once
+        ping_interval.tick().await;
         loop {
             ping_interval.tick().await;
-            if ping_tx.send(ProtocolMessage::ping()).is_err() {
+            // A full buffer already shows the client is alive but slow
+            if let Err(TrySendError::Closed(_)) = ping_tx.try_send(ProtocolMessage::ping()) {
                 break;
             }
         }
     });
 
+    let init_deadline = (config.connection_init_timeout_secs > 0).then(|| {
+        tokio::time::Instant::now() + Duration::from_secs(config.connection_init_timeout_secs)
+    });
+
     // Main message processing loop
-    while let Some(result) = ws_receiver.next().await {
+    loop {
+        let init_timeout = async {
+            match init_deadline {
+                Some(deadline) if !connection_initialized => {
+                    tokio::time::sleep_until(deadline).await
+                }
+                _ => std::future::pending().await,
+            }
+        };
+        let next = tokio::select! {
+            biased;
+            _ = connection_closed.changed() => break,
+            _ = init_timeout => {
+                warn!("GraphQL WebSocket connection not initialised in time");
+                closer.close(close_code::INIT_TIMEOUT, "Connection initialisation timeout");
+                break;
+            }
+            next = ws_receiver.next() => next,
+        };
+        let Some(result) = next else {
+            break;
+        };
         match result {
             Ok(Message::Text(text)) => {
                 let msg: ProtocolMessage = match serde_json::from_str(&text) {
@@ -231,18 +442,52 @@ pub async fn handle_websocket_connection(
                     MessageType::ConnectionInit => {
                         if connection_initialized {
                             warn!("Connection already initialized");
+                            closer.close(
+                                close_code::TOO_MANY_INIT_REQUESTS,
+                                "Too many initialisation requests",
+                            );
                             break;
                         }
+                        if auth_user.is_none()
+                            && let Some(auth) = &auth
+                        {
+                            auth_user = match session_token_from_payload(msg.payload.as_ref()) {
+                                Some(token) => match auth
+                                    .auth_manager
+                                    .get_session(&token, &auth.ip_addr, &auth.user_agent)
+                                    .await
+                                {
+                                    Ok(session) => Some(crate::auth::AuthUser::new(
+                                        session.user_id,
+                                        session.provider,
+                                        token,
+                                        session.is_admin,
+                                        session.is_editor,
+                                        session.email,
+                                        session.name,
+                                    )),
+                                    Err(e) => {
+                                        warn!("GraphQL WebSocket session token rejected: {}", e);
+                                        None
+                                    }
+                                },
+                                None => None,
+                            };
+                            if auth_user.is_none() {
+                                closer.close(close_code::FORBIDDEN, "Forbidden");
+                                break;
+                            }
+                        }
                         connection_initialized = true;
                         debug!("Connection initialized");
-                        if tx.send(ProtocolMessage::connection_ack()).is_err() {
+                        if !queue(&tx, &closer, ProtocolMessage::connection_ack()) {
                             break;
                         }
                     }
 
                     MessageType::Ping => {
                         debug!("Received ping, sending pong");
-                        if tx.send(ProtocolMessage::pong()).is_err() {
+                        if !queue(&tx, &closer, ProtocolMessage::pong()) {
                             break;
                         }
                     }
@@ -254,6 +499,7 @@ pub async fn handle_websocket_connection(
                     MessageType::Subscribe => {
                         if !connection_initialized {
                             warn!("Received subscribe before connection_init");
+                            closer.close(close_code::UNAUTHORIZED, "Unauthorized");
                             break;
                         }
 
@@ -269,13 +515,14 @@ pub async fn handle_websocket_connection(
                             Some(p) => p,
                             None => {
                                 warn!("Subscribe message missing payload");
-                                if tx
-                                    .send(ProtocolMessage::error(
+                                if !queue(
+                                    &tx,
+                                    &closer,
+                                    ProtocolMessage::error(
                                         subscription_id,
                                         vec!["Missing payload".to_string()],
-                                    ))
-                                    .is_err()
-                                {
+                                    ),
+                                ) {
                                     break;
                                 }
                                 continue;
@@ -288,13 +535,14 @@ pub async fn handle_websocket_connection(
                                 Ok(req) => req,
                                 Err(e) => {
                                     error!("Failed to parse GraphQL request: {}", e);
-                                    if tx
-                                        .send(ProtocolMessage::error(
+                                    if !queue(
+                                        &tx,
+                                        &closer,
+                                        ProtocolMessage::error(
                                             subscription_id,
                                             vec![format!("Invalid GraphQL request: {}", e)],
-                                        ))
-                                        .is_err()
-                                    {
+                                        ),
+                                    ) {
                                         break;
                                     }
                                     continue;
@@ -306,13 +554,14 @@ pub async fn handle_websocket_connection(
                             Ok(s) => s,
                             Err(e) => {
                                 error!("Failed to get GraphQL schema: {:?}", e);
-                                if tx
-                                    .send(ProtocolMessage::error(
+                                if !queue(
+                                    &tx,
+                                    &closer,
+                                    ProtocolMessage::error(
                                         subscription_id,
                                         vec![format!("Schema error: {:?}", e)],
-                                    ))
-                                    .is_err()
-                                {
+                                    ),
+                                ) {
                                     break;
                                 }
                                 continue;
@@ -333,20 +582,38 @@ pub async fn handle_websocket_connection(
                             crate::auth::JsAuthContext::anonymous()
                         };
 
-                        // Check subscription limit
+                        // Check subscription limits
                         let mut state = subscription_state.lock().await;
-                        if state.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
-                            warn!(
-                                "Maximum subscriptions ({}) reached",
-                                MAX_SUBSCRIPTIONS_PER_CONNECTION
-                            );
-                            if tx
-                                .send(ProtocolMessage::error(
-                                    subscription_id,
-                                    vec!["Maximum subscriptions reached".to_string()],
-                                ))
-                                .is_err()
-                            {
+                        let limit_error = if state.is_full() {
+                            Some(format!(
+                                "Maximum subscriptions per connection ({}) reached",
+                                config.max_subscriptions_per_connection
+                            ))
+                        } else {
+                            None
+                        };
+                        let user_subscription = match (&limit_error, &auth_user) {
+                            (None, Some(user)) => UserSubscription::acquire(
+                                &user.user_id,
+                                config.max_subscriptions_per_user,
+                            ),
+                            _ => None,
+                        };
+                        let limit_error = limit_error.or_else(|| {
+                            (auth_user.is_some() && user_subscription.is_none()).then(|| {
+                                format!(
+                                    "Maximum subscriptions per user ({}) reached",
+                                    config.max_subscriptions_per_user
+                                )
+                            })
+                        });
+                        if let Some(limit_error) = limit_error {
+                            warn!("{}", limit_error);
+                            if !queue(
+                                &tx,
+                                &closer,
+                                ProtocolMessage::error(subscription_id, vec![limit_error]),
+                            ) {
                                 break;
                             }
                             continue;
@@ -355,7 +622,10 @@ pub async fn handle_websocket_connection(
                         // Spawn subscription task
                         let sub_id = subscription_id.clone();
                         let tx_clone = tx.clone();
+                        let closer_clone = closer.clone();
                         let handle = tokio::spawn(async move {
+                            // Counted against the user until the task ends
+                            let _user_subscription = user_subscription;
                             debug!("Starting subscription: {}", sub_id);
                             let mut stream = Box::pin(
                                 schema.execute_stream(graphql_request.data(js_auth_context)),
@@ -370,17 +640,37 @@ pub async fn handle_websocket_connection(
                                     }
                                 };
 
-                                if tx_clone
-                                    .send(ProtocolMessage::next(sub_id.clone(), payload))
-                                    .is_err()
+                                match tx_clone
+                                    .send_timeout(
+                                        ProtocolMessage::next(sub_id.clone(), payload),
+                                        slow_consumer_timeout,
+                                    )
+                                    .await
                                 {
-                                    debug!("Subscription {} - client disconnected", sub_id);
-                                    break;
+                                    Ok(()) => {}
+                                    Err(SendTimeoutError::Timeout(_)) => {
+                                        warn!(
+                                            "Subscription {} - client is not reading its messages; closing",
+                                            sub_id
+                                        );
+                                        closer_clone
+                                            .close(close_code::SLOW_CONSUMER, "Slow consumer");
+                                        return;
+                                    }
+                                    Err(SendTimeoutError::Closed(_)) => {
+                                        debug!("Subscription {} - client disconnected", sub_id);
+                                        return;
+                                    }
                                 }
                             }
 
                             debug!("Subscription {} completed", sub_id);
-                            let _ = tx_clone.send(ProtocolMessage::complete(sub_id.clone()));
+                            let _ = tx_clone
+                                .send_timeout(
+                                    ProtocolMessage::complete(sub_id.clone()),
+                                    slow_consumer_timeout,
+                                )
+                                .await;
                         });
 
                         if !state.add(subscription_id.clone(), handle) {
@@ -431,5 +721,57 @@ pub async fn handle_websocket_connection(
     info!("Cleaning up WebSocket connection");
     subscription_state.lock().await.abort_all().await;
     ping_task.abort();
-    send_task.abort();
+    // Let the send task deliver queued messages or a close frame before it
+    // goes
+    drop(tx);
+    if tokio::time::timeout(SEND_DRAIN_TIMEOUT, send_task)
+        .await
+        .is_err()
+    {
+        debug!("WebSocket send task did not finish in time");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_session_token_from_payload() {
+        let token = |payload: Value| session_token_from_payload(Some(&payload));
+        assert_eq!(
+            token(json!({ "Authorization": "Bearer abc" })),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            token(json!({ "headers": { "authorization": "Bearer def" } })),
+            Some("def".to_string())
+        );
+        assert_eq!(token(json!({ "token": "ghi" })), Some("ghi".to_string()));
+        assert_eq!(token(json!({ "Authorization": "Basic abc" })), None);
+        assert_eq!(token(json!({ "token": " " })), None);
+        assert_eq!(session_token_from_payload(None), None);
+    }
+
+    #[test]
+    fn test_user_subscription_limit() {
+        let user_id = "graphql-ws-limit-user";
+        let first = UserSubscription::acquire(user_id, 2).unwrap();
+        let second = UserSubscription::acquire(user_id, 2).unwrap();
+        assert!(UserSubscription::acquire(user_id, 2).is_none());
+
+        drop(first);
+        let third = UserSubscription::acquire(user_id, 2).unwrap();
+        drop(second);
+        drop(third);
+        assert!(!lock_user_subscriptions().contains_key(user_id));
+
+        // 0 lifts the limit
+        let unlimited: Vec<_> = (0..5)
+            .map(|_| UserSubscription::acquire(user_id, 0).unwrap())
+            .collect();
+        assert_eq!(lock_user_subscriptions()[user_id], 5);
+        drop(unlimited);
+    }
 }
@@ -0,0 +1,292 @@
+//! Dependency checks of the `/health` endpoint (`[health]`).
+//!
+//! Every request runs the checks concurrently, each limited to
+//! `timeout_ms`:
+//!
+//! - `database`: `SELECT 1` on the primary pool
+//! - `notification_listener`: the task listening for changes announced by
+//!   other instances is still running
+//! - `secrets`: the secrets tables answer a query; `encrypted` tells whether
+//!   values are encrypted at rest
+//! - `outbound`: a request to `outbound_probe_url` gets a response
+//!
+//! Checks that don't apply (no database in memory mode, no probe URL) are
+//! `skipped`. The instance is `unhealthy` when the database check fails and
+//! `degraded` when any other check fails.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::config::HealthConfig;
+
+static SETTINGS: OnceLock<RwLock<HealthConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<HealthConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the health check configuration. Called once at server startup.
+pub fn configure(config: &HealthConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> HealthConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+/// Status of the instance as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub status: CheckStatus,
+    /// How long the check took; not set for skipped checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Why the check failed or was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Details of a check, such as whether secrets are encrypted
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl CheckResult {
+    fn skipped(message: &str) -> Self {
+        Self {
+            status: CheckStatus::Skipped,
+            latency_ms: None,
+            message: Some(message.to_string()),
+            details: Default::default(),
+        }
+    }
+}
+
+/// Results of all checks
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// Check whose failure makes the instance unhealthy rather than degraded
+const CRITICAL_CHECK: &str = "database";
+
+/// The overall status of check results
+fn overall_status(checks: &BTreeMap<&'static str, CheckResult>) -> HealthStatus {
+    let failed = |check: &CheckResult| check.status == CheckStatus::Failed;
+    if checks.get(CRITICAL_CHECK).is_some_and(failed) {
+        HealthStatus::Unhealthy
+    } else if checks.values().any(failed) {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    }
+}
+
+/// Run `check` within `timeout`, timing it
+async fn timed<F>(timeout: Duration, check: F) -> CheckResult
+where
+    F: Future<Output = Result<serde_json::Map<String, serde_json::Value>, String>>,
+{
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(timeout, check).await;
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+    let (status, message, details) = match outcome {
+        Ok(Ok(details)) => (CheckStatus::Ok, None, details),
+        Ok(Err(e)) => (CheckStatus::Failed, Some(e), Default::default()),
+        Err(_) => (
+            CheckStatus::Failed,
+            Some(format!("Timed out after {} ms", timeout.as_millis())),
+            Default::default(),
+        ),
+    };
+    CheckResult {
+        status,
+        latency_ms,
+        message,
+        details,
+    }
+}
+
+async fn check_database(timeout: Duration) -> CheckResult {
+    let Some(db) = crate::database::get_global_database() else {
+        return CheckResult::skipped("Database not initialized (memory mode)");
+    };
+    timed(timeout, async move {
+        db.health_check().await.map_err(|e| format!("{:#}", e))?;
+        Ok(Default::default())
+    })
+    .await
+}
+
+async fn check_notification_listener(timeout: Duration) -> CheckResult {
+    if crate::database::get_global_database().is_none() {
+        return CheckResult::skipped("Database not initialized (memory mode)");
+    }
+    timed(timeout, async {
+        let listener = crate::notifications::get_global_listener()
+            .ok_or("Notification listener not initialized")?;
+        if !listener.is_running().await {
+            return Err("Notification listener stopped".to_string());
+        }
+        Ok(Default::default())
+    })
+    .await
+}
+
+async fn check_secrets(timeout: Duration) -> CheckResult {
+    let Some(db) = crate::database::get_global_database() else {
+        return CheckResult::skipped("Database not initialized (memory mode)");
+    };
+    timed(timeout, async move {
+        for table in ["script_secrets", "user_secrets"] {
+            sqlx::query(sqlx::AssertSqlSafe(format!(
+                "SELECT 1 FROM {} LIMIT 1",
+                table
+            )))
+            .fetch_optional(db.pool())
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", table, e))?;
+        }
+        let mut details = serde_json::Map::new();
+        details.insert(
+            "encrypted".to_string(),
+            crate::repository::secret_encryption_enabled().into(),
+        );
+        Ok(details)
+    })
+    .await
+}
+
+/// Shared client of outbound probes; the probe URL comes from the
+/// configuration, so it is not restricted like `fetch` targets
+fn probe_client() -> Result<&'static reqwest::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+async fn check_outbound(url: Option<String>, timeout: Duration) -> CheckResult {
+    let Some(url) = url else {
+        return CheckResult::skipped("health.outbound_probe_url not set");
+    };
+    timed(timeout, async move {
+        // Any response shows the network path works
+        let response = probe_client()?
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+        let mut details = serde_json::Map::new();
+        details.insert("http_status".to_string(), response.status().as_u16().into());
+        Ok(details)
+    })
+    .await
+}
+
+/// Run every check
+pub async fn run_checks() -> HealthReport {
+    let config = current_settings();
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let (database, notification_listener, secrets, outbound) = tokio::join!(
+        check_database(timeout),
+        check_notification_listener(timeout),
+        check_secrets(timeout),
+        check_outbound(config.outbound_probe_url, timeout),
+    );
+    let checks = BTreeMap::from([
+        ("database", database),
+        ("notification_listener", notification_listener),
+        ("secrets", secrets),
+        ("outbound", outbound),
+    ]);
+    HealthReport {
+        status: overall_status(&checks),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: CheckStatus) -> CheckResult {
+        CheckResult {
+            status,
+            latency_ms: Some(1),
+            message: None,
+            details: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_overall_status() {
+        let mut checks = BTreeMap::from([
+            ("database", result(CheckStatus::Ok)),
+            ("secrets", result(CheckStatus::Ok)),
+            ("outbound", result(CheckStatus::Skipped)),
+        ]);
+        assert_eq!(overall_status(&checks), HealthStatus::Healthy);
+
+        checks.insert("secrets", result(CheckStatus::Failed));
+        assert_eq!(overall_status(&checks), HealthStatus::Degraded);
+
+        checks.insert("database", result(CheckStatus::Failed));
+        assert_eq!(overall_status(&checks), HealthStatus::Unhealthy);
+
+        checks.insert("database", result(CheckStatus::Skipped));
+        assert_eq!(overall_status(&checks), HealthStatus::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_timed() {
+        let ok = timed(Duration::from_secs(1), async { Ok(Default::default()) }).await;
+        assert_eq!(ok.status, CheckStatus::Ok);
+        assert!(ok.latency_ms.is_some());
+
+        let failed = timed(Duration::from_secs(1), async { Err("down".to_string()) }).await;
+        assert_eq!(failed.status, CheckStatus::Failed);
+        assert_eq!(failed.message.as_deref(), Some("down"));
+
+        let slow = timed(Duration::from_millis(10), async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(Default::default())
+        })
+        .await;
+        assert_eq!(slow.status, CheckStatus::Failed);
+        assert!(slow.message.unwrap().starts_with("Timed out"));
+    }
+}
@@ -11,6 +11,9 @@
 //! 4. Timeout enforcement for all requests
 //! 5. TLS/SSL certificate validation
 //! 6. Audit logging for secret access
+//!
+//! Requests made while handling an HTTP request carry its `X-Request-Id` and
+//! a W3C `traceparent` header so upstream logs can be correlated with ours.
 
 use reqwest::Method;
 use reqwest::header::HeaderMap;
@@ -126,12 +129,30 @@ impl HttpClient {
     ///
     /// Supports secret injection via `{{secret:identifier}}` template syntax in headers.
     /// Validates secret access based on target URL and script URI constraints.
+    /// The request and response body bytes count towards the script's
+    /// resource usage.
     pub fn fetch(
         &self,
         url: String,
         options: FetchOptions,
         script_uri: Option<&str>,
         user_id: Option<&str>,
+    ) -> Result<FetchResponse, HttpError> {
+        let sent = options.body.as_ref().map_or(0, String::len);
+        let result = self.send_fetch(url, options, script_uri, user_id);
+        if let Some(script_uri) = script_uri {
+            let received = result.as_ref().map_or(0, |response| response.body.len());
+            crate::script_resources::record_outbound(script_uri, (sent + received) as u64);
+        }
+        result
+    }
+
+    fn send_fetch(
+        &self,
+        url: String,
+        options: FetchOptions,
+        script_uri: Option<&str>,
+        user_id: Option<&str>,
     ) -> Result<FetchResponse, HttpError> {
         // Parse HTTP method
         let method = Method::from_str(&options.method.to_uppercase())
@@ -337,6 +358,39 @@ impl HttpClient {
         }
     }
 
+    /// POST a binary body with the URL checks of `fetch`. Redirects are not
+    /// followed; secrets are not injected into `headers`.
+    pub fn post_bytes(
+        &self,
+        url: &str,
+        headers: HashMap<String, String>,
+        body: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<FetchResponse, HttpError> {
+        let parsed_url = self.validate(url)?;
+        let mut header_map = HeaderMap::new();
+        for (key, value) in headers {
+            let header_name = reqwest::header::HeaderName::from_str(&key)
+                .map_err(|e| HttpError::InvalidHeader(format!("Invalid header name: {}", e)))?;
+            let header_value = reqwest::header::HeaderValue::from_str(&value)
+                .map_err(|e| HttpError::InvalidHeader(format!("Invalid header value: {}", e)))?;
+            header_map.insert(header_name, header_value);
+        }
+        let client = if self.manual_redirects {
+            shared_client()?
+        } else {
+            shared_test_client()?
+        };
+        let response = client
+            .post(parsed_url.as_str())
+            .headers(header_map)
+            .body(body)
+            .timeout(timeout)
+            .send()
+            .map_err(|e| HttpError::RequestFailed(e.to_string()))?;
+        self.convert_response(response)
+    }
+
     /// Process headers and inject secrets, looking up values from the database.
     /// Checks `user_secrets` first (when `user_id` is given), then `script_secrets`.
     /// Environment variables and config files are never consulted.
@@ -482,6 +536,30 @@ impl Default for FetchOptions {
     }
 }
 
+impl FetchOptions {
+    /// Add `X-Request-Id` and `traceparent` headers for the request being
+    /// handled, unless the script set them itself
+    pub fn with_trace_headers(
+        mut self,
+        request_id: Option<&str>,
+        traceparent: Option<String>,
+    ) -> Self {
+        let headers = self.headers.get_or_insert_with(HashMap::new);
+        let mut add = |name: &str, value: String| {
+            if !headers.keys().any(|key| key.eq_ignore_ascii_case(name)) {
+                headers.insert(name.to_string(), value);
+            }
+        };
+        if let Some(request_id) = request_id {
+            add(crate::middleware::REQUEST_ID_HEADER, request_id.to_string());
+        }
+        if let Some(traceparent) = traceparent {
+            add(crate::middleware::TRACEPARENT_HEADER, traceparent);
+        }
+        self
+    }
+}
+
 /// Response from fetch request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FetchResponse {
@@ -655,4 +733,34 @@ mod tests {
         let result = HttpClient::validate_url("http://[::ffff:10.0.0.5]/api");
         assert!(matches!(result, Err(HttpError::BlockedUrl(_))));
     }
+
+    #[test]
+    fn test_with_trace_headers() {
+        let options = FetchOptions::default().with_trace_headers(
+            Some("req_1_2"),
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00".to_string()),
+        );
+        let headers = options.headers.expect("headers added");
+        assert_eq!(
+            headers.get("x-request-id").map(String::as_str),
+            Some("req_1_2")
+        );
+        assert!(headers.contains_key("traceparent"));
+
+        // Headers set by the script win
+        let options = FetchOptions {
+            headers: Some(HashMap::from([(
+                "X-Request-Id".to_string(),
+                "custom".to_string(),
+            )])),
+            ..Default::default()
+        }
+        .with_trace_headers(Some("req_1_2"), None);
+        let headers = options.headers.expect("headers kept");
+        assert_eq!(headers.len(), 1);
+        assert_eq!(
+            headers.get("X-Request-Id").map(String::as_str),
+            Some("custom")
+        );
+    }
 }
@@ -0,0 +1,351 @@
+//! Localization with translation bundles stored as script assets.
+//!
+//! A script's bundle for a locale is its JSON asset `i18n/<locale>.json`,
+//! e.g. `i18n/fi-FI.json`. Keys may be nested objects addressed with dots
+//! (`nav.home`), and values may contain `{name}` placeholders filled from
+//! the parameters. A value may also be an object of plural forms (`zero`,
+//! `one` and `other`) chosen by the `count` parameter.
+//!
+//! `i18n.t(key, params)` looks the key up in the request's locales in
+//! `Accept-Language` order, each followed by its language without the
+//! region (`fi-FI`, then `fi`), and finally in the configured default
+//! locale. A key found nowhere is returned as is.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::repository;
+
+/// Locale used when a request names none with a bundle
+pub const DEFAULT_LOCALE: &str = "en";
+
+static DEFAULT: OnceLock<RwLock<String>> = OnceLock::new();
+
+fn default_setting() -> &'static RwLock<String> {
+    DEFAULT.get_or_init(|| RwLock::new(DEFAULT_LOCALE.to_string()))
+}
+
+/// Set the default locale. Called once at server startup.
+pub fn configure(default_locale: &str) {
+    let locale = normalize(default_locale).unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+    match default_setting().write() {
+        Ok(mut guard) => *guard = locale,
+        Err(poisoned) => *poisoned.into_inner() = locale,
+    }
+}
+
+fn default_locale() -> String {
+    match default_setting().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+thread_local! {
+    static REQUEST_LOCALES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Restores the previous request locales when dropped
+#[derive(Debug)]
+pub struct LocaleGuard {
+    previous: Vec<String>,
+}
+
+impl Drop for LocaleGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        REQUEST_LOCALES.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Make the locales of an `Accept-Language` header those of the current
+/// thread's script execution until the guard is dropped
+pub fn enter_request_locales(accept_language: Option<&str>) -> LocaleGuard {
+    let locales = accept_language
+        .map(parse_accept_language)
+        .unwrap_or_default();
+    REQUEST_LOCALES.with(|current| LocaleGuard {
+        previous: std::mem::replace(&mut *current.borrow_mut(), locales),
+    })
+}
+
+fn request_locales() -> Vec<String> {
+    REQUEST_LOCALES.with(|current| current.borrow().clone())
+}
+
+/// Canonical form of a language tag (`fi-FI`, `zh-Hant-TW`), or `None`
+/// when it is not one
+fn normalize(tag: &str) -> Option<String> {
+    let subtags: Vec<&str> = tag.trim().split(['-', '_']).collect();
+    let valid = subtags.iter().all(|subtag| {
+        (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if !valid || !subtags[0].chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let normalized: Vec<String> = subtags
+        .iter()
+        .enumerate()
+        .map(|(index, subtag)| match (index, subtag.len()) {
+            (0, _) => subtag.to_ascii_lowercase(),
+            (_, 2) => subtag.to_ascii_uppercase(),
+            (_, 4) => {
+                let lower = subtag.to_ascii_lowercase();
+                lower[..1].to_ascii_uppercase() + &lower[1..]
+            }
+            _ => subtag.to_ascii_lowercase(),
+        })
+        .collect();
+    Some(normalized.join("-"))
+}
+
+/// Locales of an `Accept-Language` header, most preferred first. `*` and
+/// locales with `q=0` are left out.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut weighted: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let locale = normalize(parts.next()?)?;
+            let quality = parts
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((locale, quality))
+        })
+        .collect();
+    // Stable, so equal qualities keep the header's order
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut locales: Vec<String> = Vec::new();
+    for (locale, _) in weighted {
+        if !locales.contains(&locale) {
+            locales.push(locale);
+        }
+    }
+    locales
+}
+
+/// Locales to try for `preferred`, in order: each locale followed by its
+/// parent tags, then the default locale and its parents
+fn fallback_chain(preferred: &[String], default: &str) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::new();
+    for locale in preferred.iter().map(String::as_str).chain([default]) {
+        let mut tag = locale;
+        loop {
+            if !chain.iter().any(|existing| existing == tag) {
+                chain.push(tag.to_string());
+            }
+            match tag.rsplit_once('-') {
+                Some((parent, _)) => tag = parent,
+                None => break,
+            }
+        }
+    }
+    chain
+}
+
+/// Translations of one script execution. Bundles are loaded on first use
+/// and kept for the rest of the execution.
+pub struct Translator {
+    script_uri: String,
+    /// Locale chosen with `i18n.setLocale`, tried before the request's
+    preferred: Option<String>,
+    bundles: HashMap<String, Option<Value>>,
+}
+
+impl Translator {
+    pub fn new(script_uri: &str) -> Self {
+        Self {
+            script_uri: script_uri.to_string(),
+            preferred: None,
+            bundles: HashMap::new(),
+        }
+    }
+
+    /// Try `locale` before the request's locales
+    pub fn set_locale(&mut self, locale: &str) -> Result<(), String> {
+        let locale = normalize(locale).ok_or_else(|| format!("Invalid locale '{}'", locale))?;
+        self.preferred = Some(locale);
+        Ok(())
+    }
+
+    fn chain(&self) -> Vec<String> {
+        let mut preferred: Vec<String> = self.preferred.iter().cloned().collect();
+        preferred.extend(request_locales());
+        fallback_chain(&preferred, &default_locale())
+    }
+
+    fn bundle(&mut self, locale: &str) -> Option<&Value> {
+        let script_uri = &self.script_uri;
+        self.bundles
+            .entry(locale.to_string())
+            .or_insert_with(|| {
+                let asset = repository::fetch_asset(script_uri, &format!("i18n/{}.json", locale))?;
+                match serde_json::from_slice::<Value>(&asset.content) {
+                    Ok(bundle) if bundle.is_object() => Some(bundle),
+                    Ok(_) => {
+                        warn!(script_uri = %script_uri, locale, "Translation bundle is not a JSON object");
+                        None
+                    }
+                    Err(e) => {
+                        warn!(script_uri = %script_uri, locale, "Invalid translation bundle: {}", e);
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+
+    /// The first locale of the fallback chain that has a bundle, or the
+    /// default locale
+    pub fn locale(&mut self) -> String {
+        for locale in self.chain() {
+            if self.bundle(&locale).is_some() {
+                return locale;
+            }
+        }
+        default_locale()
+    }
+
+    /// Translate `key`, filling placeholders from `params`
+    pub fn translate(&mut self, key: &str, params: &Value) -> String {
+        for locale in self.chain() {
+            if let Some(message) = self
+                .bundle(&locale)
+                .and_then(|bundle| lookup(bundle, key))
+                .and_then(|value| select_form(value, params))
+            {
+                return interpolate(&message, params);
+            }
+        }
+        debug!(script_uri = %self.script_uri, key, "No translation found");
+        key.to_string()
+    }
+}
+
+/// The value of `key` in `bundle`: a top-level key containing dots, or a
+/// dotted path through nested objects
+fn lookup<'a>(bundle: &'a Value, key: &str) -> Option<&'a Value> {
+    bundle.get(key).or_else(|| {
+        key.split('.')
+            .try_fold(bundle, |value, segment| value.get(segment))
+    })
+}
+
+/// The message of a value: a string, or the plural form for `count`
+fn select_form(value: &Value, params: &Value) -> Option<String> {
+    match value {
+        Value::String(message) => Some(message.clone()),
+        Value::Object(forms) => {
+            let count = params.get("count").and_then(Value::as_f64);
+            let form = match count {
+                Some(count) if count == 0.0 && forms.contains_key("zero") => "zero",
+                Some(1.0) => "one",
+                _ => "other",
+            };
+            forms
+                .get(form)
+                .or_else(|| forms.get("other"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        }
+        _ => None,
+    }
+}
+
+/// Replace `{name}` placeholders with the matching parameter. Unknown
+/// placeholders are kept.
+fn interpolate(message: &str, params: &Value) -> String {
+    let mut result = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+        let name = &after[..end];
+        match params.get(name.trim()) {
+            Some(Value::String(value)) => result.push_str(value),
+            Some(Value::Null) | None => {
+                result.push('{');
+                result.push_str(name);
+                result.push('}');
+            }
+            Some(value) => result.push_str(&value.to_string()),
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_accept_language() {
+        assert_eq!(
+            parse_accept_language("fi-fi, en;q=0.8, sv;q=0.9, *;q=0.5, de;q=0"),
+            vec!["fi-FI", "sv", "en"]
+        );
+        assert_eq!(
+            parse_accept_language("zh_hant_tw, bogus!"),
+            vec!["zh-Hant-TW"]
+        );
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        assert_eq!(
+            fallback_chain(&["fi-FI".to_string(), "en-GB".to_string()], "en"),
+            vec!["fi-FI", "fi", "en-GB", "en"]
+        );
+        assert_eq!(fallback_chain(&[], "en-US"), vec!["en-US", "en"]);
+    }
+
+    #[test]
+    fn test_lookup_and_interpolate() {
+        let bundle = json!({
+            "greeting": "Hello, {name}!",
+            "nav": { "home": "Home" },
+            "cart.items": { "zero": "Your cart is empty", "one": "{count} item", "other": "{count} items" }
+        });
+        let message = |key: &str, params: Value| {
+            lookup(&bundle, key)
+                .and_then(|value| select_form(value, &params))
+                .map(|message| interpolate(&message, &params))
+        };
+        assert_eq!(
+            message("greeting", json!({ "name": "Ada" })).as_deref(),
+            Some("Hello, Ada!")
+        );
+        assert_eq!(
+            message("greeting", json!({})).as_deref(),
+            Some("Hello, {name}!")
+        );
+        assert_eq!(message("nav.home", json!(null)).as_deref(), Some("Home"));
+        assert_eq!(
+            message("cart.items", json!({ "count": 0 })).as_deref(),
+            Some("Your cart is empty")
+        );
+        assert_eq!(
+            message("cart.items", json!({ "count": 1 })).as_deref(),
+            Some("1 item")
+        );
+        assert_eq!(
+            message("cart.items", json!({ "count": 5 })).as_deref(),
+            Some("5 items")
+        );
+        assert_eq!(message("nav.missing", json!(null)), None);
+    }
+}
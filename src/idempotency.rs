@@ -0,0 +1,321 @@
+//! Idempotency-Key support for script routes.
+//!
+//! Clients retry requests that timed out or lost their connection, and a
+//! retried POST runs its handler's side effects twice. Routes registered with
+//! the `idempotency` option of `routeRegistry.registerRoute` accept an
+//! `Idempotency-Key` request header: the first request with a key runs the
+//! handler and its response is stored in the `idempotency_keys` table;
+//! retries with the same key get the stored response back, marked with
+//! `Idempotent-Replayed: true`, without running the handler again.
+//!
+//! Keys are scoped to the route's method and path and to the caller (the
+//! signed-in user and tenant), so different callers may use the same key.
+//! Reusing a key for a different request body answers 422, and a retry that
+//! arrives while the first request is still running answers 409. Server
+//! errors are not stored, so a retry after one runs the handler again.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use axum::{
+    http::{HeaderMap, HeaderValue},
+    response::Response,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::js_engine::JsHttpResponse;
+use crate::repository::{self, IdempotencyClaim, Repository as _, StoredResponse};
+
+/// Request header carrying the client's idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header marking a replayed response
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a stored response is replayed when the route sets no TTL
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest accepted idempotency key
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Shortest time before a claim whose request never completed is taken over
+const MIN_STALE_CLAIM_AFTER: Duration = Duration::from_secs(60);
+
+/// Interval between background sweeps of expired idempotency keys
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+
+static EXPIRY_WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Idempotency registered with a route (`registerRoute` option `idempotency`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteIdempotency {
+    /// How long responses are replayed; defaults to 24 hours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+impl RouteIdempotency {
+    fn ttl(&self) -> Duration {
+        self.ttl_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL)
+    }
+}
+
+/// A request that claimed its idempotency key. Exactly one of
+/// [`IdempotentRequest::complete`] or [`IdempotentRequest::release`] must be
+/// called once the handler has run.
+#[derive(Debug)]
+pub struct IdempotentRequest {
+    scope: String,
+    key: String,
+}
+
+/// What to do with a request to an idempotent route
+#[derive(Debug)]
+pub enum Begin {
+    /// Run the handler. Holds the claimed key unless the request carried no
+    /// key or the key store is unavailable.
+    Execute(Option<IdempotentRequest>),
+    /// Answer with this response without running the handler
+    Respond(Response),
+}
+
+/// Idempotency key of a request: None without the header, an error when the
+/// header is not 1-255 visible ASCII characters
+pub fn request_key(headers: &HeaderMap) -> Result<Option<String>, String> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    let key = value
+        .to_str()
+        .map_err(|_| "Idempotency-Key must be visible ASCII".to_string())?
+        .trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(format!(
+            "Idempotency-Key must be 1-{} characters",
+            MAX_KEY_LENGTH
+        ));
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        return Err("Idempotency-Key must be visible ASCII".to_string());
+    }
+    Ok(Some(key.to_string()))
+}
+
+/// Scope a key is unique within: the route's method and path, and the caller
+pub fn request_scope(
+    method: &str,
+    path: &str,
+    user_id: Option<&str>,
+    tenant: Option<&str>,
+) -> String {
+    format!(
+        "{} {}{}{}",
+        method,
+        path,
+        user_scope_marker(user_id.unwrap_or("")),
+        tenant.unwrap_or("")
+    )
+}
+
+/// The part of every [`request_scope`] of `user_id` that names the user
+pub fn user_scope_marker(user_id: &str) -> String {
+    format!(" user:{} tenant:", user_id)
+}
+
+/// Fingerprint of the request a key was first used for
+pub fn request_hash(query: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(query.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// Claim `key` for a request, or answer it from the stored response of an
+/// earlier request with the same key
+pub async fn begin(
+    route: &RouteIdempotency,
+    scope: String,
+    key: String,
+    request_hash: &str,
+    script_timeout_ms: u64,
+    path: &str,
+    request_id: &str,
+) -> Begin {
+    let now = Utc::now();
+    let ttl = chrono::Duration::from_std(route.ttl()).unwrap_or(chrono::Duration::days(1));
+    let stale_after = Duration::from_millis(script_timeout_ms)
+        .saturating_mul(2)
+        .max(MIN_STALE_CLAIM_AFTER);
+    let stale_after =
+        chrono::Duration::from_std(stale_after).unwrap_or(chrono::Duration::minutes(1));
+
+    let claim = repository::get_repository()
+        .claim_idempotency_key(&scope, &key, request_hash, now + ttl, now - stale_after)
+        .await;
+    match claim {
+        Ok(IdempotencyClaim::Claimed) => Begin::Execute(Some(IdempotentRequest { scope, key })),
+        Ok(IdempotencyClaim::InProgress { request_hash: hash }) if hash == request_hash => {
+            Begin::Respond(crate::error_to_response(crate::error::errors::conflict(
+                path,
+                "A request with this Idempotency-Key is still being processed",
+                request_id,
+            )))
+        }
+        Ok(IdempotencyClaim::Completed {
+            request_hash: hash,
+            response,
+        }) if hash == request_hash => {
+            info!(
+                "[{}] Replaying stored response for Idempotency-Key '{}'",
+                request_id, key
+            );
+            Begin::Respond(replayed_response(response))
+        }
+        Ok(_) => Begin::Respond(crate::error_to_response(
+            crate::error::errors::unprocessable_entity(
+                path,
+                "Idempotency-Key was already used for a different request",
+                request_id,
+            ),
+        )),
+        Err(e) => {
+            // Like rate limits, idempotency fails open: the request runs
+            // without protection rather than not at all
+            warn!(
+                "[{}] Idempotency key store unavailable, running request untracked: {}",
+                request_id, e
+            );
+            Begin::Execute(None)
+        }
+    }
+}
+
+impl IdempotentRequest {
+    /// Store the handler's response for replay. Server errors release the
+    /// key instead so a retry runs the handler again.
+    pub async fn complete(self, response: &JsHttpResponse) {
+        if response.status >= 500 {
+            return self.release().await;
+        }
+        let stored = StoredResponse {
+            status: response.status,
+            content_type: response.content_type.clone(),
+            headers: response.headers.clone(),
+            body: response.body.clone(),
+        };
+        if let Err(e) = repository::get_repository()
+            .complete_idempotency_key(&self.scope, &self.key, &stored)
+            .await
+        {
+            warn!("Failed to store idempotent response: {}", e);
+        }
+    }
+
+    /// Give the key up without storing a response
+    pub async fn release(self) {
+        if let Err(e) = repository::get_repository()
+            .release_idempotency_key(&self.scope, &self.key)
+            .await
+        {
+            warn!("Failed to release idempotency key: {}", e);
+        }
+    }
+}
+
+fn replayed_response(stored: StoredResponse) -> Response {
+    let mut response = crate::build_http_response_from_js(JsHttpResponse {
+        status: stored.status,
+        body: stored.body,
+        content_type: stored.content_type,
+        headers: stored.headers,
+    });
+    response
+        .headers_mut()
+        .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// Start the background task that deletes expired idempotency keys. Only
+/// the first call starts a worker.
+pub fn spawn_expiry_worker() {
+    if EXPIRY_WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository::get_repository()
+                .purge_expired_idempotency_keys()
+                .await
+            {
+                Ok(0) => {}
+                Ok(count) => debug!("Removed {} expired idempotency keys", count),
+                Err(e) => warn!("Failed to purge expired idempotency keys: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_key() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_key(&headers), Ok(None));
+
+        headers.insert(
+            IDEMPOTENCY_KEY_HEADER,
+            HeaderValue::from_static(" abc-123 "),
+        );
+        assert_eq!(request_key(&headers), Ok(Some("abc-123".to_string())));
+
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static(""));
+        assert!(request_key(&headers).is_err());
+
+        let long = "k".repeat(MAX_KEY_LENGTH + 1);
+        headers.insert(
+            IDEMPOTENCY_KEY_HEADER,
+            HeaderValue::from_str(&long).unwrap(),
+        );
+        assert!(request_key(&headers).is_err());
+    }
+
+    #[test]
+    fn test_request_scope_separates_callers() {
+        let anonymous = request_scope("POST", "/orders", None, None);
+        let user = request_scope("POST", "/orders", Some("u1"), None);
+        let tenant = request_scope("POST", "/orders", Some("u1"), Some("shop"));
+        assert_ne!(anonymous, user);
+        assert_ne!(user, tenant);
+        assert_ne!(user, request_scope("PUT", "/orders", Some("u1"), None));
+        assert!(tenant.contains(&user_scope_marker("u1")));
+        assert!(
+            !request_scope("POST", "/orders", Some("u10"), None).contains(&user_scope_marker("u1"))
+        );
+    }
+
+    #[test]
+    fn test_request_hash() {
+        assert_eq!(request_hash("a=1", b"{}"), request_hash("a=1", b"{}"));
+        assert_ne!(request_hash("a=1", b"{}"), request_hash("a=2", b"{}"));
+        // The separator keeps query and body from running together
+        assert_ne!(request_hash("a", b"b"), request_hash("ab", b""));
+    }
+
+    #[test]
+    fn test_route_idempotency_ttl() {
+        assert_eq!(RouteIdempotency::default().ttl(), DEFAULT_TTL);
+        let route: RouteIdempotency = serde_json::from_str(r#"{"ttlSeconds": 60}"#).unwrap();
+        assert_eq!(route.ttl(), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,570 @@
+//! Resizing, cropping and format conversion of image assets.
+//!
+//! Asset routes registered with a [`TransformPolicy`] apply a transform given
+//! in the query string, e.g. `/logo.png?w=400&format=webp`, and scripts call
+//! `images.transform(name, options)`. Both describe the same
+//! [`ImageTransform`]: an optional crop, then a resize, then encoding to the
+//! requested format. A route's policy lists the widths and formats clients
+//! may ask for, so public requests can only render a bounded set of
+//! variants.
+//!
+//! Results are cached by the source's content hash and the transform, so an
+//! edited asset never serves a stale rendition.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Largest width or height a transform may produce
+pub const MAX_OUTPUT_DIMENSION: u32 = 4096;
+
+/// Largest width or height of a source image that is decoded
+const MAX_SOURCE_DIMENSION: u32 = 16384;
+
+/// Most memory the decoder may allocate for one source image
+const MAX_DECODE_ALLOC_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Quality used for lossy formats when none is given
+const DEFAULT_QUALITY: u8 = 80;
+
+/// rav1e speed for AVIF output (1 is slowest and smallest, 10 fastest)
+const AVIF_SPEED: u8 = 8;
+
+/// Most transformed images kept; the cache is emptied when it fills up
+const MAX_CACHED_IMAGES: usize = 200;
+
+/// Most bytes of transformed images kept
+const MAX_CACHED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Query parameters that select an image transform on asset routes
+const QUERY_PARAMETERS: [&str; 6] = ["w", "h", "fit", "crop", "format", "q"];
+
+/// Most widths or formats a route's transform policy may list
+pub const MAX_POLICY_ENTRIES: usize = 16;
+
+/// How a resize with both a width and a height treats the aspect ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale to fit within the box, keeping the aspect ratio
+    #[default]
+    Contain,
+    /// Scale to cover the box and crop the overflow from the center
+    Cover,
+    /// Stretch to exactly the box
+    Fill,
+}
+
+/// Output encoding of a transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Gif,
+    Webp,
+    Avif,
+}
+
+impl OutputFormat {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::Webp),
+            "avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::Gif => Some(Self::Gif),
+            ImageFormat::WebP => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn mimetype(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+        }
+    }
+}
+
+/// Region of the source image kept by a crop, in source pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A crop, resize and re-encode of an image. Without a width or height the
+/// image keeps its size; without a format it keeps its source format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImageTransform {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    pub crop: Option<CropRect>,
+    pub format: Option<OutputFormat>,
+    /// 1-100, for JPEG and AVIF output. WebP output is lossless.
+    pub quality: Option<u8>,
+}
+
+impl ImageTransform {
+    /// The transform selected by an asset request's query string, or `None`
+    /// when it has none of the transform parameters (`w`, `h`, `fit`,
+    /// `crop`, `format` and `q`)
+    pub fn from_query(query: &str) -> Result<Option<Self>, String> {
+        let mut transform = Self::default();
+        let mut found = false;
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            if !QUERY_PARAMETERS.contains(&key.as_ref()) {
+                continue;
+            }
+            found = true;
+            match key.as_ref() {
+                "w" => transform.width = Some(parse_number(&key, &value)?),
+                "h" => transform.height = Some(parse_number(&key, &value)?),
+                "q" => transform.quality = Some(parse_number(&key, &value)?),
+                "fit" => {
+                    transform.fit = match value.as_ref() {
+                        "contain" => Fit::Contain,
+                        "cover" => Fit::Cover,
+                        "fill" => Fit::Fill,
+                        _ => return Err(format!("Invalid fit '{}'", value)),
+                    }
+                }
+                "crop" => {
+                    let parts: Vec<&str> = value.split(',').collect();
+                    let [x, y, width, height] = parts.as_slice() else {
+                        return Err("crop must be x,y,width,height".to_string());
+                    };
+                    transform.crop = Some(CropRect {
+                        x: parse_number("crop", x)?,
+                        y: parse_number("crop", y)?,
+                        width: parse_number("crop", width)?,
+                        height: parse_number("crop", height)?,
+                    });
+                }
+                "format" => {
+                    transform.format = Some(
+                        OutputFormat::parse(&value)
+                            .ok_or_else(|| format!("Unsupported format '{}'", value))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            return Ok(None);
+        }
+        transform.validate()?;
+        Ok(Some(transform))
+    }
+
+    /// Check the dimensions and quality are in range
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("width", self.width), ("height", self.height)] {
+            if let Some(value) = value
+                && !(1..=MAX_OUTPUT_DIMENSION).contains(&value)
+            {
+                return Err(format!(
+                    "{} must be between 1 and {}",
+                    name, MAX_OUTPUT_DIMENSION
+                ));
+            }
+        }
+        if let Some(crop) = &self.crop
+            && (crop.width == 0 || crop.height == 0)
+        {
+            return Err("crop width and height must be positive".to_string());
+        }
+        if let Some(quality) = self.quality
+            && !(1..=100).contains(&quality)
+        {
+            return Err("quality must be between 1 and 100".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The transforms an asset route serves (`imageTransforms` option of
+/// `routeRegistry.registerAssetRoute`): only `w` and `format`, each from its
+/// list. Routes without a policy ignore transform query parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransformPolicy {
+    /// Widths `w` may select
+    pub widths: Vec<u32>,
+    /// Formats `format` may select
+    pub formats: Vec<OutputFormat>,
+}
+
+impl TransformPolicy {
+    /// A policy from a route's allowed widths and format names
+    pub fn new(widths: Vec<u32>, formats: &[String]) -> Result<Self, String> {
+        if widths.len() > MAX_POLICY_ENTRIES || formats.len() > MAX_POLICY_ENTRIES {
+            return Err(format!(
+                "at most {} widths and {} formats may be listed",
+                MAX_POLICY_ENTRIES, MAX_POLICY_ENTRIES
+            ));
+        }
+        if let Some(width) = widths
+            .iter()
+            .find(|width| !(1..=MAX_OUTPUT_DIMENSION).contains(*width))
+        {
+            return Err(format!(
+                "width {} is not between 1 and {}",
+                width, MAX_OUTPUT_DIMENSION
+            ));
+        }
+        let formats = formats
+            .iter()
+            .map(|name| {
+                OutputFormat::parse(name).ok_or_else(|| format!("Unsupported format '{}'", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { widths, formats })
+    }
+
+    /// Check a transform requested on the route is one the policy allows
+    pub fn check(&self, transform: &ImageTransform) -> Result<(), String> {
+        if transform.height.is_some()
+            || transform.crop.is_some()
+            || transform.quality.is_some()
+            || transform.fit != Fit::default()
+        {
+            return Err("Only w and format can be used on this route".to_string());
+        }
+        if let Some(width) = transform.width
+            && !self.widths.contains(&width)
+        {
+            return Err(format!("Width {} is not allowed on this route", width));
+        }
+        if let Some(format) = transform.format
+            && !self.formats.contains(&format)
+        {
+            return Err(format!(
+                "Format {} is not allowed on this route",
+                format.mimetype()
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {} '{}'", name, value))
+}
+
+/// Whether assets of `mimetype` can be transformed
+pub fn is_transformable(mimetype: &str) -> bool {
+    ImageFormat::from_mime_type(mimetype)
+        .and_then(OutputFormat::from_image_format)
+        .is_some()
+}
+
+/// A transformed image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformedImage {
+    pub content: Vec<u8>,
+    pub mimetype: &'static str,
+}
+
+/// (source content hash, transform) -> transformed image
+type ImageCache = HashMap<(String, String), Arc<TransformedImage>>;
+
+static IMAGE_CACHE: OnceLock<Mutex<(ImageCache, usize)>> = OnceLock::new();
+
+fn cache() -> &'static Mutex<(ImageCache, usize)> {
+    IMAGE_CACHE.get_or_init(|| Mutex::new((HashMap::new(), 0)))
+}
+
+/// Apply `transform` to the image `content`, from the cache when the same
+/// source was transformed the same way before. CPU-bound: call it off the
+/// async runtime.
+pub fn transform(
+    content: &[u8],
+    transform: &ImageTransform,
+) -> Result<Arc<TransformedImage>, String> {
+    transform.validate()?;
+    let key = (
+        crate::asset_registry::content_hash(content),
+        serde_json::to_string(transform).map_err(|e| e.to_string())?,
+    );
+    if let Ok(guard) = cache().lock()
+        && let Some(image) = guard.0.get(&key)
+    {
+        return Ok(Arc::clone(image));
+    }
+
+    debug!(transform = %key.1, "Transforming image");
+    let image = Arc::new(apply(content, transform)?);
+
+    if let Ok(mut guard) = cache().lock() {
+        let (images, bytes) = &mut *guard;
+        if images.len() >= MAX_CACHED_IMAGES || *bytes + image.content.len() > MAX_CACHED_BYTES {
+            images.clear();
+            *bytes = 0;
+        }
+        if image.content.len() <= MAX_CACHED_BYTES {
+            *bytes += image.content.len();
+            images.insert(key, Arc::clone(&image));
+        }
+    }
+    Ok(image)
+}
+
+fn apply(content: &[u8], transform: &ImageTransform) -> Result<TransformedImage, String> {
+    let mut reader = ImageReader::new(Cursor::new(content))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let source_format = reader
+        .format()
+        .and_then(OutputFormat::from_image_format)
+        .ok_or_else(|| "Unsupported image format".to_string())?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC_BYTES);
+    reader.limits(limits);
+    let mut image = reader
+        .decode()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    if let Some(crop) = transform.crop {
+        if crop.x >= image.width() || crop.y >= image.height() {
+            return Err("crop is outside the image".to_string());
+        }
+        image = image.crop_imm(crop.x, crop.y, crop.width, crop.height);
+    }
+    image = resize(image, transform);
+
+    let format = transform.format.unwrap_or(source_format);
+    Ok(TransformedImage {
+        content: encode(&image, format, transform.quality.unwrap_or(DEFAULT_QUALITY))?,
+        mimetype: format.mimetype(),
+    })
+}
+
+fn resize(image: DynamicImage, transform: &ImageTransform) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let scaled = |numerator: u32, denominator: u32, size: u32| {
+        ((u64::from(size) * u64::from(numerator)) / u64::from(denominator).max(1))
+            .clamp(1, u64::from(MAX_OUTPUT_DIMENSION)) as u32
+    };
+    match (transform.width, transform.height) {
+        (None, None) => image,
+        (Some(w), None) => image.resize_exact(w, scaled(w, width, height), FilterType::Lanczos3),
+        (None, Some(h)) => image.resize_exact(scaled(h, height, width), h, FilterType::Lanczos3),
+        (Some(w), Some(h)) => match transform.fit {
+            Fit::Contain => image.resize(w, h, FilterType::Lanczos3),
+            Fit::Cover => image.resize_to_fill(w, h, FilterType::Lanczos3),
+            Fit::Fill => image.resize_exact(w, h, FilterType::Lanczos3),
+        },
+    }
+}
+
+fn encode(image: &DynamicImage, format: OutputFormat, quality: u8) -> Result<Vec<u8>, String> {
+    let mut output = Cursor::new(Vec::new());
+    let result = match format {
+        OutputFormat::Png => image.write_to(&mut output, ImageFormat::Png),
+        OutputFormat::Gif => image.write_to(&mut output, ImageFormat::Gif),
+        OutputFormat::Webp => {
+            DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut output, ImageFormat::WebP)
+        }
+        // JPEG has no alpha channel
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut output, quality)),
+        OutputFormat::Avif => DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(
+            AvifEncoder::new_with_speed_quality(&mut output, AVIF_SPEED, quality),
+        ),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(output.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut output = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+            .write_to(&mut output, ImageFormat::Png)
+            .unwrap();
+        output.into_inner()
+    }
+
+    fn dimensions(content: &[u8]) -> (u32, u32) {
+        image::load_from_memory(content).unwrap().dimensions()
+    }
+
+    #[test]
+    fn test_transform_from_query() {
+        assert_eq!(ImageTransform::from_query("v=3").unwrap(), None);
+        let transform = ImageTransform::from_query("w=400&fit=cover&h=300&format=webp&q=70&v=3")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            transform,
+            ImageTransform {
+                width: Some(400),
+                height: Some(300),
+                fit: Fit::Cover,
+                crop: None,
+                format: Some(OutputFormat::Webp),
+                quality: Some(70),
+            }
+        );
+        assert_eq!(
+            ImageTransform::from_query("crop=1,2,3,4")
+                .unwrap()
+                .unwrap()
+                .crop,
+            Some(CropRect {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4
+            })
+        );
+
+        assert!(ImageTransform::from_query("w=0").is_err());
+        assert!(ImageTransform::from_query("w=100000").is_err());
+        assert!(ImageTransform::from_query("format=bmp").is_err());
+        assert!(ImageTransform::from_query("crop=1,2").is_err());
+        assert!(ImageTransform::from_query("q=101").is_err());
+    }
+
+    #[test]
+    fn test_transform_policy() {
+        let policy = TransformPolicy::new(vec![320, 640], &["webp".to_string()]).unwrap();
+        let check =
+            |query: &str| policy.check(&ImageTransform::from_query(query).unwrap().unwrap());
+        assert!(check("w=320").is_ok());
+        assert!(check("w=640&format=webp").is_ok());
+        assert!(check("format=webp").is_ok());
+        assert!(check("w=400").is_err());
+        assert!(check("format=avif").is_err());
+        assert!(check("w=320&h=100").is_err());
+        assert!(check("w=320&q=50").is_err());
+        assert!(check("crop=0,0,10,10").is_err());
+        assert!(check("w=320&fit=cover").is_err());
+
+        // Nothing listed allows nothing
+        let none = TransformPolicy::default();
+        assert!(
+            none.check(&ImageTransform::from_query("w=320").unwrap().unwrap())
+                .is_err()
+        );
+
+        assert!(TransformPolicy::new(vec![0], &[]).is_err());
+        assert!(TransformPolicy::new(vec![5000], &[]).is_err());
+        assert!(TransformPolicy::new(vec![], &["bmp".to_string()]).is_err());
+        assert!(TransformPolicy::new((1..=17).collect(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_resize_and_crop() {
+        let source = png(200, 100);
+
+        let half = transform(
+            &source,
+            &ImageTransform {
+                width: Some(100),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(half.mimetype, "image/png");
+        assert_eq!(dimensions(&half.content), (100, 50));
+
+        let contained = ImageTransform {
+            width: Some(50),
+            height: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(
+            dimensions(&transform(&source, &contained).unwrap().content),
+            (50, 25)
+        );
+        let covered = ImageTransform {
+            fit: Fit::Cover,
+            ..contained.clone()
+        };
+        assert_eq!(
+            dimensions(&transform(&source, &covered).unwrap().content),
+            (50, 50)
+        );
+
+        let cropped = ImageTransform {
+            crop: Some(CropRect {
+                x: 150,
+                y: 0,
+                width: 100,
+                height: 40,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            dimensions(&transform(&source, &cropped).unwrap().content),
+            (50, 40)
+        );
+    }
+
+    #[test]
+    fn test_format_conversion_is_cached() {
+        let source = png(16, 16);
+        let to_webp = ImageTransform {
+            format: Some(OutputFormat::Webp),
+            ..Default::default()
+        };
+        let webp = transform(&source, &to_webp).unwrap();
+        assert_eq!(webp.mimetype, "image/webp");
+        assert_eq!(
+            image::guess_format(&webp.content).unwrap(),
+            ImageFormat::WebP
+        );
+        assert!(Arc::ptr_eq(&webp, &transform(&source, &to_webp).unwrap()));
+
+        let avif = transform(
+            &source,
+            &ImageTransform {
+                format: Some(OutputFormat::Avif),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(avif.mimetype, "image/avif");
+        assert!(!avif.content.is_empty());
+
+        assert!(transform(b"not an image", &to_webp).is_err());
+        assert!(is_transformable("image/jpeg"));
+        assert!(!is_transformable("image/svg+xml"));
+    }
+}
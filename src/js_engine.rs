@@ -1,15 +1,18 @@
 use rquickjs::{Context, Function, Runtime, Value};
 use serde_json::Value as JsonValue;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+use crate::graphql_errors::ResolverError;
 use crate::module_loader;
 use crate::repository;
 use crate::scheduler::ScheduledInvocation;
+use crate::script_errors::{JsError, ScriptFailure};
 use crate::security::UserContext;
 
 // Use the enhanced secure globals implementation
@@ -27,59 +30,87 @@ fn transpile_if_needed(uri: &str, content: &str) -> Result<String, String> {
 
 /// Extract detailed error information from a rquickjs::Error
 ///
-/// QuickJS errors often include line numbers and column information in their
-/// Display output. This function ensures we capture the full error message
-/// which may contain file names, line numbers, and stack traces.
+/// Takes the pending exception off the context and describes it with its
+/// message, location and stack trace. See [`JsError`] for the structured form.
 fn extract_error_details(ctx: &rquickjs::Ctx<'_>, error: &rquickjs::Error) -> String {
-    // Try to get the pending exception value which may have more details
-    let exception_val = ctx.catch();
-
-    // Try to convert to string to get detailed error message
-    if let Some(err_str) = exception_val.as_string()
-        && let Ok(rust_str) = err_str.to_string()
-        && !rust_str.is_empty()
-    {
-        return rust_str;
-    }
-
-    // Try to get as an object and extract properties
-    if let Some(err_obj) = exception_val.as_object() {
-        let mut parts = Vec::new();
-
-        // Get message
-        if let Ok(msg) = err_obj.get::<_, String>("message") {
-            parts.push(msg);
-        }
+    let captured = JsError::capture(ctx, error);
+    note_out_of_memory(error, &captured);
+    captured.to_string()
+}
 
-        // Get fileName if available
-        if let Ok(file) = err_obj.get::<_, String>("fileName") {
-            parts.push(format!("at {}", file));
-        }
+thread_local! {
+    /// Set when an error captured on this thread was the runtime running out
+    /// of memory; the execution's runtime reports it when dropped
+    static OUT_OF_MEMORY: Cell<bool> = const { Cell::new(false) };
+}
 
-        // Get lineNumber if available
-        if let Ok(line) = err_obj.get::<_, i32>("lineNumber") {
-            parts.push(format!("line {}", line));
-        }
+fn note_out_of_memory(error: &rquickjs::Error, captured: &JsError) {
+    if matches!(error, rquickjs::Error::Allocation) || captured.is_out_of_memory() {
+        OUT_OF_MEMORY.with(|flag| flag.set(true));
+    }
+}
 
-        // Get columnNumber if available
-        if let Ok(col) = err_obj.get::<_, i32>("columnNumber") {
-            parts.push(format!("column {}", col));
+/// Capture the pending exception of a script, mapping its positions back to
+/// the script's source: first through the transpiler's map when the script
+/// was transpiled, then through the source map uploaded with the script
+fn capture_script_error(
+    ctx: &rquickjs::Ctx<'_>,
+    error: &rquickjs::Error,
+    script_uri: &str,
+    content: &str,
+    transpiled_map: Option<&str>,
+) -> JsError {
+    let mut captured = JsError::capture(ctx, error);
+    note_out_of_memory(error, &captured);
+    if let Some(json) = transpiled_map {
+        match crate::source_maps::ScriptSourceMap::parse(json) {
+            Ok(map) => captured.remap(script_uri, &map.with_source(script_uri)),
+            Err(e) => debug!("Ignoring source map of {}: {}", script_uri, e),
         }
+    }
+    if let Some(map) = crate::source_maps::uploaded_source_map(script_uri, content) {
+        captured.remap(script_uri, &map);
+    }
+    captured
+}
 
-        // Get stack trace if available
-        if let Ok(stack) = err_obj.get::<_, String>("stack")
-            && !stack.is_empty()
-        {
-            parts.push(format!("\nStack: {}", stack));
+/// The settled value of a promise returned by a handler, running the job
+/// queue and settling the execution's `fetchAsync()` requests and
+/// `setTimeout()` timers as they finish until it settles (see
+/// [`crate::event_loop`]); other values are returned as they are, so
+/// handlers can be `async` functions. A rejected promise throws its reason,
+/// as if the handler had thrown it.
+fn await_value(value: Value<'_>) -> rquickjs::Result<Value<'_>> {
+    let Some(promise) = value.as_promise() else {
+        return Ok(value);
+    };
+    loop {
+        match promise.finish::<Value>() {
+            Err(rquickjs::Error::WouldBlock) => {}
+            result => return result,
         }
-
-        if !parts.is_empty() {
-            return parts.join(", ");
+        let wake = crate::event_loop::next_wake()
+            .map_err(|e| rquickjs::Error::new_from_js_message("promise", "value", &e))?
+            .ok_or_else(|| {
+                rquickjs::Error::new_from_js_message(
+                    "promise",
+                    "value",
+                    "Promise never settles: no pending job, request or timer is left to resolve it",
+                )
+            })?;
+        let settle: Function = value.ctx().globals().get("__settleHostOperation")?;
+        match wake {
+            crate::event_loop::Wake::Completed {
+                id,
+                outcome: Ok(result),
+            } => settle.call::<_, ()>((id, true, result))?,
+            crate::event_loop::Wake::Completed {
+                id,
+                outcome: Err(message),
+            } => settle.call::<_, ()>((id, false, message))?,
+            crate::event_loop::Wake::TimerFired { id } => settle.call::<_, ()>((id, true))?,
         }
     }
-
-    // Fall back to the error Display implementation
-    format!("{}", error)
 }
 
 /// Helper to safely drop Context before Runtime to prevent GC assertions
@@ -100,6 +131,10 @@ pub struct ExecutionLimits {
     pub timeout_ms: u64,
     pub max_memory_mb: usize,
     pub max_script_size_bytes: usize,
+    /// Highest timeout a script's own execution timeout may set
+    pub max_script_timeout_ms: u64,
+    /// Highest limit a script's own memory limit may set
+    pub max_script_memory_bytes: usize,
 }
 
 impl Default for ExecutionLimits {
@@ -108,6 +143,27 @@ impl Default for ExecutionLimits {
             timeout_ms: 2000,
             max_memory_mb: 50,
             max_script_size_bytes: 1_000_000, // 1MB
+            max_script_timeout_ms: 60_000,
+            max_script_memory_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl ExecutionLimits {
+    /// Timeout of a script whose own execution timeout is `script_timeout_ms`,
+    /// bounded by `max_script_timeout_ms`
+    pub fn script_timeout_ms(&self, script_timeout_ms: u64) -> u64 {
+        script_timeout_ms.min(self.max_script_timeout_ms)
+    }
+
+    /// Memory limit of an execution of `script_uri`: the script's own limit,
+    /// bounded by `max_script_memory_bytes`, or else `max_memory_mb`
+    pub fn memory_limit_bytes(&self, script_uri: Option<&str>) -> usize {
+        match script_uri.and_then(repository::script_memory_limit) {
+            Some(bytes) => usize::try_from(bytes)
+                .unwrap_or(usize::MAX)
+                .min(self.max_script_memory_bytes),
+            None => self.max_memory_mb * 1024 * 1024,
         }
     }
 }
@@ -126,18 +182,123 @@ pub fn current_execution_limits() -> ExecutionLimits {
     CONFIGURED_LIMITS.get().cloned().unwrap_or_default()
 }
 
+/// A runtime from `create_sandboxed_runtime`. When dropped it goes back to
+/// the runtime pool, which keeps it for the worker's next execution unless
+/// it is due for recycling (see [`crate::runtime_pool`]). When dropped, the
+/// execution's time, memory and whether it timed out are added to the
+/// script's resource usage, timeouts and panics count towards its
+/// quarantine, and running out of memory is logged to the script's log as
+/// FATAL. Requests and timers the execution left pending are dropped with
+/// it.
+struct SandboxedRuntime {
+    runtime: Option<Runtime>,
+    uses: u32,
+    script_uri: Option<String>,
+    memory_limit_bytes: usize,
+    started: Instant,
+    timed_out: Arc<AtomicBool>,
+    _event_loop: crate::event_loop::Scope,
+}
+
+impl std::ops::Deref for SandboxedRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Runtime {
+        self.runtime
+            .as_ref()
+            .expect("runtime is present until dropped")
+    }
+}
+
+impl Drop for SandboxedRuntime {
+    fn drop(&mut self) {
+        let Some(runtime) = self.runtime.take() else {
+            return;
+        };
+        let out_of_memory = OUT_OF_MEMORY.with(|flag| flag.replace(false));
+        if let Some(script_uri) = &self.script_uri {
+            if out_of_memory {
+                report_out_of_memory(script_uri, self.memory_limit_bytes);
+            }
+            crate::script_resources::record_execution(
+                script_uri,
+                self.started.elapsed(),
+                runtime.memory_usage().malloc_size,
+                self.timed_out.load(Ordering::Relaxed),
+            );
+            if self.timed_out.load(Ordering::Relaxed) {
+                crate::security::script_quarantine::record_failure(
+                    script_uri,
+                    crate::security::script_quarantine::ScriptFailure::Timeout,
+                );
+            } else if std::thread::panicking() {
+                crate::security::script_quarantine::record_failure(
+                    script_uri,
+                    crate::security::script_quarantine::ScriptFailure::Panic,
+                );
+            }
+        }
+        crate::runtime_pool::release(
+            self.script_uri.as_deref(),
+            runtime,
+            self.uses,
+            self.timed_out.load(Ordering::Relaxed) || out_of_memory,
+        );
+    }
+}
+
+/// Record that an execution of `script_uri` was stopped for exceeding its
+/// memory limit
+fn report_out_of_memory(script_uri: &str, memory_limit_bytes: usize) {
+    let message = format!(
+        "Script ran out of memory and was terminated (limit {} bytes)",
+        memory_limit_bytes
+    );
+    error!(script_uri = %script_uri, "{}", message);
+    if repository::get_repository_opt().is_some() {
+        repository::insert_log_message(script_uri, &message, "FATAL");
+    }
+}
+
 /// Creates a QuickJS runtime with memory, stack, and wall-clock limits enforced.
+/// Worker pool threads reuse a runtime kept from a previous execution; every
+/// execution still gets a fresh context.
 ///
 /// The interrupt handler is the only mechanism that can stop a runaway script
 /// (e.g. `while(true) {}`); outer tokio timeouts abandon the blocking thread
 /// but cannot terminate execution running on it.
-fn create_sandboxed_runtime(limits: &ExecutionLimits) -> Result<Runtime, String> {
-    let rt = Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
-    rt.set_memory_limit(limits.max_memory_mb * 1024 * 1024);
+///
+/// Executions of `script_uri` are counted in its resource usage; warm-ups
+/// pass `None`.
+fn create_sandboxed_runtime(
+    script_uri: Option<&str>,
+    limits: &ExecutionLimits,
+) -> Result<SandboxedRuntime, String> {
+    OUT_OF_MEMORY.with(|flag| flag.set(false));
+    let (rt, uses) = crate::runtime_pool::acquire(script_uri)?;
+    let memory_limit_bytes = limits.memory_limit_bytes(script_uri);
+    rt.set_memory_limit(memory_limit_bytes);
     rt.set_max_stack_size(512 * 1024);
+    // Time spent paused in the debugger does not count against the limit
     let deadline = Instant::now() + Duration::from_millis(limits.timeout_ms);
-    rt.set_interrupt_handler(Some(Box::new(move || Instant::now() >= deadline)));
-    Ok(rt)
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let interrupted = timed_out.clone();
+    rt.set_interrupt_handler(Some(Box::new(move || {
+        let expired = Instant::now() >= deadline + crate::debugger::paused_time();
+        if expired {
+            interrupted.store(true, Ordering::Relaxed);
+        }
+        expired
+    })));
+    Ok(SandboxedRuntime {
+        runtime: Some(rt),
+        uses,
+        script_uri: script_uri.map(str::to_string),
+        memory_limit_bytes,
+        started: Instant::now(),
+        _event_loop: crate::event_loop::enter(deadline, timed_out.clone()),
+        timed_out,
+    })
 }
 
 /// Parameters for secure script execution in request context
@@ -158,6 +319,10 @@ pub struct RequestExecutionParams {
     pub route_params: Option<HashMap<String, String>>,
     /// Uploaded files from multipart form data
     pub uploaded_files: Option<Vec<crate::parsers::UploadedFile>>,
+    /// Wall-clock budget replacing the configured execution timeout
+    pub timeout_ms: Option<u64>,
+    /// Tenant of the request and its branding, exposed as `request.tenant`
+    pub tenant: Option<JsonValue>,
 }
 
 /// Kinds of handler invocations supported by the runtime.
@@ -170,6 +335,9 @@ pub enum HandlerInvocationKind {
     StreamCustomization,
     Init,
     Scheduled,
+    Notification,
+    Event,
+    Task,
     McpTool,
 }
 
@@ -183,11 +351,120 @@ impl HandlerInvocationKind {
             HandlerInvocationKind::StreamCustomization => "streamCustomization",
             HandlerInvocationKind::Init => "init",
             HandlerInvocationKind::Scheduled => "scheduled",
+            HandlerInvocationKind::Notification => "notification",
+            HandlerInvocationKind::Event => "event",
+            HandlerInvocationKind::Task => "task",
             HandlerInvocationKind::McpTool => "mcpTool",
         }
     }
 }
 
+/// Request/handler context attached to log entries written by scripts while a
+/// handler runs. Set per execution thread with [`enter_log_context`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogContext {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handler: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// Trace flags, forwarded with the trace ID on outbound calls
+    #[serde(skip)]
+    pub trace_flags: Option<String>,
+}
+
+impl LogContext {
+    pub fn for_handler(kind: HandlerInvocationKind, handler: impl Into<String>) -> Self {
+        Self {
+            handler: Some(handler.into()),
+            kind: Some(kind.as_str().to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_method_and_path(
+        mut self,
+        method: impl Into<String>,
+        path: impl Into<String>,
+    ) -> Self {
+        self.method = Some(method.into());
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_trace(mut self, trace: &crate::middleware::TraceContext) -> Self {
+        self.trace_id = Some(trace.trace_id.clone());
+        self.trace_flags = Some(trace.flags.clone());
+        self
+    }
+
+    /// Trace the handler belongs to, if it runs for an HTTP request
+    pub fn trace_context(&self) -> Option<crate::middleware::TraceContext> {
+        Some(crate::middleware::TraceContext {
+            trace_id: self.trace_id.clone()?,
+            flags: self.trace_flags.clone().unwrap_or_else(|| "00".to_string()),
+        })
+    }
+}
+
+thread_local! {
+    static CURRENT_LOG_CONTEXT: RefCell<Option<LogContext>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous log context when dropped
+#[derive(Debug)]
+pub struct LogContextGuard {
+    previous: Option<LogContext>,
+}
+
+impl Drop for LogContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_LOG_CONTEXT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Make `context` the log context of the current thread until the guard is
+/// dropped. Fields left unset are inherited from the enclosing context, so a
+/// request ID set by the HTTP layer survives nested handler invocations.
+pub fn enter_log_context(context: LogContext) -> LogContextGuard {
+    CURRENT_LOG_CONTEXT.with(|current| {
+        let previous = current.borrow().clone();
+        let merged = match &previous {
+            Some(outer) => LogContext {
+                request_id: context.request_id.or_else(|| outer.request_id.clone()),
+                handler: context.handler.or_else(|| outer.handler.clone()),
+                kind: context.kind.or_else(|| outer.kind.clone()),
+                method: context.method.or_else(|| outer.method.clone()),
+                path: context.path.or_else(|| outer.path.clone()),
+                trace_id: context.trace_id.or_else(|| outer.trace_id.clone()),
+                trace_flags: context.trace_flags.or_else(|| outer.trace_flags.clone()),
+            },
+            None => context,
+        };
+        *current.borrow_mut() = Some(merged);
+        LogContextGuard { previous }
+    })
+}
+
+/// Log context of the handler running on the current thread, if any
+pub fn current_log_context() -> Option<LogContext> {
+    CURRENT_LOG_CONTEXT.with(|current| current.borrow().clone())
+}
+
 /// Normalized view of inbound request data passed to JavaScript.
 #[derive(Debug, Clone, Default)]
 pub struct JsRequestContext {
@@ -201,6 +478,8 @@ pub struct JsRequestContext {
     pub route_params: HashMap<String, String>,
     /// Uploaded files from multipart form data
     pub uploaded_files: Vec<crate::parsers::UploadedFile>,
+    /// Tenant of the request and its branding
+    pub tenant: Option<JsonValue>,
 }
 
 /// Builder that assembles the single context object passed to all handlers.
@@ -292,6 +571,27 @@ impl JsHandlerContextBuilder {
             request_obj.set("headers", headers_obj)?;
         }
 
+        // Content negotiation against the Accept header
+        let accept = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("accept"))
+            .map(|(_, value)| value.clone());
+        let accept_for_accepts = accept.clone();
+        request_obj.set(
+            "accepts",
+            rquickjs::Function::new(ctx.clone(), move |media_type: String| {
+                crate::content_negotiation::accepts(accept_for_accepts.as_deref(), &media_type)
+            })?,
+        )?;
+        request_obj.set(
+            "preferredType",
+            rquickjs::Function::new(ctx.clone(), move |types: Vec<String>| {
+                crate::content_negotiation::preferred(accept.as_deref(), &types)
+                    .map(|index| types[index].clone())
+            })?,
+        )?;
+
         // Query params
         let query_obj = rquickjs::Object::new(ctx.clone())?;
         for (key, value) in &request.query_params {
@@ -313,7 +613,9 @@ impl JsHandlerContextBuilder {
         }
         request_obj.set("params", route_obj)?;
 
-        // Uploaded files (base64-encoded data)
+        // Uploaded files. Small files carry their content as base64 `data`;
+        // files spooled to disk carry their `path`, and either is read on
+        // demand with read() or bytes(), or stored with saveAsAsset().
         let files_array = rquickjs::Array::new(ctx.clone())?;
         for (idx, file) in request.uploaded_files.iter().enumerate() {
             let file_obj = rquickjs::Object::new(ctx.clone())?;
@@ -324,11 +626,79 @@ impl JsHandlerContextBuilder {
             if let Some(ref content_type) = file.content_type {
                 file_obj.set("contentType", content_type.as_str())?;
             }
-            // Encode file data as base64 for JavaScript
-            let base64_data =
-                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &file.data);
-            file_obj.set("data", base64_data)?;
-            file_obj.set("size", file.size as u32)?;
+            match &file.content {
+                crate::parsers::UploadContent::Memory(data) => {
+                    let base64_data =
+                        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data);
+                    file_obj.set("data", base64_data)?;
+                }
+                crate::parsers::UploadContent::Spooled(spooled) => {
+                    file_obj.set("path", spooled.path().to_string_lossy().as_ref())?;
+                }
+            }
+            file_obj.set("size", file.size as f64)?;
+            file_obj.set("sha256", file.sha256.as_str())?;
+            // The function may outlive the request in a reused runtime, so it
+            // must not keep a spool file alive
+            let reader = file.reader();
+            file_obj.set(
+                "read",
+                rquickjs::Function::new(ctx.clone(), move || match reader.read() {
+                    Ok(data) => {
+                        base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data)
+                    }
+                    Err(e) => format!("Error: Cannot read uploaded file: {}", e),
+                })?,
+            )?;
+            let reader = file.reader();
+            file_obj.set(
+                "bytes",
+                rquickjs::Function::new(
+                    ctx.clone(),
+                    move |ctx: rquickjs::Ctx<'js>| -> rquickjs::Result<rquickjs::TypedArray<'js, u8>> {
+                        match reader.read() {
+                            Ok(data) => rquickjs::TypedArray::new(ctx, data.into_owned()),
+                            Err(e) => Err(rquickjs::Exception::throw_message(
+                                &ctx,
+                                &format!("Cannot read uploaded file: {}", e),
+                            )),
+                        }
+                    },
+                )?,
+            )?;
+            // Stored through assetStorage.upsertAsset, so the caller's asset
+            // permissions and limits apply
+            let reader = file.reader();
+            let mimetype = file
+                .content_type
+                .clone()
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            file_obj.set(
+                "saveAsAsset",
+                rquickjs::Function::new(
+                    ctx.clone(),
+                    move |ctx: rquickjs::Ctx<'js>, name: String| -> rquickjs::Result<String> {
+                        let data = match reader.read() {
+                            Ok(data) => data,
+                            Err(e) => {
+                                return Ok(format!("Error: Cannot read uploaded file: {}", e));
+                            }
+                        };
+                        let Some(asset_storage) = ctx
+                            .globals()
+                            .get::<_, Option<rquickjs::Object>>("assetStorage")?
+                        else {
+                            return Ok("Error: Asset storage is not available".to_string());
+                        };
+                        let upsert: rquickjs::Function = asset_storage.get("upsertAsset")?;
+                        let content = base64::Engine::encode(
+                            &base64::engine::general_purpose::STANDARD,
+                            data,
+                        );
+                        upsert.call((name, mimetype.clone(), content))
+                    },
+                )?,
+            )?;
             files_array.set(idx, file_obj)?;
         }
         request_obj.set("files", files_array)?;
@@ -340,6 +710,10 @@ impl JsHandlerContextBuilder {
             request_obj.set("body", rquickjs::Value::new_null(ctx.clone()))?;
         }
 
+        if let Some(tenant) = &request.tenant {
+            request_obj.set("tenant", serde_json_to_js_value(ctx, tenant)?)?;
+        }
+
         if let Some(auth_ctx) = auth_context {
             let auth_obj = crate::auth::AuthJsApi::create_auth_object(ctx, auth_ctx.clone())?;
             request_obj.set("auth", auth_obj)?;
@@ -505,7 +879,10 @@ fn setup_secure_global_functions(
     register_fn: Option<RegisterFunctionType>,
     _auth_context: Option<crate::auth::JsAuthContext>, // Kept for API compatibility but unused
 ) -> Result<(), rquickjs::Error> {
-    let secure_context = SecureGlobalContext::new_with_config(user_context, config.clone());
+    // Scripts only get the sandbox features their capability manifest grants
+    let mut config = config.clone();
+    config.restrict_to(&crate::security::script_manifests::granted(script_uri));
+    let secure_context = SecureGlobalContext::new_with_config(user_context, config);
 
     // Setup secure functions with proper capability validation
     secure_context.setup_secure_functions(ctx, script_uri, register_fn)?;
@@ -516,6 +893,9 @@ fn setup_secure_global_functions(
     // Add validation helpers
     setup_validation_helpers(ctx)?;
 
+    // Add the error class GraphQL resolvers throw for clients
+    setup_graphql_error_class(ctx)?;
+
     // Auth is no longer set up as a global - it's attached to req.auth by the caller
 
     Ok(())
@@ -530,6 +910,8 @@ fn setup_secure_global_functions(
 /// - Response.error(status, message) - Error response
 /// - Response.noContent() - 204 No Content
 /// - Response.redirect(url) - 302 redirect
+/// - Response.negotiate(request, representations, status) - The
+///   representation the request's Accept header prefers, or 406
 fn setup_response_builders(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Error> {
     // Create the ResponseBuilder object with builder methods using JavaScript
     ctx.eval::<(), _>(
@@ -581,6 +963,62 @@ fn setup_response_builders(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Erro
                         "Location": url
                     }
                 };
+            },
+            negotiate: function(request, representations, status = 200) {
+                const types = Object.keys(representations);
+                const chosen = request && typeof request.preferredType === "function"
+                    ? request.preferredType(types)
+                    : types[0];
+                if (chosen === null || chosen === undefined) {
+                    return {
+                        status: 406,
+                        body: JSON.stringify({ error: "Not Acceptable", available: types }),
+                        contentType: "application/json",
+                        headers: { "Vary": "Accept" }
+                    };
+                }
+                const shorthands = {
+                    json: "application/json",
+                    html: "text/html; charset=UTF-8",
+                    csv: "text/csv; charset=UTF-8",
+                    text: "text/plain; charset=UTF-8",
+                    xml: "application/xml"
+                };
+                const contentType = shorthands[chosen.toLowerCase()] || chosen;
+                let body = representations[chosen];
+                if (typeof body === "function") {
+                    body = body();
+                }
+                if (typeof body !== "string") {
+                    body = /[/+]json\b/.test(contentType) ? JSON.stringify(body) : String(body);
+                }
+                return {
+                    status: status,
+                    body: body,
+                    contentType: contentType,
+                    headers: { "Vary": "Accept" }
+                };
+            }
+        };
+        "#,
+    )?;
+
+    Ok(())
+}
+
+/// Sets up `GraphQLError`, the error GraphQL resolvers throw to report an
+/// expected failure to the client. The second argument holds the error's
+/// extensions, or just its code as a string.
+fn setup_graphql_error_class(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Error> {
+    ctx.eval::<(), _>(
+        r#"
+        globalThis.GraphQLError = class GraphQLError extends Error {
+            constructor(message, extensions) {
+                super(message);
+                this.name = "GraphQLError";
+                this.extensions = typeof extensions === "string"
+                    ? { code: extensions }
+                    : Object.assign({}, extensions);
             }
         };
         "#,
@@ -592,12 +1030,13 @@ fn setup_response_builders(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Erro
 /// Sets up validation helper functions for JavaScript execution contexts
 ///
 /// This function provides convenient validation utilities for JavaScript handlers
-/// to validate query parameters, path parameters, and other input data.
+/// to validate query parameters, path parameters, and other input data. They are
+/// added to the `validate` object holding the Rust-backed format checks.
 fn setup_validation_helpers(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Error> {
-    // Create the validation object with helper functions using JavaScript
+    // Add the helper functions, written in JavaScript, to the validation object
     ctx.eval::<(), _>(
         r#"
-        globalThis.validate = {
+        globalThis.validate = Object.assign(globalThis.validate || {}, {
             requireQueryParam: function(context, paramName) {
                 if (!context.request || !context.request.query) {
                     throw new Error("Request context or query parameters not available");
@@ -662,7 +1101,7 @@ fn setup_validation_helpers(ctx: &rquickjs::Ctx<'_>) -> Result<(), rquickjs::Err
 
                 return num;
             }
-        };
+        });
 
         // Also expose as global functions for convenience
         globalThis.requireQueryParam = function(paramName) {
@@ -760,7 +1199,7 @@ pub fn execute_script_secure(
     let registrations = Rc::new(RefCell::new(HashMap::new()));
     let uri_owned = uri.to_string();
 
-    match create_sandboxed_runtime(&limits) {
+    match create_sandboxed_runtime(Some(uri), &limits) {
         Ok(rt) => match Context::full(&rt) {
             Ok(ctx) => {
                 // Create a shared location for detailed error message
@@ -888,7 +1327,7 @@ pub fn execute_script(uri: &str, content: &str) -> ScriptExecutionResult {
     let registrations = Rc::new(RefCell::new(HashMap::new()));
     let uri_owned = uri.to_string();
 
-    match create_sandboxed_runtime(&limits) {
+    match create_sandboxed_runtime(Some(uri), &limits) {
         Ok(rt) => {
             match Context::full(&rt) {
                 Ok(ctx) => {
@@ -1034,12 +1473,66 @@ impl JsHttpResponse {
 pub fn execute_script_for_request_secure(
     params: RequestExecutionParams,
 ) -> Result<JsHttpResponse, String> {
+    execute_script_for_request_detailed(params).map_err(|failure| failure.message)
+}
+
+/// Like [`execute_script_for_request_secure`], keeping the exception a failed
+/// script threw, with positions mapped back to the source of transpiled scripts
+pub fn execute_script_for_request_detailed(
+    params: RequestExecutionParams,
+) -> Result<JsHttpResponse, ScriptFailure> {
+    let owner_script = repository::fetch_script(&params.script_uri)
+        .ok_or_else(|| format!("no script for uri {}", params.script_uri))?;
+    execute_script_source_for_request(params, &owner_script)
+}
+
+/// Like [`execute_script_for_request_detailed`], running `owner_script` as
+/// the script at `params.script_uri` instead of the stored content
+pub fn execute_script_source_for_request(
+    params: RequestExecutionParams,
+    owner_script: &str,
+) -> Result<JsHttpResponse, ScriptFailure> {
     let script_uri_owned = params.script_uri.clone();
     let auth_context = params.auth_context.clone(); // Clone for later use
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let _log_context = enter_log_context(
+        LogContext::for_handler(HandlerInvocationKind::HttpRoute, &params.handler_name)
+            .with_method_and_path(&params.method, &params.path),
+    );
+    let _locales = crate::i18n::enter_request_locales(
+        params
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("accept-language"))
+            .map(|(_, value)| value.as_str()),
+    );
+    let mut limits = current_execution_limits();
+    if let Some(timeout_ms) = params.timeout_ms {
+        limits.timeout_ms = timeout_ms;
+    }
+    let rt = create_sandboxed_runtime(Some(owner_script), &limits)?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
 
+    // Run with debug hooks while a debugger is interested in this script
+    let instrumented = if crate::debugger::should_instrument(&params.script_uri, owner_script) {
+        match crate::debugger::instrument(&params.script_uri, owner_script) {
+            Ok(instrumented) => Some(instrumented),
+            Err(e) => {
+                warn!(script_uri = %params.script_uri, "Cannot instrument script for debugging: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let _debug_execution = instrumented
+        .as_ref()
+        .map(|_| crate::debugger::ExecutionGuard::enter(&params.script_uri));
+
     ctx.with(|ctx| -> Result<(), rquickjs::Error> {
+        if instrumented.is_some() {
+            crate::debugger::install_hook(&ctx)?;
+        }
+
         // Set up all secure global functions
         // For request handling, we don't need GraphQL registration but enable everything else
         let security_config = GlobalSecurityConfig {
@@ -1061,23 +1554,27 @@ pub fn execute_script_for_request_secure(
     })
     .map_err(|e| format!("install secure host fns: {}", e))?;
 
-    let owner_script = repository::fetch_script(&params.script_uri)
-        .ok_or_else(|| format!("no script for uri {}", params.script_uri))?;
-
-    // Transpile if needed (TypeScript/JSX/TSX)
-    let executable_code = transpile_if_needed(&params.script_uri, &owner_script)?;
+    // Transpile if needed (TypeScript/JSX/TSX), keeping the source map for
+    // error reporting
+    let prepared = module_loader::prepare_executable_program(
+        &params.script_uri,
+        instrumented.as_deref().unwrap_or(owner_script),
+    )
+    .map_err(|e| format!("Transpilation error: {}", e))?;
+    let source_map = prepared.source_map.as_deref();
 
     // Evaluate the script and capture detailed error information if it fails
-    ctx.with(|ctx| -> Result<(), String> {
-        let result = crate::bytecode::eval_program(&ctx, &params.script_uri, &executable_code);
+    ctx.with(|ctx| -> Result<(), ScriptFailure> {
+        let result = crate::bytecode::eval_program(&ctx, &params.script_uri, &prepared.code);
         if let Err(ref e) = result {
-            let details = extract_error_details(&ctx, e);
-            return Err(format!("owner eval: {}", details));
+            let exception =
+                capture_script_error(&ctx, e, &params.script_uri, owner_script, source_map);
+            return Err(ScriptFailure::exception("owner eval", exception));
         }
         Ok(())
     })?;
 
-    let response_exec = ctx.with(|ctx| -> Result<JsHttpResponse, String> {
+    let response_exec = ctx.with(|ctx| -> Result<JsHttpResponse, ScriptFailure> {
         let global = ctx.globals();
         let func: Function = global
             .get::<_, Function>(&params.handler_name)
@@ -1092,6 +1589,7 @@ pub fn execute_script_for_request_secure(
             body: params.raw_body.clone(),
             route_params: params.route_params.clone().unwrap_or_default(),
             uploaded_files: params.uploaded_files.clone().unwrap_or_default(),
+            tenant: params.tenant.clone(),
         };
 
         let mut context_builder = JsHandlerContextBuilder::new(HandlerInvocationKind::HttpRoute)
@@ -1110,13 +1608,13 @@ pub fn execute_script_for_request_secure(
         global.set("context", handler_context.clone()).map_err(|e| format!("set context global: {}", e))?;
 
         // Call the handler function with automatic transaction handling
-        let result: Value = func.call::<_, Value>((handler_context,)).map_err(|e| {
-            let details = extract_error_details(&ctx, &e);
+        let result: Value = func.call::<_, Value>((handler_context,)).and_then(await_value).map_err(|e| {
+            let exception = capture_script_error(&ctx, &e, &params.script_uri, owner_script, source_map);
             // Auto-rollback on exception if transaction is active
             if crate::database::get_current_transaction_active() {
                 let _ = crate::database::Database::rollback_transaction();
             }
-            format!("call handler: {}", details)
+            ScriptFailure::exception("call handler", exception)
         })?;
 
         // Auto-commit on success if transaction is active
@@ -1158,11 +1656,11 @@ pub fn execute_script_for_request_secure(
                         if let Ok(to_string_fn) = obj.get::<_, rquickjs::Function>("toString") {
                             to_string_fn.call::<_, String>(()).map_err(|e| format!("Failed to call toString: {}", e))?
                         } else {
-                            return Err("Body must be a string or have a toString() method".to_string());
+                            return Err("Body must be a string or have a toString() method".to_string().into());
                         }
                     }
                 } else {
-                    return Err("Body must be a string or object with __html property".to_string());
+                    return Err("Body must be a string or object with __html property".to_string().into());
                 };
 
                 (body_string.into_bytes(), false)
@@ -1223,7 +1721,7 @@ pub fn execute_script_for_request_secure(
         }
     });
 
-    let response_result = response_exec.map_err(|e| e.to_string())?;
+    let response_result = response_exec?;
 
     // Ensure clean shutdown: drop Context before Runtime
     drop(ctx);
@@ -1246,7 +1744,7 @@ pub fn execute_script_for_request(
 ) -> Result<(u16, String, Option<String>), String> {
     let script_uri_owned = script_uri.to_string();
     let auth_ctx = crate::auth::JsAuthContext::anonymous();
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
 
     ctx.with(|ctx| -> Result<(), rquickjs::Error> {
@@ -1297,6 +1795,7 @@ pub fn execute_script_for_request(
                 body: raw_body.clone(),
                 route_params: HashMap::new(),
                 uploaded_files: Vec::new(),
+                tenant: None,
             };
 
             let mut context_builder =
@@ -1318,6 +1817,7 @@ pub fn execute_script_for_request(
 
             let val = func
                 .call::<_, Value>((handler_context,))
+                .and_then(await_value)
                 .map_err(|e| format!("call error: {}", e))?;
 
             let obj = val
@@ -1348,7 +1848,11 @@ pub fn execute_scheduled_handler(
     handler_name: &str,
     invocation: &ScheduledInvocation,
 ) -> Result<(), String> {
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let _log_context = enter_log_context(LogContext::for_handler(
+        HandlerInvocationKind::Scheduled,
+        handler_name,
+    ));
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
     let script_uri_owned = script_uri.to_string();
 
@@ -1409,14 +1913,16 @@ pub fn execute_scheduled_handler(
             .set("context", handler_context.clone())
             .map_err(|e| format!("set context global: {}", e))?;
 
-        func.call::<_, Value>((handler_context,)).map_err(|e| {
-            let details = extract_error_details(&ctx, &e);
-            // Auto-rollback on exception if transaction is active
-            if crate::database::get_current_transaction_active() {
-                let _ = crate::database::Database::rollback_transaction();
-            }
-            format!("call handler: {}", details)
-        })?;
+        func.call::<_, Value>((handler_context,))
+            .and_then(await_value)
+            .map_err(|e| {
+                let details = extract_error_details(&ctx, &e);
+                // Auto-rollback on exception if transaction is active
+                if crate::database::get_current_transaction_active() {
+                    let _ = crate::database::Database::rollback_transaction();
+                }
+                format!("call handler: {}", details)
+            })?;
 
         // Auto-commit on success if transaction is active
         if crate::database::get_current_transaction_active() {
@@ -1434,124 +1940,459 @@ pub fn execute_scheduled_handler(
     Ok(())
 }
 
-/// Executes a JavaScript GraphQL resolver function and returns the result as a string.
-/// This is used by the GraphQL system to call JavaScript resolver functions.
-pub fn execute_graphql_resolver(params: GraphqlResolverExecutionParams) -> Result<String, String> {
-    let script_uri_owned = params.script_uri.clone();
-    let resolver_function_owned = params.resolver_function.clone();
-    let args_owned = params.args.clone();
-    let auth_context = params.auth_context.clone();
-
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+/// Executes a JavaScript handler subscribed with db.listen for a database
+/// notification. The channel and payload are passed as
+/// `context.metadata.notification`.
+pub fn execute_notification_handler(
+    script_uri: &str,
+    handler_name: &str,
+    channel: &str,
+    payload: &str,
+    process_id: u32,
+) -> Result<(), String> {
+    let _log_context = enter_log_context(LogContext::for_handler(
+        HandlerInvocationKind::Notification,
+        handler_name,
+    ));
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
+    let script_uri_owned = script_uri.to_string();
 
-    let result_exec = ctx.with(|ctx| -> Result<String, rquickjs::Error> {
-        // Set up all global functions using the secure helper function
-        // For GraphQL resolvers, we don't need GraphQL registration (no-ops) or stream registration
-        let config = GlobalSecurityConfig {
+    ctx.with(|ctx| -> Result<(), rquickjs::Error> {
+        let security_config = GlobalSecurityConfig {
             enable_graphql_registration: false,
-            enable_streams: false,
-            enable_audit_logging: false, // Disable audit logging to avoid runtime conflicts
+            enable_audit_logging: false,
             ..Default::default()
         };
 
-        // GraphQL resolvers run with admin context to allow script management operations
-        // In production, this should be secured via GraphQL-level authentication/authorization
         setup_secure_global_functions(
             &ctx,
             &script_uri_owned,
-            UserContext::admin("graphql-resolver".to_string()),
-            &config,
+            UserContext::admin("notification".to_string()),
+            &security_config,
             None,
-            auth_context.clone(),
-        )?;
-
-        // Override specific functions that have different signatures for GraphQL resolver context
-        let _global = ctx.globals();
-
-        // Load and execute the script
-        let script_content = repository::fetch_script(&script_uri_owned)
-            .ok_or_else(|| rquickjs::Error::new_from_js("Script", "not found"))?;
-
-        // Execute the script
-        crate::bytecode::eval_program(&ctx, &script_uri_owned, &script_content)?;
+            None,
+        )
+    })
+    .map_err(|e| format!("install notification globals: {}", e))?;
 
-        let resolver_result: rquickjs::Value = ctx.globals().get(&resolver_function_owned)?;
-        let resolver_func = resolver_result
-            .as_function()
-            .ok_or_else(|| rquickjs::Error::new_from_js("Function", "not found"))?;
+    let owner_script = repository::fetch_script(script_uri)
+        .ok_or_else(|| format!("no script for uri {}", script_uri))?;
 
-        let request_context = JsRequestContext {
-            path: Some("/graphql".to_string()),
-            method: Some("POST".to_string()),
-            headers: HashMap::new(),
-            query_params: HashMap::new(),
-            form_data: HashMap::new(),
-            body: None,
-            route_params: HashMap::new(),
-            uploaded_files: Vec::new(),
-        };
+    // Transpile if needed (TypeScript/JSX/TSX)
+    let executable_code = transpile_if_needed(script_uri, &owner_script)?;
 
-        let mut context_builder =
-            JsHandlerContextBuilder::new(params.operation_kind.as_handler_kind())
-                .with_script_metadata(&params.script_uri, &params.resolver_function)
-                .with_request(request_context)
-                .with_metadata_value(
-                    "graphql",
-                    serde_json::json!({
-                        "fieldName": params.field_name,
-                        "operation": params.operation_kind.as_str()
-                    }),
-                );
+    ctx.with(|ctx| {
+        crate::bytecode::eval_program(&ctx, script_uri, &executable_code).map_err(|e| {
+            let details = extract_error_details(&ctx, &e);
+            format!("script eval: {}", details)
+        })
+    })?;
 
-        if let Some(args) = args_owned {
-            context_builder = context_builder.with_args(args);
-        }
+    let handler_result = ctx.with(|ctx| -> Result<(), String> {
+        let global = ctx.globals();
+        let func: Function = global
+            .get::<_, Function>(handler_name)
+            .map_err(|e| format!("no handler {}: {}", handler_name, e))?;
 
-        if let Some(auth_ctx) = auth_context.clone() {
-            context_builder = context_builder.with_auth_context(auth_ctx);
-        }
+        let notification_meta = serde_json::json!({
+            "channel": channel,
+            "payload": payload,
+            "processId": process_id,
+        });
 
-        let handler_context = context_builder.build(&ctx)?;
+        let handler_context = JsHandlerContextBuilder::new(HandlerInvocationKind::Notification)
+            .with_script_metadata(script_uri, handler_name)
+            .with_metadata_value("notification", notification_meta)
+            .build(&ctx)
+            .map_err(|e| format!("build context: {}", e))?;
 
-        // Set context as a global variable so personalStorage and other APIs can access it
-        let global = ctx.globals();
-        global.set("context", handler_context.clone())?;
+        global
+            .set("context", handler_context.clone())
+            .map_err(|e| format!("set context global: {}", e))?;
 
-        let result_value = resolver_func
-            .call::<_, rquickjs::Value>((handler_context,))
-            .inspect_err(|_e| {
+        func.call::<_, Value>((handler_context,))
+            .and_then(await_value)
+            .map_err(|e| {
+                let details = extract_error_details(&ctx, &e);
                 // Auto-rollback on exception if transaction is active
                 if crate::database::get_current_transaction_active() {
                     let _ = crate::database::Database::rollback_transaction();
                 }
+                format!("call handler: {}", details)
             })?;
 
         // Auto-commit on success if transaction is active
         if crate::database::get_current_transaction_active() {
-            crate::database::Database::commit_transaction().map_err(|e| {
-                rquickjs::Error::new_from_js("Transaction", Box::leak(e.into_boxed_str()))
-            })?;
+            crate::database::Database::commit_transaction()
+                .map_err(|e| format!("transaction commit failed: {}", e))?;
         }
 
-        // Convert the result to a JSON string
-        let result_string: String = if result_value.is_string() {
-            result_value
-                .as_string()
-                .ok_or_else(|| rquickjs::Error::new_from_js("value", "string"))?
-                .to_string()?
-        } else {
-            // Use JavaScript's JSON.stringify to convert any value to JSON
-            let json_obj: rquickjs::Object = ctx.globals().get("JSON")?;
-            let json_stringify: rquickjs::Function = json_obj.get("stringify")?;
-            let json_str: String = json_stringify.call((result_value,))?;
-            json_str
+        Ok(())
+    });
+
+    // Ensure clean shutdown
+    drop(ctx);
+
+    handler_result?;
+    Ok(())
+}
+
+/// Executes a JavaScript handler subscribed with events.subscribe. The event
+/// is passed as `context.meta.event`.
+pub fn execute_event_handler(
+    script_uri: &str,
+    handler_name: &str,
+    event: &crate::events::Event,
+) -> Result<(), String> {
+    let _log_context = enter_log_context(LogContext::for_handler(
+        HandlerInvocationKind::Event,
+        handler_name,
+    ));
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
+    let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
+    let script_uri_owned = script_uri.to_string();
+
+    ctx.with(|ctx| -> Result<(), rquickjs::Error> {
+        let security_config = GlobalSecurityConfig {
+            enable_graphql_registration: false,
+            enable_audit_logging: false,
+            ..Default::default()
         };
 
-        Ok(result_string)
+        setup_secure_global_functions(
+            &ctx,
+            &script_uri_owned,
+            UserContext::admin("event".to_string()),
+            &security_config,
+            None,
+            None,
+        )
+    })
+    .map_err(|e| format!("install event globals: {}", e))?;
+
+    let owner_script = repository::fetch_script(script_uri)
+        .ok_or_else(|| format!("no script for uri {}", script_uri))?;
+
+    // Transpile if needed (TypeScript/JSX/TSX)
+    let executable_code = transpile_if_needed(script_uri, &owner_script)?;
+
+    ctx.with(|ctx| {
+        crate::bytecode::eval_program(&ctx, script_uri, &executable_code).map_err(|e| {
+            let details = extract_error_details(&ctx, &e);
+            format!("script eval: {}", details)
+        })
+    })?;
+
+    let handler_result = ctx.with(|ctx| -> Result<(), String> {
+        let global = ctx.globals();
+        let func: Function = global
+            .get::<_, Function>(handler_name)
+            .map_err(|e| format!("no handler {}: {}", handler_name, e))?;
+
+        let event_meta =
+            serde_json::to_value(event).map_err(|e| format!("serialize event: {}", e))?;
+
+        let handler_context = JsHandlerContextBuilder::new(HandlerInvocationKind::Event)
+            .with_script_metadata(script_uri, handler_name)
+            .with_metadata_value("event", event_meta)
+            .build(&ctx)
+            .map_err(|e| format!("build context: {}", e))?;
+
+        global
+            .set("context", handler_context.clone())
+            .map_err(|e| format!("set context global: {}", e))?;
+
+        func.call::<_, Value>((handler_context,))
+            .and_then(await_value)
+            .map_err(|e| {
+                let details = extract_error_details(&ctx, &e);
+                // Auto-rollback on exception if transaction is active
+                if crate::database::get_current_transaction_active() {
+                    let _ = crate::database::Database::rollback_transaction();
+                }
+                format!("call handler: {}", details)
+            })?;
+
+        // Auto-commit on success if transaction is active
+        if crate::database::get_current_transaction_active() {
+            crate::database::Database::commit_transaction()
+                .map_err(|e| format!("transaction commit failed: {}", e))?;
+        }
+
+        Ok(())
     });
 
-    let result_string = result_exec.map_err(|e| format!("JavaScript execution error: {}", e))?;
+    // Ensure clean shutdown
+    drop(ctx);
+
+    handler_result?;
+    Ok(())
+}
+
+/// Executes a JavaScript handler of a task queued with tasks.enqueue. The
+/// task is passed as `context.meta.task`.
+pub fn execute_task_handler(
+    script_uri: &str,
+    handler_name: &str,
+    claimed: &crate::tasks::ClaimedTask,
+) -> Result<(), String> {
+    let _log_context = enter_log_context(LogContext::for_handler(
+        HandlerInvocationKind::Task,
+        handler_name,
+    ));
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
+    let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
+    let script_uri_owned = script_uri.to_string();
+
+    ctx.with(|ctx| -> Result<(), rquickjs::Error> {
+        let security_config = GlobalSecurityConfig {
+            enable_graphql_registration: false,
+            enable_audit_logging: false,
+            ..Default::default()
+        };
+
+        setup_secure_global_functions(
+            &ctx,
+            &script_uri_owned,
+            UserContext::admin("task".to_string()),
+            &security_config,
+            None,
+            None,
+        )
+    })
+    .map_err(|e| format!("install task globals: {}", e))?;
+
+    let owner_script = repository::fetch_script(script_uri)
+        .ok_or_else(|| format!("no script for uri {}", script_uri))?;
+
+    // Transpile if needed (TypeScript/JSX/TSX)
+    let executable_code = transpile_if_needed(script_uri, &owner_script)?;
+
+    ctx.with(|ctx| {
+        crate::bytecode::eval_program(&ctx, script_uri, &executable_code).map_err(|e| {
+            let details = extract_error_details(&ctx, &e);
+            format!("script eval: {}", details)
+        })
+    })?;
+
+    let handler_result = ctx.with(|ctx| -> Result<(), String> {
+        let global = ctx.globals();
+        let func: Function = global
+            .get::<_, Function>(handler_name)
+            .map_err(|e| format!("no handler {}: {}", handler_name, e))?;
+
+        let task_meta = serde_json::json!({
+            "id": claimed.task.id.to_string(),
+            "payload": claimed.task.payload,
+            "priority": claimed.task.priority,
+            "attempt": claimed.attempts,
+            "maxAttempts": claimed.task.max_attempts,
+        });
+
+        let handler_context = JsHandlerContextBuilder::new(HandlerInvocationKind::Task)
+            .with_script_metadata(script_uri, handler_name)
+            .with_metadata_value("task", task_meta)
+            .build(&ctx)
+            .map_err(|e| format!("build context: {}", e))?;
+
+        global
+            .set("context", handler_context.clone())
+            .map_err(|e| format!("set context global: {}", e))?;
+
+        func.call::<_, Value>((handler_context,))
+            .and_then(await_value)
+            .map_err(|e| {
+                let details = extract_error_details(&ctx, &e);
+                // Auto-rollback on exception if transaction is active
+                if crate::database::get_current_transaction_active() {
+                    let _ = crate::database::Database::rollback_transaction();
+                }
+                format!("call handler: {}", details)
+            })?;
+
+        // Auto-commit on success if transaction is active
+        if crate::database::get_current_transaction_active() {
+            crate::database::Database::commit_transaction()
+                .map_err(|e| format!("transaction commit failed: {}", e))?;
+        }
+
+        Ok(())
+    });
+
+    // Ensure clean shutdown
+    drop(ctx);
+
+    handler_result?;
+    Ok(())
+}
+
+/// The user a GraphQL resolver runs as for the calling `auth` context.
+///
+/// Signed-in callers run with their own context, by role, so script ownership
+/// and collaborator checks apply to mutations. Other callers run as anonymous,
+/// except when the server runs without authentication, where resolvers keep
+/// running with admin context.
+pub fn graphql_resolver_user(auth: Option<&crate::auth::JsAuthContext>) -> UserContext {
+    resolver_user(auth, crate::security::is_auth_enabled())
+}
+
+fn resolver_user(auth: Option<&crate::auth::JsAuthContext>, auth_enabled: bool) -> UserContext {
+    use crate::auth::JsAuthContext;
+
+    match auth {
+        Some(JsAuthContext {
+            is_authenticated: true,
+            user_id: Some(user_id),
+            is_admin,
+            is_editor,
+            ..
+        }) => {
+            if *is_admin {
+                UserContext::admin(user_id.clone())
+            } else if *is_editor {
+                UserContext::editor(user_id.clone())
+            } else {
+                UserContext::authenticated(user_id.clone())
+            }
+        }
+        _ if !auth_enabled => UserContext::admin("graphql-resolver".to_string()),
+        _ => UserContext::anonymous(),
+    }
+}
+
+/// Executes a JavaScript GraphQL resolver function and returns the result as a string.
+/// This is used by the GraphQL system to call JavaScript resolver functions.
+///
+/// Errors a script throws as `GraphQLError` come back as client errors; see
+/// [`crate::graphql_errors`].
+pub fn execute_graphql_resolver(
+    params: GraphqlResolverExecutionParams,
+) -> Result<String, ResolverError> {
+    let script_uri_owned = params.script_uri.clone();
+    let resolver_function_owned = params.resolver_function.clone();
+    let args_owned = params.args.clone();
+    let auth_context = params.auth_context.clone();
+    let _log_context = enter_log_context(LogContext::for_handler(
+        params.operation_kind.as_handler_kind(),
+        &params.resolver_function,
+    ));
+
+    let rt = create_sandboxed_runtime(Some(&script_uri_owned), &current_execution_limits())?;
+    let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
+
+    let result_exec = ctx.with(|ctx| -> Result<String, ResolverError> {
+        let run = || -> Result<String, rquickjs::Error> {
+            // Set up all global functions using the secure helper function
+            // For GraphQL resolvers, we don't need GraphQL registration (no-ops) or stream registration
+            let config = GlobalSecurityConfig {
+                enable_graphql_registration: false,
+                enable_streams: false,
+                enable_audit_logging: false, // Disable audit logging to avoid runtime conflicts
+                ..Default::default()
+            };
+
+            let user_context = graphql_resolver_user(auth_context.as_ref());
+
+            setup_secure_global_functions(
+                &ctx,
+                &script_uri_owned,
+                user_context,
+                &config,
+                None,
+                auth_context.clone(),
+            )?;
+
+            // Override specific functions that have different signatures for GraphQL resolver context
+            let _global = ctx.globals();
+
+            // Load and execute the script
+            let script_content = repository::fetch_script(&script_uri_owned)
+                .ok_or_else(|| rquickjs::Error::new_from_js("Script", "not found"))?;
+
+            // Execute the script
+            crate::bytecode::eval_program(&ctx, &script_uri_owned, &script_content)?;
+
+            let resolver_result: rquickjs::Value = ctx.globals().get(&resolver_function_owned)?;
+            let resolver_func = resolver_result
+                .as_function()
+                .ok_or_else(|| rquickjs::Error::new_from_js("Function", "not found"))?;
+
+            let request_context = JsRequestContext {
+                path: Some("/graphql".to_string()),
+                method: Some("POST".to_string()),
+                headers: HashMap::new(),
+                query_params: HashMap::new(),
+                form_data: HashMap::new(),
+                body: None,
+                route_params: HashMap::new(),
+                uploaded_files: Vec::new(),
+                tenant: None,
+            };
+
+            let mut context_builder =
+                JsHandlerContextBuilder::new(params.operation_kind.as_handler_kind())
+                    .with_script_metadata(&params.script_uri, &params.resolver_function)
+                    .with_request(request_context)
+                    .with_metadata_value(
+                        "graphql",
+                        serde_json::json!({
+                            "fieldName": params.field_name,
+                            "operation": params.operation_kind.as_str()
+                        }),
+                    );
+
+            if let Some(args) = args_owned {
+                context_builder = context_builder.with_args(args);
+            }
+
+            if let Some(auth_ctx) = auth_context.clone() {
+                context_builder = context_builder.with_auth_context(auth_ctx);
+            }
+
+            let handler_context = context_builder.build(&ctx)?;
+
+            // Set context as a global variable so personalStorage and other APIs can access it
+            let global = ctx.globals();
+            global.set("context", handler_context.clone())?;
+
+            let result_value = resolver_func
+                .call::<_, rquickjs::Value>((handler_context,))
+                .and_then(await_value)
+                .inspect_err(|_e| {
+                    // Auto-rollback on exception if transaction is active
+                    if crate::database::get_current_transaction_active() {
+                        let _ = crate::database::Database::rollback_transaction();
+                    }
+                })?;
+
+            // Auto-commit on success if transaction is active
+            if crate::database::get_current_transaction_active() {
+                crate::database::Database::commit_transaction().map_err(|e| {
+                    rquickjs::Error::new_from_js("Transaction", Box::leak(e.into_boxed_str()))
+                })?;
+            }
+
+            // Convert the result to a JSON string
+            let result_string: String = if result_value.is_string() {
+                result_value
+                    .as_string()
+                    .ok_or_else(|| rquickjs::Error::new_from_js("value", "string"))?
+                    .to_string()?
+            } else {
+                // Use JavaScript's JSON.stringify to convert any value to JSON
+                let json_obj: rquickjs::Object = ctx.globals().get("JSON")?;
+                let json_stringify: rquickjs::Function = json_obj.get("stringify")?;
+                let json_str: String = json_stringify.call((result_value,))?;
+                json_str
+            };
+
+            Ok(result_string)
+        };
+        run().map_err(|e| ResolverError::capture(&ctx, &e))
+    });
+
+    let result_string = result_exec?;
 
     // Ensure clean shutdown: drop Context before Runtime
     drop(ctx);
@@ -1580,7 +2421,7 @@ pub fn execute_mcp_prompt_handler(
     let handler_function_owned = handler_function.to_string();
     let arguments_owned = arguments.clone();
 
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
 
     let result_exec = ctx.with(|ctx| -> Result<serde_json::Value, rquickjs::Error> {
@@ -1627,7 +2468,7 @@ pub fn execute_mcp_prompt_handler(
         let args_obj: rquickjs::Value = ctx.json_parse(args_str)?;
 
         // Call the handler with arguments
-        let result: rquickjs::Value = handler_func.call((args_obj,))?;
+        let result: rquickjs::Value = handler_func.call((args_obj,)).and_then(await_value)?;
 
         // Convert result to JSON
         let result_json_str = ctx
@@ -1668,8 +2509,12 @@ pub fn execute_mcp_tool_handler(
     let arguments_owned = arguments.clone();
     let auth_context_owned = auth_context;
     let user_context_owned = user_context;
+    let _log_context = enter_log_context(LogContext::for_handler(
+        HandlerInvocationKind::McpTool,
+        handler_function,
+    ));
 
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
 
     let result_exec = ctx.with(|ctx| -> Result<String, rquickjs::Error> {
@@ -1719,6 +2564,7 @@ pub fn execute_mcp_tool_handler(
             body: None,
             route_params: HashMap::new(),
             uploaded_files: Vec::new(),
+            tenant: None,
         };
 
         let mut context_builder = JsHandlerContextBuilder::new(HandlerInvocationKind::McpTool)
@@ -1744,6 +2590,7 @@ pub fn execute_mcp_tool_handler(
 
         let result_value = handler_func
             .call::<_, rquickjs::Value>((handler_context,))
+            .and_then(await_value)
             .inspect_err(|_e| {
                 // Auto-rollback on exception if transaction is active
                 if crate::database::get_current_transaction_active() {
@@ -1809,7 +2656,7 @@ pub fn execute_stream_customization_function(
     let path_owned = path.to_string();
     let query_params_owned = query_params.clone();
 
-    let rt = create_sandboxed_runtime(&current_execution_limits())?;
+    let rt = create_sandboxed_runtime(Some(script_uri), &current_execution_limits())?;
     let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
 
     let result_exec = ctx.with(
@@ -1852,6 +2699,7 @@ pub fn execute_stream_customization_function(
                 body: None,
                 route_params: HashMap::new(),
                 uploaded_files: Vec::new(),
+                tenant: None,
             };
 
             let mut context_builder =
@@ -1890,6 +2738,7 @@ pub fn execute_stream_customization_function(
             // Call the function with req object
             let result_value: rquickjs::Value = customization_func
                 .call((handler_context,))
+                .and_then(await_value)
                 .inspect_err(|_e| {
                     // Auto-rollback on exception if transaction is active
                     if crate::database::get_current_transaction_active() {
@@ -1939,6 +2788,22 @@ pub fn execute_stream_customization_function(
     Ok(filter_criteria)
 }
 
+/// Prepares a script for its next request without running it: transpiles it
+/// and compiles its bytecode into the caches. On a worker pool thread this
+/// also leaves the thread's runtime created and cached for reuse.
+///
+/// Returns whether any bytecode had to be compiled.
+pub fn warm_up_script(script_uri: &str, script_content: &str) -> Result<bool, String> {
+    let prepared = module_loader::prepare_executable_program(script_uri, script_content)
+        .map_err(|e| format!("Transpilation error: {}", e))?;
+    let rt = create_sandboxed_runtime(None, &current_execution_limits())?;
+    let ctx = Context::full(&rt).map_err(|e| format!("context create: {}", e))?;
+    ctx.with(|ctx| {
+        crate::bytecode::precompile(&ctx, script_uri, &prepared.code)
+            .map_err(|e| extract_error_details(&ctx, &e))
+    })
+}
+
 /// Calls the init() function in a script if it exists
 ///
 /// This function executes a script and checks if it has an `init()` function defined.
@@ -1979,7 +2844,7 @@ pub fn call_init_if_exists_with_timeout(
         timeout_ms,
         ..current_execution_limits()
     };
-    let rt = create_sandboxed_runtime(&limits)?;
+    let rt = create_sandboxed_runtime(Some(script_uri), &limits)?;
 
     let ctx = Context::full(&rt).map_err(|e| format!("Failed to create context: {}", e))?;
 
@@ -2109,7 +2974,10 @@ pub fn call_init_if_exists_with_timeout(
 
             // Call init function with context
             debug!("Calling init() function for script: {}", script_uri);
-            let call_result = init_func.call::<_, ()>((handler_context,));
+            let call_result = init_func
+                .call::<_, Value>((handler_context,))
+                .and_then(await_value)
+                .map(|_| ());
 
             if let Err(ref e) = call_result {
                 let details = extract_error_details(&ctx, e);
@@ -2159,8 +3027,122 @@ mod tests {
     use crate::stream_registry;
     use std::sync::{Arc, Once, OnceLock};
 
-    static INIT: Once = Once::new();
-    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    #[test]
+    fn test_await_value_settles_promises() {
+        let rt = Runtime::new().unwrap();
+        let ctx = Context::full(&rt).unwrap();
+        ctx.with(|ctx| {
+            let resolved: Value = ctx
+                .eval(
+                    "(async () => { const [a, b] = await Promise.all([1, Promise.resolve(2)]); \
+                     return a + b; })()",
+                )
+                .unwrap();
+            assert_eq!(await_value(resolved).unwrap().as_int(), Some(3));
+
+            let plain: Value = ctx.eval("42").unwrap();
+            assert_eq!(await_value(plain).unwrap().as_int(), Some(42));
+
+            let rejected: Value = ctx
+                .eval("(async () => { throw new Error('boom'); })()")
+                .unwrap();
+            assert!(await_value(rejected).is_err());
+            let _ = ctx.catch();
+
+            let pending: Value = ctx.eval("new Promise(() => {})").unwrap();
+            assert!(matches!(
+                await_value(pending),
+                Err(rquickjs::Error::FromJsMessage { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_await_value_runs_timers() {
+        let rt = create_sandboxed_runtime(None, &ExecutionLimits::default())
+            .expect("runtime creation failed");
+        let ctx = Context::full(&rt).unwrap();
+        ctx.with(|ctx| {
+            SecureGlobalContext::new_with_config(
+                UserContext::admin("test".to_string()),
+                GlobalSecurityConfig::default(),
+            )
+            .setup_secure_functions(&ctx, "https://example.com/timers", None)
+            .unwrap();
+
+            // Timers run concurrently, in due order, and cleared ones never run
+            let started = Instant::now();
+            let timers: Value = ctx
+                .eval(
+                    r#"(async () => {
+                        const order = [];
+                        const sleep = (ms, value) =>
+                            new Promise((resolve) => setTimeout(() => { order.push(value); resolve(value); }, ms));
+                        const cleared = setTimeout(() => order.push("cleared"), 10);
+                        clearTimeout(cleared);
+                        const values = await Promise.all([sleep(150, "slow"), sleep(100, "fast")]);
+                        return values.join(",") + ";" + order.join(",");
+                    })()"#,
+                )
+                .unwrap();
+            let result = await_value(timers).unwrap();
+            assert_eq!(
+                result.as_string().unwrap().to_string().unwrap(),
+                "slow,fast;fast,slow"
+            );
+            assert!(started.elapsed() < Duration::from_millis(240));
+
+            // A throwing timer callback fails the call
+            let throwing: Value = ctx
+                .eval(
+                    r#"new Promise((resolve) => setTimeout(() => { throw new Error("timer failed"); }, 1))"#,
+                )
+                .unwrap();
+            assert!(await_value(throwing).is_err());
+            let _ = ctx.catch();
+
+            // fetchAsync() failures reject its promise instead of throwing
+            let fetched: Value = ctx
+                .eval(
+                    r#"fetchAsync("http://localhost/blocked").then(
+                        () => "resolved",
+                        (e) => "rejected: " + e.message,
+                    )"#,
+                )
+                .unwrap();
+            let result = await_value(fetched).unwrap();
+            let message = result.as_string().unwrap().to_string().unwrap();
+            assert!(message.starts_with("rejected: Fetch error"), "{}", message);
+        });
+    }
+
+    #[test]
+    fn test_await_value_stops_at_timeout() {
+        let limits = ExecutionLimits {
+            timeout_ms: 100,
+            ..ExecutionLimits::default()
+        };
+        let rt = create_sandboxed_runtime(None, &limits).expect("runtime creation failed");
+        let ctx = Context::full(&rt).unwrap();
+        let started = Instant::now();
+        ctx.with(|ctx| {
+            SecureGlobalContext::new_with_config(
+                UserContext::admin("test".to_string()),
+                GlobalSecurityConfig::default(),
+            )
+            .setup_secure_functions(&ctx, "https://example.com/timers", None)
+            .unwrap();
+            let waiting: Value = ctx
+                .eval("new Promise((resolve) => setTimeout(resolve, 60000))")
+                .unwrap();
+            assert!(await_value(waiting).is_err());
+        });
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(rt.timed_out.load(Ordering::Relaxed));
+    }
+
+    static INIT: Once = Once::new();
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
 
     fn setup_db() {
         INIT.call_once(|| {
@@ -2201,7 +3183,7 @@ mod tests {
             timeout_ms: 200,
             ..ExecutionLimits::default()
         };
-        let rt = create_sandboxed_runtime(&limits).expect("runtime creation failed");
+        let rt = create_sandboxed_runtime(None, &limits).expect("runtime creation failed");
         let ctx = Context::full(&rt).expect("context creation failed");
         let start = Instant::now();
         let result: Result<(), rquickjs::Error> =
@@ -2215,6 +3197,29 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_worker_threads_reuse_runtime() {
+        let pool = crate::worker_pool::WorkerPool::new(1, 4);
+        let execute = || {
+            let rt = create_sandboxed_runtime(None, &ExecutionLimits::default()).unwrap();
+            let ctx = Context::full(&rt).unwrap();
+            let saw_previous_global = ctx.with(|ctx| {
+                let seen = ctx
+                    .eval::<bool, _>("typeof leaked !== 'undefined'")
+                    .unwrap();
+                ctx.eval::<(), _>("var leaked = 1;").unwrap();
+                seen
+            });
+            drop(ctx);
+            (rt.uses, saw_previous_global)
+        };
+        assert_eq!(pool.run("script", execute).await, Ok((1, false)));
+        // The runtime is reused, but each execution gets a fresh context
+        assert_eq!(pool.run("script", execute).await, Ok((2, false)));
+        // Outside the pool every execution gets its own runtime
+        assert_eq!(execute().0, 1);
+    }
+
     #[test]
     fn test_memory_limit_stops_runaway_allocation() {
         let limits = ExecutionLimits {
@@ -2222,7 +3227,7 @@ mod tests {
             max_memory_mb: 8,
             ..ExecutionLimits::default()
         };
-        let rt = create_sandboxed_runtime(&limits).expect("runtime creation failed");
+        let rt = create_sandboxed_runtime(None, &limits).expect("runtime creation failed");
         let ctx = Context::full(&rt).expect("context creation failed");
         let start = Instant::now();
         let result: Result<(), rquickjs::Error> = ctx.with(|ctx| {
@@ -2239,6 +3244,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_out_of_memory_is_noted() {
+        let limits = ExecutionLimits {
+            timeout_ms: 10_000,
+            max_memory_mb: 8,
+            ..ExecutionLimits::default()
+        };
+        let rt = create_sandboxed_runtime(None, &limits).expect("runtime creation failed");
+        assert_eq!(rt.memory_limit_bytes, 8 * 1024 * 1024);
+        let ctx = Context::full(&rt).expect("context creation failed");
+        ctx.with(|ctx| {
+            let thrown = ctx
+                .eval::<(), _>("throw new Error('plain failure')")
+                .unwrap_err();
+            extract_error_details(&ctx, &thrown);
+            assert!(!OUT_OF_MEMORY.with(Cell::get));
+
+            let oom = ctx
+                .eval::<(), _>("const a = []; while(true) { a.push('x'.repeat(1024 * 1024)); }")
+                .unwrap_err();
+            assert!(extract_error_details(&ctx, &oom).contains("out of memory"));
+        });
+        assert!(OUT_OF_MEMORY.with(Cell::get));
+        drop(ctx);
+        drop(rt);
+        // The runtime takes the flag with it
+        assert!(!OUT_OF_MEMORY.with(Cell::get));
+    }
+
     // Check if we should skip database-dependent tests
     fn should_skip_db_tests() -> bool {
         std::env::var("DATABASE_URL").is_err()
@@ -2295,9 +3329,11 @@ mod tests {
     }
 
     // Shadow execute_graphql_resolver
-    fn execute_graphql_resolver(params: GraphqlResolverExecutionParams) -> Result<String, String> {
+    fn execute_graphql_resolver(
+        params: GraphqlResolverExecutionParams,
+    ) -> Result<String, ResolverError> {
         if should_skip_db_tests() {
-            return Err("Test skipped: DATABASE_URL not set".to_string());
+            return Err("Test skipped: DATABASE_URL not set".to_string().into());
         }
         let rt = get_runtime();
         let _guard = rt.enter();
@@ -2771,7 +3807,7 @@ mod tests {
         let result = execute_graphql_resolver(params);
 
         assert!(result.is_err(), "Should fail when function doesn't exist");
-        assert!(result.unwrap_err().contains("not found"));
+        assert!(result.unwrap_err().to_string().contains("not found"));
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -2803,7 +3839,191 @@ mod tests {
             result.is_err(),
             "Should fail when resolver throws exception"
         );
-        assert!(result.unwrap_err().contains("execution error"));
+        assert!(result.unwrap_err().to_string().contains("execution error"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execute_graphql_resolver_with_client_error() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_content = r#"
+            function orderResolver() {
+                throw new GraphQLError("Order not found", { code: "NOT_FOUND", orderId: "42" });
+            }
+        "#;
+
+        let _ = repository::upsert_script("client-error-resolver", script_content);
+        let params = GraphqlResolverExecutionParams {
+            script_uri: "client-error-resolver".to_string(),
+            resolver_function: "orderResolver".to_string(),
+            field_name: "orderResolver".to_string(),
+            operation_kind: GraphqlOperationKind::Query,
+            args: None,
+            auth_context: None,
+        };
+
+        match execute_graphql_resolver(params) {
+            Err(ResolverError::Client {
+                message,
+                extensions,
+            }) => {
+                assert_eq!(message, "Order not found");
+                assert_eq!(extensions["code"], "NOT_FOUND");
+                assert_eq!(extensions["orderId"], "42");
+            }
+            other => panic!("Expected a client error, got {:?}", other),
+        }
+        let _ = repository::delete_script("client-error-resolver");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execute_graphql_resolver_enforces_script_acl() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let target_uri = "acl-target-script";
+        let _ = repository::delete_script(target_uri);
+        repository::upsert_script_with_owner(target_uri, "// v1", Some("acl-owner"), false)
+            .expect("Should create target script");
+
+        let resolver_content = r#"
+            function editResolver() {
+                return scriptStorage.setScriptLabels("acl-target-script", { tags: ["edited"] });
+            }
+        "#;
+        let _ = repository::upsert_script("acl-resolver", resolver_content);
+        let _ = repository::set_script_privileged("acl-resolver", true);
+
+        let run_as = |user_id: &str| {
+            execute_graphql_resolver(GraphqlResolverExecutionParams {
+                script_uri: "acl-resolver".to_string(),
+                resolver_function: "editResolver".to_string(),
+                field_name: "editResolver".to_string(),
+                operation_kind: GraphqlOperationKind::Mutation,
+                args: None,
+                auth_context: Some(crate::auth::JsAuthContext::authenticated(
+                    user_id.to_string(),
+                    None,
+                    None,
+                    "test".to_string(),
+                    false,
+                    false,
+                )),
+            })
+            .expect("Resolver should execute")
+        };
+
+        // Authenticated non-owners are denied
+        assert!(run_as("acl-stranger").contains("Permission denied"));
+
+        // Collaborators may edit
+        repository::add_script_collaborator(target_uri, "acl-stranger")
+            .expect("Should add collaborator");
+        assert!(run_as("acl-stranger").contains("edited"));
+
+        let _ = repository::delete_script(target_uri);
+        let _ = repository::delete_script("acl-resolver");
+    }
+
+    fn auth_with_role(
+        user_id: &str,
+        is_admin: bool,
+        is_editor: bool,
+    ) -> crate::auth::JsAuthContext {
+        crate::auth::JsAuthContext::authenticated(
+            user_id.to_string(),
+            None,
+            None,
+            "test".to_string(),
+            is_admin,
+            is_editor,
+        )
+    }
+
+    #[test]
+    fn test_resolver_user_by_role() {
+        use crate::security::Capability;
+
+        let admin = resolver_user(Some(&auth_with_role("admin-user", true, false)), true);
+        assert_eq!(admin.user_id.as_deref(), Some("admin-user"));
+        assert!(admin.has_capability(&Capability::DeleteScripts));
+
+        let editor = resolver_user(Some(&auth_with_role("editor-user", false, true)), true);
+        assert_eq!(editor.user_id.as_deref(), Some("editor-user"));
+        assert!(editor.has_capability(&Capability::ManageGraphQL));
+        assert!(editor.has_capability(&Capability::DeleteLogs));
+        assert!(!editor.has_capability(&Capability::DeleteScripts));
+
+        let user = resolver_user(Some(&auth_with_role("plain-user", false, false)), true);
+        assert!(user.is_authenticated);
+        assert!(!user.has_capability(&Capability::ManageGraphQL));
+    }
+
+    #[test]
+    fn test_resolver_user_anonymous() {
+        use crate::security::Capability;
+
+        // With auth enabled, anonymous callers never get admin rights
+        let anonymous = crate::auth::JsAuthContext::anonymous();
+        for auth in [None, Some(&anonymous)] {
+            let user = resolver_user(auth, true);
+            assert!(!user.is_authenticated);
+            assert!(user.user_id.is_none());
+            assert!(!user.has_capability(&Capability::DeleteScripts));
+        }
+
+        // Signed in but without a user id is not an authenticated caller
+        let mut no_id = auth_with_role("x", true, false);
+        no_id.user_id = None;
+        assert!(!resolver_user(Some(&no_id), true).is_authenticated);
+
+        // Only without authentication do resolvers run as admin
+        let user = resolver_user(Some(&anonymous), false);
+        assert_eq!(user.user_id.as_deref(), Some("graphql-resolver"));
+        assert!(user.has_capability(&Capability::DeleteScripts));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execute_graphql_resolver_runs_with_caller_role() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let resolver_content = r#"
+            function resetStatsResolver() {
+                return __resetQueryStats();
+            }
+        "#;
+        let _ = repository::upsert_script("role-resolver", resolver_content);
+        let _ = repository::set_script_privileged("role-resolver", true);
+
+        let run_as = |auth: crate::auth::JsAuthContext| {
+            execute_graphql_resolver(GraphqlResolverExecutionParams {
+                script_uri: "role-resolver".to_string(),
+                resolver_function: "resetStatsResolver".to_string(),
+                field_name: "resetStatsResolver".to_string(),
+                operation_kind: GraphqlOperationKind::Mutation,
+                args: None,
+                auth_context: Some(auth),
+            })
+            .expect("Resolver should execute")
+        };
+
+        // Resetting query stats needs DeleteLogs, which admins and editors have
+        assert!(!run_as(auth_with_role("role-admin", true, false)).contains("Insufficient"));
+        assert!(!run_as(auth_with_role("role-editor", false, true)).contains("Insufficient"));
+        assert!(run_as(auth_with_role("role-user", false, false)).contains("Insufficient"));
+
+        let _ = repository::delete_script("role-resolver");
     }
 
     #[test]
@@ -3113,6 +4333,8 @@ mod tests {
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3147,6 +4369,8 @@ mod tests {
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3182,6 +4406,8 @@ mod tests {
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3228,6 +4454,8 @@ This is **bold** text.`;
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3281,6 +4509,8 @@ This is **bold** text.`;
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3331,6 +4561,8 @@ This is **bold** text.`;
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3381,6 +4613,8 @@ This is **bold** text.`;
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3435,6 +4669,8 @@ This is **bold** text.`;
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3503,6 +4739,8 @@ function hello() {
             route_params: None,
             auth_context: None,
             uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
         };
         let result = execute_script_for_request_secure(params);
 
@@ -3530,60 +4768,33 @@ function hello() {
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_response_builders() {
+    async fn test_markdown_render() {
+        use crate::security::UserContext;
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        let script_content = r#"
-            function testHandler(context) {
-                // Test ResponseBuilder.json
-                const jsonResponse = ResponseBuilder.json({ message: "Hello World", code: 200 });
-                if (jsonResponse.status !== 200 || jsonResponse.contentType !== "application/json") {
-                    throw new Error("JSON response failed");
-                }
-
-                // Test ResponseBuilder.text
-                const textResponse = ResponseBuilder.text("Plain text response", 201);
-                if (textResponse.status !== 201 || textResponse.contentType !== "text/plain; charset=UTF-8") {
-                    throw new Error("Text response failed");
-                }
-
-                // Test ResponseBuilder.html
-                const htmlResponse = ResponseBuilder.html("<h1>Hello</h1>", 200);
-                if (htmlResponse.status !== 200 || htmlResponse.contentType !== "text/html; charset=UTF-8") {
-                    throw new Error("HTML response failed");
-                }
-
-                // Test ResponseBuilder.error
-                const errorResponse = ResponseBuilder.error(400, "Bad Request");
-                if (errorResponse.status !== 400 || !errorResponse.body.includes("Bad Request")) {
-                    throw new Error("Error response failed");
-                }
-
-                // Test ResponseBuilder.noContent
-                const noContentResponse = ResponseBuilder.noContent();
-                if (noContentResponse.status !== 204 || noContentResponse.body !== "") {
-                    throw new Error("No content response failed");
-                }
 
-                // Test ResponseBuilder.redirect
-                const redirectResponse = ResponseBuilder.redirect("https://example.com", 302);
-                if (redirectResponse.status !== 302 || !redirectResponse.headers.Location) {
-                    throw new Error("Redirect response failed");
-                }
-
-                return ResponseBuilder.json({ success: true });
+        let script_content = r#"
+            function testRender(context) {
+                const page = markdown.render("- [x] <i>done</i>\n\n| A |\n|---|\n| 1 |", {
+                    taskLists: true
+                });
+                const invalid = markdown.render("text", "tables");
+                return {
+                    status: 200,
+                    body: JSON.stringify({ page, invalid }),
+                    contentType: "application/json"
+                };
             }
         "#;
 
-        let _ = repository::upsert_script("response-builder-test", script_content);
-
+        let _ = repository::upsert_script("test-markdown-render", script_content);
         let params = RequestExecutionParams {
-            script_uri: "response-builder-test".to_string(),
-            handler_name: "testHandler".to_string(),
+            script_uri: "test-markdown-render".to_string(),
+            handler_name: "testRender".to_string(),
             path: "/test".to_string(),
             method: "GET".to_string(),
             query_params: None,
@@ -3591,136 +4802,1718 @@ function hello() {
             raw_body: None,
             headers: HashMap::new(),
             user_context: UserContext::admin("test".to_string()),
+            route_params: None,
             auth_context: None,
             uploaded_files: None,
-            route_params: None,
+            timeout_ms: None,
+            tenant: None,
         };
-
-        let result = execute_script_for_request_secure(params);
-        if let Err(ref e) = result {
-            eprintln!("Test error: {}", e);
-        }
-        assert!(result.is_ok(), "Response builder test should succeed");
-
-        let response = result.unwrap();
-        assert_eq!(response.status, 200);
-        let body_str = String::from_utf8_lossy(&response.body);
-        assert!(body_str.contains("success"));
-        assert!(body_str.contains("true"));
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        let page = body["page"].as_str().unwrap();
+        assert!(page.contains("checkbox"), "{}", page);
+        assert!(page.contains("&lt;i&gt;done&lt;/i&gt;"), "{}", page);
+        assert!(page.contains("<td>1</td>"), "{}", page);
+        assert_eq!(body["invalid"], "Error: options must be an object");
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_query_object_guarantees() {
+    async fn test_html_sanitize() {
+        use crate::security::UserContext;
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        let script_content = r#"
-            function testHandler(context) {
-                // Test that context.request.query is always available
-                if (!context.request || !context.request.query) {
-                    throw new Error("context.request.query should always be available");
-                }
-
-                // Test that it's an object (even if empty)
-                if (typeof context.request.query !== 'object') {
-                    throw new Error("context.request.query should be an object");
-                }
-
-                // Test that we can safely access properties
-                const param1 = context.request.query.param1 || "default";
-                const param2 = context.request.query.param2 || "default2";
 
-                return ResponseBuilder.json({
-                    param1: param1,
-                    param2: param2,
-                    queryType: typeof context.request.query
-                });
+        let script_content = r#"
+            function testSanitize(context) {
+                const input = '<p onclick="x()">Hi <em>you</em><script>alert(1)</script></p>';
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        basic: html.sanitize(input),
+                        strict: html.sanitize(input, { tags: ["p"] }),
+                        invalid: html.sanitize(input, { tags: ["style"] })
+                    }),
+                    contentType: "application/json"
+                };
             }
         "#;
 
-        let _ = repository::upsert_script("query-guarantees-test", script_content);
-
+        let _ = repository::upsert_script("test-html-sanitize", script_content);
         let params = RequestExecutionParams {
-            script_uri: "query-guarantees-test".to_string(),
-            handler_name: "testHandler".to_string(),
+            script_uri: "test-html-sanitize".to_string(),
+            handler_name: "testSanitize".to_string(),
             path: "/test".to_string(),
             method: "GET".to_string(),
-            query_params: Some(HashMap::from([
-                ("param1".to_string(), "value1".to_string()),
-                ("param2".to_string(), "value2".to_string()),
-            ])),
+            query_params: None,
             form_data: None,
             raw_body: None,
             headers: HashMap::new(),
             user_context: UserContext::admin("test".to_string()),
+            route_params: None,
             auth_context: None,
             uploaded_files: None,
-            route_params: None,
+            timeout_ms: None,
+            tenant: None,
         };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
 
-        let result = execute_script_for_request_secure(params);
-        assert!(result.is_ok(), "Query guarantees test should succeed");
-
-        let response = result.unwrap();
-        assert_eq!(response.status, 200);
-        let body_str = String::from_utf8_lossy(&response.body);
-        assert!(body_str.contains("value1"));
-        assert!(body_str.contains("value2"));
-        assert!(body_str.contains("object"));
+        assert_eq!(body["basic"], "<p>Hi <em>you</em></p>");
+        assert_eq!(body["strict"], "<p>Hi you</p>");
+        assert_eq!(body["invalid"], "Error: <style> elements cannot be allowed");
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_query_object_guarantees_empty_params() {
+    async fn test_ical_build_and_parse() {
+        use crate::security::UserContext;
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        let script_content = r#"
-            function testHandler(context) {
-                // Test that context.request.query is available even with no query params
-                if (!context.request || !context.request.query) {
-                    throw new Error("context.request.query should always be available");
-                }
-
-                // Should be an empty object
-                const keys = Object.keys(context.request.query);
-                if (keys.length !== 0) {
-                    throw new Error("Expected empty query object, got: " + keys.length + " keys");
-                }
 
-                return ResponseBuilder.json({ queryEmpty: true });
+        let script_content = r#"
+            function testIcal(context) {
+                const text = ical.build([{
+                    uid: "standup-1",
+                    summary: "Standup",
+                    start: "2026-01-15T09:00",
+                    end: "2026-01-15T09:15",
+                    timeZone: "Europe/Helsinki",
+                    attendees: [{ email: "ada@example.com", rsvp: true }]
+                }], { method: "request" });
+                const parsed = JSON.parse(ical.parse(text));
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        method: parsed.method,
+                        event: parsed.events[0],
+                        invalid: ical.build([{ start: "soon" }]),
+                        notCalendar: ical.parse("hello")
+                    }),
+                    contentType: "application/json"
+                };
             }
         "#;
 
-        let _ = repository::upsert_script("query-empty-test", script_content);
-
+        let _ = repository::upsert_script("test-ical", script_content);
         let params = RequestExecutionParams {
-            script_uri: "query-empty-test".to_string(),
-            handler_name: "testHandler".to_string(),
+            script_uri: "test-ical".to_string(),
+            handler_name: "testIcal".to_string(),
             path: "/test".to_string(),
             method: "GET".to_string(),
-            query_params: None, // No query params
+            query_params: None,
             form_data: None,
             raw_body: None,
             headers: HashMap::new(),
             user_context: UserContext::admin("test".to_string()),
+            route_params: None,
             auth_context: None,
             uploaded_files: None,
-            route_params: None,
+            timeout_ms: None,
+            tenant: None,
         };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["method"], "REQUEST");
+        assert_eq!(body["event"]["uid"], "standup-1");
+        assert_eq!(body["event"]["start"], "2026-01-15T07:00:00Z");
+        assert_eq!(body["event"]["end"], "2026-01-15T07:15:00Z");
+        assert_eq!(body["event"]["attendees"][0]["email"], "ada@example.com");
+        assert_eq!(body["event"]["attendees"][0]["rsvp"], true);
+        assert_eq!(
+            body["invalid"],
+            "Error: events[0]: 'soon' is not a date or date-time"
+        );
+        assert_eq!(
+            body["notCalendar"],
+            "Error: Input is not an iCalendar document"
+        );
+    }
 
-        let result = execute_script_for_request_secure(params);
-        assert!(result.is_ok(), "Empty query test should succeed");
-
-        let response = result.unwrap();
-        assert_eq!(response.status, 200);
-        let body_str = String::from_utf8_lossy(&response.body);
-        assert!(body_str.contains("queryEmpty"));
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_validate_helpers() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testValidate(context) {
+                const result = JSON.parse(validate.object(
+                    { email: "nope", tags: ["a", 2], address: {} },
+                    {
+                        email: { type: "email", required: true },
+                        name: { type: "string", required: true },
+                        tags: { items: { type: "string" } },
+                        address: { fields: { city: { required: true } } }
+                    }
+                ));
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        checks: [
+                            validate.email("ada@example.com"),
+                            validate.email("ada@"),
+                            validate.url("https://example.com/a"),
+                            validate.url("javascript:alert(1)"),
+                            validate.uuid("67e55044-10b1-426f-9247-bb680e5fe0c8"),
+                            validate.uuid(42),
+                            validate.length("héllo", 1, 5),
+                            validate.length([1, 2, 3], 4),
+                            validate.oneOf("b", ["a", "b"]),
+                            validate.oneOf({ a: 1 }, [{ a: 2 }])
+                        ],
+                        result,
+                        badRules: validate.object({}, { name: { type: "date" } })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-validate", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-validate".to_string(),
+            handler_name: "testValidate".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(
+            body["checks"],
+            serde_json::json!([
+                true, false, true, false, true, false, true, false, true, false
+            ])
+        );
+        assert_eq!(body["result"]["valid"], false);
+        let messages: Vec<&str> = body["result"]["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["message"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "address.city is required",
+                "email must be a valid email address",
+                "name is required",
+                "tags[1] must be a string",
+            ]
+        );
+        assert!(
+            body["badRules"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid rules")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_llm_complete_checks_params() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testLlm(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        denied: llm.complete("openai", { model: "gpt-4o-mini", prompt: "Hi" }),
+                        unknown: llm.complete("nope", { model: "x", prompt: "Hi" }),
+                        badParams: llm.complete("openai", { model: "x", prompts: "Hi" }),
+                        streamNoProvider: llm.stream({ model: "x", prompt: "Hi" }, "/chat"),
+                        streamDenied: llm.stream({ provider: "openai", model: "x", prompt: "Hi" }, "/chat")
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-llm", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-llm".to_string(),
+            handler_name: "testLlm".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(
+            body["denied"],
+            "Error: Model 'gpt-4o-mini' is not allowed for this script"
+        );
+        assert_eq!(body["unknown"], "Error: Unknown LLM provider 'nope'");
+        assert!(
+            body["badParams"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid params: unknown field `prompts`")
+        );
+        assert_eq!(
+            body["streamNoProvider"],
+            "Error: params.provider is required"
+        );
+        // Anonymous callers may not send to streams
+        assert!(
+            body["streamDenied"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Insufficient capabilities")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_embeddings_and_vectors_check_params() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testEmbeddings(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        badInput: embeddings.create(42),
+                        denied: embeddings.create("Hi", { model: "text-embedding-3-large" }),
+                        anthropic: embeddings.create(["Hi"], { provider: "anthropic" }),
+                        search: JSON.parse(vectors.search("docs", [1, 0])),
+                        moderationOff: moderation.check("Hi"),
+                        moderationBadAction: moderation.check("Hi", { action: "ban" })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-embeddings", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-embeddings".to_string(),
+            handler_name: "testEmbeddings".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(
+            body["badInput"],
+            "Error: input must be a string or an array of strings"
+        );
+        assert_eq!(
+            body["denied"],
+            "Error: Model 'text-embedding-3-large' is not allowed for this script"
+        );
+        assert_eq!(
+            body["anthropic"],
+            "Error: Provider 'anthropic' has no embeddings API"
+        );
+        // Anonymous callers may not use the script's database
+        assert_eq!(
+            body["search"]["error"],
+            "Insufficient permissions for database operations"
+        );
+        assert_eq!(body["moderationOff"], "Error: Moderation is not configured");
+        assert!(
+            body["moderationBadAction"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: ")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prompts_save_and_render() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testPrompts(context) {
+                const definition = {
+                    template: "Summarize{{#if words}} in {{words}} words{{/if}}: {{text}}",
+                    description: "Summarize a text",
+                    variables: [{ name: "text", required: true }, { name: "words" }]
+                };
+                const first = JSON.parse(prompts.save("test.summarize", definition));
+                const same = JSON.parse(prompts.save("test.summarize", definition));
+                const changed = JSON.parse(prompts.save("test.summarize", {
+                    ...definition,
+                    template: "Summary of <{{text}}>"
+                }));
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        first: first.version,
+                        same: same.version,
+                        changed: changed.version,
+                        latest: prompts.render("test.summarize", { text: "a & b" }),
+                        previous: prompts.render("test.summarize", { text: "a", words: 5 }, first.version),
+                        fetched: JSON.parse(prompts.get("test.summarize")).version,
+                        listed: JSON.parse(prompts.list()).some((p) => p.name === "test.summarize"),
+                        missing: prompts.render("test.summarize", {}),
+                        unknown: prompts.render("test.no-such-prompt"),
+                        badName: prompts.save("Test Prompt", definition)
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-prompts", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-prompts".to_string(),
+            handler_name: "testPrompts".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("prompt-admin".to_string()),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        let first = body["first"].as_i64().unwrap();
+        assert_eq!(body["same"].as_i64(), Some(first));
+        assert_eq!(body["changed"].as_i64(), Some(first + 1));
+        assert_eq!(body["fetched"].as_i64(), Some(first + 1));
+        assert_eq!(body["latest"], "Summary of <a & b>");
+        assert_eq!(body["previous"], "Summarize in 5 words: a");
+        assert_eq!(body["listed"], true);
+        assert_eq!(
+            body["missing"],
+            "Error: Prompt 'test.summarize' is missing required variables: text"
+        );
+        assert_eq!(
+            body["unknown"],
+            "Error: Prompt 'test.no-such-prompt' not found"
+        );
+        assert!(
+            body["badName"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid prompt name 'Test Prompt'")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_webhooks_send_and_get() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testWebhooks(context) {
+                const sent = JSON.parse(
+                    webhooks.send("https://hooks.example.com/orders", { orderId: 42 }, { maxAttempts: 1 })
+                );
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        sent: sent,
+                        fetched: JSON.parse(webhooks.get(sent.id)),
+                        listed: JSON.parse(webhooks.list({ status: "pending" })).some((d) => d.id === sent.id),
+                        unknown: webhooks.get("not-an-id"),
+                        badScheme: webhooks.send("ftp://hooks.example.com", "ping"),
+                        reserved: webhooks.send("https://hooks.example.com", "ping", {
+                            headers: { "X-Webhook-Id": "mine" }
+                        }),
+                        noSecret: webhooks.send("https://hooks.example.com", "ping", { secret: "no_such_secret" }),
+                        otherScript: webhooks.list({ scriptUri: "https://example.com/other" })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-webhooks", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-webhooks".to_string(),
+            handler_name: "testWebhooks".to_string(),
+            path: "/test".to_string(),
+            method: "POST".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["sent"]["status"], "pending");
+        assert_eq!(body["sent"]["maxAttempts"], 1);
+        assert_eq!(body["sent"]["scriptUri"], "test-webhooks");
+        assert_eq!(body["fetched"]["id"], body["sent"]["id"]);
+        assert_eq!(body["listed"], true);
+        assert_eq!(body["unknown"], "null");
+        assert_eq!(
+            body["badScheme"],
+            "Error: Webhook URLs must use http or https"
+        );
+        assert_eq!(
+            body["reserved"],
+            "Error: Header 'X-Webhook-Id' is set by the engine"
+        );
+        assert_eq!(
+            body["noSecret"],
+            "Error: Secret 'no_such_secret' is not set"
+        );
+        assert_eq!(
+            body["otherScript"],
+            "Error: Scripts may only list their own deliveries"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_events_publish_and_subscribe() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let subscriber = r#"
+            function init() {
+                events.subscribe("test-events.ping", "onPing");
+            }
+            function onPing(context) {
+                sharedStorage.setItem("lastEvent", JSON.stringify(context.meta.event));
+            }
+        "#;
+        let _ = repository::upsert_script("test-events-subscriber", subscriber);
+        repository::remove_script_properties_item("test-events-subscriber", "lastEvent");
+        call_init_if_exists(
+            "test-events-subscriber",
+            subscriber,
+            crate::script_init::InitContext::new("test-events-subscriber".to_string(), false),
+        )
+        .expect("init runs");
+
+        let publisher = r#"
+            function testEvents(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        published: JSON.parse(events.publish("test-events.ping", { n: 7 })),
+                        unsubscribed: JSON.parse(events.publish("test-events.nobody")),
+                        badTopic: events.publish("Test Events"),
+                        outsideInit: events.subscribe("test-events.ping", "onPing")
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+        let _ = repository::upsert_script("test-events-publisher", publisher);
+        let params = RequestExecutionParams {
+            script_uri: "test-events-publisher".to_string(),
+            handler_name: "testEvents".to_string(),
+            path: "/test".to_string(),
+            method: "POST".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["published"]["topic"], "test-events.ping");
+        assert_eq!(body["published"]["subscribers"], 1);
+        assert_eq!(body["unsubscribed"]["subscribers"], 0);
+        assert!(
+            body["badTopic"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid topic 'Test Events'")
+        );
+        assert_eq!(
+            body["outsideInit"],
+            "Error: events.subscribe can only be called from init()"
+        );
+
+        let mut delivered = None;
+        for _ in 0..50 {
+            delivered =
+                repository::get_script_properties_item("test-events-subscriber", "lastEvent");
+            if delivered.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let event: JsonValue = serde_json::from_str(&delivered.expect("event delivered")).unwrap();
+        assert_eq!(event["id"], body["published"]["id"]);
+        assert_eq!(event["payload"]["n"], 7);
+        assert_eq!(event["source"], "test-events-publisher");
+        assert_eq!(event["depth"], 1);
+
+        crate::events::clear_script_subscriptions("test-events-subscriber");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tasks_enqueue_and_get() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testTasks(context) {
+                const queued = JSON.parse(
+                    tasks.enqueue("buildReport", { reportId: 42 }, { priority: 10, delayMs: 60000, maxAttempts: 2 })
+                );
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        queued: queued,
+                        fetched: JSON.parse(tasks.get(queued.id)),
+                        listed: JSON.parse(tasks.list({ status: "pending" })).some((t) => t.id === queued.id),
+                        unknown: tasks.get("not-an-id"),
+                        once: [1, 2].map(() =>
+                            JSON.parse(
+                                tasks.enqueue("buildReport", { reportId: 7 }, {
+                                    runAfter: "24h",
+                                    idempotencyKey: "report-7"
+                                })
+                            )
+                        ),
+                        badRunAfter: tasks.enqueue("buildReport", null, { runAfter: "soon" }),
+                        noHandler: tasks.enqueue(" "),
+                        badPriority: tasks.enqueue("buildReport", null, { priority: 5000 }),
+                        bothTimes: tasks.enqueue("buildReport", null, {
+                            delayMs: 1000,
+                            runAt: "2030-01-01T00:00:00Z"
+                        }),
+                        otherScript: tasks.list({ scriptUri: "https://example.com/other" })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+            function buildReport(context) {}
+        "#;
+
+        let _ = repository::upsert_script("test-tasks", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-tasks".to_string(),
+            handler_name: "testTasks".to_string(),
+            path: "/test".to_string(),
+            method: "POST".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["queued"]["status"], "pending");
+        assert_eq!(body["queued"]["priority"], 10);
+        assert_eq!(body["queued"]["maxAttempts"], 2);
+        assert_eq!(body["queued"]["payload"]["reportId"], 42);
+        assert_eq!(body["fetched"]["id"], body["queued"]["id"]);
+        assert_eq!(body["listed"], true);
+        assert_eq!(body["unknown"], "null");
+        assert_eq!(body["queued"]["duplicate"], false);
+        assert_eq!(body["once"][0]["id"], body["once"][1]["id"]);
+        assert_eq!(body["once"][1]["duplicate"], true);
+        assert_eq!(body["once"][0]["idempotencyKey"], "report-7");
+        assert_eq!(
+            body["badRunAfter"],
+            "Error: Invalid runAfter 'soon': use a duration such as '30s', '15m', '24h' or '1d12h'"
+        );
+        assert_eq!(
+            body["noHandler"],
+            "Error: tasks.enqueue requires a non-empty handler name"
+        );
+        assert_eq!(
+            body["badPriority"],
+            "Error: priority must be between -1000 and 1000"
+        );
+        assert_eq!(
+            body["bothTimes"],
+            "Error: Use only one of runAt, runAfter and delayMs"
+        );
+        assert_eq!(
+            body["otherScript"],
+            "Error: Scripts may only list their own tasks"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_email_send_and_list() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        crate::email::configure(&crate::config::EmailConfig {
+            provider: crate::config::EmailProvider::Log,
+            from: "Example <noreply@example.com>".to_string(),
+            script_daily_limits: [("test-email".to_string(), 1000)].into(),
+            daily_limit: 1,
+            ..crate::config::EmailConfig::default()
+        });
+
+        let script_content = r#"
+            function testEmail(context) {
+                const sent = JSON.parse(
+                    email.send({
+                        to: ["Ann <ann@example.com>"],
+                        subject: "Welcome",
+                        text: "Hello Ann",
+                        attachments: [{ filename: "note.txt", content: "hi" }]
+                    })
+                );
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        sent: sent,
+                        listed: JSON.parse(email.list({ status: "sent" })).some((e) => e.id === sent.id),
+                        badAddress: email.send({ to: "ann", subject: "Hi", text: "x" }),
+                        noTemplate: email.send({ to: "ann@example.com", subject: "Hi", template: "missing.hbs" }),
+                        otherScript: email.list({ scriptUri: "https://example.com/other" })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+        let quota_script = r#"
+            function testQuota(context) {
+                const message = { to: "ann@example.com", subject: "Hi", text: "x" };
+                email.send(message);
+                return { status: 200, body: email.send(message), contentType: "text/plain" };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-email", script_content);
+        let _ = repository::upsert_script("test-email-quota", quota_script);
+        let params = |script_uri: &str, handler_name: &str| RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: handler_name.to_string(),
+            path: "/test".to_string(),
+            method: "POST".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params("test-email", "testEmail"))
+            .expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+        let quota = execute_script_for_request_secure(params("test-email-quota", "testQuota"))
+            .expect("handler runs");
+        crate::email::configure(&crate::config::EmailConfig::default());
+
+        assert_eq!(body["sent"]["status"], "sent");
+        assert_eq!(body["sent"]["recipients"][0], "ann@example.com");
+        assert!(
+            body["sent"]["messageId"]
+                .as_str()
+                .unwrap()
+                .ends_with("@example.com>")
+        );
+        assert_eq!(body["listed"], true);
+        assert!(
+            body["badAddress"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid to address 'ann'")
+        );
+        assert_eq!(
+            body["noTemplate"],
+            "Error: Template 'missing.hbs' not found"
+        );
+        assert_eq!(
+            body["otherScript"],
+            "Error: Scripts may only list their own emails"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&quota.body),
+            "Error: Daily email limit (1) reached for this script"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_notify_subscribe_push_and_sms() {
+        use crate::security::UserContext;
+        use base64::Engine;
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let vapid_key = URL_SAFE_NO_PAD.encode([5u8; 32]);
+        crate::notify::configure(&crate::config::NotifyConfig {
+            vapid_private_key: Some(vapid_key.clone()),
+            sms: crate::config::SmsConfig {
+                provider: crate::config::SmsProviderKind::Log,
+                from: "+15550100".to_string(),
+                ..crate::config::SmsConfig::default()
+            },
+            ..crate::config::NotifyConfig::default()
+        });
+        let browser_key = p256::SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let p256dh =
+            URL_SAFE_NO_PAD.encode(browser_key.public_key().to_encoded_point(false).as_bytes());
+
+        let script_content = format!(
+            r#"
+            function testNotify(context) {{
+                const subscription = {{
+                    endpoint: "https://push.example.com/send/test-notify",
+                    expirationTime: null,
+                    keys: {{ p256dh: "{p256dh}", auth: "AwMDAwMDAwMDAwMDAwMDAw" }}
+                }};
+                const stored = JSON.parse(notify.subscribe(subscription));
+                return {{
+                    status: 200,
+                    body: JSON.stringify({{
+                        stored: stored,
+                        publicKey: notify.vapidPublicKey(),
+                        badKeys: notify.subscribe({{ endpoint: subscription.endpoint, keys: {{ p256dh: "x", auth: "y" }} }}),
+                        noTarget: notify.push({{}}, "hi"),
+                        unknown: JSON.parse(notify.push({{ subscriptionId: "{unknown}" }}, {{ title: "Hi" }})),
+                        removed: notify.unsubscribe(stored.id),
+                        sms: JSON.parse(notify.sms("+358401234567", "Your code is 1234")),
+                        badNumber: notify.sms("0401234567", "hi")
+                    }}),
+                    contentType: "application/json"
+                }};
+            }}
+            function testAnonymous(context) {{
+                return {{ status: 200, body: notify.subscribe({{}}), contentType: "text/plain" }};
+            }}
+        "#,
+            unknown = uuid::Uuid::new_v4()
+        );
+
+        let _ = repository::upsert_script("test-notify", &script_content);
+        let params = |handler_name: &str, user_context: UserContext| RequestExecutionParams {
+            script_uri: "test-notify".to_string(),
+            handler_name: handler_name.to_string(),
+            path: "/test".to_string(),
+            method: "POST".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context,
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params(
+            "testNotify",
+            UserContext::authenticated("notify-user".to_string()),
+        ))
+        .expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+        let anonymous =
+            execute_script_for_request_secure(params("testAnonymous", UserContext::anonymous()))
+                .expect("handler runs");
+        crate::notify::configure(&crate::config::NotifyConfig::default());
+
+        assert_eq!(body["stored"]["userId"], "notify-user");
+        assert_eq!(body["stored"]["p256dh"], p256dh.as_str());
+        assert_eq!(
+            body["publicKey"],
+            crate::notify::vapid_public_key(&vapid_key)
+                .unwrap()
+                .as_str()
+        );
+        assert_eq!(
+            body["badKeys"],
+            "Error: Subscription keys.p256dh must be a base64url P-256 public key"
+        );
+        assert_eq!(
+            body["noTarget"],
+            "Error: Push target needs either userId or subscriptionId"
+        );
+        assert_eq!(body["unknown"]["sent"], 0);
+        assert_eq!(body["removed"], "true");
+        assert_eq!(body["sms"]["provider"], "log");
+        assert_eq!(body["sms"]["to"], "+358401234567");
+        assert!(
+            body["badNumber"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid phone number '0401234567'")
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&anonymous.body),
+            "Error: notify.subscribe requires a signed-in user"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cache_get_set_delete() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testCache(context) {
+                const before = cache.get("profile:1");
+                const stored = cache.set("profile:1", JSON.stringify({ name: "Ada" }), { ttlSeconds: 60 });
+                const after = JSON.parse(cache.get("profile:1"));
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        before,
+                        stored,
+                        after,
+                        badTtl: cache.set("profile:2", "x", { ttlSeconds: 0 }),
+                        deleted: cache.delete("profile:1"),
+                        deletedAgain: cache.delete("profile:1"),
+                        gone: cache.get("profile:1")
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-cache", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-cache".to_string(),
+            handler_name: "testCache".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "before": null,
+                "stored": "Value cached",
+                "after": { "name": "Ada" },
+                "badTtl": "Error: ttlSeconds must be a positive number",
+                "deleted": true,
+                "deletedAgain": false,
+                "gone": null
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_admin_operations_require_admin() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testAdmin(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        status: admin.maintenanceStatus(),
+                        drained: admin.drainStreams("/no-such-stream"),
+                        badPath: admin.drainStreams("no-slash"),
+                        badOptions: admin.setMaintenanceMode({ enabled: true, colour: "red" }),
+                        missing: admin.reloadScript("test-admin-ops-missing")
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+        let _ = repository::upsert_script("test-admin-ops", script_content);
+        let params = |user_context| RequestExecutionParams {
+            script_uri: "test-admin-ops".to_string(),
+            handler_name: "testAdmin".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context,
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let response =
+            execute_script_for_request_secure(params(UserContext::admin("admin-ops".to_string())))
+                .expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+        let status: JsonValue = serde_json::from_str(body["status"].as_str().unwrap()).unwrap();
+        assert_eq!(status["enabled"], false);
+        assert_eq!(body["drained"], "0");
+        assert_eq!(body["badPath"], "Error: Path must start with '/'");
+        assert!(
+            body["badOptions"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid options")
+        );
+        assert_eq!(
+            body["missing"],
+            "Error: Script 'test-admin-ops-missing' not found"
+        );
+
+        let response = execute_script_for_request_secure(params(UserContext::anonymous()))
+            .expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+        assert!(body["status"].as_str().unwrap().starts_with("Error:"));
+        assert!(body["drained"].as_str().unwrap().starts_with("Error:"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_vector_search_checks_arguments() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testSearch(context) {
+                const search = (...args) => JSON.parse(vectors.search("docs", [1, 0], ...args)).error;
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        zeroK: search(0),
+                        badFilter: search(5, "lang"),
+                        badOffset: search(5, { lang: "en" }, 1.5),
+                        badOption: search({ page: 2 }),
+                        badQuery: JSON.parse(vectors.search("docs", { text: "hi" })).error
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-vector-search", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-vector-search".to_string(),
+            handler_name: "testSearch".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("vector-admin".to_string()),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["zeroK"], "k must be an integer of at least 1");
+        assert_eq!(body["badFilter"], "filter must be an object");
+        assert_eq!(body["badOffset"], "offset must be an integer of at least 0");
+        assert!(
+            body["badOption"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid options: unknown field `page`")
+        );
+        assert_eq!(
+            body["badQuery"],
+            "query must be a string or an array of numbers"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pdf_from_html() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_content = r#"
+            function testPdf(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        pdf: pdf.fromHtml("<h1>Invoice</h1><p>Total: 10</p>", {
+                            pageSize: "letter",
+                            title: "Invoice"
+                        }),
+                        invalid: pdf.fromHtml("<p>x</p>", { colour: "red" })
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+
+        let _ = repository::upsert_script("test-pdf-from-html", script_content);
+        let params = RequestExecutionParams {
+            script_uri: "test-pdf-from-html".to_string(),
+            handler_name: "testPdf".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        let pdf = base64::Engine::decode(
+            &base64::engine::general_purpose::STANDARD,
+            body["pdf"].as_str().unwrap(),
+        )
+        .unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(
+            body["invalid"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error: Invalid options: unknown field `colour`")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_templates_render() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_uri = "test-templates-render";
+        let script_content = r#"
+            function testTemplate(context) {
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        page: templates.render("templates/greeting.hbs", {
+                            name: "<Ada>",
+                            tags: ["x", "y"]
+                        }),
+                        missing: templates.render("templates/missing.hbs", {})
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+        repository::upsert_script(script_uri, script_content).unwrap();
+        let now = std::time::SystemTime::now();
+        repository::upsert_asset(repository::Asset {
+            uri: "templates/greeting.hbs".to_string(),
+            name: None,
+            mimetype: "text/x-handlebars-template".to_string(),
+            content: b"Hello {{name}}{{#each tags}} #{{this}}{{/each}}".to_vec(),
+            created_at: now,
+            updated_at: now,
+            script_uri: script_uri.to_string(),
+            headers: HashMap::new(),
+        })
+        .unwrap();
+
+        let params = RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: "testTemplate".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["page"], "Hello &lt;Ada&gt; #x #y");
+        assert_eq!(
+            body["missing"],
+            "Error: Template 'templates/missing.hbs' not found"
+        );
+        repository::delete_asset(script_uri, "templates/greeting.hbs");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_images_transform() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_uri = "test-images-transform";
+        let script_content = r#"
+            function testImage(context) {
+                const thumb = JSON.parse(images.transform("photo.png", {
+                    width: 20,
+                    format: "jpeg"
+                }));
+                return {
+                    status: 200,
+                    body: JSON.stringify({
+                        contentType: thumb.contentType,
+                        size: thumb.bodyBase64.length > 0,
+                        invalid: images.transform("photo.png", { width: 0 }),
+                        notImage: images.transform("notes.txt")
+                    }),
+                    contentType: "application/json"
+                };
+            }
+        "#;
+        repository::upsert_script(script_uri, script_content).unwrap();
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::new_rgb8(40, 20)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, mimetype, content) in [
+            ("photo.png", "image/png", png.into_inner()),
+            ("notes.txt", "text/plain", b"hi".to_vec()),
+        ] {
+            repository::upsert_asset(repository::Asset {
+                uri: name.to_string(),
+                name: None,
+                mimetype: mimetype.to_string(),
+                content,
+                created_at: now,
+                updated_at: now,
+                script_uri: script_uri.to_string(),
+                headers: HashMap::new(),
+            })
+            .unwrap();
+        }
+
+        let params = RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: "testImage".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::anonymous(),
+            route_params: None,
+            auth_context: None,
+            uploaded_files: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let response = execute_script_for_request_secure(params).expect("handler runs");
+        let body: JsonValue = serde_json::from_slice(&response.body).unwrap();
+
+        assert_eq!(body["contentType"], "image/jpeg");
+        assert_eq!(body["size"], true);
+        assert_eq!(body["invalid"], "Error: width must be between 1 and 4096");
+        assert_eq!(
+            body["notImage"],
+            "Error: Asset 'notes.txt' is not a supported image (text/plain)"
+        );
+        repository::delete_asset(script_uri, "photo.png");
+        repository::delete_asset(script_uri, "notes.txt");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_response_builders() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_content = r#"
+            function testHandler(context) {
+                // Test ResponseBuilder.json
+                const jsonResponse = ResponseBuilder.json({ message: "Hello World", code: 200 });
+                if (jsonResponse.status !== 200 || jsonResponse.contentType !== "application/json") {
+                    throw new Error("JSON response failed");
+                }
+
+                // Test ResponseBuilder.text
+                const textResponse = ResponseBuilder.text("Plain text response", 201);
+                if (textResponse.status !== 201 || textResponse.contentType !== "text/plain; charset=UTF-8") {
+                    throw new Error("Text response failed");
+                }
+
+                // Test ResponseBuilder.html
+                const htmlResponse = ResponseBuilder.html("<h1>Hello</h1>", 200);
+                if (htmlResponse.status !== 200 || htmlResponse.contentType !== "text/html; charset=UTF-8") {
+                    throw new Error("HTML response failed");
+                }
+
+                // Test ResponseBuilder.error
+                const errorResponse = ResponseBuilder.error(400, "Bad Request");
+                if (errorResponse.status !== 400 || !errorResponse.body.includes("Bad Request")) {
+                    throw new Error("Error response failed");
+                }
+
+                // Test ResponseBuilder.noContent
+                const noContentResponse = ResponseBuilder.noContent();
+                if (noContentResponse.status !== 204 || noContentResponse.body !== "") {
+                    throw new Error("No content response failed");
+                }
+
+                // Test ResponseBuilder.redirect
+                const redirectResponse = ResponseBuilder.redirect("https://example.com", 302);
+                if (redirectResponse.status !== 302 || !redirectResponse.headers.Location) {
+                    throw new Error("Redirect response failed");
+                }
+
+                return ResponseBuilder.json({ success: true });
+            }
+        "#;
+
+        let _ = repository::upsert_script("response-builder-test", script_content);
+
+        let params = RequestExecutionParams {
+            script_uri: "response-builder-test".to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let result = execute_script_for_request_secure(params);
+        if let Err(ref e) = result {
+            eprintln!("Test error: {}", e);
+        }
+        assert!(result.is_ok(), "Response builder test should succeed");
+
+        let response = result.unwrap();
+        assert_eq!(response.status, 200);
+        let body_str = String::from_utf8_lossy(&response.body);
+        assert!(body_str.contains("success"));
+        assert!(body_str.contains("true"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_content_negotiation() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_content = r#"
+            function report(context) {
+                if (context.request.query.check) {
+                    return ResponseBuilder.json({
+                        html: context.request.accepts("html"),
+                        png: context.request.accepts("image/png"),
+                        preferred: context.request.preferredType(["json", "csv"])
+                    });
+                }
+                return ResponseBuilder.negotiate(context.request, {
+                    json: () => ({ total: 3 }),
+                    csv: () => "total\n3",
+                    html: "<p>3</p>"
+                });
+            }
+        "#;
+        let _ = repository::upsert_script("content-negotiation-test", script_content);
+
+        let run = |accept: &str, query: Option<&str>| {
+            let params = RequestExecutionParams {
+                script_uri: "content-negotiation-test".to_string(),
+                handler_name: "report".to_string(),
+                path: "/report".to_string(),
+                method: "GET".to_string(),
+                query_params: query.map(|key| HashMap::from([(key.to_string(), "1".to_string())])),
+                form_data: None,
+                raw_body: None,
+                headers: HashMap::from([("accept".to_string(), accept.to_string())]),
+                user_context: UserContext::anonymous(),
+                auth_context: None,
+                uploaded_files: None,
+                route_params: None,
+                timeout_ms: None,
+                tenant: None,
+            };
+            execute_script_for_request_secure(params).expect("handler runs")
+        };
+
+        let csv = run("text/csv, application/json;q=0.5", None);
+        assert_eq!(csv.content_type.as_deref(), Some("text/csv; charset=UTF-8"));
+        assert_eq!(csv.body, b"total\n3");
+        assert_eq!(csv.headers.get("Vary").map(String::as_str), Some("Accept"));
+
+        let json = run("*/*", None);
+        assert_eq!(json.content_type.as_deref(), Some("application/json"));
+        assert_eq!(json.body, br#"{"total":3}"#);
+
+        let rejected = run("image/png", None);
+        assert_eq!(rejected.status, 406);
+
+        let checks = run("text/html, text/csv;q=0.9", Some("check"));
+        let body: JsonValue = serde_json::from_slice(&checks.body).unwrap();
+        assert_eq!(body["html"], true);
+        assert_eq!(body["png"], false);
+        assert_eq!(body["preferred"], "csv");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_i18n_translate() {
+        use crate::security::UserContext;
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+
+        let script_uri = "test-i18n-translate";
+        let script_content = r#"
+            function greet(context) {
+                if (context.request.query.as) {
+                    i18n.setLocale(context.request.query.as);
+                }
+                return ResponseBuilder.json({
+                    locale: i18n.locale(),
+                    hello: i18n.t("greeting", { name: "Ada" }),
+                    items: i18n.t("cart.items", { count: 2 }),
+                    home: i18n.t("nav.home"),
+                    missing: i18n.t("nav.missing")
+                });
+            }
+        "#;
+        repository::upsert_script(script_uri, script_content).unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, content) in [
+            (
+                "i18n/en.json",
+                r#"{"greeting": "Hello, {name}!", "nav": {"home": "Home"},
+                    "cart": {"items": {"one": "{count} item", "other": "{count} items"}}}"#,
+            ),
+            (
+                "i18n/fi.json",
+                r#"{"greeting": "Hei, {name}!",
+                    "cart": {"items": {"one": "{count} tuote", "other": "{count} tuotetta"}}}"#,
+            ),
+        ] {
+            repository::upsert_asset(repository::Asset {
+                uri: name.to_string(),
+                name: None,
+                mimetype: "application/json".to_string(),
+                content: content.as_bytes().to_vec(),
+                created_at: now,
+                updated_at: now,
+                script_uri: script_uri.to_string(),
+                headers: HashMap::new(),
+            })
+            .unwrap();
+        }
+
+        let run = |accept_language: Option<&str>, locale: Option<&str>| {
+            let params = RequestExecutionParams {
+                script_uri: script_uri.to_string(),
+                handler_name: "greet".to_string(),
+                path: "/greet".to_string(),
+                method: "GET".to_string(),
+                query_params: locale
+                    .map(|locale| HashMap::from([("as".to_string(), locale.to_string())])),
+                form_data: None,
+                raw_body: None,
+                headers: accept_language
+                    .map(|value| {
+                        HashMap::from([("accept-language".to_string(), value.to_string())])
+                    })
+                    .unwrap_or_default(),
+                user_context: UserContext::anonymous(),
+                auth_context: None,
+                uploaded_files: None,
+                route_params: None,
+                timeout_ms: None,
+                tenant: None,
+            };
+            let response = execute_script_for_request_secure(params).expect("handler runs");
+            serde_json::from_slice::<JsonValue>(&response.body).unwrap()
+        };
+
+        // fi-FI falls back to fi, and keys fi lacks to the default locale
+        let finnish = run(Some("fi-FI, en;q=0.5"), None);
+        assert_eq!(finnish["locale"], "fi");
+        assert_eq!(finnish["hello"], "Hei, Ada!");
+        assert_eq!(finnish["items"], "2 tuotetta");
+        assert_eq!(finnish["home"], "Home");
+        assert_eq!(finnish["missing"], "nav.missing");
+
+        let default = run(None, None);
+        assert_eq!(default["locale"], "en");
+        assert_eq!(default["hello"], "Hello, Ada!");
+
+        let chosen = run(Some("en"), Some("fi"));
+        assert_eq!(chosen["hello"], "Hei, Ada!");
+
+        repository::delete_asset(script_uri, "i18n/en.json");
+        repository::delete_asset(script_uri, "i18n/fi.json");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_object_guarantees() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_content = r#"
+            function testHandler(context) {
+                // Test that context.request.query is always available
+                if (!context.request || !context.request.query) {
+                    throw new Error("context.request.query should always be available");
+                }
+
+                // Test that it's an object (even if empty)
+                if (typeof context.request.query !== 'object') {
+                    throw new Error("context.request.query should be an object");
+                }
+
+                // Test that we can safely access properties
+                const param1 = context.request.query.param1 || "default";
+                const param2 = context.request.query.param2 || "default2";
+
+                return ResponseBuilder.json({
+                    param1: param1,
+                    param2: param2,
+                    queryType: typeof context.request.query
+                });
+            }
+        "#;
+
+        let _ = repository::upsert_script("query-guarantees-test", script_content);
+
+        let params = RequestExecutionParams {
+            script_uri: "query-guarantees-test".to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: Some(HashMap::from([
+                ("param1".to_string(), "value1".to_string()),
+                ("param2".to_string(), "value2".to_string()),
+            ])),
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let result = execute_script_for_request_secure(params);
+        assert!(result.is_ok(), "Query guarantees test should succeed");
+
+        let response = result.unwrap();
+        assert_eq!(response.status, 200);
+        let body_str = String::from_utf8_lossy(&response.body);
+        assert!(body_str.contains("value1"));
+        assert!(body_str.contains("value2"));
+        assert!(body_str.contains("object"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_query_object_guarantees_empty_params() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_content = r#"
+            function testHandler(context) {
+                // Test that context.request.query is available even with no query params
+                if (!context.request || !context.request.query) {
+                    throw new Error("context.request.query should always be available");
+                }
+
+                // Should be an empty object
+                const keys = Object.keys(context.request.query);
+                if (keys.length !== 0) {
+                    throw new Error("Expected empty query object, got: " + keys.length + " keys");
+                }
+
+                return ResponseBuilder.json({ queryEmpty: true });
+            }
+        "#;
+
+        let _ = repository::upsert_script("query-empty-test", script_content);
+
+        let params = RequestExecutionParams {
+            script_uri: "query-empty-test".to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None, // No query params
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let result = execute_script_for_request_secure(params);
+        assert!(result.is_ok(), "Empty query test should succeed");
+
+        let response = result.unwrap();
+        assert_eq!(response.status, 200);
+        let body_str = String::from_utf8_lossy(&response.body);
+        assert!(body_str.contains("queryEmpty"));
         assert!(body_str.contains("true"));
     }
     #[tokio::test(flavor = "multi_thread")]
@@ -3772,6 +6565,8 @@ function hello() {
                 ("userId".to_string(), "123".to_string()),
                 ("postId".to_string(), "456".to_string()),
             ])),
+            timeout_ms: None,
+            tenant: None,
         };
 
         let result = execute_script_for_request_secure(params);
@@ -3882,6 +6677,8 @@ function hello() {
             auth_context: None,
             uploaded_files: None,
             route_params: Some(HashMap::from([("userId".to_string(), "123".to_string())])),
+            timeout_ms: None,
+            tenant: None,
         };
 
         let result = execute_script_for_request_secure(params);
@@ -3894,4 +6691,305 @@ function hello() {
         assert!(body_str.contains("value1"));
         assert!(body_str.contains("123"));
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_handler_exception_is_source_mapped() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        // Type declarations vanish in transpilation, so the generated line of
+        // the throw differs from its line in the source
+        let script_content = r#"interface Order {
+    id: string;
+    total: number;
+}
+
+type Handler = (context: unknown) => unknown;
+
+function testHandler(context: unknown) {
+    const order: Order = { id: "o-1", total: 0 };
+    throw new TypeError("bad order " + order.id);
+}
+"#;
+
+        let _ = repository::upsert_script("source-mapped-error-test.ts", script_content);
+
+        let params = RequestExecutionParams {
+            script_uri: "source-mapped-error-test.ts".to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let failure =
+            super::execute_script_for_request_detailed(params).expect_err("handler should throw");
+        assert_eq!(failure.summary, "call handler: TypeError: bad order o-1");
+        let exception = failure.exception.expect("exception should be captured");
+        assert_eq!(exception.name.as_deref(), Some("TypeError"));
+        assert_eq!(exception.line_number, Some(10));
+        assert!(
+            exception
+                .stack
+                .as_deref()
+                .unwrap_or_default()
+                .contains("source-mapped-error-test.ts:10:"),
+            "stack should point into the source: {:?}",
+            exception.stack
+        );
+    }
+
+    fn async_handler_params(script_uri: &str, handler_name: &str) -> RequestExecutionParams {
+        RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: handler_name.to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: Some(1000),
+            tenant: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_handlers() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = unique_test_id("async-handler-test");
+        let script_content = r#"
+async function rejects(context) {
+    await null;
+    throw new Error("async failure");
+}
+
+function neverSettles(context) {
+    return new Promise(() => {});
+}
+
+async function waitsForTimer(context) {
+    await new Promise((resolve) => setTimeout(resolve, 200));
+    return ResponseBuilder.text("late");
+}
+
+async function resolves(context) {
+    const values = await Promise.all([
+        new Promise((resolve) => setTimeout(resolve, 20, "a")),
+        Promise.resolve("b"),
+    ]);
+    return ResponseBuilder.text(values.join(","));
+}
+"#;
+        let _ = repository::upsert_script(&script_uri, script_content);
+
+        let response = super::execute_script_for_request_detailed(async_handler_params(
+            &script_uri,
+            "resolves",
+        ))
+        .expect("handler should resolve");
+        assert_eq!(response.status, 200);
+        assert_eq!(String::from_utf8_lossy(&response.body), "a,b");
+
+        // A rejection fails the call like a thrown error
+        let failure = super::execute_script_for_request_detailed(async_handler_params(
+            &script_uri,
+            "rejects",
+        ))
+        .expect_err("handler should reject");
+        assert_eq!(failure.summary, "call handler: Error: async failure");
+
+        // A promise nothing can settle is reported instead of hanging
+        let started = Instant::now();
+        let failure = super::execute_script_for_request_detailed(async_handler_params(
+            &script_uri,
+            "neverSettles",
+        ))
+        .expect_err("handler should never settle");
+        assert!(
+            failure.summary.contains("Promise never settles"),
+            "{}",
+            failure.summary
+        );
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        // Waiting on a timer counts against the execution timeout
+        let mut params = async_handler_params(&script_uri, "waitsForTimer");
+        params.timeout_ms = Some(50);
+        let failure = super::execute_script_for_request_detailed(params)
+            .expect_err("handler should time out");
+        assert!(failure.summary.contains("timed out"), "{}", failure.summary);
+
+        let _ = repository::delete_script(&script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_handler_exception_uses_uploaded_source_map() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "uploaded-source-map-test.js";
+        let script_content = "function testHandler(context) {\n  throw new Error(\"boom\");\n}\n";
+        let _ = repository::upsert_script(script_uri, script_content);
+        // Everything on generated line 2 comes from src/handler.ts line 10
+        let stored = repository::StoredSourceMap {
+            source_map:
+                r#"{"version":3,"sources":["src/handler.ts"],"names":[],"mappings":";AASA"}"#
+                    .to_string(),
+            content_hash: crate::source_maps::content_hash(script_content),
+        };
+        repository::set_script_source_map(script_uri, Some(&stored))
+            .expect("Should store source map");
+
+        let params = RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+
+        let exception = super::execute_script_for_request_detailed(params)
+            .expect_err("handler should throw")
+            .exception
+            .expect("exception should be captured");
+        assert_eq!(exception.file_name.as_deref(), Some("src/handler.ts"));
+        assert_eq!(exception.line_number, Some(10));
+        assert!(
+            exception
+                .stack
+                .as_deref()
+                .unwrap_or_default()
+                .contains("at testHandler (src/handler.ts:10:1)"),
+            "stack should point into the original source: {:?}",
+            exception.stack
+        );
+
+        // A map uploaded for other content no longer applies
+        let _ = repository::upsert_script(script_uri, &format!("{}// v2\n", script_content));
+        let params = RequestExecutionParams {
+            script_uri: script_uri.to_string(),
+            handler_name: "testHandler".to_string(),
+            path: "/test".to_string(),
+            method: "GET".to_string(),
+            query_params: None,
+            form_data: None,
+            raw_body: None,
+            headers: HashMap::new(),
+            user_context: UserContext::admin("test".to_string()),
+            auth_context: None,
+            uploaded_files: None,
+            route_params: None,
+            timeout_ms: None,
+            tenant: None,
+        };
+        let exception = super::execute_script_for_request_detailed(params)
+            .expect_err("handler should throw")
+            .exception
+            .expect("exception should be captured");
+        assert_eq!(exception.file_name.as_deref(), Some(script_uri));
+        assert_eq!(exception.line_number, Some(2));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_dry_run_discards_writes() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "dry-run-test.js";
+        let draft = r#"
+            function preview(context) {
+                const req = context.request;
+                sharedStorage.setItem("visits", req.query.visits);
+                console.log("stored " + sharedStorage.getItem("visits"));
+                const sent = dispatcher.sendMessage("preview.done", "{}");
+                return {
+                    status: 201,
+                    body: JSON.stringify({ method: req.method, id: req.params.id, sent: sent }),
+                    contentType: "application/json",
+                };
+            }
+        "#;
+        let request: crate::dry_run::DryRunRequest = serde_json::from_str(
+            r#"{"handler": "preview", "method": "put", "path": "/items/7?visits=3", "params": {"id": "7"}}"#,
+        )
+        .unwrap();
+
+        let result = crate::dry_run::execute(
+            script_uri,
+            draft,
+            request,
+            UserContext::admin("test".to_string()),
+            None,
+        );
+        let response = result.response.expect("dry run should respond");
+        assert_eq!(response.status, 201);
+        let body: serde_json::Value =
+            serde_json::from_str(response.body.as_deref().unwrap()).unwrap();
+        assert_eq!(body["method"], "PUT");
+        assert_eq!(body["id"], "7");
+        assert_eq!(
+            body["sent"],
+            "dispatcher.sendMessage is disabled in dry runs"
+        );
+        assert_eq!(result.logs.len(), 1);
+        assert_eq!(result.logs[0].message, "stored 3");
+
+        // The write was visible to the handler but did not persist
+        assert_eq!(
+            repository::get_script_properties_item(script_uri, "visits"),
+            None
+        );
+        assert!(!crate::database::get_current_transaction_active());
+        assert!(!crate::dry_run::is_active());
+
+        // A failing handler reports the exception
+        let request: crate::dry_run::DryRunRequest =
+            serde_json::from_str(r#"{"handler": "missing"}"#).unwrap();
+        let result = crate::dry_run::execute(
+            script_uri,
+            draft,
+            request,
+            UserContext::admin("test".to_string()),
+            None,
+        );
+        assert!(result.response.is_none());
+        assert!(result.error.unwrap().contains("no handler missing"));
+    }
 }
@@ -3,43 +3,93 @@ use axum::http::{Request, StatusCode};
 use axum::response::{IntoResponse, Redirect, Response, Sse, sse::Event};
 use axum::{Router, routing::any};
 use axum_server::Server;
-use futures::StreamExt as FuturesStreamExt;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio_stream::wrappers::BroadcastStream;
 use tracing::{debug, error, info, warn};
 
+pub mod admin_ops;
+pub mod api_reference;
+pub mod asset_cdn;
+pub mod asset_compression;
 pub mod asset_registry;
+pub mod asset_upload;
+pub mod build_info;
 pub mod bytecode;
+pub mod cache;
 pub mod config;
+pub mod content_negotiation;
 pub mod conversion;
 pub mod database;
 pub mod db_schema_utils;
+pub mod debugger;
+pub mod dev_watch;
 pub mod dispatcher;
+pub mod docs_search;
+pub mod dry_run;
+pub mod email;
 pub mod error;
+pub mod event_loop;
+pub mod events;
 pub mod graphql;
+pub mod graphql_errors;
+pub mod graphql_persisted;
 pub mod graphql_schema_gen;
+pub mod graphql_sse;
 pub mod graphql_ws;
+pub mod health;
 pub mod http_client;
+pub mod i18n;
+pub mod idempotency;
+pub mod image_transform;
 pub mod js_engine;
+pub mod llm;
+pub mod llm_usage;
 pub mod mcp;
 pub mod mcp_client;
+pub mod metering;
 pub mod middleware;
+pub mod moderation;
 pub mod module_loader;
 pub mod notifications;
+pub mod notify;
 pub mod openapi_schemas;
 pub mod parsers;
+pub mod path_normalization;
+pub mod pdf;
+pub mod prompts;
+pub mod query_log;
+pub mod rate_limit_rules;
 pub mod repository;
+pub mod request_schema;
+pub mod response_cache;
 pub mod route_index;
+pub mod route_usage;
+pub mod runtime_pool;
 pub mod safe_helpers;
 pub mod scheduler;
+pub mod script_errors;
 pub mod script_init;
+pub mod script_lint;
+pub mod script_resources;
 pub mod security;
+pub mod self_test;
+pub mod signed_urls;
+pub mod source_maps;
+pub mod staging;
 pub mod stream_manager;
 pub mod stream_registry;
+pub mod tasks;
+pub mod templates;
+pub mod tenancy;
+pub mod tenant_quotas;
 pub mod transpiler;
+pub mod tus;
+pub mod user_data;
 pub mod user_repository;
+pub mod webhooks;
+pub mod worker_pool;
 
 // Authentication module (Phase 1 - Core Infrastructure)
 pub mod auth;
@@ -61,6 +111,7 @@ use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, OAuth2, SecuritySch
     paths(
         health_handler,
         health_cluster_handler,
+        engine_version_handler,
         auth::routes::login_page,
         auth::routes::start_login,
         auth::routes::oauth_callback,
@@ -72,11 +123,16 @@ use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, OAuth2, SecuritySch
         auth::metadata::metadata_handler,
         auth::metadata::protected_resource_metadata_handler,
         auth::client_registration::register_client_handler,
+        docs_search::search_handler,
+        api_reference::api_reference_handler,
     ),
     components(
         schemas(
             openapi_schemas::HealthResponse,
+            openapi_schemas::HealthCheck,
             openapi_schemas::ClusterHealthResponse,
+            openapi_schemas::EngineVersionResponse,
+            openapi_schemas::LoadedScripts,
             openapi_schemas::DatabaseStatus,
             openapi_schemas::ScriptStatus,
             openapi_schemas::SystemInfo,
@@ -91,6 +147,13 @@ use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, OAuth2, SecuritySch
             openapi_schemas::McpToolsList,
             openapi_schemas::OAuth2TokenResponse,
             openapi_schemas::AuthStatusResponse,
+            openapi_schemas::DocsSearchResponse,
+            openapi_schemas::DocsSearchHit,
+            api_reference::ApiReference,
+            api_reference::ApiGlobal,
+            api_reference::ApiType,
+            api_reference::ApiMember,
+            api_reference::ApiParam,
             openapi_schemas::ErrorResponse,
             openapi_schemas::ValidationErrorResponse,
             openapi_schemas::UnauthorizedErrorResponse,
@@ -109,6 +172,7 @@ use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, OAuth2, SecuritySch
         (name = "GraphQL", description = "GraphQL API endpoints for queries, mutations, and subscriptions"),
         (name = "MCP", description = "Model Context Protocol (JSON-RPC 2.0) endpoints for AI tool integration"),
         (name = "Authentication", description = "OAuth2 authentication and authorization endpoints"),
+        (name = "Documentation", description = "Documentation search and API reference"),
     )
 )]
 struct ApiDoc;
@@ -210,7 +274,7 @@ pub fn get_rust_openapi_spec() -> String {
                     "post": {
                         "tags": ["GraphQL"],
                         "summary": "Execute GraphQL query or mutation",
-                        "description": "HTTP endpoint for executing GraphQL queries and mutations. Use /graphql/ws for subscriptions via WebSocket or /graphql/sse for subscriptions via Server-Sent Events.",
+                        "description": "HTTP endpoint for executing GraphQL queries and mutations. The body may also be a JSON array of requests, executed concurrently and answered with an array of responses in the same order (up to server.graphql.max_batch_size). A request may name a persisted query by its SHA-256 hash in extensions.persistedQuery instead of carrying its query document; unknown hashes are answered with a PersistedQueryNotFound error. Use /graphql/ws for subscriptions via WebSocket or /graphql/sse for subscriptions via Server-Sent Events.",
                         "requestBody": {
                             "required": true,
                             "content": {
@@ -273,22 +337,29 @@ pub fn get_rust_openapi_spec() -> String {
                 paths.insert("/graphql/sse".to_string(), serde_json::json!({
                     "get": {
                         "tags": ["GraphQL"],
-                        "summary": "GraphQL Server-Sent Events subscriptions",
-                        "description": "Server-Sent Events endpoint for GraphQL subscriptions. Allows real-time data streaming over HTTP.",
-                        "x-protocol": "text/event-stream",
+                        "summary": "GraphQL over Server-Sent Events",
+                        "description": "graphql-sse protocol endpoint. Without a stream token, runs the operation from the query string on its own event stream (distinct connections mode). With a token, opens the reserved stream (single connection mode). Results arrive as `next` events followed by a `complete` event.",
+                        "x-protocol": "graphql-sse",
                         "x-transport": "sse",
                         "parameters": [
                             {
                                 "name": "query",
                                 "in": "query",
-                                "required": true,
+                                "required": false,
+                                "schema": {"type": "string"},
+                                "description": "GraphQL query, mutation or subscription (distinct connections mode)"
+                            },
+                            {
+                                "name": "x-graphql-event-stream-token",
+                                "in": "header",
+                                "required": false,
                                 "schema": {"type": "string"},
-                                "description": "GraphQL subscription query"
+                                "description": "Token of a reserved stream (single connection mode)"
                             }
                         ],
                         "responses": {
                             "200": {
-                                "description": "Event stream with GraphQL subscription data",
+                                "description": "Event stream of `next` and `complete` events",
                                 "headers": {
                                     "Content-Type": {
                                         "schema": {"type": "string"}
@@ -297,6 +368,80 @@ pub fn get_rust_openapi_spec() -> String {
                             },
                             "401": {
                                 "description": "Authentication required"
+                            },
+                            "404": {
+                                "description": "Stream token not found"
+                            },
+                            "409": {
+                                "description": "Reserved stream already open"
+                            }
+                        },
+                        "security": [{"oauth2": []}]
+                    },
+                    "put": {
+                        "tags": ["GraphQL"],
+                        "summary": "Reserve a GraphQL SSE stream",
+                        "description": "Reserves a stream for single connection mode and returns its token as plain text.",
+                        "responses": {
+                            "201": {
+                                "description": "Stream token",
+                                "content": {"text/plain": {"schema": {"type": "string"}}}
+                            },
+                            "401": {
+                                "description": "Authentication required"
+                            }
+                        },
+                        "security": [{"oauth2": []}]
+                    },
+                    "post": {
+                        "tags": ["GraphQL"],
+                        "summary": "Run a GraphQL operation over SSE",
+                        "description": "Without a stream token, runs the operation on its own event stream. With a token, starts the operation identified by `extensions.operationId` on the reserved stream.",
+                        "requestBody": {
+                            "required": true,
+                            "content": {
+                                "application/json": {
+                                    "schema": {"$ref": "#/components/schemas/GraphQLRequest"}
+                                }
+                            }
+                        },
+                        "responses": {
+                            "200": {
+                                "description": "Event stream of `next` and `complete` events (distinct connections mode)"
+                            },
+                            "202": {
+                                "description": "Operation accepted (single connection mode)"
+                            },
+                            "400": {
+                                "description": "Invalid request or missing operation id"
+                            },
+                            "404": {
+                                "description": "Stream token not found"
+                            },
+                            "409": {
+                                "description": "Operation id already running"
+                            }
+                        },
+                        "security": [{"oauth2": []}]
+                    },
+                    "delete": {
+                        "tags": ["GraphQL"],
+                        "summary": "Stop a GraphQL SSE operation",
+                        "description": "Stops an operation running on a reserved stream.",
+                        "parameters": [
+                            {
+                                "name": "operationId",
+                                "in": "query",
+                                "required": true,
+                                "schema": {"type": "string"}
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "Operation stopped"
+                            },
+                            "404": {
+                                "description": "Stream token not found"
                             }
                         },
                         "security": [{"oauth2": []}]
@@ -347,6 +492,39 @@ pub fn get_rust_openapi_spec() -> String {
                     }
                 }));
 
+                // Direct asset uploads
+                paths.insert(asset_upload::UPLOAD_PATH.to_string(), serde_json::json!({
+                    "post": {
+                        "tags": ["Assets"],
+                        "summary": "Upload an asset",
+                        "description": "Stores one file of a multipart/form-data body as an asset of the script in the scriptUri field, under the name in the name field or the file name. Requires the WriteAssets capability, administrator rights or owning the script. Files are limited by repository.max_asset_size_bytes and repository.allowed_asset_types. The uploadAsset GraphQL mutation does the same through the GraphQL multipart request spec.",
+                        "requestBody": {
+                            "required": true,
+                            "content": {
+                                "multipart/form-data": {
+                                    "schema": {
+                                        "type": "object",
+                                        "required": ["scriptUri", "file"],
+                                        "properties": {
+                                            "scriptUri": {"type": "string"},
+                                            "name": {"type": "string"},
+                                            "file": {"type": "string", "format": "binary"}
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                        "responses": {
+                            "201": {"description": "Asset stored; name, scriptUri, mimetype, size and sha256 of the asset"},
+                            "400": {"description": "Missing field, several files or invalid asset name"},
+                            "401": {"description": "Authentication required"},
+                            "403": {"description": "No permission to write assets of the script"},
+                            "413": {"description": "File larger than repository.max_asset_size_bytes"},
+                            "415": {"description": "Not a multipart body, or a file type outside repository.allowed_asset_types"}
+                        }
+                    }
+                }));
+
                 // TypeScript type definitions
                 let version = env!("CARGO_PKG_VERSION");
                 let type_defs_path = format!("/api/types/v{}/aiwebengine.d.ts", version);
@@ -570,13 +748,72 @@ fn error_to_response(error_response: error::ErrorResponse) -> Response {
     (status, body).into_response()
 }
 
+/// 429 response for a request over its tenant's quota, with Retry-After
+fn quota_exceeded_response(
+    exceeded: &tenant_quotas::QuotaExceeded,
+    path: &str,
+    request_id: &str,
+) -> Response {
+    warn!("[{}] ⚠️  {} ({})", request_id, exceeded, path);
+    let mut response = error_to_response(error::errors::too_many_requests(
+        path,
+        &exceeded.to_string(),
+        request_id,
+    ));
+    response.headers_mut().insert(
+        axum::http::header::RETRY_AFTER,
+        axum::http::HeaderValue::from(exceeded.retry_after_secs),
+    );
+    response
+}
+
+/// 429 response for a request over a rate limit rule, with Retry-After
+fn rate_limited_response(
+    limited: &rate_limit_rules::RateLimited,
+    path: &str,
+    request_id: &str,
+) -> Response {
+    warn!("[{}] ⚠️  {} ({})", request_id, limited, path);
+    let mut response = error_to_response(error::errors::too_many_requests(
+        path,
+        &limited.to_string(),
+        request_id,
+    ));
+    response.headers_mut().insert(
+        axum::http::header::RETRY_AFTER,
+        axum::http::HeaderValue::from(limited.retry_after_secs),
+    );
+    response
+}
+
+/// 503 response for a script route while maintenance mode is on, with
+/// Retry-After
+fn maintenance_response(
+    maintenance: &admin_ops::MaintenanceMode,
+    path: &str,
+    request_id: &str,
+) -> Response {
+    let reason = maintenance
+        .message
+        .as_deref()
+        .unwrap_or("The service is down for maintenance");
+    let mut response =
+        error_to_response(error::errors::service_unavailable(path, reason, request_id));
+    response.headers_mut().insert(
+        axum::http::header::RETRY_AFTER,
+        axum::http::HeaderValue::from(maintenance.retry_after_seconds),
+    );
+    response
+}
+
 /// Helper: Get client metadata for stream connection from customization function or query params
 fn get_stream_client_metadata(
+    stream_key: &str,
     path: &str,
     query_params: &HashMap<String, String>,
     auth_user: Option<&auth::AuthUser>,
 ) -> Result<Option<HashMap<String, String>>, String> {
-    let stream_info = stream_registry::GLOBAL_STREAM_REGISTRY.get_stream_info(path);
+    let stream_info = stream_registry::GLOBAL_STREAM_REGISTRY.get_stream_info(stream_key);
 
     if let Some((script_uri, Some(func_name))) = stream_info {
         // Execute customization function to get filter criteria
@@ -621,8 +858,9 @@ fn build_stream_error_response(message: &str) -> Response {
         })
 }
 
-/// Handle Server-Sent Events stream requests
-async fn handle_stream_request(req: Request<Body>) -> Response {
+/// Handle Server-Sent Events stream requests for the stream registered
+/// under `stream_key` (see [`route_index::host_scoped_path`])
+async fn handle_stream_request(req: Request<Body>, stream_key: String) -> Response {
     let path = req.uri().path().to_string();
     let query_string = req.uri().query().map(|s| s.to_string()).unwrap_or_default();
     let query_params = parse_query_string(&query_string);
@@ -635,22 +873,43 @@ async fn handle_stream_request(req: Request<Body>) -> Response {
         path, query_params
     );
 
-    // Get client metadata from customization function or query params
-    let client_metadata = match get_stream_client_metadata(&path, &query_params, auth_user.as_ref())
-    {
-        Ok(metadata) => metadata,
-        Err(e) => {
-            error!("Customization function failed for stream '{}': {}", path, e);
-            return build_stream_error_response(&format!(
-                "Stream customization function failed: {}",
-                e
-            ));
+    // Count the connection against its tenant's quota until the stream ends
+    let tenant = tenancy::tenant_for_request(
+        req.headers()
+            .get(axum::http::header::HOST)
+            .and_then(|value| value.to_str().ok()),
+        auth_user.as_ref().map(|user| user.user_id.as_str()),
+        auth_user.as_ref().and_then(|user| user.email.as_deref()),
+    );
+    let stream_slot = match tenant.as_deref().map(tenant_quotas::open_stream) {
+        Some(Err(exceeded)) => {
+            let request_id = req
+                .extensions()
+                .get::<middleware::RequestId>()
+                .map(|rid| rid.0.clone())
+                .unwrap_or_else(|| "unknown".to_string());
+            return quota_exceeded_response(&exceeded, &path, &request_id);
         }
+        Some(Ok(slot)) => Some(slot),
+        None => None,
     };
 
+    // Get client metadata from customization function or query params
+    let client_metadata =
+        match get_stream_client_metadata(&stream_key, &path, &query_params, auth_user.as_ref()) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("Customization function failed for stream '{}': {}", path, e);
+                return build_stream_error_response(&format!(
+                    "Stream customization function failed: {}",
+                    e
+                ));
+            }
+        };
+
     // Create a connection with the stream manager
     let connection = match stream_manager::StreamConnectionManager::new()
-        .create_connection(&path, client_metadata)
+        .create_connection(&stream_key, client_metadata)
         .await
     {
         Ok(conn) => conn,
@@ -676,8 +935,10 @@ async fn handle_stream_request(req: Request<Body>) -> Response {
     let connection_id_for_stream = connection_id.clone();
 
     // Convert to SSE events, handling both messages and errors
-    let path_for_cleanup = path.clone();
+    let path_for_cleanup = stream_key.clone();
     let sse_stream = tokio_stream::StreamExt::map(receiver_stream, move |result| {
+        // The slot is released when the client disconnects and the stream is dropped
+        let _ = &stream_slot;
         match result {
             Ok(msg) => {
                 debug!(
@@ -930,6 +1191,22 @@ async fn initialize_auth_manager(
         )?;
     }
 
+    if auth_config.captcha.enabled {
+        info!(
+            "CAPTCHA ({:?}) required after {} failed sign-ins",
+            auth_config.captcha.provider, auth_config.captcha.failure_threshold
+        );
+        auth_manager = auth_manager.with_captcha(auth::CaptchaGuard::new(auth_config.captcha)?);
+    }
+
+    if auth_config.lockout.enabled {
+        info!(
+            "Accounts locked for {}s after {} failed sign-ins",
+            auth_config.lockout.lockout_duration_secs, auth_config.lockout.failure_threshold
+        );
+        auth_manager = auth_manager.with_lockout(auth::LockoutPolicy::new(auth_config.lockout));
+    }
+
     Ok(Arc::new(auth_manager))
 }
 
@@ -1100,15 +1377,16 @@ async fn initialize_script_functions(config: &config::Config) -> AppResult<()> {
     Ok(())
 }
 
-/// Starts the web server with custom configuration
-pub async fn start_server_with_config(
-    config: config::Config,
-    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
-) -> AppResult<u16> {
+/// Apply the configuration to the modules reading it, before any script runs
+fn apply_configuration(config: &config::Config) {
     // Apply the capability mode before any script runs. Fail closed: anonymous
     // users get minimal read-only capabilities unless development mode is
     // explicitly enabled in configuration.
     security::set_development_mode(config.security.development_mode);
+    repository::set_trash_retention_days(config.repository.trash_retention_days);
+    repository::set_default_storage_quota_bytes(config.repository.default_storage_quota_bytes);
+    tenancy::configure(&config.tenancy);
+    rate_limit_rules::configure(&config.security);
     if security::is_development_mode() {
         warn!(
             "Development mode is ENABLED: anonymous users receive elevated capabilities \
@@ -1121,11 +1399,77 @@ pub async fn start_server_with_config(
     let js_limits = js_engine::ExecutionLimits {
         timeout_ms: config.javascript.execution_timeout_ms,
         max_memory_mb: (config.javascript.max_memory_bytes / (1024 * 1024)).max(1),
+        max_script_timeout_ms: config.javascript.max_script_execution_timeout_ms,
+        max_script_memory_bytes: config.javascript.max_heap_bytes,
         ..js_engine::ExecutionLimits::default()
     };
     if !js_engine::configure_execution_limits(js_limits) {
         debug!("JavaScript execution limits were already configured");
     }
+    script_errors::configure_error_details(config.javascript.expose_error_details);
+    if !worker_pool::configure(
+        config.javascript.worker_threads,
+        config.javascript.max_concurrent_executions,
+    ) {
+        debug!("Script worker pool was already started");
+    }
+    runtime_pool::configure(&config.javascript.runtime_pool);
+    debugger::configure(config.javascript.enable_debugger);
+    script_lint::configure(&config.javascript.lint);
+    pdf::configure(&config.javascript.pdf);
+    llm::configure(&config.javascript.llm);
+    webhooks::configure(&config.javascript.webhooks);
+    tasks::configure(&config.javascript.tasks);
+    email::configure(&config.javascript.email);
+    notify::configure(&config.javascript.notify);
+    cache::configure(&config.javascript.cache);
+    response_cache::configure(&config.javascript.cache);
+    graphql_persisted::configure(&config.server.graphql);
+    asset_cdn::configure(&config.cdn);
+    health::configure(&config.health);
+    build_info::configure(config);
+    asset_compression::configure(&config.performance);
+    asset_upload::configure(&config.repository);
+    signed_urls::configure(&config.security);
+    route_usage::configure(&config.repository);
+    script_resources::configure(&config.repository);
+    metering::configure(&config.metering);
+    security::audit_export::configure(&config.security.audit_export);
+    security::threat_response::configure(&config.security.threat_response);
+    security::script_manifests::configure(&config.security.script_manifests);
+    security::script_quarantine::configure(&config.security.script_quarantine);
+    i18n::configure(&config.javascript.default_locale);
+    path_normalization::configure(config.server.path_normalization);
+}
+
+/// Check a deployment without serving requests (`--self-test`): initialize
+/// the database and authentication, run every script's `init()` in a
+/// sandbox, and check the composed GraphQL schema and registered routes
+pub async fn run_self_test(config: config::Config) -> AppResult<self_test::SelfTestReport> {
+    apply_configuration(&config);
+    initialize_database_and_repository(&config).await?;
+    scheduler::initialize_global_scheduler();
+
+    let mut report = self_test::SelfTestReport::default();
+    let pool = database::get_global_database().map(|db| db.pool().clone());
+    if let Err(e) = initialize_auth_if_enabled(&config, pool).await {
+        report.component_errors.push(e.to_string());
+    }
+
+    let init_timeout = config
+        .javascript
+        .init_timeout_ms
+        .unwrap_or(config.javascript.execution_timeout_ms);
+    self_test::check_scripts(&mut report, init_timeout).await?;
+    Ok(report)
+}
+
+/// Starts the web server with custom configuration
+pub async fn start_server_with_config(
+    config: config::Config,
+    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
+) -> AppResult<u16> {
+    apply_configuration(&config);
 
     // Initialize all core components
     initialize_components(&config).await?;
@@ -1135,6 +1479,17 @@ pub async fn start_server_with_config(
     let (scheduler_shutdown_tx, scheduler_shutdown_rx) = tokio::sync::oneshot::channel();
 
     scheduler::spawn_worker(scheduler_shutdown_rx);
+    repository::spawn_property_expiry_worker();
+    idempotency::spawn_expiry_worker();
+    webhooks::spawn_worker();
+    tasks::spawn_worker();
+    email::spawn_worker();
+    route_usage::spawn_worker();
+    script_resources::spawn_worker();
+    metering::spawn_worker();
+    security::audit_export::spawn_worker();
+    security::threat_response::spawn_worker();
+    dev_watch::spawn_watcher(&config.dev_watch);
 
     tokio::spawn(async move {
         let _ = shutdown_rx.await;
@@ -1153,6 +1508,7 @@ pub async fn start_server_with_config(
 
     // Determine if auth is enabled
     let auth_enabled = auth_manager.is_some();
+    security::set_auth_enabled(auth_enabled);
 
     // Build the router with all routes and middleware
     let app = setup_routes(
@@ -1178,20 +1534,27 @@ pub async fn start_server_with_config(
     Ok(actual_port)
 }
 
-/// Health check endpoint - returns basic instance status
+/// Health check endpoint - returns instance status with dependency checks
 #[utoipa::path(
     get,
     path = "/health",
     tags = ["Health"],
     responses(
-        (status = 200, description = "Service is healthy", body = crate::openapi_schemas::HealthResponse),
+        (status = 200, description = "Service is healthy or degraded", body = crate::openapi_schemas::HealthResponse),
+        (status = 503, description = "Database unreachable", body = crate::openapi_schemas::HealthResponse),
     )
 )]
 async fn health_handler() -> impl IntoResponse {
     let server_id = notifications::get_server_id().unwrap_or_else(|| "unknown".to_string());
+    let report = health::run_checks().await;
+    let status = if report.status == health::HealthStatus::Unhealthy {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
 
-    axum::response::Json(serde_json::json!({
-        "status": "healthy",
+    let body = axum::response::Json(serde_json::json!({
+        "status": report.status,
         "instance_id": server_id,
         "timestamp": chrono::Utc::now().to_rfc3339(),
         "version": {
@@ -1200,8 +1563,9 @@ async fn health_handler() -> impl IntoResponse {
             "git_commit_timestamp": option_env!("VERGEN_GIT_COMMIT_TIMESTAMP").unwrap_or(""),
             "build_timestamp": option_env!("VERGEN_BUILD_TIMESTAMP").unwrap_or("")
         },
-        "checks": {}
-    }))
+        "checks": report.checks,
+    }));
+    (status, body)
 }
 
 /// Cluster health endpoint - returns detailed cluster status
@@ -1221,11 +1585,28 @@ async fn health_cluster_handler() -> impl IntoResponse {
         let pool = db.pool();
         let size = pool.size() as usize;
         let idle = pool.num_idle();
+        let replicas: Vec<serde_json::Value> = db
+            .replicas()
+            .iter()
+            .map(|replica| {
+                let size = replica.size() as usize;
+                let idle = replica.num_idle();
+                serde_json::json!({
+                    "active_connections": size.saturating_sub(idle),
+                    "idle_connections": idle,
+                    "max_connections": replica.options().get_max_connections(),
+                })
+            })
+            .collect();
         serde_json::json!({
             "available": true,
             "active_connections": size.saturating_sub(idle),
             "idle_connections": idle,
             "max_connections": pool.options().get_max_connections(),
+            "min_connections": pool.options().get_min_connections(),
+            "acquire_timeout_ms": pool.options().get_acquire_timeout().as_millis() as u64,
+            "acquire": database::pool_metrics(),
+            "read_replicas": replicas,
         })
     } else {
         serde_json::json!({
@@ -1267,10 +1648,37 @@ async fn health_cluster_handler() -> impl IntoResponse {
         "scheduler": {
             "total_jobs": total_jobs,
             "jobs_by_script": job_counts,
-        }
+        },
+        "script_workers": worker_pool::metrics(),
+        "script_runtimes": runtime_pool::metrics(),
     }))
 }
 
+/// Version endpoint - returns the build, enabled features and loaded scripts
+/// of the instance
+#[utoipa::path(
+    get,
+    path = "/engine/version",
+    tags = ["Health"],
+    responses(
+        (status = 200, description = "Version information of the instance", body = crate::openapi_schemas::EngineVersionResponse),
+        (status = 500, description = "Scripts could not be listed"),
+    )
+)]
+async fn engine_version_handler() -> Response {
+    match build_info::collect().await {
+        Ok(info) => axum::response::Json(info).into_response(),
+        Err(e) => {
+            error!("Failed to collect version information: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                axum::response::Json(serde_json::json!({ "error": e })),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Initialize authentication manager if configured and enabled.
 ///
 /// Fails startup when authentication is explicitly enabled but cannot be
@@ -1308,6 +1716,32 @@ async fn initialize_auth_if_enabled(
             user_repository::set_bootstrap_admins(auth_config.bootstrap_admins.clone());
         }
 
+        // Bootstrap editors are rules granting the Editor role to one address
+        let bootstrap_roles: Vec<_> = auth_config
+            .bootstrap_editors
+            .iter()
+            .map(|email| auth::BootstrapRoleRule {
+                pattern: email.clone(),
+                roles: vec![user_repository::UserRole::Editor],
+            })
+            .chain(auth_config.bootstrap_roles.iter().cloned())
+            .collect();
+        if !bootstrap_roles.is_empty() {
+            info!(
+                "Configuring {} bootstrap role rule(s): {:?}",
+                bootstrap_roles.len(),
+                bootstrap_roles
+                    .iter()
+                    .map(|rule| rule.pattern.as_str())
+                    .collect::<Vec<_>>()
+            );
+            user_repository::set_bootstrap_roles(bootstrap_roles);
+        }
+
+        if let Some(schema) = &auth_config.profile_schema {
+            user_repository::set_profile_schema(schema.clone()).map_err(AppError::config)?;
+        }
+
         match initialize_auth_manager(auth_config, &config.server, &config.security, pool).await {
             Ok(manager) => {
                 info!("AuthManager initialized successfully");
@@ -1329,6 +1763,57 @@ async fn initialize_auth_if_enabled(
     }
 }
 
+/// Execute a `/graphql` request: a single operation, or a batch of up to
+/// `max_batch_size` operations run concurrently and answered in request
+/// order. Every operation's persisted query is resolved on its own; one
+/// that cannot be is answered with an error response in its place.
+async fn execute_graphql_batch(
+    schema: &async_graphql::dynamic::Schema,
+    request: async_graphql::BatchRequest,
+    max_batch_size: usize,
+    js_auth_context: auth::JsAuthContext,
+) -> serde_json::Value {
+    let response = match request {
+        async_graphql::BatchRequest::Single(request) => {
+            serde_json::to_value(execute_graphql_operation(schema, request, js_auth_context).await)
+        }
+        async_graphql::BatchRequest::Batch(requests)
+            if requests.is_empty() || requests.len() > max_batch_size =>
+        {
+            let message = if max_batch_size == 0 {
+                "Batched operations are disabled".to_string()
+            } else {
+                format!(
+                    "A batch must hold between 1 and {} operations, got {}",
+                    max_batch_size,
+                    requests.len()
+                )
+            };
+            return serde_json::json!({ "error": message });
+        }
+        async_graphql::BatchRequest::Batch(requests) => {
+            let responses = futures::future::join_all(requests.into_iter().map(|request| {
+                execute_graphql_operation(schema, request, js_auth_context.clone())
+            }))
+            .await;
+            serde_json::to_value(responses)
+        }
+    };
+    response.unwrap_or(serde_json::Value::Null)
+}
+
+/// Execute one `/graphql` operation, running the persisted query it names
+async fn execute_graphql_operation(
+    schema: &async_graphql::dynamic::Schema,
+    mut request: async_graphql::Request,
+    js_auth_context: auth::JsAuthContext,
+) -> async_graphql::Response {
+    if let Err(error) = graphql_persisted::resolve(&mut request) {
+        return async_graphql::Response::from_errors(vec![error]);
+    }
+    schema.execute(request.data(js_auth_context)).await
+}
+
 /// Setup all routes and middleware for the application
 async fn setup_routes(
     config: &config::Config,
@@ -1340,6 +1825,8 @@ async fn setup_routes(
     // Cap on request body reads; bodies beyond this are rejected instead of
     // being buffered into memory (usize is Copy, so each closure gets its own)
     let max_request_body = config.security.max_request_body_bytes;
+    let max_graphql_batch = config.server.graphql.max_batch_size;
+    let max_upload_request_body = config.repository.max_upload_request_bytes;
 
     // GraphQL handler - executes queries (supports GET and POST)
     let graphql_post_handler = move |req: axum::http::Request<axum::body::Body>| async move {
@@ -1348,8 +1835,21 @@ async fn setup_routes(
 
         let (parts, body) = req.into_parts();
         let method = parts.method.clone();
+        // Multipart requests carry file uploads (the GraphQL multipart request
+        // spec) and get the upload size limit
+        let multipart_content_type = parts
+            .headers
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .filter(|ct| ct.starts_with("multipart/form-data"))
+            .map(str::to_string);
+        let body_limit = if multipart_content_type.is_some() {
+            max_upload_request_body
+        } else {
+            max_request_body
+        };
 
-        let body_bytes = match axum::body::to_bytes(body, max_request_body).await {
+        let body_bytes = match axum::body::to_bytes(body, body_limit).await {
             Ok(bytes) => bytes,
             Err(_) => {
                 return axum::response::Json(
@@ -1358,7 +1858,8 @@ async fn setup_routes(
             }
         };
 
-        let request: async_graphql::Request = if method == axum::http::Method::GET {
+        // POST bodies may carry a JSON array of operations (a batch)
+        let request: async_graphql::BatchRequest = if method == axum::http::Method::GET {
             // Parse from query params
             let query_string = parts.uri.query().unwrap_or("");
             let query_params = url::form_urlencoded::parse(query_string.as_bytes());
@@ -1401,7 +1902,24 @@ async fn setup_routes(
             if let Some(op) = operation_name {
                 req = req.operation_name(op);
             }
-            req
+            async_graphql::BatchRequest::Single(req)
+        } else if let Some(content_type) = multipart_content_type {
+            let options = async_graphql::http::MultipartOptions::default()
+                .max_file_size(asset_upload::max_file_bytes());
+            match async_graphql::http::receive_batch_body(
+                Some(content_type),
+                futures::io::Cursor::new(body_bytes),
+                options,
+            )
+            .await
+            {
+                Ok(req) => req,
+                Err(e) => {
+                    return axum::response::Json(
+                        serde_json::json!({"error": format!("Invalid multipart request: {}", e)}),
+                    );
+                }
+            }
         } else {
             match serde_json::from_slice(&body_bytes) {
                 Ok(req) => req,
@@ -1426,231 +1944,96 @@ async fn setup_routes(
         // Create authentication context for GraphQL execution
         let js_auth_context = create_js_auth_context(auth_user.as_ref());
 
-        let response = schema.execute(request.data(js_auth_context)).await;
-        axum::response::Json(serde_json::to_value(response).unwrap_or(serde_json::Value::Null))
+        axum::response::Json(
+            execute_graphql_batch(&schema, request, max_graphql_batch, js_auth_context).await,
+        )
     };
 
-    // GraphQL WebSocket handler - handles subscriptions over WebSocket using graphql-transport-ws protocol
+    // GraphQL WebSocket handler - handles subscriptions over WebSocket using graphql-transport-ws protocol.
+    // With authentication enabled, clients without a session cookie or header
+    // authenticate in their connection_init payload.
+    let graphql_ws_auth_manager = auth_manager.cloned();
+    let graphql_ws_config = config.server.graphql_ws.clone();
     let graphql_ws_handler =
-        |ws: axum::extract::ws::WebSocketUpgrade, req: axum::http::Request<axum::body::Body>| async move {
-            // Extract authentication context before upgrade
-            let auth_user = req.extensions().get::<auth::AuthUser>().cloned();
+        move |ws: axum::extract::ws::WebSocketUpgrade,
+              req: axum::http::Request<axum::body::Body>| {
+            let auth_manager = graphql_ws_auth_manager.clone();
+            let ws_config = graphql_ws_config.clone();
+            async move {
+                // Extract authentication context before upgrade
+                let auth_user = req.extensions().get::<auth::AuthUser>().cloned();
+                let connection_auth = auth_manager.map(|auth_manager| graphql_ws::ConnectionAuth {
+                    auth_manager,
+                    ip_addr: rate_limit_rules::client_ip(req.headers()),
+                    user_agent: req
+                        .headers()
+                        .get(axum::http::header::USER_AGENT)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                });
 
-            ws.on_upgrade(move |socket| graphql_ws::handle_websocket_connection(socket, auth_user))
+                ws.on_upgrade(move |socket| {
+                    graphql_ws::handle_websocket_connection(
+                        socket,
+                        auth_user,
+                        connection_auth,
+                        ws_config,
+                    )
+                })
+            }
         };
 
-    // GraphQL SSE handler - handles subscriptions over Server-Sent Events using execute_stream
+    // GraphQL SSE handler - graphql-sse protocol in distinct connections and
+    // single connection modes
     let graphql_sse_handler = move |req: axum::http::Request<axum::body::Body>| async move {
-        // Extract authentication context before consuming the request
-        let auth_user = req.extensions().get::<auth::AuthUser>().cloned();
+        graphql_sse::handle(req, max_request_body).await
+    };
 
-        let (parts, body) = req.into_parts();
-        info!("GraphQL SSE request for URI: {}", parts.uri);
+    // MCP JSON-RPC handler - supports tools/list and tools/call methods
+    let mcp_handler = move |req: axum::http::Request<axum::body::Body>| async move {
+        let mcp_session = req
+            .extensions()
+            .get::<auth::McpAuthSession>()
+            .map(|auth_session| auth_session.session.clone());
 
-        let body_bytes = match axum::body::to_bytes(body, max_request_body).await {
+        let body_bytes = match axum::body::to_bytes(req.into_body(), max_request_body).await {
             Ok(bytes) => bytes,
             Err(e) => {
-                error!("GraphQL SSE: Failed to read request body: {}", e);
-                return axum::response::Response::builder()
-                    .status(StatusCode::PAYLOAD_TOO_LARGE)
-                    .header("content-type", "text/plain")
-                    .body(axum::body::Body::from(
-                        "Request body too large or unreadable",
-                    ))
-                    .unwrap_or_else(|err| {
-                        error!("Failed to build error response: {}", err);
-                        axum::response::Response::new(axum::body::Body::from("Payload Too Large"))
-                    });
+                error!("MCP: Failed to read request body: {}", e);
+                return axum::response::Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "error": {
+                        "code": -32700,
+                        "message": "Parse error: request body too large or unreadable"
+                    },
+                    "id": null
+                }));
             }
         };
 
-        let method = parts.method.clone();
-        let request: async_graphql::Request = if method == axum::http::Method::GET {
-            // Parse from query params
-            let query_string = parts.uri.query().unwrap_or("");
-            let query_params = url::form_urlencoded::parse(query_string.as_bytes());
-
-            let mut query = None;
-            let mut variables = None;
-            let mut operation_name = None;
+        #[derive(Deserialize)]
+        struct JsonRpcRequest {
+            jsonrpc: String,
+            id: Option<serde_json::Value>,
+            method: String,
+            params: Option<serde_json::Value>,
+        }
 
-            for (key, value) in query_params {
-                match key.as_ref() {
-                    "query" => query = Some(value.into_owned()),
-                    "variables" => {
-                        if !value.is_empty()
-                            && let Ok(vars) = serde_json::from_str(&value)
-                        {
-                            variables = Some(vars);
-                        }
-                    }
-                    "operationName" => operation_name = Some(value.into_owned()),
-                    _ => {}
-                }
+        let rpc_request: JsonRpcRequest = match serde_json::from_slice(&body_bytes) {
+            Ok(req) => req,
+            Err(e) => {
+                error!("MCP: Invalid JSON-RPC request: {}", e);
+                return axum::response::Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "error": {
+                        "code": -32700,
+                        "message": format!("Parse error: {}", e)
+                    },
+                    "id": null
+                }));
             }
-
-            if query.is_none() {
-                error!("GraphQL SSE: Missing query parameter");
-                return axum::response::Response::builder()
-                    .status(400)
-                    .header("content-type", "text/plain")
-                    .body(axum::body::Body::from("Missing query parameter"))
-                    .unwrap_or_else(|err| {
-                        error!("Failed to build error response: {}", err);
-                        axum::response::Response::new(axum::body::Body::from("Bad Request"))
-                    });
-            }
-
-            let mut req = if let Some(q) = query {
-                async_graphql::Request::new(q)
-            } else {
-                error!("GraphQL SSE: Missing query parameter");
-                return axum::response::Response::builder()
-                    .status(400)
-                    .header("content-type", "text/plain")
-                    .body(axum::body::Body::from("Missing query parameter"))
-                    .unwrap_or_else(|err| {
-                        error!("Failed to build error response: {}", err);
-                        axum::response::Response::new(axum::body::Body::from("Bad Request"))
-                    });
-            };
-            if let Some(vars) = variables {
-                req = req.variables(vars);
-            }
-            if let Some(op) = operation_name {
-                req = req.operation_name(op);
-            }
-            req
-        } else {
-            match serde_json::from_slice(&body_bytes) {
-                Ok(req) => req,
-                Err(e) => {
-                    error!("GraphQL SSE: Invalid JSON in request body: {}", e);
-                    return axum::response::Response::builder()
-                        .status(400)
-                        .header("content-type", "text/plain")
-                        .body(axum::body::Body::from(format!("Invalid JSON: {}", e)))
-                        .unwrap_or_else(|err| {
-                            error!("Failed to build error response: {}", err);
-                            axum::response::Response::new(axum::body::Body::from("Bad Request"))
-                        });
-                }
-            }
-        };
-
-        // Get the current schema (rebuilds if necessary)
-        let schema = match graphql::get_schema() {
-            Ok(schema) => schema,
-            Err(e) => {
-                error!("GraphQL SSE: Failed to get schema: {:?}", e);
-                return axum::response::Response::builder()
-                    .status(500)
-                    .header("content-type", "text/plain")
-                    .body(axum::body::Body::from(format!("Schema error: {:?}", e)))
-                    .unwrap_or_else(|err| {
-                        error!("Failed to build error response: {}", err);
-                        axum::response::Response::new(axum::body::Body::from(
-                            "Internal Server Error",
-                        ))
-                    });
-            }
-        };
-
-        // Create authentication context for GraphQL execution
-        let js_auth_context = create_js_auth_context(auth_user.as_ref());
-
-        // Check if this is a subscription operation
-        let is_subscription = request.query.trim_start().starts_with("subscription");
-
-        if is_subscription {
-            // Execute subscription via async-graphql streaming and forward events
-            let (tx, rx) = tokio::sync::mpsc::channel(100);
-
-            tokio::spawn(async move {
-                let stream = schema.execute_stream(request.data(js_auth_context));
-
-                let mut stream = std::pin::pin!(stream);
-                while let Some(response) = FuturesStreamExt::next(&mut stream).await {
-                    let json_data =
-                        serde_json::to_string(&response).unwrap_or_else(|_| "{}".to_string());
-                    let event =
-                        Ok::<Event, std::convert::Infallible>(Event::default().data(json_data));
-
-                    if tx.send(event).await.is_err() {
-                        break;
-                    }
-                }
-            });
-
-            let receiver_stream = tokio_stream::wrappers::ReceiverStream::new(rx);
-            Sse::new(receiver_stream)
-                .keep_alive(axum::response::sse::KeepAlive::default())
-                .into_response()
-        } else {
-            // Handle regular queries/mutations as single response
-            let response = schema.execute(request.data(js_auth_context)).await;
-            let json_data = serde_json::to_string(&response).unwrap_or_else(|_| "{}".to_string());
-
-            // Return SSE formatted response for consistency
-            let sse_data = format!("data: {}\n\n", json_data);
-            axum::response::Response::builder()
-                .header("content-type", "text/event-stream")
-                .header("cache-control", "no-cache")
-                .header("connection", "keep-alive")
-                .header("access-control-allow-origin", "*")
-                .header("access-control-allow-headers", "content-type")
-                .body(axum::body::Body::from(sse_data))
-                .unwrap_or_else(|err| {
-                    error!("Failed to build SSE response: {}", err);
-                    axum::response::Response::new(axum::body::Body::from("Internal Server Error"))
-                })
-        }
-    };
-
-    // MCP JSON-RPC handler - supports tools/list and tools/call methods
-    let mcp_handler = move |req: axum::http::Request<axum::body::Body>| async move {
-        let mcp_session = req
-            .extensions()
-            .get::<auth::McpAuthSession>()
-            .map(|auth_session| auth_session.session.clone());
-
-        let body_bytes = match axum::body::to_bytes(req.into_body(), max_request_body).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                error!("MCP: Failed to read request body: {}", e);
-                return axum::response::Json(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "error": {
-                        "code": -32700,
-                        "message": "Parse error: request body too large or unreadable"
-                    },
-                    "id": null
-                }));
-            }
-        };
-
-        #[derive(Deserialize)]
-        struct JsonRpcRequest {
-            jsonrpc: String,
-            id: Option<serde_json::Value>,
-            method: String,
-            params: Option<serde_json::Value>,
-        }
-
-        let rpc_request: JsonRpcRequest = match serde_json::from_slice(&body_bytes) {
-            Ok(req) => req,
-            Err(e) => {
-                error!("MCP: Invalid JSON-RPC request: {}", e);
-                return axum::response::Json(serde_json::json!({
-                    "jsonrpc": "2.0",
-                    "error": {
-                        "code": -32700,
-                        "message": format!("Parse error: {}", e)
-                    },
-                    "id": null
-                }));
-            }
-        };
+        };
 
         // Validate JSON-RPC version
         if rpc_request.jsonrpc != "2.0" {
@@ -1825,7 +2208,9 @@ async fn setup_routes(
                 }
             }
             "prompts/list" => {
-                let prompts = mcp::list_prompts();
+                let mut prompts = mcp::list_prompts();
+                let saved = prompts::mcp_prompts(&prompts).await;
+                prompts.extend(saved);
 
                 let prompts_list: Vec<serde_json::Value> = prompts
                     .iter()
@@ -1882,7 +2267,13 @@ async fn setup_routes(
 
                 let arguments = params.arguments.unwrap_or(serde_json::json!({}));
 
-                match mcp::execute_mcp_prompt(&params.name, arguments) {
+                // Prompts registered by scripts shadow saved prompt templates
+                let saved = match mcp::get_prompt(&params.name) {
+                    Some(_) => None,
+                    None => prompts::mcp_get_prompt(&params.name, &arguments).await,
+                };
+
+                match saved.unwrap_or_else(|| mcp::execute_mcp_prompt(&params.name, arguments)) {
                     Ok(result) => {
                         // The handler should return an object with a "messages" array
                         axum::response::Json(serde_json::json!({
@@ -2033,21 +2424,43 @@ async fn setup_routes(
     if let Some(auth_mgr) = auth_manager {
         info!("✅ Authentication ENABLED - mounting auth routes and middleware");
 
-        // GraphQL API endpoints (queries, mutations, subscriptions) - REQUIRES authentication
+        // GraphQL API endpoints (queries, mutations, subscriptions) - REQUIRES authentication.
+        // The WebSocket endpoint authenticates in connection_init instead,
+        // since browsers can't send headers with the upgrade request.
         let auth_mgr_for_graphql_api = Arc::clone(auth_mgr);
         let graphql_api_router = Router::new()
             .route(
                 "/graphql",
                 axum::routing::get(graphql_post_handler).post(graphql_post_handler),
             )
-            .route("/graphql/ws", axum::routing::get(graphql_ws_handler))
-            .route("/graphql/sse", axum::routing::get(graphql_sse_handler))
+            .route(
+                "/graphql/sse",
+                axum::routing::get(graphql_sse_handler)
+                    .post(graphql_sse_handler)
+                    .put(graphql_sse_handler)
+                    .delete(graphql_sse_handler),
+            )
             .layer(axum::middleware::from_fn_with_state(
                 auth_mgr_for_graphql_api,
                 auth::required_auth_middleware,
             ));
 
-        app = app.merge(graphql_api_router);
+        app = app
+            .merge(graphql_api_router)
+            .route("/graphql/ws", axum::routing::get(graphql_ws_handler));
+
+        // Direct asset uploads - REQUIRES authentication
+        let asset_upload_router = Router::new()
+            .route(
+                asset_upload::UPLOAD_PATH,
+                axum::routing::post(asset_upload::upload_handler),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::clone(auth_mgr),
+                auth::required_auth_middleware,
+            ));
+
+        app = app.merge(asset_upload_router);
 
         // MCP endpoint - REQUIRES Bearer token authentication
         // Supports JSON-RPC 2.0 protocol with tools/list and tools/call methods
@@ -2095,7 +2508,19 @@ async fn setup_routes(
                 axum::routing::get(graphql_post_handler).post(graphql_post_handler),
             )
             .route("/graphql/ws", axum::routing::get(graphql_ws_handler))
-            .route("/graphql/sse", axum::routing::get(graphql_sse_handler));
+            .route(
+                "/graphql/sse",
+                axum::routing::get(graphql_sse_handler)
+                    .post(graphql_sse_handler)
+                    .put(graphql_sse_handler)
+                    .delete(graphql_sse_handler),
+            );
+
+        // Asset uploads without authentication, with administrator rights
+        app = app.route(
+            asset_upload::UPLOAD_PATH,
+            axum::routing::post(asset_upload::upload_handler),
+        );
 
         // MCP endpoint without authentication (auth is disabled globally)
         app = app.route("/mcp", axum::routing::post(mcp_handler));
@@ -2108,6 +2533,18 @@ async fn setup_routes(
             "/health/cluster",
             axum::routing::get(health_cluster_handler),
         )
+        .route(
+            "/engine/version",
+            axum::routing::get(engine_version_handler),
+        )
+        .route(
+            "/engine/docs/search",
+            axum::routing::get(docs_search::search_handler),
+        )
+        .route(
+            "/engine/docs/api",
+            axum::routing::get(api_reference::api_reference_handler),
+        )
         .route(
             "/.well-known/microsoft-identity-association.json",
             axum::routing::get(|| async {
@@ -2121,6 +2558,15 @@ async fn setup_routes(
             }),
         );
 
+    // Script debugger, for administrators and only when enabled
+    if config.javascript.enable_debugger {
+        warn!("Script debugger ENABLED at /engine/debugger");
+        app = app.route(
+            "/engine/debugger",
+            axum::routing::get(debugger::websocket_handler),
+        );
+    }
+
     // Add TypeScript type definitions endpoint (no authentication required)
     let version = env!("CARGO_PKG_VERSION");
     let type_defs_path = format!("/api/types/v{}/aiwebengine.d.ts", version);
@@ -2201,39 +2647,54 @@ async fn setup_routes(
     let auth_enabled_for_path = auth_enabled;
     let script_timeout_for_home = script_timeout_ms;
     let script_timeout_for_path = script_timeout_ms;
-    let max_upload_for_home = config.repository.max_upload_size_bytes;
-    let max_upload_for_path = config.repository.max_upload_size_bytes;
+    let upload_limits = Arc::new(parsers::UploadLimits::from_config(&config.repository));
+    parsers::remove_stale_spool_files(&upload_limits.spool_dir);
+    tus::configure(&upload_limits);
+    let upload_limits_for_home = Arc::clone(&upload_limits);
+    let upload_limits_for_path = upload_limits;
 
     app = app
         .route(
             "/",
-            any(move |req: Request<Body>| async move {
-                handle_dynamic_request(
-                    req,
-                    script_timeout_for_home,
-                    auth_enabled_for_home,
-                    max_upload_for_home,
-                    max_request_body,
-                )
-                .await
+            any(move |req: Request<Body>| {
+                let upload_limits = Arc::clone(&upload_limits_for_home);
+                async move {
+                    handle_dynamic_request(
+                        req,
+                        script_timeout_for_home,
+                        auth_enabled_for_home,
+                        upload_limits,
+                        max_request_body,
+                    )
+                    .await
+                }
             }),
         )
         .route(
             "/{*path}",
-            any(move |req: Request<Body>| async move {
-                handle_dynamic_request(
-                    req,
-                    script_timeout_for_path,
-                    auth_enabled_for_path,
-                    max_upload_for_path,
-                    max_request_body,
-                )
-                .await
+            any(move |req: Request<Body>| {
+                let upload_limits = Arc::clone(&upload_limits_for_path);
+                async move {
+                    handle_dynamic_request(
+                        req,
+                        script_timeout_for_path,
+                        auth_enabled_for_path,
+                        upload_limits,
+                        max_request_body,
+                    )
+                    .await
+                }
             }),
         );
 
     // Add middleware layers (applied in reverse order to how they're added)
-    // So request_id runs first, then auth middleware
+    // So request_id runs first, then auth middleware, then threat response IP
+    // blocks, then rate limit rules
+    app = app.layer(axum::middleware::from_fn(rate_limit_rules::middleware));
+    app = app.layer(axum::middleware::from_fn(
+        security::threat_response::middleware,
+    ));
+
     if let Some(auth_mgr) = auth_manager {
         let auth_mgr_for_middleware = Arc::clone(auth_mgr);
         info!("✅ Adding optional_auth_middleware layer to all routes");
@@ -2248,29 +2709,190 @@ async fn setup_routes(
     app
 }
 
-/// Handle dynamic requests by routing to registered JavaScript handlers
+/// Handle dynamic requests by routing to registered JavaScript handlers,
+/// with the overrides configured for the request's tenant or host applied
 async fn handle_dynamic_request(
     req: Request<Body>,
     script_timeout_ms: u64,
     _auth_enabled: bool,
-    max_upload_size: usize,
+    upload_limits: Arc<parsers::UploadLimits>,
     max_request_body_bytes: usize,
-) -> impl IntoResponse {
+) -> Response {
+    let req = match path_normalization::apply(req) {
+        Ok(req) => req,
+        Err(redirect) => return redirect,
+    };
+    // HTTP/2 requests carry the host in the URI authority instead
+    let host = req
+        .headers()
+        .get(axum::http::header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+        .map(str::to_string);
+    let auth_user = req.extensions().get::<auth::AuthUser>();
+    let tenant = tenancy::tenant_for_request(
+        host.as_deref(),
+        auth_user.map(|user| user.user_id.as_str()),
+        auth_user.and_then(|user| user.email.as_deref()),
+    );
+    let overrides = tenancy::overrides_for(tenant.as_deref(), host.as_deref());
+
+    // Cross-origin calls are only answered for origins the tenant allows
+    let cors_origin = overrides.as_ref().and_then(|overrides| {
+        req.headers()
+            .get(axum::http::header::ORIGIN)
+            .and_then(|value| value.to_str().ok())
+            .filter(|origin| tenancy::cors_origin_allowed(&overrides.cors_allowed_origins, origin))
+            .map(str::to_string)
+    });
+    if let Some(origin) = cors_origin.as_deref()
+        && req.method() == axum::http::Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(axum::http::header::ACCESS_CONTROL_REQUEST_METHOD)
+    {
+        return cors_preflight_response(origin, req.headers());
+    }
+
+    let script_timeout_ms = overrides
+        .as_ref()
+        .and_then(|overrides| overrides.execution_timeout_ms)
+        .unwrap_or(script_timeout_ms);
+    let mut response = route_usage::track(dispatch_dynamic_request(
+        req,
+        host,
+        tenant,
+        overrides,
+        script_timeout_ms,
+        upload_limits,
+        max_request_body_bytes,
+    ))
+    .await;
+
+    if let Some(origin) = cors_origin {
+        let headers = response.headers_mut();
+        if let Ok(value) = axum::http::HeaderValue::from_str(&origin) {
+            headers.insert(axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+        }
+        headers.append(
+            axum::http::header::VARY,
+            axum::http::HeaderValue::from_static("Origin"),
+        );
+    }
+    response
+}
+
+/// Answer a CORS preflight request from an allowed origin
+fn cors_preflight_response(origin: &str, request_headers: &axum::http::HeaderMap) -> Response {
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    let headers = response.headers_mut();
+    if let Ok(value) = axum::http::HeaderValue::from_str(origin) {
+        headers.insert(axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+    }
+    headers.insert(
+        axum::http::header::ACCESS_CONTROL_ALLOW_METHODS,
+        axum::http::HeaderValue::from_static("GET, HEAD, POST, PUT, PATCH, DELETE, OPTIONS"),
+    );
+    if let Some(requested) = request_headers.get(axum::http::header::ACCESS_CONTROL_REQUEST_HEADERS)
+    {
+        headers.insert(
+            axum::http::header::ACCESS_CONTROL_ALLOW_HEADERS,
+            requested.clone(),
+        );
+    }
+    headers.insert(
+        axum::http::header::ACCESS_CONTROL_MAX_AGE,
+        axum::http::HeaderValue::from_static("600"),
+    );
+    headers.insert(
+        axum::http::header::VARY,
+        axum::http::HeaderValue::from_static("Origin"),
+    );
+    response
+}
+
+/// Response for an anonymous request to a tenant that requires sign-in:
+/// browsers navigating to a page are sent to the login page, other clients
+/// get 401
+fn authentication_required_response(
+    method: &str,
+    path: &str,
+    query: &str,
+    accepts_html: bool,
+    request_id: &str,
+) -> Response {
+    if method == "GET" && accepts_html {
+        let full_path = if query.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}?{}", path, query)
+        };
+        let login_url = format!("/auth/login?redirect={}", urlencoding::encode(&full_path));
+        return Redirect::temporary(&login_url).into_response();
+    }
+    error_to_response(error::errors::unauthorized(path, request_id))
+}
+
+/// Serve a dynamic request: registered assets, then streams, then upload
+/// endpoints, then script routes. `tenant` and `overrides` were resolved by the caller.
+async fn dispatch_dynamic_request(
+    req: Request<Body>,
+    host: Option<String>,
+    tenant: Option<String>,
+    overrides: Option<config::TenantOverrideConfig>,
+    script_timeout_ms: u64,
+    upload_limits: Arc<parsers::UploadLimits>,
+    max_request_body_bytes: usize,
+) -> Response {
     let path = req.uri().path().to_string();
     let request_method = req.method().to_string();
 
+    // Extract request ID from extensions before consuming the request
+    let request_id = req
+        .extensions()
+        .get::<middleware::RequestId>()
+        .map(|rid| rid.0.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+
     // Check for registered asset paths first if it's a GET request
-    if let Some(asset_response) = try_serve_asset(&path, &request_method).await {
+    let query = req.uri().query().unwrap_or("").to_string();
+    let accept_encoding = req
+        .headers()
+        .get(axum::http::header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok());
+    if let Some(asset_response) = try_serve_asset(
+        host.as_deref(),
+        &path,
+        &query,
+        &request_method,
+        accept_encoding,
+        &request_id,
+    )
+    .await
+    {
         return asset_response;
     }
 
     // Check if this is a request to a registered stream path
-    if should_route_to_stream(&path, &request_method) {
-        return handle_stream_request(req).await;
+    if let Some(stream_key) = stream_key_for_request(host.as_deref(), &path, &request_method) {
+        return handle_stream_request(req, stream_key).await;
+    }
+
+    // Resumable uploads to an endpoint registered with registerUploadRoute
+    if let Some(upload) = tus::match_request(host.as_deref(), &path) {
+        if req.extensions().get::<auth::AuthUser>().is_none()
+            && overrides
+                .as_ref()
+                .is_some_and(|overrides| overrides.require_authentication)
+        {
+            return error_to_response(error::errors::unauthorized(&path, &request_id));
+        }
+        let tenant_info = tenancy::request_tenant_json(tenant.as_deref(), overrides.as_ref());
+        return tus::handle_request(req, upload, tenant, tenant_info, &request_id).await;
     }
 
     // Match against the cached route index (rebuilt lazily on script changes)
-    let route_lookup = match route_index::lookup(&path, &request_method).await {
+    let route_lookup = match route_index::lookup(host.as_deref(), &path, &request_method).await {
         Ok(lookup) => lookup,
         Err(e) => {
             // Treat lookup failure as no match, mirroring the previous behavior
@@ -2280,32 +2902,76 @@ async fn handle_dynamic_request(
         }
     };
 
-    let (owner_uri, handler_name, route_params, strip_body) = match route_lookup {
+    let (
+        owner_uri,
+        handler_name,
+        route_pattern,
+        route_params,
+        strip_body,
+        rate_limit,
+        route_idempotency,
+        route_uploads,
+        route_schema,
+        script_timeout_override,
+        route_cache,
+        route_auth,
+    ) = match route_lookup {
         route_index::RouteLookup::Handler {
             script_uri,
             handler_name,
+            route,
             params,
             strip_body,
-        } => (script_uri, handler_name, params, strip_body),
-        no_handler => {
-            // Extract request ID from extensions
-            let request_id = req
-                .extensions()
-                .get::<middleware::RequestId>()
-                .map(|rid| rid.0.clone())
-                .unwrap_or_else(|| "unknown".to_string());
-
-            if matches!(no_handler, route_index::RouteLookup::MethodNotAllowed) {
-                warn!(
-                    "[{}] ⚠️  Method not allowed: {} {} (path exists but method not registered)",
-                    request_id, request_method, path
+            rate_limit,
+            idempotency,
+            uploads,
+            schema,
+            execution_timeout_ms,
+            cache,
+            auth,
+        } => (
+            script_uri,
+            handler_name,
+            route,
+            params,
+            strip_body,
+            rate_limit,
+            idempotency,
+            uploads,
+            schema,
+            execution_timeout_ms,
+            cache,
+            auth,
+        ),
+        route_index::RouteLookup::MethodNotAllowed { allowed } => {
+            let allow = axum::http::HeaderValue::from_str(&allowed.join(", "))
+                .unwrap_or_else(|_| axum::http::HeaderValue::from_static("OPTIONS"));
+            // OPTIONS is answered from the registrations unless a script
+            // registered a handler for it
+            if request_method == "OPTIONS" {
+                debug!(
+                    "[{}] Answering OPTIONS {} with Allow: {:?}",
+                    request_id, path, allow
                 );
-                return error_to_response(error::errors::method_not_allowed(
-                    &path,
-                    &request_method,
-                    &request_id,
-                ));
-            } else if path == "/" && request_method == "GET" {
+                return (StatusCode::NO_CONTENT, [(axum::http::header::ALLOW, allow)])
+                    .into_response();
+            }
+            warn!(
+                "[{}] ⚠️  Method not allowed: {} {} (path exists but method not registered)",
+                request_id, request_method, path
+            );
+            let mut response = error_to_response(error::errors::method_not_allowed(
+                &path,
+                &request_method,
+                &request_id,
+            ));
+            response
+                .headers_mut()
+                .insert(axum::http::header::ALLOW, allow);
+            return response;
+        }
+        route_index::RouteLookup::NotFound => {
+            if path == "/" && request_method == "GET" {
                 info!(
                     "[{}] 🔄 Redirecting root path to /engine/installed for bootstrapping",
                     request_id
@@ -2320,6 +2986,30 @@ async fn handle_dynamic_request(
             }
         }
     };
+    route_usage::matched(&owner_uri, &route_pattern, &request_method);
+
+    if security::threat_response::is_quarantined(&owner_uri) {
+        warn!(
+            "[{}] Script {} is quarantined; refusing {} {}",
+            request_id, owner_uri, request_method, path
+        );
+        return error_to_response(error::errors::service_unavailable(
+            &path,
+            "This script is quarantined until an administrator releases it",
+            &request_id,
+        ));
+    }
+
+    // A route's or script's own timeout wins over the tenant's, up to the
+    // configured ceiling
+    let timeout_override = script_timeout_override
+        .map(|ms| js_engine::current_execution_limits().script_timeout_ms(ms))
+        .or_else(|| {
+            overrides
+                .as_ref()
+                .and_then(|overrides| overrides.execution_timeout_ms)
+        });
+    let script_timeout_ms = timeout_override.unwrap_or(script_timeout_ms);
 
     let owner_uri_cl = owner_uri.clone();
     let handler_cl = handler_name.clone();
@@ -2327,17 +3017,140 @@ async fn handle_dynamic_request(
     let method_log = request_method.clone();
     let query_string = req.uri().query().map(|s| s.to_string()).unwrap_or_default();
     let query_params = parse_query_string(&query_string);
-
-    // Extract request ID from extensions before consuming the request
-    let request_id = req
-        .extensions()
-        .get::<middleware::RequestId>()
-        .map(|rid| rid.0.clone())
-        .unwrap_or_else(|| "unknown".to_string());
+    let trace_context = req.extensions().get::<middleware::TraceContext>().cloned();
 
     // Extract authentication context from middleware
     let auth_user = req.extensions().get::<auth::AuthUser>().cloned();
 
+    let tenant_requires_auth = overrides
+        .as_ref()
+        .is_some_and(|overrides| overrides.require_authentication);
+    if auth_user.is_none() && (tenant_requires_auth || route_auth.is_some()) {
+        let accepts_html = req
+            .headers()
+            .get(axum::http::header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"));
+        info!(
+            "[{}] Sign-in required for {} {} on this {}",
+            request_id,
+            request_method,
+            path,
+            if tenant_requires_auth {
+                "tenant"
+            } else {
+                "route"
+            }
+        );
+        return authentication_required_response(
+            &request_method,
+            &path,
+            &query_string,
+            accepts_html,
+            &request_id,
+        );
+    }
+
+    // Routes registered with auth: { groups } are for members of the groups
+    if let (Some(requirement), Some(user)) = (route_auth.as_ref(), auth_user.as_ref())
+        && !requirement.allows(user).await
+    {
+        info!(
+            "[{}] User {} is not in a group allowed to use {} {}",
+            request_id, user.user_id, request_method, path
+        );
+        return error_to_response(error::errors::forbidden(
+            &path,
+            "Membership in one of the route's groups is required",
+            &request_id,
+        ));
+    }
+
+    // Administrators keep using script routes during maintenance
+    let maintenance = admin_ops::maintenance();
+    if maintenance.enabled && !auth_user.as_ref().is_some_and(|user| user.is_admin) {
+        return maintenance_response(&maintenance, &path, &request_id);
+    }
+
+    if let Some(tenant) = tenant.as_deref()
+        && let Err(exceeded) = tenant_quotas::check_request(tenant)
+    {
+        return quota_exceeded_response(&exceeded, &path, &request_id);
+    }
+
+    if let Some(rule) = rate_limit.as_ref()
+        && let Err(limited) = rate_limit_rules::check(
+            rule,
+            &rate_limit_rules::client_ip(req.headers()),
+            auth_user.as_ref().map(|user| user.user_id.as_str()),
+        )
+        .await
+    {
+        return rate_limited_response(&limited, &path, &request_id);
+    }
+
+    // Routes registered with cache answer anonymous reads from earlier
+    // responses without running the handler. A stale response is refreshed
+    // in the background by the first request that gets it.
+    let cache_key = route_cache
+        .as_ref()
+        .filter(|_| response_cache::applies_to(&request_method, auth_user.is_some()))
+        .map(|route| {
+            response_cache::request_key(
+                route,
+                &owner_uri,
+                tenant.as_deref(),
+                host.as_deref(),
+                &path,
+                &query_string,
+                req.headers(),
+            )
+        });
+    if let (Some(route), Some(key)) = (route_cache.as_ref(), cache_key.as_ref())
+        && let Some(hit) = response_cache::lookup(key)
+    {
+        debug!(
+            "[{}] Serving {} {} from the response cache",
+            request_id, request_method, path
+        );
+        if hit.refresh {
+            let mut headers = HashMap::new();
+            for name in &route.vary {
+                if let Some(value) = req.headers().get(name).and_then(|v| v.to_str().ok()) {
+                    headers.insert(name.clone(), value.to_string());
+                }
+            }
+            let params = js_engine::RequestExecutionParams {
+                script_uri: owner_uri.clone(),
+                handler_name: handler_name.clone(),
+                path: path.clone(),
+                method: "GET".to_string(),
+                query_params: Some(query_params.clone()),
+                form_data: Some(HashMap::new()),
+                raw_body: None,
+                headers,
+                user_context: security::UserContext::anonymous(),
+                auth_context: Some(auth::JsAuthContext::anonymous()),
+                route_params: Some(route_params.clone()),
+                uploaded_files: Some(Vec::new()),
+                timeout_ms: timeout_override,
+                tenant: tenancy::request_tenant_json(tenant.as_deref(), overrides.as_ref()),
+            };
+            spawn_route_refresh(
+                params,
+                tenant.clone(),
+                Arc::clone(route),
+                key.clone(),
+                script_timeout_ms,
+            );
+        }
+        let mut response = hit.response;
+        if strip_body {
+            *response.body_mut() = Body::empty();
+        }
+        return response;
+    }
+
     if let Some(ref user) = auth_user {
         info!(
             "[{}] Authentication context found: user_id={}, provider={}",
@@ -2371,37 +3184,153 @@ async fn handle_dynamic_request(
         .get(axum::http::header::CONTENT_TYPE)
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
+    let idempotency_key = if route_idempotency.is_some() {
+        match idempotency::request_key(req.headers()) {
+            Ok(key) => key,
+            Err(reason) => {
+                return error_to_response(error::errors::bad_request(&path, &reason, &request_id));
+            }
+        }
+    } else {
+        None
+    };
     let body = req.into_body();
 
-    // Read the body with a size cap: form submissions are bounded by the
-    // configured upload limit (plus headroom for multipart framing), everything
-    // else by the general request body limit. Oversized bodies are rejected
-    // instead of being buffered into memory.
-    let is_form_data = content_type
+    // Multipart bodies are parsed as they arrive, with large files spooled to
+    // disk. Other bodies are read with a size cap: URL-encoded forms by the
+    // upload limit, everything else by the general request body limit.
+    // Oversized bodies are rejected instead of being buffered into memory.
+    let is_multipart = content_type
         .as_ref()
-        .map(|ct| {
-            ct.contains("application/x-www-form-urlencoded") || ct.contains("multipart/form-data")
-        })
-        .unwrap_or(false);
+        .is_some_and(|ct| ct.contains("multipart/form-data"));
+    let is_urlencoded = content_type
+        .as_ref()
+        .is_some_and(|ct| ct.contains("application/x-www-form-urlencoded"));
 
-    let body_limit = if is_form_data {
-        max_upload_size.saturating_add(64 * 1024)
+    let route_limits = route_uploads
+        .as_ref()
+        .map(|rules| upload_limits.for_route(rules));
+    let upload_limits = route_limits.as_ref().unwrap_or(&upload_limits);
+    let (body_bytes, form) = if is_multipart {
+        let form = parse_form_data(content_type.as_deref(), body, upload_limits).await;
+        (axum::body::Bytes::new(), form)
     } else {
-        max_request_body_bytes
+        let body_limit = if is_urlencoded {
+            upload_limits.max_file_bytes
+        } else {
+            max_request_body_bytes
+        };
+        let body_bytes = match to_bytes(body, body_limit).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return Response::builder()
+                    .status(StatusCode::PAYLOAD_TOO_LARGE)
+                    .header("content-type", "text/plain")
+                    .body(Body::from("Request body too large"))
+                    .unwrap_or_else(|_| Response::new(Body::from("Payload Too Large")));
+            }
+        };
+        let form = if is_urlencoded {
+            let body = Body::from(body_bytes.clone());
+            parse_form_data(content_type.as_deref(), body, upload_limits).await
+        } else {
+            Ok((HashMap::new(), Vec::new()))
+        };
+        (body_bytes, form)
     };
 
-    let body_bytes = match to_bytes(body, body_limit).await {
-        Ok(bytes) => bytes,
-        Err(_) => {
+    let (form_data, uploaded_files) = match form {
+        Ok((fields, files)) => (fields, files),
+        Err(status) => {
+            // Return error response for form parsing failures
+            let error_message = match status {
+                StatusCode::PAYLOAD_TOO_LARGE => "File upload exceeds maximum size limit",
+                StatusCode::INTERNAL_SERVER_ERROR => "Failed to store file upload",
+                _ => "Failed to parse form data",
+            };
             return Response::builder()
-                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .status(status)
                 .header("content-type", "text/plain")
-                .body(Body::from("Request body too large"))
-                .unwrap_or_else(|_| Response::new(Body::from("Payload Too Large")));
+                .body(Body::from(error_message))
+                .unwrap_or_else(|_| Response::new(Body::from(error_message)));
         }
     };
 
-    // Make raw body available for all requests that might have a body
+    if let Some(rules) = route_uploads.as_ref()
+        && let Err(violation) = rules.check(&uploaded_files)
+    {
+        info!(
+            "[{}] Rejected uploads for {} {}: {}",
+            request_id, request_method, path, violation
+        );
+        let reason = violation.to_string();
+        return error_to_response(match violation {
+            parsers::UploadViolation::TooManyFiles { .. } => {
+                error::errors::bad_request(&path, &reason, &request_id)
+            }
+            parsers::UploadViolation::TypeNotAllowed { .. } => {
+                error::errors::unsupported_media_type(&path, &reason, &request_id)
+            }
+        });
+    }
+
+    if let Some(validator) = route_schema.as_ref() {
+        let body = if is_multipart || is_urlencoded {
+            request_schema::RequestBody::Form(&form_data)
+        } else {
+            request_schema::RequestBody::Raw(&body_bytes)
+        };
+        if let Err(violations) = validator.validate(body, &query_params, &route_params) {
+            info!(
+                "[{}] Request to {} {} failed its schema: {} violation(s)",
+                request_id,
+                request_method,
+                path,
+                violations.len()
+            );
+            return error_to_response(error::errors::schema_validation_failed(
+                &path,
+                &violations,
+                &request_id,
+            ));
+        }
+    }
+
+    // Routes registered with idempotency replay the stored response to
+    // retries carrying the same Idempotency-Key instead of running again.
+    // Multipart bodies are not kept, so they are identified by their parts.
+    let mut idempotent_request = None;
+    if let (Some(route_idempotency), Some(key)) = (route_idempotency.as_ref(), idempotency_key) {
+        let scope = idempotency::request_scope(
+            &request_method,
+            &path,
+            auth_user.as_ref().map(|user| user.user_id.as_str()),
+            tenant.as_deref(),
+        );
+        let request_hash = if is_multipart {
+            let digest = parsers::form_digest(&form_data, &uploaded_files);
+            idempotency::request_hash(&query_string, digest.as_bytes())
+        } else {
+            idempotency::request_hash(&query_string, &body_bytes)
+        };
+        match idempotency::begin(
+            route_idempotency,
+            scope,
+            key,
+            &request_hash,
+            script_timeout_ms,
+            &path,
+            &request_id,
+        )
+        .await
+        {
+            idempotency::Begin::Execute(claim) => idempotent_request = claim,
+            idempotency::Begin::Respond(response) => return response,
+        }
+    }
+
+    // Make raw body available for all requests that might have a body.
+    // Multipart bodies are only available as form fields and files.
     // Note: While RFC 7231 doesn't explicitly forbid request bodies for DELETE,
     // some HTTP clients and proxies may not support it. However, we support it
     // for maximum flexibility in API design.
@@ -2411,55 +3340,35 @@ async fn handle_dynamic_request(
         None
     };
 
-    let (form_data, uploaded_files) = if is_form_data {
-        // Parse form data from the bytes
-        let body = Body::from(body_bytes.clone());
-        match parse_form_data(content_type.as_deref(), body, max_upload_size).await {
-            Ok((fields, files)) => (fields, files),
-            Err(status) => {
-                // Return error response for form parsing failures
-                let error_message = match status {
-                    StatusCode::PAYLOAD_TOO_LARGE => "File upload exceeds maximum size limit",
-                    _ => "Failed to parse form data",
-                };
-                return Response::builder()
-                    .status(status)
-                    .header("content-type", "text/plain")
-                    .body(Body::from(error_message))
-                    .unwrap_or_else(|_| Response::new(Body::from(error_message)));
-            }
-        }
-    } else {
-        (HashMap::new(), Vec::new())
-    };
-
     let path_clone = path.clone();
     let headers_for_worker = header_map;
-    let worker = move || -> Result<js_engine::JsHttpResponse, String> {
+    let request_id_for_worker = request_id.clone();
+    let tenant_for_worker = tenant.clone();
+    let metered_user = auth_user.as_ref().map(|user| user.user_id.clone());
+    let tenant_info = tenancy::request_tenant_json(tenant.as_deref(), overrides.as_ref());
+    let worker = move || -> Result<js_engine::JsHttpResponse, script_errors::ScriptFailure> {
+        // Tag log entries written by the handler with this request; fetch()
+        // forwards the request ID and trace to upstream services
+        let mut log_context =
+            js_engine::LogContext::default().with_request_id(request_id_for_worker);
+        if let Some(trace) = &trace_context {
+            log_context = log_context.with_trace(trace);
+        }
+        let _log_context = js_engine::enter_log_context(log_context);
+        // Scope the handler's script data to the request's tenant
+        let _tenant = tenancy::enter_tenant(tenant_for_worker);
+
         // Create authentication context for JavaScript
-        let auth_context = if let Some(ref auth_user) = auth_user {
-            auth::JsAuthContext::authenticated(
-                auth_user.user_id.clone(),
-                auth_user.email.clone(),
-                auth_user.name.clone(),
-                auth_user.provider.clone(),
-                auth_user.is_admin,
-                auth_user.is_editor,
-            )
-        } else {
-            auth::JsAuthContext::anonymous()
-        };
+        let auth_context = auth_user
+            .as_ref()
+            .map(auth::AuthUser::js_auth_context)
+            .unwrap_or_else(auth::JsAuthContext::anonymous);
 
         // Create UserContext for secure globals based on authenticated user
-        let user_context = if let Some(ref auth_user) = auth_user {
-            if auth_user.is_admin {
-                security::UserContext::admin(auth_user.user_id.clone())
-            } else {
-                security::UserContext::authenticated(auth_user.user_id.clone())
-            }
-        } else {
-            security::UserContext::anonymous()
-        };
+        let user_context = auth_user
+            .as_ref()
+            .map(auth::AuthUser::user_context)
+            .unwrap_or_else(security::UserContext::anonymous);
 
         // Use the secure execution path with authentication context
         let params = js_engine::RequestExecutionParams {
@@ -2475,29 +3384,71 @@ async fn handle_dynamic_request(
             auth_context: Some(auth_context),
             route_params: Some(route_params.clone()),
             uploaded_files: Some(uploaded_files.clone()),
+            timeout_ms: timeout_override,
+            tenant: tenant_info,
         };
 
-        js_engine::execute_script_for_request_secure(params)
+        js_engine::execute_script_for_request_detailed(params)
     };
 
-    // The timeout must wrap the un-awaited join handle: awaiting spawn_blocking first
-    // would block until the script finishes and the timeout could never fire. On
-    // timeout the blocking thread is abandoned; the QuickJS interrupt handler
-    // (see js_engine::create_sandboxed_runtime) terminates the script itself.
-    let timed = match tokio::time::timeout(
-        std::time::Duration::from_millis(script_timeout_ms),
-        tokio::task::spawn_blocking(worker),
+    // Time spent waiting for a worker counts against the timeout. On timeout
+    // the worker is not waited for; the QuickJS interrupt handler (see
+    // js_engine::create_sandboxed_runtime) terminates the script itself, and
+    // an execution still queued is skipped.
+    let started = std::time::Instant::now();
+    let timed = tokio::time::timeout(
+        std::time::Duration::from_millis(script_timeout_ms) + debugger::pause_allowance(),
+        worker_pool::run(&owner_uri, worker),
     )
-    .await
-    {
-        Ok(join) => join.map_err(|e| format!("join error: {}", e)),
+    .await;
+    if let Some(tenant) = tenant.as_deref() {
+        tenant_quotas::record_execution(tenant, started.elapsed());
+    }
+    metering::record_request(
+        tenant.as_deref(),
+        metered_user.as_deref(),
+        &owner_uri,
+        started.elapsed(),
+    );
+    let timed = match timed {
+        Ok(Err(worker_pool::WorkerPoolError::Saturated)) => {
+            if let Some(claim) = idempotent_request {
+                claim.release().await;
+            }
+            warn!(
+                "[{}] Script workers saturated; rejecting {} {}",
+                request_id, method_log, path_log
+            );
+            return error_to_response(error::errors::service_unavailable(
+                &path,
+                &worker_pool::WorkerPoolError::Saturated.to_string(),
+                &request_id,
+            ));
+        }
+        Ok(result) => result.map_err(|e| e.to_string()),
         Err(_) => {
+            if let Some(claim) = idempotent_request {
+                claim.release().await;
+            }
             return error_to_response(error::errors::script_timeout(&path, &request_id));
         }
     };
 
+    // Store the response for retries, or give the key up when the handler
+    // did not produce one
+    if let Some(claim) = idempotent_request {
+        match &timed {
+            Ok(Ok(js_response)) => claim.complete(js_response).await,
+            _ => claim.release().await,
+        }
+    }
+
     match timed {
         Ok(Ok(js_response)) => {
+            let cached = match (route_cache.as_ref(), cache_key) {
+                (Some(route), Some(key)) => response_cache::store(route, key, &js_response),
+                _ => false,
+            };
             info!(
                 "[{}] ✅ Successfully executed handler '{}' - status: {}, body_length: {} bytes, headers: {}",
                 request_id,
@@ -2507,26 +3458,60 @@ async fn handle_dynamic_request(
                 js_response.headers.len()
             );
             let mut response = build_http_response_from_js(js_response);
+            if cached {
+                response.headers_mut().insert(
+                    response_cache::CACHE_STATUS_HEADER,
+                    axum::http::HeaderValue::from_static("MISS"),
+                );
+            }
             if strip_body {
                 *response.body_mut() = Body::empty();
             }
             response
         }
-        Ok(Err(e)) => {
+        Ok(Err(failure)) => {
+            let exception = failure.exception.as_ref();
             error!(
+                request_id = %request_id,
+                handler = %handler_name,
+                script = %owner_uri,
+                exception = exception.map(|e| e.headline()).as_deref(),
+                line = exception.and_then(|e| e.line_number),
+                column = exception.and_then(|e| e.column_number),
                 "[{}] ❌ Script execution error for {} {}: {} (handler: {}, script: {})",
-                request_id, method_log, path_log, e, handler_name, owner_uri
+                request_id, method_log, path_log, failure, handler_name, owner_uri
             );
-            // Log FATAL error to database
+            // Log FATAL error to database, with the exception as structured data
             let error_msg = format!(
                 "Script execution failed for handler '{}': {}",
-                handler_name, e
+                handler_name, failure
             );
-            repository::insert_log_message_async(&owner_uri, &error_msg, "FATAL").await;
+            let data = exception.and_then(|e| serde_json::to_value(e).ok());
+            let context = serde_json::to_value(
+                js_engine::LogContext::for_handler(
+                    js_engine::HandlerInvocationKind::HttpRoute,
+                    &handler_name,
+                )
+                .with_request_id(&request_id)
+                .with_method_and_path(&method_log, &path_log),
+            )
+            .ok();
+            repository::insert_structured_log_message_async(
+                &owner_uri,
+                &error_msg,
+                "FATAL",
+                data.as_ref(),
+                context.as_ref(),
+            )
+            .await;
 
-            error_to_response(error::errors::script_execution_failed(
+            let exposed = exception
+                .filter(|_| script_errors::error_details_exposed())
+                .map(|e| e.sanitized());
+            error_to_response(error::errors::script_exception(
                 &path,
-                &e,
+                &failure.summary,
+                exposed,
                 &request_id,
             ))
         }
@@ -2540,6 +3525,52 @@ async fn handle_dynamic_request(
     }
 }
 
+/// Refresh a stale cached route response: run the handler for an anonymous
+/// GET on a worker and store its response, or let a later request retry
+fn spawn_route_refresh(
+    params: js_engine::RequestExecutionParams,
+    tenant: Option<String>,
+    route: Arc<response_cache::RouteCache>,
+    key: response_cache::CacheKey,
+    script_timeout_ms: u64,
+) {
+    tokio::spawn(async move {
+        let script_uri = params.script_uri.clone();
+        let path = params.path.clone();
+        let worker = move || {
+            let _tenant = tenancy::enter_tenant(tenant);
+            js_engine::execute_script_for_request_detailed(params)
+        };
+        let refreshed = tokio::time::timeout(
+            std::time::Duration::from_millis(script_timeout_ms),
+            worker_pool::run(&script_uri, worker),
+        )
+        .await;
+        match refreshed {
+            Ok(Ok(Ok(js_response))) => {
+                if response_cache::store(&route, key, &js_response) {
+                    debug!("Refreshed cached response of GET {}", path);
+                }
+            }
+            Ok(Ok(Err(failure))) => {
+                warn!(
+                    "Refreshing cached response of GET {} failed: {}",
+                    path, failure
+                );
+                response_cache::refresh_failed(&key);
+            }
+            Ok(Err(e)) => {
+                debug!("Could not refresh cached response of GET {}: {}", path, e);
+                response_cache::refresh_failed(&key);
+            }
+            Err(_) => {
+                warn!("Refreshing cached response of GET {} timed out", path);
+                response_cache::refresh_failed(&key);
+            }
+        }
+    });
+}
+
 /// Finds an available port starting from the given port.
 /// Returns the available port and the socket address.
 fn find_available_port(config: &config::Config) -> AppResult<(u16, std::net::SocketAddr)> {
@@ -2660,67 +3691,311 @@ pub async fn start_server_without_shutdown_with_config(config: config::Config) -
 // Helper Functions for Refactored Route Setup and Request Handling
 // ============================================================================
 
-/// Try to serve an asset if the path matches a registered asset
-async fn try_serve_asset(path: &str, method: &str) -> Option<Response> {
+/// Try to serve an asset if the path matches a registered asset. Image
+/// assets are transformed as the query's `w`, `h`, `fit`, `crop`, `format`
+/// and `q` parameters ask. Asset routes registered with `cache` are served
+/// from the response cache, stale copies while they are refreshed.
+async fn try_serve_asset(
+    host: Option<&str>,
+    path: &str,
+    query: &str,
+    method: &str,
+    accept_encoding: Option<&str>,
+    request_id: &str,
+) -> Option<Response> {
     // Asset routes have no per-method registration (see `AssetPathRegistration`),
     // so HEAD is served the same way as GET with the body dropped afterward.
     if method != "GET" && method != "HEAD" {
         return None;
     }
 
-    let registration = asset_registry::get_global_registry().get_asset_registration(path)?;
+    // Content-hashed URLs of versioned routes only match the asset's current content
+    let (registration, requested_hash) =
+        asset_registry::get_global_registry().resolve_request(host, path)?;
 
-    if let Some(asset) =
-        repository::fetch_asset_async(&registration.script_uri, &registration.asset_name).await
+    // Private assets are only served at signed URLs
+    if registration.private
+        && !signed_urls::verify(&registration.script_uri, &registration.asset_name, query)
     {
-        let mut response = asset.content.into_response();
-        // For text/* types, ensure charset=utf-8 is declared so browsers don't
-        // fall back to Windows-1252 and garble multi-byte UTF-8 characters.
-        let content_type =
-            if asset.mimetype.starts_with("text/") && !asset.mimetype.contains("charset") {
-                format!("{}; charset=utf-8", asset.mimetype)
-            } else {
-                asset.mimetype.clone()
-            };
-        response.headers_mut().insert(
-            axum::http::header::CONTENT_TYPE,
-            axum::http::HeaderValue::from_str(&content_type).unwrap_or(
-                axum::http::HeaderValue::from_static("application/octet-stream"),
-            ),
-        );
+        return Some(error_to_response(error::errors::forbidden(
+            path,
+            "Missing, invalid or expired signature",
+            request_id,
+        )));
+    }
+
+    let encoding = asset_compression::negotiate(accept_encoding);
+
+    // Content-hashed URLs are cached by clients for good, so only stable
+    // paths use the response cache. Each encoding is cached on its own.
+    let cache_key = registration
+        .cache
+        .as_ref()
+        .filter(|_| requested_hash.is_none())
+        .map(|route| {
+            response_cache::request_key(
+                route,
+                &registration.script_uri,
+                None,
+                host,
+                path,
+                query,
+                &axum::http::HeaderMap::new(),
+            )
+        })
+        .map(|key| match encoding {
+            Some(encoding) => key.with_variant(encoding.as_str()),
+            None => key,
+        });
+    if let Some(key) = cache_key.as_ref()
+        && let Some(hit) = response_cache::lookup(key)
+    {
+        if hit.refresh {
+            let registration = registration.clone();
+            let key = key.clone();
+            let (path, query, request_id) =
+                (path.to_string(), query.to_string(), request_id.to_string());
+            tokio::spawn(async move {
+                match load_asset_response(&registration, None, &path, &query, encoding, &request_id)
+                    .await
+                {
+                    Some(Ok(js_response)) => {
+                        if let Some(route) = registration.cache.as_ref() {
+                            response_cache::store(route, key, &js_response);
+                        }
+                    }
+                    _ => response_cache::refresh_failed(&key),
+                }
+            });
+        }
+        let mut response = hit.response;
         if method == "HEAD" {
             *response.body_mut() = Body::empty();
         }
         return Some(response);
     }
 
-    warn!(
-        "Asset '{}' registered for path '{}' from script '{}' but not found in repository",
-        registration.asset_name, path, registration.script_uri
-    );
-    None
+    let js_response = match load_asset_response(
+        &registration,
+        requested_hash.as_deref(),
+        path,
+        query,
+        encoding,
+        request_id,
+    )
+    .await?
+    {
+        Ok(js_response) => js_response,
+        Err(response) => return Some(response),
+    };
+    let cached = match (registration.cache.as_ref(), cache_key) {
+        (Some(route), Some(key)) => response_cache::store(route, key, &js_response),
+        _ => false,
+    };
+    let mut response = build_http_response_from_js(js_response);
+    if cached {
+        response.headers_mut().insert(
+            response_cache::CACHE_STATUS_HEADER,
+            axum::http::HeaderValue::from_static("MISS"),
+        );
+    }
+    if method == "HEAD" {
+        *response.body_mut() = Body::empty();
+    }
+    Some(response)
 }
 
-/// Check if request should be routed to a stream handler
-fn should_route_to_stream(path: &str, method: &str) -> bool {
-    let is_get = method == "GET";
-    let is_stream_registered = stream_registry::GLOBAL_STREAM_REGISTRY.is_stream_registered(path);
+/// The response for a registered asset path: None when the asset is gone or
+/// does not have the requested content hash, an error response when its
+/// image transform fails or is not one the route allows. Compressible assets are sent in `encoding`,
+/// precompressed when a variant is stored.
+async fn load_asset_response(
+    registration: &asset_registry::AssetPathRegistration,
+    requested_hash: Option<&str>,
+    path: &str,
+    query: &str,
+    encoding: Option<asset_compression::Encoding>,
+    request_id: &str,
+) -> Option<Result<js_engine::JsHttpResponse, Response>> {
+    let mut asset =
+        repository::fetch_asset_async(&registration.script_uri, &registration.asset_name).await;
+    if asset.is_none()
+        && let Some(fallback) = registration.fallback_asset.as_deref()
+    {
+        asset = repository::fetch_asset_async(&registration.script_uri, fallback).await;
+    }
+    let Some(asset) = asset else {
+        // Paths under asset prefixes name assets that may simply not exist
+        if !registration.from_prefix {
+            warn!(
+                "Asset '{}' registered for path '{}' from script '{}' but not found in repository",
+                registration.asset_name, path, registration.script_uri
+            );
+        }
+        return None;
+    };
+    if let Some(hash) = requested_hash
+        && hash != asset_registry::content_hash(&asset.content)
+    {
+        return None;
+    }
+    let asset_name = asset.uri.clone();
+    let policy = registration
+        .image_transforms
+        .as_ref()
+        .filter(|_| image_transform::is_transformable(&asset.mimetype));
+    let transformed = policy.is_some();
+    let (mut content, mimetype) = if let Some(policy) = policy {
+        match transform_asset_image(asset.content, asset.mimetype, query, policy).await {
+            Ok(transformed) => transformed,
+            Err(e) => {
+                return Some(Err(error_to_response(error::errors::bad_request(
+                    path, &e, request_id,
+                ))));
+            }
+        }
+    } else {
+        (asset.content, asset.mimetype)
+    };
+    // For text/* types, ensure charset=utf-8 is declared so browsers don't
+    // fall back to Windows-1252 and garble multi-byte UTF-8 characters.
+    let content_type = if mimetype.starts_with("text/") && !mimetype.contains("charset") {
+        format!("{}; charset=utf-8", mimetype)
+    } else {
+        mimetype
+    };
+    let content_type = if axum::http::HeaderValue::from_str(&content_type).is_ok() {
+        content_type
+    } else {
+        "application/octet-stream".to_string()
+    };
+    // Custom headers stored with the asset, then those of the route
+    // registration, which take precedence. Names are lowercased so an
+    // override replaces the header whatever its case.
+    let mut headers: HashMap<String, String> = asset
+        .headers
+        .iter()
+        .chain(registration.headers.iter())
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+        .collect();
+    // The route's cache policy decides what CDNs keep and how they find it
+    // for purging
+    if let Some(cache_control) = registration
+        .cache_policy
+        .as_ref()
+        .and_then(asset_cdn::CachePolicy::cache_control)
+    {
+        headers.insert("cache-control".to_string(), cache_control);
+    }
+    if let Some((name, keys)) = asset_cdn::surrogate_key_header(
+        registration.cache_policy.as_ref(),
+        &registration.script_uri,
+        &asset_name,
+    ) {
+        headers.insert(name, keys);
+    }
+    if requested_hash.is_some() {
+        headers.insert(
+            "cache-control".to_string(),
+            asset_registry::IMMUTABLE_CACHE_CONTROL.to_string(),
+        );
+    } else if registration.versioned {
+        // The stable path always points at the latest version, so clients
+        // must revalidate it
+        headers
+            .entry("cache-control".to_string())
+            .or_insert_with(|| "no-cache".to_string());
+    }
+    if registration.private {
+        // Signed URLs expire, so shared caches must not keep the response
+        headers.insert("cache-control".to_string(), "private, no-cache".to_string());
+    }
+    if asset_compression::should_compress(&content_type, content.len()) {
+        let vary = headers.entry("vary".to_string()).or_default();
+        if !vary.to_ascii_lowercase().contains("accept-encoding") {
+            if !vary.is_empty() {
+                vary.push_str(", ");
+            }
+            vary.push_str("Accept-Encoding");
+        }
+        if let Some(encoding) = encoding {
+            // Image transforms produce new content, which has no stored variants
+            let stored = if transformed {
+                None
+            } else {
+                repository::fetch_asset_encoding_async(
+                    &registration.script_uri,
+                    &asset_name,
+                    encoding,
+                )
+                .await
+            };
+            let used = match stored {
+                Some(variant) if variant.is_empty() => None,
+                Some(variant) => {
+                    content = variant;
+                    Some(encoding)
+                }
+                None => {
+                    let (body, used) =
+                        asset_compression::compress_response(content, encoding).await;
+                    content = body;
+                    used
+                }
+            };
+            if let Some(used) = used {
+                headers.insert("content-encoding".to_string(), used.as_str().to_string());
+            }
+        }
+    }
+    Some(Ok(js_engine::JsHttpResponse {
+        status: 200,
+        body: content,
+        content_type: Some(content_type),
+        headers,
+    }))
+}
 
-    info!(
-        "Stream check - method: {}, is_get: {}, path: '{}', is_registered: {}",
-        method, is_get, path, is_stream_registered
-    );
+/// Apply the image transform in an asset request's query, if it has one and
+/// the route's `policy` allows it, on the blocking pool
+async fn transform_asset_image(
+    content: Vec<u8>,
+    mimetype: String,
+    query: &str,
+    policy: &image_transform::TransformPolicy,
+) -> Result<(Vec<u8>, String), String> {
+    let Some(transform) = image_transform::ImageTransform::from_query(query)? else {
+        return Ok((content, mimetype));
+    };
+    policy.check(&transform)?;
+    let transformed =
+        tokio::task::spawn_blocking(move || image_transform::transform(&content, &transform))
+            .await
+            .map_err(|e| format!("Image transform failed: {}", e))??;
+    let image_transform::TransformedImage { content, mimetype } =
+        std::sync::Arc::unwrap_or_clone(transformed);
+    Ok((content, mimetype.to_string()))
+}
 
-    if is_get && is_stream_registered {
-        info!("Routing to stream handler for path: {}", path);
-        return true;
+/// Registry key of the stream a request should be routed to, if any: the
+/// stream registered for the request's host, else the one for any host
+fn stream_key_for_request(host: Option<&str>, path: &str, method: &str) -> Option<String> {
+    if method != "GET" {
+        return None;
     }
+    let key = route_index::request_path_keys(host, path)
+        .into_iter()
+        .find(|key| stream_registry::GLOBAL_STREAM_REGISTRY.is_stream_registered(key));
 
-    false
+    info!(
+        "Stream check - method: {}, path: '{}', stream: {:?}",
+        method, path, key
+    );
+    key
 }
 
 /// Build an HTTP response from a JavaScript response object
-fn build_http_response_from_js(js_response: js_engine::JsHttpResponse) -> Response {
+pub(crate) fn build_http_response_from_js(js_response: js_engine::JsHttpResponse) -> Response {
     let mut response = (
         StatusCode::from_u16(js_response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
         js_response.body,
@@ -2982,4 +4257,125 @@ mod tests {
         let params = match_route_pattern("/api/users/:id", "/api/posts/123");
         assert!(params.is_none(), "Different literals shouldn't match");
     }
+
+    /// A schema whose `delayed` field answers its argument after sleeping
+    /// that many milliseconds
+    fn delayed_schema() -> async_graphql::dynamic::Schema {
+        use async_graphql::dynamic::{Field, FieldFuture, InputValue, Object, Schema, TypeRef};
+        let query = Object::new("Query").field(
+            Field::new("delayed", TypeRef::named_nn(TypeRef::INT), |ctx| {
+                FieldFuture::new(async move {
+                    let ms = ctx.args.try_get("ms")?.i64()?;
+                    tokio::time::sleep(std::time::Duration::from_millis(ms as u64)).await;
+                    Ok(Some(async_graphql::Value::from(ms)))
+                })
+            })
+            .argument(InputValue::new("ms", TypeRef::named_nn(TypeRef::INT))),
+        );
+        Schema::build("Query", None, None)
+            .register(query)
+            .finish()
+            .unwrap()
+    }
+
+    async fn post_graphql(body: &str, max_batch_size: usize) -> serde_json::Value {
+        let request: async_graphql::BatchRequest = serde_json::from_str(body).unwrap();
+        execute_graphql_batch(
+            &delayed_schema(),
+            request,
+            max_batch_size,
+            auth::JsAuthContext::anonymous(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_graphql_single_request() {
+        let response = post_graphql(r#"{"query": "{ delayed(ms: 1) }"}"#, 10).await;
+        assert_eq!(response["data"]["delayed"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_batch_keeps_request_order() {
+        // The first operation finishes last, yet its response comes first
+        let body = r#"[
+            {"query": "{ delayed(ms: 200) }"},
+            {"query": "{ delayed(ms: 1) }"},
+            {"query": "{ delayed(ms: 50) }"}
+        ]"#;
+        let started = std::time::Instant::now();
+        let response = post_graphql(body, 3).await;
+        let delays: Vec<_> = response
+            .as_array()
+            .expect("a batch is answered with an array")
+            .iter()
+            .map(|item| item["data"]["delayed"].as_i64().unwrap())
+            .collect();
+        assert_eq!(delays, vec![200, 1, 50]);
+        assert!(
+            started.elapsed() < std::time::Duration::from_millis(250),
+            "batched operations should run concurrently"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_batch_limits() {
+        let empty = post_graphql("[]", 10).await;
+        assert_eq!(
+            empty["error"],
+            "A batch must hold between 1 and 10 operations, got 0"
+        );
+
+        let body = r#"[{"query": "{ delayed(ms: 1) }"}, {"query": "{ delayed(ms: 1) }"}]"#;
+        let over = post_graphql(body, 1).await;
+        assert_eq!(
+            over["error"],
+            "A batch must hold between 1 and 1 operations, got 2"
+        );
+
+        let disabled = post_graphql(body, 0).await;
+        assert_eq!(disabled["error"], "Batched operations are disabled");
+        // A single request is not a batch and still runs
+        let single = post_graphql(r#"{"query": "{ delayed(ms: 1) }"}"#, 0).await;
+        assert_eq!(single["data"]["delayed"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_batch_persisted_queries() {
+        let hash = graphql_persisted::store("{ delayed(ms: 2) }");
+        let unknown = graphql_persisted::query_hash("{ delayed(ms: 3) }");
+        let persisted = |hash: &str| {
+            serde_json::json!({
+                "extensions": { "persistedQuery": { "version": 1, "sha256Hash": hash } }
+            })
+        };
+        let body = serde_json::json!([
+            persisted(&hash),
+            persisted(&unknown),
+            { "query": "{ delayed(ms: 1) }" },
+        ]);
+        let response = post_graphql(&body.to_string(), 10).await;
+
+        // The miss fails only its own operation
+        assert_eq!(response[0]["data"]["delayed"], 2);
+        assert_eq!(
+            response[1]["errors"][0]["message"],
+            "PersistedQueryNotFound"
+        );
+        assert_eq!(
+            response[1]["errors"][0]["extensions"]["code"],
+            "PERSISTED_QUERY_NOT_FOUND"
+        );
+        assert_eq!(response[2]["data"]["delayed"], 1);
+
+        // Sending the document with its hash stores it for later requests
+        let register = serde_json::json!({
+            "query": "{ delayed(ms: 3) }",
+            "extensions": { "persistedQuery": { "version": 1, "sha256Hash": unknown } }
+        });
+        let stored = post_graphql(&register.to_string(), 10).await;
+        assert_eq!(stored["data"]["delayed"], 3);
+        let retried = post_graphql(&persisted(&unknown).to_string(), 10).await;
+        assert_eq!(retried["data"]["delayed"], 3);
+    }
 }
@@ -0,0 +1,1699 @@
+//! LLM completions and embeddings for scripts (`llm.complete`,
+//! `llm.stream`, `embeddings.create`).
+//!
+//! `llm.complete(provider, params)` sends a chat completion to a provider
+//! named in `[javascript.llm]`. `openai` and `anthropic` are built in;
+//! administrators add others, such as a local OpenAI-compatible server.
+//! The API key comes from the script's or the calling user's secret named by
+//! the provider's `api_key_secret`, so scripts never see it.
+//!
+//! Scripts may only use the models allowed for them in the configuration:
+//! `script_models` lists them per script URI, `allowed_models` for every
+//! other script. A pattern ending in `*` allows every model starting with
+//! the text before it.
+//!
+//! With `tools` set, the model may call registered MCP tools. The engine
+//! advertises them to the provider, runs the calls the model requests with
+//! the caller's permissions and sends back the results, for up to
+//! `maxToolRounds` rounds, and returns the final answer.
+//!
+//! `llm.stream(params, streamPath)` runs the same completion in the
+//! background and sends its text to the connections of a registered stream
+//! as the provider produces it.
+//!
+//! `embeddings.create(input, options)` turns texts into vectors with the
+//! configured `embedding_provider` and `embedding_model`, for storing in
+//! the script's vector collections (`vectors`).
+//!
+//! Prompts and outputs are checked by the configured moderation
+//! (`moderation`) when its `input` or `output` action is set.
+
+use std::io::Read;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::config::{LlmApi, LlmConfig, LlmProviderConfig};
+
+/// `maxTokens` used when a script gives none
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// Largest provider response read
+const MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Most texts one `embeddings.create` call may embed
+pub const MAX_EMBEDDING_INPUTS: usize = 256;
+
+/// `maxToolRounds` used when a script gives none
+const DEFAULT_MAX_TOOL_ROUNDS: u32 = 5;
+
+/// Most rounds of tool calls one completion may run
+const MAX_TOOL_ROUNDS: u32 = 20;
+
+/// Longest streamed text is held back before it is sent to the stream
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Version header the Anthropic messages API requires
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+static SETTINGS: OnceLock<RwLock<LlmConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<LlmConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the LLM configuration. Called once at server startup.
+pub fn configure(config: &LlmConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+pub(crate) fn current_settings() -> LlmConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Shared connection-pooled client; provider base URLs come from the
+/// configuration, so they are not restricted like `fetch` targets
+fn shared_client() -> Result<&'static reqwest::blocking::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::blocking::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Author of a chat message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+/// One chat message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+/// Parameters of `llm.complete`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CompletionParams {
+    pub model: String,
+    /// System prompt, placed before the messages
+    pub system: Option<String>,
+    pub messages: Vec<Message>,
+    /// User message appended after the messages
+    pub prompt: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+    /// Sequences that end the completion
+    pub stop: Vec<String>,
+    /// Registered MCP tools the model may call
+    pub tools: Option<ToolSelection>,
+    /// Most rounds of tool calls before the completion fails
+    pub max_tool_rounds: Option<u32>,
+}
+
+/// MCP tools offered to the model
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ToolSelection {
+    /// `true` for every registered tool
+    All(bool),
+    /// Names of registered tools
+    Named(Vec<String>),
+}
+
+impl ToolSelection {
+    fn is_enabled(&self) -> bool {
+        match self {
+            Self::All(all) => *all,
+            Self::Named(names) => !names.is_empty(),
+        }
+    }
+}
+
+/// Result of `llm.complete`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub provider: String,
+    /// Model that answered, as reported by the provider
+    pub model: String,
+    pub text: String,
+    /// Why generation stopped, such as "stop", "length" or "end_turn"
+    pub finish_reason: Option<String>,
+    /// Summed over every round when tools were called
+    pub usage: Usage,
+    /// Tool calls run while completing, in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Flagged prompt and output when moderation is set to `flag`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<crate::moderation::CompletionModeration>,
+}
+
+/// A tool call the model requested and the engine ran
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: Value,
+    /// What the tool returned; null when it failed
+    pub result: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A tool call as requested by the model
+#[derive(Debug, Clone, PartialEq)]
+struct RequestedToolCall {
+    id: String,
+    name: String,
+    /// Arguments, or why they could not be read
+    arguments: Result<Value, String>,
+}
+
+/// Token counts reported by the provider
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+}
+
+impl Usage {
+    fn add(&mut self, other: &Usage) {
+        let sum = |total: Option<u64>, more: Option<u64>| match (total, more) {
+            (Some(total), Some(more)) => Some(total + more),
+            (total, more) => total.or(more),
+        };
+        self.input_tokens = sum(self.input_tokens, other.input_tokens);
+        self.output_tokens = sum(self.output_tokens, other.output_tokens);
+    }
+}
+
+/// Options of `embeddings.create`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct EmbeddingParams {
+    /// Provider name; defaults to `embedding_provider` of the configuration
+    pub provider: Option<String>,
+    /// Model name; defaults to `embedding_model` of the configuration
+    pub model: Option<String>,
+    /// Length of the returned vectors, for models that can shorten them
+    pub dimensions: Option<u32>,
+}
+
+/// Result of `embeddings.create`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Embeddings {
+    pub provider: String,
+    pub model: String,
+    /// One vector per input text, in input order
+    pub embeddings: Vec<Vec<f32>>,
+    pub usage: Usage,
+}
+
+/// HTTP request for one completion or embedding call
+#[derive(Debug)]
+struct ProviderRequest {
+    url: String,
+    headers: Vec<(&'static str, String)>,
+    body: Value,
+}
+
+/// Run a completion for `script_uri`. `user_id` is the signed-in user,
+/// whose secrets are tried before the script's. When `params` enables
+/// tools, `call_tool` runs each tool call the model requests.
+pub fn complete(
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider_name: &str,
+    params: &CompletionParams,
+    mut call_tool: impl FnMut(&str, Value) -> Result<Value, String>,
+) -> Result<Completion, String> {
+    let mut prepared = prepare(script_uri, user_id, provider_name, params, false)?;
+    let tools = selected_tools(params.tools.as_ref())?;
+    let max_rounds = params.max_tool_rounds.unwrap_or(DEFAULT_MAX_TOOL_ROUNDS);
+    if max_rounds > MAX_TOOL_ROUNDS {
+        return Err(format!("maxToolRounds must be at most {}", MAX_TOOL_ROUNDS));
+    }
+    if !tools.is_empty() {
+        add_tools(prepared.api, &mut prepared.request.body, &tools);
+    }
+
+    // Rounds that ran are paid for even when a later one fails
+    let mut usage = Usage::default();
+    let mut run_rounds = || {
+        let mut tool_calls = Vec::new();
+        for round in 0..=max_rounds {
+            debug!(
+                script_uri = %script_uri,
+                provider = %provider_name,
+                model = %params.model,
+                round,
+                "Sending LLM completion"
+            );
+            let body = send(&prepared.config, provider_name, &prepared.request)?;
+            let mut completion = parse_response(prepared.api, provider_name, &body)?;
+            usage.add(&completion.usage);
+
+            let requested = if tools.is_empty() {
+                Vec::new()
+            } else {
+                parse_tool_calls(prepared.api, provider_name, &body)?
+            };
+            if requested.is_empty() {
+                completion.usage = usage.clone();
+                completion.tool_calls = tool_calls;
+                log_completion(script_uri, &completion);
+                return Ok(completion);
+            }
+            if round == max_rounds {
+                break;
+            }
+
+            let mut results = Vec::with_capacity(requested.len());
+            for call in &requested {
+                debug!(
+                    script_uri = %script_uri,
+                    tool = %call.name,
+                    "Running tool call requested by LLM"
+                );
+                let result = call.arguments.clone().and_then(|arguments| {
+                    if tools.iter().any(|tool| tool.name == call.name) {
+                        call_tool(&call.name, arguments)
+                    } else {
+                        Err(format!("Tool '{}' is not available", call.name))
+                    }
+                });
+                let (value, error) = match &result {
+                    Ok(value) => (value.clone(), None),
+                    Err(e) => (Value::Null, Some(e.clone())),
+                };
+                tool_calls.push(ToolCall {
+                    name: call.name.clone(),
+                    arguments: call.arguments.clone().unwrap_or(Value::Null),
+                    result: value,
+                    error,
+                });
+                results.push(result);
+            }
+            add_tool_results(
+                prepared.api,
+                &mut prepared.request.body,
+                &body,
+                &requested,
+                &results,
+            );
+        }
+        Err(format!(
+            "The model was still calling tools after {} rounds",
+            max_rounds
+        ))
+    };
+    let result = run_rounds();
+    crate::llm_usage::record(
+        &prepared.config,
+        script_uri,
+        user_id,
+        provider_name,
+        &params.model,
+        &usage,
+    );
+    let mut completion = result?;
+    completion.moderation = crate::moderation::moderate_output(
+        &prepared.config,
+        script_uri,
+        user_id,
+        prepared.input_moderation,
+        &completion.text,
+    )?;
+    Ok(completion)
+}
+
+/// A streamed completion whose parameters have been checked, ready to run
+/// on a thread that may block
+#[derive(Debug)]
+pub struct CompletionStream {
+    script_uri: String,
+    user_id: Option<String>,
+    provider_name: String,
+    /// Model as requested, which usage is recorded under
+    model: String,
+    api: LlmApi,
+    config: LlmConfig,
+    request: ProviderRequest,
+    input_moderation: Option<crate::moderation::ModerationResult>,
+}
+
+/// Check a completion for `script_uri` and prepare it for streaming, so
+/// mistakes are reported before anything runs in the background
+pub fn prepare_stream(
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider_name: &str,
+    params: &CompletionParams,
+) -> Result<CompletionStream, String> {
+    let prepared = prepare(script_uri, user_id, provider_name, params, true)?;
+    Ok(CompletionStream {
+        script_uri: script_uri.to_string(),
+        user_id: user_id.map(str::to_string),
+        provider_name: provider_name.to_string(),
+        model: params.model.clone(),
+        api: prepared.api,
+        config: prepared.config,
+        request: prepared.request,
+        input_moderation: prepared.input_moderation,
+    })
+}
+
+impl CompletionStream {
+    /// Run the completion, passing each piece of text to `on_delta` as the
+    /// provider produces it. Returns the whole completion.
+    pub fn run(self, mut on_delta: impl FnMut(&str)) -> Result<Completion, String> {
+        debug!(
+            script_uri = %self.script_uri,
+            provider = %self.provider_name,
+            "Streaming LLM completion"
+        );
+        let response = post(&self.config, &self.provider_name, &self.request)?;
+        let reader = std::io::BufReader::new(response.take(MAX_RESPONSE_BYTES));
+        let mut completion = Completion {
+            provider: self.provider_name.clone(),
+            model: String::new(),
+            text: String::new(),
+            finish_reason: None,
+            usage: Usage::default(),
+            tool_calls: Vec::new(),
+            moderation: None,
+        };
+        let result = read_event_stream(reader, |data| {
+            let data: Value = serde_json::from_str(data)
+                .map_err(|_| format!("{} returned an unexpected response", self.provider_name))?;
+            if let Some(delta) = apply_stream_event(self.api, &mut completion, &data)? {
+                completion.text.push_str(&delta);
+                on_delta(&delta);
+            }
+            Ok(())
+        })
+        .map_err(|e| format!("{} stream failed: {}", self.provider_name, e));
+        crate::llm_usage::record(
+            &self.config,
+            &self.script_uri,
+            self.user_id.as_deref(),
+            &self.provider_name,
+            &self.model,
+            &completion.usage,
+        );
+        result?;
+        log_completion(&self.script_uri, &completion);
+        completion.moderation = crate::moderation::moderate_output(
+            &self.config,
+            &self.script_uri,
+            self.user_id.as_deref(),
+            self.input_moderation,
+            &completion.text,
+        )?;
+        Ok(completion)
+    }
+
+    /// Run the completion, sending its text to the connections of the
+    /// stream at `path` as `llm.delta` messages, then an `llm.done` message
+    /// with the whole completion or an `llm.error` message. Every message
+    /// carries `id`. Must run inside the server's runtime.
+    pub fn pipe_to_stream(self, id: &str, path: &str) {
+        let send = |message: Value| {
+            if let Err(e) = crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                .broadcast_to_stream(path, &message.to_string())
+            {
+                warn!("Failed to send LLM stream message to '{}': {}", path, e);
+            }
+        };
+
+        // Tokens arrive a few characters at a time; batch them so each
+        // message does not become a broadcast of its own
+        let mut pending = String::new();
+        let mut last_flush = Instant::now();
+        let result = self.run(|delta| {
+            pending.push_str(delta);
+            if last_flush.elapsed() >= STREAM_FLUSH_INTERVAL {
+                send(json!({ "type": "llm.delta", "id": id, "text": pending }));
+                pending.clear();
+                last_flush = Instant::now();
+            }
+        });
+        if !pending.is_empty() {
+            send(json!({ "type": "llm.delta", "id": id, "text": pending }));
+        }
+        match result {
+            Ok(completion) => {
+                send(json!({ "type": "llm.done", "id": id, "completion": completion }))
+            }
+            Err(e) => {
+                warn!("LLM stream {} to '{}' failed: {}", id, path, e);
+                send(json!({ "type": "llm.error", "id": id, "error": e }));
+            }
+        }
+    }
+}
+
+/// A checked completion request
+struct PreparedCompletion {
+    api: LlmApi,
+    config: LlmConfig,
+    request: ProviderRequest,
+    /// Flagged user messages when moderation is set to `flag`
+    input_moderation: Option<crate::moderation::ModerationResult>,
+}
+
+fn prepare(
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider_name: &str,
+    params: &CompletionParams,
+    stream: bool,
+) -> Result<PreparedCompletion, String> {
+    let config = current_settings();
+    let provider = provider_config(&config, provider_name)
+        .ok_or_else(|| format!("Unknown LLM provider '{}'", provider_name))?;
+    if stream && params.tools.as_ref().is_some_and(ToolSelection::is_enabled) {
+        return Err("tools are not supported when streaming".to_string());
+    }
+
+    if params.model.trim().is_empty() {
+        return Err("model is required".to_string());
+    }
+    if !model_allowed(allowed_models(&config, script_uri), &params.model) {
+        return Err(format!(
+            "Model '{}' is not allowed for this script",
+            params.model
+        ));
+    }
+    crate::llm_usage::check_budget(&config, script_uri, user_id)?;
+    let max_tokens = params
+        .max_tokens
+        .unwrap_or(DEFAULT_MAX_TOKENS.min(config.max_output_tokens));
+    if max_tokens == 0 || max_tokens > config.max_output_tokens {
+        return Err(format!(
+            "maxTokens must be between 1 and {}",
+            config.max_output_tokens
+        ));
+    }
+
+    let api_key = api_key(script_uri, user_id, &provider)?;
+    let mut request = build_request(&provider, api_key.as_deref(), params, max_tokens)?;
+    let input_moderation =
+        crate::moderation::moderate_input(&config, script_uri, user_id, &user_text(params))?;
+    if stream {
+        request.body["stream"] = json!(true);
+        // Only OpenAI itself is known to report usage in streams
+        if provider.api == LlmApi::Openai {
+            request.body["stream_options"] = json!({ "include_usage": true });
+        }
+    }
+    Ok(PreparedCompletion {
+        api: provider.api,
+        config,
+        request,
+        input_moderation,
+    })
+}
+
+/// Text of the user messages and prompt, which input moderation checks
+fn user_text(params: &CompletionParams) -> String {
+    params
+        .messages
+        .iter()
+        .filter(|message| message.role == Role::User)
+        .map(|message| message.content.as_str())
+        .chain(params.prompt.as_deref())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn log_completion(script_uri: &str, completion: &Completion) {
+    info!(
+        script_uri = %script_uri,
+        provider = %completion.provider,
+        model = %completion.model,
+        input_tokens = ?completion.usage.input_tokens,
+        output_tokens = ?completion.usage.output_tokens,
+        "LLM completion finished"
+    );
+}
+
+/// Compute embeddings of `input` for `script_uri` with the configured
+/// embedding provider and model unless `params` names others
+pub fn embed(
+    script_uri: &str,
+    user_id: Option<&str>,
+    input: &[String],
+    params: &EmbeddingParams,
+) -> Result<Embeddings, String> {
+    let config = current_settings();
+    let provider_name = params
+        .provider
+        .as_deref()
+        .unwrap_or(&config.embedding_provider);
+    let provider = provider_config(&config, provider_name)
+        .ok_or_else(|| format!("Unknown LLM provider '{}'", provider_name))?;
+    if provider.api == LlmApi::Anthropic {
+        return Err(format!(
+            "Provider '{}' has no embeddings API",
+            provider_name
+        ));
+    }
+    let model = params.model.as_deref().unwrap_or(&config.embedding_model);
+    if model != config.embedding_model && !model_allowed(allowed_models(&config, script_uri), model)
+    {
+        return Err(format!("Model '{}' is not allowed for this script", model));
+    }
+    if input.is_empty() || input.len() > MAX_EMBEDDING_INPUTS {
+        return Err(format!(
+            "input must contain between 1 and {} texts",
+            MAX_EMBEDDING_INPUTS
+        ));
+    }
+    crate::llm_usage::check_budget(&config, script_uri, user_id)?;
+
+    let api_key = api_key(script_uri, user_id, &provider)?;
+    let request = build_embedding_request(
+        &provider,
+        api_key.as_deref(),
+        model,
+        input,
+        params.dimensions,
+    )?;
+
+    debug!(
+        script_uri = %script_uri,
+        provider = %provider_name,
+        model = %model,
+        inputs = input.len(),
+        "Sending embedding request"
+    );
+    let body = send(&config, provider_name, &request)?;
+
+    let embeddings = parse_embedding_response(provider_name, input.len(), &body)?;
+    crate::llm_usage::record(
+        &config,
+        script_uri,
+        user_id,
+        provider_name,
+        model,
+        &embeddings.usage,
+    );
+    info!(
+        script_uri = %script_uri,
+        provider = %provider_name,
+        model = %embeddings.model,
+        input_tokens = ?embeddings.usage.input_tokens,
+        "Embeddings finished"
+    );
+    Ok(embeddings)
+}
+
+/// Check `input` with the moderation API of `provider_name` and return the
+/// provider's JSON response
+pub(crate) fn moderate(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider_name: &str,
+    model: &str,
+    input: &str,
+) -> Result<Value, String> {
+    let provider = provider_config(config, provider_name)
+        .ok_or_else(|| format!("Unknown LLM provider '{}'", provider_name))?;
+    if provider.api == LlmApi::Anthropic {
+        return Err(format!(
+            "Provider '{}' has no moderation API",
+            provider_name
+        ));
+    }
+    let api_key = api_key(script_uri, user_id, &provider)?;
+    let base_url = provider
+        .base_url
+        .as_deref()
+        .unwrap_or("https://api.openai.com/v1")
+        .trim_end_matches('/');
+    let request = ProviderRequest {
+        url: format!("{}/moderations", base_url),
+        headers: api_key
+            .map(|key| vec![("authorization", format!("Bearer {}", key))])
+            .unwrap_or_default(),
+        body: json!({ "model": model, "input": input }),
+    };
+    debug!(
+        script_uri = %script_uri,
+        provider = %provider_name,
+        model = %model,
+        "Sending moderation request"
+    );
+    send(config, provider_name, &request)
+}
+
+/// Models the script may use
+fn allowed_models<'a>(config: &'a LlmConfig, script_uri: &str) -> &'a [String] {
+    config
+        .script_models
+        .get(script_uri)
+        .unwrap_or(&config.allowed_models)
+}
+
+/// The provider's API key from the user's or the script's secrets
+fn api_key(
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider: &LlmProviderConfig,
+) -> Result<Option<String>, String> {
+    match &provider.api_key_secret {
+        Some(secret) => crate::repository::resolve_secret_db(script_uri, secret, user_id)
+            .map(Some)
+            .ok_or_else(|| format!("Secret '{}' is not set", secret)),
+        None => Ok(None),
+    }
+}
+
+/// The configured provider, falling back to the built-in ones
+fn provider_config(config: &LlmConfig, name: &str) -> Option<LlmProviderConfig> {
+    if let Some(provider) = config.providers.get(name) {
+        return Some(provider.clone());
+    }
+    let (api, secret) = match name {
+        "openai" => (LlmApi::Openai, "openai_api_key"),
+        "anthropic" => (LlmApi::Anthropic, "anthropic_api_key"),
+        _ => return None,
+    };
+    Some(LlmProviderConfig {
+        api,
+        base_url: None,
+        api_key_secret: Some(secret.to_string()),
+    })
+}
+
+/// Post a request to a provider; an error status becomes an error with the
+/// provider's message
+fn post(
+    config: &LlmConfig,
+    provider_name: &str,
+    request: &ProviderRequest,
+) -> Result<reqwest::blocking::Response, String> {
+    let client = shared_client()?;
+    let mut builder = client
+        .post(&request.url)
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .json(&request.body);
+    for (name, value) in &request.headers {
+        builder = builder.header(*name, value);
+    }
+    let response = builder
+        .send()
+        .map_err(|e| format!("{} request failed: {}", provider_name, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = read_json(provider_name, response)?;
+        let message = body
+            .pointer("/error/message")
+            .and_then(Value::as_str)
+            .unwrap_or("no error message");
+        return Err(format!(
+            "{} returned HTTP {}: {}",
+            provider_name,
+            status.as_u16(),
+            message
+        ));
+    }
+    Ok(response)
+}
+
+fn read_json(provider_name: &str, response: reqwest::blocking::Response) -> Result<Value, String> {
+    let mut body = Vec::new();
+    response
+        .take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Failed to read {} response: {}", provider_name, e))?;
+    if body.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err(format!("{} response is too large", provider_name));
+    }
+    Ok(serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Post a request to a provider and return its JSON response
+fn send(
+    config: &LlmConfig,
+    provider_name: &str,
+    request: &ProviderRequest,
+) -> Result<Value, String> {
+    let response = post(config, provider_name, request)?;
+    read_json(provider_name, response)
+}
+
+/// Whether a pattern allows the model: an exact name, or a prefix ending
+/// in `*`
+fn model_allowed(patterns: &[String], model: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => pattern == model,
+        })
+}
+
+fn build_request(
+    provider: &LlmProviderConfig,
+    api_key: Option<&str>,
+    params: &CompletionParams,
+    max_tokens: u32,
+) -> Result<ProviderRequest, String> {
+    let mut messages = params.messages.clone();
+    if let Some(prompt) = &params.prompt {
+        messages.push(Message {
+            role: Role::User,
+            content: prompt.clone(),
+        });
+    }
+    if !messages.iter().any(|message| message.role != Role::System) {
+        return Err("messages or prompt must contain a user message".to_string());
+    }
+    if let Some(temperature) = params.temperature
+        && !(0.0..=2.0).contains(&temperature)
+    {
+        return Err("temperature must be between 0 and 2".to_string());
+    }
+
+    let base_url = |default: &str| {
+        provider
+            .base_url
+            .as_deref()
+            .unwrap_or(default)
+            .trim_end_matches('/')
+            .to_string()
+    };
+    match provider.api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => {
+            let mut chat = Vec::new();
+            if let Some(system) = &params.system {
+                chat.push(json!({ "role": "system", "content": system }));
+            }
+            chat.extend(messages.iter().map(|message| json!(message)));
+            let mut body = json!({ "model": params.model, "messages": chat });
+            // OpenAI's newer models only accept max_completion_tokens;
+            // compatible servers implement the older max_tokens
+            let tokens_field = if provider.api == LlmApi::Openai {
+                "max_completion_tokens"
+            } else {
+                "max_tokens"
+            };
+            body[tokens_field] = json!(max_tokens);
+            if let Some(temperature) = params.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            if !params.stop.is_empty() {
+                body["stop"] = json!(params.stop);
+            }
+            let headers = api_key
+                .map(|key| vec![("authorization", format!("Bearer {}", key))])
+                .unwrap_or_default();
+            Ok(ProviderRequest {
+                url: format!("{}/chat/completions", base_url("https://api.openai.com/v1")),
+                headers,
+                body,
+            })
+        }
+        LlmApi::Anthropic => {
+            // System messages go to the separate system parameter
+            let system: Vec<&str> = params
+                .system
+                .iter()
+                .map(String::as_str)
+                .chain(
+                    messages
+                        .iter()
+                        .filter(|message| message.role == Role::System)
+                        .map(|message| message.content.as_str()),
+                )
+                .collect();
+            let chat: Vec<&Message> = messages
+                .iter()
+                .filter(|message| message.role != Role::System)
+                .collect();
+            let mut body = json!({
+                "model": params.model,
+                "messages": chat,
+                "max_tokens": max_tokens,
+            });
+            if !system.is_empty() {
+                body["system"] = json!(system.join("\n\n"));
+            }
+            if let Some(temperature) = params.temperature {
+                body["temperature"] = json!(temperature.min(1.0));
+            }
+            if !params.stop.is_empty() {
+                body["stop_sequences"] = json!(params.stop);
+            }
+            let mut headers = vec![("anthropic-version", ANTHROPIC_VERSION.to_string())];
+            if let Some(key) = api_key {
+                headers.push(("x-api-key", key.to_string()));
+            }
+            Ok(ProviderRequest {
+                url: format!("{}/messages", base_url("https://api.anthropic.com/v1")),
+                headers,
+                body,
+            })
+        }
+    }
+}
+
+fn parse_response(api: LlmApi, provider_name: &str, body: &Value) -> Result<Completion, String> {
+    let invalid = || format!("{} returned an unexpected response", provider_name);
+    let model = body
+        .get("model")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    let count = |pointer: &str| body.pointer(pointer).and_then(Value::as_u64);
+    let reason = |pointer: &str| {
+        body.pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    match api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => {
+            let message = body.pointer("/choices/0/message").ok_or_else(invalid)?;
+            // Messages that only call tools have no content
+            let text = match message.get("content") {
+                Some(Value::String(text)) => text.as_str(),
+                _ if message.get("tool_calls").is_some() => "",
+                _ => return Err(invalid()),
+            };
+            Ok(Completion {
+                provider: provider_name.to_string(),
+                model,
+                text: text.to_string(),
+                finish_reason: reason("/choices/0/finish_reason"),
+                usage: Usage {
+                    input_tokens: count("/usage/prompt_tokens"),
+                    output_tokens: count("/usage/completion_tokens"),
+                },
+                tool_calls: Vec::new(),
+                moderation: None,
+            })
+        }
+        LlmApi::Anthropic => {
+            let blocks = body
+                .get("content")
+                .and_then(Value::as_array)
+                .ok_or_else(invalid)?;
+            let text = blocks
+                .iter()
+                .filter(|block| block.get("type").and_then(Value::as_str) == Some("text"))
+                .filter_map(|block| block.get("text").and_then(Value::as_str))
+                .collect::<String>();
+            Ok(Completion {
+                provider: provider_name.to_string(),
+                model,
+                text,
+                finish_reason: reason("/stop_reason"),
+                usage: Usage {
+                    input_tokens: count("/usage/input_tokens"),
+                    output_tokens: count("/usage/output_tokens"),
+                },
+                tool_calls: Vec::new(),
+                moderation: None,
+            })
+        }
+    }
+}
+
+/// The registered MCP tools `selection` names, sorted by name
+fn selected_tools(selection: Option<&ToolSelection>) -> Result<Vec<crate::mcp::McpTool>, String> {
+    let mut tools = match selection {
+        None | Some(ToolSelection::All(false)) => return Ok(Vec::new()),
+        Some(ToolSelection::All(true)) => crate::mcp::list_tools(),
+        Some(ToolSelection::Named(names)) => {
+            let registered = crate::mcp::list_tools();
+            names
+                .iter()
+                .map(|name| {
+                    registered
+                        .iter()
+                        .find(|tool| &tool.name == name)
+                        .cloned()
+                        .ok_or_else(|| format!("Unknown MCP tool '{}'", name))
+                })
+                .collect::<Result<_, _>>()?
+        }
+    };
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools.dedup_by(|a, b| a.name == b.name);
+    Ok(tools)
+}
+
+/// Advertise `tools` in a completion request
+fn add_tools(api: LlmApi, body: &mut Value, tools: &[crate::mcp::McpTool]) {
+    body["tools"] = match api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.input_schema,
+                    }
+                })
+            })
+            .collect(),
+        LlmApi::Anthropic => tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.input_schema,
+                })
+            })
+            .collect(),
+    };
+}
+
+/// The tool calls a completion response requests
+fn parse_tool_calls(
+    api: LlmApi,
+    provider_name: &str,
+    body: &Value,
+) -> Result<Vec<RequestedToolCall>, String> {
+    let invalid = || format!("{} returned an unexpected tool call", provider_name);
+    let text = |call: &Value, pointer: &str| {
+        call.pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(invalid)
+    };
+    match api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => body
+            .pointer("/choices/0/message/tool_calls")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|call| {
+                // Arguments arrive as a JSON document in a string
+                let arguments = text(call, "/function/arguments")?;
+                Ok(RequestedToolCall {
+                    id: text(call, "/id")?,
+                    name: text(call, "/function/name")?,
+                    arguments: serde_json::from_str(&arguments)
+                        .map_err(|e| format!("Invalid tool arguments: {}", e)),
+                })
+            })
+            .collect(),
+        LlmApi::Anthropic => body
+            .get("content")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|block| block.get("type").and_then(Value::as_str) == Some("tool_use"))
+            .map(|block| {
+                Ok(RequestedToolCall {
+                    id: text(block, "/id")?,
+                    name: text(block, "/name")?,
+                    arguments: Ok(block.get("input").cloned().unwrap_or_else(|| json!({}))),
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Append the model's tool-calling turn in `response` and the results of
+/// the calls to the messages of a completion request
+fn add_tool_results(
+    api: LlmApi,
+    body: &mut Value,
+    response: &Value,
+    calls: &[RequestedToolCall],
+    results: &[Result<Value, String>],
+) {
+    let content = |result: &Result<Value, String>| match result {
+        Ok(Value::String(text)) => text.clone(),
+        Ok(value) => value.to_string(),
+        Err(e) => format!("Error: {}", e),
+    };
+    let Some(messages) = body.get_mut("messages").and_then(Value::as_array_mut) else {
+        return;
+    };
+    match api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => {
+            if let Some(message) = response.pointer("/choices/0/message") {
+                messages.push(message.clone());
+            }
+            messages.extend(calls.iter().zip(results).map(|(call, result)| {
+                json!({ "role": "tool", "tool_call_id": call.id, "content": content(result) })
+            }));
+        }
+        LlmApi::Anthropic => {
+            messages.push(json!({
+                "role": "assistant",
+                "content": response.get("content").cloned().unwrap_or_else(|| json!([])),
+            }));
+            let results: Vec<Value> = calls
+                .iter()
+                .zip(results)
+                .map(|(call, result)| {
+                    json!({
+                        "type": "tool_result",
+                        "tool_use_id": call.id,
+                        "content": content(result),
+                        "is_error": result.is_err(),
+                    })
+                })
+                .collect();
+            messages.push(json!({ "role": "user", "content": results }));
+        }
+    }
+}
+
+fn build_embedding_request(
+    provider: &LlmProviderConfig,
+    api_key: Option<&str>,
+    model: &str,
+    input: &[String],
+    dimensions: Option<u32>,
+) -> Result<ProviderRequest, String> {
+    let mut body = json!({ "model": model, "input": input, "encoding_format": "float" });
+    if let Some(dimensions) = dimensions {
+        body["dimensions"] = json!(dimensions);
+    }
+    let base_url = provider
+        .base_url
+        .as_deref()
+        .unwrap_or("https://api.openai.com/v1")
+        .trim_end_matches('/');
+    Ok(ProviderRequest {
+        url: format!("{}/embeddings", base_url),
+        headers: api_key
+            .map(|key| vec![("authorization", format!("Bearer {}", key))])
+            .unwrap_or_default(),
+        body,
+    })
+}
+
+fn parse_embedding_response(
+    provider_name: &str,
+    inputs: usize,
+    body: &Value,
+) -> Result<Embeddings, String> {
+    let invalid = || format!("{} returned an unexpected response", provider_name);
+    let data = body
+        .get("data")
+        .and_then(Value::as_array)
+        .ok_or_else(invalid)?;
+    if data.len() != inputs {
+        return Err(invalid());
+    }
+    let mut embeddings = vec![Vec::new(); inputs];
+    for (position, item) in data.iter().enumerate() {
+        // Providers report each vector's input position; fall back to order
+        let index = item
+            .get("index")
+            .and_then(Value::as_u64)
+            .map_or(position, |index| index as usize);
+        let vector = item
+            .get("embedding")
+            .and_then(Value::as_array)
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|value| value.as_f64().map(|value| value as f32))
+            .collect::<Option<Vec<f32>>>()
+            .ok_or_else(invalid)?;
+        *embeddings.get_mut(index).ok_or_else(invalid)? = vector;
+    }
+    if embeddings.iter().any(Vec::is_empty) {
+        return Err(invalid());
+    }
+    Ok(Embeddings {
+        provider: provider_name.to_string(),
+        model: body
+            .get("model")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        embeddings,
+        usage: Usage {
+            input_tokens: body.pointer("/usage/prompt_tokens").and_then(Value::as_u64),
+            output_tokens: None,
+        },
+    })
+}
+
+/// Read a server-sent event stream, passing the data of each event to
+/// `on_data` until the stream ends or sends OpenAI's `[DONE]`
+fn read_event_stream(
+    reader: impl std::io::BufRead,
+    mut on_data: impl FnMut(&str) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut data = String::new();
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.is_empty() {
+            if data == "[DONE]" {
+                return Ok(());
+            }
+            if !data.is_empty() {
+                on_data(&data)?;
+            }
+            data.clear();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            if !data.is_empty() {
+                data.push('\n');
+            }
+            data.push_str(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    if !data.is_empty() && data != "[DONE]" {
+        on_data(&data)?;
+    }
+    Ok(())
+}
+
+/// Fold one streamed event into `completion`, returning the text it adds
+fn apply_stream_event(
+    api: LlmApi,
+    completion: &mut Completion,
+    event: &Value,
+) -> Result<Option<String>, String> {
+    if let Some(message) = event.pointer("/error/message").and_then(Value::as_str) {
+        return Err(message.to_string());
+    }
+    let text = |pointer: &str| {
+        event
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let count = |pointer: &str| event.pointer(pointer).and_then(Value::as_u64);
+    match api {
+        LlmApi::Openai | LlmApi::OpenaiCompatible => {
+            if let Some(model) = text("/model") {
+                completion.model = model;
+            }
+            if let Some(reason) = text("/choices/0/finish_reason") {
+                completion.finish_reason = Some(reason);
+            }
+            if let Some(input_tokens) = count("/usage/prompt_tokens") {
+                completion.usage.input_tokens = Some(input_tokens);
+                completion.usage.output_tokens = count("/usage/completion_tokens");
+            }
+            Ok(text("/choices/0/delta/content").filter(|delta| !delta.is_empty()))
+        }
+        LlmApi::Anthropic => match event.get("type").and_then(Value::as_str) {
+            Some("message_start") => {
+                completion.model = text("/message/model").unwrap_or_default();
+                completion.usage.input_tokens = count("/message/usage/input_tokens");
+                Ok(None)
+            }
+            Some("content_block_delta") => Ok(text("/delta/text")),
+            Some("message_delta") => {
+                completion.finish_reason = text("/delta/stop_reason");
+                completion.usage.output_tokens = count("/usage/output_tokens");
+                Ok(None)
+            }
+            _ => Ok(None),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    /// Serializes tests that replace the process-global LLM configuration
+    static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+    fn params() -> CompletionParams {
+        CompletionParams {
+            model: "test-model".to_string(),
+            system: Some("Be brief.".to_string()),
+            messages: vec![Message {
+                role: Role::System,
+                content: "Answer in English.".to_string(),
+            }],
+            prompt: Some("Hello?".to_string()),
+            temperature: Some(1.5),
+            stop: vec!["END".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_openai_request() {
+        let provider = provider_config(&LlmConfig::default(), "openai").unwrap();
+        let request = build_request(&provider, Some("sk-test"), &params(), 100).unwrap();
+        assert_eq!(request.url, "https://api.openai.com/v1/chat/completions");
+        assert_eq!(
+            request.headers,
+            vec![("authorization", "Bearer sk-test".to_string())]
+        );
+        assert_eq!(request.body["max_completion_tokens"], 100);
+        assert_eq!(request.body["messages"][0]["content"], "Be brief.");
+        assert_eq!(request.body["messages"][2]["role"], "user");
+        assert_eq!(request.body["stop"], json!(["END"]));
+
+        let local = LlmProviderConfig {
+            api: LlmApi::OpenaiCompatible,
+            base_url: Some("http://localhost:11434/v1/".to_string()),
+            api_key_secret: None,
+        };
+        let request = build_request(&local, None, &params(), 100).unwrap();
+        assert_eq!(request.url, "http://localhost:11434/v1/chat/completions");
+        assert!(request.headers.is_empty());
+        assert_eq!(request.body["max_tokens"], 100);
+    }
+
+    #[test]
+    fn test_build_anthropic_request() {
+        let provider = provider_config(&LlmConfig::default(), "anthropic").unwrap();
+        let request = build_request(&provider, Some("key"), &params(), 100).unwrap();
+        assert_eq!(request.url, "https://api.anthropic.com/v1/messages");
+        assert!(request.headers.contains(&("x-api-key", "key".to_string())));
+        assert_eq!(request.body["system"], "Be brief.\n\nAnswer in English.");
+        assert_eq!(
+            request.body["messages"],
+            json!([{ "role": "user", "content": "Hello?" }])
+        );
+        assert_eq!(request.body["max_tokens"], 100);
+        assert_eq!(request.body["temperature"], 1.0);
+        assert_eq!(request.body["stop_sequences"], json!(["END"]));
+
+        let only_system = CompletionParams {
+            prompt: None,
+            ..params()
+        };
+        assert!(build_request(&provider, None, &only_system, 100).is_err());
+    }
+
+    #[test]
+    fn test_parse_responses() {
+        let openai = json!({
+            "model": "gpt-4o-mini-2024-07-18",
+            "choices": [{ "message": { "role": "assistant", "content": "Hi" }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 1 }
+        });
+        let completion = parse_response(LlmApi::Openai, "openai", &openai).unwrap();
+        assert_eq!(completion.text, "Hi");
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(completion.usage.input_tokens, Some(9));
+
+        let anthropic = json!({
+            "model": "claude-test",
+            "content": [{ "type": "text", "text": "Hel" }, { "type": "text", "text": "lo" }],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 12, "output_tokens": 2 }
+        });
+        let completion = parse_response(LlmApi::Anthropic, "anthropic", &anthropic).unwrap();
+        assert_eq!(completion.text, "Hello");
+        assert_eq!(completion.usage.output_tokens, Some(2));
+
+        assert!(parse_response(LlmApi::Openai, "openai", &anthropic).is_err());
+    }
+
+    #[test]
+    fn test_embedding_request_and_response() {
+        let config = LlmConfig::default();
+        let input = vec!["first".to_string(), "second".to_string()];
+        let openai = provider_config(&config, "openai").unwrap();
+        let request =
+            build_embedding_request(&openai, Some("sk-test"), "m", &input, Some(256)).unwrap();
+        assert_eq!(request.url, "https://api.openai.com/v1/embeddings");
+        assert_eq!(request.body["input"], json!(["first", "second"]));
+        assert_eq!(request.body["dimensions"], 256);
+
+        let response = json!({
+            "model": "text-embedding-3-small",
+            "data": [
+                { "index": 1, "embedding": [0.5, -0.5] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "usage": { "prompt_tokens": 4 }
+        });
+        let embeddings = parse_embedding_response("openai", 2, &response).unwrap();
+        assert_eq!(embeddings.embeddings, vec![vec![1.0, 0.0], vec![0.5, -0.5]]);
+        assert_eq!(embeddings.usage.input_tokens, Some(4));
+        assert!(parse_embedding_response("openai", 3, &response).is_err());
+    }
+
+    #[test]
+    fn test_read_anthropic_event_stream() {
+        let body = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"model\":\"claude-test\",\"usage\":{\"input_tokens\":7}}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":1}}\n\n",
+            "event: error\n",
+            "data: {\"type\":\"error\",\"error\":{\"message\":\"Overloaded\"}}\n\n",
+        );
+        let mut completion = Completion {
+            provider: "anthropic".to_string(),
+            model: String::new(),
+            text: String::new(),
+            finish_reason: None,
+            usage: Usage::default(),
+            tool_calls: Vec::new(),
+            moderation: None,
+        };
+        let result = read_event_stream(body.as_bytes(), |data| {
+            let event: Value = serde_json::from_str(data).unwrap();
+            if let Some(delta) = apply_stream_event(LlmApi::Anthropic, &mut completion, &event)? {
+                completion.text.push_str(&delta);
+            }
+            Ok(())
+        });
+        assert_eq!(result, Err("Overloaded".to_string()));
+        assert_eq!(completion.text, "Hi");
+        assert_eq!(completion.model, "claude-test");
+        assert_eq!(completion.finish_reason.as_deref(), Some("end_turn"));
+        assert_eq!(completion.usage.input_tokens, Some(7));
+        assert_eq!(completion.usage.output_tokens, Some(1));
+    }
+
+    #[test]
+    fn test_anthropic_tool_round() {
+        let response = json!({
+            "content": [
+                { "type": "text", "text": "Checking." },
+                { "type": "tool_use", "id": "toolu_1", "name": "weather", "input": { "city": "Oulu" } }
+            ],
+            "stop_reason": "tool_use"
+        });
+        let calls = parse_tool_calls(LlmApi::Anthropic, "anthropic", &response).unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "weather");
+        assert_eq!(calls[0].arguments, Ok(json!({ "city": "Oulu" })));
+
+        let mut body = json!({ "messages": [{ "role": "user", "content": "Weather?" }] });
+        add_tool_results(
+            LlmApi::Anthropic,
+            &mut body,
+            &response,
+            &calls,
+            &[Err("offline".to_string())],
+        );
+        assert_eq!(body["messages"][1]["content"], response["content"]);
+        assert_eq!(
+            body["messages"][2],
+            json!({
+                "role": "user",
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": "toolu_1",
+                    "content": "Error: offline",
+                    "is_error": true
+                }]
+            })
+        );
+
+        let openai = json!({
+            "choices": [{ "message": { "tool_calls": [
+                { "id": "call_1", "function": { "name": "weather", "arguments": "{oops" } }
+            ] } }]
+        });
+        let calls = parse_tool_calls(LlmApi::Openai, "openai", &openai).unwrap();
+        assert!(calls[0].arguments.is_err());
+    }
+
+    #[test]
+    fn test_complete_with_tools() {
+        let _guard = SETTINGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let script_uri = "https://example.com/llm-tools-test";
+        crate::mcp::register_mcp_tool(
+            "llm_test_weather".to_string(),
+            "Current weather of a city".to_string(),
+            json!({ "type": "object", "properties": { "city": { "type": "string" } } }),
+            "weather".to_string(),
+            script_uri.to_string(),
+        );
+
+        let responses = [
+            json!({
+                "model": "llama3.2",
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": { "name": "llm_test_weather", "arguments": "{\"city\":\"Oulu\"}" }
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": { "prompt_tokens": 20, "completion_tokens": 5 }
+            }),
+            json!({
+                "model": "llama3.2",
+                "choices": [{ "message": { "content": "It is sunny in Oulu." }, "finish_reason": "stop" }],
+                "usage": { "prompt_tokens": 30, "completion_tokens": 7 }
+            }),
+        ];
+        let (port, server) = serve(
+            "application/json",
+            responses.iter().map(Value::to_string).collect(),
+        );
+        let mut config = LlmConfig::default();
+        config.providers.insert(
+            "local".to_string(),
+            LlmProviderConfig {
+                api: LlmApi::OpenaiCompatible,
+                base_url: Some(format!("http://127.0.0.1:{}/v1", port)),
+                api_key_secret: None,
+            },
+        );
+        config.allowed_models = vec!["llama3*".to_string()];
+        configure(&config);
+
+        let params = CompletionParams {
+            model: "llama3.2".to_string(),
+            prompt: Some("Weather in Oulu?".to_string()),
+            tools: Some(ToolSelection::Named(vec!["llm_test_weather".to_string()])),
+            ..Default::default()
+        };
+        let mut called = Vec::new();
+        let completion = complete(script_uri, None, "local", &params, |name, arguments| {
+            called.push((name.to_string(), arguments));
+            Ok(json!({ "sky": "sunny" }))
+        })
+        .unwrap();
+        assert_eq!(
+            called,
+            vec![("llm_test_weather".to_string(), json!({ "city": "Oulu" }))]
+        );
+        assert_eq!(completion.text, "It is sunny in Oulu.");
+        assert_eq!(completion.usage.input_tokens, Some(50));
+        assert_eq!(completion.usage.output_tokens, Some(12));
+        assert_eq!(completion.tool_calls.len(), 1);
+        assert_eq!(completion.tool_calls[0].result, json!({ "sky": "sunny" }));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains(r#""name":"llm_test_weather""#));
+        assert!(requests[1].contains(r#""role":"tool""#));
+        assert!(requests[1].contains(r#""tool_call_id":"call_1""#));
+
+        let unknown = CompletionParams {
+            tools: Some(ToolSelection::Named(vec!["no_such_tool".to_string()])),
+            ..params.clone()
+        };
+        assert_eq!(
+            complete(script_uri, None, "local", &unknown, no_tools),
+            Err("Unknown MCP tool 'no_such_tool'".to_string())
+        );
+        assert_eq!(
+            prepare_stream(script_uri, None, "local", &params).err(),
+            Some("tools are not supported when streaming".to_string())
+        );
+        crate::mcp::clear_script_mcp_registrations(script_uri);
+        configure(&LlmConfig::default());
+    }
+
+    #[test]
+    fn test_model_allowed() {
+        let patterns = vec!["gpt-4o-mini".to_string(), "claude-*".to_string()];
+        assert!(model_allowed(&patterns, "gpt-4o-mini"));
+        assert!(model_allowed(&patterns, "claude-sonnet-4"));
+        assert!(!model_allowed(&patterns, "gpt-4o"));
+        assert!(!model_allowed(&[], "gpt-4o-mini"));
+    }
+
+    /// Answer one request on a local port with `body`; the thread returns
+    /// the request it read
+    fn serve_once(content_type: &str, body: String) -> (u16, std::thread::JoinHandle<String>) {
+        let (port, server) = serve(content_type, vec![body]);
+        (
+            port,
+            std::thread::spawn(move || server.join().unwrap().remove(0)),
+        )
+    }
+
+    /// Answer one request on a local port with each of `bodies` in turn;
+    /// the thread returns the requests it read
+    fn serve(
+        content_type: &str,
+        bodies: Vec<String>,
+    ) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let content_type = content_type.to_string();
+        let server = std::thread::spawn(move || {
+            bodies
+                .into_iter()
+                .map(|body| answer(&listener, &content_type, body))
+                .collect()
+        });
+        (port, server)
+    }
+
+    /// Answer one request accepted on `listener`, returning it
+    fn answer(listener: &TcpListener, content_type: &str, body: String) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // Read the headers, then as much body as Content-Length names
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length || n == 0 {
+                    break;
+                }
+            }
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        )
+        .unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    fn no_tools(name: &str, _arguments: Value) -> Result<Value, String> {
+        Err(format!("unexpected call to {}", name))
+    }
+
+    #[test]
+    fn test_complete_against_local_provider() {
+        let _guard = SETTINGS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let body = json!({
+            "model": "llama3.2",
+            "choices": [{ "message": { "content": "Pong" }, "finish_reason": "stop" }]
+        })
+        .to_string();
+        let (port, server) = serve_once("application/json", body);
+
+        let mut config = LlmConfig::default();
+        config.providers.insert(
+            "local".to_string(),
+            LlmProviderConfig {
+                api: LlmApi::OpenaiCompatible,
+                base_url: Some(format!("http://127.0.0.1:{}/v1", port)),
+                api_key_secret: None,
+            },
+        );
+        config.allowed_models = vec!["llama3*".to_string()];
+        config.script_models.insert(
+            "https://example.com/restricted".to_string(),
+            vec!["gpt-4o-mini".to_string()],
+        );
+        configure(&config);
+
+        let params = CompletionParams {
+            model: "llama3.2".to_string(),
+            prompt: Some("Ping".to_string()),
+            ..Default::default()
+        };
+        let denied = complete(
+            "https://example.com/restricted",
+            None,
+            "local",
+            &params,
+            no_tools,
+        );
+        assert_eq!(
+            denied,
+            Err("Model 'llama3.2' is not allowed for this script".to_string())
+        );
+        assert!(complete("https://example.com/chat", None, "nope", &params, no_tools).is_err());
+
+        let completion =
+            complete("https://example.com/chat", None, "local", &params, no_tools).unwrap();
+        assert_eq!(completion.text, "Pong");
+        assert_eq!(completion.provider, "local");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions "));
+        assert!(request.contains(r#""content":"Ping""#));
+
+        let events = [
+            json!({ "model": "llama3.2", "choices": [{ "delta": { "content": "Po" } }] }),
+            json!({ "model": "llama3.2", "choices": [{ "delta": { "content": "ng" }, "finish_reason": "stop" }] }),
+        ];
+        let body = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect::<String>()
+            + "data: [DONE]\n\n";
+        let (port, server) = serve_once("text/event-stream", body);
+        config.providers.get_mut("local").unwrap().base_url =
+            Some(format!("http://127.0.0.1:{}/v1", port));
+        configure(&config);
+
+        let stream = prepare_stream("https://example.com/chat", None, "local", &params).unwrap();
+        let mut deltas = Vec::new();
+        let completion = stream.run(|delta| deltas.push(delta.to_string())).unwrap();
+        assert_eq!(deltas, vec!["Po", "ng"]);
+        assert_eq!(completion.text, "Pong");
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+        assert!(server.join().unwrap().contains(r#""stream":true"#));
+        configure(&LlmConfig::default());
+    }
+}
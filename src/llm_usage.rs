@@ -0,0 +1,380 @@
+//! Token usage accounting and monthly budgets of LLM calls.
+//!
+//! Every `llm.complete`, `llm.stream` and `embeddings.create` call adds its
+//! tokens and estimated cost to the `llm_usage` table, summed per calendar
+//! month (UTC), script, user, provider and model. The cost is estimated from
+//! `[javascript.llm.prices]`; models without a price cost nothing.
+//!
+//! `[javascript.llm.budgets]` limits the monthly cost of each script and of
+//! each signed-in user. A call made past a soft limit logs a warning; past a
+//! hard limit calls are refused until the month ends. Limits are checked
+//! before each call, so the call that crosses a hard limit still completes.
+//!
+//! `console.llmUsage()` and the `llmUsage` GraphQL query report the usage.
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::{LlmBudgetLimits, LlmConfig, LlmPrice};
+use crate::llm::Usage;
+use crate::repository;
+
+/// Usage of one script, user, provider and model in a month
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmUsageEntry {
+    pub script_uri: String,
+    /// None for calls made without a signed-in user
+    pub user_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    /// Estimated cost in USD
+    pub cost: f64,
+}
+
+/// Month-to-date cost of a script or user against its limits
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    /// Script URI or user ID
+    pub id: String,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost: f64,
+    pub soft_limit: Option<f64>,
+    pub hard_limit: Option<f64>,
+    pub over_soft_limit: bool,
+    pub over_hard_limit: bool,
+}
+
+/// Report of `console.llmUsage()`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmUsageReport {
+    /// Month reported, as YYYY-MM
+    pub month: String,
+    pub entries: Vec<LlmUsageEntry>,
+    /// Totals per script
+    pub scripts: Vec<BudgetStatus>,
+    /// Totals per signed-in user
+    pub users: Vec<BudgetStatus>,
+}
+
+/// First day of the current month (UTC)
+fn current_month() -> NaiveDate {
+    let today = Utc::now().date_naive();
+    today.with_day(1).unwrap_or(today)
+}
+
+/// Read a YYYY-MM month
+fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid month '{}': use YYYY-MM", month))
+}
+
+/// Price of `model`: the longest matching pattern of `prices`
+fn price_for(config: &LlmConfig, model: &str) -> Option<LlmPrice> {
+    config
+        .prices
+        .iter()
+        .filter(|(pattern, _)| match pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => *pattern == model,
+        })
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, price)| *price)
+}
+
+/// Estimated cost of `usage` with `model`, in USD
+pub fn estimate_cost(config: &LlmConfig, model: &str, usage: &Usage) -> f64 {
+    let Some(price) = price_for(config, model) else {
+        return 0.0;
+    };
+    let tokens = |count: Option<u64>| count.unwrap_or(0) as f64 / 1_000_000.0;
+    tokens(usage.input_tokens) * price.input + tokens(usage.output_tokens) * price.output
+}
+
+fn script_limits(config: &LlmConfig, script_uri: &str) -> LlmBudgetLimits {
+    config
+        .budgets
+        .scripts
+        .get(script_uri)
+        .copied()
+        .unwrap_or(config.budgets.script)
+}
+
+fn has_limits(limits: &LlmBudgetLimits) -> bool {
+    limits.soft.is_some() || limits.hard.is_some()
+}
+
+/// Refuse a call of `script_uri` for `user_id` when the script or the user
+/// is past a hard limit this month; log a warning past a soft limit
+pub fn check_budget(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+) -> Result<(), String> {
+    let script = script_limits(config, script_uri);
+    let user = user_id.map(|_| config.budgets.user).unwrap_or_default();
+    if !has_limits(&script) && !has_limits(&user) {
+        return Ok(());
+    }
+    // Without a database there is no usage to check against
+    if repository::get_repository_opt().is_none() {
+        return Ok(());
+    }
+    let (script_cost, user_cost) =
+        repository::get_llm_usage_cost(current_month(), script_uri, user_id)
+            .map_err(|e| format!("Failed to check LLM budget: {}", e))?;
+
+    for (subject, cost, limits) in [
+        ("this script", script_cost, script),
+        ("this user", user_cost, user),
+    ] {
+        if let Some(hard) = limits.hard
+            && cost >= hard
+        {
+            return Err(format!(
+                "Monthly LLM budget of ${:.2} for {} is used up",
+                hard, subject
+            ));
+        }
+        if let Some(soft) = limits.soft
+            && cost >= soft
+        {
+            warn!(
+                script_uri = %script_uri,
+                user_id = ?user_id,
+                cost,
+                soft_limit = soft,
+                "LLM call past the soft monthly budget of {}",
+                subject
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Add the tokens and estimated cost of one call to this month's usage.
+/// Failures are logged; the call's result is returned regardless.
+pub fn record(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+    provider: &str,
+    model: &str,
+    usage: &Usage,
+) {
+    if repository::get_repository_opt().is_none() {
+        return;
+    }
+    let entry = LlmUsageEntry {
+        script_uri: script_uri.to_string(),
+        user_id: user_id.map(str::to_string),
+        provider: provider.to_string(),
+        model: model.to_string(),
+        calls: 1,
+        input_tokens: usage.input_tokens.unwrap_or(0) as i64,
+        output_tokens: usage.output_tokens.unwrap_or(0) as i64,
+        cost: estimate_cost(config, model, usage),
+    };
+    let tenant = crate::tenancy::current_tenant();
+    crate::metering::record(
+        crate::metering::Metric::LlmInputTokens,
+        entry.input_tokens,
+        tenant.as_deref(),
+        user_id,
+        Some(script_uri),
+    );
+    crate::metering::record(
+        crate::metering::Metric::LlmOutputTokens,
+        entry.output_tokens,
+        tenant.as_deref(),
+        user_id,
+        Some(script_uri),
+    );
+    if let Err(e) = repository::record_llm_usage(current_month(), &entry) {
+        warn!(
+            script_uri = %script_uri,
+            "Failed to record LLM usage: {}",
+            e
+        );
+    }
+}
+
+/// Options of `console.llmUsage()`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct UsageReportOptions {
+    /// YYYY-MM; defaults to the current month
+    pub month: Option<String>,
+    /// Only this script
+    pub script_uri: Option<String>,
+    /// Only this user
+    pub user_id: Option<String>,
+}
+
+/// A month's usage with the totals of each script and user against their
+/// limits
+pub fn usage_report(
+    config: &LlmConfig,
+    options: &UsageReportOptions,
+) -> Result<LlmUsageReport, String> {
+    let month = match &options.month {
+        Some(month) => parse_month(month)?,
+        None => current_month(),
+    };
+    let entries = repository::list_llm_usage(
+        month,
+        options.script_uri.as_deref(),
+        options.user_id.as_deref(),
+    )
+    .map_err(|e| format!("Failed to read LLM usage: {}", e))?;
+
+    let scripts = totals(
+        &entries,
+        |entry| Some(&entry.script_uri),
+        |uri| script_limits(config, uri),
+    );
+    let users = totals(
+        &entries,
+        |entry| entry.user_id.as_ref(),
+        |_| config.budgets.user,
+    );
+    Ok(LlmUsageReport {
+        month: month.format("%Y-%m").to_string(),
+        entries,
+        scripts,
+        users,
+    })
+}
+
+/// Sum `entries` per the ID `key` picks, sorted by ID
+fn totals(
+    entries: &[LlmUsageEntry],
+    key: impl Fn(&LlmUsageEntry) -> Option<&String>,
+    limits: impl Fn(&str) -> LlmBudgetLimits,
+) -> Vec<BudgetStatus> {
+    let mut totals: std::collections::BTreeMap<&str, BudgetStatus> = Default::default();
+    for entry in entries {
+        let Some(id) = key(entry) else {
+            continue;
+        };
+        let total = totals.entry(id).or_insert_with(|| {
+            let limits = limits(id);
+            BudgetStatus {
+                id: id.clone(),
+                calls: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                cost: 0.0,
+                soft_limit: limits.soft,
+                hard_limit: limits.hard,
+                over_soft_limit: false,
+                over_hard_limit: false,
+            }
+        });
+        total.calls += entry.calls;
+        total.input_tokens += entry.input_tokens;
+        total.output_tokens += entry.output_tokens;
+        total.cost += entry.cost;
+    }
+    totals
+        .into_values()
+        .map(|mut total| {
+            total.over_soft_limit = total.soft_limit.is_some_and(|soft| total.cost >= soft);
+            total.over_hard_limit = total.hard_limit.is_some_and(|hard| total.cost >= hard);
+            total
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let mut config = LlmConfig::default();
+        config.prices.insert(
+            "gpt-4o*".to_string(),
+            LlmPrice {
+                input: 2.5,
+                output: 10.0,
+            },
+        );
+        config.prices.insert(
+            "gpt-4o-mini*".to_string(),
+            LlmPrice {
+                input: 0.15,
+                output: 0.6,
+            },
+        );
+        let usage = Usage {
+            input_tokens: Some(2_000_000),
+            output_tokens: Some(500_000),
+        };
+        assert_eq!(estimate_cost(&config, "gpt-4o-2024-08-06", &usage), 10.0);
+        assert!((estimate_cost(&config, "gpt-4o-mini", &usage) - 0.6).abs() < 1e-9);
+        assert_eq!(estimate_cost(&config, "claude-haiku-4-5", &usage), 0.0);
+    }
+
+    #[test]
+    fn test_usage_totals() {
+        let entry = |script_uri: &str, user_id: Option<&str>, cost: f64| LlmUsageEntry {
+            script_uri: script_uri.to_string(),
+            user_id: user_id.map(str::to_string),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            calls: 2,
+            input_tokens: 100,
+            output_tokens: 10,
+            cost,
+        };
+        let entries = vec![
+            entry("https://example.com/a", Some("alice"), 3.0),
+            entry("https://example.com/a", None, 4.0),
+            entry("https://example.com/b", Some("alice"), 1.0),
+        ];
+        let mut config = LlmConfig::default();
+        config.budgets.script = LlmBudgetLimits {
+            soft: Some(5.0),
+            hard: Some(10.0),
+        };
+        config.budgets.scripts.insert(
+            "https://example.com/b".to_string(),
+            LlmBudgetLimits {
+                soft: None,
+                hard: Some(1.0),
+            },
+        );
+
+        let scripts = totals(
+            &entries,
+            |entry| Some(&entry.script_uri),
+            |uri| script_limits(&config, uri),
+        );
+        assert_eq!(scripts.len(), 2);
+        assert_eq!(scripts[0].cost, 7.0);
+        assert_eq!(scripts[0].calls, 4);
+        assert!(scripts[0].over_soft_limit && !scripts[0].over_hard_limit);
+        assert!(scripts[1].over_hard_limit);
+
+        let users = totals(
+            &entries,
+            |entry| entry.user_id.as_ref(),
+            |_| config.budgets.user,
+        );
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].id, "alice");
+        assert_eq!(users[0].cost, 4.0);
+
+        assert!(parse_month("2026-02").is_ok());
+        assert!(parse_month("2026-13").is_err());
+    }
+}
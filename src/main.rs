@@ -1,7 +1,7 @@
 use aiwebengine::{AppResult, config::AppConfig, start_server_with_config};
 use clap::{Arg, Command};
 use tokio::sync::oneshot;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
@@ -23,6 +23,16 @@ async fn main() -> AppResult<()> {
                 .help("Validate configuration and exit")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("self-test")
+                .long("self-test")
+                .help(
+                    "Initialize components, run every script's init() in a sandbox, \
+                     check the GraphQL schema and route conflicts, and exit \
+                     (non-zero on failure)",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .get_matches();
 
     // Load configuration first to get logging preferences
@@ -56,25 +66,37 @@ async fn main() -> AppResult<()> {
         tracing_subscriber::EnvFilter::new(format!("aiwebengine={},warn", log_level))
     };
 
+    // The query log sees sqlx statement events regardless of the log level,
+    // so the level filter is attached to the output layer only
+    let query_log = aiwebengine::query_log::layer(&config.repository);
+
     // Initialize logging based on configuration format
     match config.logging.format.as_str() {
         "json" => {
             tracing_subscriber::registry()
-                .with(filter)
-                .with(tracing_subscriber::fmt::layer().json())
+                .with(query_log)
+                .with(tracing_subscriber::fmt::layer().json().with_filter(filter))
                 .init();
         }
         "compact" => {
             tracing_subscriber::registry()
-                .with(filter)
-                .with(tracing_subscriber::fmt::layer().compact())
+                .with(query_log)
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .compact()
+                        .with_filter(filter),
+                )
                 .init();
         }
         _ => {
             // "pretty" or default
             tracing_subscriber::registry()
-                .with(filter)
-                .with(tracing_subscriber::fmt::layer().pretty())
+                .with(query_log)
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .pretty()
+                        .with_filter(filter),
+                )
                 .init();
         }
     }
@@ -137,6 +159,20 @@ async fn main() -> AppResult<()> {
 
     tracing::debug!("Configuration validation completed successfully");
 
+    if matches.get_flag("self-test") {
+        tracing::info!("Running self-test");
+        match aiwebengine::run_self_test(config).await {
+            Ok(report) => {
+                println!("{}", report);
+                std::process::exit(if report.passed() { 0 } else { 1 });
+            }
+            Err(e) => {
+                eprintln!("✗ Self-test failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     tracing::info!("Starting AIWebEngine Server");
     tracing::info!("Configuration loaded successfully");
     tracing::info!(
@@ -0,0 +1,506 @@
+//! Usage records for billing (`[metering]`).
+//!
+//! With `metering.enabled` set, the engine sums what each tenant, user and
+//! script uses — requests served by script routes, milliseconds of script
+//! execution and LLM input and output tokens — in memory, and every
+//! `flush_interval_secs` turns the sums into usage records covering that
+//! window. Stored bytes are sampled every `storage_sample_interval_secs`:
+//! per tenant when tenancy is enabled, otherwise per script.
+//!
+//! Records go to the configured sink: the `usage_records` table, or JSON
+//! batches posted to `webhook_url` with `X-Metering-Timestamp` and, when
+//! `webhook_secret` is set, `X-Metering-Signature` (`sha256=` and the hex
+//! HMAC-SHA256 of `{timestamp}.{body}`). Records that cannot be written are
+//! kept and sent again with the same IDs, so a sink can drop duplicates.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::{MeteringConfig, MeteringSinkKind};
+use crate::repository::{self, Repository as _};
+
+/// Most usage sums kept between flushes; usage of further tenants, users and
+/// scripts is dropped until the next flush
+const MAX_PENDING_KEYS: usize = 100_000;
+
+/// Most records kept for resending while the sink is failing; the oldest
+/// are dropped first
+const MAX_UNSENT_RECORDS: usize = 100_000;
+
+/// How often old records are deleted from the table sink
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What a usage record measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Requests served by script routes
+    Requests,
+    /// Milliseconds script handlers ran, including time queued for a worker
+    JsMs,
+    /// Stored shared storage and asset bytes, sampled
+    StorageBytes,
+    LlmInputTokens,
+    LlmOutputTokens,
+}
+
+impl Metric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Metric::Requests => "requests",
+            Metric::JsMs => "js_ms",
+            Metric::StorageBytes => "storage_bytes",
+            Metric::LlmInputTokens => "llm_input_tokens",
+            Metric::LlmOutputTokens => "llm_output_tokens",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Metric::Requests => "request",
+            Metric::JsMs => "millisecond",
+            Metric::StorageBytes => "byte",
+            Metric::LlmInputTokens | Metric::LlmOutputTokens => "token",
+        }
+    }
+}
+
+/// Quantity of one metric used over `[period_start, period_end)`; samples
+/// have both set to when they were taken
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub id: Uuid,
+    pub metric: Metric,
+    pub quantity: i64,
+    pub unit: &'static str,
+    pub tenant: Option<String>,
+    pub user_id: Option<String>,
+    pub script_uri: Option<String>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+}
+
+impl UsageRecord {
+    fn new(
+        key: &UsageKey,
+        quantity: i64,
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            metric: key.metric,
+            quantity,
+            unit: key.metric.unit(),
+            tenant: key.tenant.clone(),
+            user_id: key.user_id.clone(),
+            script_uri: key.script_uri.clone(),
+            period_start,
+            period_end,
+        }
+    }
+}
+
+/// Who used a metric
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    metric: Metric,
+    tenant: Option<String>,
+    user_id: Option<String>,
+    script_uri: Option<String>,
+}
+
+/// Usage summed since `since`
+struct Pending {
+    since: DateTime<Utc>,
+    sums: HashMap<UsageKey, i64>,
+}
+
+static SETTINGS: OnceLock<RwLock<MeteringConfig>> = OnceLock::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PENDING: OnceLock<Mutex<Pending>> = OnceLock::new();
+static UNSENT: OnceLock<Mutex<Vec<UsageRecord>>> = OnceLock::new();
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn settings() -> &'static RwLock<MeteringConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the metering configuration. Called once at server startup.
+pub fn configure(config: &MeteringConfig) {
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> MeteringConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn lock_pending() -> MutexGuard<'static, Pending> {
+    let pending = PENDING.get_or_init(|| {
+        Mutex::new(Pending {
+            since: Utc::now(),
+            sums: HashMap::new(),
+        })
+    });
+    match pending.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Metering mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+fn lock_unsent() -> MutexGuard<'static, Vec<UsageRecord>> {
+    match UNSENT.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Add `quantity` of `metric` to the usage of a tenant, user and script.
+/// Does nothing while metering is disabled.
+pub fn record(
+    metric: Metric,
+    quantity: i64,
+    tenant: Option<&str>,
+    user_id: Option<&str>,
+    script_uri: Option<&str>,
+) {
+    if !ENABLED.load(Ordering::Relaxed) || quantity <= 0 {
+        return;
+    }
+    let key = UsageKey {
+        metric,
+        tenant: tenant.map(str::to_string),
+        user_id: user_id.map(str::to_string),
+        script_uri: script_uri.map(str::to_string),
+    };
+    let mut pending = lock_pending();
+    if !pending.sums.contains_key(&key) && pending.sums.len() >= MAX_PENDING_KEYS {
+        debug!(
+            "Metering buffer is full; dropping {} usage",
+            metric.as_str()
+        );
+        return;
+    }
+    *pending.sums.entry(key).or_default() += quantity;
+}
+
+/// Count one request served by a script route and how long its handler ran
+pub fn record_request(
+    tenant: Option<&str>,
+    user_id: Option<&str>,
+    script_uri: &str,
+    elapsed: Duration,
+) {
+    record(Metric::Requests, 1, tenant, user_id, Some(script_uri));
+    record(
+        Metric::JsMs,
+        i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX),
+        tenant,
+        user_id,
+        Some(script_uri),
+    );
+}
+
+/// Turn the usage summed so far into records ending `now`
+fn take_records(now: DateTime<Utc>) -> Vec<UsageRecord> {
+    let mut pending = lock_pending();
+    let since = std::mem::replace(&mut pending.since, now);
+    pending
+        .sums
+        .drain()
+        .map(|(key, quantity)| UsageRecord::new(&key, quantity, since, now))
+        .collect()
+}
+
+/// Records of the bytes stored per tenant, or per script without tenancy
+fn sample_storage(now: DateTime<Utc>) -> Result<Vec<UsageRecord>, String> {
+    let tenancy = crate::tenancy::is_enabled();
+    let stored = if tenancy {
+        repository::list_tenant_storage_bytes()
+    } else {
+        repository::list_script_storage_bytes()
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(stored
+        .into_iter()
+        .map(|(owner, bytes)| {
+            let (tenant, script_uri) = if tenancy {
+                (Some(owner), None)
+            } else {
+                (None, Some(owner))
+            };
+            let key = UsageKey {
+                metric: Metric::StorageBytes,
+                tenant,
+                user_id: None,
+                script_uri,
+            };
+            UsageRecord::new(&key, i64::try_from(bytes).unwrap_or(i64::MAX), now, now)
+        })
+        .collect())
+}
+
+/// `X-Metering-Signature` of `body` sent at `timestamp`
+fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Shared connection-pooled client; the webhook URL comes from the
+/// configuration, so it is not restricted like `fetch` targets
+fn shared_client() -> Result<&'static reqwest::blocking::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::blocking::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Post one batch of records to the webhook sink
+fn post_batch(config: &MeteringConfig, records: &[UsageRecord]) -> Result<(), String> {
+    let url = config
+        .webhook_url
+        .as_deref()
+        .ok_or("metering.webhook_url is not set")?;
+    let body = serde_json::to_string(&serde_json::json!({ "records": records }))
+        .map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+    let mut request = shared_client()?
+        .post(url)
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .header("Content-Type", "application/json")
+        .header("X-Metering-Timestamp", timestamp.to_string());
+    if let Some(secret) = &config.webhook_secret {
+        request = request.header("X-Metering-Signature", signature(secret, timestamp, &body));
+    }
+    let response = request.body(body).send().map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status().as_u16()))
+    }
+}
+
+/// Write records to the configured sink. Returns the records that were not
+/// written.
+async fn write(config: &MeteringConfig, records: Vec<UsageRecord>) -> Vec<UsageRecord> {
+    match config.sink {
+        MeteringSinkKind::Table => {
+            match repository::get_repository()
+                .insert_usage_records(&records)
+                .await
+            {
+                Ok(()) => Vec::new(),
+                Err(e) => {
+                    warn!("Failed to store {} usage records: {}", records.len(), e);
+                    records
+                }
+            }
+        }
+        MeteringSinkKind::Webhook => {
+            let config = config.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut unsent = Vec::new();
+                for batch in records.chunks(config.batch_size.max(1)) {
+                    if !unsent.is_empty() {
+                        unsent.extend_from_slice(batch);
+                        continue;
+                    }
+                    if let Err(e) = post_batch(&config, batch) {
+                        warn!("Failed to post {} usage records: {}", batch.len(), e);
+                        unsent.extend_from_slice(batch);
+                    }
+                }
+                unsent
+            })
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Metering webhook task failed: {}", e);
+                Vec::new()
+            })
+        }
+    }
+}
+
+/// Write the records summed so far and any left over from failed writes
+async fn flush(config: &MeteringConfig, mut records: Vec<UsageRecord>) {
+    let mut unsent = std::mem::take(&mut *lock_unsent());
+    unsent.append(&mut records);
+    if unsent.is_empty() {
+        return;
+    }
+    let count = unsent.len();
+    let mut failed = write(config, unsent).await;
+    if failed.is_empty() {
+        debug!("Wrote {} usage records", count);
+        return;
+    }
+    let mut unsent = lock_unsent();
+    failed.append(&mut unsent);
+    if failed.len() > MAX_UNSENT_RECORDS {
+        let dropped = failed.len() - MAX_UNSENT_RECORDS;
+        warn!("Dropping {} unsent usage records", dropped);
+        failed.drain(..dropped);
+    }
+    *unsent = failed;
+}
+
+/// Start the background task that writes usage records, samples stored
+/// bytes and deletes old records. Only the first call starts a worker, and
+/// only while metering is enabled.
+pub fn spawn_worker() {
+    if !ENABLED.load(Ordering::Relaxed) || WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        info!("Metering worker started");
+        let mut last_sample: Option<std::time::Instant> = None;
+        let mut last_purge: Option<std::time::Instant> = None;
+        loop {
+            let config = current_settings();
+            tokio::time::sleep(Duration::from_secs(config.flush_interval_secs.max(1))).await;
+            if repository::get_repository_opt().is_none() {
+                continue;
+            }
+
+            let now = Utc::now();
+            let mut records = take_records(now);
+            if last_sample.is_none_or(|at| {
+                at.elapsed() >= Duration::from_secs(config.storage_sample_interval_secs)
+            }) {
+                last_sample = Some(std::time::Instant::now());
+                match tokio::task::spawn_blocking(move || sample_storage(now)).await {
+                    Ok(Ok(samples)) => records.extend(samples),
+                    Ok(Err(e)) => warn!("Failed to sample stored bytes: {}", e),
+                    Err(e) => warn!("Storage sampling task failed: {}", e),
+                }
+            }
+            flush(&config, records).await;
+
+            if config.sink == MeteringSinkKind::Table
+                && config.retention_days > 0
+                && last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL)
+            {
+                last_purge = Some(std::time::Instant::now());
+                let before = now
+                    - chrono::Duration::days(
+                        i64::try_from(config.retention_days).unwrap_or(i64::MAX / 86_400),
+                    );
+                match repository::get_repository()
+                    .purge_usage_records(before)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Removed {} usage records", count),
+                    Err(e) => warn!("Failed to purge usage records: {}", e),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_is_summed_into_records() {
+        configure(&MeteringConfig {
+            enabled: true,
+            ..MeteringConfig::default()
+        });
+        let started = Utc::now();
+        take_records(started);
+
+        record_request(
+            Some("acme"),
+            Some("user-1"),
+            "https://example.com/metered",
+            Duration::from_millis(40),
+        );
+        record_request(
+            Some("acme"),
+            Some("user-1"),
+            "https://example.com/metered",
+            Duration::from_millis(2),
+        );
+        record(
+            Metric::LlmInputTokens,
+            120,
+            Some("acme"),
+            None,
+            Some("https://example.com/metered"),
+        );
+        record(Metric::LlmOutputTokens, 0, Some("acme"), None, None);
+
+        let end = Utc::now();
+        let records: Vec<UsageRecord> = take_records(end)
+            .into_iter()
+            .filter(|record| record.script_uri.as_deref() == Some("https://example.com/metered"))
+            .collect();
+        let quantity = |metric: Metric| {
+            records
+                .iter()
+                .find(|record| record.metric == metric)
+                .map(|record| record.quantity)
+        };
+        assert_eq!(quantity(Metric::Requests), Some(2));
+        assert_eq!(quantity(Metric::JsMs), Some(42));
+        assert_eq!(quantity(Metric::LlmInputTokens), Some(120));
+        assert_eq!(quantity(Metric::LlmOutputTokens), None);
+
+        let requests = records
+            .iter()
+            .find(|record| record.metric == Metric::Requests)
+            .unwrap();
+        assert_eq!(requests.tenant.as_deref(), Some("acme"));
+        assert_eq!(requests.user_id.as_deref(), Some("user-1"));
+        assert_eq!(requests.unit, "request");
+        assert_eq!(requests.period_start, started);
+        assert_eq!(requests.period_end, end);
+
+        let json = serde_json::to_value(requests).unwrap();
+        assert_eq!(json["metric"], "requests");
+        assert_eq!(json["scriptUri"], "https://example.com/metered");
+    }
+
+    #[test]
+    fn test_signature() {
+        let signature = signature("secret", 1_700_000_000, r#"{"records":[]}"#);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert_ne!(
+            signature,
+            super::signature("other", 1_700_000_000, r#"{"records":[]}"#)
+        );
+    }
+}
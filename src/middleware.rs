@@ -8,15 +8,27 @@ static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
 /// Header name for request ID
 pub const REQUEST_ID_HEADER: &str = "x-request-id";
 
+/// Header name for W3C trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
 /// Middleware that generates and injects request IDs
 pub async fn request_id_middleware(request: Request, next: Next) -> Response {
     let request_id = generate_request_id();
 
+    // Continue the caller's trace, or start a new one
+    let trace_context = request
+        .headers()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::parse)
+        .unwrap_or_else(TraceContext::generate);
+
     // Add request ID to request extensions for use in handlers
     let mut request = request;
     request
         .extensions_mut()
         .insert(RequestId(request_id.clone()));
+    request.extensions_mut().insert(trace_context);
 
     // Add request ID to response headers
     let mut response = next.run(request).await;
@@ -58,6 +70,63 @@ pub fn extract_or_generate_request_id(headers: &HeaderMap) -> String {
 #[derive(Clone, Debug)]
 pub struct RequestId(pub String);
 
+/// W3C trace context of a request, stored in request extensions so calls
+/// made while handling the request can be correlated with it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits
+    pub trace_id: String,
+    /// Trace flags as two hex digits (`01` = sampled)
+    pub flags: String,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header value (`00-<trace-id>-<parent-id>-<flags>`).
+    /// Returns None for malformed values and the all-zero IDs the spec
+    /// declares invalid.
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+        let is_hex = |value: &str, len: usize| {
+            value.len() == len
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+        };
+        // Later versions may append fields; version 00 must have exactly four
+        let valid = is_hex(version, 2)
+            && version != "ff"
+            && (version != "00" || parts.next().is_none())
+            && is_hex(trace_id, 32)
+            && trace_id.chars().any(|c| c != '0')
+            && is_hex(parent_id, 16)
+            && parent_id.chars().any(|c| c != '0')
+            && is_hex(flags, 2);
+        valid.then(|| Self {
+            trace_id: trace_id.to_string(),
+            flags: flags.to_string(),
+        })
+    }
+
+    /// Start a new trace
+    pub fn generate() -> Self {
+        Self {
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            flags: "00".to_string(),
+        }
+    }
+
+    /// `traceparent` value for an outbound call made within this trace, with
+    /// a new span ID as the parent
+    pub fn child_traceparent(&self) -> String {
+        let span_id = uuid::Uuid::new_v4().simple().to_string();
+        format!("00-{}-{}-{}", self.trace_id, &span_id[..16], self.flags)
+    }
+}
+
 /// Helper trait to get request ID from various sources
 pub trait HasRequestId {
     fn request_id(&self) -> &str;
@@ -153,6 +222,37 @@ mod tests {
         assert!(id.starts_with("req_"));
     }
 
+    #[test]
+    fn test_trace_context_parse() {
+        let context =
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+                .expect("valid traceparent");
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.flags, "01");
+
+        // Invalid values start a new trace instead
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_trace_context_child_traceparent() {
+        let context = TraceContext::generate();
+        let child = context.child_traceparent();
+        let parsed = TraceContext::parse(&child).expect("child traceparent is valid");
+        assert_eq!(parsed, context);
+        assert_ne!(context.child_traceparent(), child);
+    }
+
     #[test]
     fn test_request_id_struct() {
         let request_id = RequestId("test-123".to_string());
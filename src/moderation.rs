@@ -0,0 +1,334 @@
+//! Content moderation of inbound and generated text (`moderation.check`).
+//!
+//! Text is checked by the moderation API of the provider named in
+//! `[javascript.llm.moderation]` (OpenAI, or an OpenAI-compatible server
+//! running a local model) and against local regular expressions per
+//! category. Either may be left out.
+//!
+//! Scripts check text explicitly with `moderation.check(text, options)`.
+//! The `input` and `output` settings also check the user messages and the
+//! generated text of every `llm.*` call, with one of these actions:
+//!
+//! - `audit` logs flagged text, to the server log and the script's log
+//! - `flag` also reports why in the `moderation` field of the completion
+//! - `block` also fails the call
+//!
+//! Streamed text reaches its listeners before the whole output can be
+//! checked, so a blocked stream ends with an `llm.error` message instead of
+//! `llm.done`.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::config::{LlmConfig, ModerationAction};
+use crate::repository;
+
+/// Longest text one check accepts
+pub const MAX_MODERATION_INPUT_BYTES: usize = 256 * 1024;
+
+/// Outcome of checking one text
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Categories the text was flagged for, sorted
+    pub categories: Vec<String>,
+    /// Score per category reported by the provider, from 0 to 1
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub scores: BTreeMap<String, f64>,
+}
+
+/// Flagged prompt and output of one `llm.*` call, reported with the `flag`
+/// action
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionModeration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<ModerationResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<ModerationResult>,
+}
+
+/// Options of `moderation.check`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct CheckOptions {
+    /// Policy applied when the text is flagged; none only reports
+    pub action: Option<ModerationAction>,
+}
+
+fn is_configured(config: &LlmConfig) -> bool {
+    !config.moderation.provider.is_empty() || !config.moderation.patterns.is_empty()
+}
+
+/// Check `text` with the configured provider and patterns
+pub fn check(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+    text: &str,
+) -> Result<ModerationResult, String> {
+    if !is_configured(config) {
+        return Err("Moderation is not configured".to_string());
+    }
+    if text.len() > MAX_MODERATION_INPUT_BYTES {
+        return Err(format!(
+            "text must not exceed {} bytes",
+            MAX_MODERATION_INPUT_BYTES
+        ));
+    }
+
+    let mut result = ModerationResult::default();
+    let moderation = &config.moderation;
+    if !moderation.provider.is_empty() {
+        let body = crate::llm::moderate(
+            config,
+            script_uri,
+            user_id,
+            &moderation.provider,
+            &moderation.model,
+            text,
+        )?;
+        result = parse_moderation_response(&moderation.provider, &body)?;
+    }
+    for category in matching_categories(config, text)? {
+        if !result.categories.contains(&category) {
+            result.categories.push(category);
+        }
+    }
+    result.categories.sort();
+    result.flagged |= !result.categories.is_empty();
+    Ok(result)
+}
+
+/// Categories whose local patterns match `text`
+fn matching_categories(config: &LlmConfig, text: &str) -> Result<Vec<String>, String> {
+    let mut categories = Vec::new();
+    for (category, patterns) in &config.moderation.patterns {
+        for pattern in patterns {
+            let regex = Regex::new(pattern)
+                .map_err(|e| format!("Invalid moderation pattern of '{}': {}", category, e))?;
+            if regex.is_match(text) {
+                categories.push(category.clone());
+                break;
+            }
+        }
+    }
+    Ok(categories)
+}
+
+fn parse_moderation_response(
+    provider_name: &str,
+    body: &Value,
+) -> Result<ModerationResult, String> {
+    let result = body
+        .pointer("/results/0")
+        .ok_or_else(|| format!("{} returned an unexpected response", provider_name))?;
+    let categories = result
+        .get("categories")
+        .and_then(Value::as_object)
+        .map(|categories| {
+            categories
+                .iter()
+                .filter(|(_, flagged)| flagged.as_bool() == Some(true))
+                .map(|(category, _)| category.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+    let scores = result
+        .get("category_scores")
+        .and_then(Value::as_object)
+        .map(|scores| {
+            scores
+                .iter()
+                .filter_map(|(category, score)| Some((category.clone(), score.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(ModerationResult {
+        flagged: result.get("flagged").and_then(Value::as_bool) == Some(true),
+        categories,
+        scores,
+    })
+}
+
+/// Apply `action` to a checked text described by `subject`: log it when
+/// flagged and fail when blocked. Returns the result when the script should
+/// see it.
+fn enforce(
+    action: ModerationAction,
+    subject: &str,
+    script_uri: &str,
+    user_id: Option<&str>,
+    result: ModerationResult,
+) -> Result<Option<ModerationResult>, String> {
+    if action == ModerationAction::Off || !result.flagged {
+        return Ok(None);
+    }
+    let categories = result.categories.join(", ");
+    warn!(
+        script_uri = %script_uri,
+        user_id = ?user_id,
+        action = ?action,
+        categories = %categories,
+        "Moderation flagged {}",
+        subject
+    );
+    if repository::get_repository_opt().is_some() {
+        repository::insert_log_message(
+            script_uri,
+            &format!("Moderation flagged {}: {}", subject, categories),
+            "WARN",
+        );
+    }
+    match action {
+        ModerationAction::Block => Err(format!("Moderation blocked {}: {}", subject, categories)),
+        ModerationAction::Flag => Ok(Some(result)),
+        ModerationAction::Audit | ModerationAction::Off => Ok(None),
+    }
+}
+
+/// Check `text` under `action` for an `llm.*` call. A failed check fails the
+/// call only when it would block.
+fn apply(
+    config: &LlmConfig,
+    action: ModerationAction,
+    subject: &str,
+    script_uri: &str,
+    user_id: Option<&str>,
+    text: &str,
+) -> Result<Option<ModerationResult>, String> {
+    if action == ModerationAction::Off || text.trim().is_empty() {
+        return Ok(None);
+    }
+    match check(config, script_uri, user_id, text) {
+        Ok(result) => enforce(action, subject, script_uri, user_id, result),
+        Err(e) if action == ModerationAction::Block => {
+            Err(format!("Failed to moderate {}: {}", subject, e))
+        }
+        Err(e) => {
+            warn!(script_uri = %script_uri, "Failed to moderate {}: {}", subject, e);
+            Ok(None)
+        }
+    }
+}
+
+/// `moderation.check`: check `text` and apply the script's chosen action
+pub fn check_text(
+    script_uri: &str,
+    user_id: Option<&str>,
+    text: &str,
+    options: &CheckOptions,
+) -> Result<ModerationResult, String> {
+    let config = crate::llm::current_settings();
+    let result = check(&config, script_uri, user_id, text)?;
+    if let Some(action) = options.action {
+        enforce(action, "text", script_uri, user_id, result.clone())?;
+    }
+    Ok(result)
+}
+
+/// Apply the `input` action to the user messages of an `llm.*` call
+pub fn moderate_input(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+    text: &str,
+) -> Result<Option<ModerationResult>, String> {
+    apply(
+        config,
+        config.moderation.input,
+        "LLM prompt",
+        script_uri,
+        user_id,
+        text,
+    )
+}
+
+/// Apply the `output` action to the text an `llm.*` call generated and
+/// report what the `flag` action found
+pub fn moderate_output(
+    config: &LlmConfig,
+    script_uri: &str,
+    user_id: Option<&str>,
+    input: Option<ModerationResult>,
+    text: &str,
+) -> Result<Option<CompletionModeration>, String> {
+    let output = apply(
+        config,
+        config.moderation.output,
+        "LLM output",
+        script_uri,
+        user_id,
+        text,
+    )?;
+    Ok((input.is_some() || output.is_some()).then_some(CompletionModeration { input, output }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(action: ModerationAction) -> LlmConfig {
+        let mut config = LlmConfig::default();
+        config.moderation.patterns.insert(
+            "secrets".to_string(),
+            vec![r"sk-[A-Za-z0-9]{20,}".to_string()],
+        );
+        config.moderation.output = action;
+        config
+    }
+
+    #[test]
+    fn test_pattern_moderation() {
+        let uri = "https://example.com/chat";
+        let key = "the key is sk-abcdefghijklmnopqrstuvwxyz";
+        let result = check(&config(ModerationAction::Off), uri, None, key).unwrap();
+        assert!(result.flagged);
+        assert_eq!(result.categories, vec!["secrets".to_string()]);
+        assert!(
+            !check(&config(ModerationAction::Off), uri, None, "hello")
+                .unwrap()
+                .flagged
+        );
+        assert!(check(&LlmConfig::default(), uri, None, key).is_err());
+
+        assert_eq!(
+            moderate_output(&config(ModerationAction::Audit), uri, None, None, key),
+            Ok(None)
+        );
+        let flagged =
+            moderate_output(&config(ModerationAction::Flag), uri, None, None, key).unwrap();
+        assert!(flagged.unwrap().output.unwrap().flagged);
+        assert_eq!(
+            moderate_output(&config(ModerationAction::Block), uri, None, None, key),
+            Err("Moderation blocked LLM output: secrets".to_string())
+        );
+        assert_eq!(
+            moderate_output(&config(ModerationAction::Block), uri, None, None, "hello"),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_parse_moderation_response() {
+        let body = json!({
+            "id": "modr-1",
+            "results": [{
+                "flagged": true,
+                "categories": { "harassment": true, "violence": false },
+                "category_scores": { "harassment": 0.91, "violence": 0.02 }
+            }]
+        });
+        let result = parse_moderation_response("openai", &body).unwrap();
+        assert!(result.flagged);
+        assert_eq!(result.categories, vec!["harassment".to_string()]);
+        assert_eq!(result.scores["violence"], 0.02);
+        assert!(parse_moderation_response("openai", &json!({})).is_err());
+    }
+}
@@ -28,6 +28,9 @@ impl std::error::Error for ModuleLoaderError {}
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PreparedExecutable {
     pub code: String,
+    /// Source map from `code` back to the script source. Only set for
+    /// transpiled scripts that import no modules; bundled programs have none.
+    pub source_map: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -49,10 +52,13 @@ pub fn prepare_executable_program(
 
     let root_path = root_module_path(script_uri)?;
     let mut linker = ModuleLinker::new(script_uri);
-    let root_code = linker.compile_root_module(&root_path, root_content)?;
+    let root = linker.compile_root_module(&root_path, root_content)?;
 
     if linker.module_order.is_empty() {
-        return Ok(PreparedExecutable { code: root_code });
+        return Ok(PreparedExecutable {
+            code: root.code,
+            source_map: root.source_map,
+        });
     }
 
     let mut bundled = String::from("const __asset_module_factories__ = Object.create(null);\n");
@@ -85,7 +91,7 @@ pub fn prepare_executable_program(
         ));
     }
 
-    bundled.push_str(&root_code);
+    bundled.push_str(&root.code);
 
     let code = transpiler::transpile_if_needed(script_uri, &bundled).map_err(|error| {
         ModuleLoaderError::Transpilation(format!(
@@ -94,7 +100,10 @@ pub fn prepare_executable_program(
         ))
     })?;
 
-    Ok(PreparedExecutable { code })
+    Ok(PreparedExecutable {
+        code,
+        source_map: None,
+    })
 }
 
 struct ModuleLinker<'a> {
@@ -118,11 +127,11 @@ impl<'a> ModuleLinker<'a> {
         &mut self,
         root_path: &str,
         root_content: &str,
-    ) -> Result<String, ModuleLoaderError> {
+    ) -> Result<transpiler::Transpiled, ModuleLoaderError> {
         let transformed = transform_module_source(root_content, root_path, true)?;
         self.resolve_dependencies(root_path, &transformed)?;
 
-        transpiler::transpile_if_needed(root_path, &transformed.code).map_err(|error| {
+        transpiler::transpile_with_source_map(root_path, &transformed.code).map_err(|error| {
             ModuleLoaderError::Transpilation(format!(
                 "Failed transpiling root module '{}': {}",
                 root_path, error
@@ -784,6 +793,7 @@ mod tests {
 
         assert!(prepared.code.contains("message"));
         assert!(!prepared.code.contains(": string"));
+        assert!(prepared.source_map.is_some());
     }
 
     #[test]
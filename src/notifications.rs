@@ -2,10 +2,10 @@ use crate::error::AppResult;
 use crate::stream_registry::FilterMatchMode;
 use crate::{graphql, repository, scheduler, script_init};
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::{PgListener, PgPool};
-use std::collections::HashMap;
-use std::sync::{Arc, OnceLock};
-use tokio::sync::RwLock;
+use sqlx::postgres::{PgListener, PgNotification, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{Notify, RwLock};
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -16,6 +16,143 @@ static GLOBAL_LISTENER: OnceLock<Arc<NotificationListener>> = OnceLock::new();
 /// Global server ID for this instance
 static GLOBAL_SERVER_ID: OnceLock<String> = OnceLock::new();
 
+/// Channels the engine itself listens on; scripts may not subscribe to them
+pub const RESERVED_CHANNELS: &[&str] = &[
+    "script_upserted",
+    "script_deleted",
+    "stream_broadcast",
+    crate::events::EVENT_CHANNEL,
+    crate::response_cache::PURGE_CHANNEL,
+    crate::admin_ops::ADMIN_CHANNEL,
+    crate::security::threat_response::THREAT_RESPONSE_CHANNEL,
+];
+
+/// Channels one script may listen on
+pub const MAX_CHANNELS_PER_SCRIPT: usize = 20;
+
+/// Script handler subscribed to a database notification channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelSubscription {
+    pub script_uri: String,
+    pub handler_name: String,
+}
+
+/// Subscriptions registered by scripts with db.listen, by channel
+static SCRIPT_CHANNELS: OnceLock<Mutex<HashMap<String, Vec<ChannelSubscription>>>> =
+    OnceLock::new();
+
+/// Wakes the listener loop when the set of script channels changes
+static SCRIPT_CHANNELS_CHANGED: Notify = Notify::const_new();
+
+fn lock_script_channels()
+-> std::sync::MutexGuard<'static, HashMap<String, Vec<ChannelSubscription>>> {
+    match SCRIPT_CHANNELS.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Script channel registry mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Check that `channel` is a plain lowercase identifier that is not used by
+/// the engine. Postgres folds unquoted NOTIFY channel names to lowercase, so
+/// anything else would never match what external writers send.
+pub fn validate_channel_name(channel: &str) -> Result<(), String> {
+    let mut chars = channel.chars();
+    let valid = channel.len() <= 63
+        && matches!(chars.next(), Some('a'..='z' | '_'))
+        && chars.all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_'));
+    if !valid {
+        return Err(format!(
+            "Invalid channel '{}': use lowercase letters, digits and underscores (max 63 characters)",
+            channel
+        ));
+    }
+    if RESERVED_CHANNELS.contains(&channel) {
+        return Err(format!("Channel '{}' is reserved", channel));
+    }
+    Ok(())
+}
+
+/// Subscribe a script handler to a database notification channel. Returns
+/// false when the same handler was already subscribed.
+pub fn register_script_channel(
+    script_uri: &str,
+    channel: &str,
+    handler_name: &str,
+) -> Result<bool, String> {
+    validate_channel_name(channel)?;
+
+    let mut channels = lock_script_channels();
+    let subscription = ChannelSubscription {
+        script_uri: script_uri.to_string(),
+        handler_name: handler_name.to_string(),
+    };
+    if channels
+        .get(channel)
+        .is_some_and(|subscriptions| subscriptions.contains(&subscription))
+    {
+        return Ok(false);
+    }
+    let script_channel_count = channels
+        .iter()
+        .filter(|(name, subscriptions)| {
+            name.as_str() != channel && subscriptions.iter().any(|s| s.script_uri == script_uri)
+        })
+        .count();
+    if script_channel_count >= MAX_CHANNELS_PER_SCRIPT {
+        return Err(format!(
+            "A script may listen on at most {} channels",
+            MAX_CHANNELS_PER_SCRIPT
+        ));
+    }
+
+    let subscriptions = channels.entry(channel.to_string()).or_default();
+    let new_channel = subscriptions.is_empty();
+    subscriptions.push(subscription);
+    drop(channels);
+
+    if new_channel {
+        SCRIPT_CHANNELS_CHANGED.notify_one();
+    }
+    Ok(true)
+}
+
+/// Remove every channel subscription of a script, returning how many were
+/// removed
+pub fn clear_script_channels(script_uri: &str) -> usize {
+    let mut channels = lock_script_channels();
+    let before = channels.len();
+    let mut removed = 0;
+    channels.retain(|_, subscriptions| {
+        let count = subscriptions.len();
+        subscriptions.retain(|s| s.script_uri != script_uri);
+        removed += count - subscriptions.len();
+        !subscriptions.is_empty()
+    });
+    let channel_removed = channels.len() != before;
+    drop(channels);
+
+    if channel_removed {
+        SCRIPT_CHANNELS_CHANGED.notify_one();
+    }
+    removed
+}
+
+/// Handlers subscribed to `channel`
+pub fn script_channel_subscriptions(channel: &str) -> Vec<ChannelSubscription> {
+    lock_script_channels()
+        .get(channel)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Channels scripts currently listen on
+pub fn script_channels() -> HashSet<String> {
+    lock_script_channels().keys().cloned().collect()
+}
+
 /// Initialize the global notification listener
 pub fn initialize_global_listener(listener: Arc<NotificationListener>) -> bool {
     GLOBAL_LISTENER.set(listener).is_ok()
@@ -148,10 +285,65 @@ impl NotificationListener {
             }
         })?;
 
+        listener
+            .listen(crate::events::EVENT_CHANNEL)
+            .await
+            .map_err(|e| crate::error::AppError::Database {
+                message: format!(
+                    "Failed to listen on {}: {}",
+                    crate::events::EVENT_CHANNEL,
+                    e
+                ),
+                source: None,
+            })?;
+
+        listener
+            .listen(crate::response_cache::PURGE_CHANNEL)
+            .await
+            .map_err(|e| crate::error::AppError::Database {
+                message: format!(
+                    "Failed to listen on {}: {}",
+                    crate::response_cache::PURGE_CHANNEL,
+                    e
+                ),
+                source: None,
+            })?;
+
+        listener
+            .listen(crate::admin_ops::ADMIN_CHANNEL)
+            .await
+            .map_err(|e| crate::error::AppError::Database {
+                message: format!(
+                    "Failed to listen on {}: {}",
+                    crate::admin_ops::ADMIN_CHANNEL,
+                    e
+                ),
+                source: None,
+            })?;
+
+        listener
+            .listen(crate::security::threat_response::THREAT_RESPONSE_CHANNEL)
+            .await
+            .map_err(|e| crate::error::AppError::Database {
+                message: format!(
+                    "Failed to listen on {}: {}",
+                    crate::security::threat_response::THREAT_RESPONSE_CHANNEL,
+                    e
+                ),
+                source: None,
+            })?;
+
         info!(
-            "Listening on PostgreSQL channels: script_upserted, script_deleted, stream_broadcast"
+            "Listening on PostgreSQL channels: script_upserted, script_deleted, stream_broadcast, {}, {}, {}, {}",
+            crate::events::EVENT_CHANNEL,
+            crate::response_cache::PURGE_CHANNEL,
+            crate::admin_ops::ADMIN_CHANNEL,
+            crate::security::threat_response::THREAT_RESPONSE_CHANNEL
         );
 
+        let mut listening = HashSet::new();
+        Self::sync_script_channels(&mut listener, &mut listening).await;
+
         loop {
             tokio::select! {
                 // Handle shutdown signal
@@ -160,6 +352,11 @@ impl NotificationListener {
                     break;
                 }
 
+                // Scripts subscribed to or dropped channels
+                _ = SCRIPT_CHANNELS_CHANGED.notified() => {
+                    Self::sync_script_channels(&mut listener, &mut listening).await;
+                }
+
                 // Handle notifications
                 notification = listener.recv() => {
                     match notification {
@@ -230,6 +427,62 @@ impl NotificationListener {
                                         }
                                     }
                                 }
+                                crate::events::EVENT_CHANNEL => {
+                                    match serde_json::from_str::<crate::events::EventMessage>(notification.payload()) {
+                                        Ok(msg) => {
+                                            // Own events were dispatched when published
+                                            if msg.server_id == server_id {
+                                                continue;
+                                            }
+                                            debug!(
+                                                "Dispatching event '{}' from server {}",
+                                                msg.event.topic, msg.server_id
+                                            );
+                                            crate::events::dispatch(&msg.event);
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to parse script event payload: {}", e);
+                                        }
+                                    }
+                                }
+                                crate::response_cache::PURGE_CHANNEL => {
+                                    match serde_json::from_str::<crate::response_cache::PurgeMessage>(notification.payload()) {
+                                        Ok(msg) => {
+                                            // Own purges were applied when requested
+                                            if msg.server_id == server_id {
+                                                continue;
+                                            }
+                                            crate::response_cache::apply_purge(&msg);
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to parse response cache purge payload: {}", e);
+                                        }
+                                    }
+                                }
+                                crate::admin_ops::ADMIN_CHANNEL => {
+                                    match serde_json::from_str::<crate::admin_ops::AdminMessage>(notification.payload()) {
+                                        Ok(msg) => {
+                                            // Own operations were applied when requested
+                                            if msg.server_id == server_id {
+                                                continue;
+                                            }
+                                            crate::admin_ops::apply_remote(&msg).await;
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to parse admin operation payload: {}", e);
+                                        }
+                                    }
+                                }
+                                crate::security::threat_response::THREAT_RESPONSE_CHANNEL => {
+                                    // The payload is the sending server's id;
+                                    // own changes were applied when made
+                                    if notification.payload() != server_id {
+                                        crate::security::threat_response::refresh().await;
+                                    }
+                                }
+                                _ if listening.contains(channel) => {
+                                    Self::handle_script_notification(&notification);
+                                }
                                 _ => {
                                     warn!("Unknown notification channel: {}", channel);
                                 }
@@ -247,6 +500,79 @@ impl NotificationListener {
         Ok(())
     }
 
+    /// LISTEN on channels scripts subscribed to since the last call and
+    /// UNLISTEN on channels no script uses anymore
+    async fn sync_script_channels(listener: &mut PgListener, listening: &mut HashSet<String>) {
+        let wanted = script_channels();
+
+        for channel in wanted.difference(listening).cloned().collect::<Vec<_>>() {
+            match listener.listen(&channel).await {
+                Ok(()) => {
+                    debug!("Listening on script channel: {}", channel);
+                    listening.insert(channel);
+                }
+                Err(e) => error!("Failed to listen on script channel {}: {}", channel, e),
+            }
+        }
+
+        for channel in listening.difference(&wanted).cloned().collect::<Vec<_>>() {
+            match listener.unlisten(&channel).await {
+                Ok(()) => {
+                    debug!("Stopped listening on script channel: {}", channel);
+                    listening.remove(&channel);
+                }
+                Err(e) => warn!("Failed to unlisten script channel {}: {}", channel, e),
+            }
+        }
+    }
+
+    /// Run the handlers subscribed to a script channel, each on a blocking
+    /// thread like scheduled jobs
+    fn handle_script_notification(notification: &PgNotification) {
+        let channel = notification.channel().to_string();
+        let payload = notification.payload().to_string();
+        let process_id = notification.process_id();
+
+        for subscription in script_channel_subscriptions(&channel) {
+            let channel = channel.clone();
+            let payload = payload.clone();
+            tokio::spawn(async move {
+                let script_uri = subscription.script_uri.clone();
+                let handler_name = subscription.handler_name.clone();
+                let execution = tokio::task::spawn_blocking(move || {
+                    crate::js_engine::execute_notification_handler(
+                        &subscription.script_uri,
+                        &subscription.handler_name,
+                        &channel,
+                        &payload,
+                        process_id,
+                    )
+                })
+                .await;
+
+                let failure = match execution {
+                    Ok(Ok(())) => None,
+                    Ok(Err(err)) => Some(err),
+                    Err(join_err) => Some(format!("handler panicked: {}", join_err)),
+                };
+                if let Some(err) = failure {
+                    warn!(
+                        script = %script_uri,
+                        handler = %handler_name,
+                        error = %err,
+                        "Notification handler failed"
+                    );
+                    repository::insert_log_message_async(
+                        &script_uri,
+                        &format!("notification handler '{}' failed: {}", handler_name, err),
+                        "ERROR",
+                    )
+                    .await;
+                }
+            });
+        }
+    }
+
     /// Handle script upserted notification
     async fn handle_script_upserted(uri: &str) -> AppResult<()> {
         info!("Handling script upserted for: {}", uri);
@@ -288,6 +614,7 @@ impl NotificationListener {
         // Ensure route lookups and bytecode pick up the changed source
         crate::route_index::invalidate();
         crate::bytecode::invalidate(uri);
+        crate::response_cache::purge_script(uri);
 
         Ok(())
     }
@@ -296,8 +623,10 @@ impl NotificationListener {
     async fn handle_script_deleted(uri: &str) -> AppResult<()> {
         info!("Handling script deleted for: {}", uri);
 
-        // Clear any scheduled jobs for this script
+        // Clear any scheduled jobs and channel subscriptions for this script
         scheduler::clear_script_jobs(uri);
+        clear_script_channels(uri);
+        crate::events::clear_script_subscriptions(uri);
         debug!("Cleared scheduled jobs for script '{}'", uri);
 
         // Clear GraphQL registrations for this script
@@ -324,6 +653,7 @@ impl NotificationListener {
         }
         crate::route_index::invalidate();
         crate::bytecode::invalidate(uri);
+        crate::response_cache::purge_script(uri);
 
         info!("✓ Script '{}' cleanup completed after remote deletion", uri);
 
@@ -375,6 +705,16 @@ impl NotificationListener {
         Ok(())
     }
 
+    /// Whether the listen loop is running; it stops for good when it can't
+    /// connect or subscribe
+    pub async fn is_running(&self) -> bool {
+        self.task_handle
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
     /// Stop the listener
     pub async fn stop(&self) -> AppResult<()> {
         info!("Stopping notification listener...");
@@ -430,4 +770,56 @@ mod tests {
         assert_eq!(deserialized.timestamp, msg.timestamp);
         assert_eq!(deserialized.server_id, msg.server_id);
     }
+
+    #[test]
+    fn test_validate_channel_name() {
+        assert!(validate_channel_name("orders_changed").is_ok());
+        assert!(validate_channel_name("_events2").is_ok());
+        assert!(validate_channel_name("").is_err());
+        assert!(validate_channel_name("Orders").is_err());
+        assert!(validate_channel_name("2fast").is_err());
+        assert!(validate_channel_name("a-b").is_err());
+        assert!(validate_channel_name(&"a".repeat(64)).is_err());
+        assert!(validate_channel_name("script_upserted").is_err());
+        assert!(validate_channel_name("script_event").is_err());
+    }
+
+    #[test]
+    fn test_script_channel_registry() {
+        let uri = "https://example.com/channel-registry-test";
+        let other = "https://example.com/channel-registry-other";
+
+        assert_eq!(
+            register_script_channel(uri, "registry_test_a", "onA"),
+            Ok(true)
+        );
+        assert_eq!(
+            register_script_channel(uri, "registry_test_a", "onA"),
+            Ok(false)
+        );
+        assert_eq!(
+            register_script_channel(other, "registry_test_a", "onA"),
+            Ok(true)
+        );
+        assert!(register_script_channel(uri, "stream_broadcast", "onA").is_err());
+        assert_eq!(script_channel_subscriptions("registry_test_a").len(), 2);
+        assert!(script_channels().contains("registry_test_a"));
+
+        assert_eq!(clear_script_channels(uri), 1);
+        assert_eq!(
+            script_channel_subscriptions("registry_test_a"),
+            vec![ChannelSubscription {
+                script_uri: other.to_string(),
+                handler_name: "onA".to_string(),
+            }]
+        );
+        assert_eq!(clear_script_channels(other), 1);
+        assert!(!script_channels().contains("registry_test_a"));
+
+        for i in 0..MAX_CHANNELS_PER_SCRIPT {
+            register_script_channel(uri, &format!("registry_test_{}", i), "on").unwrap();
+        }
+        assert!(register_script_channel(uri, "registry_test_extra", "on").is_err());
+        assert_eq!(clear_script_channels(uri), MAX_CHANNELS_PER_SCRIPT);
+    }
 }
@@ -0,0 +1,764 @@
+//! Web push and SMS notifications of scripts (`notify`).
+//!
+//! Web push: a page subscribes with the browser's `PushManager`, using
+//! `notify.vapidPublicKey()` as the `applicationServerKey`, and hands the
+//! subscription to a route that calls `notify.subscribe(subscription)`. It
+//! is stored in `push_subscriptions` for the signed-in user.
+//! `notify.push({ userId }, payload)` then sends the payload to every
+//! subscription of that user in the script, encrypted for each browser
+//! (RFC 8291) and signed with the server's VAPID key (RFC 8292).
+//! Subscriptions the push service reports gone are removed.
+//!
+//! SMS: `notify.sms(to, message)` sends a text message with the provider
+//! of `[javascript.notify.sms]`. Twilio and a generic JSON API are built
+//! in; a provider is an [`SmsSender`], so adding one means implementing it
+//! and naming it in [`SmsProviderKind`].
+
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use aes_gcm::{
+    Aes128Gcm, Nonce,
+    aead::{Aead, KeyInit},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use hkdf::Hkdf;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::{NotifyConfig, SmsConfig, SmsProviderKind};
+use crate::repository;
+
+/// Largest push payload: the 4096 bytes push services accept, less the
+/// encryption header, tag and padding delimiter
+pub const MAX_PUSH_PAYLOAD_BYTES: usize = 4096 - 86 - 16 - 1;
+
+/// Record size announced in the encryption header; payloads fit one record
+const RECORD_SIZE: u32 = 4096;
+
+/// How long a VAPID signature is valid
+const VAPID_VALIDITY: chrono::Duration = chrono::Duration::hours(12);
+
+/// Longest accepted push `topic`
+const MAX_TOPIC_LENGTH: usize = 32;
+
+static SETTINGS: OnceLock<RwLock<NotifyConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<NotifyConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the notification configuration. Called once at server startup.
+pub fn configure(config: &NotifyConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> NotifyConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// A stored browser push subscription
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushSubscription {
+    pub id: Uuid,
+    pub script_uri: String,
+    pub user_id: String,
+    /// Push service URL messages are posted to
+    pub endpoint: String,
+    /// Base64url P-256 public key of the browser
+    pub p256dh: String,
+    /// Base64url authentication secret of the browser
+    pub auth: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A push subscription to store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewPushSubscription {
+    pub script_uri: String,
+    pub user_id: String,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// Keys of a browser's `PushSubscription.toJSON()`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct SubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// A browser's `PushSubscription.toJSON()`, as given to `notify.subscribe`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SubscriptionInput {
+    pub endpoint: String,
+    pub keys: SubscriptionKeys,
+}
+
+/// Who `notify.push` sends to: every subscription of a user, or one
+/// subscription
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct PushTarget {
+    pub user_id: Option<String>,
+    pub subscription_id: Option<String>,
+}
+
+/// How soon the push service should deliver a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Urgency {
+    VeryLow,
+    Low,
+    Normal,
+    High,
+}
+
+impl Urgency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Urgency::VeryLow => "very-low",
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::High => "high",
+        }
+    }
+}
+
+/// Options of `notify.push`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct PushOptions {
+    /// Seconds the push service keeps an undelivered message
+    pub ttl: Option<u32>,
+    pub urgency: Option<Urgency>,
+    /// A newer message with the same topic replaces an undelivered one
+    pub topic: Option<String>,
+}
+
+/// What `notify.push` did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushResult {
+    pub sent: usize,
+    pub failed: usize,
+    /// Subscriptions the push service reported gone, now removed
+    pub removed: usize,
+    /// Why sends failed
+    pub errors: Vec<String>,
+}
+
+/// A text message handed to the SMS provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmsReceipt {
+    pub to: String,
+    pub provider: SmsProviderKind,
+    /// The provider's ID of the message, when it returns one
+    pub message_id: Option<String>,
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim().trim_end_matches('='))
+        .map_err(|e| e.to_string())
+}
+
+fn signing_key(private_key: &str) -> Result<SigningKey, String> {
+    let bytes = decode_base64url(private_key)?;
+    SigningKey::from_slice(&bytes).map_err(|_| "not a P-256 private key".to_string())
+}
+
+/// The base64url public key of a VAPID private key, for browsers'
+/// `applicationServerKey`
+pub fn vapid_public_key(private_key: &str) -> Result<String, String> {
+    let key = signing_key(private_key)?;
+    Ok(URL_SAFE_NO_PAD.encode(key.verifying_key().to_encoded_point(false).as_bytes()))
+}
+
+/// The configured VAPID key and subject; an error while push is disabled
+fn vapid(config: &NotifyConfig) -> Result<(SigningKey, String), String> {
+    let key = config
+        .vapid_private_key
+        .as_deref()
+        .ok_or_else(|| "Web push is not configured on this server".to_string())?;
+    Ok((signing_key(key)?, config.vapid_subject.clone()))
+}
+
+/// The public key of the configured VAPID key, for `notify.vapidPublicKey()`
+pub fn public_key() -> Result<String, String> {
+    let (key, _) = vapid(&current_settings())?;
+    Ok(URL_SAFE_NO_PAD.encode(key.verifying_key().to_encoded_point(false).as_bytes()))
+}
+
+/// `Authorization` header value of a push request to `endpoint` (RFC 8292)
+fn vapid_authorization(
+    key: &SigningKey,
+    subject: &str,
+    endpoint: &url::Url,
+) -> Result<String, String> {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"typ":"JWT","alg":"ES256"}"#);
+    let claims = json!({
+        "aud": endpoint.origin().ascii_serialization(),
+        "exp": (Utc::now() + VAPID_VALIDITY).timestamp(),
+        "sub": subject,
+    });
+    let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signing_input = format!("{}.{}", header, claims);
+    let signature: Signature = key.sign(signing_input.as_bytes());
+    let public_key = URL_SAFE_NO_PAD.encode(key.verifying_key().to_encoded_point(false).as_bytes());
+    Ok(format!(
+        "vapid t={}.{}, k={}",
+        signing_input,
+        URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        public_key
+    ))
+}
+
+fn hkdf_expand<const N: usize>(salt: &[u8], ikm: &[u8], info: &[&[u8]]) -> [u8; N] {
+    let mut okm = [0u8; N];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand_multi_info(info, &mut okm)
+        .expect("output length is valid for HKDF-SHA-256");
+    okm
+}
+
+/// Content encryption key and nonce of a message (RFC 8291 section 3.4)
+fn derive_keys(
+    ecdh_secret: &[u8],
+    auth_secret: &[u8],
+    ua_public: &[u8],
+    as_public: &[u8],
+    salt: &[u8],
+) -> ([u8; 16], [u8; 12]) {
+    let ikm: [u8; 32] = hkdf_expand(
+        auth_secret,
+        ecdh_secret,
+        &[b"WebPush: info\0", ua_public, as_public],
+    );
+    let cek = hkdf_expand(salt, &ikm, &[b"Content-Encoding: aes128gcm\0"]);
+    let nonce = hkdf_expand(salt, &ikm, &[b"Content-Encoding: nonce\0"]);
+    (cek, nonce)
+}
+
+/// Encrypt `payload` for a browser with the server key `as_secret` and
+/// `salt`, as one `aes128gcm` record (RFC 8188) with its header
+fn encrypt_with(
+    payload: &[u8],
+    ua_public: &PublicKey,
+    auth_secret: &[u8],
+    as_secret: &SecretKey,
+    salt: [u8; 16],
+) -> Result<Vec<u8>, String> {
+    let ua_public_bytes = ua_public.to_encoded_point(false);
+    let as_public_bytes = as_secret.public_key().to_encoded_point(false);
+    let ecdh_secret =
+        p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_public.as_affine());
+    let (cek, nonce) = derive_keys(
+        ecdh_secret.raw_secret_bytes(),
+        auth_secret,
+        ua_public_bytes.as_bytes(),
+        as_public_bytes.as_bytes(),
+        &salt,
+    );
+
+    // The last (and only) record ends with the padding delimiter 2
+    let mut plaintext = Vec::with_capacity(payload.len() + 1);
+    plaintext.extend_from_slice(payload);
+    plaintext.push(2);
+    let ciphertext = Aes128Gcm::new(&cek.into())
+        .encrypt(&Nonce::from(nonce), plaintext.as_slice())
+        .map_err(|e| format!("Failed to encrypt push payload: {}", e))?;
+
+    let key_id = as_public_bytes.as_bytes();
+    let mut body = Vec::with_capacity(21 + key_id.len() + ciphertext.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(key_id.len() as u8);
+    body.extend_from_slice(key_id);
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// Encrypt `payload` for `subscription` with a fresh server key and salt
+fn encrypt_payload(payload: &[u8], subscription: &PushSubscription) -> Result<Vec<u8>, String> {
+    let ua_public = PublicKey::from_sec1_bytes(&decode_base64url(&subscription.p256dh)?)
+        .map_err(|_| "Subscription p256dh is not a P-256 public key".to_string())?;
+    let auth_secret = decode_base64url(&subscription.auth)?;
+    let as_secret = loop {
+        // A random 32-byte string is a valid scalar all but ~2^-32 of the time
+        if let Ok(key) = SecretKey::from_slice(&rand::random::<[u8; 32]>()) {
+            break key;
+        }
+    };
+    encrypt_with(
+        payload,
+        &ua_public,
+        &auth_secret,
+        &as_secret,
+        rand::random(),
+    )
+}
+
+/// Store the browser subscription `input` of `user_id` for `script_uri`
+pub fn subscribe(
+    script_uri: &str,
+    user_id: Option<&str>,
+    input: &SubscriptionInput,
+) -> Result<PushSubscription, String> {
+    let config = current_settings();
+    vapid(&config)?;
+    let user_id =
+        user_id.ok_or_else(|| "notify.subscribe requires a signed-in user".to_string())?;
+    let endpoint = url::Url::parse(&input.endpoint)
+        .ok()
+        .filter(|url| url.scheme() == "https")
+        .ok_or_else(|| "Subscription endpoint must be an https URL".to_string())?;
+    let p256dh = decode_base64url(&input.keys.p256dh)
+        .ok()
+        .filter(|key| PublicKey::from_sec1_bytes(key).is_ok())
+        .ok_or_else(|| {
+            "Subscription keys.p256dh must be a base64url P-256 public key".to_string()
+        })?;
+    if decode_base64url(&input.keys.auth).map(|auth| auth.len()) != Ok(16) {
+        return Err("Subscription keys.auth must be a base64url 16-byte secret".to_string());
+    }
+    let others = repository::count_push_subscriptions(script_uri, user_id, endpoint.as_str())
+        .map_err(|e| e.to_string())?;
+    if others >= config.max_subscriptions_per_user {
+        return Err(format!(
+            "A user may have at most {} push subscriptions",
+            config.max_subscriptions_per_user
+        ));
+    }
+
+    let subscription = repository::upsert_push_subscription(&NewPushSubscription {
+        script_uri: script_uri.to_string(),
+        user_id: user_id.to_string(),
+        endpoint: endpoint.to_string(),
+        p256dh: URL_SAFE_NO_PAD.encode(p256dh),
+        auth: input.keys.auth.trim().trim_end_matches('=').to_string(),
+    })
+    .map_err(|e| e.to_string())?;
+    debug!(script_uri = %script_uri, id = %subscription.id, "Stored push subscription");
+    Ok(subscription)
+}
+
+/// Remove the subscription of `script_uri` with the ID or endpoint `key`
+pub fn unsubscribe(script_uri: &str, key: &str) -> Result<bool, String> {
+    repository::delete_push_subscription(script_uri, key).map_err(|e| e.to_string())
+}
+
+/// The body of a push payload: strings are sent as they are, anything else
+/// as JSON
+fn push_body(payload: &Value) -> Vec<u8> {
+    match payload {
+        Value::String(text) => text.as_bytes().to_vec(),
+        other => other.to_string().into_bytes(),
+    }
+}
+
+/// Send `payload` to the subscriptions of `script_uri` that `target` names
+pub fn push(
+    script_uri: &str,
+    target: &PushTarget,
+    payload: &Value,
+    options: &PushOptions,
+) -> Result<PushResult, String> {
+    let config = current_settings();
+    let (key, subject) = vapid(&config)?;
+    let body = push_body(payload);
+    if body.len() > MAX_PUSH_PAYLOAD_BYTES {
+        return Err(format!(
+            "Push payload must not exceed {} bytes",
+            MAX_PUSH_PAYLOAD_BYTES
+        ));
+    }
+    if let Some(topic) = &options.topic
+        && (topic.is_empty()
+            || topic.len() > MAX_TOPIC_LENGTH
+            || !topic
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    {
+        return Err(format!(
+            "topic must be 1 to {} letters, digits, '-' or '_'",
+            MAX_TOPIC_LENGTH
+        ));
+    }
+    let subscriptions = match (&target.user_id, &target.subscription_id) {
+        (Some(user_id), None) => {
+            repository::list_push_subscriptions(script_uri, Some(user_id), None)
+        }
+        (None, Some(id)) => match Uuid::parse_str(id) {
+            Ok(id) => repository::list_push_subscriptions(script_uri, None, Some(id)),
+            Err(_) => Ok(Vec::new()),
+        },
+        _ => return Err("Push target needs either userId or subscriptionId".to_string()),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let client = crate::http_client::HttpClient::new().map_err(|e| e.to_string())?;
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let mut result = PushResult::default();
+    for subscription in &subscriptions {
+        let sent = url::Url::parse(&subscription.endpoint)
+            .map_err(|e| e.to_string())
+            .and_then(|endpoint| {
+                let mut headers = std::collections::HashMap::from([
+                    (
+                        "Authorization".to_string(),
+                        vapid_authorization(&key, &subject, &endpoint)?,
+                    ),
+                    ("Content-Encoding".to_string(), "aes128gcm".to_string()),
+                    (
+                        "Content-Type".to_string(),
+                        "application/octet-stream".to_string(),
+                    ),
+                    (
+                        "TTL".to_string(),
+                        options.ttl.unwrap_or(config.push_ttl_seconds).to_string(),
+                    ),
+                ]);
+                if let Some(urgency) = options.urgency {
+                    headers.insert("Urgency".to_string(), urgency.as_str().to_string());
+                }
+                if let Some(topic) = &options.topic {
+                    headers.insert("Topic".to_string(), topic.clone());
+                }
+                let encrypted = encrypt_payload(&body, subscription)?;
+                client
+                    .post_bytes(endpoint.as_str(), headers, encrypted, timeout)
+                    .map_err(|e| e.to_string())
+            });
+        match sent {
+            Ok(response) if response.ok => result.sent += 1,
+            // The browser unsubscribed or the subscription expired
+            Ok(response) if response.status == 404 || response.status == 410 => {
+                if let Err(e) =
+                    repository::delete_push_subscription(script_uri, &subscription.id.to_string())
+                {
+                    warn!(id = %subscription.id, "Failed to remove push subscription: {}", e);
+                }
+                result.removed += 1;
+            }
+            Ok(response) => {
+                result.failed += 1;
+                result.errors.push(format!(
+                    "HTTP {} from {}",
+                    response.status,
+                    url::Url::parse(&subscription.endpoint)
+                        .ok()
+                        .and_then(|url| url.host_str().map(str::to_string))
+                        .unwrap_or_default()
+                ));
+            }
+            Err(error) => {
+                result.failed += 1;
+                result.errors.push(error);
+            }
+        }
+    }
+    debug!(
+        script_uri = %script_uri,
+        sent = result.sent,
+        failed = result.failed,
+        removed = result.removed,
+        "Sent push notifications"
+    );
+    Ok(result)
+}
+
+/// Shared connection-pooled client; SMS provider URLs come from the
+/// configuration, so they are not restricted like `fetch` targets
+fn shared_client() -> Result<&'static reqwest::blocking::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::blocking::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// A service that sends text messages
+pub trait SmsSender {
+    /// Send `body` to the E.164 number `to`; returns the provider's message
+    /// ID, if any
+    fn send(&self, to: &str, body: &str) -> Result<Option<String>, String>;
+}
+
+/// The provider's error message of a failed request
+fn provider_error(provider: &str, response: reqwest::blocking::Response) -> String {
+    let status = response.status();
+    let message = response
+        .json::<Value>()
+        .ok()
+        .and_then(|body| {
+            body.get("message")
+                .or_else(|| body.get("error"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| status.to_string());
+    format!("{} refused the message: {}", provider, message)
+}
+
+/// Twilio's Messages API
+struct TwilioSms {
+    account_sid: String,
+    auth_token: String,
+    from: String,
+    timeout: Duration,
+}
+
+impl SmsSender for TwilioSms {
+    fn send(&self, to: &str, body: &str) -> Result<Option<String>, String> {
+        let url = format!(
+            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            self.account_sid
+        );
+        let response = shared_client()?
+            .post(url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[("To", to), ("From", &self.from), ("Body", body)])
+            .timeout(self.timeout)
+            .send()
+            .map_err(|e| format!("Twilio request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(provider_error("Twilio", response));
+        }
+        let sent: Value = response
+            .json()
+            .map_err(|e| format!("Invalid Twilio response: {}", e))?;
+        Ok(sent.get("sid").and_then(Value::as_str).map(str::to_string))
+    }
+}
+
+/// A JSON API posted `{ to, from, body }`
+struct HttpSms {
+    api_url: String,
+    token: Option<String>,
+    from: String,
+    timeout: Duration,
+}
+
+impl SmsSender for HttpSms {
+    fn send(&self, to: &str, body: &str) -> Result<Option<String>, String> {
+        let mut request = shared_client()?
+            .post(&self.api_url)
+            .json(&json!({ "to": to, "from": self.from, "body": body }))
+            .timeout(self.timeout);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .map_err(|e| format!("SMS request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(provider_error("The SMS provider", response));
+        }
+        Ok(response
+            .json::<Value>()
+            .ok()
+            .and_then(|sent| sent.get("id").and_then(Value::as_str).map(str::to_string)))
+    }
+}
+
+/// Logs messages instead of sending them
+struct LogSms;
+
+impl SmsSender for LogSms {
+    fn send(&self, to: &str, body: &str) -> Result<Option<String>, String> {
+        info!(to = %to, "SMS (log provider, not sent): {}", body);
+        Ok(None)
+    }
+}
+
+/// The configured SMS provider for `script_uri`, with its credentials
+fn sms_sender(
+    config: &SmsConfig,
+    script_uri: &str,
+    timeout: Duration,
+) -> Result<Box<dyn SmsSender>, String> {
+    let credentials = || match &config.credentials_secret {
+        Some(secret) => repository::resolve_secret_db(script_uri, secret, None)
+            .map(Some)
+            .ok_or_else(|| format!("Secret '{}' is not set", secret)),
+        None => Ok(None),
+    };
+    match config.provider {
+        SmsProviderKind::Disabled => Err("SMS is not configured on this server".to_string()),
+        SmsProviderKind::Twilio => Ok(Box::new(TwilioSms {
+            account_sid: config.account_sid.clone().unwrap_or_default(),
+            auth_token: credentials()?.unwrap_or_default(),
+            from: config.from.clone(),
+            timeout,
+        })),
+        SmsProviderKind::Http => Ok(Box::new(HttpSms {
+            api_url: config.api_url.clone().unwrap_or_default(),
+            token: credentials()?,
+            from: config.from.clone(),
+            timeout,
+        })),
+        SmsProviderKind::Log => Ok(Box::new(LogSms)),
+    }
+}
+
+/// Whether `number` is in E.164 form, such as "+358401234567"
+fn is_e164(number: &str) -> bool {
+    number.strip_prefix('+').is_some_and(|digits| {
+        (7..=15).contains(&digits.len())
+            && !digits.starts_with('0')
+            && digits.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+/// Send the text message `body` from `script_uri` to `to`
+pub fn sms(script_uri: &str, to: &str, body: &str) -> Result<SmsReceipt, String> {
+    let config = current_settings();
+    let to = to.trim();
+    if !is_e164(to) {
+        return Err(format!(
+            "Invalid phone number '{}': use E.164 form such as +358401234567",
+            to
+        ));
+    }
+    if body.trim().is_empty() {
+        return Err("notify.sms requires a message".to_string());
+    }
+    if body.chars().count() > config.sms.max_length {
+        return Err(format!(
+            "Message must not exceed {} characters",
+            config.sms.max_length
+        ));
+    }
+    let sender = sms_sender(
+        &config.sms,
+        script_uri,
+        Duration::from_millis(config.timeout_ms),
+    )?;
+    let message_id = sender.send(to, body).inspect_err(|error| {
+        warn!(script_uri = %script_uri, "Failed to send SMS: {}", error);
+    })?;
+    debug!(script_uri = %script_uri, "Sent SMS");
+    Ok(SmsReceipt {
+        to: to.to_string(),
+        provider: config.sms.provider,
+        message_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(ua_secret: &SecretKey, auth: &[u8]) -> PushSubscription {
+        PushSubscription {
+            id: Uuid::new_v4(),
+            script_uri: "https://example.com/push".to_string(),
+            user_id: "user-1".to_string(),
+            endpoint: "https://push.example.com/send/abc".to_string(),
+            p256dh: URL_SAFE_NO_PAD
+                .encode(ua_secret.public_key().to_encoded_point(false).as_bytes()),
+            auth: URL_SAFE_NO_PAD.encode(auth),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_encrypt_payload_round_trip() {
+        let ua_secret = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let auth = [3u8; 16];
+        let body = encrypt_payload(b"hello", &subscription(&ua_secret, &auth)).unwrap();
+
+        // Decrypt as the browser would
+        let salt = &body[..16];
+        assert_eq!(u32::from_be_bytes(body[16..20].try_into().unwrap()), 4096);
+        assert_eq!(body[20], 65);
+        let as_public = PublicKey::from_sec1_bytes(&body[21..86]).unwrap();
+        let ecdh_secret =
+            p256::ecdh::diffie_hellman(ua_secret.to_nonzero_scalar(), as_public.as_affine());
+        let (cek, nonce) = derive_keys(
+            ecdh_secret.raw_secret_bytes(),
+            &auth,
+            ua_secret.public_key().to_encoded_point(false).as_bytes(),
+            &body[21..86],
+            salt,
+        );
+        let plaintext = Aes128Gcm::new(&cek.into())
+            .decrypt(&Nonce::from(nonce), &body[86..])
+            .unwrap();
+        assert_eq!(plaintext, b"hello\x02");
+    }
+
+    #[test]
+    fn test_vapid_authorization() {
+        let private_key = URL_SAFE_NO_PAD.encode([5u8; 32]);
+        let key = signing_key(&private_key).unwrap();
+        let endpoint = url::Url::parse("https://push.example.com/send/abc").unwrap();
+        let header = vapid_authorization(&key, "mailto:admin@example.com", &endpoint).unwrap();
+
+        let (token, public_key) = header
+            .strip_prefix("vapid t=")
+            .and_then(|rest| rest.split_once(", k="))
+            .unwrap();
+        assert_eq!(public_key, vapid_public_key(&private_key).unwrap());
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["aud"], "https://push.example.com");
+        assert_eq!(claims["sub"], "mailto:admin@example.com");
+
+        use p256::ecdsa::signature::Verifier;
+        let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()).unwrap();
+        assert!(
+            key.verifying_key()
+                .verify(format!("{}.{}", parts[0], parts[1]).as_bytes(), &signature)
+                .is_ok()
+        );
+        assert!(vapid_public_key("not-a-key").is_err());
+    }
+
+    #[test]
+    fn test_sms_validation() {
+        assert!(is_e164("+358401234567"));
+        assert!(!is_e164("0401234567"));
+        assert!(!is_e164("+0401234567"));
+        assert!(!is_e164("+35840 123"));
+        assert!(
+            sms("https://example.com/sms", "040", "hi")
+                .unwrap_err()
+                .starts_with("Invalid phone number '040'")
+        );
+        assert_eq!(
+            sms("https://example.com/sms", "+358401234567", " ").unwrap_err(),
+            "notify.sms requires a message"
+        );
+    }
+}
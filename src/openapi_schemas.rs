@@ -64,14 +64,79 @@ pub struct BuildVersion {
 /// Health check response
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct HealthResponse {
-    /// Overall status of the service
+    /// Overall status of the service: healthy, degraded (a dependency other
+    /// than the database failed) or unhealthy (the database failed)
+    #[schema(example = "healthy")]
     pub status: String,
+    /// Server instance answering the request
+    pub instance_id: String,
     /// Current timestamp
     pub timestamp: String,
     /// Build and version information
     pub version: BuildVersion,
-    /// Database connection status
-    pub database: String,
+    /// Dependency checks by name: database, notification_listener, secrets
+    /// and outbound
+    pub checks: std::collections::HashMap<String, HealthCheck>,
+}
+
+/// Result of one dependency check
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HealthCheck {
+    /// ok, failed or skipped
+    #[schema(example = "ok")]
+    pub status: String,
+    /// How long the check took, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Why the check failed or was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Check-specific details, such as whether secrets are encrypted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+}
+
+/// What an instance is running
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EngineVersionResponse {
+    /// Server instance answering the request
+    pub instance_id: String,
+    /// Cargo package version from Cargo.toml
+    #[schema(example = "0.1.0")]
+    pub version: String,
+    /// Git commit hash (short SHA) - empty if git unavailable
+    #[schema(example = "abc123def456")]
+    pub git_commit: String,
+    /// Git commit timestamp in ISO 8601 format - empty if git unavailable
+    #[schema(example = "2026-01-18T10:30:00+00:00")]
+    pub git_commit_timestamp: String,
+    /// Build timestamp in ISO 8601 format - empty if unavailable
+    #[schema(example = "2026-01-18T10:35:00+00:00")]
+    pub build_timestamp: String,
+    /// debug or release
+    #[schema(example = "release")]
+    pub profile: String,
+    /// Optional subsystems enabled in the configuration
+    #[schema(example = json!(["auth", "compression", "metrics"]))]
+    pub features: Vec<String>,
+    /// Scripts loaded by the instance
+    pub scripts: LoadedScripts,
+}
+
+/// Scripts loaded by an instance
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LoadedScripts {
+    /// Total number of scripts
+    pub total: usize,
+    /// Scripts whose init() succeeded
+    pub initialized: usize,
+    /// Scripts whose init() failed
+    pub failed: usize,
+    /// Routes registered by initialized scripts
+    pub routes: usize,
+    /// Scheduled jobs registered on the instance
+    pub scheduled_jobs: usize,
 }
 
 /// Detailed cluster health response
@@ -277,3 +342,35 @@ pub struct AuthStatusResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub roles: Option<Vec<String>>,
 }
+
+// ============================================================================
+// Documentation Schemas
+// ============================================================================
+
+/// Documentation search response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocsSearchResponse {
+    /// The query as received
+    pub query: String,
+    /// Number of matches before the limit was applied
+    pub total: usize,
+    /// Matches ordered by descending score
+    pub results: Vec<DocsSearchHit>,
+}
+
+/// A single documentation search match
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocsSearchHit {
+    /// Source of the match: "doc", "route" or "graphql"
+    #[schema(example = "doc")]
+    pub kind: String,
+    /// Page title, "METHOD /path" for routes, "query name" for GraphQL operations
+    pub title: String,
+    /// Where the match can be viewed
+    #[schema(example = "/engine/docs/getting-started")]
+    pub url: String,
+    /// Excerpt of the matching text
+    pub snippet: String,
+    /// Relevance score (higher is better)
+    pub score: f64,
+}
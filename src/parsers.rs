@@ -1,36 +1,364 @@
 use axum::body::Body;
 use axum::http::StatusCode;
 use futures_util::TryStreamExt;
-use multer::Multipart;
+use multer::{Constraints, Multipart, SizeLimit};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_urlencoded;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use tracing::error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, error, warn};
+
+/// Spooled files older than this are leftovers of a crashed server
+const STALE_SPOOL_AGE: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Prefix of spooled upload file names
+const SPOOL_FILE_PREFIX: &str = "upload-";
+
+/// Size limits of form submissions and where large uploads are spooled
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    /// Largest file or field, and largest URL-encoded form
+    pub max_file_bytes: usize,
+    /// Largest multipart body, all files and fields together
+    pub max_request_bytes: usize,
+    /// Files larger than this are written to `spool_dir` instead of being
+    /// kept in memory
+    pub spool_threshold_bytes: usize,
+    pub spool_dir: PathBuf,
+}
+
+impl UploadLimits {
+    pub fn from_config(config: &crate::config::RepositoryConfig) -> Self {
+        Self {
+            max_file_bytes: config.max_upload_size_bytes,
+            max_request_bytes: config.max_upload_request_bytes,
+            spool_threshold_bytes: config.upload_spool_threshold_bytes,
+            spool_dir: config
+                .upload_spool_dir
+                .as_ref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| std::env::temp_dir().join("aiwebengine-uploads")),
+        }
+    }
+
+    /// These limits tightened by a route's upload rules, so oversized files
+    /// are rejected while they arrive
+    pub fn for_route(&self, rules: &RouteUploads) -> Self {
+        let mut limits = self.clone();
+        if let Some(max_file_size) = rules.max_file_size {
+            let max_file_size = usize::try_from(max_file_size).unwrap_or(usize::MAX);
+            limits.max_file_bytes = limits.max_file_bytes.min(max_file_size);
+        }
+        limits
+    }
+}
+
+/// Upload rules registered with a route (`registerRoute` option `uploads`),
+/// enforced before the handler runs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteUploads {
+    /// Largest accepted file or field in bytes, below the server's own limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// Most files accepted in one request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+    /// Accepted content types, such as `application/pdf` or `image/*`;
+    /// empty accepts any type
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_types: Vec<String>,
+}
+
+/// Why uploaded files were refused by a route's [`RouteUploads`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadViolation {
+    TooManyFiles { max_files: usize },
+    TypeNotAllowed { field: String, content_type: String },
+}
+
+impl std::fmt::Display for UploadViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadViolation::TooManyFiles { max_files } => {
+                write!(f, "At most {} files may be uploaded", max_files)
+            }
+            UploadViolation::TypeNotAllowed {
+                field,
+                content_type,
+            } => write!(
+                f,
+                "File '{}' has content type '{}', which is not allowed",
+                field, content_type
+            ),
+        }
+    }
+}
+
+impl RouteUploads {
+    /// Checks the files of a request. Sizes are enforced while the body is
+    /// parsed, with the limits of [`UploadLimits::for_route`].
+    pub fn check(&self, files: &[UploadedFile]) -> Result<(), UploadViolation> {
+        if let Some(max_files) = self.max_files
+            && files.len() > max_files
+        {
+            return Err(UploadViolation::TooManyFiles { max_files });
+        }
+        if self.allowed_types.is_empty() {
+            return Ok(());
+        }
+        for file in files {
+            let content_type = file
+                .content_type
+                .as_deref()
+                .and_then(|value| value.split(';').next())
+                .map(|value| value.trim().to_ascii_lowercase())
+                .filter(|value| !value.is_empty())
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            if !self
+                .allowed_types
+                .iter()
+                .any(|allowed| content_type_matches(allowed, &content_type))
+            {
+                return Err(UploadViolation::TypeNotAllowed {
+                    field: file.field_name.clone(),
+                    content_type,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `content_type` is accepted by `pattern`: an exact type, `type/*`
+/// or `*/*`
+pub fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(main_type) => content_type
+            .split_once('/')
+            .is_some_and(|(candidate, _)| candidate == main_type),
+        None => pattern == content_type,
+    }
+}
+
+/// An uploaded file written to disk, deleted when the last reference to
+/// it is dropped
+#[derive(Debug)]
+pub struct SpooledFile {
+    path: PathBuf,
+}
+
+impl SpooledFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove spooled upload {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Where the content of an uploaded file is kept
+#[derive(Debug, Clone)]
+pub enum UploadContent {
+    Memory(Vec<u8>),
+    Spooled(Arc<SpooledFile>),
+}
 
 /// Represents an uploaded file from multipart form data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct UploadedFile {
     pub field_name: String,
     pub filename: Option<String>,
     pub content_type: Option<String>,
-    pub data: Vec<u8>,
     pub size: usize,
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+    pub content: UploadContent,
+}
+
+impl UploadedFile {
+    /// An upload whose content was written to `path` by other means, such as
+    /// a resumable upload. The file is deleted with the last reference to it.
+    pub fn from_file(
+        field_name: String,
+        filename: Option<String>,
+        content_type: Option<String>,
+        path: PathBuf,
+    ) -> std::io::Result<Self> {
+        let spooled = Arc::new(SpooledFile { path });
+        let mut file = std::fs::File::open(spooled.path())?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut size = 0u64;
+        loop {
+            let read = std::io::Read::read(&mut file, &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+        Ok(Self {
+            field_name,
+            filename,
+            content_type,
+            size: usize::try_from(size).unwrap_or(usize::MAX),
+            sha256: hex::encode(hasher.finalize()),
+            content: UploadContent::Spooled(spooled),
+        })
+    }
+
+    /// Path of the file's content when it was spooled to disk
+    pub fn path(&self) -> Option<&Path> {
+        match &self.content {
+            UploadContent::Memory(_) => None,
+            UploadContent::Spooled(file) => Some(file.path()),
+        }
+    }
+
+    /// The file's content, read from disk when it was spooled
+    pub fn read(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match &self.content {
+            UploadContent::Memory(data) => Ok(Cow::Borrowed(data)),
+            UploadContent::Spooled(file) => std::fs::read(file.path()).map(Cow::Owned),
+        }
+    }
+
+    /// A handle reading the file's content that does not keep a spool file
+    /// alive, for holders that may outlive the request
+    pub fn reader(&self) -> UploadReader {
+        match &self.content {
+            UploadContent::Memory(data) => UploadReader::Memory(data.clone()),
+            UploadContent::Spooled(file) => UploadReader::Spooled(Arc::downgrade(file)),
+        }
+    }
+}
+
+/// Reads an uploaded file's content for as long as its request keeps it
+#[derive(Debug, Clone)]
+pub enum UploadReader {
+    Memory(Vec<u8>),
+    Spooled(Weak<SpooledFile>),
+}
+
+impl UploadReader {
+    pub fn read(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            UploadReader::Memory(data) => Ok(Cow::Borrowed(data)),
+            UploadReader::Spooled(file) => {
+                let file = file.upgrade().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "upload was removed after its request finished",
+                    )
+                })?;
+                std::fs::read(file.path()).map(Cow::Owned)
+            }
+        }
+    }
+}
+
+/// Receives one file's chunks, in memory until it grows past the spool
+/// threshold and in a spool file after that
+struct UploadSink<'a> {
+    limits: &'a UploadLimits,
+    hasher: Sha256,
+    size: usize,
+    memory: Vec<u8>,
+    spooled: Option<(tokio::fs::File, Arc<SpooledFile>)>,
+}
+
+impl<'a> UploadSink<'a> {
+    fn new(limits: &'a UploadLimits) -> Self {
+        Self {
+            limits,
+            hasher: Sha256::new(),
+            size: 0,
+            memory: Vec::new(),
+            spooled: None,
+        }
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.hasher.update(chunk);
+        self.size += chunk.len();
+        if self.spooled.is_none() && self.size > self.limits.spool_threshold_bytes {
+            tokio::fs::create_dir_all(&self.limits.spool_dir).await?;
+            let path = self.limits.spool_dir.join(format!(
+                "{}{}",
+                SPOOL_FILE_PREFIX,
+                uuid::Uuid::new_v4()
+            ));
+            // Owned before writing, so a failed write still removes the file
+            let spooled = Arc::new(SpooledFile { path });
+            let mut file = tokio::fs::File::create_new(spooled.path()).await?;
+            debug!("Spooling upload to {}", spooled.path().display());
+            file.write_all(&std::mem::take(&mut self.memory)).await?;
+            self.spooled = Some((file, spooled));
+        }
+        match &mut self.spooled {
+            Some((file, _)) => file.write_all(chunk).await,
+            None => {
+                self.memory.extend_from_slice(chunk);
+                Ok(())
+            }
+        }
+    }
+
+    async fn finish(self) -> std::io::Result<(usize, String, UploadContent)> {
+        let content = match self.spooled {
+            Some((mut file, spooled)) => {
+                file.flush().await?;
+                UploadContent::Spooled(spooled)
+            }
+            None => UploadContent::Memory(self.memory),
+        };
+        Ok((self.size, hex::encode(self.hasher.finalize()), content))
+    }
+}
+
+fn multipart_status(e: &multer::Error) -> StatusCode {
+    match e {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            StatusCode::PAYLOAD_TOO_LARGE
+        }
+        _ => StatusCode::BAD_REQUEST,
+    }
 }
 
 /// Parse form data based on content type.
 /// Supports application/x-www-form-urlencoded and multipart/form-data.
-/// Returns 413 status code if any file exceeds the size limit.
+///
+/// Multipart bodies are read part by part as they arrive. Files larger
+/// than the spool threshold are written to temporary files, which are
+/// deleted when the last [`UploadedFile`] referring to them is dropped.
+/// Returns 413 status code if a file or the whole body exceeds its limit.
 pub async fn parse_form_data(
     content_type: Option<&str>,
     body: Body,
-    max_upload_size: usize,
+    limits: &UploadLimits,
 ) -> Result<(HashMap<String, String>, Vec<UploadedFile>), StatusCode> {
     if let Some(ct) = content_type {
         if ct.starts_with("application/x-www-form-urlencoded") {
             // Convert body to bytes (bounded by the upload limit) and parse as
             // URL-encoded form data
-            match axum::body::to_bytes(body, max_upload_size).await {
+            match axum::body::to_bytes(body, limits.max_file_bytes).await {
                 Ok(bytes) => {
                     let body_str =
                         String::from_utf8(bytes.to_vec()).map_err(|_| StatusCode::BAD_REQUEST)?;
@@ -53,38 +381,26 @@ pub async fn parse_form_data(
                 std::io::Error::other(e)
             });
 
-            let mut multipart = Multipart::new(stream, boundary);
+            let constraints = Constraints::new().size_limit(
+                SizeLimit::new()
+                    .whole_stream(limits.max_request_bytes as u64)
+                    .per_field(limits.max_file_bytes as u64),
+            );
+            let mut multipart = Multipart::with_constraints(stream, boundary, constraints);
             let mut fields = HashMap::new();
             let mut files = Vec::new();
 
             // Filename validation regex: only alphanumeric, underscore, hyphen, and dot
             let filename_regex = Regex::new(r"^[a-zA-Z0-9_\-\.]+$").unwrap();
 
-            while let Some(field) = multipart.next_field().await.map_err(|e| {
+            while let Some(mut field) = multipart.next_field().await.map_err(|e| {
                 error!("Failed to read multipart field: {}", e);
-                StatusCode::BAD_REQUEST
+                multipart_status(&e)
             })? {
                 let field_name = field.name().unwrap_or("").to_string();
                 let filename = field.file_name().map(|s| s.to_string());
                 let content_type = field.content_type().map(|m| m.to_string());
 
-                // Read field data
-                let data = field.bytes().await.map_err(|e| {
-                    error!("Failed to read field data: {}", e);
-                    StatusCode::BAD_REQUEST
-                })?;
-
-                let size = data.len();
-
-                // Check size limit
-                if size > max_upload_size {
-                    error!(
-                        "File upload exceeds size limit: {} bytes (max: {} bytes)",
-                        size, max_upload_size
-                    );
-                    return Err(StatusCode::PAYLOAD_TOO_LARGE);
-                }
-
                 // If it's a file upload (has filename)
                 if let Some(ref fname) = filename {
                     // Validate filename: check for path traversal and invalid characters
@@ -98,15 +414,35 @@ pub async fn parse_form_data(
                         return Err(StatusCode::BAD_REQUEST);
                     }
 
+                    let mut sink = UploadSink::new(limits);
+                    while let Some(chunk) = field.chunk().await.map_err(|e| {
+                        error!("Failed to read file data: {}", e);
+                        multipart_status(&e)
+                    })? {
+                        sink.write(&chunk).await.map_err(|e| {
+                            error!("Failed to spool file upload: {}", e);
+                            StatusCode::INTERNAL_SERVER_ERROR
+                        })?;
+                    }
+                    let (size, sha256, content) = sink.finish().await.map_err(|e| {
+                        error!("Failed to spool file upload: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?;
+
                     files.push(UploadedFile {
-                        field_name: field_name.clone(),
+                        field_name,
                         filename,
                         content_type,
-                        data: data.to_vec(),
                         size,
+                        sha256,
+                        content,
                     });
                 } else {
                     // Regular form field (text)
+                    let data = field.bytes().await.map_err(|e| {
+                        error!("Failed to read field data: {}", e);
+                        multipart_status(&e)
+                    })?;
                     if let Ok(value) = String::from_utf8(data.to_vec()) {
                         fields.insert(field_name, value);
                     }
@@ -122,21 +458,100 @@ pub async fn parse_form_data(
     }
 }
 
+/// Hex-encoded SHA-256 identifying a parsed form submission by its fields
+/// and the names and content hashes of its files
+pub fn form_digest(fields: &HashMap<String, String>, files: &[UploadedFile]) -> String {
+    let mut hasher = Sha256::new();
+    let mut names: Vec<&String> = fields.keys().collect();
+    names.sort();
+    for name in names {
+        for part in [name.as_str(), fields[name].as_str()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+    }
+    for file in files {
+        for part in [
+            file.field_name.as_str(),
+            file.filename.as_deref().unwrap_or_default(),
+            file.sha256.as_str(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Remove spooled uploads a crashed server left behind in `dir`
+pub fn remove_stale_spool_files(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_spool_file = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(SPOOL_FILE_PREFIX));
+        let is_stale = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_SPOOL_AGE);
+        if is_spool_file && is_stale {
+            match std::fs::remove_file(entry.path()) {
+                Ok(()) => debug!("Removed stale spooled upload {}", entry.path().display()),
+                Err(e) => warn!(
+                    "Failed to remove stale spooled upload {}: {}",
+                    entry.path().display(),
+                    e
+                ),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use axum::body::Body;
 
+    fn test_limits(spool_dir: &Path) -> UploadLimits {
+        UploadLimits {
+            max_file_bytes: 10 * 1024 * 1024,
+            max_request_bytes: 20 * 1024 * 1024,
+            spool_threshold_bytes: 16,
+            spool_dir: spool_dir.to_path_buf(),
+        }
+    }
+
+    fn multipart_body(parts: &[(&str, Option<&str>, &[u8])]) -> Body {
+        let mut body = Vec::new();
+        for (name, filename, data) in parts {
+            body.extend_from_slice(b"--abc\r\n");
+            let disposition = match filename {
+                Some(filename) => format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+                    name, filename
+                ),
+                None => format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name),
+            };
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--abc--\r\n");
+        Body::from(body)
+    }
+
     #[tokio::test]
     async fn test_parse_form_data_urlencoded() {
         let body = Body::from("a=1&b=hello+world");
-        let result = parse_form_data(
-            Some("application/x-www-form-urlencoded"),
-            body,
-            10 * 1024 * 1024,
-        )
-        .await
-        .unwrap();
+        let limits = test_limits(&std::env::temp_dir());
+        let result = parse_form_data(Some("application/x-www-form-urlencoded"), body, &limits)
+            .await
+            .unwrap();
         assert_eq!(result.0.get("a"), Some(&"1".to_string()));
         assert_eq!(result.0.get("b"), Some(&"hello world".to_string()));
         assert!(result.1.is_empty());
@@ -147,13 +562,135 @@ mod tests {
         let body = Body::from("ignored");
         // Note: This is a simplified test - real multipart requires proper formatting
         // In practice, multipart parsing will fail on invalid data
-        let result = parse_form_data(
-            Some("multipart/form-data; boundary=abc"),
-            body,
-            10 * 1024 * 1024,
-        )
-        .await;
+        let limits = test_limits(&std::env::temp_dir());
+        let result =
+            parse_form_data(Some("multipart/form-data; boundary=abc"), body, &limits).await;
         // Expect error on malformed multipart data
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_parse_form_data_spools_large_files() {
+        let spool_dir = std::env::temp_dir().join(format!("upload-test-{}", uuid::Uuid::new_v4()));
+        let limits = test_limits(&spool_dir);
+        let body = multipart_body(&[
+            ("title", None, b"Report"),
+            ("small", Some("small.txt"), b"tiny"),
+            ("large", Some("large.bin"), &[7u8; 100]),
+        ]);
+        let (fields, files) =
+            parse_form_data(Some("multipart/form-data; boundary=abc"), body, &limits)
+                .await
+                .unwrap();
+
+        assert_eq!(fields.get("title").map(String::as_str), Some("Report"));
+        assert_eq!(files.len(), 2);
+        assert!(files[0].path().is_none());
+        assert_eq!(files[0].read().unwrap().as_ref(), b"tiny");
+        assert_eq!(files[0].sha256, hex::encode(Sha256::digest(b"tiny")));
+
+        let path = files[1].path().unwrap().to_path_buf();
+        assert!(path.starts_with(&spool_dir));
+        assert_eq!(files[1].size, 100);
+        assert_eq!(files[1].read().unwrap().as_ref(), &[7u8; 100]);
+        assert_eq!(files[1].sha256, hex::encode(Sha256::digest([7u8; 100])));
+
+        let copy = files[1].clone();
+        drop(files);
+        assert!(path.exists());
+        drop(copy);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir(&spool_dir);
+    }
+
+    #[tokio::test]
+    async fn test_parse_form_data_limits() {
+        let limits = UploadLimits {
+            max_file_bytes: 50,
+            max_request_bytes: 120,
+            ..test_limits(&std::env::temp_dir())
+        };
+        let too_large_file = multipart_body(&[("file", Some("a.bin"), &[0u8; 60])]);
+        assert_eq!(
+            parse_form_data(
+                Some("multipart/form-data; boundary=abc"),
+                too_large_file,
+                &limits
+            )
+            .await
+            .unwrap_err(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let too_large_body = multipart_body(&[
+            ("a", Some("a.bin"), &[0u8; 40]),
+            ("b", Some("b.bin"), &[0u8; 40]),
+            ("c", Some("c.bin"), &[0u8; 40]),
+        ]);
+        assert_eq!(
+            parse_form_data(
+                Some("multipart/form-data; boundary=abc"),
+                too_large_body,
+                &limits
+            )
+            .await
+            .unwrap_err(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_route_uploads() {
+        let limits = test_limits(&std::env::temp_dir());
+        let body = multipart_body(&[
+            ("doc", Some("a.bin"), b"one"),
+            ("doc", Some("b.bin"), b"two"),
+        ]);
+        let (_, files) = parse_form_data(Some("multipart/form-data; boundary=abc"), body, &limits)
+            .await
+            .unwrap();
+
+        assert!(RouteUploads::default().check(&files).is_ok());
+        let rules = RouteUploads {
+            max_files: Some(1),
+            ..RouteUploads::default()
+        };
+        assert_eq!(
+            rules.check(&files),
+            Err(UploadViolation::TooManyFiles { max_files: 1 })
+        );
+
+        let rules = RouteUploads {
+            allowed_types: vec!["image/*".to_string(), "application/pdf".to_string()],
+            ..RouteUploads::default()
+        };
+        assert_eq!(
+            rules.check(&files),
+            Err(UploadViolation::TypeNotAllowed {
+                field: "doc".to_string(),
+                content_type: "application/octet-stream".to_string(),
+            })
+        );
+        let rules = RouteUploads {
+            allowed_types: vec!["Application/*".to_string()],
+            ..RouteUploads::default()
+        };
+        assert!(rules.check(&files).is_ok());
+
+        let rules = RouteUploads {
+            max_file_size: Some(2),
+            ..RouteUploads::default()
+        };
+        let body = multipart_body(&[("doc", Some("a.bin"), b"one")]);
+        assert_eq!(
+            parse_form_data(
+                Some("multipart/form-data; boundary=abc"),
+                body,
+                &limits.for_route(&rules)
+            )
+            .await
+            .unwrap_err(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
 }
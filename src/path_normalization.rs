@@ -0,0 +1,189 @@
+//! Normalization of request paths before they are matched against script
+//! routes (`[server] path_normalization`).
+//!
+//! A normalized path has:
+//!
+//! - percent-encoded unreserved characters (letters, digits, `-`, `.`, `_`,
+//!   `~`) decoded and the remaining escapes in uppercase, so `/caf%65` and
+//!   `/cafe` are the same path
+//! - runs of slashes collapsed into one: `/api//users` is `/api/users`
+//! - no trailing slash, except for the root path: `/api/users/` is
+//!   `/api/users`
+//!
+//! The policy decides what happens to requests whose path is not normalized:
+//! `strict` (default) routes them as they are, `redirect` answers with a
+//! 308 redirect to the normalized path, and `rewrite` routes them as if the
+//! normalized path had been requested.
+
+use std::sync::{OnceLock, RwLock};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Uri, header, uri::PathAndQuery};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+/// What to do with requests whose path is not normalized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathNormalization {
+    /// Match the path as it was requested
+    #[default]
+    Strict,
+    /// Redirect to the normalized path (HTTP 308, keeping the method)
+    Redirect,
+    /// Match the normalized path
+    Rewrite,
+}
+
+static POLICY: OnceLock<RwLock<PathNormalization>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<PathNormalization> {
+    POLICY.get_or_init(Default::default)
+}
+
+/// Apply the normalization policy. Called once at server startup.
+pub fn configure(policy: PathNormalization) {
+    match settings().write() {
+        Ok(mut guard) => *guard = policy,
+        Err(poisoned) => *poisoned.into_inner() = policy,
+    }
+}
+
+fn current_policy() -> PathNormalization {
+    match settings().read() {
+        Ok(guard) => *guard,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// The normalized form of `path`
+pub fn normalize(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut normalized = String::with_capacity(path.len() + 1);
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte == b'%'
+            && let (Some(high), Some(low)) = (
+                bytes.get(i + 1).copied().and_then(hex_value),
+                bytes.get(i + 2).copied().and_then(hex_value),
+            )
+        {
+            let decoded = high * 16 + low;
+            if is_unreserved(decoded) {
+                normalized.push(decoded as char);
+            } else {
+                normalized.push_str(&format!("%{:02X}", decoded));
+            }
+            i += 3;
+            continue;
+        }
+        if byte == b'/' && normalized.ends_with('/') {
+            i += 1;
+            continue;
+        }
+        // Paths are ASCII once parsed into a URI; push the character at i
+        let ch = path[i..].chars().next().unwrap_or_default();
+        normalized.push(ch);
+        i += ch.len_utf8();
+    }
+    if !normalized.starts_with('/') {
+        normalized.insert(0, '/');
+    }
+    if normalized.len() > 1 && normalized.ends_with('/') {
+        normalized.pop();
+    }
+    normalized
+}
+
+/// The pattern requests are matched against for a route registered as
+/// `pattern`: normalized unless the policy is strict, so a route registered
+/// with a trailing slash stays reachable
+pub fn route_pattern(pattern: &str) -> String {
+    match current_policy() {
+        PathNormalization::Strict => pattern.to_string(),
+        PathNormalization::Redirect | PathNormalization::Rewrite => normalize(pattern),
+    }
+}
+
+/// `uri` with its path replaced by `path`, keeping the query string
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Apply the configured policy to a request about to be routed: the request
+/// to route, or the redirect to answer it with
+pub fn apply(mut req: Request<Body>) -> Result<Request<Body>, Response> {
+    let policy = current_policy();
+    if policy == PathNormalization::Strict {
+        return Ok(req);
+    }
+    let normalized = normalize(req.uri().path());
+    if normalized == req.uri().path() {
+        return Ok(req);
+    }
+    if policy == PathNormalization::Redirect {
+        let location = match req.uri().query() {
+            Some(query) => format!("{}?{}", normalized, query),
+            None => normalized,
+        };
+        return Err((
+            StatusCode::PERMANENT_REDIRECT,
+            [(header::LOCATION, location)],
+        )
+            .into_response());
+    }
+    if let Some(uri) = with_path(req.uri(), &normalized) {
+        *req.uri_mut() = uri;
+    }
+    Ok(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("/api/users"), "/api/users");
+        assert_eq!(normalize("/api/users/"), "/api/users");
+        assert_eq!(normalize("//api///users//"), "/api/users");
+        assert_eq!(normalize("/"), "/");
+        assert_eq!(normalize("//"), "/");
+        assert_eq!(normalize(""), "/");
+        assert_eq!(normalize("/caf%65/%7Euser"), "/cafe/~user");
+        assert_eq!(normalize("/a%2fb/%3f"), "/a%2Fb/%3F");
+        assert_eq!(normalize("/100%"), "/100%");
+        assert_eq!(normalize("/%2F%2F"), "/%2F%2F");
+    }
+
+    #[test]
+    fn test_normalize_is_idempotent() {
+        for path in ["/api//users/", "/caf%65/", "/a%2fb//", "/%25%32%46"] {
+            let once = normalize(path);
+            assert_eq!(normalize(&once), once);
+        }
+    }
+
+    #[test]
+    fn test_with_path_keeps_query() {
+        let uri: Uri = "/api//users/?page=2".parse().unwrap();
+        let rewritten = with_path(&uri, &normalize(uri.path())).unwrap();
+        assert_eq!(rewritten.path(), "/api/users");
+        assert_eq!(rewritten.query(), Some("page=2"));
+    }
+}
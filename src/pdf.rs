@@ -0,0 +1,1307 @@
+//! HTML to PDF rendering for `pdf.fromHtml`.
+//!
+//! Renders the subset of HTML that invoices and reports are made of:
+//! headings, paragraphs, line breaks, bold, italic and code text, lists,
+//! block quotes, preformatted text, horizontal rules and tables. `align`
+//! attributes and `text-align` styles are honored; other styling, images
+//! and scripts are ignored.
+//!
+//! Text is set in the standard PDF Helvetica and Courier fonts, so nothing
+//! is embedded. Characters outside Windows-1252 are rendered as `?`.
+//!
+//! Each rendering runs within the `[javascript.pdf]` budget: a maximum
+//! input size, page count and rendering time.
+
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::Deserialize;
+
+use crate::config::PdfConfig;
+
+static LIMITS: OnceLock<RwLock<PdfConfig>> = OnceLock::new();
+
+fn limits() -> &'static RwLock<PdfConfig> {
+    LIMITS.get_or_init(Default::default)
+}
+
+/// Apply the PDF budget. Called once at server startup.
+pub fn configure(config: &PdfConfig) {
+    match limits().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_limits() -> PdfConfig {
+    match limits().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Page sizes in points (width, height), portrait
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    fn points(self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.0, 842.0),
+            Self::Letter => (612.0, 792.0),
+            Self::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+/// Options of `pdf.fromHtml`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    pub landscape: bool,
+    /// Page margin in points
+    pub margin: f32,
+    /// Body text size in points; headings scale from it
+    pub font_size: f32,
+    /// Document title; defaults to the HTML `<title>`
+    pub title: Option<String>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::A4,
+            landscape: false,
+            margin: 50.0,
+            font_size: 11.0,
+            title: None,
+        }
+    }
+}
+
+/// Render `html` to a PDF document within the configured budget
+pub fn from_html(html: &str, options: &PdfOptions) -> Result<Vec<u8>, String> {
+    render(html, options, &current_limits())
+}
+
+fn render(html: &str, options: &PdfOptions, limits: &PdfConfig) -> Result<Vec<u8>, String> {
+    if html.len() > limits.max_html_bytes {
+        return Err(format!(
+            "HTML is larger than the {} byte limit",
+            limits.max_html_bytes
+        ));
+    }
+    if !(4.0..=72.0).contains(&options.font_size) {
+        return Err("fontSize must be between 4 and 72".to_string());
+    }
+    let (width, height) = match options.page_size.points() {
+        (width, height) if options.landscape => (height, width),
+        size => size,
+    };
+    if !(0.0..width.min(height) / 3.0).contains(&options.margin) {
+        return Err("margin must be at least 0 and less than a third of the page".to_string());
+    }
+
+    let document = parse(html, options.font_size);
+    let mut layout = Layout {
+        width,
+        height,
+        margin: options.margin,
+        pages: vec![Vec::new()],
+        y: height - options.margin,
+        max_pages: limits.max_pages,
+        deadline: Instant::now() + Duration::from_millis(limits.timeout_ms),
+        timeout_ms: limits.timeout_ms,
+    };
+    for block in &document.blocks {
+        layout.block(block)?;
+    }
+    let title = options.title.clone().or(document.title);
+    Ok(write_pdf(&layout.pages, width, height, title.as_deref()))
+}
+
+// ----------------------------------------------------------------------------
+// Fonts
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+    MonoBold,
+}
+
+impl Font {
+    const ALL: [Font; 6] = [
+        Font::Regular,
+        Font::Bold,
+        Font::Italic,
+        Font::BoldItalic,
+        Font::Mono,
+        Font::MonoBold,
+    ];
+
+    fn select(bold: bool, italic: bool, mono: bool) -> Self {
+        match (mono, bold, italic) {
+            (true, true, _) => Font::MonoBold,
+            (true, false, _) => Font::Mono,
+            (false, true, true) => Font::BoldItalic,
+            (false, true, false) => Font::Bold,
+            (false, false, true) => Font::Italic,
+            (false, false, false) => Font::Regular,
+        }
+    }
+
+    fn bolder(self) -> Self {
+        match self {
+            Font::Regular => Font::Bold,
+            Font::Italic => Font::BoldItalic,
+            Font::Mono => Font::MonoBold,
+            bold => bold,
+        }
+    }
+
+    fn resource_name(self) -> Name<'static> {
+        Name(match self {
+            Font::Regular => b"F1",
+            Font::Bold => b"F2",
+            Font::Italic => b"F3",
+            Font::BoldItalic => b"F4",
+            Font::Mono => b"F5",
+            Font::MonoBold => b"F6",
+        })
+    }
+
+    fn base_font(self) -> Name<'static> {
+        Name(match self {
+            Font::Regular => b"Helvetica",
+            Font::Bold => b"Helvetica-Bold",
+            Font::Italic => b"Helvetica-Oblique",
+            Font::BoldItalic => b"Helvetica-BoldOblique",
+            Font::Mono => b"Courier",
+            Font::MonoBold => b"Courier-Bold",
+        })
+    }
+
+    /// Advance width of a Windows-1252 byte, in points at `size`
+    fn width(self, byte: u8, size: f32) -> f32 {
+        let units = match self {
+            Font::Mono | Font::MonoBold => 600,
+            Font::Regular | Font::Italic => match byte {
+                32..=126 => HELVETICA_WIDTHS[usize::from(byte - 32)],
+                0x95 => 350,
+                _ => 556,
+            },
+            Font::Bold | Font::BoldItalic => match byte {
+                32..=126 => HELVETICA_BOLD_WIDTHS[usize::from(byte - 32)],
+                0x95 => 350,
+                _ => 611,
+            },
+        };
+        f32::from(units) * size / 1000.0
+    }
+}
+
+/// Helvetica advance widths of ASCII 32-126 in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Helvetica-Bold advance widths of ASCII 32-126 in 1/1000 em
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Windows-1252 byte for `c`, or `?` when it has none
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        '„' => 0x84,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        _ => b'?',
+    }
+}
+
+// ----------------------------------------------------------------------------
+// HTML to blocks
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Text in one font, as Windows-1252 bytes. `\n` forces a line break.
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    text: Vec<u8>,
+    font: Font,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Paragraph {
+    runs: Vec<Run>,
+    size: f32,
+    align: Align,
+    indent: f32,
+    space_after: f32,
+    preformatted: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Cell {
+    runs: Vec<Run>,
+    align: Align,
+    header: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Text(Paragraph),
+    Rule,
+    Table { rows: Vec<Vec<Cell>>, size: f32 },
+}
+
+struct Document {
+    blocks: Vec<Block>,
+    title: Option<String>,
+}
+
+/// Elements that start a new block of text
+const BLOCK_ELEMENTS: [&str; 14] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "main",
+    "nav",
+    "address",
+    "figure",
+    "figcaption",
+    "dl",
+    "dt",
+    "dd",
+];
+
+/// Elements whose content is not rendered
+const HIDDEN_ELEMENTS: [&str; 5] = ["head", "script", "style", "template", "noscript"];
+
+/// Walks the HTML and collects blocks of styled text
+struct Builder {
+    base_size: f32,
+    blocks: Vec<Block>,
+    runs: Vec<Run>,
+    title: Option<String>,
+    in_title: bool,
+    hidden: usize,
+    bold: usize,
+    italic: usize,
+    mono: usize,
+    pre: usize,
+    quote: usize,
+    heading: Option<u8>,
+    /// Alignment set by an open element, innermost last
+    aligns: Vec<(String, Align)>,
+    /// Open lists, innermost last: the next item number, or `None` when
+    /// unordered
+    lists: Vec<Option<u32>>,
+    table_depth: usize,
+    rows: Vec<Vec<Cell>>,
+    cell: Option<Cell>,
+}
+
+fn parse(html: &str, base_size: f32) -> Document {
+    let mut builder = Builder {
+        base_size,
+        blocks: Vec::new(),
+        runs: Vec::new(),
+        title: None,
+        in_title: false,
+        hidden: 0,
+        bold: 0,
+        italic: 0,
+        mono: 0,
+        pre: 0,
+        quote: 0,
+        heading: None,
+        aligns: Vec::new(),
+        lists: Vec::new(),
+        table_depth: 0,
+        rows: Vec::new(),
+        cell: None,
+    };
+    for token in Tokenizer::new(html) {
+        match token {
+            Token::Text(text) => builder.text(&text),
+            Token::Open { name, attrs } => builder.open(&name, &attrs),
+            Token::Close(name) => builder.close(&name),
+        }
+    }
+    builder.finish_table();
+    builder.flush();
+    Document {
+        blocks: builder.blocks,
+        title: builder.title,
+    }
+}
+
+impl Builder {
+    fn font(&self) -> Font {
+        Font::select(
+            self.bold > 0 || self.heading.is_some(),
+            self.italic > 0,
+            self.mono > 0,
+        )
+    }
+
+    fn align(&self) -> Align {
+        self.aligns.last().map_or(Align::Left, |(_, align)| *align)
+    }
+
+    fn target(&mut self) -> &mut Vec<Run> {
+        match &mut self.cell {
+            Some(cell) => &mut cell.runs,
+            None => &mut self.runs,
+        }
+    }
+
+    fn push_text(&mut self, text: &[u8]) {
+        let font = self.font();
+        let runs = self.target();
+        match runs.last_mut() {
+            Some(run) if run.font == font => run.text.extend_from_slice(text),
+            _ => runs.push(Run {
+                text: text.to_vec(),
+                font,
+            }),
+        }
+    }
+
+    fn text(&mut self, raw: &str) {
+        let decoded = html_escape::decode_html_entities(raw);
+        if self.in_title {
+            self.title
+                .get_or_insert_with(String::new)
+                .push_str(decoded.trim());
+            return;
+        }
+        if self.hidden > 0 {
+            return;
+        }
+
+        let mut bytes = Vec::with_capacity(decoded.len());
+        if self.pre > 0 {
+            for c in decoded.replace("\r\n", "\n").chars() {
+                match c {
+                    '\n' => bytes.push(b'\n'),
+                    '\t' => bytes.extend_from_slice(b"    "),
+                    c => bytes.push(win_ansi(c)),
+                }
+            }
+        } else {
+            let mut at_space = self
+                .target()
+                .last()
+                .and_then(|run| run.text.last())
+                .is_none_or(|last| *last == b' ' || *last == b'\n');
+            for c in decoded.chars() {
+                if c.is_whitespace() && c != '\u{a0}' {
+                    if !at_space {
+                        bytes.push(b' ');
+                        at_space = true;
+                    }
+                } else {
+                    bytes.push(win_ansi(c));
+                    at_space = false;
+                }
+            }
+        }
+        if !bytes.is_empty() {
+            self.push_text(&bytes);
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &[(String, String)]) {
+        if HIDDEN_ELEMENTS.contains(&name) {
+            self.hidden += 1;
+            return;
+        }
+        if name == "title" {
+            self.in_title = true;
+            return;
+        }
+        let is_heading = heading_level(name).is_some();
+        if BLOCK_ELEMENTS.contains(&name) || is_heading {
+            self.flush();
+            if let Some(align) = attr_align(attrs) {
+                self.aligns.push((name.to_string(), align));
+            }
+        }
+        match name {
+            _ if is_heading => self.heading = heading_level(name),
+            "br" => self.push_text(b"\n"),
+            "hr" => {
+                self.flush();
+                self.blocks.push(Block::Rule);
+            }
+            "ul" | "ol" => {
+                self.flush();
+                let start = attrs
+                    .iter()
+                    .find(|(key, _)| key == "start")
+                    .and_then(|(_, value)| value.trim().parse().ok())
+                    .unwrap_or(1);
+                self.lists.push((name == "ol").then_some(start));
+            }
+            "li" => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1).into_bytes()
+                    }
+                    _ => vec![0x95, b' '],
+                };
+                self.push_text(&marker);
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote += 1;
+            }
+            "pre" => {
+                self.flush();
+                self.pre += 1;
+                self.mono += 1;
+            }
+            "b" | "strong" => self.bold += 1,
+            "i" | "em" | "cite" | "var" => self.italic += 1,
+            "code" | "tt" | "kbd" | "samp" => self.mono += 1,
+            "table" => {
+                if self.table_depth == 0 {
+                    self.flush();
+                }
+                self.table_depth += 1;
+            }
+            "tr" if self.table_depth == 1 => {
+                self.finish_cell();
+                self.rows.push(Vec::new());
+            }
+            "td" | "th" if self.table_depth == 1 => {
+                self.finish_cell();
+                if self.rows.is_empty() {
+                    self.rows.push(Vec::new());
+                }
+                self.cell = Some(Cell {
+                    runs: Vec::new(),
+                    align: attr_align(attrs).unwrap_or_default(),
+                    header: name == "th",
+                });
+            }
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        if HIDDEN_ELEMENTS.contains(&name) {
+            self.hidden = self.hidden.saturating_sub(1);
+            return;
+        }
+        if name == "title" {
+            self.in_title = false;
+            return;
+        }
+        let is_heading = heading_level(name).is_some();
+        if BLOCK_ELEMENTS.contains(&name) || is_heading || name == "li" {
+            self.flush();
+            if self
+                .aligns
+                .last()
+                .is_some_and(|(element, _)| element == name)
+            {
+                self.aligns.pop();
+            }
+        }
+        match name {
+            _ if is_heading => self.heading = None,
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            "blockquote" => {
+                self.flush();
+                self.quote = self.quote.saturating_sub(1);
+            }
+            "pre" => {
+                self.flush();
+                self.pre = self.pre.saturating_sub(1);
+                self.mono = self.mono.saturating_sub(1);
+            }
+            "b" | "strong" => self.bold = self.bold.saturating_sub(1),
+            "i" | "em" | "cite" | "var" => self.italic = self.italic.saturating_sub(1),
+            "code" | "tt" | "kbd" | "samp" => self.mono = self.mono.saturating_sub(1),
+            "td" | "th" if self.table_depth == 1 => self.finish_cell(),
+            "table" => {
+                self.table_depth = self.table_depth.saturating_sub(1);
+                if self.table_depth == 0 {
+                    self.finish_table();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// End the current paragraph
+    fn flush(&mut self) {
+        if self.cell.is_some() {
+            // Block elements inside a cell only break the line
+            let ends_with_break = self
+                .target()
+                .last()
+                .and_then(|run| run.text.last())
+                .is_none_or(|last| *last == b'\n');
+            if !ends_with_break {
+                self.push_text(b"\n");
+            }
+            return;
+        }
+        let runs = std::mem::take(&mut self.runs);
+        if runs
+            .iter()
+            .all(|run| run.text.iter().all(|byte| byte.is_ascii_whitespace()))
+        {
+            return;
+        }
+        let size = match self.heading {
+            Some(level) => self.base_size * heading_scale(level),
+            None => self.base_size,
+        };
+        let in_list = !self.lists.is_empty();
+        self.blocks.push(Block::Text(Paragraph {
+            runs,
+            size,
+            align: self.align(),
+            indent: 18.0 * self.lists.len() as f32 + 20.0 * self.quote as f32,
+            space_after: if in_list { size * 0.2 } else { size * 0.6 },
+            preformatted: self.pre > 0,
+        }));
+    }
+
+    fn finish_cell(&mut self) {
+        if let Some(mut cell) = self.cell.take() {
+            while cell
+                .runs
+                .last()
+                .is_some_and(|run| run.text.last() == Some(&b'\n'))
+            {
+                if let Some(run) = cell.runs.last_mut() {
+                    run.text.pop();
+                    if run.text.is_empty() {
+                        cell.runs.pop();
+                    }
+                }
+            }
+            if let Some(row) = self.rows.last_mut() {
+                row.push(cell);
+            }
+        }
+    }
+
+    fn finish_table(&mut self) {
+        self.finish_cell();
+        let rows: Vec<Vec<Cell>> = std::mem::take(&mut self.rows)
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .collect();
+        if !rows.is_empty() {
+            self.blocks.push(Block::Table {
+                rows,
+                size: self.base_size,
+            });
+        }
+    }
+}
+
+fn heading_level(name: &str) -> Option<u8> {
+    match name.as_bytes() {
+        [b'h', level @ b'1'..=b'6'] => Some(level - b'0'),
+        _ => None,
+    }
+}
+
+fn heading_scale(level: u8) -> f32 {
+    match level {
+        1 => 2.0,
+        2 => 1.6,
+        3 => 1.3,
+        4 => 1.15,
+        5 => 1.0,
+        _ => 0.9,
+    }
+}
+
+/// Alignment from an `align` attribute or a `text-align` style
+fn attr_align(attrs: &[(String, String)]) -> Option<Align> {
+    let value = attrs.iter().find_map(|(key, value)| match key.as_str() {
+        "align" => Some(value.trim().to_ascii_lowercase()),
+        "style" => value.split(';').find_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            (property.trim().eq_ignore_ascii_case("text-align"))
+                .then(|| value.trim().to_ascii_lowercase())
+        }),
+        _ => None,
+    })?;
+    match value.as_str() {
+        "center" => Some(Align::Center),
+        "right" | "end" => Some(Align::Right),
+        "left" | "start" => Some(Align::Left),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Open {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    Close(String),
+}
+
+/// A forgiving HTML tokenizer: malformed markup is read as text
+struct Tokenizer<'a> {
+    html: &'a str,
+    pos: usize,
+    /// Raw text element whose content is skipped up to its end tag
+    raw_text: Option<&'static str>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Self {
+            html,
+            pos: 0,
+            raw_text: None,
+        }
+    }
+
+    fn tag(&mut self) -> Option<Token> {
+        let rest = &self.html[self.pos..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            self.pos += 4 + comment.find("-->").map_or(comment.len(), |end| end + 3);
+            return None;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            self.pos += rest.find('>').map_or(rest.len(), |end| end + 1);
+            return None;
+        }
+
+        let closing = rest.starts_with("</");
+        let name_start = if closing { 2 } else { 1 };
+        let name_len = rest[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len() - name_start);
+        let name = rest[name_start..name_start + name_len].to_ascii_lowercase();
+
+        // Attributes up to the closing '>', skipping quoted values
+        let bytes = rest.as_bytes();
+        let mut end = name_start + name_len;
+        let mut quote = None;
+        while end < bytes.len() {
+            match (quote, bytes[end]) {
+                (None, b'>') => break,
+                (None, q @ (b'"' | b'\'')) => quote = Some(q),
+                (Some(q), b) if b == q => quote = None,
+                _ => {}
+            }
+            end += 1;
+        }
+        let attr_text = &rest[name_start + name_len..end.min(rest.len())];
+        self.pos += (end + 1).min(rest.len());
+
+        if closing {
+            Some(Token::Close(name))
+        } else {
+            if name == "script" || name == "style" {
+                self.raw_text = Some(if name == "script" { "script" } else { "style" });
+            }
+            Some(Token::Open {
+                name,
+                attrs: parse_attrs(attr_text),
+            })
+        }
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if let Some(element) = self.raw_text.take() {
+            let rest = &self.html[self.pos..];
+            let end = rest
+                .to_ascii_lowercase()
+                .find(&format!("</{}", element))
+                .unwrap_or(rest.len());
+            self.pos += end;
+        }
+        while self.pos < self.html.len() {
+            let rest = &self.html[self.pos..];
+            let is_tag = rest.starts_with('<')
+                && rest
+                    .chars()
+                    .nth(1)
+                    .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!' || c == '?');
+            if is_tag {
+                if let Some(token) = self.tag() {
+                    return Some(token);
+                }
+                continue;
+            }
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let text_len = rest[first..]
+                .find('<')
+                .map_or(rest.len(), |index| index + first);
+            self.pos += text_len;
+            return Some(Token::Text(rest[..text_len].to_string()));
+        }
+        None
+    }
+}
+
+fn parse_attrs(text: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = text.trim_start_matches(['/', ' ']).trim();
+    while !rest.is_empty() {
+        let key_len = rest
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let key = rest[..key_len].to_ascii_lowercase();
+        rest = rest[key_len..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(q).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = html_escape::decode_html_entities(raw).into_owned();
+            rest = remaining;
+        }
+        if !key.is_empty() {
+            attrs.push((key, value));
+        }
+        rest = rest.trim_start_matches(['/', ' ']).trim_start();
+    }
+    attrs
+}
+
+// ----------------------------------------------------------------------------
+// Layout
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Text {
+        x: f32,
+        y: f32,
+        font: Font,
+        size: f32,
+        text: Vec<u8>,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+}
+
+/// A laid out line: pieces of text with their fonts, and the total width
+#[derive(Debug, Default)]
+struct Line {
+    pieces: Vec<(Font, Vec<u8>)>,
+    width: f32,
+}
+
+impl Line {
+    fn push(&mut self, font: Font, text: &[u8], width: f32) {
+        match self.pieces.last_mut() {
+            Some((last, piece)) if *last == font => piece.extend_from_slice(text),
+            _ => self.pieces.push((font, text.to_vec())),
+        }
+        self.width += width;
+    }
+}
+
+fn text_width(font: Font, text: &[u8], size: f32) -> f32 {
+    text.iter().map(|byte| font.width(*byte, size)).sum()
+}
+
+/// Break `runs` into lines no wider than `max_width`. Outside preformatted
+/// text, spaces at line breaks are dropped and overlong words are split.
+fn wrap(runs: &[Run], max_width: f32, size: f32, preformatted: bool) -> Vec<Line> {
+    let mut lines = vec![Line::default()];
+    let mut pending_space: Option<(Font, f32)> = None;
+    for run in runs {
+        let font = run.font;
+        for piece in split_pieces(&run.text) {
+            let Some(line) = lines.last_mut() else {
+                continue;
+            };
+            match piece {
+                b"\n" => {
+                    pending_space = None;
+                    lines.push(Line::default());
+                }
+                b" " if !preformatted => {
+                    if !line.pieces.is_empty() {
+                        pending_space = Some((font, font.width(b' ', size)));
+                    }
+                }
+                word => {
+                    let space_width = pending_space.map_or(0.0, |(_, width)| width);
+                    let width = text_width(font, word, size);
+                    if !line.pieces.is_empty() && line.width + space_width + width > max_width {
+                        lines.push(Line::default());
+                    } else if let Some((space_font, space_width)) = pending_space {
+                        line.push(space_font, b" ", space_width);
+                    }
+                    pending_space = None;
+                    place_word(&mut lines, font, word, size, max_width);
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Add `word` to the last line, splitting it across lines when it does not
+/// fit on an empty one
+fn place_word(lines: &mut Vec<Line>, font: Font, word: &[u8], size: f32, max_width: f32) {
+    let mut start = 0;
+    while start < word.len() {
+        let Some(line) = lines.last_mut() else {
+            return;
+        };
+        let mut end = start;
+        let mut width = 0.0;
+        while end < word.len() {
+            let char_width = font.width(word[end], size);
+            if line.width + width + char_width > max_width
+                && (end > start || !line.pieces.is_empty())
+            {
+                break;
+            }
+            width += char_width;
+            end += 1;
+        }
+        if end == start {
+            lines.push(Line::default());
+            continue;
+        }
+        line.push(font, &word[start..end], width);
+        start = end;
+        if start < word.len() {
+            lines.push(Line::default());
+        }
+    }
+}
+
+/// Split text into words, single spaces and line breaks. Runs of spaces
+/// stay together so preformatted text keeps them.
+fn split_pieces(text: &[u8]) -> Vec<&[u8]> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (index, byte) in text.iter().enumerate() {
+        if *byte == b'\n' || *byte == b' ' {
+            if start < index {
+                pieces.push(&text[start..index]);
+            }
+            pieces.push(&text[index..index + 1]);
+            start = index + 1;
+        }
+    }
+    if start < text.len() {
+        pieces.push(&text[start..]);
+    }
+    pieces
+}
+
+struct Layout {
+    width: f32,
+    height: f32,
+    margin: f32,
+    pages: Vec<Vec<Op>>,
+    /// Top of the next line on the current page
+    y: f32,
+    max_pages: usize,
+    deadline: Instant,
+    timeout_ms: u64,
+}
+
+impl Layout {
+    fn content_width(&self) -> f32 {
+        self.width - 2.0 * self.margin
+    }
+
+    fn check_deadline(&self) -> Result<(), String> {
+        if Instant::now() > self.deadline {
+            return Err(format!(
+                "PDF rendering took longer than the {} ms limit",
+                self.timeout_ms
+            ));
+        }
+        Ok(())
+    }
+
+    /// Start a new page unless `height` still fits on the current one
+    fn ensure_space(&mut self, height: f32) -> Result<(), String> {
+        let page_is_empty = self.pages.last().is_none_or(Vec::is_empty);
+        if self.y - height >= self.margin || page_is_empty {
+            return Ok(());
+        }
+        if self.pages.len() >= self.max_pages {
+            return Err(format!(
+                "PDF would have more than the {} page limit",
+                self.max_pages
+            ));
+        }
+        self.pages.push(Vec::new());
+        self.y = self.height - self.margin;
+        Ok(())
+    }
+
+    fn push(&mut self, op: Op) {
+        if let Some(page) = self.pages.last_mut() {
+            page.push(op);
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Result<(), String> {
+        self.check_deadline()?;
+        match block {
+            Block::Text(paragraph) => self.paragraph(paragraph),
+            Block::Rule => {
+                let size = 12.0;
+                self.ensure_space(size)?;
+                let y = self.y - size / 2.0;
+                self.push(Op::Line {
+                    from: (self.margin, y),
+                    to: (self.width - self.margin, y),
+                });
+                self.y -= size;
+                Ok(())
+            }
+            Block::Table { rows, size } => self.table(rows, *size),
+        }
+    }
+
+    fn paragraph(&mut self, paragraph: &Paragraph) -> Result<(), String> {
+        let size = paragraph.size;
+        let width = (self.content_width() - paragraph.indent).max(size);
+        let line_height = size * 1.3;
+        for line in wrap(&paragraph.runs, width, size, paragraph.preformatted) {
+            self.check_deadline()?;
+            self.ensure_space(line_height)?;
+            let x =
+                self.margin + paragraph.indent + align_offset(paragraph.align, width, line.width);
+            self.line(&line, x, self.y - size, size);
+            self.y -= line_height;
+        }
+        self.y -= paragraph.space_after;
+        Ok(())
+    }
+
+    fn line(&mut self, line: &Line, mut x: f32, baseline: f32, size: f32) {
+        for (font, text) in &line.pieces {
+            let width = text_width(*font, text, size);
+            self.push(Op::Text {
+                x,
+                y: baseline,
+                font: *font,
+                size,
+                text: text.clone(),
+            });
+            x += width;
+        }
+    }
+
+    fn table(&mut self, rows: &[Vec<Cell>], size: f32) -> Result<(), String> {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(1).max(1);
+        let column_width = self.content_width() / columns as f32;
+        let padding = 4.0;
+        let line_height = size * 1.3;
+        let text_width = (column_width - 2.0 * padding).max(size);
+
+        for row in rows {
+            self.check_deadline()?;
+            let cells: Vec<(Vec<Line>, Align)> = row
+                .iter()
+                .map(|cell| {
+                    let runs: Vec<Run> = cell
+                        .runs
+                        .iter()
+                        .map(|run| Run {
+                            text: run.text.clone(),
+                            font: if cell.header {
+                                run.font.bolder()
+                            } else {
+                                run.font
+                            },
+                        })
+                        .collect();
+                    (wrap(&runs, text_width, size, false), cell.align)
+                })
+                .collect();
+            let row_lines = cells
+                .iter()
+                .map(|(lines, _)| lines.len())
+                .max()
+                .unwrap_or(1);
+            let row_height = row_lines as f32 * line_height + 2.0 * padding;
+            self.ensure_space(row_height)?;
+
+            let top = self.y;
+            let bottom = top - row_height;
+            for (index, (lines, align)) in cells.iter().enumerate() {
+                let left = self.margin + index as f32 * column_width;
+                for (number, line) in lines.iter().enumerate() {
+                    let x = left + padding + align_offset(*align, text_width, line.width);
+                    let baseline = top - padding - number as f32 * line_height - size;
+                    self.line(line, x, baseline, size);
+                }
+            }
+            let right = self.margin + columns as f32 * column_width;
+            for y in [top, bottom] {
+                self.push(Op::Line {
+                    from: (self.margin, y),
+                    to: (right, y),
+                });
+            }
+            for column in 0..=columns {
+                let x = self.margin + column as f32 * column_width;
+                self.push(Op::Line {
+                    from: (x, top),
+                    to: (x, bottom),
+                });
+            }
+            self.y = bottom;
+        }
+        self.y -= size * 0.6;
+        Ok(())
+    }
+}
+
+fn align_offset(align: Align, available: f32, used: f32) -> f32 {
+    match align {
+        Align::Left => 0.0,
+        Align::Center => ((available - used) / 2.0).max(0.0),
+        Align::Right => (available - used).max(0.0),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// PDF output
+// ----------------------------------------------------------------------------
+
+fn write_pdf(pages: &[Vec<Op>], width: f32, height: f32, title: Option<&str>) -> Vec<u8> {
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let info_id = Ref::new(3);
+    let font_id = |font: Font| {
+        let index = Font::ALL.iter().position(|f| *f == font).unwrap_or(0);
+        Ref::new(4 + index as i32)
+    };
+    let first_page = 4 + Font::ALL.len() as i32;
+    let page_ids: Vec<Ref> = (0..pages.len())
+        .map(|index| Ref::new(first_page + 2 * index as i32))
+        .collect();
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(pages.len() as i32);
+    for font in Font::ALL {
+        pdf.type1_font(font_id(font))
+            .base_font(font.base_font())
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    for (ops, page_id) in pages.iter().zip(&page_ids) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, width, height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        let mut resources = page.resources();
+        let mut fonts = resources.fonts();
+        for font in Font::ALL {
+            fonts.pair(font.resource_name(), font_id(font));
+        }
+        fonts.finish();
+        resources.finish();
+        page.finish();
+
+        let mut content = Content::new();
+        content.set_line_width(0.5);
+        content.set_stroke_gray(0.5);
+        for op in ops {
+            match op {
+                Op::Text {
+                    x,
+                    y,
+                    font,
+                    size,
+                    text,
+                } => {
+                    content.begin_text();
+                    content.set_font(font.resource_name(), *size);
+                    content.set_text_matrix([1.0, 0.0, 0.0, 1.0, *x, *y]);
+                    content.show(Str(text));
+                    content.end_text();
+                }
+                Op::Line { from, to } => {
+                    content.move_to(from.0, from.1);
+                    content.line_to(to.0, to.1);
+                    content.stroke();
+                }
+            }
+        }
+        pdf.stream(content_id, &content.finish());
+    }
+
+    let mut info = pdf.document_info(info_id);
+    info.producer(TextStr("aiwebengine"));
+    if let Some(title) = title {
+        info.title(TextStr(title));
+    }
+    info.finish();
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    fn page_count(pdf: &[u8]) -> usize {
+        pdf.windows(b"/Type /Page\n".len())
+            .filter(|window| *window == b"/Type /Page\n")
+            .count()
+    }
+
+    #[test]
+    fn test_renders_invoice_html() {
+        let html = r#"<!DOCTYPE html><html><head><title>Invoice 42</title>
+            <style>body { color: red }</style></head>
+            <body><h1>Invoice</h1><p>Thanks, <b>Ada</b> &amp; co.</p>
+            <table><tr><th>Item</th><th align="right">Price</th></tr>
+            <tr><td>Widget</td><td align="right">&euro;9.50</td></tr></table>
+            <ul><li>Due in 30 days</li></ul><script>ignored()</script></body></html>"#;
+        let pdf = from_html(html, &PdfOptions::default()).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(contains(&pdf, b"(Invoice)"));
+        assert!(contains(&pdf, b"(Ada)"));
+        assert!(contains(&pdf, b"(Widget)"));
+        // Non-ASCII text is written as a hex string
+        assert!(contains(&pdf, b"<80392E3530>"));
+        assert!(contains(&pdf, b"(Invoice 42)"));
+        assert!(!contains(&pdf, b"ignored"));
+        assert!(!contains(&pdf, b"color"));
+        assert_eq!(page_count(&pdf), 1);
+    }
+
+    #[test]
+    fn test_wrap_fits_width() {
+        let runs = vec![Run {
+            text: b"the quick brown fox jumps over the lazy dog ".repeat(10),
+            font: Font::Regular,
+        }];
+        let lines = wrap(&runs, 200.0, 11.0, false);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.width <= 200.0));
+
+        let long_word = vec![Run {
+            text: vec![b'W'; 100],
+            font: Font::Bold,
+        }];
+        let lines = wrap(&long_word, 100.0, 11.0, false);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.width <= 100.0));
+    }
+
+    #[test]
+    fn test_budget_limits() {
+        let limits = PdfConfig {
+            max_html_bytes: 10_000,
+            max_pages: 2,
+            timeout_ms: 5000,
+        };
+        let long = "<p>Line</p>".repeat(200);
+        let error = render(&long, &PdfOptions::default(), &limits).unwrap_err();
+        assert_eq!(error, "PDF would have more than the 2 page limit");
+
+        let short = "<p>Line</p>".repeat(60);
+        let pdf = render(&short, &PdfOptions::default(), &limits).unwrap();
+        assert_eq!(page_count(&pdf), 2);
+
+        let too_big = "x".repeat(10_001);
+        assert!(
+            render(&too_big, &PdfOptions::default(), &limits)
+                .unwrap_err()
+                .contains("10000 byte limit")
+        );
+
+        let options = PdfOptions {
+            margin: 400.0,
+            ..Default::default()
+        };
+        assert!(render("<p>x</p>", &options, &limits).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Managed prompt templates (`prompts.*`).
+//!
+//! Scripts save prompt templates under a name with `prompts.save`; every
+//! save of a changed template adds a version in the `prompt_templates` table.
+//! `prompts.render(name, vars)` fills in the latest version (or a named one)
+//! with Handlebars, without HTML escaping since prompts are plain text.
+//!
+//! The latest version of every saved template is also offered as an MCP
+//! prompt, next to the prompts scripts register with `mcpRegistry`, so the
+//! HTTP and MCP surfaces use the same templates. A prompt registered by a
+//! script takes precedence over a saved template of the same name.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use handlebars::Handlebars;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::mcp::{McpPrompt, PromptArgument};
+use crate::repository::{self, Repository as _};
+
+/// Longest template accepted by `prompts.save`
+const MAX_TEMPLATE_BYTES: usize = 64 * 1024;
+
+/// Longest prompt name
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Most variables one template may declare
+const MAX_VARIABLES: usize = 50;
+
+/// Name a template is registered under in its own Handlebars registry
+const TEMPLATE_NAME: &str = "prompt";
+
+/// A variable a template uses; rendering fails when a required one is
+/// missing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PromptVariable {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// What `prompts.save` stores
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct PromptDefinition {
+    pub template: String,
+    pub description: String,
+    pub variables: Vec<PromptVariable>,
+}
+
+/// One version of a saved prompt template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: i32,
+    pub description: String,
+    pub template: String,
+    pub variables: Vec<PromptVariable>,
+    /// Script that saved this version
+    pub script_uri: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PromptTemplate {
+    /// Fill in the template with `vars`, a JSON object
+    pub fn render(&self, vars: &Value) -> Result<String, String> {
+        let vars = match vars {
+            Value::Null => &json!({}),
+            Value::Object(_) => vars,
+            _ => return Err("vars must be an object".to_string()),
+        };
+        let missing: Vec<&str> = self
+            .variables
+            .iter()
+            .filter(|variable| {
+                variable.required && vars.get(&variable.name).is_none_or(Value::is_null)
+            })
+            .map(|variable| variable.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Prompt '{}' is missing required variables: {}",
+                self.name,
+                missing.join(", ")
+            ));
+        }
+
+        compile(&self.name, &self.template)?
+            .render(TEMPLATE_NAME, vars)
+            .map_err(|e| format!("Prompt '{}' rendering error: {}", self.name, e))
+    }
+
+    /// The template as an MCP prompt; saved templates have no handler
+    fn to_mcp_prompt(&self) -> McpPrompt {
+        McpPrompt {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            arguments: self
+                .variables
+                .iter()
+                .map(|variable| PromptArgument {
+                    name: variable.name.clone(),
+                    description: variable.description.clone(),
+                    required: variable.required,
+                })
+                .collect(),
+            handler_function: String::new(),
+            script_uri: self.script_uri.clone(),
+        }
+    }
+}
+
+fn compile(name: &str, template: &str) -> Result<Handlebars<'static>, String> {
+    let mut registry = Handlebars::new();
+    registry.register_escape_fn(handlebars::no_escape);
+    registry
+        .register_template_string(TEMPLATE_NAME, template)
+        .map_err(|e| format!("Prompt '{}' compilation error: {}", name, e))?;
+    Ok(registry)
+}
+
+/// Check a prompt name: lowercase letters, digits, `_`, `-` and `.`
+pub fn validate_name(name: &str) -> Result<(), String> {
+    static NAME_PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = NAME_PATTERN
+        .get_or_init(|| Regex::new(r"^[a-z0-9][a-z0-9_.-]*$").expect("valid prompt name regex"));
+    if name.len() > MAX_NAME_LENGTH || !pattern.is_match(name) {
+        return Err(format!(
+            "Invalid prompt name '{}': use up to {} lowercase letters, digits, '_', '-' and '.'",
+            name, MAX_NAME_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn validate_definition(name: &str, definition: &PromptDefinition) -> Result<(), String> {
+    validate_name(name)?;
+    if definition.template.trim().is_empty() {
+        return Err("template must not be empty".to_string());
+    }
+    if definition.template.len() > MAX_TEMPLATE_BYTES {
+        return Err(format!(
+            "template must not exceed {} bytes",
+            MAX_TEMPLATE_BYTES
+        ));
+    }
+    if definition.variables.len() > MAX_VARIABLES {
+        return Err(format!(
+            "A prompt may declare at most {} variables",
+            MAX_VARIABLES
+        ));
+    }
+    let mut seen = HashSet::new();
+    for variable in &definition.variables {
+        if variable.name.is_empty() || !seen.insert(variable.name.as_str()) {
+            return Err(format!(
+                "Variable names must be unique and non-empty, got '{}'",
+                variable.name
+            ));
+        }
+    }
+    compile(name, &definition.template).map(|_| ())
+}
+
+/// Save `definition` as the next version of the prompt `name`, unless it
+/// matches the latest version, which is returned instead
+pub fn save(
+    script_uri: &str,
+    name: &str,
+    definition: &PromptDefinition,
+) -> Result<PromptTemplate, String> {
+    validate_definition(name, definition)?;
+    repository::save_prompt_template(script_uri, name, definition).map_err(|e| e.to_string())
+}
+
+/// The prompt `name` at `version`, or its latest version
+pub fn get(name: &str, version: Option<i32>) -> Result<Option<PromptTemplate>, String> {
+    repository::get_prompt_template(name, version).map_err(|e| e.to_string())
+}
+
+/// The latest version of every saved prompt, by name
+pub fn list() -> Result<Vec<PromptTemplate>, String> {
+    repository::list_prompt_templates().map_err(|e| e.to_string())
+}
+
+/// Render the prompt `name` at `version`, or its latest version
+pub fn render(name: &str, vars: &Value, version: Option<i32>) -> Result<String, String> {
+    let template = get(name, version)?.ok_or_else(|| match version {
+        Some(version) => format!("Prompt '{}' version {} not found", name, version),
+        None => format!("Prompt '{}' not found", name),
+    })?;
+    template.render(vars)
+}
+
+/// Saved prompts for MCP prompts/list, leaving out names a script
+/// registered a prompt under
+pub async fn mcp_prompts(registered: &[McpPrompt]) -> Vec<McpPrompt> {
+    let registered: HashSet<&str> = registered
+        .iter()
+        .map(|prompt| prompt.name.as_str())
+        .collect();
+    match repository::get_repository().list_prompt_templates().await {
+        Ok(templates) => templates
+            .iter()
+            .filter(|template| !registered.contains(template.name.as_str()))
+            .map(PromptTemplate::to_mcp_prompt)
+            .collect(),
+        Err(e) => {
+            warn!("Failed to list saved prompts for MCP: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Answer MCP prompts/get with the latest version of the saved prompt
+/// `name`, or None when there is no such prompt
+pub async fn mcp_get_prompt(name: &str, arguments: &Value) -> Option<Result<Value, String>> {
+    let template = match repository::get_repository()
+        .get_prompt_template(name, None)
+        .await
+    {
+        Ok(template) => template?,
+        Err(e) => return Some(Err(e.to_string())),
+    };
+    Some(template.render(arguments).map(|text| {
+        json!({
+            "description": template.description,
+            "messages": [{
+                "role": "user",
+                "content": { "type": "text", "text": text }
+            }]
+        })
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(text: &str) -> PromptTemplate {
+        PromptTemplate {
+            name: "summarize".to_string(),
+            version: 1,
+            description: "Summarize a text".to_string(),
+            template: text.to_string(),
+            variables: vec![
+                PromptVariable {
+                    name: "text".to_string(),
+                    description: String::new(),
+                    required: true,
+                },
+                PromptVariable {
+                    name: "tone".to_string(),
+                    description: String::new(),
+                    required: false,
+                },
+            ],
+            script_uri: "https://example.com/prompts".to_string(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let prompt = template("Summarize{{#if tone}} in a {{tone}} tone{{/if}}: {{text}}");
+        assert_eq!(
+            prompt
+                .render(&json!({ "text": "<b>Rust</b> & more", "tone": "dry" }))
+                .unwrap(),
+            "Summarize in a dry tone: <b>Rust</b> & more"
+        );
+        assert_eq!(
+            prompt.render(&json!({ "tone": "dry" })),
+            Err("Prompt 'summarize' is missing required variables: text".to_string())
+        );
+        assert!(prompt.render(&json!("text")).is_err());
+
+        let mcp = prompt.to_mcp_prompt();
+        assert_eq!(mcp.arguments.len(), 2);
+        assert!(mcp.arguments[0].required);
+    }
+
+    #[test]
+    fn test_validate_definition() {
+        let definition = PromptDefinition {
+            template: "Hello {{name}}".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_definition("greeting.v2", &definition).is_ok());
+        assert!(validate_definition("Greeting", &definition).is_err());
+
+        let unclosed = PromptDefinition {
+            template: "Hello {{#if name}}".to_string(),
+            ..Default::default()
+        };
+        assert!(validate_definition("greeting", &unclosed).is_err());
+
+        let duplicate = PromptDefinition {
+            variables: vec![
+                PromptVariable {
+                    name: "name".to_string(),
+                    description: String::new(),
+                    required: true,
+                };
+                2
+            ],
+            ..definition
+        };
+        assert!(validate_definition("greeting", &duplicate).is_err());
+    }
+}
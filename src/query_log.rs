@@ -0,0 +1,569 @@
+//! Opt-in statement log with slow query statistics.
+//!
+//! sqlx emits a `sqlx::query` tracing event for every statement it runs. When
+//! `repository.query_log_enabled` is set, [`layer`] returns a tracing layer
+//! that turns those events into per-statement aggregates: literals are
+//! replaced with `?` so that calls differing only in their values share a
+//! fingerprint, and each fingerprint keeps its call count, durations, row
+//! counts and the handlers that issued it. Slow statements are also kept in a
+//! short list of recent occurrences. `console.queryStats` and the `queryStats`
+//! GraphQL query read the report.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::{Filtered, Targets};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::RepositoryConfig;
+
+/// Distinct fingerprints tracked; statements beyond this are only counted
+pub const MAX_TRACKED_STATEMENTS: usize = 1000;
+/// Slow statement occurrences kept for the report
+pub const MAX_RECENT_SLOW_QUERIES: usize = 100;
+/// Handlers remembered per fingerprint
+const MAX_HANDLERS_PER_STATEMENT: usize = 20;
+/// Fingerprints are cut to this many characters
+const MAX_FINGERPRINT_CHARS: usize = 2000;
+
+pub const DEFAULT_REPORT_LIMIT: usize = 50;
+pub const MAX_REPORT_LIMIT: usize = 500;
+
+static QUERY_LOG: OnceLock<QueryLog> = OnceLock::new();
+
+/// Normalize a SQL statement so that calls differing only in literal values
+/// share one entry: string and numeric literals become `?`, comments are
+/// dropped and whitespace is collapsed. Bind placeholders (`$1`) and quoted
+/// identifiers are kept as written.
+pub fn fingerprint(sql: &str) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut out = String::with_capacity(sql.len().min(MAX_FINGERPRINT_CHARS));
+    let mut pending_space = false;
+    let mut i = 0;
+
+    let push = |out: &mut String, pending_space: &mut bool, s: &str| {
+        if *pending_space && !out.is_empty() {
+            out.push(' ');
+        }
+        *pending_space = false;
+        out.push_str(s);
+    };
+
+    while i < chars.len() && out.len() < MAX_FINGERPRINT_CHARS {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            pending_space = true;
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            pending_space = true;
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            pending_space = true;
+        } else if c == '\'' {
+            // String literal; '' is an escaped quote
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' {
+                    if chars.get(i + 1) == Some(&'\'') {
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+            push(&mut out, &mut pending_space, "?");
+        } else if c == '"' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+            let ident: String = chars[start..i].iter().collect();
+            push(&mut out, &mut pending_space, &ident);
+        } else if c.is_ascii_digit() {
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_')
+            {
+                i += 1;
+            }
+            push(&mut out, &mut pending_space, "?");
+        } else if c.is_alphanumeric() || c == '_' || c == '$' {
+            // Identifiers, keywords and $n placeholders; digits inside them
+            // are part of the name
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            push(&mut out, &mut pending_space, &word);
+        } else {
+            push(&mut out, &mut pending_space, &c.to_string());
+            i += 1;
+        }
+    }
+
+    out.truncate(
+        out.char_indices()
+            .nth(MAX_FINGERPRINT_CHARS)
+            .map_or(out.len(), |(index, _)| index),
+    );
+    out
+}
+
+/// One finished statement
+#[derive(Debug, Clone, Default)]
+pub struct QueryEvent {
+    pub sql: String,
+    pub elapsed: Duration,
+    pub rows_returned: u64,
+    pub rows_affected: u64,
+    /// Script handler running on the thread that issued the statement
+    pub handler: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct StatementStats {
+    calls: u64,
+    slow_calls: u64,
+    total_micros: u64,
+    max_micros: u64,
+    rows_returned: u64,
+    rows_affected: u64,
+    handlers: HashMap<String, u64>,
+}
+
+/// A slow statement occurrence
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowQuery {
+    pub fingerprint: String,
+    pub duration_ms: f64,
+    pub rows_returned: u64,
+    pub rows_affected: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handler: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandlerCalls {
+    pub handler: String,
+    pub calls: u64,
+}
+
+/// Aggregate for one statement fingerprint
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementReport {
+    pub fingerprint: String,
+    pub calls: u64,
+    pub slow_calls: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub rows_returned: u64,
+    pub rows_affected: u64,
+    /// Handlers that issued the statement, most frequent first
+    pub handlers: Vec<HandlerCalls>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStatsReport {
+    pub enabled: bool,
+    pub slow_threshold_ms: u64,
+    /// When collection started (or was last reset)
+    pub since: Option<String>,
+    /// Statements not tracked because the fingerprint table was full
+    pub untracked_calls: u64,
+    /// Statements by total time spent, highest first
+    pub statements: Vec<StatementReport>,
+    /// Most recent slow statements, newest first
+    pub recent_slow: Vec<SlowQuery>,
+}
+
+/// Options for [`QueryLog::report`]
+#[derive(Debug, Clone)]
+pub struct QueryStatsOptions {
+    /// Only include fingerprints with at least one slow call
+    pub slow_only: bool,
+    pub limit: usize,
+}
+
+impl Default for QueryStatsOptions {
+    fn default() -> Self {
+        Self {
+            slow_only: false,
+            limit: DEFAULT_REPORT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueryLogState {
+    since: DateTime<Utc>,
+    statements: HashMap<String, StatementStats>,
+    recent_slow: VecDeque<SlowQuery>,
+    untracked_calls: u64,
+}
+
+impl QueryLogState {
+    fn new() -> Self {
+        Self {
+            since: Utc::now(),
+            statements: HashMap::new(),
+            recent_slow: VecDeque::new(),
+            untracked_calls: 0,
+        }
+    }
+}
+
+/// In-memory statement statistics
+#[derive(Debug)]
+pub struct QueryLog {
+    slow_threshold: Duration,
+    state: Mutex<QueryLogState>,
+}
+
+impl QueryLog {
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self {
+            slow_threshold,
+            state: Mutex::new(QueryLogState::new()),
+        }
+    }
+
+    pub fn record(&self, event: QueryEvent) {
+        let fingerprint = fingerprint(&event.sql);
+        if fingerprint.is_empty() {
+            return;
+        }
+        let micros = event.elapsed.as_micros().min(u64::MAX as u128) as u64;
+        let slow = event.elapsed >= self.slow_threshold;
+
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if slow {
+            if state.recent_slow.len() >= MAX_RECENT_SLOW_QUERIES {
+                state.recent_slow.pop_back();
+            }
+            state.recent_slow.push_front(SlowQuery {
+                fingerprint: fingerprint.clone(),
+                duration_ms: micros as f64 / 1000.0,
+                rows_returned: event.rows_returned,
+                rows_affected: event.rows_affected,
+                handler: event.handler.clone(),
+                request_id: event.request_id,
+                timestamp: Utc::now().to_rfc3339(),
+            });
+        }
+
+        if !state.statements.contains_key(&fingerprint)
+            && state.statements.len() >= MAX_TRACKED_STATEMENTS
+        {
+            state.untracked_calls += 1;
+            return;
+        }
+        let stats = state.statements.entry(fingerprint).or_default();
+        stats.calls += 1;
+        stats.total_micros = stats.total_micros.saturating_add(micros);
+        stats.max_micros = stats.max_micros.max(micros);
+        stats.rows_returned += event.rows_returned;
+        stats.rows_affected += event.rows_affected;
+        if slow {
+            stats.slow_calls += 1;
+        }
+        if let Some(handler) = event.handler
+            && (stats.handlers.contains_key(&handler)
+                || stats.handlers.len() < MAX_HANDLERS_PER_STATEMENT)
+        {
+            *stats.handlers.entry(handler).or_default() += 1;
+        }
+    }
+
+    pub fn report(&self, options: &QueryStatsOptions) -> QueryStatsReport {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let mut statements: Vec<StatementReport> = state
+            .statements
+            .iter()
+            .filter(|(_, stats)| !options.slow_only || stats.slow_calls > 0)
+            .map(|(fingerprint, stats)| {
+                let mut handlers: Vec<HandlerCalls> = stats
+                    .handlers
+                    .iter()
+                    .map(|(handler, calls)| HandlerCalls {
+                        handler: handler.clone(),
+                        calls: *calls,
+                    })
+                    .collect();
+                handlers.sort_by(|a, b| b.calls.cmp(&a.calls).then(a.handler.cmp(&b.handler)));
+                StatementReport {
+                    fingerprint: fingerprint.clone(),
+                    calls: stats.calls,
+                    slow_calls: stats.slow_calls,
+                    total_ms: stats.total_micros as f64 / 1000.0,
+                    avg_ms: stats.total_micros as f64 / stats.calls.max(1) as f64 / 1000.0,
+                    max_ms: stats.max_micros as f64 / 1000.0,
+                    rows_returned: stats.rows_returned,
+                    rows_affected: stats.rows_affected,
+                    handlers,
+                }
+            })
+            .collect();
+        statements.sort_by(|a, b| {
+            b.total_ms
+                .total_cmp(&a.total_ms)
+                .then(a.fingerprint.cmp(&b.fingerprint))
+        });
+        statements.truncate(options.limit);
+
+        QueryStatsReport {
+            enabled: true,
+            slow_threshold_ms: self.slow_threshold.as_millis() as u64,
+            since: Some(state.since.to_rfc3339()),
+            untracked_calls: state.untracked_calls,
+            statements,
+            recent_slow: state
+                .recent_slow
+                .iter()
+                .take(options.limit)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Drop everything collected so far
+    pub fn reset(&self) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *state = QueryLogState::new();
+    }
+}
+
+/// Tracing layer feeding `sqlx::query` events into the global query log
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueryLogLayer;
+
+#[derive(Default)]
+struct QueryEventVisitor {
+    summary: String,
+    statement: String,
+    rows_returned: u64,
+    rows_affected: u64,
+    elapsed_secs: f64,
+}
+
+impl Visit for QueryEventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_returned" => self.rows_returned = value,
+            "rows_affected" => self.rows_affected = value,
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+impl<S: Subscriber> Layer<S> for QueryLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let Some(log) = QUERY_LOG.get() else {
+            return;
+        };
+        let mut visitor = QueryEventVisitor::default();
+        event.record(&mut visitor);
+
+        // sqlx leaves db.statement empty when the summary is the whole SQL
+        let sql = if visitor.statement.trim().is_empty() {
+            visitor.summary
+        } else {
+            visitor.statement
+        };
+        let context = crate::js_engine::current_log_context();
+        log.record(QueryEvent {
+            sql,
+            elapsed: Duration::from_secs_f64(visitor.elapsed_secs.max(0.0)),
+            rows_returned: visitor.rows_returned,
+            rows_affected: visitor.rows_affected,
+            handler: context.as_ref().and_then(|c| c.handler.clone()),
+            request_id: context.and_then(|c| c.request_id),
+        });
+    }
+}
+
+/// Start collecting statement statistics if the configuration asks for it.
+/// The returned layer must be added to the tracing subscriber; it only sees
+/// `sqlx::query` events, whatever the log level of the other layers.
+pub fn layer<S>(config: &RepositoryConfig) -> Option<Filtered<QueryLogLayer, Targets, S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    if !config.query_log_enabled {
+        return None;
+    }
+    let _ = QUERY_LOG.set(QueryLog::new(Duration::from_millis(
+        config.slow_query_threshold_ms,
+    )));
+    Some(
+        QueryLogLayer.with_filter(Targets::new().with_target("sqlx::query", tracing::Level::TRACE)),
+    )
+}
+
+/// Current statistics; `enabled` is false when the query log is off
+pub fn report(options: &QueryStatsOptions) -> QueryStatsReport {
+    match QUERY_LOG.get() {
+        Some(log) => log.report(options),
+        None => QueryStatsReport {
+            enabled: false,
+            slow_threshold_ms: 0,
+            since: None,
+            untracked_calls: 0,
+            statements: Vec::new(),
+            recent_slow: Vec::new(),
+        },
+    }
+}
+
+/// Clear the collected statistics; returns false when the query log is off
+pub fn reset() -> bool {
+    match QUERY_LOG.get() {
+        Some(log) => {
+            log.reset();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(sql: &str, ms: u64, handler: Option<&str>) -> QueryEvent {
+        QueryEvent {
+            sql: sql.to_string(),
+            elapsed: Duration::from_millis(ms),
+            rows_returned: 2,
+            rows_affected: 0,
+            handler: handler.map(str::to_string),
+            request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(
+            fingerprint("SELECT *\n  FROM items WHERE id = 42 AND name = 'it''s' -- note\n"),
+            "SELECT * FROM items WHERE id = ? AND name = ?"
+        );
+        assert_eq!(
+            fingerprint("select value from scriptdb_1a2b.t1 where x = $1 limit 10"),
+            "select value from scriptdb_1a2b.t1 where x = $1 limit ?"
+        );
+        assert_eq!(
+            fingerprint("SELECT \"Col 1\" /* hint */ FROM t WHERE v > 1.5e3"),
+            "SELECT \"Col 1\" FROM t WHERE v > ?"
+        );
+        assert_eq!(fingerprint("SELECT 1"), fingerprint("SELECT  2"));
+    }
+
+    #[test]
+    fn test_query_log_aggregates_by_fingerprint() {
+        let log = QueryLog::new(Duration::from_millis(100));
+        log.record(event("SELECT * FROM t WHERE id = 1", 10, Some("list")));
+        log.record(event("SELECT * FROM t WHERE id = 2", 150, Some("list")));
+        log.record(event("SELECT * FROM t WHERE id = 3", 20, Some("detail")));
+        log.record(event("DELETE FROM t", 5, None));
+
+        let report = log.report(&QueryStatsOptions::default());
+        assert!(report.enabled);
+        assert_eq!(report.slow_threshold_ms, 100);
+        assert_eq!(report.statements.len(), 2);
+
+        let select = &report.statements[0];
+        assert_eq!(select.fingerprint, "SELECT * FROM t WHERE id = ?");
+        assert_eq!(select.calls, 3);
+        assert_eq!(select.slow_calls, 1);
+        assert_eq!(select.total_ms, 180.0);
+        assert_eq!(select.avg_ms, 60.0);
+        assert_eq!(select.max_ms, 150.0);
+        assert_eq!(select.rows_returned, 6);
+        assert_eq!(select.handlers[0].handler, "list");
+        assert_eq!(select.handlers[0].calls, 2);
+
+        assert_eq!(report.recent_slow.len(), 1);
+        assert_eq!(report.recent_slow[0].duration_ms, 150.0);
+        assert_eq!(report.recent_slow[0].handler.as_deref(), Some("list"));
+
+        let slow_only = log.report(&QueryStatsOptions {
+            slow_only: true,
+            limit: 10,
+        });
+        assert_eq!(slow_only.statements.len(), 1);
+
+        log.reset();
+        let report = log.report(&QueryStatsOptions::default());
+        assert!(report.statements.is_empty());
+        assert!(report.recent_slow.is_empty());
+    }
+
+    #[test]
+    fn test_query_log_bounds_tracked_statements() {
+        let log = QueryLog::new(Duration::from_secs(60));
+        for i in 0..MAX_TRACKED_STATEMENTS + 5 {
+            log.record(event(&format!("SELECT * FROM t{}", i), 1, None));
+        }
+        let report = log.report(&QueryStatsOptions {
+            slow_only: false,
+            limit: MAX_REPORT_LIMIT,
+        });
+        assert_eq!(report.untracked_calls, 5);
+        assert_eq!(report.statements.len(), MAX_REPORT_LIMIT);
+    }
+}
@@ -0,0 +1,352 @@
+//! Declarative rate limits for HTTP requests.
+//!
+//! Rules come from two places: `security.rate_limit_rules` in the server
+//! configuration, matched against the path of every request by
+//! [`middleware`], and the `rateLimit` option of `routeRegistry.registerRoute`,
+//! checked once a request has been matched to the route. Both are enforced
+//! before any JavaScript runs; a request over a limit is answered with HTTP
+//! 429 and a Retry-After header.
+//!
+//! Budgets are token buckets kept by [`RateLimiter`] in the `rate_limits`
+//! table, so every server in a cluster draws from the same bucket. Rules are
+//! only enforced while `security.enable_rate_limiting` is set.
+
+use std::sync::{OnceLock, RwLock};
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{RateLimitRuleConfig, SecurityConfig};
+use crate::security::{RateLimitConfig, RateLimitKey, RateLimiter};
+
+/// Who shares the budget of a rate limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitScope {
+    /// Each client IP address has its own budget
+    #[default]
+    Ip,
+    /// Each signed-in user has their own budget; anonymous requests are
+    /// limited per IP address
+    User,
+    /// All clients share one budget
+    Route,
+}
+
+/// Rate limit registered with a route (`registerRoute` option `rateLimit`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteRateLimit {
+    pub requests_per_minute: u32,
+    #[serde(default)]
+    pub per: RateLimitScope,
+    /// Requests allowed in a burst; defaults to `requests_per_minute`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+}
+
+/// A rate limit ready to be checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitRule {
+    /// Name of the rule's buckets, unique per rule
+    pub name: String,
+    pub per: RateLimitScope,
+    pub requests_per_minute: u32,
+    pub burst: Option<u32>,
+}
+
+impl RateLimitRule {
+    /// Rule for a route registered at `pattern` for `method`
+    pub fn for_route(method: &str, pattern: &str, limit: &RouteRateLimit) -> Self {
+        Self {
+            name: format!("route:{} {}", method, pattern),
+            per: limit.per,
+            requests_per_minute: limit.requests_per_minute,
+            burst: limit.burst,
+        }
+    }
+
+    fn bucket_config(&self) -> RateLimitConfig {
+        let max_tokens = self.burst.unwrap_or(self.requests_per_minute).max(1);
+        RateLimitConfig {
+            max_tokens,
+            refill_rate: f64::from(self.requests_per_minute) / 60.0,
+            window_duration: chrono::Duration::minutes(1),
+            burst_allowance: 0,
+            enabled: true,
+        }
+    }
+
+    /// Bucket a request from `client_ip` by `user_id` draws from
+    pub fn bucket_key(&self, client_ip: &str, user_id: Option<&str>) -> RateLimitKey {
+        match (self.per, user_id) {
+            (RateLimitScope::Route, _) => RateLimitKey::Endpoint(self.name.clone()),
+            (RateLimitScope::User, Some(user_id)) => {
+                RateLimitKey::UserEndpoint(user_id.to_string(), self.name.clone())
+            }
+            _ => RateLimitKey::IpEndpoint(client_ip.to_string(), self.name.clone()),
+        }
+    }
+}
+
+/// A request rejected by a rate limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    pub rule: String,
+    pub requests_per_minute: u32,
+    /// Seconds until the bucket holds a token again
+    pub retry_after_secs: u64,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Rate limit '{}' of {} requests per minute exceeded",
+            self.rule, self.requests_per_minute
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConfiguredRule {
+    path: String,
+    methods: Vec<String>,
+    rule: RateLimitRule,
+}
+
+#[derive(Debug, Default)]
+struct Settings {
+    enabled: bool,
+    rules: Vec<ConfiguredRule>,
+}
+
+static SETTINGS: OnceLock<RwLock<Settings>> = OnceLock::new();
+static LIMITER: OnceLock<Option<RateLimiter>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<Settings> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+fn configured_rule(index: usize, config: &RateLimitRuleConfig) -> ConfiguredRule {
+    ConfiguredRule {
+        path: config.path.clone(),
+        methods: config
+            .methods
+            .iter()
+            .map(|method| method.to_ascii_uppercase())
+            .collect(),
+        rule: RateLimitRule {
+            name: format!("config:{}:{}", index, config.path),
+            per: config.per,
+            requests_per_minute: config.requests_per_minute,
+            burst: config.burst,
+        },
+    }
+}
+
+/// Apply the rate limit configuration. Called once at server startup.
+pub fn configure(config: &SecurityConfig) {
+    let configured = Settings {
+        enabled: config.enable_rate_limiting,
+        rules: config
+            .rate_limit_rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| configured_rule(index, rule))
+            .collect(),
+    };
+    match settings().write() {
+        Ok(mut guard) => *guard = configured,
+        Err(poisoned) => *poisoned.into_inner() = configured,
+    }
+}
+
+/// Whether rate limit rules are enforced
+pub fn is_enabled() -> bool {
+    match settings().read() {
+        Ok(guard) => guard.enabled,
+        Err(poisoned) => poisoned.into_inner().enabled,
+    }
+}
+
+/// Configured rules that apply to a request
+fn matching_rules(path: &str, method: &str) -> Vec<RateLimitRule> {
+    let select = |settings: &Settings| {
+        if !settings.enabled {
+            return Vec::new();
+        }
+        settings
+            .rules
+            .iter()
+            .filter(|configured| {
+                (configured.methods.is_empty() || configured.methods.iter().any(|m| m == method))
+                    && crate::route_index::path_matches(&configured.path, path)
+            })
+            .map(|configured| configured.rule.clone())
+            .collect()
+    };
+    match settings().read() {
+        Ok(guard) => select(&guard),
+        Err(poisoned) => select(&poisoned.into_inner()),
+    }
+}
+
+fn limiter() -> Option<&'static RateLimiter> {
+    LIMITER
+        .get_or_init(|| {
+            crate::database::get_global_database().map(|db| RateLimiter::new(db.pool().clone()))
+        })
+        .as_ref()
+}
+
+/// Take one request from the rule's bucket for this client. Requests are
+/// allowed when no database is available, like other rate limit checks.
+pub async fn check(
+    rule: &RateLimitRule,
+    client_ip: &str,
+    user_id: Option<&str>,
+) -> Result<(), RateLimited> {
+    if !is_enabled() {
+        return Ok(());
+    }
+    let Some(limiter) = limiter() else {
+        return Ok(());
+    };
+    let result = limiter
+        .check_rate_limit_with_config(rule.bucket_key(client_ip, user_id), 1, rule.bucket_config())
+        .await;
+    if result.allowed {
+        return Ok(());
+    }
+    Err(RateLimited {
+        rule: rule.name.clone(),
+        requests_per_minute: rule.requests_per_minute,
+        retry_after_secs: result
+            .retry_after
+            .map(|secs| secs.ceil().max(1.0) as u64)
+            .unwrap_or(60),
+    })
+}
+
+/// Client address of a request as reported by the proxy in front of the
+/// server (`X-Forwarded-For`, then `X-Real-IP`)
+pub fn client_ip(headers: &axum::http::HeaderMap) -> String {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|forwarded| forwarded.split(',').next())
+        .or_else(|| {
+            headers
+                .get("x-real-ip")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(|ip| ip.trim().to_string())
+        .filter(|ip| !ip.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Middleware enforcing the configured rules that match a request's path
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let rules = matching_rules(req.uri().path(), req.method().as_str());
+    if rules.is_empty() {
+        return next.run(req).await;
+    }
+
+    let ip = client_ip(req.headers());
+    let user_id = req
+        .extensions()
+        .get::<crate::auth::AuthUser>()
+        .map(|user| user.user_id.clone());
+    for rule in &rules {
+        if let Err(limited) = check(rule, &ip, user_id.as_deref()).await {
+            let request_id = req
+                .extensions()
+                .get::<crate::middleware::RequestId>()
+                .map(|rid| rid.0.clone())
+                .unwrap_or_else(|| "unknown".to_string());
+            return crate::rate_limited_response(&limited, req.uri().path(), &request_id);
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_keys() {
+        let rule = RateLimitRule::for_route(
+            "POST",
+            "/api/orders",
+            &RouteRateLimit {
+                requests_per_minute: 30,
+                per: RateLimitScope::User,
+                burst: None,
+            },
+        );
+        assert_eq!(rule.name, "route:POST /api/orders");
+        assert_eq!(
+            rule.bucket_key("10.0.0.1", Some("u1")).as_string(),
+            "user_endpoint:u1:route:POST /api/orders"
+        );
+        // Anonymous requests fall back to their IP address
+        assert_eq!(
+            rule.bucket_key("10.0.0.1", None).as_string(),
+            "ip_endpoint:10.0.0.1:route:POST /api/orders"
+        );
+
+        let shared = RateLimitRule {
+            per: RateLimitScope::Route,
+            ..rule
+        };
+        assert_eq!(
+            shared.bucket_key("10.0.0.1", Some("u1")).as_string(),
+            "endpoint:route:POST /api/orders"
+        );
+    }
+
+    #[test]
+    fn test_bucket_config() {
+        let rule = RateLimitRule {
+            name: "config:0:/api/*".to_string(),
+            per: RateLimitScope::Ip,
+            requests_per_minute: 120,
+            burst: None,
+        };
+        let config = rule.bucket_config();
+        assert_eq!(config.max_tokens, 120);
+        assert_eq!(config.refill_rate, 2.0);
+
+        let bursty = RateLimitRule {
+            burst: Some(10),
+            ..rule
+        };
+        assert_eq!(bursty.bucket_config().max_tokens, 10);
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(client_ip(&headers), "unknown");
+        headers.insert("x-real-ip", "192.0.2.7".parse().unwrap());
+        assert_eq!(client_ip(&headers), "192.0.2.7");
+        headers.insert("x-forwarded-for", "198.51.100.1, 10.0.0.1".parse().unwrap());
+        assert_eq!(client_ip(&headers), "198.51.100.1");
+    }
+
+    #[test]
+    fn test_route_rate_limit_serde() {
+        let limit: RouteRateLimit =
+            serde_json::from_str(r#"{"requestsPerMinute": 10, "per": "user"}"#).unwrap();
+        assert_eq!(
+            limit,
+            RouteRateLimit {
+                requests_per_minute: 10,
+                per: RateLimitScope::User,
+                burst: None,
+            }
+        );
+    }
+}
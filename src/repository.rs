@@ -3,8 +3,9 @@ use crate::scheduler;
 use chrono::{DateTime, Utc};
 use sqlx::{PgConnection, PgPool, Row};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, OnceLock, PoisonError};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, error, info, warn};
 
 // TODO: Transaction Integration
@@ -81,6 +82,10 @@ pub enum RepositoryError {
     AssetNotFound(String),
     #[error("Invalid data format: {0}")]
     InvalidData(String),
+    #[error("Storage quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("System script is protected: {0}")]
+    ProtectedScript(String),
 }
 
 /// OpenAPI metadata for a registered route
@@ -97,6 +102,30 @@ pub struct RouteMetadata {
     pub parameters: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "requestBody")]
     pub request_body: Option<serde_json::Value>,
+    /// Normalized host names the route is served on; empty means any host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
+    /// Rate limit checked before the handler runs
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "rateLimit")]
+    pub rate_limit: Option<crate::rate_limit_rules::RouteRateLimit>,
+    /// Replays responses to retries carrying the same Idempotency-Key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency: Option<crate::idempotency::RouteIdempotency>,
+    /// Upload rules checked before the handler runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploads: Option<crate::parsers::RouteUploads>,
+    /// JSON Schemas the request is validated against before the handler runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<crate::request_schema::RouteSchema>,
+    /// Caches responses to anonymous GET requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<crate::response_cache::RouteCache>,
+    /// Sign-in and group membership required to use the route
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<crate::auth::RouteAuth>,
+    /// Handler timeout replacing the script's and the configured one
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
 }
 
 impl RouteMetadata {
@@ -108,6 +137,14 @@ impl RouteMetadata {
             tags: Vec::new(),
             parameters: None,
             request_body: None,
+            hosts: Vec::new(),
+            rate_limit: None,
+            idempotency: None,
+            uploads: None,
+            schema: None,
+            cache: None,
+            auth: None,
+            timeout_ms: None,
         }
     }
 }
@@ -133,6 +170,104 @@ impl LogEntry {
     }
 }
 
+/// Default number of entries in one page of [`query_log_messages`]
+pub const DEFAULT_LOG_PAGE_SIZE: i64 = 100;
+
+/// Maximum number of entries in one page of [`query_log_messages`]
+pub const MAX_LOG_PAGE_SIZE: i64 = 1000;
+
+/// Filters and paging for [`query_log_messages`]. Entries are returned newest
+/// first; pass the `next_cursor` of a page as `cursor` to get the next one.
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    pub script_uri: Option<String>,
+    /// Log levels to include (e.g. "ERROR", "WARN"); empty means all levels
+    pub levels: Vec<String>,
+    /// Inclusive lower bound on the entry timestamp
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the entry timestamp
+    pub until: Option<DateTime<Utc>>,
+    /// Case-insensitive substring the message must contain
+    pub search: Option<String>,
+    /// Object the structured `data` of the entry must contain (JSONB `@>`)
+    pub data: Option<serde_json::Value>,
+    /// Only entries written while handling this request
+    pub request_id: Option<String>,
+    pub cursor: Option<String>,
+    /// Page size, defaults to [`DEFAULT_LOG_PAGE_SIZE`] and is capped at [`MAX_LOG_PAGE_SIZE`]
+    pub limit: Option<i64>,
+}
+
+/// Log entry returned by [`query_log_messages`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    pub id: String,
+    pub script_uri: String,
+    pub level: String,
+    pub message: String,
+    /// Structured payload logged with the message
+    pub data: Option<serde_json::Value>,
+    /// Request/handler context captured when the entry was written
+    pub context: Option<serde_json::Value>,
+    pub timestamp: DateTime<Utc>,
+    /// Cursor continuing the query after this entry
+    pub cursor: String,
+}
+
+/// One page of log entries
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub entries: Vec<LogRecord>,
+    /// Cursor of the last entry when more entries match the query
+    pub next_cursor: Option<String>,
+}
+
+/// Encode the keyset position of a log entry as an opaque cursor
+fn encode_log_cursor(created_at: &DateTime<Utc>, id: &str) -> String {
+    use base64::Engine as _;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!(
+        "{}|{}",
+        created_at.timestamp_micros(),
+        id
+    ))
+}
+
+/// Decode a cursor produced by [`encode_log_cursor`]
+fn decode_log_cursor(cursor: &str) -> AppResult<(DateTime<Utc>, String)> {
+    use base64::Engine as _;
+    let invalid = || -> AppError {
+        RepositoryError::InvalidData(format!("Invalid log cursor: {}", cursor)).into()
+    };
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (micros, id) = decoded.split_once('|').ok_or_else(invalid)?;
+    let created_at = micros
+        .parse::<i64>()
+        .ok()
+        .and_then(DateTime::from_timestamp_micros)
+        .ok_or_else(invalid)?;
+    if uuid::Uuid::parse_str(id).is_err() {
+        return Err(invalid());
+    }
+    Ok((created_at, id.to_string()))
+}
+
+/// Escape `%`, `_` and `\` so a search term matches literally in ILIKE
+fn escape_like_pattern(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
 /// Script metadata for tracking initialization status and registrations
 #[derive(Debug, Clone)]
 pub struct ScriptMetadata {
@@ -148,6 +283,18 @@ pub struct ScriptMetadata {
     pub registrations: RouteRegistrations,
     pub privileged: bool,
     pub owners: Vec<String>,
+    /// Human-readable summary of what the script does
+    pub description: Option<String>,
+    /// Free-form labels used to categorize and filter scripts
+    pub tags: Vec<String>,
+    /// Highest migration version applied with `db.migrate`, if any
+    pub schema_version: Option<i64>,
+    /// Handler timeout replacing `javascript.execution_timeout_ms`, if set
+    pub execution_timeout_ms: Option<u64>,
+    /// Memory limit replacing `javascript.max_memory_bytes`, if set
+    pub memory_limit_bytes: Option<u64>,
+    /// Protected from being overwritten or deleted without `force`
+    pub system: bool,
 }
 
 impl ScriptMetadata {
@@ -168,7 +315,44 @@ impl ScriptMetadata {
             registrations: HashMap::new(),
             privileged: false,
             owners: Vec::new(),
+            description: None,
+            tags: Vec::new(),
+            schema_version: None,
+            execution_timeout_ms: None,
+            memory_limit_bytes: None,
+            system: false,
+        }
+    }
+
+    /// Apply description and tags loaded from storage
+    pub fn apply_labels(&mut self, labels: ScriptLabels) {
+        self.description = labels.description;
+        self.tags = labels.tags;
+    }
+
+    /// Whether the script carries every one of the given tags
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|wanted| {
+            let wanted = wanted.trim().to_lowercase();
+            self.tags.contains(&wanted)
+        })
+    }
+
+    /// Case-insensitive substring match against uri, name, and description
+    pub fn matches_text(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
         }
+        self.uri.to_lowercase().contains(&query)
+            || self
+                .name
+                .as_deref()
+                .is_some_and(|name| name.to_lowercase().contains(&query))
+            || self
+                .description
+                .as_deref()
+                .is_some_and(|description| description.to_lowercase().contains(&query))
     }
 
     /// Mark script as initialized successfully
@@ -207,6 +391,93 @@ impl ScriptMetadata {
     }
 }
 
+/// Maximum number of tags per script
+pub const MAX_SCRIPT_TAGS: usize = 20;
+/// Maximum length of a single script tag
+pub const MAX_SCRIPT_TAG_LENGTH: usize = 64;
+/// Maximum length of a script description
+pub const MAX_SCRIPT_DESCRIPTION_LENGTH: usize = 2000;
+
+/// Source map uploaded alongside a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSourceMap {
+    /// The source map JSON
+    pub source_map: String,
+    /// SHA-256 of the script content the map was uploaded for
+    pub content_hash: String,
+}
+
+/// Categorization metadata for a script: description and tags
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ScriptLabels {
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl ScriptLabels {
+    /// Validate and normalize labels: the description is trimmed (empty
+    /// becomes `None`), tags are trimmed, lowercased, and deduplicated.
+    pub fn new(description: Option<String>, tags: Vec<String>) -> AppResult<Self> {
+        let description = description
+            .map(|d| d.trim().to_string())
+            .filter(|d| !d.is_empty());
+        if let Some(description) = &description
+            && description.chars().count() > MAX_SCRIPT_DESCRIPTION_LENGTH
+        {
+            return Err(AppError::validation(
+                "description",
+                format!(
+                    "must be at most {} characters",
+                    MAX_SCRIPT_DESCRIPTION_LENGTH
+                ),
+            ));
+        }
+
+        let mut normalized: Vec<String> = Vec::new();
+        for tag in tags {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() {
+                continue;
+            }
+            if tag.chars().count() > MAX_SCRIPT_TAG_LENGTH {
+                return Err(AppError::validation(
+                    "tags",
+                    format!(
+                        "tag '{}' is longer than {} characters",
+                        tag, MAX_SCRIPT_TAG_LENGTH
+                    ),
+                ));
+            }
+            if !tag
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/'))
+            {
+                return Err(AppError::validation(
+                    "tags",
+                    format!(
+                        "tag '{}' may only contain letters, digits, and - _ . : /",
+                        tag
+                    ),
+                ));
+            }
+            if !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+        if normalized.len() > MAX_SCRIPT_TAGS {
+            return Err(AppError::validation(
+                "tags",
+                format!("at most {} tags are allowed", MAX_SCRIPT_TAGS),
+            ));
+        }
+
+        Ok(Self {
+            description,
+            tags: normalized,
+        })
+    }
+}
+
 /// Script security metadata exposed to admin tooling
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ScriptSecurityProfile {
@@ -226,6 +497,130 @@ pub struct Asset {
     pub created_at: std::time::SystemTime,
     pub updated_at: std::time::SystemTime,
     pub script_uri: String,
+    /// Extra response headers (e.g. Cache-Control) sent when the asset is served
+    pub headers: HashMap<String, String>,
+}
+
+/// Default number of days soft-deleted scripts and assets stay restorable
+pub const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+static TRASH_RETENTION_DAYS: AtomicU64 = AtomicU64::new(DEFAULT_TRASH_RETENTION_DAYS);
+
+/// Set the trash retention from configuration (`repository.trash_retention_days`).
+/// Called once at server startup.
+pub fn set_trash_retention_days(days: u64) {
+    TRASH_RETENTION_DAYS.store(days, Ordering::Relaxed);
+}
+
+/// Number of days trashed scripts and assets are kept before being purged
+pub fn trash_retention_days() -> u64 {
+    TRASH_RETENTION_DAYS.load(Ordering::Relaxed)
+}
+
+/// Default per-script storage quota (shared storage plus assets) in bytes
+pub const DEFAULT_STORAGE_QUOTA_BYTES: u64 = 100 * 1024 * 1024;
+
+static DEFAULT_STORAGE_QUOTA: AtomicU64 = AtomicU64::new(DEFAULT_STORAGE_QUOTA_BYTES);
+
+/// Set the default storage quota from configuration
+/// (`repository.default_storage_quota_bytes`). Called once at server startup.
+pub fn set_default_storage_quota_bytes(bytes: u64) {
+    DEFAULT_STORAGE_QUOTA.store(bytes, Ordering::Relaxed);
+}
+
+/// Storage quota of scripts without an override; 0 means unlimited
+pub fn default_storage_quota_bytes() -> u64 {
+    DEFAULT_STORAGE_QUOTA.load(Ordering::Relaxed)
+}
+
+/// Bytes a script stores in shared storage and assets, and its quota
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStorageUsage {
+    pub script_uri: String,
+    /// Keys and values of live shared storage items
+    pub properties_bytes: u64,
+    /// Content of the script's assets
+    pub asset_bytes: u64,
+    pub used_bytes: u64,
+    /// Effective quota; `None` when the script may store without limit
+    pub quota_bytes: Option<u64>,
+    /// Per-script override of the configured default, if set
+    pub quota_override: Option<u64>,
+}
+
+/// Soft-deleted script awaiting restore or purge
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedScript {
+    pub uri: String,
+    pub name: Option<String>,
+    pub owners: Vec<String>,
+    /// Number of assets that were trashed together with the script
+    pub asset_count: i64,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Individually soft-deleted asset awaiting restore or purge
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedAsset {
+    pub uri: String,
+    pub script_uri: String,
+    pub name: Option<String>,
+    pub mimetype: String,
+    pub size: i64,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Staging slot of a script: a version staged to replace the live one, and
+/// the version the last promotion or rollback replaced
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptStaging {
+    pub uri: String,
+    pub staged_content: Option<String>,
+    pub staged_at: Option<DateTime<Utc>>,
+    pub staged_by: Option<String>,
+    /// Why the staged version's `init()` failed
+    pub staged_init_error: Option<String>,
+    /// Routes the staged version registers, as "METHOD path"
+    pub staged_routes: Vec<String>,
+    /// Version replaced by the last promotion or rollback
+    pub previous_content: Option<String>,
+    pub promoted_at: Option<DateTime<Utc>>,
+}
+
+/// Counts of entries permanently removed by a trash purge
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrashPurgeSummary {
+    pub scripts: u64,
+    pub assets: u64,
+    pub tables: u64,
+}
+
+/// Handler response stored for an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Result of claiming an idempotency key
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    /// The key was free (or its entry expired); the caller runs the request
+    /// and then completes or releases the key
+    Claimed,
+    /// An earlier request with the key is still running
+    InProgress { request_hash: String },
+    /// An earlier request with the key finished with `response`
+    Completed {
+        request_hash: String,
+        response: StoredResponse,
+    },
 }
 
 // ============================================================================
@@ -268,6 +663,151 @@ pub struct ForeignKeyInfo {
     pub referenced_column: String,
 }
 
+/// Rows returned by `db.query` when the script does not ask for a limit
+pub const DEFAULT_SCRIPT_QUERY_MAX_ROWS: u64 = 1000;
+/// Upper bound on the row limit a script may request from `db.query`
+pub const MAX_SCRIPT_QUERY_MAX_ROWS: u64 = 10_000;
+/// Statement timeout for `db.query` when the script does not ask for one
+pub const DEFAULT_SCRIPT_QUERY_TIMEOUT_MS: u64 = 5_000;
+/// Upper bound on the statement timeout a script may request from `db.query`
+pub const MAX_SCRIPT_QUERY_TIMEOUT_MS: u64 = 30_000;
+/// Maximum number of bound parameters in a single `db.query` statement
+pub const MAX_SCRIPT_QUERY_PARAMS: usize = 1000;
+
+/// Limits applied to a statement run through `db.query`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptQueryOptions {
+    pub max_rows: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for ScriptQueryOptions {
+    fn default() -> Self {
+        Self {
+            max_rows: DEFAULT_SCRIPT_QUERY_MAX_ROWS,
+            timeout_ms: DEFAULT_SCRIPT_QUERY_TIMEOUT_MS,
+        }
+    }
+}
+
+impl ScriptQueryOptions {
+    /// Options with script-requested limits clamped to the allowed maximums
+    pub fn new(max_rows: Option<u64>, timeout_ms: Option<u64>) -> Self {
+        Self {
+            max_rows: max_rows
+                .unwrap_or(DEFAULT_SCRIPT_QUERY_MAX_ROWS)
+                .clamp(1, MAX_SCRIPT_QUERY_MAX_ROWS),
+            timeout_ms: timeout_ms
+                .unwrap_or(DEFAULT_SCRIPT_QUERY_TIMEOUT_MS)
+                .clamp(1, MAX_SCRIPT_QUERY_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Result of a statement run through `db.query`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptQueryResult {
+    pub rows: Vec<serde_json::Value>,
+    /// Rows returned by a query, or affected by INSERT/UPDATE/DELETE
+    pub row_count: u64,
+    /// Whether more rows were available than `max_rows`
+    pub truncated: bool,
+}
+
+/// A migration declared by a script with `db.migrate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptMigration {
+    pub version: i64,
+    /// Statements run in order, in one transaction
+    pub up: Vec<String>,
+}
+
+impl ScriptMigration {
+    /// SHA-256 of the statements, recorded to detect edits to applied migrations
+    pub fn checksum(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        for statement in &self.up {
+            hasher.update(statement.trim().as_bytes());
+            hasher.update([0]);
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Result of `db.migrate`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptMigrationSummary {
+    /// Versions applied by this call, in ascending order
+    pub applied: Vec<i64>,
+    /// Highest applied version
+    pub version: Option<i64>,
+}
+
+/// Most documents one `vectors.upsert` call may store
+pub const MAX_VECTOR_UPSERT_DOCUMENTS: usize = 256;
+/// Matches `vectors.search` returns when the script does not ask for a limit
+pub const DEFAULT_VECTOR_SEARCH_LIMIT: u32 = 10;
+/// Upper bound on the matches a script may request from `vectors.search`
+pub const MAX_VECTOR_SEARCH_LIMIT: u32 = 100;
+/// How far `vectors.search` pages may reach: offset plus limit. pgvector's
+/// HNSW index returns at most this many candidates (`hnsw.ef_search`).
+pub const MAX_VECTOR_SEARCH_DEPTH: u32 = 1000;
+
+/// A document stored in a script's vector collection
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct VectorDocument {
+    /// Identifier unique within the collection; storing it again replaces
+    /// the document
+    pub id: String,
+    /// Text the embedding was computed from
+    #[serde(default)]
+    pub text: Option<String>,
+    /// JSON object `vectors.search` can filter on
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    /// Computed from `text` with `embeddings.create` when missing
+    #[serde(default)]
+    pub embedding: Vec<f32>,
+}
+
+/// Options of `vectors.search`
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct VectorSearchOptions {
+    pub limit: Option<u32>,
+    /// Matches to skip, usually the `nextOffset` of the previous page
+    pub offset: Option<u32>,
+    /// Only documents whose metadata contains this JSON object
+    pub filter: Option<serde_json::Value>,
+    /// Only matches scoring at least this much
+    pub min_score: Option<f64>,
+}
+
+/// One page of `vectors.search` matches
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VectorSearchPage {
+    /// Best match first
+    pub matches: Vec<VectorMatch>,
+    /// Offset of the next page, or None when this is the last one
+    pub next_offset: Option<u32>,
+}
+
+/// A document found by `vectors.search`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VectorMatch {
+    pub id: String,
+    pub text: Option<String>,
+    pub metadata: serde_json::Value,
+    /// Cosine similarity to the query vector, from -1 to 1
+    pub score: f64,
+}
+
 static DYNAMIC_SCRIPTS: OnceLock<Mutex<HashMap<String, ScriptMetadata>>> = OnceLock::new();
 
 static SCRIPT_PRIVILEGE_OVERRIDES: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
@@ -564,21 +1104,74 @@ where
     Ok(scripts)
 }
 
-/// Database-backed delete script
-async fn db_delete_script<'e, E>(executor: E, uri: &str) -> AppResult<bool>
+/// Database-backed soft delete of a script: moves the script row, its assets,
+/// ownership and table metadata into the trash in a single statement. The
+/// whole scripts row is kept in `script_row` so restoring brings back every
+/// column.
+/// Script-owned tables are left in place until the trash entry is purged.
+async fn db_trash_script<'e, E>(executor: E, uri: &str) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let result = sqlx::query(
         r#"
-        DELETE FROM scripts WHERE uri = $1
+        WITH owners AS (
+            SELECT COALESCE(array_agg(user_id ORDER BY created_at), '{}') AS ids
+            FROM script_owners WHERE script_uri = $1
+        ),
+        collaborators AS (
+            SELECT COALESCE(array_agg(user_id ORDER BY created_at), '{}') AS ids
+            FROM script_collaborators WHERE script_uri = $1
+        ),
+        tables AS (
+            SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                'logical_table_name', logical_table_name,
+                'physical_table_name', physical_table_name,
+                'schema_json', schema_json,
+                'created_at', created_at
+            )), '[]'::jsonb) AS rows
+            FROM script_tables WHERE script_uri = $1
+        ),
+        trashed_assets AS (
+            INSERT INTO asset_trash (uri, script_uri, mimetype, content, name, headers, created_at, deleted_at, deleted_with_script)
+            SELECT uri, script_uri, mimetype, content, name, headers, created_at, NOW(), TRUE
+            FROM assets WHERE script_uri = $1
+            ON CONFLICT (uri) DO UPDATE SET
+                script_uri = EXCLUDED.script_uri,
+                mimetype = EXCLUDED.mimetype,
+                content = EXCLUDED.content,
+                name = EXCLUDED.name,
+                headers = EXCLUDED.headers,
+                created_at = EXCLUDED.created_at,
+                deleted_at = EXCLUDED.deleted_at,
+                deleted_with_script = TRUE
+        ),
+        removed AS (
+            DELETE FROM scripts WHERE uri = $1
+            RETURNING *
+        )
+        INSERT INTO script_trash (uri, content, name, privileged, description, tags, owners, collaborators, script_tables, script_row, created_at, deleted_at)
+        SELECT r.uri, r.content, r.name, r.privileged, r.description, r.tags, o.ids, c.ids, t.rows, to_jsonb(r), r.created_at, NOW()
+        FROM removed r, owners o, collaborators c, tables t
+        ON CONFLICT (uri) DO UPDATE SET
+            content = EXCLUDED.content,
+            name = EXCLUDED.name,
+            privileged = EXCLUDED.privileged,
+            description = EXCLUDED.description,
+            tags = EXCLUDED.tags,
+            owners = EXCLUDED.owners,
+            collaborators = EXCLUDED.collaborators,
+            script_tables = EXCLUDED.script_tables,
+            script_row = EXCLUDED.script_row,
+            created_at = EXCLUDED.created_at,
+            deleted_at = EXCLUDED.deleted_at
         "#,
     )
     .bind(uri)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error deleting script: {}", e);
+        error!("Database error moving script to trash: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -587,7 +1180,7 @@ where
 
     let existed = result.rows_affected() > 0;
     if existed {
-        debug!("Deleted script from database: {}", uri);
+        debug!("Moved script to trash: {}", uri);
     } else {
         debug!("Script not found in database for deletion: {}", uri);
     }
@@ -595,6 +1188,290 @@ where
     Ok(existed)
 }
 
+/// Database-backed restore of a trashed script together with the assets,
+/// owners, collaborators and table metadata that were trashed with it.
+/// Runs several statements, so the caller provides a transaction.
+async fn db_restore_script(conn: &mut PgConnection, uri: &str) -> AppResult<bool> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error restoring script: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM scripts WHERE uri = $1)")
+        .bind(uri)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(map_db_err)?;
+    if exists {
+        return Err(AppError::validation(
+            "uri",
+            format!("A script with URI '{}' already exists", uri),
+        ));
+    }
+
+    // Scripts trashed before script_row existed only have the basic columns
+    let mut restored = 0;
+    for statement in [
+        r#"
+        INSERT INTO scripts
+        SELECT (jsonb_populate_record(
+            NULL::scripts,
+            script_row || jsonb_build_object('updated_at', NOW())
+        )).*
+        FROM script_trash WHERE uri = $1 AND script_row IS NOT NULL
+        "#,
+        r#"
+        INSERT INTO scripts (uri, content, name, privileged, description, tags, created_at, updated_at)
+        SELECT uri, content, name, privileged, description, tags, created_at, NOW()
+        FROM script_trash WHERE uri = $1 AND script_row IS NULL
+        "#,
+    ] {
+        restored += sqlx::query(statement)
+            .bind(uri)
+            .execute(&mut *conn)
+            .await
+            .map_err(map_db_err)?
+            .rows_affected();
+    }
+    if restored == 0 {
+        return Ok(false);
+    }
+
+    for statement in [
+        r#"
+        INSERT INTO script_owners (script_uri, user_id)
+        SELECT uri, unnest(owners) FROM script_trash WHERE uri = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        r#"
+        INSERT INTO script_collaborators (script_uri, user_id)
+        SELECT uri, unnest(collaborators) FROM script_trash WHERE uri = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        r#"
+        INSERT INTO script_tables (script_uri, logical_table_name, physical_table_name, schema_json, created_at)
+        SELECT s.uri, t->>'logical_table_name', t->>'physical_table_name', t->'schema_json',
+               (t->>'created_at')::timestamptz
+        FROM script_trash s, jsonb_array_elements(s.script_tables) AS t
+        WHERE s.uri = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        r#"
+        INSERT INTO assets (uri, mimetype, content, name, script_uri, headers, created_at, updated_at)
+        SELECT uri, mimetype, content, name, script_uri, headers, created_at, NOW()
+        FROM asset_trash WHERE script_uri = $1 AND deleted_with_script
+        ON CONFLICT (uri) DO NOTHING
+        "#,
+        // Assets whose URI was taken in the meantime stay in the trash
+        r#"
+        DELETE FROM asset_trash
+        WHERE script_uri = $1 AND deleted_with_script
+          AND uri IN (SELECT uri FROM assets WHERE script_uri = $1)
+        "#,
+        r#"
+        DELETE FROM script_trash WHERE uri = $1
+        "#,
+    ] {
+        sqlx::query(statement)
+            .bind(uri)
+            .execute(&mut *conn)
+            .await
+            .map_err(map_db_err)?;
+    }
+
+    debug!("Restored script from trash: {}", uri);
+    Ok(true)
+}
+
+/// Database-backed getter for the staging slot of a script
+async fn db_get_script_staging<'e, E>(executor: E, uri: &str) -> AppResult<Option<ScriptStaging>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error getting script staging: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    let row = sqlx::query(
+        r#"
+        SELECT script_uri, staged_content, staged_at, staged_by, staged_init_error,
+               staged_routes, previous_content, promoted_at
+        FROM script_staging WHERE script_uri = $1
+        "#,
+    )
+    .bind(uri)
+    .fetch_optional(executor)
+    .await
+    .map_err(map_db_err)?;
+
+    row.map(|row| {
+        Ok(ScriptStaging {
+            uri: row.try_get("script_uri")?,
+            staged_content: row.try_get("staged_content")?,
+            staged_at: row.try_get("staged_at")?,
+            staged_by: row.try_get("staged_by")?,
+            staged_init_error: row.try_get("staged_init_error")?,
+            staged_routes: row.try_get("staged_routes")?,
+            previous_content: row.try_get("previous_content")?,
+            promoted_at: row.try_get("promoted_at")?,
+        })
+    })
+    .transpose()
+    .map_err(map_db_err)
+}
+
+/// Database-backed staging of a script version; false when the script does
+/// not exist
+async fn db_stage_script<'e, E>(executor: E, staging: &ScriptStaging) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO script_staging
+            (script_uri, staged_content, staged_at, staged_by, staged_init_error, staged_routes)
+        SELECT uri, $2, NOW(), $3, $4, $5 FROM scripts WHERE uri = $1
+        ON CONFLICT (script_uri) DO UPDATE SET
+            staged_content = EXCLUDED.staged_content,
+            staged_at = EXCLUDED.staged_at,
+            staged_by = EXCLUDED.staged_by,
+            staged_init_error = EXCLUDED.staged_init_error,
+            staged_routes = EXCLUDED.staged_routes
+        "#,
+    )
+    .bind(&staging.uri)
+    .bind(&staging.staged_content)
+    .bind(&staging.staged_by)
+    .bind(&staging.staged_init_error)
+    .bind(&staging.staged_routes)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error staging script: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed removal of the staged version of a script
+async fn db_discard_staged_script<'e, E>(executor: E, uri: &str) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        UPDATE script_staging
+        SET staged_content = NULL, staged_at = NULL, staged_by = NULL,
+            staged_init_error = NULL, staged_routes = '{}'
+        WHERE script_uri = $1 AND staged_content IS NOT NULL
+        "#,
+    )
+    .bind(uri)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error discarding staged script: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed swap of the live version of a script with its staged
+/// version (`promote`) or with the version the last swap replaced. The
+/// replaced version is kept as the previous one. False when there is
+/// nothing to swap in.
+async fn db_swap_script_version(
+    conn: &mut PgConnection,
+    uri: &str,
+    promote: bool,
+) -> AppResult<bool> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error swapping script version: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    let row = sqlx::query(
+        r#"
+        SELECT s.content, st.staged_content, st.previous_content
+        FROM scripts s JOIN script_staging st ON st.script_uri = s.uri
+        WHERE s.uri = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(uri)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(map_db_err)?;
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let live: String = row.try_get("content").map_err(map_db_err)?;
+    let replacement: Option<String> = row
+        .try_get(if promote {
+            "staged_content"
+        } else {
+            "previous_content"
+        })
+        .map_err(map_db_err)?;
+    let Some(replacement) = replacement else {
+        return Ok(false);
+    };
+
+    sqlx::query("UPDATE scripts SET content = $2, updated_at = NOW() WHERE uri = $1")
+        .bind(uri)
+        .bind(&replacement)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_db_err)?;
+
+    let update_slot = if promote {
+        r#"
+        UPDATE script_staging
+        SET previous_content = $2, promoted_at = NOW(),
+            staged_content = NULL, staged_at = NULL, staged_by = NULL,
+            staged_init_error = NULL, staged_routes = '{}'
+        WHERE script_uri = $1
+        "#
+    } else {
+        r#"
+        UPDATE script_staging
+        SET previous_content = $2, promoted_at = NOW()
+        WHERE script_uri = $1
+        "#
+    };
+    sqlx::query(update_slot)
+        .bind(uri)
+        .bind(&live)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_db_err)?;
+
+    debug!(
+        "Swapped {} version of script: {}",
+        if promote { "staged" } else { "previous" },
+        uri
+    );
+    Ok(true)
+}
+
 /// Database-backed getter for script privilege flag
 async fn db_get_script_privileged<'e, E>(executor: E, uri: &str) -> AppResult<Option<bool>>
 where
@@ -908,775 +1785,817 @@ where
     Ok(owners_map)
 }
 
-/// Database-backed set shared storage item
-async fn db_set_script_properties_item(
-    mut executor: crate::database::TransactionExecutor<'_>,
-    script_uri: &str,
-    key: &str,
-    value: &str,
-) -> AppResult<()> {
-    let now = chrono::Utc::now();
-
-    // Try to update existing item
-    let update_result = match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                UPDATE script_properties
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND key = $4
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(key)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                UPDATE script_properties
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND key = $4
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(key)
-            .execute(pool)
-            .await
-        }
-    }
+/// Database-backed add script collaborator
+async fn db_add_script_collaborator<'e, E>(executor: E, uri: &str, user_id: &str) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    // Insert collaborator record (ignore if already exists)
+    sqlx::query(
+        r#"
+        INSERT INTO script_collaborators (script_uri, user_id, created_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (script_uri, user_id) DO NOTHING
+        "#,
+    )
+    .bind(uri)
+    .bind(user_id)
+    .execute(executor)
+    .await
     .map_err(|e| {
-        error!("Database error updating shared storage: {}", e);
+        error!("Database error adding script collaborator: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if update_result.rows_affected() > 0 {
-        debug!(
-            "Updated shared storage item in database: {}:{}",
-            script_uri, key
-        );
-        return Ok(());
-    }
+    debug!("Added collaborator {} to script {}", user_id, uri);
+    Ok(())
+}
 
-    // Item doesn't exist, create new one
-    match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                INSERT INTO script_properties (script_uri, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $4)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                INSERT INTO script_properties (script_uri, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $4)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(pool)
-            .await
+/// Database-backed remove script collaborator
+async fn db_remove_script_collaborator<'e, E>(
+    executor: E,
+    uri: &str,
+    user_id: &str,
+) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        DELETE FROM script_collaborators WHERE script_uri = $1 AND user_id = $2
+        "#,
+    )
+    .bind(uri)
+    .bind(user_id)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error removing script collaborator: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
+    })?;
+
+    let existed = result.rows_affected() > 0;
+    if existed {
+        debug!("Removed collaborator {} from script {}", user_id, uri);
+    } else {
+        debug!("Collaborator {} was not found for script {}", user_id, uri);
     }
+
+    Ok(existed)
+}
+
+/// Database-backed get script collaborators
+async fn db_get_script_collaborators<'e, E>(executor: E, uri: &str) -> AppResult<Vec<String>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT user_id
+        FROM script_collaborators
+        WHERE script_uri = $1
+        ORDER BY created_at ASC
+        "#,
+    )
+    .bind(uri)
+    .fetch_all(executor)
+    .await
     .map_err(|e| {
-        error!("Database error creating shared storage item: {}", e);
+        error!("Database error getting script collaborators: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Created new shared storage item in database: {}:{}",
-        script_uri, key
-    );
-    Ok(())
+    let collaborators = rows
+        .into_iter()
+        .map(|row| {
+            row.try_get("user_id").map_err(|e| {
+                error!("Database error parsing user_id: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })
+        })
+        .collect::<Result<Vec<String>, AppError>>()?;
+
+    Ok(collaborators)
 }
 
-/// Database-backed get shared storage item
-async fn db_get_script_properties_item<'e, E>(
+/// Database-backed check if user collaborates on script, themselves or
+/// through a group (`group:<name>` collaborator entries)
+async fn db_user_collaborates_on_script<'e, E>(
     executor: E,
-    script_uri: &str,
-    key: &str,
-) -> AppResult<Option<String>>
+    uri: &str,
+    user_id: &str,
+) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let row = sqlx::query(
         r#"
-        SELECT value FROM script_properties WHERE script_uri = $1 AND key = $2
+        SELECT EXISTS(
+            SELECT 1
+            FROM script_collaborators
+            WHERE script_uri = $1 AND user_id = $2
+        ) OR EXISTS(
+            SELECT 1
+            FROM script_collaborators c
+            JOIN user_group_members m ON c.user_id = $3 || m.group_name
+            WHERE c.script_uri = $1 AND m.user_id = $2
+        ) as collaborates
         "#,
     )
-    .bind(script_uri)
-    .bind(key)
+    .bind(uri)
+    .bind(user_id)
+    .bind(crate::user_repository::GROUP_PRINCIPAL_PREFIX)
+    .fetch_one(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error checking script collaborator: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    let collaborates: bool = row.try_get("collaborates").map_err(|e| {
+        error!("Database error parsing collaborator check: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(collaborates)
+}
+
+/// Database-backed get script description and tags
+async fn db_get_script_labels<'e, E>(executor: E, uri: &str) -> AppResult<Option<ScriptLabels>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let row = sqlx::query(
+        r#"
+        SELECT description, tags FROM scripts WHERE uri = $1
+        "#,
+    )
+    .bind(uri)
     .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error getting shared storage item: {}", e);
+        error!("Database error getting script labels: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if let Some(row) = row {
-        let value: String = row.try_get("value").map_err(|e| {
-            error!("Database error getting value: {}", e);
+    match row {
+        Some(row) => Ok(Some(ScriptLabels {
+            description: row.try_get("description").map_err(|e| {
+                error!("Database error parsing script description: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?,
+            tags: row.try_get("tags").map_err(|e| {
+                error!("Database error parsing script tags: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Fetch description and tags for every script in one query
+async fn db_get_all_script_labels<'e, E>(executor: E) -> AppResult<HashMap<String, ScriptLabels>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT uri, description, tags FROM scripts
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error getting all script labels: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    let mut labels = HashMap::new();
+    for row in rows {
+        let uri: String = row.try_get("uri").map_err(|e| {
+            error!("Database error parsing script uri: {}", e);
             AppError::Database {
                 message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
-        Ok(Some(value))
-    } else {
-        Ok(None)
+        labels.insert(
+            uri,
+            ScriptLabels {
+                description: row.try_get("description").map_err(|e| {
+                    error!("Database error parsing script description: {}", e);
+                    AppError::Database {
+                        message: format!("Database error: {}", e),
+                        source: None,
+                    }
+                })?,
+                tags: row.try_get("tags").map_err(|e| {
+                    error!("Database error parsing script tags: {}", e);
+                    AppError::Database {
+                        message: format!("Database error: {}", e),
+                        source: None,
+                    }
+                })?,
+            },
+        );
     }
+    Ok(labels)
 }
 
-/// Database-backed remove shared storage item
-async fn db_remove_script_properties_item<'e, E>(
+/// Database-backed set script description and tags. Returns false when the
+/// script does not exist.
+async fn db_set_script_labels<'e, E>(
     executor: E,
-    script_uri: &str,
-    key: &str,
+    uri: &str,
+    labels: &ScriptLabels,
 ) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let result = sqlx::query(
         r#"
-        DELETE FROM script_properties WHERE script_uri = $1 AND key = $2
+        UPDATE scripts SET description = $1, tags = $2, updated_at = $3 WHERE uri = $4
         "#,
     )
-    .bind(script_uri)
-    .bind(key)
+    .bind(labels.description.as_deref())
+    .bind(&labels.tags)
+    .bind(chrono::Utc::now())
+    .bind(uri)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error removing shared storage item: {}", e);
+        error!("Database error updating script labels: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let existed = result.rows_affected() > 0;
-    if existed {
-        debug!(
-            "Removed script storage item from database: {}:{}",
-            script_uri, key
-        );
-    } else {
-        debug!(
-            "Script storage item not found in database for removal: {}:{}",
-            script_uri, key
-        );
-    }
-
-    Ok(existed)
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed clear all shared storage for a script
-async fn db_clear_script_properties<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
+/// Database-backed get of the source map uploaded for a script
+async fn db_get_script_source_map<'e, E>(
+    executor: E,
+    uri: &str,
+) -> AppResult<Option<StoredSourceMap>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    sqlx::query(
+    let row = sqlx::query(
         r#"
-        DELETE FROM script_properties WHERE script_uri = $1
+        SELECT source_map, source_map_content_hash FROM scripts
+        WHERE uri = $1 AND source_map IS NOT NULL
         "#,
     )
-    .bind(script_uri)
-    .execute(executor)
+    .bind(uri)
+    .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error clearing shared storage: {}", e);
+        error!("Database error getting script source map: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Cleared all script storage items from database for script: {}",
-        script_uri
-    );
-    Ok(())
-}
-
-/// Database-backed set personal storage item
-async fn db_set_user_properties_item(
-    mut executor: crate::database::TransactionExecutor<'_>,
-    script_uri: &str,
-    user_id: &str,
-    key: &str,
-    value: &str,
-) -> AppResult<()> {
-    let now = chrono::Utc::now();
-
-    // Try to update existing item
-    let update_result = match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                UPDATE user_properties
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND user_id = $4 AND key = $5
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(user_id)
-            .bind(key)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                UPDATE user_properties
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND user_id = $4 AND key = $5
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(user_id)
-            .bind(key)
-            .execute(pool)
-            .await
-        }
-    }
-    .map_err(|e| {
-        error!("Database error updating personal storage: {}", e);
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let map_err = |e: sqlx::Error| {
+        error!("Database error parsing script source map: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
-    })?;
-
-    if update_result.rows_affected() > 0 {
-        debug!(
-            "Updated personal storage item in database: {}:{}:{}",
-            script_uri, user_id, key
-        );
-        return Ok(());
-    }
-
-    // Item doesn't exist, create new one
-    match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                INSERT INTO user_properties (script_uri, user_id, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $5)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(user_id)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                INSERT INTO user_properties (script_uri, user_id, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $5)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(user_id)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(pool)
-            .await
-        }
-    }
+    };
+    Ok(Some(StoredSourceMap {
+        source_map: row.try_get("source_map").map_err(map_err)?,
+        content_hash: row
+            .try_get::<Option<String>, _>("source_map_content_hash")
+            .map_err(map_err)?
+            .unwrap_or_default(),
+    }))
+}
+
+/// Database-backed set or clear of the source map uploaded for a script.
+/// Returns false when the script does not exist.
+async fn db_set_script_source_map<'e, E>(
+    executor: E,
+    uri: &str,
+    source_map: Option<&StoredSourceMap>,
+) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        UPDATE scripts SET source_map = $1, source_map_content_hash = $2 WHERE uri = $3
+        "#,
+    )
+    .bind(source_map.map(|map| map.source_map.as_str()))
+    .bind(source_map.map(|map| map.content_hash.as_str()))
+    .bind(uri)
+    .execute(executor)
+    .await
     .map_err(|e| {
-        error!("Database error inserting personal storage item: {}", e);
+        error!("Database error updating script source map: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Inserted personal storage item to database: {}:{}:{}",
-        script_uri, user_id, key
-    );
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed get personal storage item
-async fn db_get_user_properties_item<'e, E>(
+/// Sum the shared storage and asset bytes of a script, leaving out the
+/// shared storage key and asset that are about to be replaced
+async fn db_get_script_storage_usage<'e, E>(
     executor: E,
     script_uri: &str,
-    user_id: &str,
-    key: &str,
-) -> AppResult<Option<String>>
+    exclude_key: Option<&str>,
+    exclude_asset_uri: Option<&str>,
+) -> AppResult<ScriptStorageUsage>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let row = sqlx::query(
         r#"
-        SELECT value FROM user_properties WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        SELECT
+            COALESCE((
+                SELECT SUM(octet_length(key) + octet_length(value))
+                FROM script_properties
+                WHERE script_uri = $1
+                  AND ($2::TEXT IS NULL OR key <> $2)
+                  AND (expires_at IS NULL OR expires_at > NOW())
+            ), 0)::BIGINT AS properties_bytes,
+            COALESCE((
+                SELECT SUM(octet_length(content))
+                FROM assets
+                WHERE script_uri = $1 AND ($3::TEXT IS NULL OR uri <> $3)
+            ), 0)::BIGINT AS asset_bytes,
+            (SELECT storage_quota_bytes FROM scripts WHERE uri = $1) AS quota_override
         "#,
     )
     .bind(script_uri)
-    .bind(user_id)
-    .bind(key)
-    .fetch_optional(executor)
+    .bind(exclude_key)
+    .bind(exclude_asset_uri)
+    .fetch_one(executor)
     .await
     .map_err(|e| {
-        error!("Database error getting personal storage item: {}", e);
+        error!("Database error getting script storage usage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if let Some(row) = row {
-        let value: String = row.try_get("value").map_err(|e| {
-            error!("Database error getting value: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        Ok(Some(value))
-    } else {
-        Ok(None)
-    }
+    let parse_error = |e: sqlx::Error| {
+        error!("Database error parsing script storage usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let properties_bytes: i64 = row.try_get("properties_bytes").map_err(parse_error)?;
+    let asset_bytes: i64 = row.try_get("asset_bytes").map_err(parse_error)?;
+    let quota_override: Option<i64> = row.try_get("quota_override").map_err(parse_error)?;
+    let quota_override = quota_override.map(|q| q.max(0) as u64);
+    let quota = quota_override.unwrap_or_else(default_storage_quota_bytes);
+    let properties_bytes = properties_bytes.max(0) as u64;
+    let asset_bytes = asset_bytes.max(0) as u64;
+
+    Ok(ScriptStorageUsage {
+        script_uri: script_uri.to_string(),
+        properties_bytes,
+        asset_bytes,
+        used_bytes: properties_bytes + asset_bytes,
+        quota_bytes: (quota > 0).then_some(quota),
+        quota_override,
+    })
 }
 
-/// Database-backed remove personal storage item
-async fn db_remove_user_properties_item<'e, E>(
+/// Sum the live shared storage bytes stored under a tenant's key prefix by
+/// all scripts, leaving out the item of `exclude` (script URI, key) that is
+/// about to be replaced
+async fn db_get_tenant_storage_bytes<'e, E>(
     executor: E,
-    script_uri: &str,
-    user_id: &str,
-    key: &str,
-) -> AppResult<bool>
+    prefix: &str,
+    exclude: Option<(&str, &str)>,
+) -> AppResult<u64>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let result = sqlx::query(
+    let bytes: i64 = sqlx::query_scalar(
         r#"
-        DELETE FROM user_properties WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        SELECT COALESCE(SUM(octet_length(key) + octet_length(value)), 0)::BIGINT
+        FROM script_properties
+        WHERE starts_with(key, $1)
+          AND ($2::TEXT IS NULL OR NOT (script_uri = $2 AND key = $3))
+          AND (expires_at IS NULL OR expires_at > NOW())
         "#,
     )
-    .bind(script_uri)
-    .bind(user_id)
-    .bind(key)
-    .execute(executor)
+    .bind(prefix)
+    .bind(exclude.map(|(script_uri, _)| script_uri))
+    .bind(exclude.map(|(_, key)| key))
+    .fetch_one(executor)
     .await
     .map_err(|e| {
-        error!("Database error removing personal storage item: {}", e);
+        error!("Database error getting tenant storage usage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let existed = result.rows_affected() > 0;
-    if existed {
-        debug!(
-            "Removed personal storage item from database: {}:{}:{}",
-            script_uri, user_id, key
-        );
-    } else {
-        debug!(
-            "Personal storage item not found in database for removal: {}:{}:{}",
-            script_uri, user_id, key
-        );
-    }
-
-    Ok(existed)
+    Ok(bytes.max(0) as u64)
 }
 
-/// Database-backed clear all personal storage for a script and user
-async fn db_clear_user_properties<'e, E>(
-    executor: E,
-    script_uri: &str,
-    user_id: &str,
-) -> AppResult<()>
+/// Live shared storage bytes of every tenant, keyed by tenant ID
+async fn db_list_tenant_storage_bytes<'e, E>(executor: E) -> AppResult<HashMap<String, u64>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    sqlx::query(
+    let rows: Vec<(String, i64)> = sqlx::query_as(
         r#"
-        DELETE FROM user_properties WHERE script_uri = $1 AND user_id = $2
+        SELECT split_part(key, '/', 2) AS tenant,
+               SUM(octet_length(key) + octet_length(value))::BIGINT AS bytes
+        FROM script_properties
+        WHERE starts_with(key, 'tenant/')
+          AND (expires_at IS NULL OR expires_at > NOW())
+        GROUP BY 1
         "#,
     )
-    .bind(script_uri)
-    .bind(user_id)
-    .execute(executor)
+    .fetch_all(executor)
     .await
     .map_err(|e| {
-        error!("Database error clearing personal storage: {}", e);
+        error!("Database error listing tenant storage usage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Cleared all personal storage items from database for script {} and user {}",
-        script_uri, user_id
-    );
-    Ok(())
+    Ok(rows
+        .into_iter()
+        .filter(|(tenant, _)| !tenant.is_empty())
+        .map(|(tenant, bytes)| (tenant, bytes.max(0) as u64))
+        .collect())
 }
 
-/// Database-backed set script secret item
-async fn db_set_script_secret(
-    mut executor: crate::database::TransactionExecutor<'_>,
-    script_uri: &str,
-    key: &str,
-    value: &str,
-) -> AppResult<()> {
-    let now = chrono::Utc::now();
-
-    // Encrypt the value if secret encryption is configured
-    let stored_value: String = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
-        let encrypted = enc.encrypt_field(value).map_err(|e| AppError::Internal {
-            message: format!("Failed to encrypt secret value: {}", e),
-        })?;
-        serde_json::to_string(&encrypted).map_err(|e| AppError::Internal {
-            message: format!("Failed to serialize encrypted secret: {}", e),
-        })?
-    } else {
-        value.to_string()
-    };
-    let value: &str = &stored_value;
-
-    // Try to update existing item
-    let update_result = match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                UPDATE script_secrets
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND key = $4
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(key)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                UPDATE script_secrets
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND key = $4
-                "#,
-            )
-            .bind(value)
-            .bind(now)
-            .bind(script_uri)
-            .bind(key)
-            .execute(pool)
-            .await
-        }
-    }
+/// Shared storage and asset bytes of every script, keyed by script URI
+async fn db_list_script_storage_bytes(pool: &PgPool) -> AppResult<HashMap<String, u64>> {
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        r#"
+        SELECT script_uri, SUM(bytes)::BIGINT AS bytes
+        FROM (
+            SELECT script_uri, octet_length(key) + octet_length(value) AS bytes
+            FROM script_properties
+            WHERE expires_at IS NULL OR expires_at > NOW()
+            UNION ALL
+            SELECT script_uri, octet_length(content) AS bytes
+            FROM assets
+        ) stored
+        GROUP BY script_uri
+        "#,
+    )
+    .fetch_all(pool)
+    .await
     .map_err(|e| {
-        error!("Database error updating script secret: {}", e);
+        error!("Database error listing script storage usage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if update_result.rows_affected() > 0 {
-        debug!("Updated script secret in database: {}:{}", script_uri, key);
-        return Ok(());
-    }
+    Ok(rows
+        .into_iter()
+        .map(|(script_uri, bytes)| (script_uri, bytes.max(0) as u64))
+        .collect())
+}
 
-    // Item doesn't exist, create new one
-    match executor {
-        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
-            sqlx::query(
-                r#"
-                INSERT INTO script_secrets (script_uri, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $4)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(&mut ***tx)
-            .await
-        }
-        crate::database::TransactionExecutor::Pool(pool) => {
-            sqlx::query(
-                r#"
-                INSERT INTO script_secrets (script_uri, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $4)
-                "#,
-            )
-            .bind(script_uri)
-            .bind(key)
-            .bind(value)
-            .bind(now)
-            .execute(pool)
-            .await
-        }
-    }
+/// Set or clear (None) the storage quota override of a script
+async fn db_set_script_storage_quota<'e, E>(
+    executor: E,
+    uri: &str,
+    quota_bytes: Option<i64>,
+) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        UPDATE scripts SET storage_quota_bytes = $1, updated_at = $2 WHERE uri = $3
+        "#,
+    )
+    .bind(quota_bytes)
+    .bind(chrono::Utc::now())
+    .bind(uri)
+    .execute(executor)
+    .await
     .map_err(|e| {
-        error!("Database error creating script secret: {}", e);
+        error!("Database error updating script storage quota: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Created new script secret in database: {}:{}",
-        script_uri, key
-    );
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed get script secret
-async fn db_get_script_secret<'e, E>(
-    executor: E,
-    script_uri: &str,
-    key: &str,
-) -> AppResult<Option<String>>
+/// Execution timeout override of a script
+async fn db_get_script_execution_timeout<'e, E>(executor: E, uri: &str) -> AppResult<Option<u64>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let row = sqlx::query(
-        r#"
-        SELECT value FROM script_secrets WHERE script_uri = $1 AND key = $2
-        "#,
+    let timeout_ms: Option<Option<i64>> =
+        sqlx::query_scalar("SELECT execution_timeout_ms FROM scripts WHERE uri = $1")
+            .bind(uri)
+            .fetch_optional(executor)
+            .await
+            .map_err(|e| {
+                error!("Database error getting script execution timeout: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+
+    Ok(timeout_ms.flatten().map(|ms| ms.max(1) as u64))
+}
+
+/// Execution timeout overrides of every script that has one
+async fn db_get_all_script_execution_timeouts<'e, E>(executor: E) -> AppResult<HashMap<String, u64>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT uri, execution_timeout_ms FROM scripts WHERE execution_timeout_ms IS NOT NULL",
     )
-    .bind(script_uri)
-    .bind(key)
-    .fetch_optional(executor)
+    .fetch_all(executor)
     .await
     .map_err(|e| {
-        error!("Database error getting script secret: {}", e);
+        error!("Database error getting script execution timeouts: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if let Some(row) = row {
-        let raw: String = row.try_get("value").map_err(|e| {
-            error!("Database error getting value: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        // Decrypt if encryption is configured and the value is in encrypted form
-        let value = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
-            match serde_json::from_str::<crate::security::encryption::EncryptedData>(&raw) {
-                Ok(encrypted) => match enc.decrypt_field(&encrypted) {
-                    Ok(plain) => plain,
-                    Err(e) => {
-                        error!(
-                            "Failed to decrypt script secret {}:{}: {}",
-                            script_uri, key, e
-                        );
-                        raw
-                    }
-                },
-                Err(_) => raw, // stored before encryption was enabled — return as-is
-            }
-        } else {
-            raw
-        };
-        Ok(Some(value))
-    } else {
-        Ok(None)
-    }
+    Ok(rows
+        .into_iter()
+        .map(|(uri, ms)| (uri, ms.max(1) as u64))
+        .collect())
 }
 
-/// Database-backed remove script secret
-async fn db_remove_script_secret<'e, E>(executor: E, script_uri: &str, key: &str) -> AppResult<bool>
+/// Set or clear (None) the execution timeout override of a script
+async fn db_set_script_execution_timeout<'e, E>(
+    executor: E,
+    uri: &str,
+    timeout_ms: Option<i64>,
+) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let result = sqlx::query(
         r#"
-        DELETE FROM script_secrets WHERE script_uri = $1 AND key = $2
+        UPDATE scripts SET execution_timeout_ms = $1, updated_at = $2 WHERE uri = $3
         "#,
     )
-    .bind(script_uri)
-    .bind(key)
+    .bind(timeout_ms)
+    .bind(chrono::Utc::now())
+    .bind(uri)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error removing script secret: {}", e);
+        error!("Database error updating script execution timeout: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let existed = result.rows_affected() > 0;
-    if existed {
-        debug!(
-            "Removed script secret from database: {}:{}",
-            script_uri, key
-        );
-    } else {
-        debug!(
-            "Script secret not found in database for removal: {}:{}",
-            script_uri, key
-        );
-    }
+    Ok(result.rows_affected() > 0)
+}
 
-    Ok(existed)
+/// Memory limit override of a script
+async fn db_get_script_memory_limit<'e, E>(executor: E, uri: &str) -> AppResult<Option<u64>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let limit_bytes: Option<Option<i64>> =
+        sqlx::query_scalar("SELECT memory_limit_bytes FROM scripts WHERE uri = $1")
+            .bind(uri)
+            .fetch_optional(executor)
+            .await
+            .map_err(|e| {
+                error!("Database error getting script memory limit: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+
+    Ok(limit_bytes.flatten().map(|bytes| bytes.max(1) as u64))
 }
 
-/// Database-backed clear all script secrets for a script
-async fn db_clear_script_secrets<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
+/// Memory limit overrides of every script that has one
+async fn db_get_all_script_memory_limits<'e, E>(executor: E) -> AppResult<HashMap<String, u64>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    sqlx::query(
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT uri, memory_limit_bytes FROM scripts WHERE memory_limit_bytes IS NOT NULL",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error getting script memory limits: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(uri, bytes)| (uri, bytes.max(1) as u64))
+        .collect())
+}
+
+/// Set or clear (None) the memory limit override of a script
+async fn db_set_script_memory_limit<'e, E>(
+    executor: E,
+    uri: &str,
+    limit_bytes: Option<i64>,
+) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
         r#"
-        DELETE FROM script_secrets WHERE script_uri = $1
+        UPDATE scripts SET memory_limit_bytes = $1, updated_at = $2 WHERE uri = $3
         "#,
     )
-    .bind(script_uri)
+    .bind(limit_bytes)
+    .bind(chrono::Utc::now())
+    .bind(uri)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error clearing script secrets: {}", e);
+        error!("Database error updating script memory limit: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Cleared all script secrets from database for script: {}",
-        script_uri
-    );
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed list script secret keys for a script
-async fn db_list_script_secrets<'e, E>(executor: E, script_uri: &str) -> AppResult<Vec<String>>
+/// System flag of a script
+async fn db_get_script_system<'e, E>(executor: E, uri: &str) -> AppResult<Option<bool>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let rows = sqlx::query_scalar::<_, String>(
+    sqlx::query_scalar("SELECT system FROM scripts WHERE uri = $1")
+        .bind(uri)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| {
+            error!("Database error getting script system flag: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })
+}
+
+/// URIs of every system script
+async fn db_get_system_scripts<'e, E>(executor: E) -> AppResult<std::collections::HashSet<String>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let uris: Vec<String> = sqlx::query_scalar("SELECT uri FROM scripts WHERE system")
+        .fetch_all(executor)
+        .await
+        .map_err(|e| {
+            error!("Database error getting system scripts: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+
+    Ok(uris.into_iter().collect())
+}
+
+/// Set or clear the system flag of a script
+async fn db_set_script_system<'e, E>(executor: E, uri: &str, system: bool) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
         r#"
-        SELECT key FROM script_secrets WHERE script_uri = $1 ORDER BY key ASC
+        UPDATE scripts SET system = $1, updated_at = $2 WHERE uri = $3
         "#,
     )
-    .bind(script_uri)
-    .fetch_all(executor)
+    .bind(system)
+    .bind(chrono::Utc::now())
+    .bind(uri)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error listing script secret keys: {}", e);
+        error!("Database error updating script system flag: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!(
-        "Listed {} script secret keys from database for script: {}",
-        rows.len(),
-        script_uri
-    );
-    Ok(rows)
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed set user secret item
-async fn db_set_user_secret(
+/// Database-backed set shared storage item
+async fn db_set_script_properties_item(
     mut executor: crate::database::TransactionExecutor<'_>,
     script_uri: &str,
-    user_id: &str,
     key: &str,
     value: &str,
+    expires_at: Option<DateTime<Utc>>,
 ) -> AppResult<()> {
     let now = chrono::Utc::now();
 
-    // Encrypt the value if secret encryption is configured
-    let stored_value: String = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
-        let encrypted = enc.encrypt_field(value).map_err(|e| AppError::Internal {
-            message: format!("Failed to encrypt user secret value: {}", e),
-        })?;
-        serde_json::to_string(&encrypted).map_err(|e| AppError::Internal {
-            message: format!("Failed to serialize encrypted user secret: {}", e),
-        })?
-    } else {
-        value.to_string()
-    };
-    let value: &str = &stored_value;
-
     // Try to update existing item
     let update_result = match executor {
         crate::database::TransactionExecutor::Transaction(ref mut tx) => {
             sqlx::query(
                 r#"
-                UPDATE user_secrets
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND user_id = $4 AND key = $5
+                UPDATE script_properties
+                SET value = $1, updated_at = $2, expires_at = $5
+                WHERE script_uri = $3 AND key = $4
                 "#,
             )
             .bind(value)
             .bind(now)
             .bind(script_uri)
-            .bind(user_id)
             .bind(key)
+            .bind(expires_at)
             .execute(&mut ***tx)
             .await
         }
         crate::database::TransactionExecutor::Pool(pool) => {
             sqlx::query(
                 r#"
-                UPDATE user_secrets
-                SET value = $1, updated_at = $2
-                WHERE script_uri = $3 AND user_id = $4 AND key = $5
+                UPDATE script_properties
+                SET value = $1, updated_at = $2, expires_at = $5
+                WHERE script_uri = $3 AND key = $4
                 "#,
             )
             .bind(value)
             .bind(now)
             .bind(script_uri)
-            .bind(user_id)
             .bind(key)
+            .bind(expires_at)
             .execute(pool)
             .await
         }
     }
     .map_err(|e| {
-        error!("Database error updating user secret: {}", e);
+        error!("Database error updating shared storage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -1685,8 +2604,8 @@ async fn db_set_user_secret(
 
     if update_result.rows_affected() > 0 {
         debug!(
-            "Updated user secret in database: {}:{}:{}",
-            script_uri, user_id, key
+            "Updated shared storage item in database: {}:{}",
+            script_uri, key
         );
         return Ok(());
     }
@@ -1696,36 +2615,36 @@ async fn db_set_user_secret(
         crate::database::TransactionExecutor::Transaction(ref mut tx) => {
             sqlx::query(
                 r#"
-                INSERT INTO user_secrets (script_uri, user_id, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $5)
+                INSERT INTO script_properties (script_uri, key, value, created_at, updated_at, expires_at)
+                VALUES ($1, $2, $3, $4, $4, $5)
                 "#,
             )
             .bind(script_uri)
-            .bind(user_id)
             .bind(key)
             .bind(value)
             .bind(now)
+            .bind(expires_at)
             .execute(&mut ***tx)
             .await
         }
         crate::database::TransactionExecutor::Pool(pool) => {
             sqlx::query(
                 r#"
-                INSERT INTO user_secrets (script_uri, user_id, key, value, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $5)
+                INSERT INTO script_properties (script_uri, key, value, created_at, updated_at, expires_at)
+                VALUES ($1, $2, $3, $4, $4, $5)
                 "#,
             )
             .bind(script_uri)
-            .bind(user_id)
             .bind(key)
             .bind(value)
             .bind(now)
+            .bind(expires_at)
             .execute(pool)
             .await
         }
     }
     .map_err(|e| {
-        error!("Database error inserting user secret: {}", e);
+        error!("Database error creating shared storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -1733,34 +2652,39 @@ async fn db_set_user_secret(
     })?;
 
     debug!(
-        "Inserted user secret to database: {}:{}:{}",
-        script_uri, user_id, key
+        "Created new shared storage item in database: {}:{}",
+        script_uri, key
     );
     Ok(())
 }
 
-/// Database-backed get user secret
-async fn db_get_user_secret<'e, E>(
+/// Database-backed get shared storage item
+async fn db_get_script_properties_item<'e, E>(
     executor: E,
     script_uri: &str,
-    user_id: &str,
     key: &str,
 ) -> AppResult<Option<String>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
+    // Expired items read as missing and are deleted in the same statement
     let row = sqlx::query(
         r#"
-        SELECT value FROM user_secrets WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        WITH expired AS (
+            DELETE FROM script_properties
+            WHERE script_uri = $1 AND key = $2 AND expires_at <= NOW()
+        )
+        SELECT value FROM script_properties
+        WHERE script_uri = $1 AND key = $2
+          AND (expires_at IS NULL OR expires_at > NOW())
         "#,
     )
     .bind(script_uri)
-    .bind(user_id)
     .bind(key)
     .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error getting user secret: {}", e);
+        error!("Database error getting shared storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -1768,42 +2692,23 @@ where
     })?;
 
     if let Some(row) = row {
-        let raw: String = row.try_get("value").map_err(|e| {
+        let value: String = row.try_get("value").map_err(|e| {
             error!("Database error getting value: {}", e);
             AppError::Database {
                 message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
-        // Decrypt if encryption is configured and the value is in encrypted form
-        let value = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
-            match serde_json::from_str::<crate::security::encryption::EncryptedData>(&raw) {
-                Ok(encrypted) => match enc.decrypt_field(&encrypted) {
-                    Ok(plain) => plain,
-                    Err(e) => {
-                        error!(
-                            "Failed to decrypt user secret {}:{}:{}: {}",
-                            script_uri, user_id, key, e
-                        );
-                        raw
-                    }
-                },
-                Err(_) => raw, // stored before encryption was enabled — return as-is
-            }
-        } else {
-            raw
-        };
         Ok(Some(value))
     } else {
         Ok(None)
     }
 }
 
-/// Database-backed remove user secret
-async fn db_remove_user_secret<'e, E>(
+/// Database-backed remove shared storage item
+async fn db_remove_script_properties_item<'e, E>(
     executor: E,
     script_uri: &str,
-    user_id: &str,
     key: &str,
 ) -> AppResult<bool>
 where
@@ -1811,16 +2716,15 @@ where
 {
     let result = sqlx::query(
         r#"
-        DELETE FROM user_secrets WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        DELETE FROM script_properties WHERE script_uri = $1 AND key = $2
         "#,
     )
     .bind(script_uri)
-    .bind(user_id)
     .bind(key)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error removing user secret: {}", e);
+        error!("Database error removing shared storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -1830,187 +2734,90 @@ where
     let existed = result.rows_affected() > 0;
     if existed {
         debug!(
-            "Removed user secret from database: {}:{}:{}",
-            script_uri, user_id, key
+            "Removed script storage item from database: {}:{}",
+            script_uri, key
         );
     } else {
         debug!(
-            "User secret not found in database for removal: {}:{}:{}",
-            script_uri, user_id, key
+            "Script storage item not found in database for removal: {}:{}",
+            script_uri, key
         );
     }
 
     Ok(existed)
 }
 
-/// Database-backed clear all user secrets for a script and user
-async fn db_clear_user_secrets<'e, E>(executor: E, script_uri: &str, user_id: &str) -> AppResult<()>
-where
-    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
-{
-    sqlx::query(
-        r#"
-        DELETE FROM user_secrets WHERE script_uri = $1 AND user_id = $2
-        "#,
-    )
-    .bind(script_uri)
-    .bind(user_id)
-    .execute(executor)
-    .await
-    .map_err(|e| {
-        error!("Database error clearing user secrets: {}", e);
-        AppError::Database {
-            message: format!("Database error: {}", e),
-            source: None,
-        }
-    })?;
-
-    debug!(
-        "Cleared all user secrets from database for script {} and user {}",
-        script_uri, user_id
-    );
-    Ok(())
-}
-
-/// Database-backed insert log message
-async fn db_insert_log_message<'e, E>(
+/// Database-backed set the expiry of a live shared storage item
+async fn db_touch_script_properties_item<'e, E>(
     executor: E,
     script_uri: &str,
-    message: &str,
-    log_level: &str,
-) -> AppResult<()>
+    key: &str,
+    expires_at: DateTime<Utc>,
+) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    sqlx::query(
+    let result = sqlx::query(
         r#"
-        INSERT INTO logs (script_uri, message, log_level, created_at)
-        VALUES ($1, $2, $3, NOW())
+        UPDATE script_properties SET expires_at = $3
+        WHERE script_uri = $1 AND key = $2
+          AND (expires_at IS NULL OR expires_at > NOW())
         "#,
     )
     .bind(script_uri)
-    .bind(message)
-    .bind(log_level)
+    .bind(key)
+    .bind(expires_at)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error inserting log message: {}", e);
-        AppError::Database {
-            message: format!("Database error: {}", e),
-            source: None,
-        }
-    })?;
-
-    debug!(
-        "Inserted log message to database for script: {}",
-        script_uri
-    );
-    Ok(())
-}
-
-/// Database-backed fetch log messages for a script
-async fn db_fetch_log_messages<'e, E>(executor: E, script_uri: &str) -> AppResult<Vec<LogEntry>>
-where
-    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
-{
-    let rows = sqlx::query(
-        r#"
-        SELECT message, log_level, created_at FROM logs
-        WHERE script_uri = $1
-        ORDER BY created_at ASC
-        "#,
-    )
-    .bind(script_uri)
-    .fetch_all(executor)
-    .await
-    .map_err(|e| {
-        error!("Database error fetching log messages: {}", e);
+        error!("Database error touching shared storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let messages = rows
-        .into_iter()
-        .map(|row| {
-            let message: String = row.try_get("message")?;
-            let log_level: String = row.try_get("log_level")?;
-            let created_at: DateTime<Utc> = row.try_get("created_at")?;
-            // Convert chrono DateTime to SystemTime
-            let system_time = SystemTime::from(created_at);
-            Ok(LogEntry::new(message, log_level, system_time))
-        })
-        .collect::<Result<Vec<LogEntry>, sqlx::Error>>()
-        .map_err(|e| {
-            error!("Database error getting message/level/timestamp: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-
-    Ok(messages)
+    Ok(result.rows_affected() > 0)
 }
 
-/// Database-backed fetch all log messages
-async fn db_fetch_all_log_messages<'e, E>(executor: E) -> AppResult<Vec<LogEntry>>
+/// Database-backed delete expired shared storage items of all scripts
+async fn db_purge_expired_script_properties<'e, E>(executor: E) -> AppResult<u64>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let rows = sqlx::query(
+    let result = sqlx::query(
         r#"
-        SELECT message, log_level, created_at FROM logs
-        ORDER BY created_at DESC
+        DELETE FROM script_properties WHERE expires_at <= NOW()
         "#,
     )
-    .fetch_all(executor)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching all log messages: {}", e);
+        error!("Database error purging expired shared storage items: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let messages = rows
-        .into_iter()
-        .map(|row| {
-            let message: String = row.try_get("message")?;
-            let log_level: String = row.try_get("log_level")?;
-            let created_at: DateTime<Utc> = row.try_get("created_at")?;
-            // Convert chrono DateTime to SystemTime
-            let system_time = SystemTime::from(created_at);
-            Ok(LogEntry::new(message, log_level, system_time))
-        })
-        .collect::<Result<Vec<LogEntry>, sqlx::Error>>()
-        .map_err(|e| {
-            error!("Database error getting message/level/timestamp: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-
-    Ok(messages)
+    Ok(result.rows_affected())
 }
 
-/// Database-backed clear log messages for a script
-async fn db_clear_log_messages<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
+/// Database-backed clear all shared storage for a script
+async fn db_clear_script_properties<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     sqlx::query(
         r#"
-        DELETE FROM logs WHERE script_uri = $1
+        DELETE FROM script_properties WHERE script_uri = $1
         "#,
     )
     .bind(script_uri)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error clearing log messages: {}", e);
+        error!("Database error clearing shared storage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -2018,89 +2825,95 @@ where
     })?;
 
     debug!(
-        "Cleared log messages from database for script: {}",
+        "Cleared all script storage items from database for script: {}",
         script_uri
     );
     Ok(())
 }
 
-/// Database-backed prune log messages (keep only latest 20 per script)
-async fn db_prune_log_messages<'e, E>(executor: E) -> AppResult<()>
+/// Database-backed clear of the shared storage items whose key starts with
+/// `prefix`
+async fn db_clear_script_properties_prefix<'e, E>(
+    executor: E,
+    script_uri: &str,
+    prefix: &str,
+) -> AppResult<u64>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    // For each script_uri, keep only the 20 most recent messages
-    sqlx::query(
+    let result = sqlx::query(
         r#"
-        DELETE FROM logs
-        WHERE id IN (
-            SELECT id FROM (
-                SELECT id,
-                       ROW_NUMBER() OVER (PARTITION BY script_uri ORDER BY created_at DESC) as rn
-                FROM logs
-            ) ranked
-            WHERE rn > 20
-        )
+        DELETE FROM script_properties WHERE script_uri = $1 AND starts_with(key, $2)
         "#,
     )
+    .bind(script_uri)
+    .bind(prefix)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error pruning log messages: {}", e);
+        error!("Database error clearing shared storage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!("Pruned log messages in database, keeping 20 entries per script");
-    Ok(())
+    debug!(
+        "Cleared {} script storage items with prefix '{}' for script: {}",
+        result.rows_affected(),
+        prefix,
+        script_uri
+    );
+    Ok(result.rows_affected())
 }
 
-/// Database-backed upsert asset
-async fn db_upsert_asset(
+/// Database-backed set personal storage item
+async fn db_set_user_properties_item(
     mut executor: crate::database::TransactionExecutor<'_>,
-    asset: &Asset,
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+    value: &str,
 ) -> AppResult<()> {
     let now = chrono::Utc::now();
 
-    // Try to update existing asset
+    // Try to update existing item
     let update_result = match executor {
         crate::database::TransactionExecutor::Transaction(ref mut tx) => {
             sqlx::query(
                 r#"
-                UPDATE assets
-                SET mimetype = $1, content = $2, script_uri = $3, updated_at = $4
-                WHERE uri = $5
+                UPDATE user_properties
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND user_id = $4 AND key = $5
                 "#,
             )
-            .bind(&asset.mimetype)
-            .bind(&asset.content)
-            .bind(&asset.script_uri)
+            .bind(value)
             .bind(now)
-            .bind(&asset.uri)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
             .execute(&mut ***tx)
             .await
         }
         crate::database::TransactionExecutor::Pool(pool) => {
             sqlx::query(
                 r#"
-                UPDATE assets
-                SET mimetype = $1, content = $2, script_uri = $3, updated_at = $4
-                WHERE uri = $5
+                UPDATE user_properties
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND user_id = $4 AND key = $5
                 "#,
             )
-            .bind(&asset.mimetype)
-            .bind(&asset.content)
-            .bind(&asset.script_uri)
+            .bind(value)
             .bind(now)
-            .bind(&asset.uri)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
             .execute(pool)
             .await
         }
     }
     .map_err(|e| {
-        error!("Database error updating asset: {}", e);
+        error!("Database error updating personal storage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -2108,24 +2921,26 @@ async fn db_upsert_asset(
     })?;
 
     if update_result.rows_affected() > 0 {
-        debug!("Updated existing asset in database: {}", asset.uri);
+        debug!(
+            "Updated personal storage item in database: {}:{}:{}",
+            script_uri, user_id, key
+        );
         return Ok(());
     }
 
-    // Asset doesn't exist, create new one
+    // Item doesn't exist, create new one
     match executor {
         crate::database::TransactionExecutor::Transaction(ref mut tx) => {
             sqlx::query(
                 r#"
-                INSERT INTO assets (uri, mimetype, content, name, script_uri, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $6)
+                INSERT INTO user_properties (script_uri, user_id, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
                 "#,
             )
-            .bind(&asset.uri)
-            .bind(&asset.mimetype)
-            .bind(&asset.content)
-            .bind(&asset.name)
-            .bind(&asset.script_uri)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .bind(value)
             .bind(now)
             .execute(&mut ***tx)
             .await
@@ -2133,48 +2948,56 @@ async fn db_upsert_asset(
         crate::database::TransactionExecutor::Pool(pool) => {
             sqlx::query(
                 r#"
-                INSERT INTO assets (uri, mimetype, content, name, script_uri, created_at, updated_at)
-                VALUES ($1, $2, $3, $4, $5, $6, $6)
+                INSERT INTO user_properties (script_uri, user_id, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
                 "#,
             )
-            .bind(&asset.uri)
-            .bind(&asset.mimetype)
-            .bind(&asset.content)
-            .bind(&asset.name)
-            .bind(&asset.script_uri)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .bind(value)
             .bind(now)
             .execute(pool)
             .await
         }
     }
     .map_err(|e| {
-        error!("Database error creating asset: {}", e);
+        error!("Database error inserting personal storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    debug!("Created new asset in database: {}", asset.uri);
+    debug!(
+        "Inserted personal storage item to database: {}:{}:{}",
+        script_uri, user_id, key
+    );
     Ok(())
 }
 
-/// Database-backed get asset by URI
-async fn db_get_asset<'e, E>(executor: E, script_uri: &str, uri: &str) -> AppResult<Option<Asset>>
+/// Database-backed get personal storage item
+async fn db_get_user_properties_item<'e, E>(
+    executor: E,
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+) -> AppResult<Option<String>>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
     let row = sqlx::query(
         r#"
-        SELECT uri, mimetype, content, name, script_uri, created_at, updated_at FROM assets WHERE script_uri = $1 AND uri = $2
+        SELECT value FROM user_properties WHERE script_uri = $1 AND user_id = $2 AND key = $3
         "#,
     )
     .bind(script_uri)
-    .bind(uri)
+    .bind(user_id)
+    .bind(key)
     .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error getting asset: {}", e);
+        error!("Database error getting personal storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -2182,290 +3005,321 @@ where
     })?;
 
     if let Some(row) = row {
-        let uri: String = row.try_get("uri").map_err(|e| {
-            error!("Database error getting uri: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let mimetype: String = row.try_get("mimetype").map_err(|e| {
-            error!("Database error getting mimetype: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let content: Vec<u8> = row.try_get("content").map_err(|e| {
-            error!("Database error getting content: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at").map_err(|e| {
-            error!("Database error getting created_at: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at").map_err(|e| {
-            error!("Database error getting updated_at: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let name: Option<String> = row.try_get("name").ok();
-        let script_uri: String = row.try_get("script_uri").map_err(|e| {
-            error!("Database error getting script_uri: {}", e);
+        let value: String = row.try_get("value").map_err(|e| {
+            error!("Database error getting value: {}", e);
             AppError::Database {
                 message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
-        Ok(Some(Asset {
-            uri,
-            name,
-            mimetype,
-            content,
-            created_at: created_at.into(),
-            updated_at: updated_at.into(),
-            script_uri,
-        }))
+        Ok(Some(value))
     } else {
         Ok(None)
     }
 }
 
-/// Database-backed list all assets for a script
-async fn db_list_assets<'e, E>(executor: E, script_uri: &str) -> AppResult<HashMap<String, Asset>>
+/// Database-backed remove personal storage item
+async fn db_remove_user_properties_item<'e, E>(
+    executor: E,
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+) -> AppResult<bool>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let rows = sqlx::query(
+    let result = sqlx::query(
         r#"
-        SELECT uri, mimetype, content, name, script_uri, created_at, updated_at FROM assets WHERE script_uri = $1 ORDER BY uri
+        DELETE FROM user_properties WHERE script_uri = $1 AND user_id = $2 AND key = $3
         "#,
     )
     .bind(script_uri)
-    .fetch_all(executor)
+    .bind(user_id)
+    .bind(key)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error listing assets: {}", e);
+        error!("Database error removing personal storage item: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let mut assets = HashMap::new();
-    for row in rows {
-        let uri: String = row.try_get("uri").map_err(|e| {
-            error!("Database error getting uri: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let mimetype: String = row.try_get("mimetype").map_err(|e| {
-            error!("Database error getting mimetype: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let content: Vec<u8> = row.try_get("content").map_err(|e| {
-            error!("Database error getting content: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at").map_err(|e| {
-            error!("Database error getting created_at: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at").map_err(|e| {
-            error!("Database error getting updated_at: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        let name: Option<String> = row.try_get("name").ok();
-        let script_uri: String = row.try_get("script_uri").map_err(|e| {
-            error!("Database error getting script_uri: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-        assets.insert(
-            uri.clone(),
-            Asset {
-                uri,
-                name,
-                mimetype,
-                content,
-                created_at: created_at.into(),
-                updated_at: updated_at.into(),
-                script_uri,
-            },
+    let existed = result.rows_affected() > 0;
+    if existed {
+        debug!(
+            "Removed personal storage item from database: {}:{}:{}",
+            script_uri, user_id, key
+        );
+    } else {
+        debug!(
+            "Personal storage item not found in database for removal: {}:{}:{}",
+            script_uri, user_id, key
         );
     }
 
-    Ok(assets)
+    Ok(existed)
 }
 
-/// Database-backed delete asset
-async fn db_delete_asset<'e, E>(executor: E, script_uri: &str, uri: &str) -> AppResult<bool>
+/// Database-backed clear all personal storage for a script and user
+async fn db_clear_user_properties<'e, E>(
+    executor: E,
+    script_uri: &str,
+    user_id: &str,
+) -> AppResult<()>
 where
     E: sqlx::Executor<'e, Database = sqlx::Postgres>,
 {
-    let result = sqlx::query(
+    sqlx::query(
         r#"
-        DELETE FROM assets WHERE script_uri = $1 AND uri = $2
+        DELETE FROM user_properties WHERE script_uri = $1 AND user_id = $2
         "#,
     )
     .bind(script_uri)
-    .bind(uri)
+    .bind(user_id)
     .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error deleting asset: {}", e);
+        error!("Database error clearing personal storage: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let existed = result.rows_affected() > 0;
-    if existed {
-        debug!("Deleted asset from database: {}", uri);
-    } else {
-        debug!("Asset not found in database for deletion: {}", uri);
-    }
-
-    Ok(existed)
+    debug!(
+        "Cleared all personal storage items from database for script {} and user {}",
+        script_uri, user_id
+    );
+    Ok(())
 }
 
-// ============================================================================
-// Script Database Schema Management Functions
-// ============================================================================
-
-use crate::db_schema_utils::{
-    ColumnType, MAX_COLUMNS_PER_TABLE, MAX_TABLES_PER_SCRIPT, generate_physical_table_name,
-    quote_identifier, validate_default_value, validate_identifier,
-};
-
-/// Database-backed create script-owned table
-async fn db_create_script_table(
-    pool: &PgPool,
+/// Database-backed set script secret item
+async fn db_set_script_secret(
+    mut executor: crate::database::TransactionExecutor<'_>,
     script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<String> {
-    // Validate the logical table name
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
-    })?;
+    key: &str,
+    value: &str,
+) -> AppResult<()> {
+    let now = chrono::Utc::now();
 
-    // Check table limit for this script
-    let table_count: i64 =
-        sqlx::query_scalar("SELECT COUNT(*) FROM script_tables WHERE script_uri = $1")
+    // Encrypt the value if secret encryption is configured
+    let stored_value: String = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
+        let encrypted = enc.encrypt_field(value).map_err(|e| AppError::Internal {
+            message: format!("Failed to encrypt secret value: {}", e),
+        })?;
+        serde_json::to_string(&encrypted).map_err(|e| AppError::Internal {
+            message: format!("Failed to serialize encrypted secret: {}", e),
+        })?
+    } else {
+        value.to_string()
+    };
+    let value: &str = &stored_value;
+
+    // Try to update existing item
+    let update_result = match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                UPDATE script_secrets
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND key = $4
+                "#,
+            )
+            .bind(value)
+            .bind(now)
             .bind(script_uri)
-            .fetch_one(pool)
+            .bind(key)
+            .execute(&mut ***tx)
             .await
-            .map_err(|e| {
-                error!("Database error counting tables for script: {}", e);
-                AppError::Database {
-                    message: format!("Database error: {}", e),
-                    source: None,
-                }
-            })?;
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                UPDATE script_secrets
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND key = $4
+                "#,
+            )
+            .bind(value)
+            .bind(now)
+            .bind(script_uri)
+            .bind(key)
+            .execute(pool)
+            .await
+        }
+    }
+    .map_err(|e| {
+        error!("Database error updating script secret: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    if table_count >= MAX_TABLES_PER_SCRIPT as i64 {
-        return Err(AppError::Validation {
-            field: "table_name".to_string(),
-            reason: format!(
-                "Script has reached maximum table limit of {}",
-                MAX_TABLES_PER_SCRIPT
-            ),
-        });
+    if update_result.rows_affected() > 0 {
+        debug!("Updated script secret in database: {}:{}", script_uri, key);
+        return Ok(());
     }
 
-    // Generate physical table name
-    let physical_table_name = generate_physical_table_name(script_uri, logical_table_name);
+    // Item doesn't exist, create new one
+    match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                INSERT INTO script_secrets (script_uri, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $4)
+                "#,
+            )
+            .bind(script_uri)
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(&mut ***tx)
+            .await
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                INSERT INTO script_secrets (script_uri, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $4)
+                "#,
+            )
+            .bind(script_uri)
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(pool)
+            .await
+        }
+    }
+    .map_err(|e| {
+        error!("Database error creating script secret: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    // Check if table already exists for this script
-    let exists: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2)",
+    debug!(
+        "Created new script secret in database: {}:{}",
+        script_uri, key
+    );
+    Ok(())
+}
+
+/// Database-backed get script secret
+async fn db_get_script_secret<'e, E>(
+    executor: E,
+    script_uri: &str,
+    key: &str,
+) -> AppResult<Option<String>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let row = sqlx::query(
+        r#"
+        SELECT value FROM script_secrets WHERE script_uri = $1 AND key = $2
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_one(pool)
+    .bind(key)
+    .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error checking table existence: {}", e);
+        error!("Database error getting script secret: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if exists {
-        return Err(AppError::Validation {
-            field: "table_name".to_string(),
-            reason: format!(
-                "Table '{}' already exists for this script",
-                logical_table_name
-            ),
-        });
-    }
-
-    // Create the physical table with id column
-    let create_table_sql = format!(
-        "CREATE TABLE {} (id SERIAL PRIMARY KEY)",
-        quote_identifier(&physical_table_name)
-    );
-
-    sqlx::query(sqlx::AssertSqlSafe(create_table_sql.as_str()))
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error creating table: {}", e);
+    if let Some(row) = row {
+        let raw: String = row.try_get("value").map_err(|e| {
+            error!("Database error getting value: {}", e);
             AppError::Database {
-                message: format!("Failed to create table: {}", e),
+                message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
+        // Decrypt if encryption is configured and the value is in encrypted form
+        let value = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
+            match serde_json::from_str::<crate::security::encryption::EncryptedData>(&raw) {
+                Ok(encrypted) => match enc.decrypt_field(&encrypted) {
+                    Ok(plain) => plain,
+                    Err(e) => {
+                        error!(
+                            "Failed to decrypt script secret {}:{}: {}",
+                            script_uri, key, e
+                        );
+                        raw
+                    }
+                },
+                Err(_) => raw, // stored before encryption was enabled — return as-is
+            }
+        } else {
+            raw
+        };
+        Ok(Some(value))
+    } else {
+        Ok(None)
+    }
+}
 
-    // Record the table in script_tables metadata
-    let schema_json = serde_json::json!({
-        "columns": [
-            {"name": "id", "type": "SERIAL", "nullable": false, "primary_key": true}
-        ]
-    });
+/// Database-backed remove script secret
+async fn db_remove_script_secret<'e, E>(executor: E, script_uri: &str, key: &str) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        DELETE FROM script_secrets WHERE script_uri = $1 AND key = $2
+        "#,
+    )
+    .bind(script_uri)
+    .bind(key)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error removing script secret: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    let existed = result.rows_affected() > 0;
+    if existed {
+        debug!(
+            "Removed script secret from database: {}:{}",
+            script_uri, key
+        );
+    } else {
+        debug!(
+            "Script secret not found in database for removal: {}:{}",
+            script_uri, key
+        );
+    }
+
+    Ok(existed)
+}
 
+/// Database-backed clear all script secrets for a script
+async fn db_clear_script_secrets<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
     sqlx::query(
         r#"
-        INSERT INTO script_tables (script_uri, logical_table_name, physical_table_name, schema_json)
-        VALUES ($1, $2, $3, $4)
+        DELETE FROM script_secrets WHERE script_uri = $1
         "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .bind(&physical_table_name)
-    .bind(schema_json)
-    .execute(pool)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error recording table metadata: {}", e);
+        error!("Database error clearing script secrets: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -2473,360 +3327,283 @@ async fn db_create_script_table(
     })?;
 
     debug!(
-        "Created script table: {} -> {}",
-        logical_table_name, physical_table_name
+        "Cleared all script secrets from database for script: {}",
+        script_uri
     );
-
-    Ok(physical_table_name)
+    Ok(())
 }
 
-/// Database-backed add column to script-owned table
-async fn db_add_column_to_script_table(
-    pool: &PgPool,
-    script_uri: &str,
-    logical_table_name: &str,
-    column_name: &str,
-    column_type: ColumnType,
-    nullable: bool,
-    default_value: Option<&str>,
-) -> AppResult<()> {
-    // Validate identifiers
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
-    })?;
-    validate_identifier(column_name).map_err(|e| AppError::Validation {
-        field: "column_name".to_string(),
-        reason: e.to_string(),
-    })?;
-
-    // Get the physical table name
-    let row: Option<(String, serde_json::Value)> = sqlx::query_as(
-        "SELECT physical_table_name, schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+/// Database-backed list script secret keys for a script
+async fn db_list_script_secrets<'e, E>(executor: E, script_uri: &str) -> AppResult<Vec<String>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT key FROM script_secrets WHERE script_uri = $1 ORDER BY key ASC
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
+    .fetch_all(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching table metadata: {}", e);
+        error!("Database error listing script secret keys: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let (physical_table_name, mut schema_json) = row.ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
-    })?;
-
-    // Check column limit
-    let column_count = schema_json
-        .get("columns")
-        .and_then(|c| c.as_array())
-        .map(|c| c.len())
-        .unwrap_or(0);
-
-    if column_count >= MAX_COLUMNS_PER_TABLE {
-        return Err(AppError::Validation {
-            field: "column_name".to_string(),
-            reason: format!(
-                "Table has reached maximum column limit of {}",
-                MAX_COLUMNS_PER_TABLE
-            ),
-        });
-    }
-
-    // Check if column already exists
-    if let Some(columns) = schema_json.get("columns").and_then(|c| c.as_array())
-        && columns
-            .iter()
-            .any(|col| col.get("name").and_then(|n| n.as_str()) == Some(column_name))
-    {
-        return Err(AppError::Validation {
-            field: "column_name".to_string(),
-            reason: format!(
-                "Column '{}' already exists in table '{}'",
-                column_name, logical_table_name
-            ),
-        });
-    }
-
-    // Build ALTER TABLE statement
-    let mut alter_sql = format!(
-        "ALTER TABLE {} ADD COLUMN {} {}",
-        quote_identifier(&physical_table_name),
-        quote_identifier(column_name),
-        column_type.to_sql()
-    );
-
-    if !nullable {
-        alter_sql.push_str(" NOT NULL");
-    }
-
-    if let Some(default) = default_value {
-        let validated_default =
-            validate_default_value(&column_type, default).map_err(|e| AppError::Validation {
-                field: "default_value".to_string(),
-                reason: e.to_string(),
-            })?;
-        alter_sql.push_str(&format!(" DEFAULT {}", validated_default));
-    }
-
-    // Execute the ALTER TABLE
-    sqlx::query(sqlx::AssertSqlSafe(alter_sql.as_str()))
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error adding column: {}", e);
-            AppError::Database {
-                message: format!("Failed to add column: {}", e),
-                source: None,
-            }
-        })?;
-
-    // Update schema_json metadata
-    if let Some(columns) = schema_json
-        .get_mut("columns")
-        .and_then(|c| c.as_array_mut())
-    {
-        columns.push(serde_json::json!({
-            "name": column_name,
-            "type": column_type.to_sql(),
-            "nullable": nullable,
-            "default": default_value,
-        }));
-    }
-
-    sqlx::query("UPDATE script_tables SET schema_json = $1, updated_at = NOW() WHERE script_uri = $2 AND logical_table_name = $3")
-        .bind(schema_json)
-        .bind(script_uri)
-        .bind(logical_table_name)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error updating schema metadata: {}", e);
-            AppError::Database {
-                message: format!("Database error: {}", e),
-                source: None,
-            }
-        })?;
-
     debug!(
-        "Added column {} to table {}: {} {}",
-        column_name,
-        logical_table_name,
-        column_type.to_sql(),
-        if nullable { "NULL" } else { "NOT NULL" }
+        "Listed {} script secret keys from database for script: {}",
+        rows.len(),
+        script_uri
     );
-
-    Ok(())
+    Ok(rows)
 }
 
-/// Database-backed add reference column (creates INTEGER column with FK constraint)
-async fn db_add_reference_column(
-    pool: &PgPool,
+/// Database-backed set user secret item
+async fn db_set_user_secret(
+    mut executor: crate::database::TransactionExecutor<'_>,
     script_uri: &str,
-    logical_table_name: &str,
-    column_name: &str,
-    referenced_logical_table_name: &str,
-    nullable: bool,
+    user_id: &str,
+    key: &str,
+    value: &str,
 ) -> AppResult<()> {
-    // Validate identifiers
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
-    })?;
-    validate_identifier(column_name).map_err(|e| AppError::Validation {
-        field: "column_name".to_string(),
-        reason: e.to_string(),
-    })?;
-    validate_identifier(referenced_logical_table_name).map_err(|e| AppError::Validation {
-        field: "referenced_table_name".to_string(),
-        reason: e.to_string(),
-    })?;
+    let now = chrono::Utc::now();
 
-    // First, add the integer column
-    db_add_column_to_script_table(
-        pool,
-        script_uri,
-        logical_table_name,
-        column_name,
-        ColumnType::Integer,
-        nullable,
-        None,
-    )
-    .await?;
+    // Encrypt the value if secret encryption is configured
+    let stored_value: String = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
+        let encrypted = enc.encrypt_field(value).map_err(|e| AppError::Internal {
+            message: format!("Failed to encrypt user secret value: {}", e),
+        })?;
+        serde_json::to_string(&encrypted).map_err(|e| AppError::Internal {
+            message: format!("Failed to serialize encrypted user secret: {}", e),
+        })?
+    } else {
+        value.to_string()
+    };
+    let value: &str = &stored_value;
 
-    // Get physical table names for FK constraint
-    let source_table: String = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
-    )
-    .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
-    .await
+    // Try to update existing item
+    let update_result = match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                UPDATE user_secrets
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND user_id = $4 AND key = $5
+                "#,
+            )
+            .bind(value)
+            .bind(now)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .execute(&mut ***tx)
+            .await
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                UPDATE user_secrets
+                SET value = $1, updated_at = $2
+                WHERE script_uri = $3 AND user_id = $4 AND key = $5
+                "#,
+            )
+            .bind(value)
+            .bind(now)
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .execute(pool)
+            .await
+        }
+    }
     .map_err(|e| {
-        error!("Database error fetching source table: {}", e);
+        error!("Database error updating user secret: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
-    })?
-    .ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
     })?;
 
-    let referenced_table: String = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
-    )
-    .bind(script_uri)
-    .bind(referenced_logical_table_name)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        error!("Database error fetching referenced table: {}", e);
-        AppError::Database {
-            message: format!("Database error: {}", e),
-            source: None,
-        }
-    })?
-    .ok_or_else(|| AppError::Validation {
-        field: "referenced_table_name".to_string(),
-        reason: format!(
-            "Referenced table '{}' not found for this script",
-            referenced_logical_table_name
-        ),
-    })?;
-
-    // Create the foreign key constraint
-    let constraint_name = format!("fk_{}_{}", logical_table_name.replace("_", ""), column_name);
-    let alter_sql = format!(
-        "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} (id)",
-        quote_identifier(&source_table),
-        quote_identifier(&constraint_name),
-        quote_identifier(column_name),
-        quote_identifier(&referenced_table)
-    );
+    if update_result.rows_affected() > 0 {
+        debug!(
+            "Updated user secret in database: {}:{}:{}",
+            script_uri, user_id, key
+        );
+        return Ok(());
+    }
 
-    sqlx::query(sqlx::AssertSqlSafe(alter_sql.as_str()))
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error creating foreign key: {}", e);
-            AppError::Database {
-                message: format!("Failed to create foreign key: {}", e),
-                source: None,
-            }
-        })?;
+    // Item doesn't exist, create new one
+    match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                INSERT INTO user_secrets (script_uri, user_id, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
+                "#,
+            )
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(&mut ***tx)
+            .await
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                INSERT INTO user_secrets (script_uri, user_id, key, value, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
+                "#,
+            )
+            .bind(script_uri)
+            .bind(user_id)
+            .bind(key)
+            .bind(value)
+            .bind(now)
+            .execute(pool)
+            .await
+        }
+    }
+    .map_err(|e| {
+        error!("Database error inserting user secret: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
     debug!(
-        "Created reference column: {}.{} -> {}.id (nullable: {})",
-        logical_table_name, column_name, referenced_logical_table_name, nullable
+        "Inserted user secret to database: {}:{}:{}",
+        script_uri, user_id, key
     );
-
     Ok(())
 }
 
-/// Database-backed drop column from script-owned table
-async fn db_drop_column(
-    pool: &PgPool,
+/// Database-backed get user secret
+async fn db_get_user_secret<'e, E>(
+    executor: E,
     script_uri: &str,
-    logical_table_name: &str,
-    column_name: &str,
-) -> AppResult<bool> {
-    // Validate identifiers
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
-    })?;
-    validate_identifier(column_name).map_err(|e| AppError::Validation {
-        field: "column_name".to_string(),
-        reason: e.to_string(),
-    })?;
-
-    // Get the physical table name and schema
-    let row: Option<(String, serde_json::Value)> = sqlx::query_as(
-        "SELECT physical_table_name, schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    user_id: &str,
+    key: &str,
+) -> AppResult<Option<String>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let row = sqlx::query(
+        r#"
+        SELECT value FROM user_secrets WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
+    .bind(user_id)
+    .bind(key)
+    .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching table metadata: {}", e);
+        error!("Database error getting user secret: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let (physical_table_name, mut schema_json) = row.ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
-    })?;
-
-    // Check if column exists in schema
-    let column_exists = schema_json
-        .get("columns")
-        .and_then(|c| c.as_array())
-        .map(|columns| {
-            columns
-                .iter()
-                .any(|col| col.get("name").and_then(|n| n.as_str()) == Some(column_name))
-        })
-        .unwrap_or(false);
-
-    if !column_exists {
-        return Ok(false);
-    }
-
-    // Don't allow dropping the id column
-    if column_name == "id" {
-        return Err(AppError::Validation {
-            field: "column_name".to_string(),
-            reason: "Cannot drop the 'id' column".to_string(),
-        });
-    }
-
-    // Drop the column
-    let drop_sql = format!(
-        "ALTER TABLE {} DROP COLUMN {}",
-        quote_identifier(&physical_table_name),
-        quote_identifier(column_name)
-    );
-
-    sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error dropping column: {}", e);
+    if let Some(row) = row {
+        let raw: String = row.try_get("value").map_err(|e| {
+            error!("Database error getting value: {}", e);
             AppError::Database {
-                message: format!("Failed to drop column: {}", e),
+                message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
+        // Decrypt if encryption is configured and the value is in encrypted form
+        let value = if let Some(enc) = GLOBAL_SECRET_ENCRYPTION.get() {
+            match serde_json::from_str::<crate::security::encryption::EncryptedData>(&raw) {
+                Ok(encrypted) => match enc.decrypt_field(&encrypted) {
+                    Ok(plain) => plain,
+                    Err(e) => {
+                        error!(
+                            "Failed to decrypt user secret {}:{}:{}: {}",
+                            script_uri, user_id, key, e
+                        );
+                        raw
+                    }
+                },
+                Err(_) => raw, // stored before encryption was enabled — return as-is
+            }
+        } else {
+            raw
+        };
+        Ok(Some(value))
+    } else {
+        Ok(None)
+    }
+}
 
-    // Update schema_json metadata
-    if let Some(columns) = schema_json
-        .get_mut("columns")
-        .and_then(|c| c.as_array_mut())
-    {
-        columns.retain(|col| col.get("name").and_then(|n| n.as_str()) != Some(column_name));
+/// Database-backed remove user secret
+async fn db_remove_user_secret<'e, E>(
+    executor: E,
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        DELETE FROM user_secrets WHERE script_uri = $1 AND user_id = $2 AND key = $3
+        "#,
+    )
+    .bind(script_uri)
+    .bind(user_id)
+    .bind(key)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error removing user secret: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    let existed = result.rows_affected() > 0;
+    if existed {
+        debug!(
+            "Removed user secret from database: {}:{}:{}",
+            script_uri, user_id, key
+        );
+    } else {
+        debug!(
+            "User secret not found in database for removal: {}:{}:{}",
+            script_uri, user_id, key
+        );
     }
 
+    Ok(existed)
+}
+
+/// Database-backed clear all user secrets for a script and user
+async fn db_clear_user_secrets<'e, E>(executor: E, script_uri: &str, user_id: &str) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
     sqlx::query(
-        "UPDATE script_tables SET schema_json = $1, updated_at = NOW() WHERE script_uri = $2 AND logical_table_name = $3",
+        r#"
+        DELETE FROM user_secrets WHERE script_uri = $1 AND user_id = $2
+        "#,
     )
-    .bind(schema_json)
     .bind(script_uri)
-    .bind(logical_table_name)
-    .execute(pool)
+    .bind(user_id)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error updating schema metadata: {}", e);
+        error!("Database error clearing user secrets: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
@@ -2834,3304 +3611,9649 @@ async fn db_drop_column(
     })?;
 
     debug!(
-        "Dropped column {} from table {}",
-        column_name, logical_table_name
+        "Cleared all user secrets from database for script {} and user {}",
+        script_uri, user_id
     );
+    Ok(())
+}
 
-    Ok(true)
-}
-
-/// Database-backed drop script-owned table
-async fn db_drop_script_table(
-    pool: &PgPool,
+/// Database-backed insert log message
+async fn db_insert_log_message<'e, E>(
+    executor: E,
     script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<bool> {
-    // Validate identifier
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
-    })?;
-
-    // Get the physical table name
-    let physical_table_name: Option<String> = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    message: &str,
+    log_level: &str,
+    data: Option<&serde_json::Value>,
+    context: Option<&serde_json::Value>,
+) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO logs (script_uri, message, log_level, data, context, created_at)
+        VALUES ($1, $2, $3, $4, $5, NOW())
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
+    .bind(message)
+    .bind(log_level)
+    .bind(data)
+    .bind(context)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching table metadata: {}", e);
+        error!("Database error inserting log message: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    if let Some(physical_name) = physical_table_name {
-        // Drop the physical table
-        let drop_sql = format!(
-            "DROP TABLE IF EXISTS {} CASCADE",
-            quote_identifier(&physical_name)
-        );
-
-        sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
-            .execute(pool)
-            .await
-            .map_err(|e| {
-                error!("Database error dropping table: {}", e);
-                AppError::Database {
-                    message: format!("Failed to drop table: {}", e),
-                    source: None,
-                }
-            })?;
-
-        // Remove from script_tables metadata (will cascade due to FK)
-        sqlx::query("DELETE FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2")
-            .bind(script_uri)
-            .bind(logical_table_name)
-            .execute(pool)
-            .await
-            .map_err(|e| {
-                error!("Database error removing table metadata: {}", e);
-                AppError::Database {
-                    message: format!("Database error: {}", e),
-                    source: None,
-                }
-            })?;
-
-        debug!(
-            "Dropped script table: {} ({})",
-            logical_table_name, physical_name
-        );
-        Ok(true)
-    } else {
-        Ok(false)
-    }
+    debug!(
+        "Inserted log message to database for script: {}",
+        script_uri
+    );
+    Ok(())
 }
 
-/// Database-backed drop all tables for a script
-async fn db_drop_all_script_tables(pool: &PgPool, script_uri: &str) -> AppResult<usize> {
-    // Get all tables for this script
-    let tables: Vec<String> =
-        sqlx::query_scalar("SELECT physical_table_name FROM script_tables WHERE script_uri = $1")
-            .bind(script_uri)
-            .fetch_all(pool)
-            .await
-            .map_err(|e| {
-                error!("Database error fetching script tables: {}", e);
-                AppError::Database {
-                    message: format!("Database error: {}", e),
-                    source: None,
-                }
-            })?;
+/// Database-backed fetch log messages for a script
+async fn db_fetch_log_messages<'e, E>(executor: E, script_uri: &str) -> AppResult<Vec<LogEntry>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT message, log_level, created_at FROM logs
+        WHERE script_uri = $1
+        ORDER BY created_at ASC
+        "#,
+    )
+    .bind(script_uri)
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching log messages: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    let count = tables.len();
+    let messages = rows
+        .into_iter()
+        .map(|row| {
+            let message: String = row.try_get("message")?;
+            let log_level: String = row.try_get("log_level")?;
+            let created_at: DateTime<Utc> = row.try_get("created_at")?;
+            // Convert chrono DateTime to SystemTime
+            let system_time = SystemTime::from(created_at);
+            Ok(LogEntry::new(message, log_level, system_time))
+        })
+        .collect::<Result<Vec<LogEntry>, sqlx::Error>>()
+        .map_err(|e| {
+            error!("Database error getting message/level/timestamp: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
 
-    // Drop each table
-    for physical_name in tables {
-        let drop_sql = format!(
-            "DROP TABLE IF EXISTS {} CASCADE",
-            quote_identifier(&physical_name)
-        );
+    Ok(messages)
+}
 
-        sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
-            .execute(pool)
-            .await
-            .map_err(|e| {
-                error!("Database error dropping table {}: {}", physical_name, e);
-                AppError::Database {
-                    message: format!("Failed to drop table: {}", e),
-                    source: None,
-                }
-            })?;
-    }
+/// Database-backed fetch all log messages
+async fn db_fetch_all_log_messages<'e, E>(executor: E) -> AppResult<Vec<LogEntry>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT message, log_level, created_at FROM logs
+        ORDER BY created_at DESC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching all log messages: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    // Delete metadata entries (script_uri FK will auto-delete on script deletion)
-    sqlx::query("DELETE FROM script_tables WHERE script_uri = $1")
-        .bind(script_uri)
-        .execute(pool)
-        .await
+    let messages = rows
+        .into_iter()
+        .map(|row| {
+            let message: String = row.try_get("message")?;
+            let log_level: String = row.try_get("log_level")?;
+            let created_at: DateTime<Utc> = row.try_get("created_at")?;
+            // Convert chrono DateTime to SystemTime
+            let system_time = SystemTime::from(created_at);
+            Ok(LogEntry::new(message, log_level, system_time))
+        })
+        .collect::<Result<Vec<LogEntry>, sqlx::Error>>()
         .map_err(|e| {
-            error!("Database error removing table metadata: {}", e);
+            error!("Database error getting message/level/timestamp: {}", e);
             AppError::Database {
                 message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
 
-    if count > 0 {
-        debug!("Dropped {} tables for script {}", count, script_uri);
-    }
-
-    Ok(count)
+    Ok(messages)
 }
 
-// ============================================================================
-// Script Database Schema Introspection Functions
-// ============================================================================
-
-/// List all tables owned by a script
-async fn db_list_script_tables(pool: &PgPool, script_uri: &str) -> AppResult<Vec<TableInfo>> {
-    let rows = sqlx::query!(
+/// Database-backed paginated log query, newest entries first
+async fn db_query_log_messages<'e, E>(executor: E, query: &LogQuery) -> AppResult<LogPage>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LOG_PAGE_SIZE)
+        .clamp(1, MAX_LOG_PAGE_SIZE);
+    let cursor = query.cursor.as_deref().map(decode_log_cursor).transpose()?;
+    let levels: Vec<String> = query.levels.iter().map(|l| l.to_uppercase()).collect();
+    let search = query
+        .search
+        .as_deref()
+        .filter(|term| !term.is_empty())
+        .map(escape_like_pattern);
+
+    // One extra row tells whether another page follows
+    let rows = sqlx::query(
         r#"
-        SELECT logical_table_name, physical_table_name, created_at
-        FROM script_tables
-        WHERE script_uri = $1
-        ORDER BY logical_table_name
+        SELECT id::text AS id, script_uri, message, log_level, data, context, created_at
+        FROM logs
+        WHERE ($1::text IS NULL OR script_uri = $1)
+          AND (cardinality($2::text[]) = 0 OR log_level = ANY($2))
+          AND ($3::timestamptz IS NULL OR created_at >= $3)
+          AND ($4::timestamptz IS NULL OR created_at < $4)
+          AND ($5::text IS NULL OR message ILIKE '%' || $5 || '%')
+          AND ($6::timestamptz IS NULL OR (created_at, id) < ($6, $7::uuid))
+          AND ($9::jsonb IS NULL OR data @> $9)
+          AND ($10::text IS NULL OR context->>'requestId' = $10)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $8
         "#,
-        script_uri
     )
-    .fetch_all(pool)
+    .bind(&query.script_uri)
+    .bind(&levels)
+    .bind(query.since)
+    .bind(query.until)
+    .bind(search)
+    .bind(cursor.as_ref().map(|(created_at, _)| *created_at))
+    .bind(cursor.as_ref().map(|(_, id)| id.as_str()))
+    .bind(limit + 1)
+    .bind(&query.data)
+    .bind(&query.request_id)
+    .fetch_all(executor)
     .await
     .map_err(|e| {
-        error!("Database error listing script tables: {}", e);
+        error!("Database error querying log messages: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    Ok(rows
+    let mut entries = rows
         .into_iter()
-        .map(|row| TableInfo {
-            logical_name: row.logical_table_name,
-            physical_name: row.physical_table_name,
-            created_at: row.created_at,
+        .map(|row| {
+            let id: String = row.try_get("id")?;
+            let created_at: DateTime<Utc> = row.try_get("created_at")?;
+            Ok(LogRecord {
+                cursor: encode_log_cursor(&created_at, &id),
+                id,
+                script_uri: row.try_get("script_uri")?,
+                level: row.try_get("log_level")?,
+                message: row.try_get("message")?,
+                data: row.try_get("data")?,
+                context: row.try_get("context")?,
+                timestamp: created_at,
+            })
         })
-        .collect())
+        .collect::<Result<Vec<LogRecord>, sqlx::Error>>()
+        .map_err(|e| {
+            error!("Database error parsing log entry: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+
+    let next_cursor = if entries.len() as i64 > limit {
+        entries.truncate(limit as usize);
+        entries.last().map(|entry| entry.cursor.clone())
+    } else {
+        None
+    };
+
+    Ok(LogPage {
+        entries,
+        next_cursor,
+    })
 }
 
-/// Get detailed schema information for a specific table
-async fn db_get_table_schema(
-    pool: &PgPool,
-    script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<TableSchema> {
-    // Fetch schema_json from script_tables
-    let schema_json: Option<serde_json::Value> = sqlx::query_scalar(
-        "SELECT schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+/// Database-backed clear log messages for a script
+async fn db_clear_log_messages<'e, E>(executor: E, script_uri: &str) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query(
+        r#"
+        DELETE FROM logs WHERE script_uri = $1
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
+    .execute(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching table schema: {}", e);
+        error!("Database error clearing log messages: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let schema_json = schema_json.ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
-    })?;
+    debug!(
+        "Cleared log messages from database for script: {}",
+        script_uri
+    );
+    Ok(())
+}
 
-    // Parse columns from schema_json
-    let columns_array = schema_json
-        .get("columns")
-        .and_then(|c| c.as_array())
-        .ok_or_else(|| AppError::Validation {
-            field: "schema_json".to_string(),
-            reason: "Invalid schema format: missing columns array".to_string(),
-        })?;
-
-    let mut columns = Vec::new();
-    for col in columns_array {
-        let name = col
-            .get("name")
-            .and_then(|n| n.as_str())
-            .ok_or_else(|| AppError::Validation {
-                field: "column".to_string(),
-                reason: "Column missing name".to_string(),
-            })?
-            .to_string();
-
-        let data_type = col
-            .get("type")
-            .and_then(|t| t.as_str())
-            .ok_or_else(|| AppError::Validation {
-                field: "column".to_string(),
-                reason: format!("Column '{}' missing type", name),
-            })?
-            .to_string();
-
-        let nullable = col
-            .get("nullable")
-            .and_then(|n| n.as_bool())
-            .unwrap_or(true);
+/// Database-backed prune log messages (keep only latest 20 per script)
+async fn db_prune_log_messages<'e, E>(executor: E) -> AppResult<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    // For each script_uri, keep only the 20 most recent messages
+    sqlx::query(
+        r#"
+        DELETE FROM logs
+        WHERE id IN (
+            SELECT id FROM (
+                SELECT id,
+                       ROW_NUMBER() OVER (PARTITION BY script_uri ORDER BY created_at DESC) as rn
+                FROM logs
+            ) ranked
+            WHERE rn > 20
+        )
+        "#,
+    )
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error pruning log messages: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-        let default_value = col.get("default").map(|d| {
-            if let Some(s) = d.as_str() {
-                s.to_string()
-            } else {
-                d.to_string()
-            }
-        });
+    debug!("Pruned log messages in database, keeping 20 entries per script");
+    Ok(())
+}
 
-        let is_primary_key = col
-            .get("primary_key")
-            .and_then(|p| p.as_bool())
-            .unwrap_or(false);
+/// Database-backed upsert asset
+async fn db_upsert_asset(
+    mut executor: crate::database::TransactionExecutor<'_>,
+    asset: &Asset,
+    variants: &crate::asset_compression::Variants,
+) -> AppResult<()> {
+    let now = chrono::Utc::now();
+    let headers = serde_json::to_value(&asset.headers).map_err(|e| AppError::Database {
+        message: format!("Failed to serialize asset headers: {}", e),
+        source: None,
+    })?;
 
-        columns.push(ColumnInfo {
-            name,
-            data_type,
-            nullable,
-            default_value,
-            is_primary_key,
-        });
+    // Try to update existing asset
+    let update_result = match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                UPDATE assets
+                SET mimetype = $1, content = $2, script_uri = $3, updated_at = $4, headers = $6,
+                    content_gzip = $7, content_br = $8
+                WHERE uri = $5
+                "#,
+            )
+            .bind(&asset.mimetype)
+            .bind(&asset.content)
+            .bind(&asset.script_uri)
+            .bind(now)
+            .bind(&asset.uri)
+            .bind(&headers)
+            .bind(&variants.gzip)
+            .bind(&variants.brotli)
+            .execute(&mut ***tx)
+            .await
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                UPDATE assets
+                SET mimetype = $1, content = $2, script_uri = $3, updated_at = $4, headers = $6,
+                    content_gzip = $7, content_br = $8
+                WHERE uri = $5
+                "#,
+            )
+            .bind(&asset.mimetype)
+            .bind(&asset.content)
+            .bind(&asset.script_uri)
+            .bind(now)
+            .bind(&asset.uri)
+            .bind(&headers)
+            .bind(&variants.gzip)
+            .bind(&variants.brotli)
+            .execute(pool)
+            .await
+        }
     }
+    .map_err(|e| {
+        error!("Database error updating asset: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    Ok(TableSchema {
-        table_name: logical_table_name.to_string(),
-        columns,
-    })
-}
+    if update_result.rows_affected() > 0 {
+        debug!("Updated existing asset in database: {}", asset.uri);
+        return Ok(());
+    }
 
-/// Get foreign key relationships for a table
-async fn db_get_foreign_keys(
-    pool: &PgPool,
-    script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<Vec<ForeignKeyInfo>> {
-    // Get the physical table name first
-    let physical_table_name: Option<String> = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
-    )
-    .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(pool)
-    .await
+    // Asset doesn't exist, create new one
+    match executor {
+        crate::database::TransactionExecutor::Transaction(ref mut tx) => {
+            sqlx::query(
+                r#"
+                INSERT INTO assets (uri, mimetype, content, name, script_uri, created_at, updated_at, headers, content_gzip, content_br)
+                VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(&asset.uri)
+            .bind(&asset.mimetype)
+            .bind(&asset.content)
+            .bind(&asset.name)
+            .bind(&asset.script_uri)
+            .bind(now)
+            .bind(&headers)
+            .bind(&variants.gzip)
+            .bind(&variants.brotli)
+            .execute(&mut ***tx)
+            .await
+        }
+        crate::database::TransactionExecutor::Pool(pool) => {
+            sqlx::query(
+                r#"
+                INSERT INTO assets (uri, mimetype, content, name, script_uri, created_at, updated_at, headers, content_gzip, content_br)
+                VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(&asset.uri)
+            .bind(&asset.mimetype)
+            .bind(&asset.content)
+            .bind(&asset.name)
+            .bind(&asset.script_uri)
+            .bind(now)
+            .bind(&headers)
+            .bind(&variants.gzip)
+            .bind(&variants.brotli)
+            .execute(pool)
+            .await
+        }
+    }
     .map_err(|e| {
-        error!("Database error fetching table metadata: {}", e);
+        error!("Database error creating asset: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let physical_table_name = physical_table_name.ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
+    debug!("Created new asset in database: {}", asset.uri);
+    Ok(())
+}
+
+/// Read the JSONB `headers` column of an asset row
+fn parse_asset_headers(row: &sqlx::postgres::PgRow) -> AppResult<HashMap<String, String>> {
+    let value: serde_json::Value = row.try_get("headers").map_err(|e| {
+        error!("Database error getting headers: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
     })?;
+    serde_json::from_value(value).map_err(|e| AppError::Database {
+        message: format!("Invalid asset headers: {}", e),
+        source: None,
+    })
+}
 
-    // Query PostgreSQL information_schema for foreign keys
-    let rows = sqlx::query!(
+/// Database-backed get asset by URI
+async fn db_get_asset<'e, E>(executor: E, script_uri: &str, uri: &str) -> AppResult<Option<Asset>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let row = sqlx::query(
         r#"
-        SELECT
-            kcu.column_name,
-            ccu.table_name AS referenced_table_physical,
-            ccu.column_name AS referenced_column
-        FROM information_schema.table_constraints AS tc
-        JOIN information_schema.key_column_usage AS kcu
-            ON tc.constraint_name = kcu.constraint_name
-            AND tc.table_schema = kcu.table_schema
-        JOIN information_schema.constraint_column_usage AS ccu
-            ON ccu.constraint_name = tc.constraint_name
-            AND ccu.table_schema = tc.table_schema
-        WHERE tc.constraint_type = 'FOREIGN KEY'
-            AND tc.table_name = $1
+        SELECT uri, mimetype, content, name, script_uri, created_at, updated_at, headers FROM assets WHERE script_uri = $1 AND uri = $2
         "#,
-        &physical_table_name
     )
-    .fetch_all(pool)
+    .bind(script_uri)
+    .bind(uri)
+    .fetch_optional(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching foreign keys: {}", e);
+        error!("Database error getting asset: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    let mut foreign_keys = Vec::new();
-
-    for row in rows {
-        // Look up the logical table name for the referenced table
-        let referenced_table_logical: Option<String> = sqlx::query_scalar(
-            "SELECT logical_table_name FROM script_tables WHERE script_uri = $1 AND physical_table_name = $2",
-        )
-        .bind(script_uri)
-        .bind(&row.referenced_table_physical)
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| {
-            error!("Database error fetching referenced table name: {}", e);
+    if let Some(row) = row {
+        let uri: String = row.try_get("uri").map_err(|e| {
+            error!("Database error getting uri: {}", e);
             AppError::Database {
                 message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
-
-        // Only include FK if it references another table owned by the same script
-        if let Some(referenced_logical) = referenced_table_logical
-            && let Some(column_name) = row.column_name
-        {
-            foreign_keys.push(ForeignKeyInfo {
-                column_name,
-                referenced_table_logical: referenced_logical,
-                referenced_table_physical: row.referenced_table_physical.unwrap_or_default(),
-                referenced_column: row.referenced_column.unwrap_or_else(|| "id".to_string()),
-            });
-        }
-    }
-
-    Ok(foreign_keys)
-}
-
-// ============================================================================
-// Script Database Data Access Functions
-// ============================================================================
-
-/// Supported comparison operators for query filters.
-enum FilterOp {
-    Eq,
-    Gt,
-    Gte,
-    Lt,
-    Lte,
-    Ne,
-}
-
-/// A single resolved filter condition (column, operator, value).
-struct FilterCondition {
-    column: String,
-    op: FilterOp,
-    value: serde_json::Value,
-}
-
-/// Parse a filter map into a flat list of `FilterCondition`s.
-///
-/// Supports two forms:
-/// - Equality:  `{ "col": value }`
-/// - Operators: `{ "col": { "$gt": v, "$lt": w, ... } }`
-///
-/// Allowed operators: `$gt`, `$gte`, `$lt`, `$lte`, `$ne`.
-fn parse_filter_conditions(
-    filters: &HashMap<String, serde_json::Value>,
-) -> AppResult<Vec<FilterCondition>> {
-    let mut conditions = Vec::new();
-    for (column, value) in filters {
-        validate_identifier(column).map_err(|e| AppError::Validation {
-            field: "filter_column".to_string(),
-            reason: e.to_string(),
+        let mimetype: String = row.try_get("mimetype").map_err(|e| {
+            error!("Database error getting mimetype: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
         })?;
-        match value {
-            serde_json::Value::Object(ops) => {
-                for (op_key, op_val) in ops {
-                    let op = match op_key.as_str() {
-                        "$gt" => FilterOp::Gt,
-                        "$gte" => FilterOp::Gte,
-                        "$lt" => FilterOp::Lt,
-                        "$lte" => FilterOp::Lte,
-                        "$ne" => FilterOp::Ne,
-                        other => {
-                            return Err(AppError::Validation {
-                                field: "filter_operator".to_string(),
-                                reason: format!("Unknown filter operator: {}", other),
-                            });
-                        }
-                    };
-                    conditions.push(FilterCondition {
-                        column: column.clone(),
-                        op,
-                        value: op_val.clone(),
-                    });
-                }
+        let content: Vec<u8> = row.try_get("content").map_err(|e| {
+            error!("Database error getting content: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
             }
-            _ => {
-                conditions.push(FilterCondition {
-                    column: column.clone(),
-                    op: FilterOp::Eq,
-                    value: value.clone(),
-                });
+        })?;
+        let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at").map_err(|e| {
+            error!("Database error getting created_at: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
             }
-        }
+        })?;
+        let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at").map_err(|e| {
+            error!("Database error getting updated_at: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let name: Option<String> = row.try_get("name").ok();
+        let script_uri: String = row.try_get("script_uri").map_err(|e| {
+            error!("Database error getting script_uri: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let headers = parse_asset_headers(&row)?;
+        Ok(Some(Asset {
+            uri,
+            name,
+            mimetype,
+            content,
+            created_at: created_at.into(),
+            updated_at: updated_at.into(),
+            script_uri,
+            headers,
+        }))
+    } else {
+        Ok(None)
     }
-    Ok(conditions)
 }
 
-/// Bind a single JSON value to a sqlx query as the appropriate Postgres type.
-fn bind_json_value<'q>(
-    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
-    value: &'q serde_json::Value,
-) -> AppResult<sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>> {
-    match value {
-        serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Ok(query.bind(i))
-            } else if let Some(f) = n.as_f64() {
-                Ok(query.bind(f))
-            } else {
-                Err(AppError::Validation {
-                    field: "value".to_string(),
-                    reason: "Numeric value out of range".to_string(),
-                })
-            }
+/// Database-backed get of a stored compressed variant of an asset (see
+/// [`crate::asset_compression`])
+async fn db_get_asset_encoding<'e, E>(
+    executor: E,
+    script_uri: &str,
+    uri: &str,
+    encoding: crate::asset_compression::Encoding,
+) -> AppResult<Option<Vec<u8>>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let query = match encoding {
+        crate::asset_compression::Encoding::Gzip => {
+            "SELECT content_gzip AS variant FROM assets WHERE script_uri = $1 AND uri = $2"
         }
-        serde_json::Value::String(s) => Ok(query.bind(s.as_str())),
-        serde_json::Value::Bool(b) => Ok(query.bind(*b)),
-        serde_json::Value::Null => Ok(query.bind(Option::<String>::None)),
-        _ => Err(AppError::Validation {
-            field: "value".to_string(),
-            reason: "Unsupported value type (array/object not allowed)".to_string(),
-        }),
-    }
-}
+        crate::asset_compression::Encoding::Brotli => {
+            "SELECT content_br AS variant FROM assets WHERE script_uri = $1 AND uri = $2"
+        }
+    };
+    let row = sqlx::query(query)
+        .bind(script_uri)
+        .bind(uri)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| {
+            error!("Database error getting asset variant: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
 
-/// Convert a sqlx `PgRow` to a `serde_json::Value::Object`.
-fn row_to_json(row: &sqlx::postgres::PgRow) -> serde_json::Value {
-    use sqlx::Column;
-    let mut obj = serde_json::Map::new();
-    for (idx, column) in row.columns().iter().enumerate() {
-        let column_name = column.name().to_string();
-        let value = if let Ok(v) = row.try_get::<i64, _>(idx) {
-            serde_json::Value::Number(v.into())
-        } else if let Ok(v) = row.try_get::<i32, _>(idx) {
-            serde_json::Value::Number(v.into())
-        } else if let Ok(v) = row.try_get::<String, _>(idx) {
-            serde_json::Value::String(v)
-        } else if let Ok(v) = row.try_get::<bool, _>(idx) {
-            serde_json::Value::Bool(v)
-        } else if let Ok(v) = row.try_get::<DateTime<Utc>, _>(idx) {
-            serde_json::Value::String(v.to_rfc3339())
-        } else {
-            serde_json::Value::Null
-        };
-        obj.insert(column_name, value);
+    match row {
+        Some(row) => row.try_get("variant").map_err(|e| {
+            error!("Database error getting asset variant: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        }),
+        None => Ok(None),
     }
-    serde_json::Value::Object(obj)
 }
 
-/// Look up the physical table name for a script-owned logical table.
-async fn get_physical_table_name(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<String> {
-    let physical_table_name: Option<String> = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+/// Database-backed list all assets for a script
+async fn db_list_assets<'e, E>(executor: E, script_uri: &str) -> AppResult<HashMap<String, Asset>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT uri, mimetype, content, name, script_uri, created_at, updated_at, headers FROM assets WHERE script_uri = $1 ORDER BY uri
+        "#,
     )
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(&mut *conn)
+    .fetch_all(executor)
     .await
     .map_err(|e| {
-        error!("Database error fetching table metadata: {}", e);
+        error!("Database error listing assets: {}", e);
         AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    physical_table_name.ok_or_else(|| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: format!("Table '{}' not found for this script", logical_table_name),
-    })
-}
-
-/// Query rows from a script-owned table.
-///
-/// `filters` supports equality (`{"col": value}`) and comparison operators
-/// (`{"col": {"$gt": v}}`). Supported operators: `$gt`, `$gte`, `$lt`,
-/// `$lte`, `$ne`.
-///
-/// `order_by` must be a valid column identifier; `order_dir` is `"asc"` or
-/// `"desc"` (defaults to `"asc"`).
-async fn db_query_table(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    filters: Option<&HashMap<String, serde_json::Value>>,
-    limit: Option<i64>,
-    order_by: Option<&str>,
-    order_dir: Option<&str>,
-) -> AppResult<Vec<serde_json::Value>> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
-
-    // Parse filter conditions (supports equality and range operators)
-    let conditions = if let Some(f) = filters {
-        parse_filter_conditions(f)?
-    } else {
-        Vec::new()
-    };
-
-    // Build WHERE clause
-    let mut sql = format!("SELECT * FROM {}", quote_identifier(&physical_table_name));
-    let mut param_count = 0usize;
-
-    if !conditions.is_empty() {
-        let clauses: Vec<String> = conditions
-            .iter()
-            .map(|c| {
-                param_count += 1;
-                let op_str = match c.op {
-                    FilterOp::Eq => "=",
-                    FilterOp::Gt => ">",
-                    FilterOp::Gte => ">=",
-                    FilterOp::Lt => "<",
-                    FilterOp::Lte => "<=",
-                    FilterOp::Ne => "!=",
-                };
-                format!(
-                    "{} {} ${}",
-                    quote_identifier(&c.column),
-                    op_str,
-                    param_count
-                )
-            })
-            .collect();
-        sql.push_str(&format!(" WHERE {}", clauses.join(" AND ")));
-    }
-
-    // ORDER BY
-    if let Some(order_col) = order_by {
-        validate_identifier(order_col).map_err(|e| AppError::Validation {
-            field: "order_by".to_string(),
-            reason: e.to_string(),
+    let mut assets = HashMap::new();
+    for row in rows {
+        let uri: String = row.try_get("uri").map_err(|e| {
+            error!("Database error getting uri: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
         })?;
-        let dir = match order_dir.unwrap_or("asc").to_lowercase().as_str() {
-            "desc" => "DESC",
-            _ => "ASC",
-        };
-        sql.push_str(&format!(
-            " ORDER BY {} {}",
-            quote_identifier(order_col),
-            dir
-        ));
+        let mimetype: String = row.try_get("mimetype").map_err(|e| {
+            error!("Database error getting mimetype: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let content: Vec<u8> = row.try_get("content").map_err(|e| {
+            error!("Database error getting content: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let created_at: chrono::DateTime<chrono::Utc> = row.try_get("created_at").map_err(|e| {
+            error!("Database error getting created_at: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let updated_at: chrono::DateTime<chrono::Utc> = row.try_get("updated_at").map_err(|e| {
+            error!("Database error getting updated_at: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let name: Option<String> = row.try_get("name").ok();
+        let script_uri: String = row.try_get("script_uri").map_err(|e| {
+            error!("Database error getting script_uri: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+        let headers = parse_asset_headers(&row)?;
+        assets.insert(
+            uri.clone(),
+            Asset {
+                uri,
+                name,
+                mimetype,
+                content,
+                created_at: created_at.into(),
+                updated_at: updated_at.into(),
+                script_uri,
+                headers,
+            },
+        );
     }
 
-    // LIMIT (default 100, max 1000)
-    let limit_val = limit.unwrap_or(100).min(1000);
-    param_count += 1;
-    sql.push_str(&format!(" LIMIT ${}", param_count));
+    Ok(assets)
+}
 
-    // Bind parameters
-    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-    for cond in &conditions {
-        sql_query = bind_json_value(sql_query, &cond.value)?;
-    }
-    sql_query = sql_query.bind(limit_val);
+/// Database-backed check whether a script URI has a trash entry
+async fn db_script_in_trash<'e, E>(executor: E, uri: &str) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM script_trash WHERE uri = $1)")
+        .bind(uri)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| {
+            error!("Database error checking script trash: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })
+}
 
-    let rows = sql_query.fetch_all(&mut *conn).await.map_err(|e| {
-        error!("Database error querying table: {}", e);
+/// Database-backed soft delete of a single asset
+async fn db_trash_asset<'e, E>(executor: E, script_uri: &str, uri: &str) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        WITH removed AS (
+            DELETE FROM assets WHERE script_uri = $1 AND uri = $2
+            RETURNING uri, script_uri, mimetype, content, name, headers, created_at
+        )
+        INSERT INTO asset_trash (uri, script_uri, mimetype, content, name, headers, created_at, deleted_at, deleted_with_script)
+        SELECT uri, script_uri, mimetype, content, name, headers, created_at, NOW(), FALSE
+        FROM removed
+        ON CONFLICT (uri) DO UPDATE SET
+            script_uri = EXCLUDED.script_uri,
+            mimetype = EXCLUDED.mimetype,
+            content = EXCLUDED.content,
+            name = EXCLUDED.name,
+            headers = EXCLUDED.headers,
+            created_at = EXCLUDED.created_at,
+            deleted_at = EXCLUDED.deleted_at,
+            deleted_with_script = FALSE
+        "#,
+    )
+    .bind(script_uri)
+    .bind(uri)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error moving asset to trash: {}", e);
         AppError::Database {
-            message: format!("Query error: {}", e),
+            message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    Ok(rows.iter().map(row_to_json).collect())
-}
-
-/// Insert a row into a script-owned table
-async fn db_insert_row(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
-
-    if data.is_empty() {
-        return Err(AppError::Validation {
-            field: "data".to_string(),
-            reason: "No data provided for insert".to_string(),
-        });
-    }
-
-    let mut columns = Vec::new();
-    let mut placeholders = Vec::new();
-    let mut param_count = 0usize;
-
-    for column in data.keys() {
-        validate_identifier(column).map_err(|e| AppError::Validation {
-            field: "column_name".to_string(),
-            reason: e.to_string(),
-        })?;
-        columns.push(quote_identifier(column));
-        param_count += 1;
-        placeholders.push(format!("${}", param_count));
+    let existed = result.rows_affected() > 0;
+    if existed {
+        debug!("Moved asset to trash: {}", uri);
+    } else {
+        debug!("Asset not found in database for deletion: {}", uri);
     }
 
-    let sql = format!(
-        "INSERT INTO {} ({}) VALUES ({}) RETURNING *",
-        quote_identifier(&physical_table_name),
-        columns.join(", "),
-        placeholders.join(", ")
-    );
-
-    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-    for value in data.values() {
-        sql_query = bind_json_value(sql_query, value)?;
-    }
+    Ok(existed)
+}
 
-    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
-        error!("Database error inserting row: {}", e);
+/// Database-backed restore of an individually deleted asset. The owning
+/// script must exist and the URI must still be free.
+async fn db_restore_asset<'e, E>(executor: E, script_uri: &str, uri: &str) -> AppResult<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let result = sqlx::query(
+        r#"
+        WITH restored AS (
+            DELETE FROM asset_trash
+            WHERE script_uri = $1 AND uri = $2 AND NOT deleted_with_script
+            RETURNING uri, script_uri, mimetype, content, name, headers, created_at
+        )
+        INSERT INTO assets (uri, mimetype, content, name, script_uri, headers, created_at, updated_at)
+        SELECT uri, mimetype, content, name, script_uri, headers, created_at, NOW()
+        FROM restored
+        "#,
+    )
+    .bind(script_uri)
+    .bind(uri)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error restoring asset: {}", e);
         AppError::Database {
-            message: format!("Insert error: {}", e),
+            message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    Ok(row_to_json(&row))
+    Ok(result.rows_affected() > 0)
 }
 
-/// Update a row in a script-owned table
-async fn db_update_row(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    id: i32,
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
-
-    if data.is_empty() {
-        return Err(AppError::Validation {
-            field: "data".to_string(),
-            reason: "No data provided for update".to_string(),
-        });
-    }
-
-    let mut set_clauses = Vec::new();
-    let mut param_count = 0usize;
-
-    for column in data.keys() {
-        validate_identifier(column).map_err(|e| AppError::Validation {
-            field: "column_name".to_string(),
-            reason: e.to_string(),
-        })?;
-        param_count += 1;
-        set_clauses.push(format!("{} = ${}", quote_identifier(column), param_count));
-    }
-
-    param_count += 1;
-    let sql = format!(
-        "UPDATE {} SET {} WHERE id = ${} RETURNING *",
-        quote_identifier(&physical_table_name),
-        set_clauses.join(", "),
-        param_count
-    );
-
-    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-    for value in data.values() {
-        sql_query = bind_json_value(sql_query, value)?;
-    }
-    sql_query = sql_query.bind(id);
-
-    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
-        error!("Database error updating row: {}", e);
+/// Database-backed listing of trashed scripts, most recently deleted first
+async fn db_list_trashed_scripts<'e, E>(executor: E) -> AppResult<Vec<TrashedScript>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT s.uri, s.name, s.owners, s.deleted_at,
+               (SELECT COUNT(*) FROM asset_trash a
+                WHERE a.script_uri = s.uri AND a.deleted_with_script) AS asset_count
+        FROM script_trash s
+        ORDER BY s.deleted_at DESC
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error listing trashed scripts: {}", e);
         AppError::Database {
-            message: format!("Update error: {}", e),
+            message: format!("Database error: {}", e),
             source: None,
         }
     })?;
 
-    Ok(row_to_json(&row))
+    rows.into_iter()
+        .map(|row| {
+            Ok(TrashedScript {
+                uri: row.try_get("uri")?,
+                name: row.try_get("name")?,
+                owners: row.try_get("owners")?,
+                asset_count: row.try_get("asset_count")?,
+                deleted_at: row.try_get("deleted_at")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|e| {
+            error!("Database error parsing trashed script: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })
 }
 
-/// Delete a row from a script-owned table by ID
-async fn db_delete_row(
-    conn: &mut PgConnection,
+/// Database-backed listing of individually trashed assets of a script
+async fn db_list_trashed_assets<'e, E>(
+    executor: E,
     script_uri: &str,
-    logical_table_name: &str,
-    id: i32,
-) -> AppResult<bool> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
-
-    let sql = format!(
-        "DELETE FROM {} WHERE id = $1",
-        quote_identifier(&physical_table_name)
-    );
+) -> AppResult<Vec<TrashedAsset>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows = sqlx::query(
+        r#"
+        SELECT uri, script_uri, name, mimetype, octet_length(content)::BIGINT AS size, deleted_at
+        FROM asset_trash
+        WHERE script_uri = $1 AND NOT deleted_with_script
+        ORDER BY deleted_at DESC
+        "#,
+    )
+    .bind(script_uri)
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error listing trashed assets: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    let result = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
-        .bind(id)
-        .execute(&mut *conn)
-        .await
+    rows.into_iter()
+        .map(|row| {
+            Ok(TrashedAsset {
+                uri: row.try_get("uri")?,
+                script_uri: row.try_get("script_uri")?,
+                name: row.try_get("name")?,
+                mimetype: row.try_get("mimetype")?,
+                size: row.try_get("size")?,
+                deleted_at: row.try_get("deleted_at")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
         .map_err(|e| {
-            error!("Database error deleting row: {}", e);
+            error!("Database error parsing trashed asset: {}", e);
             AppError::Database {
-                message: format!("Delete error: {}", e),
+                message: format!("Database error: {}", e),
                 source: None,
             }
-        })?;
-
-    Ok(result.rows_affected() > 0)
+        })
 }
 
-/// Upsert a row into a script-owned table using INSERT … ON CONFLICT DO UPDATE.
-///
-/// `key_columns` names the columns that form the conflict target; a unique
-/// index on those columns must exist (create one with `db_add_unique_index`).
-/// `data` must contain values for all columns, including the key columns.
-async fn db_upsert_row(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    key_columns: &[String],
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+/// Database-backed purge of trash entries deleted before `cutoff` (or of a
+/// single script's entry when `uri` is given). Drops the physical tables of
+/// purged scripts, which were kept while the script was restorable.
+async fn db_purge_trash(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+    uri: Option<&str>,
+) -> AppResult<TrashPurgeSummary> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error purging trash: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
 
-    if data.is_empty() {
-        return Err(AppError::Validation {
-            field: "data".to_string(),
-            reason: "No data provided for upsert".to_string(),
-        });
-    }
-    if key_columns.is_empty() {
-        return Err(AppError::Validation {
-            field: "key_columns".to_string(),
-            reason: "At least one key column must be specified".to_string(),
-        });
+    let purged_tables: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT t->>'physical_table_name'
+        FROM script_trash s, jsonb_array_elements(s.script_tables) AS t
+        WHERE s.deleted_at < $1 AND ($2::TEXT IS NULL OR s.uri = $2)
+          -- a re-created script may have claimed the same physical table
+          AND t->>'physical_table_name' NOT IN (SELECT physical_table_name FROM script_tables)
+        "#,
+    )
+    .bind(cutoff)
+    .bind(uri)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
+
+    for physical_name in &purged_tables {
+        let drop_sql = format!(
+            "DROP TABLE IF EXISTS {} CASCADE",
+            quote_identifier(physical_name)
+        );
+        sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
+            .execute(pool)
+            .await
+            .map_err(map_db_err)?;
     }
 
-    // Validate key columns
-    for kc in key_columns {
-        validate_identifier(kc).map_err(|e| AppError::Validation {
-            field: "key_column".to_string(),
-            reason: e.to_string(),
-        })?;
+    // Drop the private schemas used by db.query, unless the script was re-created
+    let purged_uris: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT uri FROM script_trash
+        WHERE deleted_at < $1 AND ($2::TEXT IS NULL OR uri = $2)
+          AND uri NOT IN (SELECT uri FROM scripts)
+        "#,
+    )
+    .bind(cutoff)
+    .bind(uri)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
+
+    for script_uri in &purged_uris {
+        let schema = generate_script_schema_name(script_uri);
+        let drop_sql = format!(
+            r#"
+            DO $$
+            BEGIN
+                IF EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{name}') THEN
+                    DROP OWNED BY {role} CASCADE;
+                    DROP ROLE {role};
+                END IF;
+            END
+            $$
+            "#,
+            name = schema,
+            role = quote_identifier(&schema)
+        );
+        sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
+            .execute(pool)
+            .await
+            .map_err(map_db_err)?;
+        script_schemas_ready().remove(&schema);
+
+        sqlx::query("DELETE FROM script_migrations WHERE script_uri = $1")
+            .bind(script_uri)
+            .execute(pool)
+            .await
+            .map_err(map_db_err)?;
     }
 
-    // Build column/placeholder lists (deterministic order via sorted keys)
-    let mut ordered_cols: Vec<&String> = data.keys().collect();
-    ordered_cols.sort();
+    let scripts = sqlx::query(
+        r#"
+        DELETE FROM script_trash WHERE deleted_at < $1 AND ($2::TEXT IS NULL OR uri = $2)
+        "#,
+    )
+    .bind(cutoff)
+    .bind(uri)
+    .execute(pool)
+    .await
+    .map_err(map_db_err)?
+    .rows_affected();
 
-    let mut col_list = Vec::new();
-    let mut placeholder_list = Vec::new();
-    let mut param_count = 0usize;
+    let assets = sqlx::query(
+        r#"
+        DELETE FROM asset_trash
+        WHERE deleted_at < $1 AND ($2::TEXT IS NULL OR (script_uri = $2 AND deleted_with_script))
+        "#,
+    )
+    .bind(cutoff)
+    .bind(uri)
+    .execute(pool)
+    .await
+    .map_err(map_db_err)?
+    .rows_affected();
 
-    for col in &ordered_cols {
-        validate_identifier(col).map_err(|e| AppError::Validation {
-            field: "column_name".to_string(),
-            reason: e.to_string(),
-        })?;
-        col_list.push(quote_identifier(col));
-        param_count += 1;
-        placeholder_list.push(format!("${}", param_count));
+    if scripts > 0 || assets > 0 {
+        debug!(
+            "Purged {} scripts, {} assets and {} tables from trash",
+            scripts,
+            assets,
+            purged_tables.len()
+        );
     }
 
-    // SET clause: update all non-key columns
-    let key_set: std::collections::HashSet<&str> = key_columns.iter().map(|s| s.as_str()).collect();
-    let set_clauses: Vec<String> = ordered_cols
-        .iter()
-        .filter(|c| !key_set.contains(c.as_str()))
-        .map(|c| format!("{} = EXCLUDED.{}", quote_identifier(c), quote_identifier(c)))
-        .collect();
+    Ok(TrashPurgeSummary {
+        scripts,
+        assets,
+        tables: purged_tables.len() as u64,
+    })
+}
 
-    let conflict_target = key_columns
-        .iter()
-        .map(|c| quote_identifier(c))
-        .collect::<Vec<_>>()
-        .join(", ");
+// ============================================================================
+// Idempotency Keys
+// ============================================================================
 
-    let sql = if set_clauses.is_empty() {
-        // All columns are key columns — DO NOTHING is the right action
-        format!(
-            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO NOTHING RETURNING *",
-            quote_identifier(&physical_table_name),
-            col_list.join(", "),
-            placeholder_list.join(", "),
-            conflict_target,
+/// Database-backed claim of an idempotency key. A key whose entry expired, or
+/// whose request started before `stale_before` without completing (the
+/// server handling it went away), is claimed anew.
+async fn db_claim_idempotency_key(
+    pool: &PgPool,
+    scope: &str,
+    key: &str,
+    request_hash: &str,
+    expires_at: DateTime<Utc>,
+    stale_before: DateTime<Utc>,
+) -> AppResult<IdempotencyClaim> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error claiming idempotency key: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    // The entry may be purged between the claim and the read; try again once
+    for _ in 0..2 {
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (scope, idempotency_key, request_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (scope, idempotency_key) DO UPDATE SET
+                request_hash = EXCLUDED.request_hash,
+                status = NULL,
+                content_type = NULL,
+                headers = '{}',
+                body = NULL,
+                created_at = NOW(),
+                expires_at = EXCLUDED.expires_at
+            WHERE idempotency_keys.expires_at <= NOW()
+               OR (idempotency_keys.status IS NULL AND idempotency_keys.created_at < $5)
+            "#,
         )
-    } else {
-        format!(
-            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {} RETURNING *",
-            quote_identifier(&physical_table_name),
-            col_list.join(", "),
-            placeholder_list.join(", "),
-            conflict_target,
-            set_clauses.join(", "),
+        .bind(scope)
+        .bind(key)
+        .bind(request_hash)
+        .bind(expires_at)
+        .bind(stale_before)
+        .execute(pool)
+        .await
+        .map_err(map_db_err)?
+        .rows_affected()
+            > 0;
+        if claimed {
+            return Ok(IdempotencyClaim::Claimed);
+        }
+
+        let row = sqlx::query(
+            r#"
+            SELECT request_hash, status, content_type, headers, body
+            FROM idempotency_keys WHERE scope = $1 AND idempotency_key = $2
+            "#,
         )
-    };
+        .bind(scope)
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map_err(map_db_err)?;
+        let Some(row) = row else {
+            continue;
+        };
 
-    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-    for col in &ordered_cols {
-        let value = &data[*col];
-        sql_query = bind_json_value(sql_query, value)?;
+        let request_hash: String = row.try_get("request_hash").map_err(map_db_err)?;
+        let status: Option<i32> = row.try_get("status").map_err(map_db_err)?;
+        let Some(status) = status else {
+            return Ok(IdempotencyClaim::InProgress { request_hash });
+        };
+        let headers: serde_json::Value = row.try_get("headers").map_err(map_db_err)?;
+        let headers = serde_json::from_value(headers).map_err(|e| AppError::Database {
+            message: format!("Invalid stored response headers: {}", e),
+            source: None,
+        })?;
+        let body: Option<Vec<u8>> = row.try_get("body").map_err(map_db_err)?;
+        return Ok(IdempotencyClaim::Completed {
+            request_hash,
+            response: StoredResponse {
+                status: status as u16,
+                content_type: row.try_get("content_type").map_err(map_db_err)?,
+                headers,
+                body: body.unwrap_or_default(),
+            },
+        });
     }
 
-    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
-        error!("Database error upserting row: {}", e);
+    Ok(IdempotencyClaim::InProgress {
+        request_hash: request_hash.to_string(),
+    })
+}
+
+/// Database-backed store of the response of a claimed idempotency key
+async fn db_complete_idempotency_key(
+    pool: &PgPool,
+    scope: &str,
+    key: &str,
+    response: &StoredResponse,
+) -> AppResult<()> {
+    let headers = serde_json::to_value(&response.headers).map_err(|e| AppError::Database {
+        message: format!("Failed to serialize response headers: {}", e),
+        source: None,
+    })?;
+    sqlx::query(
+        r#"
+        UPDATE idempotency_keys
+        SET status = $3, content_type = $4, headers = $5, body = $6
+        WHERE scope = $1 AND idempotency_key = $2
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .bind(i32::from(response.status))
+    .bind(&response.content_type)
+    .bind(&headers)
+    .bind(&response.body)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error storing idempotent response: {}", e);
         AppError::Database {
-            message: format!("Upsert error: {}", e),
+            message: format!("Database error: {}", e),
             source: None,
         }
     })?;
+    Ok(())
+}
 
-    Ok(row_to_json(&row))
+/// Database-backed release of a claimed idempotency key whose request did
+/// not produce a response worth replaying
+async fn db_release_idempotency_key(pool: &PgPool, scope: &str, key: &str) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM idempotency_keys
+        WHERE scope = $1 AND idempotency_key = $2 AND status IS NULL
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error releasing idempotency key: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+    Ok(())
 }
 
-/// Delete rows from a script-owned table matching the given filter conditions.
-///
-/// Supports the same filter syntax as `db_query_table` (equality and range
-/// operators). Returns the number of rows deleted.
-async fn db_delete_where(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    filters: &HashMap<String, serde_json::Value>,
-) -> AppResult<u64> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+/// Database-backed delete of expired idempotency keys
+async fn db_purge_expired_idempotency_keys(pool: &PgPool) -> AppResult<u64> {
+    let result = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= NOW()")
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error purging expired idempotency keys: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+    Ok(result.rows_affected())
+}
 
-    if filters.is_empty() {
-        return Err(AppError::Validation {
-            field: "filters".to_string(),
-            reason: "deleteWhere requires at least one filter to prevent accidental full-table delete. Use dropTable to remove all rows.".to_string(),
-        });
-    }
+// ============================================================================
+// Prompt Templates
+// ============================================================================
 
-    let conditions = parse_filter_conditions(filters)?;
+fn prompt_template_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::prompts::PromptTemplate, sqlx::Error> {
+    let variables: serde_json::Value = row.try_get("variables")?;
+    Ok(crate::prompts::PromptTemplate {
+        name: row.try_get("name")?,
+        version: row.try_get("version")?,
+        description: row.try_get("description")?,
+        template: row.try_get("template")?,
+        variables: serde_json::from_value(variables)
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+        script_uri: row.try_get("script_uri")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
 
-    let mut param_count = 0usize;
-    let clauses: Vec<String> = conditions
-        .iter()
-        .map(|c| {
-            param_count += 1;
-            let op_str = match c.op {
-                FilterOp::Eq => "=",
-                FilterOp::Gt => ">",
-                FilterOp::Gte => ">=",
-                FilterOp::Lt => "<",
-                FilterOp::Lte => "<=",
-                FilterOp::Ne => "!=",
-            };
-            format!(
-                "{} {} ${}",
-                quote_identifier(&c.column),
-                op_str,
-                param_count
-            )
-        })
-        .collect();
+/// Database-backed save of a prompt template as its next version. Saving the
+/// same template, description and variables as the latest version returns
+/// that version instead of adding one.
+async fn db_save_prompt_template(
+    pool: &PgPool,
+    script_uri: &str,
+    name: &str,
+    definition: &crate::prompts::PromptDefinition,
+) -> AppResult<crate::prompts::PromptTemplate> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error saving prompt template {}: {}", name, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let variables =
+        serde_json::to_value(&definition.variables).map_err(|e| AppError::Database {
+            message: format!("Failed to serialize prompt variables: {}", e),
+            source: None,
+        })?;
 
-    let sql = format!(
-        "DELETE FROM {} WHERE {}",
-        quote_identifier(&physical_table_name),
-        clauses.join(" AND ")
-    );
+    let mut tx = crate::database::begin(pool).await.map_err(map_db_err)?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(format!("prompt_templates:{}", name))
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_err)?;
 
-    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-    for cond in &conditions {
-        sql_query = bind_json_value(sql_query, &cond.value)?;
+    let latest = sqlx::query(
+        r#"
+        SELECT name, version, description, template, variables, script_uri, created_at
+        FROM prompt_templates WHERE name = $1
+        ORDER BY version DESC LIMIT 1
+        "#,
+    )
+    .bind(name)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(map_db_err)?
+    .map(|row| prompt_template_from_row(&row))
+    .transpose()
+    .map_err(map_db_err)?;
+
+    if let Some(latest) = &latest
+        && latest.template == definition.template
+        && latest.description == definition.description
+        && latest.variables == definition.variables
+    {
+        return Ok(latest.clone());
     }
 
-    let result = sql_query.execute(&mut *conn).await.map_err(|e| {
-        error!("Database error in deleteWhere: {}", e);
+    let row = sqlx::query(
+        r#"
+        INSERT INTO prompt_templates (name, version, description, template, variables, script_uri)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING name, version, description, template, variables, script_uri, created_at
+        "#,
+    )
+    .bind(name)
+    .bind(latest.map_or(1, |latest| latest.version + 1))
+    .bind(&definition.description)
+    .bind(&definition.template)
+    .bind(&variables)
+    .bind(script_uri)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(map_db_err)?;
+    let saved = prompt_template_from_row(&row).map_err(map_db_err)?;
+    tx.commit().await.map_err(map_db_err)?;
+
+    info!(
+        "Saved prompt template {} version {} from {}",
+        name, saved.version, script_uri
+    );
+    Ok(saved)
+}
+
+/// Database-backed lookup of a prompt template at `version`, or its latest
+/// version
+async fn db_get_prompt_template(
+    pool: &PgPool,
+    name: &str,
+    version: Option<i32>,
+) -> AppResult<Option<crate::prompts::PromptTemplate>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error getting prompt template {}: {}", name, e);
         AppError::Database {
-            message: format!("Delete error: {}", e),
+            message: format!("Database error: {}", e),
             source: None,
         }
-    })?;
+    };
+    sqlx::query(
+        r#"
+        SELECT name, version, description, template, variables, script_uri, created_at
+        FROM prompt_templates
+        WHERE name = $1 AND ($2::INTEGER IS NULL OR version = $2)
+        ORDER BY version DESC LIMIT 1
+        "#,
+    )
+    .bind(name)
+    .bind(version)
+    .fetch_optional(pool)
+    .await
+    .map_err(map_db_err)?
+    .map(|row| prompt_template_from_row(&row))
+    .transpose()
+    .map_err(map_db_err)
+}
 
-    Ok(result.rows_affected())
+/// Database-backed list of the latest version of every prompt template
+async fn db_list_prompt_templates(pool: &PgPool) -> AppResult<Vec<crate::prompts::PromptTemplate>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing prompt templates: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT DISTINCT ON (name)
+            name, version, description, template, variables, script_uri, created_at
+        FROM prompt_templates
+        ORDER BY name, version DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(prompt_template_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
 }
 
-/// Atomically acquire or extend a distributed lease stored in a script-owned table.
-///
-/// The table must have been created with `db_create_lease_table` (or manually
-/// given the schema `lease_id TEXT, owner TEXT, expires_at TIMESTAMPTZ` with a
-/// UNIQUE constraint on `lease_id`).
-///
-/// Returns `{acquired: bool, owner: string, expires_at: string}`.
-///
-/// How it works (single-statement, race-free):
-/// - If no row with `lease_id` exists → INSERT succeeds → we own the lease.
-/// - If a row exists and is expired OR already owned by us → UPDATE succeeds → we own it.
-/// - If a row exists, is not expired, and belongs to someone else → nothing changes → we do NOT own it.
-async fn db_acquire_lease(
-    conn: &mut PgConnection,
+// ============================================================================
+// LLM Usage
+// ============================================================================
+
+/// Database-backed addition of one call's usage to its monthly totals
+async fn db_record_llm_usage(
+    pool: &PgPool,
+    month: chrono::NaiveDate,
+    entry: &crate::llm_usage::LlmUsageEntry,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO llm_usage
+            (month, script_uri, user_id, provider, model, calls, input_tokens, output_tokens, cost)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (month, script_uri, user_id, provider, model) DO UPDATE SET
+            calls = llm_usage.calls + EXCLUDED.calls,
+            input_tokens = llm_usage.input_tokens + EXCLUDED.input_tokens,
+            output_tokens = llm_usage.output_tokens + EXCLUDED.output_tokens,
+            cost = llm_usage.cost + EXCLUDED.cost,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(month)
+    .bind(&entry.script_uri)
+    .bind(entry.user_id.as_deref().unwrap_or(""))
+    .bind(&entry.provider)
+    .bind(&entry.model)
+    .bind(entry.calls)
+    .bind(entry.input_tokens)
+    .bind(entry.output_tokens)
+    .bind(entry.cost)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording LLM usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+    Ok(())
+}
+
+/// Database-backed month-to-date cost of a script and of a user
+async fn db_get_llm_usage_cost(
+    pool: &PgPool,
+    month: chrono::NaiveDate,
     script_uri: &str,
-    logical_table_name: &str,
-    lease_id: &str,
-    owner: &str,
-    ttl_ms: i64,
-) -> AppResult<serde_json::Value> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+    user_id: Option<&str>,
+) -> AppResult<(f64, f64)> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error reading LLM usage cost: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let row = sqlx::query(
+        r#"
+        SELECT
+            COALESCE(SUM(cost) FILTER (WHERE script_uri = $2), 0) AS script_cost,
+            COALESCE(SUM(cost) FILTER (WHERE user_id = $3), 0) AS user_cost
+        FROM llm_usage
+        WHERE month = $1 AND (script_uri = $2 OR user_id = $3)
+        "#,
+    )
+    .bind(month)
+    .bind(script_uri)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(map_db_err)?;
+    Ok((
+        row.try_get("script_cost").map_err(map_db_err)?,
+        row.try_get("user_cost").map_err(map_db_err)?,
+    ))
+}
 
-    if ttl_ms <= 0 {
-        return Err(AppError::Validation {
-            field: "ttl_ms".to_string(),
-            reason: "ttl_ms must be a positive integer".to_string(),
-        });
-    }
+/// Database-backed list of a month's usage, optionally of one script or user
+async fn db_list_llm_usage(
+    pool: &PgPool,
+    month: chrono::NaiveDate,
+    script_uri: Option<&str>,
+    user_id: Option<&str>,
+) -> AppResult<Vec<crate::llm_usage::LlmUsageEntry>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing LLM usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let rows = sqlx::query(
+        r#"
+        SELECT script_uri, user_id, provider, model, calls, input_tokens, output_tokens, cost
+        FROM llm_usage
+        WHERE month = $1
+          AND ($2::TEXT IS NULL OR script_uri = $2)
+          AND ($3::TEXT IS NULL OR user_id = $3)
+        ORDER BY script_uri, user_id, provider, model
+        "#,
+    )
+    .bind(month)
+    .bind(script_uri)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
 
-    // Single-statement atomic upsert: wins only if slot is free or ours.
-    // bind order: $1=lease_id, $2=owner, $3=ttl_ms (bigint milliseconds)
-    let sql = format!(
+    rows.iter()
+        .map(|row| {
+            let user_id: String = row.try_get("user_id")?;
+            Ok(crate::llm_usage::LlmUsageEntry {
+                script_uri: row.try_get("script_uri")?,
+                user_id: (!user_id.is_empty()).then_some(user_id),
+                provider: row.try_get("provider")?,
+                model: row.try_get("model")?,
+                calls: row.try_get("calls")?,
+                input_tokens: row.try_get("input_tokens")?,
+                output_tokens: row.try_get("output_tokens")?,
+                cost: row.try_get("cost")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(map_db_err)
+}
+
+// ============================================================================
+// Route Usage
+// ============================================================================
+
+/// Database-backed addition of a route's counts to its day's totals
+async fn db_record_route_usage(
+    pool: &PgPool,
+    entry: &crate::route_usage::RouteUsageEntry,
+) -> AppResult<()> {
+    let status_counts: serde_json::Map<String, serde_json::Value> = entry
+        .status_counts
+        .iter()
+        .map(|(status, count)| (status.to_string(), (*count).into()))
+        .collect();
+    sqlx::query(
         r#"
-        INSERT INTO {tbl} (lease_id, owner, expires_at)
-        VALUES ($1, $2, NOW() + ($3::bigint * interval '1 millisecond'))
-        ON CONFLICT (lease_id) DO UPDATE
-            SET owner      = EXCLUDED.owner,
-                expires_at = EXCLUDED.expires_at
-        WHERE {tbl}.expires_at <= NOW()
-           OR {tbl}.owner = EXCLUDED.owner
-        RETURNING owner, expires_at
+        INSERT INTO route_usage
+            (day, script_uri, route, method, requests, total_ms, max_ms, status_counts,
+             latency_buckets, last_request_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (day, script_uri, route, method) DO UPDATE SET
+            requests = route_usage.requests + EXCLUDED.requests,
+            total_ms = route_usage.total_ms + EXCLUDED.total_ms,
+            max_ms = GREATEST(route_usage.max_ms, EXCLUDED.max_ms),
+            status_counts = (
+                SELECT COALESCE(jsonb_object_agg(
+                    key,
+                    COALESCE((route_usage.status_counts->>key)::BIGINT, 0)
+                        + COALESCE((EXCLUDED.status_counts->>key)::BIGINT, 0)
+                ), '{}'::JSONB)
+                FROM jsonb_object_keys(route_usage.status_counts || EXCLUDED.status_counts) AS key
+            ),
+            latency_buckets = ARRAY(
+                SELECT COALESCE(old, 0) + COALESCE(new, 0)
+                FROM unnest(route_usage.latency_buckets, EXCLUDED.latency_buckets)
+                    WITH ORDINALITY AS buckets(old, new, position)
+                ORDER BY position
+            ),
+            last_request_at = GREATEST(route_usage.last_request_at, EXCLUDED.last_request_at)
         "#,
-        tbl = quote_identifier(&physical_table_name)
-    );
+    )
+    .bind(entry.day)
+    .bind(&entry.script_uri)
+    .bind(&entry.route)
+    .bind(&entry.method)
+    .bind(entry.requests)
+    .bind(entry.total_ms)
+    .bind(entry.max_ms)
+    .bind(serde_json::Value::Object(status_counts))
+    .bind(&entry.latency_buckets)
+    .bind(entry.last_request_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording route usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+    Ok(())
+}
 
-    let upsert_row = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
-        .bind(lease_id)
-        .bind(owner)
-        .bind(ttl_ms)
-        .fetch_optional(&mut *conn)
-        .await
-        .map_err(|e| {
-            error!("Database error in acquireLease: {}", e);
-            AppError::Database {
-                message: format!("Lease error: {}", e),
-                source: None,
-            }
-        })?;
+/// Database-backed list of a script's route usage since a day
+async fn db_list_route_usage(
+    pool: &PgPool,
+    script_uri: &str,
+    since: chrono::NaiveDate,
+) -> AppResult<Vec<crate::route_usage::RouteUsageEntry>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing route usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let rows = sqlx::query(
+        r#"
+        SELECT day, script_uri, route, method, requests, total_ms, max_ms, status_counts,
+            latency_buckets, last_request_at
+        FROM route_usage
+        WHERE script_uri = $1 AND day >= $2
+        ORDER BY day, route, method
+        "#,
+    )
+    .bind(script_uri)
+    .bind(since)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
 
-    if let Some(row) = upsert_row {
-        // Upsert succeeded — we hold the lease
-        let row_owner: String = row.try_get("owner").unwrap_or_default();
-        let expires_at: String = row
-            .try_get::<DateTime<Utc>, _>("expires_at")
-            .map(|dt| dt.to_rfc3339())
-            .unwrap_or_default();
-        return Ok(serde_json::json!({
-            "acquired": row_owner == owner,
-            "owner": row_owner,
-            "expires_at": expires_at,
-        }));
-    }
+    rows.iter()
+        .map(|row| {
+            let status_counts: serde_json::Value = row.try_get("status_counts")?;
+            Ok(crate::route_usage::RouteUsageEntry {
+                day: row.try_get("day")?,
+                script_uri: row.try_get("script_uri")?,
+                route: row.try_get("route")?,
+                method: row.try_get("method")?,
+                requests: row.try_get("requests")?,
+                total_ms: row.try_get("total_ms")?,
+                max_ms: row.try_get("max_ms")?,
+                status_counts: status_counts
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(status, count)| Some((status.parse().ok()?, count.as_i64()?)))
+                    .collect(),
+                latency_buckets: row.try_get("latency_buckets")?,
+                last_request_at: row.try_get("last_request_at")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(map_db_err)
+}
 
-    // Upsert produced no row — someone else holds an active lease.
-    // Do a plain SELECT to return current lease info (best-effort, non-critical).
-    let select_sql = format!(
-        "SELECT owner, expires_at FROM {} WHERE lease_id = $1",
-        quote_identifier(&physical_table_name)
-    );
-    let current = sqlx::query(sqlx::AssertSqlSafe(select_sql.as_str()))
-        .bind(lease_id)
-        .fetch_optional(&mut *conn)
+/// Database-backed removal of route usage of days before `before`
+async fn db_purge_route_usage(pool: &PgPool, before: chrono::NaiveDate) -> AppResult<u64> {
+    let result = sqlx::query("DELETE FROM route_usage WHERE day < $1")
+        .bind(before)
+        .execute(pool)
         .await
         .map_err(|e| {
-            error!("Database error reading current lease: {}", e);
+            error!("Database error purging route usage: {}", e);
             AppError::Database {
-                message: format!("Lease read error: {}", e),
+                message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
+    Ok(result.rows_affected())
+}
 
-    if let Some(row) = current {
-        let row_owner: String = row.try_get("owner").unwrap_or_default();
-        let expires_at: String = row
-            .try_get::<DateTime<Utc>, _>("expires_at")
-            .map(|dt| dt.to_rfc3339())
-            .unwrap_or_default();
-        Ok(serde_json::json!({
-            "acquired": false,
-            "owner": row_owner,
-            "expires_at": expires_at,
-        }))
-    } else {
-        Ok(serde_json::json!({
-            "acquired": false,
-            "owner": serde_json::Value::Null,
-            "expires_at": serde_json::Value::Null,
-        }))
-    }
-}
-
-/// Create a properly structured lease table owned by the given script.
-///
-/// The table schema is: `lease_id TEXT UNIQUE NOT NULL, owner TEXT NOT NULL,
-/// expires_at TIMESTAMPTZ NOT NULL`. An explicit UNIQUE index on `lease_id`
-/// is created so that `acquireLease` can use it as a conflict target.
-///
-/// Returns the physical table name on success.
-async fn db_create_lease_table(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<String> {
-    use crate::db_schema_utils::{
-        MAX_TABLES_PER_SCRIPT, generate_physical_table_name, validate_identifier,
-    };
+// ============================================================================
+// Script Resource Usage
+// ============================================================================
 
-    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
-        field: "table_name".to_string(),
-        reason: e.to_string(),
+/// Database-backed addition of a script's resource usage to its day's totals
+async fn db_record_script_resources(
+    pool: &PgPool,
+    entry: &crate::script_resources::ScriptResourceEntry,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO script_resource_usage
+            (day, script_uri, executions, cpu_ms, peak_memory_bytes, timeouts, outbound_bytes,
+             last_execution_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (day, script_uri) DO UPDATE SET
+            executions = script_resource_usage.executions + EXCLUDED.executions,
+            cpu_ms = script_resource_usage.cpu_ms + EXCLUDED.cpu_ms,
+            peak_memory_bytes = GREATEST(
+                script_resource_usage.peak_memory_bytes, EXCLUDED.peak_memory_bytes
+            ),
+            timeouts = script_resource_usage.timeouts + EXCLUDED.timeouts,
+            outbound_bytes = script_resource_usage.outbound_bytes + EXCLUDED.outbound_bytes,
+            last_execution_at = GREATEST(
+                script_resource_usage.last_execution_at, EXCLUDED.last_execution_at
+            )
+        "#,
+    )
+    .bind(entry.day)
+    .bind(&entry.script_uri)
+    .bind(entry.executions)
+    .bind(entry.cpu_ms)
+    .bind(entry.peak_memory_bytes)
+    .bind(entry.timeouts)
+    .bind(entry.outbound_bytes)
+    .bind(entry.last_execution_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording script resource usage: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
     })?;
+    Ok(())
+}
 
-    // Count existing tables for this script
-    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM script_tables WHERE script_uri = $1")
-        .bind(script_uri)
-        .fetch_one(&mut *conn)
-        .await
-        .map_err(|e| AppError::Database {
+/// Database-backed list of script resource usage since a day, of one script
+/// or all
+async fn db_list_script_resources(
+    pool: &PgPool,
+    script_uri: Option<&str>,
+    since: chrono::NaiveDate,
+) -> AppResult<Vec<crate::script_resources::ScriptResourceEntry>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing script resource usage: {}", e);
+        AppError::Database {
             message: format!("Database error: {}", e),
             source: None,
-        })?;
-
-    if count >= MAX_TABLES_PER_SCRIPT as i64 {
-        return Err(AppError::Validation {
-            field: "table_name".to_string(),
-            reason: format!("Maximum table limit of {} reached", MAX_TABLES_PER_SCRIPT),
-        });
-    }
-
-    // Check for duplicates
-    let existing: Option<String> = sqlx::query_scalar(
-        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+        }
+    };
+    let rows = sqlx::query(
+        r#"
+        SELECT day, script_uri, executions, cpu_ms, peak_memory_bytes, timeouts, outbound_bytes,
+            last_execution_at
+        FROM script_resource_usage
+        WHERE day >= $1 AND ($2::TEXT IS NULL OR script_uri = $2)
+        ORDER BY day, script_uri
+        "#,
     )
+    .bind(since)
     .bind(script_uri)
-    .bind(logical_table_name)
-    .fetch_optional(&mut *conn)
+    .fetch_all(pool)
     .await
-    .map_err(|e| AppError::Database {
-        message: format!("Database error: {}", e),
-        source: None,
-    })?;
-
-    if let Some(existing_physical) = existing {
-        // Table already exists — idempotent
-        return Ok(existing_physical);
-    }
+    .map_err(map_db_err)?;
 
-    let physical_name = generate_physical_table_name(script_uri, logical_table_name);
-
-    // Create the table with the required lease schema
-    let create_sql = format!(
-        r#"CREATE TABLE {} (
-            lease_id  TEXT        NOT NULL,
-            owner     TEXT        NOT NULL,
-            expires_at TIMESTAMPTZ NOT NULL,
-            CONSTRAINT {}_pkey PRIMARY KEY (lease_id)
-        )"#,
-        quote_identifier(&physical_name),
-        physical_name,
-    );
+    rows.iter()
+        .map(|row| {
+            Ok(crate::script_resources::ScriptResourceEntry {
+                day: row.try_get("day")?,
+                script_uri: row.try_get("script_uri")?,
+                executions: row.try_get("executions")?,
+                cpu_ms: row.try_get("cpu_ms")?,
+                peak_memory_bytes: row.try_get("peak_memory_bytes")?,
+                timeouts: row.try_get("timeouts")?,
+                outbound_bytes: row.try_get("outbound_bytes")?,
+                last_execution_at: row.try_get("last_execution_at")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(map_db_err)
+}
 
-    sqlx::query(sqlx::AssertSqlSafe(create_sql.as_str()))
-        .execute(&mut *conn)
+/// Database-backed removal of script resource usage of days before `before`
+async fn db_purge_script_resources(pool: &PgPool, before: chrono::NaiveDate) -> AppResult<u64> {
+    let result = sqlx::query("DELETE FROM script_resource_usage WHERE day < $1")
+        .bind(before)
+        .execute(pool)
         .await
-        .map_err(|e| AppError::Database {
-            message: format!("Error creating lease table: {}", e),
-            source: None,
+        .map_err(|e| {
+            error!("Database error purging script resource usage: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
         })?;
-
-    // Register in metadata
-    sqlx::query(
-        "INSERT INTO script_tables (script_uri, logical_table_name, physical_table_name, created_at)
-         VALUES ($1, $2, $3, NOW())",
-    )
-    .bind(script_uri)
-    .bind(logical_table_name)
-    .bind(&physical_name)
-    .execute(&mut *conn)
-    .await
-    .map_err(|e| AppError::Database {
-        message: format!("Error registering lease table: {}", e),
-        source: None,
-    })?;
-
-    debug!(
-        "Created lease table '{}' (physical: '{}') for script '{}'",
-        logical_table_name, physical_name, script_uri
-    );
-
-    Ok(physical_name)
+    Ok(result.rows_affected())
 }
 
-/// Add a unique index on one or more columns of a script-owned table.
-///
-/// This is required before using those columns as a conflict target in
-/// `db_upsert_row`. Index names are derived from the physical table name and
-/// columns to avoid collisions.
-async fn db_add_unique_index(
-    conn: &mut PgConnection,
-    script_uri: &str,
-    logical_table_name: &str,
-    columns: &[String],
-) -> AppResult<()> {
-    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+// ============================================================================
+// Usage Records
+// ============================================================================
 
-    if columns.is_empty() {
-        return Err(AppError::Validation {
-            field: "columns".to_string(),
-            reason: "At least one column must be specified".to_string(),
-        });
+/// Database-backed insert of metering records; records already stored by an
+/// earlier attempt are skipped
+async fn db_insert_usage_records(
+    pool: &PgPool,
+    records: &[crate::metering::UsageRecord],
+) -> AppResult<()> {
+    if records.is_empty() {
+        return Ok(());
     }
-
-    for col in columns {
-        validate_identifier(col).map_err(|e| AppError::Validation {
-            field: "column".to_string(),
-            reason: e.to_string(),
-        })?;
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error storing usage records: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let mut tx = pool.begin().await.map_err(map_db_err)?;
+    for record in records {
+        sqlx::query(
+            r#"
+            INSERT INTO usage_records
+                (id, metric, quantity, unit, tenant, user_id, script_uri, period_start, period_end)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(record.id)
+        .bind(record.metric.as_str())
+        .bind(record.quantity)
+        .bind(record.metric.unit())
+        .bind(&record.tenant)
+        .bind(&record.user_id)
+        .bind(&record.script_uri)
+        .bind(record.period_start)
+        .bind(record.period_end)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_err)?;
     }
+    tx.commit().await.map_err(map_db_err)
+}
 
-    // Build a deterministic index name
-    let cols_slug = columns.join("_");
-    // Truncate to stay within PostgreSQL's 63-char identifier limit
-    let index_name = format!(
-        "{}_uniq_{}",
-        &physical_table_name[..physical_table_name.len().min(40)],
-        &cols_slug[..cols_slug.len().min(20)]
-    );
-
-    let col_list = columns
-        .iter()
-        .map(|c| quote_identifier(c))
-        .collect::<Vec<_>>()
-        .join(", ");
-
-    let sql = format!(
-        "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({})",
-        quote_identifier(&index_name),
-        quote_identifier(&physical_table_name),
-        col_list
-    );
-
-    sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
-        .execute(&mut *conn)
+/// Database-backed removal of usage records whose period ended before
+/// `before`
+async fn db_purge_usage_records(pool: &PgPool, before: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query("DELETE FROM usage_records WHERE period_end < $1")
+        .bind(before)
+        .execute(pool)
         .await
         .map_err(|e| {
-            error!("Database error adding unique index: {}", e);
+            error!("Database error purging usage records: {}", e);
             AppError::Database {
-                message: format!("Index error: {}", e),
+                message: format!("Database error: {}", e),
                 source: None,
             }
         })?;
-
-    debug!(
-        "Created unique index '{}' on table '{}' for script '{}'",
-        index_name, logical_table_name, script_uri
-    );
-
-    Ok(())
+    Ok(result.rows_affected())
 }
 
-/// Fetch scripts from repository with proper error handling
-pub fn fetch_scripts() -> HashMap<String, String> {
-    let repo = get_repository();
+// ============================================================================
+// Threat Actions
+// ============================================================================
 
-    // Use run_blocking to call async repository method
-    let result = run_blocking(async { repo.list_scripts().await });
+fn threat_action_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::security::threat_response::ThreatAction, sqlx::Error> {
+    use crate::security::threat_response::ThreatActionStatus;
+
+    let action: String = row.try_get("action")?;
+    let status: String = row.try_get("status")?;
+    Ok(crate::security::threat_response::ThreatAction {
+        id: row.try_get("id")?,
+        policy: row.try_get("policy")?,
+        action: crate::config::ThreatActionKind::parse(&action).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown threat action '{}'", action).into())
+        })?,
+        subject: row.try_get("subject")?,
+        indicator: row.try_get("indicator")?,
+        description: row.try_get("description")?,
+        status: ThreatActionStatus::parse(&status).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown threat action status '{}'", status).into())
+        })?,
+        duration_secs: row.try_get("duration_secs")?,
+        expires_at: row.try_get("expires_at")?,
+        created_at: row.try_get("created_at")?,
+        decided_by: row.try_get("decided_by")?,
+        decided_at: row.try_get("decided_at")?,
+    })
+}
 
-    match result {
-        Ok(scripts) => {
-            debug!("Loaded {} scripts from repository", scripts.len());
-            scripts
-        }
-        Err(e) => {
-            error!("Failed to fetch scripts: {}", e);
-            HashMap::new()
+/// Database-backed record of an action taken or queued by a threat
+/// response policy
+async fn db_insert_threat_action(
+    pool: &PgPool,
+    action: &crate::security::threat_response::ThreatAction,
+) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO threat_actions
+            (id, policy, action, subject, indicator, description, status,
+             duration_secs, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+    )
+    .bind(action.id)
+    .bind(&action.policy)
+    .bind(action.action.as_str())
+    .bind(&action.subject)
+    .bind(&action.indicator)
+    .bind(&action.description)
+    .bind(action.status.as_str())
+    .bind(action.duration_secs)
+    .bind(action.expires_at)
+    .bind(action.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!(
+            "Database error recording threat action {}: {}",
+            action.id, e
+        );
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    })?;
+    Ok(())
 }
 
-/// Fetch a single script by URI with proper error handling
-pub fn fetch_script(uri: &str) -> Option<String> {
-    let repo = get_repository();
-
-    let result = run_blocking(async { repo.get_script(uri).await });
-
-    match result {
-        Ok(Some(script)) => {
-            debug!("Loaded script from repository: {}", uri);
-            Some(script)
+/// Database-backed list of threat actions, newest first
+async fn db_list_threat_actions(
+    pool: &PgPool,
+    status: Option<crate::security::threat_response::ThreatActionStatus>,
+    limit: i64,
+) -> AppResult<Vec<crate::security::threat_response::ThreatAction>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing threat actions: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-        Ok(None) => None,
-        Err(e) => {
-            warn!("Failed to fetch script {}: {}", uri, e);
-            None
+    };
+    sqlx::query(
+        r#"
+        SELECT id, policy, action, subject, indicator, description, status,
+               duration_secs, expires_at, created_at, decided_by, decided_at
+        FROM threat_actions
+        WHERE ($1::TEXT IS NULL OR status = $1)
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(status.map(|status| status.as_str()))
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(threat_action_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
+}
+
+/// Database-backed list of the active threat actions that have not expired
+async fn db_list_active_threat_actions(
+    pool: &PgPool,
+) -> AppResult<Vec<crate::security::threat_response::ThreatAction>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing active threat actions: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, policy, action, subject, indicator, description, status,
+               duration_secs, expires_at, created_at, decided_by, decided_at
+        FROM threat_actions
+        WHERE status = 'active' AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(threat_action_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
 }
 
-/// Get metadata for a script
-pub fn get_script_metadata(uri: &str) -> AppResult<ScriptMetadata> {
-    let repo = get_repository();
-    run_blocking(async { repo.get_script_metadata(uri).await })
+/// Database-backed check for a pending or unexpired active action on a
+/// subject
+async fn db_has_open_threat_action(
+    pool: &PgPool,
+    action: crate::config::ThreatActionKind,
+    subject: &str,
+) -> AppResult<bool> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM threat_actions
+            WHERE action = $1 AND subject = $2
+              AND (status = 'pending'
+                   OR (status = 'active' AND (expires_at IS NULL OR expires_at > NOW())))
+        )
+        "#,
+    )
+    .bind(action.as_str())
+    .bind(subject)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        error!(
+            "Database error checking threat actions on {}: {}",
+            subject, e
+        );
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })
 }
 
-/// Get metadata for all scripts
-pub fn get_all_script_metadata() -> AppResult<Vec<ScriptMetadata>> {
-    let repo = get_repository();
-    run_blocking(async { repo.get_all_script_metadata().await })
+/// Database-backed decision on a threat action in status `from`. An action
+/// made active starts its duration now. Returns `None` when there is no
+/// such action in that status.
+async fn db_decide_threat_action(
+    pool: &PgPool,
+    id: uuid::Uuid,
+    from: crate::security::threat_response::ThreatActionStatus,
+    to: crate::security::threat_response::ThreatActionStatus,
+    decided_by: &str,
+) -> AppResult<Option<crate::security::threat_response::ThreatAction>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error deciding threat action {}: {}", id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        UPDATE threat_actions
+        SET status = $3,
+            decided_by = $4,
+            decided_at = NOW(),
+            expires_at = CASE
+                WHEN $3 = 'active' AND duration_secs > 0
+                    THEN NOW() + duration_secs * INTERVAL '1 second'
+                ELSE expires_at
+            END
+        WHERE id = $1 AND status = $2
+        RETURNING id, policy, action, subject, indicator, description, status,
+                  duration_secs, expires_at, created_at, decided_by, decided_at
+        "#,
+    )
+    .bind(id)
+    .bind(from.as_str())
+    .bind(to.as_str())
+    .bind(decided_by)
+    .fetch_optional(pool)
+    .await
+    .map_err(map_db_err)?
+    .as_ref()
+    .map(threat_action_from_row)
+    .transpose()
+    .map_err(map_db_err)
 }
 
-pub fn mark_script_init_failed(uri: &str, error: String) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.update_script_init_status(uri, false, Some(error), None)
-            .await
+// ============================================================================
+// Script Capability Manifests
+// ============================================================================
+
+fn script_manifest_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::security::script_manifests::ScriptManifest, sqlx::Error> {
+    use crate::security::script_manifests::ScriptCapability;
+
+    let parse = |column: &str| -> Result<Vec<ScriptCapability>, sqlx::Error> {
+        let names: Vec<String> = row.try_get(column)?;
+        names
+            .iter()
+            .map(|name| {
+                ScriptCapability::parse(name).ok_or_else(|| {
+                    sqlx::Error::Decode(format!("unknown script capability '{}'", name).into())
+                })
+            })
+            .collect()
+    };
+    Ok(crate::security::script_manifests::ScriptManifest {
+        uri: row.try_get("uri")?,
+        requested: parse("requested_capabilities")?,
+        approved: parse("approved_capabilities")?,
+        approved_by: row.try_get("capabilities_approved_by")?,
+        approved_at: row.try_get("capabilities_approved_at")?,
     })
 }
 
-pub fn mark_script_initialized(uri: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.update_script_init_status(uri, true, None, None).await })
+fn capability_names(
+    capabilities: &[crate::security::script_manifests::ScriptCapability],
+) -> Vec<String> {
+    capabilities
+        .iter()
+        .map(|capability| capability.as_str().to_string())
+        .collect()
 }
 
-pub fn mark_script_initialized_with_registrations(
+/// Capability manifest of a script, None if the script doesn't exist
+async fn db_get_script_manifest(
+    pool: &PgPool,
     uri: &str,
-    registrations: RouteRegistrations,
-) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.update_script_init_status(uri, true, None, Some(registrations))
-            .await
-    })
+) -> AppResult<Option<crate::security::script_manifests::ScriptManifest>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!(
+            "Database error getting capability manifest of {}: {}",
+            uri, e
+        );
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT uri, requested_capabilities, approved_capabilities,
+               capabilities_approved_by, capabilities_approved_at
+        FROM scripts
+        WHERE uri = $1
+        "#,
+    )
+    .bind(uri)
+    .fetch_optional(pool)
+    .await
+    .map_err(map_db_err)?
+    .as_ref()
+    .map(script_manifest_from_row)
+    .transpose()
+    .map_err(map_db_err)
 }
 
-/// Determine current privilege state for a script
-pub fn get_script_security_profile(uri: &str) -> AppResult<ScriptSecurityProfile> {
-    let default_privileged = default_privileged_for(uri);
-    let repo = get_repository();
+/// Store the capability manifest of a script
+async fn db_update_script_manifest(
+    pool: &PgPool,
+    manifest: &crate::security::script_manifests::ScriptManifest,
+) -> AppResult<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scripts
+        SET requested_capabilities = $2,
+            approved_capabilities = $3,
+            capabilities_approved_by = $4,
+            capabilities_approved_at = $5,
+            updated_at = NOW()
+        WHERE uri = $1
+        "#,
+    )
+    .bind(&manifest.uri)
+    .bind(capability_names(&manifest.requested))
+    .bind(capability_names(&manifest.approved))
+    .bind(&manifest.approved_by)
+    .bind(manifest.approved_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!(
+            "Database error updating capability manifest of {}: {}",
+            manifest.uri, e
+        );
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    // Try repository first
-    let result = run_blocking(async { repo.get_script_privileged(uri).await });
+    Ok(result.rows_affected() > 0)
+}
 
-    if let Ok(Some(privileged)) = result {
-        return Ok(ScriptSecurityProfile {
-            uri: uri.to_string(),
-            privileged,
-            default_privileged,
-        });
-    }
+/// Capability manifests with requested capabilities that aren't approved
+async fn db_list_pending_script_manifests(
+    pool: &PgPool,
+) -> AppResult<Vec<crate::security::script_manifests::ScriptManifest>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing pending capability manifests: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT uri, requested_capabilities, approved_capabilities,
+               capabilities_approved_by, capabilities_approved_at
+        FROM scripts
+        WHERE NOT (requested_capabilities <@ approved_capabilities)
+        ORDER BY updated_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(script_manifest_from_row)
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(map_db_err)
+}
 
-    // If repository didn't have it (or failed), check overrides explicitly
-    // This handles the case where we are in Postgres mode but the script is static/local
-    // and has a local override.
-    if let Ok(guard) = safe_lock_privilege_overrides()
-        && let Some(value) = guard.get(uri)
-    {
-        return Ok(ScriptSecurityProfile {
-            uri: uri.to_string(),
-            privileged: *value,
-            default_privileged,
-        });
-    }
+// ============================================================================
+// Webhook Deliveries
+// ============================================================================
 
-    Ok(ScriptSecurityProfile {
-        uri: uri.to_string(),
-        privileged: default_privileged,
-        default_privileged,
+fn webhook_delivery_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::webhooks::WebhookDelivery, sqlx::Error> {
+    let status: String = row.try_get("status")?;
+    Ok(crate::webhooks::WebhookDelivery {
+        id: row.try_get("id")?,
+        script_uri: row.try_get("script_uri")?,
+        url: row.try_get("url")?,
+        status: crate::webhooks::DeliveryStatus::parse(&status).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown webhook status '{}'", status).into())
+        })?,
+        attempts: row.try_get("attempts")?,
+        max_attempts: row.try_get("max_attempts")?,
+        next_attempt_at: row.try_get("next_attempt_at")?,
+        last_status_code: row.try_get("last_status_code")?,
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        delivered_at: row.try_get("delivered_at")?,
     })
 }
 
-/// Helper used by security enforcement
-pub fn is_script_privileged(uri: &str) -> AppResult<bool> {
-    Ok(get_script_security_profile(uri)?.privileged)
+/// Database-backed queueing of a webhook delivery, due at once
+async fn db_enqueue_webhook<'e, E>(
+    executor: E,
+    webhook: &crate::webhooks::NewWebhook,
+) -> AppResult<crate::webhooks::WebhookDelivery>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error queueing webhook {}: {}", webhook.id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let headers = serde_json::to_value(&webhook.headers).map_err(|e| AppError::Database {
+        message: format!("Failed to serialize webhook headers: {}", e),
+        source: None,
+    })?;
+    let row = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries
+            (id, script_uri, url, payload, content_type, headers, secret_name, max_attempts)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, script_uri, url, status, attempts, max_attempts, next_attempt_at,
+            last_status_code, last_error, created_at, updated_at, delivered_at
+        "#,
+    )
+    .bind(webhook.id)
+    .bind(&webhook.script_uri)
+    .bind(&webhook.url)
+    .bind(&webhook.payload)
+    .bind(&webhook.content_type)
+    .bind(&headers)
+    .bind(&webhook.secret_name)
+    .bind(webhook.max_attempts)
+    .fetch_one(executor)
+    .await
+    .map_err(map_db_err)?;
+    webhook_delivery_from_row(&row).map_err(map_db_err)
 }
 
-/// Update the privilege flag for a script
-pub fn set_script_privileged(uri: &str, privileged: bool) -> AppResult<()> {
-    // Ensure script exists before toggling
-    if fetch_script(uri).is_none() {
-        return Err(RepositoryError::ScriptNotFound(uri.to_string()).into());
-    }
-
-    let repo = get_repository();
-
-    // 1. Try to update repository (DB or Memory)
-    let result = run_blocking(async { repo.set_script_privileged(uri, privileged).await });
-
-    if let Err(e) = result {
-        warn!("Repository set privileged failed for {}: {}", uri, e);
-    }
-
-    // 2. Also update in-memory structures to ensure static scripts or fallbacks work
-
-    // Update dynamic metadata cache if present
-    if let Ok(mut guard) = safe_lock_scripts()
-        && let Some(metadata) = guard.get_mut(uri)
-    {
-        metadata.privileged = privileged;
-        debug!(script = %uri, privileged, "Updated in-memory script privilege");
-        return Ok(());
-    }
+/// Database-backed claim of up to `limit` due deliveries for one attempt
+/// each. Claimed deliveries are skipped by other claims until `lock_for`
+/// passes, so an attempt whose worker died is retried.
+async fn db_claim_webhooks(
+    pool: &PgPool,
+    limit: i64,
+    lock_for: Duration,
+) -> AppResult<Vec<crate::webhooks::ClaimedWebhook>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error claiming webhooks: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let rows = sqlx::query(
+        r#"
+        WITH due AS (
+            SELECT id FROM webhook_deliveries
+            WHERE status = 'pending'
+              AND next_attempt_at <= NOW()
+              AND (locked_until IS NULL OR locked_until <= NOW())
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE webhook_deliveries AS deliveries
+        SET attempts = deliveries.attempts + 1,
+            locked_until = NOW() + make_interval(secs => $2),
+            updated_at = NOW()
+        FROM due
+        WHERE deliveries.id = due.id
+        RETURNING deliveries.id, deliveries.script_uri, deliveries.url, deliveries.payload,
+            deliveries.content_type, deliveries.headers, deliveries.secret_name,
+            deliveries.attempts, deliveries.max_attempts
+        "#,
+    )
+    .bind(limit)
+    .bind(lock_for.as_secs_f64())
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
 
-    // Persist override for static scripts or when metadata not present
-    let mut guard = safe_lock_privilege_overrides()?;
-    guard.insert(uri.to_string(), privileged);
-    debug!(script = %uri, privileged, "Stored privilege override in memory");
+    rows.iter()
+        .map(|row| {
+            let headers: serde_json::Value = row.try_get("headers")?;
+            Ok(crate::webhooks::ClaimedWebhook {
+                webhook: crate::webhooks::NewWebhook {
+                    id: row.try_get("id")?,
+                    script_uri: row.try_get("script_uri")?,
+                    url: row.try_get("url")?,
+                    payload: row.try_get("payload")?,
+                    content_type: row.try_get("content_type")?,
+                    headers: serde_json::from_value(headers)
+                        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                    secret_name: row.try_get("secret_name")?,
+                    max_attempts: row.try_get("max_attempts")?,
+                },
+                attempts: row.try_get("attempts")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(map_db_err)
+}
 
+/// Database-backed record of how a claimed attempt went
+async fn db_finish_webhook_attempt(
+    pool: &PgPool,
+    id: uuid::Uuid,
+    outcome: &crate::webhooks::AttemptOutcome,
+) -> AppResult<()> {
+    use crate::webhooks::AttemptOutcome;
+    let (status, status_code, error, next_attempt_at) = match outcome {
+        AttemptOutcome::Delivered { status_code } => ("delivered", Some(*status_code), None, None),
+        AttemptOutcome::Retry {
+            status_code,
+            error,
+            next_attempt_at,
+        } => (
+            "pending",
+            *status_code,
+            Some(error.as_str()),
+            Some(*next_attempt_at),
+        ),
+        AttemptOutcome::Failed { status_code, error } => {
+            ("failed", *status_code, Some(error.as_str()), None)
+        }
+    };
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2,
+            last_status_code = $3,
+            last_error = $4,
+            next_attempt_at = COALESCE($5, next_attempt_at),
+            delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() ELSE delivered_at END,
+            locked_until = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(status)
+    .bind(status_code)
+    .bind(error)
+    .bind(next_attempt_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording webhook attempt {}: {}", id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
     Ok(())
 }
 
-/// Insert log message with error handling
-pub fn insert_log_message(script_uri: &str, message: &str, log_level: &str) {
-    run_blocking(insert_log_message_async(script_uri, message, log_level))
+/// Database-backed lookup of a webhook delivery
+async fn db_get_webhook_delivery(
+    pool: &PgPool,
+    id: uuid::Uuid,
+) -> AppResult<Option<crate::webhooks::WebhookDelivery>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error getting webhook {}: {}", id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, script_uri, url, status, attempts, max_attempts, next_attempt_at,
+            last_status_code, last_error, created_at, updated_at, delivered_at
+        FROM webhook_deliveries WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(map_db_err)?
+    .map(|row| webhook_delivery_from_row(&row))
+    .transpose()
+    .map_err(map_db_err)
 }
 
-/// Async variant of [`insert_log_message`] for callers already in async context
-pub async fn insert_log_message_async(script_uri: &str, message: &str, log_level: &str) {
-    let repo = get_repository();
-    if let Err(e) = repo.insert_log(script_uri, message, log_level).await {
-        error!(
-            "Failed to insert log message for {}: {}. Message: {}",
-            script_uri, e, message
-        );
-        // Log to system instead as fallback
-        error!("FALLBACK LOG [{}]: {}", script_uri, message);
-    }
+/// Database-backed list of webhook deliveries, newest first
+async fn db_list_webhook_deliveries(
+    pool: &PgPool,
+    status: Option<crate::webhooks::DeliveryStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::webhooks::WebhookDelivery>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing webhooks: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, script_uri, url, status, attempts, max_attempts, next_attempt_at,
+            last_status_code, last_error, created_at, updated_at, delivered_at
+        FROM webhook_deliveries
+        WHERE ($1::TEXT IS NULL OR status = $1)
+          AND ($2::TEXT IS NULL OR script_uri = $2)
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(status.map(|status| status.as_str()))
+    .bind(script_uri)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(webhook_delivery_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
 }
 
-/// Fetch log messages with error handling
-pub fn fetch_log_messages(script_uri: &str) -> Vec<LogEntry> {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.fetch_logs(script_uri).await });
+/// Database-backed removal of deliveries that finished before `before`
+async fn db_purge_webhook_deliveries(pool: &PgPool, before: DateTime<Utc>) -> AppResult<u64> {
+    let result =
+        sqlx::query("DELETE FROM webhook_deliveries WHERE status <> 'pending' AND updated_at < $1")
+            .bind(before)
+            .execute(pool)
+            .await
+            .map_err(|e| {
+                error!("Database error purging webhooks: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+    Ok(result.rows_affected())
+}
 
-    match result {
-        Ok(messages) => messages,
-        Err(e) => {
-            error!("Failed to fetch log messages for {}: {}", script_uri, e);
-            let now = SystemTime::now();
-            vec![LogEntry::new(
-                format!("Error: Could not retrieve logs - {}", e),
-                "ERROR".to_string(),
-                now,
-            )]
+// ============================================================================
+// Script Tasks
+// ============================================================================
+
+fn script_task_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::tasks::ScriptTask, sqlx::Error> {
+    let status: String = row.try_get("status")?;
+    Ok(crate::tasks::ScriptTask {
+        id: row.try_get("id")?,
+        script_uri: row.try_get("script_uri")?,
+        handler_name: row.try_get("handler_name")?,
+        payload: row.try_get("payload")?,
+        priority: row.try_get("priority")?,
+        status: crate::tasks::TaskStatus::parse(&status).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown task status '{}'", status).into())
+        })?,
+        attempts: row.try_get("attempts")?,
+        max_attempts: row.try_get("max_attempts")?,
+        run_at: row.try_get("run_at")?,
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+        completed_at: row.try_get("completed_at")?,
+        idempotency_key: row.try_get("idempotency_key")?,
+    })
+}
+
+/// Database-backed queueing of a task. When the script already has a task
+/// with the idempotency key, that task is returned as a duplicate instead.
+async fn db_enqueue_task<'e, E>(
+    executor: E,
+    task: &crate::tasks::NewTask,
+) -> AppResult<crate::tasks::EnqueuedTask>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error queueing task {}: {}", task.id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    };
+    let row = sqlx::query(
+        r#"
+        WITH inserted AS (
+            INSERT INTO script_tasks
+                (id, script_uri, handler_name, payload, priority, max_attempts, run_at,
+                 idempotency_key)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (script_uri, idempotency_key) WHERE idempotency_key IS NOT NULL
+            DO NOTHING
+            RETURNING id, script_uri, handler_name, payload, priority, status, attempts,
+                max_attempts, run_at, last_error, created_at, updated_at, completed_at,
+                idempotency_key
+        )
+        SELECT *, FALSE AS duplicate FROM inserted
+        UNION ALL
+        SELECT id, script_uri, handler_name, payload, priority,
+            CASE WHEN status = 'pending' AND locked_until > NOW() THEN 'running'
+                ELSE status END AS status,
+            attempts, max_attempts, run_at, last_error, created_at, updated_at,
+            completed_at, idempotency_key, TRUE AS duplicate
+        FROM script_tasks
+        WHERE script_uri = $2 AND idempotency_key = $8
+          AND NOT EXISTS (SELECT 1 FROM inserted)
+        "#,
+    )
+    .bind(task.id)
+    .bind(&task.script_uri)
+    .bind(&task.handler_name)
+    .bind(&task.payload)
+    .bind(task.priority)
+    .bind(task.max_attempts)
+    .bind(task.run_at)
+    .bind(&task.idempotency_key)
+    .fetch_optional(executor)
+    .await
+    .map_err(map_db_err)?
+    // The conflicting task was committed after this statement's snapshot
+    .ok_or_else(|| AppError::Database {
+        message: format!(
+            "A task with idempotency key '{}' is being queued concurrently; try again",
+            task.idempotency_key.as_deref().unwrap_or_default()
+        ),
+        source: None,
+    })?;
+    Ok(crate::tasks::EnqueuedTask {
+        task: script_task_from_row(&row).map_err(map_db_err)?,
+        duplicate: row.try_get("duplicate").map_err(map_db_err)?,
+    })
 }
 
-/// Fetch ALL log messages from all script URIs
-pub fn fetch_all_log_messages() -> Vec<LogEntry> {
-    // Try database first if configured
-    let repo = get_repository();
-    let result = run_blocking(async { repo.fetch_all_logs().await });
+/// Database-backed claim of up to `limit` due tasks for one attempt each,
+/// highest priority first. Claimed tasks are skipped by other claims until
+/// `visibility` passes, so a task whose worker stopped runs again.
+async fn db_claim_tasks(
+    pool: &PgPool,
+    limit: i64,
+    visibility: Duration,
+) -> AppResult<Vec<crate::tasks::ClaimedTask>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error claiming tasks: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let rows = sqlx::query(
+        r#"
+        WITH due AS (
+            SELECT id FROM script_tasks
+            WHERE status = 'pending'
+              AND run_at <= NOW()
+              AND (locked_until IS NULL OR locked_until <= NOW())
+            ORDER BY priority DESC, run_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE script_tasks AS tasks
+        SET attempts = tasks.attempts + 1,
+            locked_until = NOW() + make_interval(secs => $2),
+            updated_at = NOW()
+        FROM due
+        WHERE tasks.id = due.id
+        RETURNING tasks.id, tasks.script_uri, tasks.handler_name, tasks.payload,
+            tasks.priority, tasks.attempts, tasks.max_attempts, tasks.run_at,
+            tasks.idempotency_key
+        "#,
+    )
+    .bind(limit)
+    .bind(visibility.as_secs_f64())
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?;
 
-    match result {
-        Ok(messages) => messages,
-        Err(e) => {
-            error!("Failed to fetch all log messages: {}", e);
-            vec![LogEntry::new(
-                format!("Error: Could not retrieve logs - {}", e),
-                "ERROR".to_string(),
-                SystemTime::now(),
-            )]
+    let mut claimed = rows
+        .iter()
+        .map(|row| {
+            Ok(crate::tasks::ClaimedTask {
+                task: crate::tasks::NewTask {
+                    id: row.try_get("id")?,
+                    script_uri: row.try_get("script_uri")?,
+                    handler_name: row.try_get("handler_name")?,
+                    payload: row.try_get("payload")?,
+                    priority: row.try_get("priority")?,
+                    max_attempts: row.try_get("max_attempts")?,
+                    run_at: row.try_get("run_at")?,
+                    idempotency_key: row.try_get("idempotency_key")?,
+                },
+                attempts: row.try_get("attempts")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(map_db_err)?;
+    // UPDATE ... RETURNING does not keep the order of the CTE
+    claimed.sort_by(|a, b| {
+        b.task
+            .priority
+            .cmp(&a.task.priority)
+            .then(a.task.run_at.cmp(&b.task.run_at))
+    });
+    Ok(claimed)
+}
+
+/// Database-backed record of how a claimed attempt went
+async fn db_finish_task_attempt(
+    pool: &PgPool,
+    id: uuid::Uuid,
+    outcome: &crate::tasks::TaskOutcome,
+) -> AppResult<()> {
+    use crate::tasks::TaskOutcome;
+    let (status, error, run_at) = match outcome {
+        TaskOutcome::Completed => ("completed", None, None),
+        TaskOutcome::Retry { error, run_at } => ("pending", Some(error.as_str()), Some(*run_at)),
+        TaskOutcome::Failed { error } => ("failed", Some(error.as_str()), None),
+    };
+    sqlx::query(
+        r#"
+        UPDATE script_tasks
+        SET status = $2,
+            last_error = COALESCE($3, last_error),
+            run_at = COALESCE($4, run_at),
+            completed_at = CASE WHEN $2 = 'completed' THEN NOW() ELSE completed_at END,
+            locked_until = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(status)
+    .bind(error)
+    .bind(run_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording task attempt {}: {}", id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    })?;
+    Ok(())
 }
 
-/// Clear log messages for a script
-pub fn clear_log_messages(script_uri: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.clear_logs(script_uri).await })
+/// Database-backed lookup of a task
+async fn db_get_task(pool: &PgPool, id: uuid::Uuid) -> AppResult<Option<crate::tasks::ScriptTask>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error getting task {}: {}", id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, script_uri, handler_name, payload, priority,
+            CASE WHEN status = 'pending' AND locked_until > NOW() THEN 'running'
+                ELSE status END AS status,
+            attempts, max_attempts, run_at, last_error, created_at, updated_at, completed_at,
+            idempotency_key
+        FROM script_tasks WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(map_db_err)?
+    .map(|row| script_task_from_row(&row))
+    .transpose()
+    .map_err(map_db_err)
 }
 
-/// Keep only the latest `limit` log messages (default 20) for each script URI and remove older ones
-pub fn prune_log_messages() -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.prune_logs().await })
+/// Database-backed list of tasks, newest first
+async fn db_list_tasks(
+    pool: &PgPool,
+    status: Option<crate::tasks::TaskStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::tasks::ScriptTask>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing tasks: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT * FROM (
+            SELECT id, script_uri, handler_name, payload, priority,
+                CASE WHEN status = 'pending' AND locked_until > NOW() THEN 'running'
+                    ELSE status END AS status,
+                attempts, max_attempts, run_at, last_error, created_at, updated_at,
+                completed_at, idempotency_key
+            FROM script_tasks
+            WHERE $2::TEXT IS NULL OR script_uri = $2
+        ) AS tasks
+        WHERE $1::TEXT IS NULL OR status = $1
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(status.map(|status| status.as_str()))
+    .bind(script_uri)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(script_task_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
 }
 
-/// Upsert script with error handling
-pub fn upsert_script(uri: &str, content: &str) -> AppResult<()> {
-    run_blocking(upsert_script_async(uri, content))
+/// Database-backed queue depth per script
+async fn db_task_queue_stats(pool: &PgPool) -> AppResult<Vec<crate::tasks::TaskQueueStats>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error reading task queue stats: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT script_uri,
+            COUNT(*) FILTER (WHERE status = 'pending'
+                AND (locked_until IS NULL OR locked_until <= NOW())) AS pending,
+            COUNT(*) FILTER (WHERE status = 'pending' AND locked_until > NOW()) AS running,
+            COUNT(*) FILTER (WHERE status = 'completed') AS completed,
+            COUNT(*) FILTER (WHERE status = 'failed') AS failed,
+            MIN(run_at) FILTER (WHERE status = 'pending' AND run_at <= NOW()
+                AND (locked_until IS NULL OR locked_until <= NOW())) AS oldest_due_at
+        FROM script_tasks
+        GROUP BY script_uri
+        ORDER BY script_uri
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(|row| {
+        Ok(crate::tasks::TaskQueueStats {
+            script_uri: row.try_get("script_uri")?,
+            pending: row.try_get("pending")?,
+            running: row.try_get("running")?,
+            completed: row.try_get("completed")?,
+            failed: row.try_get("failed")?,
+            oldest_due_at: row.try_get("oldest_due_at")?,
+        })
+    })
+    .collect::<Result<_, sqlx::Error>>()
+    .map_err(map_db_err)
 }
 
-/// Async variant of [`upsert_script`] for callers already in async context
-pub async fn upsert_script_async(uri: &str, content: &str) -> AppResult<()> {
-    if uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("URI cannot be empty".to_string()).into());
-    }
+/// Database-backed removal of tasks that finished before `before`
+async fn db_purge_tasks(pool: &PgPool, before: DateTime<Utc>) -> AppResult<u64> {
+    let result =
+        sqlx::query("DELETE FROM script_tasks WHERE status <> 'pending' AND updated_at < $1")
+            .bind(before)
+            .execute(pool)
+            .await
+            .map_err(|e| {
+                error!("Database error purging tasks: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+    Ok(result.rows_affected())
+}
 
-    if content.len() > 1_000_000 {
-        // 1MB limit
-        return Err(
-            RepositoryError::InvalidData("Script content too large (>1MB)".to_string()).into(),
-        );
-    }
+// ============================================================================
+// Email Log
+// ============================================================================
 
-    let repo = get_repository();
-    repo.upsert_script(uri, content).await
+fn email_log_entry_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::email::EmailLogEntry, sqlx::Error> {
+    let status: String = row.try_get("status")?;
+    Ok(crate::email::EmailLogEntry {
+        id: row.try_get("id")?,
+        script_uri: row.try_get("script_uri")?,
+        recipients: row.try_get("recipients")?,
+        subject: row.try_get("subject")?,
+        status: crate::email::EmailStatus::parse(&status).ok_or_else(|| {
+            sqlx::Error::Decode(format!("unknown email status '{}'", status).into())
+        })?,
+        error: row.try_get("error")?,
+        message_id: row.try_get("message_id")?,
+        created_at: row.try_get("created_at")?,
+    })
 }
 
-/// Upsert script and set owner if it's a new script
-pub fn upsert_script_with_owner(
-    uri: &str,
-    content: &str,
-    owner_user_id: Option<&str>,
-) -> AppResult<()> {
-    debug!(
-        "upsert_script_with_owner called: uri={}, owner_user_id={:?}, content_len={}",
-        uri,
-        owner_user_id,
-        content.len()
-    );
+/// Database-backed record of a sent or failed email
+async fn db_insert_email_log(pool: &PgPool, entry: &crate::email::EmailLogEntry) -> AppResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO email_log
+            (id, script_uri, recipients, subject, status, error, message_id, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(entry.id)
+    .bind(&entry.script_uri)
+    .bind(&entry.recipients)
+    .bind(&entry.subject)
+    .bind(entry.status.as_str())
+    .bind(&entry.error)
+    .bind(&entry.message_id)
+    .bind(entry.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording email {}: {}", entry.id, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+    Ok(())
+}
 
-    if uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("URI cannot be empty".to_string()).into());
-    }
+/// Database-backed count of the emails a script sent or tried to send
+/// since `since`
+async fn db_count_emails_since(
+    pool: &PgPool,
+    script_uri: &str,
+    since: DateTime<Utc>,
+) -> AppResult<i64> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM email_log WHERE script_uri = $1 AND created_at >= $2")
+        .bind(script_uri)
+        .bind(since)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error counting emails of {}: {}", script_uri, e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })
+}
 
-    if content.len() > 1_000_000 {
-        // 1MB limit
-        return Err(
-            RepositoryError::InvalidData("Script content too large (>1MB)".to_string()).into(),
-        );
-    }
+/// Database-backed list of email log entries, newest first
+async fn db_list_email_log(
+    pool: &PgPool,
+    status: Option<crate::email::EmailStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::email::EmailLogEntry>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing email log: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, script_uri, recipients, subject, status, error, message_id, created_at
+        FROM email_log
+        WHERE ($1::TEXT IS NULL OR status = $1)
+          AND ($2::TEXT IS NULL OR script_uri = $2)
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(status.map(|status| status.as_str()))
+    .bind(script_uri)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(email_log_entry_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
+}
 
-    // Check if script already exists
-    let script_exists = fetch_script(uri).is_some();
-    debug!(
-        "Script existence check: uri={}, exists={}",
-        uri, script_exists
-    );
+/// Database-backed deletion of email log entries older than `before`
+async fn db_purge_email_log(pool: &PgPool, before: DateTime<Utc>) -> AppResult<u64> {
+    let result = sqlx::query("DELETE FROM email_log WHERE created_at < $1")
+        .bind(before)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error purging email log: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+    Ok(result.rows_affected())
+}
 
-    // Upsert the script
-    let repo = get_repository();
-    run_blocking(async { repo.upsert_script(uri, content).await })?;
+// ============================================================================
+// Push Subscriptions
+// ============================================================================
 
-    // Assign ownership if needed:
-    // - For NEW scripts: set the creator as owner
-    // - For EXISTING scripts: set owner if they don't have any owners yet (backfill)
-    // - Skip bootstrap scripts (they remain ownerless)
-    let is_bootstrap = is_bootstrap_script(uri);
-    debug!(
-        "Bootstrap check: uri={}, is_bootstrap={}",
-        uri, is_bootstrap
-    );
+fn push_subscription_from_row(
+    row: &sqlx::postgres::PgRow,
+) -> Result<crate::notify::PushSubscription, sqlx::Error> {
+    Ok(crate::notify::PushSubscription {
+        id: row.try_get("id")?,
+        script_uri: row.try_get("script_uri")?,
+        user_id: row.try_get("user_id")?,
+        endpoint: row.try_get("endpoint")?,
+        p256dh: row.try_get("p256dh")?,
+        auth: row.try_get("auth")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
 
-    if !is_bootstrap && let Some(user_id) = owner_user_id {
-        // Check if script has any owners
-        let owner_count = run_blocking(async { repo.count_script_owners(uri).await }).unwrap_or(0);
-        let has_owners = owner_count > 0;
-        debug!(
-            "Owner count check: uri={}, count={}, has_owners={}",
-            uri, owner_count, has_owners
-        );
+/// Database-backed storage of a push subscription. A subscription with the
+/// same endpoint in the script is replaced, so a browser that subscribes
+/// again keeps one entry.
+async fn db_upsert_push_subscription(
+    pool: &PgPool,
+    subscription: &crate::notify::NewPushSubscription,
+) -> AppResult<crate::notify::PushSubscription> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error storing push subscription: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let row = sqlx::query(
+        r#"
+        INSERT INTO push_subscriptions (id, script_uri, user_id, endpoint, p256dh, auth)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (script_uri, endpoint) DO UPDATE
+        SET user_id = EXCLUDED.user_id,
+            p256dh = EXCLUDED.p256dh,
+            auth = EXCLUDED.auth,
+            updated_at = NOW()
+        RETURNING id, script_uri, user_id, endpoint, p256dh, auth, created_at, updated_at
+        "#,
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(&subscription.script_uri)
+    .bind(&subscription.user_id)
+    .bind(&subscription.endpoint)
+    .bind(&subscription.p256dh)
+    .bind(&subscription.auth)
+    .fetch_one(pool)
+    .await
+    .map_err(map_db_err)?;
+    push_subscription_from_row(&row).map_err(map_db_err)
+}
 
-        // If script has no owners, assign this user as the first owner
-        if !has_owners {
-            debug!("Attempting to add owner: uri={}, user_id={}", uri, user_id);
-            run_blocking(async { repo.add_script_owner(uri, user_id).await })?;
-            if script_exists {
-                debug!(
-                    "✓ Backfilled owner {} for existing script {} (had no owners)",
-                    user_id, uri
-                );
-            } else {
-                debug!("✓ Set initial owner {} for new script {}", user_id, uri);
-            }
-        } else {
-            debug!(
-                "Skipping ownership assignment - script already has {} owner(s): uri={}",
-                owner_count, uri
-            );
+/// Database-backed count of a user's push subscriptions in a script, other
+/// than the one for `endpoint`
+async fn db_count_push_subscriptions(
+    pool: &PgPool,
+    script_uri: &str,
+    user_id: &str,
+    endpoint: &str,
+) -> AppResult<i64> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM push_subscriptions
+        WHERE script_uri = $1 AND user_id = $2 AND endpoint <> $3
+        "#,
+    )
+    .bind(script_uri)
+    .bind(user_id)
+    .bind(endpoint)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error counting push subscriptions: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    } else if is_bootstrap {
-        debug!(
-            "Skipping ownership assignment - bootstrap script: uri={}",
-            uri
-        );
-    } else {
-        debug!(
-            "Skipping ownership assignment - no user_id provided: uri={}",
-            uri
-        );
-    }
+    })
+}
 
-    Ok(())
+/// Database-backed list of a script's push subscriptions of a user or
+/// with an ID
+async fn db_list_push_subscriptions(
+    pool: &PgPool,
+    script_uri: &str,
+    user_id: Option<&str>,
+    id: Option<uuid::Uuid>,
+) -> AppResult<Vec<crate::notify::PushSubscription>> {
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error listing push subscriptions: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    sqlx::query(
+        r#"
+        SELECT id, script_uri, user_id, endpoint, p256dh, auth, created_at, updated_at
+        FROM push_subscriptions
+        WHERE script_uri = $1
+          AND ($2::TEXT IS NULL OR user_id = $2)
+          AND ($3::UUID IS NULL OR id = $3)
+        ORDER BY created_at
+        "#,
+    )
+    .bind(script_uri)
+    .bind(user_id)
+    .bind(id)
+    .fetch_all(pool)
+    .await
+    .map_err(map_db_err)?
+    .iter()
+    .map(push_subscription_from_row)
+    .collect::<Result<_, _>>()
+    .map_err(map_db_err)
 }
 
-/// Bootstrap hardcoded scripts into database on startup
-pub fn bootstrap_scripts() -> AppResult<()> {
-    run_blocking(bootstrap_scripts_async())
+/// Database-backed removal of a script's push subscription by ID or
+/// endpoint
+async fn db_delete_push_subscription(
+    pool: &PgPool,
+    script_uri: &str,
+    key: &str,
+) -> AppResult<bool> {
+    let result = sqlx::query(
+        "DELETE FROM push_subscriptions WHERE script_uri = $1 AND (endpoint = $2 OR id::TEXT = $2)",
+    )
+    .bind(script_uri)
+    .bind(key)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error removing push subscription: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+    Ok(result.rows_affected() > 0)
 }
 
-/// Async variant of [`bootstrap_scripts`] for callers already in async context
-pub async fn bootstrap_scripts_async() -> AppResult<()> {
-    if let Some(db) = get_db_pool() {
-        let pool = db.pool();
+// ============================================================================
+// Script Database Schema Management Functions
+// ============================================================================
 
-        // Define the hardcoded scripts
-        let hardcoded_scripts = vec![
-            (
-                "https://example.com/core",
-                include_str!("../scripts/feature_scripts/core.js"),
-            ),
-            (
-                "https://example.com/cli",
-                include_str!("../scripts/feature_scripts/cli.js"),
-            ),
-            (
-                "https://example.com/admin",
-                include_str!("../scripts/feature_scripts/admin.js"),
-            ),
-            (
-                "https://example.com/auth",
-                include_str!("../scripts/feature_scripts/auth.js"),
+use crate::db_schema_utils::{
+    ColumnType, MAX_COLUMNS_PER_TABLE, MAX_INDEXED_VECTOR_DIMENSIONS, MAX_TABLES_PER_SCRIPT,
+    generate_physical_table_name, generate_script_schema_name, generate_vector_table_name,
+    quote_identifier, validate_default_value, validate_identifier, validate_script_sql,
+    validate_tenant_script_sql, vector_literal,
+};
+
+/// Database-backed create script-owned table
+async fn db_create_script_table(
+    pool: &PgPool,
+    script_uri: &str,
+    logical_table_name: &str,
+) -> AppResult<String> {
+    // Validate the logical table name
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+
+    // Check table limit for this script
+    let table_count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM script_tables WHERE script_uri = $1")
+            .bind(script_uri)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                error!("Database error counting tables for script: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+
+    if table_count >= MAX_TABLES_PER_SCRIPT as i64 {
+        return Err(AppError::Validation {
+            field: "table_name".to_string(),
+            reason: format!(
+                "Script has reached maximum table limit of {}",
+                MAX_TABLES_PER_SCRIPT
             ),
-        ];
+        });
+    }
 
-        // Include test scripts when appropriate
-        let mut all_scripts = hardcoded_scripts;
-        let include_test_scripts =
-            std::env::var("AIWEBENGINE_INCLUDE_TEST_SCRIPTS").is_ok() || cfg!(test);
+    // Generate physical table name
+    let physical_table_name = generate_physical_table_name(script_uri, logical_table_name);
 
-        if include_test_scripts {
-            all_scripts.push((
-                "https://example.com/graphql_test",
-                include_str!("../scripts/test_scripts/graphql_test.js"),
-            ));
-            all_scripts.push((
-                "https://example.com/dispatcher_test",
-                include_str!("../scripts/test_scripts/dispatcher_test.js"),
-            ));
+    // Check if table already exists for this script
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2)",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error checking table existence: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
+    })?;
 
-        let result = async {
-            for (uri, code) in all_scripts {
-                let mut exists = false;
-                // Check if script already exists
-                if let Ok(Some(existing_content)) = db_get_script(pool, uri).await {
-                    debug!("Script already exists in database: {}", uri);
-                    exists = true;
+    if exists {
+        return Err(AppError::Validation {
+            field: "table_name".to_string(),
+            reason: format!(
+                "Table '{}' already exists for this script",
+                logical_table_name
+            ),
+        });
+    }
 
-                    // Update if content differs
-                    if existing_content != code {
-                        info!("Updating bootstrap script in database: {}", uri);
-                        if let Err(e) = db_upsert_script(
-                            crate::database::TransactionExecutor::Pool(pool),
-                            uri,
-                            code,
-                        )
-                        .await
-                        {
-                            error!("Failed to update bootstrap script {}: {}", uri, e);
-                            return Err(e);
-                        }
-                    }
-                }
-
-                if !exists {
-                    // Insert the script
-                    if let Err(e) = db_upsert_script(
-                        crate::database::TransactionExecutor::Pool(pool),
-                        uri,
-                        code,
-                    )
-                    .await
-                    {
-                        error!("Failed to bootstrap script {}: {}", uri, e);
-                        return Err(e);
-                    } else {
-                        info!("Bootstrapped script into database: {}", uri);
-                    }
-                }
+    // Create the physical table with id column
+    let create_table_sql = format!(
+        "CREATE TABLE {} (id SERIAL PRIMARY KEY)",
+        quote_identifier(&physical_table_name)
+    );
 
-                // Ensure privileged flag is set for privileged scripts
-                if PRIVILEGED_BOOTSTRAP_SCRIPTS.contains(&uri) {
-                    if let Err(e) = db_set_script_privileged(pool, uri, true).await {
-                        warn!(
-                            "Failed to flag bootstrapped script {} as privileged: {}",
-                            uri, e
-                        );
-                        eprintln!("DEBUG: Failed to set privileged flag for {}: {}", uri, e);
-                    } else {
-                        eprintln!("DEBUG: Successfully set privileged flag for {}", uri);
-                    }
-                }
+    sqlx::query(sqlx::AssertSqlSafe(create_table_sql.as_str()))
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error creating table: {}", e);
+            AppError::Database {
+                message: format!("Failed to create table: {}", e),
+                source: None,
             }
-            Ok(())
-        }
-        .await;
+        })?;
 
-        match result {
-            Ok(()) => {
-                info!("Successfully bootstrapped scripts into database");
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to bootstrap scripts: {}", e);
-                Err(e)
-            }
+    // Record the table in script_tables metadata
+    let schema_json = serde_json::json!({
+        "columns": [
+            {"name": "id", "type": "SERIAL", "nullable": false, "primary_key": true}
+        ]
+    });
+
+    sqlx::query(
+        r#"
+        INSERT INTO script_tables (script_uri, logical_table_name, physical_table_name, schema_json)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .bind(&physical_table_name)
+    .bind(schema_json)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error recording table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    } else {
-        debug!("Database not configured, skipping script bootstrap");
-        Ok(())
-    }
-}
+    })?;
 
-/// Bootstrap hardcoded assets into database on startup
-pub fn bootstrap_assets() -> AppResult<()> {
-    run_blocking(async { bootstrap_assets_async().await })
+    debug!(
+        "Created script table: {} -> {}",
+        logical_table_name, physical_table_name
+    );
+
+    Ok(physical_table_name)
 }
 
-/// Async version of bootstrap_assets
-pub async fn bootstrap_assets_async() -> AppResult<()> {
-    let repo = get_repository();
-    let assets = get_static_assets();
-    info!("Bootstrapping {} assets...", assets.len());
+/// Database-backed add column to script-owned table
+async fn db_add_column_to_script_table(
+    pool: &PgPool,
+    script_uri: &str,
+    logical_table_name: &str,
+    column_name: &str,
+    column_type: ColumnType,
+    nullable: bool,
+    default_value: Option<&str>,
+) -> AppResult<()> {
+    // Validate identifiers
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+    validate_identifier(column_name).map_err(|e| AppError::Validation {
+        field: "column_name".to_string(),
+        reason: e.to_string(),
+    })?;
 
-    for (uri, asset) in assets {
-        debug!("Bootstrapping asset: {}", uri);
-        if let Err(e) = repo.upsert_asset(asset).await {
-            error!("Failed to bootstrap asset {}: {}", uri, e);
-            return Err(e);
+    // Get the physical table name
+    let row: Option<(String, serde_json::Value)> = sqlx::query_as(
+        "SELECT physical_table_name, schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    })?;
 
-    Ok(())
-}
+    let (physical_table_name, mut schema_json) = row.ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })?;
 
-/// Delete script with error handling
-pub fn delete_script(uri: &str) -> bool {
-    let repo = get_repository();
+    // Check column limit
+    let column_count = schema_json
+        .get("columns")
+        .and_then(|c| c.as_array())
+        .map(|c| c.len())
+        .unwrap_or(0);
 
-    let result = run_blocking(async { repo.delete_script(uri).await });
+    if column_count >= MAX_COLUMNS_PER_TABLE {
+        return Err(AppError::Validation {
+            field: "column_name".to_string(),
+            reason: format!(
+                "Table has reached maximum column limit of {}",
+                MAX_COLUMNS_PER_TABLE
+            ),
+        });
+    }
 
-    match result {
-        Ok(existed) => {
-            if existed {
-                scheduler::clear_script_jobs(uri);
-                debug!("Deleted script from repository: {}", uri);
-            } else {
-                debug!("Script not found in repository for deletion: {}", uri);
-            }
-            existed
-        }
-        Err(e) => {
-            error!("Failed to delete script {}: {}", uri, e);
-            false
-        }
+    // Check if column already exists
+    if let Some(columns) = schema_json.get("columns").and_then(|c| c.as_array())
+        && columns
+            .iter()
+            .any(|col| col.get("name").and_then(|n| n.as_str()) == Some(column_name))
+    {
+        return Err(AppError::Validation {
+            field: "column_name".to_string(),
+            reason: format!(
+                "Column '{}' already exists in table '{}'",
+                column_name, logical_table_name
+            ),
+        });
     }
-}
 
-/// Add an owner to a script
-pub fn add_script_owner(uri: &str, user_id: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.add_script_owner(uri, user_id).await })
-}
+    // Build ALTER TABLE statement
+    let mut alter_sql = format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        quote_identifier(&physical_table_name),
+        quote_identifier(column_name),
+        column_type.to_sql()
+    );
 
-/// Remove an owner from a script
-pub fn remove_script_owner(uri: &str, user_id: &str) -> AppResult<bool> {
-    let repo = get_repository();
-    run_blocking(async { repo.remove_script_owner(uri, user_id).await })
-}
+    if !nullable {
+        alter_sql.push_str(" NOT NULL");
+    }
 
-/// Get all owners of a script
-pub fn get_script_owners(uri: &str) -> AppResult<Vec<String>> {
-    let repo = get_repository();
-    run_blocking(async { repo.get_script_owners(uri).await })
-}
+    if let Some(default) = default_value {
+        let validated_default =
+            validate_default_value(&column_type, default).map_err(|e| AppError::Validation {
+                field: "default_value".to_string(),
+                reason: e.to_string(),
+            })?;
+        alter_sql.push_str(&format!(" DEFAULT {}", validated_default));
+    }
 
-/// Check if a user owns a script
-pub fn user_owns_script(uri: &str, user_id: &str) -> AppResult<bool> {
-    let repo = get_repository();
-    run_blocking(async { repo.user_owns_script(uri, user_id).await })
-}
+    // Execute the ALTER TABLE
+    sqlx::query(sqlx::AssertSqlSafe(alter_sql.as_str()))
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error adding column: {}", e);
+            AppError::Database {
+                message: format!("Failed to add column: {}", e),
+                source: None,
+            }
+        })?;
 
-/// Count the number of owners for a script
-pub fn count_script_owners(uri: &str) -> AppResult<i64> {
-    let repo = get_repository();
-    run_blocking(async { repo.count_script_owners(uri).await })
-}
+    // Update schema_json metadata
+    if let Some(columns) = schema_json
+        .get_mut("columns")
+        .and_then(|c| c.as_array_mut())
+    {
+        columns.push(serde_json::json!({
+            "name": column_name,
+            "type": column_type.to_sql(),
+            "nullable": nullable,
+            "default": default_value,
+        }));
+    }
 
-// ============================================================================
-// Script Database Schema Public API
-// ============================================================================
+    sqlx::query("UPDATE script_tables SET schema_json = $1, updated_at = NOW() WHERE script_uri = $2 AND logical_table_name = $3")
+        .bind(schema_json)
+        .bind(script_uri)
+        .bind(logical_table_name)
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error updating schema metadata: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
 
-/// Create a new table for a script
-pub fn create_script_table(script_uri: &str, logical_table_name: &str) -> AppResult<String> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.create_script_table(script_uri, logical_table_name)
-            .await
-    })
-}
+    debug!(
+        "Added column {} to table {}: {} {}",
+        column_name,
+        logical_table_name,
+        column_type.to_sql(),
+        if nullable { "NULL" } else { "NOT NULL" }
+    );
 
-/// Add a column to a script-owned table
-pub fn add_column_to_script_table(
-    script_uri: &str,
-    logical_table_name: &str,
-    column_name: &str,
-    column_type: ColumnType,
-    nullable: bool,
-    default_value: Option<&str>,
-) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.add_column_to_script_table(
-            script_uri,
-            logical_table_name,
-            column_name,
-            column_type,
-            nullable,
-            default_value,
-        )
-        .await
-    })
+    Ok(())
 }
 
-/// Add a reference column (INTEGER with FK) to a script-owned table
-pub fn add_reference_column(
+/// Database-backed add reference column (creates INTEGER column with FK constraint)
+async fn db_add_reference_column(
+    pool: &PgPool,
     script_uri: &str,
     logical_table_name: &str,
     column_name: &str,
     referenced_logical_table_name: &str,
     nullable: bool,
 ) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.add_reference_column(
-            script_uri,
-            logical_table_name,
-            column_name,
-            referenced_logical_table_name,
-            nullable,
-        )
+    // Validate identifiers
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+    validate_identifier(column_name).map_err(|e| AppError::Validation {
+        field: "column_name".to_string(),
+        reason: e.to_string(),
+    })?;
+    validate_identifier(referenced_logical_table_name).map_err(|e| AppError::Validation {
+        field: "referenced_table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+
+    // First, add the integer column
+    db_add_column_to_script_table(
+        pool,
+        script_uri,
+        logical_table_name,
+        column_name,
+        ColumnType::Integer,
+        nullable,
+        None,
+    )
+    .await?;
+
+    // Get physical table names for FK constraint
+    let source_table: String = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching source table: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?
+    .ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })?;
+
+    let referenced_table: String = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(referenced_logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching referenced table: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?
+    .ok_or_else(|| AppError::Validation {
+        field: "referenced_table_name".to_string(),
+        reason: format!(
+            "Referenced table '{}' not found for this script",
+            referenced_logical_table_name
+        ),
+    })?;
+
+    // Create the foreign key constraint
+    let constraint_name = format!("fk_{}_{}", logical_table_name.replace("_", ""), column_name);
+    let alter_sql = format!(
+        "ALTER TABLE {} ADD CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} (id)",
+        quote_identifier(&source_table),
+        quote_identifier(&constraint_name),
+        quote_identifier(column_name),
+        quote_identifier(&referenced_table)
+    );
+
+    sqlx::query(sqlx::AssertSqlSafe(alter_sql.as_str()))
+        .execute(pool)
         .await
-    })
+        .map_err(|e| {
+            error!("Database error creating foreign key: {}", e);
+            AppError::Database {
+                message: format!("Failed to create foreign key: {}", e),
+                source: None,
+            }
+        })?;
+
+    debug!(
+        "Created reference column: {}.{} -> {}.id (nullable: {})",
+        logical_table_name, column_name, referenced_logical_table_name, nullable
+    );
+
+    Ok(())
 }
 
-/// Drop a column from a script-owned table
-pub fn drop_column(
+/// Database-backed drop column from script-owned table
+async fn db_drop_column(
+    pool: &PgPool,
     script_uri: &str,
     logical_table_name: &str,
     column_name: &str,
 ) -> AppResult<bool> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.drop_column(script_uri, logical_table_name, column_name)
-            .await
-    })
-}
+    // Validate identifiers
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+    validate_identifier(column_name).map_err(|e| AppError::Validation {
+        field: "column_name".to_string(),
+        reason: e.to_string(),
+    })?;
 
-/// Drop a script-owned table
-pub fn drop_script_table(script_uri: &str, logical_table_name: &str) -> AppResult<bool> {
-    let repo = get_repository();
-    run_blocking(async { repo.drop_script_table(script_uri, logical_table_name).await })
-}
+    // Get the physical table name and schema
+    let row: Option<(String, serde_json::Value)> = sqlx::query_as(
+        "SELECT physical_table_name, schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-// ============================================================================
-// Script Database Introspection and Data Operations (Synchronous Wrappers)
-// ============================================================================
+    let (physical_table_name, mut schema_json) = row.ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })?;
 
-/// List all tables owned by a script
-pub fn list_script_tables(script_uri: &str) -> AppResult<Vec<TableInfo>> {
-    let repo = get_repository();
-    run_blocking(async { repo.list_script_tables(script_uri).await })
-}
+    // Check if column exists in schema
+    let column_exists = schema_json
+        .get("columns")
+        .and_then(|c| c.as_array())
+        .map(|columns| {
+            columns
+                .iter()
+                .any(|col| col.get("name").and_then(|n| n.as_str()) == Some(column_name))
+        })
+        .unwrap_or(false);
 
-/// Get detailed schema for a table
-pub fn get_table_schema(script_uri: &str, logical_table_name: &str) -> AppResult<TableSchema> {
-    let repo = get_repository();
-    run_blocking(async { repo.get_table_schema(script_uri, logical_table_name).await })
-}
+    if !column_exists {
+        return Ok(false);
+    }
 
-/// Get foreign key relationships for a table
-pub fn get_foreign_keys(
-    script_uri: &str,
-    logical_table_name: &str,
-) -> AppResult<Vec<ForeignKeyInfo>> {
-    let repo = get_repository();
-    run_blocking(async { repo.get_foreign_keys(script_uri, logical_table_name).await })
-}
+    // Don't allow dropping the id column
+    if column_name == "id" {
+        return Err(AppError::Validation {
+            field: "column_name".to_string(),
+            reason: "Cannot drop the 'id' column".to_string(),
+        });
+    }
 
-/// Query rows from a script-owned table
-pub fn query_table(
-    script_uri: &str,
-    logical_table_name: &str,
-    filters: Option<&HashMap<String, serde_json::Value>>,
-    limit: Option<i64>,
-    order_by: Option<&str>,
-    order_dir: Option<&str>,
-) -> AppResult<Vec<serde_json::Value>> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.query_table(
-            script_uri,
-            logical_table_name,
-            filters,
-            limit,
-            order_by,
-            order_dir,
-        )
+    // Drop the column
+    let drop_sql = format!(
+        "ALTER TABLE {} DROP COLUMN {}",
+        quote_identifier(&physical_table_name),
+        quote_identifier(column_name)
+    );
+
+    sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
+        .execute(pool)
         .await
-    })
-}
+        .map_err(|e| {
+            error!("Database error dropping column: {}", e);
+            AppError::Database {
+                message: format!("Failed to drop column: {}", e),
+                source: None,
+            }
+        })?;
 
-/// Insert a row into a script-owned table
-pub fn insert_row(
-    script_uri: &str,
-    logical_table_name: &str,
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let repo = get_repository();
-    run_blocking(async { repo.insert_row(script_uri, logical_table_name, data).await })
-}
+    // Update schema_json metadata
+    if let Some(columns) = schema_json
+        .get_mut("columns")
+        .and_then(|c| c.as_array_mut())
+    {
+        columns.retain(|col| col.get("name").and_then(|n| n.as_str()) != Some(column_name));
+    }
 
-/// Update a row in a script-owned table
-pub fn update_row(
-    script_uri: &str,
-    logical_table_name: &str,
-    id: i32,
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.update_row(script_uri, logical_table_name, id, data)
-            .await
-    })
-}
+    sqlx::query(
+        "UPDATE script_tables SET schema_json = $1, updated_at = NOW() WHERE script_uri = $2 AND logical_table_name = $3",
+    )
+    .bind(schema_json)
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error updating schema metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-/// Delete a row from a script-owned table
-pub fn delete_row(script_uri: &str, logical_table_name: &str, id: i32) -> AppResult<bool> {
-    let repo = get_repository();
-    run_blocking(async { repo.delete_row(script_uri, logical_table_name, id).await })
-}
+    debug!(
+        "Dropped column {} from table {}",
+        column_name, logical_table_name
+    );
 
-/// Upsert a row into a script-owned table (INSERT … ON CONFLICT DO UPDATE)
-pub fn upsert_row(
-    script_uri: &str,
-    logical_table_name: &str,
-    key_columns: &[String],
-    data: &HashMap<String, serde_json::Value>,
-) -> AppResult<serde_json::Value> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.upsert_row(script_uri, logical_table_name, key_columns, data)
-            .await
-    })
+    Ok(true)
 }
 
-/// Delete rows from a script-owned table matching the given filters
-pub fn delete_where(
+/// Database-backed drop script-owned table
+async fn db_drop_script_table(
+    pool: &PgPool,
     script_uri: &str,
     logical_table_name: &str,
-    filters: &HashMap<String, serde_json::Value>,
-) -> AppResult<u64> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.delete_where(script_uri, logical_table_name, filters)
+) -> AppResult<bool> {
+    // Validate identifier
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+
+    // Get the physical table name
+    let physical_table_name: Option<String> = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    if let Some(physical_name) = physical_table_name {
+        // Drop the physical table
+        let drop_sql = format!(
+            "DROP TABLE IF EXISTS {} CASCADE",
+            quote_identifier(&physical_name)
+        );
+
+        sqlx::query(sqlx::AssertSqlSafe(drop_sql.as_str()))
+            .execute(pool)
             .await
-    })
-}
+            .map_err(|e| {
+                error!("Database error dropping table: {}", e);
+                AppError::Database {
+                    message: format!("Failed to drop table: {}", e),
+                    source: None,
+                }
+            })?;
 
-/// Atomically acquire or extend a distributed lease in a script-owned table
-pub fn acquire_lease(
-    script_uri: &str,
-    logical_table_name: &str,
-    lease_id: &str,
-    owner: &str,
-    ttl_ms: i64,
-) -> AppResult<serde_json::Value> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.acquire_lease(script_uri, logical_table_name, lease_id, owner, ttl_ms)
+        // Remove from script_tables metadata (will cascade due to FK)
+        sqlx::query("DELETE FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2")
+            .bind(script_uri)
+            .bind(logical_table_name)
+            .execute(pool)
             .await
-    })
+            .map_err(|e| {
+                error!("Database error removing table metadata: {}", e);
+                AppError::Database {
+                    message: format!("Database error: {}", e),
+                    source: None,
+                }
+            })?;
+
+        debug!(
+            "Dropped script table: {} ({})",
+            logical_table_name, physical_name
+        );
+        Ok(true)
+    } else {
+        Ok(false)
+    }
 }
 
-/// Create a lease table with the required schema in a script-owned table
-pub fn create_lease_table(script_uri: &str, logical_table_name: &str) -> AppResult<String> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.create_lease_table(script_uri, logical_table_name)
-            .await
-    })
+// ============================================================================
+// Script Database Schema Introspection Functions
+// ============================================================================
+
+/// List all tables owned by a script
+async fn db_list_script_tables(pool: &PgPool, script_uri: &str) -> AppResult<Vec<TableInfo>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT logical_table_name, physical_table_name, created_at
+        FROM script_tables
+        WHERE script_uri = $1
+        ORDER BY logical_table_name
+        "#,
+        script_uri
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error listing script tables: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| TableInfo {
+            logical_name: row.logical_table_name,
+            physical_name: row.physical_table_name,
+            created_at: row.created_at,
+        })
+        .collect())
 }
 
-/// Add a unique index on one or more columns of a script-owned table
-pub fn add_unique_index(
+/// Get detailed schema information for a specific table
+async fn db_get_table_schema(
+    pool: &PgPool,
     script_uri: &str,
     logical_table_name: &str,
-    columns: &[String],
-) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async {
-        repo.add_unique_index(script_uri, logical_table_name, columns)
-            .await
-    })
-}
+) -> AppResult<TableSchema> {
+    // Fetch schema_json from script_tables
+    let schema_json: Option<serde_json::Value> = sqlx::query_scalar(
+        "SELECT schema_json FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table schema: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-/// Helper function to get static assets embedded at compile time
-fn get_static_assets() -> HashMap<String, Asset> {
-    let mut m = HashMap::new();
-    let now = std::time::SystemTime::now();
+    let schema_json = schema_json.ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })?;
 
-    // Logo asset
-    let logo_content = include_bytes!("../assets/logo.svg").to_vec();
-    let logo = Asset {
-        uri: "logo.svg".to_string(),
-        name: Some("Logo".to_string()),
-        mimetype: "image/svg+xml".to_string(),
-        content: logo_content,
-        created_at: now,
-        updated_at: now,
-        script_uri: "https://example.com/core".to_string(),
-    };
-    m.insert("logo.svg".to_string(), logo);
-
-    let engine_css_content = include_bytes!("../assets/engine.css").to_vec();
-    let engine_css = Asset {
-        uri: "engine.css".to_string(),
-        name: Some("Engine Styles".to_string()),
-        mimetype: "text/css".to_string(),
-        content: engine_css_content,
-        created_at: now,
-        updated_at: now,
-        script_uri: "https://example.com/core".to_string(),
-    };
-    m.insert("engine.css".to_string(), engine_css);
-
-    let favicon_content = include_bytes!("../assets/favicon.ico").to_vec();
-    let favicon = Asset {
-        uri: "favicon.ico".to_string(),
-        name: Some("Favicon".to_string()),
-        mimetype: "image/x-icon".to_string(),
-        content: favicon_content,
-        created_at: now,
-        updated_at: now,
-        script_uri: "https://example.com/core".to_string(),
-    };
-    m.insert("favicon.ico".to_string(), favicon);
-
-    let aiwebengine_dts_content = include_bytes!("../assets/aiwebengine.d.ts").to_vec();
-    let aiwebengine_dts = Asset {
-        uri: "aiwebengine.d.ts".to_string(),
-        name: Some("TypeScript Type Definitions".to_string()),
-        mimetype: "text/plain".to_string(),
-        content: aiwebengine_dts_content,
-        created_at: now,
-        updated_at: now,
-        script_uri: "https://example.com/core".to_string(),
-    };
-    m.insert("aiwebengine.d.ts".to_string(), aiwebengine_dts);
-
-    let aiwebengine_priv_dts_content = include_bytes!("../assets/aiwebengine-priv.d.ts").to_vec();
-    let aiwebengine_priv_dts = Asset {
-        uri: "aiwebengine-priv.d.ts".to_string(),
-        name: Some("TypeScript Private Type Definitions".to_string()),
-        mimetype: "text/plain".to_string(),
-        content: aiwebengine_priv_dts_content,
-        created_at: now,
-        updated_at: now,
-        script_uri: "https://example.com/core".to_string(),
-    };
-    m.insert("aiwebengine-priv.d.ts".to_string(), aiwebengine_priv_dts);
+    // Parse columns from schema_json
+    let columns_array = schema_json
+        .get("columns")
+        .and_then(|c| c.as_array())
+        .ok_or_else(|| AppError::Validation {
+            field: "schema_json".to_string(),
+            reason: "Invalid schema format: missing columns array".to_string(),
+        })?;
 
-    m
-}
+    let mut columns = Vec::new();
+    for col in columns_array {
+        let name = col
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| AppError::Validation {
+                field: "column".to_string(),
+                reason: "Column missing name".to_string(),
+            })?
+            .to_string();
 
-/// Helper function to get static scripts embedded at compile time
-fn get_static_scripts() -> HashMap<String, String> {
-    let mut m = HashMap::new();
+        let data_type = col
+            .get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| AppError::Validation {
+                field: "column".to_string(),
+                reason: format!("Column '{}' missing type", name),
+            })?
+            .to_string();
 
-    let core = include_str!("../scripts/feature_scripts/core.js");
-    let cli = include_str!("../scripts/feature_scripts/cli.js");
-    let admin = include_str!("../scripts/feature_scripts/admin.js");
-    let auth = include_str!("../scripts/feature_scripts/auth.js");
+        let nullable = col
+            .get("nullable")
+            .and_then(|n| n.as_bool())
+            .unwrap_or(true);
 
-    m.insert("https://example.com/core".to_string(), core.to_string());
-    m.insert("https://example.com/cli".to_string(), cli.to_string());
-    m.insert("https://example.com/admin".to_string(), admin.to_string());
-    m.insert("https://example.com/auth".to_string(), auth.to_string());
+        let default_value = col.get("default").map(|d| {
+            if let Some(s) = d.as_str() {
+                s.to_string()
+            } else {
+                d.to_string()
+            }
+        });
 
-    // Include test scripts when appropriate
-    let include_test_scripts =
-        std::env::var("AIWEBENGINE_INCLUDE_TEST_SCRIPTS").is_ok() || cfg!(test);
+        let is_primary_key = col
+            .get("primary_key")
+            .and_then(|p| p.as_bool())
+            .unwrap_or(false);
 
-    if include_test_scripts {
-        let graphql_test = include_str!("../scripts/test_scripts/graphql_test.js");
-        m.insert(
-            "https://example.com/graphql_test".to_string(),
-            graphql_test.to_string(),
-        );
-        let dispatcher_test = include_str!("../scripts/test_scripts/dispatcher_test.js");
-        m.insert(
-            "https://example.com/dispatcher_test".to_string(),
-            dispatcher_test.to_string(),
-        );
+        columns.push(ColumnInfo {
+            name,
+            data_type,
+            nullable,
+            default_value,
+            is_primary_key,
+        });
     }
 
-    m
+    Ok(TableSchema {
+        table_name: logical_table_name.to_string(),
+        columns,
+    })
 }
 
-/// Fetch assets with error handling (static + dynamic)
-pub fn fetch_assets(script_uri: &str) -> HashMap<String, Asset> {
-    let repo = get_repository();
+/// Get foreign key relationships for a table
+async fn db_get_foreign_keys(
+    pool: &PgPool,
+    script_uri: &str,
+    logical_table_name: &str,
+) -> AppResult<Vec<ForeignKeyInfo>> {
+    // Get the physical table name first
+    let physical_table_name: Option<String> = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
 
-    let result = run_blocking(async { repo.list_assets(script_uri).await });
+    let physical_table_name = physical_table_name.ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })?;
 
-    match result {
-        Ok(assets) => {
-            debug!(
-                "Loaded {} assets from repository for script {}",
-                assets.len(),
-                script_uri
-            );
-            assets
+    // Query PostgreSQL information_schema for foreign keys
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            kcu.column_name,
+            ccu.table_name AS referenced_table_physical,
+            ccu.column_name AS referenced_column
+        FROM information_schema.table_constraints AS tc
+        JOIN information_schema.key_column_usage AS kcu
+            ON tc.constraint_name = kcu.constraint_name
+            AND tc.table_schema = kcu.table_schema
+        JOIN information_schema.constraint_column_usage AS ccu
+            ON ccu.constraint_name = tc.constraint_name
+            AND ccu.table_schema = tc.table_schema
+        WHERE tc.constraint_type = 'FOREIGN KEY'
+            AND tc.table_name = $1
+        "#,
+        &physical_table_name
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching foreign keys: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-        Err(e) => {
-            error!("Failed to fetch assets: {}", e);
-            HashMap::new()
+    })?;
+
+    let mut foreign_keys = Vec::new();
+
+    for row in rows {
+        // Look up the logical table name for the referenced table
+        let referenced_table_logical: Option<String> = sqlx::query_scalar(
+            "SELECT logical_table_name FROM script_tables WHERE script_uri = $1 AND physical_table_name = $2",
+        )
+        .bind(script_uri)
+        .bind(&row.referenced_table_physical)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error fetching referenced table name: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+
+        // Only include FK if it references another table owned by the same script
+        if let Some(referenced_logical) = referenced_table_logical
+            && let Some(column_name) = row.column_name
+        {
+            foreign_keys.push(ForeignKeyInfo {
+                column_name,
+                referenced_table_logical: referenced_logical,
+                referenced_table_physical: row.referenced_table_physical.unwrap_or_default(),
+                referenced_column: row.referenced_column.unwrap_or_else(|| "id".to_string()),
+            });
         }
     }
-}
 
-/// Fetch single asset by URI with error handling (dynamic first, then static)
-pub fn fetch_asset(script_uri: &str, uri: &str) -> Option<Asset> {
-    run_blocking(fetch_asset_async(script_uri, uri))
+    Ok(foreign_keys)
 }
 
-/// Async variant of [`fetch_asset`] for callers already in async context
-pub async fn fetch_asset_async(script_uri: &str, uri: &str) -> Option<Asset> {
-    let repo = get_repository();
-
-    // Try repository first (DB or Memory)
-    let result = repo.get_asset(script_uri, uri).await;
+// ============================================================================
+// Script Database Data Access Functions
+// ============================================================================
 
-    match result {
-        Ok(Some(asset)) => {
-            debug!("Loaded asset from repository: {}", uri);
-            return Some(asset);
-        }
-        Ok(None) => {
-            // Not in repository, check static assets if script_uri is core
-        }
-        Err(e) => {
-            warn!("Repository asset fetch failed for {}: {}", uri, e);
-            // Fall through to static assets
-        }
-    }
-
-    // Check static assets if script_uri is core
-    if script_uri == "https://example.com/core"
-        && let Some(asset) = get_static_assets().get(uri)
-    {
-        return Some(asset.clone());
-    }
-
-    None
+/// Supported comparison operators for query filters.
+enum FilterOp {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Ne,
 }
 
-/// Upsert asset with validation and error handling
-pub fn upsert_asset(asset: Asset) -> AppResult<()> {
-    run_blocking(upsert_asset_async(asset))
+/// A single resolved filter condition (column, operator, value).
+struct FilterCondition {
+    column: String,
+    op: FilterOp,
+    value: serde_json::Value,
 }
 
-/// Async variant of [`upsert_asset`] for callers already in async context
-pub async fn upsert_asset_async(asset: Asset) -> AppResult<()> {
-    if asset.uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Asset URI cannot be empty".to_string()).into());
-    }
-
-    if asset.content.len() > 10_000_000 {
-        // 10MB limit for assets
-        return Err(
-            RepositoryError::InvalidData("Asset content too large (>10MB)".to_string()).into(),
-        );
-    }
-
-    if asset.mimetype.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("MIME type cannot be empty".to_string()).into());
+/// Parse a filter map into a flat list of `FilterCondition`s.
+///
+/// Supports two forms:
+/// - Equality:  `{ "col": value }`
+/// - Operators: `{ "col": { "$gt": v, "$lt": w, ... } }`
+///
+/// Allowed operators: `$gt`, `$gte`, `$lt`, `$lte`, `$ne`.
+fn parse_filter_conditions(
+    filters: &HashMap<String, serde_json::Value>,
+) -> AppResult<Vec<FilterCondition>> {
+    let mut conditions = Vec::new();
+    for (column, value) in filters {
+        validate_identifier(column).map_err(|e| AppError::Validation {
+            field: "filter_column".to_string(),
+            reason: e.to_string(),
+        })?;
+        match value {
+            serde_json::Value::Object(ops) => {
+                for (op_key, op_val) in ops {
+                    let op = match op_key.as_str() {
+                        "$gt" => FilterOp::Gt,
+                        "$gte" => FilterOp::Gte,
+                        "$lt" => FilterOp::Lt,
+                        "$lte" => FilterOp::Lte,
+                        "$ne" => FilterOp::Ne,
+                        other => {
+                            return Err(AppError::Validation {
+                                field: "filter_operator".to_string(),
+                                reason: format!("Unknown filter operator: {}", other),
+                            });
+                        }
+                    };
+                    conditions.push(FilterCondition {
+                        column: column.clone(),
+                        op,
+                        value: op_val.clone(),
+                    });
+                }
+            }
+            _ => {
+                conditions.push(FilterCondition {
+                    column: column.clone(),
+                    op: FilterOp::Eq,
+                    value: value.clone(),
+                });
+            }
+        }
     }
-
-    let repo = get_repository();
-    repo.upsert_asset(asset).await
+    Ok(conditions)
 }
 
-/// Delete asset with error handling  
-pub fn delete_asset(script_uri: &str, uri: &str) -> bool {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.delete_asset(script_uri, uri).await });
-
-    match result {
-        Ok(existed) => existed,
-        Err(e) => {
-            error!("Failed to delete asset {}: {}", uri, e);
-            false
+/// Bind a single JSON value to a sqlx query as the appropriate Postgres type.
+fn bind_json_value<'q>(
+    query: sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>,
+    value: &'q serde_json::Value,
+) -> AppResult<sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>> {
+    match value {
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(query.bind(i))
+            } else if let Some(f) = n.as_f64() {
+                Ok(query.bind(f))
+            } else {
+                Err(AppError::Validation {
+                    field: "value".to_string(),
+                    reason: "Numeric value out of range".to_string(),
+                })
+            }
         }
+        serde_json::Value::String(s) => Ok(query.bind(s.as_str())),
+        serde_json::Value::Bool(b) => Ok(query.bind(*b)),
+        serde_json::Value::Null => Ok(query.bind(Option::<String>::None)),
+        _ => Err(AppError::Validation {
+            field: "value".to_string(),
+            reason: "Unsupported value type (array/object not allowed)".to_string(),
+        }),
     }
 }
 
-/// Get repository statistics for monitoring
-pub fn get_repository_stats() -> HashMap<String, usize> {
-    let mut stats = HashMap::new();
+/// Convert a sqlx `PgRow` to a `serde_json::Value::Object`.
+fn row_to_json(row: &sqlx::postgres::PgRow) -> serde_json::Value {
+    use sqlx::Column;
+    let mut obj = serde_json::Map::new();
+    for (idx, column) in row.columns().iter().enumerate() {
+        let column_name = column.name().to_string();
+        let value = if let Ok(v) = row.try_get::<i64, _>(idx) {
+            serde_json::Value::Number(v.into())
+        } else if let Ok(v) = row.try_get::<i32, _>(idx) {
+            serde_json::Value::Number(v.into())
+        } else if let Ok(v) = row.try_get::<i16, _>(idx) {
+            serde_json::Value::Number(v.into())
+        } else if let Ok(v) = row.try_get::<f64, _>(idx) {
+            serde_json::Number::from_f64(v)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number)
+        } else if let Ok(v) = row.try_get::<f32, _>(idx) {
+            serde_json::Number::from_f64(v as f64)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number)
+        } else if let Ok(v) = row.try_get::<serde_json::Value, _>(idx) {
+            v
+        } else if let Ok(v) = row.try_get::<uuid::Uuid, _>(idx) {
+            serde_json::Value::String(v.to_string())
+        } else if let Ok(v) = row.try_get::<String, _>(idx) {
+            serde_json::Value::String(v)
+        } else if let Ok(v) = row.try_get::<bool, _>(idx) {
+            serde_json::Value::Bool(v)
+        } else if let Ok(v) = row.try_get::<DateTime<Utc>, _>(idx) {
+            serde_json::Value::String(v.to_rfc3339())
+        } else {
+            serde_json::Value::Null
+        };
+        obj.insert(column_name, value);
+    }
+    serde_json::Value::Object(obj)
+}
 
-    // Count scripts
-    match safe_lock_scripts() {
-        Ok(guard) => {
-            stats.insert("dynamic_scripts".to_string(), guard.len());
-        }
-        Err(_) => {
-            stats.insert("dynamic_scripts".to_string(), 0);
+/// Look up the physical table name for a script-owned logical table.
+async fn get_physical_table_name(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+) -> AppResult<String> {
+    let physical_table_name: Option<String> = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        error!("Database error fetching table metadata: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
         }
-    }
+    })?;
 
-    // Count assets
-    let asset_count = if let Some(db) = get_db_pool() {
-        run_blocking(async {
-            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM assets")
-                .fetch_one(db.pool())
-                .await
-                .unwrap_or(0) as usize
-        })
-    } else {
-        0
-    };
-    stats.insert("assets".to_string(), asset_count);
+    physical_table_name.ok_or_else(|| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: format!("Table '{}' not found for this script", logical_table_name),
+    })
+}
 
-    // Count total log entries
-    let log_count = if let Some(db) = get_db_pool() {
-        run_blocking(async {
-            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM logs")
-                .fetch_one(db.pool())
-                .await
-                .unwrap_or(0) as usize
-        })
-    } else {
-        0
-    };
-    stats.insert("log_entries".to_string(), log_count);
+/// Query rows from a script-owned table.
+///
+/// `filters` supports equality (`{"col": value}`) and comparison operators
+/// (`{"col": {"$gt": v}}`). Supported operators: `$gt`, `$gte`, `$lt`,
+/// `$lte`, `$ne`.
+///
+/// `order_by` must be a valid column identifier; `order_dir` is `"asc"` or
+/// `"desc"` (defaults to `"asc"`).
+async fn db_query_table(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    filters: Option<&HashMap<String, serde_json::Value>>,
+    limit: Option<i64>,
+    order_by: Option<&str>,
+    order_dir: Option<&str>,
+) -> AppResult<Vec<serde_json::Value>> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-    // Count shared storage entries
-    let script_properties_count = if let Some(db) = get_db_pool() {
-        run_blocking(async {
-            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM script_properties")
-                .fetch_one(db.pool())
-                .await
-                .unwrap_or(0) as usize
-        })
+    // Parse filter conditions (supports equality and range operators)
+    let conditions = if let Some(f) = filters {
+        parse_filter_conditions(f)?
     } else {
-        0
+        Vec::new()
     };
-    stats.insert(
-        "script_properties_entries".to_string(),
-        script_properties_count,
-    );
 
-    stats
-}
+    // Build WHERE clause
+    let mut sql = format!("SELECT * FROM {}", quote_identifier(&physical_table_name));
+    let mut param_count = 0usize;
 
-/// Set a shared storage item (key-value pair for a specific script)
-pub fn set_script_properties_item(script_uri: &str, key: &str, value: &str) -> AppResult<()> {
-    if script_uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
-    }
-
-    if key.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    if !conditions.is_empty() {
+        let clauses: Vec<String> = conditions
+            .iter()
+            .map(|c| {
+                param_count += 1;
+                let op_str = match c.op {
+                    FilterOp::Eq => "=",
+                    FilterOp::Gt => ">",
+                    FilterOp::Gte => ">=",
+                    FilterOp::Lt => "<",
+                    FilterOp::Lte => "<=",
+                    FilterOp::Ne => "!=",
+                };
+                format!(
+                    "{} {} ${}",
+                    quote_identifier(&c.column),
+                    op_str,
+                    param_count
+                )
+            })
+            .collect();
+        sql.push_str(&format!(" WHERE {}", clauses.join(" AND ")));
     }
 
-    if value.len() > 1_000_000 {
-        // 1MB limit per value
-        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    // ORDER BY
+    if let Some(order_col) = order_by {
+        validate_identifier(order_col).map_err(|e| AppError::Validation {
+            field: "order_by".to_string(),
+            reason: e.to_string(),
+        })?;
+        let dir = match order_dir.unwrap_or("asc").to_lowercase().as_str() {
+            "desc" => "DESC",
+            _ => "ASC",
+        };
+        sql.push_str(&format!(
+            " ORDER BY {} {}",
+            quote_identifier(order_col),
+            dir
+        ));
     }
 
-    let repo = get_repository();
-    run_blocking(async { repo.set_script_properties(script_uri, key, value).await })
-}
-
-/// Get a shared storage item
-pub fn get_script_properties_item(script_uri: &str, key: &str) -> Option<String> {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.get_script_properties(script_uri, key).await });
+    // LIMIT (default 100, max 1000)
+    let limit_val = limit.unwrap_or(100).min(1000);
+    param_count += 1;
+    sql.push_str(&format!(" LIMIT ${}", param_count));
 
-    match result {
-        Ok(value) => value,
-        Err(e) => {
-            error!(
-                "Failed to get shared storage item {}:{}: {}",
-                script_uri, key, e
-            );
-            None
-        }
+    // Bind parameters
+    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for cond in &conditions {
+        sql_query = bind_json_value(sql_query, &cond.value)?;
     }
-}
-
-/// Remove a shared storage item
-pub fn remove_script_properties_item(script_uri: &str, key: &str) -> bool {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.remove_script_properties(script_uri, key).await });
+    sql_query = sql_query.bind(limit_val);
 
-    match result {
-        Ok(existed) => existed,
-        Err(e) => {
-            error!(
-                "Failed to remove shared storage item {}:{}: {}",
-                script_uri, key, e
-            );
-            false
+    let rows = sql_query.fetch_all(&mut *conn).await.map_err(|e| {
+        error!("Database error querying table: {}", e);
+        AppError::Database {
+            message: format!("Query error: {}", e),
+            source: None,
         }
-    }
-}
+    })?;
 
-/// Clear all shared storage items for a specific script
-pub fn clear_script_properties(script_uri: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.clear_script_properties(script_uri).await })
+    Ok(rows.iter().map(row_to_json).collect())
 }
 
-/// Set a personal storage item (key-value pair for a specific script and user)
-pub fn set_user_properties_item(
+/// Insert a row into a script-owned table
+async fn db_insert_row(
+    conn: &mut PgConnection,
     script_uri: &str,
-    user_id: &str,
-    key: &str,
-    value: &str,
-) -> AppResult<()> {
-    if script_uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
-    }
+    logical_table_name: &str,
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-    if user_id.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("User ID cannot be empty".to_string()).into());
+    if data.is_empty() {
+        return Err(AppError::Validation {
+            field: "data".to_string(),
+            reason: "No data provided for insert".to_string(),
+        });
     }
 
-    if key.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
-    }
+    let mut columns = Vec::new();
+    let mut placeholders = Vec::new();
+    let mut param_count = 0usize;
 
-    if value.len() > 1_000_000 {
-        // 1MB limit per value
-        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    for column in data.keys() {
+        validate_identifier(column).map_err(|e| AppError::Validation {
+            field: "column_name".to_string(),
+            reason: e.to_string(),
+        })?;
+        columns.push(quote_identifier(column));
+        param_count += 1;
+        placeholders.push(format!("${}", param_count));
     }
 
-    let repo = get_repository();
-    run_blocking(async {
-        repo.set_user_properties(script_uri, user_id, key, value)
-            .await
-    })
-}
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) RETURNING *",
+        quote_identifier(&physical_table_name),
+        columns.join(", "),
+        placeholders.join(", ")
+    );
 
-/// Get a personal storage item
-pub fn get_user_properties_item(script_uri: &str, user_id: &str, key: &str) -> Option<String> {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.get_user_properties(script_uri, user_id, key).await });
+    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for value in data.values() {
+        sql_query = bind_json_value(sql_query, value)?;
+    }
 
-    match result {
-        Ok(value) => value,
-        Err(e) => {
-            error!(
-                "Failed to get personal storage item {}:{}:{}: {}",
-                script_uri, user_id, key, e
-            );
-            None
+    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
+        error!("Database error inserting row: {}", e);
+        AppError::Database {
+            message: format!("Insert error: {}", e),
+            source: None,
         }
-    }
+    })?;
+
+    Ok(row_to_json(&row))
 }
 
-/// Remove a personal storage item
-pub fn remove_user_properties_item(script_uri: &str, user_id: &str, key: &str) -> bool {
-    let repo = get_repository();
-    let result =
-        run_blocking(async { repo.remove_user_properties(script_uri, user_id, key).await });
+/// Update a row in a script-owned table
+async fn db_update_row(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    id: i32,
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-    match result {
-        Ok(existed) => existed,
-        Err(e) => {
-            error!(
-                "Failed to remove personal storage item {}:{}:{}: {}",
-                script_uri, user_id, key, e
-            );
-            false
-        }
+    if data.is_empty() {
+        return Err(AppError::Validation {
+            field: "data".to_string(),
+            reason: "No data provided for update".to_string(),
+        });
     }
-}
 
-/// Clear all personal storage items for a specific script and user
-pub fn clear_user_properties(script_uri: &str, user_id: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.clear_user_properties(script_uri, user_id).await })
-}
+    let mut set_clauses = Vec::new();
+    let mut param_count = 0usize;
 
-/// Set a script secret (key-value pair for a specific script)
-pub fn set_script_secret_item(script_uri: &str, key: &str, value: &str) -> AppResult<()> {
-    if script_uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
+    for column in data.keys() {
+        validate_identifier(column).map_err(|e| AppError::Validation {
+            field: "column_name".to_string(),
+            reason: e.to_string(),
+        })?;
+        param_count += 1;
+        set_clauses.push(format!("{} = ${}", quote_identifier(column), param_count));
     }
 
-    if key.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
-    }
+    param_count += 1;
+    let sql = format!(
+        "UPDATE {} SET {} WHERE id = ${} RETURNING *",
+        quote_identifier(&physical_table_name),
+        set_clauses.join(", "),
+        param_count
+    );
 
-    if value.len() > 1_000_000 {
-        // 1MB limit per value
-        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for value in data.values() {
+        sql_query = bind_json_value(sql_query, value)?;
     }
+    sql_query = sql_query.bind(id);
 
-    let repo = get_repository();
-    run_blocking(async { repo.set_script_secret(script_uri, key, value).await })
-}
+    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
+        error!("Database error updating row: {}", e);
+        AppError::Database {
+            message: format!("Update error: {}", e),
+            source: None,
+        }
+    })?;
 
-/// Get a script secret
-pub fn get_script_secret_item(script_uri: &str, key: &str) -> Option<String> {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.get_script_secret(script_uri, key).await });
+    Ok(row_to_json(&row))
+}
 
-    match result {
-        Ok(value) => value,
-        Err(e) => {
-            error!("Failed to get script secret {}:{}: {}", script_uri, key, e);
-            None
-        }
-    }
-}
-
-/// Resolve a secret by checking user_secrets first (if user_id is given), then script_secrets.
-/// Never reads environment variables or config files — only the database is consulted.
-/// Returns `None` if the repository is not initialized (e.g. in tests without a DB).
-pub fn resolve_secret_db(script_uri: &str, key: &str, user_id: Option<&str>) -> Option<String> {
-    get_repository_opt()?;
-    if let Some(uid) = user_id
-        && let Some(value) = get_user_secret_item(script_uri, uid, key)
-    {
-        return Some(value);
-    }
-    crate::repository::get_script_secret_item(script_uri, key)
-}
-
-/// Remove a script secret
-pub fn remove_script_secret_item(script_uri: &str, key: &str) -> bool {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.remove_script_secret(script_uri, key).await });
+/// Delete a row from a script-owned table by ID
+async fn db_delete_row(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    id: i32,
+) -> AppResult<bool> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-    match result {
-        Ok(existed) => existed,
-        Err(e) => {
-            error!(
-                "Failed to remove script secret {}:{}: {}",
-                script_uri, key, e
-            );
-            false
-        }
-    }
-}
+    let sql = format!(
+        "DELETE FROM {} WHERE id = $1",
+        quote_identifier(&physical_table_name)
+    );
 
-/// Clear all script secrets for a specific script
-pub fn clear_script_secrets(script_uri: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.clear_script_secrets(script_uri).await })
-}
+    let result = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            error!("Database error deleting row: {}", e);
+            AppError::Database {
+                message: format!("Delete error: {}", e),
+                source: None,
+            }
+        })?;
 
-/// List all secret keys for a specific script
-pub fn list_script_secrets(script_uri: &str) -> AppResult<Vec<String>> {
-    let repo = get_repository();
-    run_blocking(async { repo.list_script_secrets(script_uri).await })
+    Ok(result.rows_affected() > 0)
 }
 
-/// Set a user secret (key-value pair for a specific script and user)
-pub fn set_user_secret_item(
+/// Upsert a row into a script-owned table using INSERT … ON CONFLICT DO UPDATE.
+///
+/// `key_columns` names the columns that form the conflict target; a unique
+/// index on those columns must exist (create one with `db_add_unique_index`).
+/// `data` must contain values for all columns, including the key columns.
+async fn db_upsert_row(
+    conn: &mut PgConnection,
     script_uri: &str,
-    user_id: &str,
-    key: &str,
-    value: &str,
-) -> AppResult<()> {
-    if script_uri.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
-    }
+    logical_table_name: &str,
+    key_columns: &[String],
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-    if user_id.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("User ID cannot be empty".to_string()).into());
+    if data.is_empty() {
+        return Err(AppError::Validation {
+            field: "data".to_string(),
+            reason: "No data provided for upsert".to_string(),
+        });
     }
-
-    if key.trim().is_empty() {
-        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    if key_columns.is_empty() {
+        return Err(AppError::Validation {
+            field: "key_columns".to_string(),
+            reason: "At least one key column must be specified".to_string(),
+        });
     }
 
-    if value.len() > 1_000_000 {
-        // 1MB limit per value
-        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    // Validate key columns
+    for kc in key_columns {
+        validate_identifier(kc).map_err(|e| AppError::Validation {
+            field: "key_column".to_string(),
+            reason: e.to_string(),
+        })?;
     }
 
-    let repo = get_repository();
-    run_blocking(async { repo.set_user_secret(script_uri, user_id, key, value).await })
-}
+    // Build column/placeholder lists (deterministic order via sorted keys)
+    let mut ordered_cols: Vec<&String> = data.keys().collect();
+    ordered_cols.sort();
 
-/// Get a user secret
-pub fn get_user_secret_item(script_uri: &str, user_id: &str, key: &str) -> Option<String> {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.get_user_secret(script_uri, user_id, key).await });
+    let mut col_list = Vec::new();
+    let mut placeholder_list = Vec::new();
+    let mut param_count = 0usize;
 
-    match result {
-        Ok(value) => value,
-        Err(e) => {
-            error!(
-                "Failed to get user secret {}:{}:{}: {}",
-                script_uri, user_id, key, e
-            );
-            None
-        }
+    for col in &ordered_cols {
+        validate_identifier(col).map_err(|e| AppError::Validation {
+            field: "column_name".to_string(),
+            reason: e.to_string(),
+        })?;
+        col_list.push(quote_identifier(col));
+        param_count += 1;
+        placeholder_list.push(format!("${}", param_count));
     }
-}
 
-/// Remove a user secret
-pub fn remove_user_secret_item(script_uri: &str, user_id: &str, key: &str) -> bool {
-    let repo = get_repository();
-    let result = run_blocking(async { repo.remove_user_secret(script_uri, user_id, key).await });
+    // SET clause: update all non-key columns
+    let key_set: std::collections::HashSet<&str> = key_columns.iter().map(|s| s.as_str()).collect();
+    let set_clauses: Vec<String> = ordered_cols
+        .iter()
+        .filter(|c| !key_set.contains(c.as_str()))
+        .map(|c| format!("{} = EXCLUDED.{}", quote_identifier(c), quote_identifier(c)))
+        .collect();
 
-    match result {
-        Ok(existed) => existed,
-        Err(e) => {
-            error!(
-                "Failed to remove user secret {}:{}:{}: {}",
-                script_uri, user_id, key, e
-            );
-            false
-        }
+    let conflict_target = key_columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = if set_clauses.is_empty() {
+        // All columns are key columns — DO NOTHING is the right action
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO NOTHING RETURNING *",
+            quote_identifier(&physical_table_name),
+            col_list.join(", "),
+            placeholder_list.join(", "),
+            conflict_target,
+        )
+    } else {
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {} RETURNING *",
+            quote_identifier(&physical_table_name),
+            col_list.join(", "),
+            placeholder_list.join(", "),
+            conflict_target,
+            set_clauses.join(", "),
+        )
+    };
+
+    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for col in &ordered_cols {
+        let value = &data[*col];
+        sql_query = bind_json_value(sql_query, value)?;
     }
-}
 
-/// Clear all user secrets for a specific script and user
-pub fn clear_user_secrets(script_uri: &str, user_id: &str) -> AppResult<()> {
-    let repo = get_repository();
-    run_blocking(async { repo.clear_user_secrets(script_uri, user_id).await })
+    let row = sql_query.fetch_one(&mut *conn).await.map_err(|e| {
+        error!("Database error upserting row: {}", e);
+        AppError::Database {
+            message: format!("Upsert error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(row_to_json(&row))
 }
 
-use async_trait::async_trait;
+/// Delete rows from a script-owned table matching the given filter conditions.
+///
+/// Supports the same filter syntax as `db_query_table` (equality and range
+/// operators). Returns the number of rows deleted.
+async fn db_delete_where(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    filters: &HashMap<String, serde_json::Value>,
+) -> AppResult<u64> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
 
-/// Abstract repository interface
-#[async_trait]
-pub trait Repository: Send + Sync {
-    // Script operations
-    async fn get_script(&self, uri: &str) -> AppResult<Option<String>>;
-    async fn list_scripts(&self) -> AppResult<HashMap<String, String>>;
-    async fn upsert_script(&self, uri: &str, content: &str) -> AppResult<()>;
-    async fn delete_script(&self, uri: &str) -> AppResult<bool>;
-    async fn get_script_metadata(&self, uri: &str) -> AppResult<ScriptMetadata>;
-    async fn get_all_script_metadata(&self) -> AppResult<Vec<ScriptMetadata>>;
-    async fn update_script_init_status(
-        &self,
-        uri: &str,
-        initialized: bool,
-        init_error: Option<String>,
-        registrations: Option<RouteRegistrations>,
-    ) -> AppResult<()>;
+    if filters.is_empty() {
+        return Err(AppError::Validation {
+            field: "filters".to_string(),
+            reason: "deleteWhere requires at least one filter to prevent accidental full-table delete. Use dropTable to remove all rows.".to_string(),
+        });
+    }
 
-    // Asset operations
-    async fn get_asset(&self, script_uri: &str, uri: &str) -> AppResult<Option<Asset>>;
-    async fn list_assets(&self, script_uri: &str) -> AppResult<HashMap<String, Asset>>;
-    async fn upsert_asset(&self, asset: Asset) -> AppResult<()>;
-    async fn delete_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool>;
+    let conditions = parse_filter_conditions(filters)?;
 
-    // Log operations
-    async fn insert_log(&self, script_uri: &str, message: &str, level: &str) -> AppResult<()>;
-    async fn fetch_logs(&self, script_uri: &str) -> AppResult<Vec<LogEntry>>;
-    async fn fetch_all_logs(&self) -> AppResult<Vec<LogEntry>>;
-    async fn clear_logs(&self, script_uri: &str) -> AppResult<()>;
+    let mut param_count = 0usize;
+    let clauses: Vec<String> = conditions
+        .iter()
+        .map(|c| {
+            param_count += 1;
+            let op_str = match c.op {
+                FilterOp::Eq => "=",
+                FilterOp::Gt => ">",
+                FilterOp::Gte => ">=",
+                FilterOp::Lt => "<",
+                FilterOp::Lte => "<=",
+                FilterOp::Ne => "!=",
+            };
+            format!(
+                "{} {} ${}",
+                quote_identifier(&c.column),
+                op_str,
+                param_count
+            )
+        })
+        .collect();
+
+    let sql = format!(
+        "DELETE FROM {} WHERE {}",
+        quote_identifier(&physical_table_name),
+        clauses.join(" AND ")
+    );
+
+    let mut sql_query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for cond in &conditions {
+        sql_query = bind_json_value(sql_query, &cond.value)?;
+    }
+
+    let result = sql_query.execute(&mut *conn).await.map_err(|e| {
+        error!("Database error in deleteWhere: {}", e);
+        AppError::Database {
+            message: format!("Delete error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(result.rows_affected())
+}
+
+/// Atomically acquire or extend a distributed lease stored in a script-owned table.
+///
+/// The table must have been created with `db_create_lease_table` (or manually
+/// given the schema `lease_id TEXT, owner TEXT, expires_at TIMESTAMPTZ` with a
+/// UNIQUE constraint on `lease_id`).
+///
+/// Returns `{acquired: bool, owner: string, expires_at: string}`.
+///
+/// How it works (single-statement, race-free):
+/// - If no row with `lease_id` exists → INSERT succeeds → we own the lease.
+/// - If a row exists and is expired OR already owned by us → UPDATE succeeds → we own it.
+/// - If a row exists, is not expired, and belongs to someone else → nothing changes → we do NOT own it.
+async fn db_acquire_lease(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    lease_id: &str,
+    owner: &str,
+    ttl_ms: i64,
+) -> AppResult<serde_json::Value> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+
+    if ttl_ms <= 0 {
+        return Err(AppError::Validation {
+            field: "ttl_ms".to_string(),
+            reason: "ttl_ms must be a positive integer".to_string(),
+        });
+    }
+
+    // Single-statement atomic upsert: wins only if slot is free or ours.
+    // bind order: $1=lease_id, $2=owner, $3=ttl_ms (bigint milliseconds)
+    let sql = format!(
+        r#"
+        INSERT INTO {tbl} (lease_id, owner, expires_at)
+        VALUES ($1, $2, NOW() + ($3::bigint * interval '1 millisecond'))
+        ON CONFLICT (lease_id) DO UPDATE
+            SET owner      = EXCLUDED.owner,
+                expires_at = EXCLUDED.expires_at
+        WHERE {tbl}.expires_at <= NOW()
+           OR {tbl}.owner = EXCLUDED.owner
+        RETURNING owner, expires_at
+        "#,
+        tbl = quote_identifier(&physical_table_name)
+    );
+
+    let upsert_row = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .bind(lease_id)
+        .bind(owner)
+        .bind(ttl_ms)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| {
+            error!("Database error in acquireLease: {}", e);
+            AppError::Database {
+                message: format!("Lease error: {}", e),
+                source: None,
+            }
+        })?;
+
+    if let Some(row) = upsert_row {
+        // Upsert succeeded — we hold the lease
+        let row_owner: String = row.try_get("owner").unwrap_or_default();
+        let expires_at: String = row
+            .try_get::<DateTime<Utc>, _>("expires_at")
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+        return Ok(serde_json::json!({
+            "acquired": row_owner == owner,
+            "owner": row_owner,
+            "expires_at": expires_at,
+        }));
+    }
+
+    // Upsert produced no row — someone else holds an active lease.
+    // Do a plain SELECT to return current lease info (best-effort, non-critical).
+    let select_sql = format!(
+        "SELECT owner, expires_at FROM {} WHERE lease_id = $1",
+        quote_identifier(&physical_table_name)
+    );
+    let current = sqlx::query(sqlx::AssertSqlSafe(select_sql.as_str()))
+        .bind(lease_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| {
+            error!("Database error reading current lease: {}", e);
+            AppError::Database {
+                message: format!("Lease read error: {}", e),
+                source: None,
+            }
+        })?;
+
+    if let Some(row) = current {
+        let row_owner: String = row.try_get("owner").unwrap_or_default();
+        let expires_at: String = row
+            .try_get::<DateTime<Utc>, _>("expires_at")
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_default();
+        Ok(serde_json::json!({
+            "acquired": false,
+            "owner": row_owner,
+            "expires_at": expires_at,
+        }))
+    } else {
+        Ok(serde_json::json!({
+            "acquired": false,
+            "owner": serde_json::Value::Null,
+            "expires_at": serde_json::Value::Null,
+        }))
+    }
+}
+
+/// Create a properly structured lease table owned by the given script.
+///
+/// The table schema is: `lease_id TEXT UNIQUE NOT NULL, owner TEXT NOT NULL,
+/// expires_at TIMESTAMPTZ NOT NULL`. An explicit UNIQUE index on `lease_id`
+/// is created so that `acquireLease` can use it as a conflict target.
+///
+/// Returns the physical table name on success.
+async fn db_create_lease_table(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+) -> AppResult<String> {
+    use crate::db_schema_utils::{
+        MAX_TABLES_PER_SCRIPT, generate_physical_table_name, validate_identifier,
+    };
+
+    validate_identifier(logical_table_name).map_err(|e| AppError::Validation {
+        field: "table_name".to_string(),
+        reason: e.to_string(),
+    })?;
+
+    // Count existing tables for this script
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM script_tables WHERE script_uri = $1")
+        .bind(script_uri)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        })?;
+
+    if count >= MAX_TABLES_PER_SCRIPT as i64 {
+        return Err(AppError::Validation {
+            field: "table_name".to_string(),
+            reason: format!("Maximum table limit of {} reached", MAX_TABLES_PER_SCRIPT),
+        });
+    }
+
+    // Check for duplicates
+    let existing: Option<String> = sqlx::query_scalar(
+        "SELECT physical_table_name FROM script_tables WHERE script_uri = $1 AND logical_table_name = $2",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| AppError::Database {
+        message: format!("Database error: {}", e),
+        source: None,
+    })?;
+
+    if let Some(existing_physical) = existing {
+        // Table already exists — idempotent
+        return Ok(existing_physical);
+    }
+
+    let physical_name = generate_physical_table_name(script_uri, logical_table_name);
+
+    // Create the table with the required lease schema
+    let create_sql = format!(
+        r#"CREATE TABLE {} (
+            lease_id  TEXT        NOT NULL,
+            owner     TEXT        NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            CONSTRAINT {}_pkey PRIMARY KEY (lease_id)
+        )"#,
+        quote_identifier(&physical_name),
+        physical_name,
+    );
+
+    sqlx::query(sqlx::AssertSqlSafe(create_sql.as_str()))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| AppError::Database {
+            message: format!("Error creating lease table: {}", e),
+            source: None,
+        })?;
+
+    // Register in metadata
+    sqlx::query(
+        "INSERT INTO script_tables (script_uri, logical_table_name, physical_table_name, created_at)
+         VALUES ($1, $2, $3, NOW())",
+    )
+    .bind(script_uri)
+    .bind(logical_table_name)
+    .bind(&physical_name)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| AppError::Database {
+        message: format!("Error registering lease table: {}", e),
+        source: None,
+    })?;
+
+    debug!(
+        "Created lease table '{}' (physical: '{}') for script '{}'",
+        logical_table_name, physical_name, script_uri
+    );
+
+    Ok(physical_name)
+}
+
+/// Add a unique index on one or more columns of a script-owned table.
+///
+/// This is required before using those columns as a conflict target in
+/// `db_upsert_row`. Index names are derived from the physical table name and
+/// columns to avoid collisions.
+async fn db_add_unique_index(
+    conn: &mut PgConnection,
+    script_uri: &str,
+    logical_table_name: &str,
+    columns: &[String],
+) -> AppResult<()> {
+    let physical_table_name = get_physical_table_name(conn, script_uri, logical_table_name).await?;
+
+    if columns.is_empty() {
+        return Err(AppError::Validation {
+            field: "columns".to_string(),
+            reason: "At least one column must be specified".to_string(),
+        });
+    }
+
+    for col in columns {
+        validate_identifier(col).map_err(|e| AppError::Validation {
+            field: "column".to_string(),
+            reason: e.to_string(),
+        })?;
+    }
+
+    // Build a deterministic index name
+    let cols_slug = columns.join("_");
+    // Truncate to stay within PostgreSQL's 63-char identifier limit
+    let index_name = format!(
+        "{}_uniq_{}",
+        &physical_table_name[..physical_table_name.len().min(40)],
+        &cols_slug[..cols_slug.len().min(20)]
+    );
+
+    let col_list = columns
+        .iter()
+        .map(|c| quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = format!(
+        "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({})",
+        quote_identifier(&index_name),
+        quote_identifier(&physical_table_name),
+        col_list
+    );
+
+    sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            error!("Database error adding unique index: {}", e);
+            AppError::Database {
+                message: format!("Index error: {}", e),
+                source: None,
+            }
+        })?;
+
+    debug!(
+        "Created unique index '{}' on table '{}' for script '{}'",
+        index_name, logical_table_name, script_uri
+    );
+
+    Ok(())
+}
+
+/// A NULL parameter whose type Postgres infers from the statement, so scripts
+/// can pass `null` for columns of any type
+struct UntypedNull;
+
+impl sqlx::Type<sqlx::Postgres> for UntypedNull {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        // OID 0 leaves the parameter type unspecified
+        sqlx::postgres::PgTypeInfo::with_oid(sqlx::postgres::types::Oid(0))
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for UntypedNull {
+    fn encode_by_ref(
+        &self,
+        _buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        Ok(sqlx::encode::IsNull::Yes)
+    }
+}
+
+/// Script schemas known to exist, so `db.query` only sets one up once per process
+static SCRIPT_SCHEMAS_READY: OnceLock<Mutex<std::collections::HashSet<String>>> = OnceLock::new();
+
+fn script_schemas_ready() -> std::sync::MutexGuard<'static, std::collections::HashSet<String>> {
+    SCRIPT_SCHEMAS_READY
+        .get_or_init(|| Mutex::new(std::collections::HashSet::new()))
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Create the script's private schema and the NOLOGIN role that owns it.
+///
+/// Statements from `db.query` run as that role with the schema as the only
+/// entry on the search path, so scripts see neither the engine's tables nor
+/// each other's data.
+async fn db_ensure_script_schema(pool: &PgPool, script_uri: &str) -> AppResult<String> {
+    let schema = generate_script_schema_name(script_uri);
+    if script_schemas_ready().contains(&schema) {
+        return Ok(schema);
+    }
+
+    // The schema name is a hex digest, so it is safe to embed as a literal
+    let sql = format!(
+        r#"
+        DO $$
+        BEGIN
+            IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = '{name}') THEN
+                BEGIN
+                    CREATE ROLE {role} NOLOGIN;
+                EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL;
+                END;
+            END IF;
+            IF NOT pg_has_role(current_user, '{name}', 'MEMBER') THEN
+                GRANT {role} TO CURRENT_USER;
+            END IF;
+            BEGIN
+                CREATE SCHEMA IF NOT EXISTS {role} AUTHORIZATION {role};
+            EXCEPTION WHEN duplicate_schema OR unique_violation THEN NULL;
+            END;
+        END
+        $$
+        "#,
+        name = schema,
+        role = quote_identifier(&schema)
+    );
+
+    sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .execute(pool)
+        .await
+        .map_err(|e| {
+            error!(
+                "Database error creating schema for script {}: {}",
+                script_uri, e
+            );
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+
+    script_schemas_ready().insert(schema.clone());
+    debug!("Prepared schema '{}' for script '{}'", schema, script_uri);
+    Ok(schema)
+}
+
+/// Row-level security for every table of a script's schema while tenancy is
+/// enabled: a `tenant_id` column defaulting to the transaction's tenant and a
+/// policy limiting reads and writes to rows of that tenant. Runs as the
+/// script's role, which owns the tables; FORCE makes the policy apply to the
+/// owner too. Tables that already force row-level security are skipped.
+const TENANT_POLICY_SQL: &str = r#"
+DO $$
+DECLARE
+    t record;
+BEGIN
+    FOR t IN
+        SELECT c.relname FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = current_schema()
+          AND c.relkind IN ('r', 'p')
+          AND NOT c.relforcerowsecurity
+    LOOP
+        EXECUTE format('ALTER TABLE %I ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT current_setting(''app.tenant_id'', true)', t.relname);
+        EXECUTE format('ALTER TABLE %I ENABLE ROW LEVEL SECURITY', t.relname);
+        EXECUTE format('ALTER TABLE %I FORCE ROW LEVEL SECURITY', t.relname);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I', t.relname);
+        EXECUTE format('CREATE POLICY tenant_isolation ON %I USING (tenant_id = current_setting(''app.tenant_id'', true)) WITH CHECK (tenant_id = current_setting(''app.tenant_id'', true))', t.relname);
+    END LOOP;
+END
+$$
+"#;
+
+/// Set `app.tenant_id` for the rest of the transaction
+async fn db_set_transaction_tenant(
+    conn: &mut sqlx::PgConnection,
+    tenant: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT set_config($1, $2, true)")
+        .bind(crate::tenancy::TENANT_SETTING)
+        .bind(tenant)
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+/// Run a script-supplied statement with bound parameters in the script's
+/// private schema.
+///
+/// The statement runs in its own transaction as the script's role, with
+/// `statement_timeout` set from `options`. At most `options.max_rows` rows are
+/// returned; `truncated` reports whether more were available.
+///
+/// With a `tenant` (tenancy enabled; the empty string for "no tenant") the
+/// statement only sees and writes rows of that tenant, see
+/// [`TENANT_POLICY_SQL`].
+async fn db_run_script_query(
+    pool: &PgPool,
+    script_uri: &str,
+    sql: &str,
+    params: &[serde_json::Value],
+    options: &ScriptQueryOptions,
+    tenant: Option<&str>,
+) -> AppResult<ScriptQueryResult> {
+    use futures::TryStreamExt;
+
+    validate_script_sql(sql)
+        .and_then(|()| {
+            if tenant.is_some() {
+                validate_tenant_script_sql(sql)
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|e| AppError::Validation {
+            field: "sql".to_string(),
+            reason: e.to_string(),
+        })?;
+    if params.len() > MAX_SCRIPT_QUERY_PARAMS {
+        return Err(AppError::Validation {
+            field: "params".to_string(),
+            reason: format!(
+                "Too many parameters ({}, maximum {})",
+                params.len(),
+                MAX_SCRIPT_QUERY_PARAMS
+            ),
+        });
+    }
+
+    let schema = db_ensure_script_schema(pool, script_uri).await?;
+
+    let map_db_err = |e: sqlx::Error| {
+        debug!("Script query failed for {}: {}", script_uri, e);
+        AppError::Database {
+            message: format!("Query error: {}", e),
+            source: None,
+        }
+    };
+
+    let mut tx = crate::database::begin(pool).await.map_err(map_db_err)?;
+
+    let setup = [
+        format!("SET LOCAL ROLE {}", quote_identifier(&schema)),
+        format!("SET LOCAL search_path TO {}", quote_identifier(&schema)),
+        format!("SET LOCAL statement_timeout = {}", options.timeout_ms),
+    ];
+    for statement in &setup {
+        sqlx::query(sqlx::AssertSqlSafe(statement.as_str()))
+            .execute(&mut *tx)
+            .await
+            .map_err(map_db_err)?;
+    }
+    if let Some(tenant) = tenant {
+        db_set_transaction_tenant(&mut tx, tenant)
+            .await
+            .map_err(map_db_err)?;
+    }
+
+    let mut query = sqlx::query(sqlx::AssertSqlSafe(sql)).persistent(false);
+    for param in params {
+        query = match param {
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+                query.bind(sqlx::types::Json(param))
+            }
+            serde_json::Value::Null => query.bind(UntypedNull),
+            _ => bind_json_value(query, param)?,
+        };
+    }
+
+    let mut rows = Vec::new();
+    let mut row_count = 0;
+    let mut truncated = false;
+    {
+        let mut results = sqlx::Executor::fetch_many(&mut *tx, query);
+        while let Some(result) = results.try_next().await.map_err(map_db_err)? {
+            match result {
+                sqlx::Either::Left(done) => row_count += done.rows_affected(),
+                sqlx::Either::Right(row) => {
+                    if rows.len() as u64 >= options.max_rows {
+                        truncated = true;
+                        break;
+                    }
+                    rows.push(row_to_json(&row));
+                }
+            }
+        }
+    }
+
+    // Tables the statement created get the tenant policy before anyone can
+    // write to them without it
+    if tenant.is_some() {
+        sqlx::query(TENANT_POLICY_SQL)
+            .execute(&mut *tx)
+            .await
+            .map_err(map_db_err)?;
+    }
+
+    tx.commit().await.map_err(map_db_err)?;
+
+    Ok(ScriptQueryResult {
+        row_count: if truncated {
+            rows.len() as u64
+        } else {
+            row_count
+        },
+        rows,
+        truncated,
+    })
+}
+
+/// Apply a script's pending migrations to its private schema.
+///
+/// Each migration runs in its own transaction as the script's role and is
+/// recorded in `script_migrations` with the checksum of its statements, so
+/// calling this again (e.g. from every init()) only applies new versions.
+/// An advisory lock keeps servers initializing the same script from applying
+/// a migration twice. Changing an applied migration is an error.
+async fn db_migrate_script_schema(
+    pool: &PgPool,
+    script_uri: &str,
+    migrations: &[ScriptMigration],
+    tenant: Option<&str>,
+) -> AppResult<ScriptMigrationSummary> {
+    let invalid = |reason: String| AppError::Validation {
+        field: "migrations".to_string(),
+        reason,
+    };
+
+    let mut migrations: Vec<&ScriptMigration> = migrations.iter().collect();
+    migrations.sort_by_key(|migration| migration.version);
+    for (index, migration) in migrations.iter().enumerate() {
+        if migration.version <= 0 {
+            return Err(invalid(format!(
+                "Migration version must be a positive integer, got {}",
+                migration.version
+            )));
+        }
+        if index > 0 && migrations[index - 1].version == migration.version {
+            return Err(invalid(format!(
+                "Migration version {} is declared more than once",
+                migration.version
+            )));
+        }
+        if migration.up.is_empty() {
+            return Err(invalid(format!(
+                "Migration {} has no statements",
+                migration.version
+            )));
+        }
+        for statement in &migration.up {
+            validate_script_sql(statement)
+                .and_then(|()| {
+                    if tenant.is_some() {
+                        validate_tenant_script_sql(statement)
+                    } else {
+                        Ok(())
+                    }
+                })
+                .map_err(|e| invalid(format!("Migration {}: {}", migration.version, e)))?;
+        }
+    }
+
+    let schema = db_ensure_script_schema(pool, script_uri).await?;
+
+    let map_db_err = |e: sqlx::Error| {
+        error!("Database error migrating script {}: {}", script_uri, e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    let mut applied = Vec::new();
+    for migration in migrations {
+        let checksum = migration.checksum();
+        let mut tx = crate::database::begin(pool).await.map_err(map_db_err)?;
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(format!("script_migrations:{}", script_uri))
+            .execute(&mut *tx)
+            .await
+            .map_err(map_db_err)?;
+
+        let applied_checksum: Option<String> = sqlx::query_scalar(
+            "SELECT checksum FROM script_migrations WHERE script_uri = $1 AND version = $2",
+        )
+        .bind(script_uri)
+        .bind(migration.version)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(map_db_err)?;
+
+        if let Some(applied_checksum) = applied_checksum {
+            if applied_checksum != checksum {
+                return Err(invalid(format!(
+                    "Migration {} was changed after it was applied; add a new version instead",
+                    migration.version
+                )));
+            }
+            continue;
+        }
+
+        let setup = [
+            format!("SET LOCAL ROLE {}", quote_identifier(&schema)),
+            format!("SET LOCAL search_path TO {}", quote_identifier(&schema)),
+            format!(
+                "SET LOCAL statement_timeout = {}",
+                MAX_SCRIPT_QUERY_TIMEOUT_MS
+            ),
+        ];
+        for statement in &setup {
+            sqlx::query(sqlx::AssertSqlSafe(statement.as_str()))
+                .execute(&mut *tx)
+                .await
+                .map_err(map_db_err)?;
+        }
+        if let Some(tenant) = tenant {
+            db_set_transaction_tenant(&mut tx, tenant)
+                .await
+                .map_err(map_db_err)?;
+        }
+
+        for statement in &migration.up {
+            sqlx::query(sqlx::AssertSqlSafe(statement.as_str()))
+                .persistent(false)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    debug!(
+                        "Migration {} failed for {}: {}",
+                        migration.version, script_uri, e
+                    );
+                    AppError::Database {
+                        message: format!("Migration {} failed: {}", migration.version, e),
+                        source: None,
+                    }
+                })?;
+        }
+
+        if tenant.is_some() {
+            sqlx::query(TENANT_POLICY_SQL)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_db_err)?;
+        }
+
+        for statement in [
+            "SET LOCAL ROLE NONE",
+            "SET LOCAL search_path TO DEFAULT",
+            "SET LOCAL statement_timeout TO DEFAULT",
+        ] {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(map_db_err)?;
+        }
+
+        sqlx::query(
+            "INSERT INTO script_migrations (script_uri, version, checksum) VALUES ($1, $2, $3)",
+        )
+        .bind(script_uri)
+        .bind(migration.version)
+        .bind(&checksum)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_db_err)?;
+
+        tx.commit().await.map_err(map_db_err)?;
+        info!(
+            "Applied migration {} for script '{}'",
+            migration.version, script_uri
+        );
+        applied.push(migration.version);
+    }
+
+    let version = db_get_script_schema_version(pool, script_uri).await?;
+    if let Ok(mut guard) = safe_lock_scripts()
+        && let Some(metadata) = guard.get_mut(script_uri)
+    {
+        metadata.schema_version = version;
+    }
+
+    Ok(ScriptMigrationSummary { applied, version })
+}
+
+/// Schema pgvector is installed in, once known
+static PGVECTOR_SCHEMA: OnceLock<String> = OnceLock::new();
+
+/// Install pgvector when it is missing and return the schema it lives in.
+///
+/// Installing needs a database user allowed to create extensions; where the
+/// engine runs without one, an administrator installs pgvector beforehand.
+async fn db_ensure_pgvector(pool: &PgPool) -> AppResult<String> {
+    if let Some(schema) = PGVECTOR_SCHEMA.get() {
+        return Ok(schema.clone());
+    }
+
+    let lookup = "SELECT n.nspname FROM pg_extension e \
+                  JOIN pg_namespace n ON n.oid = e.extnamespace \
+                  WHERE e.extname = 'vector'";
+    let mut schema: Option<String> = sqlx::query_scalar(lookup)
+        .fetch_optional(pool)
+        .await
+        .map_err(vector_db_error)?;
+    let mut install_error = None;
+    if schema.is_none() {
+        // Another server may be installing it at the same time, so look it
+        // up again whether or not this succeeds
+        if let Err(e) = sqlx::query("CREATE EXTENSION IF NOT EXISTS vector")
+            .execute(pool)
+            .await
+        {
+            install_error = Some(e.to_string());
+        }
+        schema = sqlx::query_scalar(lookup)
+            .fetch_optional(pool)
+            .await
+            .map_err(vector_db_error)?;
+    }
+
+    let schema = schema.ok_or_else(|| {
+        let reason = install_error.unwrap_or_else(|| "not installed".to_string());
+        error!("pgvector extension is not available: {}", reason);
+        AppError::Database {
+            message: format!("Vector collections need the pgvector extension: {}", reason),
+            source: None,
+        }
+    })?;
+    let _ = PGVECTOR_SCHEMA.set(schema.clone());
+    Ok(schema)
+}
+
+fn vector_db_error(e: sqlx::Error) -> AppError {
+    debug!("Vector store query failed: {}", e);
+    AppError::Database {
+        message: format!("Vector store error: {}", e),
+        source: None,
+    }
+}
+
+/// Begin a transaction running as the script's role, with the script's
+/// schema and pgvector's on the search path
+async fn db_begin_vector_transaction(
+    pool: &PgPool,
+    script_uri: &str,
+    tenant: Option<&str>,
+) -> AppResult<sqlx::Transaction<'static, sqlx::Postgres>> {
+    let schema = db_ensure_script_schema(pool, script_uri).await?;
+    let vector_schema = db_ensure_pgvector(pool).await?;
+
+    let mut tx = crate::database::begin(pool)
+        .await
+        .map_err(vector_db_error)?;
+    let setup = [
+        format!("SET LOCAL ROLE {}", quote_identifier(&schema)),
+        format!(
+            "SET LOCAL search_path TO {}, {}",
+            quote_identifier(&schema),
+            quote_identifier(&vector_schema)
+        ),
+        format!(
+            "SET LOCAL statement_timeout = {}",
+            MAX_SCRIPT_QUERY_TIMEOUT_MS
+        ),
+    ];
+    for statement in &setup {
+        sqlx::query(sqlx::AssertSqlSafe(statement.as_str()))
+            .execute(&mut *tx)
+            .await
+            .map_err(vector_db_error)?;
+    }
+    if let Some(tenant) = tenant {
+        db_set_transaction_tenant(&mut tx, tenant)
+            .await
+            .map_err(vector_db_error)?;
+    }
+    Ok(tx)
+}
+
+/// Vector length of a collection's table, or None if it does not exist
+async fn db_vector_collection_dimensions(
+    conn: &mut PgConnection,
+    table_name: &str,
+) -> AppResult<Option<usize>> {
+    let dimensions: Option<i32> = sqlx::query_scalar(
+        "SELECT atttypmod FROM pg_attribute \
+         WHERE attrelid = to_regclass($1) AND attname = 'embedding' AND NOT attisdropped",
+    )
+    .bind(quote_identifier(table_name))
+    .fetch_optional(conn)
+    .await
+    .map_err(vector_db_error)?;
+    Ok(dimensions.map(|dimensions| dimensions.max(0) as usize))
+}
+
+fn vector_collection_table(collection: &str) -> AppResult<String> {
+    generate_vector_table_name(collection).map_err(|e| AppError::Validation {
+        field: "collection".to_string(),
+        reason: e.to_string(),
+    })
+}
+
+fn vector_literal_param(embedding: &[f32]) -> AppResult<String> {
+    vector_literal(embedding).map_err(|e| AppError::Validation {
+        field: "embedding".to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Store documents in a script's vector collection, replacing documents
+/// with the same IDs.
+///
+/// The collection is a table in the script's private schema, created by the
+/// first call with the length of its vectors fixed from then on. Returns the
+/// number of documents stored.
+async fn db_upsert_vectors(
+    pool: &PgPool,
+    script_uri: &str,
+    collection: &str,
+    documents: &[VectorDocument],
+    tenant: Option<&str>,
+) -> AppResult<u64> {
+    let invalid = |reason: String| AppError::Validation {
+        field: "documents".to_string(),
+        reason,
+    };
+
+    let table_name = vector_collection_table(collection)?;
+    if documents.is_empty() || documents.len() > MAX_VECTOR_UPSERT_DOCUMENTS {
+        return Err(invalid(format!(
+            "Between 1 and {} documents may be stored at once",
+            MAX_VECTOR_UPSERT_DOCUMENTS
+        )));
+    }
+    let dimensions = documents[0].embedding.len();
+    let mut rows = Vec::with_capacity(documents.len());
+    for document in documents {
+        if document.id.is_empty() {
+            return Err(invalid("Every document needs an id".to_string()));
+        }
+        if document.embedding.len() != dimensions {
+            return Err(invalid(
+                "All embeddings must have the same number of dimensions".to_string(),
+            ));
+        }
+        let metadata = match &document.metadata {
+            None => serde_json::json!({}),
+            Some(metadata @ serde_json::Value::Object(_)) => metadata.clone(),
+            Some(_) => {
+                return Err(invalid(format!(
+                    "Metadata of document '{}' must be an object",
+                    document.id
+                )));
+            }
+        };
+        rows.push((
+            document,
+            metadata,
+            vector_literal_param(&document.embedding)?,
+        ));
+    }
+
+    let mut tx = db_begin_vector_transaction(pool, script_uri, tenant).await?;
+    match db_vector_collection_dimensions(&mut tx, &table_name).await? {
+        Some(existing) if existing != dimensions => {
+            return Err(invalid(format!(
+                "Collection '{}' stores {}-dimensional vectors, got {}",
+                collection, existing, dimensions
+            )));
+        }
+        Some(_) => {}
+        None => {
+            let table = quote_identifier(&table_name);
+            let create_table = format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    id TEXT PRIMARY KEY,
+                    content TEXT,
+                    metadata JSONB NOT NULL DEFAULT '{{}}',
+                    embedding vector({}) NOT NULL,
+                    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+                )",
+                table, dimensions
+            );
+            sqlx::query(sqlx::AssertSqlSafe(create_table.as_str()))
+                .execute(&mut *tx)
+                .await
+                .map_err(vector_db_error)?;
+            if dimensions <= MAX_INDEXED_VECTOR_DIMENSIONS {
+                let create_index = format!(
+                    "CREATE INDEX IF NOT EXISTS {} ON {} USING hnsw (embedding vector_cosine_ops)",
+                    quote_identifier(&format!("{}_embedding_idx", table_name)),
+                    table
+                );
+                sqlx::query(sqlx::AssertSqlSafe(create_index.as_str()))
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(vector_db_error)?;
+            }
+            if tenant.is_some() {
+                sqlx::query(TENANT_POLICY_SQL)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(vector_db_error)?;
+            }
+            debug!(
+                "Created vector collection '{}' ({} dimensions) for script '{}'",
+                collection, dimensions, script_uri
+            );
+        }
+    }
+
+    let upsert = format!(
+        "INSERT INTO {} (id, content, metadata, embedding) VALUES ($1, $2, $3, CAST($4 AS vector))
+         ON CONFLICT (id) DO UPDATE SET content = EXCLUDED.content, metadata = EXCLUDED.metadata,
+             embedding = EXCLUDED.embedding, updated_at = NOW()",
+        quote_identifier(&table_name)
+    );
+    for (document, metadata, embedding) in &rows {
+        sqlx::query(sqlx::AssertSqlSafe(upsert.as_str()))
+            .bind(&document.id)
+            .bind(&document.text)
+            .bind(sqlx::types::Json(metadata))
+            .bind(embedding)
+            .execute(&mut *tx)
+            .await
+            .map_err(vector_db_error)?;
+    }
+
+    tx.commit().await.map_err(vector_db_error)?;
+    Ok(rows.len() as u64)
+}
+
+/// One page of the documents of a script's vector collection closest to
+/// `embedding` by cosine distance, best first. A collection that does not
+/// exist yet has no matches.
+///
+/// Filters apply before paging, so `nextOffset` continues the same ordered
+/// result. Pages may reach [`MAX_VECTOR_SEARCH_DEPTH`] matches deep.
+async fn db_search_vectors(
+    pool: &PgPool,
+    script_uri: &str,
+    collection: &str,
+    embedding: &[f32],
+    options: &VectorSearchOptions,
+    tenant: Option<&str>,
+) -> AppResult<VectorSearchPage> {
+    let table_name = vector_collection_table(collection)?;
+    let query_vector = vector_literal_param(embedding)?;
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_VECTOR_SEARCH_LIMIT)
+        .clamp(1, MAX_VECTOR_SEARCH_LIMIT);
+    let offset = options.offset.unwrap_or(0);
+    if offset + limit > MAX_VECTOR_SEARCH_DEPTH {
+        return Err(AppError::Validation {
+            field: "offset".to_string(),
+            reason: format!(
+                "offset plus limit must not exceed {}",
+                MAX_VECTOR_SEARCH_DEPTH
+            ),
+        });
+    }
+    if let Some(filter) = &options.filter
+        && !filter.is_object()
+    {
+        return Err(AppError::Validation {
+            field: "filter".to_string(),
+            reason: "filter must be an object".to_string(),
+        });
+    }
+
+    let empty = VectorSearchPage {
+        matches: Vec::new(),
+        next_offset: None,
+    };
+    let mut tx = db_begin_vector_transaction(pool, script_uri, tenant).await?;
+    match db_vector_collection_dimensions(&mut tx, &table_name).await? {
+        None => return Ok(empty),
+        Some(dimensions) if dimensions != embedding.len() => {
+            return Err(AppError::Validation {
+                field: "embedding".to_string(),
+                reason: format!(
+                    "Collection '{}' stores {}-dimensional vectors, got {}",
+                    collection,
+                    dimensions,
+                    embedding.len()
+                ),
+            });
+        }
+        Some(_) => {}
+    }
+
+    // The HNSW index yields ef_search candidates; ask for enough to fill
+    // the page and tell whether another follows
+    sqlx::query("SELECT set_config('hnsw.ef_search', $1, true)")
+        .bind((offset + limit + 1).max(40).to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(vector_db_error)?;
+
+    let search = format!(
+        "SELECT id, content, metadata, 1 - (embedding <=> CAST($1 AS vector)) AS score
+         FROM {}
+         WHERE ($2::jsonb IS NULL OR metadata @> $2::jsonb)
+           AND ($3::float8 IS NULL OR 1 - (embedding <=> CAST($1 AS vector)) >= $3::float8)
+         ORDER BY embedding <=> CAST($1 AS vector)
+         LIMIT $4 OFFSET $5",
+        quote_identifier(&table_name)
+    );
+    let mut rows: Vec<(
+        String,
+        Option<String>,
+        sqlx::types::Json<serde_json::Value>,
+        f64,
+    )> = sqlx::query_as(sqlx::AssertSqlSafe(search.as_str()))
+        .bind(&query_vector)
+        .bind(options.filter.as_ref().map(sqlx::types::Json))
+        .bind(options.min_score)
+        .bind(i64::from(limit) + 1)
+        .bind(i64::from(offset))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(vector_db_error)?;
+    tx.commit().await.map_err(vector_db_error)?;
+
+    let has_more = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+    Ok(VectorSearchPage {
+        matches: rows
+            .into_iter()
+            .map(|(id, text, metadata, score)| VectorMatch {
+                id,
+                text,
+                metadata: metadata.0,
+                score,
+            })
+            .collect(),
+        next_offset: (has_more && offset + limit < MAX_VECTOR_SEARCH_DEPTH)
+            .then_some(offset + limit),
+    })
+}
+
+/// Delete documents from a script's vector collection by ID
+async fn db_delete_vectors(
+    pool: &PgPool,
+    script_uri: &str,
+    collection: &str,
+    ids: &[String],
+    tenant: Option<&str>,
+) -> AppResult<u64> {
+    let table_name = vector_collection_table(collection)?;
+    let mut tx = db_begin_vector_transaction(pool, script_uri, tenant).await?;
+    if db_vector_collection_dimensions(&mut tx, &table_name)
+        .await?
+        .is_none()
+    {
+        return Ok(0);
+    }
+
+    let delete = format!(
+        "DELETE FROM {} WHERE id = ANY($1)",
+        quote_identifier(&table_name)
+    );
+    let result = sqlx::query(sqlx::AssertSqlSafe(delete.as_str()))
+        .bind(ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(vector_db_error)?;
+    tx.commit().await.map_err(vector_db_error)?;
+    Ok(result.rows_affected())
+}
+
+/// Drop a script's vector collection. Returns whether it existed.
+async fn db_drop_vector_collection(
+    pool: &PgPool,
+    script_uri: &str,
+    collection: &str,
+) -> AppResult<bool> {
+    let table_name = vector_collection_table(collection)?;
+    let mut tx = db_begin_vector_transaction(pool, script_uri, None).await?;
+    if db_vector_collection_dimensions(&mut tx, &table_name)
+        .await?
+        .is_none()
+    {
+        return Ok(false);
+    }
+
+    let drop = format!("DROP TABLE {}", quote_identifier(&table_name));
+    sqlx::query(sqlx::AssertSqlSafe(drop.as_str()))
+        .execute(&mut *tx)
+        .await
+        .map_err(vector_db_error)?;
+    tx.commit().await.map_err(vector_db_error)?;
+    debug!(
+        "Dropped vector collection '{}' for script '{}'",
+        collection, script_uri
+    );
+    Ok(true)
+}
+
+/// Highest migration version applied by a script
+async fn db_get_script_schema_version<'e, E>(
+    executor: E,
+    script_uri: &str,
+) -> AppResult<Option<i64>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar("SELECT MAX(version) FROM script_migrations WHERE script_uri = $1")
+        .bind(script_uri)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| {
+            error!("Database error getting schema version: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })
+}
+
+/// Highest applied migration version of every script that has migrations
+async fn db_get_all_script_schema_versions<'e, E>(executor: E) -> AppResult<HashMap<String, i64>>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let rows: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT script_uri, MAX(version) FROM script_migrations GROUP BY script_uri",
+    )
+    .fetch_all(executor)
+    .await
+    .map_err(|e| {
+        error!("Database error getting schema versions: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    Ok(rows.into_iter().collect())
+}
+
+/// Fetch scripts from repository with proper error handling
+pub fn fetch_scripts() -> HashMap<String, String> {
+    let repo = get_repository();
+
+    // Use run_blocking to call async repository method
+    let result = run_blocking(async { repo.list_scripts().await });
+
+    match result {
+        Ok(scripts) => {
+            debug!("Loaded {} scripts from repository", scripts.len());
+            scripts
+        }
+        Err(e) => {
+            error!("Failed to fetch scripts: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Fetch a single script by URI with proper error handling
+pub fn fetch_script(uri: &str) -> Option<String> {
+    let repo = get_repository();
+
+    let result = run_blocking(async { repo.get_script(uri).await });
+
+    match result {
+        Ok(Some(script)) => {
+            debug!("Loaded script from repository: {}", uri);
+            Some(script)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to fetch script {}: {}", uri, e);
+            None
+        }
+    }
+}
+
+/// Get metadata for a script
+pub fn get_script_metadata(uri: &str) -> AppResult<ScriptMetadata> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_metadata(uri).await })
+}
+
+/// Get metadata for all scripts
+pub fn get_all_script_metadata() -> AppResult<Vec<ScriptMetadata>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_all_script_metadata().await })
+}
+
+pub fn mark_script_init_failed(uri: &str, error: String) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.update_script_init_status(uri, false, Some(error), None)
+            .await
+    })
+}
+
+pub fn mark_script_initialized(uri: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.update_script_init_status(uri, true, None, None).await })
+}
+
+pub fn mark_script_initialized_with_registrations(
+    uri: &str,
+    registrations: RouteRegistrations,
+) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.update_script_init_status(uri, true, None, Some(registrations))
+            .await
+    })
+}
+
+/// Determine current privilege state for a script
+pub fn get_script_security_profile(uri: &str) -> AppResult<ScriptSecurityProfile> {
+    let default_privileged = default_privileged_for(uri);
+    let repo = get_repository();
+
+    // Try repository first
+    let result = run_blocking(async { repo.get_script_privileged(uri).await });
+
+    if let Ok(Some(privileged)) = result {
+        return Ok(ScriptSecurityProfile {
+            uri: uri.to_string(),
+            privileged,
+            default_privileged,
+        });
+    }
+
+    // If repository didn't have it (or failed), check overrides explicitly
+    // This handles the case where we are in Postgres mode but the script is static/local
+    // and has a local override.
+    if let Ok(guard) = safe_lock_privilege_overrides()
+        && let Some(value) = guard.get(uri)
+    {
+        return Ok(ScriptSecurityProfile {
+            uri: uri.to_string(),
+            privileged: *value,
+            default_privileged,
+        });
+    }
+
+    Ok(ScriptSecurityProfile {
+        uri: uri.to_string(),
+        privileged: default_privileged,
+        default_privileged,
+    })
+}
+
+/// Helper used by security enforcement
+pub fn is_script_privileged(uri: &str) -> AppResult<bool> {
+    Ok(get_script_security_profile(uri)?.privileged)
+}
+
+/// Update the privilege flag for a script
+pub fn set_script_privileged(uri: &str, privileged: bool) -> AppResult<()> {
+    // Ensure script exists before toggling
+    if fetch_script(uri).is_none() {
+        return Err(RepositoryError::ScriptNotFound(uri.to_string()).into());
+    }
+
+    let repo = get_repository();
+
+    // 1. Try to update repository (DB or Memory)
+    let result = run_blocking(async { repo.set_script_privileged(uri, privileged).await });
+
+    if let Err(e) = result {
+        warn!("Repository set privileged failed for {}: {}", uri, e);
+    }
+
+    // 2. Also update in-memory structures to ensure static scripts or fallbacks work
+
+    // Update dynamic metadata cache if present
+    if let Ok(mut guard) = safe_lock_scripts()
+        && let Some(metadata) = guard.get_mut(uri)
+    {
+        metadata.privileged = privileged;
+        debug!(script = %uri, privileged, "Updated in-memory script privilege");
+        return Ok(());
+    }
+
+    // Persist override for static scripts or when metadata not present
+    let mut guard = safe_lock_privilege_overrides()?;
+    guard.insert(uri.to_string(), privileged);
+    debug!(script = %uri, privileged, "Stored privilege override in memory");
+
+    Ok(())
+}
+
+/// Insert log message with error handling
+pub fn insert_log_message(script_uri: &str, message: &str, log_level: &str) {
+    run_blocking(insert_log_message_async(script_uri, message, log_level))
+}
+
+/// Async variant of [`insert_log_message`] for callers already in async context
+pub async fn insert_log_message_async(script_uri: &str, message: &str, log_level: &str) {
+    let repo = get_repository();
+    if let Err(e) = repo.insert_log(script_uri, message, log_level).await {
+        error!(
+            "Failed to insert log message for {}: {}. Message: {}",
+            script_uri, e, message
+        );
+        // Log to system instead as fallback
+        error!("FALLBACK LOG [{}]: {}", script_uri, message);
+    }
+}
+
+/// Insert a log message with a structured payload and the request/handler
+/// context it was written in
+pub fn insert_structured_log_message(
+    script_uri: &str,
+    message: &str,
+    log_level: &str,
+    data: Option<&serde_json::Value>,
+    context: Option<&serde_json::Value>,
+) {
+    let repo = get_repository();
+    let result = run_blocking(async {
+        repo.insert_structured_log(script_uri, message, log_level, data, context)
+            .await
+    });
+    if let Err(e) = result {
+        error!(
+            "Failed to insert log message for {}: {}. Message: {}",
+            script_uri, e, message
+        );
+        error!("FALLBACK LOG [{}]: {}", script_uri, message);
+    }
+}
+
+/// Async variant of [`insert_structured_log_message`] for callers already in
+/// async context
+pub async fn insert_structured_log_message_async(
+    script_uri: &str,
+    message: &str,
+    log_level: &str,
+    data: Option<&serde_json::Value>,
+    context: Option<&serde_json::Value>,
+) {
+    let repo = get_repository();
+    if let Err(e) = repo
+        .insert_structured_log(script_uri, message, log_level, data, context)
+        .await
+    {
+        error!(
+            "Failed to insert log message for {}: {}. Message: {}",
+            script_uri, e, message
+        );
+        error!("FALLBACK LOG [{}]: {}", script_uri, message);
+    }
+}
+
+/// Fetch log messages with error handling
+pub fn fetch_log_messages(script_uri: &str) -> Vec<LogEntry> {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.fetch_logs(script_uri).await });
+
+    match result {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Failed to fetch log messages for {}: {}", script_uri, e);
+            let now = SystemTime::now();
+            vec![LogEntry::new(
+                format!("Error: Could not retrieve logs - {}", e),
+                "ERROR".to_string(),
+                now,
+            )]
+        }
+    }
+}
+
+/// Fetch ALL log messages from all script URIs
+pub fn fetch_all_log_messages() -> Vec<LogEntry> {
+    // Try database first if configured
+    let repo = get_repository();
+    let result = run_blocking(async { repo.fetch_all_logs().await });
+
+    match result {
+        Ok(messages) => messages,
+        Err(e) => {
+            error!("Failed to fetch all log messages: {}", e);
+            vec![LogEntry::new(
+                format!("Error: Could not retrieve logs - {}", e),
+                "ERROR".to_string(),
+                SystemTime::now(),
+            )]
+        }
+    }
+}
+
+/// Query log messages with filters and cursor pagination
+pub fn query_log_messages(query: &LogQuery) -> AppResult<LogPage> {
+    let repo = get_repository();
+    run_blocking(async { repo.query_logs(query).await })
+}
+
+/// Clear log messages for a script
+pub fn clear_log_messages(script_uri: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.clear_logs(script_uri).await })
+}
+
+/// Keep only the latest `limit` log messages (default 20) for each script URI and remove older ones
+pub fn prune_log_messages() -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.prune_logs().await })
+}
+
+/// Fail with `ProtectedScript` if `uri` is a system script and the caller
+/// didn't ask to `force` the change
+async fn ensure_script_not_system(uri: &str, force: bool) -> AppResult<()> {
+    if !force && get_repository().get_script_system(uri).await? == Some(true) {
+        return Err(RepositoryError::ProtectedScript(uri.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Upsert script with error handling. System scripts are refused.
+pub fn upsert_script(uri: &str, content: &str) -> AppResult<()> {
+    run_blocking(upsert_script_async(uri, content))
+}
+
+/// Async variant of [`upsert_script`] for callers already in async context
+pub async fn upsert_script_async(uri: &str, content: &str) -> AppResult<()> {
+    if uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("URI cannot be empty".to_string()).into());
+    }
+
+    if content.len() > 1_000_000 {
+        // 1MB limit
+        return Err(
+            RepositoryError::InvalidData("Script content too large (>1MB)".to_string()).into(),
+        );
+    }
+
+    ensure_script_not_system(uri, false).await?;
+
+    let repo = get_repository();
+    repo.upsert_script(uri, content).await
+}
+
+/// Upsert script and set owner if it's a new script. System scripts are
+/// only overwritten with `force`.
+pub fn upsert_script_with_owner(
+    uri: &str,
+    content: &str,
+    owner_user_id: Option<&str>,
+    force: bool,
+) -> AppResult<()> {
+    debug!(
+        "upsert_script_with_owner called: uri={}, owner_user_id={:?}, content_len={}",
+        uri,
+        owner_user_id,
+        content.len()
+    );
+
+    if uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("URI cannot be empty".to_string()).into());
+    }
+
+    if content.len() > 1_000_000 {
+        // 1MB limit
+        return Err(
+            RepositoryError::InvalidData("Script content too large (>1MB)".to_string()).into(),
+        );
+    }
+
+    run_blocking(ensure_script_not_system(uri, force))?;
+
+    // Check if script already exists
+    let script_exists = fetch_script(uri).is_some();
+    debug!(
+        "Script existence check: uri={}, exists={}",
+        uri, script_exists
+    );
+
+    // Upsert the script
+    let repo = get_repository();
+    run_blocking(async { repo.upsert_script(uri, content).await })?;
+
+    // Assign ownership if needed:
+    // - For NEW scripts: set the creator as owner
+    // - For EXISTING scripts: set owner if they don't have any owners yet (backfill)
+    // - Skip bootstrap scripts (they remain ownerless)
+    let is_bootstrap = is_bootstrap_script(uri);
+    debug!(
+        "Bootstrap check: uri={}, is_bootstrap={}",
+        uri, is_bootstrap
+    );
+
+    if !is_bootstrap && let Some(user_id) = owner_user_id {
+        // Check if script has any owners
+        let owner_count = run_blocking(async { repo.count_script_owners(uri).await }).unwrap_or(0);
+        let has_owners = owner_count > 0;
+        debug!(
+            "Owner count check: uri={}, count={}, has_owners={}",
+            uri, owner_count, has_owners
+        );
+
+        // If script has no owners, assign this user as the first owner
+        if !has_owners {
+            debug!("Attempting to add owner: uri={}, user_id={}", uri, user_id);
+            run_blocking(async { repo.add_script_owner(uri, user_id).await })?;
+            if script_exists {
+                debug!(
+                    "✓ Backfilled owner {} for existing script {} (had no owners)",
+                    user_id, uri
+                );
+            } else {
+                debug!("✓ Set initial owner {} for new script {}", user_id, uri);
+            }
+        } else {
+            debug!(
+                "Skipping ownership assignment - script already has {} owner(s): uri={}",
+                owner_count, uri
+            );
+        }
+    } else if is_bootstrap {
+        debug!(
+            "Skipping ownership assignment - bootstrap script: uri={}",
+            uri
+        );
+    } else {
+        debug!(
+            "Skipping ownership assignment - no user_id provided: uri={}",
+            uri
+        );
+    }
+
+    Ok(())
+}
+
+/// Bootstrap hardcoded scripts into database on startup
+pub fn bootstrap_scripts() -> AppResult<()> {
+    run_blocking(bootstrap_scripts_async())
+}
+
+/// URIs and sources of the feature scripts built into the binary
+pub fn bootstrap_script_sources() -> Vec<(&'static str, &'static str)> {
+    vec![
+        (
+            "https://example.com/core",
+            include_str!("../scripts/feature_scripts/core.js"),
+        ),
+        (
+            "https://example.com/cli",
+            include_str!("../scripts/feature_scripts/cli.js"),
+        ),
+        (
+            "https://example.com/admin",
+            include_str!("../scripts/feature_scripts/admin.js"),
+        ),
+        (
+            "https://example.com/auth",
+            include_str!("../scripts/feature_scripts/auth.js"),
+        ),
+    ]
+}
+
+/// Async variant of [`bootstrap_scripts`] for callers already in async context
+pub async fn bootstrap_scripts_async() -> AppResult<()> {
+    if let Some(db) = get_db_pool() {
+        let pool = db.pool();
+
+        // Define the hardcoded scripts
+        let hardcoded_scripts = bootstrap_script_sources();
+
+        // Bootstrapped feature scripts are protected from editors
+        let system_scripts: Vec<&str> = hardcoded_scripts.iter().map(|(uri, _)| *uri).collect();
+
+        // Include test scripts when appropriate
+        let mut all_scripts = hardcoded_scripts;
+        let include_test_scripts =
+            std::env::var("AIWEBENGINE_INCLUDE_TEST_SCRIPTS").is_ok() || cfg!(test);
+
+        if include_test_scripts {
+            all_scripts.push((
+                "https://example.com/graphql_test",
+                include_str!("../scripts/test_scripts/graphql_test.js"),
+            ));
+            all_scripts.push((
+                "https://example.com/dispatcher_test",
+                include_str!("../scripts/test_scripts/dispatcher_test.js"),
+            ));
+        }
+
+        let result = async {
+            for (uri, code) in all_scripts {
+                let mut exists = false;
+                // Check if script already exists
+                if let Ok(Some(existing_content)) = db_get_script(pool, uri).await {
+                    debug!("Script already exists in database: {}", uri);
+                    exists = true;
+
+                    // Update if content differs
+                    if existing_content != code {
+                        info!("Updating bootstrap script in database: {}", uri);
+                        if let Err(e) = db_upsert_script(
+                            crate::database::TransactionExecutor::Pool(pool),
+                            uri,
+                            code,
+                        )
+                        .await
+                        {
+                            error!("Failed to update bootstrap script {}: {}", uri, e);
+                            return Err(e);
+                        }
+                    }
+                }
+
+                if !exists {
+                    // Insert the script
+                    if let Err(e) = db_upsert_script(
+                        crate::database::TransactionExecutor::Pool(pool),
+                        uri,
+                        code,
+                    )
+                    .await
+                    {
+                        error!("Failed to bootstrap script {}: {}", uri, e);
+                        return Err(e);
+                    } else {
+                        info!("Bootstrapped script into database: {}", uri);
+                    }
+                }
+
+                // Ensure privileged flag is set for privileged scripts
+                if PRIVILEGED_BOOTSTRAP_SCRIPTS.contains(&uri) {
+                    if let Err(e) = db_set_script_privileged(pool, uri, true).await {
+                        warn!(
+                            "Failed to flag bootstrapped script {} as privileged: {}",
+                            uri, e
+                        );
+                        eprintln!("DEBUG: Failed to set privileged flag for {}: {}", uri, e);
+                    } else {
+                        eprintln!("DEBUG: Successfully set privileged flag for {}", uri);
+                    }
+                }
+
+                if system_scripts.contains(&uri)
+                    && let Err(e) = db_set_script_system(pool, uri, true).await
+                {
+                    warn!(
+                        "Failed to flag bootstrapped script {} as system script: {}",
+                        uri, e
+                    );
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                info!("Successfully bootstrapped scripts into database");
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to bootstrap scripts: {}", e);
+                Err(e)
+            }
+        }
+    } else {
+        debug!("Database not configured, skipping script bootstrap");
+        Ok(())
+    }
+}
+
+/// Bootstrap hardcoded assets into database on startup
+pub fn bootstrap_assets() -> AppResult<()> {
+    run_blocking(async { bootstrap_assets_async().await })
+}
+
+/// Async version of bootstrap_assets
+pub async fn bootstrap_assets_async() -> AppResult<()> {
+    let repo = get_repository();
+    let assets = get_static_assets();
+    info!("Bootstrapping {} assets...", assets.len());
+
+    for (uri, asset) in assets {
+        debug!("Bootstrapping asset: {}", uri);
+        if let Err(e) = repo.upsert_asset(asset).await {
+            error!("Failed to bootstrap asset {}: {}", uri, e);
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete script with error handling. System scripts are refused.
+pub fn delete_script(uri: &str) -> bool {
+    match try_delete_script(uri, false) {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!("Failed to delete script {}: {}", uri, e);
+            false
+        }
+    }
+}
+
+/// Delete a script, returning whether it existed. System scripts are only
+/// deleted with `force`.
+pub fn try_delete_script(uri: &str, force: bool) -> AppResult<bool> {
+    let repo = get_repository();
+
+    let existed = run_blocking(async {
+        ensure_script_not_system(uri, force).await?;
+        repo.delete_script(uri).await
+    })?;
+
+    if existed {
+        scheduler::clear_script_jobs(uri);
+        crate::notifications::clear_script_channels(uri);
+        crate::events::clear_script_subscriptions(uri);
+        debug!("Deleted script from repository: {}", uri);
+    } else {
+        debug!("Script not found in repository for deletion: {}", uri);
+    }
+    Ok(existed)
+}
+
+/// Whether a script is a protected system script
+pub fn is_system_script(uri: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    Ok(run_blocking(async { repo.get_script_system(uri).await })?.unwrap_or(false))
+}
+
+/// Mark a script as a system script, or lift the protection
+pub fn set_script_system(uri: &str, system: bool) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_system(uri, system).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Add an owner to a script
+pub fn add_script_owner(uri: &str, user_id: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.add_script_owner(uri, user_id).await })
+}
+
+/// Remove an owner from a script
+pub fn remove_script_owner(uri: &str, user_id: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.remove_script_owner(uri, user_id).await })
+}
+
+/// Get all owners of a script
+pub fn get_script_owners(uri: &str) -> AppResult<Vec<String>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_owners(uri).await })
+}
+
+/// Check if a user owns a script
+pub fn user_owns_script(uri: &str, user_id: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.user_owns_script(uri, user_id).await })
+}
+
+/// Count the number of owners for a script
+pub fn count_script_owners(uri: &str) -> AppResult<i64> {
+    let repo = get_repository();
+    run_blocking(async { repo.count_script_owners(uri).await })
+}
+
+/// Add a collaborator to a script
+pub fn add_script_collaborator(uri: &str, user_id: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.add_script_collaborator(uri, user_id).await })
+}
+
+/// Remove a collaborator from a script
+pub fn remove_script_collaborator(uri: &str, user_id: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.remove_script_collaborator(uri, user_id).await })
+}
+
+/// Get all collaborators of a script
+pub fn get_script_collaborators(uri: &str) -> AppResult<Vec<String>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_collaborators(uri).await })
+}
+
+/// Check if a user is a collaborator on a script
+pub fn user_collaborates_on_script(uri: &str, user_id: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.user_collaborates_on_script(uri, user_id).await })
+}
+
+/// Restore a soft-deleted script, its owners, collaborators, tables and the
+/// assets deleted with it. Fails if a script with the same URI exists again.
+pub fn restore_script(uri: &str) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.restore_script(uri).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Restore an individually deleted asset of an existing script
+pub fn restore_asset(script_uri: &str, uri: &str) -> AppResult<()> {
+    if fetch_script(script_uri).is_none() {
+        return Err(RepositoryError::ScriptNotFound(script_uri.to_string()).into());
+    }
+    if fetch_asset(script_uri, uri).is_some() {
+        return Err(AppError::validation(
+            "uri",
+            format!("An asset with URI '{}' already exists", uri),
+        ));
+    }
+    let repo = get_repository();
+    if run_blocking(async { repo.restore_asset(script_uri, uri).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::AssetNotFound(uri.to_string()).into())
+    }
+}
+
+/// List soft-deleted scripts
+pub fn list_trashed_scripts() -> AppResult<Vec<TrashedScript>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_trashed_scripts().await })
+}
+
+/// List individually soft-deleted assets of a script
+pub fn list_trashed_assets(script_uri: &str) -> AppResult<Vec<TrashedAsset>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_trashed_assets(script_uri).await })
+}
+
+/// Permanently remove trash entries older than the configured retention
+pub fn purge_trash() -> AppResult<TrashPurgeSummary> {
+    let retention_days = i64::try_from(trash_retention_days()).unwrap_or(i64::MAX);
+    let cutoff = Utc::now() - chrono::Duration::days(retention_days.min(365_000));
+    let repo = get_repository();
+    run_blocking(async { repo.purge_trash(cutoff).await })
+}
+
+/// Get the description and tags of a script
+pub fn get_script_labels(uri: &str) -> AppResult<ScriptLabels> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_labels(uri).await })?
+        .ok_or_else(|| RepositoryError::ScriptNotFound(uri.to_string()).into())
+}
+
+/// Replace the description and tags of a script
+pub fn set_script_labels(uri: &str, labels: &ScriptLabels) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_labels(uri, labels).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Get the source map uploaded for a script, if any
+pub fn get_script_source_map(uri: &str) -> AppResult<Option<StoredSourceMap>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_source_map(uri).await })
+}
+
+/// Store the source map uploaded for a script, or remove it with `None`
+pub fn set_script_source_map(uri: &str, source_map: Option<&StoredSourceMap>) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_source_map(uri, source_map).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Get the bytes a script stores in shared storage and assets, and its quota
+pub fn get_script_storage_usage(script_uri: &str) -> AppResult<ScriptStorageUsage> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_storage_usage(script_uri, None, None).await })
+}
+
+/// Override the storage quota of a script (0 = unlimited), or fall back to
+/// the configured default with `None`
+pub fn set_script_storage_quota(uri: &str, quota_bytes: Option<u64>) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_storage_quota(uri, quota_bytes).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Override the handler timeout of a script, or fall back to the configured
+/// `javascript.execution_timeout_ms` with `None`
+pub fn set_script_execution_timeout(uri: &str, timeout_ms: Option<u64>) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_execution_timeout(uri, timeout_ms).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Override the memory limit of a script, or fall back to the configured
+/// `javascript.max_memory_bytes` with `None`
+pub fn set_script_memory_limit(uri: &str, limit_bytes: Option<u64>) -> AppResult<()> {
+    let repo = get_repository();
+    if run_blocking(async { repo.set_script_memory_limit(uri, limit_bytes).await })? {
+        Ok(())
+    } else {
+        Err(RepositoryError::ScriptNotFound(uri.to_string()).into())
+    }
+}
+
+/// Memory limit override of a script as last loaded with its metadata.
+/// Reads the metadata cache only, so executions can check it cheaply.
+pub fn script_memory_limit(uri: &str) -> Option<u64> {
+    safe_lock_scripts().ok()?.get(uri)?.memory_limit_bytes
+}
+
+/// Fail with `QuotaExceeded` if storing `new_bytes` in place of the given
+/// shared storage key or asset would take the script over its quota
+async fn ensure_storage_quota(
+    script_uri: &str,
+    exclude_key: Option<&str>,
+    exclude_asset_uri: Option<&str>,
+    new_bytes: u64,
+) -> AppResult<()> {
+    let repo = get_repository();
+    let usage = repo
+        .get_script_storage_usage(script_uri, exclude_key, exclude_asset_uri)
+        .await?;
+    let Some(quota) = usage.quota_bytes else {
+        return Ok(());
+    };
+
+    let needed = usage.used_bytes.saturating_add(new_bytes);
+    if needed > quota {
+        return Err(RepositoryError::QuotaExceeded(format!(
+            "script '{}' would use {} bytes of its {} byte quota",
+            script_uri, needed, quota
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Fail with `QuotaExceeded` if storing `new_bytes` in place of a tenant's
+/// shared storage key would take the tenant over its storage quota. Keys
+/// outside any tenant's prefix are not limited here.
+async fn ensure_tenant_storage_quota(script_uri: &str, key: &str, new_bytes: u64) -> AppResult<()> {
+    let Some(tenant) = crate::tenancy::tenant_of_storage_key(key) else {
+        return Ok(());
+    };
+    let quota = crate::tenancy::quotas().storage_bytes;
+    if quota == 0 {
+        return Ok(());
+    }
+
+    let prefix = crate::tenancy::tenant_storage_prefix(tenant);
+    let used = get_repository()
+        .get_tenant_storage_bytes(&prefix, Some((script_uri, key)))
+        .await?;
+    let needed = used.saturating_add(new_bytes);
+    if needed > quota {
+        return Err(RepositoryError::QuotaExceeded(format!(
+            "tenant '{}' would use {} bytes of its {} byte quota",
+            tenant, needed, quota
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Live shared storage bytes of every tenant, keyed by tenant ID
+pub fn list_tenant_storage_bytes() -> AppResult<HashMap<String, u64>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_tenant_storage_bytes().await })
+}
+
+/// Shared storage and asset bytes of every script, keyed by script URI
+pub fn list_script_storage_bytes() -> AppResult<HashMap<String, u64>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_script_storage_bytes().await })
+}
+
+// ============================================================================
+// Script Database Schema Public API
+// ============================================================================
+
+/// Create a new table for a script
+pub fn create_script_table(script_uri: &str, logical_table_name: &str) -> AppResult<String> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.create_script_table(script_uri, logical_table_name)
+            .await
+    })
+}
+
+/// Add a column to a script-owned table
+pub fn add_column_to_script_table(
+    script_uri: &str,
+    logical_table_name: &str,
+    column_name: &str,
+    column_type: ColumnType,
+    nullable: bool,
+    default_value: Option<&str>,
+) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.add_column_to_script_table(
+            script_uri,
+            logical_table_name,
+            column_name,
+            column_type,
+            nullable,
+            default_value,
+        )
+        .await
+    })
+}
+
+/// Add a reference column (INTEGER with FK) to a script-owned table
+pub fn add_reference_column(
+    script_uri: &str,
+    logical_table_name: &str,
+    column_name: &str,
+    referenced_logical_table_name: &str,
+    nullable: bool,
+) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.add_reference_column(
+            script_uri,
+            logical_table_name,
+            column_name,
+            referenced_logical_table_name,
+            nullable,
+        )
+        .await
+    })
+}
+
+/// Drop a column from a script-owned table
+pub fn drop_column(
+    script_uri: &str,
+    logical_table_name: &str,
+    column_name: &str,
+) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.drop_column(script_uri, logical_table_name, column_name)
+            .await
+    })
+}
+
+/// Drop a script-owned table
+pub fn drop_script_table(script_uri: &str, logical_table_name: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.drop_script_table(script_uri, logical_table_name).await })
+}
+
+// ============================================================================
+// Script Database Introspection and Data Operations (Synchronous Wrappers)
+// ============================================================================
+
+/// List all tables owned by a script
+pub fn list_script_tables(script_uri: &str) -> AppResult<Vec<TableInfo>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_script_tables(script_uri).await })
+}
+
+/// Get detailed schema for a table
+pub fn get_table_schema(script_uri: &str, logical_table_name: &str) -> AppResult<TableSchema> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_table_schema(script_uri, logical_table_name).await })
+}
+
+/// Get foreign key relationships for a table
+pub fn get_foreign_keys(
+    script_uri: &str,
+    logical_table_name: &str,
+) -> AppResult<Vec<ForeignKeyInfo>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_foreign_keys(script_uri, logical_table_name).await })
+}
+
+/// Query rows from a script-owned table
+pub fn query_table(
+    script_uri: &str,
+    logical_table_name: &str,
+    filters: Option<&HashMap<String, serde_json::Value>>,
+    limit: Option<i64>,
+    order_by: Option<&str>,
+    order_dir: Option<&str>,
+) -> AppResult<Vec<serde_json::Value>> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.query_table(
+            script_uri,
+            logical_table_name,
+            filters,
+            limit,
+            order_by,
+            order_dir,
+        )
+        .await
+    })
+}
+
+/// Insert a row into a script-owned table
+pub fn insert_row(
+    script_uri: &str,
+    logical_table_name: &str,
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let repo = get_repository();
+    run_blocking(async { repo.insert_row(script_uri, logical_table_name, data).await })
+}
+
+/// Update a row in a script-owned table
+pub fn update_row(
+    script_uri: &str,
+    logical_table_name: &str,
+    id: i32,
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.update_row(script_uri, logical_table_name, id, data)
+            .await
+    })
+}
+
+/// Delete a row from a script-owned table
+pub fn delete_row(script_uri: &str, logical_table_name: &str, id: i32) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.delete_row(script_uri, logical_table_name, id).await })
+}
+
+/// Upsert a row into a script-owned table (INSERT … ON CONFLICT DO UPDATE)
+pub fn upsert_row(
+    script_uri: &str,
+    logical_table_name: &str,
+    key_columns: &[String],
+    data: &HashMap<String, serde_json::Value>,
+) -> AppResult<serde_json::Value> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.upsert_row(script_uri, logical_table_name, key_columns, data)
+            .await
+    })
+}
+
+/// Delete rows from a script-owned table matching the given filters
+pub fn delete_where(
+    script_uri: &str,
+    logical_table_name: &str,
+    filters: &HashMap<String, serde_json::Value>,
+) -> AppResult<u64> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.delete_where(script_uri, logical_table_name, filters)
+            .await
+    })
+}
+
+/// Atomically acquire or extend a distributed lease in a script-owned table
+pub fn acquire_lease(
+    script_uri: &str,
+    logical_table_name: &str,
+    lease_id: &str,
+    owner: &str,
+    ttl_ms: i64,
+) -> AppResult<serde_json::Value> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.acquire_lease(script_uri, logical_table_name, lease_id, owner, ttl_ms)
+            .await
+    })
+}
+
+/// Create a lease table with the required schema in a script-owned table
+pub fn create_lease_table(script_uri: &str, logical_table_name: &str) -> AppResult<String> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.create_lease_table(script_uri, logical_table_name)
+            .await
+    })
+}
+
+/// Add a unique index on one or more columns of a script-owned table
+pub fn add_unique_index(
+    script_uri: &str,
+    logical_table_name: &str,
+    columns: &[String],
+) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.add_unique_index(script_uri, logical_table_name, columns)
+            .await
+    })
+}
+
+/// Run a parameterized SQL statement in the script's private schema
+pub fn run_script_query(
+    script_uri: &str,
+    sql: &str,
+    params: &[serde_json::Value],
+    options: &ScriptQueryOptions,
+) -> AppResult<ScriptQueryResult> {
+    let repo = get_repository();
+    let tenant = crate::tenancy::transaction_tenant();
+    run_blocking(async {
+        repo.run_script_query(script_uri, sql, params, options, tenant.as_deref())
+            .await
+    })
+}
+
+/// Apply a script's pending `db.migrate` migrations to its private schema
+pub fn migrate_script_schema(
+    script_uri: &str,
+    migrations: &[ScriptMigration],
+) -> AppResult<ScriptMigrationSummary> {
+    let repo = get_repository();
+    let tenant = crate::tenancy::transaction_tenant();
+    run_blocking(async {
+        repo.migrate_script_schema(script_uri, migrations, tenant.as_deref())
+            .await
+    })
+}
+
+/// Store documents in the script's vector collection
+pub fn upsert_vectors(
+    script_uri: &str,
+    collection: &str,
+    documents: &[VectorDocument],
+) -> AppResult<u64> {
+    let repo = get_repository();
+    let tenant = crate::tenancy::transaction_tenant();
+    run_blocking(async {
+        repo.upsert_vectors(script_uri, collection, documents, tenant.as_deref())
+            .await
+    })
+}
+
+/// Find a page of the documents of the script's vector collection closest
+/// to a vector
+pub fn search_vectors(
+    script_uri: &str,
+    collection: &str,
+    embedding: &[f32],
+    options: &VectorSearchOptions,
+) -> AppResult<VectorSearchPage> {
+    let repo = get_repository();
+    let tenant = crate::tenancy::transaction_tenant();
+    run_blocking(async {
+        repo.search_vectors(
+            script_uri,
+            collection,
+            embedding,
+            options,
+            tenant.as_deref(),
+        )
+        .await
+    })
+}
+
+/// Delete documents from the script's vector collection
+pub fn delete_vectors(script_uri: &str, collection: &str, ids: &[String]) -> AppResult<u64> {
+    let repo = get_repository();
+    let tenant = crate::tenancy::transaction_tenant();
+    run_blocking(async {
+        repo.delete_vectors(script_uri, collection, ids, tenant.as_deref())
+            .await
+    })
+}
+
+/// Drop the script's vector collection
+pub fn drop_vector_collection(script_uri: &str, collection: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.drop_vector_collection(script_uri, collection).await })
+}
+
+/// Helper function to get static assets embedded at compile time
+fn get_static_assets() -> HashMap<String, Asset> {
+    let mut m = HashMap::new();
+    let now = std::time::SystemTime::now();
+
+    // Logo asset
+    let logo_content = include_bytes!("../assets/logo.svg").to_vec();
+    let logo = Asset {
+        uri: "logo.svg".to_string(),
+        name: Some("Logo".to_string()),
+        mimetype: "image/svg+xml".to_string(),
+        content: logo_content,
+        created_at: now,
+        updated_at: now,
+        script_uri: "https://example.com/core".to_string(),
+        headers: HashMap::new(),
+    };
+    m.insert("logo.svg".to_string(), logo);
+
+    let engine_css_content = include_bytes!("../assets/engine.css").to_vec();
+    let engine_css = Asset {
+        uri: "engine.css".to_string(),
+        name: Some("Engine Styles".to_string()),
+        mimetype: "text/css".to_string(),
+        content: engine_css_content,
+        created_at: now,
+        updated_at: now,
+        script_uri: "https://example.com/core".to_string(),
+        headers: HashMap::new(),
+    };
+    m.insert("engine.css".to_string(), engine_css);
+
+    let favicon_content = include_bytes!("../assets/favicon.ico").to_vec();
+    let favicon = Asset {
+        uri: "favicon.ico".to_string(),
+        name: Some("Favicon".to_string()),
+        mimetype: "image/x-icon".to_string(),
+        content: favicon_content,
+        created_at: now,
+        updated_at: now,
+        script_uri: "https://example.com/core".to_string(),
+        headers: HashMap::new(),
+    };
+    m.insert("favicon.ico".to_string(), favicon);
+
+    let aiwebengine_dts_content = include_bytes!("../assets/aiwebengine.d.ts").to_vec();
+    let aiwebengine_dts = Asset {
+        uri: "aiwebengine.d.ts".to_string(),
+        name: Some("TypeScript Type Definitions".to_string()),
+        mimetype: "text/plain".to_string(),
+        content: aiwebengine_dts_content,
+        created_at: now,
+        updated_at: now,
+        script_uri: "https://example.com/core".to_string(),
+        headers: HashMap::new(),
+    };
+    m.insert("aiwebengine.d.ts".to_string(), aiwebengine_dts);
+
+    let aiwebengine_priv_dts_content = include_bytes!("../assets/aiwebengine-priv.d.ts").to_vec();
+    let aiwebengine_priv_dts = Asset {
+        uri: "aiwebengine-priv.d.ts".to_string(),
+        name: Some("TypeScript Private Type Definitions".to_string()),
+        mimetype: "text/plain".to_string(),
+        content: aiwebengine_priv_dts_content,
+        created_at: now,
+        updated_at: now,
+        script_uri: "https://example.com/core".to_string(),
+        headers: HashMap::new(),
+    };
+    m.insert("aiwebengine-priv.d.ts".to_string(), aiwebengine_priv_dts);
+
+    m
+}
+
+/// Helper function to get static scripts embedded at compile time
+fn get_static_scripts() -> HashMap<String, String> {
+    let mut m = HashMap::new();
+
+    let core = include_str!("../scripts/feature_scripts/core.js");
+    let cli = include_str!("../scripts/feature_scripts/cli.js");
+    let admin = include_str!("../scripts/feature_scripts/admin.js");
+    let auth = include_str!("../scripts/feature_scripts/auth.js");
+
+    m.insert("https://example.com/core".to_string(), core.to_string());
+    m.insert("https://example.com/cli".to_string(), cli.to_string());
+    m.insert("https://example.com/admin".to_string(), admin.to_string());
+    m.insert("https://example.com/auth".to_string(), auth.to_string());
+
+    // Include test scripts when appropriate
+    let include_test_scripts =
+        std::env::var("AIWEBENGINE_INCLUDE_TEST_SCRIPTS").is_ok() || cfg!(test);
+
+    if include_test_scripts {
+        let graphql_test = include_str!("../scripts/test_scripts/graphql_test.js");
+        m.insert(
+            "https://example.com/graphql_test".to_string(),
+            graphql_test.to_string(),
+        );
+        let dispatcher_test = include_str!("../scripts/test_scripts/dispatcher_test.js");
+        m.insert(
+            "https://example.com/dispatcher_test".to_string(),
+            dispatcher_test.to_string(),
+        );
+    }
+
+    m
+}
+
+/// Fetch assets with error handling (static + dynamic)
+pub fn fetch_assets(script_uri: &str) -> HashMap<String, Asset> {
+    let repo = get_repository();
+
+    let result = run_blocking(async { repo.list_assets(script_uri).await });
+
+    match result {
+        Ok(assets) => {
+            debug!(
+                "Loaded {} assets from repository for script {}",
+                assets.len(),
+                script_uri
+            );
+            assets
+        }
+        Err(e) => {
+            error!("Failed to fetch assets: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Fetch single asset by URI with error handling (dynamic first, then static)
+pub fn fetch_asset(script_uri: &str, uri: &str) -> Option<Asset> {
+    run_blocking(fetch_asset_async(script_uri, uri))
+}
+
+/// Async variant of [`fetch_asset`] for callers already in async context
+pub async fn fetch_asset_async(script_uri: &str, uri: &str) -> Option<Asset> {
+    let repo = get_repository();
+
+    // Try repository first (DB or Memory)
+    let result = repo.get_asset(script_uri, uri).await;
+
+    match result {
+        Ok(Some(asset)) => {
+            debug!("Loaded asset from repository: {}", uri);
+            return Some(asset);
+        }
+        Ok(None) => {
+            // Not in repository, check static assets if script_uri is core
+        }
+        Err(e) => {
+            warn!("Repository asset fetch failed for {}: {}", uri, e);
+            // Fall through to static assets
+        }
+    }
+
+    // Check static assets if script_uri is core
+    if script_uri == "https://example.com/core"
+        && let Some(asset) = get_static_assets().get(uri)
+    {
+        return Some(asset.clone());
+    }
+
+    None
+}
+
+/// A stored compressed variant of an asset, see [`Repository::get_asset_encoding`]
+pub async fn fetch_asset_encoding_async(
+    script_uri: &str,
+    uri: &str,
+    encoding: crate::asset_compression::Encoding,
+) -> Option<Vec<u8>> {
+    let repo = get_repository();
+    match repo.get_asset_encoding(script_uri, uri, encoding).await {
+        Ok(variant) => variant,
+        Err(e) => {
+            warn!("Repository asset variant fetch failed for {}: {}", uri, e);
+            None
+        }
+    }
+}
+
+/// Upsert asset with validation and error handling
+pub fn upsert_asset(asset: Asset) -> AppResult<()> {
+    run_blocking(upsert_asset_async(asset))
+}
+
+/// Async variant of [`upsert_asset`] for callers already in async context
+pub async fn upsert_asset_async(asset: Asset) -> AppResult<()> {
+    if asset.uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Asset URI cannot be empty".to_string()).into());
+    }
+
+    if asset.content.len() > 10_000_000 {
+        // 10MB limit for assets
+        return Err(
+            RepositoryError::InvalidData("Asset content too large (>10MB)".to_string()).into(),
+        );
+    }
+
+    if asset.mimetype.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("MIME type cannot be empty".to_string()).into());
+    }
+
+    crate::asset_registry::validate_asset_headers(&asset.headers)
+        .map_err(RepositoryError::InvalidData)?;
+
+    ensure_storage_quota(
+        &asset.script_uri,
+        None,
+        Some(&asset.uri),
+        asset.content.len() as u64,
+    )
+    .await?;
+
+    let repo = get_repository();
+    repo.upsert_asset(asset).await
+}
+
+/// Delete asset with error handling  
+pub fn delete_asset(script_uri: &str, uri: &str) -> bool {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.delete_asset(script_uri, uri).await });
+
+    match result {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!("Failed to delete asset {}: {}", uri, e);
+            false
+        }
+    }
+}
+
+/// Get repository statistics for monitoring
+pub fn get_repository_stats() -> HashMap<String, usize> {
+    let mut stats = HashMap::new();
+
+    // Count scripts
+    match safe_lock_scripts() {
+        Ok(guard) => {
+            stats.insert("dynamic_scripts".to_string(), guard.len());
+        }
+        Err(_) => {
+            stats.insert("dynamic_scripts".to_string(), 0);
+        }
+    }
+
+    // Count assets
+    let asset_count = if let Some(db) = get_db_pool() {
+        run_blocking(async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM assets")
+                .fetch_one(db.pool())
+                .await
+                .unwrap_or(0) as usize
+        })
+    } else {
+        0
+    };
+    stats.insert("assets".to_string(), asset_count);
+
+    // Count total log entries
+    let log_count = if let Some(db) = get_db_pool() {
+        run_blocking(async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM logs")
+                .fetch_one(db.pool())
+                .await
+                .unwrap_or(0) as usize
+        })
+    } else {
+        0
+    };
+    stats.insert("log_entries".to_string(), log_count);
+
+    // Count shared storage entries
+    let script_properties_count = if let Some(db) = get_db_pool() {
+        run_blocking(async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM script_properties")
+                .fetch_one(db.pool())
+                .await
+                .unwrap_or(0) as usize
+        })
+    } else {
+        0
+    };
+    stats.insert(
+        "script_properties_entries".to_string(),
+        script_properties_count,
+    );
+
+    stats
+}
+
+/// Set a shared storage item (key-value pair for a specific script)
+pub fn set_script_properties_item(script_uri: &str, key: &str, value: &str) -> AppResult<()> {
+    set_script_properties_item_with_ttl(script_uri, key, value, None)
+}
+
+/// Set a shared storage item that expires after `ttl`. Without a TTL the item
+/// never expires; setting an item replaces any previous expiry.
+pub fn set_script_properties_item_with_ttl(
+    script_uri: &str,
+    key: &str,
+    value: &str,
+    ttl: Option<Duration>,
+) -> AppResult<()> {
+    if script_uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
+    }
+
+    if key.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    }
+
+    if value.len() > 1_000_000 {
+        // 1MB limit per value
+        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    }
+
+    let expires_at = ttl.map(expiry_after).transpose()?;
+    let repo = get_repository();
+    run_blocking(async {
+        ensure_storage_quota(
+            script_uri,
+            Some(key),
+            None,
+            (key.len() + value.len()) as u64,
+        )
+        .await?;
+        ensure_tenant_storage_quota(script_uri, key, (key.len() + value.len()) as u64).await?;
+        repo.set_script_properties(script_uri, key, value, expires_at)
+            .await
+    })
+}
+
+/// Extend (or shorten) the TTL of a live shared storage item. Returns false
+/// when the item does not exist or has already expired.
+pub fn touch_script_properties_item(script_uri: &str, key: &str, ttl: Duration) -> AppResult<bool> {
+    let expires_at = expiry_after(ttl)?;
+    let repo = get_repository();
+    run_blocking(async {
+        repo.touch_script_properties(script_uri, key, expires_at)
+            .await
+    })
+}
+
+/// Delete expired shared storage items of all scripts, returning how many were removed
+pub fn purge_expired_script_properties() -> AppResult<u64> {
+    let repo = get_repository();
+    run_blocking(async { repo.purge_expired_script_properties().await })
+}
+
+/// Save a prompt template as its next version, unless it matches the latest
+pub fn save_prompt_template(
+    script_uri: &str,
+    name: &str,
+    definition: &crate::prompts::PromptDefinition,
+) -> AppResult<crate::prompts::PromptTemplate> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.save_prompt_template(script_uri, name, definition)
+            .await
+    })
+}
+
+/// Get a prompt template at `version`, or its latest version
+pub fn get_prompt_template(
+    name: &str,
+    version: Option<i32>,
+) -> AppResult<Option<crate::prompts::PromptTemplate>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_prompt_template(name, version).await })
+}
+
+/// List the latest version of every prompt template
+pub fn list_prompt_templates() -> AppResult<Vec<crate::prompts::PromptTemplate>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_prompt_templates().await })
+}
+
+/// Add one LLM call's usage to its monthly totals
+pub fn record_llm_usage(
+    month: chrono::NaiveDate,
+    entry: &crate::llm_usage::LlmUsageEntry,
+) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.record_llm_usage(month, entry).await })
+}
+
+/// Month-to-date LLM cost of a script and of a user
+pub fn get_llm_usage_cost(
+    month: chrono::NaiveDate,
+    script_uri: &str,
+    user_id: Option<&str>,
+) -> AppResult<(f64, f64)> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_llm_usage_cost(month, script_uri, user_id).await })
+}
+
+/// A month's LLM usage, optionally of one script or user
+pub fn list_llm_usage(
+    month: chrono::NaiveDate,
+    script_uri: Option<&str>,
+    user_id: Option<&str>,
+) -> AppResult<Vec<crate::llm_usage::LlmUsageEntry>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_llm_usage(month, script_uri, user_id).await })
+}
+
+/// A script's route usage since a day
+pub fn list_route_usage(
+    script_uri: &str,
+    since: chrono::NaiveDate,
+) -> AppResult<Vec<crate::route_usage::RouteUsageEntry>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_route_usage(script_uri, since).await })
+}
+
+/// Resource usage of one script or all since a day
+pub fn list_script_resources(
+    script_uri: Option<&str>,
+    since: chrono::NaiveDate,
+) -> AppResult<Vec<crate::script_resources::ScriptResourceEntry>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_script_resources(script_uri, since).await })
+}
+
+/// Threat actions, newest first
+pub fn list_threat_actions(
+    status: Option<crate::security::threat_response::ThreatActionStatus>,
+    limit: i64,
+) -> AppResult<Vec<crate::security::threat_response::ThreatAction>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_threat_actions(status, limit).await })
+}
+
+/// Move a threat action from status `from` to `to`
+pub fn decide_threat_action(
+    id: uuid::Uuid,
+    from: crate::security::threat_response::ThreatActionStatus,
+    to: crate::security::threat_response::ThreatActionStatus,
+    decided_by: &str,
+) -> AppResult<Option<crate::security::threat_response::ThreatAction>> {
+    let repo = get_repository();
+    run_blocking(async { repo.decide_threat_action(id, from, to, decided_by).await })
+}
+
+/// Capability manifest of a script, None if the script doesn't exist
+pub fn get_script_manifest(
+    uri: &str,
+) -> AppResult<Option<crate::security::script_manifests::ScriptManifest>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_script_manifest(uri).await })
+}
+
+/// Store the capability manifest of a script; false if the script doesn't
+/// exist
+pub fn update_script_manifest(
+    manifest: &crate::security::script_manifests::ScriptManifest,
+) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.update_script_manifest(manifest).await })
+}
+
+/// Capability manifests with requested capabilities waiting for approval
+pub fn list_pending_script_manifests()
+-> AppResult<Vec<crate::security::script_manifests::ScriptManifest>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_pending_script_manifests().await })
+}
+
+/// Queue a webhook delivery, inside the handler's transaction if any
+pub fn enqueue_webhook(
+    webhook: &crate::webhooks::NewWebhook,
+) -> AppResult<crate::webhooks::WebhookDelivery> {
+    let repo = get_repository();
+    run_blocking(async { repo.enqueue_webhook(webhook).await })
+}
+
+/// Get a webhook delivery
+pub fn get_webhook_delivery(id: uuid::Uuid) -> AppResult<Option<crate::webhooks::WebhookDelivery>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_webhook_delivery(id).await })
+}
+
+/// List webhook deliveries, newest first
+pub fn list_webhook_deliveries(
+    status: Option<crate::webhooks::DeliveryStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::webhooks::WebhookDelivery>> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.list_webhook_deliveries(status, script_uri, limit)
+            .await
+    })
+}
+
+/// Queue a task, inside the handler's transaction if any
+pub fn enqueue_task(task: &crate::tasks::NewTask) -> AppResult<crate::tasks::EnqueuedTask> {
+    let repo = get_repository();
+    run_blocking(async { repo.enqueue_task(task).await })
+}
+
+/// Get a task
+pub fn get_task(id: uuid::Uuid) -> AppResult<Option<crate::tasks::ScriptTask>> {
+    let repo = get_repository();
+    run_blocking(async { repo.get_task(id).await })
+}
+
+/// List tasks, newest first
+pub fn list_tasks(
+    status: Option<crate::tasks::TaskStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::tasks::ScriptTask>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_tasks(status, script_uri, limit).await })
+}
+
+/// Queue depth of every script with tasks
+pub fn task_queue_stats() -> AppResult<Vec<crate::tasks::TaskQueueStats>> {
+    let repo = get_repository();
+    run_blocking(async { repo.task_queue_stats().await })
+}
+
+/// Record a sent or failed email
+pub fn insert_email_log(entry: &crate::email::EmailLogEntry) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.insert_email_log(entry).await })
+}
+
+/// Emails a script sent or tried to send since `since`
+pub fn count_emails_since(script_uri: &str, since: DateTime<Utc>) -> AppResult<i64> {
+    let repo = get_repository();
+    run_blocking(async { repo.count_emails_since(script_uri, since).await })
+}
+
+/// Store a push subscription, replacing one with the same endpoint
+pub fn upsert_push_subscription(
+    subscription: &crate::notify::NewPushSubscription,
+) -> AppResult<crate::notify::PushSubscription> {
+    let repo = get_repository();
+    run_blocking(async { repo.upsert_push_subscription(subscription).await })
+}
+
+/// A user's push subscriptions in a script, other than the one for `endpoint`
+pub fn count_push_subscriptions(script_uri: &str, user_id: &str, endpoint: &str) -> AppResult<i64> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.count_push_subscriptions(script_uri, user_id, endpoint)
+            .await
+    })
+}
+
+/// A script's push subscriptions of a user or with an ID
+pub fn list_push_subscriptions(
+    script_uri: &str,
+    user_id: Option<&str>,
+    id: Option<uuid::Uuid>,
+) -> AppResult<Vec<crate::notify::PushSubscription>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_push_subscriptions(script_uri, user_id, id).await })
+}
+
+/// Remove a script's push subscription by ID or endpoint
+pub fn delete_push_subscription(script_uri: &str, key: &str) -> AppResult<bool> {
+    let repo = get_repository();
+    run_blocking(async { repo.delete_push_subscription(script_uri, key).await })
+}
+
+/// Email log entries, newest first
+pub fn list_email_log(
+    status: Option<crate::email::EmailStatus>,
+    script_uri: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<crate::email::EmailLogEntry>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_email_log(status, script_uri, limit).await })
+}
+
+/// Interval between background sweeps of expired shared storage items
+const PROPERTY_EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+static PROPERTY_EXPIRY_WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Start the background task that deletes expired shared storage items.
+/// Reads already treat expired items as missing; the sweep reclaims the rows
+/// of keys that are never read again. Only the first call starts a worker.
+pub fn spawn_property_expiry_worker() {
+    if PROPERTY_EXPIRY_WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(PROPERTY_EXPIRY_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match get_repository().purge_expired_script_properties().await {
+                Ok(0) => {}
+                Ok(count) => debug!("Removed {} expired shared storage items", count),
+                Err(e) => warn!("Failed to purge expired shared storage items: {}", e),
+            }
+        }
+    });
+}
+
+fn expiry_after(ttl: Duration) -> AppResult<DateTime<Utc>> {
+    chrono::Duration::from_std(ttl)
+        .ok()
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| RepositoryError::InvalidData("TTL is out of range".to_string()).into())
+}
+
+/// Get a shared storage item
+pub fn get_script_properties_item(script_uri: &str, key: &str) -> Option<String> {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.get_script_properties(script_uri, key).await });
+
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!(
+                "Failed to get shared storage item {}:{}: {}",
+                script_uri, key, e
+            );
+            None
+        }
+    }
+}
+
+/// Remove a shared storage item
+pub fn remove_script_properties_item(script_uri: &str, key: &str) -> bool {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.remove_script_properties(script_uri, key).await });
+
+    match result {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!(
+                "Failed to remove shared storage item {}:{}: {}",
+                script_uri, key, e
+            );
+            false
+        }
+    }
+}
+
+/// Clear all shared storage items for a specific script
+pub fn clear_script_properties(script_uri: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.clear_script_properties(script_uri).await })
+}
+
+/// Clear the shared storage items of a script whose key starts with `prefix`
+/// (one tenant's items when tenancy is enabled)
+pub fn clear_script_properties_prefix(script_uri: &str, prefix: &str) -> AppResult<u64> {
+    let repo = get_repository();
+    run_blocking(async {
+        repo.clear_script_properties_prefix(script_uri, prefix)
+            .await
+    })
+}
+
+/// Set a personal storage item (key-value pair for a specific script and user)
+pub fn set_user_properties_item(
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+    value: &str,
+) -> AppResult<()> {
+    if script_uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
+    }
+
+    if user_id.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("User ID cannot be empty".to_string()).into());
+    }
+
+    if key.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    }
+
+    if value.len() > 1_000_000 {
+        // 1MB limit per value
+        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    }
+
+    let repo = get_repository();
+    run_blocking(async {
+        repo.set_user_properties(script_uri, user_id, key, value)
+            .await
+    })
+}
+
+/// Get a personal storage item
+pub fn get_user_properties_item(script_uri: &str, user_id: &str, key: &str) -> Option<String> {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.get_user_properties(script_uri, user_id, key).await });
+
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!(
+                "Failed to get personal storage item {}:{}:{}: {}",
+                script_uri, user_id, key, e
+            );
+            None
+        }
+    }
+}
+
+/// Remove a personal storage item
+pub fn remove_user_properties_item(script_uri: &str, user_id: &str, key: &str) -> bool {
+    let repo = get_repository();
+    let result =
+        run_blocking(async { repo.remove_user_properties(script_uri, user_id, key).await });
+
+    match result {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!(
+                "Failed to remove personal storage item {}:{}:{}: {}",
+                script_uri, user_id, key, e
+            );
+            false
+        }
+    }
+}
+
+/// Clear all personal storage items for a specific script and user
+pub fn clear_user_properties(script_uri: &str, user_id: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.clear_user_properties(script_uri, user_id).await })
+}
+
+/// Set a script secret (key-value pair for a specific script)
+pub fn set_script_secret_item(script_uri: &str, key: &str, value: &str) -> AppResult<()> {
+    if script_uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
+    }
+
+    if key.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    }
+
+    if value.len() > 1_000_000 {
+        // 1MB limit per value
+        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    }
+
+    let repo = get_repository();
+    run_blocking(async { repo.set_script_secret(script_uri, key, value).await })
+}
+
+/// Get a script secret
+pub fn get_script_secret_item(script_uri: &str, key: &str) -> Option<String> {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.get_script_secret(script_uri, key).await });
+
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to get script secret {}:{}: {}", script_uri, key, e);
+            None
+        }
+    }
+}
+
+/// Resolve a secret by checking user_secrets first (if user_id is given), then script_secrets.
+/// Never reads environment variables or config files — only the database is consulted.
+/// Returns `None` if the repository is not initialized (e.g. in tests without a DB).
+pub fn resolve_secret_db(script_uri: &str, key: &str, user_id: Option<&str>) -> Option<String> {
+    get_repository_opt()?;
+    if let Some(uid) = user_id
+        && let Some(value) = get_user_secret_item(script_uri, uid, key)
+    {
+        return Some(value);
+    }
+    crate::repository::get_script_secret_item(script_uri, key)
+}
+
+/// Remove a script secret
+pub fn remove_script_secret_item(script_uri: &str, key: &str) -> bool {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.remove_script_secret(script_uri, key).await });
+
+    match result {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!(
+                "Failed to remove script secret {}:{}: {}",
+                script_uri, key, e
+            );
+            false
+        }
+    }
+}
+
+/// Clear all script secrets for a specific script
+pub fn clear_script_secrets(script_uri: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.clear_script_secrets(script_uri).await })
+}
+
+/// List all secret keys for a specific script
+pub fn list_script_secrets(script_uri: &str) -> AppResult<Vec<String>> {
+    let repo = get_repository();
+    run_blocking(async { repo.list_script_secrets(script_uri).await })
+}
+
+/// Set a user secret (key-value pair for a specific script and user)
+pub fn set_user_secret_item(
+    script_uri: &str,
+    user_id: &str,
+    key: &str,
+    value: &str,
+) -> AppResult<()> {
+    if script_uri.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Script URI cannot be empty".to_string()).into());
+    }
+
+    if user_id.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("User ID cannot be empty".to_string()).into());
+    }
+
+    if key.trim().is_empty() {
+        return Err(RepositoryError::InvalidData("Key cannot be empty".to_string()).into());
+    }
+
+    if value.len() > 1_000_000 {
+        // 1MB limit per value
+        return Err(RepositoryError::InvalidData("Value too large (>1MB)".to_string()).into());
+    }
+
+    let repo = get_repository();
+    run_blocking(async { repo.set_user_secret(script_uri, user_id, key, value).await })
+}
+
+/// Get a user secret
+pub fn get_user_secret_item(script_uri: &str, user_id: &str, key: &str) -> Option<String> {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.get_user_secret(script_uri, user_id, key).await });
+
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            error!(
+                "Failed to get user secret {}:{}:{}: {}",
+                script_uri, user_id, key, e
+            );
+            None
+        }
+    }
+}
+
+/// Remove a user secret
+pub fn remove_user_secret_item(script_uri: &str, user_id: &str, key: &str) -> bool {
+    let repo = get_repository();
+    let result = run_blocking(async { repo.remove_user_secret(script_uri, user_id, key).await });
+
+    match result {
+        Ok(existed) => existed,
+        Err(e) => {
+            error!(
+                "Failed to remove user secret {}:{}:{}: {}",
+                script_uri, user_id, key, e
+            );
+            false
+        }
+    }
+}
+
+/// Clear all user secrets for a specific script and user
+pub fn clear_user_secrets(script_uri: &str, user_id: &str) -> AppResult<()> {
+    let repo = get_repository();
+    run_blocking(async { repo.clear_user_secrets(script_uri, user_id).await })
+}
+
+use async_trait::async_trait;
+
+/// Abstract repository interface
+#[async_trait]
+pub trait Repository: Send + Sync {
+    // Script operations
+    async fn get_script(&self, uri: &str) -> AppResult<Option<String>>;
+    async fn list_scripts(&self) -> AppResult<HashMap<String, String>>;
+    async fn upsert_script(&self, uri: &str, content: &str) -> AppResult<()>;
+    async fn delete_script(&self, uri: &str) -> AppResult<bool>;
+    async fn get_script_metadata(&self, uri: &str) -> AppResult<ScriptMetadata>;
+    async fn get_all_script_metadata(&self) -> AppResult<Vec<ScriptMetadata>>;
+    async fn update_script_init_status(
+        &self,
+        uri: &str,
+        initialized: bool,
+        init_error: Option<String>,
+        registrations: Option<RouteRegistrations>,
+    ) -> AppResult<()>;
+
+    // Asset operations
+    async fn get_asset(&self, script_uri: &str, uri: &str) -> AppResult<Option<Asset>>;
+    async fn list_assets(&self, script_uri: &str) -> AppResult<HashMap<String, Asset>>;
+    async fn upsert_asset(&self, asset: Asset) -> AppResult<()>;
+    /// A stored compressed variant of an asset: None when it was not
+    /// computed, empty when it did not pay off
+    async fn get_asset_encoding(
+        &self,
+        script_uri: &str,
+        uri: &str,
+        encoding: crate::asset_compression::Encoding,
+    ) -> AppResult<Option<Vec<u8>>>;
+    async fn delete_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool>;
+
+    // Log operations
+    async fn insert_log(&self, script_uri: &str, message: &str, level: &str) -> AppResult<()>;
+    async fn insert_structured_log(
+        &self,
+        script_uri: &str,
+        message: &str,
+        level: &str,
+        data: Option<&serde_json::Value>,
+        context: Option<&serde_json::Value>,
+    ) -> AppResult<()>;
+    async fn fetch_logs(&self, script_uri: &str) -> AppResult<Vec<LogEntry>>;
+    async fn fetch_all_logs(&self) -> AppResult<Vec<LogEntry>>;
+    async fn query_logs(&self, query: &LogQuery) -> AppResult<LogPage>;
+    async fn clear_logs(&self, script_uri: &str) -> AppResult<()>;
     async fn prune_logs(&self) -> AppResult<()>;
 
-    // Shared storage operations
-    async fn get_script_properties(&self, script_uri: &str, key: &str)
-    -> AppResult<Option<String>>;
-    async fn set_script_properties(
-        &self,
-        script_uri: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()>;
-    async fn remove_script_properties(&self, script_uri: &str, key: &str) -> AppResult<bool>;
-    async fn clear_script_properties(&self, script_uri: &str) -> AppResult<()>;
+    // Shared storage operations
+    async fn get_script_properties(&self, script_uri: &str, key: &str)
+    -> AppResult<Option<String>>;
+    async fn set_script_properties(
+        &self,
+        script_uri: &str,
+        key: &str,
+        value: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()>;
+    async fn touch_script_properties(
+        &self,
+        script_uri: &str,
+        key: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<bool>;
+    async fn purge_expired_script_properties(&self) -> AppResult<u64>;
+    async fn remove_script_properties(&self, script_uri: &str, key: &str) -> AppResult<bool>;
+    async fn clear_script_properties(&self, script_uri: &str) -> AppResult<()>;
+    async fn clear_script_properties_prefix(
+        &self,
+        script_uri: &str,
+        prefix: &str,
+    ) -> AppResult<u64>;
+
+    // Personal storage operations
+    async fn get_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<Option<String>>;
+    async fn set_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+        value: &str,
+    ) -> AppResult<()>;
+    async fn remove_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<bool>;
+    async fn clear_user_properties(&self, script_uri: &str, user_id: &str) -> AppResult<()>;
+
+    // Script secrets operations
+    async fn get_script_secret(&self, script_uri: &str, key: &str) -> AppResult<Option<String>>;
+    async fn set_script_secret(&self, script_uri: &str, key: &str, value: &str) -> AppResult<()>;
+    async fn remove_script_secret(&self, script_uri: &str, key: &str) -> AppResult<bool>;
+    async fn clear_script_secrets(&self, script_uri: &str) -> AppResult<()>;
+    async fn list_script_secrets(&self, script_uri: &str) -> AppResult<Vec<String>>;
+
+    // User secrets operations
+    async fn get_user_secret(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<Option<String>>;
+    async fn set_user_secret(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+        value: &str,
+    ) -> AppResult<()>;
+    async fn remove_user_secret(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<bool>;
+    async fn clear_user_secrets(&self, script_uri: &str, user_id: &str) -> AppResult<()>;
+
+    // Security operations
+    async fn get_script_privileged(&self, uri: &str) -> AppResult<Option<bool>>;
+    async fn set_script_privileged(&self, uri: &str, privileged: bool) -> AppResult<()>;
+    async fn get_script_system(&self, uri: &str) -> AppResult<Option<bool>>;
+    async fn set_script_system(&self, uri: &str, system: bool) -> AppResult<bool>;
+
+    // Ownership operations
+    async fn add_script_owner(&self, uri: &str, user_id: &str) -> AppResult<()>;
+    async fn remove_script_owner(&self, uri: &str, user_id: &str) -> AppResult<bool>;
+    async fn get_script_owners(&self, uri: &str) -> AppResult<Vec<String>>;
+    async fn user_owns_script(&self, uri: &str, user_id: &str) -> AppResult<bool>;
+    async fn count_script_owners(&self, uri: &str) -> AppResult<i64>;
+
+    // Collaborator operations
+    async fn add_script_collaborator(&self, uri: &str, user_id: &str) -> AppResult<()>;
+    async fn remove_script_collaborator(&self, uri: &str, user_id: &str) -> AppResult<bool>;
+    async fn get_script_collaborators(&self, uri: &str) -> AppResult<Vec<String>>;
+    async fn user_collaborates_on_script(&self, uri: &str, user_id: &str) -> AppResult<bool>;
+
+    // Trash operations
+    async fn restore_script(&self, uri: &str) -> AppResult<bool>;
+    async fn restore_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool>;
+    async fn list_trashed_scripts(&self) -> AppResult<Vec<TrashedScript>>;
+    async fn list_trashed_assets(&self, script_uri: &str) -> AppResult<Vec<TrashedAsset>>;
+    async fn purge_trash(&self, cutoff: DateTime<Utc>) -> AppResult<TrashPurgeSummary>;
+
+    // Staging operations
+    async fn get_script_staging(&self, uri: &str) -> AppResult<Option<ScriptStaging>>;
+    async fn stage_script(&self, staging: &ScriptStaging) -> AppResult<bool>;
+    async fn discard_staged_script(&self, uri: &str) -> AppResult<bool>;
+    async fn promote_staged_script(&self, uri: &str) -> AppResult<bool>;
+    async fn rollback_script(&self, uri: &str) -> AppResult<bool>;
+
+    // Script label operations
+    async fn get_script_labels(&self, uri: &str) -> AppResult<Option<ScriptLabels>>;
+    async fn set_script_labels(&self, uri: &str, labels: &ScriptLabels) -> AppResult<bool>;
+
+    // Source map operations
+    async fn get_script_source_map(&self, uri: &str) -> AppResult<Option<StoredSourceMap>>;
+    async fn set_script_source_map(
+        &self,
+        uri: &str,
+        source_map: Option<&StoredSourceMap>,
+    ) -> AppResult<bool>;
+
+    // Storage quota operations
+    async fn get_script_storage_usage(
+        &self,
+        script_uri: &str,
+        exclude_key: Option<&str>,
+        exclude_asset_uri: Option<&str>,
+    ) -> AppResult<ScriptStorageUsage>;
+    async fn set_script_storage_quota(
+        &self,
+        uri: &str,
+        quota_bytes: Option<u64>,
+    ) -> AppResult<bool>;
+    async fn set_script_execution_timeout(
+        &self,
+        uri: &str,
+        timeout_ms: Option<u64>,
+    ) -> AppResult<bool>;
+    async fn set_script_memory_limit(&self, uri: &str, limit_bytes: Option<u64>)
+    -> AppResult<bool>;
+    async fn get_tenant_storage_bytes(
+        &self,
+        prefix: &str,
+        exclude: Option<(&str, &str)>,
+    ) -> AppResult<u64>;
+    async fn list_tenant_storage_bytes(&self) -> AppResult<HashMap<String, u64>>;
+    async fn list_script_storage_bytes(&self) -> AppResult<HashMap<String, u64>>;
+
+    // Idempotency keys
+    async fn claim_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> AppResult<IdempotencyClaim>;
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+    ) -> AppResult<()>;
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> AppResult<()>;
+    async fn purge_expired_idempotency_keys(&self) -> AppResult<u64>;
+
+    // Prompt templates
+    async fn save_prompt_template(
+        &self,
+        script_uri: &str,
+        name: &str,
+        definition: &crate::prompts::PromptDefinition,
+    ) -> AppResult<crate::prompts::PromptTemplate>;
+    async fn get_prompt_template(
+        &self,
+        name: &str,
+        version: Option<i32>,
+    ) -> AppResult<Option<crate::prompts::PromptTemplate>>;
+    async fn list_prompt_templates(&self) -> AppResult<Vec<crate::prompts::PromptTemplate>>;
+
+    // LLM usage
+    async fn record_llm_usage(
+        &self,
+        month: chrono::NaiveDate,
+        entry: &crate::llm_usage::LlmUsageEntry,
+    ) -> AppResult<()>;
+    async fn get_llm_usage_cost(
+        &self,
+        month: chrono::NaiveDate,
+        script_uri: &str,
+        user_id: Option<&str>,
+    ) -> AppResult<(f64, f64)>;
+    async fn list_llm_usage(
+        &self,
+        month: chrono::NaiveDate,
+        script_uri: Option<&str>,
+        user_id: Option<&str>,
+    ) -> AppResult<Vec<crate::llm_usage::LlmUsageEntry>>;
+
+    // Route usage
+    async fn record_route_usage(
+        &self,
+        entry: &crate::route_usage::RouteUsageEntry,
+    ) -> AppResult<()>;
+    async fn list_route_usage(
+        &self,
+        script_uri: &str,
+        since: chrono::NaiveDate,
+    ) -> AppResult<Vec<crate::route_usage::RouteUsageEntry>>;
+    async fn purge_route_usage(&self, before: chrono::NaiveDate) -> AppResult<u64>;
+
+    // Script resource usage
+    async fn record_script_resources(
+        &self,
+        entry: &crate::script_resources::ScriptResourceEntry,
+    ) -> AppResult<()>;
+    async fn list_script_resources(
+        &self,
+        script_uri: Option<&str>,
+        since: chrono::NaiveDate,
+    ) -> AppResult<Vec<crate::script_resources::ScriptResourceEntry>>;
+    async fn purge_script_resources(&self, before: chrono::NaiveDate) -> AppResult<u64>;
+
+    // Usage records
+    async fn insert_usage_records(&self, records: &[crate::metering::UsageRecord])
+    -> AppResult<()>;
+    async fn purge_usage_records(&self, before: DateTime<Utc>) -> AppResult<u64>;
+
+    // Threat actions
+    async fn insert_threat_action(
+        &self,
+        action: &crate::security::threat_response::ThreatAction,
+    ) -> AppResult<()>;
+    async fn list_threat_actions(
+        &self,
+        status: Option<crate::security::threat_response::ThreatActionStatus>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::security::threat_response::ThreatAction>>;
+    async fn list_active_threat_actions(
+        &self,
+    ) -> AppResult<Vec<crate::security::threat_response::ThreatAction>>;
+    async fn has_open_threat_action(
+        &self,
+        action: crate::config::ThreatActionKind,
+        subject: &str,
+    ) -> AppResult<bool>;
+    async fn decide_threat_action(
+        &self,
+        id: uuid::Uuid,
+        from: crate::security::threat_response::ThreatActionStatus,
+        to: crate::security::threat_response::ThreatActionStatus,
+        decided_by: &str,
+    ) -> AppResult<Option<crate::security::threat_response::ThreatAction>>;
+
+    // Script capability manifests
+    async fn get_script_manifest(
+        &self,
+        uri: &str,
+    ) -> AppResult<Option<crate::security::script_manifests::ScriptManifest>>;
+    async fn update_script_manifest(
+        &self,
+        manifest: &crate::security::script_manifests::ScriptManifest,
+    ) -> AppResult<bool>;
+    async fn list_pending_script_manifests(
+        &self,
+    ) -> AppResult<Vec<crate::security::script_manifests::ScriptManifest>>;
+
+    // Webhook deliveries
+    async fn enqueue_webhook(
+        &self,
+        webhook: &crate::webhooks::NewWebhook,
+    ) -> AppResult<crate::webhooks::WebhookDelivery>;
+    async fn claim_webhooks(
+        &self,
+        limit: i64,
+        lock_for: Duration,
+    ) -> AppResult<Vec<crate::webhooks::ClaimedWebhook>>;
+    async fn finish_webhook_attempt(
+        &self,
+        id: uuid::Uuid,
+        outcome: &crate::webhooks::AttemptOutcome,
+    ) -> AppResult<()>;
+    async fn get_webhook_delivery(
+        &self,
+        id: uuid::Uuid,
+    ) -> AppResult<Option<crate::webhooks::WebhookDelivery>>;
+    async fn list_webhook_deliveries(
+        &self,
+        status: Option<crate::webhooks::DeliveryStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::webhooks::WebhookDelivery>>;
+    async fn purge_webhook_deliveries(&self, before: DateTime<Utc>) -> AppResult<u64>;
+
+    // Script tasks
+    async fn enqueue_task(
+        &self,
+        task: &crate::tasks::NewTask,
+    ) -> AppResult<crate::tasks::EnqueuedTask>;
+    async fn claim_tasks(
+        &self,
+        limit: i64,
+        visibility: Duration,
+    ) -> AppResult<Vec<crate::tasks::ClaimedTask>>;
+    async fn finish_task_attempt(
+        &self,
+        id: uuid::Uuid,
+        outcome: &crate::tasks::TaskOutcome,
+    ) -> AppResult<()>;
+    async fn get_task(&self, id: uuid::Uuid) -> AppResult<Option<crate::tasks::ScriptTask>>;
+    async fn list_tasks(
+        &self,
+        status: Option<crate::tasks::TaskStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::tasks::ScriptTask>>;
+    async fn task_queue_stats(&self) -> AppResult<Vec<crate::tasks::TaskQueueStats>>;
+    async fn purge_tasks(&self, before: DateTime<Utc>) -> AppResult<u64>;
+
+    // Email log
+    async fn insert_email_log(&self, entry: &crate::email::EmailLogEntry) -> AppResult<()>;
+    async fn count_emails_since(&self, script_uri: &str, since: DateTime<Utc>) -> AppResult<i64>;
+    async fn list_email_log(
+        &self,
+        status: Option<crate::email::EmailStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::email::EmailLogEntry>>;
+    async fn purge_email_log(&self, before: DateTime<Utc>) -> AppResult<u64>;
+
+    // Push subscriptions
+    async fn upsert_push_subscription(
+        &self,
+        subscription: &crate::notify::NewPushSubscription,
+    ) -> AppResult<crate::notify::PushSubscription>;
+    async fn count_push_subscriptions(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        endpoint: &str,
+    ) -> AppResult<i64>;
+    async fn list_push_subscriptions(
+        &self,
+        script_uri: &str,
+        user_id: Option<&str>,
+        id: Option<uuid::Uuid>,
+    ) -> AppResult<Vec<crate::notify::PushSubscription>>;
+    async fn delete_push_subscription(&self, script_uri: &str, key: &str) -> AppResult<bool>;
+
+    // Script database schema operations
+    async fn create_script_table(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+    ) -> AppResult<String>;
+    async fn add_column_to_script_table(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        column_name: &str,
+        column_type: ColumnType,
+        nullable: bool,
+        default_value: Option<&str>,
+    ) -> AppResult<()>;
+    async fn add_reference_column(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        column_name: &str,
+        referenced_logical_table_name: &str,
+        nullable: bool,
+    ) -> AppResult<()>;
+    async fn drop_column(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        column_name: &str,
+    ) -> AppResult<bool>;
+    async fn drop_script_table(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+    ) -> AppResult<bool>;
+
+    // Script database introspection operations
+    async fn list_script_tables(&self, script_uri: &str) -> AppResult<Vec<TableInfo>>;
+    async fn get_table_schema(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+    ) -> AppResult<TableSchema>;
+    async fn get_foreign_keys(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+    ) -> AppResult<Vec<ForeignKeyInfo>>;
+
+    // Script database data operations
+    async fn query_table(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        filters: Option<&HashMap<String, serde_json::Value>>,
+        limit: Option<i64>,
+        order_by: Option<&str>,
+        order_dir: Option<&str>,
+    ) -> AppResult<Vec<serde_json::Value>>;
+    async fn insert_row(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        data: &HashMap<String, serde_json::Value>,
+    ) -> AppResult<serde_json::Value>;
+    async fn update_row(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        id: i32,
+        data: &HashMap<String, serde_json::Value>,
+    ) -> AppResult<serde_json::Value>;
+    async fn delete_row(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        id: i32,
+    ) -> AppResult<bool>;
+    async fn upsert_row(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        key_columns: &[String],
+        data: &HashMap<String, serde_json::Value>,
+    ) -> AppResult<serde_json::Value>;
+    async fn delete_where(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        filters: &HashMap<String, serde_json::Value>,
+    ) -> AppResult<u64>;
+    async fn acquire_lease(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        lease_id: &str,
+        owner: &str,
+        ttl_ms: i64,
+    ) -> AppResult<serde_json::Value>;
+    async fn create_lease_table(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+    ) -> AppResult<String>;
+    async fn add_unique_index(
+        &self,
+        script_uri: &str,
+        logical_table_name: &str,
+        columns: &[String],
+    ) -> AppResult<()>;
+    async fn run_script_query(
+        &self,
+        script_uri: &str,
+        sql: &str,
+        params: &[serde_json::Value],
+        options: &ScriptQueryOptions,
+        tenant: Option<&str>,
+    ) -> AppResult<ScriptQueryResult>;
+    async fn migrate_script_schema(
+        &self,
+        script_uri: &str,
+        migrations: &[ScriptMigration],
+        tenant: Option<&str>,
+    ) -> AppResult<ScriptMigrationSummary>;
+    async fn upsert_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        documents: &[VectorDocument],
+        tenant: Option<&str>,
+    ) -> AppResult<u64>;
+    async fn search_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        embedding: &[f32],
+        options: &VectorSearchOptions,
+        tenant: Option<&str>,
+    ) -> AppResult<VectorSearchPage>;
+    async fn delete_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        ids: &[String],
+        tenant: Option<&str>,
+    ) -> AppResult<u64>;
+    async fn drop_vector_collection(&self, script_uri: &str, collection: &str) -> AppResult<bool>;
+}
+
+/// PostgreSQL implementation of the Repository trait
+pub struct PostgresRepository {
+    pool: PgPool,
+    server_id: String,
+}
+
+impl PostgresRepository {
+    pub fn new(pool: PgPool, server_id: String) -> Self {
+        Self { pool, server_id }
+    }
+
+    /// Swap a script version in and let every instance reload the script
+    async fn swap_script_version(&self, uri: &str, promote: bool) -> AppResult<bool> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        let swapped = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_swap_script_version(tx, uri, promote).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                let mut tx =
+                    crate::database::begin(pool)
+                        .await
+                        .map_err(|e| AppError::Database {
+                            message: format!("Failed to begin transaction: {}", e),
+                            source: None,
+                        })?;
+                let swapped = db_swap_script_version(&mut tx, uri, promote).await?;
+                tx.commit().await.map_err(|e| AppError::Database {
+                    message: format!("Failed to commit transaction: {}", e),
+                    source: None,
+                })?;
+                swapped
+            }
+        };
+
+        if swapped {
+            send_script_notification(&self.pool, uri, "upserted", &self.server_id).await?;
+            if let Ok(mut guard) = safe_lock_scripts() {
+                guard.remove(uri);
+            }
+            crate::route_index::invalidate();
+            crate::bytecode::invalidate(uri);
+            crate::response_cache::purge_script(uri);
+        }
+        Ok(swapped)
+    }
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    async fn get_script(&self, uri: &str) -> AppResult<Option<String>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script(&mut **tx, uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => db_get_script(pool, uri).await,
+        }
+    }
+
+    async fn list_scripts(&self) -> AppResult<HashMap<String, String>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_list_scripts(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => db_list_scripts(pool).await,
+        }
+    }
+
+    async fn upsert_script(&self, uri: &str, content: &str) -> AppResult<()> {
+        // A script created at a trashed URI supersedes the trashed copy; purge
+        // it so its leftover tables cannot collide with the new script's tables
+        let executor = crate::database::get_current_executor(&self.pool);
+        let trashed = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_script_in_trash(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_script_in_trash(pool, uri).await?
+            }
+        };
+        if trashed {
+            db_purge_trash(&self.pool, Utc::now(), Some(uri)).await?;
+        }
+
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_upsert_script(executor, uri, content).await?;
+
+        // Send notification after successful upsert
+        send_script_notification(&self.pool, uri, "upserted", &self.server_id).await?;
+
+        // Invalidate cache
+        if let Ok(mut guard) = safe_lock_scripts() {
+            guard.remove(uri);
+        }
+        crate::route_index::invalidate();
+        crate::bytecode::invalidate(uri);
+        crate::response_cache::purge_script(uri);
+        Ok(())
+    }
+
+    async fn delete_script(&self, uri: &str) -> AppResult<bool> {
+        // Move the script to the trash (within transaction if active). Its
+        // tables are kept so the script can be restored; purge drops them.
+        let executor = crate::database::get_current_executor(&self.pool);
+        let result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_trash_script(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => db_trash_script(pool, uri).await?,
+        };
+
+        if result {
+            // Send notification after successful deletion
+            send_script_notification(&self.pool, uri, "deleted", &self.server_id).await?;
+
+            // Update in-memory cache
+            if let Ok(mut guard) = safe_lock_scripts() {
+                guard.remove(uri);
+            }
+            crate::route_index::invalidate();
+            crate::bytecode::invalidate(uri);
+            crate::response_cache::purge_script(uri);
+        }
+        Ok(result)
+    }
+
+    async fn get_script_metadata(&self, uri: &str) -> AppResult<ScriptMetadata> {
+        // Check cache first
+        if let Ok(guard) = safe_lock_scripts()
+            && let Some(metadata) = guard.get(uri)
+        {
+            return Ok(metadata.clone());
+        }
+
+        // Fetch from DB
+        let content = self
+            .get_script(uri)
+            .await?
+            .ok_or_else(|| RepositoryError::ScriptNotFound(uri.to_string()))?;
+
+        let privileged = self
+            .get_script_privileged(uri)
+            .await?
+            .unwrap_or(default_privileged_for(uri));
+
+        let executor = crate::database::get_current_executor(&self.pool);
+        let owners = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_owners(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_owners(pool, uri).await?
+            }
+        };
+
+        let labels = self.get_script_labels(uri).await?.unwrap_or_default();
+
+        let executor = crate::database::get_current_executor(&self.pool);
+        let schema_version = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_schema_version(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_schema_version(pool, uri).await?
+            }
+        };
+
+        let executor = crate::database::get_current_executor(&self.pool);
+        let execution_timeout_ms = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_execution_timeout(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_execution_timeout(pool, uri).await?
+            }
+        };
+
+        let executor = crate::database::get_current_executor(&self.pool);
+        let memory_limit_bytes = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_memory_limit(&mut **tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_memory_limit(pool, uri).await?
+            }
+        };
+
+        let system = self.get_script_system(uri).await?.unwrap_or(false);
+
+        let mut metadata = ScriptMetadata::new(uri.to_string(), content);
+        metadata.privileged = privileged;
+        metadata.system = system;
+        metadata.owners = owners;
+        metadata.apply_labels(labels);
+        metadata.schema_version = schema_version;
+        metadata.execution_timeout_ms = execution_timeout_ms;
+        metadata.memory_limit_bytes = memory_limit_bytes;
+
+        // Cache it
+        if let Ok(mut guard) = safe_lock_scripts() {
+            guard.insert(uri.to_string(), metadata.clone());
+        }
+
+        Ok(metadata)
+    }
+
+    async fn get_all_script_metadata(&self) -> AppResult<Vec<ScriptMetadata>> {
+        // Fetch scripts from database only (no static scripts for Postgres)
+        let db_scripts = self.list_scripts().await?;
+
+        // Fetch all owners in one query
+        let executor = crate::database::get_current_executor(&self.pool);
+        let all_owners = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_owners(&mut **tx).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_owners(pool).await?
+            }
+        };
+
+        let mut metadata_list = Vec::new();
+
+        // Scope for mutex lock
+        {
+            let mut guard = safe_lock_scripts()?;
+            for (uri, content) in db_scripts {
+                if let Some(cached) = guard.get(&uri) {
+                    // Use cached version to preserve runtime state
+                    metadata_list.push(cached.clone());
+                } else {
+                    // Create new metadata and cache it
+                    let mut metadata = ScriptMetadata::new(uri.clone(), content);
+                    // Set owners from bulk query
+                    if let Some(owners) = all_owners.get(&uri) {
+                        metadata.owners = owners.clone();
+                    }
+                    guard.insert(uri.clone(), metadata.clone());
+                    metadata_list.push(metadata);
+                }
+            }
+        }
+
+        // Resolve privilege flags with one bulk query instead of a per-script
+        // lookup (which previously issued a nested blocking database call per
+        // script). Precedence matches get_script_security_profile: database
+        // value, then local override, then bootstrap default.
+        let executor = crate::database::get_current_executor(&self.pool);
+        let db_privileges_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_privileged(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_privileged(pool).await
+            }
+        };
+        let db_privileges = match db_privileges_result {
+            Ok(privileges) => privileges,
+            Err(e) => {
+                warn!("Failed to bulk-fetch script privileges: {}", e);
+                HashMap::new()
+            }
+        };
+        let overrides = safe_lock_privilege_overrides()
+            .map(|guard| guard.clone())
+            .unwrap_or_default();
+        for metadata in &mut metadata_list {
+            metadata.privileged = db_privileges
+                .get(&metadata.uri)
+                .copied()
+                .or_else(|| overrides.get(&metadata.uri).copied())
+                .unwrap_or_else(|| default_privileged_for(&metadata.uri));
+        }
+
+        // Labels can be edited on another node without the script content
+        // changing, so read them fresh rather than trusting the cache
+        let executor = crate::database::get_current_executor(&self.pool);
+        let db_labels_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_labels(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_labels(pool).await
+            }
+        };
+        match db_labels_result {
+            Ok(mut labels) => {
+                for metadata in &mut metadata_list {
+                    if let Some(script_labels) = labels.remove(&metadata.uri) {
+                        metadata.apply_labels(script_labels);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to bulk-fetch script labels: {}", e),
+        }
+
+        // Migrations may have been applied by another node's init()
+        let executor = crate::database::get_current_executor(&self.pool);
+        let schema_versions_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_schema_versions(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_schema_versions(pool).await
+            }
+        };
+        match schema_versions_result {
+            Ok(versions) => {
+                for metadata in &mut metadata_list {
+                    metadata.schema_version = versions.get(&metadata.uri).copied();
+                }
+            }
+            Err(e) => warn!("Failed to bulk-fetch script schema versions: {}", e),
+        }
+
+        // Timeouts can be changed on another node
+        let executor = crate::database::get_current_executor(&self.pool);
+        let timeouts_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_execution_timeouts(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_execution_timeouts(pool).await
+            }
+        };
+        match timeouts_result {
+            Ok(timeouts) => {
+                for metadata in &mut metadata_list {
+                    metadata.execution_timeout_ms = timeouts.get(&metadata.uri).copied();
+                }
+            }
+            Err(e) => warn!("Failed to bulk-fetch script execution timeouts: {}", e),
+        }
+
+        // Memory limits can be changed on another node; executions read them
+        // from the cache
+        let executor = crate::database::get_current_executor(&self.pool);
+        let memory_limits_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_all_script_memory_limits(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_all_script_memory_limits(pool).await
+            }
+        };
+        match memory_limits_result {
+            Ok(limits) => {
+                let mut cache = safe_lock_scripts().ok();
+                for metadata in &mut metadata_list {
+                    metadata.memory_limit_bytes = limits.get(&metadata.uri).copied();
+                    if let Some(cached) = cache
+                        .as_mut()
+                        .and_then(|guard| guard.get_mut(&metadata.uri))
+                    {
+                        cached.memory_limit_bytes = metadata.memory_limit_bytes;
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to bulk-fetch script memory limits: {}", e),
+        }
+
+        // System flags can be changed on another node
+        let executor = crate::database::get_current_executor(&self.pool);
+        let system_scripts_result = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_system_scripts(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => db_get_system_scripts(pool).await,
+        };
+        match system_scripts_result {
+            Ok(system_scripts) => {
+                for metadata in &mut metadata_list {
+                    metadata.system = system_scripts.contains(&metadata.uri);
+                }
+            }
+            Err(e) => warn!("Failed to bulk-fetch system scripts: {}", e),
+        }
+
+        Ok(metadata_list)
+    }
+
+    async fn update_script_init_status(
+        &self,
+        uri: &str,
+        initialized: bool,
+        init_error: Option<String>,
+        registrations: Option<RouteRegistrations>,
+    ) -> AppResult<()> {
+        let mut guard = safe_lock_scripts()?;
+        if let Some(metadata) = guard.get_mut(uri) {
+            metadata.initialized = initialized;
+            metadata.init_error = init_error;
+            if let Some(regs) = registrations {
+                metadata.registrations = regs;
+            }
+            if initialized {
+                metadata.last_init_time = Some(SystemTime::now());
+            }
+        } else {
+            // If it's a static script, it might not be in dynamic scripts map yet
+            if let Some(content) = get_static_scripts().get(uri) {
+                let mut metadata = ScriptMetadata::new(uri.to_string(), content.clone());
+                metadata.initialized = initialized;
+                metadata.init_error = init_error;
+                if let Some(regs) = registrations {
+                    metadata.registrations = regs;
+                }
+                if initialized {
+                    metadata.last_init_time = Some(SystemTime::now());
+                }
+                guard.insert(uri.to_string(), metadata);
+            } else {
+                return Err(RepositoryError::ScriptNotFound(uri.to_string()).into());
+            }
+        }
+        drop(guard);
+        crate::route_index::invalidate();
+        Ok(())
+    }
+
+    async fn get_asset(&self, script_uri: &str, uri: &str) -> AppResult<Option<Asset>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_asset(&mut **tx, script_uri, uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_asset(pool, script_uri, uri).await
+            }
+        }
+    }
+
+    async fn list_assets(&self, script_uri: &str) -> AppResult<HashMap<String, Asset>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_list_assets(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_list_assets(pool, script_uri).await
+            }
+        }
+    }
 
-    // Personal storage operations
-    async fn get_user_properties(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<Option<String>>;
-    async fn set_user_properties(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()>;
-    async fn remove_user_properties(
+    async fn upsert_asset(&self, asset: Asset) -> AppResult<()> {
+        // Compress before taking the executor; brotli takes a while on
+        // larger assets
+        let (asset, variants) = tokio::task::spawn_blocking(move || {
+            let variants = crate::asset_compression::precompress(&asset.mimetype, &asset.content);
+            (asset, variants)
+        })
+        .await
+        .map_err(|e| AppError::Internal {
+            message: format!("Asset compression failed: {}", e),
+        })?;
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_upsert_asset(executor, &asset, &variants).await?;
+        crate::response_cache::purge_script(&asset.script_uri);
+        crate::asset_cdn::purge_asset(&asset.script_uri, &asset.uri);
+        Ok(())
+    }
+
+    async fn get_asset_encoding(
         &self,
         script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<bool>;
-    async fn clear_user_properties(&self, script_uri: &str, user_id: &str) -> AppResult<()>;
+        uri: &str,
+        encoding: crate::asset_compression::Encoding,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_asset_encoding(&mut **tx, script_uri, uri, encoding).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_asset_encoding(pool, script_uri, uri, encoding).await
+            }
+        }
+    }
 
-    // Script secrets operations
-    async fn get_script_secret(&self, script_uri: &str, key: &str) -> AppResult<Option<String>>;
-    async fn set_script_secret(&self, script_uri: &str, key: &str, value: &str) -> AppResult<()>;
-    async fn remove_script_secret(&self, script_uri: &str, key: &str) -> AppResult<bool>;
-    async fn clear_script_secrets(&self, script_uri: &str) -> AppResult<()>;
-    async fn list_script_secrets(&self, script_uri: &str) -> AppResult<Vec<String>>;
+    async fn delete_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        let deleted = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_trash_asset(&mut **tx, script_uri, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_trash_asset(pool, script_uri, uri).await?
+            }
+        };
+        if deleted {
+            crate::response_cache::purge_script(script_uri);
+            crate::asset_cdn::purge_asset(script_uri, uri);
+        }
+        Ok(deleted)
+    }
 
-    // User secrets operations
-    async fn get_user_secret(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<Option<String>>;
-    async fn set_user_secret(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()>;
-    async fn remove_user_secret(
+    async fn insert_log(&self, script_uri: &str, message: &str, level: &str) -> AppResult<()> {
+        self.insert_structured_log(script_uri, message, level, None, None)
+            .await
+    }
+
+    async fn insert_structured_log(
         &self,
         script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<bool>;
-    async fn clear_user_secrets(&self, script_uri: &str, user_id: &str) -> AppResult<()>;
+        message: &str,
+        level: &str,
+        data: Option<&serde_json::Value>,
+        context: Option<&serde_json::Value>,
+    ) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_insert_log_message(&mut **tx, script_uri, message, level, data, context).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_insert_log_message(pool, script_uri, message, level, data, context).await
+            }
+        }
+    }
 
-    // Security operations
-    async fn get_script_privileged(&self, uri: &str) -> AppResult<Option<bool>>;
-    async fn set_script_privileged(&self, uri: &str, privileged: bool) -> AppResult<()>;
+    async fn fetch_logs(&self, script_uri: &str) -> AppResult<Vec<LogEntry>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_fetch_log_messages(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_fetch_log_messages(pool, script_uri).await
+            }
+        }
+    }
 
-    // Ownership operations
-    async fn add_script_owner(&self, uri: &str, user_id: &str) -> AppResult<()>;
-    async fn remove_script_owner(&self, uri: &str, user_id: &str) -> AppResult<bool>;
-    async fn get_script_owners(&self, uri: &str) -> AppResult<Vec<String>>;
-    async fn user_owns_script(&self, uri: &str, user_id: &str) -> AppResult<bool>;
-    async fn count_script_owners(&self, uri: &str) -> AppResult<i64>;
+    async fn fetch_all_logs(&self) -> AppResult<Vec<LogEntry>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_fetch_all_log_messages(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_fetch_all_log_messages(pool).await
+            }
+        }
+    }
 
-    // Script database schema operations
-    async fn create_script_table(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-    ) -> AppResult<String>;
-    async fn add_column_to_script_table(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        column_name: &str,
-        column_type: ColumnType,
-        nullable: bool,
-        default_value: Option<&str>,
-    ) -> AppResult<()>;
-    async fn add_reference_column(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        column_name: &str,
-        referenced_logical_table_name: &str,
-        nullable: bool,
-    ) -> AppResult<()>;
-    async fn drop_column(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        column_name: &str,
-    ) -> AppResult<bool>;
-    async fn drop_script_table(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-    ) -> AppResult<bool>;
+    async fn query_logs(&self, query: &LogQuery) -> AppResult<LogPage> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_query_log_messages(&mut **tx, query).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_query_log_messages(pool, query).await
+            }
+        }
+    }
 
-    // Script database introspection operations
-    async fn list_script_tables(&self, script_uri: &str) -> AppResult<Vec<TableInfo>>;
-    async fn get_table_schema(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-    ) -> AppResult<TableSchema>;
-    async fn get_foreign_keys(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-    ) -> AppResult<Vec<ForeignKeyInfo>>;
+    async fn clear_logs(&self, script_uri: &str) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_clear_log_messages(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_clear_log_messages(pool, script_uri).await
+            }
+        }
+    }
 
-    // Script database data operations
-    async fn query_table(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        filters: Option<&HashMap<String, serde_json::Value>>,
-        limit: Option<i64>,
-        order_by: Option<&str>,
-        order_dir: Option<&str>,
-    ) -> AppResult<Vec<serde_json::Value>>;
-    async fn insert_row(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        data: &HashMap<String, serde_json::Value>,
-    ) -> AppResult<serde_json::Value>;
-    async fn update_row(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        id: i32,
-        data: &HashMap<String, serde_json::Value>,
-    ) -> AppResult<serde_json::Value>;
-    async fn delete_row(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        id: i32,
-    ) -> AppResult<bool>;
-    async fn upsert_row(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        key_columns: &[String],
-        data: &HashMap<String, serde_json::Value>,
-    ) -> AppResult<serde_json::Value>;
-    async fn delete_where(
-        &self,
-        script_uri: &str,
-        logical_table_name: &str,
-        filters: &HashMap<String, serde_json::Value>,
-    ) -> AppResult<u64>;
-    async fn acquire_lease(
+    async fn prune_logs(&self) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_prune_log_messages(&mut **tx).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => db_prune_log_messages(pool).await,
+        }
+    }
+
+    async fn get_script_properties(
         &self,
         script_uri: &str,
-        logical_table_name: &str,
-        lease_id: &str,
-        owner: &str,
-        ttl_ms: i64,
-    ) -> AppResult<serde_json::Value>;
-    async fn create_lease_table(
+        key: &str,
+    ) -> AppResult<Option<String>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_properties_item(&mut **tx, script_uri, key).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_properties_item(pool, script_uri, key).await
+            }
+        }
+    }
+
+    async fn set_script_properties(
         &self,
         script_uri: &str,
-        logical_table_name: &str,
-    ) -> AppResult<String>;
-    async fn add_unique_index(
+        key: &str,
+        value: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_set_script_properties_item(executor, script_uri, key, value, expires_at).await
+    }
+
+    async fn touch_script_properties(
         &self,
         script_uri: &str,
-        logical_table_name: &str,
-        columns: &[String],
-    ) -> AppResult<()>;
-}
-
-/// PostgreSQL implementation of the Repository trait
-pub struct PostgresRepository {
-    pool: PgPool,
-    server_id: String,
-}
+        key: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<bool> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_touch_script_properties_item(&mut **tx, script_uri, key, expires_at).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_touch_script_properties_item(pool, script_uri, key, expires_at).await
+            }
+        }
+    }
 
-impl PostgresRepository {
-    pub fn new(pool: PgPool, server_id: String) -> Self {
-        Self { pool, server_id }
+    async fn purge_expired_script_properties(&self) -> AppResult<u64> {
+        // Background maintenance, never part of a handler transaction
+        db_purge_expired_script_properties(&self.pool).await
     }
-}
 
-#[async_trait]
-impl Repository for PostgresRepository {
-    async fn get_script(&self, uri: &str) -> AppResult<Option<String>> {
+    async fn remove_script_properties(&self, script_uri: &str, key: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script(&mut **tx, uri).await
+                db_remove_script_properties_item(&mut **tx, script_uri, key).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_remove_script_properties_item(pool, script_uri, key).await
             }
-            crate::database::TransactionExecutor::Pool(pool) => db_get_script(pool, uri).await,
         }
     }
 
-    async fn list_scripts(&self) -> AppResult<HashMap<String, String>> {
+    async fn clear_script_properties(&self, script_uri: &str) -> AppResult<()> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_list_scripts(&mut **tx).await
+                db_clear_script_properties(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_clear_script_properties(pool, script_uri).await
             }
-            crate::database::TransactionExecutor::Pool(pool) => db_list_scripts(pool).await,
         }
     }
 
-    async fn upsert_script(&self, uri: &str, content: &str) -> AppResult<()> {
+    async fn clear_script_properties_prefix(
+        &self,
+        script_uri: &str,
+        prefix: &str,
+    ) -> AppResult<u64> {
         let executor = crate::database::get_current_executor(&self.pool);
-        db_upsert_script(executor, uri, content).await?;
-
-        // Send notification after successful upsert
-        send_script_notification(&self.pool, uri, "upserted", &self.server_id).await?;
-
-        // Invalidate cache
-        if let Ok(mut guard) = safe_lock_scripts() {
-            guard.remove(uri);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_clear_script_properties_prefix(&mut **tx, script_uri, prefix).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_clear_script_properties_prefix(pool, script_uri, prefix).await
+            }
         }
-        crate::route_index::invalidate();
-        crate::bytecode::invalidate(uri);
-        Ok(())
     }
 
-    async fn delete_script(&self, uri: &str) -> AppResult<bool> {
-        // First, drop all script-owned tables (outside transaction)
-        let _ = db_drop_all_script_tables(&self.pool, uri).await;
-
-        // Delete the script (within transaction if active)
+    async fn get_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<Option<String>> {
         let executor = crate::database::get_current_executor(&self.pool);
-        let result = match executor {
+        match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_delete_script(&mut **tx, uri).await?
+                db_get_user_properties_item(&mut **tx, script_uri, user_id, key).await
             }
-            crate::database::TransactionExecutor::Pool(pool) => db_delete_script(pool, uri).await?,
-        };
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_user_properties_item(pool, script_uri, user_id, key).await
+            }
+        }
+    }
 
-        if result {
-            // Send notification after successful deletion
-            send_script_notification(&self.pool, uri, "deleted", &self.server_id).await?;
+    async fn set_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+        value: &str,
+    ) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_set_user_properties_item(executor, script_uri, user_id, key, value).await
+    }
 
-            // Update in-memory cache
-            if let Ok(mut guard) = safe_lock_scripts() {
-                guard.remove(uri);
+    async fn remove_user_properties(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<bool> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_remove_user_properties_item(&mut **tx, script_uri, user_id, key).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_remove_user_properties_item(pool, script_uri, user_id, key).await
             }
-            crate::route_index::invalidate();
-            crate::bytecode::invalidate(uri);
         }
-        Ok(result)
     }
 
-    async fn get_script_metadata(&self, uri: &str) -> AppResult<ScriptMetadata> {
-        // Check cache first
-        if let Ok(guard) = safe_lock_scripts()
-            && let Some(metadata) = guard.get(uri)
-        {
-            return Ok(metadata.clone());
+    async fn clear_user_properties(&self, script_uri: &str, user_id: &str) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_clear_user_properties(&mut **tx, script_uri, user_id).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_clear_user_properties(pool, script_uri, user_id).await
+            }
         }
+    }
 
-        // Fetch from DB
-        let content = self
-            .get_script(uri)
-            .await?
-            .ok_or_else(|| RepositoryError::ScriptNotFound(uri.to_string()))?;
-
-        let privileged = self
-            .get_script_privileged(uri)
-            .await?
-            .unwrap_or(default_privileged_for(uri));
-
+    async fn get_script_secret(&self, script_uri: &str, key: &str) -> AppResult<Option<String>> {
         let executor = crate::database::get_current_executor(&self.pool);
-        let owners = match executor {
+        match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script_owners(&mut **tx, uri).await?
+                db_get_script_secret(&mut **tx, script_uri, key).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_script_owners(pool, uri).await?
+                db_get_script_secret(pool, script_uri, key).await
             }
-        };
-
-        let mut metadata = ScriptMetadata::new(uri.to_string(), content);
-        metadata.privileged = privileged;
-        metadata.owners = owners;
-
-        // Cache it
-        if let Ok(mut guard) = safe_lock_scripts() {
-            guard.insert(uri.to_string(), metadata.clone());
         }
-
-        Ok(metadata)
     }
 
-    async fn get_all_script_metadata(&self) -> AppResult<Vec<ScriptMetadata>> {
-        // Fetch scripts from database only (no static scripts for Postgres)
-        let db_scripts = self.list_scripts().await?;
+    async fn set_script_secret(&self, script_uri: &str, key: &str, value: &str) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_set_script_secret(executor, script_uri, key, value).await
+    }
 
-        // Fetch all owners in one query
+    async fn remove_script_secret(&self, script_uri: &str, key: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
-        let all_owners = match executor {
+        match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_all_script_owners(&mut **tx).await?
+                db_remove_script_secret(&mut **tx, script_uri, key).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_all_script_owners(pool).await?
+                db_remove_script_secret(pool, script_uri, key).await
             }
-        };
+        }
+    }
 
-        let mut metadata_list = Vec::new();
+    async fn clear_script_secrets(&self, script_uri: &str) -> AppResult<()> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_clear_script_secrets(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_clear_script_secrets(pool, script_uri).await
+            }
+        }
+    }
 
-        // Scope for mutex lock
-        {
-            let mut guard = safe_lock_scripts()?;
-            for (uri, content) in db_scripts {
-                if let Some(cached) = guard.get(&uri) {
-                    // Use cached version to preserve runtime state
-                    metadata_list.push(cached.clone());
-                } else {
-                    // Create new metadata and cache it
-                    let mut metadata = ScriptMetadata::new(uri.clone(), content);
-                    // Set owners from bulk query
-                    if let Some(owners) = all_owners.get(&uri) {
-                        metadata.owners = owners.clone();
-                    }
-                    guard.insert(uri.clone(), metadata.clone());
-                    metadata_list.push(metadata);
-                }
+    async fn list_script_secrets(&self, script_uri: &str) -> AppResult<Vec<String>> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_list_script_secrets(&mut **tx, script_uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_list_script_secrets(pool, script_uri).await
             }
         }
+    }
 
-        // Resolve privilege flags with one bulk query instead of a per-script
-        // lookup (which previously issued a nested blocking database call per
-        // script). Precedence matches get_script_security_profile: database
-        // value, then local override, then bootstrap default.
+    async fn get_user_secret(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<Option<String>> {
         let executor = crate::database::get_current_executor(&self.pool);
-        let db_privileges_result = match executor {
+        match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_all_script_privileged(&mut **tx).await
+                db_get_user_secret(&mut **tx, script_uri, user_id, key).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_all_script_privileged(pool).await
-            }
-        };
-        let db_privileges = match db_privileges_result {
-            Ok(privileges) => privileges,
-            Err(e) => {
-                warn!("Failed to bulk-fetch script privileges: {}", e);
-                HashMap::new()
+                db_get_user_secret(pool, script_uri, user_id, key).await
             }
-        };
-        let overrides = safe_lock_privilege_overrides()
-            .map(|guard| guard.clone())
-            .unwrap_or_default();
-        for metadata in &mut metadata_list {
-            metadata.privileged = db_privileges
-                .get(&metadata.uri)
-                .copied()
-                .or_else(|| overrides.get(&metadata.uri).copied())
-                .unwrap_or_else(|| default_privileged_for(&metadata.uri));
         }
-
-        Ok(metadata_list)
     }
 
-    async fn update_script_init_status(
+    async fn set_user_secret(
         &self,
-        uri: &str,
-        initialized: bool,
-        init_error: Option<String>,
-        registrations: Option<RouteRegistrations>,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+        value: &str,
     ) -> AppResult<()> {
-        let mut guard = safe_lock_scripts()?;
-        if let Some(metadata) = guard.get_mut(uri) {
-            metadata.initialized = initialized;
-            metadata.init_error = init_error;
-            if let Some(regs) = registrations {
-                metadata.registrations = regs;
-            }
-            if initialized {
-                metadata.last_init_time = Some(SystemTime::now());
+        let executor = crate::database::get_current_executor(&self.pool);
+        db_set_user_secret(executor, script_uri, user_id, key, value).await
+    }
+
+    async fn remove_user_secret(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        key: &str,
+    ) -> AppResult<bool> {
+        let executor = crate::database::get_current_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_remove_user_secret(&mut **tx, script_uri, user_id, key).await
             }
-        } else {
-            // If it's a static script, it might not be in dynamic scripts map yet
-            if let Some(content) = get_static_scripts().get(uri) {
-                let mut metadata = ScriptMetadata::new(uri.to_string(), content.clone());
-                metadata.initialized = initialized;
-                metadata.init_error = init_error;
-                if let Some(regs) = registrations {
-                    metadata.registrations = regs;
-                }
-                if initialized {
-                    metadata.last_init_time = Some(SystemTime::now());
-                }
-                guard.insert(uri.to_string(), metadata);
-            } else {
-                return Err(RepositoryError::ScriptNotFound(uri.to_string()).into());
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_remove_user_secret(pool, script_uri, user_id, key).await
             }
         }
-        drop(guard);
-        crate::route_index::invalidate();
-        Ok(())
     }
 
-    async fn get_asset(&self, script_uri: &str, uri: &str) -> AppResult<Option<Asset>> {
+    async fn clear_user_secrets(&self, script_uri: &str, user_id: &str) -> AppResult<()> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_asset(&mut **tx, script_uri, uri).await
+                db_clear_user_secrets(&mut **tx, script_uri, user_id).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_asset(pool, script_uri, uri).await
+                db_clear_user_secrets(pool, script_uri, user_id).await
             }
         }
     }
 
-    async fn list_assets(&self, script_uri: &str) -> AppResult<HashMap<String, Asset>> {
+    async fn get_script_privileged(&self, uri: &str) -> AppResult<Option<bool>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_list_assets(&mut **tx, script_uri).await
+                db_get_script_privileged(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_list_assets(pool, script_uri).await
+                db_get_script_privileged(pool, uri).await
             }
         }
     }
 
-    async fn upsert_asset(&self, asset: Asset) -> AppResult<()> {
+    async fn set_script_privileged(&self, uri: &str, privileged: bool) -> AppResult<()> {
         let executor = crate::database::get_current_executor(&self.pool);
-        db_upsert_asset(executor, &asset).await
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_set_script_privileged(&mut **tx, uri, privileged).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_set_script_privileged(pool, uri, privileged).await
+            }
+        }
     }
 
-    async fn delete_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool> {
+    async fn get_script_system(&self, uri: &str) -> AppResult<Option<bool>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_delete_asset(&mut **tx, script_uri, uri).await
+                db_get_script_system(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_delete_asset(pool, script_uri, uri).await
+                db_get_script_system(pool, uri).await
             }
         }
     }
 
-    async fn insert_log(&self, script_uri: &str, message: &str, level: &str) -> AppResult<()> {
+    async fn set_script_system(&self, uri: &str, system: bool) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
-        match executor {
+        let updated = match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_insert_log_message(&mut **tx, script_uri, message, level).await
+                db_set_script_system(&mut **tx, uri, system).await?
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_insert_log_message(pool, script_uri, message, level).await
+                db_set_script_system(pool, uri, system).await?
             }
+        };
+
+        if updated
+            && let Ok(mut guard) = safe_lock_scripts()
+            && let Some(metadata) = guard.get_mut(uri)
+        {
+            metadata.system = system;
         }
+        Ok(updated)
     }
 
-    async fn fetch_logs(&self, script_uri: &str) -> AppResult<Vec<LogEntry>> {
+    async fn add_script_owner(&self, uri: &str, user_id: &str) -> AppResult<()> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_fetch_log_messages(&mut **tx, script_uri).await
+                db_add_script_owner(&mut **tx, uri, user_id).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_fetch_log_messages(pool, script_uri).await
+                db_add_script_owner(pool, uri, user_id).await
             }
         }
     }
 
-    async fn fetch_all_logs(&self) -> AppResult<Vec<LogEntry>> {
+    async fn remove_script_owner(&self, uri: &str, user_id: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_fetch_all_log_messages(&mut **tx).await
+                db_remove_script_owner(&mut **tx, uri, user_id).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_fetch_all_log_messages(pool).await
+                db_remove_script_owner(pool, uri, user_id).await
             }
         }
     }
 
-    async fn clear_logs(&self, script_uri: &str) -> AppResult<()> {
+    async fn get_script_owners(&self, uri: &str) -> AppResult<Vec<String>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_clear_log_messages(&mut **tx, script_uri).await
+                db_get_script_owners(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_clear_log_messages(pool, script_uri).await
+                db_get_script_owners(pool, uri).await
             }
         }
     }
 
-    async fn prune_logs(&self) -> AppResult<()> {
+    async fn user_owns_script(&self, uri: &str, user_id: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_prune_log_messages(&mut **tx).await
+                db_user_owns_script(&mut **tx, uri, user_id).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_user_owns_script(pool, uri, user_id).await
             }
-            crate::database::TransactionExecutor::Pool(pool) => db_prune_log_messages(pool).await,
         }
     }
 
-    async fn get_script_properties(
-        &self,
-        script_uri: &str,
-        key: &str,
-    ) -> AppResult<Option<String>> {
+    async fn count_script_owners(&self, uri: &str) -> AppResult<i64> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script_properties_item(&mut **tx, script_uri, key).await
+                db_count_script_owners(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_script_properties_item(pool, script_uri, key).await
+                db_count_script_owners(pool, uri).await
             }
         }
     }
 
-    async fn set_script_properties(
-        &self,
-        script_uri: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()> {
+    async fn add_script_collaborator(&self, uri: &str, user_id: &str) -> AppResult<()> {
         let executor = crate::database::get_current_executor(&self.pool);
-        db_set_script_properties_item(executor, script_uri, key, value).await
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_add_script_collaborator(&mut **tx, uri, user_id).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_add_script_collaborator(pool, uri, user_id).await
+            }
+        }
     }
 
-    async fn remove_script_properties(&self, script_uri: &str, key: &str) -> AppResult<bool> {
+    async fn remove_script_collaborator(&self, uri: &str, user_id: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_remove_script_properties_item(&mut **tx, script_uri, key).await
+                db_remove_script_collaborator(&mut **tx, uri, user_id).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_remove_script_properties_item(pool, script_uri, key).await
+                db_remove_script_collaborator(pool, uri, user_id).await
             }
         }
     }
 
-    async fn clear_script_properties(&self, script_uri: &str) -> AppResult<()> {
+    async fn get_script_collaborators(&self, uri: &str) -> AppResult<Vec<String>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_clear_script_properties(&mut **tx, script_uri).await
+                db_get_script_collaborators(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_clear_script_properties(pool, script_uri).await
+                db_get_script_collaborators(pool, uri).await
             }
         }
     }
 
-    async fn get_user_properties(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<Option<String>> {
+    async fn user_collaborates_on_script(&self, uri: &str, user_id: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_user_properties_item(&mut **tx, script_uri, user_id, key).await
+                db_user_collaborates_on_script(&mut **tx, uri, user_id).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_user_properties_item(pool, script_uri, user_id, key).await
+                db_user_collaborates_on_script(pool, uri, user_id).await
             }
         }
     }
 
-    async fn set_user_properties(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()> {
+    async fn restore_script(&self, uri: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
-        db_set_user_properties_item(executor, script_uri, user_id, key, value).await
+        let restored = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_restore_script(tx, uri).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                let mut tx =
+                    crate::database::begin(pool)
+                        .await
+                        .map_err(|e| AppError::Database {
+                            message: format!("Failed to begin transaction: {}", e),
+                            source: None,
+                        })?;
+                let restored = db_restore_script(&mut tx, uri).await?;
+                tx.commit().await.map_err(|e| AppError::Database {
+                    message: format!("Failed to commit transaction: {}", e),
+                    source: None,
+                })?;
+                restored
+            }
+        };
+
+        if restored {
+            // Let other instances pick up the script again
+            send_script_notification(&self.pool, uri, "upserted", &self.server_id).await?;
+            crate::route_index::invalidate();
+        }
+        Ok(restored)
     }
 
-    async fn remove_user_properties(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<bool> {
+    async fn restore_asset(&self, script_uri: &str, uri: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_remove_user_properties_item(&mut **tx, script_uri, user_id, key).await
+                db_restore_asset(&mut **tx, script_uri, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_remove_user_properties_item(pool, script_uri, user_id, key).await
+                db_restore_asset(pool, script_uri, uri).await
             }
         }
     }
 
-    async fn clear_user_properties(&self, script_uri: &str, user_id: &str) -> AppResult<()> {
-        let executor = crate::database::get_current_executor(&self.pool);
+    async fn list_trashed_scripts(&self) -> AppResult<Vec<TrashedScript>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_clear_user_properties(&mut **tx, script_uri, user_id).await
-            }
-            crate::database::TransactionExecutor::Pool(pool) => {
-                db_clear_user_properties(pool, script_uri, user_id).await
+                db_list_trashed_scripts(&mut **tx).await
             }
+            crate::database::TransactionExecutor::Pool(pool) => db_list_trashed_scripts(pool).await,
         }
     }
 
-    async fn get_script_secret(&self, script_uri: &str, key: &str) -> AppResult<Option<String>> {
-        let executor = crate::database::get_current_executor(&self.pool);
+    async fn list_trashed_assets(&self, script_uri: &str) -> AppResult<Vec<TrashedAsset>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script_secret(&mut **tx, script_uri, key).await
+                db_list_trashed_assets(&mut **tx, script_uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_script_secret(pool, script_uri, key).await
+                db_list_trashed_assets(pool, script_uri).await
             }
         }
     }
 
-    async fn set_script_secret(&self, script_uri: &str, key: &str, value: &str) -> AppResult<()> {
-        let executor = crate::database::get_current_executor(&self.pool);
-        db_set_script_secret(executor, script_uri, key, value).await
+    async fn purge_trash(&self, cutoff: DateTime<Utc>) -> AppResult<TrashPurgeSummary> {
+        // Dropping tables happens outside any transaction, like delete_script used to
+        db_purge_trash(&self.pool, cutoff, None).await
     }
 
-    async fn remove_script_secret(&self, script_uri: &str, key: &str) -> AppResult<bool> {
-        let executor = crate::database::get_current_executor(&self.pool);
+    async fn get_script_staging(&self, uri: &str) -> AppResult<Option<ScriptStaging>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_remove_script_secret(&mut **tx, script_uri, key).await
+                db_get_script_staging(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_remove_script_secret(pool, script_uri, key).await
+                db_get_script_staging(pool, uri).await
             }
         }
     }
 
-    async fn clear_script_secrets(&self, script_uri: &str) -> AppResult<()> {
+    async fn stage_script(&self, staging: &ScriptStaging) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_clear_script_secrets(&mut **tx, script_uri).await
+                db_stage_script(&mut **tx, staging).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_clear_script_secrets(pool, script_uri).await
+                db_stage_script(pool, staging).await
             }
         }
     }
 
-    async fn list_script_secrets(&self, script_uri: &str) -> AppResult<Vec<String>> {
+    async fn discard_staged_script(&self, uri: &str) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_list_script_secrets(&mut **tx, script_uri).await
+                db_discard_staged_script(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_list_script_secrets(pool, script_uri).await
+                db_discard_staged_script(pool, uri).await
             }
         }
     }
 
-    async fn get_user_secret(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-    ) -> AppResult<Option<String>> {
+    async fn promote_staged_script(&self, uri: &str) -> AppResult<bool> {
+        self.swap_script_version(uri, true).await
+    }
+
+    async fn rollback_script(&self, uri: &str) -> AppResult<bool> {
+        self.swap_script_version(uri, false).await
+    }
+
+    async fn get_script_labels(&self, uri: &str) -> AppResult<Option<ScriptLabels>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_user_secret(&mut **tx, script_uri, user_id, key).await
+                db_get_script_labels(&mut **tx, uri).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_user_secret(pool, script_uri, user_id, key).await
+                db_get_script_labels(pool, uri).await
             }
         }
     }
 
-    async fn set_user_secret(
-        &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
-        value: &str,
-    ) -> AppResult<()> {
+    async fn set_script_labels(&self, uri: &str, labels: &ScriptLabels) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
-        db_set_user_secret(executor, script_uri, user_id, key, value).await
+        let updated = match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_set_script_labels(&mut **tx, uri, labels).await?
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_set_script_labels(pool, uri, labels).await?
+            }
+        };
+
+        if updated
+            && let Ok(mut guard) = safe_lock_scripts()
+            && let Some(metadata) = guard.get_mut(uri)
+        {
+            metadata.apply_labels(labels.clone());
+        }
+        Ok(updated)
     }
 
-    async fn remove_user_secret(
+    async fn get_script_source_map(&self, uri: &str) -> AppResult<Option<StoredSourceMap>> {
+        let executor = crate::database::get_current_read_executor(&self.pool);
+        match executor {
+            crate::database::TransactionExecutor::Transaction(tx) => {
+                db_get_script_source_map(&mut **tx, uri).await
+            }
+            crate::database::TransactionExecutor::Pool(pool) => {
+                db_get_script_source_map(pool, uri).await
+            }
+        }
+    }
+
+    async fn set_script_source_map(
         &self,
-        script_uri: &str,
-        user_id: &str,
-        key: &str,
+        uri: &str,
+        source_map: Option<&StoredSourceMap>,
     ) -> AppResult<bool> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_remove_user_secret(&mut **tx, script_uri, user_id, key).await
+                db_set_script_source_map(&mut **tx, uri, source_map).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_remove_user_secret(pool, script_uri, user_id, key).await
+                db_set_script_source_map(pool, uri, source_map).await
             }
         }
     }
 
-    async fn clear_user_secrets(&self, script_uri: &str, user_id: &str) -> AppResult<()> {
+    async fn get_script_storage_usage(
+        &self,
+        script_uri: &str,
+        exclude_key: Option<&str>,
+        exclude_asset_uri: Option<&str>,
+    ) -> AppResult<ScriptStorageUsage> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_clear_user_secrets(&mut **tx, script_uri, user_id).await
+                db_get_script_storage_usage(&mut **tx, script_uri, exclude_key, exclude_asset_uri)
+                    .await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_clear_user_secrets(pool, script_uri, user_id).await
+                db_get_script_storage_usage(pool, script_uri, exclude_key, exclude_asset_uri).await
             }
         }
     }
 
-    async fn get_script_privileged(&self, uri: &str) -> AppResult<Option<bool>> {
+    async fn set_script_storage_quota(
+        &self,
+        uri: &str,
+        quota_bytes: Option<u64>,
+    ) -> AppResult<bool> {
+        let quota_bytes = quota_bytes.map(|q| i64::try_from(q).unwrap_or(i64::MAX));
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script_privileged(&mut **tx, uri).await
+                db_set_script_storage_quota(&mut **tx, uri, quota_bytes).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_script_privileged(pool, uri).await
+                db_set_script_storage_quota(pool, uri, quota_bytes).await
             }
         }
     }
 
-    async fn set_script_privileged(&self, uri: &str, privileged: bool) -> AppResult<()> {
+    async fn set_script_execution_timeout(
+        &self,
+        uri: &str,
+        timeout_ms: Option<u64>,
+    ) -> AppResult<bool> {
+        let stored = timeout_ms.map(|ms| i64::try_from(ms).unwrap_or(i64::MAX));
         let executor = crate::database::get_current_executor(&self.pool);
-        match executor {
+        let updated = match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_set_script_privileged(&mut **tx, uri, privileged).await
+                db_set_script_execution_timeout(&mut **tx, uri, stored).await?
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_set_script_privileged(pool, uri, privileged).await
+                db_set_script_execution_timeout(pool, uri, stored).await?
+            }
+        };
+
+        if updated {
+            if let Ok(mut guard) = safe_lock_scripts()
+                && let Some(metadata) = guard.get_mut(uri)
+            {
+                metadata.execution_timeout_ms = timeout_ms;
             }
+            // Route lookups carry the timeout of the route's script
+            crate::route_index::invalidate();
         }
+        Ok(updated)
     }
 
-    async fn add_script_owner(&self, uri: &str, user_id: &str) -> AppResult<()> {
+    async fn set_script_memory_limit(
+        &self,
+        uri: &str,
+        limit_bytes: Option<u64>,
+    ) -> AppResult<bool> {
+        let stored = limit_bytes.map(|bytes| i64::try_from(bytes).unwrap_or(i64::MAX));
         let executor = crate::database::get_current_executor(&self.pool);
-        match executor {
+        let updated = match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_add_script_owner(&mut **tx, uri, user_id).await
+                db_set_script_memory_limit(&mut **tx, uri, stored).await?
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_add_script_owner(pool, uri, user_id).await
+                db_set_script_memory_limit(pool, uri, stored).await?
             }
+        };
+
+        if updated
+            && let Ok(mut guard) = safe_lock_scripts()
+            && let Some(metadata) = guard.get_mut(uri)
+        {
+            metadata.memory_limit_bytes = limit_bytes;
         }
+        Ok(updated)
     }
 
-    async fn remove_script_owner(&self, uri: &str, user_id: &str) -> AppResult<bool> {
+    async fn get_tenant_storage_bytes(
+        &self,
+        prefix: &str,
+        exclude: Option<(&str, &str)>,
+    ) -> AppResult<u64> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_remove_script_owner(&mut **tx, uri, user_id).await
+                db_get_tenant_storage_bytes(&mut **tx, prefix, exclude).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_remove_script_owner(pool, uri, user_id).await
+                db_get_tenant_storage_bytes(pool, prefix, exclude).await
             }
         }
     }
 
-    async fn get_script_owners(&self, uri: &str) -> AppResult<Vec<String>> {
+    async fn list_tenant_storage_bytes(&self) -> AppResult<HashMap<String, u64>> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_get_script_owners(&mut **tx, uri).await
+                db_list_tenant_storage_bytes(&mut **tx).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_get_script_owners(pool, uri).await
+                db_list_tenant_storage_bytes(pool).await
             }
         }
     }
 
-    async fn user_owns_script(&self, uri: &str, user_id: &str) -> AppResult<bool> {
+    async fn list_script_storage_bytes(&self) -> AppResult<HashMap<String, u64>> {
+        db_list_script_storage_bytes(&self.pool).await
+    }
+
+    // Idempotency keys are claimed and completed around handler execution,
+    // never inside a handler transaction
+    async fn claim_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> AppResult<IdempotencyClaim> {
+        db_claim_idempotency_key(
+            &self.pool,
+            scope,
+            key,
+            request_hash,
+            expires_at,
+            stale_before,
+        )
+        .await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+    ) -> AppResult<()> {
+        db_complete_idempotency_key(&self.pool, scope, key, response).await
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> AppResult<()> {
+        db_release_idempotency_key(&self.pool, scope, key).await
+    }
+
+    async fn purge_expired_idempotency_keys(&self) -> AppResult<u64> {
+        db_purge_expired_idempotency_keys(&self.pool).await
+    }
+
+    // Prompt templates are shared by all scripts and outlive any handler
+    // transaction
+    async fn save_prompt_template(
+        &self,
+        script_uri: &str,
+        name: &str,
+        definition: &crate::prompts::PromptDefinition,
+    ) -> AppResult<crate::prompts::PromptTemplate> {
+        db_save_prompt_template(&self.pool, script_uri, name, definition).await
+    }
+
+    async fn get_prompt_template(
+        &self,
+        name: &str,
+        version: Option<i32>,
+    ) -> AppResult<Option<crate::prompts::PromptTemplate>> {
+        db_get_prompt_template(&self.pool, name, version).await
+    }
+
+    async fn list_prompt_templates(&self) -> AppResult<Vec<crate::prompts::PromptTemplate>> {
+        db_list_prompt_templates(&self.pool).await
+    }
+
+    // Usage is recorded whether or not the calling handler's transaction
+    // commits; the provider was paid either way
+    async fn record_llm_usage(
+        &self,
+        month: chrono::NaiveDate,
+        entry: &crate::llm_usage::LlmUsageEntry,
+    ) -> AppResult<()> {
+        db_record_llm_usage(&self.pool, month, entry).await
+    }
+
+    async fn get_llm_usage_cost(
+        &self,
+        month: chrono::NaiveDate,
+        script_uri: &str,
+        user_id: Option<&str>,
+    ) -> AppResult<(f64, f64)> {
+        db_get_llm_usage_cost(&self.pool, month, script_uri, user_id).await
+    }
+
+    async fn list_llm_usage(
+        &self,
+        month: chrono::NaiveDate,
+        script_uri: Option<&str>,
+        user_id: Option<&str>,
+    ) -> AppResult<Vec<crate::llm_usage::LlmUsageEntry>> {
+        db_list_llm_usage(&self.pool, month, script_uri, user_id).await
+    }
+
+    async fn record_route_usage(
+        &self,
+        entry: &crate::route_usage::RouteUsageEntry,
+    ) -> AppResult<()> {
+        db_record_route_usage(&self.pool, entry).await
+    }
+
+    async fn list_route_usage(
+        &self,
+        script_uri: &str,
+        since: chrono::NaiveDate,
+    ) -> AppResult<Vec<crate::route_usage::RouteUsageEntry>> {
+        db_list_route_usage(&self.pool, script_uri, since).await
+    }
+
+    async fn purge_route_usage(&self, before: chrono::NaiveDate) -> AppResult<u64> {
+        db_purge_route_usage(&self.pool, before).await
+    }
+
+    async fn record_script_resources(
+        &self,
+        entry: &crate::script_resources::ScriptResourceEntry,
+    ) -> AppResult<()> {
+        db_record_script_resources(&self.pool, entry).await
+    }
+
+    async fn list_script_resources(
+        &self,
+        script_uri: Option<&str>,
+        since: chrono::NaiveDate,
+    ) -> AppResult<Vec<crate::script_resources::ScriptResourceEntry>> {
+        db_list_script_resources(&self.pool, script_uri, since).await
+    }
+
+    async fn purge_script_resources(&self, before: chrono::NaiveDate) -> AppResult<u64> {
+        db_purge_script_resources(&self.pool, before).await
+    }
+
+    async fn insert_usage_records(
+        &self,
+        records: &[crate::metering::UsageRecord],
+    ) -> AppResult<()> {
+        db_insert_usage_records(&self.pool, records).await
+    }
+
+    async fn purge_usage_records(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        db_purge_usage_records(&self.pool, before).await
+    }
+
+    async fn insert_threat_action(
+        &self,
+        action: &crate::security::threat_response::ThreatAction,
+    ) -> AppResult<()> {
+        db_insert_threat_action(&self.pool, action).await
+    }
+
+    async fn list_threat_actions(
+        &self,
+        status: Option<crate::security::threat_response::ThreatActionStatus>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::security::threat_response::ThreatAction>> {
+        db_list_threat_actions(&self.pool, status, limit).await
+    }
+
+    async fn list_active_threat_actions(
+        &self,
+    ) -> AppResult<Vec<crate::security::threat_response::ThreatAction>> {
+        db_list_active_threat_actions(&self.pool).await
+    }
+
+    async fn has_open_threat_action(
+        &self,
+        action: crate::config::ThreatActionKind,
+        subject: &str,
+    ) -> AppResult<bool> {
+        db_has_open_threat_action(&self.pool, action, subject).await
+    }
+
+    async fn decide_threat_action(
+        &self,
+        id: uuid::Uuid,
+        from: crate::security::threat_response::ThreatActionStatus,
+        to: crate::security::threat_response::ThreatActionStatus,
+        decided_by: &str,
+    ) -> AppResult<Option<crate::security::threat_response::ThreatAction>> {
+        db_decide_threat_action(&self.pool, id, from, to, decided_by).await
+    }
+
+    async fn get_script_manifest(
+        &self,
+        uri: &str,
+    ) -> AppResult<Option<crate::security::script_manifests::ScriptManifest>> {
+        db_get_script_manifest(&self.pool, uri).await
+    }
+
+    async fn update_script_manifest(
+        &self,
+        manifest: &crate::security::script_manifests::ScriptManifest,
+    ) -> AppResult<bool> {
+        db_update_script_manifest(&self.pool, manifest).await
+    }
+
+    async fn list_pending_script_manifests(
+        &self,
+    ) -> AppResult<Vec<crate::security::script_manifests::ScriptManifest>> {
+        db_list_pending_script_manifests(&self.pool).await
+    }
+
+    // A delivery queued by a handler is sent only if its transaction
+    // commits; the worker's own queries run outside any handler
+    async fn enqueue_webhook(
+        &self,
+        webhook: &crate::webhooks::NewWebhook,
+    ) -> AppResult<crate::webhooks::WebhookDelivery> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_user_owns_script(&mut **tx, uri, user_id).await
+                db_enqueue_webhook(&mut **tx, webhook).await
             }
             crate::database::TransactionExecutor::Pool(pool) => {
-                db_user_owns_script(pool, uri, user_id).await
+                db_enqueue_webhook(pool, webhook).await
             }
         }
     }
 
-    async fn count_script_owners(&self, uri: &str) -> AppResult<i64> {
+    async fn claim_webhooks(
+        &self,
+        limit: i64,
+        lock_for: Duration,
+    ) -> AppResult<Vec<crate::webhooks::ClaimedWebhook>> {
+        db_claim_webhooks(&self.pool, limit, lock_for).await
+    }
+
+    async fn finish_webhook_attempt(
+        &self,
+        id: uuid::Uuid,
+        outcome: &crate::webhooks::AttemptOutcome,
+    ) -> AppResult<()> {
+        db_finish_webhook_attempt(&self.pool, id, outcome).await
+    }
+
+    async fn get_webhook_delivery(
+        &self,
+        id: uuid::Uuid,
+    ) -> AppResult<Option<crate::webhooks::WebhookDelivery>> {
+        db_get_webhook_delivery(&self.pool, id).await
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        status: Option<crate::webhooks::DeliveryStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::webhooks::WebhookDelivery>> {
+        db_list_webhook_deliveries(&self.pool, status, script_uri, limit).await
+    }
+
+    async fn purge_webhook_deliveries(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        db_purge_webhook_deliveries(&self.pool, before).await
+    }
+
+    // Like webhooks, a task queued by a handler exists only if its
+    // transaction commits
+    async fn enqueue_task(
+        &self,
+        task: &crate::tasks::NewTask,
+    ) -> AppResult<crate::tasks::EnqueuedTask> {
         let executor = crate::database::get_current_executor(&self.pool);
         match executor {
             crate::database::TransactionExecutor::Transaction(tx) => {
-                db_count_script_owners(&mut **tx, uri).await
-            }
-            crate::database::TransactionExecutor::Pool(pool) => {
-                db_count_script_owners(pool, uri).await
+                db_enqueue_task(&mut **tx, task).await
             }
+            crate::database::TransactionExecutor::Pool(pool) => db_enqueue_task(pool, task).await,
         }
     }
 
+    async fn claim_tasks(
+        &self,
+        limit: i64,
+        visibility: Duration,
+    ) -> AppResult<Vec<crate::tasks::ClaimedTask>> {
+        db_claim_tasks(&self.pool, limit, visibility).await
+    }
+
+    async fn finish_task_attempt(
+        &self,
+        id: uuid::Uuid,
+        outcome: &crate::tasks::TaskOutcome,
+    ) -> AppResult<()> {
+        db_finish_task_attempt(&self.pool, id, outcome).await
+    }
+
+    async fn get_task(&self, id: uuid::Uuid) -> AppResult<Option<crate::tasks::ScriptTask>> {
+        db_get_task(&self.pool, id).await
+    }
+
+    async fn list_tasks(
+        &self,
+        status: Option<crate::tasks::TaskStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::tasks::ScriptTask>> {
+        db_list_tasks(&self.pool, status, script_uri, limit).await
+    }
+
+    async fn task_queue_stats(&self) -> AppResult<Vec<crate::tasks::TaskQueueStats>> {
+        db_task_queue_stats(&self.pool).await
+    }
+
+    async fn purge_tasks(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        db_purge_tasks(&self.pool, before).await
+    }
+
+    async fn insert_email_log(&self, entry: &crate::email::EmailLogEntry) -> AppResult<()> {
+        db_insert_email_log(&self.pool, entry).await
+    }
+
+    async fn count_emails_since(&self, script_uri: &str, since: DateTime<Utc>) -> AppResult<i64> {
+        db_count_emails_since(&self.pool, script_uri, since).await
+    }
+
+    async fn list_email_log(
+        &self,
+        status: Option<crate::email::EmailStatus>,
+        script_uri: Option<&str>,
+        limit: i64,
+    ) -> AppResult<Vec<crate::email::EmailLogEntry>> {
+        db_list_email_log(&self.pool, status, script_uri, limit).await
+    }
+
+    async fn purge_email_log(&self, before: DateTime<Utc>) -> AppResult<u64> {
+        db_purge_email_log(&self.pool, before).await
+    }
+
+    async fn upsert_push_subscription(
+        &self,
+        subscription: &crate::notify::NewPushSubscription,
+    ) -> AppResult<crate::notify::PushSubscription> {
+        db_upsert_push_subscription(&self.pool, subscription).await
+    }
+
+    async fn count_push_subscriptions(
+        &self,
+        script_uri: &str,
+        user_id: &str,
+        endpoint: &str,
+    ) -> AppResult<i64> {
+        db_count_push_subscriptions(&self.pool, script_uri, user_id, endpoint).await
+    }
+
+    async fn list_push_subscriptions(
+        &self,
+        script_uri: &str,
+        user_id: Option<&str>,
+        id: Option<uuid::Uuid>,
+    ) -> AppResult<Vec<crate::notify::PushSubscription>> {
+        db_list_push_subscriptions(&self.pool, script_uri, user_id, id).await
+    }
+
+    async fn delete_push_subscription(&self, script_uri: &str, key: &str) -> AppResult<bool> {
+        db_delete_push_subscription(&self.pool, script_uri, key).await
+    }
+
     async fn create_script_table(
         &self,
         script_uri: &str,
@@ -6226,10 +13348,13 @@ impl Repository for PostgresRepository {
         order_by: Option<&str>,
         order_dir: Option<&str>,
     ) -> AppResult<Vec<serde_json::Value>> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_query_table(
             &mut conn,
             script_uri,
@@ -6248,10 +13373,13 @@ impl Repository for PostgresRepository {
         logical_table_name: &str,
         data: &HashMap<String, serde_json::Value>,
     ) -> AppResult<serde_json::Value> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_insert_row(&mut conn, script_uri, logical_table_name, data).await
     }
 
@@ -6262,10 +13390,13 @@ impl Repository for PostgresRepository {
         id: i32,
         data: &HashMap<String, serde_json::Value>,
     ) -> AppResult<serde_json::Value> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_update_row(&mut conn, script_uri, logical_table_name, id, data).await
     }
 
@@ -6275,10 +13406,13 @@ impl Repository for PostgresRepository {
         logical_table_name: &str,
         id: i32,
     ) -> AppResult<bool> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_delete_row(&mut conn, script_uri, logical_table_name, id).await
     }
 
@@ -6289,10 +13423,13 @@ impl Repository for PostgresRepository {
         key_columns: &[String],
         data: &HashMap<String, serde_json::Value>,
     ) -> AppResult<serde_json::Value> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_upsert_row(&mut conn, script_uri, logical_table_name, key_columns, data).await
     }
 
@@ -6302,10 +13439,13 @@ impl Repository for PostgresRepository {
         logical_table_name: &str,
         filters: &HashMap<String, serde_json::Value>,
     ) -> AppResult<u64> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_delete_where(&mut conn, script_uri, logical_table_name, filters).await
     }
 
@@ -6317,10 +13457,13 @@ impl Repository for PostgresRepository {
         owner: &str,
         ttl_ms: i64,
     ) -> AppResult<serde_json::Value> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_acquire_lease(
             &mut conn,
             script_uri,
@@ -6337,10 +13480,13 @@ impl Repository for PostgresRepository {
         script_uri: &str,
         logical_table_name: &str,
     ) -> AppResult<String> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_create_lease_table(&mut conn, script_uri, logical_table_name).await
     }
 
@@ -6350,12 +13496,72 @@ impl Repository for PostgresRepository {
         logical_table_name: &str,
         columns: &[String],
     ) -> AppResult<()> {
-        let mut conn = self.pool.acquire().await.map_err(|e| AppError::Database {
-            message: format!("Failed to acquire connection: {}", e),
-            source: None,
-        })?;
+        let mut conn =
+            crate::database::acquire(&self.pool)
+                .await
+                .map_err(|e| AppError::Database {
+                    message: format!("Failed to acquire connection: {}", e),
+                    source: None,
+                })?;
         db_add_unique_index(&mut conn, script_uri, logical_table_name, columns).await
     }
+
+    async fn run_script_query(
+        &self,
+        script_uri: &str,
+        sql: &str,
+        params: &[serde_json::Value],
+        options: &ScriptQueryOptions,
+        tenant: Option<&str>,
+    ) -> AppResult<ScriptQueryResult> {
+        db_run_script_query(&self.pool, script_uri, sql, params, options, tenant).await
+    }
+
+    async fn migrate_script_schema(
+        &self,
+        script_uri: &str,
+        migrations: &[ScriptMigration],
+        tenant: Option<&str>,
+    ) -> AppResult<ScriptMigrationSummary> {
+        db_migrate_script_schema(&self.pool, script_uri, migrations, tenant).await
+    }
+    async fn upsert_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        documents: &[VectorDocument],
+        tenant: Option<&str>,
+    ) -> AppResult<u64> {
+        db_upsert_vectors(&self.pool, script_uri, collection, documents, tenant).await
+    }
+
+    async fn search_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        embedding: &[f32],
+        options: &VectorSearchOptions,
+        tenant: Option<&str>,
+    ) -> AppResult<VectorSearchPage> {
+        db_search_vectors(
+            &self.pool, script_uri, collection, embedding, options, tenant,
+        )
+        .await
+    }
+
+    async fn delete_vectors(
+        &self,
+        script_uri: &str,
+        collection: &str,
+        ids: &[String],
+        tenant: Option<&str>,
+    ) -> AppResult<u64> {
+        db_delete_vectors(&self.pool, script_uri, collection, ids, tenant).await
+    }
+
+    async fn drop_vector_collection(&self, script_uri: &str, collection: &str) -> AppResult<bool> {
+        db_drop_vector_collection(&self.pool, script_uri, collection).await
+    }
 }
 
 /// Global secret encryption instance (optional — if not set, secrets are stored plaintext)
@@ -6369,6 +13575,11 @@ pub fn initialize_secret_encryption(enc: Arc<crate::security::encryption::DataEn
     GLOBAL_SECRET_ENCRYPTION.set(enc).is_ok()
 }
 
+/// Whether secret values are encrypted at rest
+pub fn secret_encryption_enabled() -> bool {
+    GLOBAL_SECRET_ENCRYPTION.get().is_some()
+}
+
 /// Global repository instance
 static GLOBAL_REPOSITORY: OnceLock<PostgresRepository> = OnceLock::new();
 
@@ -6424,109 +13635,887 @@ mod tests {
             let server_id = crate::notifications::generate_server_id();
             crate::notifications::initialize_server_id(server_id.clone());
 
-            // Initialize PostgresRepository with pool and server_id
-            let repo = crate::repository::PostgresRepository::new(pool, server_id);
-            crate::repository::initialize_repository(repo);
-        });
-    }
+            // Initialize PostgresRepository with pool and server_id
+            let repo = crate::repository::PostgresRepository::new(pool, server_id);
+            crate::repository::initialize_repository(repo);
+        });
+    }
+
+    fn get_runtime() -> &'static tokio::runtime::Runtime {
+        RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().unwrap())
+    }
+
+    // Helper to check if we should skip database-dependent tests
+    fn should_skip_db_tests() -> bool {
+        std::env::var("DATABASE_URL").is_err()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_operations() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let uri = "test://example";
+        let content = "console.log('test')";
+
+        // Test upsert
+        assert!(upsert_script(uri, content).is_ok());
+
+        // Test fetch
+        let fetched = fetch_script(uri);
+        assert_eq!(fetched, Some(content.to_string()));
+
+        // Test delete
+        assert!(delete_script(uri));
+        assert!(!delete_script(uri)); // Should return false for non-existent
+    }
+
+    #[test]
+    fn test_bootstrap_scripts() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let _lock = GLOBAL_TEST_LOCK.lock().unwrap();
+        // Test that bootstrap_scripts doesn't crash when database is available
+        let result = bootstrap_scripts();
+        assert!(
+            result.is_ok(),
+            "bootstrap_scripts should succeed even without database"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_bootstrap_assets() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        // Test that bootstrap_assets doesn't crash when database is available
+        let result = bootstrap_assets();
+        assert!(
+            result.is_ok(),
+            "bootstrap_assets should succeed with database"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_properties_operations() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://storage-script";
+        let key = "test_key";
+        let value = "test_value";
+
+        // Test set item
+        assert!(set_script_properties_item(script_uri, key, value).is_ok());
+
+        // Test get item
+        let retrieved = get_script_properties_item(script_uri, key);
+        assert_eq!(retrieved, Some(value.to_string()));
+
+        // Test remove item
+        assert!(remove_script_properties_item(script_uri, key));
+
+        // Verify item is gone
+        let retrieved_after_remove = get_script_properties_item(script_uri, key);
+        assert_eq!(retrieved_after_remove, None);
+
+        // Test clear storage
+        assert!(set_script_properties_item(script_uri, "key1", "value1").is_ok());
+        assert!(set_script_properties_item(script_uri, "key2", "value2").is_ok());
+
+        assert!(clear_script_properties(script_uri).is_ok());
+
+        // Verify both items are gone
+        assert_eq!(get_script_properties_item(script_uri, "key1"), None);
+        assert_eq!(get_script_properties_item(script_uri, "key2"), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_properties_ttl() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://storage-ttl-script";
+        let _ = clear_script_properties(script_uri);
+        let short = Duration::from_millis(300);
+
+        assert!(
+            set_script_properties_item_with_ttl(script_uri, "cached", "v", Some(short)).is_ok()
+        );
+        assert!(
+            set_script_properties_item_with_ttl(script_uri, "touched", "v", Some(short)).is_ok()
+        );
+        assert!(set_script_properties_item_with_ttl(script_uri, "swept", "v", Some(short)).is_ok());
+        assert_eq!(
+            get_script_properties_item(script_uri, "cached"),
+            Some("v".to_string())
+        );
+        assert!(
+            touch_script_properties_item(script_uri, "touched", Duration::from_secs(60)).unwrap()
+        );
+        assert!(!touch_script_properties_item(script_uri, "missing", short).unwrap());
+
+        std::thread::sleep(Duration::from_millis(500));
+
+        // Expired items read as missing and can no longer be touched
+        assert_eq!(get_script_properties_item(script_uri, "cached"), None);
+        assert!(!touch_script_properties_item(script_uri, "cached", short).unwrap());
+        assert_eq!(
+            get_script_properties_item(script_uri, "touched"),
+            Some("v".to_string())
+        );
+        assert!(purge_expired_script_properties().unwrap() >= 1);
+
+        // Setting without a TTL makes the item persistent again
+        assert!(
+            set_script_properties_item_with_ttl(script_uri, "cached", "v2", Some(short)).is_ok()
+        );
+        assert!(set_script_properties_item(script_uri, "cached", "v3").is_ok());
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(
+            get_script_properties_item(script_uri, "cached"),
+            Some("v3".to_string())
+        );
+
+        let _ = clear_script_properties(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_idempotency_key_lifecycle() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let repo = get_repository();
+        let scope = "POST /test/orders user:idempotency-test tenant:";
+        let key = "order-1";
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let stale_before = Utc::now() - chrono::Duration::minutes(1);
+        let _ = repo.release_idempotency_key(scope, key).await;
+        run_blocking(async {
+            sqlx::query("DELETE FROM idempotency_keys WHERE scope = $1")
+                .bind(scope)
+                .execute(&repo.pool)
+                .await
+        })
+        .expect("Should clear idempotency keys");
+
+        let claim = |hash: &'static str| {
+            repo.claim_idempotency_key(scope, key, hash, expires_at, stale_before)
+        };
+        assert_eq!(claim("h1").await.unwrap(), IdempotencyClaim::Claimed);
+        assert_eq!(
+            claim("h1").await.unwrap(),
+            IdempotencyClaim::InProgress {
+                request_hash: "h1".to_string()
+            }
+        );
+
+        // A released key can be claimed again
+        repo.release_idempotency_key(scope, key).await.unwrap();
+        assert_eq!(claim("h1").await.unwrap(), IdempotencyClaim::Claimed);
+
+        let response = StoredResponse {
+            status: 201,
+            content_type: Some("application/json".to_string()),
+            headers: HashMap::from([("location".to_string(), "/orders/1".to_string())]),
+            body: b"{\"id\":1}".to_vec(),
+        };
+        repo.complete_idempotency_key(scope, key, &response)
+            .await
+            .unwrap();
+        assert_eq!(
+            claim("h1").await.unwrap(),
+            IdempotencyClaim::Completed {
+                request_hash: "h1".to_string(),
+                response
+            }
+        );
+
+        // Completed keys are not released, and expired ones are claimed anew
+        repo.release_idempotency_key(scope, key).await.unwrap();
+        assert!(matches!(
+            claim("h2").await.unwrap(),
+            IdempotencyClaim::Completed { .. }
+        ));
+        run_blocking(async {
+            sqlx::query("UPDATE idempotency_keys SET expires_at = NOW() WHERE scope = $1")
+                .bind(scope)
+                .execute(&repo.pool)
+                .await
+        })
+        .expect("Should expire key");
+        assert!(repo.purge_expired_idempotency_keys().await.unwrap() >= 1);
+        assert_eq!(claim("h2").await.unwrap(), IdempotencyClaim::Claimed);
+        repo.release_idempotency_key(scope, key).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_webhook_delivery_lifecycle() {
+        use crate::webhooks::{AttemptOutcome, DeliveryStatus, NewWebhook};
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let repo = get_repository();
+        let script_uri = "https://example.com/webhook-test";
+        run_blocking(async {
+            sqlx::query("DELETE FROM webhook_deliveries WHERE script_uri = $1")
+                .bind(script_uri)
+                .execute(&repo.pool)
+                .await
+        })
+        .expect("Should clear webhook deliveries");
+
+        let webhook = NewWebhook {
+            id: uuid::Uuid::new_v4(),
+            script_uri: script_uri.to_string(),
+            url: "https://hooks.example.com/orders".to_string(),
+            payload: "{\"order\":1}".to_string(),
+            content_type: "application/json".to_string(),
+            headers: HashMap::from([("X-Source".to_string(), "test".to_string())]),
+            secret_name: Some("hook_secret".to_string()),
+            max_attempts: 2,
+        };
+        let queued = repo.enqueue_webhook(&webhook).await.unwrap();
+        assert_eq!(queued.status, DeliveryStatus::Pending);
+        assert_eq!(queued.attempts, 0);
+
+        let claim = || repo.claim_webhooks(100, Duration::from_secs(60));
+        let claimed = claim().await.unwrap();
+        let ours = claimed
+            .iter()
+            .find(|claimed| claimed.webhook.id == webhook.id)
+            .expect("due delivery is claimed");
+        assert_eq!(ours.webhook, webhook);
+        assert_eq!(ours.attempts, 1);
+        // A claimed delivery is not claimed again while its attempt runs
+        assert!(
+            !claim()
+                .await
+                .unwrap()
+                .iter()
+                .any(|claimed| claimed.webhook.id == webhook.id)
+        );
+
+        let retry_at = Utc::now() - chrono::Duration::seconds(1);
+        repo.finish_webhook_attempt(
+            webhook.id,
+            &AttemptOutcome::Retry {
+                status_code: Some(503),
+                error: "HTTP 503".to_string(),
+                next_attempt_at: retry_at,
+            },
+        )
+        .await
+        .unwrap();
+        let ours = claim().await.unwrap();
+        assert_eq!(
+            ours.iter()
+                .find(|claimed| claimed.webhook.id == webhook.id)
+                .map(|claimed| claimed.attempts),
+            Some(2)
+        );
+        repo.finish_webhook_attempt(
+            webhook.id,
+            &AttemptOutcome::Failed {
+                status_code: None,
+                error: "connection refused".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let failed = repo
+            .list_webhook_deliveries(Some(DeliveryStatus::Failed), Some(script_uri), 10)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 2);
+        assert_eq!(failed[0].last_error.as_deref(), Some("connection refused"));
+        assert_eq!(failed[0].last_status_code, None);
+        assert_eq!(
+            repo.get_webhook_delivery(webhook.id).await.unwrap(),
+            Some(failed[0].clone())
+        );
+
+        assert!(
+            repo.purge_webhook_deliveries(Utc::now() + chrono::Duration::seconds(1))
+                .await
+                .unwrap()
+                >= 1
+        );
+        assert_eq!(repo.get_webhook_delivery(webhook.id).await.unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_task_queue_lifecycle() {
+        use crate::tasks::{NewTask, TaskOutcome, TaskStatus};
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let repo = get_repository();
+        let script_uri = "https://example.com/task-test";
+        run_blocking(async {
+            sqlx::query("DELETE FROM script_tasks WHERE script_uri = $1")
+                .bind(script_uri)
+                .execute(&repo.pool)
+                .await
+        })
+        .expect("Should clear tasks");
+
+        let task = |priority: i32| NewTask {
+            id: uuid::Uuid::new_v4(),
+            script_uri: script_uri.to_string(),
+            handler_name: "buildReport".to_string(),
+            payload: serde_json::json!({ "priority": priority }),
+            priority,
+            max_attempts: 2,
+            run_at: Utc::now() - chrono::Duration::seconds(1),
+            idempotency_key: None,
+        };
+        let low = task(0);
+        let high = task(10);
+        let queued = repo.enqueue_task(&low).await.unwrap();
+        assert!(!queued.duplicate);
+        assert_eq!(queued.task.status, TaskStatus::Pending);
+        assert_eq!(queued.task.attempts, 0);
+        repo.enqueue_task(&high).await.unwrap();
+
+        let claim = || repo.claim_tasks(100, Duration::from_secs(60));
+        let ours: Vec<_> = claim()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|claimed| claimed.task.script_uri == script_uri)
+            .collect();
+        assert_eq!(ours.len(), 2);
+        assert_eq!(ours[0].task.id, high.id);
+        assert_eq!(ours[0].attempts, 1);
+        // Claimed tasks are hidden from other workers while they run
+        assert!(
+            !claim()
+                .await
+                .unwrap()
+                .iter()
+                .any(|claimed| claimed.task.script_uri == script_uri)
+        );
+        assert_eq!(
+            repo.get_task(low.id).await.unwrap().unwrap().status,
+            TaskStatus::Running
+        );
+        let stats = repo.task_queue_stats().await.unwrap();
+        let ours_stats = stats
+            .iter()
+            .find(|stats| stats.script_uri == script_uri)
+            .expect("script has stats");
+        assert_eq!((ours_stats.pending, ours_stats.running), (0, 2));
+
+        repo.finish_task_attempt(high.id, &TaskOutcome::Completed)
+            .await
+            .unwrap();
+        repo.finish_task_attempt(
+            low.id,
+            &TaskOutcome::Retry {
+                error: "boom".to_string(),
+                run_at: Utc::now() - chrono::Duration::seconds(1),
+            },
+        )
+        .await
+        .unwrap();
+        let retried = claim().await.unwrap();
+        assert_eq!(
+            retried
+                .iter()
+                .find(|claimed| claimed.task.id == low.id)
+                .map(|claimed| claimed.attempts),
+            Some(2)
+        );
+        repo.finish_task_attempt(
+            low.id,
+            &TaskOutcome::Failed {
+                error: "boom again".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let failed = repo
+            .list_tasks(Some(TaskStatus::Failed), Some(script_uri), 10)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, low.id);
+        assert_eq!(failed[0].attempts, 2);
+        assert_eq!(failed[0].last_error.as_deref(), Some("boom again"));
+        let completed = repo.get_task(high.id).await.unwrap().unwrap();
+        assert_eq!(completed.status, TaskStatus::Completed);
+        assert!(completed.completed_at.is_some());
+
+        // A second task with the same idempotency key returns the first
+        let keyed = || NewTask {
+            run_at: Utc::now() + chrono::Duration::hours(1),
+            idempotency_key: Some("order-42".to_string()),
+            ..task(0)
+        };
+        let first = repo.enqueue_task(&keyed()).await.unwrap();
+        let second = repo.enqueue_task(&keyed()).await.unwrap();
+        assert!(!first.duplicate);
+        assert!(second.duplicate);
+        assert_eq!(second.task.id, first.task.id);
+        assert_eq!(second.task.idempotency_key.as_deref(), Some("order-42"));
+
+        assert!(
+            repo.purge_tasks(Utc::now() + chrono::Duration::seconds(1))
+                .await
+                .unwrap()
+                >= 2
+        );
+        assert_eq!(repo.get_task(low.id).await.unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_storage_quota() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://quota-script";
+        assert!(upsert_script(script_uri, "console.log('quota');").is_ok());
+        let _ = clear_script_properties(script_uri);
+        set_script_storage_quota(script_uri, Some(100)).expect("Should set quota");
+
+        // 4-byte key + 40-byte value
+        let value = "x".repeat(40);
+        assert!(set_script_properties_item(script_uri, "key1", &value).is_ok());
+        let usage = get_script_storage_usage(script_uri).expect("Should get usage");
+        assert_eq!(usage.properties_bytes, 44);
+        assert_eq!(usage.quota_bytes, Some(100));
+        assert_eq!(usage.quota_override, Some(100));
+
+        // Replacing an item only counts the new value
+        assert!(set_script_properties_item(script_uri, "key1", &"y".repeat(90)).is_ok());
+        let err = set_script_properties_item(script_uri, "key2", &value).unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { .. }));
+        assert!(set_script_properties_item(script_uri, "key1", &value).is_ok());
+
+        // Assets share the quota with shared storage
+        let now = SystemTime::now();
+        let asset = Asset {
+            uri: "test-quota-asset.bin".to_string(),
+            name: None,
+            mimetype: "application/octet-stream".to_string(),
+            content: vec![0; 80],
+            created_at: now,
+            updated_at: now,
+            script_uri: script_uri.to_string(),
+            headers: HashMap::new(),
+        };
+        let err = upsert_asset(asset.clone()).unwrap_err();
+        assert!(matches!(err, AppError::QuotaExceeded { .. }));
+
+        // 0 lifts the limit; None restores the configured default
+        set_script_storage_quota(script_uri, Some(0)).expect("Should lift quota");
+        assert!(upsert_asset(asset).is_ok());
+        assert_eq!(
+            get_script_storage_usage(script_uri)
+                .expect("Should get usage")
+                .asset_bytes,
+            80
+        );
+        set_script_storage_quota(script_uri, None).expect("Should reset quota");
+        let usage = get_script_storage_usage(script_uri).expect("Should get usage");
+        assert_eq!(usage.quota_override, None);
+        assert_eq!(usage.quota_bytes, Some(default_storage_quota_bytes()));
+
+        assert!(set_script_storage_quota("test://missing-quota-script", Some(1)).is_err());
 
-    fn get_runtime() -> &'static tokio::runtime::Runtime {
-        RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().unwrap())
+        let _ = clear_script_properties(script_uri);
+        delete_script(script_uri);
     }
 
-    // Helper to check if we should skip database-dependent tests
-    fn should_skip_db_tests() -> bool {
-        std::env::var("DATABASE_URL").is_err()
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_execution_timeout() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://slow-report-script";
+        assert!(upsert_script(script_uri, "function init() {}").is_ok());
+        let repo = get_repository();
+        let timeout_of = |uri: &'static str| {
+            run_blocking(async { get_repository().get_all_script_metadata().await })
+                .expect("Should list scripts")
+                .into_iter()
+                .find(|metadata| metadata.uri == uri)
+                .and_then(|metadata| metadata.execution_timeout_ms)
+        };
+
+        set_script_execution_timeout(script_uri, Some(30_000)).expect("Should set timeout");
+        let metadata = run_blocking(async { repo.get_script_metadata(script_uri).await })
+            .expect("Should get metadata");
+        assert_eq!(metadata.execution_timeout_ms, Some(30_000));
+        assert_eq!(timeout_of(script_uri), Some(30_000));
+
+        set_script_execution_timeout(script_uri, None).expect("Should reset timeout");
+        assert_eq!(timeout_of(script_uri), None);
+
+        assert!(set_script_execution_timeout("test://missing-timeout-script", Some(1)).is_err());
+        delete_script(script_uri);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_script_operations() {
+    async fn test_script_memory_limit() {
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        let uri = "test://example";
-        let content = "console.log('test')";
+        let script_uri = "test://memory-hungry-script";
+        assert!(upsert_script(script_uri, "function init() {}").is_ok());
+        let repo = get_repository();
+        run_blocking(async { repo.get_all_script_metadata().await }).expect("Should list scripts");
 
-        // Test upsert
-        assert!(upsert_script(uri, content).is_ok());
+        set_script_memory_limit(script_uri, Some(64 * 1024 * 1024)).expect("Should set limit");
+        let metadata = run_blocking(async { repo.get_script_metadata(script_uri).await })
+            .expect("Should get metadata");
+        assert_eq!(metadata.memory_limit_bytes, Some(64 * 1024 * 1024));
+        assert_eq!(script_memory_limit(script_uri), Some(64 * 1024 * 1024));
 
-        // Test fetch
-        let fetched = fetch_script(uri);
-        assert_eq!(fetched, Some(content.to_string()));
+        set_script_memory_limit(script_uri, None).expect("Should reset limit");
+        assert_eq!(script_memory_limit(script_uri), None);
 
-        // Test delete
-        assert!(delete_script(uri));
-        assert!(!delete_script(uri)); // Should return false for non-existent
+        assert!(set_script_memory_limit("test://missing-memory-script", Some(1)).is_err());
+        delete_script(script_uri);
     }
 
-    #[test]
-    fn test_bootstrap_scripts() {
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_system_script_protection() {
         if should_skip_db_tests() {
             return;
         }
-        let _lock = GLOBAL_TEST_LOCK.lock().unwrap();
-        // Test that bootstrap_scripts doesn't crash when database is available
-        let result = bootstrap_scripts();
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://system-script";
+        assert!(upsert_script(script_uri, "function init() {}").is_ok());
+        set_script_system(script_uri, true).expect("Should flag script");
+        assert!(is_system_script(script_uri).expect("Should read flag"));
+
+        assert!(upsert_script(script_uri, "// replaced").is_err());
         assert!(
-            result.is_ok(),
-            "bootstrap_scripts should succeed even without database"
+            upsert_script_with_owner(script_uri, "// replaced", Some("editor"), false).is_err()
         );
+        assert!(!delete_script(script_uri));
+        assert!(try_delete_script(script_uri, false).is_err());
+        assert_eq!(
+            fetch_script(script_uri).as_deref(),
+            Some("function init() {}")
+        );
+
+        upsert_script_with_owner(script_uri, "// forced", None, true).expect("Should force update");
+        assert!(try_delete_script(script_uri, true).expect("Should force delete"));
+        assert!(set_script_system("test://missing-system-script", true).is_err());
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_bootstrap_assets() {
+    async fn test_tenant_storage_quota() {
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        // Test that bootstrap_assets doesn't crash when database is available
-        let result = bootstrap_assets();
+        let script_uri = "test://tenant-quota-script";
+        assert!(upsert_script(script_uri, "console.log('tenant quota');").is_ok());
+        let _ = clear_script_properties(script_uri);
+        crate::tenancy::configure(&crate::config::TenancyConfig {
+            quotas: crate::config::TenantQuotaConfig {
+                storage_bytes: 40,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        // "tenant/a/k" plus ten bytes of value is 20 bytes
+        set_script_properties_item_with_ttl(script_uri, "tenant/a/k", &"x".repeat(10), None)
+            .expect("Should store within quota");
+        // Replacing the same item doesn't count it twice
+        set_script_properties_item_with_ttl(script_uri, "tenant/a/k", &"y".repeat(10), None)
+            .expect("Should replace within quota");
+        set_script_properties_item_with_ttl(script_uri, "tenant/a/j", &"z".repeat(10), None)
+            .expect("Should fill the quota");
+        let err = set_script_properties_item_with_ttl(script_uri, "tenant/a/i", "1", None)
+            .expect_err("Should exceed the quota");
+        assert!(err.to_string().contains("quota"));
+        // Other tenants and untenanted keys have their own budget
+        set_script_properties_item_with_ttl(script_uri, "tenant/b/k", &"x".repeat(10), None)
+            .expect("Other tenant should store");
+        set_script_properties_item_with_ttl(script_uri, "plain", &"x".repeat(100), None)
+            .expect("Untenanted key should store");
+
+        let usage = list_tenant_storage_bytes().expect("Should list tenant storage");
+        assert_eq!(usage.get("a"), Some(&40));
+        assert_eq!(usage.get("b"), Some(&20));
+
+        crate::tenancy::configure(&crate::config::TenancyConfig::default());
+        let _ = clear_script_properties(script_uri);
+        delete_script(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_script_query() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://sql-script";
+        let other_uri = "test://sql-other-script";
+        let options = ScriptQueryOptions::default();
+
+        // Tables are created in the script's own schema
+        for uri in [script_uri, other_uri] {
+            run_script_query(uri, "DROP TABLE IF EXISTS notes", &[], &options)
+                .expect("Should drop table");
+            run_script_query(
+                uri,
+                "CREATE TABLE notes (id SERIAL PRIMARY KEY, body TEXT, score REAL, tags JSONB)",
+                &[],
+                &options,
+            )
+            .expect("Should create table");
+        }
+
+        let inserted = run_script_query(
+            script_uri,
+            "INSERT INTO notes (body, score, tags) VALUES ($1, $2, $3), ($4, $5, $6) RETURNING id",
+            &[
+                serde_json::json!("first"),
+                serde_json::json!(1.5),
+                serde_json::json!(["a"]),
+                serde_json::json!("second; DROP TABLE notes"),
+                serde_json::json!(2),
+                serde_json::Value::Null,
+            ],
+            &options,
+        )
+        .expect("Should insert rows");
+        assert_eq!(inserted.row_count, 2);
+        assert_eq!(inserted.rows.len(), 2);
+
+        let selected = run_script_query(
+            script_uri,
+            "SELECT body, score, tags FROM notes WHERE score > $1 ORDER BY id",
+            &[serde_json::json!(1)],
+            &options,
+        )
+        .expect("Should select rows");
+        assert_eq!(selected.row_count, 2);
+        assert!(!selected.truncated);
+        assert_eq!(selected.rows[0]["body"], "first");
+        assert_eq!(selected.rows[0]["score"], 1.5);
+        assert_eq!(selected.rows[0]["tags"], serde_json::json!(["a"]));
+        assert_eq!(selected.rows[1]["body"], "second; DROP TABLE notes");
+
+        // Row limit
+        let limited = run_script_query(
+            script_uri,
+            "SELECT * FROM notes",
+            &[],
+            &ScriptQueryOptions::new(Some(1), None),
+        )
+        .expect("Should select rows");
+        assert_eq!(limited.rows.len(), 1);
+        assert!(limited.truncated);
+
+        // Other scripts have their own, empty table of the same name
+        let other = run_script_query(other_uri, "SELECT * FROM notes", &[], &options)
+            .expect("Should select rows");
+        assert!(other.rows.is_empty());
+
+        // The engine's tables and other scripts' schemas are out of reach
         assert!(
-            result.is_ok(),
-            "bootstrap_assets should succeed with database"
+            run_script_query(script_uri, "SELECT * FROM public.scripts", &[], &options).is_err()
+        );
+        let other_schema = crate::db_schema_utils::generate_script_schema_name(other_uri);
+        let cross_sql = format!("SELECT * FROM {}.notes", other_schema);
+        assert!(run_script_query(script_uri, &cross_sql, &[], &options).is_err());
+
+        // Statements that could leave the sandbox are rejected before running
+        let err = run_script_query(script_uri, "RESET ROLE", &[], &options).unwrap_err();
+        assert!(matches!(err, AppError::Validation { .. }));
+
+        // Statement timeout
+        let slow = run_script_query(
+            script_uri,
+            "SELECT pg_sleep(1)",
+            &[],
+            &ScriptQueryOptions::new(None, Some(50)),
         );
+        assert!(slow.is_err());
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_script_properties_operations() {
+    async fn test_run_script_query_with_tenant() {
         if should_skip_db_tests() {
             return;
         }
         let rt = get_runtime();
         let _guard = rt.enter();
         setup_db();
-        let script_uri = "test://storage-script";
-        let key = "test_key";
-        let value = "test_value";
+        let repo = get_repository();
+        let script_uri = "test://sql-tenant-script";
+        let options = ScriptQueryOptions::default();
+        let query = |sql: &str, params: &[serde_json::Value], tenant: &str| {
+            run_blocking(repo.run_script_query(script_uri, sql, params, &options, Some(tenant)))
+        };
 
-        // Test set item
-        assert!(set_script_properties_item(script_uri, key, value).is_ok());
+        query("DROP TABLE IF EXISTS notes", &[], "a").expect("Should drop table");
+        query(
+            "CREATE TABLE notes (id SERIAL PRIMARY KEY, body TEXT)",
+            &[],
+            "a",
+        )
+        .expect("Should create table");
+        for tenant in ["a", "b"] {
+            query(
+                "INSERT INTO notes (body) VALUES ($1)",
+                &[serde_json::json!(tenant)],
+                tenant,
+            )
+            .expect("Should insert row");
+        }
 
-        // Test get item
-        let retrieved = get_script_properties_item(script_uri, key);
-        assert_eq!(retrieved, Some(value.to_string()));
+        // Each tenant sees only its own rows
+        for tenant in ["a", "b"] {
+            let selected = query("SELECT body, tenant_id FROM notes", &[], tenant)
+                .expect("Should select rows");
+            assert_eq!(selected.rows.len(), 1);
+            assert_eq!(selected.rows[0]["body"], tenant);
+            assert_eq!(selected.rows[0]["tenant_id"], tenant);
+        }
+        let untenanted = query("SELECT * FROM notes", &[], "").expect("Should select rows");
+        assert!(untenanted.rows.is_empty());
 
-        // Test remove item
-        assert!(remove_script_properties_item(script_uri, key));
+        // Rows cannot be moved to another tenant
+        assert!(query("UPDATE notes SET tenant_id = 'b'", &[], "a").is_err());
 
-        // Verify item is gone
-        let retrieved_after_remove = get_script_properties_item(script_uri, key);
-        assert_eq!(retrieved_after_remove, None);
+        // The policy cannot be switched off
+        let err = query("ALTER TABLE notes DISABLE ROW LEVEL SECURITY", &[], "a").unwrap_err();
+        assert!(matches!(err, AppError::Validation { .. }));
 
-        // Test clear storage
-        assert!(set_script_properties_item(script_uri, "key1", "value1").is_ok());
-        assert!(set_script_properties_item(script_uri, "key2", "value2").is_ok());
+        query("DROP TABLE notes", &[], "a").expect("Should drop table");
+    }
 
-        assert!(clear_script_properties(script_uri).is_ok());
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_migrate_script_schema() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://migrating-script";
+        assert!(upsert_script(script_uri, "console.log('migrations');").is_ok());
 
-        // Verify both items are gone
-        assert_eq!(get_script_properties_item(script_uri, "key1"), None);
-        assert_eq!(get_script_properties_item(script_uri, "key2"), None);
+        // Start from an empty schema
+        let options = ScriptQueryOptions::default();
+        run_script_query(script_uri, "DROP TABLE IF EXISTS tasks", &[], &options)
+            .expect("Should drop table");
+        run_blocking(async {
+            sqlx::query("DELETE FROM script_migrations WHERE script_uri = $1")
+                .bind(script_uri)
+                .execute(&get_repository().pool)
+                .await
+        })
+        .expect("Should clear migrations");
+
+        let migrations = vec![
+            ScriptMigration {
+                version: 2,
+                up: vec!["ALTER TABLE tasks ADD COLUMN done BOOLEAN DEFAULT false".to_string()],
+            },
+            ScriptMigration {
+                version: 1,
+                up: vec!["CREATE TABLE tasks (id SERIAL PRIMARY KEY, title TEXT)".to_string()],
+            },
+        ];
+        let summary = migrate_script_schema(script_uri, &migrations).expect("Should migrate");
+        assert_eq!(summary.applied, vec![1, 2]);
+        assert_eq!(summary.version, Some(2));
+
+        // Applying the same list again is a no-op
+        let summary = migrate_script_schema(script_uri, &migrations).expect("Should migrate");
+        assert!(summary.applied.is_empty());
+        assert_eq!(summary.version, Some(2));
+
+        let inserted = run_script_query(
+            script_uri,
+            "INSERT INTO tasks (title) VALUES ($1) RETURNING done",
+            &[serde_json::json!("write tests")],
+            &options,
+        )
+        .expect("Migrated table should exist");
+        assert_eq!(inserted.rows[0]["done"], false);
+
+        let metadata = get_script_metadata(script_uri).expect("Should get metadata");
+        assert_eq!(metadata.schema_version, Some(2));
+
+        // Editing an applied migration is rejected
+        let edited = vec![ScriptMigration {
+            version: 1,
+            up: vec!["CREATE TABLE tasks (id BIGSERIAL PRIMARY KEY)".to_string()],
+        }];
+        let err = migrate_script_schema(script_uri, &edited).unwrap_err();
+        assert!(matches!(err, AppError::Validation { .. }));
+
+        // A failing migration is not recorded
+        let failing = vec![ScriptMigration {
+            version: 3,
+            up: vec![
+                "ALTER TABLE tasks ADD COLUMN due TIMESTAMPTZ".to_string(),
+                "ALTER TABLE missing ADD COLUMN x INTEGER".to_string(),
+            ],
+        }];
+        assert!(migrate_script_schema(script_uri, &failing).is_err());
+        let summary = migrate_script_schema(script_uri, &[]).expect("Should migrate");
+        assert_eq!(summary.version, Some(2));
+
+        // Duplicate versions and statements outside the sandbox are rejected
+        let duplicate = vec![migrations[1].clone(), migrations[1].clone()];
+        assert!(migrate_script_schema(script_uri, &duplicate).is_err());
+        let forbidden = vec![ScriptMigration {
+            version: 4,
+            up: vec!["CREATE ROLE intruder".to_string()],
+        }];
+        assert!(migrate_script_schema(script_uri, &forbidden).is_err());
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -6940,7 +14929,7 @@ mod tests {
         let script_code = "console.log('owned script');";
 
         // Create a new script with an owner
-        let result = upsert_script_with_owner(script_uri, script_code, Some(owner_user_id));
+        let result = upsert_script_with_owner(script_uri, script_code, Some(owner_user_id), false);
         assert!(
             result.is_ok(),
             "Should successfully create script with owner"
@@ -7001,7 +14990,8 @@ mod tests {
         // Now update the script with a user (simulating editing in the editor)
         let editor_user_id = "editor-user-456";
         let updated_code = "console.log('backfill test - updated');";
-        let result = upsert_script_with_owner(script_uri, updated_code, Some(editor_user_id));
+        let result =
+            upsert_script_with_owner(script_uri, updated_code, Some(editor_user_id), false);
         assert!(result.is_ok(), "Should update script with owner backfill");
 
         // Verify the owner was backfilled
@@ -7027,12 +15017,12 @@ mod tests {
         let script_code = "console.log('original');";
 
         // Create script with owner
-        let result = upsert_script_with_owner(script_uri, script_code, Some(owner_user_id));
+        let result = upsert_script_with_owner(script_uri, script_code, Some(owner_user_id), false);
         assert!(result.is_ok(), "Should create script with owner");
 
         // Update script with same user
         let updated_code = "console.log('updated');";
-        let result = upsert_script_with_owner(script_uri, updated_code, Some(owner_user_id));
+        let result = upsert_script_with_owner(script_uri, updated_code, Some(owner_user_id), false);
         assert!(result.is_ok(), "Should update script");
 
         // Verify still only one owner
@@ -7054,8 +15044,9 @@ mod tests {
         let user_id = "admin-user";
         let script_code = "console.log('bootstrap script');";
 
-        // Try to create/update a bootstrap script with an owner
-        let result = upsert_script_with_owner(bootstrap_uri, script_code, Some(user_id));
+        // Try to create/update a bootstrap script with an owner; bootstrap
+        // scripts are system scripts, so the overwrite has to be forced
+        let result = upsert_script_with_owner(bootstrap_uri, script_code, Some(user_id), true);
         assert!(result.is_ok(), "Should succeed");
 
         // Verify it has no owners (bootstrap scripts are ownerless)
@@ -7081,7 +15072,7 @@ mod tests {
         let _ = delete_script(script_uri);
 
         // Create script with first owner
-        upsert_script_with_owner(script_uri, script_code, Some(owner1))
+        upsert_script_with_owner(script_uri, script_code, Some(owner1), false)
             .expect("Should create script");
 
         // Add second owner
@@ -7110,6 +15101,217 @@ mod tests {
         assert_eq!(owners_after[0], owner2, "Only owner2 should remain");
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_collaborators_add_remove() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://collaborator-script";
+        let owner = "collab-owner";
+        let collaborator = "collab-editor";
+
+        // Clean up any stale state from a prior run
+        let _ = delete_script(script_uri);
+
+        upsert_script_with_owner(script_uri, "console.log('collab');", Some(owner), false)
+            .expect("Should create script");
+
+        add_script_collaborator(script_uri, collaborator).expect("Should add collaborator");
+        // Adding twice is a no-op
+        add_script_collaborator(script_uri, collaborator).expect("Should ignore duplicate");
+
+        let collaborators = get_script_collaborators(script_uri).expect("Should get collaborators");
+        assert_eq!(collaborators, vec![collaborator.to_string()]);
+        assert!(user_collaborates_on_script(script_uri, collaborator).expect("Should check"));
+        assert!(!user_collaborates_on_script(script_uri, owner).expect("Should check"));
+
+        // Collaborators are not owners
+        assert!(!user_owns_script(script_uri, collaborator).expect("Should check ownership"));
+
+        assert!(remove_script_collaborator(script_uri, collaborator).expect("Should remove"));
+        assert!(
+            !remove_script_collaborator(script_uri, collaborator).expect("Should report missing")
+        );
+        assert!(
+            get_script_collaborators(script_uri)
+                .expect("Should get collaborators")
+                .is_empty()
+        );
+
+        let _ = delete_script(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_asset_headers_round_trip() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://asset-headers-script";
+        let asset_uri = "asset-headers/report.pdf";
+
+        upsert_script(script_uri, "console.log('headers');").expect("Should create script");
+        let now = std::time::SystemTime::now();
+        let headers =
+            HashMap::from([("Content-Disposition".to_string(), "attachment".to_string())]);
+        let asset = Asset {
+            uri: asset_uri.to_string(),
+            name: None,
+            mimetype: "application/pdf".to_string(),
+            content: b"%PDF".to_vec(),
+            created_at: now,
+            updated_at: now,
+            script_uri: script_uri.to_string(),
+            headers: headers.clone(),
+        };
+        upsert_asset(asset.clone()).expect("Should create asset");
+        assert_eq!(
+            fetch_asset(script_uri, asset_uri).map(|a| a.headers),
+            Some(headers)
+        );
+
+        // Server-controlled headers are rejected
+        let mut invalid = asset;
+        invalid.headers = HashMap::from([("Content-Length".to_string(), "1".to_string())]);
+        assert!(upsert_asset(invalid).is_err());
+
+        let _ = delete_script(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_deleted_script_can_be_restored_from_trash() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://trash-script";
+        let owner = "trash-owner";
+        let asset_uri = "trash-script/logo.svg";
+
+        // Re-creating the script discards any trashed copy from a prior run
+        upsert_script_with_owner(script_uri, "console.log('trash');", Some(owner), false)
+            .expect("Should create script");
+        add_script_collaborator(script_uri, "trash-editor").expect("Should add collaborator");
+        let now = std::time::SystemTime::now();
+        upsert_asset(Asset {
+            uri: asset_uri.to_string(),
+            name: None,
+            mimetype: "image/svg+xml".to_string(),
+            content: b"<svg/>".to_vec(),
+            created_at: now,
+            updated_at: now,
+            script_uri: script_uri.to_string(),
+            headers: HashMap::new(),
+        })
+        .expect("Should create asset");
+
+        assert!(delete_script(script_uri));
+        assert!(fetch_script(script_uri).is_none());
+        assert!(fetch_asset(script_uri, asset_uri).is_none());
+
+        let trashed = list_trashed_scripts().expect("Should list trash");
+        let entry = trashed
+            .iter()
+            .find(|s| s.uri == script_uri)
+            .expect("Deleted script should be in trash");
+        assert_eq!(entry.owners, vec![owner.to_string()]);
+        assert_eq!(entry.asset_count, 1);
+
+        restore_script(script_uri).expect("Should restore script");
+        assert_eq!(
+            fetch_script(script_uri).as_deref(),
+            Some("console.log('trash');")
+        );
+        assert!(user_owns_script(script_uri, owner).expect("Should check ownership"));
+        assert!(user_collaborates_on_script(script_uri, "trash-editor").expect("Should check"));
+        assert!(fetch_asset(script_uri, asset_uri).is_some());
+        assert!(
+            !list_trashed_scripts()
+                .expect("Should list trash")
+                .iter()
+                .any(|s| s.uri == script_uri)
+        );
+        assert!(restore_script(script_uri).is_err());
+
+        // Individually deleted assets are restored on their own
+        assert!(delete_asset(script_uri, asset_uri));
+        let trashed_assets = list_trashed_assets(script_uri).expect("Should list trashed assets");
+        assert_eq!(trashed_assets.len(), 1);
+        assert_eq!(trashed_assets[0].size, 6);
+        restore_asset(script_uri, asset_uri).expect("Should restore asset");
+        assert!(fetch_asset(script_uri, asset_uri).is_some());
+        assert!(restore_asset(script_uri, asset_uri).is_err());
+
+        let _ = delete_script(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_restored_script_keeps_every_column() {
+        use crate::security::script_manifests::{ScriptCapability, ScriptManifest};
+
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://trash-round-trip";
+
+        upsert_script_with_owner(script_uri, "console.log('round trip');", None, false)
+            .expect("Should create script");
+        set_script_privileged(script_uri, true).expect("Should set privileged");
+        set_script_storage_quota(script_uri, Some(4096)).expect("Should set quota");
+        set_script_execution_timeout(script_uri, Some(1500)).expect("Should set timeout");
+        set_script_memory_limit(script_uri, Some(32 * 1024 * 1024)).expect("Should set limit");
+        set_script_system(script_uri, true).expect("Should set system flag");
+        let source_map = StoredSourceMap {
+            source_map: r#"{"version":3,"mappings":""}"#.to_string(),
+            content_hash: "abc123".to_string(),
+        };
+        set_script_source_map(script_uri, Some(&source_map)).expect("Should set source map");
+        let manifest = ScriptManifest {
+            uri: script_uri.to_string(),
+            requested: vec![ScriptCapability::Fetch, ScriptCapability::Db],
+            approved: vec![ScriptCapability::Fetch],
+            approved_by: Some("trash-admin".to_string()),
+            approved_at: Some(Utc::now()),
+        };
+        assert!(update_script_manifest(&manifest).expect("Should store manifest"));
+
+        // System scripts are only trashed with force
+        assert!(try_delete_script(script_uri, true).expect("Should trash script"));
+        restore_script(script_uri).expect("Should restore script");
+
+        let metadata = get_script_metadata(script_uri).expect("Should get metadata");
+        assert!(metadata.privileged);
+        assert!(metadata.system);
+        assert_eq!(metadata.execution_timeout_ms, Some(1500));
+        assert_eq!(metadata.memory_limit_bytes, Some(32 * 1024 * 1024));
+        let usage = get_script_storage_usage(script_uri).expect("Should get usage");
+        assert_eq!(usage.quota_override, Some(4096));
+        assert_eq!(
+            get_script_source_map(script_uri).expect("Should get source map"),
+            Some(source_map)
+        );
+        let restored = get_script_manifest(script_uri)
+            .expect("Should get manifest")
+            .expect("Restored script should have a manifest");
+        assert_eq!(restored.requested, manifest.requested);
+        assert_eq!(restored.approved, manifest.approved);
+        assert_eq!(restored.approved_by, manifest.approved_by);
+        assert!(restored.approved_at.is_some());
+
+        assert!(is_system_script(script_uri).expect("Should check system flag"));
+        let _ = try_delete_script(script_uri, true);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_script_ownership_without_user() {
         if should_skip_db_tests() {
@@ -7123,7 +15325,7 @@ mod tests {
         let script_code = "console.log('no user');";
 
         // Create script without owner (None user_id)
-        let result = upsert_script_with_owner(script_uri, script_code, None);
+        let result = upsert_script_with_owner(script_uri, script_code, None, false);
         assert!(result.is_ok(), "Should create script even without user_id");
 
         // Verify script exists but has no owners
@@ -7137,4 +15339,119 @@ mod tests {
         let owners = get_script_owners(script_uri).expect("Should get owners");
         assert_eq!(owners.len(), 0, "Script should have no owners");
     }
+
+    #[test]
+    fn test_script_labels_normalization() {
+        let labels = ScriptLabels::new(
+            Some("  Billing API  ".to_string()),
+            vec![
+                " API ".to_string(),
+                "billing".to_string(),
+                "api".to_string(),
+                "".to_string(),
+            ],
+        )
+        .expect("valid labels");
+        assert_eq!(labels.description.as_deref(), Some("Billing API"));
+        assert_eq!(labels.tags, vec!["api", "billing"]);
+
+        let cleared = ScriptLabels::new(Some("   ".to_string()), Vec::new()).expect("valid");
+        assert_eq!(cleared.description, None);
+
+        assert!(ScriptLabels::new(None, vec!["has space".to_string()]).is_err());
+        assert!(ScriptLabels::new(None, vec!["x".repeat(MAX_SCRIPT_TAG_LENGTH + 1)]).is_err());
+        let too_many = (0..=MAX_SCRIPT_TAGS).map(|i| format!("tag{}", i)).collect();
+        assert!(ScriptLabels::new(None, too_many).is_err());
+    }
+
+    #[test]
+    fn test_script_metadata_filters() {
+        let mut metadata =
+            ScriptMetadata::new("https://example.com/billing".to_string(), String::new());
+        metadata.apply_labels(ScriptLabels {
+            description: Some("Invoices and payments".to_string()),
+            tags: vec!["api".to_string(), "billing".to_string()],
+        });
+
+        assert!(metadata.has_tags(&[]));
+        assert!(metadata.has_tags(&["API".to_string()]));
+        assert!(metadata.has_tags(&["api".to_string(), "billing".to_string()]));
+        assert!(!metadata.has_tags(&["api".to_string(), "admin".to_string()]));
+
+        assert!(metadata.matches_text("PAYMENTS"));
+        assert!(metadata.matches_text("example.com/bill"));
+        assert!(!metadata.matches_text("shipping"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_labels_operations() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://labels-script";
+        assert!(upsert_script(script_uri, "console.log('labels');").is_ok());
+
+        let labels = ScriptLabels::new(
+            Some("Labelled script".to_string()),
+            vec!["Test".to_string(), "labels".to_string()],
+        )
+        .expect("valid labels");
+        set_script_labels(script_uri, &labels).expect("Should set labels");
+
+        let fetched = get_script_labels(script_uri).expect("Should get labels");
+        assert_eq!(fetched, labels);
+
+        let metadata = get_all_script_metadata().expect("Should list metadata");
+        let listed = metadata
+            .iter()
+            .find(|m| m.uri == script_uri)
+            .expect("script listed");
+        assert_eq!(listed.tags, vec!["test", "labels"]);
+        assert_eq!(listed.description.as_deref(), Some("Labelled script"));
+
+        // Content updates must not reset labels
+        assert!(upsert_script(script_uri, "console.log('labels v2');").is_ok());
+        assert_eq!(
+            get_script_labels(script_uri).expect("Should get labels"),
+            labels
+        );
+
+        assert!(set_script_labels("test://missing-labels-script", &labels).is_err());
+
+        delete_script(script_uri);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_script_source_map_operations() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://source-map-script";
+        assert!(upsert_script(script_uri, "console.log('mapped');").is_ok());
+        assert_eq!(get_script_source_map(script_uri).unwrap(), None);
+
+        let stored = StoredSourceMap {
+            source_map: r#"{"version":3,"sources":["src/main.ts"],"names":[],"mappings":"AAAA"}"#
+                .to_string(),
+            content_hash: "abc".to_string(),
+        };
+        set_script_source_map(script_uri, Some(&stored)).expect("Should set source map");
+        assert_eq!(
+            get_script_source_map(script_uri).unwrap(),
+            Some(stored.clone())
+        );
+
+        set_script_source_map(script_uri, None).expect("Should clear source map");
+        assert_eq!(get_script_source_map(script_uri).unwrap(), None);
+
+        assert!(set_script_source_map("test://missing-source-map-script", Some(&stored)).is_err());
+
+        delete_script(script_uri);
+    }
 }
@@ -0,0 +1,424 @@
+//! JSON Schema validation of script route requests.
+//!
+//! Routes registered with the `schema` option of `routeRegistry.registerRoute`
+//! declare JSON Schemas for the request body, the query string and the path
+//! parameters. The schemas are compiled when the route index is built and
+//! checked before the handler runs: a request that does not match answers 400
+//! with every violation listed, so handlers can rely on the shape of their
+//! input. The same schemas describe the route in the generated OpenAPI
+//! document.
+//!
+//! Query strings, path parameters and URL-encoded or multipart form fields
+//! arrive as strings; a field whose schema property declares an `integer`,
+//! `number` or `boolean` type is converted before validation when it parses
+//! as one. Handlers still receive the original strings.
+//!
+//! The crate is built without remote or file `$ref` resolution, so a schema
+//! can only refer to its own definitions.
+
+use std::collections::HashMap;
+
+use jsonschema::Validator;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+/// Request schemas registered with a route (`registerRoute` option `schema`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RouteSchema {
+    /// Schema for the JSON body, or for the form fields of form posts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Schema for the query string, as an object of parameter names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<Value>,
+    /// Schema for the path parameters, as an object of parameter names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+/// Part of the request a violation was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestPart {
+    Body,
+    Query,
+    Params,
+}
+
+/// One way a request failed its route's schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    pub location: RequestPart,
+    /// JSON pointer to the offending value within the part; empty for the
+    /// part as a whole
+    pub path: String,
+    pub message: String,
+}
+
+/// Request body as read by the dispatcher
+#[derive(Debug, Clone, Copy)]
+pub enum RequestBody<'a> {
+    /// Raw bytes, validated as JSON
+    Raw(&'a [u8]),
+    /// Fields of a URL-encoded or multipart form
+    Form(&'a HashMap<String, String>),
+}
+
+/// Compiled form of a [`RouteSchema`]
+#[derive(Debug)]
+pub struct RouteValidator {
+    body: Option<CompiledSchema>,
+    query: Option<CompiledSchema>,
+    params: Option<CompiledSchema>,
+}
+
+#[derive(Debug)]
+struct CompiledSchema {
+    validator: Validator,
+    /// Declared types of top-level properties, used to convert string fields
+    property_types: HashMap<String, Vec<String>>,
+}
+
+impl RouteSchema {
+    /// Compiles the schemas, failing with a message naming the bad part
+    pub fn compile(&self) -> Result<RouteValidator, String> {
+        Ok(RouteValidator {
+            body: compile_part("body", self.body.as_ref())?,
+            query: compile_part("query", self.query.as_ref())?,
+            params: compile_part("params", self.params.as_ref())?,
+        })
+    }
+
+    /// OpenAPI parameter objects for the query and path parameter schemas
+    pub fn openapi_parameters(&self) -> Vec<Value> {
+        let mut parameters = Vec::new();
+        for (location, schema) in [("path", &self.params), ("query", &self.query)] {
+            let Some(schema) = schema else { continue };
+            let required = required_properties(schema);
+            let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                continue;
+            };
+            for (name, property) in properties {
+                let mut parameter = json!({
+                    "name": name,
+                    "in": location,
+                    "required": location == "path" || required.contains(&name.as_str()),
+                    "schema": property,
+                });
+                if let Some(description) = property.get("description") {
+                    parameter["description"] = description.clone();
+                }
+                parameters.push(parameter);
+            }
+        }
+        parameters
+    }
+
+    /// OpenAPI request body object for the body schema
+    pub fn openapi_request_body(&self) -> Option<Value> {
+        self.body.as_ref().map(|schema| {
+            json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            })
+        })
+    }
+}
+
+impl RouteValidator {
+    /// Checks a request against the route's schemas, returning every
+    /// violation found
+    pub fn validate(
+        &self,
+        body: RequestBody<'_>,
+        query: &HashMap<String, String>,
+        params: &HashMap<String, String>,
+    ) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations = Vec::new();
+
+        if let Some(schema) = &self.body {
+            let instance = match body {
+                RequestBody::Raw([]) => Some(Value::Null),
+                RequestBody::Raw(bytes) => match serde_json::from_slice(bytes) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        violations.push(SchemaViolation {
+                            location: RequestPart::Body,
+                            path: String::new(),
+                            message: format!("Request body is not valid JSON: {}", e),
+                        });
+                        None
+                    }
+                },
+                RequestBody::Form(fields) => Some(schema.coerce_fields(fields)),
+            };
+            if let Some(instance) = instance {
+                schema.collect_violations(RequestPart::Body, &instance, &mut violations);
+            }
+        }
+        if let Some(schema) = &self.query {
+            let instance = schema.coerce_fields(query);
+            schema.collect_violations(RequestPart::Query, &instance, &mut violations);
+        }
+        if let Some(schema) = &self.params {
+            let instance = schema.coerce_fields(params);
+            schema.collect_violations(RequestPart::Params, &instance, &mut violations);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl CompiledSchema {
+    fn coerce_fields(&self, fields: &HashMap<String, String>) -> Value {
+        Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| {
+                    let types = self.property_types.get(name).map(Vec::as_slice);
+                    (name.clone(), coerce_field(value, types.unwrap_or_default()))
+                })
+                .collect::<Map<_, _>>(),
+        )
+    }
+
+    fn collect_violations(
+        &self,
+        location: RequestPart,
+        instance: &Value,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        // Messages are masked so they do not echo submitted values
+        violations.extend(
+            self.validator
+                .iter_errors(instance)
+                .map(|error| SchemaViolation {
+                    location,
+                    path: error.instance_path().as_str().to_string(),
+                    message: error.masked().to_string(),
+                }),
+        );
+    }
+}
+
+fn compile_part(name: &str, schema: Option<&Value>) -> Result<Option<CompiledSchema>, String> {
+    let Some(schema) = schema else {
+        return Ok(None);
+    };
+    let validator = jsonschema::options()
+        .build(schema)
+        .map_err(|e| format!("schema.{} is not a valid JSON Schema: {}", name, e))?;
+    let property_types = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, property)| {
+                    let types = match property.get("type") {
+                        Some(Value::String(ty)) => vec![ty.clone()],
+                        Some(Value::Array(types)) => types
+                            .iter()
+                            .filter_map(|ty| ty.as_str().map(str::to_string))
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    (name.clone(), types)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Some(CompiledSchema {
+        validator,
+        property_types,
+    }))
+}
+
+/// Converts a string field to the first declared scalar type it parses as,
+/// leaving it a string otherwise
+fn coerce_field(value: &str, types: &[String]) -> Value {
+    for ty in types {
+        match ty.as_str() {
+            "integer" => {
+                if let Ok(number) = value.parse::<i64>() {
+                    return Value::from(number);
+                }
+            }
+            "number" => {
+                if let Some(number) = value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                {
+                    return Value::Number(number);
+                }
+            }
+            "boolean" => match value {
+                "true" => return Value::Bool(true),
+                "false" => return Value::Bool(false),
+                _ => {}
+            },
+            "string" => break,
+            _ => {}
+        }
+    }
+    Value::String(value.to_string())
+}
+
+fn required_properties(schema: &Value) -> Vec<&str> {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn schema() -> RouteSchema {
+        serde_json::from_value(json!({
+            "body": {
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": { "type": "string", "minLength": 1 },
+                    "age": { "type": "integer", "minimum": 0 }
+                }
+            },
+            "query": {
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "maximum": 100, "description": "Page size" },
+                    "draft": { "type": "boolean" }
+                }
+            },
+            "params": {
+                "type": "object",
+                "properties": { "id": { "type": "string", "pattern": "^[0-9]+$" } }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_request() {
+        let validator = schema().compile().unwrap();
+        let query = fields(&[("limit", "10"), ("draft", "true")]);
+        let params = fields(&[("id", "42")]);
+
+        assert_eq!(
+            validator.validate(
+                RequestBody::Raw(br#"{"name":"Ada","age":36}"#),
+                &query,
+                &params
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validator.validate(
+                RequestBody::Form(&fields(&[("name", "Ada"), ("age", "36")])),
+                &query,
+                &params
+            ),
+            Ok(())
+        );
+
+        let violations = validator
+            .validate(
+                RequestBody::Raw(br#"{"age":-1}"#),
+                &fields(&[("limit", "500"), ("draft", "maybe")]),
+                &fields(&[("id", "abc")]),
+            )
+            .unwrap_err();
+        let mut found: Vec<_> = violations
+            .iter()
+            .map(|v| format!("{:?}{}", v.location, v.path))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                "Body",
+                "Body/age",
+                "Params/id",
+                "Query/draft",
+                "Query/limit"
+            ]
+        );
+        assert!(violations.iter().all(|v| !v.message.contains("maybe")));
+
+        let violations = validator
+            .validate(RequestBody::Raw(b"{not json"), &query, &params)
+            .unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert!(
+            violations[0]
+                .message
+                .starts_with("Request body is not valid JSON")
+        );
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_schema() {
+        let bad: RouteSchema =
+            serde_json::from_value(json!({ "query": { "type": "nope" } })).unwrap();
+        let err = bad.compile().unwrap_err();
+        assert!(err.starts_with("schema.query"), "{}", err);
+
+        let remote: RouteSchema = serde_json::from_value(
+            json!({ "body": { "$ref": "https://example.com/schema.json" } }),
+        )
+        .unwrap();
+        assert!(remote.compile().is_err());
+
+        assert!(serde_json::from_value::<RouteSchema>(json!({ "headers": {} })).is_err());
+    }
+
+    #[test]
+    fn test_coerce_field() {
+        let types = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(coerce_field("7", &types(&["integer"])), json!(7));
+        assert_eq!(coerce_field("7.5", &types(&["integer"])), json!("7.5"));
+        assert_eq!(coerce_field("7.5", &types(&["number"])), json!(7.5));
+        assert_eq!(coerce_field("false", &types(&["boolean"])), json!(false));
+        assert_eq!(
+            coerce_field("7", &types(&["string", "integer"])),
+            json!("7")
+        );
+        assert_eq!(coerce_field("7", &[]), json!("7"));
+    }
+
+    #[test]
+    fn test_openapi_output() {
+        let schema = schema();
+        let parameters = schema.openapi_parameters();
+        assert_eq!(parameters.len(), 3);
+        assert_eq!(parameters[0]["in"], "path");
+        assert_eq!(parameters[0]["required"], true);
+        let limit = parameters.iter().find(|p| p["name"] == "limit").unwrap();
+        assert_eq!(limit["in"], "query");
+        assert_eq!(limit["required"], false);
+        assert_eq!(limit["description"], "Page size");
+
+        let body = schema.openapi_request_body().unwrap();
+        assert_eq!(
+            body["content"]["application/json"]["schema"]["required"],
+            json!(["name"])
+        );
+        assert!(RouteSchema::default().openapi_request_body().is_none());
+    }
+}
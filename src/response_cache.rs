@@ -0,0 +1,530 @@
+//! Response cache of script routes.
+//!
+//! Routes registered with the `cache` option of `routeRegistry.registerRoute`
+//! keep the responses of their GET handler in memory and answer later GET
+//! and HEAD requests from there without running JavaScript. Asset routes
+//! registered with `cache` keep their responses the same way, saving the
+//! database read. Responses are keyed by the request's tenant, host, path
+//! and query string, and by the values of the request headers the route
+//! lists in `vary`.
+//!
+//! A response past its TTL is still served for the route's stale window
+//! (`staleWhileRevalidateSeconds`, or the configured default) marked
+//! `X-Cache: STALE`, and the first request that gets it refreshes it in the
+//! background, so a traffic spike on an expired entry never waits for a
+//! slow handler. Only when the stale window has passed as well does a
+//! request run the handler itself.
+//!
+//! Only anonymous requests use the cache, since a signed-in user's response
+//! may hold their own data. Only 200 responses without `Set-Cookie` and
+//! without `Cache-Control: private` or `no-store` are stored.
+//!
+//! `cache.purgeRoute(path)` drops the cached responses of a script's route
+//! on every server: the purge is applied locally and sent to the other
+//! instances over the `route_cache_purge` database notification channel.
+//! Changing or deleting a script drops all of its cached responses.
+
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::Response;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+
+use crate::config::CacheConfig;
+use crate::js_engine::JsHttpResponse;
+
+/// Database notification channel carrying purges to other instances
+pub const PURGE_CHANNEL: &str = "route_cache_purge";
+
+/// Response header telling whether a response came from the cache
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// Request headers one route may vary its cached responses on
+pub const MAX_VARY_HEADERS: usize = 8;
+
+/// Response caching registered with a route (`registerRoute` option `cache`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteCache {
+    /// How long a response is served from the cache
+    pub ttl_seconds: u64,
+    /// Lowercased request headers whose values are part of the cache key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vary: Vec<String>,
+    /// How long an expired response is still served while it is refreshed;
+    /// defaults to `javascript.cache.stale_while_revalidate_seconds`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_while_revalidate_seconds: Option<u64>,
+}
+
+impl RouteCache {
+    /// Check the options given to `registerRoute`, normalizing `vary`
+    pub fn new(ttl_seconds: u64, vary: Vec<String>) -> Result<Self, String> {
+        if ttl_seconds == 0 {
+            return Err("cache.ttlSeconds must be a positive integer".to_string());
+        }
+        if vary.len() > MAX_VARY_HEADERS {
+            return Err(format!(
+                "cache.vary must not list more than {} headers",
+                MAX_VARY_HEADERS
+            ));
+        }
+        let mut names = Vec::with_capacity(vary.len());
+        for name in vary {
+            if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(format!("cache.vary entry '{}' is not a header name", name));
+            }
+            names.push(name.to_ascii_lowercase());
+        }
+        names.sort();
+        names.dedup();
+        Ok(Self {
+            ttl_seconds,
+            vary: names,
+            stale_while_revalidate_seconds: None,
+        })
+    }
+}
+
+/// A request to a route with a response cache, identified for lookups
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    script_uri: String,
+    path: String,
+    key: String,
+}
+
+impl CacheKey {
+    /// The key of one representation of the response, such as a content
+    /// encoding
+    pub fn with_variant(mut self, variant: &str) -> Self {
+        self.key.push_str("\n#");
+        self.key.push_str(variant);
+        self
+    }
+}
+
+/// Purge sent over `PURGE_CHANNEL`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurgeMessage {
+    pub script_uri: String,
+    /// Request path or `/prefix/*`; None purges all of the script's responses
+    pub path: Option<String>,
+    pub server_id: String,
+}
+
+/// A cached response found for a request
+pub struct Hit {
+    /// The response, marked `X-Cache: HIT` or `STALE`
+    pub response: Response,
+    /// The response is stale and this caller should refresh it, then
+    /// [`store`] the new response or call [`refresh_failed`]
+    pub refresh: bool,
+}
+
+struct CachedResponse {
+    script_uri: String,
+    path: String,
+    response: JsHttpResponse,
+    stored_at: Instant,
+    expires_at: Instant,
+    /// Served stale until then, and dropped after
+    stale_until: Instant,
+    /// A request is refreshing the response
+    refreshing: bool,
+}
+
+struct Limits {
+    max_responses: usize,
+    max_response_bytes: usize,
+    max_ttl_seconds: u64,
+    stale_while_revalidate_seconds: u64,
+}
+
+static LIMITS: OnceLock<Mutex<Limits>> = OnceLock::new();
+static RESPONSES: OnceLock<Mutex<LruCache<String, CachedResponse>>> = OnceLock::new();
+
+fn limits_from(config: &CacheConfig) -> Limits {
+    Limits {
+        max_responses: config.max_responses,
+        max_response_bytes: config.max_response_bytes,
+        max_ttl_seconds: config.max_ttl_seconds,
+        stale_while_revalidate_seconds: config.stale_while_revalidate_seconds,
+    }
+}
+
+fn lock_limits() -> MutexGuard<'static, Limits> {
+    match LIMITS
+        .get_or_init(|| Mutex::new(limits_from(&CacheConfig::default())))
+        .lock()
+    {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn capacity(max_responses: usize) -> NonZeroUsize {
+    NonZeroUsize::new(max_responses).unwrap_or(NonZeroUsize::MIN)
+}
+
+fn lock_responses() -> MutexGuard<'static, LruCache<String, CachedResponse>> {
+    let responses = RESPONSES.get_or_init(|| {
+        Mutex::new(LruCache::new(capacity(
+            CacheConfig::default().max_responses,
+        )))
+    });
+    match responses.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Response cache mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Apply the cache configuration. Called once at server startup.
+pub fn configure(config: &CacheConfig) {
+    *lock_limits() = limits_from(config);
+    lock_responses().resize(capacity(config.max_responses));
+}
+
+/// Whether a request may be answered from the cache: anonymous GET and
+/// HEAD requests
+pub fn applies_to(method: &str, authenticated: bool) -> bool {
+    !authenticated && matches!(method, "GET" | "HEAD")
+}
+
+/// Key of a request to a route with a response cache
+pub fn request_key(
+    route: &RouteCache,
+    script_uri: &str,
+    tenant: Option<&str>,
+    host: Option<&str>,
+    path: &str,
+    query: &str,
+    headers: &HeaderMap,
+) -> CacheKey {
+    let host = host
+        .and_then(crate::route_index::normalize_host)
+        .unwrap_or_default();
+    let mut key = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        tenant.unwrap_or(""),
+        host,
+        script_uri,
+        path,
+        query
+    );
+    for name in &route.vary {
+        let values: Vec<&str> = headers
+            .get_all(name.as_str())
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        key.push('\n');
+        key.push_str(name);
+        key.push(':');
+        key.push_str(&values.join(","));
+    }
+    CacheKey {
+        script_uri: script_uri.to_string(),
+        path: path.to_string(),
+        key,
+    }
+}
+
+/// The cached response for `key`, fresh or within its stale window, with
+/// its age
+pub fn lookup(key: &CacheKey) -> Option<Hit> {
+    let (response, age, stale, refresh) = {
+        let mut responses = lock_responses();
+        let cached = responses.get_mut(&key.key)?;
+        let now = Instant::now();
+        if cached.stale_until <= now {
+            responses.pop(&key.key);
+            return None;
+        }
+        let stale = cached.expires_at <= now;
+        // Only the first request to find the response stale refreshes it
+        let refresh = stale && !cached.refreshing;
+        if refresh {
+            cached.refreshing = true;
+        }
+        (
+            cached.response.clone(),
+            now.duration_since(cached.stored_at).as_secs(),
+            stale,
+            refresh,
+        )
+    };
+    let mut response = crate::build_http_response_from_js(response);
+    let headers = response.headers_mut();
+    headers.insert(
+        CACHE_STATUS_HEADER,
+        HeaderValue::from_static(if stale { "STALE" } else { "HIT" }),
+    );
+    headers.insert(axum::http::header::AGE, HeaderValue::from(age));
+    Some(Hit { response, refresh })
+}
+
+/// Let a later request refresh a stale response after this refresh did not
+/// produce a cacheable one
+pub fn refresh_failed(key: &CacheKey) {
+    if let Some(cached) = lock_responses().peek_mut(&key.key) {
+        cached.refreshing = false;
+    }
+}
+
+/// Whether a handler's response may be stored
+fn cacheable(response: &JsHttpResponse, max_response_bytes: usize) -> bool {
+    if response.status != 200 || response.body.len() > max_response_bytes {
+        return false;
+    }
+    !response.headers.iter().any(|(name, value)| {
+        let name = name.to_ascii_lowercase();
+        name == "set-cookie"
+            || (name == "cache-control" && {
+                let value = value.to_ascii_lowercase();
+                value.contains("no-store") || value.contains("private")
+            })
+    })
+}
+
+/// Store a handler's response for later requests with the same key.
+/// Returns whether it was stored.
+pub fn store(route: &RouteCache, key: CacheKey, response: &JsHttpResponse) -> bool {
+    let (max_response_bytes, ttl, stale_window) = {
+        let limits = lock_limits();
+        let stale_window = route
+            .stale_while_revalidate_seconds
+            .unwrap_or(limits.stale_while_revalidate_seconds);
+        (
+            limits.max_response_bytes,
+            Duration::from_secs(route.ttl_seconds.min(limits.max_ttl_seconds)),
+            Duration::from_secs(stale_window.min(limits.max_ttl_seconds)),
+        )
+    };
+    if !cacheable(response, max_response_bytes) {
+        refresh_failed(&key);
+        return false;
+    }
+    let now = Instant::now();
+    lock_responses().put(
+        key.key,
+        CachedResponse {
+            script_uri: key.script_uri,
+            path: key.path,
+            response: response.clone(),
+            stored_at: now,
+            expires_at: now + ttl,
+            stale_until: now + ttl + stale_window,
+            refreshing: false,
+        },
+    );
+    true
+}
+
+/// Whether a cached request path is covered by a purge of `pattern`, a
+/// request path or a `/prefix/*`
+fn path_matches(pattern: &str, path: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) if prefix.ends_with('/') => path.starts_with(prefix),
+        _ => pattern == path,
+    }
+}
+
+/// Drop cached responses of `script_uri` on this instance: those for
+/// `path` (a request path or `/prefix/*`), or all of them
+pub fn purge_local(script_uri: &str, path: Option<&str>) -> usize {
+    let mut responses = lock_responses();
+    let keys: Vec<String> = responses
+        .iter()
+        .filter(|(_, cached)| {
+            cached.script_uri == script_uri
+                && path.is_none_or(|pattern| path_matches(pattern, &cached.path))
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+    for key in &keys {
+        responses.pop(key);
+    }
+    keys.len()
+}
+
+/// Drop every cached response on this instance
+pub fn purge_all() -> usize {
+    let mut responses = lock_responses();
+    let purged = responses.len();
+    responses.clear();
+    purged
+}
+
+/// Drop all cached responses of a script after it or one of its assets
+/// changed
+pub fn purge_script(script_uri: &str) {
+    let purged = purge_local(script_uri, None);
+    if purged > 0 {
+        debug!(
+            "Dropped {} cached responses of changed script {}",
+            purged, script_uri
+        );
+    }
+}
+
+/// Drop the cached responses of a script's route on every instance;
+/// returns how many were dropped on this one
+pub fn purge_route(script_uri: &str, path: &str) -> Result<usize, String> {
+    if !path.starts_with('/') {
+        return Err("Path must start with '/'".to_string());
+    }
+    let purged = purge_local(script_uri, Some(path));
+    broadcast_purge(script_uri, path);
+    Ok(purged)
+}
+
+fn broadcast_purge(script_uri: &str, path: &str) {
+    let (Some(db), Some(server_id)) = (
+        crate::database::get_global_database(),
+        crate::notifications::get_server_id(),
+    ) else {
+        return;
+    };
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let message = match serde_json::to_string(&PurgeMessage {
+        script_uri: script_uri.to_string(),
+        path: Some(path.to_string()),
+        server_id,
+    }) {
+        Ok(message) => message,
+        Err(e) => {
+            error!("Failed to serialize response cache purge: {}", e);
+            return;
+        }
+    };
+    handle.spawn(async move {
+        if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(PURGE_CHANNEL)
+            .bind(&message)
+            .execute(db.pool())
+            .await
+        {
+            error!("Failed to broadcast response cache purge: {}", e);
+        }
+    });
+}
+
+/// Apply a purge received from another instance
+pub fn apply_purge(message: &PurgeMessage) {
+    let purged = purge_local(&message.script_uri, message.path.as_deref());
+    debug!(
+        "Purged {} cached responses of {} from server {}",
+        purged, message.script_uri, message.server_id
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route() -> RouteCache {
+        RouteCache::new(60, vec!["Accept-Language".to_string()]).unwrap()
+    }
+
+    fn key(path: &str, query: &str, language: &str) -> CacheKey {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-language", HeaderValue::from_str(language).unwrap());
+        request_key(
+            &route(),
+            "https://example.com/response-cache-unit",
+            None,
+            Some("example.com"),
+            path,
+            query,
+            &headers,
+        )
+    }
+
+    #[test]
+    fn test_route_cache_options() {
+        assert_eq!(route().vary, vec!["accept-language".to_string()]);
+        assert!(RouteCache::new(0, Vec::new()).is_err());
+        assert!(RouteCache::new(60, vec!["bad header".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_applies_to_anonymous_reads() {
+        assert!(applies_to("GET", false));
+        assert!(applies_to("HEAD", false));
+        assert!(!applies_to("GET", true));
+        assert!(!applies_to("POST", false));
+    }
+
+    #[test]
+    fn test_store_lookup_and_purge() {
+        let uri = "https://example.com/response-cache-unit";
+        let mut response = JsHttpResponse::new(200, b"tere".to_vec());
+        assert!(store(&route(), key("/products/1", "", "et"), &response));
+        let hit = lookup(&key("/products/1", "", "et")).unwrap();
+        assert!(!hit.refresh);
+        assert_eq!(hit.response.headers()[CACHE_STATUS_HEADER], "HIT");
+        assert!(lookup(&key("/products/1", "", "fi")).is_none());
+        assert!(lookup(&key("/products/1", "a=1", "et")).is_none());
+
+        assert!(store(&route(), key("/products/2", "", "et"), &response));
+        assert_eq!(purge_local(uri, Some("/products/1")), 1);
+        assert!(lookup(&key("/products/1", "", "et")).is_none());
+        assert_eq!(purge_local(uri, Some("/products/*")), 1);
+
+        response
+            .headers
+            .insert("Set-Cookie".to_string(), "a=b".to_string());
+        assert!(!store(&route(), key("/products/3", "", "et"), &response));
+        assert!(!store(
+            &route(),
+            key("/products/3", "", "et"),
+            &JsHttpResponse::new(404, Vec::new())
+        ));
+    }
+
+    #[test]
+    fn test_stale_responses_are_refreshed_once() {
+        let mut route = route();
+        route.ttl_seconds = 0;
+        route.stale_while_revalidate_seconds = Some(60);
+        let stale_key = key("/stale", "", "et");
+        assert!(store(
+            &route,
+            stale_key.clone(),
+            &JsHttpResponse::new(200, b"old".to_vec())
+        ));
+
+        let first = lookup(&stale_key).unwrap();
+        assert!(first.refresh);
+        assert_eq!(first.response.headers()[CACHE_STATUS_HEADER], "STALE");
+        assert!(!lookup(&stale_key).unwrap().refresh);
+        refresh_failed(&stale_key);
+        assert!(lookup(&stale_key).unwrap().refresh);
+
+        route.stale_while_revalidate_seconds = Some(0);
+        assert!(store(
+            &route,
+            stale_key.clone(),
+            &JsHttpResponse::new(200, b"new".to_vec())
+        ));
+        assert!(lookup(&stale_key).is_none());
+    }
+
+    #[test]
+    fn test_path_matches() {
+        assert!(path_matches("/a", "/a"));
+        assert!(!path_matches("/a", "/a/b"));
+        assert!(path_matches("/a/*", "/a/b"));
+        assert!(!path_matches("/a/*", "/ab"));
+    }
+}
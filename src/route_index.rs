@@ -4,13 +4,24 @@
 //! read including all script contents) twice per request. This module builds
 //! the lookup table once and serves matching from memory; script changes
 //! invalidate the index and the next request rebuilds it.
+//!
+//! Routes registered with `hosts` are only served for requests whose Host
+//! header names one of them. A request is matched against the routes of its
+//! host first and falls back to the routes registered for any host.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, RwLock};
 
-use tracing::debug;
+use serde::Serialize;
+use tracing::{debug, warn};
 
+use crate::auth::RouteAuth;
+use crate::idempotency::RouteIdempotency;
+use crate::parsers::RouteUploads;
+use crate::rate_limit_rules::RateLimitRule;
 use crate::repository::{self, Repository as _};
+use crate::request_schema::RouteValidator;
+use crate::response_cache::RouteCache;
 
 /// Result of a route lookup.
 #[derive(Debug)]
@@ -19,6 +30,8 @@ pub enum RouteLookup {
     Handler {
         script_uri: String,
         handler_name: String,
+        /// Pattern the route was registered under
+        route: String,
         /// Parameters extracted from `:param` path segments
         params: HashMap<String, String>,
         /// True when a HEAD request was served by falling back to the path's
@@ -26,9 +39,30 @@ pub enum RouteLookup {
         /// caller must run the handler as usual but drop the response body
         /// before returning it, per RFC 7231 §4.3.2.
         strip_body: bool,
+        /// Rate limit registered with the route, checked before the handler
+        /// runs
+        rate_limit: Option<RateLimitRule>,
+        /// Idempotency registered with the route
+        idempotency: Option<RouteIdempotency>,
+        /// Upload rules registered with the route
+        uploads: Option<Arc<RouteUploads>>,
+        /// Request schemas registered with the route, compiled
+        schema: Option<Arc<RouteValidator>>,
+        /// Handler timeout set for the route, or else for its script, not
+        /// yet bounded by `javascript.max_script_execution_timeout_ms`
+        execution_timeout_ms: Option<u64>,
+        /// Response caching registered with the route
+        cache: Option<Arc<RouteCache>>,
+        /// Sign-in requirement registered with the route
+        auth: Option<Arc<RouteAuth>>,
+    },
+    /// The path is registered, but not for the requested method (HTTP 405,
+    /// or the answer to an OPTIONS request).
+    MethodNotAllowed {
+        /// Methods the path is registered for, with HEAD when GET is and
+        /// OPTIONS; sorted, for the `Allow` header
+        allowed: Vec<String>,
     },
-    /// The path is registered, but not for the requested method (HTTP 405).
-    MethodNotAllowed,
     /// No registration matches the path (HTTP 404).
     NotFound,
 }
@@ -37,6 +71,14 @@ pub enum RouteLookup {
 struct RouteTarget {
     script_uri: String,
     handler_name: String,
+    route: String,
+    rate_limit: Option<RateLimitRule>,
+    idempotency: Option<RouteIdempotency>,
+    uploads: Option<Arc<RouteUploads>>,
+    schema: Option<Arc<RouteValidator>>,
+    execution_timeout_ms: Option<u64>,
+    cache: Option<Arc<RouteCache>>,
+    auth: Option<Arc<RouteAuth>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -66,13 +108,50 @@ impl PatternRoute {
 }
 
 #[derive(Debug, Default)]
-struct IndexInner {
+struct RouteTable {
     /// (path, method) -> target, for patterns without params or wildcards
     exact: HashMap<(String, String), RouteTarget>,
     /// Param and wildcard patterns, competing on specificity at lookup time
     patterns: Vec<PatternRoute>,
 }
 
+impl RouteTable {
+    fn insert(&mut self, pattern: &str, method: &str, target: RouteTarget) {
+        if pattern.ends_with("/*") {
+            self.patterns.push(PatternRoute {
+                // Keep the trailing '/' so "/api/*" matches "/api/x" but
+                // not "/apix"
+                pattern: pattern[..pattern.len() - 1].to_string(),
+                method: method.to_string(),
+                kind: PatternKind::Wildcard,
+                specificity: calculate_route_specificity(pattern),
+                target,
+            });
+        } else if pattern.split('/').any(|part| part.starts_with(':')) {
+            self.patterns.push(PatternRoute {
+                pattern: pattern.to_string(),
+                method: method.to_string(),
+                kind: PatternKind::Param,
+                specificity: calculate_route_specificity(pattern),
+                target,
+            });
+        } else {
+            // Scripts are inserted in precedence order; the first one wins
+            self.exact
+                .entry((pattern.to_string(), method.to_string()))
+                .or_insert(target);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct IndexInner {
+    /// Routes registered without hosts
+    any_host: RouteTable,
+    /// Normalized host name -> routes registered for that host
+    by_host: HashMap<String, RouteTable>,
+}
+
 static INDEX: RwLock<Option<Arc<IndexInner>>> = RwLock::new(None);
 
 /// Drops the cached index; the next lookup rebuilds it from script metadata.
@@ -83,6 +162,12 @@ pub fn invalidate() {
     }
 }
 
+/// Rebuilds the index now if a script change invalidated it, so the next
+/// request does not pay for the rebuild.
+pub async fn warm() -> Result<(), String> {
+    current_index().await.map(|_| ())
+}
+
 /// Returns the current index, rebuilding it from script metadata if a script
 /// change invalidated it. Concurrent rebuilds are harmless (last write wins).
 async fn current_index() -> Result<Arc<IndexInner>, String> {
@@ -99,9 +184,10 @@ async fn current_index() -> Result<Arc<IndexInner>, String> {
 
     let inner = build_index(&metadata);
     debug!(
-        "Rebuilt route index: {} exact routes, {} pattern routes",
-        inner.exact.len(),
-        inner.patterns.len()
+        "Rebuilt route index: {} exact routes, {} pattern routes, {} virtual hosts",
+        inner.any_host.exact.len(),
+        inner.any_host.patterns.len(),
+        inner.by_host.len()
     );
 
     let index = Arc::new(inner);
@@ -113,37 +199,49 @@ async fn current_index() -> Result<Arc<IndexInner>, String> {
 
 fn build_index(metadata: &[repository::ScriptMetadata]) -> IndexInner {
     let mut inner = IndexInner::default();
-    for script in metadata {
-        if !script.initialized || script.registrations.is_empty() {
-            continue;
-        }
+    for script in by_precedence(metadata) {
         for ((pattern, method), route_meta) in &script.registrations {
+            // Schemas are checked when registered; one that no longer
+            // compiles leaves the route unvalidated rather than unreachable
+            let schema = route_meta
+                .schema
+                .as_ref()
+                .and_then(|schema| match schema.compile() {
+                    Ok(validator) => Some(Arc::new(validator)),
+                    Err(e) => {
+                        warn!(
+                            "Ignoring request schema of {} {} in {}: {}",
+                            method, pattern, script.uri, e
+                        );
+                        None
+                    }
+                });
             let target = RouteTarget {
                 script_uri: script.uri.clone(),
                 handler_name: route_meta.handler_name.clone(),
+                route: pattern.clone(),
+                rate_limit: route_meta
+                    .rate_limit
+                    .as_ref()
+                    .map(|limit| RateLimitRule::for_route(method, pattern, limit)),
+                idempotency: route_meta.idempotency.clone(),
+                uploads: route_meta.uploads.clone().map(Arc::new),
+                schema,
+                execution_timeout_ms: route_meta.timeout_ms.or(script.execution_timeout_ms),
+                cache: route_meta.cache.clone().map(Arc::new),
+                auth: route_meta.auth.clone().map(Arc::new),
             };
-            if pattern.ends_with("/*") {
-                inner.patterns.push(PatternRoute {
-                    // Keep the trailing '/' so "/api/*" matches "/api/x" but
-                    // not "/apix"
-                    pattern: pattern[..pattern.len() - 1].to_string(),
-                    method: method.clone(),
-                    kind: PatternKind::Wildcard,
-                    specificity: calculate_route_specificity(pattern),
-                    target,
-                });
-            } else if pattern.split('/').any(|part| part.starts_with(':')) {
-                inner.patterns.push(PatternRoute {
-                    pattern: pattern.clone(),
-                    method: method.clone(),
-                    kind: PatternKind::Param,
-                    specificity: calculate_route_specificity(pattern),
-                    target,
-                });
-            } else {
-                inner
-                    .exact
-                    .insert((pattern.clone(), method.clone()), target);
+            let pattern = &crate::path_normalization::route_pattern(pattern);
+            if route_meta.hosts.is_empty() {
+                inner.any_host.insert(pattern, method, target);
+                continue;
+            }
+            for host in &route_meta.hosts {
+                inner.by_host.entry(host.clone()).or_default().insert(
+                    pattern,
+                    method,
+                    target.clone(),
+                );
             }
         }
     }
@@ -159,39 +257,90 @@ fn build_index(metadata: &[repository::ScriptMetadata]) -> IndexInner {
 /// registered (RFC 7231 §4.3.2): a script that explicitly registers HEAD
 /// always wins, otherwise the GET handler runs and [`RouteLookup::Handler`]
 /// is returned with `strip_body: true` so the caller drops the body.
-pub async fn lookup(path: &str, method: &str) -> Result<RouteLookup, String> {
+///
+/// `host` is the request's Host header; routes registered for that host are
+/// tried before routes registered for any host.
+pub async fn lookup(host: Option<&str>, path: &str, method: &str) -> Result<RouteLookup, String> {
     let index = current_index().await?;
-    Ok(resolve(&index, path, method))
+    Ok(resolve(&index, host, path, method))
+}
+
+fn resolve(index: &IndexInner, host: Option<&str>, path: &str, method: &str) -> RouteLookup {
+    let host_table = host
+        .and_then(normalize_host)
+        .and_then(|host| index.by_host.get(&host));
+    let mut allowed: Option<Vec<String>> = None;
+    for table in host_table.into_iter().chain([&index.any_host]) {
+        match resolve_table(table, path, method) {
+            RouteLookup::MethodNotAllowed { allowed: methods } => {
+                allowed.get_or_insert_default().extend(methods)
+            }
+            RouteLookup::NotFound => {}
+            handler => return handler,
+        }
+    }
+    match allowed {
+        Some(mut allowed) => {
+            allowed.sort();
+            allowed.dedup();
+            RouteLookup::MethodNotAllowed { allowed }
+        }
+        None => RouteLookup::NotFound,
+    }
 }
 
-fn resolve(index: &IndexInner, path: &str, method: &str) -> RouteLookup {
-    let result = match_index(index, path, method);
+fn resolve_table(table: &RouteTable, path: &str, method: &str) -> RouteLookup {
+    let result = match_table(table, path, method);
     if method == "HEAD"
         && !matches!(result, RouteLookup::Handler { .. })
         && let RouteLookup::Handler {
             script_uri,
             handler_name,
+            route,
             params,
+            rate_limit,
+            idempotency,
+            uploads,
+            schema,
+            execution_timeout_ms,
+            cache,
+            auth,
             ..
-        } = match_index(index, path, "GET")
+        } = match_table(table, path, "GET")
     {
         return RouteLookup::Handler {
             script_uri,
             handler_name,
+            route,
             params,
             strip_body: true,
+            rate_limit,
+            idempotency,
+            uploads,
+            schema,
+            execution_timeout_ms,
+            cache,
+            auth,
         };
     }
     result
 }
 
-fn match_index(index: &IndexInner, path: &str, method: &str) -> RouteLookup {
+fn match_table(index: &RouteTable, path: &str, method: &str) -> RouteLookup {
     if let Some(target) = index.exact.get(&(path.to_string(), method.to_string())) {
         return RouteLookup::Handler {
             script_uri: target.script_uri.clone(),
             handler_name: target.handler_name.clone(),
+            route: target.route.clone(),
             params: HashMap::new(),
             strip_body: false,
+            rate_limit: target.rate_limit.clone(),
+            idempotency: target.idempotency.clone(),
+            uploads: target.uploads.clone(),
+            schema: target.schema.clone(),
+            execution_timeout_ms: target.execution_timeout_ms,
+            cache: target.cache.clone(),
+            auth: target.auth.clone(),
         };
     }
 
@@ -213,23 +362,174 @@ fn match_index(index: &IndexInner, path: &str, method: &str) -> RouteLookup {
         return RouteLookup::Handler {
             script_uri: route.target.script_uri.clone(),
             handler_name: route.target.handler_name.clone(),
+            route: route.target.route.clone(),
             params,
             strip_body: false,
+            rate_limit: route.target.rate_limit.clone(),
+            idempotency: route.target.idempotency.clone(),
+            uploads: route.target.uploads.clone(),
+            schema: route.target.schema.clone(),
+            execution_timeout_ms: route.target.execution_timeout_ms,
+            cache: route.target.cache.clone(),
+            auth: route.target.auth.clone(),
         };
     }
 
     // No handler for this method; distinguish 405 (path registered under
     // another method) from 404
-    let path_registered = index.exact.keys().any(|(p, _)| p == path)
-        || index
-            .patterns
-            .iter()
-            .any(|route| route.matches(path).is_some());
-    if path_registered {
-        RouteLookup::MethodNotAllowed
-    } else {
-        RouteLookup::NotFound
+    let mut allowed: Vec<String> = index
+        .exact
+        .keys()
+        .filter(|(p, _)| p == path)
+        .map(|(_, method)| method.clone())
+        .chain(
+            index
+                .patterns
+                .iter()
+                .filter(|route| route.matches(path).is_some())
+                .map(|route| route.method.clone()),
+        )
+        .collect();
+    if allowed.is_empty() {
+        return RouteLookup::NotFound;
+    }
+    // HEAD is served by GET handlers and OPTIONS by the engine
+    if allowed.iter().any(|method| method == "GET") {
+        allowed.push("HEAD".to_string());
+    }
+    allowed.push("OPTIONS".to_string());
+    allowed.sort();
+    allowed.dedup();
+    RouteLookup::MethodNotAllowed { allowed }
+}
+
+/// A route registered by more than one script. Only the winner serves it;
+/// the others are shadowed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteConflict {
+    /// Host the route is registered for; None for any host
+    pub host: Option<String>,
+    pub method: String,
+    pub path: String,
+    /// Scripts registering the route, the winner first
+    pub scripts: Vec<String>,
+    /// Script whose handler serves the route
+    pub winner: String,
+}
+
+/// `pattern` with parameter names dropped, so `/users/:id` and
+/// `/users/:user` compare equal
+fn route_shape(pattern: &str) -> String {
+    crate::path_normalization::route_pattern(pattern)
+        .split('/')
+        .map(|part| if part.starts_with(':') { ":" } else { part })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Initialized scripts in the order their routes take precedence: when
+/// scripts register the same route, the one created first serves it
+fn by_precedence(metadata: &[repository::ScriptMetadata]) -> Vec<&repository::ScriptMetadata> {
+    let mut scripts: Vec<_> = metadata
+        .iter()
+        .filter(|script| script.initialized && !script.registrations.is_empty())
+        .collect();
+    scripts.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.uri.cmp(&b.uri)));
+    scripts
+}
+
+/// Routes registered by more than one of `scripts`, given as script URI and
+/// registrations in the order they take precedence
+pub fn find_conflicts<'a>(
+    scripts: impl IntoIterator<Item = (&'a str, &'a repository::RouteRegistrations)>,
+) -> Vec<RouteConflict> {
+    let mut routes: BTreeMap<(Option<String>, String, String), (String, Vec<String>)> =
+        BTreeMap::new();
+    for (script_uri, registrations) in scripts {
+        for ((pattern, method), route_meta) in registrations {
+            let hosts: Vec<Option<String>> = if route_meta.hosts.is_empty() {
+                vec![None]
+            } else {
+                route_meta.hosts.iter().cloned().map(Some).collect()
+            };
+            for host in hosts {
+                let (_, uris) = routes
+                    .entry((host, method.clone(), route_shape(pattern)))
+                    .or_insert_with(|| (pattern.clone(), Vec::new()));
+                if !uris.iter().any(|uri| uri == script_uri) {
+                    uris.push(script_uri.to_string());
+                }
+            }
+        }
     }
+    routes
+        .into_iter()
+        .filter(|(_, (_, scripts))| scripts.len() > 1)
+        .map(|((host, method, _), (path, scripts))| RouteConflict {
+            host,
+            method,
+            path,
+            winner: scripts[0].clone(),
+            scripts,
+        })
+        .collect()
+}
+
+/// Routes currently registered by more than one initialized script
+pub async fn conflicts() -> Result<Vec<RouteConflict>, String> {
+    let metadata = repository::get_repository()
+        .get_all_script_metadata()
+        .await
+        .map_err(|e| format!("Failed to fetch script metadata: {}", e))?;
+    Ok(find_conflicts(by_precedence(&metadata).into_iter().map(
+        |script| (script.uri.as_str(), &script.registrations),
+    )))
+}
+
+/// Normalize a Host header or registered host name: lowercased, without the
+/// port. Returns None for anything but a plain DNS name or IPv4 address.
+pub fn normalize_host(host: &str) -> Option<String> {
+    let host = host.trim();
+    let name = host.split_once(':').map_or(host, |(name, _)| name);
+    let name = name.strip_suffix('.').unwrap_or(name).to_ascii_lowercase();
+    let valid = !name.is_empty()
+        && name.len() <= 253
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then_some(name)
+}
+
+/// Key under which the asset and stream registries store `path` registered
+/// for `host`: the host name followed by the path. Paths registered for any
+/// host are stored as is; they start with `/`, so the two never collide.
+pub fn host_scoped_path(host: Option<&str>, path: &str) -> String {
+    match host {
+        Some(host) => format!("{}{}", host, path),
+        None => path.to_string(),
+    }
+}
+
+/// Split a registry key built by [`host_scoped_path`] into host and path
+pub fn split_host_scoped_path(key: &str) -> (Option<&str>, &str) {
+    if key.starts_with('/') {
+        return (None, key);
+    }
+    match key.find('/') {
+        Some(index) => (Some(&key[..index]), &key[index..]),
+        None => (None, key),
+    }
+}
+
+/// Registry keys to look up for a request, most specific first: the path
+/// registered for the request's host, then the path registered for any host
+pub fn request_path_keys(host: Option<&str>, path: &str) -> Vec<String> {
+    let mut keys = Vec::with_capacity(2);
+    if let Some(host) = host.and_then(normalize_host) {
+        keys.push(host_scoped_path(Some(&host), path));
+    }
+    keys.push(path.to_string());
+    keys
 }
 
 /// Calculate specificity score for a route pattern
@@ -254,6 +554,21 @@ pub fn calculate_route_specificity(pattern: &str) -> i32 {
     (exact_count * 1000) + (param_count * 100) - (wildcard_depth * 10)
 }
 
+/// Whether `path` matches a route pattern as registered with
+/// `registerRoute`: an exact path, a `/prefix/*` wildcard, or a pattern with
+/// `:param` segments
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*')
+        && prefix.ends_with('/')
+    {
+        path.starts_with(prefix)
+    } else if pattern.split('/').any(|part| part.starts_with(':')) {
+        match_route_pattern(pattern, path).is_some()
+    } else {
+        pattern == path
+    }
+}
+
 /// Match a route pattern with parameters against a path
 /// Returns extracted parameters if the pattern matches
 pub fn match_route_pattern(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
@@ -318,7 +633,7 @@ mod tests {
             ],
         )]);
 
-        let (handler, params) = handler_of(match_index(&index, "/api/users/me", "GET"));
+        let (handler, params) = handler_of(match_table(&index.any_host, "/api/users/me", "GET"));
         assert_eq!(handler, "exact_handler");
         assert!(params.is_empty());
     }
@@ -330,7 +645,7 @@ mod tests {
             &[("/api/users/:id", "GET", "param_handler")],
         )]);
 
-        let (handler, params) = handler_of(match_index(&index, "/api/users/42", "GET"));
+        let (handler, params) = handler_of(match_table(&index.any_host, "/api/users/42", "GET"));
         assert_eq!(handler, "param_handler");
         assert_eq!(params.get("id").map(String::as_str), Some("42"));
     }
@@ -342,11 +657,11 @@ mod tests {
             &[("/files/*", "GET", "files_handler")],
         )]);
 
-        let (handler, _) = handler_of(match_index(&index, "/files/a/b/c.txt", "GET"));
+        let (handler, _) = handler_of(match_table(&index.any_host, "/files/a/b/c.txt", "GET"));
         assert_eq!(handler, "files_handler");
         // The prefix keeps its slash: /filesx must not match
         assert!(matches!(
-            match_index(&index, "/filesx", "GET"),
+            match_table(&index.any_host, "/filesx", "GET"),
             RouteLookup::NotFound
         ));
     }
@@ -363,7 +678,7 @@ mod tests {
             ],
         )]);
 
-        let (handler, _) = handler_of(match_index(&index, "/a/b/c/d", "GET"));
+        let (handler, _) = handler_of(match_table(&index.any_host, "/a/b/c/d", "GET"));
         assert_eq!(handler, "deep_wildcard");
     }
 
@@ -375,11 +690,11 @@ mod tests {
         )]);
 
         assert!(matches!(
-            match_index(&index, "/api/thing", "GET"),
-            RouteLookup::MethodNotAllowed
+            match_table(&index.any_host, "/api/thing", "GET"),
+            RouteLookup::MethodNotAllowed { .. }
         ));
         assert!(matches!(
-            match_index(&index, "/api/other", "GET"),
+            match_table(&index.any_host, "/api/other", "GET"),
             RouteLookup::NotFound
         ));
     }
@@ -391,7 +706,7 @@ mod tests {
         let index = build_index(&[metadata]);
 
         assert!(matches!(
-            match_index(&index, "/route", "GET"),
+            match_table(&index.any_host, "/route", "GET"),
             RouteLookup::NotFound
         ));
     }
@@ -403,7 +718,7 @@ mod tests {
             &[("/api/users", "GET", "list_users")],
         )]);
 
-        match resolve(&index, "/api/users", "HEAD") {
+        match resolve(&index, None, "/api/users", "HEAD") {
             RouteLookup::Handler {
                 handler_name,
                 strip_body,
@@ -426,7 +741,7 @@ mod tests {
             ],
         )]);
 
-        match resolve(&index, "/api/users", "HEAD") {
+        match resolve(&index, None, "/api/users", "HEAD") {
             RouteLookup::Handler {
                 handler_name,
                 strip_body,
@@ -439,6 +754,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_allowed_methods_of_registered_path() {
+        let index = build_index(&[
+            script_with_routes(
+                "s1",
+                &[
+                    ("/items", "GET", "list"),
+                    ("/items/:id", "DELETE", "remove"),
+                ],
+            ),
+            script_with_routes("s2", &[("/items", "POST", "create")]),
+        ]);
+
+        let allowed_of = |path: &str, method: &str| match resolve(&index, None, path, method) {
+            RouteLookup::MethodNotAllowed { allowed } => allowed,
+            other => panic!("Expected method not allowed, got {:?}", other),
+        };
+        assert_eq!(
+            allowed_of("/items", "OPTIONS"),
+            vec!["GET", "HEAD", "OPTIONS", "POST"]
+        );
+        assert_eq!(allowed_of("/items/7", "PUT"), vec!["DELETE", "OPTIONS"]);
+    }
+
     #[test]
     fn test_head_still_405_when_path_only_registered_for_other_methods() {
         let index = build_index(&[script_with_routes(
@@ -447,8 +786,8 @@ mod tests {
         )]);
 
         assert!(matches!(
-            resolve(&index, "/api/thing", "HEAD"),
-            RouteLookup::MethodNotAllowed
+            resolve(&index, None, "/api/thing", "HEAD"),
+            RouteLookup::MethodNotAllowed { .. }
         ));
     }
 
@@ -462,11 +801,212 @@ mod tests {
             ],
         )]);
 
-        let (handler, params) = handler_of(resolve(&index, "/api/users/42", "HEAD"));
+        let (handler, params) = handler_of(resolve(&index, None, "/api/users/42", "HEAD"));
         assert_eq!(handler, "get_user");
         assert_eq!(params.get("id").map(String::as_str), Some("42"));
 
-        let (handler, _) = handler_of(resolve(&index, "/files/a/b.txt", "HEAD"));
+        let (handler, _) = handler_of(resolve(&index, None, "/files/a/b.txt", "HEAD"));
         assert_eq!(handler, "get_file");
     }
+
+    #[test]
+    fn test_host_routes_win_over_any_host_routes() {
+        let mut shop = script_with_routes(
+            "shop",
+            &[("/", "GET", "shop_home"), ("/cart", "GET", "shop_cart")],
+        );
+        for route in shop.registrations.values_mut() {
+            route.hosts = vec!["shop.example.com".to_string()];
+        }
+        let index = build_index(&[
+            shop,
+            script_with_routes(
+                "site",
+                &[("/", "GET", "site_home"), ("/about", "GET", "about")],
+            ),
+        ]);
+
+        let (handler, _) = handler_of(resolve(&index, Some("Shop.Example.com:8080"), "/", "GET"));
+        assert_eq!(handler, "shop_home");
+        // Paths the host doesn't register fall back to any-host routes
+        let (handler, _) = handler_of(resolve(&index, Some("shop.example.com"), "/about", "GET"));
+        assert_eq!(handler, "about");
+        // Other hosts never see the host's routes
+        let (handler, _) = handler_of(resolve(&index, Some("www.example.com"), "/", "GET"));
+        assert_eq!(handler, "site_home");
+        assert!(matches!(
+            resolve(&index, Some("www.example.com"), "/cart", "GET"),
+            RouteLookup::NotFound
+        ));
+        assert!(matches!(
+            resolve(&index, None, "/cart", "GET"),
+            RouteLookup::NotFound
+        ));
+        assert!(matches!(
+            resolve(&index, Some("shop.example.com"), "/cart", "POST"),
+            RouteLookup::MethodNotAllowed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_host_scoped_path_keys() {
+        let key = host_scoped_path(Some("shop.example.com"), "/events");
+        assert_eq!(key, "shop.example.com/events");
+        assert_eq!(
+            split_host_scoped_path(&key),
+            (Some("shop.example.com"), "/events")
+        );
+        assert_eq!(split_host_scoped_path("/events"), (None, "/events"));
+        assert_eq!(
+            request_path_keys(Some("Shop.Example.com:443"), "/events"),
+            vec!["shop.example.com/events".to_string(), "/events".to_string()]
+        );
+        assert_eq!(request_path_keys(None, "/events"), vec!["/events"]);
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(
+            normalize_host("Shop.Example.com:8443"),
+            Some("shop.example.com".to_string())
+        );
+        assert_eq!(
+            normalize_host("example.com."),
+            Some("example.com".to_string())
+        );
+        assert_eq!(normalize_host("10.0.0.1"), Some("10.0.0.1".to_string()));
+        assert_eq!(normalize_host(""), None);
+        assert_eq!(normalize_host("[::1]:8080"), None);
+        assert_eq!(normalize_host("a/b"), None);
+    }
+
+    #[test]
+    fn test_path_matches() {
+        assert!(path_matches("/api/*", "/api/orders/1"));
+        assert!(!path_matches("/api/*", "/apix"));
+        assert!(path_matches("/users/:id", "/users/42"));
+        assert!(!path_matches("/users/:id", "/users/42/posts"));
+        assert!(path_matches("/login", "/login"));
+        assert!(!path_matches("/login", "/login/x"));
+    }
+
+    #[test]
+    fn test_route_rate_limit_carried_by_lookup() {
+        let mut metadata = script_with_routes("s1", &[]);
+        let mut route = RouteMetadata::simple("create_order".to_string());
+        route.rate_limit = Some(crate::rate_limit_rules::RouteRateLimit {
+            requests_per_minute: 10,
+            per: crate::rate_limit_rules::RateLimitScope::User,
+            burst: None,
+        });
+        metadata
+            .registrations
+            .insert(("/orders/:id".to_string(), "POST".to_string()), route);
+        let index = build_index(&[metadata]);
+
+        match resolve(&index, None, "/orders/7", "POST") {
+            RouteLookup::Handler { rate_limit, .. } => {
+                let rule = rate_limit.expect("route rate limit");
+                assert_eq!(rule.name, "route:POST /orders/:id");
+                assert_eq!(rule.requests_per_minute, 10);
+            }
+            other => panic!("Expected a handler, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_script_execution_timeout_carried_by_lookup() {
+        let mut slow = script_with_routes("reports", &[("/reports/:id", "GET", "render")]);
+        slow.execution_timeout_ms = Some(30_000);
+        let index = build_index(&[slow, script_with_routes("s1", &[("/fast", "GET", "fast")])]);
+
+        let timeout_of = |path: &str, method: &str| match resolve(&index, None, path, method) {
+            RouteLookup::Handler {
+                execution_timeout_ms,
+                ..
+            } => execution_timeout_ms,
+            other => panic!("Expected a handler, got {:?}", other),
+        };
+        assert_eq!(timeout_of("/reports/7", "GET"), Some(30_000));
+        assert_eq!(timeout_of("/reports/7", "HEAD"), Some(30_000));
+        assert_eq!(timeout_of("/fast", "GET"), None);
+    }
+
+    #[test]
+    fn test_route_timeout_wins_over_script_timeout() {
+        let mut script = script_with_routes(
+            "reports",
+            &[
+                ("/reports/export", "GET", "export"),
+                ("/reports", "GET", "list"),
+            ],
+        );
+        script.execution_timeout_ms = Some(5_000);
+        if let Some(route) = script
+            .registrations
+            .get_mut(&("/reports/export".to_string(), "GET".to_string()))
+        {
+            route.timeout_ms = Some(60_000);
+        }
+        let index = build_index(&[script]);
+
+        let timeout_of = |path: &str| match resolve(&index, None, path, "GET") {
+            RouteLookup::Handler {
+                execution_timeout_ms,
+                ..
+            } => execution_timeout_ms,
+            other => panic!("Expected a handler, got {:?}", other),
+        };
+        assert_eq!(timeout_of("/reports/export"), Some(60_000));
+        assert_eq!(timeout_of("/reports"), Some(5_000));
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let a = script_with_routes("a", &[("/users/:id", "GET", "get"), ("/a", "GET", "a")]);
+        let b = script_with_routes(
+            "b",
+            &[
+                ("/users/:user", "GET", "show"),
+                ("/users/:id", "POST", "save"),
+            ],
+        );
+        let mut c = script_with_routes("c", &[("/a", "GET", "a")]);
+        for route in c.registrations.values_mut() {
+            route.hosts = vec!["shop.example.com".to_string()];
+        }
+
+        let conflicts = find_conflicts(
+            [&a, &b, &c]
+                .iter()
+                .map(|script| (script.uri.as_str(), &script.registrations)),
+        );
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].host, None);
+        assert_eq!(conflicts[0].method, "GET");
+        assert!(conflicts[0].path.starts_with("/users/:"));
+        assert_eq!(conflicts[0].scripts, vec!["a", "b"]);
+        assert_eq!(conflicts[0].winner, "a");
+    }
+
+    #[test]
+    fn test_earliest_created_script_wins_conflicts() {
+        let mut newer = script_with_routes("newer", &[("/a", "GET", "newer_a")]);
+        let mut older = script_with_routes("older", &[("/a", "GET", "older_a")]);
+        older.created_at = std::time::UNIX_EPOCH;
+        newer.created_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(60);
+
+        let index = build_index(&[newer.clone(), older.clone()]);
+        assert_eq!(handler_of(resolve(&index, None, "/a", "GET")).0, "older_a");
+
+        let metadata = [newer, older];
+        let conflicts = find_conflicts(
+            by_precedence(&metadata)
+                .into_iter()
+                .map(|script| (script.uri.as_str(), &script.registrations)),
+        );
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].winner, "older");
+        assert_eq!(conflicts[0].scripts, vec!["older", "newer"]);
+    }
 }
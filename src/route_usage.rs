@@ -0,0 +1,534 @@
+//! Request counts, status codes and latency of script routes.
+//!
+//! Every request served by a registered route is counted under the route's
+//! pattern (`/items/:id`, not the requested path), its method and the day
+//! (UTC). Counts are kept in memory and added to the `route_usage` table every
+//! `repository.route_usage_flush_interval_secs`; latencies are kept as a
+//! histogram so the 50th and 95th percentiles can be estimated across days
+//! and server instances. Days older than `repository.route_usage_retention_days`
+//! are removed.
+//!
+//! `scriptStorage.getRouteUsage()` and the `routeUsage` GraphQL query report a
+//! script's routes, including registered routes that received no requests.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::RepositoryConfig;
+use crate::repository;
+
+/// Upper bounds (ms) of the latency histogram buckets; a last bucket holds
+/// slower requests
+pub const LATENCY_BUCKETS_MS: [f64; 13] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0,
+];
+
+/// Days reported when the caller gives none
+pub const DEFAULT_REPORT_DAYS: u32 = 7;
+
+/// Most days one report covers
+pub const MAX_REPORT_DAYS: u32 = 366;
+
+/// Routes aggregated in memory between flushes; requests to further routes
+/// are dropped until the next flush
+const MAX_PENDING_ROUTES: usize = 10_000;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Usage of one route on one day, as stored in `route_usage`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteUsageEntry {
+    pub day: NaiveDate,
+    pub script_uri: String,
+    /// Registered pattern
+    pub route: String,
+    pub method: String,
+    pub requests: i64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    /// Requests per response status
+    pub status_counts: BTreeMap<u16, i64>,
+    /// Requests per `LATENCY_BUCKETS_MS` bucket, then the overflow bucket
+    pub latency_buckets: Vec<i64>,
+    pub last_request_at: Option<DateTime<Utc>>,
+}
+
+impl RouteUsageEntry {
+    fn new(day: NaiveDate, script_uri: &str, route: &str, method: &str) -> Self {
+        Self {
+            day,
+            script_uri: script_uri.to_string(),
+            route: route.to_string(),
+            method: method.to_string(),
+            requests: 0,
+            total_ms: 0.0,
+            max_ms: 0.0,
+            status_counts: BTreeMap::new(),
+            latency_buckets: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            last_request_at: None,
+        }
+    }
+
+    fn add(&mut self, other: &RouteUsageEntry) {
+        self.requests += other.requests;
+        self.total_ms += other.total_ms;
+        self.max_ms = self.max_ms.max(other.max_ms);
+        for (status, count) in &other.status_counts {
+            *self.status_counts.entry(*status).or_default() += count;
+        }
+        if self.latency_buckets.len() < other.latency_buckets.len() {
+            self.latency_buckets.resize(other.latency_buckets.len(), 0);
+        }
+        for (bucket, count) in self.latency_buckets.iter_mut().zip(&other.latency_buckets) {
+            *bucket += count;
+        }
+        self.last_request_at = self.last_request_at.max(other.last_request_at);
+    }
+}
+
+/// Requests that got one status
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusCount {
+    pub status: u16,
+    pub count: i64,
+}
+
+/// Usage of one route over the reported days
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteUsage {
+    pub route: String,
+    pub method: String,
+    pub requests: i64,
+    /// Requests answered with a 5xx status
+    pub errors: i64,
+    pub status_codes: Vec<StatusCount>,
+    pub avg_ms: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub last_request_at: Option<String>,
+}
+
+/// Report of `scriptStorage.getRouteUsage()`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteUsageReport {
+    pub script_uri: String,
+    /// First day reported (YYYY-MM-DD, UTC)
+    pub since: String,
+    pub days: u32,
+    /// Most requested first; registered routes without requests last
+    pub routes: Vec<RouteUsage>,
+}
+
+/// Options of `scriptStorage.getRouteUsage()`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RouteUsageOptions {
+    /// Days to report, ending today
+    pub days: Option<u32>,
+}
+
+struct Settings {
+    enabled: bool,
+    flush_interval: Duration,
+    retention_days: u64,
+}
+
+static SETTINGS: OnceLock<Mutex<Settings>> = OnceLock::new();
+static PENDING: OnceLock<Mutex<HashMap<(NaiveDate, String, String, String), RouteUsageEntry>>> =
+    OnceLock::new();
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    /// Route matched by the request being served: script URI, pattern, method
+    static MATCHED_ROUTE: RefCell<Option<(String, String, String)>>;
+}
+
+fn settings_from(config: &RepositoryConfig) -> Settings {
+    Settings {
+        enabled: config.route_usage_enabled,
+        flush_interval: Duration::from_secs(config.route_usage_flush_interval_secs.max(1)),
+        retention_days: config.route_usage_retention_days,
+    }
+}
+
+fn lock_settings() -> MutexGuard<'static, Settings> {
+    match SETTINGS
+        .get_or_init(|| Mutex::new(settings_from(&RepositoryConfig::default())))
+        .lock()
+    {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn lock_pending()
+-> MutexGuard<'static, HashMap<(NaiveDate, String, String, String), RouteUsageEntry>> {
+    match PENDING.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Route usage mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Apply the repository configuration. Called once at server startup.
+pub fn configure(config: &RepositoryConfig) {
+    *lock_settings() = settings_from(config);
+}
+
+/// Serve a request with `serve`, then count it under the route it matched,
+/// if any (see [`matched`])
+pub async fn track<F>(serve: F) -> axum::response::Response
+where
+    F: Future<Output = axum::response::Response>,
+{
+    if !lock_settings().enabled {
+        return serve.await;
+    }
+    let started = std::time::Instant::now();
+    MATCHED_ROUTE
+        .scope(RefCell::new(None), async move {
+            let response = serve.await;
+            if let Some((script_uri, route, method)) = MATCHED_ROUTE.with(|m| m.borrow_mut().take())
+            {
+                record(
+                    &script_uri,
+                    &route,
+                    &method,
+                    response.status().as_u16(),
+                    started.elapsed(),
+                );
+            }
+            response
+        })
+        .await
+}
+
+/// Note the route the request being [`track`]ed matched
+pub fn matched(script_uri: &str, route: &str, method: &str) {
+    let _ = MATCHED_ROUTE.try_with(|m| {
+        *m.borrow_mut() = Some((
+            script_uri.to_string(),
+            route.to_string(),
+            method.to_string(),
+        ))
+    });
+}
+
+fn bucket_for(ms: f64) -> usize {
+    LATENCY_BUCKETS_MS
+        .iter()
+        .position(|bound| ms <= *bound)
+        .unwrap_or(LATENCY_BUCKETS_MS.len())
+}
+
+/// Count one request to a route
+pub fn record(script_uri: &str, route: &str, method: &str, status: u16, elapsed: Duration) {
+    let now = Utc::now();
+    let key = (
+        now.date_naive(),
+        script_uri.to_string(),
+        route.to_string(),
+        method.to_string(),
+    );
+    let mut pending = lock_pending();
+    if !pending.contains_key(&key) && pending.len() >= MAX_PENDING_ROUTES {
+        debug!(
+            "Route usage buffer is full; not counting {} {}",
+            method, route
+        );
+        return;
+    }
+    let entry = pending
+        .entry(key)
+        .or_insert_with(|| RouteUsageEntry::new(now.date_naive(), script_uri, route, method));
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    entry.requests += 1;
+    entry.total_ms += ms;
+    entry.max_ms = entry.max_ms.max(ms);
+    *entry.status_counts.entry(status).or_default() += 1;
+    entry.latency_buckets[bucket_for(ms)] += 1;
+    entry.last_request_at = Some(now);
+}
+
+/// Add the counts kept in memory to the `route_usage` table. Counts that fail
+/// to be stored are kept for the next flush.
+pub async fn flush() {
+    let entries: Vec<RouteUsageEntry> = lock_pending().drain().map(|(_, entry)| entry).collect();
+    if entries.is_empty() {
+        return;
+    }
+    let repo = repository::get_repository();
+    let mut failed = Vec::new();
+    for entry in entries {
+        if let Err(e) = repo.record_route_usage(&entry).await {
+            warn!(
+                "Failed to store usage of {} {} in {}: {}",
+                entry.method, entry.route, entry.script_uri, e
+            );
+            failed.push(entry);
+        }
+    }
+    let mut pending = lock_pending();
+    for entry in failed {
+        let key = (
+            entry.day,
+            entry.script_uri.clone(),
+            entry.route.clone(),
+            entry.method.clone(),
+        );
+        match pending.get_mut(&key) {
+            Some(existing) => existing.add(&entry),
+            None => {
+                pending.insert(key, entry);
+            }
+        }
+    }
+}
+
+/// Start flushing counts to the database and removing old days
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut last_purge: Option<std::time::Instant> = None;
+        loop {
+            let (flush_interval, retention_days) = {
+                let settings = lock_settings();
+                (settings.flush_interval, settings.retention_days)
+            };
+            tokio::time::sleep(flush_interval).await;
+            if repository::get_repository_opt().is_none() {
+                continue;
+            }
+            flush().await;
+
+            if retention_days > 0 && last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                last_purge = Some(std::time::Instant::now());
+                let before = Utc::now().date_naive()
+                    - chrono::Duration::days(i64::try_from(retention_days).unwrap_or(i64::MAX));
+                match repository::get_repository().purge_route_usage(before).await {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Removed {} route usage rows", count),
+                    Err(e) => warn!("Failed to purge route usage: {}", e),
+                }
+            }
+        }
+    });
+}
+
+/// Estimate the `quantile` latency from histogram `buckets`, interpolating
+/// within the bucket it falls in. The overflow bucket reports `max_ms`.
+pub fn percentile(buckets: &[i64], quantile: f64, max_ms: f64) -> Option<f64> {
+    let total: i64 = buckets.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = (quantile * total as f64).ceil().max(1.0);
+    let mut seen = 0.0;
+    for (index, count) in buckets.iter().enumerate() {
+        if *count == 0 {
+            continue;
+        }
+        let count = *count as f64;
+        if seen + count >= rank {
+            let Some(upper) = LATENCY_BUCKETS_MS.get(index) else {
+                return Some(max_ms);
+            };
+            let lower = if index == 0 {
+                0.0
+            } else {
+                LATENCY_BUCKETS_MS[index - 1]
+            };
+            let estimate = lower + (upper - lower) * ((rank - seen) / count);
+            return Some(estimate.min(max_ms));
+        }
+        seen += count;
+    }
+    Some(max_ms)
+}
+
+fn route_usage(entry: &RouteUsageEntry) -> RouteUsage {
+    let counted = entry.requests > 0;
+    RouteUsage {
+        route: entry.route.clone(),
+        method: entry.method.clone(),
+        requests: entry.requests,
+        errors: entry
+            .status_counts
+            .iter()
+            .filter(|(status, _)| **status >= 500)
+            .map(|(_, count)| count)
+            .sum(),
+        status_codes: entry
+            .status_counts
+            .iter()
+            .map(|(status, count)| StatusCount {
+                status: *status,
+                count: *count,
+            })
+            .collect(),
+        avg_ms: counted.then(|| entry.total_ms / entry.requests as f64),
+        p50_ms: percentile(&entry.latency_buckets, 0.5, entry.max_ms),
+        p95_ms: percentile(&entry.latency_buckets, 0.95, entry.max_ms),
+        max_ms: counted.then_some(entry.max_ms),
+        last_request_at: entry.last_request_at.map(|at| at.to_rfc3339()),
+    }
+}
+
+/// Sum `entries` per route and add registered `routes` that got no requests
+pub fn summarize(
+    entries: &[RouteUsageEntry],
+    registered: impl IntoIterator<Item = (String, String)>,
+) -> Vec<RouteUsage> {
+    let mut totals: BTreeMap<(String, String), RouteUsageEntry> = BTreeMap::new();
+    for entry in entries {
+        totals
+            .entry((entry.route.clone(), entry.method.clone()))
+            .or_insert_with(|| {
+                RouteUsageEntry::new(entry.day, &entry.script_uri, &entry.route, &entry.method)
+            })
+            .add(entry);
+    }
+    for (route, method) in registered {
+        totals
+            .entry((route.clone(), method.clone()))
+            .or_insert_with(|| RouteUsageEntry::new(Utc::now().date_naive(), "", &route, &method));
+    }
+    let mut routes: Vec<RouteUsage> = totals.values().map(route_usage).collect();
+    routes.sort_by(|a, b| b.requests.cmp(&a.requests));
+    routes
+}
+
+/// Usage of a script's routes over the last days, including counts not yet
+/// flushed by this instance
+pub fn report(script_uri: &str, options: &RouteUsageOptions) -> Result<RouteUsageReport, String> {
+    let days = options.days.unwrap_or(DEFAULT_REPORT_DAYS);
+    if !(1..=MAX_REPORT_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {}", MAX_REPORT_DAYS));
+    }
+    let since = Utc::now().date_naive() - chrono::Duration::days(i64::from(days) - 1);
+    let mut entries = repository::list_route_usage(script_uri, since)
+        .map_err(|e| format!("Failed to read route usage: {}", e))?;
+    entries.extend(
+        lock_pending()
+            .values()
+            .filter(|entry| entry.script_uri == script_uri && entry.day >= since)
+            .cloned(),
+    );
+    let registered = repository::get_script_metadata(script_uri)
+        .map(|metadata| metadata.registrations.into_keys().collect::<Vec<_>>())
+        .unwrap_or_default();
+    Ok(RouteUsageReport {
+        script_uri: script_uri.to_string(),
+        since: since.format("%Y-%m-%d").to_string(),
+        days,
+        routes: summarize(&entries, registered),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(route: &str, status: u16, ms: f64, requests: i64) -> RouteUsageEntry {
+        let mut entry = RouteUsageEntry::new(
+            NaiveDate::from_ymd_opt(2026, 5, 1).unwrap(),
+            "https://example.com/usage",
+            route,
+            "GET",
+        );
+        entry.requests = requests;
+        entry.total_ms = ms * requests as f64;
+        entry.max_ms = ms;
+        entry.status_counts.insert(status, requests);
+        entry.latency_buckets[bucket_for(ms)] = requests;
+        entry
+    }
+
+    #[test]
+    fn test_bucket_for() {
+        assert_eq!(bucket_for(0.2), 0);
+        assert_eq!(bucket_for(1.0), 0);
+        assert_eq!(bucket_for(1.5), 1);
+        assert_eq!(bucket_for(750.0), 9);
+        assert_eq!(bucket_for(60_000.0), LATENCY_BUCKETS_MS.len());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut buckets = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        assert_eq!(percentile(&buckets, 0.5, 0.0), None);
+
+        // 90 requests of 10-20 ms, 10 of 200-500 ms
+        buckets[4] = 90;
+        buckets[8] = 10;
+        let p50 = percentile(&buckets, 0.5, 480.0).unwrap();
+        assert!((10.0..=20.0).contains(&p50));
+        let p95 = percentile(&buckets, 0.95, 480.0).unwrap();
+        assert!((200.0..=480.0).contains(&p95));
+
+        buckets[LATENCY_BUCKETS_MS.len()] = 100;
+        assert_eq!(percentile(&buckets, 0.99, 42_000.0), Some(42_000.0));
+    }
+
+    #[test]
+    fn test_summarize_merges_days_and_lists_unused_routes() {
+        let mut later = entry("/items/:id", 500, 150.0, 1);
+        later.day = NaiveDate::from_ymd_opt(2026, 5, 2).unwrap();
+        let routes = summarize(
+            &[
+                entry("/items/:id", 200, 15.0, 9),
+                later,
+                entry("/", 200, 3.0, 2),
+            ],
+            [
+                ("/items/:id".to_string(), "GET".to_string()),
+                ("/unused".to_string(), "POST".to_string()),
+            ],
+        );
+
+        assert_eq!(routes.len(), 3);
+        let items = &routes[0];
+        assert_eq!(items.route, "/items/:id");
+        assert_eq!(items.requests, 10);
+        assert_eq!(items.errors, 1);
+        assert_eq!(
+            items.status_codes,
+            vec![
+                StatusCount {
+                    status: 200,
+                    count: 9
+                },
+                StatusCount {
+                    status: 500,
+                    count: 1
+                }
+            ]
+        );
+        assert_eq!(items.max_ms, Some(150.0));
+        assert_eq!(routes[1].route, "/");
+        let unused = &routes[2];
+        assert_eq!(
+            (unused.route.as_str(), unused.method.as_str()),
+            ("/unused", "POST")
+        );
+        assert_eq!(unused.requests, 0);
+        assert_eq!(unused.p50_ms, None);
+        assert_eq!(unused.avg_ms, None);
+    }
+}
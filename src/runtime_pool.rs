@@ -0,0 +1,286 @@
+//! QuickJS runtimes kept by worker pool threads between executions
+//! (`[javascript.runtime_pool]`).
+//!
+//! Creating a runtime is the costly part of starting an execution, so each
+//! worker thread keeps the runtimes of its previous executions; every
+//! execution still gets a fresh context. With `reuse = "shared"` a worker
+//! keeps one runtime for every script, and with `reuse = "per_script"` it
+//! keeps one per script it has run, up to `max_runtimes_per_worker`,
+//! forgetting the least recently used first.
+//!
+//! A runtime is recycled, and the next execution creates a new one, when:
+//!
+//! - it has served `max_reuses` executions
+//! - its execution timed out, ran out of memory or left jobs pending, which
+//!   would run in the next script's time
+//! - it still holds more than `max_retained_memory_bytes` after garbage
+//!   collection, which points at a leak
+//!
+//! With `prewarm`, every worker creates a runtime at startup, so the first
+//! request it serves does not pay for one.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use rquickjs::Runtime;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::RuntimePoolConfig;
+
+/// Which runtimes a worker reuses for an execution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeReuse {
+    /// One runtime per worker serves every script
+    #[default]
+    Shared,
+    /// Each worker keeps a runtime per script
+    PerScript,
+}
+
+static SETTINGS: OnceLock<RwLock<RuntimePoolConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<RuntimePoolConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the runtime pool settings and, with `prewarm`, create a runtime on
+/// every worker. Called once at server startup, after the worker pool is
+/// started.
+pub fn configure(config: &RuntimePoolConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+    if config.prewarm {
+        crate::worker_pool::run_on_each_worker(prewarm);
+    }
+}
+
+fn current_settings() -> RuntimePoolConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+struct CachedRuntime {
+    /// Script the runtime last served; None for pre-warmed runtimes and in
+    /// shared mode
+    script_uri: Option<String>,
+    runtime: Runtime,
+    uses: u32,
+}
+
+/// Runtimes one worker keeps, least recently used first
+#[derive(Default)]
+struct RuntimeCache {
+    entries: Vec<CachedRuntime>,
+}
+
+impl RuntimeCache {
+    /// A kept runtime for an execution of `script_uri`, with the executions
+    /// it has served
+    fn take(&mut self, script_uri: Option<&str>, reuse: RuntimeReuse) -> Option<(Runtime, u32)> {
+        let index = match reuse {
+            RuntimeReuse::Shared => self.entries.len().checked_sub(1),
+            RuntimeReuse::PerScript => self
+                .entries
+                .iter()
+                .position(|entry| entry.script_uri.as_deref() == script_uri)
+                .or_else(|| {
+                    self.entries
+                        .iter()
+                        .position(|entry| entry.script_uri.is_none())
+                }),
+        }?;
+        let entry = self.entries.remove(index);
+        Some((entry.runtime, entry.uses))
+    }
+
+    fn keep(&mut self, mut entry: CachedRuntime, reuse: RuntimeReuse, max_runtimes: usize) {
+        match reuse {
+            RuntimeReuse::Shared => {
+                entry.script_uri = None;
+                self.entries.clear();
+            }
+            RuntimeReuse::PerScript => {
+                self.entries
+                    .retain(|kept| kept.script_uri != entry.script_uri);
+                while self.entries.len() >= max_runtimes.max(1) {
+                    self.entries.remove(0);
+                }
+            }
+        }
+        self.entries.push(entry);
+    }
+}
+
+thread_local! {
+    static CACHE: RefCell<RuntimeCache> = RefCell::new(RuntimeCache::default());
+}
+
+static CREATED: AtomicU64 = AtomicU64::new(0);
+static REUSED: AtomicU64 = AtomicU64::new(0);
+static RECYCLED: AtomicU64 = AtomicU64::new(0);
+static UNHEALTHY: AtomicU64 = AtomicU64::new(0);
+
+/// Runtime counters since startup, across all workers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuntimePoolMetrics {
+    pub reuse: RuntimeReuse,
+    pub created: u64,
+    /// Executions that ran in a kept runtime
+    pub reused: u64,
+    /// Runtimes retired after serving `max_reuses` executions
+    pub recycled: u64,
+    /// Runtimes retired because they timed out, ran out of memory, left jobs
+    /// pending or kept too much memory
+    pub unhealthy: u64,
+}
+
+pub fn metrics() -> RuntimePoolMetrics {
+    RuntimePoolMetrics {
+        reuse: current_settings().reuse,
+        created: CREATED.load(Ordering::Relaxed),
+        reused: REUSED.load(Ordering::Relaxed),
+        recycled: RECYCLED.load(Ordering::Relaxed),
+        unhealthy: UNHEALTHY.load(Ordering::Relaxed),
+    }
+}
+
+fn create() -> Result<Runtime, String> {
+    let runtime = Runtime::new().map_err(|e| format!("Failed to create runtime: {}", e))?;
+    CREATED.fetch_add(1, Ordering::Relaxed);
+    Ok(runtime)
+}
+
+fn prewarm() {
+    let settings = current_settings();
+    match create() {
+        Ok(runtime) => CACHE.with(|cache| {
+            cache.borrow_mut().keep(
+                CachedRuntime {
+                    script_uri: None,
+                    runtime,
+                    uses: 0,
+                },
+                settings.reuse,
+                settings.max_runtimes_per_worker,
+            )
+        }),
+        Err(e) => warn!("Failed to pre-warm script runtime: {}", e),
+    }
+}
+
+/// A runtime for an execution of `script_uri`, with the executions it has
+/// served including this one. Worker threads reuse a kept runtime when they
+/// have one; other threads always get a new runtime.
+pub fn acquire(script_uri: Option<&str>) -> Result<(Runtime, u32), String> {
+    if crate::worker_pool::is_worker_thread() {
+        let reuse = current_settings().reuse;
+        if let Some((runtime, uses)) =
+            CACHE.with(|cache| cache.borrow_mut().take(script_uri, reuse))
+        {
+            if uses > 0 {
+                REUSED.fetch_add(1, Ordering::Relaxed);
+            }
+            return Ok((runtime, uses + 1));
+        }
+    }
+    Ok((create()?, 1))
+}
+
+/// Keep `runtime` for the worker's next execution unless it is due for
+/// recycling. `aborted` tells whether its execution timed out or ran out of
+/// memory.
+pub fn release(script_uri: Option<&str>, runtime: Runtime, uses: u32, aborted: bool) {
+    if std::thread::panicking() || !crate::worker_pool::is_worker_thread() {
+        return;
+    }
+    let settings = current_settings();
+    if uses >= settings.max_reuses {
+        RECYCLED.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    if aborted || runtime.is_job_pending() {
+        UNHEALTHY.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    runtime.run_gc();
+    let retained = runtime.memory_usage().malloc_size;
+    if retained > settings.max_retained_memory_bytes as i64 {
+        debug!(
+            "Recycling script runtime holding {} bytes after garbage collection",
+            retained
+        );
+        UNHEALTHY.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    CACHE.with(|cache| {
+        cache.borrow_mut().keep(
+            CachedRuntime {
+                script_uri: script_uri.map(str::to_string),
+                runtime,
+                uses,
+            },
+            settings.reuse,
+            settings.max_runtimes_per_worker,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(script_uri: Option<&str>, uses: u32) -> CachedRuntime {
+        CachedRuntime {
+            script_uri: script_uri.map(str::to_string),
+            runtime: Runtime::new().unwrap(),
+            uses,
+        }
+    }
+
+    #[test]
+    fn test_shared_cache_keeps_one_runtime() {
+        let mut cache = RuntimeCache::default();
+        cache.keep(cached(Some("a"), 1), RuntimeReuse::Shared, 4);
+        cache.keep(cached(Some("b"), 2), RuntimeReuse::Shared, 4);
+        assert_eq!(cache.entries.len(), 1);
+        let (_, uses) = cache.take(Some("c"), RuntimeReuse::Shared).unwrap();
+        assert_eq!(uses, 2);
+        assert!(cache.take(Some("c"), RuntimeReuse::Shared).is_none());
+    }
+
+    #[test]
+    fn test_per_script_cache() {
+        let mut cache = RuntimeCache::default();
+        cache.keep(cached(None, 0), RuntimeReuse::PerScript, 2);
+        cache.keep(cached(Some("a"), 3), RuntimeReuse::PerScript, 2);
+        assert_eq!(
+            cache
+                .take(Some("a"), RuntimeReuse::PerScript)
+                .map(|(_, uses)| uses),
+            Some(3)
+        );
+        // Scripts without a runtime of their own get the pre-warmed one
+        assert_eq!(
+            cache
+                .take(Some("b"), RuntimeReuse::PerScript)
+                .map(|(_, uses)| uses),
+            Some(0)
+        );
+        assert!(cache.take(Some("b"), RuntimeReuse::PerScript).is_none());
+
+        // The least recently used runtime makes room for a new one
+        cache.keep(cached(Some("a"), 1), RuntimeReuse::PerScript, 2);
+        cache.keep(cached(Some("b"), 1), RuntimeReuse::PerScript, 2);
+        cache.keep(cached(Some("c"), 1), RuntimeReuse::PerScript, 2);
+        assert!(cache.take(Some("a"), RuntimeReuse::PerScript).is_none());
+        assert!(cache.take(Some("b"), RuntimeReuse::PerScript).is_some());
+        assert!(cache.take(Some("c"), RuntimeReuse::PerScript).is_some());
+    }
+}
@@ -782,6 +782,20 @@ impl Scheduler {
             "Acquired lock for job execution"
         );
 
+        if crate::security::threat_response::is_quarantined(&script_uri) {
+            debug!(
+                script = %script_uri,
+                handler = %handler_name,
+                job = %job_key,
+                "Skipping job execution - script is quarantined"
+            );
+            if Self::has_database() {
+                self.finalize_db_job_execution(&invocation, false).await;
+            }
+            lock_guard.release().await;
+            return;
+        }
+
         let execution = tokio::task::spawn_blocking(move || {
             js_engine::execute_scheduled_handler(&script_uri, &handler_name, &invocation_for_engine)
         })
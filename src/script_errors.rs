@@ -0,0 +1,420 @@
+//! Structured errors for exceptions thrown by scripts.
+//!
+//! A failed script used to surface as one opaque string. [`JsError`] keeps
+//! the parts of the QuickJS exception object (name, message, location and
+//! stack) so failures can be logged as structured data, mapped back to the
+//! original source of transpiled scripts, and shown to developers.
+//!
+//! Error responses only carry the exception's stack and location when
+//! `javascript.expose_error_details` is set; otherwise clients see a one-line
+//! summary.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::source_maps::ScriptSourceMap;
+
+/// Most stack frames included in an error response
+const MAX_RESPONSE_FRAMES: usize = 10;
+
+/// Longest exception message included in an error response
+const MAX_RESPONSE_MESSAGE_LENGTH: usize = 1000;
+
+static EXPOSE_ERROR_DETAILS: AtomicBool = AtomicBool::new(false);
+
+/// Set whether error responses include exception details. Called at startup.
+pub fn configure_error_details(expose: bool) {
+    EXPOSE_ERROR_DETAILS.store(expose, Ordering::Relaxed);
+}
+
+/// Whether error responses include exception details
+pub fn error_details_exposed() -> bool {
+    EXPOSE_ERROR_DETAILS.load(Ordering::Relaxed)
+}
+
+/// An exception thrown by a script
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsError {
+    /// Error class, e.g. `TypeError`; None when a non-Error value was thrown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// 1-based line of the throwing statement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_number: Option<u32>,
+    /// 1-based column of the throwing statement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column_number: Option<u32>,
+}
+
+impl JsError {
+    /// Take the pending exception off the context. Errors that did not come
+    /// from a thrown value (conversion failures and the like) are described
+    /// by their Display output.
+    pub fn capture(ctx: &rquickjs::Ctx<'_>, error: &rquickjs::Error) -> Self {
+        let exception = ctx.catch();
+
+        if let Some(err_str) = exception.as_string()
+            && let Ok(message) = err_str.to_string()
+            && !message.is_empty()
+        {
+            return Self {
+                message,
+                ..Default::default()
+            };
+        }
+
+        if let Some(err_obj) = exception.as_object() {
+            let mut captured = Self {
+                name: err_obj.get::<_, String>("name").ok(),
+                message: err_obj.get::<_, String>("message").unwrap_or_default(),
+                stack: err_obj
+                    .get::<_, String>("stack")
+                    .ok()
+                    .filter(|stack| !stack.is_empty()),
+                file_name: err_obj.get::<_, String>("fileName").ok(),
+                line_number: err_obj
+                    .get::<_, i32>("lineNumber")
+                    .ok()
+                    .and_then(|line| u32::try_from(line).ok()),
+                column_number: err_obj
+                    .get::<_, i32>("columnNumber")
+                    .ok()
+                    .and_then(|column| u32::try_from(column).ok()),
+            };
+            // QuickJS sets the location on syntax errors only; the top stack
+            // frame has it for everything else
+            let top_frame = captured
+                .frames()
+                .find(|frame| frame.line.is_some())
+                .map(|frame| (frame.file.to_string(), frame.line, frame.column));
+            if captured.line_number.is_none()
+                && let Some((file, line, column)) = top_frame
+            {
+                captured.file_name = Some(file);
+                captured.line_number = line;
+                captured.column_number = column;
+            }
+            if captured.name.is_some() || !captured.message.is_empty() || captured.stack.is_some() {
+                return captured;
+            }
+        }
+
+        if !exception.is_undefined()
+            && let Ok(rquickjs::convert::Coerced(message)) =
+                exception.get::<rquickjs::convert::Coerced<String>>()
+        {
+            return Self {
+                message,
+                ..Default::default()
+            };
+        }
+
+        Self {
+            message: error.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// `name: message`, or just the message for thrown non-Error values
+    pub fn headline(&self) -> String {
+        match &self.name {
+            Some(name) if !self.message.is_empty() => format!("{}: {}", name, self.message),
+            Some(name) => name.clone(),
+            None => self.message.clone(),
+        }
+    }
+
+    /// Whether the error is QuickJS giving up on an allocation beyond the
+    /// runtime's memory limit
+    pub fn is_out_of_memory(&self) -> bool {
+        self.name.as_deref() == Some("InternalError") && self.message == "out of memory"
+    }
+
+    /// Frames of the stack trace
+    pub fn frames(&self) -> impl Iterator<Item = StackFrame<'_>> {
+        self.stack
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .filter_map(StackFrame::parse)
+    }
+
+    /// Rewrite the location and the stack frames in `file_name` to positions
+    /// in the source the map was generated from
+    pub fn remap(&mut self, file_name: &str, source_map: &ScriptSourceMap) {
+        if self.file_name.as_deref() == Some(file_name)
+            && let Some(line) = self.line_number
+            && let Some(original) = source_map.original_position(line, self.column_number)
+        {
+            self.file_name = Some(original.source.unwrap_or_else(|| file_name.to_string()));
+            self.line_number = Some(original.line);
+            self.column_number = Some(original.column);
+        }
+
+        if let Some(stack) = &self.stack {
+            self.stack = Some(remap_stack(stack, file_name, source_map));
+        }
+    }
+
+    /// Version of the exception fit for an error response: the message is
+    /// capped, and engine-internal frames and the frame count are trimmed
+    pub fn sanitized(&self) -> serde_json::Value {
+        let message: String = self
+            .message
+            .chars()
+            .take(MAX_RESPONSE_MESSAGE_LENGTH)
+            .collect();
+        let frames: Vec<String> = self
+            .frames()
+            .filter(|frame| !frame.is_native())
+            .take(MAX_RESPONSE_FRAMES)
+            .map(|frame| frame.to_string())
+            .collect();
+        let sanitized = JsError {
+            name: self.name.clone(),
+            message,
+            stack: (!frames.is_empty()).then(|| frames.join("\n")),
+            file_name: self.file_name.clone(),
+            line_number: self.line_number,
+            column_number: self.column_number,
+        };
+        serde_json::to_value(sanitized).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for JsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![self.message.clone()];
+        if let Some(file) = &self.file_name {
+            parts.push(format!("at {}", file));
+        }
+        if let Some(line) = self.line_number {
+            parts.push(format!("line {}", line));
+        }
+        if let Some(column) = self.column_number {
+            parts.push(format!("column {}", column));
+        }
+        if let Some(stack) = &self.stack {
+            parts.push(format!("\nStack:\n{}", stack));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Rewrite the stack frames in `file_name` found in `text` (a stack trace,
+/// or a log message that contains one) to positions in the source the map
+/// was generated from. Other lines are left as they are.
+pub fn remap_stack(text: &str, file_name: &str, source_map: &ScriptSourceMap) -> String {
+    text.lines()
+        .map(|line| match StackFrame::parse(line) {
+            Some(frame) if frame.file == file_name => frame
+                .line
+                .and_then(|l| source_map.original_position(l, frame.column))
+                .map(|original| {
+                    let file = original.source.as_deref().unwrap_or(file_name);
+                    frame.relocated(line, file, original.line, original.column)
+                })
+                .unwrap_or_else(|| line.to_string()),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One `at function (file:line:column)` line of a QuickJS stack trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame<'a> {
+    pub function: Option<&'a str>,
+    pub file: &'a str,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl<'a> StackFrame<'a> {
+    /// Parse a stack trace line; None for lines that are not frames
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("at ")?;
+        let (function, location) = match rest.strip_suffix(')') {
+            Some(inner) => {
+                let (function, location) = inner.rsplit_once(" (")?;
+                (Some(function), location)
+            }
+            None => (None, rest),
+        };
+
+        // Files may be URLs with colons of their own, so parse from the end
+        let mut parts = location.rsplitn(3, ':');
+        let last = parts.next()?;
+        let (file, line, column) = match (last.parse::<u32>(), parts.next(), parts.next()) {
+            (Ok(column), Some(line), Some(file)) if line.parse::<u32>().is_ok() => {
+                (file, line.parse().ok(), Some(column))
+            }
+            (Ok(line), Some(_), _) => {
+                let (file, _) = location.rsplit_once(':')?;
+                (file, Some(line), None)
+            }
+            _ => (location, None, None),
+        };
+        Some(Self {
+            function,
+            file,
+            line,
+            column,
+        })
+    }
+
+    /// Frames of functions implemented by the engine or host
+    pub fn is_native(&self) -> bool {
+        self.file == "native"
+    }
+
+    /// `line` with this frame's position replaced
+    fn relocated(&self, line: &str, file: &str, new_line: u32, new_column: u32) -> String {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let frame = StackFrame {
+            file,
+            line: Some(new_line),
+            column: Some(new_column),
+            ..*self
+        };
+        format!("{}{}", indent, frame)
+    }
+}
+
+impl std::fmt::Display for StackFrame<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut location = self.file.to_string();
+        if let Some(line) = self.line {
+            location.push_str(&format!(":{}", line));
+            if let Some(column) = self.column {
+                location.push_str(&format!(":{}", column));
+            }
+        }
+        match self.function {
+            Some(function) => write!(f, "at {} ({})", function, location),
+            None => write!(f, "at {}", location),
+        }
+    }
+}
+
+/// Why running a script failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptFailure {
+    /// Full description, including the exception's location and stack
+    pub message: String,
+    /// One-line description without location or stack, safe for clients
+    pub summary: String,
+    /// The exception, when the script threw one
+    pub exception: Option<Box<JsError>>,
+}
+
+impl ScriptFailure {
+    /// Failure caused by an exception thrown while `stage` ran, e.g.
+    /// "call handler"
+    pub fn exception(stage: &str, exception: JsError) -> Self {
+        Self {
+            message: format!("{}: {}", stage, exception),
+            summary: format!("{}: {}", stage, exception.headline()),
+            exception: Some(Box::new(exception)),
+        }
+    }
+}
+
+impl From<String> for ScriptFailure {
+    fn from(message: String) -> Self {
+        Self {
+            summary: message.lines().next().unwrap_or_default().to_string(),
+            message,
+            exception: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ScriptFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stack_frames() {
+        assert_eq!(
+            StackFrame::parse("    at handler (https://example.com/api.ts:12:7)"),
+            Some(StackFrame {
+                function: Some("handler"),
+                file: "https://example.com/api.ts",
+                line: Some(12),
+                column: Some(7),
+            })
+        );
+        assert_eq!(
+            StackFrame::parse("    at <eval> (main.js:3)"),
+            Some(StackFrame {
+                function: Some("<eval>"),
+                file: "main.js",
+                line: Some(3),
+                column: None,
+            })
+        );
+        let native = StackFrame::parse("    at map (native)").unwrap();
+        assert!(native.is_native());
+        assert_eq!(native.line, None);
+        assert_eq!(StackFrame::parse("TypeError: not a function"), None);
+    }
+
+    #[test]
+    fn test_sanitized_drops_native_frames() {
+        let error = JsError {
+            name: Some("TypeError".to_string()),
+            message: "x".repeat(MAX_RESPONSE_MESSAGE_LENGTH + 10),
+            stack: Some(
+                "    at map (native)\n    at handler (api.js:2:5)\n    at <eval> (api.js:9)"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let sanitized = error.sanitized();
+        assert_eq!(sanitized["name"], "TypeError");
+        assert_eq!(
+            sanitized["message"].as_str().unwrap().len(),
+            MAX_RESPONSE_MESSAGE_LENGTH
+        );
+        assert_eq!(
+            sanitized["stack"],
+            "at handler (api.js:2:5)\nat <eval> (api.js:9)"
+        );
+    }
+
+    #[test]
+    fn test_failure_summary_omits_stack() {
+        let failure = ScriptFailure::exception(
+            "call handler",
+            JsError {
+                name: Some("Error".to_string()),
+                message: "boom".to_string(),
+                stack: Some("    at handler (api.js:2:5)".to_string()),
+                file_name: Some("api.js".to_string()),
+                line_number: Some(2),
+                column_number: Some(5),
+            },
+        );
+        assert_eq!(failure.summary, "call handler: Error: boom");
+        assert!(failure.message.contains("line 2"));
+        assert!(failure.message.contains("Stack:"));
+
+        let plain = ScriptFailure::from("no handler h: not found\nmore".to_string());
+        assert_eq!(plain.summary, "no handler h: not found");
+        assert!(plain.exception.is_none());
+    }
+}
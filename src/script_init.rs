@@ -1,6 +1,6 @@
 use crate::error::AppResult;
 use crate::repository;
-use crate::repository::Repository;
+use crate::repository::{Repository, RouteRegistrations};
 use crate::scheduler;
 use std::time::{Duration, SystemTime};
 use tokio::time::timeout;
@@ -65,11 +65,75 @@ impl InitContext {
     }
 }
 
+/// Run the `init()` of a script in a sandbox transaction, so database
+/// writes it makes are rolled back. Nothing is recorded about the script;
+/// the registrations it makes with the global registries are left to the
+/// caller.
+pub async fn init_in_sandbox(
+    uri: &str,
+    content: String,
+    timeout_ms: u64,
+) -> Result<Option<RouteRegistrations>, String> {
+    let script_uri = uri.to_string();
+    let outcome = timeout(
+        Duration::from_millis(timeout_ms),
+        tokio::task::spawn_blocking(move || {
+            let _sandbox = crate::database::Database::begin_sandbox_transaction()
+                .map_err(|e| format!("Cannot start sandbox: {}", e))?;
+            let context = InitContext::new(script_uri.clone(), true);
+            crate::js_engine::call_init_if_exists_with_timeout(
+                &script_uri,
+                &content,
+                context,
+                timeout_ms,
+            )
+        }),
+    )
+    .await;
+    match outcome {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(format!("Init task failed: {}", e)),
+        Err(_) => Err(format!("Init timeout ({}ms)", timeout_ms)),
+    }
+}
+
 /// Script initializer responsible for calling init() functions
 pub struct ScriptInitializer {
     timeout_ms: u64,
 }
 
+/// Warn about routes `script_uri` registers that another script registers
+/// too, in the server log and the logs of `script_uri`
+async fn log_route_conflicts(script_uri: &str) {
+    let conflicts = match crate::route_index::conflicts().await {
+        Ok(conflicts) => conflicts,
+        Err(e) => {
+            warn!("Failed to check route conflicts of {}: {}", script_uri, e);
+            return;
+        }
+    };
+    for conflict in conflicts
+        .iter()
+        .filter(|conflict| conflict.scripts.iter().any(|uri| uri == script_uri))
+    {
+        let message = format!(
+            "Route conflict: {} {}{} is registered by {}; {} serves it",
+            conflict.method,
+            conflict.host.as_deref().unwrap_or(""),
+            conflict.path,
+            conflict.scripts.join(", "),
+            conflict.winner
+        );
+        warn!("{}", message);
+        if let Err(e) = repository::get_repository()
+            .insert_log(script_uri, &message, "WARN")
+            .await
+        {
+            warn!("Failed to log route conflict to database: {}", e);
+        }
+    }
+}
+
 impl ScriptInitializer {
     /// Create a new script initializer
     pub fn new(timeout_ms: u64) -> Self {
@@ -101,8 +165,11 @@ impl ScriptInitializer {
             }
         };
 
-        // Prevent stale scheduled work from previous deployments
+        // Prevent stale scheduled work, channel and event subscriptions from previous
+        // deployments; init() registers them again
         scheduler::clear_script_jobs(script_uri);
+        crate::notifications::clear_script_channels(script_uri);
+        crate::events::clear_script_subscriptions(script_uri);
 
         debug!("Initializing script: {}", script_uri);
 
@@ -150,6 +217,7 @@ impl ScriptInitializer {
                         "✓ Script '{}' initialized successfully in {}ms",
                         script_uri, duration_ms
                     );
+                    log_route_conflicts(script_uri).await;
                     Ok(InitResult::success(script_uri.to_string(), duration_ms))
                 }
                 Ok(None) => {
@@ -278,6 +346,46 @@ impl ScriptInitializer {
     }
 }
 
+/// Prepare an initialized script for its first request in the background:
+/// compile its bytecode on the worker that will serve it and rebuild the
+/// route index its registrations invalidated.
+pub async fn warm_up_script(script_uri: &str) {
+    let start_time = std::time::Instant::now();
+    let content = match repository::get_repository()
+        .get_script_metadata(script_uri)
+        .await
+    {
+        Ok(metadata) => metadata.content,
+        Err(e) => {
+            debug!("Skipping warm-up of '{}': {}", script_uri, e);
+            return;
+        }
+    };
+
+    let uri = script_uri.to_string();
+    match crate::worker_pool::run(script_uri, move || {
+        crate::js_engine::warm_up_script(&uri, &content)
+    })
+    .await
+    {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => warn!("Failed to warm up script '{}': {}", script_uri, e),
+        Err(e) => debug!("Skipping warm-up of '{}': {}", script_uri, e),
+    }
+
+    if let Err(e) = crate::route_index::warm().await {
+        warn!(
+            "Failed to rebuild route index after warming up '{}': {}",
+            script_uri, e
+        );
+    }
+    debug!(
+        "Script '{}' warmed up in {}ms",
+        script_uri,
+        start_time.elapsed().as_millis()
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,316 @@
+//! Syntax and lint checks for scripts being saved.
+//!
+//! [`validate`] parses a script the way the engine will load it. Syntax
+//! errors reject the save, with the line and column of the problem. Scripts
+//! that parse are then linted with the rules in `[javascript.lint]`, and the
+//! findings are returned to the caller as warnings:
+//!
+//! - `unused-handler`: a top-level function is never called and its name
+//!   appears in no string, so no registration can refer to it
+//! - `missing-init`: the script has no `init()` function, so it registers no
+//!   routes or other handlers
+//! - `forbidden-global`: the script uses a global listed in
+//!   `forbidden_globals`
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{OnceLock, RwLock};
+
+use oxc::allocator::Allocator;
+use oxc::ast::ast::{IdentifierReference, StringLiteral};
+use oxc::ast_visit::Visit;
+use oxc::parser::Parser;
+use oxc::semantic::{Scoping, SemanticBuilder, SymbolFlags};
+use serde::Serialize;
+
+use crate::config::ScriptLintConfig;
+
+static LINT: OnceLock<RwLock<ScriptLintConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<ScriptLintConfig> {
+    LINT.get_or_init(Default::default)
+}
+
+/// Apply the lint configuration. Called once at server startup.
+pub fn configure(config: &ScriptLintConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> ScriptLintConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// A script that does not parse
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxError {
+    pub message: String,
+    /// 1-based line of the error
+    pub line: usize,
+    /// 1-based column of the error, in characters
+    pub column: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Syntax error at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// A lint finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintWarning {
+    /// Rule that produced the warning, e.g. `unused-handler`
+    pub rule: &'static str,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Check that `content` parses as the script at `uri` and lint it with the
+/// configured rules
+pub fn validate(uri: &str, content: &str) -> Result<Vec<LintWarning>, SyntaxError> {
+    validate_with(uri, content, &current_settings())
+}
+
+fn validate_with(
+    uri: &str,
+    content: &str,
+    config: &ScriptLintConfig,
+) -> Result<Vec<LintWarning>, SyntaxError> {
+    let allocator = Allocator::default();
+    // Scripts without imports or exports are loaded as classic scripts
+    let source_type = crate::transpiler::get_source_type(uri).with_unambiguous(true);
+    let parsed = Parser::new(&allocator, content, source_type).parse();
+    let positions = Positions::new(content);
+    if let Some(error) = parsed.diagnostics.first() {
+        let offset = error
+            .labels
+            .as_slice()
+            .first()
+            .map_or(0, |label| label.offset());
+        let (line, column) = positions.at(offset as usize);
+        return Err(SyntaxError {
+            message: error.message.to_string(),
+            line,
+            column,
+        });
+    }
+    if parsed.panicked {
+        return Err(SyntaxError {
+            message: "Script could not be parsed".to_string(),
+            line: 1,
+            column: 1,
+        });
+    }
+
+    let scoping = SemanticBuilder::new()
+        .build(&parsed.program)
+        .semantic
+        .into_scoping();
+    let forbidden: HashSet<&str> = config
+        .forbidden_globals
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut collector = Collector {
+        scoping: &scoping,
+        forbidden: &forbidden,
+        strings: HashSet::new(),
+        forbidden_uses: Vec::new(),
+    };
+    collector.visit_program(&parsed.program);
+
+    let mut warnings = Vec::new();
+    let root_bindings = scoping.get_bindings(scoping.root_scope_id());
+
+    if config.missing_init && root_bindings.get("init").is_none() {
+        warnings.push(LintWarning {
+            rule: "missing-init",
+            message: "Script has no init() function, so it registers no handlers".to_string(),
+            line: 1,
+            column: 1,
+        });
+    }
+
+    if config.unused_handlers {
+        for symbol_id in root_bindings.values() {
+            let name = scoping.symbol_name(*symbol_id);
+            if scoping
+                .symbol_flags(*symbol_id)
+                .contains(SymbolFlags::Function)
+                && name != "init"
+                && scoping.get_resolved_reference_ids(*symbol_id).is_empty()
+                && !collector.strings.contains(name)
+            {
+                let (line, column) = positions.at(scoping.symbol_span(*symbol_id).start as usize);
+                warnings.push(LintWarning {
+                    rule: "unused-handler",
+                    message: format!(
+                        "Function '{}' is never called or registered as a handler",
+                        name
+                    ),
+                    line,
+                    column,
+                });
+            }
+        }
+    }
+
+    for (name, offset) in collector.forbidden_uses {
+        let (line, column) = positions.at(offset);
+        warnings.push(LintWarning {
+            rule: "forbidden-global",
+            message: format!("'{}' is a forbidden global", name),
+            line,
+            column,
+        });
+    }
+
+    warnings.sort_by_key(|warning| (warning.line, warning.column));
+    Ok(warnings)
+}
+
+/// Converts byte offsets to 1-based lines and columns
+struct Positions<'s> {
+    content: &'s str,
+    line_starts: Vec<usize>,
+}
+
+impl<'s> Positions<'s> {
+    fn new(content: &'s str) -> Self {
+        Self {
+            content,
+            line_starts: std::iter::once(0)
+                .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+                .collect(),
+        }
+    }
+
+    fn at(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.content.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self
+            .content
+            .get(line_start..offset)
+            .map_or(0, |text| text.chars().count());
+        (line, column + 1)
+    }
+}
+
+/// Collects the strings a script contains and its uses of forbidden globals
+struct Collector<'s> {
+    scoping: &'s Scoping,
+    forbidden: &'s HashSet<&'s str>,
+    strings: HashSet<String>,
+    /// Name and byte offset of each use of a forbidden global
+    forbidden_uses: Vec<(String, usize)>,
+}
+
+impl<'a> Visit<'a> for Collector<'_> {
+    fn visit_string_literal(&mut self, literal: &StringLiteral<'a>) {
+        self.strings.insert(literal.value.to_string());
+    }
+
+    fn visit_identifier_reference(&mut self, identifier: &IdentifierReference<'a>) {
+        let name = identifier.name.as_str();
+        if !self.forbidden.contains(name) {
+            return;
+        }
+        let is_global = identifier.reference_id.get().is_none_or(|reference_id| {
+            self.scoping
+                .get_reference(reference_id)
+                .symbol_id()
+                .is_none()
+        });
+        if is_global {
+            self.forbidden_uses
+                .push((name.to_string(), identifier.span.start as usize));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_error_has_position() {
+        let error = validate_with(
+            "broken.js",
+            "function init() {\n  let x = ;\n}\n",
+            &ScriptLintConfig::default(),
+        )
+        .unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+        assert!(
+            error
+                .to_string()
+                .starts_with("Syntax error at line 2, column 11")
+        );
+
+        assert!(
+            validate_with(
+                "typed.ts",
+                "const n: number = 1;\n",
+                &ScriptLintConfig::default()
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let source = r#"function init(context) {
+    routeRegistry.registerRoute("/", "home", "GET");
+}
+function home(context) {
+    return { status: 200, body: format(eval("1 + 1")) };
+}
+function format(value) {
+    return String(value);
+}
+function leftover() {}
+"#;
+        let warnings = validate_with("lint.js", source, &ScriptLintConfig::default()).unwrap();
+        let found: Vec<(&str, usize)> = warnings.iter().map(|w| (w.rule, w.line)).collect();
+        assert_eq!(found, vec![("forbidden-global", 5), ("unused-handler", 10)]);
+        assert!(warnings[1].message.contains("'leftover'"));
+
+        let warnings = validate_with(
+            "lint.js",
+            "function helper() {}\n",
+            &ScriptLintConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(warnings[0].rule, "missing-init");
+
+        let quiet = ScriptLintConfig {
+            unused_handlers: false,
+            missing_init: false,
+            forbidden_globals: Vec::new(),
+        };
+        assert!(validate_with("lint.js", source, &quiet).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shadowed_forbidden_global_is_allowed() {
+        let source = "function init() {\n    const eval = (x) => x;\n    return eval(1);\n}\n";
+        let warnings = validate_with("shadow.js", source, &ScriptLintConfig::default()).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+}
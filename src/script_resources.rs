@@ -0,0 +1,392 @@
+//! Execution time, memory, timeouts and outbound traffic per script.
+//!
+//! Every execution of a script (route handlers, resolvers, scheduled jobs,
+//! tasks, events, `init()`) adds the milliseconds its runtime ran, the heap
+//! QuickJS had allocated when it finished and whether the execution timeout
+//! interrupted it; `fetch()` and webhook deliveries add the bytes they sent
+//! and received. Sums are kept in memory per script and day (UTC) and added to
+//! the `script_resource_usage` table every
+//! `repository.route_usage_flush_interval_secs`, so a report covers every
+//! server instance. Days older than
+//! `repository.script_resources_retention_days` are removed.
+//!
+//! `scriptStorage.getResourceUsage()` and the `scriptResourceUsage` GraphQL
+//! query list scripts by execution time, so noisy scripts stand out.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::config::RepositoryConfig;
+use crate::repository;
+
+/// Days reported when the caller gives none
+pub const DEFAULT_REPORT_DAYS: u32 = 7;
+
+/// Most days one report covers
+pub const MAX_REPORT_DAYS: u32 = 366;
+
+/// Scripts reported when the caller gives no limit
+pub const DEFAULT_REPORT_LIMIT: usize = 50;
+
+/// Scripts aggregated in memory between flushes; usage of further scripts is
+/// dropped until the next flush
+const MAX_PENDING_SCRIPTS: usize = 10_000;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Resource usage of one script on one day, as stored in
+/// `script_resource_usage`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptResourceEntry {
+    pub day: NaiveDate,
+    pub script_uri: String,
+    pub executions: i64,
+    /// Milliseconds the script's runtime ran
+    pub cpu_ms: f64,
+    /// Most heap any execution had allocated when it finished
+    pub peak_memory_bytes: i64,
+    /// Executions interrupted by the execution timeout
+    pub timeouts: i64,
+    /// Bytes sent and received by `fetch()` and webhook deliveries
+    pub outbound_bytes: i64,
+    pub last_execution_at: Option<DateTime<Utc>>,
+}
+
+impl ScriptResourceEntry {
+    fn new(day: NaiveDate, script_uri: &str) -> Self {
+        Self {
+            day,
+            script_uri: script_uri.to_string(),
+            executions: 0,
+            cpu_ms: 0.0,
+            peak_memory_bytes: 0,
+            timeouts: 0,
+            outbound_bytes: 0,
+            last_execution_at: None,
+        }
+    }
+
+    fn add(&mut self, other: &ScriptResourceEntry) {
+        self.executions += other.executions;
+        self.cpu_ms += other.cpu_ms;
+        self.peak_memory_bytes = self.peak_memory_bytes.max(other.peak_memory_bytes);
+        self.timeouts += other.timeouts;
+        self.outbound_bytes += other.outbound_bytes;
+        self.last_execution_at = self.last_execution_at.max(other.last_execution_at);
+    }
+}
+
+/// Resource usage of one script over the reported days
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptResourceUsage {
+    pub script_uri: String,
+    pub executions: i64,
+    pub cpu_ms: f64,
+    pub avg_cpu_ms: Option<f64>,
+    pub peak_memory_bytes: i64,
+    pub timeouts: i64,
+    pub outbound_bytes: i64,
+    pub last_execution_at: Option<String>,
+}
+
+/// Report of `scriptStorage.getResourceUsage()`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptResourceReport {
+    /// First day reported (YYYY-MM-DD, UTC)
+    pub since: String,
+    pub days: u32,
+    /// Most execution time first
+    pub scripts: Vec<ScriptResourceUsage>,
+}
+
+/// Options of `scriptStorage.getResourceUsage()`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ScriptResourceOptions {
+    /// Days to report, ending today
+    pub days: Option<u32>,
+    /// Report only this script
+    pub uri: Option<String>,
+    /// Most scripts reported
+    pub limit: Option<usize>,
+}
+
+struct Settings {
+    enabled: bool,
+    flush_interval: Duration,
+    retention_days: u64,
+}
+
+static SETTINGS: OnceLock<Mutex<Settings>> = OnceLock::new();
+static PENDING: OnceLock<Mutex<HashMap<(NaiveDate, String), ScriptResourceEntry>>> =
+    OnceLock::new();
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn settings_from(config: &RepositoryConfig) -> Settings {
+    Settings {
+        enabled: config.script_resources_enabled,
+        flush_interval: Duration::from_secs(config.route_usage_flush_interval_secs.max(1)),
+        retention_days: config.script_resources_retention_days,
+    }
+}
+
+fn lock_settings() -> MutexGuard<'static, Settings> {
+    match SETTINGS
+        .get_or_init(|| Mutex::new(settings_from(&RepositoryConfig::default())))
+        .lock()
+    {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn lock_pending() -> MutexGuard<'static, HashMap<(NaiveDate, String), ScriptResourceEntry>> {
+    match PENDING.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Script resource usage mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Apply the repository configuration. Called once at server startup.
+pub fn configure(config: &RepositoryConfig) {
+    *lock_settings() = settings_from(config);
+}
+
+/// Update today's usage of a script
+fn update(script_uri: &str, apply: impl FnOnce(&mut ScriptResourceEntry)) {
+    if !lock_settings().enabled {
+        return;
+    }
+    let day = Utc::now().date_naive();
+    let key = (day, script_uri.to_string());
+    let mut pending = lock_pending();
+    if !pending.contains_key(&key) && pending.len() >= MAX_PENDING_SCRIPTS {
+        debug!(
+            "Script resource usage buffer is full; not counting {}",
+            script_uri
+        );
+        return;
+    }
+    apply(
+        pending
+            .entry(key)
+            .or_insert_with(|| ScriptResourceEntry::new(day, script_uri)),
+    );
+}
+
+/// Count one execution of a script that ran for `elapsed` and had
+/// `memory_bytes` of heap allocated when it finished
+pub fn record_execution(script_uri: &str, elapsed: Duration, memory_bytes: i64, timed_out: bool) {
+    update(script_uri, |entry| {
+        entry.executions += 1;
+        entry.cpu_ms += elapsed.as_secs_f64() * 1000.0;
+        entry.peak_memory_bytes = entry.peak_memory_bytes.max(memory_bytes);
+        if timed_out {
+            entry.timeouts += 1;
+        }
+        entry.last_execution_at = Some(Utc::now());
+    });
+}
+
+/// Count bytes a script sent and received over outbound HTTP
+pub fn record_outbound(script_uri: &str, bytes: u64) {
+    if bytes == 0 {
+        return;
+    }
+    update(script_uri, |entry| {
+        entry.outbound_bytes = entry
+            .outbound_bytes
+            .saturating_add(i64::try_from(bytes).unwrap_or(i64::MAX));
+    });
+}
+
+/// Add the usage kept in memory to the `script_resource_usage` table. Usage
+/// that fails to be stored is kept for the next flush.
+pub async fn flush() {
+    let entries: Vec<ScriptResourceEntry> =
+        lock_pending().drain().map(|(_, entry)| entry).collect();
+    if entries.is_empty() {
+        return;
+    }
+    let repo = repository::get_repository();
+    let mut failed = Vec::new();
+    for entry in entries {
+        if let Err(e) = repo.record_script_resources(&entry).await {
+            warn!(
+                "Failed to store resource usage of {}: {}",
+                entry.script_uri, e
+            );
+            failed.push(entry);
+        }
+    }
+    let mut pending = lock_pending();
+    for entry in failed {
+        match pending.get_mut(&(entry.day, entry.script_uri.clone())) {
+            Some(existing) => existing.add(&entry),
+            None => {
+                pending.insert((entry.day, entry.script_uri.clone()), entry);
+            }
+        }
+    }
+}
+
+/// Start flushing usage to the database and removing old days
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut last_purge: Option<std::time::Instant> = None;
+        loop {
+            let (flush_interval, retention_days) = {
+                let settings = lock_settings();
+                (settings.flush_interval, settings.retention_days)
+            };
+            tokio::time::sleep(flush_interval).await;
+            if repository::get_repository_opt().is_none() {
+                continue;
+            }
+            flush().await;
+
+            if retention_days > 0 && last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                last_purge = Some(std::time::Instant::now());
+                let before = Utc::now().date_naive()
+                    - chrono::Duration::days(i64::try_from(retention_days).unwrap_or(i64::MAX));
+                match repository::get_repository()
+                    .purge_script_resources(before)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Removed {} script resource usage rows", count),
+                    Err(e) => warn!("Failed to purge script resource usage: {}", e),
+                }
+            }
+        }
+    });
+}
+
+/// Sum `entries` per script, most execution time first
+pub fn summarize(entries: &[ScriptResourceEntry]) -> Vec<ScriptResourceUsage> {
+    let mut totals: BTreeMap<&str, ScriptResourceEntry> = BTreeMap::new();
+    for entry in entries {
+        totals
+            .entry(entry.script_uri.as_str())
+            .or_insert_with(|| ScriptResourceEntry::new(entry.day, &entry.script_uri))
+            .add(entry);
+    }
+    let mut scripts: Vec<ScriptResourceUsage> = totals
+        .into_values()
+        .map(|total| ScriptResourceUsage {
+            avg_cpu_ms: (total.executions > 0).then(|| total.cpu_ms / total.executions as f64),
+            script_uri: total.script_uri,
+            executions: total.executions,
+            cpu_ms: total.cpu_ms,
+            peak_memory_bytes: total.peak_memory_bytes,
+            timeouts: total.timeouts,
+            outbound_bytes: total.outbound_bytes,
+            last_execution_at: total.last_execution_at.map(|at| at.to_rfc3339()),
+        })
+        .collect();
+    scripts.sort_by(|a, b| b.cpu_ms.total_cmp(&a.cpu_ms));
+    scripts
+}
+
+/// Resource usage of scripts over the last days, including usage not yet
+/// flushed by this instance
+pub fn report(options: &ScriptResourceOptions) -> Result<ScriptResourceReport, String> {
+    let days = options.days.unwrap_or(DEFAULT_REPORT_DAYS);
+    if !(1..=MAX_REPORT_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {}", MAX_REPORT_DAYS));
+    }
+    let since = Utc::now().date_naive() - chrono::Duration::days(i64::from(days) - 1);
+    let mut entries = repository::list_script_resources(options.uri.as_deref(), since)
+        .map_err(|e| format!("Failed to read script resource usage: {}", e))?;
+    entries.extend(
+        lock_pending()
+            .values()
+            .filter(|entry| {
+                entry.day >= since
+                    && options
+                        .uri
+                        .as_deref()
+                        .is_none_or(|uri| entry.script_uri == uri)
+            })
+            .cloned(),
+    );
+    let mut scripts = summarize(&entries);
+    scripts.truncate(options.limit.unwrap_or(DEFAULT_REPORT_LIMIT));
+    Ok(ScriptResourceReport {
+        since: since.format("%Y-%m-%d").to_string(),
+        days,
+        scripts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(script_uri: &str, day: u32, executions: i64, cpu_ms: f64) -> ScriptResourceEntry {
+        let mut entry =
+            ScriptResourceEntry::new(NaiveDate::from_ymd_opt(2026, 5, day).unwrap(), script_uri);
+        entry.executions = executions;
+        entry.cpu_ms = cpu_ms;
+        entry
+    }
+
+    #[test]
+    fn test_summarize_merges_days_and_sorts_by_time() {
+        let mut busy = entry("https://example.com/busy", 1, 10, 900.0);
+        busy.peak_memory_bytes = 4096;
+        busy.timeouts = 1;
+        let mut busy_later = entry("https://example.com/busy", 2, 10, 100.0);
+        busy_later.peak_memory_bytes = 1024;
+        busy_later.outbound_bytes = 300;
+
+        let scripts = summarize(&[
+            entry("https://example.com/quiet", 1, 5, 20.0),
+            busy,
+            busy_later,
+        ]);
+
+        assert_eq!(scripts.len(), 2);
+        let busy = &scripts[0];
+        assert_eq!(busy.script_uri, "https://example.com/busy");
+        assert_eq!(busy.executions, 20);
+        assert_eq!(busy.cpu_ms, 1000.0);
+        assert_eq!(busy.avg_cpu_ms, Some(50.0));
+        assert_eq!(busy.peak_memory_bytes, 4096);
+        assert_eq!(busy.timeouts, 1);
+        assert_eq!(busy.outbound_bytes, 300);
+        assert_eq!(scripts[1].script_uri, "https://example.com/quiet");
+    }
+
+    #[test]
+    fn test_record_execution_and_outbound() {
+        let script_uri = "https://example.com/resources-test";
+        record_execution(script_uri, Duration::from_millis(40), 2048, false);
+        record_execution(script_uri, Duration::from_millis(10), 1024, true);
+        record_outbound(script_uri, 512);
+
+        let pending = lock_pending();
+        let entry = pending
+            .get(&(Utc::now().date_naive(), script_uri.to_string()))
+            .expect("usage is kept in memory");
+        assert_eq!(entry.executions, 2);
+        assert!(entry.cpu_ms >= 50.0);
+        assert_eq!(entry.peak_memory_bytes, 2048);
+        assert_eq!(entry.timeouts, 1);
+        assert_eq!(entry.outbound_bytes, 512);
+    }
+}
@@ -190,10 +190,11 @@ impl SecurityAuditor {
             }
         }
 
-        // TODO: In production, also store in database and/or send to SIEM
-        // self.store_in_database(&event).await;
-        // self.send_to_siem(&event).await;
-        // self.store_threat_assessment(&threat_assessment).await;
+        // Run the configured threat response policies
+        super::threat_response::respond(&event, &threat_assessment).await;
+
+        // Send to the configured SIEM exporters, if any
+        super::audit_export::export(&event, &effective_severity);
 
         // For critical events, consider immediate alerting
         if matches!(event.severity, SecuritySeverity::Critical) {
@@ -0,0 +1,481 @@
+//! Export of security audit events to a SIEM (`[security.audit_export]`).
+//!
+//! Every event logged through [`super::SecurityAuditor::log_event`] is
+//! turned into an [`AuditRecord`] and buffered in memory. A background worker
+//! writes the buffer in batches to each configured exporter: RFC 5424 syslog
+//! over UDP or TCP, HTTPS batches (newline-delimited JSON, or Splunk HTTP
+//! Event Collector events) and a newline-delimited JSON file. A failed batch
+//! is retried with doubling delays, per exporter, before it is dropped.
+//!
+//! Records carry `schemaVersion`; fields are only added within a version, so
+//! SIEM parsers written for one version keep working until it changes.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock, RwLock};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
+
+use super::audit::{SecurityEvent, SecuritySeverity};
+use crate::config::{
+    AuditExportConfig, FileExportConfig, HttpsExportConfig, HttpsExportFormat, SyslogExportConfig,
+    SyslogProtocol,
+};
+
+/// Version of the [`AuditRecord`] schema
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Splunk sourcetype of exported events
+const SPLUNK_SOURCETYPE: &str = "aiwebengine:audit";
+
+/// An exported audit event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub schema_version: u32,
+    pub id: String,
+    /// RFC 3339
+    pub timestamp: String,
+    /// snake_case event type, e.g. `authentication_failure`
+    pub event_type: String,
+    /// Severity the event was logged with: low, medium, high or critical
+    pub severity: &'static str,
+    /// Severity after threat analysis
+    pub effective_severity: &'static str,
+    pub user_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub resource: Option<String>,
+    pub action: Option<String>,
+    pub details: HashMap<String, String>,
+    pub error_message: Option<String>,
+    /// Server instance that logged the event
+    pub server_id: Option<String>,
+}
+
+fn severity_name(severity: &SecuritySeverity) -> &'static str {
+    match severity {
+        SecuritySeverity::Low => "low",
+        SecuritySeverity::Medium => "medium",
+        SecuritySeverity::High => "high",
+        SecuritySeverity::Critical => "critical",
+    }
+}
+
+/// `AuthenticationFailure` -> `authentication_failure`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if index > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+impl AuditRecord {
+    pub fn new(event: &SecurityEvent, effective_severity: &SecuritySeverity) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            id: event.id.clone(),
+            timestamp: event.timestamp.to_rfc3339(),
+            event_type: snake_case(&format!("{:?}", event.event_type)),
+            severity: severity_name(&event.severity),
+            effective_severity: severity_name(effective_severity),
+            user_id: event.user_id.clone(),
+            ip_address: event.ip_address.clone(),
+            user_agent: event.user_agent.clone(),
+            resource: event.resource.clone(),
+            action: event.action.clone(),
+            details: event.details.clone(),
+            error_message: event.error_message.clone(),
+            server_id: crate::notifications::get_server_id(),
+        }
+    }
+
+    /// Syslog severity (RFC 5424) of the record
+    fn syslog_severity(&self) -> u8 {
+        match self.effective_severity {
+            "critical" => 2,
+            "high" => 3,
+            "medium" => 4,
+            _ => 6,
+        }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<AuditExportConfig>> = OnceLock::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BUFFER: OnceLock<Mutex<VecDeque<AuditRecord>>> = OnceLock::new();
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the worker when a batch is full
+static WAKE: Notify = Notify::const_new();
+
+fn settings() -> &'static RwLock<AuditExportConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the audit export configuration. Called once at server startup.
+pub fn configure(config: &AuditExportConfig) {
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> AuditExportConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+fn lock_buffer() -> MutexGuard<'static, VecDeque<AuditRecord>> {
+    match BUFFER.get_or_init(Default::default).lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            warn!("Audit export buffer mutex poisoned; recovering");
+            poisoned.into_inner()
+        }
+    }
+}
+
+/// Buffer an event for export. Does nothing while export is disabled.
+pub fn export(event: &SecurityEvent, effective_severity: &SecuritySeverity) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let (buffer_size, batch_size) = match settings().read() {
+        Ok(guard) => (guard.buffer_size, guard.batch_size),
+        Err(poisoned) => {
+            let guard = poisoned.into_inner();
+            (guard.buffer_size, guard.batch_size)
+        }
+    };
+    let mut buffer = lock_buffer();
+    if buffer.len() >= buffer_size.max(1) {
+        buffer.pop_front();
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    buffer.push_back(AuditRecord::new(event, effective_severity));
+    if buffer.len() >= batch_size {
+        WAKE.notify_one();
+    }
+}
+
+/// Events dropped because the buffer was full, since startup
+pub fn dropped_events() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// RFC 5424 message of one record
+fn syslog_message(config: &SyslogExportConfig, record: &AuditRecord) -> Result<String, String> {
+    let priority = u16::from(config.facility) * 8 + u16::from(record.syslog_severity());
+    let json = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    Ok(format!(
+        "<{}>1 {} {} {} - {} - {}",
+        priority,
+        record.timestamp,
+        record.server_id.as_deref().unwrap_or("-"),
+        config.app_name,
+        record.event_type,
+        json
+    ))
+}
+
+fn write_syslog(config: &SyslogExportConfig, records: &[AuditRecord]) -> Result<(), String> {
+    let messages = records
+        .iter()
+        .map(|record| syslog_message(config, record))
+        .collect::<Result<Vec<_>, _>>()?;
+    match config.protocol {
+        SyslogProtocol::Udp => {
+            let socket = std::net::UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+            for message in messages {
+                socket
+                    .send_to(message.as_bytes(), &config.address)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        SyslogProtocol::Tcp => {
+            let mut stream =
+                std::net::TcpStream::connect(&config.address).map_err(|e| e.to_string())?;
+            stream
+                .set_write_timeout(Some(Duration::from_secs(10)))
+                .map_err(|e| e.to_string())?;
+            for message in messages {
+                write!(stream, "{} {}", message.len(), message).map_err(|e| e.to_string())?;
+            }
+            stream.flush().map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Body of one HTTPS batch
+fn https_body(format: HttpsExportFormat, records: &[AuditRecord]) -> Result<String, String> {
+    let mut body = String::new();
+    for record in records {
+        let line = match format {
+            HttpsExportFormat::Ndjson => serde_json::to_string(record),
+            HttpsExportFormat::SplunkHec => {
+                let mut event = serde_json::json!({
+                    "time": chrono::DateTime::parse_from_rfc3339(&record.timestamp)
+                        .map(|time| time.timestamp_millis() as f64 / 1000.0)
+                        .unwrap_or_default(),
+                    "sourcetype": SPLUNK_SOURCETYPE,
+                    "event": record,
+                });
+                if let Some(server_id) = &record.server_id {
+                    event["host"] = server_id.clone().into();
+                }
+                serde_json::to_string(&event)
+            }
+        }
+        .map_err(|e| e.to_string())?;
+        body.push_str(&line);
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+/// Shared connection-pooled client; the exporter URL comes from the
+/// configuration, so it is not restricted like `fetch` targets
+fn shared_client() -> Result<&'static reqwest::blocking::Client, String> {
+    static CLIENT: OnceLock<Result<reqwest::blocking::Client, String>> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            reqwest::blocking::Client::builder()
+                .use_rustls_tls()
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn write_https(config: &HttpsExportConfig, records: &[AuditRecord]) -> Result<(), String> {
+    let content_type = match config.format {
+        HttpsExportFormat::Ndjson => "application/x-ndjson",
+        HttpsExportFormat::SplunkHec => "application/json",
+    };
+    let mut request = shared_client()?
+        .post(&config.url)
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .header("Content-Type", content_type)
+        .header("X-Audit-Schema-Version", SCHEMA_VERSION.to_string());
+    if let Some(authorization) = &config.authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let response = request
+        .body(https_body(config.format, records)?)
+        .send()
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status().as_u16()))
+    }
+}
+
+fn write_file(config: &FileExportConfig, records: &[AuditRecord]) -> Result<(), String> {
+    if let Some(parent) = config.path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if config.max_bytes > 0
+        && std::fs::metadata(&config.path).is_ok_and(|metadata| metadata.len() >= config.max_bytes)
+    {
+        let mut rotated = config.path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&config.path, rotated).map_err(|e| e.to_string())?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.path)
+        .map_err(|e| e.to_string())?;
+    let body = https_body(HttpsExportFormat::Ndjson, records)?;
+    file.write_all(body.as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Write a batch to one exporter, retrying with doubling delays
+async fn write_with_retries<F>(config: &AuditExportConfig, name: &'static str, write: F)
+where
+    F: Fn() -> Result<(), String> + Clone + Send + 'static,
+{
+    let mut delay = Duration::from_millis(config.retry_delay_ms);
+    for attempt in 0..=config.max_retries {
+        let write = write.clone();
+        let result = tokio::task::spawn_blocking(write)
+            .await
+            .unwrap_or_else(|e| Err(format!("Export task failed: {}", e)));
+        match result {
+            Ok(()) => return,
+            Err(e) if attempt < config.max_retries => {
+                debug!("Audit export to {} failed, will retry: {}", name, e);
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+            Err(e) => {
+                error!(
+                    "Audit export to {} failed after {} attempts; dropping batch: {}",
+                    name,
+                    attempt + 1,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Write one batch to every configured exporter
+async fn write_batch(config: &AuditExportConfig, records: Vec<AuditRecord>) {
+    let records = std::sync::Arc::new(records);
+    let syslog = config.syslog.clone().map(|syslog| {
+        let records = records.clone();
+        write_with_retries(config, "syslog", move || write_syslog(&syslog, &records))
+    });
+    let https = config.https.clone().map(|https| {
+        let records = records.clone();
+        write_with_retries(config, "https", move || write_https(&https, &records))
+    });
+    let file = config.file.clone().map(|file| {
+        let records = records.clone();
+        write_with_retries(config, "file", move || write_file(&file, &records))
+    });
+    tokio::join!(
+        async {
+            if let Some(syslog) = syslog {
+                syslog.await
+            }
+        },
+        async {
+            if let Some(https) = https {
+                https.await
+            }
+        },
+        async {
+            if let Some(file) = file {
+                file.await
+            }
+        },
+    );
+}
+
+/// Start the background task that writes buffered events to the exporters.
+/// Only the first call starts a worker, and only while export is enabled.
+pub fn spawn_worker() {
+    if !ENABLED.load(Ordering::Relaxed) || WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let config = current_settings();
+    if config.syslog.is_none() && config.https.is_none() && config.file.is_none() {
+        warn!("Audit export is enabled without exporters; events are only buffered");
+    }
+    tokio::spawn(async {
+        info!("Audit export worker started");
+        loop {
+            let config = current_settings();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(config.flush_interval_ms.max(1))) => {}
+                _ = WAKE.notified() => {}
+            }
+            loop {
+                let batch: Vec<AuditRecord> = {
+                    let mut buffer = lock_buffer();
+                    let count = buffer.len().min(config.batch_size.max(1));
+                    buffer.drain(..count).collect()
+                };
+                if batch.is_empty() {
+                    break;
+                }
+                write_batch(&config, batch).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::SecurityEventType;
+
+    fn record() -> AuditRecord {
+        let event = SecurityEvent::new(
+            SecurityEventType::AuthenticationFailure,
+            SecuritySeverity::Medium,
+            Some("user-1".to_string()),
+        )
+        .with_request_context(None, Some("203.0.113.7".to_string()))
+        .with_detail("reason", "invalid_password");
+        AuditRecord::new(&event, &SecuritySeverity::High)
+    }
+
+    #[test]
+    fn test_record_schema() {
+        let json = serde_json::to_value(record()).unwrap();
+        assert_eq!(json["schemaVersion"], SCHEMA_VERSION);
+        assert_eq!(json["eventType"], "authentication_failure");
+        assert_eq!(json["severity"], "medium");
+        assert_eq!(json["effectiveSeverity"], "high");
+        assert_eq!(json["ipAddress"], "203.0.113.7");
+        assert_eq!(json["details"]["reason"], "invalid_password");
+    }
+
+    #[test]
+    fn test_syslog_message() {
+        let config = SyslogExportConfig::default();
+        let record = record();
+        let message = syslog_message(&config, &record).unwrap();
+        // facility 13 (log audit) * 8 + severity 3 (error)
+        assert!(message.starts_with(&format!("<107>1 {} ", record.timestamp)));
+        assert!(message.contains(" aiwebengine - authentication_failure - {"));
+    }
+
+    #[test]
+    fn test_https_body_formats() {
+        let records = [record(), record()];
+        let ndjson = https_body(HttpsExportFormat::Ndjson, &records).unwrap();
+        assert_eq!(ndjson.lines().count(), 2);
+        let first: serde_json::Value =
+            serde_json::from_str(ndjson.lines().next().unwrap()).unwrap();
+        assert_eq!(first["eventType"], "authentication_failure");
+
+        let hec = https_body(HttpsExportFormat::SplunkHec, &records).unwrap();
+        let first: serde_json::Value = serde_json::from_str(hec.lines().next().unwrap()).unwrap();
+        assert_eq!(first["sourcetype"], SPLUNK_SOURCETYPE);
+        assert_eq!(first["event"]["userId"], "user-1");
+        assert!(first["time"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_file_export_rotates() {
+        let dir = std::env::temp_dir().join(format!("audit-export-{}", uuid::Uuid::new_v4()));
+        let config = FileExportConfig {
+            path: dir.join("audit.ndjson"),
+            max_bytes: 1,
+        };
+        write_file(&config, &[record()]).unwrap();
+        write_file(&config, &[record(), record()]).unwrap();
+
+        let current = std::fs::read_to_string(&config.path).unwrap();
+        assert_eq!(current.lines().count(), 2);
+        let rotated = std::fs::read_to_string(dir.join("audit.ndjson.1")).unwrap();
+        assert_eq!(rotated.lines().count(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
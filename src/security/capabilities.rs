@@ -23,6 +23,20 @@ pub fn is_development_mode() -> bool {
     }
 }
 
+/// Whether the server runs with authentication. Set at startup once the auth
+/// manager is initialized; until then, and when auth is not configured, code
+/// that has no caller to act for (such as GraphQL resolvers) runs as admin.
+static AUTH_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Record whether authentication is configured. Called once at server startup.
+pub fn set_auth_enabled(enabled: bool) {
+    AUTH_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_auth_enabled() -> bool {
+    AUTH_ENABLED.load(Ordering::Relaxed)
+}
+
 #[derive(Debug, Clone)]
 pub struct UserContext {
     pub user_id: Option<String>,
@@ -47,6 +61,14 @@ impl UserContext {
         }
     }
 
+    pub fn editor(user_id: String) -> Self {
+        Self {
+            user_id: Some(user_id),
+            is_authenticated: true,
+            capabilities: Self::editor_capabilities(),
+        }
+    }
+
     pub fn admin(user_id: String) -> Self {
         Self {
             user_id: Some(user_id),
@@ -101,6 +123,14 @@ impl UserContext {
         .collect()
     }
 
+    fn editor_capabilities() -> HashSet<Capability> {
+        // Editors also manage GraphQL operations and logs. DeleteScripts and
+        // DeleteAssets stay admin-only: they bypass script ownership checks.
+        let mut capabilities = Self::authenticated_capabilities();
+        capabilities.extend([Capability::ManageGraphQL, Capability::DeleteLogs]);
+        capabilities
+    }
+
     fn admin_capabilities() -> HashSet<Capability> {
         // Admins can do everything
         [
@@ -232,6 +262,18 @@ mod tests {
         assert!(!user.has_capability(&Capability::DeleteLogs));
     }
 
+    #[test]
+    fn test_editor_user_capabilities() {
+        let user = UserContext::editor("editor".to_string());
+
+        assert!(user.is_authenticated);
+        assert!(user.has_capability(&Capability::WriteScripts));
+        assert!(user.has_capability(&Capability::ManageGraphQL));
+        assert!(user.has_capability(&Capability::DeleteLogs));
+        assert!(!user.has_capability(&Capability::DeleteScripts));
+        assert!(!user.has_capability(&Capability::DeleteAssets));
+    }
+
     #[test]
     fn test_admin_user_capabilities() {
         let user = UserContext::admin("admin".to_string());
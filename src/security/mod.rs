@@ -1,17 +1,23 @@
 pub mod audit;
+pub mod audit_export;
 pub mod capabilities;
 pub mod csp;
 pub mod csrf;
 pub mod encryption;
 pub mod operations;
 pub mod rate_limiting;
+pub mod script_manifests;
+pub mod script_quarantine;
 pub mod secure_globals;
 pub mod session;
 pub mod threat_detection;
+pub mod threat_response;
 pub mod validation;
 
 pub use audit::{SecurityAuditor, SecurityEvent, SecurityEventType, SecuritySeverity};
-pub use capabilities::{UserContext, is_development_mode, set_development_mode};
+pub use capabilities::{
+    UserContext, is_auth_enabled, is_development_mode, set_auth_enabled, set_development_mode,
+};
 pub use csp::{CspDirective, CspManager, CspPolicy, CspSource, CspViolationReport};
 pub use csrf::{CsrfProtection, CsrfToken, OAuthStateManager};
 pub use encryption::{
@@ -76,6 +76,14 @@ impl SecureOperations {
             )));
         }
 
+        // Reject scripts that would fail to load
+        if let Err(e) = crate::script_lint::validate(&request.script_name, &request.js_script) {
+            return Ok(OperationResult::error(format!(
+                "Invalid script content: {}",
+                e
+            )));
+        }
+
         // Call actual repository layer
         match crate::repository::upsert_script_async(&request.script_name, &request.js_script).await
         {
@@ -146,6 +154,7 @@ impl SecureOperations {
             created_at: now,
             updated_at: now,
             script_uri: "https://example.com/core".to_string(), // TODO: After UI and JavaScript API change, set based on related script
+            headers: HashMap::new(),
         };
 
         match crate::repository::upsert_asset_async(asset).await {
@@ -242,6 +242,16 @@ impl RateLimiter {
     /// Check rate limit for a key
     pub async fn check_rate_limit(&self, key: RateLimitKey, tokens: u32) -> RateLimitResult {
         let config = self.get_config(&key);
+        self.check_rate_limit_with_config(key, tokens, config).await
+    }
+
+    /// Check rate limit for a key against a caller-supplied bucket configuration
+    pub async fn check_rate_limit_with_config(
+        &self,
+        key: RateLimitKey,
+        tokens: u32,
+        config: RateLimitConfig,
+    ) -> RateLimitResult {
         let key_str = key.as_string();
 
         if !config.enabled {
@@ -0,0 +1,308 @@
+//! Per-script capability manifests (`[security.script_manifests]`).
+//!
+//! A script's manifest lists the sandbox capabilities it requests: `fetch`
+//! (`fetch()` and webhooks), `secrets`, `graphql` (`graphQLRegistry`),
+//! `streams` (stream routes), `db` (`database`, `db` and `vectors`), `email`,
+//! `notify` (push notifications), `llm` (`llm`, `embeddings` and
+//! `moderation`) and `tasks` (background task queue).
+//! Editors request capabilities with
+//! `scriptStorage.requestScriptCapabilities`; an administrator approves them
+//! with `admin.approveScriptCapabilities` or the `approveScriptCapabilities`
+//! GraphQL mutation. While manifests are enabled, secure globals only wire
+//! the capabilities that are both requested and approved into a script's
+//! context, so a script without a manifest gets none of them. Privileged
+//! scripts are exempt unless `exempt_privileged` is off.
+//!
+//! Manifests are stored with the script and read whenever a script context
+//! is set up, so approvals apply on every server from the next execution.
+
+use std::sync::{OnceLock, RwLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ScriptManifestsConfig;
+use crate::error::{AppError, AppResult};
+use crate::repository;
+
+/// A sandbox feature a script has to request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptCapability {
+    Fetch,
+    Secrets,
+    Graphql,
+    Streams,
+    Db,
+    Email,
+    Notify,
+    Llm,
+    Tasks,
+}
+
+impl ScriptCapability {
+    pub const ALL: [ScriptCapability; 9] = [
+        ScriptCapability::Fetch,
+        ScriptCapability::Secrets,
+        ScriptCapability::Graphql,
+        ScriptCapability::Streams,
+        ScriptCapability::Db,
+        ScriptCapability::Email,
+        ScriptCapability::Notify,
+        ScriptCapability::Llm,
+        ScriptCapability::Tasks,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScriptCapability::Fetch => "fetch",
+            ScriptCapability::Secrets => "secrets",
+            ScriptCapability::Graphql => "graphql",
+            ScriptCapability::Streams => "streams",
+            ScriptCapability::Db => "db",
+            ScriptCapability::Email => "email",
+            ScriptCapability::Notify => "notify",
+            ScriptCapability::Llm => "llm",
+            ScriptCapability::Tasks => "tasks",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fetch" => Some(ScriptCapability::Fetch),
+            "secrets" => Some(ScriptCapability::Secrets),
+            "graphql" => Some(ScriptCapability::Graphql),
+            "streams" => Some(ScriptCapability::Streams),
+            "db" => Some(ScriptCapability::Db),
+            "email" => Some(ScriptCapability::Email),
+            "notify" => Some(ScriptCapability::Notify),
+            "llm" => Some(ScriptCapability::Llm),
+            "tasks" => Some(ScriptCapability::Tasks),
+            _ => None,
+        }
+    }
+}
+
+/// Capabilities a script requests and those an administrator approved
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptManifest {
+    pub uri: String,
+    pub requested: Vec<ScriptCapability>,
+    pub approved: Vec<ScriptCapability>,
+    pub approved_by: Option<String>,
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+impl ScriptManifest {
+    /// Capabilities the script gets: requested and approved
+    pub fn granted(&self) -> Vec<ScriptCapability> {
+        self.requested
+            .iter()
+            .copied()
+            .filter(|capability| self.approved.contains(capability))
+            .collect()
+    }
+
+    /// Requested capabilities waiting for approval
+    pub fn pending(&self) -> Vec<ScriptCapability> {
+        self.requested
+            .iter()
+            .copied()
+            .filter(|capability| !self.approved.contains(capability))
+            .collect()
+    }
+}
+
+/// Manifest as listed for administrators, with what still needs approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptManifestView {
+    #[serde(flatten)]
+    pub manifest: ScriptManifest,
+    pub pending: Vec<ScriptCapability>,
+}
+
+impl From<ScriptManifest> for ScriptManifestView {
+    fn from(manifest: ScriptManifest) -> Self {
+        let pending = manifest.pending();
+        Self { manifest, pending }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<ScriptManifestsConfig>> = OnceLock::new();
+
+fn settings_lock() -> &'static RwLock<ScriptManifestsConfig> {
+    SETTINGS.get_or_init(|| RwLock::new(ScriptManifestsConfig::default()))
+}
+
+/// Apply the `[security.script_manifests]` configuration
+pub fn configure(config: &ScriptManifestsConfig) {
+    let mut settings = settings_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *settings = config.clone();
+}
+
+fn current_settings() -> ScriptManifestsConfig {
+    settings_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Parse capability names, dropping duplicates
+pub fn parse_capabilities(names: &[String]) -> AppResult<Vec<ScriptCapability>> {
+    let mut capabilities = Vec::new();
+    for name in names {
+        let capability = ScriptCapability::parse(name.trim()).ok_or_else(|| {
+            AppError::validation(
+                "capabilities",
+                format!(
+                    "unknown capability '{}'; expected one of {}",
+                    name,
+                    ScriptCapability::ALL
+                        .map(ScriptCapability::as_str)
+                        .join(", ")
+                ),
+            )
+        })?;
+        if !capabilities.contains(&capability) {
+            capabilities.push(capability);
+        }
+    }
+    Ok(capabilities)
+}
+
+/// Capabilities wired into the context of a script. Everything while
+/// manifests are disabled; otherwise only what the script's manifest grants,
+/// nothing when it can't be read.
+pub fn granted(uri: &str) -> Vec<ScriptCapability> {
+    let settings = current_settings();
+    if !settings.enabled
+        || (settings.exempt_privileged && repository::is_script_privileged(uri).unwrap_or(false))
+    {
+        return ScriptCapability::ALL.to_vec();
+    }
+
+    match repository::get_script_manifest(uri) {
+        Ok(Some(manifest)) => manifest.granted(),
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("Failed to read capability manifest of {}: {}", uri, e);
+            Vec::new()
+        }
+    }
+}
+
+/// The manifest of a script
+pub fn get(uri: &str) -> AppResult<ScriptManifest> {
+    repository::get_script_manifest(uri)?
+        .ok_or_else(|| repository::RepositoryError::ScriptNotFound(uri.to_string()).into())
+}
+
+/// Replace the capabilities a script requests. Approvals of capabilities
+/// it no longer requests are dropped; new requests wait for approval.
+pub fn request(uri: &str, capabilities: Vec<ScriptCapability>) -> AppResult<ScriptManifest> {
+    let mut manifest = get(uri)?;
+    manifest
+        .approved
+        .retain(|capability| capabilities.contains(capability));
+    manifest.requested = capabilities;
+    store(manifest)
+}
+
+/// Approve requested capabilities of a script, all of them when
+/// `capabilities` is `None`. Approvals not listed are revoked.
+pub fn approve(
+    uri: &str,
+    capabilities: Option<Vec<ScriptCapability>>,
+    approved_by: &str,
+) -> AppResult<ScriptManifest> {
+    let mut manifest = get(uri)?;
+    let capabilities = capabilities.unwrap_or_else(|| manifest.requested.clone());
+    if let Some(capability) = capabilities
+        .iter()
+        .find(|capability| !manifest.requested.contains(capability))
+    {
+        return Err(AppError::validation(
+            "capabilities",
+            format!("'{}' is not requested by the script", capability.as_str()),
+        ));
+    }
+
+    manifest.approved = capabilities;
+    manifest.approved_by = Some(approved_by.to_string());
+    manifest.approved_at = Some(Utc::now());
+    store(manifest)
+}
+
+fn store(manifest: ScriptManifest) -> AppResult<ScriptManifest> {
+    if repository::update_script_manifest(&manifest)? {
+        Ok(manifest)
+    } else {
+        Err(repository::RepositoryError::ScriptNotFound(manifest.uri).into())
+    }
+}
+
+/// Manifests with requested capabilities waiting for approval
+pub fn list_pending() -> AppResult<Vec<ScriptManifestView>> {
+    Ok(repository::list_pending_script_manifests()?
+        .into_iter()
+        .map(ScriptManifestView::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(requested: &[ScriptCapability], approved: &[ScriptCapability]) -> ScriptManifest {
+        ScriptManifest {
+            uri: "https://example.com/app".to_string(),
+            requested: requested.to_vec(),
+            approved: approved.to_vec(),
+            approved_by: None,
+            approved_at: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_capabilities() {
+        let names = vec!["fetch".to_string(), " db ".to_string(), "fetch".to_string()];
+        assert_eq!(
+            parse_capabilities(&names).unwrap(),
+            vec![ScriptCapability::Fetch, ScriptCapability::Db]
+        );
+        assert!(parse_capabilities(&["smtp".to_string()]).is_err());
+
+        for capability in ScriptCapability::ALL {
+            assert_eq!(
+                ScriptCapability::parse(capability.as_str()),
+                Some(capability)
+            );
+        }
+    }
+
+    #[test]
+    fn test_granted_requires_request_and_approval() {
+        use ScriptCapability::*;
+
+        let manifest = manifest(&[Fetch, Secrets], &[Secrets, Db]);
+        assert_eq!(manifest.granted(), vec![Secrets]);
+        assert_eq!(manifest.pending(), vec![Fetch]);
+
+        let view = ScriptManifestView::from(manifest);
+        let json = serde_json::to_value(&view).unwrap();
+        assert_eq!(json["requested"], serde_json::json!(["fetch", "secrets"]));
+        assert_eq!(json["pending"], serde_json::json!(["fetch"]));
+    }
+
+    #[test]
+    fn test_disabled_grants_everything() {
+        assert_eq!(
+            granted("https://example.com/unknown"),
+            ScriptCapability::ALL.to_vec()
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Quarantine of misbehaving scripts (`[security.script_quarantine]`).
+//!
+//! Every execution that hits the execution timeout or panics counts as a
+//! failure of its script. A script with `failure_threshold` failures within
+//! `failure_window_secs` is quarantined: it stays stored, but its routes
+//! answer 503 and its scheduled jobs are skipped until an administrator
+//! releases it with `admin.releaseScript` or the `releaseScript` GraphQL
+//! mutation. System scripts are never quarantined.
+//!
+//! The quarantine is a `quarantine_script` action of the
+//! [`threat_response`](super::threat_response) module, so it is stored,
+//! listed and applied on every instance like the actions of threat policies.
+//! Admin scripts learn about it from the `system.script_quarantined` event,
+//! broadcast to every instance.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tracing::{error, warn};
+
+use super::threat_response::{self, ThreatAction};
+use crate::config::{ScriptQuarantineConfig, ThreatActionKind, ThreatPolicyConfig};
+use crate::repository::{self, Repository as _};
+
+/// Policy name of quarantines taken for failing scripts
+pub const POLICY_NAME: &str = "script_quarantine";
+
+/// Event topic announcing a quarantined script
+pub const QUARANTINE_EVENT_TOPIC: &str = "system.script_quarantined";
+
+/// Most scripts whose failures are tracked at once
+const MAX_TRACKED_SCRIPTS: usize = 10_000;
+
+/// How an execution failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptFailure {
+    Timeout,
+    Panic,
+}
+
+impl ScriptFailure {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScriptFailure::Timeout => "timeout",
+            ScriptFailure::Panic => "panic",
+        }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<ScriptQuarantineConfig>> = OnceLock::new();
+static FAILURES: OnceLock<Mutex<HashMap<String, VecDeque<(Instant, ScriptFailure)>>>> =
+    OnceLock::new();
+
+fn settings_lock() -> &'static RwLock<ScriptQuarantineConfig> {
+    SETTINGS.get_or_init(|| RwLock::new(ScriptQuarantineConfig::default()))
+}
+
+fn lock_failures() -> MutexGuard<'static, HashMap<String, VecDeque<(Instant, ScriptFailure)>>> {
+    FAILURES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Apply the `[security.script_quarantine]` configuration
+pub fn configure(config: &ScriptQuarantineConfig) {
+    let mut settings = settings_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *settings = config.clone();
+}
+
+fn current_settings() -> ScriptQuarantineConfig {
+    settings_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Count a failed execution of a script. Returns the failures within the
+/// window when they reach the threshold; the count then starts over.
+fn count_failure(
+    config: &ScriptQuarantineConfig,
+    script_uri: &str,
+    failure: ScriptFailure,
+) -> Option<Vec<ScriptFailure>> {
+    let window = Duration::from_secs(config.failure_window_secs);
+    let mut failures = lock_failures();
+
+    if failures.len() >= MAX_TRACKED_SCRIPTS && !failures.contains_key(script_uri) {
+        failures.retain(|_, recent| recent.back().is_some_and(|(at, _)| at.elapsed() < window));
+        if failures.len() >= MAX_TRACKED_SCRIPTS {
+            warn!(
+                "Script failure tracking is full; not counting {}",
+                script_uri
+            );
+            return None;
+        }
+    }
+
+    let recent = failures.entry(script_uri.to_string()).or_default();
+    while recent.front().is_some_and(|(at, _)| at.elapsed() >= window) {
+        recent.pop_front();
+    }
+    recent.push_back((Instant::now(), failure));
+    if recent.len() < config.failure_threshold as usize {
+        return None;
+    }
+    let reached = recent.iter().map(|(_, failure)| *failure).collect();
+    failures.remove(script_uri);
+    Some(reached)
+}
+
+/// Count an execution of a script that timed out or panicked, and
+/// quarantine the script when it has failed too often
+pub fn record_failure(script_uri: &str, failure: ScriptFailure) {
+    let config = current_settings();
+    if !config.enabled {
+        return;
+    }
+    let Some(failures) = count_failure(&config, script_uri, failure) else {
+        return;
+    };
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    let script_uri = script_uri.to_string();
+    let window_secs = config.failure_window_secs;
+    handle.spawn(async move {
+        if let Err(e) = quarantine(&script_uri, &failures, window_secs).await {
+            error!("Failed to quarantine script {}: {}", script_uri, e);
+        }
+    });
+}
+
+fn describe(failures: &[ScriptFailure], window_secs: u64) -> String {
+    let timeouts = failures
+        .iter()
+        .filter(|failure| **failure == ScriptFailure::Timeout)
+        .count();
+    format!(
+        "{} failures within {} seconds (timeouts: {}, panics: {})",
+        failures.len(),
+        window_secs,
+        timeouts,
+        failures.len() - timeouts
+    )
+}
+
+async fn quarantine(
+    script_uri: &str,
+    failures: &[ScriptFailure],
+    window_secs: u64,
+) -> Result<(), String> {
+    let Some(repo) = repository::get_repository_opt() else {
+        return Ok(());
+    };
+    if repo
+        .get_script_system(script_uri)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or(false)
+    {
+        warn!(
+            "System script {} keeps failing but is not quarantined",
+            script_uri
+        );
+        return Ok(());
+    }
+
+    let description = describe(failures, window_secs);
+    let last = failures.last().copied().unwrap_or(ScriptFailure::Timeout);
+    let policy = ThreatPolicyConfig {
+        name: POLICY_NAME.to_string(),
+        indicator: format!("repeated_{}", last.as_str()),
+        action: ThreatActionKind::QuarantineScript,
+        duration_secs: 0,
+        require_review: false,
+    };
+    let Some(action) =
+        threat_response::take_action(&policy, script_uri.to_string(), description.clone()).await?
+    else {
+        return Ok(());
+    };
+
+    repository::insert_log_message_async(
+        script_uri,
+        &format!(
+            "Script quarantined after {}; an administrator has to release it",
+            description
+        ),
+        "FATAL",
+    )
+    .await;
+    announce(&action);
+    Ok(())
+}
+
+/// Tell admin scripts on every instance about a quarantine
+fn announce(action: &ThreatAction) {
+    let payload = json!({
+        "scriptUri": action.subject,
+        "actionId": action.id.to_string(),
+        "reason": action.description,
+        "quarantinedAt": action.created_at.to_rfc3339(),
+    });
+    if let Err(e) = crate::events::publish(
+        &action.subject,
+        QUARANTINE_EVENT_TOPIC,
+        payload,
+        &crate::events::PublishOptions { broadcast: true },
+    ) {
+        warn!("Failed to announce quarantine of {}: {}", action.subject, e);
+    }
+}
+
+/// Lift the quarantine of a script after a fix, forgetting its failures
+pub fn release(script_uri: &str, released_by: &str) -> Result<Vec<ThreatAction>, String> {
+    let lifted = threat_response::release_script(script_uri, released_by)?;
+    lock_failures().remove(script_uri);
+    Ok(lifted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ScriptQuarantineConfig {
+        ScriptQuarantineConfig {
+            enabled: true,
+            failure_threshold: 3,
+            failure_window_secs: 600,
+        }
+    }
+
+    #[test]
+    fn test_quarantines_at_threshold() {
+        let config = config();
+        let script_uri = "https://example.com/flaky";
+
+        assert!(count_failure(&config, script_uri, ScriptFailure::Timeout).is_none());
+        assert!(count_failure(&config, script_uri, ScriptFailure::Panic).is_none());
+        assert!(
+            count_failure(&config, "https://example.com/other", ScriptFailure::Timeout).is_none()
+        );
+
+        let failures = count_failure(&config, script_uri, ScriptFailure::Timeout).unwrap();
+        assert_eq!(
+            failures,
+            vec![
+                ScriptFailure::Timeout,
+                ScriptFailure::Panic,
+                ScriptFailure::Timeout
+            ]
+        );
+        assert_eq!(
+            describe(&failures, 600),
+            "3 failures within 600 seconds (timeouts: 2, panics: 1)"
+        );
+
+        // The count starts over
+        assert!(count_failure(&config, script_uri, ScriptFailure::Timeout).is_none());
+    }
+}
@@ -6,8 +6,10 @@ use tracing::{debug, error, warn};
 
 use crate::repository;
 use crate::scheduler;
+use crate::security::script_manifests::ScriptCapability;
 use crate::security::{
-    SecureOperations, SecurityAuditor, SecurityEventType, SecuritySeverity, UserContext,
+    InputValidator, SecureOperations, SecurityAuditor, SecurityEventType, SecuritySeverity,
+    UserContext,
 };
 
 // Type alias for route registration callback function
@@ -25,6 +27,836 @@ fn parse_filter_match_mode(
     }
 }
 
+/// Check that the user may modify an existing script: administrators (holders
+/// of DeleteScripts) may modify any script, other users only scripts they own
+/// or collaborate on. New scripts can be created by anyone with WriteScripts.
+fn check_script_write_permission(user_ctx: &UserContext, script_name: &str) -> Result<(), String> {
+    if repository::fetch_script(script_name).is_none() {
+        return Ok(());
+    }
+
+    let is_admin = user_ctx.has_capability(&crate::security::Capability::DeleteScripts);
+    let user_owns = if let Some(user_id) = &user_ctx.user_id {
+        repository::user_owns_script(script_name, user_id).unwrap_or(false)
+    } else {
+        false
+    };
+    let user_collaborates = match (&user_ctx.user_id, is_admin || user_owns) {
+        (Some(user_id), false) => {
+            repository::user_collaborates_on_script(script_name, user_id).unwrap_or(false)
+        }
+        _ => false,
+    };
+
+    debug!(
+        script_name = %script_name,
+        user_id = ?user_ctx.user_id,
+        is_admin = is_admin,
+        user_owns = user_owns,
+        user_collaborates = user_collaborates,
+        "Checking script update permissions for existing script"
+    );
+
+    if !is_admin && !user_owns && !user_collaborates {
+        warn!(
+            script_name = %script_name,
+            user_id = ?user_ctx.user_id,
+            is_admin = is_admin,
+            user_owns = user_owns,
+            "Permission denied: user is neither admin, owner nor collaborator"
+        );
+        return Err(format!(
+            "Error: Permission denied. You must be an administrator, owner or collaborator to modify script '{}'",
+            script_name
+        ));
+    }
+    Ok(())
+}
+
+/// Run a staging operation on a script the user may write, returning its
+/// result as JSON or an error message
+fn run_staging_operation<T: serde::Serialize>(
+    user_ctx: &UserContext,
+    script_name: &str,
+    operation: impl std::future::Future<Output = Result<T, String>>,
+) -> String {
+    if let Err(e) = user_ctx.require_capability(&crate::security::Capability::WriteScripts) {
+        return format!("Error: {}", e);
+    }
+    if let Err(message) = check_script_write_permission(user_ctx, script_name) {
+        return message;
+    }
+    let result =
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(operation));
+    match result.and_then(|value| serde_json::to_string(&value).map_err(|e| e.to_string())) {
+        Ok(json) => json,
+        Err(e) => format!("Error: {}", e),
+    }
+}
+
+/// Record a change of the live version of a script in the audit log
+fn audit_script_swap(
+    auditor: &SecurityAuditor,
+    user_id: Option<String>,
+    script_name: &str,
+    action: &str,
+) {
+    let event = crate::security::SecurityEvent::new(
+        SecurityEventType::SystemSecurityEvent,
+        SecuritySeverity::Medium,
+        user_id,
+    )
+    .with_resource("script".to_string())
+    .with_action(action.to_string())
+    .with_detail("script_name", script_name);
+    let auditor = auditor.clone();
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move {
+            auditor.log_event(event).await;
+        });
+    }
+}
+
+/// Merge `description` / `tags` keys from a JS options object into existing
+/// labels. Keys that are absent keep their current value; `null` clears them.
+fn merge_script_labels(
+    existing: repository::ScriptLabels,
+    options: &rquickjs::Object<'_>,
+) -> crate::error::AppResult<repository::ScriptLabels> {
+    let mut description = existing.description;
+    let mut tags = existing.tags;
+
+    if options.contains_key("description").unwrap_or(false) {
+        description = options
+            .get::<_, Option<String>>("description")
+            .map_err(|_| crate::error::AppError::validation("description", "must be a string"))?;
+    }
+    if options.contains_key("tags").unwrap_or(false) {
+        tags = options
+            .get::<_, Option<Vec<String>>>("tags")
+            .map_err(|_| crate::error::AppError::validation("tags", "must be an array of strings"))?
+            .unwrap_or_default();
+    }
+
+    repository::ScriptLabels::new(description, tags)
+}
+
+/// Read the `headers` key of an asset options object. Returns `None` when the
+/// key is absent so callers keep the asset's current headers; `null` clears them.
+fn read_asset_headers_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<HashMap<String, String>>, String> {
+    if !options.contains_key("headers").unwrap_or(false) {
+        return Ok(None);
+    }
+    let headers = options
+        .get::<_, Option<HashMap<String, String>>>("headers")
+        .map_err(|_| "headers must be an object of string values".to_string())?
+        .unwrap_or_default();
+    crate::asset_registry::validate_asset_headers(&headers)?;
+    Ok(Some(headers))
+}
+
+/// Read the `sourceMap` key of a script options object. Returns `None` when
+/// the key is absent so callers keep the stored map; `null` removes it.
+fn read_source_map_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<Option<String>>, String> {
+    if !options.contains_key("sourceMap").unwrap_or(false) {
+        return Ok(None);
+    }
+    let source_map = options
+        .get::<_, Option<String>>("sourceMap")
+        .map_err(|_| "sourceMap must be a string".to_string())?;
+    if let Some(source_map) = &source_map {
+        crate::source_maps::validate_upload(source_map)?;
+    }
+    Ok(Some(source_map))
+}
+
+/// Read the request argument of scriptStorage.dryRun
+fn read_dry_run_request(
+    request: rquickjs::Value<'_>,
+) -> Result<crate::dry_run::DryRunRequest, String> {
+    if !request.is_object() {
+        return Err("request must be an object".to_string());
+    }
+    let json = request
+        .ctx()
+        .clone()
+        .json_stringify(request)
+        .ok()
+        .flatten()
+        .and_then(|json| json.to_string().ok())
+        .ok_or_else(|| "request must be JSON-serializable".to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Read an optional options object argument, such as the options of
+/// markdown.render; `undefined` and `null` use the defaults
+fn read_options_object<T: serde::de::DeserializeOwned + Default>(
+    options: Option<rquickjs::Value<'_>>,
+    name: &str,
+) -> Result<T, String> {
+    let Some(options) = options.filter(|options| !options.is_undefined() && !options.is_null())
+    else {
+        return Ok(T::default());
+    };
+    if !options.is_object() {
+        return Err(format!("{} must be an object", name));
+    }
+    let json = options
+        .ctx()
+        .clone()
+        .json_stringify(options)
+        .ok()
+        .flatten()
+        .and_then(|json| json.to_string().ok())
+        .ok_or_else(|| format!("{} must be JSON-serializable", name))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid {}: {}", name, e))
+}
+
+/// Longest TTL accepted for shared storage items (ten years)
+const MAX_STORAGE_TTL_SECONDS: f64 = 10.0 * 365.0 * 24.0 * 60.0 * 60.0;
+
+/// Validate a `ttlSeconds` value from JavaScript
+fn ttl_from_seconds(seconds: f64) -> Result<std::time::Duration, String> {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return Err("ttlSeconds must be a positive number".to_string());
+    }
+    if seconds > MAX_STORAGE_TTL_SECONDS {
+        return Err(format!(
+            "ttlSeconds must be at most {}",
+            MAX_STORAGE_TTL_SECONDS
+        ));
+    }
+    Ok(std::time::Duration::from_secs_f64(seconds))
+}
+
+/// Lowest memory limit `scriptStorage.setMemoryLimit` accepts; below it a
+/// script cannot even create its context
+const MIN_SCRIPT_MEMORY_LIMIT_BYTES: u64 = 1024 * 1024;
+
+/// Maximum size of the serialized structured payload of one log entry
+const MAX_LOG_DATA_BYTES: usize = 64 * 1024;
+
+/// Read a console.queryLogs options object into a repository log query.
+/// Times are milliseconds since the epoch or RFC 3339 strings.
+fn read_log_query_option(options: &rquickjs::Object<'_>) -> Result<repository::LogQuery, String> {
+    let string_option = |key: &str| {
+        options
+            .get::<_, Option<String>>(key)
+            .map_err(|_| format!("{} must be a string", key))
+    };
+    let time_option = |key: &str| -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+        let value: rquickjs::Value = options
+            .get(key)
+            .map_err(|_| format!("{} must be a timestamp", key))?;
+        if value.is_undefined() || value.is_null() {
+            return Ok(None);
+        }
+        if let Some(millis) = value.as_number() {
+            return chrono::DateTime::from_timestamp_millis(millis as i64)
+                .map(Some)
+                .ok_or_else(|| format!("{} is out of range", key));
+        }
+        match value.as_string().map(|s| s.to_string()) {
+            Some(Ok(text)) => chrono::DateTime::parse_from_rfc3339(&text)
+                .map(|time| Some(time.with_timezone(&chrono::Utc)))
+                .map_err(|_| format!("{} must be an RFC 3339 timestamp", key)),
+            _ => Err(format!(
+                "{} must be milliseconds since the epoch or an RFC 3339 string",
+                key
+            )),
+        }
+    };
+
+    let json_option = |key: &str| -> Result<Option<serde_json::Value>, String> {
+        let value: rquickjs::Value = options
+            .get(key)
+            .map_err(|_| format!("{} must be an object", key))?;
+        if value.is_undefined() || value.is_null() {
+            return Ok(None);
+        }
+        if !value.is_object() {
+            return Err(format!("{} must be an object", key));
+        }
+        let json = options
+            .ctx()
+            .json_stringify(value)
+            .ok()
+            .flatten()
+            .and_then(|json| json.to_string().ok())
+            .ok_or_else(|| format!("{} must be JSON-serializable", key))?;
+        serde_json::from_str(&json).map_err(|e| format!("{} is not valid JSON: {}", key, e))
+    };
+
+    Ok(repository::LogQuery {
+        script_uri: string_option("scriptUri")?,
+        levels: options
+            .get::<_, Option<Vec<String>>>("levels")
+            .map_err(|_| "levels must be an array of strings".to_string())?
+            .unwrap_or_default(),
+        since: time_option("since")?,
+        until: time_option("until")?,
+        search: string_option("search")?,
+        data: json_option("data")?,
+        request_id: string_option("requestId")?,
+        cursor: string_option("cursor")?,
+        limit: options
+            .get::<_, Option<f64>>("limit")
+            .map_err(|_| "limit must be a number".to_string())?
+            .map(|limit| limit as i64),
+    })
+}
+
+/// Read a console.queryStats options object
+fn read_query_stats_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<crate::query_log::QueryStatsOptions, String> {
+    let slow_only = options
+        .get::<_, Option<bool>>("slowOnly")
+        .map_err(|_| "slowOnly must be a boolean".to_string())?
+        .unwrap_or(false);
+    let limit = match options
+        .get::<_, Option<f64>>("limit")
+        .map_err(|_| "limit must be a number".to_string())?
+    {
+        Some(limit) if limit < 1.0 => return Err("limit must be positive".to_string()),
+        Some(limit) => (limit as usize).min(crate::query_log::MAX_REPORT_LIMIT),
+        None => crate::query_log::DEFAULT_REPORT_LIMIT,
+    };
+    Ok(crate::query_log::QueryStatsOptions { slow_only, limit })
+}
+
+/// Read the `host` option of a stream or asset route call: the host name the
+/// path is scoped to, or None for every host
+fn read_host_option(options: &rquickjs::Object<'_>) -> Result<Option<String>, String> {
+    match options
+        .get::<_, Option<String>>("host")
+        .map_err(|_| "host must be a string".to_string())?
+    {
+        Some(host) => crate::route_index::normalize_host(&host)
+            .map(Some)
+            .ok_or_else(|| format!("'{}' is not a valid host name", host)),
+        None => Ok(None),
+    }
+}
+
+/// Read the `cache` option of a route or asset route call:
+/// `{ ttlSeconds, vary?, staleWhileRevalidateSeconds? }`
+fn read_route_cache_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<crate::response_cache::RouteCache>, String> {
+    let Some(cache) = options
+        .get::<_, Option<rquickjs::Object>>("cache")
+        .map_err(|_| "cache must be an object".to_string())?
+    else {
+        return Ok(None);
+    };
+    let whole_seconds = |name: &str, min: f64| -> Result<Option<u64>, String> {
+        match cache.get::<_, Option<f64>>(name) {
+            Ok(None) => Ok(None),
+            Ok(Some(seconds)) if seconds.fract() == 0.0 && seconds >= min => {
+                Ok(Some(seconds as u64))
+            }
+            _ if min > 0.0 => Err(format!("cache.{} must be a positive integer", name)),
+            _ => Err(format!("cache.{} must be a non-negative integer", name)),
+        }
+    };
+    let ttl_seconds = whole_seconds("ttlSeconds", 1.0)?
+        .ok_or_else(|| "cache.ttlSeconds must be a positive integer".to_string())?;
+    let stale_while_revalidate_seconds = whole_seconds("staleWhileRevalidateSeconds", 0.0)?;
+    let vary = cache
+        .get::<_, Option<Vec<String>>>("vary")
+        .map_err(|_| "cache.vary must be an array of header names".to_string())?
+        .unwrap_or_default();
+    let mut route_cache = crate::response_cache::RouteCache::new(ttl_seconds, vary)?;
+    route_cache.stale_while_revalidate_seconds = stale_while_revalidate_seconds;
+    Ok(Some(route_cache))
+}
+
+/// Read the `timeoutMs` option of a route: the handler's timeout in
+/// milliseconds, at most `javascript.max_script_execution_timeout_ms`
+fn read_route_timeout_option(options: &rquickjs::Object<'_>) -> Result<Option<u64>, String> {
+    let ceiling = crate::js_engine::current_execution_limits().max_script_timeout_ms;
+    match options
+        .get::<_, Option<f64>>("timeoutMs")
+        .map_err(|_| "timeoutMs must be a number".to_string())?
+    {
+        None => Ok(None),
+        Some(ms) if !ms.is_finite() || ms < 1.0 || ms.fract() != 0.0 => {
+            Err("timeoutMs must be a positive whole number of milliseconds".to_string())
+        }
+        Some(ms) if ms > ceiling as f64 => Err(format!(
+            "timeoutMs must be at most {} milliseconds",
+            ceiling
+        )),
+        Some(ms) => Ok(Some(ms as u64)),
+    }
+}
+
+/// Read the `auth` option of a route: `true` for any signed-in user, or
+/// `{ groups: [...] }` for members of the listed groups
+fn read_route_auth_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<crate::auth::RouteAuth>, String> {
+    const INVALID: &str = "auth must be true or { groups: [group names] }";
+    let value = options
+        .get::<_, rquickjs::Value>("auth")
+        .map_err(|_| INVALID.to_string())?;
+    if value.is_undefined() || value.is_null() || value.as_bool() == Some(false) {
+        return Ok(None);
+    }
+    if value.as_bool() == Some(true) {
+        return Ok(Some(crate::auth::RouteAuth::default()));
+    }
+    let auth = value.as_object().ok_or_else(|| INVALID.to_string())?;
+    let mut groups = auth
+        .get::<_, Option<Vec<String>>>("groups")
+        .map_err(|_| INVALID.to_string())?
+        .unwrap_or_default();
+    for group in &groups {
+        crate::user_repository::validate_group_name(group).map_err(|e| e.to_string())?;
+    }
+    groups.sort();
+    groups.dedup();
+    Ok(Some(crate::auth::RouteAuth { groups }))
+}
+
+/// Read the `{ description?, roles? }` options of `admin.createUserGroup` and
+/// `admin.updateUserGroup`. Absent keys keep the given values; a null
+/// description clears it.
+fn read_group_options(
+    options: Option<rquickjs::Object<'_>>,
+    description: Option<String>,
+    roles: Vec<crate::user_repository::UserRole>,
+) -> Result<(Option<String>, Vec<crate::user_repository::UserRole>), String> {
+    let Some(options) = options else {
+        return Ok((description, roles));
+    };
+    let description = if options.contains_key("description").unwrap_or(false) {
+        options
+            .get::<_, Option<String>>("description")
+            .map_err(|_| "description must be a string".to_string())?
+    } else {
+        description
+    };
+    let Some(names) = options
+        .get::<_, Option<Vec<String>>>("roles")
+        .map_err(|_| "roles must be an array of role names".to_string())?
+    else {
+        return Ok((description, roles));
+    };
+    let mut roles = Vec::with_capacity(names.len());
+    for name in names {
+        let role = match name.as_str() {
+            "Editor" => crate::user_repository::UserRole::Editor,
+            "Administrator" => crate::user_repository::UserRole::Administrator,
+            _ => {
+                return Err(format!(
+                    "Invalid group role: {}. Must be Editor or Administrator",
+                    name
+                ));
+            }
+        };
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+    }
+    Ok((description, roles))
+}
+
+/// Read the `cachePolicy` option of an asset route or prefix:
+/// `{ maxAge?, sMaxAge?, surrogateKeys? }`
+fn read_cache_policy_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<crate::asset_cdn::CachePolicy>, String> {
+    let Some(policy) = options
+        .get::<_, Option<rquickjs::Object>>("cachePolicy")
+        .map_err(|_| "cachePolicy must be an object".to_string())?
+    else {
+        return Ok(None);
+    };
+    let seconds = |name: &str| -> Result<Option<u64>, String> {
+        match policy.get::<_, Option<f64>>(name) {
+            Ok(None) => Ok(None),
+            Ok(Some(seconds)) if seconds.fract() == 0.0 && seconds >= 0.0 => {
+                Ok(Some(seconds as u64))
+            }
+            _ => Err(format!(
+                "cachePolicy.{} must be a non-negative integer",
+                name
+            )),
+        }
+    };
+    let max_age = seconds("maxAge")?;
+    let s_maxage = seconds("sMaxAge")?;
+    let surrogate_keys = policy
+        .get::<_, Option<Vec<String>>>("surrogateKeys")
+        .map_err(|_| "cachePolicy.surrogateKeys must be an array of strings".to_string())?
+        .unwrap_or_default();
+    crate::asset_cdn::CachePolicy::new(max_age, s_maxage, surrogate_keys).map(Some)
+}
+
+/// Read the `imageTransforms` option of an asset route:
+/// `{ widths?: number[], formats?: string[] }`
+fn read_image_transforms_option(
+    options: &rquickjs::Object<'_>,
+) -> Result<Option<crate::image_transform::TransformPolicy>, String> {
+    let Some(transforms) = options
+        .get::<_, Option<rquickjs::Object>>("imageTransforms")
+        .map_err(|_| "imageTransforms must be an object".to_string())?
+    else {
+        return Ok(None);
+    };
+    let widths = transforms
+        .get::<_, Option<Vec<f64>>>("widths")
+        .map_err(|_| "imageTransforms.widths must be an array of numbers".to_string())?
+        .unwrap_or_default();
+    if let Some(width) = widths
+        .iter()
+        .find(|width| width.fract() != 0.0 || **width < 1.0)
+    {
+        return Err(format!(
+            "imageTransforms.widths must be positive integers, got {}",
+            width
+        ));
+    }
+    let formats = transforms
+        .get::<_, Option<Vec<String>>>("formats")
+        .map_err(|_| "imageTransforms.formats must be an array of strings".to_string())?
+        .unwrap_or_default();
+    crate::image_transform::TransformPolicy::new(
+        widths
+            .into_iter()
+            .map(|width| width.min(u32::MAX as f64) as u32)
+            .collect(),
+        &formats,
+    )
+    .map(Some)
+}
+
+/// The user a `userStorage` profile call is about, and whether the caller
+/// is an administrator: the caller by default, any user for administrators
+fn profile_target(
+    user_ctx: &UserContext,
+    function: &'static str,
+    user_id: Option<String>,
+) -> JsResult<(String, bool)> {
+    let is_admin = user_ctx.has_capability(&crate::security::Capability::DeleteScripts);
+    match (user_id, user_ctx.user_id.as_ref()) {
+        (Some(user_id), _) if is_admin => Ok((user_id, is_admin)),
+        (Some(user_id), Some(own)) if &user_id == own => Ok((user_id, is_admin)),
+        (None, Some(own)) if user_ctx.is_authenticated => Ok((own.clone(), is_admin)),
+        (Some(_), _) => Err(rquickjs::Error::new_from_js_message(
+            function,
+            "permission_denied",
+            "Administrator privileges required for other users' profiles",
+        )),
+        (None, _) => Err(rquickjs::Error::new_from_js_message(
+            function,
+            "permission_denied",
+            "Authentication required",
+        )),
+    }
+}
+
+/// A profile as returned to scripts
+fn profile_json(
+    function: &'static str,
+    profile: crate::user_repository::UserProfile,
+) -> JsResult<String> {
+    serde_json::to_string(&profile).map_err(|e| {
+        rquickjs::Error::new_from_js_message(
+            function,
+            "serialize_error",
+            &format!("Failed to serialize profile: {}", e),
+        )
+    })
+}
+
+/// A JS string's contents, or None for any other value
+fn js_string(value: &rquickjs::Value<'_>) -> Option<String> {
+    value.as_string().and_then(|text| text.to_string().ok())
+}
+
+/// A JS value as JSON; values JSON.stringify drops, such as `undefined`,
+/// read as null
+fn read_json_value(value: rquickjs::Value<'_>) -> serde_json::Value {
+    value
+        .ctx()
+        .clone()
+        .json_stringify(value)
+        .ok()
+        .flatten()
+        .and_then(|json| json.to_string().ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Read the params of llm.stream: completion params plus the provider name
+fn read_llm_stream_params(
+    value: rquickjs::Value<'_>,
+) -> Result<(String, crate::llm::CompletionParams), String> {
+    if !value.is_object() {
+        return Err("params must be an object".to_string());
+    }
+    let mut params = read_json_value(value);
+    let provider = match params
+        .as_object_mut()
+        .and_then(|params| params.remove("provider"))
+    {
+        Some(serde_json::Value::String(provider)) => provider,
+        _ => return Err("params.provider is required".to_string()),
+    };
+    let params = serde_json::from_value(params).map_err(|e| format!("Invalid params: {}", e))?;
+    Ok((provider, params))
+}
+
+/// Read the input of embeddings.create: a string or an array of strings
+fn read_embedding_input(value: rquickjs::Value<'_>) -> Result<Vec<String>, String> {
+    if let Some(text) = js_string(&value) {
+        return Ok(vec![text]);
+    }
+    serde_json::from_value(read_json_value(value))
+        .map_err(|_| "input must be a string or an array of strings".to_string())
+}
+
+/// Read the arguments of vectors.search after the query: an options object,
+/// or the number of matches followed by an optional metadata filter and
+/// offset
+fn read_vector_search_options(
+    k_or_options: Option<rquickjs::Value<'_>>,
+    filter: Option<rquickjs::Value<'_>>,
+    offset: Option<rquickjs::Value<'_>>,
+) -> Result<repository::VectorSearchOptions, String> {
+    let Some(k) = k_or_options.as_ref().filter(|value| value.is_number()) else {
+        return read_options_object(k_or_options, "options");
+    };
+    let integer = |value: &rquickjs::Value<'_>, name: &str, min: f64| {
+        value
+            .as_number()
+            .filter(|n| n.fract() == 0.0 && *n >= min && *n <= f64::from(u32::MAX))
+            .map(|n| n as u32)
+            .ok_or_else(|| format!("{} must be an integer of at least {}", name, min))
+    };
+    let filter = match filter.filter(|filter| !filter.is_undefined() && !filter.is_null()) {
+        Some(filter) if !filter.is_object() => return Err("filter must be an object".to_string()),
+        Some(filter) => Some(read_json_value(filter)),
+        None => None,
+    };
+    let offset = match offset.filter(|offset| !offset.is_undefined() && !offset.is_null()) {
+        Some(offset) => Some(integer(&offset, "offset", 0.0)?),
+        None => None,
+    };
+    Ok(repository::VectorSearchOptions {
+        limit: Some(integer(k, "k", 1.0)?),
+        offset,
+        filter,
+        min_score: None,
+    })
+}
+
+/// Read the bound parameters of a db.query call; they must form an array of
+/// JSON-serializable values
+fn read_script_query_params(params: rquickjs::Value<'_>) -> Result<Vec<serde_json::Value>, String> {
+    if params.is_undefined() || params.is_null() {
+        return Ok(Vec::new());
+    }
+    if !params.is_array() {
+        return Err("params must be an array".to_string());
+    }
+    let json = params
+        .ctx()
+        .clone()
+        .json_stringify(params)
+        .ok()
+        .flatten()
+        .and_then(|json| json.to_string().ok())
+        .ok_or_else(|| "params must be JSON-serializable".to_string())?;
+    serde_json::from_str(&json).map_err(|e| format!("params is not valid JSON: {}", e))
+}
+
+/// Read the `[{ version, up }]` list passed to db.migrate; `up` is one SQL
+/// statement or an array of them
+fn read_script_migrations(
+    migrations: rquickjs::Value<'_>,
+) -> Result<Vec<repository::ScriptMigration>, String> {
+    if !migrations.is_array() {
+        return Err("migrations must be an array".to_string());
+    }
+    let json = migrations
+        .ctx()
+        .clone()
+        .json_stringify(migrations)
+        .ok()
+        .flatten()
+        .and_then(|json| json.to_string().ok())
+        .ok_or_else(|| "migrations must be JSON-serializable".to_string())?;
+    let entries: Vec<serde_json::Value> =
+        serde_json::from_str(&json).map_err(|e| format!("migrations is not valid JSON: {}", e))?;
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let version = entry
+                .get("version")
+                .and_then(serde_json::Value::as_i64)
+                .ok_or_else(|| format!("migrations[{}].version must be an integer", index))?;
+            let up = match entry.get("up") {
+                Some(serde_json::Value::String(sql)) => vec![sql.clone()],
+                Some(serde_json::Value::Array(statements)) => statements
+                    .iter()
+                    .map(|sql| sql.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| format!("migrations[{}].up must contain only strings", index))?,
+                _ => {
+                    return Err(format!(
+                        "migrations[{}].up must be a string or an array of strings",
+                        index
+                    ));
+                }
+            };
+            Ok(repository::ScriptMigration { version, up })
+        })
+        .collect()
+}
+
+/// Read a db.query options object into repository query limits
+fn read_script_query_options(
+    options: &rquickjs::Object<'_>,
+) -> Result<repository::ScriptQueryOptions, String> {
+    let number_option = |key: &str| -> Result<Option<u64>, String> {
+        match options.get::<_, Option<f64>>(key) {
+            Ok(Some(value)) if !value.is_finite() || value < 1.0 => {
+                Err(format!("{} must be a positive number", key))
+            }
+            Ok(value) => Ok(value.map(|value| value as u64)),
+            Err(_) => Err(format!("{} must be a number", key)),
+        }
+    };
+    Ok(repository::ScriptQueryOptions::new(
+        number_option("maxRows")?,
+        number_option("timeoutMs")?,
+    ))
+}
+
+/// Options of a fetch() or fetchAsync() call. Dry runs only make requests
+/// that change nothing upstream, and calls made while handling a request
+/// carry its trace headers.
+fn prepare_fetch_options(
+    options_json: Option<String>,
+) -> JsResult<crate::http_client::FetchOptions> {
+    let options: crate::http_client::FetchOptions = match options_json {
+        Some(json_str) => serde_json::from_str(&json_str).map_err(|e| {
+            rquickjs::Error::new_from_js_message(
+                "options",
+                "FetchOptions",
+                &format!("Invalid fetch options: {}", e),
+            )
+        })?,
+        None => Default::default(),
+    };
+
+    if !matches!(options.method.to_ascii_uppercase().as_str(), "GET" | "HEAD")
+        && let Err(e) =
+            crate::dry_run::ensure_allowed(&format!("fetch with method {}", options.method))
+    {
+        return Err(rquickjs::Error::new_from_js_message("fetch", "dry_run", &e));
+    }
+
+    // Correlate the upstream call with the request being handled
+    Ok(match crate::js_engine::current_log_context() {
+        Some(log_context) => options.with_trace_headers(
+            log_context.request_id.as_deref(),
+            log_context
+                .trace_context()
+                .map(|trace| trace.child_traceparent()),
+        ),
+        None => options,
+    })
+}
+
+/// Make a fetch() request, resolving secrets for `script_uri` and
+/// `user_id`; the response as JSON, or the kind and message of the failure
+fn perform_fetch(
+    url: String,
+    options: crate::http_client::FetchOptions,
+    script_uri: &str,
+    user_id: Option<&str>,
+) -> Result<String, (&'static str, String)> {
+    let client = crate::http_client::HttpClient::new().map_err(|e| {
+        (
+            "client_init",
+            format!("Failed to create HTTP client: {}", e),
+        )
+    })?;
+    let response = client
+        .fetch(url, options, Some(script_uri), user_id)
+        .map_err(|e| ("request_failed", format!("Fetch error: {}", e)))?;
+    serde_json::to_string(&response)
+        .map_err(|e| ("serialize", format!("Failed to serialize response: {}", e)))
+}
+
+/// McpClient authenticates with the secret named by its secret identifier;
+/// scripts without the secrets capability may not name one
+fn require_mcp_secrets(secrets_enabled: bool, method: &str) -> JsResult<()> {
+    if secrets_enabled {
+        return Ok(());
+    }
+    Err(rquickjs::Error::new_from_js_message(
+        "McpClient",
+        method,
+        "McpClient secret identifiers require the secrets capability",
+    ))
+}
+
+/// Re-initialize a script in the background after it was stored or restored,
+/// clearing its previous registrations, rebuilding the GraphQL schema and
+/// warming the script up for its first request.
+fn spawn_script_initialization(script_name: String, reason: &'static str) {
+    tokio::task::spawn(async move {
+        // Clear any existing GraphQL and MCP registrations from this script before re-initializing
+        crate::graphql::clear_script_graphql_registrations(&script_name);
+        crate::mcp::clear_script_mcp_registrations(&script_name);
+
+        let initializer = crate::script_init::ScriptInitializer::new(5000); // 5s timeout
+        match initializer.initialize_script(&script_name, false).await {
+            Ok(result) => {
+                if result.success {
+                    debug!("Script '{}' initialized after {}", script_name, reason);
+                    // Rebuild GraphQL schema after script initialization
+                    if let Err(e) = crate::graphql::rebuild_schema() {
+                        warn!(
+                            "Failed to rebuild GraphQL schema after script '{}' initialization: {:?}",
+                            script_name, e
+                        );
+                    } else {
+                        debug!(
+                            "GraphQL schema rebuilt successfully after script '{}' initialization",
+                            script_name
+                        );
+                    }
+                    crate::script_init::warm_up_script(&script_name).await;
+                } else if let Some(err) = result.error {
+                    warn!(
+                        "Script '{}' init failed after {}: {}",
+                        script_name, reason, err
+                    );
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to initialize script '{}' after {}: {}",
+                    script_name, reason, e
+                );
+            }
+        }
+    });
+}
+
 /// Secure wrapper for JavaScript global functions that enforces Rust-level validation
 pub struct SecureGlobalContext {
     user_context: UserContext,
@@ -42,6 +874,13 @@ pub struct GlobalSecurityConfig {
     pub enable_scheduler: bool,
     pub enable_logging: bool,
     pub enable_secrets: bool,
+    pub enable_fetch: bool,
+    pub enable_graphql: bool,
+    pub enable_database: bool,
+    pub enable_email: bool,
+    pub enable_notify: bool,
+    pub enable_llm: bool,
+    pub enable_tasks: bool,
     pub enforce_strict_validation: bool,
     pub enable_audit_logging: bool, // New flag to disable audit logging in tests
 }
@@ -56,12 +895,35 @@ impl Default for GlobalSecurityConfig {
             enable_scheduler: true,
             enable_logging: true,
             enable_secrets: true,
+            enable_fetch: true,
+            enable_graphql: true,
+            enable_database: true,
+            enable_email: true,
+            enable_notify: true,
+            enable_llm: true,
+            enable_tasks: true,
             enforce_strict_validation: true,
             enable_audit_logging: true, // Enable by default
         }
     }
 }
 
+impl GlobalSecurityConfig {
+    /// Turn off the sandbox features a script's capability manifest doesn't
+    /// grant; see [`crate::security::script_manifests`]
+    pub fn restrict_to(&mut self, granted: &[ScriptCapability]) {
+        self.enable_fetch &= granted.contains(&ScriptCapability::Fetch);
+        self.enable_secrets &= granted.contains(&ScriptCapability::Secrets);
+        self.enable_graphql &= granted.contains(&ScriptCapability::Graphql);
+        self.enable_streams &= granted.contains(&ScriptCapability::Streams);
+        self.enable_database &= granted.contains(&ScriptCapability::Db);
+        self.enable_email &= granted.contains(&ScriptCapability::Email);
+        self.enable_notify &= granted.contains(&ScriptCapability::Notify);
+        self.enable_llm &= granted.contains(&ScriptCapability::Llm);
+        self.enable_tasks &= granted.contains(&ScriptCapability::Tasks);
+    }
+}
+
 impl SecureGlobalContext {
     pub fn new(user_context: UserContext) -> Self {
         let pool = crate::database::get_global_database().map(|db| db.pool().clone());
@@ -101,6 +963,9 @@ impl SecureGlobalContext {
         script_uri: &str,
         register_fn: Option<RouteRegisterFn>,
     ) -> JsResult<()> {
+        // Route registration is only handed in while init() runs
+        let in_init = register_fn.is_some();
+
         // Setup routeRegistry object with all route-related functions
         self.setup_route_registry(ctx, script_uri, register_fn)?;
 
@@ -110,6 +975,7 @@ impl SecureGlobalContext {
 
         if self.config.enable_script_management {
             self.setup_script_management_functions(ctx, script_uri)?;
+            self.setup_admin_functions(ctx)?;
         }
 
         if self.config.enable_asset_management {
@@ -120,23 +986,53 @@ impl SecureGlobalContext {
             self.setup_secrets_functions(ctx, script_uri)?;
         }
 
+        // Setup setTimeout() and the promises of host operations
+        self.setup_timer_functions(ctx)?;
+
         // Setup fetch() function for HTTP requests
-        self.setup_fetch_function(ctx, script_uri)?;
+        if self.config.enable_fetch {
+            self.setup_fetch_function(ctx, script_uri)?;
+            self.setup_webhook_functions(ctx, script_uri)?;
+        }
+        if self.config.enable_tasks {
+            self.setup_task_functions(ctx, script_uri)?;
+        }
+        if self.config.enable_email {
+            self.setup_email_functions(ctx, script_uri)?;
+        }
+        if self.config.enable_notify {
+            self.setup_notify_functions(ctx, script_uri)?;
+        }
+        if self.config.enable_llm {
+            self.setup_llm_functions(ctx, script_uri)?;
+        }
 
         // Setup database functions
-        self.setup_database_functions(ctx, script_uri)?;
+        if self.config.enable_database {
+            self.setup_database_functions(ctx, script_uri)?;
+            self.setup_db_query_functions(ctx, script_uri)?;
+            self.setup_vector_functions(ctx, script_uri)?;
+        }
 
         // Setup conversion functions (always enabled)
         self.setup_conversion_functions(ctx, script_uri)?;
+        self.setup_template_functions(ctx, script_uri)?;
+        self.setup_prompt_functions(ctx, script_uri)?;
+        self.setup_image_functions(ctx, script_uri)?;
+        self.setup_i18n_functions(ctx, script_uri)?;
+        self.setup_validation_functions(ctx)?;
 
         // Setup script storage functions
         self.setup_script_properties_functions(ctx, script_uri)?;
+        self.setup_cache_functions(ctx, script_uri)?;
 
         // Setup personal storage functions
         self.setup_user_properties_functions(ctx, script_uri)?;
 
-        // Always setup GraphQL functions, but they will be no-ops if disabled
-        self.setup_graphql_functions(ctx, script_uri)?;
+        // Setup GraphQL functions; registration is a no-op when disabled
+        if self.config.enable_graphql {
+            self.setup_graphql_functions(ctx, script_uri)?;
+        }
 
         // Setup MCP (Model Context Protocol) functions
         self.setup_mcp_functions(ctx, script_uri)?;
@@ -149,6 +1045,7 @@ impl SecureGlobalContext {
 
         // Setup message dispatcher bindings
         self.setup_dispatcher_functions(ctx, script_uri)?;
+        self.setup_event_functions(ctx, script_uri, in_init)?;
 
         // Setup JSX factory functions for server-side HTML generation
         self.setup_jsx_functions(ctx)?;
@@ -171,7 +1068,11 @@ impl SecureGlobalContext {
         let config_write = config.clone();
         let write_log = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, message: String, level: String| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>,
+                  message: String,
+                  level: String,
+                  data: Opt<String>|
+                  -> JsResult<String> {
                 // Check capability
                 if let Err(e) =
                     user_ctx_write.require_capability(&crate::security::Capability::ViewLogs)
@@ -230,8 +1131,35 @@ impl SecureGlobalContext {
                     "Secure writeLog called"
                 );
 
+                // Structured payload, serialized by the console wrapper
+                let data = match data.0 {
+                    Some(json) if json.len() > MAX_LOG_DATA_BYTES => {
+                        return Ok(format!(
+                            "Error: Log data too large (max {} bytes)",
+                            MAX_LOG_DATA_BYTES
+                        ));
+                    }
+                    Some(json) => match serde_json::from_str::<serde_json::Value>(&json) {
+                        Ok(value) => Some(value),
+                        Err(e) => return Ok(format!("Error: Invalid log data: {}", e)),
+                    },
+                    None => None,
+                };
+                // Dry runs return their log messages instead of storing them
+                if crate::dry_run::capture_log(&level, &message, data.as_ref()) {
+                    return Ok("Log written successfully".to_string());
+                }
+                let context = crate::js_engine::current_log_context()
+                    .and_then(|context| serde_json::to_value(context).ok());
+
                 // Call actual repository function
-                repository::insert_log_message(&script_uri_write, &message, &level);
+                repository::insert_structured_log_message(
+                    &script_uri_write,
+                    &message,
+                    &level,
+                    data.as_ref(),
+                    context.as_ref(),
+                );
                 Ok("Log written successfully".to_string())
             },
         )?;
@@ -308,6 +1236,10 @@ impl SecureGlobalContext {
 
                 let logs = repository::fetch_log_messages(&uri);
 
+                // Stack frames are shown in the script's original source when
+                // a source map was uploaded with it
+                let mut translator = crate::source_maps::LogTranslator::default();
+
                 // Create JSON array of log objects (same format as listLogs)
                 let log_objects: Vec<serde_json::Value> = logs
                     .iter()
@@ -320,7 +1252,7 @@ impl SecureGlobalContext {
                             .as_millis() as f64;
 
                         serde_json::json!({
-                            "message": log_entry.message,
+                            "message": translator.translate(&uri, &log_entry.message),
                             "level": log_entry.level,
                             "timestamp": timestamp_ms
                         })
@@ -338,88 +1270,393 @@ impl SecureGlobalContext {
             },
         )?;
 
-        // Create console object using JavaScript to avoid multiple ctx.clone() calls
-        // This creates wrapper functions in JavaScript space that call write_log with different levels
-        // and also attaches listLogs and listLogsForUri as methods
-        global.set("__writeLog", write_log)?;
-        global.set("__listLogs", list_logs)?;
-        global.set("__listLogsForUri", list_logs_for_uri)?;
-        // Secure pruneLogs function - allows pruning of logs per repository (keeps 20 entries per script)
-        let user_ctx_prune = user_context.clone();
-        let auditor_prune = auditor.clone();
-        let script_uri_prune = script_uri_owned.clone();
-        let config_prune = config.clone();
-        let prune_logs = Function::new(
+        // Secure queryLogs function - filtered, cursor-paginated log listing
+        let user_ctx_query = user_context.clone();
+        let query_logs = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check capability
+            move |_ctx: rquickjs::Ctx<'_>,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
                 if let Err(e) =
-                    user_ctx_prune.require_capability(&crate::security::Capability::DeleteLogs)
+                    user_ctx_query.require_capability(&crate::security::Capability::ViewLogs)
                 {
-                    if config_prune.enable_audit_logging {
-                        let rt = tokio::runtime::Handle::try_current();
-                        if let Ok(_rt) = rt {
-                            let auditor_clone = auditor_prune.clone();
-                            let user_id = user_ctx_prune.user_id.clone();
-                            tokio::spawn(async move {
-                                let _ = auditor_clone
-                                    .log_authz_failure(
-                                        user_id,
-                                        "log".to_string(),
-                                        "delete".to_string(),
-                                        "DeleteLogs".to_string(),
-                                    )
-                                    .await;
-                            });
-                        }
-                    }
                     return Ok(format!("Error: {}", e));
                 }
 
+                let query = match options.0.as_ref().map(read_log_query_option) {
+                    Some(Ok(query)) => query,
+                    Some(Err(e)) => return Ok(format!("Error: Invalid log query: {}", e)),
+                    None => repository::LogQuery::default(),
+                };
+
                 debug!(
-                    script_uri = %script_uri_prune,
-                    user_id = ?user_ctx_prune.user_id,
-                    "Secure console.pruneLogs called"
+                    user_id = ?user_ctx_query.user_id,
+                    script_uri = ?query.script_uri,
+                    "Secure console.queryLogs called"
                 );
 
-                // Call repository prune
-                match repository::prune_log_messages() {
-                    Ok(_) => Ok("Pruned logs".to_string()),
-                    Err(e) => {
-                        warn!("Failed to prune logs: {}", e);
-                        Ok(format!("Error: {}", e))
+                match repository::query_log_messages(&query) {
+                    Ok(mut page) => {
+                        let mut translator = crate::source_maps::LogTranslator::default();
+                        for entry in &mut page.entries {
+                            entry.message = translator.translate(&entry.script_uri, &entry.message);
+                        }
+                        match serde_json::to_string(&page) {
+                            Ok(json) => Ok(json),
+                            Err(e) => Ok(format!("Error: Failed to serialize logs: {}", e)),
+                        }
                     }
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
             },
         )?;
-        global.set("__pruneLogs", prune_logs)?;
-        ctx.eval::<(), _>(
-            r#"
-            (function() {
-                const writeLog = globalThis.__writeLog;
-                const listLogs = globalThis.__listLogs;
-                const listLogsForUri = globalThis.__listLogsForUri;
-                const pruneLogs = globalThis.__pruneLogs;
-                globalThis.console = {
-                    log: function(msg) { return writeLog(msg, "LOG"); },
-                    info: function(msg) { return writeLog(msg, "INFO"); },
-                    warn: function(msg) { return writeLog(msg, "WARN"); },
-                    error: function(msg) { return writeLog(msg, "ERROR"); },
-                    debug: function(msg) { return writeLog(msg, "DEBUG"); },
-                    listLogs: function() { return listLogs(); },
-                    listLogsForUri: function(uri) { return listLogsForUri(uri); },
-                    pruneLogs: function() { return pruneLogs(); }
+
+        // Secure queryStats function - statement statistics from the query log
+        let user_ctx_stats = user_context.clone();
+        let query_stats = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_stats.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let options = match options.0.as_ref().map(read_query_stats_option) {
+                    Some(Ok(options)) => options,
+                    Some(Err(e)) => {
+                        return Ok(format!("Error: Invalid query stats options: {}", e));
+                    }
+                    None => crate::query_log::QueryStatsOptions::default(),
                 };
-                delete globalThis.__writeLog;
-                delete globalThis.__listLogs;
-                delete globalThis.__listLogsForUri;
-                delete globalThis.__pruneLogs;
-            })();
-        "#,
+
+                match serde_json::to_string(&crate::query_log::report(&options)) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: Failed to serialize query stats: {}", e)),
+                }
+            },
         )?;
 
-        Ok(())
-    }
+        // Secure resetQueryStats function - clears the query log
+        let user_ctx_reset_stats = user_context.clone();
+        let reset_query_stats = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = user_ctx_reset_stats
+                    .require_capability(&crate::security::Capability::DeleteLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                if crate::query_log::reset() {
+                    Ok("Query stats reset".to_string())
+                } else {
+                    Ok("Error: Query log is not enabled".to_string())
+                }
+            },
+        )?;
+
+        // Secure tenantUsage function - per-tenant usage and quotas
+        let user_ctx_tenant_usage = user_context.clone();
+        let tenant_usage = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, tenant: Opt<String>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_tenant_usage.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let tenant = match tenant.0.as_deref().map(crate::tenancy::normalize_tenant_id) {
+                    Some(None) => return Ok("Error: Invalid tenant ID".to_string()),
+                    Some(Some(tenant)) => Some(tenant),
+                    None => None,
+                };
+                let report = crate::tenant_quotas::usage_report(tenant.as_deref());
+                match serde_json::to_string(&report) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: Failed to serialize tenant usage: {}", e)),
+                }
+            },
+        )?;
+
+        // Secure llmUsage function - monthly LLM token usage and budgets
+        let user_ctx_llm_usage = user_context.clone();
+        let llm_usage = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_llm_usage.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let options: crate::llm_usage::UsageReportOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let report =
+                    crate::llm_usage::usage_report(&crate::llm::current_settings(), &options);
+                match report
+                    .and_then(|report| serde_json::to_string(&report).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+
+        // Secure webhookDeliveries function - deliveries of every script
+        let user_ctx_webhooks = user_context.clone();
+        let webhook_deliveries = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_webhooks.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let options: crate::webhooks::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::webhooks::list(&options).and_then(|deliveries| {
+                    serde_json::to_string(&deliveries).map_err(|e| e.to_string())
+                }) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+
+        // Secure scriptTasks function - queued tasks of every script
+        let user_ctx_tasks = user_context.clone();
+        let script_tasks = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_tasks.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let options: crate::tasks::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::tasks::list(&options)
+                    .and_then(|tasks| serde_json::to_string(&tasks).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+
+        // Secure taskQueueStats function - queue depth per script
+        let user_ctx_task_stats = user_context.clone();
+        let task_queue_stats = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_task_stats.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                match crate::tasks::stats()
+                    .and_then(|stats| serde_json::to_string(&stats).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+
+        // Secure emailLog function - sent and failed emails of every script
+        let user_ctx_email = user_context.clone();
+        let email_log = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_email.require_capability(&crate::security::Capability::ViewLogs)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let options: crate::email::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::email::list(&options)
+                    .and_then(|entries| serde_json::to_string(&entries).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+
+        // Create console object using JavaScript to avoid multiple ctx.clone() calls
+        // This creates wrapper functions in JavaScript space that call write_log with different levels
+        // and also attaches listLogs and listLogsForUri as methods
+        global.set("__writeLog", write_log)?;
+        global.set("__listLogs", list_logs)?;
+        global.set("__listLogsForUri", list_logs_for_uri)?;
+        global.set("__queryLogs", query_logs)?;
+        global.set("__queryStats", query_stats)?;
+        global.set("__resetQueryStats", reset_query_stats)?;
+        global.set("__tenantUsage", tenant_usage)?;
+        global.set("__llmUsage", llm_usage)?;
+        global.set("__webhookDeliveries", webhook_deliveries)?;
+        global.set("__scriptTasks", script_tasks)?;
+        global.set("__taskQueueStats", task_queue_stats)?;
+        global.set("__emailLog", email_log)?;
+        // Secure pruneLogs function - allows pruning of logs per repository (keeps 20 entries per script)
+        let user_ctx_prune = user_context.clone();
+        let auditor_prune = auditor.clone();
+        let script_uri_prune = script_uri_owned.clone();
+        let config_prune = config.clone();
+        let prune_logs = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check capability
+                if let Err(e) =
+                    user_ctx_prune.require_capability(&crate::security::Capability::DeleteLogs)
+                {
+                    if config_prune.enable_audit_logging {
+                        let rt = tokio::runtime::Handle::try_current();
+                        if let Ok(_rt) = rt {
+                            let auditor_clone = auditor_prune.clone();
+                            let user_id = user_ctx_prune.user_id.clone();
+                            tokio::spawn(async move {
+                                let _ = auditor_clone
+                                    .log_authz_failure(
+                                        user_id,
+                                        "log".to_string(),
+                                        "delete".to_string(),
+                                        "DeleteLogs".to_string(),
+                                    )
+                                    .await;
+                            });
+                        }
+                    }
+                    return Ok(format!("Error: {}", e));
+                }
+
+                debug!(
+                    script_uri = %script_uri_prune,
+                    user_id = ?user_ctx_prune.user_id,
+                    "Secure console.pruneLogs called"
+                );
+
+                // Call repository prune
+                match repository::prune_log_messages() {
+                    Ok(_) => Ok("Pruned logs".to_string()),
+                    Err(e) => {
+                        warn!("Failed to prune logs: {}", e);
+                        Ok(format!("Error: {}", e))
+                    }
+                }
+            },
+        )?;
+        global.set("__pruneLogs", prune_logs)?;
+        ctx.eval::<(), _>(
+            r#"
+            (function() {
+                const writeLog = globalThis.__writeLog;
+                const listLogs = globalThis.__listLogs;
+                const listLogsForUri = globalThis.__listLogsForUri;
+                const queryLogs = globalThis.__queryLogs;
+                const queryStats = globalThis.__queryStats;
+                const resetQueryStats = globalThis.__resetQueryStats;
+                const tenantUsage = globalThis.__tenantUsage;
+                const llmUsage = globalThis.__llmUsage;
+                const webhookDeliveries = globalThis.__webhookDeliveries;
+                const scriptTasks = globalThis.__scriptTasks;
+                const taskQueueStats = globalThis.__taskQueueStats;
+                const emailLog = globalThis.__emailLog;
+                const pruneLogs = globalThis.__pruneLogs;
+                // console.log("message", { structured: "data" }) stores the object
+                // as JSON next to the message, and console.log({ ... }) alone uses
+                // the serialized object as the message too. Any other extra
+                // arguments are joined into the message.
+                function isStructured(value) {
+                    return value !== null && typeof value === "object" && !(value instanceof Error);
+                }
+                function stringify(value) {
+                    try {
+                        return JSON.stringify(value);
+                    } catch (e) {
+                        return undefined;
+                    }
+                }
+                function format(value) {
+                    if (typeof value === "string") return value;
+                    if (isStructured(value)) {
+                        const json = stringify(value);
+                        if (json !== undefined) return json;
+                    }
+                    return String(value);
+                }
+                function write(level, args) {
+                    let message = args[0];
+                    let json;
+                    if (args.length === 1 && isStructured(message)) {
+                        json = stringify(message);
+                    } else if (args.length === 2 && isStructured(args[1])) {
+                        json = stringify(args[1]);
+                        args = [message];
+                    }
+                    message = Array.prototype.map.call(args, format).join(" ");
+                    return json === undefined ? writeLog(message, level) : writeLog(message, level, json);
+                }
+                globalThis.console = {
+                    log: function() { return write("LOG", Array.from(arguments)); },
+                    info: function() { return write("INFO", Array.from(arguments)); },
+                    warn: function() { return write("WARN", Array.from(arguments)); },
+                    error: function() { return write("ERROR", Array.from(arguments)); },
+                    debug: function() { return write("DEBUG", Array.from(arguments)); },
+                    listLogs: function() { return listLogs(); },
+                    listLogsForUri: function(uri) { return listLogsForUri(uri); },
+                    queryLogs: function(options) { return queryLogs(options || {}); },
+                    queryStats: function(options) { return queryStats(options || {}); },
+                    resetQueryStats: function() { return resetQueryStats(); },
+                    tenantUsage: function(tenant) {
+                        return tenant == null ? tenantUsage() : tenantUsage(tenant);
+                    },
+                    llmUsage: function(options) { return llmUsage(options || {}); },
+                    webhookDeliveries: function(options) { return webhookDeliveries(options || {}); },
+                    scriptTasks: function(options) { return scriptTasks(options || {}); },
+                    taskQueueStats: function() { return taskQueueStats(); },
+                    emailLog: function(options) { return emailLog(options || {}); },
+                    pruneLogs: function() { return pruneLogs(); }
+                };
+                delete globalThis.__writeLog;
+                delete globalThis.__listLogs;
+                delete globalThis.__listLogsForUri;
+                delete globalThis.__queryLogs;
+                delete globalThis.__queryStats;
+                delete globalThis.__resetQueryStats;
+                delete globalThis.__tenantUsage;
+                delete globalThis.__llmUsage;
+                delete globalThis.__webhookDeliveries;
+                delete globalThis.__scriptTasks;
+                delete globalThis.__taskQueueStats;
+                delete globalThis.__emailLog;
+                delete globalThis.__pruneLogs;
+            })();
+        "#,
+        )?;
+
+        Ok(())
+    }
 
     /// Setup secure script management functions
     fn setup_script_management_functions(
@@ -440,7 +1677,7 @@ impl SecureGlobalContext {
         let user_ctx_list = user_context.clone();
         let list_scripts = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>, filter: Opt<rquickjs::Object<'_>>| -> JsResult<String> {
                 // Check capability
                 if let Err(_e) =
                     user_ctx_list.require_capability(&crate::security::Capability::ReadScripts)
@@ -462,9 +1699,26 @@ impl SecureGlobalContext {
                     }
                 };
 
+                // Optional filter: { tag?: string, tags?: string[], search?: string }
+                let mut required_tags: Vec<String> = Vec::new();
+                let mut search: Option<String> = None;
+                if let Some(filter) = filter.0 {
+                    if let Ok(Some(tag)) = filter.get::<_, Option<String>>("tag") {
+                        required_tags.push(tag);
+                    }
+                    if let Ok(Some(tags)) = filter.get::<_, Option<Vec<String>>>("tags") {
+                        required_tags.extend(tags);
+                    }
+                    if let Ok(query) = filter.get::<_, Option<String>>("search") {
+                        search = query;
+                    }
+                }
+
                 // Build JSON array of script metadata
                 let scripts_json: Vec<serde_json::Value> = metadata_list
                     .iter()
+                    .filter(|meta| meta.has_tags(&required_tags))
+                    .filter(|meta| search.as_deref().is_none_or(|q| meta.matches_text(q)))
                     .map(|meta| {
                         serde_json::json!({
                             "uri": meta.uri,
@@ -479,8 +1733,14 @@ impl SecureGlobalContext {
                                 .unwrap_or_default()
                                 .as_millis() as f64,
                             "privileged": meta.privileged,
+                            "system": meta.system,
                             "initialized": meta.initialized,
-                            "initError": meta.init_error.as_deref()
+                            "initError": meta.init_error.as_deref(),
+                            "description": meta.description.as_deref(),
+                            "tags": meta.tags,
+                            "schemaVersion": meta.schema_version,
+                            "executionTimeoutMs": meta.execution_timeout_ms,
+                            "memoryLimitBytes": meta.memory_limit_bytes
                         })
                     })
                     .collect();
@@ -564,6 +1824,7 @@ impl SecureGlobalContext {
                                 .duration_since(std::time::UNIX_EPOCH)
                                 .ok()
                                 .map(|d| d.as_millis() as f64),
+                            "schemaVersion": metadata.schema_version,
                         });
                         Ok(Some(status.to_string()))
                     }
@@ -633,6 +1894,34 @@ impl SecureGlobalContext {
         )?;
         script_storage.set("setScriptPrivileged", set_script_privileged)?;
 
+        // Secure setScriptSystem function (admin only) - protects a script
+        // from being overwritten or deleted without force
+        let user_ctx_set_system = user_context.clone();
+        let set_script_system = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String, system: bool| -> JsResult<bool> {
+                if !user_ctx_set_system.has_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "setScriptSystem",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
+                }
+
+                repository::set_script_system(&script_name, system).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "setScriptSystem",
+                        "repository_error",
+                        &format!("{}", e),
+                    )
+                })?;
+
+                Ok(true)
+            },
+        )?;
+        script_storage.set("setScriptSystem", set_script_system)?;
+
         // Helper to allow UI to detect admin capability
         let user_ctx_manage_privileges = user_context.clone();
         let can_manage_privileges = Function::new(
@@ -752,14 +2041,110 @@ impl SecureGlobalContext {
         )?;
         script_storage.set("removeScriptOwner", remove_script_owner)?;
 
+        // Secure getScriptCollaborators function
+        let get_script_collaborators = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                // Anyone can view collaborators (for transparency)
+                match repository::get_script_collaborators(&script_name) {
+                    Ok(collaborators) => match serde_json::to_string(&collaborators) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error serializing collaborators: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error getting collaborators: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("getScriptCollaborators", get_script_collaborators)?;
+
+        // Secure addScriptCollaborator function (admin or current owner only)
+        let user_ctx_add_collaborator = user_context.clone();
+        let add_script_collaborator = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  collaborator_id: String|
+                  -> JsResult<String> {
+                // Collaborators cannot grant access to others, only owners can
+                let is_admin = user_ctx_add_collaborator
+                    .has_capability(&crate::security::Capability::DeleteScripts);
+                let user_owns = if let Some(user_id) = &user_ctx_add_collaborator.user_id {
+                    repository::user_owns_script(&script_name, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !is_admin && !user_owns {
+                    return Ok("Error: Permission denied. You must be an administrator or owner to add collaborators".to_string());
+                }
+
+                if repository::fetch_script(&script_name).is_none() {
+                    return Ok(format!("Error: Script '{}' not found", script_name));
+                }
+
+                // "group:<name>" makes every member of the group a collaborator
+                if let Some(group) = crate::user_repository::group_principal(&collaborator_id)
+                    && let Err(e) = crate::user_repository::get_group(group)
+                {
+                    return Ok(format!("Error adding collaborator: {}", e));
+                }
+
+                match repository::add_script_collaborator(&script_name, &collaborator_id) {
+                    Ok(_) => Ok(format!(
+                        "Successfully added collaborator '{}' to script '{}'",
+                        collaborator_id, script_name
+                    )),
+                    Err(e) => Ok(format!("Error adding collaborator: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("addScriptCollaborator", add_script_collaborator)?;
+
+        // Secure removeScriptCollaborator function (admin or current owner only)
+        let user_ctx_remove_collaborator = user_context.clone();
+        let remove_script_collaborator = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  collaborator_id: String|
+                  -> JsResult<String> {
+                let is_admin = user_ctx_remove_collaborator
+                    .has_capability(&crate::security::Capability::DeleteScripts);
+                let user_owns = if let Some(user_id) = &user_ctx_remove_collaborator.user_id {
+                    repository::user_owns_script(&script_name, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !is_admin && !user_owns {
+                    return Ok("Error: Permission denied. You must be an administrator or owner to remove collaborators".to_string());
+                }
+
+                match repository::remove_script_collaborator(&script_name, &collaborator_id) {
+                    Ok(true) => Ok(format!(
+                        "Successfully removed collaborator '{}' from script '{}'",
+                        collaborator_id, script_name
+                    )),
+                    Ok(false) => Ok(format!(
+                        "Collaborator '{}' was not found for script '{}'",
+                        collaborator_id, script_name
+                    )),
+                    Err(e) => Ok(format!("Error removing collaborator: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("removeScriptCollaborator", remove_script_collaborator)?;
+
         // Secure upsertScript function
         let user_ctx_upsert = user_context.clone();
         let _config_upsert = self.config.clone();
+        let auditor_upsert = auditor.clone();
         let upsert_script = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
                   script_name: String,
-                  js_script: String|
+                  js_script: String,
+                  options: Opt<rquickjs::Object<'_>>|
                   -> JsResult<String> {
                 // Check capability
                 if let Err(e) =
@@ -774,404 +2159,656 @@ impl SecureGlobalContext {
                 }
 
                 // Check ownership permission for existing scripts
-                if let Some(_existing_script) = repository::fetch_script(&script_name) {
-                    // Script exists - check if user is admin or owns it
-                    let is_admin =
-                        user_ctx_upsert.has_capability(&crate::security::Capability::DeleteScripts);
-                    let user_owns = if let Some(user_id) = &user_ctx_upsert.user_id {
-                        repository::user_owns_script(&script_name, user_id).unwrap_or(false)
-                    } else {
-                        false
-                    };
+                if let Err(message) = check_script_write_permission(&user_ctx_upsert, &script_name)
+                {
+                    return Ok(message);
+                }
 
-                    debug!(
-                        script_name = %script_name,
-                        user_id = ?user_ctx_upsert.user_id,
-                        is_admin = is_admin,
-                        user_owns = user_owns,
-                        "Checking script update permissions for existing script"
-                    );
+                // Reject scripts that would fail to load; lint warnings are
+                // reported by lintScript and never block the save
+                let warnings = match crate::script_lint::validate(&script_name, &js_script) {
+                    Ok(warnings) => warnings,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
 
-                    if !is_admin && !user_owns {
-                        warn!(
-                            script_name = %script_name,
-                            user_id = ?user_ctx_upsert.user_id,
-                            is_admin = is_admin,
-                            user_owns = user_owns,
-                            "Permission denied: user is neither admin nor owner"
-                        );
-                        return Ok(format!(
-                            "Error: Permission denied. You must be an administrator or owner to modify script '{}'",
-                            script_name
-                        ));
+                // Validate labels before storing anything so a bad option
+                // doesn't leave the content updated but the labels stale
+                let labels = match options.0.as_ref() {
+                    Some(options) => {
+                        let existing =
+                            repository::get_script_labels(&script_name).unwrap_or_default();
+                        match merge_script_labels(existing, options) {
+                            Ok(labels) => Some(labels),
+                            Err(e) => return Ok(format!("Error: {}", e)),
+                        }
                     }
-                }
+                    None => None,
+                };
+                let source_map = match options.0.as_ref().map(read_source_map_option) {
+                    Some(Ok(source_map)) => source_map,
+                    Some(Err(e)) => return Ok(format!("Error: {}", e)),
+                    None => None,
+                };
 
-                // Store the script using repository with owner
-                let owner_user_id = user_ctx_upsert.user_id.as_deref();
-                if let Err(e) =
-                    repository::upsert_script_with_owner(&script_name, &js_script, owner_user_id)
+                // System scripts are only overwritten by administrators
+                // passing { force: true }
+                let force = options
+                    .0
+                    .as_ref()
+                    .and_then(|options| options.get::<_, Option<bool>>("force").ok().flatten())
+                    .unwrap_or(false);
+                if force
+                    && !user_ctx_upsert.has_capability(&crate::security::Capability::DeleteScripts)
                 {
+                    return Ok(
+                        "Error: Only administrators can force changes to system scripts"
+                            .to_string(),
+                    );
+                }
+                let forced_system_write =
+                    force && repository::is_system_script(&script_name).unwrap_or(false);
+
+                // Store the script using repository with owner
+                let owner_user_id = user_ctx_upsert.user_id.as_deref();
+                if let Err(e) = repository::upsert_script_with_owner(
+                    &script_name,
+                    &js_script,
+                    owner_user_id,
+                    force,
+                ) {
                     return Ok(format!("Error storing script: {}", e));
                 }
 
+                if forced_system_write {
+                    let auditor_clone = auditor_upsert.clone();
+                    let event = crate::security::SecurityEvent::new(
+                        SecurityEventType::SystemSecurityEvent,
+                        SecuritySeverity::High,
+                        user_ctx_upsert.user_id.clone(),
+                    )
+                    .with_resource(script_name.clone())
+                    .with_action("force_system_script_write".to_string());
+                    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                        handle.spawn(async move {
+                            auditor_clone.log_event(event).await;
+                        });
+                    }
+                }
+
+                if let Some(labels) = labels
+                    && let Err(e) = repository::set_script_labels(&script_name, &labels)
+                {
+                    return Ok(format!("Error storing script labels: {}", e));
+                }
+
+                if let Some(source_map) = source_map {
+                    let stored = source_map.map(|source_map| repository::StoredSourceMap {
+                        source_map,
+                        content_hash: crate::source_maps::content_hash(&js_script),
+                    });
+                    if let Err(e) = repository::set_script_source_map(&script_name, stored.as_ref())
+                    {
+                        return Ok(format!("Error storing source map: {}", e));
+                    }
+                }
+
                 debug!(
                     script_name = %script_name,
                     user_id = ?user_ctx_upsert.user_id,
                     "Secure upsertScript called"
                 );
 
+                // Scripts using forbidden globals are reported to threat
+                // detection, whose policies may quarantine them
+                let mut forbidden_globals: Vec<&str> = warnings
+                    .iter()
+                    .filter(|warning| warning.rule == "forbidden-global")
+                    .map(|warning| warning.message.as_str())
+                    .collect();
+                forbidden_globals.sort_unstable();
+                forbidden_globals.dedup();
+                if !forbidden_globals.is_empty() {
+                    let auditor_clone = auditor_upsert.clone();
+                    let event = crate::security::SecurityEvent::new(
+                        SecurityEventType::SuspiciousActivity,
+                        SecuritySeverity::Medium,
+                        user_ctx_upsert.user_id.clone(),
+                    )
+                    .with_resource(script_name.clone())
+                    .with_action("script_write".to_string())
+                    .with_detail("forbidden_globals", forbidden_globals.join("; "));
+                    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                        handle.spawn(async move {
+                            auditor_clone.log_event(event).await;
+                        });
+                    }
+                }
+
                 // Initialize the script asynchronously in the background
                 // This calls the init() function if it exists
-                let script_name_for_init = script_name.clone();
-                tokio::task::spawn(async move {
-                    // Clear any existing GraphQL and MCP registrations from this script before re-initializing
-                    crate::graphql::clear_script_graphql_registrations(&script_name_for_init);
-                    crate::mcp::clear_script_mcp_registrations(&script_name_for_init);
-
-                    let initializer = crate::script_init::ScriptInitializer::new(5000); // 5s timeout
-                    match initializer
-                        .initialize_script(&script_name_for_init, false)
-                        .await
-                    {
-                        Ok(result) => {
-                            if result.success {
-                                debug!(
-                                    "Script '{}' initialized after upsert",
-                                    script_name_for_init
-                                );
-                                // Rebuild GraphQL schema after script initialization
-                                if let Err(e) = crate::graphql::rebuild_schema() {
-                                    warn!(
-                                        "Failed to rebuild GraphQL schema after script '{}' initialization: {:?}",
-                                        script_name_for_init, e
-                                    );
-                                } else {
-                                    debug!(
-                                        "GraphQL schema rebuilt successfully after script '{}' initialization",
-                                        script_name_for_init
-                                    );
-                                }
-                            } else if let Some(err) = result.error {
-                                warn!(
-                                    "Script '{}' init failed after upsert: {}",
-                                    script_name_for_init, err
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            warn!(
-                                "Failed to initialize script '{}' after upsert: {}",
-                                script_name_for_init, e
-                            );
-                        }
-                    }
-                });
+                spawn_script_initialization(script_name.clone(), "upsert");
 
                 Ok(format!("Script '{}' upserted successfully", script_name))
             },
         )?;
         script_storage.set("upsertScript", upsert_script)?;
 
-        // Secure deleteScript function
-        let user_ctx_delete = user_context.clone();
-        let auditor_delete = auditor.clone();
-        let delete_script = Function::new(
+        // Secure dryRun function - runs an unsaved script body against a
+        // described request without persisting anything
+        let user_ctx_dry_run = user_context.clone();
+        let dry_run = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<bool> {
-                // Check capability
+            move |ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  js_script: String,
+                  request: rquickjs::Value<'_>|
+                  -> JsResult<String> {
                 if let Err(e) =
-                    user_ctx_delete.require_capability(&crate::security::Capability::DeleteScripts)
+                    user_ctx_dry_run.require_capability(&crate::security::Capability::WriteScripts)
                 {
-                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_delete.clone();
-                    let user_id = user_ctx_delete.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "script".to_string(),
-                                "delete".to_string(),
-                                "DeleteScripts".to_string(),
-                            )
-                            .await;
-                    });
-                    warn!(
-                        user_id = ?user_ctx_delete.user_id,
-                        script_name = %script_name,
-                        error = %e,
-                        "deleteScript capability check failed"
-                    );
-                    return Ok(false);
+                    return Ok(format!("Error: {}", e));
                 }
 
-                // Check ownership permission - admins can delete any script, others only owned scripts
-                let is_admin =
-                    user_ctx_delete.has_capability(&crate::security::Capability::DeleteScripts);
-                let user_owns = if let Some(user_id) = &user_ctx_delete.user_id {
-                    repository::user_owns_script(&script_name, user_id).unwrap_or(false)
-                } else {
-                    false
-                };
+                if script_name.is_empty() || js_script.is_empty() {
+                    return Ok("Error: Script name and content cannot be empty".to_string());
+                }
 
-                if !is_admin && !user_owns {
-                    warn!(
-                        user_id = ?user_ctx_delete.user_id,
-                        script_name = %script_name,
-                        "deleteScript ownership check failed - user is not admin and does not own script"
-                    );
-                    return Ok(false);
+                // The dry run sees the script's data, so it needs the same
+                // permission as saving the script
+                if let Err(message) = check_script_write_permission(&user_ctx_dry_run, &script_name)
+                {
+                    return Ok(message);
                 }
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_delete.clone();
-                let user_id = user_ctx_delete.user_id.clone();
-                let script_name_clone = script_name.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::High,
-                                user_id,
-                            )
-                            .with_resource("script".to_string())
-                            .with_action("delete".to_string())
-                            .with_detail("script_name", &script_name_clone),
-                        )
-                        .await;
-                });
+                let request = match read_dry_run_request(request) {
+                    Ok(request) => request,
+                    Err(e) => return Ok(format!("Error: Invalid dry run request: {}", e)),
+                };
 
                 debug!(
-                    user_id = ?user_ctx_delete.user_id,
                     script_name = %script_name,
-                    "Secure deleteScript called"
+                    user_id = ?user_ctx_dry_run.user_id,
+                    handler = %request.handler,
+                    "Secure dryRun called"
                 );
 
-                Ok(repository::delete_script(&script_name))
+                let result = crate::dry_run::execute(
+                    &script_name,
+                    &js_script,
+                    request,
+                    user_ctx_dry_run.clone(),
+                    get_auth_context(&ctx.globals()),
+                );
+                match serde_json::to_string(&result) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: Failed to serialize dry run result: {}", e)),
+                }
             },
         )?;
-        script_storage.set("deleteScript", delete_script)?;
+        script_storage.set("dryRun", dry_run)?;
 
-        // Set the scriptStorage object on the global scope
-        global.set("scriptStorage", script_storage)?;
-
-        Ok(())
-    }
+        // Secure lintScript function - checks the syntax of a script body and
+        // lints it without saving it
+        let lint_script = Function::new(
+            ctx.clone(),
+            move |script_name: String, content: String| -> JsResult<String> {
+                let report = match crate::script_lint::validate(&script_name, &content) {
+                    Ok(warnings) => serde_json::json!({ "valid": true, "warnings": warnings }),
+                    Err(e) => serde_json::json!({ "valid": false, "error": e, "warnings": [] }),
+                };
+                Ok(report.to_string())
+            },
+        )?;
+        script_storage.set("lintScript", lint_script)?;
 
-    /// Setup secure asset management functions
-    fn setup_asset_management_functions(
-        &self,
-        ctx: &rquickjs::Ctx<'_>,
-        script_uri: &str,
-    ) -> JsResult<()> {
-        let global = ctx.globals();
-        let user_context = self.user_context.clone();
-        let secure_ops = self.secure_ops.clone();
-        let auditor = self.auditor.clone();
-        let script_uri_owned = script_uri.to_string();
-        let script_uri_remaining = script_uri_owned.clone(); // Clone for remaining functions
+        // Secure getScriptLabels function - returns { description, tags } or null
+        let user_ctx_get_labels = user_context.clone();
+        let get_script_labels = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<Option<String>> {
+                if let Err(_e) = user_ctx_get_labels
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(None);
+                }
 
-        // Create assetStorage object
-        let asset_storage = rquickjs::Object::new(ctx.clone())?;
+                match repository::get_script_labels(&script_name) {
+                    Ok(labels) => Ok(serde_json::to_string(&labels).ok()),
+                    Err(_) => Ok(None),
+                }
+            },
+        )?;
+        script_storage.set("getScriptLabels", get_script_labels)?;
 
-        // Secure listAssets function
-        let user_ctx_list = user_context.clone();
-        let script_uri_list = script_uri_owned.clone();
-        let list_assets = Function::new(
+        // Secure setScriptLabels function - updates description/tags without
+        // touching the script content
+        let user_ctx_labels = user_context.clone();
+        let set_script_labels = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check capability
-                if let Err(_e) =
-                    user_ctx_list.require_capability(&crate::security::Capability::ReadAssets)
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  options: rquickjs::Object<'_>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_labels.require_capability(&crate::security::Capability::WriteScripts)
                 {
-                    // Return empty array JSON if no permission
-                    return Ok("[]".to_string());
+                    return Ok(format!("Error: {}", e));
                 }
 
-                debug!(
-                    user_id = ?user_ctx_list.user_id,
-                    "Secure listAssets called"
-                );
+                let existing = match repository::get_script_labels(&script_name) {
+                    Ok(labels) => labels,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                if let Err(message) = check_script_write_permission(&user_ctx_labels, &script_name)
+                {
+                    return Ok(message);
+                }
 
-                let assets = repository::fetch_assets(&script_uri_list);
+                let labels = match merge_script_labels(existing, &options) {
+                    Ok(labels) => labels,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                if let Err(e) = repository::set_script_labels(&script_name, &labels) {
+                    return Ok(format!("Error storing script labels: {}", e));
+                }
 
-                // Build JSON array of asset metadata (matching listScripts pattern)
-                let assets_json: Vec<serde_json::Value> = assets
-                    .values()
-                    .map(|asset| {
-                        serde_json::json!({
-                            "uri": asset.uri,
-                            "name": asset.name,
-                            "size": asset.content.len(),
-                            "mimetype": asset.mimetype,
-                            "createdAt": asset.created_at
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_millis() as f64,
-                            "updatedAt": asset.updated_at
-                                .duration_since(std::time::UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_millis() as f64,
-                        })
-                    })
-                    .collect();
+                debug!(
+                    script_name = %script_name,
+                    user_id = ?user_ctx_labels.user_id,
+                    tags = ?labels.tags,
+                    "Secure setScriptLabels called"
+                );
 
-                match serde_json::to_string(&assets_json) {
+                match serde_json::to_string(&labels) {
                     Ok(json) => Ok(json),
-                    Err(e) => {
-                        error!("Failed to serialize assets to JSON: {}", e);
-                        Ok("[]".to_string())
-                    }
+                    Err(e) => Ok(format!("Error serializing labels: {}", e)),
                 }
             },
         )?;
-        asset_storage.set("listAssets", list_assets)?;
+        script_storage.set("setScriptLabels", set_script_labels)?;
 
-        // Secure fetchAsset function
-        let user_ctx_fetch = user_context.clone();
-        let script_uri_fetch = script_uri_remaining.clone();
-        let fetch_asset = Function::new(
+        // Secure getStorageUsage function - returns JSON with the bytes a script
+        // stores in shared storage and assets, and its quota
+        let user_ctx_usage = user_context.clone();
+        let get_storage_usage = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
-                // Check capability
-                if let Err(e) =
-                    user_ctx_fetch.require_capability(&crate::security::Capability::ReadAssets)
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<Option<String>> {
+                if let Err(_e) =
+                    user_ctx_usage.require_capability(&crate::security::Capability::ReadScripts)
                 {
-                    return Ok(format!("Error: {}", e));
+                    return Ok(None);
                 }
 
-                debug!(
-                    user_id = ?user_ctx_fetch.user_id,
-                    uri = %uri,
-                    "Secure fetchAsset called"
-                );
-
-                match repository::fetch_asset(&script_uri_fetch, &uri) {
-                    Some(asset) => {
-                        // Convert bytes to base64 for safe JavaScript transfer
-                        Ok(base64::engine::general_purpose::STANDARD.encode(asset.content))
-                    }
-                    None => Ok(format!("Asset '{}' not found", uri)),
+                match repository::get_script_storage_usage(&script_name) {
+                    Ok(usage) => Ok(serde_json::to_string(&usage).ok()),
+                    Err(_) => Ok(None),
                 }
             },
         )?;
-        asset_storage.set("fetchAsset", fetch_asset)?;
+        script_storage.set("getStorageUsage", get_storage_usage)?;
 
-        // Secure upsertAsset function
-        let user_ctx_upsert_asset = user_context.clone();
-        let _secure_ops_asset = secure_ops.clone();
-        let auditor_asset = auditor.clone();
-        let script_uri_asset = script_uri_owned.clone();
-        let upsert_asset = Function::new(
+        // Secure getRouteUsage function - returns JSON with request counts,
+        // status codes and latency percentiles of a script's routes
+        let user_ctx_route_usage = user_context.clone();
+        let get_route_usage = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  uri: String,
-                  mimetype: String,
-                  content_b64: String,
-                  name: Opt<String>|
+                  script_name: String,
+                  options: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
-                // Decode base64 content
-                let content = match base64::engine::general_purpose::STANDARD.decode(&content_b64) {
-                    Ok(c) => c,
-                    Err(e) => return Ok(format!("Error decoding base64 content: {}", e)),
-                };
+                if let Err(e) = user_ctx_route_usage
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
 
-                // Check capability
-                if let Err(e) = user_ctx_upsert_asset
-                    .require_capability(&crate::security::Capability::WriteAssets)
+                let options: crate::route_usage::RouteUsageOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::route_usage::report(&script_name, &options)
+                    .and_then(|report| serde_json::to_string(&report).map_err(|e| e.to_string()))
                 {
-                    return Ok(format!("Access denied: {}", e));
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
+            },
+        )?;
+        script_storage.set("getRouteUsage", get_route_usage)?;
 
-                // Validate asset URI (inline validation since we can't call async)
-                if uri.is_empty() || uri.len() > 255 {
-                    return Ok("Invalid asset URI: must be 1-255 characters".to_string());
+        // Secure getResourceUsage function - returns JSON with execution
+        // time, memory, timeouts and outbound bytes per script
+        let user_ctx_resource_usage = user_context.clone();
+        let get_resource_usage = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) = user_ctx_resource_usage
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
-                if uri.contains("..") || uri.contains('\\') {
-                    return Ok("Invalid asset URI: path traversal not allowed".to_string());
+
+                let options: crate::script_resources::ScriptResourceOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::script_resources::report(&options)
+                    .and_then(|report| serde_json::to_string(&report).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
+            },
+        )?;
+        script_storage.set("getResourceUsage", get_resource_usage)?;
 
-                // Validate content size (10MB limit)
-                if content.len() > 10 * 1024 * 1024 {
-                    return Ok("Asset too large (max 10MB)".to_string());
+        // Secure setStorageQuota function (admin only) - null restores the
+        // configured default, 0 lifts the limit
+        let user_ctx_set_quota = user_context.clone();
+        let set_storage_quota = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  quota_bytes: Option<f64>|
+                  -> JsResult<bool> {
+                if !user_ctx_set_quota.has_capability(&crate::security::Capability::DeleteScripts) {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "setStorageQuota",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
                 }
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_asset.clone();
-                let user_id = user_ctx_upsert_asset.user_id.clone();
-                let uri_clone = uri.clone();
-                let script_uri_clone = script_uri_asset.clone();
-                let content_len = content.len();
-                let mimetype_clone = mimetype.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Medium,
-                                user_id,
-                            )
-                            .with_resource("asset".to_string())
-                            .with_action("upsert".to_string())
-                            .with_detail("uri", &uri_clone)
-                            .with_detail("script_uri", &script_uri_clone)
-                            .with_detail("content_size", content_len.to_string())
-                            .with_detail("mimetype", &mimetype_clone),
+                let quota_bytes = match quota_bytes {
+                    Some(bytes) if !bytes.is_finite() || bytes < 0.0 || bytes.fract() != 0.0 => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "setStorageQuota",
+                            "invalid_quota",
+                            "Quota must be a non-negative whole number of bytes or null",
+                        ));
+                    }
+                    Some(bytes) => Some(bytes as u64),
+                    None => None,
+                };
+
+                repository::set_script_storage_quota(&script_name, quota_bytes).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "setStorageQuota",
+                        "repository_error",
+                        &format!("{}", e),
+                    )
+                })?;
+
+                debug!(
+                    script_name = %script_name,
+                    user_id = ?user_ctx_set_quota.user_id,
+                    quota_bytes = ?quota_bytes,
+                    "Secure setStorageQuota called"
+                );
+
+                Ok(true)
+            },
+        )?;
+        script_storage.set("setStorageQuota", set_storage_quota)?;
+
+        // Secure setExecutionTimeout function (admin only) - null restores the
+        // configured timeout
+        let user_ctx_set_timeout = user_context.clone();
+        let set_execution_timeout = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  timeout_ms: Option<f64>|
+                  -> JsResult<bool> {
+                if !user_ctx_set_timeout.has_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "setExecutionTimeout",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
+                }
+
+                let ceiling = crate::js_engine::current_execution_limits().max_script_timeout_ms;
+                let timeout_ms = match timeout_ms {
+                    Some(ms) if !ms.is_finite() || ms < 1.0 || ms.fract() != 0.0 => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "setExecutionTimeout",
+                            "invalid_timeout",
+                            "Timeout must be a positive whole number of milliseconds or null",
+                        ));
+                    }
+                    Some(ms) if ms > ceiling as f64 => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "setExecutionTimeout",
+                            "invalid_timeout",
+                            &format!("Timeout must be at most {} milliseconds", ceiling),
+                        ));
+                    }
+                    Some(ms) => Some(ms as u64),
+                    None => None,
+                };
+
+                repository::set_script_execution_timeout(&script_name, timeout_ms).map_err(
+                    |e| {
+                        rquickjs::Error::new_from_js_message(
+                            "setExecutionTimeout",
+                            "repository_error",
+                            &format!("{}", e),
                         )
-                        .await;
-                });
+                    },
+                )?;
 
-                // Call repository directly (sync operation)
-                let now = std::time::SystemTime::now();
-                let asset = repository::Asset {
-                    uri: uri.clone(),
-                    name: name.0.or_else(|| Some(uri.clone())),
-                    mimetype,
-                    content,
-                    created_at: now,
-                    updated_at: now,
-                    script_uri: script_uri_owned.clone(),
+                debug!(
+                    script_name = %script_name,
+                    user_id = ?user_ctx_set_timeout.user_id,
+                    timeout_ms = ?timeout_ms,
+                    "Secure setExecutionTimeout called"
+                );
+
+                Ok(true)
+            },
+        )?;
+        script_storage.set("setExecutionTimeout", set_execution_timeout)?;
+
+        // Secure setMemoryLimit function (admin only) - null restores the
+        // configured limit
+        let user_ctx_set_memory = user_context.clone();
+        let set_memory_limit = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  limit_bytes: Option<f64>|
+                  -> JsResult<bool> {
+                if !user_ctx_set_memory.has_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "setMemoryLimit",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
+                }
+
+                let ceiling = crate::js_engine::current_execution_limits().max_script_memory_bytes;
+                let limit_bytes = match limit_bytes {
+                    Some(bytes)
+                        if !bytes.is_finite()
+                            || bytes < MIN_SCRIPT_MEMORY_LIMIT_BYTES as f64
+                            || bytes.fract() != 0.0 =>
+                    {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "setMemoryLimit",
+                            "invalid_limit",
+                            &format!(
+                                "Memory limit must be a whole number of bytes, at least {}, or null",
+                                MIN_SCRIPT_MEMORY_LIMIT_BYTES
+                            ),
+                        ));
+                    }
+                    Some(bytes) if bytes > ceiling as f64 => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "setMemoryLimit",
+                            "invalid_limit",
+                            &format!("Memory limit must be at most {} bytes", ceiling),
+                        ));
+                    }
+                    Some(bytes) => Some(bytes as u64),
+                    None => None,
                 };
-                match repository::upsert_asset(asset) {
-                    Ok(_) => Ok(format!("Asset '{}' upserted successfully", uri)),
-                    Err(e) => Ok(format!("Error upserting asset: {}", e)),
+
+                repository::set_script_memory_limit(&script_name, limit_bytes).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "setMemoryLimit",
+                        "repository_error",
+                        &format!("{}", e),
+                    )
+                })?;
+
+                debug!(
+                    script_name = %script_name,
+                    user_id = ?user_ctx_set_memory.user_id,
+                    limit_bytes = ?limit_bytes,
+                    "Secure setMemoryLimit called"
+                );
+
+                Ok(true)
+            },
+        )?;
+        script_storage.set("setMemoryLimit", set_memory_limit)?;
+
+        // Secure getScriptCapabilities function - returns the script's
+        // capability manifest or null
+        let user_ctx_get_capabilities = user_context.clone();
+        let get_script_capabilities = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<Option<String>> {
+                if let Err(_e) = user_ctx_get_capabilities
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(None);
+                }
+
+                match crate::security::script_manifests::get(&script_name) {
+                    Ok(manifest) => Ok(serde_json::to_string(
+                        &crate::security::script_manifests::ScriptManifestView::from(manifest),
+                    )
+                    .ok()),
+                    Err(_) => Ok(None),
                 }
             },
         )?;
-        asset_storage.set("upsertAsset", upsert_asset)?;
+        script_storage.set("getScriptCapabilities", get_script_capabilities)?;
 
-        // Secure deleteAsset function
-        let user_ctx_delete_asset = user_context.clone();
-        let auditor_delete_asset = auditor.clone();
-        let script_uri_delete_asset = script_uri_remaining.clone();
-        let delete_asset = Function::new(
+        // Secure requestScriptCapabilities function - replaces the
+        // capabilities the script requests; new ones wait for an administrator
+        let user_ctx_request_capabilities = user_context.clone();
+        let request_script_capabilities = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  capabilities: Vec<String>|
+                  -> JsResult<String> {
+                if let Err(e) = user_ctx_request_capabilities
+                    .require_capability(&crate::security::Capability::WriteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                if let Err(message) =
+                    check_script_write_permission(&user_ctx_request_capabilities, &script_name)
+                {
+                    return Ok(message);
+                }
+
+                let capabilities =
+                    match crate::security::script_manifests::parse_capabilities(&capabilities) {
+                        Ok(capabilities) => capabilities,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let manifest =
+                    match crate::security::script_manifests::request(&script_name, capabilities) {
+                        Ok(manifest) => manifest,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+
+                debug!(
+                    script_name = %script_name,
+                    user_id = ?user_ctx_request_capabilities.user_id,
+                    requested = ?manifest.requested,
+                    "Secure requestScriptCapabilities called"
+                );
+
+                match serde_json::to_string(
+                    &crate::security::script_manifests::ScriptManifestView::from(manifest),
+                ) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("requestScriptCapabilities", request_script_capabilities)?;
+
+        // Secure deleteScript function
+        let user_ctx_delete = user_context.clone();
+        let auditor_delete = auditor.clone();
+        let delete_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  force: Opt<bool>|
+                  -> JsResult<bool> {
+                let force = force.0.unwrap_or(false);
+
                 // Check capability
-                if let Err(e) = user_ctx_delete_asset
-                    .require_capability(&crate::security::Capability::DeleteAssets)
+                if let Err(e) =
+                    user_ctx_delete.require_capability(&crate::security::Capability::DeleteScripts)
                 {
                     // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_delete_asset.clone();
-                    let user_id = user_ctx_delete_asset.user_id.clone();
+                    let auditor_clone = auditor_delete.clone();
+                    let user_id = user_ctx_delete.user_id.clone();
                     tokio::task::spawn(async move {
                         let _ = auditor_clone
                             .log_authz_failure(
                                 user_id,
-                                "asset".to_string(),
+                                "script".to_string(),
                                 "delete".to_string(),
-                                "DeleteAssets".to_string(),
+                                "DeleteScripts".to_string(),
                             )
                             .await;
                     });
-                    return Ok(format!("Error: {}", e));
+                    warn!(
+                        user_id = ?user_ctx_delete.user_id,
+                        script_name = %script_name,
+                        error = %e,
+                        "deleteScript capability check failed"
+                    );
+                    return Ok(false);
+                }
+
+                // Check ownership permission - admins can delete any script, others only owned scripts
+                // (collaborators may edit but not delete)
+                let is_admin =
+                    user_ctx_delete.has_capability(&crate::security::Capability::DeleteScripts);
+                let user_owns = if let Some(user_id) = &user_ctx_delete.user_id {
+                    repository::user_owns_script(&script_name, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !is_admin && !user_owns {
+                    warn!(
+                        user_id = ?user_ctx_delete.user_id,
+                        script_name = %script_name,
+                        "deleteScript ownership check failed - user is not admin and does not own script"
+                    );
+                    return Ok(false);
                 }
 
                 // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_delete_asset.clone();
-                let user_id = user_ctx_delete_asset.user_id.clone();
-                let uri_clone = uri.clone();
+                let auditor_clone = auditor_delete.clone();
+                let user_id = user_ctx_delete.user_id.clone();
+                let script_name_clone = script_name.clone();
                 tokio::task::spawn(async move {
                     let _ = auditor_clone
                         .log_event(
@@ -1180,61 +2817,272 @@ impl SecureGlobalContext {
                                 SecuritySeverity::High,
                                 user_id,
                             )
-                            .with_resource("asset".to_string())
+                            .with_resource("script".to_string())
                             .with_action("delete".to_string())
-                            .with_detail("uri", &uri_clone),
+                            .with_detail("script_name", &script_name_clone)
+                            .with_detail("force", force),
                         )
                         .await;
                 });
 
                 debug!(
-                    user_id = ?user_ctx_delete_asset.user_id,
-                    uri = %uri,
-                    "Secure deleteAsset called"
+                    user_id = ?user_ctx_delete.user_id,
+                    script_name = %script_name,
+                    "Secure deleteScript called"
                 );
 
-                match repository::delete_asset(&script_uri_delete_asset, &uri) {
-                    true => Ok(format!("Asset '{}' deleted successfully", uri)),
-                    false => Ok(format!("Asset '{}' not found", uri)),
+                // System scripts are only deleted with force
+                match repository::try_delete_script(&script_name, force) {
+                    Ok(existed) => Ok(existed),
+                    Err(e) => {
+                        warn!(
+                            user_id = ?user_ctx_delete.user_id,
+                            script_name = %script_name,
+                            error = %e,
+                            "deleteScript failed"
+                        );
+                        Ok(false)
+                    }
                 }
             },
         )?;
-        asset_storage.set("deleteAsset", delete_asset)?;
+        script_storage.set("deleteScript", delete_script)?;
 
-        // ====================================================================
-        // Privileged URI-specific asset methods (for cross-script management)
-        // ====================================================================
+        // Secure listTrashedScripts function (admin only) - returns JSON array
+        let user_ctx_list_trash = user_context.clone();
+        let list_trashed_scripts = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = user_ctx_list_trash
+                    .require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
 
-        // Secure listAssetsForUri function (privileged scripts only)
-        let user_ctx_list_uri = user_context.clone();
-        let list_assets_for_uri = Function::new(
+                match repository::list_trashed_scripts() {
+                    Ok(scripts) => match serde_json::to_string(&scripts) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error serializing trashed scripts: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error listing trashed scripts: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("listTrashedScripts", list_trashed_scripts)?;
+
+        // Secure restoreScript function (admin only, like deleteScript)
+        let user_ctx_restore = user_context.clone();
+        let auditor_restore = auditor.clone();
+        let restore_script = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
-                // Check capability OR script ownership OR admin status
-                let has_capability =
-                    user_ctx_list_uri.has_capability(&crate::security::Capability::ReadAssets);
-                let is_admin =
-                    user_ctx_list_uri.has_capability(&crate::security::Capability::DeleteScripts);
-                let owns_script = if let Some(user_id) = &user_ctx_list_uri.user_id {
-                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
-                } else {
-                    false
-                };
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_restore.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
 
-                if !has_capability && !owns_script && !is_admin {
+                if let Err(e) = repository::restore_script(&script_name) {
+                    return Ok(format!("Error restoring script: {}", e));
+                }
+
+                let auditor_clone = auditor_restore.clone();
+                let user_id = user_ctx_restore.user_id.clone();
+                let script_name_clone = script_name.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("script".to_string())
+                            .with_action("restore".to_string())
+                            .with_detail("script_name", &script_name_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_restore.user_id,
+                    script_name = %script_name,
+                    "Secure restoreScript called"
+                );
+
+                spawn_script_initialization(script_name.clone(), "restore");
+
+                Ok(format!("Script '{}' restored successfully", script_name))
+            },
+        )?;
+        script_storage.set("restoreScript", restore_script)?;
+
+        // Staging slots (blue/green deployment) - return the slot as JSON
+        let user_ctx_staging = user_context.clone();
+        let get_script_staging = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                Ok(run_staging_operation(
+                    &user_ctx_staging,
+                    &script_name,
+                    crate::staging::status(&script_name),
+                ))
+            },
+        )?;
+        script_storage.set("getScriptStaging", get_script_staging)?;
+
+        let user_ctx_stage = user_context.clone();
+        let stage_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  script_name: String,
+                  js_script: String|
+                  -> JsResult<String> {
+                if js_script.is_empty() {
+                    return Ok("Error: Script content cannot be empty".to_string());
+                }
+                if let Err(e) = crate::script_lint::validate(&script_name, &js_script) {
+                    return Ok(format!("Error: {}", e));
+                }
+                Ok(run_staging_operation(
+                    &user_ctx_stage,
+                    &script_name,
+                    crate::staging::stage(
+                        &script_name,
+                        &js_script,
+                        user_ctx_stage.user_id.as_deref(),
+                    ),
+                ))
+            },
+        )?;
+        script_storage.set("stageScript", stage_script)?;
+
+        let user_ctx_discard = user_context.clone();
+        let discard_staged_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                Ok(run_staging_operation(
+                    &user_ctx_discard,
+                    &script_name,
+                    crate::staging::discard(&script_name),
+                ))
+            },
+        )?;
+        script_storage.set("discardStagedScript", discard_staged_script)?;
+
+        let user_ctx_promote = user_context.clone();
+        let auditor_promote = auditor.clone();
+        let promote_staged_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                let result = run_staging_operation(
+                    &user_ctx_promote,
+                    &script_name,
+                    crate::staging::promote(&script_name),
+                );
+                if !result.starts_with("Error") {
+                    audit_script_swap(
+                        &auditor_promote,
+                        user_ctx_promote.user_id.clone(),
+                        &script_name,
+                        "promote",
+                    );
+                }
+                Ok(result)
+            },
+        )?;
+        script_storage.set("promoteStagedScript", promote_staged_script)?;
+
+        let user_ctx_rollback = user_context.clone();
+        let auditor_rollback = auditor.clone();
+        let rollback_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, script_name: String| -> JsResult<String> {
+                let result = run_staging_operation(
+                    &user_ctx_rollback,
+                    &script_name,
+                    crate::staging::rollback(&script_name),
+                );
+                if !result.starts_with("Error") {
+                    audit_script_swap(
+                        &auditor_rollback,
+                        user_ctx_rollback.user_id.clone(),
+                        &script_name,
+                        "rollback",
+                    );
+                }
+                Ok(result)
+            },
+        )?;
+        script_storage.set("rollbackScript", rollback_script)?;
+
+        // Secure purgeTrash function (admin only) - permanently removes trash
+        // entries older than the configured retention, returns JSON summary
+        let user_ctx_purge = user_context.clone();
+        let purge_trash = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_purge.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                match repository::purge_trash() {
+                    Ok(summary) => match serde_json::to_string(&summary) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error serializing purge summary: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error purging trash: {}", e)),
+                }
+            },
+        )?;
+        script_storage.set("purgeTrash", purge_trash)?;
+
+        // Set the scriptStorage object on the global scope
+        global.set("scriptStorage", script_storage)?;
+
+        Ok(())
+    }
+
+    /// Setup secure asset management functions
+    fn setup_asset_management_functions(
+        &self,
+        ctx: &rquickjs::Ctx<'_>,
+        script_uri: &str,
+    ) -> JsResult<()> {
+        let global = ctx.globals();
+        let user_context = self.user_context.clone();
+        let secure_ops = self.secure_ops.clone();
+        let auditor = self.auditor.clone();
+        let script_uri_owned = script_uri.to_string();
+        let script_uri_remaining = script_uri_owned.clone(); // Clone for remaining functions
+
+        // Create assetStorage object
+        let asset_storage = rquickjs::Object::new(ctx.clone())?;
+
+        // Secure listAssets function
+        let user_ctx_list = user_context.clone();
+        let script_uri_list = script_uri_owned.clone();
+        let list_assets = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check capability
+                if let Err(_e) =
+                    user_ctx_list.require_capability(&crate::security::Capability::ReadAssets)
+                {
                     // Return empty array JSON if no permission
                     return Ok("[]".to_string());
                 }
 
                 debug!(
-                    user_id = ?user_ctx_list_uri.user_id,
-                    uri = %uri,
-                    "Secure listAssetsForUri called"
+                    user_id = ?user_ctx_list.user_id,
+                    "Secure listAssets called"
                 );
 
-                let assets = repository::fetch_assets(&uri);
+                let assets = repository::fetch_assets(&script_uri_list);
 
-                // Build JSON array of asset metadata (matching listAssets pattern)
+                // Build JSON array of asset metadata (matching listScripts pattern)
                 let assets_json: Vec<serde_json::Value> = assets
                     .values()
                     .map(|asset| {
@@ -1243,6 +3091,7 @@ impl SecureGlobalContext {
                             "name": asset.name,
                             "size": asset.content.len(),
                             "mimetype": asset.mimetype,
+                            "headers": asset.headers,
                             "createdAt": asset.created_at
                                 .duration_since(std::time::UNIX_EPOCH)
                                 .unwrap_or_default()
@@ -1264,83 +3113,85 @@ impl SecureGlobalContext {
                 }
             },
         )?;
-        asset_storage.set("listAssetsForUri", list_assets_for_uri)?;
+        asset_storage.set("listAssets", list_assets)?;
 
-        // Secure fetchAssetForUri function (privileged scripts only)
-        let user_ctx_fetch_uri = user_context.clone();
-        let fetch_asset_for_uri = Function::new(
+        // Secure fetchAsset function
+        let user_ctx_fetch = user_context.clone();
+        let script_uri_fetch = script_uri_remaining.clone();
+        let fetch_asset = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, uri: String, asset_name: String| -> JsResult<String> {
-                // Check capability OR script ownership OR admin status
-                let has_capability =
-                    user_ctx_fetch_uri.has_capability(&crate::security::Capability::ReadAssets);
-                let is_admin =
-                    user_ctx_fetch_uri.has_capability(&crate::security::Capability::DeleteScripts);
-                let owns_script = if let Some(user_id) = &user_ctx_fetch_uri.user_id {
-                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
-                } else {
-                    false
-                };
-
-                if !has_capability && !owns_script && !is_admin {
-                    return Ok("Error: Access denied".to_string());
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                // Check capability
+                if let Err(e) =
+                    user_ctx_fetch.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
 
                 debug!(
-                    user_id = ?user_ctx_fetch_uri.user_id,
+                    user_id = ?user_ctx_fetch.user_id,
                     uri = %uri,
-                    asset_name = %asset_name,
-                    "Secure fetchAssetForUri called"
+                    "Secure fetchAsset called"
                 );
 
-                match repository::fetch_asset(&uri, &asset_name) {
+                match repository::fetch_asset(&script_uri_fetch, &uri) {
                     Some(asset) => {
                         // Convert bytes to base64 for safe JavaScript transfer
                         Ok(base64::engine::general_purpose::STANDARD.encode(asset.content))
                     }
-                    None => Ok(format!("Asset '{}' not found", asset_name)),
+                    None => Ok(format!("Asset '{}' not found", uri)),
                 }
             },
         )?;
-        asset_storage.set("fetchAssetForUri", fetch_asset_for_uri)?;
+        asset_storage.set("fetchAsset", fetch_asset)?;
 
-        // Secure upsertAssetForUri function (privileged scripts only)
-        let user_ctx_upsert_uri = user_context.clone();
-        let auditor_upsert_uri = auditor.clone();
-        let upsert_asset_for_uri = Function::new(
+        // Secure upsertAsset function
+        let user_ctx_upsert_asset = user_context.clone();
+        let _secure_ops_asset = secure_ops.clone();
+        let auditor_asset = auditor.clone();
+        let script_uri_asset = script_uri_owned.clone();
+        let upsert_asset = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
                   uri: String,
-                  asset_name: String,
                   mimetype: String,
-                  content_b64: String|
+                  content_b64: String,
+                  options: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
+                // The fourth argument is either the asset name (legacy) or an
+                // options object { name, headers }
+                let mut name = None;
+                let mut headers = None;
+                if let Some(value) = options.0.as_ref() {
+                    if let Some(object) = value.as_object() {
+                        name = object.get::<_, Option<String>>("name").ok().flatten();
+                        headers = match read_asset_headers_option(object) {
+                            Ok(headers) => headers,
+                            Err(e) => return Ok(format!("Invalid asset headers: {}", e)),
+                        };
+                    } else if let Some(string) = value.as_string() {
+                        name = string.to_string().ok();
+                    }
+                }
+
                 // Decode base64 content
                 let content = match base64::engine::general_purpose::STANDARD.decode(&content_b64) {
                     Ok(c) => c,
                     Err(e) => return Ok(format!("Error decoding base64 content: {}", e)),
                 };
 
-                // Check capability OR script ownership OR admin status
-                let has_capability =
-                    user_ctx_upsert_uri.has_capability(&crate::security::Capability::WriteAssets);
-                let is_admin =
-                    user_ctx_upsert_uri.has_capability(&crate::security::Capability::DeleteScripts);
-                let owns_script = if let Some(user_id) = &user_ctx_upsert_uri.user_id {
-                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
-                } else {
-                    false
-                };
-
-                if !has_capability && !owns_script && !is_admin {
-                    return Ok("Error: Access denied".to_string());
+                // Check capability
+                if let Err(e) = user_ctx_upsert_asset
+                    .require_capability(&crate::security::Capability::WriteAssets)
+                {
+                    return Ok(format!("Access denied: {}", e));
                 }
 
                 // Validate asset URI (inline validation since we can't call async)
-                if asset_name.is_empty() || asset_name.len() > 255 {
+                if uri.is_empty() || uri.len() > 255 {
                     return Ok("Invalid asset URI: must be 1-255 characters".to_string());
                 }
-                if asset_name.contains("..") || asset_name.contains('\\') {
+                if uri.contains("..") || uri.contains('\\') {
                     return Ok("Invalid asset URI: path traversal not allowed".to_string());
                 }
 
@@ -1350,10 +3201,10 @@ impl SecureGlobalContext {
                 }
 
                 // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_upsert_uri.clone();
-                let user_id = user_ctx_upsert_uri.user_id.clone();
+                let auditor_clone = auditor_asset.clone();
+                let user_id = user_ctx_upsert_asset.user_id.clone();
                 let uri_clone = uri.clone();
-                let asset_name_clone = asset_name.clone();
+                let script_uri_clone = script_uri_asset.clone();
                 let content_len = content.len();
                 let mimetype_clone = mimetype.clone();
                 tokio::task::spawn(async move {
@@ -1365,9 +3216,9 @@ impl SecureGlobalContext {
                                 user_id,
                             )
                             .with_resource("asset".to_string())
-                            .with_action("upsert_for_uri".to_string())
-                            .with_detail("uri", &asset_name_clone)
-                            .with_detail("script_uri", &uri_clone)
+                            .with_action("upsert".to_string())
+                            .with_detail("uri", &uri_clone)
+                            .with_detail("script_uri", &script_uri_clone)
                             .with_detail("content_size", content_len.to_string())
                             .with_detail("mimetype", &mimetype_clone),
                         )
@@ -1376,62 +3227,60 @@ impl SecureGlobalContext {
 
                 // Call repository directly (sync operation)
                 let now = std::time::SystemTime::now();
+                let headers = headers.unwrap_or_else(|| {
+                    repository::fetch_asset(&script_uri_owned, &uri)
+                        .map(|existing| existing.headers)
+                        .unwrap_or_default()
+                });
                 let asset = repository::Asset {
-                    uri: asset_name.clone(),
-                    name: Some(asset_name.clone()),
+                    uri: uri.clone(),
+                    name: name.or_else(|| Some(uri.clone())),
                     mimetype,
                     content,
                     created_at: now,
                     updated_at: now,
-                    script_uri: uri.clone(),
+                    script_uri: script_uri_owned.clone(),
+                    headers,
                 };
                 match repository::upsert_asset(asset) {
-                    Ok(_) => Ok(format!("Asset '{}' upserted successfully", asset_name)),
+                    Ok(_) => Ok(format!("Asset '{}' upserted successfully", uri)),
                     Err(e) => Ok(format!("Error upserting asset: {}", e)),
                 }
             },
         )?;
-        asset_storage.set("upsertAssetForUri", upsert_asset_for_uri)?;
+        asset_storage.set("upsertAsset", upsert_asset)?;
 
-        // Secure deleteAssetForUri function (privileged scripts only)
-        let user_ctx_delete_uri = user_context.clone();
-        let auditor_delete_uri = auditor.clone();
-        let delete_asset_for_uri = Function::new(
+        // Secure deleteAsset function
+        let user_ctx_delete_asset = user_context.clone();
+        let auditor_delete_asset = auditor.clone();
+        let script_uri_delete_asset = script_uri_remaining.clone();
+        let delete_asset = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, uri: String, asset_name: String| -> JsResult<String> {
-                // Check capability OR script ownership OR admin status
-                let has_capability =
-                    user_ctx_delete_uri.has_capability(&crate::security::Capability::DeleteAssets);
-                let is_admin =
-                    user_ctx_delete_uri.has_capability(&crate::security::Capability::DeleteScripts);
-                let owns_script = if let Some(user_id) = &user_ctx_delete_uri.user_id {
-                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
-                } else {
-                    false
-                };
-
-                if !has_capability && !owns_script && !is_admin {
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                // Check capability
+                if let Err(e) = user_ctx_delete_asset
+                    .require_capability(&crate::security::Capability::DeleteAssets)
+                {
                     // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_delete_uri.clone();
-                    let user_id = user_ctx_delete_uri.user_id.clone();
+                    let auditor_clone = auditor_delete_asset.clone();
+                    let user_id = user_ctx_delete_asset.user_id.clone();
                     tokio::task::spawn(async move {
                         let _ = auditor_clone
                             .log_authz_failure(
                                 user_id,
                                 "asset".to_string(),
-                                "delete_for_uri".to_string(),
+                                "delete".to_string(),
                                 "DeleteAssets".to_string(),
                             )
                             .await;
                     });
-                    return Ok("Error: Access denied".to_string());
+                    return Ok(format!("Error: {}", e));
                 }
 
                 // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_delete_uri.clone();
-                let user_id = user_ctx_delete_uri.user_id.clone();
+                let auditor_clone = auditor_delete_asset.clone();
+                let user_id = user_ctx_delete_asset.user_id.clone();
                 let uri_clone = uri.clone();
-                let asset_name_clone = asset_name.clone();
                 tokio::task::spawn(async move {
                     let _ = auditor_clone
                         .log_event(
@@ -1441,2586 +3290,4850 @@ impl SecureGlobalContext {
                                 user_id,
                             )
                             .with_resource("asset".to_string())
-                            .with_action("delete_for_uri".to_string())
-                            .with_detail("uri", &asset_name_clone)
-                            .with_detail("script_uri", &uri_clone),
+                            .with_action("delete".to_string())
+                            .with_detail("uri", &uri_clone),
                         )
                         .await;
                 });
 
                 debug!(
-                    user_id = ?user_ctx_delete_uri.user_id,
+                    user_id = ?user_ctx_delete_asset.user_id,
                     uri = %uri,
-                    asset_name = %asset_name,
-                    "Secure deleteAssetForUri called"
+                    "Secure deleteAsset called"
                 );
 
-                match repository::delete_asset(&uri, &asset_name) {
-                    true => Ok(format!("Asset '{}' deleted successfully", asset_name)),
-                    false => Ok(format!("Asset '{}' not found", asset_name)),
+                match repository::delete_asset(&script_uri_delete_asset, &uri) {
+                    true => Ok(format!("Asset '{}' deleted successfully", uri)),
+                    false => Ok(format!("Asset '{}' not found", uri)),
                 }
             },
         )?;
-        asset_storage.set("deleteAssetForUri", delete_asset_for_uri)?;
-
-        // Set the assetStorage object on the global scope
-        global.set("assetStorage", asset_storage)?;
-        Ok(())
-    }
+        asset_storage.set("deleteAsset", delete_asset)?;
 
-    /// Setup secret storage functions
-    ///
-    /// Exposes a JavaScript API for per-user secret management scoped to the current script.
-    /// All methods require an authenticated user; unauthenticated calls return errors or false.
-    /// Secrets are stored in the user_secrets table keyed by (script_uri, user_id, key).
-    ///
-    /// - secretStorage.exists(key): boolean
-    /// - secretStorage.setSecret(key, value): string
-    /// - secretStorage.removeSecret(key): boolean
-    /// - secretStorage.clear(): string
-    fn setup_secrets_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let script_uri_owned = script_uri.to_string();
+        // Secure listTrashedAssets function - returns JSON array of this
+        // script's deleted assets
+        let user_ctx_list_trash_assets = user_context.clone();
+        let script_uri_list_trash = script_uri_remaining.clone();
+        let list_trashed_assets = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = user_ctx_list_trash_assets
+                    .require_capability(&crate::security::Capability::DeleteAssets)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
 
-        let secret_storage_obj = rquickjs::Object::new(ctx.clone())?;
+                match repository::list_trashed_assets(&script_uri_list_trash) {
+                    Ok(assets) => match serde_json::to_string(&assets) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error serializing trashed assets: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error listing trashed assets: {}", e)),
+                }
+            },
+        )?;
+        asset_storage.set("listTrashedAssets", list_trashed_assets)?;
 
-        // secretStorage.exists(key) - Check if secret exists in user_secrets or script_secrets
-        let script_uri_exists = script_uri_owned.clone();
-        let exists_fn = Function::new(
+        // Secure restoreAsset function
+        let user_ctx_restore_asset = user_context.clone();
+        let script_uri_restore_asset = script_uri_remaining.clone();
+        let restore_asset = Function::new(
             ctx.clone(),
-            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
-                let globals = ctx.globals();
-                // Check user_secrets first (if authenticated)
-                if let Some(user_id) = get_auth_user_id(&globals)
-                    && crate::repository::get_user_secret_item(&script_uri_exists, &user_id, &key)
-                        .is_some()
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                if let Err(e) = user_ctx_restore_asset
+                    .require_capability(&crate::security::Capability::DeleteAssets)
                 {
-                    return Ok(true);
+                    return Ok(format!("Error: {}", e));
+                }
+
+                debug!(
+                    user_id = ?user_ctx_restore_asset.user_id,
+                    uri = %uri,
+                    "Secure restoreAsset called"
+                );
+
+                match repository::restore_asset(&script_uri_restore_asset, &uri) {
+                    Ok(()) => Ok(format!("Asset '{}' restored successfully", uri)),
+                    Err(e) => Ok(format!("Error restoring asset: {}", e)),
                 }
-                // Fall back to script_secrets
-                Ok(crate::repository::get_script_secret_item(&script_uri_exists, &key).is_some())
             },
         )?;
-        secret_storage_obj.set("exists", exists_fn)?;
+        asset_storage.set("restoreAsset", restore_asset)?;
 
-        // secretStorage.setSecret(key, value) - Store a secret for current user
-        let script_uri_set = script_uri_owned.clone();
-        let set_secret_fn = Function::new(
+        // Secure signUrl function - a time-limited URL of a private asset
+        let user_ctx_sign_url = user_context.clone();
+        let script_uri_sign_url = script_uri_remaining.clone();
+        let sign_url = Function::new(
             ctx.clone(),
-            move |ctx: rquickjs::Ctx<'_>, key: String, value: String| -> JsResult<String> {
-                let globals = ctx.globals();
-                let user_id = match get_auth_user_id(&globals) {
-                    Some(id) => id,
-                    None => {
-                        return Ok(
-                            "Error: Secret storage requires authentication. Please log in."
-                                .to_string(),
-                        );
-                    }
-                };
-                if key.trim().is_empty() {
-                    return Ok("Error: Key cannot be empty".to_string());
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  ttl_seconds: Opt<f64>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_sign_url.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
-                if value.len() > 1_000_000 {
-                    return Ok("Error: Value too large (>1MB)".to_string());
+                let ttl_seconds = ttl_seconds.0.unwrap_or(3600.0);
+                if !ttl_seconds.is_finite() || ttl_seconds < 1.0 {
+                    return Ok("Error: ttlSeconds must be a positive number".to_string());
                 }
-                match crate::repository::set_user_secret_item(
-                    &script_uri_set,
-                    &user_id,
-                    &key,
-                    &value,
-                ) {
-                    Ok(()) => Ok("Secret set successfully".to_string()),
-                    Err(e) => Ok(format!("Error setting secret: {}", e)),
+
+                match crate::signed_urls::sign_url(&script_uri_sign_url, &name, ttl_seconds as u64)
+                {
+                    Ok(url) => Ok(url),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
             },
         )?;
-        secret_storage_obj.set("setSecret", set_secret_fn)?;
+        asset_storage.set("signUrl", sign_url)?;
 
-        // secretStorage.removeSecret(key) - Remove a single secret for current user
-        let script_uri_remove = script_uri_owned.clone();
-        let remove_secret_fn = Function::new(
+        // ====================================================================
+        // Privileged URI-specific asset methods (for cross-script management)
+        // ====================================================================
+
+        // Secure listAssetsForUri function (privileged scripts only)
+        let user_ctx_list_uri = user_context.clone();
+        let list_assets_for_uri = Function::new(
             ctx.clone(),
-            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
-                let globals = ctx.globals();
-                let user_id = match get_auth_user_id(&globals) {
-                    Some(id) => id,
-                    None => return Ok(false),
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                // Check capability OR script ownership OR admin status
+                let has_capability =
+                    user_ctx_list_uri.has_capability(&crate::security::Capability::ReadAssets);
+                let is_admin =
+                    user_ctx_list_uri.has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_list_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
                 };
-                Ok(crate::repository::remove_user_secret_item(
-                    &script_uri_remove,
-                    &user_id,
-                    &key,
-                ))
+
+                if !has_capability && !owns_script && !is_admin {
+                    // Return empty array JSON if no permission
+                    return Ok("[]".to_string());
+                }
+
+                debug!(
+                    user_id = ?user_ctx_list_uri.user_id,
+                    uri = %uri,
+                    "Secure listAssetsForUri called"
+                );
+
+                let assets = repository::fetch_assets(&uri);
+
+                // Build JSON array of asset metadata (matching listAssets pattern)
+                let assets_json: Vec<serde_json::Value> = assets
+                    .values()
+                    .map(|asset| {
+                        serde_json::json!({
+                            "uri": asset.uri,
+                            "name": asset.name,
+                            "size": asset.content.len(),
+                            "mimetype": asset.mimetype,
+                            "headers": asset.headers,
+                            "createdAt": asset.created_at
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_millis() as f64,
+                            "updatedAt": asset.updated_at
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_millis() as f64,
+                        })
+                    })
+                    .collect();
+
+                match serde_json::to_string(&assets_json) {
+                    Ok(json) => Ok(json),
+                    Err(e) => {
+                        error!("Failed to serialize assets to JSON: {}", e);
+                        Ok("[]".to_string())
+                    }
+                }
             },
         )?;
-        secret_storage_obj.set("removeSecret", remove_secret_fn)?;
+        asset_storage.set("listAssetsForUri", list_assets_for_uri)?;
 
-        // secretStorage.clear() - Clear all secrets for current user in this script
-        let script_uri_clear = script_uri_owned.clone();
-        let clear_fn = Function::new(
+        // Secure fetchAssetForUri function (privileged scripts only)
+        let user_ctx_fetch_uri = user_context.clone();
+        let fetch_asset_for_uri = Function::new(
             ctx.clone(),
-            move |ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                let globals = ctx.globals();
-                let user_id = match get_auth_user_id(&globals) {
-                    Some(id) => id,
-                    None => {
-                        return Ok(
-                            "Error: Secret storage requires authentication. Please log in."
-                                .to_string(),
-                        );
-                    }
+            move |_ctx: rquickjs::Ctx<'_>, uri: String, asset_name: String| -> JsResult<String> {
+                // Check capability OR script ownership OR admin status
+                let has_capability =
+                    user_ctx_fetch_uri.has_capability(&crate::security::Capability::ReadAssets);
+                let is_admin =
+                    user_ctx_fetch_uri.has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_fetch_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
                 };
-                match crate::repository::clear_user_secrets(&script_uri_clear, &user_id) {
-                    Ok(()) => Ok("Secrets cleared successfully".to_string()),
-                    Err(e) => Ok(format!("Error clearing secrets: {}", e)),
+
+                if !has_capability && !owns_script && !is_admin {
+                    return Ok("Error: Access denied".to_string());
                 }
-            },
-        )?;
-        secret_storage_obj.set("clear", clear_fn)?;
 
-        // ====================================================================
-        // Privileged URI-specific secret methods (for cross-script management)
-        // Only attached when the calling script itself is privileged.
-        // The user's admin role does NOT grant access — the script must be privileged.
-        // ====================================================================
-        let setup_script_privileged =
-            crate::repository::is_script_privileged(&script_uri_owned).unwrap_or(false);
-
-        if setup_script_privileged {
-            // secretStorage.listForUri(scriptUri) - List keys for any script (privileged)
-            let script_uri_list_uri = script_uri_owned.clone();
-            let list_for_uri_fn = Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>, target_uri: String| -> JsResult<Vec<String>> {
-                    match crate::repository::is_script_privileged(&script_uri_list_uri) {
-                        Ok(true) => {}
-                        Ok(false) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.listForUri",
-                                "permission_denied",
-                                &format!(
-                                    "Script '{}' is not privileged to access secretStorage.listForUri",
-                                    script_uri_list_uri
-                                ),
-                            ));
-                        }
-                        Err(e) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.listForUri",
-                                "privilege_lookup_failed",
-                                &format!(
-                                    "Unable to verify privileges for '{}': {}",
-                                    script_uri_list_uri, e
-                                ),
-                            ));
-                        }
-                    }
-                    Ok(crate::repository::list_script_secrets(&target_uri).unwrap_or_default())
-                },
-            )?;
-            secret_storage_obj.set("listForUri", list_for_uri_fn)?;
-
-            // secretStorage.setSecretForUri(scriptUri, key, value) - Store for any script (privileged)
-            let script_uri_set_uri = script_uri_owned.clone();
-            let set_for_uri_fn = Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>,
-                      target_uri: String,
-                      key: String,
-                      value: String|
-                      -> JsResult<String> {
-                    match crate::repository::is_script_privileged(&script_uri_set_uri) {
-                        Ok(true) => {}
-                        Ok(false) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.setSecretForUri",
-                                "permission_denied",
-                                &format!(
-                                    "Script '{}' is not privileged to access secretStorage.setSecretForUri",
-                                    script_uri_set_uri
-                                ),
-                            ));
-                        }
-                        Err(e) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.setSecretForUri",
-                                "privilege_lookup_failed",
-                                &format!(
-                                    "Unable to verify privileges for '{}': {}",
-                                    script_uri_set_uri, e
-                                ),
-                            ));
-                        }
-                    }
-                    if key.trim().is_empty() {
-                        return Ok("Error: Key cannot be empty".to_string());
-                    }
-                    if value.len() > 1_000_000 {
-                        return Ok("Error: Value too large (>1MB)".to_string());
-                    }
-                    match crate::repository::set_script_secret_item(&target_uri, &key, &value) {
-                        Ok(()) => Ok("Secret set successfully".to_string()),
-                        Err(e) => Ok(format!("Error setting secret: {}", e)),
-                    }
-                },
-            )?;
-            secret_storage_obj.set("setSecretForUri", set_for_uri_fn)?;
+                debug!(
+                    user_id = ?user_ctx_fetch_uri.user_id,
+                    uri = %uri,
+                    asset_name = %asset_name,
+                    "Secure fetchAssetForUri called"
+                );
 
-            // secretStorage.removeSecretForUri(scriptUri, key) - Remove for any script (privileged)
-            let script_uri_remove_uri = script_uri_owned.clone();
-            let remove_for_uri_fn = Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>, target_uri: String, key: String| -> JsResult<bool> {
-                    match crate::repository::is_script_privileged(&script_uri_remove_uri) {
-                        Ok(true) => {}
-                        Ok(false) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.removeSecretForUri",
-                                "permission_denied",
-                                &format!(
-                                    "Script '{}' is not privileged to access secretStorage.removeSecretForUri",
-                                    script_uri_remove_uri
-                                ),
-                            ));
-                        }
-                        Err(e) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.removeSecretForUri",
-                                "privilege_lookup_failed",
-                                &format!(
-                                    "Unable to verify privileges for '{}': {}",
-                                    script_uri_remove_uri, e
-                                ),
-                            ));
-                        }
+                match repository::fetch_asset(&uri, &asset_name) {
+                    Some(asset) => {
+                        // Convert bytes to base64 for safe JavaScript transfer
+                        Ok(base64::engine::general_purpose::STANDARD.encode(asset.content))
                     }
-                    Ok(crate::repository::remove_script_secret_item(
-                        &target_uri,
-                        &key,
-                    ))
-                },
-            )?;
-            secret_storage_obj.set("removeSecretForUri", remove_for_uri_fn)?;
+                    None => Ok(format!("Asset '{}' not found", asset_name)),
+                }
+            },
+        )?;
+        asset_storage.set("fetchAssetForUri", fetch_asset_for_uri)?;
 
-            // secretStorage.clearForUri(scriptUri) - Clear all for any script (privileged)
-            let script_uri_clear_uri = script_uri_owned.clone();
-            let clear_for_uri_fn = Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>, target_uri: String| -> JsResult<String> {
-                    match crate::repository::is_script_privileged(&script_uri_clear_uri) {
-                        Ok(true) => {}
-                        Ok(false) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.clearForUri",
-                                "permission_denied",
-                                &format!(
-                                    "Script '{}' is not privileged to access secretStorage.clearForUri",
-                                    script_uri_clear_uri
-                                ),
-                            ));
-                        }
-                        Err(e) => {
-                            return Err(rquickjs::Error::new_from_js_message(
-                                "secretStorage.clearForUri",
-                                "privilege_lookup_failed",
-                                &format!(
-                                    "Unable to verify privileges for '{}': {}",
-                                    script_uri_clear_uri, e
-                                ),
-                            ));
-                        }
-                    }
-                    match crate::repository::clear_script_secrets(&target_uri) {
-                        Ok(()) => Ok("Secrets cleared successfully".to_string()),
-                        Err(e) => Ok(format!("Error clearing secrets: {}", e)),
-                    }
-                },
-            )?;
-            secret_storage_obj.set("clearForUri", clear_for_uri_fn)?;
-        } // end if setup_script_privileged
+        // Secure upsertAssetForUri function (privileged scripts only)
+        let user_ctx_upsert_uri = user_context.clone();
+        let auditor_upsert_uri = auditor.clone();
+        let upsert_asset_for_uri = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  uri: String,
+                  asset_name: String,
+                  mimetype: String,
+                  content_b64: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                let headers = match options.0.as_ref().map(read_asset_headers_option) {
+                    Some(Ok(headers)) => headers,
+                    Some(Err(e)) => return Ok(format!("Invalid asset headers: {}", e)),
+                    None => None,
+                };
 
-        global.set("secretStorage", secret_storage_obj)?;
+                // Decode base64 content
+                let content = match base64::engine::general_purpose::STANDARD.decode(&content_b64) {
+                    Ok(c) => c,
+                    Err(e) => return Ok(format!("Error decoding base64 content: {}", e)),
+                };
 
-        debug!(
-            "secretStorage JavaScript API initialized for script: {}",
-            script_uri
-        );
+                // Check capability OR script ownership OR admin status
+                let has_capability =
+                    user_ctx_upsert_uri.has_capability(&crate::security::Capability::WriteAssets);
+                let is_admin =
+                    user_ctx_upsert_uri.has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_upsert_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
 
-        Ok(())
-    }
+                if !has_capability && !owns_script && !is_admin {
+                    return Ok("Error: Access denied".to_string());
+                }
 
-    /// Setup secure GraphQL functions  
-    fn setup_graphql_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let user_context = self.user_context.clone();
-        let secure_ops = self.secure_ops.clone();
-        let auditor = self.auditor.clone();
-        let script_uri_owned = script_uri.to_string();
+                // Validate asset URI (inline validation since we can't call async)
+                if asset_name.is_empty() || asset_name.len() > 255 {
+                    return Ok("Invalid asset URI: must be 1-255 characters".to_string());
+                }
+                if asset_name.contains("..") || asset_name.contains('\\') {
+                    return Ok("Invalid asset URI: path traversal not allowed".to_string());
+                }
 
-        // Secure registerGraphQLQuery function
-        let user_ctx_query = user_context.clone();
-        let _secure_ops_query = secure_ops.clone();
-        let auditor_query = auditor.clone();
-        let script_uri_query = script_uri_owned.clone();
-        let config_query = self.config.clone();
-        let register_graphql_query = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  name: String,
-                  sdl: String,
-                  resolver_function: String,
-                  visibility: String|
-                  -> JsResult<String> {
-                // If GraphQL registration is disabled, return success without doing anything
-                tracing::info!(
-                    "registerGraphQLQuery called: name={}, visibility={}, enable_graphql_registration={}",
-                    name,
-                    visibility,
-                    config_query.enable_graphql_registration
-                );
-                if !config_query.enable_graphql_registration {
-                    tracing::info!(
-                        "GraphQL registration disabled, skipping query registration for: {}",
-                        name
-                    );
-                    return Ok(format!(
-                        "GraphQL query '{}' registration skipped (disabled)",
-                        name
-                    ));
+                // Validate content size (10MB limit)
+                if content.len() > 10 * 1024 * 1024 {
+                    return Ok("Asset too large (max 10MB)".to_string());
                 }
 
-                // Check capability
-                if let Err(e) =
-                    user_ctx_query.require_capability(&crate::security::Capability::ManageGraphQL)
-                {
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_upsert_uri.clone();
+                let user_id = user_ctx_upsert_uri.user_id.clone();
+                let uri_clone = uri.clone();
+                let asset_name_clone = asset_name.clone();
+                let content_len = content.len();
+                let mimetype_clone = mimetype.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("asset".to_string())
+                            .with_action("upsert_for_uri".to_string())
+                            .with_detail("uri", &asset_name_clone)
+                            .with_detail("script_uri", &uri_clone)
+                            .with_detail("content_size", content_len.to_string())
+                            .with_detail("mimetype", &mimetype_clone),
+                        )
+                        .await;
+                });
+
+                // Call repository directly (sync operation)
+                let now = std::time::SystemTime::now();
+                let headers = headers.unwrap_or_else(|| {
+                    repository::fetch_asset(&uri, &asset_name)
+                        .map(|existing| existing.headers)
+                        .unwrap_or_default()
+                });
+                let asset = repository::Asset {
+                    uri: asset_name.clone(),
+                    name: Some(asset_name.clone()),
+                    mimetype,
+                    content,
+                    created_at: now,
+                    updated_at: now,
+                    script_uri: uri.clone(),
+                    headers,
+                };
+                match repository::upsert_asset(asset) {
+                    Ok(_) => Ok(format!("Asset '{}' upserted successfully", asset_name)),
+                    Err(e) => Ok(format!("Error upserting asset: {}", e)),
+                }
+            },
+        )?;
+        asset_storage.set("upsertAssetForUri", upsert_asset_for_uri)?;
+
+        // Secure deleteAssetForUri function (privileged scripts only)
+        let user_ctx_delete_uri = user_context.clone();
+        let auditor_delete_uri = auditor.clone();
+        let delete_asset_for_uri = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, uri: String, asset_name: String| -> JsResult<String> {
+                // Check capability OR script ownership OR admin status
+                let has_capability =
+                    user_ctx_delete_uri.has_capability(&crate::security::Capability::DeleteAssets);
+                let is_admin =
+                    user_ctx_delete_uri.has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_delete_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !has_capability && !owns_script && !is_admin {
                     // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_query.clone();
-                    let user_id = user_ctx_query.user_id.clone();
+                    let auditor_clone = auditor_delete_uri.clone();
+                    let user_id = user_ctx_delete_uri.user_id.clone();
                     tokio::task::spawn(async move {
                         let _ = auditor_clone
                             .log_authz_failure(
                                 user_id,
-                                "graphql".to_string(),
-                                "register_query".to_string(),
-                                "ManageGraphQL".to_string(),
+                                "asset".to_string(),
+                                "delete_for_uri".to_string(),
+                                "DeleteAssets".to_string(),
                             )
                             .await;
                     });
-                    return Ok(format!("Error: {}", e));
-                }
-
-                // Validate GraphQL schema inline (sync validation)
-                // Basic SDL validation
-                if sdl.is_empty() || sdl.len() > 100_000 {
-                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
-                }
-                if name.is_empty() || name.len() > 100 {
-                    return Ok(
-                        "Invalid query name: must be between 1 and 100 characters".to_string()
-                    );
-                }
-                // Check for dangerous patterns
-                if sdl.contains("__proto__") || sdl.contains("constructor") {
-                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
+                    return Ok("Error: Access denied".to_string());
                 }
 
                 // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_query.clone();
-                let user_id = user_ctx_query.user_id.clone();
-                let name_clone = name.clone();
-                let script_uri_clone = script_uri_query.clone();
-                let sdl_len = sdl.len();
-                let visibility_clone = visibility.clone();
+                let auditor_clone = auditor_delete_uri.clone();
+                let user_id = user_ctx_delete_uri.user_id.clone();
+                let uri_clone = uri.clone();
+                let asset_name_clone = asset_name.clone();
                 tokio::task::spawn(async move {
                     let _ = auditor_clone
                         .log_event(
                             crate::security::SecurityEvent::new(
                                 SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Medium,
+                                SecuritySeverity::High,
                                 user_id,
                             )
-                            .with_resource("graphql".to_string())
-                            .with_action("register_query".to_string())
-                            .with_detail("query_name", &name_clone)
-                            .with_detail("script_uri", &script_uri_clone)
-                            .with_detail("sdl_length", sdl_len.to_string())
-                            .with_detail("visibility", &visibility_clone),
+                            .with_resource("asset".to_string())
+                            .with_action("delete_for_uri".to_string())
+                            .with_detail("uri", &asset_name_clone)
+                            .with_detail("script_uri", &uri_clone),
                         )
                         .await;
                 });
 
                 debug!(
-                    user_id = ?user_ctx_query.user_id,
-                    name = %name,
-                    sdl_len = sdl.len(),
-                    visibility = %visibility,
-                    "Secure registerGraphQLQuery called"
+                    user_id = ?user_ctx_delete_uri.user_id,
+                    uri = %uri,
+                    asset_name = %asset_name,
+                    "Secure deleteAssetForUri called"
                 );
 
-                // Actually register the GraphQL query
-                match crate::graphql::register_graphql_query(
-                    name.clone(),
-                    sdl.clone(),
-                    resolver_function.clone(),
-                    script_uri_query.clone(),
-                    visibility,
-                ) {
-                    Ok(()) => Ok(format!("GraphQL query '{}' registered successfully", name)),
-                    Err(e) => Ok(format!("Error registering GraphQL query '{}': {}", name, e)),
+                match repository::delete_asset(&uri, &asset_name) {
+                    true => Ok(format!("Asset '{}' deleted successfully", asset_name)),
+                    false => Ok(format!("Asset '{}' not found", asset_name)),
                 }
             },
         )?;
+        asset_storage.set("deleteAssetForUri", delete_asset_for_uri)?;
 
-        // Secure registerGraphQLMutation function
-        let user_ctx_mutation = user_context.clone();
-        let _secure_ops_mutation = secure_ops.clone();
-        let auditor_mutation = auditor.clone();
-        let script_uri_mutation = script_uri_owned.clone();
-        let config_mutation = self.config.clone();
-        let register_graphql_mutation = Function::new(
+        // Secure listTrashedAssetsForUri function (privileged scripts only)
+        let user_ctx_list_trash_uri = user_context.clone();
+        let list_trashed_assets_for_uri = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  name: String,
-                  sdl: String,
-                  resolver_function: String,
-                  visibility: String|
-                  -> JsResult<String> {
-                // If GraphQL registration is disabled, return success without doing anything
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                // Check capability OR script ownership OR admin status
+                let has_capability = user_ctx_list_trash_uri
+                    .has_capability(&crate::security::Capability::DeleteAssets);
+                let is_admin = user_ctx_list_trash_uri
+                    .has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_list_trash_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !has_capability && !owns_script && !is_admin {
+                    return Ok("Error: Access denied".to_string());
+                }
+
+                match repository::list_trashed_assets(&uri) {
+                    Ok(assets) => match serde_json::to_string(&assets) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error serializing trashed assets: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error listing trashed assets: {}", e)),
+                }
+            },
+        )?;
+        asset_storage.set("listTrashedAssetsForUri", list_trashed_assets_for_uri)?;
+
+        // Secure restoreAssetForUri function (privileged scripts only)
+        let user_ctx_restore_uri = user_context.clone();
+        let restore_asset_for_uri = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, uri: String, asset_name: String| -> JsResult<String> {
+                // Check capability OR script ownership OR admin status
+                let has_capability =
+                    user_ctx_restore_uri.has_capability(&crate::security::Capability::DeleteAssets);
+                let is_admin = user_ctx_restore_uri
+                    .has_capability(&crate::security::Capability::DeleteScripts);
+                let owns_script = if let Some(user_id) = &user_ctx_restore_uri.user_id {
+                    repository::user_owns_script(&uri, user_id).unwrap_or(false)
+                } else {
+                    false
+                };
+
+                if !has_capability && !owns_script && !is_admin {
+                    return Ok("Error: Access denied".to_string());
+                }
+
                 debug!(
-                    "registerGraphQLMutation called: name={}, visibility={}, enable_graphql_registration={}",
-                    name, visibility, config_mutation.enable_graphql_registration
+                    user_id = ?user_ctx_restore_uri.user_id,
+                    uri = %uri,
+                    asset_name = %asset_name,
+                    "Secure restoreAssetForUri called"
                 );
-                if !config_mutation.enable_graphql_registration {
-                    debug!(
-                        "GraphQL registration disabled, skipping mutation registration for: {}",
-                        name
-                    );
-                    return Ok(format!(
-                        "GraphQL mutation '{}' registration skipped (disabled)",
-                        name
-                    ));
+
+                match repository::restore_asset(&uri, &asset_name) {
+                    Ok(()) => Ok(format!("Asset '{}' restored successfully", asset_name)),
+                    Err(e) => Ok(format!("Error restoring asset: {}", e)),
                 }
+            },
+        )?;
+        asset_storage.set("restoreAssetForUri", restore_asset_for_uri)?;
 
-                // Check capability
-                if let Err(e) = user_ctx_mutation
-                    .require_capability(&crate::security::Capability::ManageGraphQL)
+        // Set the assetStorage object on the global scope
+        global.set("assetStorage", asset_storage)?;
+        Ok(())
+    }
+
+    /// Setup secret storage functions
+    ///
+    /// Exposes a JavaScript API for per-user secret management scoped to the current script.
+    /// All methods require an authenticated user; unauthenticated calls return errors or false.
+    /// Secrets are stored in the user_secrets table keyed by (script_uri, user_id, key).
+    ///
+    /// - secretStorage.exists(key): boolean
+    /// - secretStorage.setSecret(key, value): string
+    /// - secretStorage.removeSecret(key): boolean
+    /// - secretStorage.clear(): string
+    fn setup_secrets_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let script_uri_owned = script_uri.to_string();
+
+        let secret_storage_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // secretStorage.exists(key) - Check if secret exists in user_secrets or script_secrets
+        let script_uri_exists = script_uri_owned.clone();
+        let exists_fn = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
+                let globals = ctx.globals();
+                // Check user_secrets first (if authenticated)
+                if let Some(user_id) = get_auth_user_id(&globals)
+                    && crate::repository::get_user_secret_item(&script_uri_exists, &user_id, &key)
+                        .is_some()
                 {
-                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_mutation.clone();
-                    let user_id = user_ctx_mutation.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "graphql".to_string(),
-                                "register_mutation".to_string(),
-                                "ManageGraphQL".to_string(),
-                            )
-                            .await;
-                    });
-                    return Ok(format!("Error: {}", e));
+                    return Ok(true);
                 }
+                // Fall back to script_secrets
+                Ok(crate::repository::get_script_secret_item(&script_uri_exists, &key).is_some())
+            },
+        )?;
+        secret_storage_obj.set("exists", exists_fn)?;
 
-                // Validate GraphQL schema inline (sync validation)
-                if sdl.is_empty() || sdl.len() > 100_000 {
-                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
-                }
-                if name.is_empty() || name.len() > 100 {
-                    return Ok(
-                        "Invalid mutation name: must be between 1 and 100 characters".to_string(),
-                    );
+        // secretStorage.setSecret(key, value) - Store a secret for current user
+        let script_uri_set = script_uri_owned.clone();
+        let set_secret_fn = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>, key: String, value: String| -> JsResult<String> {
+                let globals = ctx.globals();
+                let user_id = match get_auth_user_id(&globals) {
+                    Some(id) => id,
+                    None => {
+                        return Ok(
+                            "Error: Secret storage requires authentication. Please log in."
+                                .to_string(),
+                        );
+                    }
+                };
+                if key.trim().is_empty() {
+                    return Ok("Error: Key cannot be empty".to_string());
                 }
-                if sdl.contains("__proto__") || sdl.contains("constructor") {
-                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
+                if value.len() > 1_000_000 {
+                    return Ok("Error: Value too large (>1MB)".to_string());
+                }
+                match crate::repository::set_user_secret_item(
+                    &script_uri_set,
+                    &user_id,
+                    &key,
+                    &value,
+                ) {
+                    Ok(()) => Ok("Secret set successfully".to_string()),
+                    Err(e) => Ok(format!("Error setting secret: {}", e)),
+                }
+            },
+        )?;
+        secret_storage_obj.set("setSecret", set_secret_fn)?;
+
+        // secretStorage.removeSecret(key) - Remove a single secret for current user
+        let script_uri_remove = script_uri_owned.clone();
+        let remove_secret_fn = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
+                let globals = ctx.globals();
+                let user_id = match get_auth_user_id(&globals) {
+                    Some(id) => id,
+                    None => return Ok(false),
+                };
+                Ok(crate::repository::remove_user_secret_item(
+                    &script_uri_remove,
+                    &user_id,
+                    &key,
+                ))
+            },
+        )?;
+        secret_storage_obj.set("removeSecret", remove_secret_fn)?;
+
+        // secretStorage.clear() - Clear all secrets for current user in this script
+        let script_uri_clear = script_uri_owned.clone();
+        let clear_fn = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                let globals = ctx.globals();
+                let user_id = match get_auth_user_id(&globals) {
+                    Some(id) => id,
+                    None => {
+                        return Ok(
+                            "Error: Secret storage requires authentication. Please log in."
+                                .to_string(),
+                        );
+                    }
+                };
+                match crate::repository::clear_user_secrets(&script_uri_clear, &user_id) {
+                    Ok(()) => Ok("Secrets cleared successfully".to_string()),
+                    Err(e) => Ok(format!("Error clearing secrets: {}", e)),
                 }
+            },
+        )?;
+        secret_storage_obj.set("clear", clear_fn)?;
+
+        // ====================================================================
+        // Privileged URI-specific secret methods (for cross-script management)
+        // Only attached when the calling script itself is privileged.
+        // The user's admin role does NOT grant access — the script must be privileged.
+        // ====================================================================
+        let setup_script_privileged =
+            crate::repository::is_script_privileged(&script_uri_owned).unwrap_or(false);
+
+        if setup_script_privileged {
+            // secretStorage.listForUri(scriptUri) - List keys for any script (privileged)
+            let script_uri_list_uri = script_uri_owned.clone();
+            let list_for_uri_fn = Function::new(
+                ctx.clone(),
+                move |_ctx: rquickjs::Ctx<'_>, target_uri: String| -> JsResult<Vec<String>> {
+                    match crate::repository::is_script_privileged(&script_uri_list_uri) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.listForUri",
+                                "permission_denied",
+                                &format!(
+                                    "Script '{}' is not privileged to access secretStorage.listForUri",
+                                    script_uri_list_uri
+                                ),
+                            ));
+                        }
+                        Err(e) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.listForUri",
+                                "privilege_lookup_failed",
+                                &format!(
+                                    "Unable to verify privileges for '{}': {}",
+                                    script_uri_list_uri, e
+                                ),
+                            ));
+                        }
+                    }
+                    Ok(crate::repository::list_script_secrets(&target_uri).unwrap_or_default())
+                },
+            )?;
+            secret_storage_obj.set("listForUri", list_for_uri_fn)?;
+
+            // secretStorage.setSecretForUri(scriptUri, key, value) - Store for any script (privileged)
+            let script_uri_set_uri = script_uri_owned.clone();
+            let set_for_uri_fn = Function::new(
+                ctx.clone(),
+                move |_ctx: rquickjs::Ctx<'_>,
+                      target_uri: String,
+                      key: String,
+                      value: String|
+                      -> JsResult<String> {
+                    match crate::repository::is_script_privileged(&script_uri_set_uri) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.setSecretForUri",
+                                "permission_denied",
+                                &format!(
+                                    "Script '{}' is not privileged to access secretStorage.setSecretForUri",
+                                    script_uri_set_uri
+                                ),
+                            ));
+                        }
+                        Err(e) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.setSecretForUri",
+                                "privilege_lookup_failed",
+                                &format!(
+                                    "Unable to verify privileges for '{}': {}",
+                                    script_uri_set_uri, e
+                                ),
+                            ));
+                        }
+                    }
+                    if key.trim().is_empty() {
+                        return Ok("Error: Key cannot be empty".to_string());
+                    }
+                    if value.len() > 1_000_000 {
+                        return Ok("Error: Value too large (>1MB)".to_string());
+                    }
+                    match crate::repository::set_script_secret_item(&target_uri, &key, &value) {
+                        Ok(()) => Ok("Secret set successfully".to_string()),
+                        Err(e) => Ok(format!("Error setting secret: {}", e)),
+                    }
+                },
+            )?;
+            secret_storage_obj.set("setSecretForUri", set_for_uri_fn)?;
+
+            // secretStorage.removeSecretForUri(scriptUri, key) - Remove for any script (privileged)
+            let script_uri_remove_uri = script_uri_owned.clone();
+            let remove_for_uri_fn = Function::new(
+                ctx.clone(),
+                move |_ctx: rquickjs::Ctx<'_>, target_uri: String, key: String| -> JsResult<bool> {
+                    match crate::repository::is_script_privileged(&script_uri_remove_uri) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.removeSecretForUri",
+                                "permission_denied",
+                                &format!(
+                                    "Script '{}' is not privileged to access secretStorage.removeSecretForUri",
+                                    script_uri_remove_uri
+                                ),
+                            ));
+                        }
+                        Err(e) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.removeSecretForUri",
+                                "privilege_lookup_failed",
+                                &format!(
+                                    "Unable to verify privileges for '{}': {}",
+                                    script_uri_remove_uri, e
+                                ),
+                            ));
+                        }
+                    }
+                    Ok(crate::repository::remove_script_secret_item(
+                        &target_uri,
+                        &key,
+                    ))
+                },
+            )?;
+            secret_storage_obj.set("removeSecretForUri", remove_for_uri_fn)?;
+
+            // secretStorage.clearForUri(scriptUri) - Clear all for any script (privileged)
+            let script_uri_clear_uri = script_uri_owned.clone();
+            let clear_for_uri_fn = Function::new(
+                ctx.clone(),
+                move |_ctx: rquickjs::Ctx<'_>, target_uri: String| -> JsResult<String> {
+                    match crate::repository::is_script_privileged(&script_uri_clear_uri) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.clearForUri",
+                                "permission_denied",
+                                &format!(
+                                    "Script '{}' is not privileged to access secretStorage.clearForUri",
+                                    script_uri_clear_uri
+                                ),
+                            ));
+                        }
+                        Err(e) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "secretStorage.clearForUri",
+                                "privilege_lookup_failed",
+                                &format!(
+                                    "Unable to verify privileges for '{}': {}",
+                                    script_uri_clear_uri, e
+                                ),
+                            ));
+                        }
+                    }
+                    match crate::repository::clear_script_secrets(&target_uri) {
+                        Ok(()) => Ok("Secrets cleared successfully".to_string()),
+                        Err(e) => Ok(format!("Error clearing secrets: {}", e)),
+                    }
+                },
+            )?;
+            secret_storage_obj.set("clearForUri", clear_for_uri_fn)?;
+        } // end if setup_script_privileged
+
+        global.set("secretStorage", secret_storage_obj)?;
+
+        debug!(
+            "secretStorage JavaScript API initialized for script: {}",
+            script_uri
+        );
+
+        Ok(())
+    }
+
+    /// Setup secure GraphQL functions  
+    fn setup_graphql_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let user_context = self.user_context.clone();
+        let secure_ops = self.secure_ops.clone();
+        let auditor = self.auditor.clone();
+        let script_uri_owned = script_uri.to_string();
+
+        // Secure registerGraphQLQuery function
+        let user_ctx_query = user_context.clone();
+        let _secure_ops_query = secure_ops.clone();
+        let auditor_query = auditor.clone();
+        let script_uri_query = script_uri_owned.clone();
+        let config_query = self.config.clone();
+        let register_graphql_query = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  sdl: String,
+                  resolver_function: String,
+                  visibility: String|
+                  -> JsResult<String> {
+                // If GraphQL registration is disabled, return success without doing anything
+                tracing::info!(
+                    "registerGraphQLQuery called: name={}, visibility={}, enable_graphql_registration={}",
+                    name,
+                    visibility,
+                    config_query.enable_graphql_registration
+                );
+                if !config_query.enable_graphql_registration {
+                    tracing::info!(
+                        "GraphQL registration disabled, skipping query registration for: {}",
+                        name
+                    );
+                    return Ok(format!(
+                        "GraphQL query '{}' registration skipped (disabled)",
+                        name
+                    ));
+                }
+
+                // Check capability
+                if let Err(e) =
+                    user_ctx_query.require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                    let auditor_clone = auditor_query.clone();
+                    let user_id = user_ctx_query.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "graphql".to_string(),
+                                "register_query".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate GraphQL schema inline (sync validation)
+                // Basic SDL validation
+                if sdl.is_empty() || sdl.len() > 100_000 {
+                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
+                }
+                if name.is_empty() || name.len() > 100 {
+                    return Ok(
+                        "Invalid query name: must be between 1 and 100 characters".to_string()
+                    );
+                }
+                // Check for dangerous patterns
+                if sdl.contains("__proto__") || sdl.contains("constructor") {
+                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
+                }
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_query.clone();
+                let user_id = user_ctx_query.user_id.clone();
+                let name_clone = name.clone();
+                let script_uri_clone = script_uri_query.clone();
+                let sdl_len = sdl.len();
+                let visibility_clone = visibility.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("register_query".to_string())
+                            .with_detail("query_name", &name_clone)
+                            .with_detail("script_uri", &script_uri_clone)
+                            .with_detail("sdl_length", sdl_len.to_string())
+                            .with_detail("visibility", &visibility_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_query.user_id,
+                    name = %name,
+                    sdl_len = sdl.len(),
+                    visibility = %visibility,
+                    "Secure registerGraphQLQuery called"
+                );
+
+                // Actually register the GraphQL query
+                match crate::graphql::register_graphql_query(
+                    name.clone(),
+                    sdl.clone(),
+                    resolver_function.clone(),
+                    script_uri_query.clone(),
+                    visibility,
+                ) {
+                    Ok(()) => Ok(format!("GraphQL query '{}' registered successfully", name)),
+                    Err(e) => Ok(format!("Error registering GraphQL query '{}': {}", name, e)),
+                }
+            },
+        )?;
+
+        // Secure registerGraphQLMutation function
+        let user_ctx_mutation = user_context.clone();
+        let _secure_ops_mutation = secure_ops.clone();
+        let auditor_mutation = auditor.clone();
+        let script_uri_mutation = script_uri_owned.clone();
+        let config_mutation = self.config.clone();
+        let register_graphql_mutation = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  sdl: String,
+                  resolver_function: String,
+                  visibility: String|
+                  -> JsResult<String> {
+                // If GraphQL registration is disabled, return success without doing anything
+                debug!(
+                    "registerGraphQLMutation called: name={}, visibility={}, enable_graphql_registration={}",
+                    name, visibility, config_mutation.enable_graphql_registration
+                );
+                if !config_mutation.enable_graphql_registration {
+                    debug!(
+                        "GraphQL registration disabled, skipping mutation registration for: {}",
+                        name
+                    );
+                    return Ok(format!(
+                        "GraphQL mutation '{}' registration skipped (disabled)",
+                        name
+                    ));
+                }
+
+                // Check capability
+                if let Err(e) = user_ctx_mutation
+                    .require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                    let auditor_clone = auditor_mutation.clone();
+                    let user_id = user_ctx_mutation.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "graphql".to_string(),
+                                "register_mutation".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate GraphQL schema inline (sync validation)
+                if sdl.is_empty() || sdl.len() > 100_000 {
+                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
+                }
+                if name.is_empty() || name.len() > 100 {
+                    return Ok(
+                        "Invalid mutation name: must be between 1 and 100 characters".to_string(),
+                    );
+                }
+                if sdl.contains("__proto__") || sdl.contains("constructor") {
+                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
+                }
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_mutation.clone();
+                let user_id = user_ctx_mutation.user_id.clone();
+                let name_clone = name.clone();
+                let sdl_len = sdl.len();
+                let visibility_clone = visibility.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("register_mutation".to_string())
+                            .with_detail("mutation_name", &name_clone)
+                            .with_detail("sdl_length", sdl_len.to_string())
+                            .with_detail("visibility", &visibility_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_mutation.user_id,
+                    name = %name,
+                    sdl_len = sdl.len(),
+                    visibility = %visibility,
+                    "Secure registerGraphQLMutation called"
+                );
+
+                // Actually register the GraphQL mutation
+                match crate::graphql::register_graphql_mutation(
+                    name.clone(),
+                    sdl.clone(),
+                    resolver_function.clone(),
+                    script_uri_mutation.clone(),
+                    visibility,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "GraphQL mutation '{}' registered successfully",
+                        name
+                    )),
+                    Err(e) => Ok(format!(
+                        "Error registering GraphQL mutation '{}': {}",
+                        name, e
+                    )),
+                }
+            },
+        )?;
+
+        // Secure registerGraphQLSubscription function
+        let user_ctx_subscription = user_context.clone();
+        let _secure_ops_subscription = secure_ops.clone();
+        let auditor_subscription = auditor.clone();
+        let script_uri_subscription = script_uri_owned.clone();
+        let config_subscription = self.config.clone();
+        let register_graphql_subscription = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  sdl: String,
+                  resolver_function: String,
+                  visibility: String|
+                  -> JsResult<String> {
+                // If GraphQL registration is disabled, return success without doing anything
+                debug!(
+                    "registerGraphQLSubscription called: name={}, visibility={}, enable_graphql_registration={}",
+                    name, visibility, config_subscription.enable_graphql_registration
+                );
+                if !config_subscription.enable_graphql_registration {
+                    debug!(
+                        "GraphQL registration disabled, skipping subscription registration for: {}",
+                        name
+                    );
+                    return Ok(format!(
+                        "GraphQL subscription '{}' registration skipped (disabled)",
+                        name
+                    ));
+                }
+
+                // Check capability
+                if let Err(e) = user_ctx_subscription
+                    .require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                    let auditor_clone = auditor_subscription.clone();
+                    let user_id = user_ctx_subscription.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "graphql".to_string(),
+                                "register_subscription".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate GraphQL schema inline (sync validation)
+                if sdl.is_empty() || sdl.len() > 100_000 {
+                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
+                }
+                if name.is_empty() || name.len() > 100 {
+                    return Ok(
+                        "Invalid subscription name: must be between 1 and 100 characters"
+                            .to_string(),
+                    );
+                }
+                if sdl.contains("__proto__") || sdl.contains("constructor") {
+                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
+                }
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_subscription.clone();
+                let user_id = user_ctx_subscription.user_id.clone();
+                let name_clone = name.clone();
+                let sdl_len = sdl.len();
+                let visibility_clone = visibility.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("register_subscription".to_string())
+                            .with_detail("subscription_name", &name_clone)
+                            .with_detail("sdl_length", sdl_len.to_string())
+                            .with_detail("visibility", &visibility_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_subscription.user_id,
+                    name = %name,
+                    sdl_len = sdl.len(),
+                    visibility = %visibility,
+                    "Secure registerGraphQLSubscription called"
+                );
+
+                // Actually register the GraphQL subscription
+                match crate::graphql::register_graphql_subscription(
+                    name.clone(),
+                    sdl.clone(),
+                    resolver_function.clone(),
+                    script_uri_subscription.clone(),
+                    visibility,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "GraphQL subscription '{}' registered successfully",
+                        name
+                    )),
+                    Err(e) => Ok(format!(
+                        "Error registering GraphQL subscription '{}': {}",
+                        name, e
+                    )),
+                }
+            },
+        )?;
+
+        // Secure executeGraphQL function
+        let user_ctx_execute = user_context.clone();
+        let auditor_execute = auditor.clone();
+        let script_uri_execute = script_uri_owned.clone();
+        let config_execute = self.config.clone();
+        let execute_graphql = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  query: String,
+                  variables_json: Option<String>|
+                  -> JsResult<String> {
+                // If GraphQL execution is disabled, return error
+                debug!(
+                    "executeGraphQL called: query_length={}, enable_graphql_execution={}",
+                    query.len(),
+                    config_execute.enable_graphql_registration // Reuse existing config for now
+                );
+                if !config_execute.enable_graphql_registration {
+                    debug!("GraphQL execution disabled, rejecting executeGraphQL call");
+                    return Ok(
+                        "{\"errors\": [{\"message\": \"GraphQL execution is disabled\"}]}"
+                            .to_string(),
+                    );
+                }
+
+                // Check capability
+                if let Err(e) =
+                    user_ctx_execute.require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                    let auditor_clone = auditor_execute.clone();
+                    let user_id = user_ctx_execute.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "graphql".to_string(),
+                                "execute".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("{{\"errors\": [{{\"message\": \"{}\"}}]}}", e));
+                }
+
+                // Validate query
+                if query.is_empty() || query.len() > 100_000 {
+                    return Ok("{\"errors\": [{\"message\": \"Invalid query: must be between 1 and 100,000 characters\"}]}".to_string());
+                }
+
+                // Parse variables if provided
+                let variables = if let Some(vars_json) = variables_json {
+                    if vars_json.len() > 50_000 {
+                        return Ok("{\"errors\": [{\"message\": \"Variables too large: max 50,000 characters\"}]}".to_string());
+                    }
+                    match serde_json::from_str::<serde_json::Value>(&vars_json) {
+                        Ok(v) => Some(v),
+                        Err(e) => {
+                            return Ok(format!(
+                                "{{\"errors\": [{{\"message\": \"Invalid variables JSON: {}\"}}]}}",
+                                e
+                            ));
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_execute.clone();
+                let user_id = user_ctx_execute.user_id.clone();
+                let query_clone = query.clone();
+                let script_uri_clone = script_uri_execute.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("execute".to_string())
+                            .with_detail("script_uri", &script_uri_clone)
+                            .with_detail("query_length", query_clone.len().to_string()),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_execute.user_id,
+                    query_len = query.len(),
+                    has_variables = variables.is_some(),
+                    "Secure executeGraphQL called"
+                );
+
+                // Execute the GraphQL query
+                match crate::graphql::execute_graphql_query_sync(&query, variables) {
+                    Ok(result_json) => {
+                        debug!("GraphQL execution successful");
+                        Ok(result_json)
+                    }
+                    Err(e) => {
+                        tracing::error!("GraphQL execution failed: {}", e);
+                        Ok(format!(
+                            "{{\"errors\": [{{\"message\": \"GraphQL execution failed: {}\"}}]}}",
+                            e
+                        ))
+                    }
+                }
+            },
+        )?;
+
+        // Secure sendSubscriptionMessage function
+        let user_ctx_send_sub = user_context.clone();
+        let auditor_send_sub = auditor.clone();
+        let send_subscription_message = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  subscription_name: String,
+                  message: String|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    crate::dry_run::ensure_allowed("graphQLRegistry.sendSubscriptionMessage")
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                // Check capability
+                if let Err(e) = user_ctx_send_sub
+                    .require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                    let auditor_clone = auditor_send_sub.clone();
+                    let user_id = user_ctx_send_sub.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "graphql".to_string(),
+                                "send_subscription_message".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_send_sub.clone();
+                let user_id = user_ctx_send_sub.user_id.clone();
+                let subscription_name_clone = subscription_name.clone();
+                let message_clone = message.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Low,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("send_subscription_message".to_string())
+                            .with_detail("subscription_name", &subscription_name_clone)
+                            .with_detail("message_length", message_clone.len().to_string()),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_send_sub.user_id,
+                    subscription_name = %subscription_name,
+                    message_len = message.len(),
+                    "Secure sendSubscriptionMessage called"
+                );
+
+                // Send to the auto-registered stream path for this subscription
+                let stream_path = format!("/engine/graphql/subscription/{}", subscription_name);
+
+                // Call actual stream message sending (sync operation)
+                match crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                    .broadcast_to_stream(&stream_path, &message)
+                {
+                    Ok(result) => {
+                        if result.is_fully_successful() {
+                            Ok(format!(
+                                "GraphQL subscription message sent to '{}' ({} connections) successfully",
+                                subscription_name, result.successful_sends
+                            ))
+                        } else {
+                            Ok(format!(
+                                "GraphQL subscription message to '{}' partially sent: {} successful, {} failed out of {} total",
+                                subscription_name,
+                                result.successful_sends,
+                                result.failed_connections.len(),
+                                result.total_connections
+                            ))
+                        }
+                    }
+                    Err(e) => Ok(format!(
+                        "Failed to send GraphQL subscription message to '{}': {}",
+                        subscription_name, e
+                    )),
+                }
+            },
+        )?;
+
+        // Secure sendSubscriptionMessageFiltered function (selective broadcasting for GraphQL)
+        let user_ctx_send_sub_filtered = user_context.clone();
+        let auditor_send_sub_filtered = auditor.clone();
+        let send_subscription_message_filtered = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  subscription_name: String,
+                  message: String,
+                  filter_json: Option<String>,
+                  match_mode: Option<String>|
+                  -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed(
+                    "graphQLRegistry.sendSubscriptionMessageFiltered",
+                ) {
+                    return Ok(format!("Error: {}", e));
+                }
+                // Parse filter criteria from JSON string
+                let metadata_filter: HashMap<String, String> = if let Some(json_str) = filter_json {
+                    serde_json::from_str(&json_str).map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "filter",
+                            "MetadataFilter",
+                            &format!("Invalid filter JSON: {}", e),
+                        )
+                    })?
+                } else {
+                    HashMap::new() // Empty filter matches all connections
+                };
+                let match_mode = parse_filter_match_mode(match_mode)?;
+
+                // Allow system-level GraphQL subscription broadcasting without capability checks
+                let is_system_broadcast = true; // GraphQL subscriptions are considered system-level
+
+                if !is_system_broadcast {
+                    // Check capability for non-system operations (future use)
+                    if let Err(e) = user_ctx_send_sub_filtered
+                        .require_capability(&crate::security::Capability::ManageGraphQL)
+                    {
+                        // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
+                        let auditor_clone = auditor_send_sub_filtered.clone();
+                        let user_id = user_ctx_send_sub_filtered.user_id.clone();
+                        tokio::task::spawn(async move {
+                            let _ = auditor_clone
+                                .log_authz_failure(
+                                    user_id,
+                                    "graphql".to_string(),
+                                    "send_subscription_message_to_connections".to_string(),
+                                    "ManageGraphQL".to_string(),
+                                )
+                                .await;
+                        });
+                        return Ok(format!("Error: {}", e));
+                    }
+                }
+
+                // Log the operation attempt using spawn to avoid runtime conflicts
+                let auditor_clone = auditor_send_sub_filtered.clone();
+                let user_id = user_ctx_send_sub_filtered.user_id.clone();
+                let subscription_name_clone = subscription_name.clone();
+                let message_clone = message.clone();
+                let filter_clone = metadata_filter.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                SecurityEventType::SystemSecurityEvent,
+                                SecuritySeverity::Low,
+                                user_id,
+                            )
+                            .with_resource("graphql".to_string())
+                            .with_action("send_subscription_message_to_connections".to_string())
+                            .with_detail("subscription_name", &subscription_name_clone)
+                            .with_detail("message_length", message_clone.len().to_string())
+                            .with_detail("filter_criteria", format!("{:?}", filter_clone)),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_send_sub_filtered.user_id,
+                    subscription_name = %subscription_name,
+                    message_len = message.len(),
+                    filter = ?metadata_filter,
+                    match_mode = ?match_mode,
+                    "Secure sendSubscriptionMessageFiltered called"
+                );
+
+                // Send to the auto-registered stream path for this subscription with filtering
+                let stream_path = format!("/engine/graphql/subscription/{}", subscription_name);
+
+                // Call selective broadcasting (sync operation)
+                let result = crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                    .broadcast_to_stream_with_filter_mode(
+                        &stream_path,
+                        &message,
+                        &metadata_filter,
+                        match_mode,
+                    );
+
+                match result {
+                    Ok(broadcast_result) => {
+                        if broadcast_result.is_fully_successful() {
+                            Ok(format!(
+                                "GraphQL subscription message sent to '{}' with filter {:?} ({} connections) successfully",
+                                subscription_name,
+                                metadata_filter,
+                                broadcast_result.successful_sends
+                            ))
+                        } else {
+                            Ok(format!(
+                                "GraphQL subscription message to '{}' with filter {:?} partially sent: {} successful, {} failed connections",
+                                subscription_name,
+                                metadata_filter,
+                                broadcast_result.successful_sends,
+                                broadcast_result.failed_connections.len()
+                            ))
+                        }
+                    }
+                    Err(e) => Ok(format!(
+                        "Failed to send GraphQL subscription message to '{}' with filter: {}",
+                        subscription_name, e
+                    )),
+                }
+            },
+        )?;
+
+        // Create graphQLRegistry object with all 6 functions
+        let graphql_registry = rquickjs::Object::new(ctx.clone())?;
+        graphql_registry.set("registerQuery", register_graphql_query)?;
+        graphql_registry.set("registerMutation", register_graphql_mutation)?;
+        graphql_registry.set("registerSubscription", register_graphql_subscription)?;
+        graphql_registry.set("executeGraphQL", execute_graphql)?;
+        graphql_registry.set("sendSubscriptionMessage", send_subscription_message)?;
+        graphql_registry.set(
+            "sendSubscriptionMessageFiltered",
+            send_subscription_message_filtered,
+        )?;
+        global.set("graphQLRegistry", graphql_registry)?;
+
+        Ok(())
+    }
+
+    /// Setup MCP (Model Context Protocol) registry functions
+    fn setup_mcp_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let user_context = self.user_context.clone();
+        let auditor = self.auditor.clone();
+        let script_uri_owned = script_uri.to_string();
+        let config = self.config.clone();
+
+        // registerTool function - registers an MCP tool
+        let user_ctx_register = user_context.clone();
+        let auditor_register = auditor.clone();
+        let script_uri_register = script_uri_owned.clone();
+        let register_tool = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  description: String,
+                  input_schema_json: String,
+                  handler_function: String|
+                  -> JsResult<String> {
+                // Check if MCP is enabled (we can use the same config flag as GraphQL for now)
+                if !config.enable_graphql_registration {
+                    debug!(
+                        "MCP tool registration disabled, skipping tool registration for: {}",
+                        name
+                    );
+                    return Ok(format!(
+                        "MCP tool '{}' registration skipped (disabled)",
+                        name
+                    ));
+                }
+
+                // Check capability - reuse ManageGraphQL for MCP tools
+                if let Err(e) = user_ctx_register
+                    .require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    let auditor_clone = auditor_register.clone();
+                    let user_id = user_ctx_register.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "mcp".to_string(),
+                                "register_tool".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate inputs
+                if name.is_empty() || name.len() > 100 {
+                    return Ok(
+                        "Invalid tool name: must be between 1 and 100 characters".to_string()
+                    );
+                }
+                if description.is_empty() || description.len() > 1000 {
+                    return Ok(
+                        "Invalid description: must be between 1 and 1000 characters".to_string()
+                    );
+                }
+
+                // Parse and validate input schema JSON
+                let input_schema: serde_json::Value = serde_json::from_str(&input_schema_json)
+                    .map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "schema",
+                            "InputSchema",
+                            &format!("Invalid input schema JSON: {}", e),
+                        )
+                    })?;
+
+                // Check for dangerous patterns
+                if input_schema_json.contains("__proto__")
+                    || input_schema_json.contains("constructor")
+                {
+                    return Ok("Invalid schema: contains dangerous patterns".to_string());
+                }
+
+                // Log the operation attempt
+                let auditor_clone = auditor_register.clone();
+                let user_id = user_ctx_register.user_id.clone();
+                let name_clone = name.clone();
+                let script_uri_clone = script_uri_register.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                crate::security::SecurityEventType::SystemSecurityEvent,
+                                crate::security::SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("mcp".to_string())
+                            .with_action("register_tool".to_string())
+                            .with_detail("tool_name", &name_clone)
+                            .with_detail("script_uri", &script_uri_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_register.user_id,
+                    name = %name,
+                    "Secure registerTool called for MCP"
+                );
+
+                // Actually register the MCP tool
+                crate::mcp::register_mcp_tool(
+                    name.clone(),
+                    description,
+                    input_schema,
+                    handler_function,
+                    script_uri_register.clone(),
+                );
+
+                Ok(format!("MCP tool '{}' registered successfully", name))
+            },
+        )?;
+
+        // registerPrompt function - registers an MCP prompt
+        let user_ctx_prompt = user_context.clone();
+        let auditor_prompt = auditor.clone();
+        let script_uri_prompt = script_uri_owned.clone();
+        let config_prompt = config.clone();
+        let register_prompt = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  description: String,
+                  arguments_json: String,
+                  handler_function: String|
+                  -> JsResult<String> {
+                // Check if MCP is enabled
+                if !config_prompt.enable_graphql_registration {
+                    debug!(
+                        "MCP prompt registration disabled, skipping prompt registration for: {}",
+                        name
+                    );
+                    return Ok(format!(
+                        "MCP prompt '{}' registration skipped (disabled)",
+                        name
+                    ));
+                }
+
+                // Check capability - reuse ManageGraphQL for MCP prompts
+                if let Err(e) =
+                    user_ctx_prompt.require_capability(&crate::security::Capability::ManageGraphQL)
+                {
+                    let auditor_clone = auditor_prompt.clone();
+                    let user_id = user_ctx_prompt.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_authz_failure(
+                                user_id,
+                                "mcp".to_string(),
+                                "register_prompt".to_string(),
+                                "ManageGraphQL".to_string(),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate inputs
+                if name.is_empty() || name.len() > 100 {
+                    return Ok(
+                        "Invalid prompt name: must be between 1 and 100 characters".to_string()
+                    );
+                }
+                if description.is_empty() || description.len() > 1000 {
+                    return Ok(
+                        "Invalid description: must be between 1 and 1000 characters".to_string()
+                    );
+                }
+                if handler_function.is_empty() || handler_function.len() > 100 {
+                    return Ok(
+                        "Invalid handler function: must be between 1 and 100 characters"
+                            .to_string(),
+                    );
+                }
+
+                // Validate arguments JSON
+                if arguments_json.contains("__proto__") || arguments_json.contains("constructor") {
+                    return Ok("Invalid arguments: contains dangerous patterns".to_string());
+                }
+
+                // Log the operation attempt
+                let auditor_clone = auditor_prompt.clone();
+                let user_id = user_ctx_prompt.user_id.clone();
+                let name_clone = name.clone();
+                let script_uri_clone = script_uri_prompt.clone();
+                let handler_clone = handler_function.clone();
+                tokio::task::spawn(async move {
+                    let _ = auditor_clone
+                        .log_event(
+                            crate::security::SecurityEvent::new(
+                                crate::security::SecurityEventType::SystemSecurityEvent,
+                                crate::security::SecuritySeverity::Medium,
+                                user_id,
+                            )
+                            .with_resource("mcp".to_string())
+                            .with_action("register_prompt".to_string())
+                            .with_detail("prompt_name", &name_clone)
+                            .with_detail("handler", &handler_clone)
+                            .with_detail("script_uri", &script_uri_clone),
+                        )
+                        .await;
+                });
+
+                debug!(
+                    user_id = ?user_ctx_prompt.user_id,
+                    name = %name,
+                    handler = %handler_function,
+                    "Secure registerPrompt called for MCP"
+                );
+
+                // Actually register the MCP prompt
+                match crate::mcp::register_mcp_prompt(
+                    name.clone(),
+                    description,
+                    arguments_json,
+                    handler_function.clone(),
+                    script_uri_prompt.clone(),
+                ) {
+                    Ok(_) => Ok(format!(
+                        "MCP prompt '{}' registered successfully with handler '{}'",
+                        name, handler_function
+                    )),
+                    Err(e) => Ok(format!("Error registering prompt: {}", e)),
+                }
+            },
+        )?;
+
+        // Create mcpRegistry object
+        let mcp_registry = rquickjs::Object::new(ctx.clone())?;
+        mcp_registry.set("registerTool", register_tool)?;
+        mcp_registry.set("registerPrompt", register_prompt)?;
+        global.set("mcpRegistry", mcp_registry)?;
+
+        // Setup McpClient class for connecting to external MCP servers; it
+        // reaches the network like fetch()
+        if self.config.enable_fetch {
+            self.setup_mcp_client_class(ctx, script_uri)?;
+        }
+
+        Ok(())
+    }
+
+    /// Setup McpClient class for external MCP server connections. Clients
+    /// authenticate with a stored secret, so every call needs the secrets
+    /// capability too.
+    fn setup_mcp_client_class(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let script_uri_owned = script_uri.to_string();
+        // Capture user_id for secret resolution (user_secrets first, then script_secrets)
+        let user_id_for_mcp = self.user_context.user_id.clone();
+        let secrets_enabled = self.config.enable_secrets;
+
+        // McpClient constructor
+        let mcp_client_constructor = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  server_url: String,
+                  secret_identifier: String|
+                  -> JsResult<String> {
+                require_mcp_secrets(secrets_enabled, "constructor")?;
+
+                // Create MCP client instance (just validate parameters)
+                let _client = crate::mcp_client::McpClient::new(
+                    server_url.clone(),
+                    secret_identifier.clone(),
+                )
+                .map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "constructor",
+                        &format!("Failed to create MCP client: {}", e),
+                    )
+                })?;
+
+                // Serialize client to JSON (we'll store server_url and secret_identifier)
+                let client_data = serde_json::json!({
+                    "serverUrl": server_url,
+                    "secretIdentifier": secret_identifier,
+                });
+
+                Ok(serde_json::to_string(&client_data).unwrap())
+            },
+        )?;
+
+        // Create McpClient class object with constructor and prototype
+        let mcp_client_class = rquickjs::Object::new(ctx.clone())?;
+
+        // listTools method
+        let script_uri_list = script_uri_owned.clone();
+        let user_id_list = user_id_for_mcp.clone();
+        let list_tools = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, client_data_json: String| -> JsResult<String> {
+                require_mcp_secrets(secrets_enabled, "listTools")?;
+
+                // Parse client data
+                let client_data: serde_json::Value = serde_json::from_str(&client_data_json)
+                    .map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "listTools",
+                            &format!("Invalid client data: {}", e),
+                        )
+                    })?;
+
+                let server_url = client_data["serverUrl"].as_str().ok_or_else(|| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "listTools",
+                        "Missing serverUrl in client data",
+                    )
+                })?;
+
+                let secret_identifier =
+                    client_data["secretIdentifier"].as_str().ok_or_else(|| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "listTools",
+                            "Missing secretIdentifier in client data",
+                        )
+                    })?;
+
+                // Create client
+                let client = crate::mcp_client::McpClient::new(
+                    server_url.to_string(),
+                    secret_identifier.to_string(),
+                )
+                .map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "listTools",
+                        &format!("Failed to create client: {}", e),
+                    )
+                })?;
+
+                // List tools
+                let tools = client
+                    .list_tools(&script_uri_list, user_id_list.as_deref())
+                    .map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "listTools",
+                            &format!("Failed to list tools: {}", e),
+                        )
+                    })?;
+
+                // Serialize tools to JSON
+                serde_json::to_string(&tools).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "listTools",
+                        &format!("Failed to serialize tools: {}", e),
+                    )
+                })
+            },
+        )?;
+
+        // callTool method
+        let script_uri_call = script_uri_owned.clone();
+        let user_id_call = user_id_for_mcp.clone();
+        let call_tool = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  client_data_json: String,
+                  tool_name: String,
+                  arguments_json: String|
+                  -> JsResult<String> {
+                require_mcp_secrets(secrets_enabled, "callTool")?;
+                crate::dry_run::ensure_allowed("McpClient.callTool").map_err(|e| {
+                    rquickjs::Error::new_from_js_message("McpClient", "callTool", &e)
+                })?;
+
+                // Parse client data
+                let client_data: serde_json::Value = serde_json::from_str(&client_data_json)
+                    .map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "callTool",
+                            &format!("Invalid client data: {}", e),
+                        )
+                    })?;
+
+                let server_url = client_data["serverUrl"].as_str().ok_or_else(|| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "callTool",
+                        "Missing serverUrl in client data",
+                    )
+                })?;
+
+                let secret_identifier =
+                    client_data["secretIdentifier"].as_str().ok_or_else(|| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "callTool",
+                            "Missing secretIdentifier in client data",
+                        )
+                    })?;
+
+                // Parse arguments
+                let arguments: serde_json::Value =
+                    serde_json::from_str(&arguments_json).map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "callTool",
+                            &format!("Invalid arguments JSON: {}", e),
+                        )
+                    })?;
+
+                // Create client
+                let client = crate::mcp_client::McpClient::new(
+                    server_url.to_string(),
+                    secret_identifier.to_string(),
+                )
+                .map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "callTool",
+                        &format!("Failed to create client: {}", e),
+                    )
+                })?;
+
+                // Call tool
+                let result = match client.call_tool(
+                    tool_name.clone(),
+                    arguments,
+                    &script_uri_call,
+                    user_id_call.as_deref(),
+                ) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        // For JSON-RPC errors, return them as {error: {...}} objects
+                        if let crate::mcp_client::McpClientError::JsonRpc(code, message) = e {
+                            let error_obj = serde_json::json!({
+                                "error": {
+                                    "code": code,
+                                    "message": message
+                                }
+                            });
+                            return Ok(serde_json::to_string(&error_obj).unwrap());
+                        }
+
+                        // For other errors, throw JavaScript exceptions
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "McpClient",
+                            "callTool",
+                            &format!("Failed to call tool '{}': {}", tool_name, e),
+                        ));
+                    }
+                };
+
+                // Serialize result to JSON
+                serde_json::to_string(&result).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "McpClient",
+                        "callTool",
+                        &format!("Failed to serialize result: {}", e),
+                    )
+                })
+            },
+        )?;
+
+        // Set methods on the class
+        mcp_client_class.set("constructor", mcp_client_constructor)?;
+        mcp_client_class.set("_listTools", list_tools)?;
+        mcp_client_class.set("_callTool", call_tool)?;
+
+        // Set the class on global scope
+        global.set("McpClient", mcp_client_class)?;
+
+        debug!("McpClient class initialized for external MCP server connections");
+
+        Ok(())
+    }
+
+    /// Setup routeRegistry object with all route-related functions
+    fn setup_route_registry(
+        &self,
+        ctx: &rquickjs::Ctx<'_>,
+        script_uri: &str,
+        register_fn: Option<RouteRegisterFn>,
+    ) -> JsResult<()> {
+        let global = ctx.globals();
+        let user_context = self.user_context.clone();
+        let auditor = self.auditor.clone();
+        let script_uri_owned = script_uri.to_string();
+        let config = self.config.clone();
+
+        // Create the routeRegistry object
+        let route_registry = rquickjs::Object::new(ctx.clone())?;
+
+        // 1. registerRoute function
+        if let Some(register_impl) = register_fn {
+            let script_uri_for_register = script_uri_owned.clone();
+            let user_ctx_route = user_context.clone();
+            let register_route = Function::new(
+                ctx.clone(),
+                move |_ctx: rquickjs::Ctx<'_>,
+                      path: String,
+                      handler: String,
+                      method: Option<String>,
+                      metadata: Opt<rquickjs::Object>|
+                      -> Result<(), rquickjs::Error> {
+                    // Check if script is privileged OR user has admin privileges
+                    let script_privileged =
+                        match repository::is_script_privileged(&script_uri_for_register) {
+                            Ok(true) => true,
+                            Ok(false) => false,
+                            Err(e) => {
+                                return Err(rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "privilege_lookup_failed",
+                                    &format!(
+                                        "Unable to verify privileges for '{}': {}",
+                                        script_uri_for_register, e
+                                    ),
+                                ));
+                            }
+                        };
+
+                    let user_is_admin =
+                        user_ctx_route.has_capability(&crate::security::Capability::DeleteScripts);
+
+                    if !script_privileged && !user_is_admin {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "routeRegistry.registerRoute",
+                            "permission_denied",
+                            &format!(
+                                "Script '{}' is not privileged to register HTTP routes",
+                                script_uri_for_register
+                            ),
+                        ));
+                    }
+
+                    // Build RouteMetadata from parameters
+                    let mut route_meta = repository::RouteMetadata::simple(handler.clone());
+
+                    if let Some(meta_obj) = metadata.0 {
+                        // Extract summary
+                        if let Ok(summary) = meta_obj.get::<_, Option<String>>("summary") {
+                            route_meta.summary = summary;
+                        }
+                        // Extract description
+                        if let Ok(description) = meta_obj.get::<_, Option<String>>("description") {
+                            route_meta.description = description;
+                        }
+                        // Extract tags
+                        if let Ok(tags_arr) = meta_obj.get::<_, rquickjs::Array>("tags") {
+                            let mut tags = Vec::new();
+                            for i in 0..tags_arr.len() {
+                                if let Ok(tag) = tags_arr.get::<String>(i) {
+                                    tags.push(tag);
+                                }
+                            }
+                            route_meta.tags = tags;
+                        }
+                        // Extract parameters
+                        if let Ok(Some(params_json)) =
+                            meta_obj.get::<_, Option<String>>("parameters")
+                            && let Ok(params_value) =
+                                serde_json::from_str::<serde_json::Value>(&params_json)
+                        {
+                            route_meta.parameters = Some(params_value);
+                        }
+                        // Extract requestBody
+                        if let Ok(Some(body_json)) =
+                            meta_obj.get::<_, Option<String>>("requestBody")
+                            && let Ok(body_value) =
+                                serde_json::from_str::<serde_json::Value>(&body_json)
+                        {
+                            route_meta.request_body = Some(body_value);
+                        }
+                        // Extract host: one host name or a list of them
+                        if let Ok(host_value) = meta_obj.get::<_, rquickjs::Value>("host")
+                            && !host_value.is_undefined()
+                            && !host_value.is_null()
+                        {
+                            let names = match host_value.as_array() {
+                                Some(arr) => arr.iter::<String>().collect::<Result<Vec<_>, _>>(),
+                                None => host_value.get::<String>().map(|name| vec![name]),
+                            }?;
+                            let mut hosts = Vec::with_capacity(names.len());
+                            for name in names {
+                                let Some(host) = crate::route_index::normalize_host(&name) else {
+                                    return Err(rquickjs::Error::new_from_js_message(
+                                        "routeRegistry.registerRoute",
+                                        "invalid_host",
+                                        &format!("Invalid host name '{}'", name),
+                                    ));
+                                };
+                                hosts.push(host);
+                            }
+                            hosts.sort();
+                            hosts.dedup();
+                            route_meta.hosts = hosts;
+                        }
+                        // Extract rateLimit: { requestsPerMinute, per?, burst? }
+                        if let Ok(Some(limit_obj)) =
+                            meta_obj.get::<_, Option<rquickjs::Object>>("rateLimit")
+                        {
+                            let invalid = |message: &str| {
+                                rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "invalid_rate_limit",
+                                    message,
+                                )
+                            };
+                            let requests_per_minute = limit_obj
+                                .get::<_, Option<f64>>("requestsPerMinute")?
+                                .filter(|rpm| rpm.fract() == 0.0 && *rpm >= 1.0)
+                                .filter(|rpm| *rpm <= f64::from(u32::MAX))
+                                .ok_or_else(|| {
+                                    invalid(
+                                        "rateLimit.requestsPerMinute must be a positive integer",
+                                    )
+                                })? as u32;
+                            let per = match limit_obj.get::<_, Option<String>>("per")?.as_deref() {
+                                None | Some("ip") => crate::rate_limit_rules::RateLimitScope::Ip,
+                                Some("user") => crate::rate_limit_rules::RateLimitScope::User,
+                                Some("route") => crate::rate_limit_rules::RateLimitScope::Route,
+                                Some(other) => {
+                                    return Err(invalid(&format!(
+                                        "rateLimit.per must be 'ip', 'user' or 'route', got '{}'",
+                                        other
+                                    )));
+                                }
+                            };
+                            let burst = match limit_obj.get::<_, Option<f64>>("burst")? {
+                                None => None,
+                                Some(burst)
+                                    if burst.fract() == 0.0
+                                        && burst >= 1.0
+                                        && burst <= f64::from(u32::MAX) =>
+                                {
+                                    Some(burst as u32)
+                                }
+                                Some(_) => {
+                                    return Err(invalid(
+                                        "rateLimit.burst must be a positive integer",
+                                    ));
+                                }
+                            };
+                            route_meta.rate_limit = Some(crate::rate_limit_rules::RouteRateLimit {
+                                requests_per_minute,
+                                per,
+                                burst,
+                            });
+                        }
+                        // Extract idempotency: true or { ttlSeconds }
+                        if let Ok(value) = meta_obj.get::<_, rquickjs::Value>("idempotency")
+                            && !value.is_undefined()
+                            && !value.is_null()
+                        {
+                            let invalid = || {
+                                rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "invalid_idempotency",
+                                    "idempotency must be true or { ttlSeconds: positive integer }",
+                                )
+                            };
+                            if let Some(enabled) = value.as_bool() {
+                                if enabled {
+                                    route_meta.idempotency =
+                                        Some(crate::idempotency::RouteIdempotency::default());
+                                }
+                            } else if let Some(obj) = value.as_object() {
+                                let ttl_seconds = match obj.get::<_, Option<f64>>("ttlSeconds")? {
+                                    None => None,
+                                    Some(ttl) if ttl.fract() == 0.0 && ttl >= 1.0 => {
+                                        Some(ttl as u64)
+                                    }
+                                    Some(_) => return Err(invalid()),
+                                };
+                                route_meta.idempotency =
+                                    Some(crate::idempotency::RouteIdempotency { ttl_seconds });
+                            } else {
+                                return Err(invalid());
+                            }
+                        }
+                        // Extract uploads: { maxFileSize?, maxFiles?, allowedTypes? }
+                        if let Ok(Some(uploads_obj)) =
+                            meta_obj.get::<_, Option<rquickjs::Object>>("uploads")
+                        {
+                            let invalid = |message: &str| {
+                                rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "invalid_uploads",
+                                    message,
+                                )
+                            };
+                            let max_file_size =
+                                match uploads_obj.get::<_, Option<f64>>("maxFileSize")? {
+                                    None => None,
+                                    Some(size) if size.fract() == 0.0 && size >= 1.0 => {
+                                        Some(size as u64)
+                                    }
+                                    Some(_) => {
+                                        return Err(invalid(
+                                            "uploads.maxFileSize must be a positive integer",
+                                        ));
+                                    }
+                                };
+                            let max_files = match uploads_obj.get::<_, Option<f64>>("maxFiles")? {
+                                None => None,
+                                Some(count) if count.fract() == 0.0 && count >= 0.0 => {
+                                    Some(count as usize)
+                                }
+                                Some(_) => {
+                                    return Err(invalid(
+                                        "uploads.maxFiles must be a non-negative integer",
+                                    ));
+                                }
+                            };
+                            let allowed_types = uploads_obj
+                                .get::<_, Option<Vec<String>>>("allowedTypes")?
+                                .unwrap_or_default();
+                            if let Some(bad) =
+                                allowed_types.iter().find(|allowed| !allowed.contains('/'))
+                            {
+                                return Err(invalid(&format!(
+                                    "uploads.allowedTypes entry '{}' is not a content type",
+                                    bad
+                                )));
+                            }
+                            route_meta.uploads = Some(crate::parsers::RouteUploads {
+                                max_file_size,
+                                max_files,
+                                allowed_types,
+                            });
+                        }
+                        // Extract schema: { body?, query?, params? } JSON Schemas
+                        if let Ok(Some(schema_obj)) =
+                            meta_obj.get::<_, Option<rquickjs::Object>>("schema")
+                        {
+                            let invalid = |message: String| {
+                                rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "invalid_schema",
+                                    message,
+                                )
+                            };
+                            let schema_json = schema_obj
+                                .ctx()
+                                .clone()
+                                .json_stringify(schema_obj)?
+                                .map(|json| json.to_string())
+                                .transpose()?
+                                .unwrap_or_default();
+                            let schema: crate::request_schema::RouteSchema =
+                                serde_json::from_str(&schema_json).map_err(|e| {
+                                    invalid(format!(
+                                        "schema must be {{ body?, query?, params? }}: {}",
+                                        e
+                                    ))
+                                })?;
+                            schema.compile().map_err(invalid)?;
+                            route_meta.schema = Some(schema);
+                        }
+                        // Extract cache: { ttlSeconds, vary?, staleWhileRevalidateSeconds? }
+                        route_meta.cache = read_route_cache_option(&meta_obj).map_err(|e| {
+                            rquickjs::Error::new_from_js_message(
+                                "routeRegistry.registerRoute",
+                                "invalid_cache",
+                                e,
+                            )
+                        })?;
+                        // Extract auth: true or { groups }
+                        route_meta.auth = read_route_auth_option(&meta_obj).map_err(|e| {
+                            rquickjs::Error::new_from_js_message(
+                                "routeRegistry.registerRoute",
+                                "invalid_auth",
+                                e,
+                            )
+                        })?;
+                        // Extract timeoutMs, up to the configured ceiling
+                        route_meta.timeout_ms =
+                            read_route_timeout_option(&meta_obj).map_err(|e| {
+                                rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "invalid_timeout",
+                                    e,
+                                )
+                            })?;
+                    }
+
+                    let method_ref = method.as_deref();
+                    register_impl(&path, &route_meta, method_ref)
+                },
+            )?;
+            route_registry.set("registerRoute", register_route)?;
+        } else {
+            // No-op register function with privilege check
+            let script_uri_for_noop = script_uri_owned.clone();
+            let user_ctx_noop = user_context.clone();
+            let reg_noop = Function::new(
+                ctx.clone(),
+                move |_c: rquickjs::Ctx<'_>,
+                      _p: String,
+                      _h: String,
+                      _m: Option<String>,
+                      _meta: Opt<rquickjs::Object>|
+                      -> Result<(), rquickjs::Error> {
+                    // Check if script is privileged OR user has admin privileges
+                    let script_privileged =
+                        match repository::is_script_privileged(&script_uri_for_noop) {
+                            Ok(true) => true,
+                            Ok(false) => false,
+                            Err(e) => {
+                                return Err(rquickjs::Error::new_from_js_message(
+                                    "routeRegistry.registerRoute",
+                                    "privilege_lookup_failed",
+                                    &format!(
+                                        "Unable to verify privileges for '{}': {}",
+                                        script_uri_for_noop, e
+                                    ),
+                                ));
+                            }
+                        };
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_mutation.clone();
-                let user_id = user_ctx_mutation.user_id.clone();
-                let name_clone = name.clone();
-                let sdl_len = sdl.len();
-                let visibility_clone = visibility.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Medium,
-                                user_id,
-                            )
-                            .with_resource("graphql".to_string())
-                            .with_action("register_mutation".to_string())
-                            .with_detail("mutation_name", &name_clone)
-                            .with_detail("sdl_length", sdl_len.to_string())
-                            .with_detail("visibility", &visibility_clone),
-                        )
-                        .await;
-                });
+                    let user_is_admin =
+                        user_ctx_noop.has_capability(&crate::security::Capability::DeleteScripts);
 
-                debug!(
-                    user_id = ?user_ctx_mutation.user_id,
-                    name = %name,
-                    sdl_len = sdl.len(),
-                    visibility = %visibility,
-                    "Secure registerGraphQLMutation called"
-                );
+                    if !script_privileged && !user_is_admin {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "routeRegistry.registerRoute",
+                            "permission_denied",
+                            &format!(
+                                "Script '{}' is not privileged to register HTTP routes",
+                                script_uri_for_noop
+                            ),
+                        ));
+                    }
 
-                // Actually register the GraphQL mutation
-                match crate::graphql::register_graphql_mutation(
-                    name.clone(),
-                    sdl.clone(),
-                    resolver_function.clone(),
-                    script_uri_mutation.clone(),
-                    visibility,
-                ) {
-                    Ok(()) => Ok(format!(
-                        "GraphQL mutation '{}' registered successfully",
-                        name
-                    )),
-                    Err(e) => Ok(format!(
-                        "Error registering GraphQL mutation '{}': {}",
-                        name, e
-                    )),
-                }
-            },
-        )?;
+                    Ok(())
+                },
+            )?;
+            route_registry.set("registerRoute", reg_noop)?;
+        }
 
-        // Secure registerGraphQLSubscription function
-        let user_ctx_subscription = user_context.clone();
-        let _secure_ops_subscription = secure_ops.clone();
-        let auditor_subscription = auditor.clone();
-        let script_uri_subscription = script_uri_owned.clone();
-        let config_subscription = self.config.clone();
-        let register_graphql_subscription = Function::new(
+        // 2. registerStreamRoute function
+        let user_ctx_stream = user_context.clone();
+        let auditor_stream = auditor.clone();
+        let config_stream = config.clone();
+        let script_uri_stream = script_uri_owned.clone();
+        let register_stream_route = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  name: String,
-                  sdl: String,
-                  resolver_function: String,
-                  visibility: String|
+                  path: String,
+                  customization_function: Opt<Option<String>>,
+                  options: Opt<rquickjs::Object<'_>>|
                   -> JsResult<String> {
-                // If GraphQL registration is disabled, return success without doing anything
-                debug!(
-                    "registerGraphQLSubscription called: name={}, visibility={}, enable_graphql_registration={}",
-                    name, visibility, config_subscription.enable_graphql_registration
-                );
-                if !config_subscription.enable_graphql_registration {
-                    debug!(
-                        "GraphQL registration disabled, skipping subscription registration for: {}",
-                        name
-                    );
+                // Convert Opt to Option; undefined is accepted before options
+                let customization_function = customization_function.0.flatten();
+                // If streams are disabled, return success without doing anything
+                if !config_stream.enable_streams {
                     return Ok(format!(
-                        "GraphQL subscription '{}' registration skipped (disabled)",
-                        name
+                        "Stream registration disabled (stream '{}' not registered)",
+                        path
                     ));
                 }
 
-                // Check capability
-                if let Err(e) = user_ctx_subscription
-                    .require_capability(&crate::security::Capability::ManageGraphQL)
-                {
-                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_subscription.clone();
-                    let user_id = user_ctx_subscription.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "graphql".to_string(),
-                                "register_subscription".to_string(),
-                                "ManageGraphQL".to_string(),
-                            )
-                            .await;
-                    });
-                    return Ok(format!("Error: {}", e));
-                }
+                // Check if script is privileged OR user has admin privileges
+                let script_privileged = match repository::is_script_privileged(&script_uri_stream) {
+                    Ok(true) => true,
+                    Ok(false) => false,
+                    Err(e) => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "routeRegistry.registerStreamRoute",
+                            "privilege_lookup_failed",
+                            &format!(
+                                "Unable to verify privileges for '{}': {}",
+                                script_uri_stream, e
+                            ),
+                        ));
+                    }
+                };
 
-                // Validate GraphQL schema inline (sync validation)
-                if sdl.is_empty() || sdl.len() > 100_000 {
-                    return Ok("Invalid SDL: must be between 1 and 100,000 characters".to_string());
-                }
-                if name.is_empty() || name.len() > 100 {
-                    return Ok(
-                        "Invalid subscription name: must be between 1 and 100 characters"
-                            .to_string(),
-                    );
-                }
-                if sdl.contains("__proto__") || sdl.contains("constructor") {
-                    return Ok("Invalid SDL: contains dangerous patterns".to_string());
-                }
+                let user_is_admin =
+                    user_ctx_stream.has_capability(&crate::security::Capability::DeleteScripts);
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_subscription.clone();
-                let user_id = user_ctx_subscription.user_id.clone();
-                let name_clone = name.clone();
-                let sdl_len = sdl.len();
-                let visibility_clone = visibility.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Medium,
-                                user_id,
-                            )
-                            .with_resource("graphql".to_string())
-                            .with_action("register_subscription".to_string())
-                            .with_detail("subscription_name", &name_clone)
-                            .with_detail("sdl_length", sdl_len.to_string())
-                            .with_detail("visibility", &visibility_clone),
-                        )
-                        .await;
-                });
+                if !script_privileged && !user_is_admin {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "routeRegistry.registerStreamRoute",
+                        "permission_denied",
+                        &format!(
+                            "Script '{}' is not privileged to register stream routes",
+                            script_uri_stream
+                        ),
+                    ));
+                }
 
-                debug!(
-                    user_id = ?user_ctx_subscription.user_id,
-                    name = %name,
-                    sdl_len = sdl.len(),
-                    visibility = %visibility,
-                    "Secure registerGraphQLSubscription called"
-                );
+                // Validate path format
+                if path.is_empty() || !path.starts_with('/') {
+                    return Ok(format!(
+                        "Invalid stream path '{}': path must start with '/' and not be empty",
+                        path
+                    ));
+                }
 
-                // Actually register the GraphQL subscription
-                match crate::graphql::register_graphql_subscription(
-                    name.clone(),
-                    sdl.clone(),
-                    resolver_function.clone(),
-                    script_uri_subscription.clone(),
-                    visibility,
-                ) {
-                    Ok(()) => Ok(format!(
-                        "GraphQL subscription '{}' registered successfully",
-                        name
-                    )),
-                    Err(e) => Ok(format!(
-                        "Error registering GraphQL subscription '{}': {}",
-                        name, e
-                    )),
+                if path.len() > 200 {
+                    return Ok(format!(
+                        "Invalid stream path '{}': path too long (max 200 characters)",
+                        path
+                    ));
                 }
-            },
-        )?;
 
-        // Secure executeGraphQL function
-        let user_ctx_execute = user_context.clone();
-        let auditor_execute = auditor.clone();
-        let script_uri_execute = script_uri_owned.clone();
-        let config_execute = self.config.clone();
-        let execute_graphql = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  query: String,
-                  variables_json: Option<String>|
-                  -> JsResult<String> {
-                // If GraphQL execution is disabled, return error
-                debug!(
-                    "executeGraphQL called: query_length={}, enable_graphql_execution={}",
-                    query.len(),
-                    config_execute.enable_graphql_registration // Reuse existing config for now
-                );
-                if !config_execute.enable_graphql_registration {
-                    debug!("GraphQL execution disabled, rejecting executeGraphQL call");
-                    return Ok(
-                        "{\"errors\": [{\"message\": \"GraphQL execution is disabled\"}]}"
-                            .to_string(),
-                    );
+                // Validate customization function name if provided
+                if let Some(ref func_name) = customization_function {
+                    if func_name.is_empty() {
+                        return Ok(
+                            "Invalid customization function: name cannot be empty".to_string()
+                        );
+                    }
+                    if func_name.len() > 100 {
+                        return Ok(
+                            "Invalid customization function: name too long (max 100 characters)"
+                                .to_string(),
+                        );
+                    }
+                    // Basic validation: should be a valid identifier
+                    if !func_name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        return Ok("Invalid customization function: name must contain only alphanumeric characters and underscores".to_string());
+                    }
                 }
 
                 // Check capability
                 if let Err(e) =
-                    user_ctx_execute.require_capability(&crate::security::Capability::ManageGraphQL)
+                    user_ctx_stream.require_capability(&crate::security::Capability::ManageStreams)
                 {
-                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_execute.clone();
-                    let user_id = user_ctx_execute.user_id.clone();
-                    tokio::task::spawn(async move {
+                    if config_stream.enable_audit_logging
+                        && let Ok(rt) = tokio::runtime::Handle::try_current()
+                    {
+                        let auditor_clone = auditor_stream.clone();
+                        let user_id = user_ctx_stream.user_id.clone();
+                        rt.spawn(async move {
+                            let _ = auditor_clone
+                                .log_event(
+                                    crate::security::SecurityEvent::new(
+                                        crate::security::SecurityEventType::AuthorizationFailure,
+                                        crate::security::SecuritySeverity::Medium,
+                                        user_id,
+                                    )
+                                    .with_resource("stream".to_string())
+                                    .with_action("register".to_string()),
+                                )
+                                .await;
+                        });
+                    }
+                    return Ok(format!("Error: {}", e));
+                }
+
+                // Validate stream path
+                if path.contains("..") || path.contains('\\') {
+                    return Ok("Invalid stream path: path traversal not allowed".to_string());
+                }
+
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Invalid stream host: {}", e)),
+                    None => None,
+                };
+                let stream_key = crate::route_index::host_scoped_path(host.as_deref(), &path);
+
+                // Log the operation attempt
+                if config_stream.enable_audit_logging
+                    && let Ok(rt) = tokio::runtime::Handle::try_current()
+                {
+                    let auditor_clone = auditor_stream.clone();
+                    let user_id = user_ctx_stream.user_id.clone();
+                    let path_clone = stream_key.clone();
+                    let script_uri_clone = script_uri_stream.clone();
+                    rt.spawn(async move {
                         let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "graphql".to_string(),
-                                "execute".to_string(),
-                                "ManageGraphQL".to_string(),
+                            .log_event(
+                                crate::security::SecurityEvent::new(
+                                    crate::security::SecurityEventType::SystemSecurityEvent,
+                                    crate::security::SecuritySeverity::Medium,
+                                    user_id,
+                                )
+                                .with_resource("stream".to_string())
+                                .with_action("register".to_string())
+                                .with_detail("path", &path_clone)
+                                .with_detail("script_uri", &script_uri_clone),
                             )
                             .await;
                     });
-                    return Ok(format!("{{\"errors\": [{{\"message\": \"{}\"}}]}}", e));
                 }
 
-                // Validate query
-                if query.is_empty() || query.len() > 100_000 {
-                    return Ok("{\"errors\": [{\"message\": \"Invalid query: must be between 1 and 100,000 characters\"}]}".to_string());
+                // Register the stream
+                match crate::stream_registry::GLOBAL_STREAM_REGISTRY.register_stream(
+                    &stream_key,
+                    &script_uri_stream,
+                    customization_function,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "Web stream '{}' registered successfully",
+                        stream_key
+                    )),
+                    Err(e) => Ok(format!("Failed to register stream '{}': {}", stream_key, e)),
                 }
+            },
+        )?;
+        route_registry.set("registerStreamRoute", register_stream_route)?;
 
-                // Parse variables if provided
-                let variables = if let Some(vars_json) = variables_json {
-                    if vars_json.len() > 50_000 {
-                        return Ok("{\"errors\": [{\"message\": \"Variables too large: max 50,000 characters\"}]}".to_string());
-                    }
-                    match serde_json::from_str::<serde_json::Value>(&vars_json) {
-                        Ok(v) => Some(v),
-                        Err(e) => {
-                            return Ok(format!(
-                                "{{\"errors\": [{{\"message\": \"Invalid variables JSON: {}\"}}]}}",
-                                e
-                            ));
-                        }
+        // 3. registerAssetRoute function
+        let user_ctx_asset = user_context.clone();
+        let script_uri_asset = script_uri_owned.clone();
+        let register_asset_route = Function::new(
+            ctx.clone(),
+            move |_c: rquickjs::Ctx<'_>,
+                  path: String,
+                  asset_name: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> Result<String, rquickjs::Error> {
+                // Check if script is privileged OR user has admin privileges
+                let script_privileged = match repository::is_script_privileged(&script_uri_asset) {
+                    Ok(true) => true,
+                    Ok(false) => false,
+                    Err(e) => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "routeRegistry.registerAssetRoute",
+                            "privilege_lookup_failed",
+                            &format!(
+                                "Unable to verify privileges for '{}': {}",
+                                script_uri_asset, e
+                            ),
+                        ));
                     }
-                } else {
-                    None
                 };
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_execute.clone();
-                let user_id = user_ctx_execute.user_id.clone();
-                let query_clone = query.clone();
-                let script_uri_clone = script_uri_execute.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Medium,
-                                user_id,
-                            )
-                            .with_resource("graphql".to_string())
-                            .with_action("execute".to_string())
-                            .with_detail("script_uri", &script_uri_clone)
-                            .with_detail("query_length", query_clone.len().to_string()),
-                        )
-                        .await;
-                });
-
-                debug!(
-                    user_id = ?user_ctx_execute.user_id,
-                    query_len = query.len(),
-                    has_variables = variables.is_some(),
-                    "Secure executeGraphQL called"
-                );
+                let user_is_admin =
+                    user_ctx_asset.has_capability(&crate::security::Capability::DeleteScripts);
 
-                // Execute the GraphQL query
-                match crate::graphql::execute_graphql_query_sync(&query, variables) {
-                    Ok(result_json) => {
-                        debug!("GraphQL execution successful");
-                        Ok(result_json)
-                    }
-                    Err(e) => {
-                        tracing::error!("GraphQL execution failed: {}", e);
-                        Ok(format!(
-                            "{{\"errors\": [{{\"message\": \"GraphQL execution failed: {}\"}}]}}",
-                            e
-                        ))
-                    }
+                if !script_privileged && !user_is_admin {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "routeRegistry.registerAssetRoute",
+                        "permission_denied",
+                        &format!(
+                            "Script '{}' is not privileged to register asset routes",
+                            script_uri_asset
+                        ),
+                    ));
                 }
-            },
-        )?;
 
-        // Secure sendSubscriptionMessage function
-        let user_ctx_send_sub = user_context.clone();
-        let auditor_send_sub = auditor.clone();
-        let send_subscription_message = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  subscription_name: String,
-                  message: String|
-                  -> JsResult<String> {
                 // Check capability
-                if let Err(e) = user_ctx_send_sub
-                    .require_capability(&crate::security::Capability::ManageGraphQL)
+                if let Err(e) =
+                    user_ctx_asset.require_capability(&crate::security::Capability::WriteAssets)
                 {
-                    // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                    let auditor_clone = auditor_send_sub.clone();
-                    let user_id = user_ctx_send_sub.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "graphql".to_string(),
-                                "send_subscription_message".to_string(),
-                                "ManageGraphQL".to_string(),
-                            )
-                            .await;
-                    });
-                    return Ok(format!("Error: {}", e));
+                    return Ok(format!("Access denied: {}", e));
                 }
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_send_sub.clone();
-                let user_id = user_ctx_send_sub.user_id.clone();
-                let subscription_name_clone = subscription_name.clone();
-                let message_clone = message.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Low,
-                                user_id,
-                            )
-                            .with_resource("graphql".to_string())
-                            .with_action("send_subscription_message".to_string())
-                            .with_detail("subscription_name", &subscription_name_clone)
-                            .with_detail("message_length", message_clone.len().to_string()),
-                        )
-                        .await;
-                });
-
-                debug!(
-                    user_id = ?user_ctx_send_sub.user_id,
-                    subscription_name = %subscription_name,
-                    message_len = message.len(),
-                    "Secure sendSubscriptionMessage called"
-                );
+                // Validate path
+                if !path.starts_with('/') {
+                    return Ok("Path must start with '/'".to_string());
+                }
+                if path.len() > 500 {
+                    return Ok("Path too long (max 500 characters)".to_string());
+                }
 
-                // Send to the auto-registered stream path for this subscription
-                let stream_path = format!("/engine/graphql/subscription/{}", subscription_name);
+                // Validate asset name
+                if asset_name.is_empty() || asset_name.len() > 255 {
+                    return Ok("Invalid asset name: must be 1-255 characters".to_string());
+                }
+                if asset_name.contains("..") || asset_name.contains('\\') {
+                    return Ok("Invalid asset name: path characters not allowed".to_string());
+                }
 
-                // Call actual stream message sending (sync operation)
-                match crate::stream_registry::GLOBAL_STREAM_REGISTRY
-                    .broadcast_to_stream(&stream_path, &message)
-                {
-                    Ok(result) => {
-                        if result.is_fully_successful() {
-                            Ok(format!(
-                                "GraphQL subscription message sent to '{}' ({} connections) successfully",
-                                subscription_name, result.successful_sends
-                            ))
-                        } else {
-                            Ok(format!(
-                                "GraphQL subscription message to '{}' partially sent: {} successful, {} failed out of {} total",
-                                subscription_name,
-                                result.successful_sends,
-                                result.failed_connections.len(),
-                                result.total_connections
-                            ))
+                let headers = match options.0.as_ref().map(read_asset_headers_option) {
+                    Some(Ok(headers)) => headers.unwrap_or_default(),
+                    Some(Err(e)) => return Ok(format!("Invalid asset headers: {}", e)),
+                    None => HashMap::new(),
+                };
+                let versioned = match options.0.as_ref() {
+                    Some(options) => match options.get::<_, Option<bool>>("versioned") {
+                        Ok(versioned) => versioned.unwrap_or(false),
+                        Err(_) => {
+                            return Ok("Invalid versioned option: must be a boolean".to_string());
+                        }
+                    },
+                    None => false,
+                };
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Invalid asset route host: {}", e)),
+                    None => None,
+                };
+                let cache = match options.0.as_ref().map(read_route_cache_option) {
+                    Some(Ok(cache)) => cache,
+                    Some(Err(e)) => return Ok(format!("Invalid asset route cache: {}", e)),
+                    None => None,
+                };
+                let cache_policy = match options.0.as_ref().map(read_cache_policy_option) {
+                    Some(Ok(cache_policy)) => cache_policy,
+                    Some(Err(e)) => return Ok(format!("Invalid asset route cache policy: {}", e)),
+                    None => None,
+                };
+                let private = match options.0.as_ref() {
+                    Some(options) => match options.get::<_, Option<bool>>("private") {
+                        Ok(private) => private.unwrap_or(false),
+                        Err(_) => {
+                            return Ok("Invalid private option: must be a boolean".to_string());
                         }
+                    },
+                    None => false,
+                };
+                if private && cache_policy.is_some() {
+                    return Ok(
+                        "Invalid cachePolicy option: private asset routes are not cached by CDNs"
+                            .to_string(),
+                    );
+                }
+                let image_transforms = match options.0.as_ref().map(read_image_transforms_option) {
+                    Some(Ok(image_transforms)) => image_transforms,
+                    Some(Err(e)) => return Ok(format!("Invalid imageTransforms option: {}", e)),
+                    None => None,
+                };
+                let route_key = crate::route_index::host_scoped_path(host.as_deref(), &path);
+
+                // Verify the asset exists and belongs to this script
+                match repository::fetch_asset(&script_uri_asset, &asset_name) {
+                    Some(_) => {
+                        // Asset exists and belongs to this script, proceed
                     }
-                    Err(e) => Ok(format!(
-                        "Failed to send GraphQL subscription message to '{}': {}",
-                        subscription_name, e
+                    None => {
+                        return Ok(format!(
+                            "Asset '{}' not found or not owned by script '{}'",
+                            asset_name, script_uri_asset
+                        ));
+                    }
+                }
+
+                // Register the path in the global asset registry
+                match crate::asset_registry::get_global_registry().register_path_with_options(
+                    &path,
+                    &asset_name,
+                    &script_uri_asset,
+                    crate::asset_registry::AssetRouteOptions {
+                        headers,
+                        versioned,
+                        host,
+                        cache,
+                        private,
+                        cache_policy,
+                        image_transforms,
+                    },
+                ) {
+                    Ok(()) => Ok(format!(
+                        "Asset path '{}' registered to asset '{}'",
+                        route_key, asset_name
                     )),
+                    Err(e) => Ok(format!("Failed to register asset path: {}", e)),
                 }
             },
         )?;
+        route_registry.set("registerAssetRoute", register_asset_route)?;
 
-        // Secure sendSubscriptionMessageFiltered function (selective broadcasting for GraphQL)
-        let user_ctx_send_sub_filtered = user_context.clone();
-        let auditor_send_sub_filtered = auditor.clone();
-        let send_subscription_message_filtered = Function::new(
+        // registerAssetPrefix: mount every asset under an asset name prefix
+        let user_ctx_asset_prefix = user_context.clone();
+        let script_uri_asset_prefix = script_uri_owned.clone();
+        let register_asset_prefix = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  subscription_name: String,
-                  message: String,
-                  filter_json: Option<String>,
-                  match_mode: Option<String>|
-                  -> JsResult<String> {
-                // Parse filter criteria from JSON string
-                let metadata_filter: HashMap<String, String> = if let Some(json_str) = filter_json {
-                    serde_json::from_str(&json_str).map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "filter",
-                            "MetadataFilter",
-                            &format!("Invalid filter JSON: {}", e),
-                        )
-                    })?
-                } else {
-                    HashMap::new() // Empty filter matches all connections
-                };
-                let match_mode = parse_filter_match_mode(match_mode)?;
-
-                // Allow system-level GraphQL subscription broadcasting without capability checks
-                let is_system_broadcast = true; // GraphQL subscriptions are considered system-level
-
-                if !is_system_broadcast {
-                    // Check capability for non-system operations (future use)
-                    if let Err(e) = user_ctx_send_sub_filtered
-                        .require_capability(&crate::security::Capability::ManageGraphQL)
-                    {
-                        // Use spawn for fire-and-forget audit logging to avoid runtime conflicts
-                        let auditor_clone = auditor_send_sub_filtered.clone();
-                        let user_id = user_ctx_send_sub_filtered.user_id.clone();
-                        tokio::task::spawn(async move {
-                            let _ = auditor_clone
-                                .log_authz_failure(
-                                    user_id,
-                                    "graphql".to_string(),
-                                    "send_subscription_message_to_connections".to_string(),
-                                    "ManageGraphQL".to_string(),
-                                )
-                                .await;
-                        });
-                        return Ok(format!("Error: {}", e));
-                    }
+            move |_c: rquickjs::Ctx<'_>,
+                  prefix: String,
+                  asset_name_prefix: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> Result<String, rquickjs::Error> {
+                let script_privileged =
+                    match repository::is_script_privileged(&script_uri_asset_prefix) {
+                        Ok(privileged) => privileged,
+                        Err(e) => {
+                            return Err(rquickjs::Error::new_from_js_message(
+                                "routeRegistry.registerAssetPrefix",
+                                "privilege_lookup_failed",
+                                &format!(
+                                    "Unable to verify privileges for '{}': {}",
+                                    script_uri_asset_prefix, e
+                                ),
+                            ));
+                        }
+                    };
+                let user_is_admin = user_ctx_asset_prefix
+                    .has_capability(&crate::security::Capability::DeleteScripts);
+                if !script_privileged && !user_is_admin {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "routeRegistry.registerAssetPrefix",
+                        "permission_denied",
+                        &format!(
+                            "Script '{}' is not privileged to register asset routes",
+                            script_uri_asset_prefix
+                        ),
+                    ));
+                }
+                if let Err(e) = user_ctx_asset_prefix
+                    .require_capability(&crate::security::Capability::WriteAssets)
+                {
+                    return Ok(format!("Access denied: {}", e));
                 }
 
-                // Log the operation attempt using spawn to avoid runtime conflicts
-                let auditor_clone = auditor_send_sub_filtered.clone();
-                let user_id = user_ctx_send_sub_filtered.user_id.clone();
-                let subscription_name_clone = subscription_name.clone();
-                let message_clone = message.clone();
-                let filter_clone = metadata_filter.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                SecurityEventType::SystemSecurityEvent,
-                                SecuritySeverity::Low,
-                                user_id,
-                            )
-                            .with_resource("graphql".to_string())
-                            .with_action("send_subscription_message_to_connections".to_string())
-                            .with_detail("subscription_name", &subscription_name_clone)
-                            .with_detail("message_length", message_clone.len().to_string())
-                            .with_detail("filter_criteria", format!("{:?}", filter_clone)),
-                        )
-                        .await;
-                });
-
-                debug!(
-                    user_id = ?user_ctx_send_sub_filtered.user_id,
-                    subscription_name = %subscription_name,
-                    message_len = message.len(),
-                    filter = ?metadata_filter,
-                    match_mode = ?match_mode,
-                    "Secure sendSubscriptionMessageFiltered called"
-                );
-
-                // Send to the auto-registered stream path for this subscription with filtering
-                let stream_path = format!("/engine/graphql/subscription/{}", subscription_name);
+                if !prefix.starts_with('/') {
+                    return Ok("Prefix must start with '/'".to_string());
+                }
+                if prefix.len() > 500 {
+                    return Ok("Prefix too long (max 500 characters)".to_string());
+                }
 
-                // Call selective broadcasting (sync operation)
-                let result = crate::stream_registry::GLOBAL_STREAM_REGISTRY
-                    .broadcast_to_stream_with_filter_mode(
-                        &stream_path,
-                        &message,
-                        &metadata_filter,
-                        match_mode,
+                // Asset names under the prefix are the asset name prefix
+                // followed by the rest of the request path
+                let valid_asset_name = |name: &str| {
+                    !name.is_empty()
+                        && name.len() <= 255
+                        && !name.contains("..")
+                        && !name.contains('\\')
+                };
+                if !valid_asset_name(&asset_name_prefix) {
+                    return Ok(
+                        "Invalid asset name prefix: must be 1-255 characters without path characters"
+                            .to_string(),
                     );
+                }
 
-                match result {
-                    Ok(broadcast_result) => {
-                        if broadcast_result.is_fully_successful() {
-                            Ok(format!(
-                                "GraphQL subscription message sent to '{}' with filter {:?} ({} connections) successfully",
-                                subscription_name,
-                                metadata_filter,
-                                broadcast_result.successful_sends
-                            ))
-                        } else {
-                            Ok(format!(
-                                "GraphQL subscription message to '{}' with filter {:?} partially sent: {} successful, {} failed connections",
-                                subscription_name,
-                                metadata_filter,
-                                broadcast_result.successful_sends,
-                                broadcast_result.failed_connections.len()
-                            ))
+                let headers = match options.0.as_ref().map(read_asset_headers_option) {
+                    Some(Ok(headers)) => headers.unwrap_or_default(),
+                    Some(Err(e)) => return Ok(format!("Invalid asset headers: {}", e)),
+                    None => HashMap::new(),
+                };
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Invalid asset prefix host: {}", e)),
+                    None => None,
+                };
+                let cache = match options.0.as_ref().map(read_route_cache_option) {
+                    Some(Ok(cache)) => cache,
+                    Some(Err(e)) => return Ok(format!("Invalid asset prefix cache: {}", e)),
+                    None => None,
+                };
+                let cache_policy = match options.0.as_ref().map(read_cache_policy_option) {
+                    Some(Ok(cache_policy)) => cache_policy,
+                    Some(Err(e)) => {
+                        return Ok(format!("Invalid asset prefix cache policy: {}", e));
+                    }
+                    None => None,
+                };
+                let mut prefix_options = crate::asset_registry::AssetPrefixOptions {
+                    headers,
+                    host,
+                    cache,
+                    cache_policy,
+                    ..Default::default()
+                };
+                if let Some(options) = options.0.as_ref() {
+                    match options.get::<_, Option<String>>("index") {
+                        Ok(Some(index)) if valid_asset_name(&index) => prefix_options.index = index,
+                        Ok(None) => {}
+                        _ => return Ok("Invalid index option: must be an asset name".to_string()),
+                    }
+                    match options.get::<_, Option<String>>("fallback") {
+                        Ok(Some(fallback)) if valid_asset_name(&fallback) => {
+                            prefix_options.fallback = Some(fallback)
+                        }
+                        Ok(None) => {}
+                        _ => {
+                            return Ok("Invalid fallback option: must be an asset name".to_string());
                         }
                     }
-                    Err(e) => Ok(format!(
-                        "Failed to send GraphQL subscription message to '{}' with filter: {}",
-                        subscription_name, e
+                    match options.get::<_, Option<bool>>("private") {
+                        Ok(private) => prefix_options.private = private.unwrap_or(false),
+                        Err(_) => {
+                            return Ok("Invalid private option: must be a boolean".to_string());
+                        }
+                    }
+                }
+                if prefix_options.private && prefix_options.cache_policy.is_some() {
+                    return Ok(
+                        "Invalid cachePolicy option: private asset prefixes are not cached by CDNs"
+                            .to_string(),
+                    );
+                }
+                let route_key = crate::route_index::host_scoped_path(
+                    prefix_options.host.as_deref(),
+                    &crate::asset_registry::normalize_prefix(&prefix),
+                );
+
+                match crate::asset_registry::get_global_registry().register_prefix(
+                    &prefix,
+                    &asset_name_prefix,
+                    &script_uri_asset_prefix,
+                    prefix_options,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "Asset prefix '{}' registered to assets '{}*'",
+                        route_key, asset_name_prefix
                     )),
+                    Err(e) => Ok(format!("Failed to register asset prefix: {}", e)),
                 }
             },
         )?;
+        route_registry.set("registerAssetPrefix", register_asset_prefix)?;
 
-        // Create graphQLRegistry object with all 6 functions
-        let graphql_registry = rquickjs::Object::new(ctx.clone())?;
-        graphql_registry.set("registerQuery", register_graphql_query)?;
-        graphql_registry.set("registerMutation", register_graphql_mutation)?;
-        graphql_registry.set("registerSubscription", register_graphql_subscription)?;
-        graphql_registry.set("executeGraphQL", execute_graphql)?;
-        graphql_registry.set("sendSubscriptionMessage", send_subscription_message)?;
-        graphql_registry.set(
-            "sendSubscriptionMessageFiltered",
-            send_subscription_message_filtered,
-        )?;
-        global.set("graphQLRegistry", graphql_registry)?;
-
-        Ok(())
-    }
+        // registerUploadRoute: tus resumable upload endpoint
+        let user_ctx_upload = user_context.clone();
+        let script_uri_upload = script_uri_owned.clone();
+        let register_upload_route = Function::new(
+            ctx.clone(),
+            move |_c: rquickjs::Ctx<'_>,
+                  path: String,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> Result<String, rquickjs::Error> {
+                let script_privileged = match repository::is_script_privileged(&script_uri_upload) {
+                    Ok(privileged) => privileged,
+                    Err(e) => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "routeRegistry.registerUploadRoute",
+                            "privilege_lookup_failed",
+                            &format!(
+                                "Unable to verify privileges for '{}': {}",
+                                script_uri_upload, e
+                            ),
+                        ));
+                    }
+                };
+                let user_is_admin =
+                    user_ctx_upload.has_capability(&crate::security::Capability::DeleteScripts);
+                if !script_privileged && !user_is_admin {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "routeRegistry.registerUploadRoute",
+                        "permission_denied",
+                        &format!(
+                            "Script '{}' is not privileged to register upload routes",
+                            script_uri_upload
+                        ),
+                    ));
+                }
 
-    /// Setup MCP (Model Context Protocol) registry functions
-    fn setup_mcp_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let user_context = self.user_context.clone();
-        let auditor = self.auditor.clone();
-        let script_uri_owned = script_uri.to_string();
-        let config = self.config.clone();
+                if !path.starts_with('/') || path.len() < 2 || path.ends_with('/') {
+                    return Ok("Path must start with '/' and not end with '/'".to_string());
+                }
+                if path.len() > 500 {
+                    return Ok("Path too long (max 500 characters)".to_string());
+                }
+                let options = match read_options_object::<crate::tus::UploadRouteOptions>(
+                    options.0,
+                    "upload route options",
+                ) {
+                    Ok(options) => options,
+                    Err(e) => return Ok(e),
+                };
+                if options.on_complete.is_none() && options.asset.is_none() {
+                    return Ok(
+                        "Upload route needs an onComplete handler or an asset prefix".to_string(),
+                    );
+                }
+                if let Some(prefix) = &options.asset
+                    && (prefix.len() > 200 || prefix.contains("..") || prefix.contains('\\'))
+                {
+                    return Ok("Invalid asset prefix".to_string());
+                }
+                if options.max_size == Some(0) {
+                    return Ok("maxSize must be a positive integer".to_string());
+                }
+                let host = match options.host.as_deref() {
+                    Some(host) => match crate::route_index::normalize_host(host) {
+                        Some(host) => Some(host),
+                        None => {
+                            return Ok(format!(
+                                "Invalid upload route host: '{}' is not a valid host name",
+                                host
+                            ));
+                        }
+                    },
+                    None => None,
+                };
 
-        // registerTool function - registers an MCP tool
-        let user_ctx_register = user_context.clone();
-        let auditor_register = auditor.clone();
-        let script_uri_register = script_uri_owned.clone();
-        let register_tool = Function::new(
+                let route_key = crate::route_index::host_scoped_path(host.as_deref(), &path);
+                crate::tus::register_route(
+                    route_key.clone(),
+                    crate::tus::UploadRoute {
+                        script_uri: script_uri_upload.clone(),
+                        handler_name: options.on_complete,
+                        asset_prefix: options.asset,
+                        max_size: options.max_size,
+                    },
+                );
+                Ok(format!("Upload route '{}' registered", route_key))
+            },
+        )?;
+        route_registry.set("registerUploadRoute", register_upload_route)?;
+
+        // resolveAssetUrl: current URL of an asset route, content-hashed for
+        // versioned routes so pages can reference it with immutable caching
+        let resolve_asset_url = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  name: String,
-                  description: String,
-                  input_schema_json: String,
-                  handler_function: String|
+                  path: String,
+                  options: Opt<rquickjs::Object<'_>>|
                   -> JsResult<String> {
-                // Check if MCP is enabled (we can use the same config flag as GraphQL for now)
-                if !config.enable_graphql_registration {
-                    debug!(
-                        "MCP tool registration disabled, skipping tool registration for: {}",
-                        name
-                    );
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Error: Invalid host: {}", e)),
+                    None => None,
+                };
+                let route_key = crate::route_index::host_scoped_path(host.as_deref(), &path);
+                let Some(registration) =
+                    crate::asset_registry::get_global_registry().get_asset_registration(&route_key)
+                else {
                     return Ok(format!(
-                        "MCP tool '{}' registration skipped (disabled)",
-                        name
+                        "Error: No asset route registered for path '{}'",
+                        route_key
                     ));
+                };
+                if !registration.versioned {
+                    return Ok(path);
                 }
-
-                // Check capability - reuse ManageGraphQL for MCP tools
-                if let Err(e) = user_ctx_register
-                    .require_capability(&crate::security::Capability::ManageGraphQL)
-                {
-                    let auditor_clone = auditor_register.clone();
-                    let user_id = user_ctx_register.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "mcp".to_string(),
-                                "register_tool".to_string(),
-                                "ManageGraphQL".to_string(),
-                            )
-                            .await;
-                    });
-                    return Ok(format!("Error: {}", e));
+                match repository::fetch_asset(&registration.script_uri, &registration.asset_name) {
+                    Some(asset) => Ok(crate::asset_registry::versioned_path(
+                        &path,
+                        &crate::asset_registry::content_hash(&asset.content),
+                    )),
+                    None => Ok(format!(
+                        "Error: Asset '{}' registered for path '{}' not found",
+                        registration.asset_name, path
+                    )),
                 }
+            },
+        )?;
+        route_registry.set("resolveAssetUrl", resolve_asset_url)?;
 
-                // Validate inputs
-                if name.is_empty() || name.len() > 100 {
-                    return Ok(
-                        "Invalid tool name: must be between 1 and 100 characters".to_string()
-                    );
-                }
-                if description.is_empty() || description.len() > 1000 {
-                    return Ok(
-                        "Invalid description: must be between 1 and 1000 characters".to_string()
-                    );
+        // 4. sendStreamMessage function
+        let user_ctx_send = user_context.clone();
+        let auditor_send = auditor.clone();
+        let send_stream_message = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  path: String,
+                  message: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("routeRegistry.sendStreamMessage") {
+                    return Ok(format!("Error: {}", e));
                 }
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Error: Invalid stream host: {}", e)),
+                    None => None,
+                };
+                let path = crate::route_index::host_scoped_path(host.as_deref(), &path);
 
-                // Parse and validate input schema JSON
-                let input_schema: serde_json::Value = serde_json::from_str(&input_schema_json)
-                    .map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "schema",
-                            "InputSchema",
-                            &format!("Invalid input schema JSON: {}", e),
-                        )
-                    })?;
+                // Allow system-level broadcasting without capability checks for certain paths
+                let is_system_broadcast = path == "/script_updates" || path.starts_with("/system/");
 
-                // Check for dangerous patterns
-                if input_schema_json.contains("__proto__")
-                    || input_schema_json.contains("constructor")
-                {
-                    return Ok("Invalid schema: contains dangerous patterns".to_string());
+                if !is_system_broadcast {
+                    // Check capability for non-system operations
+                    if let Err(e) = user_ctx_send
+                        .require_capability(&crate::security::Capability::ManageStreams)
+                    {
+                        let auditor_clone = auditor_send.clone();
+                        let user_id = user_ctx_send.user_id.clone();
+                        tokio::task::spawn(async move {
+                            let _ = auditor_clone
+                                .log_event(
+                                    crate::security::SecurityEvent::new(
+                                        crate::security::SecurityEventType::AuthorizationFailure,
+                                        crate::security::SecuritySeverity::Medium,
+                                        user_id,
+                                    )
+                                    .with_resource("stream".to_string())
+                                    .with_action("send_message".to_string()),
+                                )
+                                .await;
+                        });
+                        return Ok(format!("Error: {}", e));
+                    }
                 }
 
                 // Log the operation attempt
-                let auditor_clone = auditor_register.clone();
-                let user_id = user_ctx_register.user_id.clone();
-                let name_clone = name.clone();
-                let script_uri_clone = script_uri_register.clone();
+                let auditor_clone = auditor_send.clone();
+                let user_id = user_ctx_send.user_id.clone();
+                let path_clone = path.clone();
+                let message_clone = message.clone();
                 tokio::task::spawn(async move {
                     let _ = auditor_clone
                         .log_event(
                             crate::security::SecurityEvent::new(
                                 crate::security::SecurityEventType::SystemSecurityEvent,
-                                crate::security::SecuritySeverity::Medium,
+                                crate::security::SecuritySeverity::Low,
                                 user_id,
                             )
-                            .with_resource("mcp".to_string())
-                            .with_action("register_tool".to_string())
-                            .with_detail("tool_name", &name_clone)
-                            .with_detail("script_uri", &script_uri_clone),
+                            .with_resource("stream".to_string())
+                            .with_action("send_message".to_string())
+                            .with_detail("path", &path_clone)
+                            .with_detail("message_length", message_clone.len().to_string()),
                         )
                         .await;
                 });
 
-                debug!(
-                    user_id = ?user_ctx_register.user_id,
-                    name = %name,
-                    "Secure registerTool called for MCP"
-                );
-
-                // Actually register the MCP tool
-                crate::mcp::register_mcp_tool(
-                    name.clone(),
-                    description,
-                    input_schema,
-                    handler_function,
-                    script_uri_register.clone(),
-                );
-
-                Ok(format!("MCP tool '{}' registered successfully", name))
+                // Send the message
+                match crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                    .broadcast_to_stream(&path, &message)
+                {
+                    Ok(result) => {
+                        if result.is_fully_successful() {
+                            Ok(format!(
+                                "Successfully sent message to {} connections on path '{}'",
+                                result.successful_sends, path
+                            ))
+                        } else {
+                            Ok(format!(
+                                "Sent message to {}/{} connections on path '{}' ({} failed)",
+                                result.successful_sends,
+                                result.total_connections,
+                                path,
+                                result.failed_connections.len()
+                            ))
+                        }
+                    }
+                    Err(e) => Ok(format!("Failed to send message to path '{}': {}", path, e)),
+                }
             },
         )?;
+        route_registry.set("sendStreamMessage", send_stream_message)?;
 
-        // registerPrompt function - registers an MCP prompt
-        let user_ctx_prompt = user_context.clone();
-        let auditor_prompt = auditor.clone();
-        let script_uri_prompt = script_uri_owned.clone();
-        let config_prompt = config.clone();
-        let register_prompt = Function::new(
+        // 5. sendStreamMessageFiltered function
+        let user_ctx_filtered = user_context.clone();
+        let auditor_filtered = auditor.clone();
+        let send_stream_message_filtered = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  name: String,
-                  description: String,
-                  arguments_json: String,
-                  handler_function: String|
+                  path: String,
+                  message: String,
+                  filter_json: Option<String>,
+                  match_mode: Opt<Option<String>>,
+                  options: Opt<rquickjs::Object<'_>>|
                   -> JsResult<String> {
-                // Check if MCP is enabled
-                if !config_prompt.enable_graphql_registration {
-                    debug!(
-                        "MCP prompt registration disabled, skipping prompt registration for: {}",
-                        name
-                    );
-                    return Ok(format!(
-                        "MCP prompt '{}' registration skipped (disabled)",
-                        name
-                    ));
-                }
-
-                // Check capability - reuse ManageGraphQL for MCP prompts
                 if let Err(e) =
-                    user_ctx_prompt.require_capability(&crate::security::Capability::ManageGraphQL)
+                    crate::dry_run::ensure_allowed("routeRegistry.sendStreamMessageFiltered")
                 {
-                    let auditor_clone = auditor_prompt.clone();
-                    let user_id = user_ctx_prompt.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_authz_failure(
-                                user_id,
-                                "mcp".to_string(),
-                                "register_prompt".to_string(),
-                                "ManageGraphQL".to_string(),
-                            )
-                            .await;
-                    });
                     return Ok(format!("Error: {}", e));
                 }
+                let match_mode = match_mode.0.flatten();
+                let host = match options.0.as_ref().map(read_host_option) {
+                    Some(Ok(host)) => host,
+                    Some(Err(e)) => return Ok(format!("Error: Invalid stream host: {}", e)),
+                    None => None,
+                };
+                let path = crate::route_index::host_scoped_path(host.as_deref(), &path);
 
-                // Validate inputs
-                if name.is_empty() || name.len() > 100 {
-                    return Ok(
-                        "Invalid prompt name: must be between 1 and 100 characters".to_string()
-                    );
-                }
-                if description.is_empty() || description.len() > 1000 {
-                    return Ok(
-                        "Invalid description: must be between 1 and 1000 characters".to_string()
-                    );
-                }
-                if handler_function.is_empty() || handler_function.len() > 100 {
-                    return Ok(
-                        "Invalid handler function: must be between 1 and 100 characters"
-                            .to_string(),
-                    );
-                }
+                // Parse filter criteria
+                let metadata_filter: HashMap<String, String> = if let Some(json_str) = filter_json {
+                    serde_json::from_str(&json_str).map_err(|e| {
+                        rquickjs::Error::new_from_js_message(
+                            "filter",
+                            "MetadataFilter",
+                            &format!("Invalid filter JSON: {}", e),
+                        )
+                    })?
+                } else {
+                    HashMap::new()
+                };
+                let match_mode = parse_filter_match_mode(match_mode)?;
 
-                // Validate arguments JSON
-                if arguments_json.contains("__proto__") || arguments_json.contains("constructor") {
-                    return Ok("Invalid arguments: contains dangerous patterns".to_string());
+                // Allow system-level broadcasting for certain paths
+                let is_system_broadcast = path == "/script_updates" || path.starts_with("/system/");
+
+                if !is_system_broadcast
+                    && let Err(e) = user_ctx_filtered
+                        .require_capability(&crate::security::Capability::ManageStreams)
+                {
+                    let auditor_clone = auditor_filtered.clone();
+                    let user_id = user_ctx_filtered.user_id.clone();
+                    tokio::task::spawn(async move {
+                        let _ = auditor_clone
+                            .log_event(
+                                crate::security::SecurityEvent::new(
+                                    crate::security::SecurityEventType::AuthorizationFailure,
+                                    crate::security::SecuritySeverity::Medium,
+                                    user_id,
+                                )
+                                .with_resource("stream".to_string())
+                                .with_action("send_filtered_message".to_string()),
+                            )
+                            .await;
+                    });
+                    return Ok(format!("Error: {}", e));
                 }
 
-                // Log the operation attempt
-                let auditor_clone = auditor_prompt.clone();
-                let user_id = user_ctx_prompt.user_id.clone();
-                let name_clone = name.clone();
-                let script_uri_clone = script_uri_prompt.clone();
-                let handler_clone = handler_function.clone();
+                // Log the operation
+                let auditor_clone = auditor_filtered.clone();
+                let user_id = user_ctx_filtered.user_id.clone();
+                let path_clone = path.clone();
+                let message_clone = message.clone();
+                let filter_clone = metadata_filter.clone();
                 tokio::task::spawn(async move {
                     let _ = auditor_clone
                         .log_event(
                             crate::security::SecurityEvent::new(
                                 crate::security::SecurityEventType::SystemSecurityEvent,
-                                crate::security::SecuritySeverity::Medium,
+                                crate::security::SecuritySeverity::Low,
                                 user_id,
                             )
-                            .with_resource("mcp".to_string())
-                            .with_action("register_prompt".to_string())
-                            .with_detail("prompt_name", &name_clone)
-                            .with_detail("handler", &handler_clone)
-                            .with_detail("script_uri", &script_uri_clone),
+                            .with_resource("stream".to_string())
+                            .with_action("send_filtered_message".to_string())
+                            .with_detail("path", &path_clone)
+                            .with_detail("message_length", message_clone.len().to_string())
+                            .with_detail("filter_criteria_count", filter_clone.len().to_string()),
                         )
                         .await;
                 });
 
-                debug!(
-                    user_id = ?user_ctx_prompt.user_id,
-                    name = %name,
-                    handler = %handler_function,
-                    "Secure registerPrompt called for MCP"
-                );
+                // Send filtered message
+                let result = crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                    .broadcast_to_stream_with_filter_mode(
+                        &path,
+                        &message,
+                        &metadata_filter,
+                        match_mode,
+                    );
 
-                // Actually register the MCP prompt
-                match crate::mcp::register_mcp_prompt(
-                    name.clone(),
-                    description,
-                    arguments_json,
-                    handler_function.clone(),
-                    script_uri_prompt.clone(),
-                ) {
-                    Ok(_) => Ok(format!(
-                        "MCP prompt '{}' registered successfully with handler '{}'",
-                        name, handler_function
+                match result {
+                    Ok(broadcast_result) => {
+                        if broadcast_result.is_fully_successful() {
+                            Ok(format!(
+                                "Successfully sent filtered message to {} connections on path '{}'",
+                                broadcast_result.successful_sends, path
+                            ))
+                        } else {
+                            Ok(format!(
+                                "Sent filtered message to {}/{} connections on path '{}' ({} failed)",
+                                broadcast_result.successful_sends,
+                                broadcast_result.total_connections,
+                                path,
+                                broadcast_result.failed_connections.len()
+                            ))
+                        }
+                    }
+                    Err(e) => Ok(format!(
+                        "Failed to send filtered message to path '{}': {}",
+                        path, e
                     )),
-                    Err(e) => Ok(format!("Error registering prompt: {}", e)),
                 }
             },
         )?;
+        route_registry.set("sendStreamMessageFiltered", send_stream_message_filtered)?;
 
-        // Create mcpRegistry object
-        let mcp_registry = rquickjs::Object::new(ctx.clone())?;
-        mcp_registry.set("registerTool", register_tool)?;
-        mcp_registry.set("registerPrompt", register_prompt)?;
-        global.set("mcpRegistry", mcp_registry)?;
-
-        // Setup McpClient class for connecting to external MCP servers
-        self.setup_mcp_client_class(ctx, script_uri)?;
+        // 6. listRoutes function
+        let user_ctx_list_routes = user_context.clone();
+        let list_routes = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check capability
+                if let Err(_e) = user_ctx_list_routes
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok("[]".to_string());
+                }
 
-        Ok(())
-    }
+                // Get all script metadata
+                match repository::get_all_script_metadata() {
+                    Ok(metadata_list) => {
+                        let mut all_routes = Vec::new();
+                        for metadata in metadata_list {
+                            if metadata.initialized && !metadata.registrations.is_empty() {
+                                for ((path, method), route_meta) in metadata.registrations {
+                                    all_routes.push(serde_json::json!({
+                                        "path": path,
+                                        "method": method,
+                                        "handler": route_meta.handler_name,
+                                        "script_uri": metadata.uri,
+                                        "summary": route_meta.summary,
+                                        "description": route_meta.description,
+                                        "tags": route_meta.tags,
+                                        "hosts": route_meta.hosts,
+                                        "rateLimit": route_meta.rate_limit,
+                                        "idempotency": route_meta.idempotency,
+                                        "timeoutMs": route_meta.timeout_ms,
+                                    }));
+                                }
+                            }
+                        }
 
-    /// Setup McpClient class for external MCP server connections
-    fn setup_mcp_client_class(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let script_uri_owned = script_uri.to_string();
-        // Capture user_id for secret resolution (user_secrets first, then script_secrets)
-        let user_id_for_mcp = self.user_context.user_id.clone();
+                        if let Ok(stream_paths) =
+                            crate::stream_registry::GLOBAL_STREAM_REGISTRY.list_stream_paths()
+                        {
+                            for key in stream_paths {
+                                if let Some((script_uri, customization_function)) =
+                                    crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                                        .get_stream_info(&key)
+                                {
+                                    let (host, path) =
+                                        crate::route_index::split_host_scoped_path(&key);
+                                    all_routes.push(serde_json::json!({
+                                        "path": path,
+                                        "hosts": host.into_iter().collect::<Vec<_>>(),
+                                        "method": "STREAM",
+                                        "handler": customization_function,
+                                        "script_uri": script_uri,
+                                        "summary": serde_json::Value::Null,
+                                        "description": serde_json::Value::Null,
+                                        "tags": Vec::<String>::new(),
+                                    }));
+                                }
+                            }
+                        }
 
-        // McpClient constructor
-        let mcp_client_constructor = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  server_url: String,
-                  secret_identifier: String|
-                  -> JsResult<String> {
-                // Create MCP client instance (just validate parameters)
-                let _client = crate::mcp_client::McpClient::new(
-                    server_url.clone(),
-                    secret_identifier.clone(),
-                )
-                .map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "constructor",
-                        &format!("Failed to create MCP client: {}", e),
-                    )
-                })?;
+                        for (key, registration) in
+                            crate::asset_registry::get_global_registry().get_all_registrations()
+                        {
+                            let (host, path) = crate::route_index::split_host_scoped_path(&key);
+                            all_routes.push(serde_json::json!({
+                                "path": path,
+                                "hosts": host.into_iter().collect::<Vec<_>>(),
+                                "method": "ASSET",
+                                "handler": registration.asset_name,
+                                "script_uri": registration.script_uri,
+                                "summary": serde_json::Value::Null,
+                                "description": serde_json::Value::Null,
+                                "tags": vec!["Assets"],
+                            }));
+                        }
 
-                // Serialize client to JSON (we'll store server_url and secret_identifier)
-                let client_data = serde_json::json!({
-                    "serverUrl": server_url,
-                    "secretIdentifier": secret_identifier,
-                });
+                        for (key, registration) in crate::asset_registry::get_global_registry()
+                            .get_all_prefix_registrations()
+                        {
+                            let (host, prefix) = crate::route_index::split_host_scoped_path(&key);
+                            all_routes.push(serde_json::json!({
+                                "path": format!("{}*", prefix),
+                                "hosts": host.into_iter().collect::<Vec<_>>(),
+                                "method": "ASSET",
+                                "handler": format!("{}*", registration.asset_name_prefix),
+                                "script_uri": registration.script_uri,
+                                "summary": serde_json::Value::Null,
+                                "description": serde_json::Value::Null,
+                                "tags": vec!["Assets"],
+                            }));
+                        }
 
-                Ok(serde_json::to_string(&client_data).unwrap())
+                        match serde_json::to_string(&all_routes) {
+                            Ok(json) => Ok(json),
+                            Err(e) => Ok(format!("Error serializing routes: {}", e)),
+                        }
+                    }
+                    Err(e) => Ok(format!("Error fetching routes: {}", e)),
+                }
             },
         )?;
+        route_registry.set("listRoutes", list_routes)?;
+
+        // 6b. generateOpenApi function
+        let user_ctx_openapi = user_context.clone();
+        let generate_openapi = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check capability
+                if let Err(_e) =
+                    user_ctx_openapi.require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok("{\"error\": \"Insufficient permissions\"}".to_string());
+                }
+
+                // Get Rust-generated OpenAPI spec (includes health, GraphQL, MCP, auth endpoints)
+                let rust_spec_str = crate::get_rust_openapi_spec();
+                let mut rust_spec: serde_json::Value = match serde_json::from_str(&rust_spec_str) {
+                    Ok(spec) => spec,
+                    Err(e) => {
+                        return Ok(format!(
+                            "{{\"error\": \"Failed to parse Rust OpenAPI spec: {}\"}}",
+                            e
+                        ));
+                    }
+                };
+
+                // Get JavaScript-registered routes from script metadata
+                match repository::get_all_script_metadata() {
+                    Ok(metadata_list) => {
+                        let mut js_paths = serde_json::Map::new();
+
+                        for metadata in metadata_list {
+                            if metadata.initialized && !metadata.registrations.is_empty() {
+                                for ((path, method), route_meta) in metadata.registrations {
+                                    // Get or create path item
+                                    let path_item = js_paths
+                                        .entry(path.clone())
+                                        .or_insert_with(|| serde_json::json!({}));
+
+                                    let path_obj = path_item.as_object_mut().unwrap();
+
+                                    // Create operation object
+                                    let mut operation = serde_json::Map::new();
+                                    operation.insert(
+                                        "summary".to_string(),
+                                        serde_json::json!(
+                                            route_meta
+                                                .summary
+                                                .unwrap_or_else(|| format!("{} {}", method, path))
+                                        ),
+                                    );
+
+                                    if let Some(desc) = route_meta.description {
+                                        operation.insert(
+                                            "description".to_string(),
+                                            serde_json::json!(desc),
+                                        );
+                                    }
+
+                                    if !route_meta.tags.is_empty() {
+                                        operation.insert(
+                                            "tags".to_string(),
+                                            serde_json::json!(route_meta.tags),
+                                        );
+                                    } else {
+                                        operation
+                                            .insert("tags".to_string(), serde_json::json!(["API"]));
+                                    }
 
-        // Create McpClient class object with constructor and prototype
-        let mcp_client_class = rquickjs::Object::new(ctx.clone())?;
+                                    // Add parameters if present, otherwise describe
+                                    // the route's query and path parameter schemas
+                                    if let Some(params) = &route_meta.parameters {
+                                        operation.insert("parameters".to_string(), params.clone());
+                                    } else if let Some(schema) = &route_meta.schema {
+                                        let params = schema.openapi_parameters();
+                                        if !params.is_empty() {
+                                            operation.insert(
+                                                "parameters".to_string(),
+                                                serde_json::json!(params),
+                                            );
+                                        }
+                                    }
 
-        // listTools method
-        let script_uri_list = script_uri_owned.clone();
-        let user_id_list = user_id_for_mcp.clone();
-        let list_tools = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, client_data_json: String| -> JsResult<String> {
-                // Parse client data
-                let client_data: serde_json::Value = serde_json::from_str(&client_data_json)
-                    .map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "listTools",
-                            &format!("Invalid client data: {}", e),
-                        )
-                    })?;
+                                    // Add requestBody if present, otherwise describe
+                                    // the route's body schema
+                                    if let Some(body) =
+                                        route_meta.request_body.clone().or_else(|| {
+                                            route_meta
+                                                .schema
+                                                .as_ref()
+                                                .and_then(|s| s.openapi_request_body())
+                                        })
+                                    {
+                                        operation.insert("requestBody".to_string(), body);
+                                    }
 
-                let server_url = client_data["serverUrl"].as_str().ok_or_else(|| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "listTools",
-                        "Missing serverUrl in client data",
-                    )
-                })?;
+                                    // Default response, plus the validation failure
+                                    // of routes with request schemas
+                                    let mut responses = serde_json::json!({
+                                        "200": {
+                                            "description": "Success"
+                                        }
+                                    });
+                                    if route_meta.schema.is_some() {
+                                        responses["400"] = serde_json::json!({
+                                            "description": "Request does not match the route's schema"
+                                        });
+                                    }
+                                    operation.insert("responses".to_string(), responses);
 
-                let secret_identifier =
-                    client_data["secretIdentifier"].as_str().ok_or_else(|| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "listTools",
-                            "Missing secretIdentifier in client data",
-                        )
-                    })?;
+                                    // Add operation metadata
+                                    operation.insert(
+                                        "x-handler".to_string(),
+                                        serde_json::json!(route_meta.handler_name),
+                                    );
+                                    operation.insert(
+                                        "x-script-uri".to_string(),
+                                        serde_json::json!(metadata.uri),
+                                    );
+                                    operation.insert(
+                                        "x-source".to_string(),
+                                        serde_json::json!("javascript"),
+                                    );
 
-                // Create client
-                let client = crate::mcp_client::McpClient::new(
-                    server_url.to_string(),
-                    secret_identifier.to_string(),
-                )
-                .map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "listTools",
-                        &format!("Failed to create client: {}", e),
-                    )
-                })?;
+                                    path_obj.insert(
+                                        method.to_lowercase(),
+                                        serde_json::json!(operation),
+                                    );
+                                }
+                            }
+                        }
 
-                // List tools
-                let tools = client
-                    .list_tools(&script_uri_list, user_id_list.as_deref())
-                    .map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "listTools",
-                            &format!("Failed to list tools: {}", e),
-                        )
-                    })?;
+                        // Add asset routes from the asset registry
+                        let asset_registrations =
+                            crate::asset_registry::get_global_registry().get_all_registrations();
 
-                // Serialize tools to JSON
-                serde_json::to_string(&tools).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "listTools",
-                        &format!("Failed to serialize tools: {}", e),
-                    )
-                })
-            },
-        )?;
+                        for (path, registration) in asset_registrations {
+                            // Determine MIME type based on file extension
+                            let extension = path.rsplit('.').next().unwrap_or("");
+                            let mime_type = match extension {
+                                "css" => "text/css",
+                                "js" => "application/javascript",
+                                "svg" => "image/svg+xml",
+                                "png" => "image/png",
+                                "jpg" | "jpeg" => "image/jpeg",
+                                "gif" => "image/gif",
+                                "ico" => "image/x-icon",
+                                "html" => "text/html",
+                                "json" => "application/json",
+                                "xml" => "application/xml",
+                                "pdf" => "application/pdf",
+                                "woff" | "woff2" => "font/woff2",
+                                "ttf" => "font/ttf",
+                                _ => "application/octet-stream",
+                            };
 
-        // callTool method
-        let script_uri_call = script_uri_owned.clone();
-        let user_id_call = user_id_for_mcp.clone();
-        let call_tool = Function::new(
-            ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  client_data_json: String,
-                  tool_name: String,
-                  arguments_json: String|
-                  -> JsResult<String> {
-                // Parse client data
-                let client_data: serde_json::Value = serde_json::from_str(&client_data_json)
-                    .map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "callTool",
-                            &format!("Invalid client data: {}", e),
-                        )
-                    })?;
+                            let mut asset_operation = serde_json::Map::new();
+                            asset_operation.insert(
+                                "summary".to_string(),
+                                serde_json::json!(format!(
+                                    "Static asset: {}",
+                                    registration.asset_name
+                                )),
+                            );
+                            asset_operation.insert(
+                                "description".to_string(),
+                                serde_json::json!(format!(
+                                    "Serves static asset '{}' registered by script '{}'",
+                                    registration.asset_name, registration.script_uri
+                                )),
+                            );
+                            asset_operation
+                                .insert("tags".to_string(), serde_json::json!(["Assets"]));
+                            asset_operation.insert(
+                                "responses".to_string(),
+                                serde_json::json!({
+                                    "200": {
+                                        "description": "Asset content",
+                                        "content": {
+                                            mime_type: {
+                                                "schema": {
+                                                    "type": "string",
+                                                    "format": "binary"
+                                                }
+                                            }
+                                        }
+                                    },
+                                    "404": {
+                                        "description": "Asset not found"
+                                    }
+                                }),
+                            );
+                            asset_operation.insert(
+                                "x-asset-name".to_string(),
+                                serde_json::json!(registration.asset_name),
+                            );
+                            asset_operation.insert(
+                                "x-script-uri".to_string(),
+                                serde_json::json!(registration.script_uri),
+                            );
+                            asset_operation.insert(
+                                "x-source".to_string(),
+                                serde_json::json!("asset-registry"),
+                            );
 
-                let server_url = client_data["serverUrl"].as_str().ok_or_else(|| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "callTool",
-                        "Missing serverUrl in client data",
-                    )
-                })?;
+                            // Add to js_paths so it gets merged
+                            let path_entry = js_paths
+                                .entry(path)
+                                .or_insert_with(|| serde_json::json!({}));
 
-                let secret_identifier =
-                    client_data["secretIdentifier"].as_str().ok_or_else(|| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "callTool",
-                            "Missing secretIdentifier in client data",
-                        )
-                    })?;
+                            if let Some(path_obj) = path_entry.as_object_mut() {
+                                path_obj
+                                    .insert("get".to_string(), serde_json::json!(asset_operation));
+                            }
+                        }
 
-                // Parse arguments
-                let arguments: serde_json::Value =
-                    serde_json::from_str(&arguments_json).map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "callTool",
-                            &format!("Invalid arguments JSON: {}", e),
-                        )
-                    })?;
+                        // Merge JavaScript paths into Rust spec
+                        if let Some(rust_paths) = rust_spec["paths"].as_object_mut() {
+                            for (path, operations) in js_paths {
+                                // If path exists in both, merge operations
+                                if let Some(existing) = rust_paths.get_mut(&path) {
+                                    if let (Some(existing_obj), Some(new_ops)) =
+                                        (existing.as_object_mut(), operations.as_object())
+                                    {
+                                        for (method, operation) in new_ops {
+                                            existing_obj.insert(method.clone(), operation.clone());
+                                        }
+                                    }
+                                } else {
+                                    // Path doesn't exist in Rust spec, add it
+                                    rust_paths.insert(path, operations);
+                                }
+                            }
+                        }
 
-                // Create client
-                let client = crate::mcp_client::McpClient::new(
-                    server_url.to_string(),
-                    secret_identifier.to_string(),
-                )
-                .map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "callTool",
-                        &format!("Failed to create client: {}", e),
-                    )
-                })?;
+                        // Collect all unique tags
+                        let mut all_tags = std::collections::HashSet::new();
+                        if let Some(paths) = rust_spec["paths"].as_object() {
+                            for operations in paths.values() {
+                                if let Some(ops) = operations.as_object() {
+                                    for operation in ops.values() {
+                                        if let Some(tags) = operation["tags"].as_array() {
+                                            for tag in tags {
+                                                if let Some(tag_str) = tag.as_str() {
+                                                    all_tags.insert(tag_str.to_string());
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
 
-                // Call tool
-                let result = match client.call_tool(
-                    tool_name.clone(),
-                    arguments,
-                    &script_uri_call,
-                    user_id_call.as_deref(),
-                ) {
-                    Ok(res) => res,
-                    Err(e) => {
-                        // For JSON-RPC errors, return them as {error: {...}} objects
-                        if let crate::mcp_client::McpClientError::JsonRpc(code, message) = e {
-                            let error_obj = serde_json::json!({
-                                "error": {
-                                    "code": code,
-                                    "message": message
+                        // Update tags in the spec if there are new ones from JavaScript
+                        if let Some(existing_tags) = rust_spec["tags"].as_array() {
+                            for tag_obj in existing_tags {
+                                if let Some(name) = tag_obj["name"].as_str() {
+                                    all_tags.insert(name.to_string());
                                 }
-                            });
-                            return Ok(serde_json::to_string(&error_obj).unwrap());
+                            }
                         }
 
-                        // For other errors, throw JavaScript exceptions
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "McpClient",
-                            "callTool",
-                            &format!("Failed to call tool '{}': {}", tool_name, e),
-                        ));
+                        // Serialize the merged spec
+                        match serde_json::to_string_pretty(&rust_spec) {
+                            Ok(json) => Ok(json),
+                            Err(e) => Ok(format!(
+                                "{{\"error\": \"Failed to serialize merged OpenAPI spec: {}\"}}",
+                                e
+                            )),
+                        }
                     }
-                };
+                    Err(e) => Ok(format!(
+                        "{{\"error\": \"Failed to fetch JavaScript routes: {}\"}}",
+                        e
+                    )),
+                }
+            },
+        )?;
+        route_registry.set("generateOpenApi", generate_openapi)?;
 
-                // Serialize result to JSON
-                serde_json::to_string(&result).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "McpClient",
-                        "callTool",
-                        &format!("Failed to serialize result: {}", e),
-                    )
-                })
+        // 7. listStreams function
+        let user_ctx_list_streams = user_context.clone();
+        let list_streams = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check capability
+                if let Err(_e) = user_ctx_list_streams
+                    .require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok("[]".to_string());
+                }
+
+                // Get streams with metadata
+                match crate::stream_registry::GLOBAL_STREAM_REGISTRY.list_streams_with_metadata() {
+                    Ok(streams) => {
+                        let stream_objects: Vec<serde_json::Value> = streams
+                            .iter()
+                            .map(|(key, script_uri)| {
+                                let (host, path) = crate::route_index::split_host_scoped_path(key);
+                                serde_json::json!({
+                                    "path": path,
+                                    "host": host,
+                                    "script_uri": script_uri,
+                                })
+                            })
+                            .collect();
+
+                        match serde_json::to_string(&stream_objects) {
+                            Ok(json) => Ok(json),
+                            Err(e) => Ok(format!("Error serializing streams: {}", e)),
+                        }
+                    }
+                    Err(e) => Ok(format!("Error fetching streams: {}", e)),
+                }
             },
         )?;
+        route_registry.set("listStreams", list_streams)?;
 
-        // Set methods on the class
-        mcp_client_class.set("constructor", mcp_client_constructor)?;
-        mcp_client_class.set("_listTools", list_tools)?;
-        mcp_client_class.set("_callTool", call_tool)?;
+        // 8. listAssets function
+        let user_ctx_list_assets = user_context.clone();
+        let list_assets = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<Vec<String>> {
+                // Check capability
+                if let Err(_e) = user_ctx_list_assets
+                    .require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(Vec::new());
+                }
 
-        // Set the class on global scope
-        global.set("McpClient", mcp_client_class)?;
+                let assets = repository::fetch_assets("https://example.com/core");
+                let asset_names: Vec<String> = assets.keys().cloned().collect();
+                Ok(asset_names)
+            },
+        )?;
+        route_registry.set("listAssets", list_assets)?;
 
-        debug!("McpClient class initialized for external MCP server connections");
+        // Set the routeRegistry object on global scope
+        global.set("routeRegistry", route_registry)?;
 
         Ok(())
     }
 
-    /// Setup routeRegistry object with all route-related functions
-    fn setup_route_registry(
-        &self,
-        ctx: &rquickjs::Ctx<'_>,
-        script_uri: &str,
-        register_fn: Option<RouteRegisterFn>,
-    ) -> JsResult<()> {
+    /// Setup fetch() function for HTTP requests with secret injection, and
+    /// fetchAsync() running requests concurrently with the execution (see
+    /// [`crate::event_loop`])
+    fn setup_fetch_function(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
         let global = ctx.globals();
-        let user_context = self.user_context.clone();
-        let auditor = self.auditor.clone();
         let script_uri_owned = script_uri.to_string();
-        let config = self.config.clone();
+        // Capture the user_id at script setup time for secret lookup in user_secrets
+        let user_id_for_fetch = self.user_context.user_id.clone();
 
-        // Create the routeRegistry object
-        let route_registry = rquickjs::Object::new(ctx.clone())?;
+        // Create the fetch function (synchronous version)
+        let fetch_fn = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  url: String,
+                  options_json: Option<String>|
+                  -> JsResult<String> {
+                let options = prepare_fetch_options(options_json)?;
+                tracing::debug!("Fetching URL: {} from script: {}", url, script_uri_owned);
+                perform_fetch(
+                    url,
+                    options,
+                    &script_uri_owned,
+                    user_id_for_fetch.as_deref(),
+                )
+                .map_err(|(kind, message)| {
+                    rquickjs::Error::new_from_js_message("fetch", kind, &message)
+                })
+            },
+        )?;
 
-        // 1. registerRoute function
-        if let Some(register_impl) = register_fn {
-            let script_uri_for_register = script_uri_owned.clone();
-            let user_ctx_route = user_context.clone();
-            let register_route = Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>,
-                      path: String,
-                      handler: String,
-                      method: Option<String>,
-                      metadata: Opt<rquickjs::Object>|
-                      -> Result<(), rquickjs::Error> {
-                    // Check if script is privileged OR user has admin privileges
-                    let script_privileged =
-                        match repository::is_script_privileged(&script_uri_for_register) {
-                            Ok(true) => true,
-                            Ok(false) => false,
-                            Err(e) => {
-                                return Err(rquickjs::Error::new_from_js_message(
-                                    "routeRegistry.registerRoute",
-                                    "privilege_lookup_failed",
-                                    &format!(
-                                        "Unable to verify privileges for '{}': {}",
-                                        script_uri_for_register, e
-                                    ),
-                                ));
-                            }
-                        };
+        global.set("fetch", fetch_fn)?;
+        debug!("fetch() function initialized with secret injection support");
 
-                    let user_is_admin =
-                        user_ctx_route.has_capability(&crate::security::Capability::DeleteScripts);
+        // Start a request on a thread of its own; its promise is settled
+        // with the response JSON by the execution's event loop
+        let script_uri_async = script_uri.to_string();
+        let user_id_async = self.user_context.user_id.clone();
+        let start_fetch = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  url: String,
+                  options_json: Option<String>|
+                  -> JsResult<u32> {
+                let options = prepare_fetch_options(options_json)?;
+                tracing::debug!(
+                    "Fetching URL asynchronously: {} from script: {}",
+                    url,
+                    script_uri_async
+                );
+                let script_uri = script_uri_async.clone();
+                let user_id = user_id_async.clone();
+                crate::event_loop::spawn(move || {
+                    perform_fetch(url, options, &script_uri, user_id.as_deref())
+                        .map_err(|(_, message)| message)
+                })
+                .map_err(|e| rquickjs::Error::new_from_js_message("fetchAsync", "start", &e))
+            },
+        )?;
+        global.set("__startFetch", start_fetch)?;
+        ctx.eval::<(), _>(
+            r#"
+            (function() {
+                const startFetch = globalThis.__startFetch;
+                const awaitHostOperation = globalThis.__awaitHostOperation;
+                globalThis.fetchAsync = async function(url, options) {
+                    const json = options === undefined || options === null
+                        ? undefined
+                        : typeof options === "string" ? options : JSON.stringify(options);
+                    return awaitHostOperation(startFetch(url, json));
+                };
+                delete globalThis.__startFetch;
+            })();
+            "#,
+        )?;
 
-                    if !script_privileged && !user_is_admin {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "routeRegistry.registerRoute",
-                            "permission_denied",
-                            &format!(
-                                "Script '{}' is not privileged to register HTTP routes",
-                                script_uri_for_register
-                            ),
-                        ));
-                    }
+        Ok(())
+    }
 
-                    // Build RouteMetadata from parameters
-                    let mut route_meta = repository::RouteMetadata::simple(handler.clone());
+    /// Setup setTimeout() and clearTimeout(), and the promises host
+    /// operations settle (see [`crate::event_loop`]). The engine settles an
+    /// operation by calling `__settleHostOperation(id, ok, value)`.
+    fn setup_timer_functions(&self, ctx: &rquickjs::Ctx<'_>) -> JsResult<()> {
+        let global = ctx.globals();
 
-                    if let Some(meta_obj) = metadata.0 {
-                        // Extract summary
-                        if let Ok(summary) = meta_obj.get::<_, Option<String>>("summary") {
-                            route_meta.summary = summary;
-                        }
-                        // Extract description
-                        if let Ok(description) = meta_obj.get::<_, Option<String>>("description") {
-                            route_meta.description = description;
-                        }
-                        // Extract tags
-                        if let Ok(tags_arr) = meta_obj.get::<_, rquickjs::Array>("tags") {
-                            let mut tags = Vec::new();
-                            for i in 0..tags_arr.len() {
-                                if let Ok(tag) = tags_arr.get::<String>(i) {
-                                    tags.push(tag);
-                                }
-                            }
-                            route_meta.tags = tags;
-                        }
-                        // Extract parameters
-                        if let Ok(Some(params_json)) =
-                            meta_obj.get::<_, Option<String>>("parameters")
-                            && let Ok(params_value) =
-                                serde_json::from_str::<serde_json::Value>(&params_json)
-                        {
-                            route_meta.parameters = Some(params_value);
-                        }
-                        // Extract requestBody
-                        if let Ok(Some(body_json)) =
-                            meta_obj.get::<_, Option<String>>("requestBody")
-                            && let Ok(body_value) =
-                                serde_json::from_str::<serde_json::Value>(&body_json)
-                        {
-                            route_meta.request_body = Some(body_value);
-                        }
+        let start_timer = Function::new(
+            ctx.clone(),
+            |_ctx: rquickjs::Ctx<'_>, delay_ms: f64| -> JsResult<u32> {
+                let delay = if delay_ms.is_finite() && delay_ms > 0.0 {
+                    std::time::Duration::from_secs_f64(delay_ms / 1000.0)
+                } else {
+                    std::time::Duration::ZERO
+                };
+                crate::event_loop::start_timer(delay)
+                    .map_err(|e| rquickjs::Error::new_from_js_message("setTimeout", "start", &e))
+            },
+        )?;
+        global.set("__startTimer", start_timer)?;
+
+        let cancel_timer = Function::new(ctx.clone(), |_ctx: rquickjs::Ctx<'_>, id: u32| {
+            crate::event_loop::cancel_timer(id);
+        })?;
+        global.set("__cancelTimer", cancel_timer)?;
+
+        ctx.eval::<(), _>(
+            r#"
+            (function() {
+                const startTimer = globalThis.__startTimer;
+                const cancelTimer = globalThis.__cancelTimer;
+                // Settle functions of pending operations by id
+                const pending = new Map();
+                globalThis.__settleHostOperation = function(id, ok, value) {
+                    const settle = pending.get(id);
+                    if (settle === undefined) return;
+                    pending.delete(id);
+                    settle(ok, value);
+                };
+                globalThis.__awaitHostOperation = function(id) {
+                    return new Promise((resolve, reject) => {
+                        pending.set(id, (ok, value) => ok ? resolve(value) : reject(new Error(value)));
+                    });
+                };
+                globalThis.setTimeout = function(callback, delay, ...args) {
+                    if (typeof callback !== "function") {
+                        throw new TypeError("setTimeout callback must be a function");
                     }
+                    const id = startTimer(Number(delay) || 0);
+                    pending.set(id, () => callback(...args));
+                    return id;
+                };
+                globalThis.clearTimeout = function(id) {
+                    if (pending.delete(id)) cancelTimer(id);
+                };
+                delete globalThis.__startTimer;
+                delete globalThis.__cancelTimer;
+            })();
+            "#,
+        )?;
 
-                    let method_ref = method.as_deref();
-                    register_impl(&path, &route_meta, method_ref)
-                },
-            )?;
-            route_registry.set("registerRoute", register_route)?;
-        } else {
-            // No-op register function with privilege check
-            let script_uri_for_noop = script_uri_owned.clone();
-            let user_ctx_noop = user_context.clone();
-            let reg_noop = Function::new(
-                ctx.clone(),
-                move |_c: rquickjs::Ctx<'_>,
-                      _p: String,
-                      _h: String,
-                      _m: Option<String>,
-                      _meta: Opt<rquickjs::Object>|
-                      -> Result<(), rquickjs::Error> {
-                    // Check if script is privileged OR user has admin privileges
-                    let script_privileged =
-                        match repository::is_script_privileged(&script_uri_for_noop) {
-                            Ok(true) => true,
-                            Ok(false) => false,
-                            Err(e) => {
-                                return Err(rquickjs::Error::new_from_js_message(
-                                    "routeRegistry.registerRoute",
-                                    "privilege_lookup_failed",
-                                    &format!(
-                                        "Unable to verify privileges for '{}': {}",
-                                        script_uri_for_noop, e
-                                    ),
-                                ));
-                            }
-                        };
+        Ok(())
+    }
 
-                    let user_is_admin =
-                        user_ctx_noop.has_capability(&crate::security::Capability::DeleteScripts);
+    /// Setup webhooks.* for outgoing webhooks delivered with retries
+    fn setup_webhook_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let webhooks_obj = rquickjs::Object::new(ctx.clone())?;
 
-                    if !script_privileged && !user_is_admin {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "routeRegistry.registerRoute",
-                            "permission_denied",
-                            &format!(
-                                "Script '{}' is not privileged to register HTTP routes",
-                                script_uri_for_noop
-                            ),
-                        ));
-                    }
+        // webhooks.send(url, payload, options) - Queue a delivery
+        let script_uri_send = script_uri.to_string();
+        let send = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  url: String,
+                  payload: rquickjs::Value<'_>,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let (body, content_type) =
+                    match crate::webhooks::payload_body(&read_json_value(payload)) {
+                        Ok(body) => body,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let options: crate::webhooks::SendOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("webhooks.send") {
+                    return Ok(format!("Error: {}", e));
+                }
+                let delivery =
+                    crate::webhooks::send(&script_uri_send, &url, body, content_type, &options)
+                        .and_then(|delivery| {
+                            serde_json::to_string(&delivery).map_err(|e| e.to_string())
+                        });
+                Ok(delivery.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        webhooks_obj.set("send", send)?;
 
-                    Ok(())
-                },
-            )?;
-            route_registry.set("registerRoute", reg_noop)?;
-        }
+        // webhooks.get(id) - A delivery of this script, or null
+        let script_uri_get = script_uri.to_string();
+        let get = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, id: String| -> JsResult<String> {
+                let delivery = crate::webhooks::get(&script_uri_get, &id).and_then(|delivery| {
+                    serde_json::to_string(&delivery).map_err(|e| e.to_string())
+                });
+                Ok(delivery.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        webhooks_obj.set("get", get)?;
 
-        // 2. registerStreamRoute function
-        let user_ctx_stream = user_context.clone();
-        let auditor_stream = auditor.clone();
-        let config_stream = config.clone();
-        let script_uri_stream = script_uri_owned.clone();
-        let register_stream_route = Function::new(
+        // webhooks.list({ status, limit }) - Deliveries of this script, newest first
+        let script_uri_list = script_uri.to_string();
+        let list = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let mut options: crate::webhooks::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if options
+                    .script_uri
+                    .as_ref()
+                    .is_some_and(|uri| *uri != script_uri_list)
+                {
+                    return Ok("Error: Scripts may only list their own deliveries".to_string());
+                }
+                options.script_uri = Some(script_uri_list.clone());
+                let deliveries = crate::webhooks::list(&options).and_then(|deliveries| {
+                    serde_json::to_string(&deliveries).map_err(|e| e.to_string())
+                });
+                Ok(deliveries.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        webhooks_obj.set("list", list)?;
+
+        ctx.globals().set("webhooks", webhooks_obj)?;
+        Ok(())
+    }
+
+    /// Setup tasks.enqueue() and friends for background work of the script
+    fn setup_task_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let tasks_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // tasks.enqueue(handlerName, payload, options) - Queue a run of a handler
+        let script_uri_enqueue = script_uri.to_string();
+        let enqueue = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  path: String,
-                  customization_function: Opt<String>|
+                  handler_name: String,
+                  payload: Opt<rquickjs::Value<'_>>,
+                  options: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
-                // Convert Opt to Option
-                let customization_function = customization_function.0;
-                // If streams are disabled, return success without doing anything
-                if !config_stream.enable_streams {
-                    return Ok(format!(
-                        "Stream registration disabled (stream '{}' not registered)",
-                        path
-                    ));
+                let payload = payload
+                    .0
+                    .map(read_json_value)
+                    .unwrap_or(serde_json::Value::Null);
+                let options: crate::tasks::EnqueueOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("tasks.enqueue") {
+                    return Ok(format!("Error: {}", e));
                 }
+                let task =
+                    crate::tasks::enqueue(&script_uri_enqueue, &handler_name, payload, &options)
+                        .and_then(|task| serde_json::to_string(&task).map_err(|e| e.to_string()));
+                Ok(task.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        tasks_obj.set("enqueue", enqueue)?;
 
-                // Check if script is privileged OR user has admin privileges
-                let script_privileged = match repository::is_script_privileged(&script_uri_stream) {
-                    Ok(true) => true,
-                    Ok(false) => false,
-                    Err(e) => {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "routeRegistry.registerStreamRoute",
-                            "privilege_lookup_failed",
-                            &format!(
-                                "Unable to verify privileges for '{}': {}",
-                                script_uri_stream, e
-                            ),
-                        ));
-                    }
-                };
+        // tasks.get(id) - A task of this script, or null
+        let script_uri_get = script_uri.to_string();
+        let get = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, id: String| -> JsResult<String> {
+                let task = crate::tasks::get(&script_uri_get, &id)
+                    .and_then(|task| serde_json::to_string(&task).map_err(|e| e.to_string()));
+                Ok(task.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        tasks_obj.set("get", get)?;
 
-                let user_is_admin =
-                    user_ctx_stream.has_capability(&crate::security::Capability::DeleteScripts);
+        // tasks.list({ status, limit }) - Tasks of this script, newest first
+        let script_uri_list = script_uri.to_string();
+        let list = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let mut options: crate::tasks::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if options
+                    .script_uri
+                    .as_ref()
+                    .is_some_and(|uri| *uri != script_uri_list)
+                {
+                    return Ok("Error: Scripts may only list their own tasks".to_string());
+                }
+                options.script_uri = Some(script_uri_list.clone());
+                let tasks = crate::tasks::list(&options)
+                    .and_then(|tasks| serde_json::to_string(&tasks).map_err(|e| e.to_string()));
+                Ok(tasks.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        tasks_obj.set("list", list)?;
 
-                if !script_privileged && !user_is_admin {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "routeRegistry.registerStreamRoute",
-                        "permission_denied",
-                        &format!(
-                            "Script '{}' is not privileged to register stream routes",
-                            script_uri_stream
-                        ),
-                    ));
+        ctx.globals().set("tasks", tasks_obj)?;
+        Ok(())
+    }
+
+    /// Setup email.send() and email.list() for outgoing email of the script
+    fn setup_email_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let email_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // email.send({ to, subject, html, text, attachments }) - Send a message
+        let script_uri_send = script_uri.to_string();
+        let send = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, message: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let message: crate::email::SendRequest =
+                    match read_options_object(message.0, "message") {
+                        Ok(message) => message,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("email.send") {
+                    return Ok(format!("Error: {}", e));
                 }
+                let sent = crate::email::send(&script_uri_send, &message)
+                    .and_then(|entry| serde_json::to_string(&entry).map_err(|e| e.to_string()));
+                Ok(sent.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        email_obj.set("send", send)?;
 
-                // Validate path format
-                if path.is_empty() || !path.starts_with('/') {
-                    return Ok(format!(
-                        "Invalid stream path '{}': path must start with '/' and not be empty",
-                        path
-                    ));
+        // email.list({ status, limit }) - Delivery log of this script, newest first
+        let script_uri_list = script_uri.to_string();
+        let list = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let mut options: crate::email::ListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if options
+                    .script_uri
+                    .as_ref()
+                    .is_some_and(|uri| *uri != script_uri_list)
+                {
+                    return Ok("Error: Scripts may only list their own emails".to_string());
                 }
+                options.script_uri = Some(script_uri_list.clone());
+                let entries = crate::email::list(&options)
+                    .and_then(|entries| serde_json::to_string(&entries).map_err(|e| e.to_string()));
+                Ok(entries.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        email_obj.set("list", list)?;
 
-                if path.len() > 200 {
-                    return Ok(format!(
-                        "Invalid stream path '{}': path too long (max 200 characters)",
-                        path
-                    ));
+        ctx.globals().set("email", email_obj)?;
+        Ok(())
+    }
+
+    /// Setup notify.push() for web push and notify.sms() for text messages
+    fn setup_notify_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let notify_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // notify.vapidPublicKey() - applicationServerKey for PushManager.subscribe
+        let vapid_public_key = Function::new(ctx.clone(), || -> JsResult<String> {
+            Ok(crate::notify::public_key().unwrap_or_else(|e| format!("Error: {}", e)))
+        })?;
+        notify_obj.set("vapidPublicKey", vapid_public_key)?;
+
+        // notify.subscribe(subscription) - Store the signed-in user's browser subscription
+        let script_uri_subscribe = script_uri.to_string();
+        let user_id = self.user_context.user_id.clone();
+        let subscribe = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  subscription: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let subscription: crate::notify::SubscriptionInput =
+                    match read_options_object(subscription.0, "subscription") {
+                        Ok(subscription) => subscription,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("notify.subscribe") {
+                    return Ok(format!("Error: {}", e));
                 }
+                let stored = crate::notify::subscribe(
+                    &script_uri_subscribe,
+                    user_id.as_deref(),
+                    &subscription,
+                )
+                .and_then(|stored| serde_json::to_string(&stored).map_err(|e| e.to_string()));
+                Ok(stored.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        notify_obj.set("subscribe", subscribe)?;
 
-                // Validate customization function name if provided
-                if let Some(ref func_name) = customization_function {
-                    if func_name.is_empty() {
-                        return Ok(
-                            "Invalid customization function: name cannot be empty".to_string()
-                        );
-                    }
-                    if func_name.len() > 100 {
-                        return Ok(
-                            "Invalid customization function: name too long (max 100 characters)"
-                                .to_string(),
-                        );
-                    }
-                    // Basic validation: should be a valid identifier
-                    if !func_name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-                        return Ok("Invalid customization function: name must contain only alphanumeric characters and underscores".to_string());
-                    }
+        // notify.unsubscribe(endpointOrId) - Remove a subscription
+        let script_uri_unsubscribe = script_uri.to_string();
+        let unsubscribe = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("notify.unsubscribe") {
+                    return Ok(format!("Error: {}", e));
                 }
+                Ok(
+                    match crate::notify::unsubscribe(&script_uri_unsubscribe, &key) {
+                        Ok(removed) => removed.to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    },
+                )
+            },
+        )?;
+        notify_obj.set("unsubscribe", unsubscribe)?;
 
-                // Check capability
-                if let Err(e) =
-                    user_ctx_stream.require_capability(&crate::security::Capability::ManageStreams)
-                {
-                    if config_stream.enable_audit_logging
-                        && let Ok(rt) = tokio::runtime::Handle::try_current()
-                    {
-                        let auditor_clone = auditor_stream.clone();
-                        let user_id = user_ctx_stream.user_id.clone();
-                        rt.spawn(async move {
-                            let _ = auditor_clone
-                                .log_event(
-                                    crate::security::SecurityEvent::new(
-                                        crate::security::SecurityEventType::AuthorizationFailure,
-                                        crate::security::SecuritySeverity::Medium,
-                                        user_id,
-                                    )
-                                    .with_resource("stream".to_string())
-                                    .with_action("register".to_string()),
-                                )
-                                .await;
-                        });
-                    }
+        // notify.push({ userId | subscriptionId }, payload, { ttl, urgency, topic })
+        let script_uri_push = script_uri.to_string();
+        let push = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  target: Opt<rquickjs::Value<'_>>,
+                  payload: Opt<rquickjs::Value<'_>>,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let target: crate::notify::PushTarget =
+                    match read_options_object(target.0, "target") {
+                        Ok(target) => target,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let payload = payload
+                    .0
+                    .map(read_json_value)
+                    .unwrap_or(serde_json::Value::Null);
+                let options: crate::notify::PushOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("notify.push") {
+                    return Ok(format!("Error: {}", e));
+                }
+                let result = crate::notify::push(&script_uri_push, &target, &payload, &options)
+                    .and_then(|result| serde_json::to_string(&result).map_err(|e| e.to_string()));
+                Ok(result.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        notify_obj.set("push", push)?;
+
+        // notify.sms(to, message) - Send a text message with the configured provider
+        let script_uri_sms = script_uri.to_string();
+        let sms = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, to: String, message: String| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("notify.sms") {
+                    return Ok(format!("Error: {}", e));
+                }
+                let receipt = crate::notify::sms(&script_uri_sms, &to, &message)
+                    .and_then(|receipt| serde_json::to_string(&receipt).map_err(|e| e.to_string()));
+                Ok(receipt.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        notify_obj.set("sms", sms)?;
+
+        ctx.globals().set("notify", notify_obj)?;
+        Ok(())
+    }
+
+    /// Setup llm.complete() for completions from the configured providers
+    fn setup_llm_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let llm_obj = rquickjs::Object::new(ctx.clone())?;
+        let script_uri_owned = script_uri.to_string();
+        // The signed-in user's secrets are tried before the script's
+        let user_id = self.user_context.user_id.clone();
+        let user_ctx_complete = self.user_context.clone();
+
+        // llm.complete(provider, params) - Run a chat completion, running the
+        // MCP tools the model calls when `tools` is set
+        let complete = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>,
+                  provider: String,
+                  params: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let params: crate::llm::CompletionParams =
+                    match read_options_object(params.0, "params") {
+                        Ok(params) => params,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                // Completions cost money even though they change nothing
+                if let Err(e) = crate::dry_run::ensure_allowed("llm.complete") {
+                    return Ok(format!("Error: {}", e));
+                }
+                // Tools run with the caller's permissions
+                let auth_context = get_auth_context(&ctx.globals());
+                let call_tool = |name: &str, arguments: serde_json::Value| {
+                    crate::mcp::execute_mcp_tool(
+                        name,
+                        arguments,
+                        auth_context.clone(),
+                        user_ctx_complete.clone(),
+                    )
+                };
+                let completion = crate::llm::complete(
+                    &script_uri_owned,
+                    user_id.as_deref(),
+                    &provider,
+                    &params,
+                    call_tool,
+                )
+                .and_then(|completion| {
+                    serde_json::to_string(&completion).map_err(|e| e.to_string())
+                });
+                Ok(completion.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        llm_obj.set("complete", complete)?;
+
+        // llm.stream({ provider, ...params }, streamPath) - Stream a completion into
+        // a registered stream
+        let script_uri_stream = script_uri.to_string();
+        let user_ctx_stream = self.user_context.clone();
+        let stream = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  params: rquickjs::Value<'_>,
+                  stream_path: String|
+                  -> JsResult<String> {
+                let (provider, params) = match read_llm_stream_params(params) {
+                    Ok(params) => params,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                if let Err(e) =
+                    user_ctx_stream.require_capability(&crate::security::Capability::ManageStreams)
+                {
                     return Ok(format!("Error: {}", e));
                 }
-
-                // Validate stream path
-                if path.contains("..") || path.contains('\\') {
-                    return Ok("Invalid stream path: path traversal not allowed".to_string());
+                if let Err(e) = crate::dry_run::ensure_allowed("llm.stream") {
+                    return Ok(format!("Error: {}", e));
                 }
-
-                // Log the operation attempt
-                if config_stream.enable_audit_logging
-                    && let Ok(rt) = tokio::runtime::Handle::try_current()
+                if !crate::stream_registry::GLOBAL_STREAM_REGISTRY
+                    .is_stream_registered(&stream_path)
                 {
-                    let auditor_clone = auditor_stream.clone();
-                    let user_id = user_ctx_stream.user_id.clone();
-                    let path_clone = path.clone();
-                    let script_uri_clone = script_uri_stream.clone();
-                    rt.spawn(async move {
-                        let _ = auditor_clone
-                            .log_event(
-                                crate::security::SecurityEvent::new(
-                                    crate::security::SecurityEventType::SystemSecurityEvent,
-                                    crate::security::SecuritySeverity::Medium,
-                                    user_id,
-                                )
-                                .with_resource("stream".to_string())
-                                .with_action("register".to_string())
-                                .with_detail("path", &path_clone)
-                                .with_detail("script_uri", &script_uri_clone),
-                            )
-                            .await;
-                    });
+                    return Ok(format!(
+                        "Error: Stream path '{}' is not registered",
+                        stream_path
+                    ));
                 }
-
-                // Register the stream
-                match crate::stream_registry::GLOBAL_STREAM_REGISTRY.register_stream(
-                    &path,
+                let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                    return Ok("Error: llm.stream is not available here".to_string());
+                };
+                let stream = match crate::llm::prepare_stream(
                     &script_uri_stream,
-                    customization_function,
+                    user_ctx_stream.user_id.as_deref(),
+                    &provider,
+                    &params,
                 ) {
-                    Ok(()) => Ok(format!("Web stream '{}' registered successfully", path)),
-                    Err(e) => Ok(format!("Failed to register stream '{}': {}", path, e)),
-                }
+                    Ok(stream) => stream,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+
+                let id = uuid::Uuid::new_v4().to_string();
+                let stream_id = id.clone();
+                let path = stream_path.clone();
+                runtime.spawn_blocking(move || stream.pipe_to_stream(&stream_id, &path));
+                Ok(serde_json::json!({ "id": id, "path": stream_path }).to_string())
             },
         )?;
-        route_registry.set("registerStreamRoute", register_stream_route)?;
+        llm_obj.set("stream", stream)?;
+        ctx.globals().set("llm", llm_obj)?;
 
-        // 3. registerAssetRoute function
-        let user_ctx_asset = user_context.clone();
-        let script_uri_asset = script_uri_owned.clone();
-        let register_asset_route = Function::new(
+        // embeddings.create(input, options) - Embed a text or an array of texts
+        let embeddings_obj = rquickjs::Object::new(ctx.clone())?;
+        let script_uri_owned = script_uri.to_string();
+        let user_id = self.user_context.user_id.clone();
+        let create = Function::new(
             ctx.clone(),
-            move |_c: rquickjs::Ctx<'_>,
-                  path: String,
-                  asset_name: String|
-                  -> Result<String, rquickjs::Error> {
-                // Check if script is privileged OR user has admin privileges
-                let script_privileged = match repository::is_script_privileged(&script_uri_asset) {
-                    Ok(true) => true,
-                    Ok(false) => false,
-                    Err(e) => {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "routeRegistry.registerAssetRoute",
-                            "privilege_lookup_failed",
-                            &format!(
-                                "Unable to verify privileges for '{}': {}",
-                                script_uri_asset, e
-                            ),
-                        ));
-                    }
+            move |_ctx: rquickjs::Ctx<'_>,
+                  input: rquickjs::Value<'_>,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let input = match read_embedding_input(input) {
+                    Ok(input) => input,
+                    Err(e) => return Ok(format!("Error: {}", e)),
                 };
+                let params: crate::llm::EmbeddingParams =
+                    match read_options_object(options.0, "options") {
+                        Ok(params) => params,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("embeddings.create") {
+                    return Ok(format!("Error: {}", e));
+                }
+                let embeddings =
+                    crate::llm::embed(&script_uri_owned, user_id.as_deref(), &input, &params)
+                        .and_then(|embeddings| {
+                            serde_json::to_string(&embeddings).map_err(|e| e.to_string())
+                        });
+                Ok(embeddings.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        embeddings_obj.set("create", create)?;
+        ctx.globals().set("embeddings", embeddings_obj)?;
 
-                let user_is_admin =
-                    user_ctx_asset.has_capability(&crate::security::Capability::DeleteScripts);
+        // moderation.check(text, options) - Check text with the configured moderation
+        let moderation_obj = rquickjs::Object::new(ctx.clone())?;
+        let script_uri_owned = script_uri.to_string();
+        let user_id = self.user_context.user_id.clone();
+        let check = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  text: String,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let options: crate::moderation::CheckOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let result = crate::moderation::check_text(
+                    &script_uri_owned,
+                    user_id.as_deref(),
+                    &text,
+                    &options,
+                )
+                .and_then(|result| serde_json::to_string(&result).map_err(|e| e.to_string()));
+                Ok(result.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        moderation_obj.set("check", check)?;
+        ctx.globals().set("moderation", moderation_obj)?;
+        Ok(())
+    }
 
-                if !script_privileged && !user_is_admin {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "routeRegistry.registerAssetRoute",
-                        "permission_denied",
-                        &format!(
-                            "Script '{}' is not privileged to register asset routes",
-                            script_uri_asset
-                        ),
-                    ));
-                }
+    /// Setup database functions
+    fn setup_database_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let script_uri_owned = script_uri.to_string();
+        let user_context = self.user_context.clone();
 
-                // Check capability
-                if let Err(e) =
-                    user_ctx_asset.require_capability(&crate::security::Capability::WriteAssets)
+        // Create the database namespace object for schema management
+        let database_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // database.createTable(tableName) - Create a new table for this script
+        let script_uri_create = script_uri_owned.clone();
+        let user_ctx_create = user_context.clone();
+        let create_table = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
+                debug!(
+                    "database.createTable called for script {} with table: {}",
+                    script_uri_create, table_name
+                );
+
+                // Check permission
+                if user_ctx_create
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    return Ok(format!("Access denied: {}", e));
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Validate path
-                if !path.starts_with('/') {
-                    return Ok("Path must start with '/'".to_string());
+                match crate::repository::create_script_table(&script_uri_create, &table_name) {
+                    Ok(physical_name) => Ok(format!(
+                        "{{\"success\": true, \"tableName\": \"{}\", \"physicalName\": \"{}\"}}",
+                        table_name, physical_name
+                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
-                if path.len() > 500 {
-                    return Ok("Path too long (max 500 characters)".to_string());
+            },
+        )?;
+        database_obj.set("createTable", create_table)?;
+
+        // database.addIntegerColumn(tableName, columnName, nullable, defaultValue)
+        let script_uri_add_int = script_uri_owned.clone();
+        let user_ctx_add_int = user_context.clone();
+        let add_integer_column = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  column_name: String,
+                  nullable: Opt<bool>,
+                  default_value: Opt<String>|
+                  -> JsResult<String> {
+                debug!(
+                    "database.addIntegerColumn called for script {}",
+                    script_uri_add_int
+                );
+
+                if user_ctx_add_int
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Validate asset name
-                if asset_name.is_empty() || asset_name.len() > 255 {
-                    return Ok("Invalid asset name: must be 1-255 characters".to_string());
+                let nullable = nullable.0.unwrap_or(true);
+                let default_val = default_value.0.as_deref();
+
+                match crate::repository::add_column_to_script_table(
+                    &script_uri_add_int,
+                    &table_name,
+                    &column_name,
+                    crate::db_schema_utils::ColumnType::Integer,
+                    nullable,
+                    default_val,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "{{\"success\": true, \"column\": \"{}\"}}",
+                        column_name
+                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
-                if asset_name.contains("..") || asset_name.contains('\\') {
-                    return Ok("Invalid asset name: path characters not allowed".to_string());
+            },
+        )?;
+        database_obj.set("addIntegerColumn", add_integer_column)?;
+
+        // database.addTextColumn(tableName, columnName, nullable, defaultValue)
+        let script_uri_add_text = script_uri_owned.clone();
+        let user_ctx_add_text = user_context.clone();
+        let add_text_column = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  column_name: String,
+                  nullable: Opt<bool>,
+                  default_value: Opt<String>|
+                  -> JsResult<String> {
+                debug!(
+                    "database.addTextColumn called for script {}",
+                    script_uri_add_text
+                );
+
+                if user_ctx_add_text
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
+                }
+
+                let nullable = nullable.0.unwrap_or(true);
+                let default_val = default_value.0.as_deref();
+
+                match crate::repository::add_column_to_script_table(
+                    &script_uri_add_text,
+                    &table_name,
+                    &column_name,
+                    crate::db_schema_utils::ColumnType::Text,
+                    nullable,
+                    default_val,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "{{\"success\": true, \"column\": \"{}\"}}",
+                        column_name
+                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
+            },
+        )?;
+        database_obj.set("addTextColumn", add_text_column)?;
+
+        // database.addBooleanColumn(tableName, columnName, nullable, defaultValue)
+        let script_uri_add_bool = script_uri_owned.clone();
+        let user_ctx_add_bool = user_context.clone();
+        let add_boolean_column = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  column_name: String,
+                  nullable: Opt<bool>,
+                  default_value: Opt<String>|
+                  -> JsResult<String> {
+                debug!(
+                    "database.addBooleanColumn called for script {}",
+                    script_uri_add_bool
+                );
 
-                // Verify the asset exists and belongs to this script
-                match repository::fetch_asset(&script_uri_asset, &asset_name) {
-                    Some(_) => {
-                        // Asset exists and belongs to this script, proceed
-                    }
-                    None => {
-                        return Ok(format!(
-                            "Asset '{}' not found or not owned by script '{}'",
-                            asset_name, script_uri_asset
-                        ));
-                    }
+                if user_ctx_add_bool
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Register the path in the global asset registry
-                match crate::asset_registry::get_global_registry().register_path(
-                    &path,
-                    &asset_name,
-                    &script_uri_asset,
+                let nullable = nullable.0.unwrap_or(true);
+                let default_val = default_value.0.as_deref();
+
+                match crate::repository::add_column_to_script_table(
+                    &script_uri_add_bool,
+                    &table_name,
+                    &column_name,
+                    crate::db_schema_utils::ColumnType::Boolean,
+                    nullable,
+                    default_val,
                 ) {
                     Ok(()) => Ok(format!(
-                        "Asset path '{}' registered to asset '{}'",
-                        path, asset_name
+                        "{{\"success\": true, \"column\": \"{}\"}}",
+                        column_name
                     )),
-                    Err(e) => Ok(format!("Failed to register asset path: {}", e)),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("registerAssetRoute", register_asset_route)?;
+        database_obj.set("addBooleanColumn", add_boolean_column)?;
 
-        // 4. sendStreamMessage function
-        let user_ctx_send = user_context.clone();
-        let auditor_send = auditor.clone();
-        let send_stream_message = Function::new(
+        // database.addTimestampColumn(tableName, columnName, nullable, defaultValue)
+        let script_uri_add_ts = script_uri_owned.clone();
+        let user_ctx_add_ts = user_context.clone();
+        let add_timestamp_column = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, path: String, message: String| -> JsResult<String> {
-                // Allow system-level broadcasting without capability checks for certain paths
-                let is_system_broadcast = path == "/script_updates" || path.starts_with("/system/");
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  column_name: String,
+                  nullable: Opt<bool>,
+                  default_value: Opt<String>|
+                  -> JsResult<String> {
+                debug!(
+                    "database.addTimestampColumn called for script {}",
+                    script_uri_add_ts
+                );
 
-                if !is_system_broadcast {
-                    // Check capability for non-system operations
-                    if let Err(e) = user_ctx_send
-                        .require_capability(&crate::security::Capability::ManageStreams)
-                    {
-                        let auditor_clone = auditor_send.clone();
-                        let user_id = user_ctx_send.user_id.clone();
-                        tokio::task::spawn(async move {
-                            let _ = auditor_clone
-                                .log_event(
-                                    crate::security::SecurityEvent::new(
-                                        crate::security::SecurityEventType::AuthorizationFailure,
-                                        crate::security::SecuritySeverity::Medium,
-                                        user_id,
-                                    )
-                                    .with_resource("stream".to_string())
-                                    .with_action("send_message".to_string()),
-                                )
-                                .await;
-                        });
-                        return Ok(format!("Error: {}", e));
-                    }
+                if user_ctx_add_ts
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Log the operation attempt
-                let auditor_clone = auditor_send.clone();
-                let user_id = user_ctx_send.user_id.clone();
-                let path_clone = path.clone();
-                let message_clone = message.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                crate::security::SecurityEventType::SystemSecurityEvent,
-                                crate::security::SecuritySeverity::Low,
-                                user_id,
-                            )
-                            .with_resource("stream".to_string())
-                            .with_action("send_message".to_string())
-                            .with_detail("path", &path_clone)
-                            .with_detail("message_length", message_clone.len().to_string()),
-                        )
-                        .await;
-                });
+                let nullable = nullable.0.unwrap_or(true);
+                let default_val = default_value.0.as_deref();
 
-                // Send the message
-                match crate::stream_registry::GLOBAL_STREAM_REGISTRY
-                    .broadcast_to_stream(&path, &message)
-                {
-                    Ok(result) => {
-                        if result.is_fully_successful() {
-                            Ok(format!(
-                                "Successfully sent message to {} connections on path '{}'",
-                                result.successful_sends, path
-                            ))
-                        } else {
-                            Ok(format!(
-                                "Sent message to {}/{} connections on path '{}' ({} failed)",
-                                result.successful_sends,
-                                result.total_connections,
-                                path,
-                                result.failed_connections.len()
-                            ))
-                        }
-                    }
-                    Err(e) => Ok(format!("Failed to send message to path '{}': {}", path, e)),
+                match crate::repository::add_column_to_script_table(
+                    &script_uri_add_ts,
+                    &table_name,
+                    &column_name,
+                    crate::db_schema_utils::ColumnType::Timestamp,
+                    nullable,
+                    default_val,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "{{\"success\": true, \"column\": \"{}\"}}",
+                        column_name
+                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("sendStreamMessage", send_stream_message)?;
+        database_obj.set("addTimestampColumn", add_timestamp_column)?;
 
-        // 5. sendStreamMessageFiltered function
-        let user_ctx_filtered = user_context.clone();
-        let auditor_filtered = auditor.clone();
-        let send_stream_message_filtered = Function::new(
+        // database.addReferenceColumn(tableName, columnName, referencedTableName, nullable)
+        let script_uri_ref = script_uri_owned.clone();
+        let user_ctx_ref = user_context.clone();
+        let add_reference_column = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  path: String,
-                  message: String,
-                  filter_json: Option<String>,
-                  match_mode: Option<String>|
+                  table_name: String,
+                  column_name: String,
+                  referenced_table_name: String,
+                  nullable: Opt<bool>|
                   -> JsResult<String> {
-                // Parse filter criteria
-                let metadata_filter: HashMap<String, String> = if let Some(json_str) = filter_json {
-                    serde_json::from_str(&json_str).map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "filter",
-                            "MetadataFilter",
-                            &format!("Invalid filter JSON: {}", e),
-                        )
-                    })?
-                } else {
-                    HashMap::new()
-                };
-                let match_mode = parse_filter_match_mode(match_mode)?;
-
-                // Allow system-level broadcasting for certain paths
-                let is_system_broadcast = path == "/script_updates" || path.starts_with("/system/");
+                debug!(
+                    "database.addReferenceColumn called for script {}",
+                    script_uri_ref
+                );
 
-                if !is_system_broadcast
-                    && let Err(e) = user_ctx_filtered
-                        .require_capability(&crate::security::Capability::ManageStreams)
+                if user_ctx_ref
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    let auditor_clone = auditor_filtered.clone();
-                    let user_id = user_ctx_filtered.user_id.clone();
-                    tokio::task::spawn(async move {
-                        let _ = auditor_clone
-                            .log_event(
-                                crate::security::SecurityEvent::new(
-                                    crate::security::SecurityEventType::AuthorizationFailure,
-                                    crate::security::SecuritySeverity::Medium,
-                                    user_id,
-                                )
-                                .with_resource("stream".to_string())
-                                .with_action("send_filtered_message".to_string()),
-                            )
-                            .await;
-                    });
-                    return Ok(format!("Error: {}", e));
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Log the operation
-                let auditor_clone = auditor_filtered.clone();
-                let user_id = user_ctx_filtered.user_id.clone();
-                let path_clone = path.clone();
-                let message_clone = message.clone();
-                let filter_clone = metadata_filter.clone();
-                tokio::task::spawn(async move {
-                    let _ = auditor_clone
-                        .log_event(
-                            crate::security::SecurityEvent::new(
-                                crate::security::SecurityEventType::SystemSecurityEvent,
-                                crate::security::SecuritySeverity::Low,
-                                user_id,
-                            )
-                            .with_resource("stream".to_string())
-                            .with_action("send_filtered_message".to_string())
-                            .with_detail("path", &path_clone)
-                            .with_detail("message_length", message_clone.len().to_string())
-                            .with_detail("filter_criteria_count", filter_clone.len().to_string()),
-                        )
-                        .await;
-                });
+                let nullable = nullable.0.unwrap_or(true);
+
+                match crate::repository::add_reference_column(
+                    &script_uri_ref,
+                    &table_name,
+                    &column_name,
+                    &referenced_table_name,
+                    nullable,
+                ) {
+                    Ok(()) => Ok(format!(
+                        "{{\"success\": true, \"foreignKey\": \"{}.{} -> {}\", \"nullable\": {}}}",
+                        table_name, column_name, referenced_table_name, nullable
+                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
+            },
+        )?;
+        database_obj.set("addReferenceColumn", add_reference_column)?;
+
+        // database.dropColumn(tableName, columnName)
+        let script_uri_drop_col = script_uri_owned.clone();
+        let user_ctx_drop_col = user_context.clone();
+        let drop_column = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  column_name: String|
+                  -> JsResult<String> {
+                debug!(
+                    "database.dropColumn called for script {} with table: {}, column: {}",
+                    script_uri_drop_col, table_name, column_name
+                );
 
-                // Send filtered message
-                let result = crate::stream_registry::GLOBAL_STREAM_REGISTRY
-                    .broadcast_to_stream_with_filter_mode(
-                        &path,
-                        &message,
-                        &metadata_filter,
-                        match_mode,
+                if user_ctx_drop_col
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
                     );
+                }
 
-                match result {
-                    Ok(broadcast_result) => {
-                        if broadcast_result.is_fully_successful() {
+                match crate::repository::drop_column(
+                    &script_uri_drop_col,
+                    &table_name,
+                    &column_name,
+                ) {
+                    Ok(existed) => {
+                        if existed {
                             Ok(format!(
-                                "Successfully sent filtered message to {} connections on path '{}'",
-                                broadcast_result.successful_sends, path
+                                "{{\"success\": true, \"tableName\": \"{}\", \"columnName\": \"{}\", \"dropped\": true}}",
+                                table_name, column_name
                             ))
                         } else {
                             Ok(format!(
-                                "Sent filtered message to {}/{} connections on path '{}' ({} failed)",
-                                broadcast_result.successful_sends,
-                                broadcast_result.total_connections,
-                                path,
-                                broadcast_result.failed_connections.len()
+                                "{{\"success\": true, \"tableName\": \"{}\", \"columnName\": \"{}\", \"dropped\": false, \"message\": \"Column did not exist\"}}",
+                                table_name, column_name
                             ))
                         }
                     }
-                    Err(e) => Ok(format!(
-                        "Failed to send filtered message to path '{}': {}",
-                        path, e
-                    )),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("sendStreamMessageFiltered", send_stream_message_filtered)?;
+        database_obj.set("dropColumn", drop_column)?;
 
-        // 6. listRoutes function
-        let user_ctx_list_routes = user_context.clone();
-        let list_routes = Function::new(
+        // database.dropTable(tableName)
+        let script_uri_drop = script_uri_owned.clone();
+        let user_ctx_drop = user_context.clone();
+        let drop_table = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check capability
-                if let Err(_e) = user_ctx_list_routes
-                    .require_capability(&crate::security::Capability::ReadScripts)
+            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
+                debug!(
+                    "database.dropTable called for script {} with table: {}",
+                    script_uri_drop, table_name
+                );
+
+                if user_ctx_drop
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    return Ok("[]".to_string());
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Get all script metadata
-                match repository::get_all_script_metadata() {
-                    Ok(metadata_list) => {
-                        let mut all_routes = Vec::new();
-                        for metadata in metadata_list {
-                            if metadata.initialized && !metadata.registrations.is_empty() {
-                                for ((path, method), route_meta) in metadata.registrations {
-                                    all_routes.push(serde_json::json!({
-                                        "path": path,
-                                        "method": method,
-                                        "handler": route_meta.handler_name,
-                                        "script_uri": metadata.uri,
-                                        "summary": route_meta.summary,
-                                        "description": route_meta.description,
-                                        "tags": route_meta.tags,
-                                    }));
-                                }
-                            }
-                        }
-
-                        if let Ok(stream_paths) =
-                            crate::stream_registry::GLOBAL_STREAM_REGISTRY.list_stream_paths()
-                        {
-                            for path in stream_paths {
-                                if let Some((script_uri, customization_function)) =
-                                    crate::stream_registry::GLOBAL_STREAM_REGISTRY
-                                        .get_stream_info(&path)
-                                {
-                                    all_routes.push(serde_json::json!({
-                                        "path": path,
-                                        "method": "STREAM",
-                                        "handler": customization_function,
-                                        "script_uri": script_uri,
-                                        "summary": serde_json::Value::Null,
-                                        "description": serde_json::Value::Null,
-                                        "tags": Vec::<String>::new(),
-                                    }));
-                                }
-                            }
-                        }
-
-                        for (path, registration) in
-                            crate::asset_registry::get_global_registry().get_all_registrations()
-                        {
-                            all_routes.push(serde_json::json!({
-                                "path": path,
-                                "method": "ASSET",
-                                "handler": registration.asset_name,
-                                "script_uri": registration.script_uri,
-                                "summary": serde_json::Value::Null,
-                                "description": serde_json::Value::Null,
-                                "tags": vec!["Assets"],
-                            }));
-                        }
-
-                        match serde_json::to_string(&all_routes) {
-                            Ok(json) => Ok(json),
-                            Err(e) => Ok(format!("Error serializing routes: {}", e)),
+                match crate::repository::drop_script_table(&script_uri_drop, &table_name) {
+                    Ok(existed) => {
+                        if existed {
+                            Ok(format!(
+                                "{{\"success\": true, \"tableName\": \"{}\", \"dropped\": true}}",
+                                table_name
+                            ))
+                        } else {
+                            Ok(format!(
+                                "{{\"success\": true, \"tableName\": \"{}\", \"dropped\": false, \"message\": \"Table did not exist\"}}",
+                                table_name
+                            ))
                         }
                     }
-                    Err(e) => Ok(format!("Error fetching routes: {}", e)),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("listRoutes", list_routes)?;
+        database_obj.set("dropTable", drop_table)?;
 
-        // 6b. generateOpenApi function
-        let user_ctx_openapi = user_context.clone();
-        let generate_openapi = Function::new(
+        // database.query(tableName, filters, limit, orderBy, orderDir)
+        // filters supports equality {"col": val} and range operators {"col": {"$gt": val, ...}}
+        let script_uri_query = script_uri_owned.clone();
+        let user_ctx_query = user_context.clone();
+        let query_table = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check capability
-                if let Err(_e) =
-                    user_ctx_openapi.require_capability(&crate::security::Capability::ReadScripts)
-                {
-                    return Ok("{\"error\": \"Insufficient permissions\"}".to_string());
-                }
-
-                // Get Rust-generated OpenAPI spec (includes health, GraphQL, MCP, auth endpoints)
-                let rust_spec_str = crate::get_rust_openapi_spec();
-                let mut rust_spec: serde_json::Value = match serde_json::from_str(&rust_spec_str) {
-                    Ok(spec) => spec,
-                    Err(e) => {
-                        return Ok(format!(
-                            "{{\"error\": \"Failed to parse Rust OpenAPI spec: {}\"}}",
-                            e
-                        ));
-                    }
-                };
-
-                // Get JavaScript-registered routes from script metadata
-                match repository::get_all_script_metadata() {
-                    Ok(metadata_list) => {
-                        let mut js_paths = serde_json::Map::new();
-
-                        for metadata in metadata_list {
-                            if metadata.initialized && !metadata.registrations.is_empty() {
-                                for ((path, method), route_meta) in metadata.registrations {
-                                    // Get or create path item
-                                    let path_item = js_paths
-                                        .entry(path.clone())
-                                        .or_insert_with(|| serde_json::json!({}));
-
-                                    let path_obj = path_item.as_object_mut().unwrap();
-
-                                    // Create operation object
-                                    let mut operation = serde_json::Map::new();
-                                    operation.insert(
-                                        "summary".to_string(),
-                                        serde_json::json!(
-                                            route_meta
-                                                .summary
-                                                .unwrap_or_else(|| format!("{} {}", method, path))
-                                        ),
-                                    );
-
-                                    if let Some(desc) = route_meta.description {
-                                        operation.insert(
-                                            "description".to_string(),
-                                            serde_json::json!(desc),
-                                        );
-                                    }
-
-                                    if !route_meta.tags.is_empty() {
-                                        operation.insert(
-                                            "tags".to_string(),
-                                            serde_json::json!(route_meta.tags),
-                                        );
-                                    } else {
-                                        operation
-                                            .insert("tags".to_string(), serde_json::json!(["API"]));
-                                    }
-
-                                    // Add parameters if present
-                                    if let Some(params) = &route_meta.parameters {
-                                        operation.insert("parameters".to_string(), params.clone());
-                                    }
-
-                                    // Add requestBody if present
-                                    if let Some(body) = &route_meta.request_body {
-                                        operation.insert("requestBody".to_string(), body.clone());
-                                    }
-
-                                    // Default response
-                                    operation.insert(
-                                        "responses".to_string(),
-                                        serde_json::json!({
-                                            "200": {
-                                                "description": "Success"
-                                            }
-                                        }),
-                                    );
-
-                                    // Add operation metadata
-                                    operation.insert(
-                                        "x-handler".to_string(),
-                                        serde_json::json!(route_meta.handler_name),
-                                    );
-                                    operation.insert(
-                                        "x-script-uri".to_string(),
-                                        serde_json::json!(metadata.uri),
-                                    );
-                                    operation.insert(
-                                        "x-source".to_string(),
-                                        serde_json::json!("javascript"),
-                                    );
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  filters: Opt<String>,
+                  limit: Opt<i32>,
+                  order_by: Opt<String>,
+                  order_dir: Opt<String>|
+                  -> JsResult<String> {
+                debug!(
+                    "database.query called for script {} on table: {}",
+                    script_uri_query, table_name
+                );
 
-                                    path_obj.insert(
-                                        method.to_lowercase(),
-                                        serde_json::json!(operation),
-                                    );
-                                }
-                            }
+                if user_ctx_query
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
+                }
+
+                let filters_map = if let Some(filters_str) = filters.0 {
+                    match serde_json::from_str::<std::collections::HashMap<String, serde_json::Value>>(
+                        &filters_str,
+                    ) {
+                        Ok(map) => Some(map),
+                        Err(e) => {
+                            return Ok(format!("{{\"error\": \"Invalid filters JSON: {}\"}}", e));
                         }
+                    }
+                } else {
+                    None
+                };
 
-                        // Add asset routes from the asset registry
-                        let asset_registrations =
-                            crate::asset_registry::get_global_registry().get_all_registrations();
+                let limit_val = limit.0.map(|l| l as i64);
+                let order_by_ref = order_by.0.as_deref();
+                let order_dir_ref = order_dir.0.as_deref();
 
-                        for (path, registration) in asset_registrations {
-                            // Determine MIME type based on file extension
-                            let extension = path.rsplit('.').next().unwrap_or("");
-                            let mime_type = match extension {
-                                "css" => "text/css",
-                                "js" => "application/javascript",
-                                "svg" => "image/svg+xml",
-                                "png" => "image/png",
-                                "jpg" | "jpeg" => "image/jpeg",
-                                "gif" => "image/gif",
-                                "ico" => "image/x-icon",
-                                "html" => "text/html",
-                                "json" => "application/json",
-                                "xml" => "application/xml",
-                                "pdf" => "application/pdf",
-                                "woff" | "woff2" => "font/woff2",
-                                "ttf" => "font/ttf",
-                                _ => "application/octet-stream",
-                            };
+                match crate::repository::query_table(
+                    &script_uri_query,
+                    &table_name,
+                    filters_map.as_ref(),
+                    limit_val,
+                    order_by_ref,
+                    order_dir_ref,
+                ) {
+                    Ok(rows) => match serde_json::to_string(&rows) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                    },
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
+            },
+        )?;
+        database_obj.set("query", query_table)?;
 
-                            let mut asset_operation = serde_json::Map::new();
-                            asset_operation.insert(
-                                "summary".to_string(),
-                                serde_json::json!(format!(
-                                    "Static asset: {}",
-                                    registration.asset_name
-                                )),
-                            );
-                            asset_operation.insert(
-                                "description".to_string(),
-                                serde_json::json!(format!(
-                                    "Serves static asset '{}' registered by script '{}'",
-                                    registration.asset_name, registration.script_uri
-                                )),
-                            );
-                            asset_operation
-                                .insert("tags".to_string(), serde_json::json!(["Assets"]));
-                            asset_operation.insert(
-                                "responses".to_string(),
-                                serde_json::json!({
-                                    "200": {
-                                        "description": "Asset content",
-                                        "content": {
-                                            mime_type: {
-                                                "schema": {
-                                                    "type": "string",
-                                                    "format": "binary"
-                                                }
-                                            }
-                                        }
-                                    },
-                                    "404": {
-                                        "description": "Asset not found"
-                                    }
-                                }),
-                            );
-                            asset_operation.insert(
-                                "x-asset-name".to_string(),
-                                serde_json::json!(registration.asset_name),
-                            );
-                            asset_operation.insert(
-                                "x-script-uri".to_string(),
-                                serde_json::json!(registration.script_uri),
-                            );
-                            asset_operation.insert(
-                                "x-source".to_string(),
-                                serde_json::json!("asset-registry"),
-                            );
+        // database.insert(tableName, data) - Insert a row
+        let script_uri_insert = script_uri_owned.clone();
+        let user_ctx_insert = user_context.clone();
+        let insert_row = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, table_name: String, data: String| -> JsResult<String> {
+                debug!(
+                    "database.insert called for script {} on table: {}",
+                    script_uri_insert, table_name
+                );
 
-                            // Add to js_paths so it gets merged
-                            let path_entry = js_paths
-                                .entry(path)
-                                .or_insert_with(|| serde_json::json!({}));
+                if user_ctx_insert
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
+                }
 
-                            if let Some(path_obj) = path_entry.as_object_mut() {
-                                path_obj
-                                    .insert("get".to_string(), serde_json::json!(asset_operation));
-                            }
-                        }
+                // Parse data from JSON string
+                let data_map = match serde_json::from_str::<
+                    std::collections::HashMap<String, serde_json::Value>,
+                >(&data)
+                {
+                    Ok(map) => map,
+                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
+                };
 
-                        // Merge JavaScript paths into Rust spec
-                        if let Some(rust_paths) = rust_spec["paths"].as_object_mut() {
-                            for (path, operations) in js_paths {
-                                // If path exists in both, merge operations
-                                if let Some(existing) = rust_paths.get_mut(&path) {
-                                    if let (Some(existing_obj), Some(new_ops)) =
-                                        (existing.as_object_mut(), operations.as_object())
-                                    {
-                                        for (method, operation) in new_ops {
-                                            existing_obj.insert(method.clone(), operation.clone());
-                                        }
-                                    }
-                                } else {
-                                    // Path doesn't exist in Rust spec, add it
-                                    rust_paths.insert(path, operations);
-                                }
-                            }
-                        }
+                match crate::repository::insert_row(&script_uri_insert, &table_name, &data_map) {
+                    Ok(row) => match serde_json::to_string(&row) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                    },
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
+            },
+        )?;
+        database_obj.set("insert", insert_row)?;
 
-                        // Collect all unique tags
-                        let mut all_tags = std::collections::HashSet::new();
-                        if let Some(paths) = rust_spec["paths"].as_object() {
-                            for operations in paths.values() {
-                                if let Some(ops) = operations.as_object() {
-                                    for operation in ops.values() {
-                                        if let Some(tags) = operation["tags"].as_array() {
-                                            for tag in tags {
-                                                if let Some(tag_str) = tag.as_str() {
-                                                    all_tags.insert(tag_str.to_string());
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        // database.update(tableName, id, data) - Update a row
+        let script_uri_update = script_uri_owned.clone();
+        let user_ctx_update = user_context.clone();
+        let update_row = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  id: i32,
+                  data: String|
+                  -> JsResult<String> {
+                debug!(
+                    "database.update called for script {} on table: {}, id: {}",
+                    script_uri_update, table_name, id
+                );
 
-                        // Update tags in the spec if there are new ones from JavaScript
-                        if let Some(existing_tags) = rust_spec["tags"].as_array() {
-                            for tag_obj in existing_tags {
-                                if let Some(name) = tag_obj["name"].as_str() {
-                                    all_tags.insert(name.to_string());
-                                }
-                            }
-                        }
+                if user_ctx_update
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
+                }
 
-                        // Serialize the merged spec
-                        match serde_json::to_string_pretty(&rust_spec) {
-                            Ok(json) => Ok(json),
-                            Err(e) => Ok(format!(
-                                "{{\"error\": \"Failed to serialize merged OpenAPI spec: {}\"}}",
-                                e
-                            )),
-                        }
-                    }
-                    Err(e) => Ok(format!(
-                        "{{\"error\": \"Failed to fetch JavaScript routes: {}\"}}",
-                        e
-                    )),
+                // Parse data from JSON string
+                let data_map = match serde_json::from_str::<
+                    std::collections::HashMap<String, serde_json::Value>,
+                >(&data)
+                {
+                    Ok(map) => map,
+                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
+                };
+
+                match crate::repository::update_row(&script_uri_update, &table_name, id, &data_map)
+                {
+                    Ok(row) => match serde_json::to_string(&row) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                    },
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("generateOpenApi", generate_openapi)?;
+        database_obj.set("update", update_row)?;
 
-        // 7. listStreams function
-        let user_ctx_list_streams = user_context.clone();
-        let list_streams = Function::new(
+        // database.delete(tableName, id) - Delete a row
+        let script_uri_delete = script_uri_owned.clone();
+        let user_ctx_delete = user_context.clone();
+        let delete_row = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check capability
-                if let Err(_e) = user_ctx_list_streams
-                    .require_capability(&crate::security::Capability::ReadScripts)
+            move |_ctx: rquickjs::Ctx<'_>, table_name: String, id: i32| -> JsResult<String> {
+                debug!(
+                    "database.delete called for script {} on table: {}, id: {}",
+                    script_uri_delete, table_name, id
+                );
+
+                if user_ctx_delete
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    return Ok("[]".to_string());
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                // Get streams with metadata
-                match crate::stream_registry::GLOBAL_STREAM_REGISTRY.list_streams_with_metadata() {
-                    Ok(streams) => {
-                        let stream_objects: Vec<serde_json::Value> = streams
-                            .iter()
-                            .map(|(path, script_uri)| {
-                                serde_json::json!({
-                                    "path": path,
-                                    "script_uri": script_uri,
-                                })
-                            })
-                            .collect();
-
-                        match serde_json::to_string(&stream_objects) {
-                            Ok(json) => Ok(json),
-                            Err(e) => Ok(format!("Error serializing streams: {}", e)),
-                        }
-                    }
-                    Err(e) => Ok(format!("Error fetching streams: {}", e)),
+                match crate::repository::delete_row(&script_uri_delete, &table_name, id) {
+                    Ok(deleted) => Ok(format!("{{\"success\": true, \"deleted\": {}}}", deleted)),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        route_registry.set("listStreams", list_streams)?;
+        database_obj.set("delete", delete_row)?;
 
-        // 8. listAssets function
-        let user_ctx_list_assets = user_context.clone();
-        let list_assets = Function::new(
+        // database.upsert(tableName, keyColumns, data)
+        // INSERT … ON CONFLICT DO UPDATE — atomically insert or update by key
+        let script_uri_upsert = script_uri_owned.clone();
+        let user_ctx_upsert = user_context.clone();
+        let upsert_row = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<Vec<String>> {
-                // Check capability
-                if let Err(_e) = user_ctx_list_assets
-                    .require_capability(&crate::security::Capability::ReadAssets)
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  key_columns_json: String,
+                  data: String|
+                  -> JsResult<String> {
+                debug!(
+                    "database.upsert called for script {} on table: {}",
+                    script_uri_upsert, table_name
+                );
+
+                if user_ctx_upsert
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    return Ok(Vec::new());
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
                 }
 
-                let assets = repository::fetch_assets("https://example.com/core");
-                let asset_names: Vec<String> = assets.keys().cloned().collect();
-                Ok(asset_names)
-            },
-        )?;
-        route_registry.set("listAssets", list_assets)?;
-
-        // Set the routeRegistry object on global scope
-        global.set("routeRegistry", route_registry)?;
+                // key_columns is a JSON array of strings, or a single string
+                let key_cols: Vec<String> = match serde_json::from_str::<serde_json::Value>(
+                    &key_columns_json,
+                ) {
+                    Ok(serde_json::Value::Array(arr)) => arr
+                        .into_iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    Ok(serde_json::Value::String(s)) => vec![s],
+                    _ => {
+                        return Ok("{\"error\": \"keyColumns must be a JSON array of strings or a single string\"}".to_string());
+                    }
+                };
 
-        Ok(())
-    }
+                let data_map = match serde_json::from_str::<
+                    std::collections::HashMap<String, serde_json::Value>,
+                >(&data)
+                {
+                    Ok(map) => map,
+                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
+                };
 
-    /// Setup fetch() function for HTTP requests with secret injection
-    fn setup_fetch_function(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let script_uri_owned = script_uri.to_string();
-        // Capture the user_id at script setup time for secret lookup in user_secrets
-        let user_id_for_fetch = self.user_context.user_id.clone();
+                match crate::repository::upsert_row(
+                    &script_uri_upsert,
+                    &table_name,
+                    &key_cols,
+                    &data_map,
+                ) {
+                    Ok(row) => match serde_json::to_string(&row) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                    },
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
+            },
+        )?;
+        database_obj.set("upsert", upsert_row)?;
 
-        // Create the fetch function (synchronous version)
-        let fetch_fn = Function::new(
+        // database.deleteWhere(tableName, filters)
+        // Bulk-delete rows matching filter conditions (equality + range operators)
+        let script_uri_dw = script_uri_owned.clone();
+        let user_ctx_dw = user_context.clone();
+        let delete_where = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  url: String,
-                  options_json: Option<String>|
+                  table_name: String,
+                  filters: String|
                   -> JsResult<String> {
-                // Parse options from JSON string
-                let options: crate::http_client::FetchOptions = if let Some(json_str) = options_json
-                {
-                    serde_json::from_str(&json_str).map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "options",
-                            "FetchOptions",
-                            &format!("Invalid fetch options: {}", e),
-                        )
-                    })?
-                } else {
-                    Default::default()
-                };
-
-                tracing::debug!("Fetching URL: {} from script: {}", url, script_uri_owned);
-
-                // Create HTTP client
-                let client = crate::http_client::HttpClient::new().map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "fetch",
-                        "client_init",
-                        &format!("Failed to create HTTP client: {}", e),
-                    )
-                })?;
+                debug!(
+                    "database.deleteWhere called for script {} on table: {}",
+                    script_uri_dw, table_name
+                );
 
-                // Perform the fetch (synchronous) with script_uri and user_id for secret resolution
-                let response = client
-                    .fetch(
-                        url.clone(),
-                        options,
-                        Some(&script_uri_owned),
-                        user_id_for_fetch.as_deref(),
-                    )
-                    .map_err(|e| {
-                        rquickjs::Error::new_from_js_message(
-                            "fetch",
-                            "request_failed",
-                            &format!("Fetch error: {}", e),
-                        )
-                    })?;
+                if user_ctx_dw
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
+                            .to_string(),
+                    );
+                }
 
-                // Convert response to JSON string
-                let response_json = serde_json::to_string(&response).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "fetch",
-                        "serialize",
-                        &format!("Failed to serialize response: {}", e),
-                    )
-                })?;
+                let filters_map = match serde_json::from_str::<
+                    std::collections::HashMap<String, serde_json::Value>,
+                >(&filters)
+                {
+                    Ok(map) => map,
+                    Err(e) => return Ok(format!("{{\"error\": \"Invalid filters JSON: {}\"}}", e)),
+                };
 
-                Ok(response_json)
+                match crate::repository::delete_where(&script_uri_dw, &table_name, &filters_map) {
+                    Ok(count) => Ok(format!("{{\"success\": true, \"deleted\": {}}}", count)),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
             },
         )?;
+        database_obj.set("deleteWhere", delete_where)?;
 
-        global.set("fetch", fetch_fn)?;
-        debug!("fetch() function initialized with secret injection support");
-
-        Ok(())
-    }
-
-    /// Setup database functions
-    fn setup_database_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        let global = ctx.globals();
-        let script_uri_owned = script_uri.to_string();
-        let user_context = self.user_context.clone();
-
-        // Create the database namespace object for schema management
-        let database_obj = rquickjs::Object::new(ctx.clone())?;
-
-        // database.createTable(tableName) - Create a new table for this script
-        let script_uri_create = script_uri_owned.clone();
-        let user_ctx_create = user_context.clone();
-        let create_table = Function::new(
+        // database.acquireLease(tableName, leaseId, owner, ttlMs)
+        // Atomic compare-and-swap lease acquisition using a script-owned lease table
+        let script_uri_lease = script_uri_owned.clone();
+        let user_ctx_lease = user_context.clone();
+        let acquire_lease = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>,
+                  table_name: String,
+                  lease_id: String,
+                  owner: String,
+                  ttl_ms: i64|
+                  -> JsResult<String> {
                 debug!(
-                    "database.createTable called for script {} with table: {}",
-                    script_uri_create, table_name
+                    "database.acquireLease called for script {} on table: {}, lease: {}",
+                    script_uri_lease, table_name, lease_id
                 );
 
-                // Check permission
-                if user_ctx_create
+                if user_ctx_lease
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
                     return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
                             .to_string(),
                     );
                 }
 
-                match crate::repository::create_script_table(&script_uri_create, &table_name) {
-                    Ok(physical_name) => Ok(format!(
-                        "{{\"success\": true, \"tableName\": \"{}\", \"physicalName\": \"{}\"}}",
-                        table_name, physical_name
-                    )),
+                match crate::repository::acquire_lease(
+                    &script_uri_lease,
+                    &table_name,
+                    &lease_id,
+                    &owner,
+                    ttl_ms,
+                ) {
+                    Ok(result) => match serde_json::to_string(&result) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                    },
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("createTable", create_table)?;
+        database_obj.set("acquireLease", acquire_lease)?;
 
-        // database.addIntegerColumn(tableName, columnName, nullable, defaultValue)
-        let script_uri_add_int = script_uri_owned.clone();
-        let user_ctx_add_int = user_context.clone();
-        let add_integer_column = Function::new(
+        // database.createLeaseTable(tableName)
+        // Create a correctly-structured lease table with a UNIQUE constraint on lease_id
+        let script_uri_clt = script_uri_owned.clone();
+        let user_ctx_clt = user_context.clone();
+        let create_lease_table = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  column_name: String,
-                  nullable: Opt<bool>,
-                  default_value: Opt<String>|
-                  -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
                 debug!(
-                    "database.addIntegerColumn called for script {}",
-                    script_uri_add_int
+                    "database.createLeaseTable called for script {} with table: {}",
+                    script_uri_clt, table_name
                 );
 
-                if user_ctx_add_int
+                if user_ctx_clt
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
@@ -4030,44 +8143,33 @@ impl SecureGlobalContext {
                     );
                 }
 
-                let nullable = nullable.0.unwrap_or(true);
-                let default_val = default_value.0.as_deref();
-
-                match crate::repository::add_column_to_script_table(
-                    &script_uri_add_int,
-                    &table_name,
-                    &column_name,
-                    crate::db_schema_utils::ColumnType::Integer,
-                    nullable,
-                    default_val,
-                ) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"column\": \"{}\"}}",
-                        column_name
+                match crate::repository::create_lease_table(&script_uri_clt, &table_name) {
+                    Ok(physical_name) => Ok(format!(
+                        "{{\"success\": true, \"tableName\": \"{}\", \"physicalName\": \"{}\"}}",
+                        table_name, physical_name
                     )),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("addIntegerColumn", add_integer_column)?;
+        database_obj.set("createLeaseTable", create_lease_table)?;
 
-        // database.addTextColumn(tableName, columnName, nullable, defaultValue)
-        let script_uri_add_text = script_uri_owned.clone();
-        let user_ctx_add_text = user_context.clone();
-        let add_text_column = Function::new(
+        // database.addUniqueIndex(tableName, columns)
+        // Add a unique index to enable upsert() with a conflict target
+        let script_uri_idx = script_uri_owned.clone();
+        let user_ctx_idx = user_context.clone();
+        let add_unique_index = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
                   table_name: String,
-                  column_name: String,
-                  nullable: Opt<bool>,
-                  default_value: Opt<String>|
+                  columns_json: String|
                   -> JsResult<String> {
                 debug!(
-                    "database.addTextColumn called for script {}",
-                    script_uri_add_text
+                    "database.addUniqueIndex called for script {} on table: {}",
+                    script_uri_idx, table_name
                 );
 
-                if user_ctx_add_text
+                if user_ctx_idx
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
@@ -4077,272 +8179,300 @@ impl SecureGlobalContext {
                     );
                 }
 
-                let nullable = nullable.0.unwrap_or(true);
-                let default_val = default_value.0.as_deref();
-
-                match crate::repository::add_column_to_script_table(
-                    &script_uri_add_text,
-                    &table_name,
-                    &column_name,
-                    crate::db_schema_utils::ColumnType::Text,
-                    nullable,
-                    default_val,
+                let columns: Vec<String> = match serde_json::from_str::<serde_json::Value>(
+                    &columns_json,
                 ) {
+                    Ok(serde_json::Value::Array(arr)) => arr
+                        .into_iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    Ok(serde_json::Value::String(s)) => vec![s],
+                    _ => {
+                        return Ok(
+                                "{\"error\": \"columns must be a JSON array of strings or a single string\"}"
+                                    .to_string(),
+                            );
+                    }
+                };
+
+                match crate::repository::add_unique_index(&script_uri_idx, &table_name, &columns) {
                     Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"column\": \"{}\"}}",
-                        column_name
+                        "{{\"success\": true, \"tableName\": \"{}\", \"columns\": {}}}",
+                        table_name, columns_json
                     )),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("addTextColumn", add_text_column)?;
+        database_obj.set("addUniqueIndex", add_unique_index)?;
 
-        // database.addBooleanColumn(tableName, columnName, nullable, defaultValue)
-        let script_uri_add_bool = script_uri_owned.clone();
-        let user_ctx_add_bool = user_context.clone();
-        let add_boolean_column = Function::new(
+        // database.generateGraphQLForTable
+        let script_uri_graphql = script_uri_owned.clone();
+        let user_ctx_graphql = user_context.clone();
+        let generate_graphql = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
+            move |ctx_inner: rquickjs::Ctx<'_>,
                   table_name: String,
-                  column_name: String,
-                  nullable: Opt<bool>,
-                  default_value: Opt<String>|
+                  options: Opt<String>|
                   -> JsResult<String> {
                 debug!(
-                    "database.addBooleanColumn called for script {}",
-                    script_uri_add_bool
+                    "database.generateGraphQLForTable called for script {} on table: {}",
+                    script_uri_graphql, table_name
                 );
 
-                if user_ctx_add_bool
+                if user_ctx_graphql
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
                     return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
+                        "{\"error\": \"Insufficient permissions for database operations\"}"
                             .to_string(),
                     );
                 }
 
-                let nullable = nullable.0.unwrap_or(true);
-                let default_val = default_value.0.as_deref();
+                // Parse options (default: ScriptInternal visibility)
+                let visibility = if let Some(opts_str) = options.0 {
+                    match serde_json::from_str::<serde_json::Value>(&opts_str) {
+                        Ok(opts) => opts
+                            .get("visibility")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("script_internal")
+                            .to_string(),
+                        Err(_) => "script_internal".to_string(),
+                    }
+                } else {
+                    "script_internal".to_string()
+                };
 
-                match crate::repository::add_column_to_script_table(
-                    &script_uri_add_bool,
+                // Get table schema
+                let schema =
+                    match crate::repository::get_table_schema(&script_uri_graphql, &table_name) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            return Ok(format!(
+                                "{{\"error\": \"Failed to get table schema: {}\"}}",
+                                e
+                            ));
+                        }
+                    };
+
+                // Get foreign keys
+                let foreign_keys =
+                    match crate::repository::get_foreign_keys(&script_uri_graphql, &table_name) {
+                        Ok(fks) => fks,
+                        Err(e) => {
+                            return Ok(format!(
+                                "{{\"error\": \"Failed to get foreign keys: {}\"}}",
+                                e
+                            ));
+                        }
+                    };
+
+                // Generate GraphQL operations
+                let operations = crate::graphql_schema_gen::generate_table_operations(
                     &table_name,
-                    &column_name,
-                    crate::db_schema_utils::ColumnType::Boolean,
-                    nullable,
-                    default_val,
-                ) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"column\": \"{}\"}}",
-                        column_name
-                    )),
+                    &schema,
+                    &foreign_keys,
+                );
+
+                // Inject resolver functions into JavaScript context
+                for query in &operations.queries {
+                    // Evaluate resolver code in the current context
+                    if let Err(e) = ctx_inner.eval::<(), _>(query.resolver_code.as_str()) {
+                        return Ok(format!(
+                            "{{\"error\": \"Failed to inject resolver {}: {:?}\"}}",
+                            query.resolver_function_name, e
+                        ));
+                    }
+                }
+
+                for mutation in &operations.mutations {
+                    if let Err(e) = ctx_inner.eval::<(), _>(mutation.resolver_code.as_str()) {
+                        return Ok(format!(
+                            "{{\"error\": \"Failed to inject resolver {}: {:?}\"}}",
+                            mutation.resolver_function_name, e
+                        ));
+                    }
+                }
+
+                // Register queries
+                for query in &operations.queries {
+                    if let Err(e) = crate::graphql::register_graphql_query(
+                        query.name.clone(),
+                        query.sdl.clone(),
+                        query.resolver_function_name.clone(),
+                        script_uri_graphql.clone(),
+                        visibility.clone(),
+                    ) {
+                        return Ok(format!(
+                            "{{\"error\": \"Failed to register query {}: {}\"}}",
+                            query.name, e
+                        ));
+                    }
+                }
+
+                // Register mutations
+                for mutation in &operations.mutations {
+                    if let Err(e) = crate::graphql::register_graphql_mutation(
+                        mutation.name.clone(),
+                        mutation.sdl.clone(),
+                        mutation.resolver_function_name.clone(),
+                        script_uri_graphql.clone(),
+                        visibility.clone(),
+                    ) {
+                        return Ok(format!(
+                            "{{\"error\": \"Failed to register mutation {}: {}\"}}",
+                            mutation.name, e
+                        ));
+                    }
+                }
+
+                // Return success with operation names
+                let query_names: Vec<&str> =
+                    operations.queries.iter().map(|q| q.name.as_str()).collect();
+                let mutation_names: Vec<&str> = operations
+                    .mutations
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .collect();
+
+                Ok(format!(
+                    "{{\"success\": true, \"table\": \"{}\", \"queries\": {:?}, \"mutations\": {:?}}}",
+                    table_name, query_names, mutation_names
+                ))
+            },
+        )?;
+        database_obj.set("generateGraphQLForTable", generate_graphql)?;
+
+        // Transaction management functions
+
+        // database.beginTransaction(timeoutMs?) - Start a new transaction or savepoint
+        let begin_transaction = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, timeout_ms: Opt<u64>| -> JsResult<String> {
+                match crate::database::Database::begin_transaction(timeout_ms.0) {
+                    Ok(_guard) => {
+                        // Note: The guard is not stored here - it's managed internally
+                        // Auto-commit/rollback happens at handler boundaries
+                        Ok("{\"success\": true, \"message\": \"Transaction started\"}".to_string())
+                    }
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                }
+            },
+        )?;
+        database_obj.set("beginTransaction", begin_transaction)?;
+
+        // database.commitTransaction() - Commit the current transaction or release savepoint
+        let commit_transaction = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                match crate::database::Database::commit_transaction() {
+                    Ok(()) => Ok(
+                        "{\"success\": true, \"message\": \"Transaction committed\"}".to_string(),
+                    ),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("addBooleanColumn", add_boolean_column)?;
+        database_obj.set("commitTransaction", commit_transaction)?;
 
-        // database.addTimestampColumn(tableName, columnName, nullable, defaultValue)
-        let script_uri_add_ts = script_uri_owned.clone();
-        let user_ctx_add_ts = user_context.clone();
-        let add_timestamp_column = Function::new(
+        // database.rollbackTransaction() - Rollback the current transaction or to savepoint
+        let rollback_transaction = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  column_name: String,
-                  nullable: Opt<bool>,
-                  default_value: Opt<String>|
-                  -> JsResult<String> {
-                debug!(
-                    "database.addTimestampColumn called for script {}",
-                    script_uri_add_ts
-                );
-
-                if user_ctx_add_ts
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                match crate::database::Database::rollback_transaction() {
+                    Ok(()) => Ok(
+                        "{\"success\": true, \"message\": \"Transaction rolled back\"}".to_string(),
+                    ),
+                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
+            },
+        )?;
+        database_obj.set("rollbackTransaction", rollback_transaction)?;
 
-                let nullable = nullable.0.unwrap_or(true);
-                let default_val = default_value.0.as_deref();
-
-                match crate::repository::add_column_to_script_table(
-                    &script_uri_add_ts,
-                    &table_name,
-                    &column_name,
-                    crate::db_schema_utils::ColumnType::Timestamp,
-                    nullable,
-                    default_val,
-                ) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"column\": \"{}\"}}",
-                        column_name
+        // database.createSavepoint(name?) - Create a named or auto-generated savepoint
+        let create_savepoint = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, name: Opt<String>| -> JsResult<String> {
+                match crate::database::Database::create_savepoint(name.0.as_deref()) {
+                    Ok(savepoint_name) => Ok(format!(
+                        "{{\"success\": true, \"savepoint\": \"{}\"}}",
+                        savepoint_name
                     )),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("addTimestampColumn", add_timestamp_column)?;
+        database_obj.set("createSavepoint", create_savepoint)?;
 
-        // database.addReferenceColumn(tableName, columnName, referencedTableName, nullable)
-        let script_uri_ref = script_uri_owned.clone();
-        let user_ctx_ref = user_context.clone();
-        let add_reference_column = Function::new(
+        // database.rollbackToSavepoint(name) - Rollback to a named savepoint
+        let rollback_to_savepoint = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  column_name: String,
-                  referenced_table_name: String,
-                  nullable: Opt<bool>|
-                  -> JsResult<String> {
-                debug!(
-                    "database.addReferenceColumn called for script {}",
-                    script_uri_ref
-                );
-
-                if user_ctx_ref
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                let nullable = nullable.0.unwrap_or(true);
-
-                match crate::repository::add_reference_column(
-                    &script_uri_ref,
-                    &table_name,
-                    &column_name,
-                    &referenced_table_name,
-                    nullable,
-                ) {
+            move |_ctx: rquickjs::Ctx<'_>, name: String| -> JsResult<String> {
+                match crate::database::Database::rollback_to_savepoint(&name) {
                     Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"foreignKey\": \"{}.{} -> {}\", \"nullable\": {}}}",
-                        table_name, column_name, referenced_table_name, nullable
+                        "{{\"success\": true, \"message\": \"Rolled back to savepoint: {}\"}}",
+                        name
                     )),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("addReferenceColumn", add_reference_column)?;
+        database_obj.set("rollbackToSavepoint", rollback_to_savepoint)?;
 
-        // database.dropColumn(tableName, columnName)
-        let script_uri_drop_col = script_uri_owned.clone();
-        let user_ctx_drop_col = user_context.clone();
-        let drop_column = Function::new(
+        // database.releaseSavepoint(name) - Release a named savepoint
+        let release_savepoint = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  column_name: String|
-                  -> JsResult<String> {
-                debug!(
-                    "database.dropColumn called for script {} with table: {}, column: {}",
-                    script_uri_drop_col, table_name, column_name
-                );
-
-                if user_ctx_drop_col
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                match crate::repository::drop_column(
-                    &script_uri_drop_col,
-                    &table_name,
-                    &column_name,
-                ) {
-                    Ok(existed) => {
-                        if existed {
-                            Ok(format!(
-                                "{{\"success\": true, \"tableName\": \"{}\", \"columnName\": \"{}\", \"dropped\": true}}",
-                                table_name, column_name
-                            ))
-                        } else {
-                            Ok(format!(
-                                "{{\"success\": true, \"tableName\": \"{}\", \"columnName\": \"{}\", \"dropped\": false, \"message\": \"Column did not exist\"}}",
-                                table_name, column_name
-                            ))
-                        }
-                    }
+            move |_ctx: rquickjs::Ctx<'_>, name: String| -> JsResult<String> {
+                match crate::database::Database::release_savepoint(&name) {
+                    Ok(()) => Ok(format!(
+                        "{{\"success\": true, \"message\": \"Released savepoint: {}\"}}",
+                        name
+                    )),
                     Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
                 }
             },
         )?;
-        database_obj.set("dropColumn", drop_column)?;
+        database_obj.set("releaseSavepoint", release_savepoint)?;
 
-        // database.dropTable(tableName)
-        let script_uri_drop = script_uri_owned.clone();
-        let user_ctx_drop = user_context.clone();
-        let drop_table = Function::new(
+        // database.checkDatabaseHealth() - Check database health status
+        let check_db_health = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
-                debug!(
-                    "database.dropTable called for script {} with table: {}",
-                    script_uri_drop, table_name
-                );
-
-                if user_ctx_drop
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                match crate::repository::drop_script_table(&script_uri_drop, &table_name) {
-                    Ok(existed) => {
-                        if existed {
-                            Ok(format!(
-                                "{{\"success\": true, \"tableName\": \"{}\", \"dropped\": true}}",
-                                table_name
-                            ))
-                        } else {
-                            Ok(format!(
-                                "{{\"success\": true, \"tableName\": \"{}\", \"dropped\": false, \"message\": \"Table did not exist\"}}",
-                                table_name
-                            ))
-                        }
-                    }
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Call the database health check
+                let result = crate::database::Database::check_health_sync();
+                Ok(result)
             },
         )?;
-        database_obj.set("dropTable", drop_table)?;
+        database_obj.set("checkDatabaseHealth", check_db_health)?;
 
-        // database.query(tableName, filters, limit, orderBy, orderDir)
-        // filters supports equality {"col": val} and range operators {"col": {"$gt": val, ...}}
-        let script_uri_query = script_uri_owned.clone();
-        let user_ctx_query = user_context.clone();
-        let query_table = Function::new(
+        // Set the database object on the global scope
+        global.set("database", database_obj)?;
+
+        debug!(
+            "database JavaScript API initialized for script: {}",
+            script_uri
+        );
+
+        Ok(())
+    }
+
+    /// Setup the `db` global for parameterized SQL in the script's private schema
+    fn setup_db_query_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let global = ctx.globals();
+        let db_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // db.query(sql, params, { maxRows, timeoutMs }) - Run one statement with bound parameters
+        let script_uri_query = script_uri.to_string();
+        let user_ctx_query = self.user_context.clone();
+        let query = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  filters: Opt<String>,
-                  limit: Opt<i32>,
-                  order_by: Opt<String>,
-                  order_dir: Opt<String>|
+                  sql: String,
+                  params: Opt<rquickjs::Value<'_>>,
+                  options: Opt<rquickjs::Object<'_>>|
                   -> JsResult<String> {
-                debug!(
-                    "database.query called for script {} on table: {}",
-                    script_uri_query, table_name
-                );
+                debug!("db.query called for script {}", script_uri_query);
 
                 if user_ctx_query
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
@@ -4354,53 +8484,44 @@ impl SecureGlobalContext {
                     );
                 }
 
-                let filters_map = if let Some(filters_str) = filters.0 {
-                    match serde_json::from_str::<std::collections::HashMap<String, serde_json::Value>>(
-                        &filters_str,
-                    ) {
-                        Ok(map) => Some(map),
-                        Err(e) => {
-                            return Ok(format!("{{\"error\": \"Invalid filters JSON: {}\"}}", e));
-                        }
-                    }
-                } else {
-                    None
-                };
-
-                let limit_val = limit.0.map(|l| l as i64);
-                let order_by_ref = order_by.0.as_deref();
-                let order_dir_ref = order_dir.0.as_deref();
+                let error_json =
+                    |message: String| serde_json::json!({ "error": message }).to_string();
 
-                match crate::repository::query_table(
-                    &script_uri_query,
-                    &table_name,
-                    filters_map.as_ref(),
-                    limit_val,
-                    order_by_ref,
-                    order_dir_ref,
-                ) {
-                    Ok(rows) => match serde_json::to_string(&rows) {
+                let params = match params.0 {
+                    Some(params) => match read_script_query_params(params) {
+                        Ok(params) => params,
+                        Err(e) => return Ok(error_json(e)),
+                    },
+                    None => Vec::new(),
+                };
+                let options = match options.0.as_ref() {
+                    Some(options) => match read_script_query_options(options) {
+                        Ok(options) => options,
+                        Err(e) => return Ok(error_json(e)),
+                    },
+                    None => repository::ScriptQueryOptions::default(),
+                };
+
+                match repository::run_script_query(&script_uri_query, &sql, &params, &options) {
+                    Ok(result) => match serde_json::to_string(&result) {
                         Ok(json) => Ok(json),
-                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                        Err(e) => Ok(error_json(format!("Serialization error: {}", e))),
                     },
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                    Err(e) => Ok(error_json(e.to_string())),
                 }
             },
         )?;
-        database_obj.set("query", query_table)?;
+        db_obj.set("query", query)?;
 
-        // database.insert(tableName, data) - Insert a row
-        let script_uri_insert = script_uri_owned.clone();
-        let user_ctx_insert = user_context.clone();
-        let insert_row = Function::new(
+        // db.migrate([{ version, up }]) - Apply pending migrations, typically from init()
+        let script_uri_migrate = script_uri.to_string();
+        let user_ctx_migrate = self.user_context.clone();
+        let migrate = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, table_name: String, data: String| -> JsResult<String> {
-                debug!(
-                    "database.insert called for script {} on table: {}",
-                    script_uri_insert, table_name
-                );
+            move |_ctx: rquickjs::Ctx<'_>, migrations: rquickjs::Value<'_>| -> JsResult<String> {
+                debug!("db.migrate called for script {}", script_uri_migrate);
 
-                if user_ctx_insert
+                if user_ctx_migrate
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
@@ -4410,42 +8531,40 @@ impl SecureGlobalContext {
                     );
                 }
 
-                // Parse data from JSON string
-                let data_map = match serde_json::from_str::<
-                    std::collections::HashMap<String, serde_json::Value>,
-                >(&data)
-                {
-                    Ok(map) => map,
-                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
+                let error_json =
+                    |message: String| serde_json::json!({ "error": message }).to_string();
+
+                let migrations = match read_script_migrations(migrations) {
+                    Ok(migrations) => migrations,
+                    Err(e) => return Ok(error_json(e)),
                 };
 
-                match crate::repository::insert_row(&script_uri_insert, &table_name, &data_map) {
-                    Ok(row) => match serde_json::to_string(&row) {
+                match repository::migrate_script_schema(&script_uri_migrate, &migrations) {
+                    Ok(summary) => match serde_json::to_string(&summary) {
                         Ok(json) => Ok(json),
-                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
+                        Err(e) => Ok(error_json(format!("Serialization error: {}", e))),
                     },
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                    Err(e) => Ok(error_json(e.to_string())),
                 }
             },
         )?;
-        database_obj.set("insert", insert_row)?;
+        db_obj.set("migrate", migrate)?;
 
-        // database.update(tableName, id, data) - Update a row
-        let script_uri_update = script_uri_owned.clone();
-        let user_ctx_update = user_context.clone();
-        let update_row = Function::new(
+        // db.listen(channel, handlerName) - Run a handler for each NOTIFY on a channel
+        let script_uri_listen = script_uri.to_string();
+        let user_ctx_listen = self.user_context.clone();
+        let listen = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  id: i32,
-                  data: String|
+                  channel: String,
+                  handler_name: String|
                   -> JsResult<String> {
                 debug!(
-                    "database.update called for script {} on table: {}, id: {}",
-                    script_uri_update, table_name, id
+                    "db.listen called for script {} on channel {}",
+                    script_uri_listen, channel
                 );
 
-                if user_ctx_update
+                if user_ctx_listen
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
@@ -4455,652 +8574,935 @@ impl SecureGlobalContext {
                     );
                 }
 
-                // Parse data from JSON string
-                let data_map = match serde_json::from_str::<
-                    std::collections::HashMap<String, serde_json::Value>,
-                >(&data)
-                {
-                    Ok(map) => map,
-                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
-                };
+                let handler_name = handler_name.trim();
+                if handler_name.is_empty() {
+                    return Ok(serde_json::json!({
+                        "error": "db.listen requires a non-empty handler name"
+                    })
+                    .to_string());
+                }
 
-                match crate::repository::update_row(&script_uri_update, &table_name, id, &data_map)
-                {
-                    Ok(row) => match serde_json::to_string(&row) {
-                        Ok(json) => Ok(json),
-                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
-                    },
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                match crate::notifications::register_script_channel(
+                    &script_uri_listen,
+                    &channel,
+                    handler_name,
+                ) {
+                    Ok(added) => Ok(serde_json::json!({
+                        "channel": channel,
+                        "handler": handler_name,
+                        "added": added,
+                    })
+                    .to_string()),
+                    Err(e) => Ok(serde_json::json!({ "error": e }).to_string()),
                 }
             },
         )?;
-        database_obj.set("update", update_row)?;
+        db_obj.set("listen", listen)?;
 
-        // database.delete(tableName, id) - Delete a row
-        let script_uri_delete = script_uri_owned.clone();
-        let user_ctx_delete = user_context.clone();
-        let delete_row = Function::new(
+        global.set("db", db_obj)?;
+
+        debug!("db JavaScript API initialized for script: {}", script_uri);
+
+        Ok(())
+    }
+
+    /// Setup the vector collection functions (`vectors.*`)
+    fn setup_vector_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let vectors_obj = rquickjs::Object::new(ctx.clone())?;
+        let error_json = |message: String| serde_json::json!({ "error": message }).to_string();
+        let permission_error =
+            "{\"error\": \"Insufficient permissions for database operations\"}".to_string();
+
+        // vectors.upsert(collection, documents, embeddingOptions) - Store documents,
+        // embedding the text of those without an embedding
+        let script_uri_upsert = script_uri.to_string();
+        let user_ctx_upsert = self.user_context.clone();
+        let denied = permission_error.clone();
+        let upsert = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, table_name: String, id: i32| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>,
+                  collection: String,
+                  documents: rquickjs::Value<'_>,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
                 debug!(
-                    "database.delete called for script {} on table: {}, id: {}",
-                    script_uri_delete, table_name, id
+                    "vectors.upsert called for script {} on collection {}",
+                    script_uri_upsert, collection
                 );
-
-                if user_ctx_delete
+                if user_ctx_upsert
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database operations\"}"
-                            .to_string(),
-                    );
+                    return Ok(denied.clone());
                 }
 
-                match crate::repository::delete_row(&script_uri_delete, &table_name, id) {
-                    Ok(deleted) => Ok(format!("{{\"success\": true, \"deleted\": {}}}", deleted)),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+                let mut documents: Vec<repository::VectorDocument> =
+                    match serde_json::from_value(read_json_value(documents)) {
+                        Ok(documents) => documents,
+                        Err(e) => return Ok(error_json(format!("Invalid documents: {}", e))),
+                    };
+                let params: crate::llm::EmbeddingParams =
+                    match read_options_object(options.0, "options") {
+                        Ok(params) => params,
+                        Err(e) => return Ok(error_json(e)),
+                    };
+
+                let mut pending = Vec::new();
+                let mut texts = Vec::new();
+                for (index, document) in documents.iter().enumerate() {
+                    if !document.embedding.is_empty() {
+                        continue;
+                    }
+                    match &document.text {
+                        Some(text) => {
+                            pending.push(index);
+                            texts.push(text.clone());
+                        }
+                        None => {
+                            return Ok(error_json(format!(
+                                "Document '{}' needs an embedding or text",
+                                document.id
+                            )));
+                        }
+                    }
+                }
+                if !texts.is_empty() {
+                    let embedded =
+                        crate::dry_run::ensure_allowed("embeddings.create").and_then(|()| {
+                            crate::llm::embed(
+                                &script_uri_upsert,
+                                user_ctx_upsert.user_id.as_deref(),
+                                &texts,
+                                &params,
+                            )
+                        });
+                    match embedded {
+                        Ok(embedded) => {
+                            for (index, embedding) in pending.into_iter().zip(embedded.embeddings) {
+                                documents[index].embedding = embedding;
+                            }
+                        }
+                        Err(e) => return Ok(error_json(e)),
+                    }
+                }
+
+                match repository::upsert_vectors(&script_uri_upsert, &collection, &documents) {
+                    Ok(stored) => Ok(serde_json::json!({ "stored": stored }).to_string()),
+                    Err(e) => Ok(error_json(e.to_string())),
                 }
             },
         )?;
-        database_obj.set("delete", delete_row)?;
-
-        // database.upsert(tableName, keyColumns, data)
-        // INSERT … ON CONFLICT DO UPDATE — atomically insert or update by key
-        let script_uri_upsert = script_uri_owned.clone();
-        let user_ctx_upsert = user_context.clone();
-        let upsert_row = Function::new(
+        vectors_obj.set("upsert", upsert)?;
+
+        // vectors.search(collection, query, k, filter, offset) or
+        // vectors.search(collection, query, { limit, offset, filter, minScore }) -
+        // Find a page of the documents closest to a text or an embedding
+        let script_uri_search = script_uri.to_string();
+        let user_ctx_search = self.user_context.clone();
+        let denied = permission_error.clone();
+        let search = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  key_columns_json: String,
-                  data: String|
+                  collection: String,
+                  query: rquickjs::Value<'_>,
+                  k_or_options: Opt<rquickjs::Value<'_>>,
+                  filter: Opt<rquickjs::Value<'_>>,
+                  offset: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
                 debug!(
-                    "database.upsert called for script {} on table: {}",
-                    script_uri_upsert, table_name
+                    "vectors.search called for script {} on collection {}",
+                    script_uri_search, collection
                 );
-
-                if user_ctx_upsert
+                if user_ctx_search
                     .require_capability(&crate::security::Capability::ManageScriptDatabase)
                     .is_err()
                 {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database operations\"}"
-                            .to_string(),
-                    );
+                    return Ok(denied.clone());
                 }
 
-                // key_columns is a JSON array of strings, or a single string
-                let key_cols: Vec<String> = match serde_json::from_str::<serde_json::Value>(
-                    &key_columns_json,
-                ) {
-                    Ok(serde_json::Value::Array(arr)) => arr
-                        .into_iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect(),
-                    Ok(serde_json::Value::String(s)) => vec![s],
-                    _ => {
-                        return Ok("{\"error\": \"keyColumns must be a JSON array of strings or a single string\"}".to_string());
+                let options = match read_vector_search_options(k_or_options.0, filter.0, offset.0) {
+                    Ok(options) => options,
+                    Err(e) => return Ok(error_json(e)),
+                };
+                let embedding = match js_string(&query) {
+                    Some(text) => {
+                        let embedded = crate::dry_run::ensure_allowed("embeddings.create")
+                            .and_then(|()| {
+                                crate::llm::embed(
+                                    &script_uri_search,
+                                    user_ctx_search.user_id.as_deref(),
+                                    &[text],
+                                    &Default::default(),
+                                )
+                            });
+                        match embedded {
+                            Ok(embedded) => {
+                                embedded.embeddings.into_iter().next().unwrap_or_default()
+                            }
+                            Err(e) => return Ok(error_json(e)),
+                        }
                     }
+                    None => match serde_json::from_value(read_json_value(query)) {
+                        Ok(embedding) => embedding,
+                        Err(_) => {
+                            return Ok(error_json(
+                                "query must be a string or an array of numbers".to_string(),
+                            ));
+                        }
+                    },
                 };
 
-                let data_map = match serde_json::from_str::<
-                    std::collections::HashMap<String, serde_json::Value>,
-                >(&data)
+                match repository::search_vectors(
+                    &script_uri_search,
+                    &collection,
+                    &embedding,
+                    &options,
+                ) {
+                    Ok(page) => match serde_json::to_string(&page) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(error_json(format!("Serialization error: {}", e))),
+                    },
+                    Err(e) => Ok(error_json(e.to_string())),
+                }
+            },
+        )?;
+        vectors_obj.set("search", search)?;
+
+        // vectors.delete(collection, ids) - Delete documents by ID
+        let script_uri_delete = script_uri.to_string();
+        let user_ctx_delete = self.user_context.clone();
+        let denied = permission_error.clone();
+        let delete = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  collection: String,
+                  ids: Vec<String>|
+                  -> JsResult<String> {
+                if user_ctx_delete
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
                 {
-                    Ok(map) => map,
-                    Err(e) => return Ok(format!("{{\"error\": \"Invalid data JSON: {}\"}}", e)),
+                    return Ok(denied.clone());
+                }
+                match repository::delete_vectors(&script_uri_delete, &collection, &ids) {
+                    Ok(deleted) => Ok(serde_json::json!({ "deleted": deleted }).to_string()),
+                    Err(e) => Ok(error_json(e.to_string())),
+                }
+            },
+        )?;
+        vectors_obj.set("delete", delete)?;
+
+        // vectors.drop(collection) - Drop a collection and all its documents
+        let script_uri_drop = script_uri.to_string();
+        let user_ctx_drop = self.user_context.clone();
+        let denied = permission_error;
+        let drop = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, collection: String| -> JsResult<String> {
+                if user_ctx_drop
+                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
+                    .is_err()
+                {
+                    return Ok(denied.clone());
+                }
+                match repository::drop_vector_collection(&script_uri_drop, &collection) {
+                    Ok(dropped) => Ok(serde_json::json!({ "dropped": dropped }).to_string()),
+                    Err(e) => Ok(error_json(e.to_string())),
+                }
+            },
+        )?;
+        vectors_obj.set("drop", drop)?;
+
+        ctx.globals().set("vectors", vectors_obj)?;
+        Ok(())
+    }
+
+    /// Setup conversion functions (markdown to HTML, etc.)
+    fn setup_conversion_functions(
+        &self,
+        ctx: &rquickjs::Ctx<'_>,
+        _script_uri: &str,
+    ) -> JsResult<()> {
+        let global = ctx.globals();
+
+        // Create the convert namespace object
+        let convert_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // convert.markdown_to_html(markdown) - Convert markdown string to HTML
+        let markdown_to_html = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, markdown: String| -> JsResult<String> {
+                // Call the conversion function
+                match crate::conversion::convert_markdown_to_html(&markdown) {
+                    Ok(html) => Ok(html),
+                    Err(e) => {
+                        // Return error as string (following pattern of other APIs)
+                        Ok(format!("Error: {}", e))
+                    }
+                }
+            },
+        )?;
+
+        // convert.render_handlebars_template(template, data) - Render Handlebars template
+        let render_handlebars_template = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, template: String, data: String| -> JsResult<String> {
+                // Call the conversion function
+                match crate::conversion::render_handlebars_template(&template, &data) {
+                    Ok(rendered) => Ok(rendered),
+                    Err(e) => {
+                        // Return error as string (following pattern of other APIs)
+                        Ok(format!("Error: {}", e))
+                    }
+                }
+            },
+        )?;
+
+        // convert.btoa(data) - Base64 encode a string (string-only)
+        let btoa = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, input: rquickjs::Value| -> JsResult<String> {
+                let Some(input_str) = input.as_string() else {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "btoa",
+                        "type_error",
+                        "btoa() expects a string parameter",
+                    ));
+                };
+
+                let input_str = input_str.to_string().map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "btoa",
+                        "type_error",
+                        &format!("btoa() expects a string parameter: {}", e),
+                    )
+                })?;
+
+                crate::conversion::convert_btoa(&input_str).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "btoa",
+                        "invalid_input",
+                        &format!("Invalid input: {}", e),
+                    )
+                })
+            },
+        )?;
+
+        // convert.atob(data) - Base64 decode a string (string-only)
+        let atob = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, input: rquickjs::Value| -> JsResult<String> {
+                let Some(input_str) = input.as_string() else {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "atob",
+                        "type_error",
+                        "atob() expects a string parameter",
+                    ));
                 };
 
-                match crate::repository::upsert_row(
-                    &script_uri_upsert,
-                    &table_name,
-                    &key_cols,
-                    &data_map,
-                ) {
-                    Ok(row) => match serde_json::to_string(&row) {
-                        Ok(json) => Ok(json),
-                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
-                    },
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+                let input_str = input_str.to_string().map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "atob",
+                        "type_error",
+                        &format!("atob() expects a string parameter: {}", e),
+                    )
+                })?;
+
+                crate::conversion::convert_atob(&input_str).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "atob",
+                        "invalid_input",
+                        &format!("Invalid input: {}", e),
+                    )
+                })
             },
         )?;
-        database_obj.set("upsert", upsert_row)?;
 
-        // database.deleteWhere(tableName, filters)
-        // Bulk-delete rows matching filter conditions (equality + range operators)
-        let script_uri_dw = script_uri_owned.clone();
-        let user_ctx_dw = user_context.clone();
-        let delete_where = Function::new(
+        convert_obj.set("markdown_to_html", markdown_to_html)?;
+        convert_obj.set("render_handlebars_template", render_handlebars_template)?;
+        convert_obj.set("btoa", btoa)?;
+        convert_obj.set("atob", atob)?;
+        global.set("convert", convert_obj)?;
+
+        // markdown.render(text, options) - Render markdown to sanitized HTML
+        let markdown_obj = rquickjs::Object::new(ctx.clone())?;
+        let render_markdown = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  filters: String|
+                  text: String,
+                  options: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
-                debug!(
-                    "database.deleteWhere called for script {} on table: {}",
-                    script_uri_dw, table_name
-                );
-
-                if user_ctx_dw
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                let filters_map = match serde_json::from_str::<
-                    std::collections::HashMap<String, serde_json::Value>,
-                >(&filters)
-                {
-                    Ok(map) => map,
-                    Err(e) => return Ok(format!("{{\"error\": \"Invalid filters JSON: {}\"}}", e)),
-                };
-
-                match crate::repository::delete_where(&script_uri_dw, &table_name, &filters_map) {
-                    Ok(count) => Ok(format!("{{\"success\": true, \"deleted\": {}}}", count)),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+                let rendered = read_options_object(options.0, "options")
+                    .and_then(|options| crate::conversion::render_markdown(&text, &options));
+                Ok(rendered.unwrap_or_else(|e| format!("Error: {}", e)))
             },
         )?;
-        database_obj.set("deleteWhere", delete_where)?;
+        markdown_obj.set("render", render_markdown)?;
+        global.set("markdown", markdown_obj)?;
 
-        // database.acquireLease(tableName, leaseId, owner, ttlMs)
-        // Atomic compare-and-swap lease acquisition using a script-owned lease table
-        let script_uri_lease = script_uri_owned.clone();
-        let user_ctx_lease = user_context.clone();
-        let acquire_lease = Function::new(
+        // html.sanitize(input, policy) - Remove markup the policy does not allow
+        let html_obj = rquickjs::Object::new(ctx.clone())?;
+        let sanitize_html = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  lease_id: String,
-                  owner: String,
-                  ttl_ms: i64|
+                  input: String,
+                  policy: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
-                debug!(
-                    "database.acquireLease called for script {} on table: {}, lease: {}",
-                    script_uri_lease, table_name, lease_id
-                );
-
-                if user_ctx_lease
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                match crate::repository::acquire_lease(
-                    &script_uri_lease,
-                    &table_name,
-                    &lease_id,
-                    &owner,
-                    ttl_ms,
-                ) {
-                    Ok(result) => match serde_json::to_string(&result) {
-                        Ok(json) => Ok(json),
-                        Err(e) => Ok(format!("{{\"error\": \"Serialization error: {}\"}}", e)),
-                    },
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+                let sanitized = read_options_object(policy.0, "policy")
+                    .and_then(|policy| crate::conversion::sanitize_html(&input, &policy));
+                Ok(sanitized.unwrap_or_else(|e| format!("Error: {}", e)))
             },
         )?;
-        database_obj.set("acquireLease", acquire_lease)?;
+        html_obj.set("sanitize", sanitize_html)?;
+        global.set("html", html_obj)?;
 
-        // database.createLeaseTable(tableName)
-        // Create a correctly-structured lease table with a UNIQUE constraint on lease_id
-        let script_uri_clt = script_uri_owned.clone();
-        let user_ctx_clt = user_context.clone();
-        let create_lease_table = Function::new(
+        // pdf.fromHtml(html, options) - Render HTML to a base64-encoded PDF
+        let pdf_obj = rquickjs::Object::new(ctx.clone())?;
+        let pdf_from_html = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, table_name: String| -> JsResult<String> {
-                debug!(
-                    "database.createLeaseTable called for script {} with table: {}",
-                    script_uri_clt, table_name
-                );
-
-                if user_ctx_clt
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                match crate::repository::create_lease_table(&script_uri_clt, &table_name) {
-                    Ok(physical_name) => Ok(format!(
-                        "{{\"success\": true, \"tableName\": \"{}\", \"physicalName\": \"{}\"}}",
-                        table_name, physical_name
-                    )),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            move |_ctx: rquickjs::Ctx<'_>,
+                  html: String,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let pdf = read_options_object(options.0, "options")
+                    .and_then(|options| crate::pdf::from_html(&html, &options));
+                Ok(match pdf {
+                    Ok(bytes) => base64::engine::general_purpose::STANDARD.encode(bytes),
+                    Err(e) => format!("Error: {}", e),
+                })
             },
         )?;
-        database_obj.set("createLeaseTable", create_lease_table)?;
+        pdf_obj.set("fromHtml", pdf_from_html)?;
+        global.set("pdf", pdf_obj)?;
 
-        // database.addUniqueIndex(tableName, columns)
-        // Add a unique index to enable upsert() with a conflict target
-        let script_uri_idx = script_uri_owned.clone();
-        let user_ctx_idx = user_context.clone();
-        let add_unique_index = Function::new(
+        // ical.build(events, options) - Write events as an iCalendar document
+        let ical_obj = rquickjs::Object::new(ctx.clone())?;
+        let ical_build = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  columns_json: String|
+                  events: Opt<rquickjs::Value<'_>>,
+                  options: Opt<rquickjs::Value<'_>>|
                   -> JsResult<String> {
-                debug!(
-                    "database.addUniqueIndex called for script {} on table: {}",
-                    script_uri_idx, table_name
-                );
-
-                if user_ctx_idx
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database schema operations\"}"
-                            .to_string(),
-                    );
-                }
-
-                let columns: Vec<String> = match serde_json::from_str::<serde_json::Value>(
-                    &columns_json,
-                ) {
-                    Ok(serde_json::Value::Array(arr)) => arr
-                        .into_iter()
-                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                        .collect(),
-                    Ok(serde_json::Value::String(s)) => vec![s],
-                    _ => {
-                        return Ok(
-                                "{\"error\": \"columns must be a JSON array of strings or a single string\"}"
-                                    .to_string(),
-                            );
-                    }
-                };
-
-                match crate::repository::add_unique_index(&script_uri_idx, &table_name, &columns) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"tableName\": \"{}\", \"columns\": {}}}",
-                        table_name, columns_json
-                    )),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+                let built = read_options_object::<Vec<crate::conversion::CalendarEvent>>(
+                    events.0, "events",
+                )
+                .and_then(|events| {
+                    read_options_object(options.0, "options")
+                        .and_then(|options| crate::conversion::build_icalendar(&events, &options))
+                });
+                Ok(built.unwrap_or_else(|e| format!("Error: {}", e)))
             },
         )?;
-        database_obj.set("addUniqueIndex", add_unique_index)?;
+        ical_obj.set("build", ical_build)?;
 
-        // database.generateGraphQLForTable
-        let script_uri_graphql = script_uri_owned.clone();
-        let user_ctx_graphql = user_context.clone();
-        let generate_graphql = Function::new(
+        // ical.parse(text) - Read the events of an iCalendar document as JSON
+        let ical_parse = Function::new(
             ctx.clone(),
-            move |ctx_inner: rquickjs::Ctx<'_>,
-                  table_name: String,
-                  options: Opt<String>|
-                  -> JsResult<String> {
-                debug!(
-                    "database.generateGraphQLForTable called for script {} on table: {}",
-                    script_uri_graphql, table_name
-                );
+            move |_ctx: rquickjs::Ctx<'_>, text: String| -> JsResult<String> {
+                let parsed = crate::conversion::parse_icalendar(&text).and_then(|calendar| {
+                    serde_json::to_string(&calendar).map_err(|e| e.to_string())
+                });
+                Ok(parsed.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        ical_obj.set("parse", ical_parse)?;
+        global.set("ical", ical_obj)?;
 
-                if user_ctx_graphql
-                    .require_capability(&crate::security::Capability::ManageScriptDatabase)
-                    .is_err()
-                {
-                    return Ok(
-                        "{\"error\": \"Insufficient permissions for database operations\"}"
-                            .to_string(),
-                    );
-                }
+        debug!(
+            "convert.*, markdown.render(), html.sanitize(), pdf.fromHtml() and ical.* functions initialized"
+        );
 
-                // Parse options (default: ScriptInternal visibility)
-                let visibility = if let Some(opts_str) = options.0 {
-                    match serde_json::from_str::<serde_json::Value>(&opts_str) {
-                        Ok(opts) => opts
-                            .get("visibility")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("script_internal")
-                            .to_string(),
-                        Err(_) => "script_internal".to_string(),
-                    }
-                } else {
-                    "script_internal".to_string()
-                };
+        Ok(())
+    }
 
-                // Get table schema
-                let schema =
-                    match crate::repository::get_table_schema(&script_uri_graphql, &table_name) {
-                        Ok(s) => s,
-                        Err(e) => {
-                            return Ok(format!(
-                                "{{\"error\": \"Failed to get table schema: {}\"}}",
-                                e
-                            ));
-                        }
-                    };
+    /// Setup templates.render for Handlebars templates stored as assets
+    fn setup_template_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let templates_obj = rquickjs::Object::new(ctx.clone())?;
 
-                // Get foreign keys
-                let foreign_keys =
-                    match crate::repository::get_foreign_keys(&script_uri_graphql, &table_name) {
-                        Ok(fks) => fks,
-                        Err(e) => {
-                            return Ok(format!(
-                                "{{\"error\": \"Failed to get foreign keys: {}\"}}",
-                                e
-                            ));
+        // templates.render(name, data) - Render the script's asset `name`
+        let user_ctx_render = self.user_context.clone();
+        let script_uri_render = script_uri.to_string();
+        let render = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  data: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_render.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+
+                let data = match data.0 {
+                    Some(value) if !value.is_undefined() => {
+                        let json = value
+                            .ctx()
+                            .clone()
+                            .json_stringify(value)?
+                            .map(|json| json.to_string())
+                            .transpose()?;
+                        match json.map(|json| serde_json::from_str(&json)) {
+                            Some(Ok(data)) => data,
+                            Some(Err(e)) => return Ok(format!("Error: Invalid data: {}", e)),
+                            None => serde_json::Value::Null,
                         }
-                    };
+                    }
+                    _ => serde_json::Value::Null,
+                };
 
-                // Generate GraphQL operations
-                let operations = crate::graphql_schema_gen::generate_table_operations(
-                    &table_name,
-                    &schema,
-                    &foreign_keys,
+                debug!(
+                    script_uri = %script_uri_render,
+                    template = %name,
+                    "Secure templates.render called"
                 );
 
-                // Inject resolver functions into JavaScript context
-                for query in &operations.queries {
-                    // Evaluate resolver code in the current context
-                    if let Err(e) = ctx_inner.eval::<(), _>(query.resolver_code.as_str()) {
-                        return Ok(format!(
-                            "{{\"error\": \"Failed to inject resolver {}: {:?}\"}}",
-                            query.resolver_function_name, e
-                        ));
-                    }
-                }
+                Ok(crate::templates::render(&script_uri_render, &name, &data)
+                    .unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
 
-                for mutation in &operations.mutations {
-                    if let Err(e) = ctx_inner.eval::<(), _>(mutation.resolver_code.as_str()) {
-                        return Ok(format!(
-                            "{{\"error\": \"Failed to inject resolver {}: {:?}\"}}",
-                            mutation.resolver_function_name, e
-                        ));
-                    }
+        templates_obj.set("render", render)?;
+        ctx.globals().set("templates", templates_obj)?;
+        Ok(())
+    }
+
+    /// Setup prompts.* for the versioned prompt templates shared with MCP
+    fn setup_prompt_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let prompts_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // prompts.save(name, { template, description, variables }) - Save a
+        // template as the next version of the prompt `name`
+        let user_ctx_save = self.user_context.clone();
+        let script_uri_save = script_uri.to_string();
+        let save = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  definition: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_save.require_capability(&crate::security::Capability::WriteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
+                let definition: crate::prompts::PromptDefinition =
+                    match read_options_object(definition.0, "definition") {
+                        Ok(definition) => definition,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
 
-                // Register queries
-                for query in &operations.queries {
-                    if let Err(e) = crate::graphql::register_graphql_query(
-                        query.name.clone(),
-                        query.sdl.clone(),
-                        query.resolver_function_name.clone(),
-                        script_uri_graphql.clone(),
-                        visibility.clone(),
-                    ) {
-                        return Ok(format!(
-                            "{{\"error\": \"Failed to register query {}: {}\"}}",
-                            query.name, e
-                        ));
+                debug!(
+                    script_uri = %script_uri_save,
+                    prompt = %name,
+                    "Secure prompts.save called"
+                );
+
+                let saved = crate::dry_run::ensure_allowed("prompts.save")
+                    .and_then(|()| crate::prompts::save(&script_uri_save, &name, &definition));
+                Ok(match saved {
+                    Ok(template) => {
+                        serde_json::to_string(&template).unwrap_or_else(|e| format!("Error: {}", e))
                     }
-                }
+                    Err(e) => format!("Error: {}", e),
+                })
+            },
+        )?;
+        prompts_obj.set("save", save)?;
 
-                // Register mutations
-                for mutation in &operations.mutations {
-                    if let Err(e) = crate::graphql::register_graphql_mutation(
-                        mutation.name.clone(),
-                        mutation.sdl.clone(),
-                        mutation.resolver_function_name.clone(),
-                        script_uri_graphql.clone(),
-                        visibility.clone(),
-                    ) {
-                        return Ok(format!(
-                            "{{\"error\": \"Failed to register mutation {}: {}\"}}",
-                            mutation.name, e
-                        ));
+        // prompts.get(name, version) - The prompt at `version`, or its latest
+        // version; null when there is none
+        let user_ctx_get = self.user_context.clone();
+        let get = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, name: String, version: Opt<i32>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_get.require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                Ok(match crate::prompts::get(&name, version.0) {
+                    Ok(template) => {
+                        serde_json::to_string(&template).unwrap_or_else(|e| format!("Error: {}", e))
                     }
+                    Err(e) => format!("Error: {}", e),
+                })
+            },
+        )?;
+        prompts_obj.set("get", get)?;
+
+        // prompts.list() - The latest version of every prompt
+        let user_ctx_list = self.user_context.clone();
+        let list = Function::new(ctx.clone(), move || -> JsResult<String> {
+            if let Err(e) =
+                user_ctx_list.require_capability(&crate::security::Capability::ReadScripts)
+            {
+                return Ok(format!("Error: {}", e));
+            }
+            Ok(match crate::prompts::list() {
+                Ok(templates) => {
+                    serde_json::to_string(&templates).unwrap_or_else(|e| format!("Error: {}", e))
+                }
+                Err(e) => format!("Error: {}", e),
+            })
+        })?;
+        prompts_obj.set("list", list)?;
+
+        // prompts.render(name, vars, version) - Fill in the prompt at
+        // `version`, or its latest version
+        let user_ctx_render = self.user_context.clone();
+        let script_uri_render = script_uri.to_string();
+        let render = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  vars: Opt<rquickjs::Value<'_>>,
+                  version: Opt<i32>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_render.require_capability(&crate::security::Capability::ReadScripts)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
+                let vars = vars.0.map(read_json_value).unwrap_or_default();
 
-                // Return success with operation names
-                let query_names: Vec<&str> =
-                    operations.queries.iter().map(|q| q.name.as_str()).collect();
-                let mutation_names: Vec<&str> = operations
-                    .mutations
-                    .iter()
-                    .map(|m| m.name.as_str())
-                    .collect();
+                debug!(
+                    script_uri = %script_uri_render,
+                    prompt = %name,
+                    "Secure prompts.render called"
+                );
 
-                Ok(format!(
-                    "{{\"success\": true, \"table\": \"{}\", \"queries\": {:?}, \"mutations\": {:?}}}",
-                    table_name, query_names, mutation_names
-                ))
+                Ok(crate::prompts::render(&name, &vars, version.0)
+                    .unwrap_or_else(|e| format!("Error: {}", e)))
             },
         )?;
-        database_obj.set("generateGraphQLForTable", generate_graphql)?;
+        prompts_obj.set("render", render)?;
 
-        // Transaction management functions
+        ctx.globals().set("prompts", prompts_obj)?;
+        Ok(())
+    }
 
-        // database.beginTransaction(timeoutMs?) - Start a new transaction or savepoint
-        let begin_transaction = Function::new(
+    /// Setup images.transform for resizing and converting image assets
+    fn setup_image_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let images_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // images.transform(name, options) - Crop, resize and convert the
+        // script's image asset `name`
+        let user_ctx_transform = self.user_context.clone();
+        let script_uri_transform = script_uri.to_string();
+        let transform = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, timeout_ms: Opt<u64>| -> JsResult<String> {
-                match crate::database::Database::begin_transaction(timeout_ms.0) {
-                    Ok(_guard) => {
-                        // Note: The guard is not stored here - it's managed internally
-                        // Auto-commit/rollback happens at handler boundaries
-                        Ok("{\"success\": true, \"message\": \"Transaction started\"}".to_string())
-                    }
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_transform.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                let options: crate::image_transform::ImageTransform =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+
+                debug!(
+                    script_uri = %script_uri_transform,
+                    asset = %name,
+                    "Secure images.transform called"
+                );
+
+                let Some(asset) = repository::fetch_asset(&script_uri_transform, &name) else {
+                    return Ok(format!("Error: Asset '{}' not found", name));
+                };
+                if !crate::image_transform::is_transformable(&asset.mimetype) {
+                    return Ok(format!(
+                        "Error: Asset '{}' is not a supported image ({})",
+                        name, asset.mimetype
+                    ));
+                }
+                match crate::image_transform::transform(&asset.content, &options) {
+                    Ok(image) => Ok(serde_json::json!({
+                        "contentType": image.mimetype,
+                        "bodyBase64": base64::engine::general_purpose::STANDARD.encode(&image.content),
+                    })
+                    .to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
             },
         )?;
-        database_obj.set("beginTransaction", begin_transaction)?;
 
-        // database.commitTransaction() - Commit the current transaction or release savepoint
-        let commit_transaction = Function::new(
+        images_obj.set("transform", transform)?;
+        ctx.globals().set("images", images_obj)?;
+        Ok(())
+    }
+
+    /// Setup validate.* checks backed by the shared InputValidator
+    fn setup_validation_functions(&self, ctx: &rquickjs::Ctx<'_>) -> JsResult<()> {
+        let validate_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // validate.email(value), validate.url(value), validate.uuid(value) -
+        // Whether value is a string of that format
+        let email = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                match crate::database::Database::commit_transaction() {
-                    Ok(()) => Ok(
-                        "{\"success\": true, \"message\": \"Transaction committed\"}".to_string(),
-                    ),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            |value: rquickjs::Value<'_>| -> JsResult<bool> {
+                Ok(js_string(&value)
+                    .is_some_and(|value| InputValidator::shared().validate_email(&value).is_ok()))
             },
         )?;
-        database_obj.set("commitTransaction", commit_transaction)?;
-
-        // database.rollbackTransaction() - Rollback the current transaction or to savepoint
-        let rollback_transaction = Function::new(
+        validate_obj.set("email", email)?;
+        let url = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                match crate::database::Database::rollback_transaction() {
-                    Ok(()) => Ok(
-                        "{\"success\": true, \"message\": \"Transaction rolled back\"}".to_string(),
-                    ),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            |value: rquickjs::Value<'_>| -> JsResult<bool> {
+                Ok(js_string(&value)
+                    .is_some_and(|value| InputValidator::shared().validate_url(&value).is_ok()))
             },
         )?;
-        database_obj.set("rollbackTransaction", rollback_transaction)?;
-
-        // database.createSavepoint(name?) - Create a named or auto-generated savepoint
-        let create_savepoint = Function::new(
+        validate_obj.set("url", url)?;
+        let uuid = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, name: Opt<String>| -> JsResult<String> {
-                match crate::database::Database::create_savepoint(name.0.as_deref()) {
-                    Ok(savepoint_name) => Ok(format!(
-                        "{{\"success\": true, \"savepoint\": \"{}\"}}",
-                        savepoint_name
-                    )),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            |value: rquickjs::Value<'_>| -> JsResult<bool> {
+                Ok(js_string(&value)
+                    .is_some_and(|value| InputValidator::shared().validate_uuid(&value).is_ok()))
             },
         )?;
-        database_obj.set("createSavepoint", create_savepoint)?;
+        validate_obj.set("uuid", uuid)?;
 
-        // database.rollbackToSavepoint(name) - Rollback to a named savepoint
-        let rollback_to_savepoint = Function::new(
+        // validate.length(value, min, max) - Whether a string's character
+        // count or an array's length is within the bounds
+        let length = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, name: String| -> JsResult<String> {
-                match crate::database::Database::rollback_to_savepoint(&name) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"message\": \"Rolled back to savepoint: {}\"}}",
-                        name
-                    )),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            |value: rquickjs::Value<'_>, min: Opt<f64>, max: Opt<f64>| -> JsResult<bool> {
+                let length = if let Some(text) = js_string(&value) {
+                    text.chars().count()
+                } else if let Some(array) = value.as_array() {
+                    array.len()
+                } else {
+                    return Ok(false);
+                };
+                let length = length as f64;
+                Ok(min.0.is_none_or(|min| length >= min) && max.0.is_none_or(|max| length <= max))
             },
         )?;
-        database_obj.set("rollbackToSavepoint", rollback_to_savepoint)?;
+        validate_obj.set("length", length)?;
 
-        // database.releaseSavepoint(name) - Release a named savepoint
-        let release_savepoint = Function::new(
+        // validate.oneOf(value, allowed) - Whether value equals an entry of
+        // the allowed array
+        let one_of = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, name: String| -> JsResult<String> {
-                match crate::database::Database::release_savepoint(&name) {
-                    Ok(()) => Ok(format!(
-                        "{{\"success\": true, \"message\": \"Released savepoint: {}\"}}",
-                        name
-                    )),
-                    Err(e) => Ok(format!("{{\"error\": \"{}\"}}", e)),
-                }
+            |value: rquickjs::Value<'_>, allowed: rquickjs::Value<'_>| -> JsResult<bool> {
+                let serde_json::Value::Array(allowed) = read_json_value(allowed) else {
+                    return Ok(false);
+                };
+                Ok(allowed.contains(&read_json_value(value)))
             },
         )?;
-        database_obj.set("releaseSavepoint", release_savepoint)?;
+        validate_obj.set("oneOf", one_of)?;
 
-        // database.checkDatabaseHealth() - Check database health status
-        let check_db_health = Function::new(
+        // validate.object(value, rules) - Check an object against field
+        // rules; returns { valid, errors: [{ path, message }] } as JSON
+        let object = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Call the database health check
-                let result = crate::database::Database::check_health_sync();
-                Ok(result)
+            |value: rquickjs::Value<'_>, rules: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let rules: std::collections::BTreeMap<
+                    String,
+                    crate::security::validation::FieldRule,
+                > = match read_options_object(rules.0, "rules") {
+                    Ok(rules) => rules,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                let errors =
+                    InputValidator::shared().validate_fields(&read_json_value(value), &rules);
+                Ok(serde_json::json!({
+                    "valid": errors.is_empty(),
+                    "errors": errors,
+                })
+                .to_string())
             },
         )?;
-        database_obj.set("checkDatabaseHealth", check_db_health)?;
-
-        // Set the database object on the global scope
-        global.set("database", database_obj)?;
-
-        debug!(
-            "database JavaScript API initialized for script: {}",
-            script_uri
-        );
+        validate_obj.set("object", object)?;
 
+        ctx.globals().set("validate", validate_obj)?;
         Ok(())
     }
 
-    /// Setup conversion functions (markdown to HTML, etc.)
-    fn setup_conversion_functions(
-        &self,
-        ctx: &rquickjs::Ctx<'_>,
-        _script_uri: &str,
-    ) -> JsResult<()> {
-        let global = ctx.globals();
+    /// Setup i18n.t, i18n.locale and i18n.setLocale for translation
+    /// bundles stored as assets
+    fn setup_i18n_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let i18n_obj = rquickjs::Object::new(ctx.clone())?;
+        // Bundles are loaded once per execution and shared by the functions
+        let translator = std::rc::Rc::new(std::cell::RefCell::new(crate::i18n::Translator::new(
+            script_uri,
+        )));
+
+        // i18n.t(key, params) - Translate `key` into the request's language
+        let user_ctx_t = self.user_context.clone();
+        let translator_t = translator.clone();
+        let t = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  key: String,
+                  params: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_t.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
 
-        // Create the convert namespace object
-        let convert_obj = rquickjs::Object::new(ctx.clone())?;
+                let params = match params.0 {
+                    Some(value) if !value.is_undefined() => {
+                        let json = value
+                            .ctx()
+                            .clone()
+                            .json_stringify(value)?
+                            .map(|json| json.to_string())
+                            .transpose()?;
+                        match json.map(|json| serde_json::from_str(&json)) {
+                            Some(Ok(params)) => params,
+                            Some(Err(e)) => return Ok(format!("Error: Invalid params: {}", e)),
+                            None => serde_json::Value::Null,
+                        }
+                    }
+                    _ => serde_json::Value::Null,
+                };
 
-        // convert.markdown_to_html(markdown) - Convert markdown string to HTML
-        let markdown_to_html = Function::new(
+                Ok(translator_t.borrow_mut().translate(&key, &params))
+            },
+        )?;
+        i18n_obj.set("t", t)?;
+
+        // i18n.locale() - Locale translations are currently taken from
+        let user_ctx_locale = self.user_context.clone();
+        let translator_locale = translator.clone();
+        let locale = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, markdown: String| -> JsResult<String> {
-                // Call the conversion function
-                match crate::conversion::convert_markdown_to_html(&markdown) {
-                    Ok(html) => Ok(html),
-                    Err(e) => {
-                        // Return error as string (following pattern of other APIs)
-                        Ok(format!("Error: {}", e))
-                    }
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_locale.require_capability(&crate::security::Capability::ReadAssets)
+                {
+                    return Ok(format!("Error: {}", e));
                 }
+                Ok(translator_locale.borrow_mut().locale())
             },
         )?;
+        i18n_obj.set("locale", locale)?;
 
-        // convert.render_handlebars_template(template, data) - Render Handlebars template
-        let render_handlebars_template = Function::new(
+        // i18n.setLocale(locale) - Prefer `locale` over the request's languages
+        let set_locale = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, template: String, data: String| -> JsResult<String> {
-                // Call the conversion function
-                match crate::conversion::render_handlebars_template(&template, &data) {
-                    Ok(rendered) => Ok(rendered),
+            move |_ctx: rquickjs::Ctx<'_>, locale: String| -> JsResult<String> {
+                Ok(match translator.borrow_mut().set_locale(&locale) {
+                    Ok(()) => "Locale set".to_string(),
+                    Err(e) => format!("Error: {}", e),
+                })
+            },
+        )?;
+        i18n_obj.set("setLocale", set_locale)?;
+
+        ctx.globals().set("i18n", i18n_obj)?;
+        debug!("i18n.t(), i18n.locale() and i18n.setLocale() functions initialized");
+        Ok(())
+    }
+
+    /// Setup cache.get/set/delete for values cached in memory and Redis, and
+    /// cache.purgeRoute for route response caches
+    fn setup_cache_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        let cache_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // cache.get(key) - The cached value, or null when missing or expired
+        let script_uri_get = script_uri.to_string();
+        let get = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<Option<String>> {
+                match crate::cache::get(&script_uri_get, &crate::tenancy::scoped_storage_key(&key))
+                {
+                    Ok(value) => Ok(value),
                     Err(e) => {
-                        // Return error as string (following pattern of other APIs)
-                        Ok(format!("Error: {}", e))
+                        warn!("cache.get rejected for key {}: {}", key, e);
+                        Ok(None)
                     }
                 }
             },
         )?;
+        cache_obj.set("get", get)?;
 
-        // convert.btoa(data) - Base64 encode a string (string-only)
-        let btoa = Function::new(
+        // cache.set(key, value, { ttlSeconds }) - Cache a value
+        let script_uri_set = script_uri.to_string();
+        let set = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, input: rquickjs::Value| -> JsResult<String> {
-                let Some(input_str) = input.as_string() else {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "btoa",
-                        "type_error",
-                        "btoa() expects a string parameter",
-                    ));
+            move |_ctx: rquickjs::Ctx<'_>,
+                  key: String,
+                  value: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                let ttl_seconds = match options.0.as_ref() {
+                    Some(options) => match options.get::<_, Option<f64>>("ttlSeconds") {
+                        Ok(ttl_seconds) => ttl_seconds,
+                        Err(_) => return Ok("Error: ttlSeconds must be a number".to_string()),
+                    },
+                    None => None,
                 };
-
-                let input_str = input_str.to_string().map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "btoa",
-                        "type_error",
-                        &format!("btoa() expects a string parameter: {}", e),
-                    )
-                })?;
-
-                crate::conversion::convert_btoa(&input_str).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "btoa",
-                        "invalid_input",
-                        &format!("Invalid input: {}", e),
-                    )
-                })
+                if let Err(e) = crate::dry_run::ensure_allowed("cache.set") {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::cache::set(
+                    &script_uri_set,
+                    &crate::tenancy::scoped_storage_key(&key),
+                    &value,
+                    ttl_seconds,
+                ) {
+                    Ok(()) => Ok("Value cached".to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
             },
         )?;
+        cache_obj.set("set", set)?;
 
-        // convert.atob(data) - Base64 decode a string (string-only)
-        let atob = Function::new(
+        // cache.delete(key) - Remove a value; whether it was cached
+        let script_uri_delete = script_uri.to_string();
+        let delete = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, input: rquickjs::Value| -> JsResult<String> {
-                let Some(input_str) = input.as_string() else {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "atob",
-                        "type_error",
-                        "atob() expects a string parameter",
-                    ));
-                };
-
-                let input_str = input_str.to_string().map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "atob",
-                        "type_error",
-                        &format!("atob() expects a string parameter: {}", e),
-                    )
-                })?;
-
-                crate::conversion::convert_atob(&input_str).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "atob",
-                        "invalid_input",
-                        &format!("Invalid input: {}", e),
-                    )
-                })
+            move |_ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
+                if let Err(e) = crate::dry_run::ensure_allowed("cache.delete") {
+                    warn!("cache.delete refused for key {}: {}", key, e);
+                    return Ok(false);
+                }
+                match crate::cache::delete(
+                    &script_uri_delete,
+                    &crate::tenancy::scoped_storage_key(&key),
+                ) {
+                    Ok(deleted) => Ok(deleted),
+                    Err(e) => {
+                        warn!("cache.delete rejected for key {}: {}", key, e);
+                        Ok(false)
+                    }
+                }
             },
         )?;
+        cache_obj.set("delete", delete)?;
 
-        convert_obj.set("markdown_to_html", markdown_to_html)?;
-        convert_obj.set("render_handlebars_template", render_handlebars_template)?;
-        convert_obj.set("btoa", btoa)?;
-        convert_obj.set("atob", atob)?;
-        global.set("convert", convert_obj)?;
-
-        debug!(
-            "convert.markdown_to_html() and convert.render_handlebars_template() functions initialized"
-        );
+        // cache.purgeRoute(path) - Drop cached responses of one of this
+        // script's routes on every server
+        let script_uri_purge = script_uri.to_string();
+        let purge_route = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, path: String| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("cache.purgeRoute") {
+                    return Ok(format!("Error: {}", e));
+                }
+                Ok(
+                    match crate::response_cache::purge_route(&script_uri_purge, &path) {
+                        Ok(purged) => purged.to_string(),
+                        Err(e) => format!("Error: {}", e),
+                    },
+                )
+            },
+        )?;
+        cache_obj.set("purgeRoute", purge_route)?;
 
+        ctx.globals().set("cache", cache_obj)?;
         Ok(())
     }
 
@@ -5127,17 +9529,21 @@ impl SecureGlobalContext {
                 );
                 Ok(crate::repository::get_script_properties_item(
                     &script_uri_get,
-                    &key,
+                    &crate::tenancy::scoped_storage_key(&key),
                 ))
             },
         )?;
         script_properties_obj.set("getItem", get_item)?;
 
-        // sharedStorage.setItem(key, value) - Set a storage item
+        // sharedStorage.setItem(key, value, { ttlSeconds }) - Set a storage item
         let script_uri_set = script_uri_owned.clone();
         let set_item = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, key: String, value: String| -> JsResult<String> {
+            move |_ctx: rquickjs::Ctx<'_>,
+                  key: String,
+                  value: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
                 debug!(
                     "sharedStorage.setItem called for script {} with key: {}",
                     script_uri_set, key
@@ -5152,7 +9558,24 @@ impl SecureGlobalContext {
                     return Ok("Error: Value too large (>1MB)".to_string());
                 }
 
-                match crate::repository::set_script_properties_item(&script_uri_set, &key, &value) {
+                let ttl = match options.0.as_ref() {
+                    Some(options) => match options.get::<_, Option<f64>>("ttlSeconds") {
+                        Ok(None) => None,
+                        Ok(Some(seconds)) => match ttl_from_seconds(seconds) {
+                            Ok(ttl) => Some(ttl),
+                            Err(e) => return Ok(format!("Error: {}", e)),
+                        },
+                        Err(_) => return Ok("Error: ttlSeconds must be a number".to_string()),
+                    },
+                    None => None,
+                };
+
+                match crate::repository::set_script_properties_item_with_ttl(
+                    &script_uri_set,
+                    &crate::tenancy::scoped_storage_key(&key),
+                    &value,
+                    ttl,
+                ) {
                     Ok(()) => Ok("Item set successfully".to_string()),
                     Err(e) => Ok(format!("Error setting item: {}", e)),
                 }
@@ -5160,6 +9583,37 @@ impl SecureGlobalContext {
         )?;
         script_properties_obj.set("setItem", set_item)?;
 
+        // sharedStorage.touch(key, ttlSeconds) - Reset the TTL of an existing item
+        let script_uri_touch = script_uri_owned.clone();
+        let touch_item = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, key: String, ttl_seconds: f64| -> JsResult<bool> {
+                debug!(
+                    "sharedStorage.touch called for script {} with key: {}",
+                    script_uri_touch, key
+                );
+                let ttl = match ttl_from_seconds(ttl_seconds) {
+                    Ok(ttl) => ttl,
+                    Err(e) => {
+                        warn!("sharedStorage.touch rejected for key {}: {}", key, e);
+                        return Ok(false);
+                    }
+                };
+                match crate::repository::touch_script_properties_item(
+                    &script_uri_touch,
+                    &crate::tenancy::scoped_storage_key(&key),
+                    ttl,
+                ) {
+                    Ok(touched) => Ok(touched),
+                    Err(e) => {
+                        warn!("Failed to touch shared storage item {}: {}", key, e);
+                        Ok(false)
+                    }
+                }
+            },
+        )?;
+        script_properties_obj.set("touch", touch_item)?;
+
         // sharedStorage.removeItem(key) - Remove a storage item
         let script_uri_remove = script_uri_owned.clone();
         let remove_item = Function::new(
@@ -5171,19 +9625,28 @@ impl SecureGlobalContext {
                 );
                 Ok(crate::repository::remove_script_properties_item(
                     &script_uri_remove,
-                    &key,
+                    &crate::tenancy::scoped_storage_key(&key),
                 ))
             },
         )?;
         script_properties_obj.set("removeItem", remove_item)?;
 
-        // sharedStorage.clear() - Clear all items for this script
+        // sharedStorage.clear() - Clear all items for this script (only the
+        // current tenant's items when tenancy is enabled)
         let script_uri_clear = script_uri_owned.clone();
         let clear_storage = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
                 debug!("sharedStorage.clear called for script {}", script_uri_clear);
-                match crate::repository::clear_script_properties(&script_uri_clear) {
+                let result = match crate::tenancy::storage_key_prefix() {
+                    Some(prefix) => crate::repository::clear_script_properties_prefix(
+                        &script_uri_clear,
+                        &prefix,
+                    )
+                    .map(|_| ()),
+                    None => crate::repository::clear_script_properties(&script_uri_clear),
+                };
+                match result {
                     Ok(()) => Ok("Storage cleared successfully".to_string()),
                     Err(e) => Ok(format!("Error clearing storage: {}", e)),
                 }
@@ -5276,8 +9739,136 @@ impl SecureGlobalContext {
                 ctx.clone(),
                 move |ctx: rquickjs::Ctx<'_>, key: String, value: String| -> JsResult<String> {
                     debug!(
-                        "personalStorage.setItem called for script {} with key: {}",
-                        script_uri_set, key
+                        "personalStorage.setItem called for script {} with key: {}",
+                        script_uri_set, key
+                    );
+
+                    // Try to get current request auth from context
+                    let globals = ctx.globals();
+                    let context_obj: rquickjs::Object =
+                        match globals.get("context") {
+                            Ok(v) => v,
+                            Err(_) => return Ok(
+                                "Error: Personal storage requires authentication. Please log in."
+                                    .to_string(),
+                            ),
+                        };
+
+                    let request_obj: rquickjs::Object =
+                        match context_obj.get("request") {
+                            Ok(v) => v,
+                            Err(_) => return Ok(
+                                "Error: Personal storage requires authentication. Please log in."
+                                    .to_string(),
+                            ),
+                        };
+
+                    let auth_obj: rquickjs::Object =
+                        match request_obj.get("auth") {
+                            Ok(v) => v,
+                            Err(_) => return Ok(
+                                "Error: Personal storage requires authentication. Please log in."
+                                    .to_string(),
+                            ),
+                        };
+
+                    let is_authenticated: bool =
+                        auth_obj.get("isAuthenticated").unwrap_or_default();
+
+                    if !is_authenticated {
+                        return Ok(
+                            "Error: Personal storage requires authentication. Please log in."
+                                .to_string(),
+                        );
+                    }
+
+                    let user_id: String =
+                        match auth_obj.get("userId") {
+                            Ok(Some(v)) => v,
+                            _ => return Ok(
+                                "Error: Personal storage requires authentication. Please log in."
+                                    .to_string(),
+                            ),
+                        };
+
+                    // Validate inputs
+                    if key.trim().is_empty() {
+                        return Ok("Error: Key cannot be empty".to_string());
+                    }
+
+                    if value.len() > 1_000_000 {
+                        return Ok("Error: Value too large (>1MB)".to_string());
+                    }
+
+                    match crate::repository::set_user_properties_item(
+                        &script_uri_set,
+                        &user_id,
+                        &key,
+                        &value,
+                    ) {
+                        Ok(()) => Ok("Item set successfully".to_string()),
+                        Err(e) => Ok(format!("Error setting item: {}", e)),
+                    }
+                },
+            )?;
+        user_properties_obj.set("setItem", set_item)?;
+
+        // personalStorage.removeItem(key) - Remove a storage item for current user
+        let script_uri_remove = script_uri_owned.clone();
+        let remove_item = Function::new(
+            ctx.clone(),
+            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
+                debug!(
+                    "personalStorage.removeItem called for script {} with key: {}",
+                    script_uri_remove, key
+                );
+
+                // Try to get current request auth from context
+                let globals = ctx.globals();
+                let context_obj: rquickjs::Object = match globals.get("context") {
+                    Ok(v) => v,
+                    Err(_) => return Ok(false),
+                };
+
+                let request_obj: rquickjs::Object = match context_obj.get("request") {
+                    Ok(v) => v,
+                    Err(_) => return Ok(false),
+                };
+
+                let auth_obj: rquickjs::Object = match request_obj.get("auth") {
+                    Ok(v) => v,
+                    Err(_) => return Ok(false),
+                };
+
+                let is_authenticated: bool = auth_obj.get("isAuthenticated").unwrap_or_default();
+
+                if !is_authenticated {
+                    return Ok(false);
+                }
+
+                let user_id: String = match auth_obj.get("userId") {
+                    Ok(Some(v)) => v,
+                    _ => return Ok(false),
+                };
+
+                Ok(crate::repository::remove_user_properties_item(
+                    &script_uri_remove,
+                    &user_id,
+                    &key,
+                ))
+            },
+        )?;
+        user_properties_obj.set("removeItem", remove_item)?;
+
+        // personalStorage.clear() - Clear all items for current user
+        let script_uri_clear = script_uri_owned.clone();
+        let clear_storage =
+            Function::new(
+                ctx.clone(),
+                move |ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                    debug!(
+                        "personalStorage.clear called for script {}",
+                        script_uri_clear
                     );
 
                     // Try to get current request auth from context
@@ -5328,513 +9919,1062 @@ impl SecureGlobalContext {
                             ),
                         };
 
-                    // Validate inputs
-                    if key.trim().is_empty() {
-                        return Ok("Error: Key cannot be empty".to_string());
+                    match crate::repository::clear_user_properties(&script_uri_clear, &user_id) {
+                        Ok(()) => Ok("Storage cleared successfully".to_string()),
+                        Err(e) => Ok(format!("Error clearing storage: {}", e)),
                     }
+                },
+            )?;
+        user_properties_obj.set("clear", clear_storage)?;
 
-                    if value.len() > 1_000_000 {
-                        return Ok("Error: Value too large (>1MB)".to_string());
+        // Set the personalStorage object on the global scope
+        global.set("personalStorage", user_properties_obj)?;
+
+        debug!(
+            "personalStorage JavaScript API initialized for script: {}",
+            script_uri
+        );
+
+        Ok(())
+    }
+
+    fn setup_scheduler_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
+        if !self.config.enable_scheduler {
+            return Ok(());
+        }
+
+        let global = ctx.globals();
+        let scheduler_obj = rquickjs::Object::new(ctx.clone())?;
+        let scheduler_handle = scheduler::get_scheduler();
+
+        let register_once_handle = scheduler_handle.clone();
+        let script_uri_once = script_uri.to_string();
+        let register_once = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: rquickjs::Object| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("schedulerService.registerOnce") {
+                    return Ok(e);
+                }
+                let handler: String = match options.get("handler") {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Ok(
+                            "schedulerService.registerOnce requires options.handler".to_string()
+                        );
                     }
+                };
+                let handler_name = handler.trim();
+                if handler_name.is_empty() {
+                    return Ok(
+                        "schedulerService.registerOnce requires a non-empty handler name"
+                            .to_string(),
+                    );
+                }
 
-                    match crate::repository::set_user_properties_item(
-                        &script_uri_set,
-                        &user_id,
-                        &key,
-                        &value,
-                    ) {
-                        Ok(()) => Ok("Item set successfully".to_string()),
-                        Err(e) => Ok(format!("Error setting item: {}", e)),
+                let run_at_value: String =
+                    match options.get("runAt") {
+                        Ok(value) => value,
+                        Err(_) => return Ok(
+                            "schedulerService.registerOnce requires options.runAt (UTC ISO string)"
+                                .to_string(),
+                        ),
+                    };
+                let run_at = match scheduler::parse_utc_timestamp(&run_at_value) {
+                    Ok(ts) => ts,
+                    Err(err) => return Ok(format!("Scheduler error: {}", err)),
+                };
+
+                let name = options.get::<_, String>("name").ok();
+
+                match register_once_handle.register_one_off(
+                    &script_uri_once,
+                    handler_name,
+                    name,
+                    run_at,
+                ) {
+                    Ok(job) => Ok(format!(
+                        "Scheduled one-time job '{}' for {} (id {})",
+                        job.key,
+                        job.schedule.next_run().to_rfc3339(),
+                        job.id
+                    )),
+                    Err(err) => Ok(format!("Scheduler error: {}", err)),
+                }
+            },
+        )?;
+
+        let register_recurring_handle = scheduler_handle.clone();
+        let script_uri_recurring = script_uri.to_string();
+        let register_recurring = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: rquickjs::Object| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("schedulerService.registerRecurring")
+                {
+                    return Ok(e);
+                }
+                let handler: String = match options.get("handler") {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Ok(
+                            "schedulerService.registerRecurring requires options.handler"
+                                .to_string(),
+                        );
                     }
-                },
-            )?;
-        user_properties_obj.set("setItem", set_item)?;
+                };
+                let handler_name = handler.trim();
+                if handler_name.is_empty() {
+                    return Ok(
+                        "schedulerService.registerRecurring requires a non-empty handler name"
+                            .to_string(),
+                    );
+                }
+
+                let interval_ms_opt = options.get::<_, f64>("intervalMilliseconds").ok();
+                let interval_min_opt = options.get::<_, f64>("intervalMinutes").ok();
+
+                if interval_ms_opt.is_some() && interval_min_opt.is_some() {
+                    return Ok(
+                        "schedulerService.registerRecurring accepts either intervalMilliseconds or intervalMinutes, not both"
+                            .to_string(),
+                    );
+                }
+
+                let (interval, interval_label) = if let Some(interval_ms_value) = interval_ms_opt {
+                    if !interval_ms_value.is_finite() || interval_ms_value < 100.0 {
+                        return Ok(
+                            "schedulerService.registerRecurring requires intervalMilliseconds >= 100"
+                                .to_string(),
+                        );
+                    }
+                    let interval_ms = interval_ms_value.floor() as i64;
+                    (
+                        ChronoDuration::milliseconds(interval_ms),
+                        format!("{} ms", interval_ms),
+                    )
+                } else if let Some(interval_min_value) = interval_min_opt {
+                    if !interval_min_value.is_finite() || interval_min_value < 1.0 {
+                        return Ok(
+                            "schedulerService.registerRecurring requires intervalMinutes >= 1"
+                                .to_string(),
+                        );
+                    }
+                    let interval_minutes = interval_min_value.floor() as i64;
+                    (
+                        ChronoDuration::minutes(interval_minutes),
+                        format!("{} minute(s)", interval_minutes),
+                    )
+                } else {
+                    return Ok(
+                        "schedulerService.registerRecurring requires intervalMilliseconds or intervalMinutes"
+                            .to_string(),
+                    );
+                };
+
+                let name = options.get::<_, String>("name").ok();
+                let first_run = if let Ok(start_at) = options.get::<_, String>("startAt") {
+                    match scheduler::parse_utc_timestamp(&start_at) {
+                        Ok(ts) => Some(ts),
+                        Err(err) => return Ok(format!("Scheduler error: {}", err)),
+                    }
+                } else {
+                    None
+                };
+
+                match register_recurring_handle.register_recurring(
+                    &script_uri_recurring,
+                    handler_name,
+                    name,
+                    interval,
+                    first_run,
+                ) {
+                    Ok(job) => Ok(format!(
+                        "Scheduled recurring job '{}' every {}; next run {} (id {})",
+                        job.key,
+                        interval_label,
+                        job.schedule.next_run().to_rfc3339(),
+                        job.id
+                    )),
+                    Err(err) => Ok(format!("Scheduler error: {}", err)),
+                }
+            },
+        )?;
+
+        let script_uri_clear = script_uri.to_string();
+        let clear_all = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("schedulerService.clearAll") {
+                    return Ok(e);
+                }
+                let removed = scheduler::clear_script_jobs(&script_uri_clear);
+                Ok(format!(
+                    "Cleared {} scheduled job(s) for {}",
+                    removed, script_uri_clear
+                ))
+            },
+        )?;
+
+        scheduler_obj.set("registerOnce", register_once)?;
+        scheduler_obj.set("registerRecurring", register_recurring)?;
+        scheduler_obj.set("clearAll", clear_all)?;
+        global.set("schedulerService", scheduler_obj)?;
+
+        Ok(())
+    }
+
+    /// Setup user management functions (admin-only)
+    fn setup_user_management_functions(
+        &self,
+        ctx: &rquickjs::Ctx<'_>,
+        _script_uri: &str,
+    ) -> JsResult<()> {
+        let global = ctx.globals();
+        let user_context = self.user_context.clone();
+
+        // listUsers - Get all users (admin only)
+        let user_ctx_list = user_context.clone();
+        let list_users = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                // Check if user has admin capabilities (DeleteScripts is admin-only)
+                if !user_ctx_list.has_capability(&crate::security::Capability::DeleteScripts) {
+                    // Return empty array instead of throwing error
+                    return Ok("[]".to_string());
+                }
+
+                debug!(
+                    user_id = ?user_ctx_list.user_id,
+                    "listUsers called by admin"
+                );
+
+                // Get all users from repository
+                let users = crate::user_repository::list_users().map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "listUsers",
+                        "error",
+                        &format!("Failed to list users: {}", e),
+                    )
+                })?;
+
+                // Convert users to JSON-friendly format
+                let users_json: Vec<serde_json::Value> = users
+                    .iter()
+                    .map(|user| {
+                        serde_json::json!({
+                            "id": user.id,
+                            "email": user.email,
+                            "name": user.name,
+                            "roles": user.roles.iter().map(|r| format!("{:?}", r)).collect::<Vec<_>>(),
+                            "created_at": format!("{:?}", user.created_at),
+                            "providers": user.providers.iter().map(|p| &p.provider_name).collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+
+                // Serialize to JSON string
+                serde_json::to_string(&users_json).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "listUsers",
+                        "serialize_error",
+                        &format!("Failed to serialize users: {}", e),
+                    )
+                })
+            },
+        )?;
 
-        // personalStorage.removeItem(key) - Remove a storage item for current user
-        let script_uri_remove = script_uri_owned.clone();
-        let remove_item = Function::new(
+        // addUserRole - Add a role to a user (admin only)
+        let user_ctx_add = user_context.clone();
+        let add_user_role = Function::new(
             ctx.clone(),
-            move |ctx: rquickjs::Ctx<'_>, key: String| -> JsResult<bool> {
+            move |_ctx: rquickjs::Ctx<'_>, user_id: String, role: String| -> JsResult<()> {
+                // Check if user has admin capabilities (DeleteScripts is admin-only)
+                if !user_ctx_add.has_capability(&crate::security::Capability::DeleteScripts) {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "addUserRole",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
+                }
+
                 debug!(
-                    "personalStorage.removeItem called for script {} with key: {}",
-                    script_uri_remove, key
+                    admin_id = ?user_ctx_add.user_id,
+                    target_user = %user_id,
+                    role = %role,
+                    "addUserRole called by admin"
                 );
 
-                // Try to get current request auth from context
-                let globals = ctx.globals();
-                let context_obj: rquickjs::Object = match globals.get("context") {
-                    Ok(v) => v,
-                    Err(_) => return Ok(false),
-                };
-
-                let request_obj: rquickjs::Object = match context_obj.get("request") {
-                    Ok(v) => v,
-                    Err(_) => return Ok(false),
-                };
-
-                let auth_obj: rquickjs::Object = match request_obj.get("auth") {
-                    Ok(v) => v,
-                    Err(_) => return Ok(false),
+                // Parse role
+                let user_role = match role.as_str() {
+                    "Editor" => crate::user_repository::UserRole::Editor,
+                    "Administrator" => crate::user_repository::UserRole::Administrator,
+                    "Authenticated" => crate::user_repository::UserRole::Authenticated,
+                    _ => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "addUserRole",
+                            "invalid_role",
+                            &format!(
+                                "Invalid role: {}. Must be Editor, Administrator, or Authenticated",
+                                role
+                            ),
+                        ));
+                    }
                 };
 
-                let is_authenticated: bool = auth_obj.get("isAuthenticated").unwrap_or_default();
-
-                if !is_authenticated {
-                    return Ok(false);
-                }
+                // Add role
+                crate::user_repository::add_user_role(&user_id, user_role).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "addUserRole",
+                        "error",
+                        &format!("Failed to add role: {}", e),
+                    )
+                })?;
 
-                let user_id: String = match auth_obj.get("userId") {
-                    Ok(Some(v)) => v,
-                    _ => return Ok(false),
-                };
+                tracing::info!(
+                    admin_id = ?user_ctx_add.user_id,
+                    target_user = %user_id,
+                    role = %role,
+                    "Role added successfully"
+                );
 
-                Ok(crate::repository::remove_user_properties_item(
-                    &script_uri_remove,
-                    &user_id,
-                    &key,
-                ))
+                Ok(())
             },
         )?;
-        user_properties_obj.set("removeItem", remove_item)?;
-
-        // personalStorage.clear() - Clear all items for current user
-        let script_uri_clear = script_uri_owned.clone();
-        let clear_storage =
-            Function::new(
-                ctx.clone(),
-                move |ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                    debug!(
-                        "personalStorage.clear called for script {}",
-                        script_uri_clear
-                    );
-
-                    // Try to get current request auth from context
-                    let globals = ctx.globals();
-                    let context_obj: rquickjs::Object =
-                        match globals.get("context") {
-                            Ok(v) => v,
-                            Err(_) => return Ok(
-                                "Error: Personal storage requires authentication. Please log in."
-                                    .to_string(),
-                            ),
-                        };
-
-                    let request_obj: rquickjs::Object =
-                        match context_obj.get("request") {
-                            Ok(v) => v,
-                            Err(_) => return Ok(
-                                "Error: Personal storage requires authentication. Please log in."
-                                    .to_string(),
-                            ),
-                        };
 
-                    let auth_obj: rquickjs::Object =
-                        match request_obj.get("auth") {
-                            Ok(v) => v,
-                            Err(_) => return Ok(
-                                "Error: Personal storage requires authentication. Please log in."
-                                    .to_string(),
-                            ),
-                        };
+        // removeUserRole - Remove a role from a user (admin only)
+        let user_ctx_remove = user_context.clone();
+        let remove_user_role = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, user_id: String, role: String| -> JsResult<()> {
+                // Check if user has admin capabilities (DeleteScripts is admin-only)
+                if !user_ctx_remove.has_capability(&crate::security::Capability::DeleteScripts) {
+                    return Err(rquickjs::Error::new_from_js_message(
+                        "removeUserRole",
+                        "permission_denied",
+                        "Administrator privileges required",
+                    ));
+                }
 
-                    let is_authenticated: bool =
-                        auth_obj.get("isAuthenticated").unwrap_or_default();
+                debug!(
+                    admin_id = ?user_ctx_remove.user_id,
+                    target_user = %user_id,
+                    role = %role,
+                    "removeUserRole called by admin"
+                );
 
-                    if !is_authenticated {
-                        return Ok(
-                            "Error: Personal storage requires authentication. Please log in."
-                                .to_string(),
-                        );
+                // Parse role
+                let user_role = match role.as_str() {
+                    "Editor" => crate::user_repository::UserRole::Editor,
+                    "Administrator" => crate::user_repository::UserRole::Administrator,
+                    "Authenticated" => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "removeUserRole",
+                            "invalid_operation",
+                            "Cannot remove Authenticated role",
+                        ));
                     }
-
-                    let user_id: String =
-                        match auth_obj.get("userId") {
-                            Ok(Some(v)) => v,
-                            _ => return Ok(
-                                "Error: Personal storage requires authentication. Please log in."
-                                    .to_string(),
-                            ),
-                        };
-
-                    match crate::repository::clear_user_properties(&script_uri_clear, &user_id) {
-                        Ok(()) => Ok("Storage cleared successfully".to_string()),
-                        Err(e) => Ok(format!("Error clearing storage: {}", e)),
+                    _ => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "removeUserRole",
+                            "invalid_role",
+                            &format!("Invalid role: {}. Must be Editor or Administrator", role),
+                        ));
                     }
-                },
-            )?;
-        user_properties_obj.set("clear", clear_storage)?;
-
-        // Set the personalStorage object on the global scope
-        global.set("personalStorage", user_properties_obj)?;
-
-        debug!(
-            "personalStorage JavaScript API initialized for script: {}",
-            script_uri
-        );
-
-        Ok(())
-    }
-
-    fn setup_scheduler_functions(&self, ctx: &rquickjs::Ctx<'_>, script_uri: &str) -> JsResult<()> {
-        if !self.config.enable_scheduler {
-            return Ok(());
-        }
-
-        let global = ctx.globals();
-        let scheduler_obj = rquickjs::Object::new(ctx.clone())?;
-        let scheduler_handle = scheduler::get_scheduler();
+                };
 
-        let register_once_handle = scheduler_handle.clone();
-        let script_uri_once = script_uri.to_string();
-        let register_once =
-            Function::new(
-                ctx.clone(),
-                move |_ctx: rquickjs::Ctx<'_>, options: rquickjs::Object| -> JsResult<String> {
-                    let handler: String = match options.get("handler") {
-                        Ok(value) => value,
-                        Err(_) => {
-                            return Ok("schedulerService.registerOnce requires options.handler"
-                                .to_string());
-                        }
-                    };
-                    let handler_name = handler.trim();
-                    if handler_name.is_empty() {
-                        return Ok(
-                            "schedulerService.registerOnce requires a non-empty handler name"
-                                .to_string(),
-                        );
-                    }
+                // Remove role
+                crate::user_repository::remove_user_role(&user_id, &user_role).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "removeUserRole",
+                        "error",
+                        &format!("Failed to remove role: {}", e),
+                    )
+                })?;
 
-                    let run_at_value: String = match options.get("runAt") {
-                        Ok(value) => value,
-                        Err(_) => return Ok(
-                            "schedulerService.registerOnce requires options.runAt (UTC ISO string)"
-                                .to_string(),
-                        ),
-                    };
-                    let run_at = match scheduler::parse_utc_timestamp(&run_at_value) {
-                        Ok(ts) => ts,
-                        Err(err) => return Ok(format!("Scheduler error: {}", err)),
-                    };
+                tracing::info!(
+                    admin_id = ?user_ctx_remove.user_id,
+                    target_user = %user_id,
+                    role = %role,
+                    "Role removed successfully"
+                );
 
-                    let name = options.get::<_, String>("name").ok();
+                Ok(())
+            },
+        )?;
 
-                    match register_once_handle.register_one_off(
-                        &script_uri_once,
-                        handler_name,
-                        name,
-                        run_at,
-                    ) {
-                        Ok(job) => Ok(format!(
-                            "Scheduled one-time job '{}' for {} (id {})",
-                            job.key,
-                            job.schedule.next_run().to_rfc3339(),
-                            job.id
-                        )),
-                        Err(err) => Ok(format!("Scheduler error: {}", err)),
-                    }
-                },
-            )?;
+        // getUserProfile - Custom profile fields of the caller or, for
+        // admins, of any user
+        let user_ctx_get_profile = user_context.clone();
+        let get_user_profile = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, user_id: Opt<String>| -> JsResult<String> {
+                let (user_id, _) =
+                    profile_target(&user_ctx_get_profile, "getUserProfile", user_id.0)?;
+                let profile = crate::user_repository::get_user_profile(&user_id).map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "getUserProfile",
+                        "error",
+                        &format!("Failed to get profile: {}", e),
+                    )
+                })?;
+                profile_json("getUserProfile", profile)
+            },
+        )?;
 
-        let register_recurring_handle = scheduler_handle.clone();
-        let script_uri_recurring = script_uri.to_string();
-        let register_recurring = Function::new(
+        // updateUserProfile - Change profile fields of the caller or, for
+        // admins, of any user
+        let user_ctx_update_profile = user_context.clone();
+        let update_user_profile = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, options: rquickjs::Object| -> JsResult<String> {
-                let handler: String = match options.get("handler") {
-                    Ok(value) => value,
-                    Err(_) => {
-                        return Ok(
-                            "schedulerService.registerRecurring requires options.handler"
-                                .to_string(),
-                        );
+            move |_ctx: rquickjs::Ctx<'_>,
+                  fields: rquickjs::Value<'_>,
+                  user_id: Opt<String>|
+                  -> JsResult<String> {
+                let (user_id, is_admin) =
+                    profile_target(&user_ctx_update_profile, "updateUserProfile", user_id.0)?;
+                let changes = match read_json_value(fields) {
+                    serde_json::Value::Object(changes) => changes,
+                    _ => {
+                        return Err(rquickjs::Error::new_from_js_message(
+                            "updateUserProfile",
+                            "invalid_fields",
+                            "Profile fields must be an object",
+                        ));
                     }
                 };
-                let handler_name = handler.trim();
-                if handler_name.is_empty() {
-                    return Ok(
-                        "schedulerService.registerRecurring requires a non-empty handler name"
-                            .to_string(),
-                    );
-                }
+                let profile =
+                    crate::user_repository::update_user_profile(&user_id, changes, is_admin)
+                        .map_err(|e| {
+                            rquickjs::Error::new_from_js_message(
+                                "updateUserProfile",
+                                "error",
+                                &format!("Failed to update profile: {}", e),
+                            )
+                        })?;
 
-                let interval_ms_opt = options.get::<_, f64>("intervalMilliseconds").ok();
-                let interval_min_opt = options.get::<_, f64>("intervalMinutes").ok();
+                tracing::info!(
+                    caller_id = ?user_ctx_update_profile.user_id,
+                    target_user = %user_id,
+                    "User profile updated"
+                );
+                profile_json("updateUserProfile", profile)
+            },
+        )?;
 
-                if interval_ms_opt.is_some() && interval_min_opt.is_some() {
-                    return Ok(
-                        "schedulerService.registerRecurring accepts either intervalMilliseconds or intervalMinutes, not both"
-                            .to_string(),
-                    );
-                }
+        // getProfileSchema - JSON Schema of the profile fields, or null
+        let get_profile_schema = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<Option<String>> {
+                Ok(crate::user_repository::profile_schema()
+                    .map(|schema| schema.schema().to_string()))
+            },
+        )?;
 
-                let (interval, interval_label) = if let Some(interval_ms_value) = interval_ms_opt {
-                    if !interval_ms_value.is_finite() || interval_ms_value < 100.0 {
-                        return Ok(
-                            "schedulerService.registerRecurring requires intervalMilliseconds >= 100"
-                                .to_string(),
-                        );
-                    }
-                    let interval_ms = interval_ms_value.floor() as i64;
-                    (
-                        ChronoDuration::milliseconds(interval_ms),
-                        format!("{} ms", interval_ms),
+        // Create userStorage object and set methods on it
+        let user_storage = rquickjs::Object::new(ctx.clone())?;
+        user_storage.set("listUsers", list_users)?;
+        user_storage.set("addUserRole", add_user_role)?;
+        user_storage.set("removeUserRole", remove_user_role)?;
+        user_storage.set("getUserProfile", get_user_profile)?;
+        user_storage.set("updateUserProfile", update_user_profile)?;
+        user_storage.set("getProfileSchema", get_profile_schema)?;
+        global.set("userStorage", user_storage)?;
+
+        debug!("User management functions initialized (admin-only)");
+        Ok(())
+    }
+
+    /// Setup operational admin functions (admin only): reloading scripts,
+    /// rebuilding the GraphQL schema, clearing caches, draining streams and
+    /// maintenance mode. Every call is applied on all server instances.
+    fn setup_admin_functions(&self, ctx: &rquickjs::Ctx<'_>) -> JsResult<()> {
+        let admin = rquickjs::Object::new(ctx.clone())?;
+        let user_context = self.user_context.clone();
+        let auditor = self.auditor.clone();
+        let audit_enabled = self.config.enable_audit_logging;
+
+        // Admin check, dry run check and audit entry shared by every operation
+        let authorize = move |operation: &str, detail: Option<String>| -> Result<(), String> {
+            user_context
+                .require_capability(&crate::security::Capability::DeleteScripts)
+                .map_err(|e| e.to_string())?;
+            crate::dry_run::ensure_allowed(&format!("admin.{}", operation))?;
+            if !audit_enabled {
+                return Ok(());
+            }
+
+            let auditor = auditor.clone();
+            let user_id = user_context.user_id.clone();
+            let operation = operation.to_string();
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    let mut event = crate::security::SecurityEvent::new(
+                        SecurityEventType::SystemSecurityEvent,
+                        SecuritySeverity::Medium,
+                        user_id,
                     )
-                } else if let Some(interval_min_value) = interval_min_opt {
-                    if !interval_min_value.is_finite() || interval_min_value < 1.0 {
-                        return Ok(
-                            "schedulerService.registerRecurring requires intervalMinutes >= 1"
-                                .to_string(),
-                        );
+                    .with_resource("server".to_string())
+                    .with_action(operation);
+                    if let Some(detail) = detail {
+                        event = event.with_detail("target", &detail);
                     }
-                    let interval_minutes = interval_min_value.floor() as i64;
-                    (
-                        ChronoDuration::minutes(interval_minutes),
-                        format!("{} minute(s)", interval_minutes),
-                    )
-                } else {
-                    return Ok(
-                        "schedulerService.registerRecurring requires intervalMilliseconds or intervalMinutes"
-                            .to_string(),
-                    );
-                };
+                    let _ = auditor.log_event(event).await;
+                });
+            }
+            Ok(())
+        };
 
-                let name = options.get::<_, String>("name").ok();
-                let first_run = if let Ok(start_at) = options.get::<_, String>("startAt") {
-                    match scheduler::parse_utc_timestamp(&start_at) {
-                        Ok(ts) => Some(ts),
-                        Err(err) => return Ok(format!("Scheduler error: {}", err)),
-                    }
-                } else {
-                    None
-                };
+        // admin.reloadScript(uri) - Re-run a script's init() and rebuild its routes
+        let authorize_reload = authorize.clone();
+        let reload_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                if let Err(e) = authorize_reload("reloadScript", Some(uri.clone())) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::admin_ops::reload_script(&uri) {
+                    Ok(()) => Ok(format!("Reloading script '{}'", uri)),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("reloadScript", reload_script)?;
 
-                match register_recurring_handle.register_recurring(
-                    &script_uri_recurring,
-                    handler_name,
-                    name,
-                    interval,
-                    first_run,
-                ) {
-                    Ok(job) => Ok(format!(
-                        "Scheduled recurring job '{}' every {}; next run {} (id {})",
-                        job.key,
-                        interval_label,
-                        job.schedule.next_run().to_rfc3339(),
-                        job.id
-                    )),
-                    Err(err) => Ok(format!("Scheduler error: {}", err)),
+        // admin.rebuildGraphQLSchema() - Rebuild the schema from the registrations
+        let authorize_rebuild = authorize.clone();
+        let rebuild_schema = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = authorize_rebuild("rebuildGraphQLSchema", None) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::admin_ops::rebuild_schema() {
+                    Ok(()) => Ok("GraphQL schema rebuilt".to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
             },
         )?;
+        admin.set("rebuildGraphQLSchema", rebuild_schema)?;
 
-        let script_uri_clear = script_uri.to_string();
-        let clear_all = Function::new(
+        // admin.clearCaches() - Clear the route index, bytecode and response caches
+        let authorize_clear = authorize.clone();
+        let clear_caches = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                let removed = scheduler::clear_script_jobs(&script_uri_clear);
-                Ok(format!(
-                    "Cleared {} scheduled job(s) for {}",
-                    removed, script_uri_clear
-                ))
+                if let Err(e) = authorize_clear("clearCaches", None) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match serde_json::to_string(&crate::admin_ops::clear_caches()) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
             },
         )?;
+        admin.set("clearCaches", clear_caches)?;
 
-        scheduler_obj.set("registerOnce", register_once)?;
-        scheduler_obj.set("registerRecurring", register_recurring)?;
-        scheduler_obj.set("clearAll", clear_all)?;
-        global.set("schedulerService", scheduler_obj)?;
+        // admin.drainStreams(path?) - Close stream connections so clients reconnect
+        let authorize_drain = authorize.clone();
+        let drain_streams = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, path: Opt<String>| -> JsResult<String> {
+                if let Err(e) = authorize_drain("drainStreams", path.0.clone()) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::admin_ops::drain_streams(path.0.as_deref()) {
+                    Ok(drained) => Ok(drained.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("drainStreams", drain_streams)?;
 
-        Ok(())
-    }
+        // admin.setMaintenanceMode({ enabled, message, retryAfterSeconds })
+        let authorize_maintenance = authorize.clone();
+        let set_maintenance = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                let options: crate::admin_ops::MaintenanceOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let mode = match crate::admin_ops::maintenance_mode(options) {
+                    Ok(mode) => mode,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                let detail = if mode.enabled { "on" } else { "off" };
+                if let Err(e) =
+                    authorize_maintenance("setMaintenanceMode", Some(detail.to_string()))
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                crate::admin_ops::set_maintenance(mode.clone());
+                match serde_json::to_string(&mode) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("setMaintenanceMode", set_maintenance)?;
 
-    /// Setup user management functions (admin-only)
-    fn setup_user_management_functions(
-        &self,
-        ctx: &rquickjs::Ctx<'_>,
-        _script_uri: &str,
-    ) -> JsResult<()> {
-        let global = ctx.globals();
-        let user_context = self.user_context.clone();
+        // admin.maintenanceStatus() - The current maintenance mode state
+        let user_ctx_status = self.user_context.clone();
+        let maintenance_status = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_status.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                match serde_json::to_string(&crate::admin_ops::maintenance()) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("maintenanceStatus", maintenance_status)?;
 
-        // listUsers - Get all users (admin only)
-        let user_ctx_list = user_context.clone();
-        let list_users = Function::new(
+        // admin.versionInfo() - Build, enabled features and loaded scripts of
+        // this instance, as served at /engine/version
+        let user_ctx_version = self.user_context.clone();
+        let version_info = Function::new(
             ctx.clone(),
             move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
-                // Check if user has admin capabilities (DeleteScripts is admin-only)
-                if !user_ctx_list.has_capability(&crate::security::Capability::DeleteScripts) {
-                    // Return empty array instead of throwing error
-                    return Ok("[]".to_string());
+                if let Err(e) =
+                    user_ctx_version.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                let info = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(crate::build_info::collect())
+                });
+                match info.and_then(|info| serde_json::to_string(&info).map_err(|e| e.to_string()))
+                {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
+            },
+        )?;
+        admin.set("versionInfo", version_info)?;
 
-                debug!(
-                    user_id = ?user_ctx_list.user_id,
-                    "listUsers called by admin"
-                );
+        // admin.routeConflicts() - Routes registered by more than one
+        // initialized script, with the script that serves each
+        let user_ctx_conflicts = self.user_context.clone();
+        let route_conflicts = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = user_ctx_conflicts
+                    .require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                let conflicts = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current().block_on(crate::route_index::conflicts())
+                });
+                match conflicts.and_then(|conflicts| {
+                    serde_json::to_string(&conflicts).map_err(|e| e.to_string())
+                }) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("routeConflicts", route_conflicts)?;
 
-                // Get all users from repository
-                let users = crate::user_repository::list_users().map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "listUsers",
-                        "error",
-                        &format!("Failed to list users: {}", e),
-                    )
-                })?;
+        // admin.threatActions({ status, limit }) - Actions taken or queued by
+        // threat response policies, newest first
+        let user_ctx_threats = self.user_context.clone();
+        let threat_actions = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, options: Opt<rquickjs::Value<'_>>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_threats.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                let options: crate::security::threat_response::ThreatActionListOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                let actions = match crate::security::threat_response::list(
+                    options.status,
+                    options
+                        .limit
+                        .unwrap_or(crate::security::threat_response::DEFAULT_LIST_LIMIT),
+                ) {
+                    Ok(actions) => actions,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                match serde_json::to_string(&actions) {
+                    Ok(json) => Ok(json),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("threatActions", threat_actions)?;
 
-                // Convert users to JSON-friendly format
-                let users_json: Vec<serde_json::Value> = users
-                    .iter()
-                    .map(|user| {
-                        serde_json::json!({
-                            "id": user.id,
-                            "email": user.email,
-                            "name": user.name,
-                            "roles": user.roles.iter().map(|r| format!("{:?}", r)).collect::<Vec<_>>(),
-                            "created_at": format!("{:?}", user.created_at),
-                            "providers": user.providers.iter().map(|p| &p.provider_name).collect::<Vec<_>>(),
-                        })
-                    })
-                    .collect();
+        // admin.reviewThreatAction(id, decision) - Approve or dismiss a queued
+        // action, or lift an active one
+        let authorize_review = authorize.clone();
+        let user_ctx_review = self.user_context.clone();
+        let review_threat_action = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, id: String, decision: String| -> JsResult<String> {
+                let Some(review) =
+                    crate::security::threat_response::ReviewDecision::parse(&decision)
+                else {
+                    return Ok(format!(
+                        "Error: Decision must be 'approve', 'dismiss' or 'lift', got '{}'",
+                        decision
+                    ));
+                };
+                if let Err(e) =
+                    authorize_review("reviewThreatAction", Some(format!("{} {}", decision, id)))
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                let decided_by = user_ctx_review.user_id.as_deref().unwrap_or("system");
+                match crate::security::threat_response::review(&id, review, decided_by) {
+                    Ok(action) => match serde_json::to_string(&action) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("reviewThreatAction", review_threat_action)?;
 
-                // Serialize to JSON string
-                serde_json::to_string(&users_json).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "listUsers",
-                        "serialize_error",
-                        &format!("Failed to serialize users: {}", e),
-                    )
-                })
+        // admin.releaseScript(uri) - Lift the quarantine of a script that kept
+        // timing out or panicking, after a fix
+        let authorize_release = authorize.clone();
+        let user_ctx_release = self.user_context.clone();
+        let release_script = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, uri: String| -> JsResult<String> {
+                if let Err(e) = authorize_release("releaseScript", Some(uri.clone())) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let released_by = user_ctx_release.user_id.as_deref().unwrap_or("system");
+                match crate::security::script_quarantine::release(&uri, released_by) {
+                    Ok(actions) => match serde_json::to_string(&actions) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
             },
         )?;
+        admin.set("releaseScript", release_script)?;
 
-        // addUserRole - Add a role to a user (admin only)
-        let user_ctx_add = user_context.clone();
-        let add_user_role = Function::new(
+        // admin.unlockAccount(userId) - Unlock an account locked after failed
+        // sign-ins and forget its failures
+        let authorize_unlock = authorize.clone();
+        let unlock_account = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, user_id: String, role: String| -> JsResult<()> {
-                // Check if user has admin capabilities (DeleteScripts is admin-only)
-                if !user_ctx_add.has_capability(&crate::security::Capability::DeleteScripts) {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "addUserRole",
-                        "permission_denied",
-                        "Administrator privileges required",
-                    ));
+            move |_ctx: rquickjs::Ctx<'_>, user_id: String| -> JsResult<String> {
+                if let Err(e) = authorize_unlock("unlockAccount", Some(user_id.clone())) {
+                    return Ok(format!("Error: {}", e));
                 }
+                match crate::user_repository::clear_account_lockout(&user_id) {
+                    Ok(unlocked) => Ok(unlocked.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("unlockAccount", unlock_account)?;
 
-                debug!(
-                    admin_id = ?user_ctx_add.user_id,
-                    target_user = %user_id,
-                    role = %role,
-                    "addUserRole called by admin"
-                );
+        // admin.userGroups() - Groups with their roles and members
+        let user_ctx_groups = self.user_context.clone();
+        let user_groups = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) =
+                    user_ctx_groups.require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::user_repository::list_groups() {
+                    Ok(groups) => match serde_json::to_string(&groups) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("userGroups", user_groups)?;
 
-                // Parse role
-                let user_role = match role.as_str() {
-                    "Editor" => crate::user_repository::UserRole::Editor,
-                    "Administrator" => crate::user_repository::UserRole::Administrator,
-                    "Authenticated" => crate::user_repository::UserRole::Authenticated,
-                    _ => {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "addUserRole",
-                            "invalid_role",
-                            &format!(
-                                "Invalid role: {}. Must be Editor, Administrator, or Authenticated",
-                                role
-                            ),
-                        ));
-                    }
+        // admin.createUserGroup(name, { description?, roles? }) - Create a
+        // group whose members get the roles when they sign in
+        let authorize_create_group = authorize.clone();
+        let create_user_group = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) = authorize_create_group("createUserGroup", Some(name.clone())) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let (description, roles) = match read_group_options(options.0, None, Vec::new()) {
+                    Ok(options) => options,
+                    Err(e) => return Ok(format!("Error: {}", e)),
                 };
+                match crate::user_repository::create_group(&name, description.as_deref(), &roles) {
+                    Ok(group) => match serde_json::to_string(&group) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("createUserGroup", create_user_group)?;
 
-                // Add role
-                crate::user_repository::add_user_role(&user_id, user_role).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "addUserRole",
-                        "error",
-                        &format!("Failed to add role: {}", e),
-                    )
-                })?;
-
-                tracing::info!(
-                    admin_id = ?user_ctx_add.user_id,
-                    target_user = %user_id,
-                    role = %role,
-                    "Role added successfully"
-                );
+        // admin.updateUserGroup(name, { description?, roles? }) - Change a
+        // group's description or roles; members get changed roles on their
+        // next sign-in
+        let authorize_update_group = authorize.clone();
+        let update_user_group = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  name: String,
+                  options: Opt<rquickjs::Object<'_>>|
+                  -> JsResult<String> {
+                if let Err(e) = authorize_update_group("updateUserGroup", Some(name.clone())) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let group = match crate::user_repository::get_group(&name) {
+                    Ok(group) => group,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                let (description, roles) =
+                    match read_group_options(options.0, group.description, group.roles) {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                match crate::user_repository::update_group(&name, description.as_deref(), &roles) {
+                    Ok(group) => match serde_json::to_string(&group) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("updateUserGroup", update_user_group)?;
 
-                Ok(())
+        // admin.deleteUserGroup(name) - Delete a group with its memberships
+        // and script collaborations
+        let authorize_delete_group = authorize.clone();
+        let delete_user_group = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, name: String| -> JsResult<String> {
+                if let Err(e) = authorize_delete_group("deleteUserGroup", Some(name.clone())) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::user_repository::delete_group(&name) {
+                    Ok(deleted) => Ok(deleted.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
             },
         )?;
+        admin.set("deleteUserGroup", delete_user_group)?;
 
-        // removeUserRole - Remove a role from a user (admin only)
-        let user_ctx_remove = user_context.clone();
-        let remove_user_role = Function::new(
+        // admin.addUserGroupMember(name, userId) - Add a user to a group
+        let authorize_add_member = authorize.clone();
+        let add_user_group_member = Function::new(
             ctx.clone(),
-            move |_ctx: rquickjs::Ctx<'_>, user_id: String, role: String| -> JsResult<()> {
-                // Check if user has admin capabilities (DeleteScripts is admin-only)
-                if !user_ctx_remove.has_capability(&crate::security::Capability::DeleteScripts) {
-                    return Err(rquickjs::Error::new_from_js_message(
-                        "removeUserRole",
-                        "permission_denied",
-                        "Administrator privileges required",
-                    ));
+            move |_ctx: rquickjs::Ctx<'_>, name: String, user_id: String| -> JsResult<String> {
+                if let Err(e) = authorize_add_member(
+                    "addUserGroupMember",
+                    Some(format!("{} {}", name, user_id)),
+                ) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::user_repository::add_group_member(&name, &user_id) {
+                    Ok(added) => Ok(added.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
                 }
+            },
+        )?;
+        admin.set("addUserGroupMember", add_user_group_member)?;
 
-                debug!(
-                    admin_id = ?user_ctx_remove.user_id,
-                    target_user = %user_id,
-                    role = %role,
-                    "removeUserRole called by admin"
-                );
+        // admin.removeUserGroupMember(name, userId) - Remove a user from a group
+        let authorize_remove_member = authorize.clone();
+        let remove_user_group_member = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, name: String, user_id: String| -> JsResult<String> {
+                if let Err(e) = authorize_remove_member(
+                    "removeUserGroupMember",
+                    Some(format!("{} {}", name, user_id)),
+                ) {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::user_repository::remove_group_member(&name, &user_id) {
+                    Ok(removed) => Ok(removed.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("removeUserGroupMember", remove_user_group_member)?;
 
-                // Parse role
-                let user_role = match role.as_str() {
-                    "Editor" => crate::user_repository::UserRole::Editor,
-                    "Administrator" => crate::user_repository::UserRole::Administrator,
-                    "Authenticated" => {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "removeUserRole",
-                            "invalid_operation",
-                            "Cannot remove Authenticated role",
-                        ));
-                    }
-                    _ => {
-                        return Err(rquickjs::Error::new_from_js_message(
-                            "removeUserRole",
-                            "invalid_role",
-                            &format!("Invalid role: {}. Must be Editor or Administrator", role),
-                        ));
-                    }
+        // admin.exportUserData(userId) - Everything kept about a user, as a
+        // JSON document (data subject access request)
+        let authorize_export_user = authorize.clone();
+        let user_ctx_export_user = self.user_context.clone();
+        let export_user_data = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, user_id: String| -> JsResult<String> {
+                if let Err(e) = authorize_export_user(
+                    "exportUserData",
+                    Some(crate::user_data::pseudonym(&user_id)),
+                ) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let requested_by = user_ctx_export_user.user_id.as_deref().unwrap_or("system");
+                match crate::user_data::export(&user_id, requested_by) {
+                    Ok(export) => Ok(export.to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("exportUserData", export_user_data)?;
+
+        // admin.deleteUserData(userId, mode) - Delete a user's account and
+        // data; "anonymize" keeps usage, log and script table rows under a
+        // pseudonym, "purge" deletes them too (data subject erasure request)
+        let authorize_delete_user = authorize.clone();
+        let user_ctx_delete_user = self.user_context.clone();
+        let delete_user_data = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>, user_id: String, mode: String| -> JsResult<String> {
+                let mode = match crate::user_data::ErasureMode::parse(&mode) {
+                    Ok(mode) => mode,
+                    Err(e) => return Ok(format!("Error: {}", e)),
                 };
+                if let Err(e) = authorize_delete_user(
+                    "deleteUserData",
+                    Some(format!(
+                        "{} {}",
+                        crate::user_data::pseudonym(&user_id),
+                        mode.as_str()
+                    )),
+                ) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let requested_by = user_ctx_delete_user.user_id.as_deref().unwrap_or("system");
+                match crate::user_data::erase(&user_id, mode, requested_by) {
+                    Ok(request) => match serde_json::to_string(&request) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("deleteUserData", delete_user_data)?;
 
-                // Remove role
-                crate::user_repository::remove_user_role(&user_id, &user_role).map_err(|e| {
-                    rquickjs::Error::new_from_js_message(
-                        "removeUserRole",
-                        "error",
-                        &format!("Failed to remove role: {}", e),
-                    )
-                })?;
-
-                tracing::info!(
-                    admin_id = ?user_ctx_remove.user_id,
-                    target_user = %user_id,
-                    role = %role,
-                    "Role removed successfully"
-                );
+        // admin.userDataRequests(userId?, limit?) - Audit trail of user data
+        // exports and erasures, newest first
+        let user_ctx_data_requests = self.user_context.clone();
+        let user_data_requests = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  user_id: Opt<String>,
+                  limit: Opt<i64>|
+                  -> JsResult<String> {
+                if let Err(e) = user_ctx_data_requests
+                    .require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::user_data::list_requests(
+                    user_id.0.as_deref().filter(|id| !id.is_empty()),
+                    limit.0.unwrap_or(100),
+                ) {
+                    Ok(requests) => match serde_json::to_string(&requests) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("userDataRequests", user_data_requests)?;
 
-                Ok(())
+        // admin.pendingScriptCapabilities() - Scripts with requested
+        // capabilities waiting for approval
+        let user_ctx_manifests = self.user_context.clone();
+        let pending_script_capabilities = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>| -> JsResult<String> {
+                if let Err(e) = user_ctx_manifests
+                    .require_capability(&crate::security::Capability::DeleteScripts)
+                {
+                    return Ok(format!("Error: {}", e));
+                }
+                match crate::security::script_manifests::list_pending() {
+                    Ok(manifests) => match serde_json::to_string(&manifests) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
             },
         )?;
+        admin.set("pendingScriptCapabilities", pending_script_capabilities)?;
 
-        // Create userStorage object and set methods on it
-        let user_storage = rquickjs::Object::new(ctx.clone())?;
-        user_storage.set("listUsers", list_users)?;
-        user_storage.set("addUserRole", add_user_role)?;
-        user_storage.set("removeUserRole", remove_user_role)?;
-        global.set("userStorage", user_storage)?;
+        // admin.approveScriptCapabilities(uri, capabilities?) - Approve the
+        // listed requested capabilities, or all of them; others are revoked
+        let authorize_approve = authorize.clone();
+        let user_ctx_approve = self.user_context.clone();
+        let approve_script_capabilities = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  uri: String,
+                  capabilities: Opt<Vec<String>>|
+                  -> JsResult<String> {
+                let capabilities = match capabilities
+                    .0
+                    .map(|names| crate::security::script_manifests::parse_capabilities(&names))
+                    .transpose()
+                {
+                    Ok(capabilities) => capabilities,
+                    Err(e) => return Ok(format!("Error: {}", e)),
+                };
+                let detail = match &capabilities {
+                    Some(capabilities) => format!(
+                        "{} [{}]",
+                        uri,
+                        capabilities
+                            .iter()
+                            .map(|capability| capability.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    None => uri.clone(),
+                };
+                if let Err(e) = authorize_approve("approveScriptCapabilities", Some(detail)) {
+                    return Ok(format!("Error: {}", e));
+                }
+                let approved_by = user_ctx_approve.user_id.as_deref().unwrap_or("system");
+                match crate::security::script_manifests::approve(&uri, capabilities, approved_by) {
+                    Ok(manifest) => match serde_json::to_string(
+                        &crate::security::script_manifests::ScriptManifestView::from(manifest),
+                    ) {
+                        Ok(json) => Ok(json),
+                        Err(e) => Ok(format!("Error: {}", e)),
+                    },
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        admin.set("approveScriptCapabilities", approve_script_capabilities)?;
 
-        debug!("User management functions initialized (admin-only)");
+        ctx.globals().set("admin", admin)?;
         Ok(())
     }
 
@@ -5855,6 +10995,9 @@ impl SecureGlobalContext {
                   message_type: String,
                   handler_name: String|
                   -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("dispatcher.registerListener") {
+                    return Ok(e);
+                }
                 // Validate inputs
                 if message_type.is_empty() {
                     return Ok(
@@ -5902,6 +11045,9 @@ impl SecureGlobalContext {
                   message_type: String,
                   message_data_json: Opt<String>|
                   -> JsResult<String> {
+                if let Err(e) = crate::dry_run::ensure_allowed("dispatcher.sendMessage") {
+                    return Ok(e);
+                }
                 // Validate message type
                 if message_type.is_empty() {
                     return Ok("dispatcher.sendMessage: message type cannot be empty".to_string());
@@ -5999,6 +11145,75 @@ impl SecureGlobalContext {
         debug!("Dispatcher functions initialized");
         Ok(())
     }
+
+    /// Setup events.publish() and events.subscribe() for events between
+    /// scripts
+    fn setup_event_functions(
+        &self,
+        ctx: &rquickjs::Ctx<'_>,
+        script_uri: &str,
+        in_init: bool,
+    ) -> JsResult<()> {
+        let events_obj = rquickjs::Object::new(ctx.clone())?;
+
+        // events.publish(topic, payload, options) - Run the handlers subscribed
+        // to a topic
+        let script_uri_publish = script_uri.to_string();
+        let publish = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  topic: String,
+                  payload: Opt<rquickjs::Value<'_>>,
+                  options: Opt<rquickjs::Value<'_>>|
+                  -> JsResult<String> {
+                let payload = payload
+                    .0
+                    .map(read_json_value)
+                    .unwrap_or(serde_json::Value::Null);
+                let options: crate::events::PublishOptions =
+                    match read_options_object(options.0, "options") {
+                        Ok(options) => options,
+                        Err(e) => return Ok(format!("Error: {}", e)),
+                    };
+                if let Err(e) = crate::dry_run::ensure_allowed("events.publish") {
+                    return Ok(format!("Error: {}", e));
+                }
+                let result = crate::events::publish(&script_uri_publish, &topic, payload, &options)
+                    .and_then(|result| serde_json::to_string(&result).map_err(|e| e.to_string()));
+                Ok(result.unwrap_or_else(|e| format!("Error: {}", e)))
+            },
+        )?;
+        events_obj.set("publish", publish)?;
+
+        // events.subscribe(topic, handlerName) - Run a handler for each event
+        // published on a topic; only from init()
+        let script_uri_subscribe = script_uri.to_string();
+        let subscribe = Function::new(
+            ctx.clone(),
+            move |_ctx: rquickjs::Ctx<'_>,
+                  topic: String,
+                  handler_name: String|
+                  -> JsResult<String> {
+                if !in_init {
+                    return Ok("Error: events.subscribe can only be called from init()".to_string());
+                }
+                let handler_name = handler_name.trim();
+                match crate::events::subscribe(&script_uri_subscribe, &topic, handler_name) {
+                    Ok(added) => Ok(serde_json::json!({
+                        "topic": topic,
+                        "handler": handler_name,
+                        "added": added,
+                    })
+                    .to_string()),
+                    Err(e) => Ok(format!("Error: {}", e)),
+                }
+            },
+        )?;
+        events_obj.set("subscribe", subscribe)?;
+
+        ctx.globals().set("events", events_obj)?;
+        Ok(())
+    }
 }
 
 /// Extract the authenticated user_id from JavaScript `context.request.auth`.
@@ -6015,6 +11230,23 @@ fn get_auth_user_id(globals: &rquickjs::Object<'_>) -> Option<String> {
     auth_obj.get("userId").ok().flatten()
 }
 
+/// Auth context of the request being handled, as exposed to the handler in
+/// `context.request.auth`
+fn get_auth_context(globals: &rquickjs::Object<'_>) -> Option<crate::auth::JsAuthContext> {
+    let context_obj: rquickjs::Object = globals.get("context").ok()?;
+    let request_obj: rquickjs::Object = context_obj.get("request").ok()?;
+    let auth_obj: rquickjs::Object = request_obj.get("auth").ok()?;
+    Some(crate::auth::JsAuthContext {
+        user_id: auth_obj.get("userId").ok().flatten(),
+        email: auth_obj.get("userEmail").ok().flatten(),
+        name: auth_obj.get("userName").ok().flatten(),
+        provider: auth_obj.get("provider").ok().flatten(),
+        is_authenticated: auth_obj.get("isAuthenticated").unwrap_or_default(),
+        is_admin: auth_obj.get("isAdmin").unwrap_or_default(),
+        is_editor: auth_obj.get("isEditor").unwrap_or_default(),
+    })
+}
+
 /// Execute a message handler function in a script
 fn execute_message_handler(
     script_uri: String,
@@ -6032,7 +11264,7 @@ fn execute_message_handler(
     ctx.with(|ctx| -> Result<(), String> {
         // Set up minimal secure global functions for handler execution
         let user_context = UserContext::admin("dispatcher".to_string());
-        let security_config = GlobalSecurityConfig {
+        let mut security_config = GlobalSecurityConfig {
             enable_graphql_registration: false,
             enable_asset_management: false,
             enable_streams: false,
@@ -6040,9 +11272,17 @@ fn execute_message_handler(
             enable_scheduler: false,
             enable_logging: true,
             enable_secrets: false,
+            enable_fetch: true,
+            enable_graphql: true,
+            enable_database: true,
+            enable_email: true,
+            enable_notify: true,
+            enable_llm: true,
+            enable_tasks: true,
             enforce_strict_validation: false,
             enable_audit_logging: false,
         };
+        security_config.restrict_to(&crate::security::script_manifests::granted(&script_uri));
 
         let secure_context = SecureGlobalContext::new_with_config(user_context, security_config);
         secure_context
@@ -371,6 +371,26 @@ impl ThreatDetector {
             });
         }
 
+        // Check for script writes using forbidden globals
+        if event.action.as_deref() == Some("script_write")
+            && let Some(globals) = event.details.get("forbidden_globals")
+        {
+            indicators.push(ThreatIndicator {
+                indicator_type: "suspicious_script_write".to_string(),
+                severity: 80.0,
+                description: format!(
+                    "Script {} written with forbidden globals: {}",
+                    event.resource.as_deref().unwrap_or("unknown"),
+                    globals
+                ),
+                evidence: vec![
+                    format!("Script: {:?}", event.resource),
+                    format!("Forbidden globals: {}", globals),
+                    format!("User: {:?}", event.user_id),
+                ],
+            });
+        }
+
         indicators
     }
 
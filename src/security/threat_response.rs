@@ -0,0 +1,648 @@
+//! Automated responses to detected threats (`[security.threat_response]`).
+//!
+//! Every event logged by the [`SecurityAuditor`](super::SecurityAuditor) is
+//! analyzed by threat detection; each configured policy acts on one of the
+//! indicator types it reports. `block_ip` answers every request from the
+//! event's IP address with 403, `quarantine_script` stops serving the routes
+//! of the script the event is about and `alert` only logs. Policies with
+//! `require_review` queue their action as `pending` for an administrator to
+//! approve or dismiss (`admin.reviewThreatAction` or the
+//! `reviewThreatAction` GraphQL mutation); active actions can be lifted the
+//! same way.
+//!
+//! Scripts that keep timing out or panicking are quarantined the same way by
+//! [`script_quarantine`](super::script_quarantine), under the
+//! `script_quarantine` policy; [`release_script`] lifts every quarantine of a
+//! script.
+//!
+//! Actions are kept in the `threat_actions` table. Each instance holds the
+//! active ones in memory, reloads them periodically and when another
+//! instance announces a change on the `threat_response` database
+//! notification channel. Subjects on the allowlist are never acted on.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{SecurityEvent, ThreatAssessment};
+use crate::config::{ThreatActionKind, ThreatPolicyConfig, ThreatResponseConfig};
+use crate::repository::{self, Repository as _};
+
+/// Database notification channel announcing changed threat actions
+pub const THREAT_RESPONSE_CHANNEL: &str = "threat_response";
+
+/// Actions one listing returns when no limit is given
+pub const DEFAULT_LIST_LIMIT: i64 = 100;
+
+/// Most actions one listing returns
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+/// Interval between reloads of the active actions
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+impl ThreatActionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThreatActionKind::BlockIp => "block_ip",
+            ThreatActionKind::QuarantineScript => "quarantine_script",
+            ThreatActionKind::Alert => "alert",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "block_ip" => Some(ThreatActionKind::BlockIp),
+            "quarantine_script" => Some(ThreatActionKind::QuarantineScript),
+            "alert" => Some(ThreatActionKind::Alert),
+            _ => None,
+        }
+    }
+}
+
+/// Where a threat action stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreatActionStatus {
+    /// Waiting for an administrator
+    Pending,
+    Active,
+    /// Rejected by an administrator
+    Dismissed,
+    /// Ended early by an administrator
+    Lifted,
+}
+
+impl ThreatActionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThreatActionStatus::Pending => "pending",
+            ThreatActionStatus::Active => "active",
+            ThreatActionStatus::Dismissed => "dismissed",
+            ThreatActionStatus::Lifted => "lifted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ThreatActionStatus::Pending),
+            "active" => Some(ThreatActionStatus::Active),
+            "dismissed" => Some(ThreatActionStatus::Dismissed),
+            "lifted" => Some(ThreatActionStatus::Lifted),
+            _ => None,
+        }
+    }
+}
+
+/// An action taken or queued by a policy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreatAction {
+    pub id: Uuid,
+    pub policy: String,
+    pub action: ThreatActionKind,
+    /// IP address or script URI acted on
+    pub subject: String,
+    pub indicator: String,
+    pub description: String,
+    pub status: ThreatActionStatus,
+    /// How long the action lasts once active (0 = until lifted)
+    pub duration_secs: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+/// Options of `admin.threatActions`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ThreatActionListOptions {
+    pub status: Option<ThreatActionStatus>,
+    pub limit: Option<i64>,
+}
+
+/// An administrator's decision on a queued or active action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewDecision {
+    /// Make a pending action active
+    Approve,
+    /// Reject a pending action
+    Dismiss,
+    /// End an active action
+    Lift,
+}
+
+impl ReviewDecision {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "approve" => Some(ReviewDecision::Approve),
+            "dismiss" => Some(ReviewDecision::Dismiss),
+            "lift" => Some(ReviewDecision::Lift),
+            _ => None,
+        }
+    }
+
+    /// Status an action must be in and the status it moves to
+    fn transition(self) -> (ThreatActionStatus, ThreatActionStatus) {
+        match self {
+            ReviewDecision::Approve => (ThreatActionStatus::Pending, ThreatActionStatus::Active),
+            ReviewDecision::Dismiss => (ThreatActionStatus::Pending, ThreatActionStatus::Dismissed),
+            ReviewDecision::Lift => (ThreatActionStatus::Active, ThreatActionStatus::Lifted),
+        }
+    }
+}
+
+/// Active actions of this instance, by subject, with their expiry
+#[derive(Debug, Default)]
+struct ActiveActions {
+    blocked_ips: HashMap<String, Option<DateTime<Utc>>>,
+    quarantined_scripts: HashMap<String, Option<DateTime<Utc>>>,
+}
+
+impl ActiveActions {
+    fn from_actions(actions: &[ThreatAction]) -> Self {
+        let mut active = Self::default();
+        for action in actions {
+            active.apply(action);
+        }
+        active
+    }
+
+    fn apply(&mut self, action: &ThreatAction) {
+        let subjects = match action.action {
+            ThreatActionKind::BlockIp => &mut self.blocked_ips,
+            ThreatActionKind::QuarantineScript => &mut self.quarantined_scripts,
+            ThreatActionKind::Alert => return,
+        };
+        if action.status == ThreatActionStatus::Active {
+            subjects.insert(action.subject.clone(), action.expires_at);
+        } else {
+            subjects.remove(&action.subject);
+        }
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<ThreatResponseConfig>> = OnceLock::new();
+static ACTIVE: OnceLock<RwLock<ActiveActions>> = OnceLock::new();
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+fn settings_lock() -> &'static RwLock<ThreatResponseConfig> {
+    SETTINGS.get_or_init(|| RwLock::new(ThreatResponseConfig::default()))
+}
+
+fn active_lock() -> &'static RwLock<ActiveActions> {
+    ACTIVE.get_or_init(Default::default)
+}
+
+/// Apply the `[security.threat_response]` configuration
+pub fn configure(config: &ThreatResponseConfig) {
+    let mut settings = settings_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *settings = config.clone();
+}
+
+fn current_settings() -> ThreatResponseConfig {
+    settings_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+fn is_active(subjects: &HashMap<String, Option<DateTime<Utc>>>, subject: &str) -> bool {
+    subjects
+        .get(subject)
+        .is_some_and(|expires_at| expires_at.is_none_or(|expires_at| expires_at > Utc::now()))
+}
+
+/// Whether requests from an IP address are blocked
+pub fn is_ip_blocked(ip: &str) -> bool {
+    let active = active_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    is_active(&active.blocked_ips, ip)
+}
+
+/// Whether a script is quarantined
+pub fn is_quarantined(script_uri: &str) -> bool {
+    let active = active_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    is_active(&active.quarantined_scripts, script_uri)
+}
+
+/// What a policy acts on for an event, if anything
+fn subject_for(policy: &ThreatPolicyConfig, event: &SecurityEvent) -> Option<String> {
+    let subject = match policy.action {
+        ThreatActionKind::BlockIp => event.ip_address.as_deref(),
+        ThreatActionKind::QuarantineScript => event.resource.as_deref(),
+        ThreatActionKind::Alert => event
+            .ip_address
+            .as_deref()
+            .or(event.resource.as_deref())
+            .or(event.user_id.as_deref()),
+    }?;
+    let subject = subject.trim();
+    (!subject.is_empty() && subject != "unknown").then(|| subject.to_string())
+}
+
+/// Policies matching an assessment's indicators with the subject each acts
+/// on; allowlisted subjects and users are left out
+fn matching_policies<'a>(
+    config: &'a ThreatResponseConfig,
+    event: &SecurityEvent,
+    assessment: &ThreatAssessment,
+) -> Vec<(&'a ThreatPolicyConfig, String, String)> {
+    let allowlisted = |value: &str| config.allowlist.iter().any(|entry| entry == value);
+    if event.user_id.as_deref().is_some_and(allowlisted) {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    for indicator in &assessment.threat_indicators {
+        for policy in config
+            .policies
+            .iter()
+            .filter(|policy| policy.indicator == indicator.indicator_type)
+        {
+            if let Some(subject) = subject_for(policy, event)
+                && !allowlisted(&subject)
+            {
+                matches.push((policy, subject, indicator.description.clone()));
+            }
+        }
+    }
+    matches
+}
+
+/// Run the policies matching a threat assessment of an event
+pub async fn respond(event: &SecurityEvent, assessment: &ThreatAssessment) {
+    if assessment.threat_indicators.is_empty() {
+        return;
+    }
+    let config = current_settings();
+    if !config.enabled {
+        return;
+    }
+
+    for (policy, subject, description) in matching_policies(&config, event, assessment) {
+        if policy.action == ThreatActionKind::Alert {
+            error!(
+                event_id = %event.id,
+                policy = %policy.name,
+                indicator = %policy.indicator,
+                subject = %subject,
+                "THREAT RESPONSE ALERT: {}",
+                description
+            );
+            continue;
+        }
+        if let Err(e) = take_action(policy, subject, description).await {
+            error!(
+                "Threat response policy '{}' failed to act: {}",
+                policy.name, e
+            );
+        }
+    }
+}
+
+/// Take or queue the action of a policy on a subject. Returns `None` when
+/// the subject already has an open action of the same kind.
+pub async fn take_action(
+    policy: &ThreatPolicyConfig,
+    subject: String,
+    description: String,
+) -> Result<Option<ThreatAction>, String> {
+    let Some(repo) = repository::get_repository_opt() else {
+        return Ok(None);
+    };
+    if repo
+        .has_open_threat_action(policy.action, &subject)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let duration_secs = i64::try_from(policy.duration_secs).unwrap_or(i64::MAX);
+    let status = if policy.require_review {
+        ThreatActionStatus::Pending
+    } else {
+        ThreatActionStatus::Active
+    };
+    let action = ThreatAction {
+        id: Uuid::new_v4(),
+        policy: policy.name.clone(),
+        action: policy.action,
+        subject,
+        indicator: policy.indicator.clone(),
+        description,
+        status,
+        duration_secs,
+        expires_at: (status == ThreatActionStatus::Active && duration_secs > 0)
+            .then(|| now + chrono::Duration::seconds(duration_secs)),
+        created_at: now,
+        decided_by: None,
+        decided_at: None,
+    };
+    repo.insert_threat_action(&action)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match status {
+        ThreatActionStatus::Pending => warn!(
+            "Threat response policy '{}' queued {} of '{}' for review",
+            action.policy,
+            action.action.as_str(),
+            action.subject
+        ),
+        _ => {
+            warn!(
+                "Threat response policy '{}' applied {} to '{}'",
+                action.policy,
+                action.action.as_str(),
+                action.subject
+            );
+            apply_local(&action);
+            broadcast();
+        }
+    }
+    Ok(Some(action))
+}
+
+/// Actions, newest first
+pub fn list(status: Option<ThreatActionStatus>, limit: i64) -> Result<Vec<ThreatAction>, String> {
+    repository::list_threat_actions(status, limit.clamp(1, MAX_LIST_LIMIT))
+        .map_err(|e| e.to_string())
+}
+
+/// Approve, dismiss or lift an action on every instance
+pub fn review(
+    id: &str,
+    decision: ReviewDecision,
+    decided_by: &str,
+) -> Result<ThreatAction, String> {
+    let id = Uuid::parse_str(id).map_err(|_| format!("Invalid threat action id '{}'", id))?;
+    let (from, to) = decision.transition();
+    let action = repository::decide_threat_action(id, from, to, decided_by)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No {} threat action with id '{}'", from.as_str(), id))?;
+    info!(
+        "Threat action {} ({} of '{}') is now {} by {}",
+        action.id,
+        action.action.as_str(),
+        action.subject,
+        action.status.as_str(),
+        decided_by
+    );
+    apply_local(&action);
+    broadcast();
+    Ok(action)
+}
+
+/// Lift every active quarantine of a script on every instance
+pub fn release_script(script_uri: &str, decided_by: &str) -> Result<Vec<ThreatAction>, String> {
+    let quarantines: Vec<ThreatAction> = list(Some(ThreatActionStatus::Active), MAX_LIST_LIMIT)?
+        .into_iter()
+        .filter(|action| {
+            action.action == ThreatActionKind::QuarantineScript && action.subject == script_uri
+        })
+        .collect();
+    if quarantines.is_empty() {
+        return Err(format!("Script '{}' is not quarantined", script_uri));
+    }
+    quarantines
+        .iter()
+        .map(|action| review(&action.id.to_string(), ReviewDecision::Lift, decided_by))
+        .collect()
+}
+
+fn apply_local(action: &ThreatAction) {
+    active_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .apply(action);
+}
+
+/// Reload the active actions from the database
+pub async fn refresh() {
+    let Some(repo) = repository::get_repository_opt() else {
+        return;
+    };
+    match repo.list_active_threat_actions().await {
+        Ok(actions) => {
+            *active_lock()
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) =
+                ActiveActions::from_actions(&actions);
+        }
+        Err(e) => error!("Failed to load active threat actions: {}", e),
+    }
+}
+
+/// Announce changed actions to the other instances
+fn broadcast() {
+    let (Some(db), Some(server_id)) = (
+        crate::database::get_global_database(),
+        crate::notifications::get_server_id(),
+    ) else {
+        return;
+    };
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    handle.spawn(async move {
+        if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(THREAT_RESPONSE_CHANNEL)
+            .bind(&server_id)
+            .execute(db.pool())
+            .await
+        {
+            error!("Failed to broadcast threat action change: {}", e);
+        }
+    });
+}
+
+/// Middleware answering requests from blocked IP addresses with 403
+pub async fn middleware(req: Request, next: Next) -> Response {
+    let ip = crate::rate_limit_rules::client_ip(req.headers());
+    if !is_ip_blocked(&ip) {
+        return next.run(req).await;
+    }
+    let request_id = req
+        .extensions()
+        .get::<crate::middleware::RequestId>()
+        .map(|rid| rid.0.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    warn!("[{}] Request from blocked IP {} refused", request_id, ip);
+    crate::error_to_response(crate::error::errors::forbidden(
+        req.uri().path(),
+        "Requests from this address are blocked",
+        &request_id,
+    ))
+}
+
+/// Start reloading the active actions periodically. Runs even while
+/// policies are disabled, since scripts can also be quarantined for failing.
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::threat_detection::{ThreatIndicator, ThreatLevel};
+    use crate::security::{SecurityEventType, SecuritySeverity};
+
+    fn assessment(indicator_type: &str) -> ThreatAssessment {
+        ThreatAssessment {
+            threat_level: ThreatLevel::High,
+            confidence_score: 80.0,
+            threat_indicators: vec![ThreatIndicator {
+                indicator_type: indicator_type.to_string(),
+                severity: 80.0,
+                description: "test".to_string(),
+                evidence: Vec::new(),
+            }],
+            recommended_actions: Vec::new(),
+            assessment_timestamp: Utc::now(),
+        }
+    }
+
+    fn action(kind: ThreatActionKind, subject: &str, status: ThreatActionStatus) -> ThreatAction {
+        ThreatAction {
+            id: Uuid::new_v4(),
+            policy: "test".to_string(),
+            action: kind,
+            subject: subject.to_string(),
+            indicator: "test".to_string(),
+            description: "test".to_string(),
+            status,
+            duration_secs: 0,
+            expires_at: None,
+            created_at: Utc::now(),
+            decided_by: None,
+            decided_at: None,
+        }
+    }
+
+    #[test]
+    fn test_matching_policies() {
+        let mut config = ThreatResponseConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let event = SecurityEvent::new(
+            SecurityEventType::AuthenticationFailure,
+            SecuritySeverity::Medium,
+            None,
+        )
+        .with_request_context(None, Some("203.0.113.9".to_string()));
+
+        let matches = matching_policies(&config, &event, &assessment("brute_force_authentication"));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0.action, ThreatActionKind::BlockIp);
+        assert_eq!(matches[0].1, "203.0.113.9");
+
+        // No script to quarantine and no policy for the indicator
+        assert!(
+            matching_policies(&config, &event, &assessment("suspicious_script_write")).is_empty()
+        );
+        assert!(matching_policies(&config, &event, &assessment("xss_attempt")).is_empty());
+
+        config.allowlist.push("203.0.113.9".to_string());
+        assert!(
+            matching_policies(&config, &event, &assessment("brute_force_authentication"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_unknown_ip_is_not_blocked() {
+        let config = ThreatResponseConfig::default();
+        let event = SecurityEvent::new(
+            SecurityEventType::AuthenticationFailure,
+            SecuritySeverity::Medium,
+            None,
+        )
+        .with_request_context(None, Some("unknown".to_string()));
+        assert!(
+            matching_policies(&config, &event, &assessment("brute_force_authentication"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_active_actions() {
+        let mut active = ActiveActions::from_actions(&[
+            action(
+                ThreatActionKind::BlockIp,
+                "198.51.100.1",
+                ThreatActionStatus::Active,
+            ),
+            action(
+                ThreatActionKind::QuarantineScript,
+                "https://example.com/bad",
+                ThreatActionStatus::Active,
+            ),
+        ]);
+        assert!(is_active(&active.blocked_ips, "198.51.100.1"));
+        assert!(is_active(
+            &active.quarantined_scripts,
+            "https://example.com/bad"
+        ));
+
+        active.apply(&action(
+            ThreatActionKind::BlockIp,
+            "198.51.100.1",
+            ThreatActionStatus::Lifted,
+        ));
+        assert!(!is_active(&active.blocked_ips, "198.51.100.1"));
+
+        let mut expired = action(
+            ThreatActionKind::BlockIp,
+            "198.51.100.2",
+            ThreatActionStatus::Active,
+        );
+        expired.expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        active.apply(&expired);
+        assert!(!is_active(&active.blocked_ips, "198.51.100.2"));
+    }
+
+    #[test]
+    fn test_review_transitions() {
+        assert_eq!(
+            ReviewDecision::parse("approve").unwrap().transition(),
+            (ThreatActionStatus::Pending, ThreatActionStatus::Active)
+        );
+        assert_eq!(
+            ReviewDecision::parse("lift").unwrap().transition(),
+            (ThreatActionStatus::Active, ThreatActionStatus::Lifted)
+        );
+        assert!(ReviewDecision::parse("block").is_none());
+        for kind in [
+            ThreatActionKind::BlockIp,
+            ThreatActionKind::QuarantineScript,
+            ThreatActionKind::Alert,
+        ] {
+            assert_eq!(ThreatActionKind::parse(kind.as_str()), Some(kind));
+        }
+    }
+}
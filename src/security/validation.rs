@@ -1,7 +1,10 @@
 use html_escape::encode_text;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -44,6 +47,9 @@ pub enum SecurityError {
 
     #[error("Prototype pollution attempt")]
     PrototypePollutionAttempt,
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
@@ -75,6 +81,7 @@ pub struct InputValidator {
     xss_patterns: Vec<Regex>,
     // Allowed MIME types for assets
     allowed_mime_types: HashSet<String>,
+    email_pattern: Regex,
 }
 
 impl Default for InputValidator {
@@ -185,6 +192,12 @@ impl InputValidator {
             "font/otf".to_string(),
         ]);
 
+        // Email addresses: dot-atom local part and a dotted host name
+        let email_pattern = Regex::new(
+            r"^[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+)*@[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?)+$",
+        )
+        .expect("Valid regex pattern for email validation");
+
         Self {
             uri_pattern,
             dangerous_patterns,
@@ -195,9 +208,17 @@ impl InputValidator {
             infinite_loop_patterns,
             xss_patterns,
             allowed_mime_types,
+            email_pattern,
         }
     }
 
+    /// Shared validator for callers that validate on every request, so the
+    /// patterns are compiled once
+    pub fn shared() -> &'static InputValidator {
+        static SHARED: OnceLock<InputValidator> = OnceLock::new();
+        SHARED.get_or_init(InputValidator::new)
+    }
+
     /// Validate and sanitize URI - COMPREHENSIVE RUST VALIDATION
     pub fn validate_uri(&self, uri: &str) -> Result<String, SecurityError> {
         // Length check
@@ -650,6 +671,181 @@ impl InputValidator {
         Ok(())
     }
 
+    /// Validate email addresses
+    pub fn validate_email(&self, email: &str) -> Result<(), SecurityError> {
+        let local_length = email.rfind('@').unwrap_or(0);
+        if email.len() > MAX_EMAIL_LENGTH || local_length > MAX_EMAIL_LOCAL_PART_LENGTH {
+            return Err(SecurityError::InvalidInput(
+                "Email address is too long".to_string(),
+            ));
+        }
+        if !self.email_pattern.is_match(email) {
+            return Err(SecurityError::InvalidInput(
+                "Invalid email address".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate UUIDs in their hyphenated form
+    pub fn validate_uuid(&self, value: &str) -> Result<(), SecurityError> {
+        if value.len() != 36 || uuid::Uuid::try_parse(value).is_err() {
+            return Err(SecurityError::InvalidInput("Invalid UUID".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check an object against field rules, returning every failure
+    pub fn validate_fields(
+        &self,
+        value: &Value,
+        rules: &BTreeMap<String, FieldRule>,
+    ) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        self.check_fields(value, rules, "", &mut errors);
+        errors
+    }
+
+    fn check_fields(
+        &self,
+        value: &Value,
+        rules: &BTreeMap<String, FieldRule>,
+        path: &str,
+        errors: &mut Vec<FieldError>,
+    ) {
+        let Some(object) = value.as_object() else {
+            errors.push(FieldError::new(path, "must be an object"));
+            return;
+        };
+        for (name, rule) in rules {
+            let field_path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            self.check_field(object.get(name), rule, &field_path, errors);
+        }
+    }
+
+    fn check_field(
+        &self,
+        value: Option<&Value>,
+        rule: &FieldRule,
+        path: &str,
+        errors: &mut Vec<FieldError>,
+    ) {
+        let value = match value {
+            None | Some(Value::Null) => {
+                if rule.required {
+                    errors.push(FieldError::new(path, "is required"));
+                }
+                return;
+            }
+            Some(value) => value,
+        };
+
+        let field_type = rule.field_type.or(if rule.fields.is_some() {
+            Some(FieldType::Object)
+        } else if rule.items.is_some() {
+            Some(FieldType::Array)
+        } else {
+            None
+        });
+        if let Some(field_type) = field_type
+            && let Err(message) = self.check_type(value, field_type)
+        {
+            errors.push(FieldError::new(path, message));
+            return;
+        }
+
+        let (length, unit) = match value {
+            Value::String(text) => (Some(text.chars().count()), "characters"),
+            Value::Array(items) => (Some(items.len()), "items"),
+            _ => (None, ""),
+        };
+        if let Some(length) = length {
+            if let Some(min) = rule.min_length
+                && length < min
+            {
+                errors.push(FieldError::new(
+                    path,
+                    format!("must be at least {} {}", min, unit),
+                ));
+            }
+            if let Some(max) = rule.max_length
+                && length > max
+            {
+                errors.push(FieldError::new(
+                    path,
+                    format!("must be at most {} {}", max, unit),
+                ));
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            if let Some(min) = rule.min
+                && number < min
+            {
+                errors.push(FieldError::new(path, format!("must be at least {}", min)));
+            }
+            if let Some(max) = rule.max
+                && number > max
+            {
+                errors.push(FieldError::new(path, format!("must be at most {}", max)));
+            }
+        }
+
+        if let Some(allowed) = &rule.one_of
+            && !allowed.contains(value)
+        {
+            let allowed = allowed
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            errors.push(FieldError::new(path, format!("must be one of {}", allowed)));
+        }
+
+        if let Some(fields) = &rule.fields {
+            self.check_fields(value, fields, path, errors);
+        }
+        if let (Some(item_rule), Value::Array(items)) = (&rule.items, value) {
+            for (index, item) in items.iter().enumerate() {
+                let item_path = format!("{}[{}]", path, index);
+                self.check_field(Some(item), item_rule, &item_path, errors);
+            }
+        }
+    }
+
+    fn check_type(&self, value: &Value, field_type: FieldType) -> Result<(), &'static str> {
+        let text = value.as_str();
+        let valid = match field_type {
+            FieldType::String => text.is_some(),
+            FieldType::Number => value.is_number(),
+            FieldType::Integer => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::Object => value.is_object(),
+            FieldType::Array => value.is_array(),
+            FieldType::Email => text.is_some_and(|text| self.validate_email(text).is_ok()),
+            FieldType::Url => text.is_some_and(|text| self.validate_url(text).is_ok()),
+            FieldType::Uuid => text.is_some_and(|text| self.validate_uuid(text).is_ok()),
+        };
+        if valid {
+            return Ok(());
+        }
+        Err(match field_type {
+            FieldType::String => "must be a string",
+            FieldType::Number => "must be a number",
+            FieldType::Integer => "must be an integer",
+            FieldType::Boolean => "must be a boolean",
+            FieldType::Object => "must be an object",
+            FieldType::Array => "must be an array",
+            FieldType::Email => "must be a valid email address",
+            FieldType::Url => "must be a valid http or https URL",
+            FieldType::Uuid => "must be a UUID",
+        })
+    }
+
     /// Validate HTTP header values
     pub fn validate_header_value(&self, value: &str) -> Result<(), SecurityError> {
         // Check for CRLF injection
@@ -816,6 +1012,73 @@ impl InputValidator {
     }
 }
 
+/// Longest accepted email address (RFC 5321 path limit)
+const MAX_EMAIL_LENGTH: usize = 254;
+
+/// Longest accepted local part of an email address
+const MAX_EMAIL_LOCAL_PART_LENGTH: usize = 64;
+
+/// Type a [`FieldRule`] requires of its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+    Email,
+    Url,
+    Uuid,
+}
+
+/// Rule for one field of an object checked with
+/// [`InputValidator::validate_fields`]. Missing and null fields only fail
+/// when `required`; the other checks apply to the values they fit.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct FieldRule {
+    #[serde(default, rename = "type")]
+    pub field_type: Option<FieldType>,
+    #[serde(default)]
+    pub required: bool,
+    /// Shortest string (in characters) or array
+    pub min_length: Option<usize>,
+    /// Longest string (in characters) or array
+    pub max_length: Option<usize>,
+    /// Smallest number
+    pub min: Option<f64>,
+    /// Largest number
+    pub max: Option<f64>,
+    /// Accepted values
+    #[serde(rename = "enum")]
+    pub one_of: Option<Vec<Value>>,
+    /// Rules for the fields of a nested object
+    pub fields: Option<BTreeMap<String, FieldRule>>,
+    /// Rule for every item of an array
+    pub items: Option<Box<FieldRule>>,
+}
+
+/// A field that failed its [`FieldRule`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// Dotted path to the field, such as `address.city` or `tags[1]`;
+    /// empty for the object itself
+    pub path: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(path: &str, message: impl std::fmt::Display) -> Self {
+        let subject = if path.is_empty() { "value" } else { path };
+        Self {
+            path: path.to_string(),
+            message: format!("{} {}", subject, message),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -968,4 +1231,95 @@ mod tests {
                 .is_err()
         );
     }
+
+    #[test]
+    fn test_email_and_uuid_validation() {
+        let validator = InputValidator::new();
+
+        assert!(validator.validate_email("ada@example.com").is_ok());
+        assert!(
+            validator
+                .validate_email("first.last+tag@mail.example.co")
+                .is_ok()
+        );
+        assert!(validator.validate_email("ada@localhost").is_err());
+        assert!(validator.validate_email("ada..b@example.com").is_err());
+        assert!(validator.validate_email("@example.com").is_err());
+        assert!(validator.validate_email("ada@-example.com").is_err());
+        assert!(
+            validator
+                .validate_email(&format!("{}@example.com", "a".repeat(65)))
+                .is_err()
+        );
+
+        assert!(
+            validator
+                .validate_uuid("67e55044-10b1-426f-9247-bb680e5fe0c8")
+                .is_ok()
+        );
+        assert!(
+            validator
+                .validate_uuid("67e5504410b1426f9247bb680e5fe0c8")
+                .is_err()
+        );
+        assert!(validator.validate_uuid("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn test_field_rules() {
+        let validator = InputValidator::new();
+        let rules: BTreeMap<String, FieldRule> = serde_json::from_value(serde_json::json!({
+            "name": { "type": "string", "required": true, "minLength": 2, "maxLength": 5 },
+            "email": { "type": "email", "required": true },
+            "age": { "type": "integer", "min": 0, "max": 150 },
+            "role": { "enum": ["admin", "user"] },
+            "address": { "fields": { "city": { "type": "string", "required": true } } },
+            "tags": { "items": { "type": "string", "maxLength": 3 }, "maxLength": 2 }
+        }))
+        .unwrap();
+
+        let valid = serde_json::json!({
+            "name": "Ada",
+            "email": "ada@example.com",
+            "age": 36,
+            "role": "admin",
+            "address": { "city": "London" },
+            "tags": ["a", "b"]
+        });
+        assert!(validator.validate_fields(&valid, &rules).is_empty());
+
+        let invalid = serde_json::json!({
+            "name": "A",
+            "email": "nope",
+            "age": 1.5,
+            "role": "root",
+            "address": {},
+            "tags": ["long", "b", "c"]
+        });
+        let errors = validator.validate_fields(&invalid, &rules);
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "address.city is required",
+                "age must be an integer",
+                "email must be a valid email address",
+                "name must be at least 2 characters",
+                "role must be one of \"admin\", \"user\"",
+                "tags must be at most 2 items",
+                "tags[0] must be at most 3 characters",
+            ]
+        );
+        assert_eq!(errors[6].path, "tags[0]");
+
+        let missing = validator.validate_fields(&serde_json::json!({}), &rules);
+        assert_eq!(missing.len(), 2);
+        let not_object = validator.validate_fields(&serde_json::json!("x"), &rules);
+        assert_eq!(not_object[0].message, "value must be an object");
+
+        assert!(
+            serde_json::from_value::<FieldRule>(serde_json::json!({ "type": "date" })).is_err()
+        );
+        assert!(serde_json::from_value::<FieldRule>(serde_json::json!({ "minLen": 1 })).is_err());
+    }
 }
@@ -0,0 +1,192 @@
+//! Deployment checks run by `--self-test` instead of starting the server.
+//!
+//! Every script is initialized the way startup would, with the feature
+//! scripts built into the binary in place of the stored ones, except that:
+//!
+//! - `init()` runs inside a sandbox transaction, so database writes it makes
+//!   are rolled back.
+//! - Init status, registrations and errors are not recorded in the
+//!   database; the report is the only output.
+//!
+//! The routes the scripts register are then checked for conflicts and the
+//! GraphQL operations they register for composing into a schema.
+
+use std::fmt;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::error::AppResult;
+use crate::graphql::SchemaContext;
+use crate::repository::{self, Repository as _};
+use crate::route_index::RouteConflict;
+
+/// Outcome of one script's `init()`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptCheck {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    /// Components that failed to initialize
+    pub component_errors: Vec<String>,
+    pub scripts: Vec<ScriptCheck>,
+    /// Why the GraphQL schema could not be built
+    pub graphql_errors: Vec<String>,
+    pub route_conflicts: Vec<RouteConflict>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.component_errors.is_empty()
+            && self.scripts.iter().all(|script| script.error.is_none())
+            && self.graphql_errors.is_empty()
+            && self.route_conflicts.is_empty()
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.component_errors {
+            writeln!(f, "✗ {}", error)?;
+        }
+        for script in &self.scripts {
+            match &script.error {
+                None => writeln!(f, "✓ init {} ({}ms)", script.uri, script.duration_ms)?,
+                Some(error) => writeln!(f, "✗ init {}: {}", script.uri, error)?,
+            }
+        }
+        if self.graphql_errors.is_empty() {
+            writeln!(f, "✓ GraphQL schema")?;
+        }
+        for error in &self.graphql_errors {
+            writeln!(f, "✗ GraphQL schema: {}", error)?;
+        }
+        if self.route_conflicts.is_empty() {
+            writeln!(f, "✓ No route conflicts")?;
+        }
+        for conflict in &self.route_conflicts {
+            writeln!(
+                f,
+                "✗ Route conflict: {} {}{} registered by {}; {} wins",
+                conflict.method,
+                conflict.host.as_deref().unwrap_or(""),
+                conflict.path,
+                conflict.scripts.join(", "),
+                conflict.winner
+            )?;
+        }
+        let failed = self
+            .scripts
+            .iter()
+            .filter(|script| script.error.is_some())
+            .count();
+        write!(
+            f,
+            "Self-test {}: {} script(s), {} failed",
+            if self.passed() { "passed" } else { "failed" },
+            self.scripts.len(),
+            failed
+        )
+    }
+}
+
+/// Stored scripts with their content, with the feature scripts of this
+/// binary replacing the stored versions, in the order their routes take
+/// precedence: earliest created first, feature scripts not yet stored last
+async fn scripts_to_check() -> AppResult<Vec<(String, String)>> {
+    let mut stored = repository::get_repository()
+        .get_all_script_metadata()
+        .await?;
+    stored.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.uri.cmp(&b.uri)));
+    let mut scripts: Vec<(String, String)> = stored
+        .into_iter()
+        .map(|metadata| (metadata.uri, metadata.content))
+        .collect();
+    for (uri, content) in repository::bootstrap_script_sources() {
+        match scripts.iter_mut().find(|(stored_uri, _)| stored_uri == uri) {
+            Some((_, stored_content)) => *stored_content = content.to_string(),
+            None => scripts.push((uri.to_string(), content.to_string())),
+        }
+    }
+    Ok(scripts)
+}
+
+/// Initialize every script and check the routes and GraphQL operations they
+/// register, adding the results to `report`
+pub async fn check_scripts(report: &mut SelfTestReport, init_timeout_ms: u64) -> AppResult<()> {
+    let mut registrations = Vec::new();
+    for (uri, content) in scripts_to_check().await? {
+        let started = Instant::now();
+        let error = match crate::script_init::init_in_sandbox(&uri, content, init_timeout_ms).await
+        {
+            Ok(Some(routes)) => {
+                registrations.push((uri.clone(), routes));
+                None
+            }
+            Ok(None) => None,
+            Err(e) => Some(e),
+        };
+        report.scripts.push(ScriptCheck {
+            uri,
+            error,
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    report.route_conflicts = crate::route_index::find_conflicts(
+        registrations
+            .iter()
+            .map(|(uri, routes)| (uri.as_str(), routes)),
+    );
+
+    for context in [SchemaContext::External, SchemaContext::Internal] {
+        if let Err(e) = crate::graphql::build_schema_with_context(context, None) {
+            report
+                .graphql_errors
+                .push(format!("{:?} schema: {}", context, e.message));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_passed() {
+        let mut report = SelfTestReport {
+            scripts: vec![ScriptCheck {
+                uri: "https://example.com/core".to_string(),
+                error: None,
+                duration_ms: 3,
+            }],
+            ..Default::default()
+        };
+        assert!(report.passed());
+        assert!(
+            report
+                .to_string()
+                .ends_with("passed: 1 script(s), 0 failed")
+        );
+
+        report.route_conflicts.push(RouteConflict {
+            host: None,
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            scripts: vec!["a".to_string(), "b".to_string()],
+            winner: "a".to_string(),
+        });
+        assert!(!report.passed());
+        assert!(
+            report
+                .to_string()
+                .contains("✗ Route conflict: GET / registered by a, b; a wins")
+        );
+    }
+}
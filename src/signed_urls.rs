@@ -0,0 +1,167 @@
+//! Time-limited signed URLs of private assets.
+//!
+//! Asset routes and prefixes registered with `private: true` only answer
+//! requests whose query carries a valid signature:
+//!
+//! ```javascript
+//! routeRegistry.registerAssetRoute("/reports/q1.pdf", "q1.pdf", { private: true });
+//! const url = assetStorage.signUrl("q1.pdf", 600);
+//! // "/reports/q1.pdf?expires=1767225600&signature=3f0c..."
+//! ```
+//!
+//! The signature is an HMAC-SHA256 of the script URI, the asset name and the
+//! expiry time (Unix seconds) keyed with `security.asset_url_key`. Without a
+//! configured key a random one is generated at startup, so signed URLs stop
+//! working on restart and are not accepted by other instances.
+
+use std::sync::{OnceLock, RwLock};
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::config::SecurityConfig;
+
+/// Query parameter holding the expiry time in Unix seconds
+pub const EXPIRES_PARAM: &str = "expires";
+
+/// Query parameter holding the hex-encoded signature
+pub const SIGNATURE_PARAM: &str = "signature";
+
+/// Longest lifetime of a signed URL
+pub const MAX_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+static KEY: OnceLock<RwLock<Vec<u8>>> = OnceLock::new();
+
+fn key_lock() -> &'static RwLock<Vec<u8>> {
+    KEY.get_or_init(|| RwLock::new(rand::random::<[u8; 32]>().to_vec()))
+}
+
+/// Use the signing key of the configuration
+pub fn configure(config: &SecurityConfig) {
+    let Some(key) = config
+        .asset_url_key
+        .as_deref()
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+    else {
+        warn!(
+            "security.asset_url_key not configured. Generating random key. Signed asset URLs will be invalid after restart."
+        );
+        return;
+    };
+    *key_lock()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = key.as_bytes().to_vec();
+}
+
+fn mac(script_uri: &str, asset_name: &str, expires: u64) -> Hmac<Sha256> {
+    let key = key_lock()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts keys of any size");
+    mac.update(script_uri.as_bytes());
+    mac.update(b"\n");
+    mac.update(asset_name.as_bytes());
+    mac.update(b"\n");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// The query string that lets a request read `asset_name` of `script_uri`
+/// until `expires`
+fn signed_query(script_uri: &str, asset_name: &str, expires: u64) -> String {
+    let signature = hex::encode(mac(script_uri, asset_name, expires).finalize().into_bytes());
+    format!(
+        "{}={}&{}={}",
+        EXPIRES_PARAM, expires, SIGNATURE_PARAM, signature
+    )
+}
+
+/// A URL of a private asset of `script_uri` valid for `ttl_secs` seconds:
+/// the asset's private path with the signature in its query
+pub fn sign_url(script_uri: &str, asset_name: &str, ttl_secs: u64) -> Result<String, String> {
+    if ttl_secs == 0 || ttl_secs > MAX_TTL_SECS {
+        return Err(format!(
+            "TTL must be between 1 and {} seconds",
+            MAX_TTL_SECS
+        ));
+    }
+    let path = crate::asset_registry::get_global_registry()
+        .private_path(script_uri, asset_name)
+        .ok_or_else(|| {
+            format!(
+                "Asset '{}' is not served by a private asset route of this script",
+                asset_name
+            )
+        })?;
+    Ok(format!(
+        "{}?{}",
+        path,
+        signed_query(script_uri, asset_name, now() + ttl_secs)
+    ))
+}
+
+/// Whether a request query carries an unexpired signature for
+/// `asset_name` of `script_uri`
+pub fn verify(script_uri: &str, asset_name: &str, query: &str) -> bool {
+    let mut expires = None;
+    let mut signature = None;
+    for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match name.as_ref() {
+            EXPIRES_PARAM => expires = value.parse::<u64>().ok(),
+            SIGNATURE_PARAM => signature = hex::decode(value.as_bytes()).ok(),
+            _ => {}
+        }
+    }
+    let (Some(expires), Some(signature)) = (expires, signature) else {
+        return false;
+    };
+    expires >= now()
+        && mac(script_uri, asset_name, expires)
+            .verify_slice(&signature)
+            .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let expires = now() + 60;
+        let query = signed_query("script1", "q1.pdf", expires);
+        assert!(verify("script1", "q1.pdf", &query));
+        assert!(verify(
+            "script1",
+            "q1.pdf",
+            &format!("download=1&{}", query)
+        ));
+
+        // The signature covers the script, the asset and the expiry time
+        assert!(!verify("script2", "q1.pdf", &query));
+        assert!(!verify("script1", "q2.pdf", &query));
+        let extended = query.replace(&expires.to_string(), &(expires + 3600).to_string());
+        assert!(!verify("script1", "q1.pdf", &extended));
+
+        assert!(!verify("script1", "q1.pdf", ""));
+        assert!(!verify(
+            "script1",
+            "q1.pdf",
+            &signed_query("script1", "q1.pdf", now() - 1)
+        ));
+    }
+
+    #[test]
+    fn test_sign_url_ttl() {
+        assert!(sign_url("script1", "q1.pdf", 0).is_err());
+        assert!(sign_url("script1", "q1.pdf", MAX_TTL_SECS + 1).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Source maps for scripts that do not run as written.
+//!
+//! TypeScript and JSX scripts run as the JavaScript the transpiler produced
+//! from them, so positions QuickJS reports point into code the script's
+//! author never saw. The transpiler records a source map for each script, and
+//! [`ScriptSourceMap`] looks generated positions up in it.
+//!
+//! Scripts bundled or minified before upload can bring their own map (the
+//! `sourceMap` option of `scriptStorage.upsertScript`). An uploaded map is
+//! stored with the hash of the content it was uploaded for and only applies
+//! while the script still has that content; see [`uploaded_source_map`].
+
+use std::collections::HashMap;
+
+use oxc_sourcemap::SourceMap;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::repository;
+
+/// Largest source map accepted for upload
+pub const MAX_SOURCE_MAP_BYTES: usize = 10 * 1024 * 1024;
+
+/// A 1-based position in a script's original source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// Source file the position is in, as named by the map
+    pub source: Option<String>,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A parsed source map
+#[derive(Debug, Clone)]
+pub struct ScriptSourceMap {
+    map: SourceMap<'static>,
+}
+
+impl ScriptSourceMap {
+    /// Parse a source map in the standard JSON format
+    pub fn parse(json: &str) -> Result<Self, String> {
+        SourceMap::from_json_string(json)
+            .map(|map| Self {
+                map: map.into_owned(),
+            })
+            .map_err(|e| format!("Invalid source map: {}", e))
+    }
+
+    /// Name every source of the map `name`. Used for maps of a single
+    /// script so mapped positions keep the script's URI.
+    pub fn with_source(mut self, name: &str) -> Self {
+        let count = self.map.get_sources().len();
+        self.map.set_sources(vec![name; count]);
+        self
+    }
+
+    /// Original position of 1-based `line` and `column` in the generated
+    /// code. A missing column is looked up as the start of the line.
+    pub fn original_position(&self, line: u32, column: Option<u32>) -> Option<SourcePosition> {
+        let line = line.checked_sub(1)?;
+        let column = column.unwrap_or(1).saturating_sub(1);
+        let table = self.map.generate_lookup_table();
+        let token = self.map.lookup_token_approx(&table, line, column)?;
+        let source_id = token.get_source_id()?;
+        Some(SourcePosition {
+            source: self.map.get_source(source_id).map(str::to_string),
+            line: token.get_src_line() + 1,
+            column: token.get_src_col() + 1,
+        })
+    }
+}
+
+/// Hash identifying the script content a source map was uploaded for
+pub fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+/// Check a source map before it is stored
+pub fn validate_upload(json: &str) -> Result<(), String> {
+    if json.len() > MAX_SOURCE_MAP_BYTES {
+        return Err(format!(
+            "Source map is larger than {} bytes",
+            MAX_SOURCE_MAP_BYTES
+        ));
+    }
+    ScriptSourceMap::parse(json).map(|_| ())
+}
+
+/// Source map uploaded for a script, if one was uploaded for its current
+/// `content`
+pub fn uploaded_source_map(script_uri: &str, content: &str) -> Option<ScriptSourceMap> {
+    let stored = match repository::get_script_source_map(script_uri) {
+        Ok(stored) => stored?,
+        Err(e) => {
+            warn!("Failed to load source map of {}: {}", script_uri, e);
+            return None;
+        }
+    };
+    if stored.content_hash != content_hash(content) {
+        return None;
+    }
+    match ScriptSourceMap::parse(&stored.source_map) {
+        Ok(map) => Some(map),
+        Err(e) => {
+            warn!("Ignoring source map of {}: {}", script_uri, e);
+            None
+        }
+    }
+}
+
+/// Translates stack frames in log messages through the source maps uploaded
+/// for the scripts that wrote them, loading each script's map once
+#[derive(Debug, Default)]
+pub struct LogTranslator {
+    maps: HashMap<String, Option<ScriptSourceMap>>,
+}
+
+impl LogTranslator {
+    /// `message` logged by `script_uri`, with the script's frames translated
+    pub fn translate(&mut self, script_uri: &str, message: &str) -> String {
+        // Only messages naming the script can hold frames to translate
+        if !message.contains(script_uri) {
+            return message.to_string();
+        }
+        let map = self.maps.entry(script_uri.to_string()).or_insert_with(|| {
+            let content = repository::fetch_script(script_uri)?;
+            uploaded_source_map(script_uri, &content)
+        });
+        match map {
+            Some(map) => crate::script_errors::remap_stack(message, script_uri, map),
+            None => message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_errors::JsError;
+
+    const SOURCE: &str = "interface Order {\n  id: string;\n}\n\nfunction handler(order: Order) {\n  throw new Error(order.id);\n}\n";
+
+    fn transpiled() -> crate::transpiler::Transpiled {
+        crate::transpiler::transpile_with_source_map("source_maps_test.ts", SOURCE).unwrap()
+    }
+
+    fn generated_position(code: &str, needle: &str) -> (u32, u32) {
+        let offset = code.find(needle).unwrap();
+        let before = &code[..offset];
+        let line = before.matches('\n').count() as u32 + 1;
+        let column = (offset - before.rfind('\n').map_or(0, |i| i + 1)) as u32 + 1;
+        (line, column)
+    }
+
+    #[test]
+    fn test_original_position() {
+        let transpiled = transpiled();
+        let map = ScriptSourceMap::parse(transpiled.source_map.as_deref().unwrap()).unwrap();
+        let (line, column) = generated_position(&transpiled.code, "throw");
+        assert_eq!(
+            map.original_position(line, Some(column)),
+            Some(SourcePosition {
+                source: Some("source_maps_test.ts".to_string()),
+                line: 6,
+                column: 3
+            })
+        );
+        assert_eq!(map.original_position(0, Some(1)), None);
+    }
+
+    #[test]
+    fn test_remap_error() {
+        let transpiled = transpiled();
+        let map = ScriptSourceMap::parse(transpiled.source_map.as_deref().unwrap()).unwrap();
+        let (line, column) = generated_position(&transpiled.code, "throw");
+        let mut error = JsError {
+            name: Some("Error".to_string()),
+            message: "o-1".to_string(),
+            stack: Some(format!(
+                "    at handler (source_maps_test.ts:{line}:{column})\n    at map (native)"
+            )),
+            file_name: Some("source_maps_test.ts".to_string()),
+            line_number: Some(line),
+            column_number: Some(column),
+        };
+        error.remap("source_maps_test.ts", &map);
+        assert_eq!(error.line_number, Some(6));
+        assert_eq!(error.column_number, Some(3));
+        assert_eq!(
+            error.stack.as_deref(),
+            Some("    at handler (source_maps_test.ts:6:3)\n    at map (native)")
+        );
+    }
+
+    #[test]
+    fn test_remap_uploaded_bundle() {
+        // A bundle of two files: line 1 comes from src/util.ts, line 2 from
+        // src/main.ts line 5
+        let map = ScriptSourceMap::parse(
+            r#"{"version":3,"sources":["src/util.ts","src/main.ts"],"names":[],"mappings":"AAAA;ACIA,MAAM"}"#,
+        )
+        .unwrap();
+        let stack = "    at helper (bundle.js:1:1)\n    at handler (bundle.js:2:9)\n    at other (lib.js:2:9)";
+        assert_eq!(
+            crate::script_errors::remap_stack(stack, "bundle.js", &map),
+            "    at helper (src/util.ts:1:1)\n    at handler (src/main.ts:5:7)\n    at other (lib.js:2:9)"
+        );
+    }
+
+    #[test]
+    fn test_validate_upload() {
+        assert!(validate_upload("not a map").is_err());
+        assert!(validate_upload(r#"{"version":3,"sources":[],"names":[],"mappings":""}"#).is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+//! Staged (blue/green) deployment of scripts.
+//!
+//! Every script has a staging slot next to its live version:
+//!
+//! - Staging a version stores it in the slot and runs its `init()` in a
+//!   sandbox transaction, under the script URI with [`STAGING_SUFFIX`]
+//!   appended so the live registrations are untouched. The outcome and the
+//!   routes it registers are kept with the slot; the live version keeps
+//!   serving. The staged content can be tried with `scriptStorage.dryRun`.
+//! - Promoting swaps the staged version with the live one in one database
+//!   transaction and reloads the script on every instance, which replaces
+//!   its route and GraphQL registrations.
+//! - The version a promotion replaced stays in the slot, so a rollback swaps
+//!   it back the same way. Rolling back again undoes the rollback.
+//!
+//! System scripts are not staged.
+
+use tracing::info;
+
+use crate::repository::{self, Repository as _, ScriptStaging};
+
+/// Appended to the URI of a script to run the `init()` of its staged version
+pub const STAGING_SUFFIX: &str = "#staged";
+
+/// Longest the `init()` of a staged version may run
+const STAGED_INIT_TIMEOUT_MS: u64 = 10_000;
+
+/// Forget what the `init()` of a staged version registered
+fn clear_staging_registrations(staging_uri: &str) {
+    crate::graphql::clear_script_graphql_registrations(staging_uri);
+    crate::mcp::clear_script_mcp_registrations(staging_uri);
+    crate::scheduler::clear_script_jobs(staging_uri);
+    crate::notifications::clear_script_channels(staging_uri);
+    crate::events::clear_script_subscriptions(staging_uri);
+}
+
+/// Registered routes as "METHOD path", sorted
+fn route_list(registrations: Option<repository::RouteRegistrations>) -> Vec<String> {
+    let mut routes: Vec<String> = registrations
+        .unwrap_or_default()
+        .into_keys()
+        .map(|(path, method)| format!("{} {}", method, path))
+        .collect();
+    routes.sort();
+    routes
+}
+
+async fn ensure_stageable(uri: &str) -> Result<(), String> {
+    let repo = repository::get_repository();
+    match repo.get_script_system(uri).await {
+        Ok(None) => Err(format!("Script '{}' not found", uri)),
+        Ok(Some(true)) => Err(format!("System script '{}' cannot be staged", uri)),
+        Ok(Some(false)) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// The staging slot of a script; None when nothing was ever staged
+pub async fn status(uri: &str) -> Result<Option<ScriptStaging>, String> {
+    repository::get_repository()
+        .get_script_staging(uri)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn current_slot(uri: &str) -> Result<ScriptStaging, String> {
+    status(uri)
+        .await?
+        .ok_or_else(|| format!("Script '{}' has no staging slot", uri))
+}
+
+/// Stage `content` as the next version of a script, replacing any version
+/// staged before
+pub async fn stage(
+    uri: &str,
+    content: &str,
+    staged_by: Option<&str>,
+) -> Result<ScriptStaging, String> {
+    ensure_stageable(uri).await?;
+
+    let staging_uri = format!("{}{}", uri, STAGING_SUFFIX);
+    let outcome = crate::script_init::init_in_sandbox(
+        &staging_uri,
+        content.to_string(),
+        STAGED_INIT_TIMEOUT_MS,
+    )
+    .await;
+    clear_staging_registrations(&staging_uri);
+    let (staged_init_error, staged_routes) = match outcome {
+        Ok(registrations) => (None, route_list(registrations)),
+        Err(e) => (Some(e), Vec::new()),
+    };
+
+    let staging = ScriptStaging {
+        uri: uri.to_string(),
+        staged_content: Some(content.to_string()),
+        staged_by: staged_by.map(str::to_string),
+        staged_init_error,
+        staged_routes,
+        ..Default::default()
+    };
+    if !repository::get_repository()
+        .stage_script(&staging)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Script '{}' not found", uri));
+    }
+    info!("Staged a new version of script '{}'", uri);
+    current_slot(uri).await
+}
+
+/// Drop the staged version of a script
+pub async fn discard(uri: &str) -> Result<ScriptStaging, String> {
+    if !repository::get_repository()
+        .discard_staged_script(uri)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Script '{}' has no staged version", uri));
+    }
+    current_slot(uri).await
+}
+
+/// Reload a script after a swap, on this instance; the repository tells
+/// the other instances
+async fn reload(uri: &str, what: &str) -> Result<ScriptStaging, String> {
+    crate::admin_ops::reload_local_script(uri)
+        .await
+        .map_err(|e| format!("{} is live but failed to initialize: {}", what, e))?;
+    current_slot(uri).await
+}
+
+/// Make the staged version of a script live. Refused when its `init()`
+/// failed when it was staged.
+pub async fn promote(uri: &str) -> Result<ScriptStaging, String> {
+    ensure_stageable(uri).await?;
+    let slot = current_slot(uri).await?;
+    if slot.staged_content.is_none() {
+        return Err(format!("Script '{}' has no staged version", uri));
+    }
+    if let Some(e) = slot.staged_init_error {
+        return Err(format!(
+            "Staged version of '{}' failed to initialize: {}",
+            uri, e
+        ));
+    }
+    if !repository::get_repository()
+        .promote_staged_script(uri)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Script '{}' has no staged version", uri));
+    }
+    info!("Promoted the staged version of script '{}'", uri);
+    reload(uri, "Promoted version").await
+}
+
+/// Make the version replaced by the last promotion or rollback of a script
+/// live again
+pub async fn rollback(uri: &str) -> Result<ScriptStaging, String> {
+    ensure_stageable(uri).await?;
+    if !repository::get_repository()
+        .rollback_script(uri)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        return Err(format!("Script '{}' has no previous version", uri));
+    }
+    info!("Rolled back script '{}'", uri);
+    reload(uri, "Previous version").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_list() {
+        let mut registrations = repository::RouteRegistrations::new();
+        for (path, method) in [("/b", "GET"), ("/a", "POST"), ("/a", "GET")] {
+            registrations.insert(
+                (path.to_string(), method.to_string()),
+                repository::RouteMetadata::simple("handler".to_string()),
+            );
+        }
+        assert_eq!(
+            route_list(Some(registrations)),
+            vec!["GET /a", "GET /b", "POST /a"]
+        );
+        assert!(route_list(None).is_empty());
+    }
+}
@@ -962,6 +962,35 @@ impl StreamRegistry {
         }
     }
 
+    /// Close the connections of one stream path, or of all streams, while
+    /// keeping the registrations so clients can reconnect
+    pub fn drain_connections(&self, path: Option<&str>) -> Result<usize, String> {
+        match self.streams.lock() {
+            Ok(mut streams) => {
+                let mut total_closed = 0;
+                for (stream_path, registration) in streams.iter_mut() {
+                    if path.is_some_and(|path| path != stream_path) {
+                        continue;
+                    }
+                    let closed = registration.connections.len();
+                    registration.connections.clear();
+                    if closed > 0 {
+                        info!(
+                            "Drained {} connections from stream path '{}'",
+                            closed, stream_path
+                        );
+                    }
+                    total_closed += closed;
+                }
+                Ok(total_closed)
+            }
+            Err(e) => {
+                error!("Failed to acquire stream registry lock: {}", e);
+                Err("Failed to drain connections: registry lock error".to_string())
+            }
+        }
+    }
+
     /// Get the total number of connections across all streams
     pub fn total_connection_count(&self) -> Result<usize, String> {
         match self.streams.lock() {
@@ -1304,6 +1333,34 @@ mod tests {
         assert!(registry.list_stream_paths().unwrap().is_empty());
     }
 
+    #[test]
+    fn test_stream_registry_drain_connections() {
+        let registry = StreamRegistry::new();
+        registry.register_stream("/a", "a.js", None).unwrap();
+        registry.register_stream("/b", "b.js", None).unwrap();
+        let receiver_conn = StreamConnection::new();
+        let mut receiver = receiver_conn.subscribe();
+        registry.add_connection("/a", receiver_conn).unwrap();
+        registry
+            .add_connection("/a", StreamConnection::new())
+            .unwrap();
+        registry
+            .add_connection("/b", StreamConnection::new())
+            .unwrap();
+
+        assert_eq!(registry.drain_connections(Some("/a")).unwrap(), 2);
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Closed)
+        ));
+        assert_eq!(registry.total_connection_count().unwrap(), 1);
+        assert_eq!(registry.drain_connections(None).unwrap(), 1);
+
+        // Registrations survive so clients can reconnect
+        assert!(registry.is_stream_registered("/a"));
+        assert!(registry.is_stream_registered("/b"));
+    }
+
     #[test]
     fn test_stream_connection_age() {
         let conn = StreamConnection::new();
@@ -0,0 +1,671 @@
+//! Background tasks of scripts (`tasks.enqueue`).
+//!
+//! A request handler hands slow work off with
+//! `tasks.enqueue(handlerName, payload, options)`, which stores the task in
+//! the `script_tasks` table and returns at once. Like webhooks, a task
+//! queued inside a transaction exists only if the transaction commits.
+//!
+//! Workers on every server instance claim due tasks, highest `priority`
+//! first, and run the handler of the queueing script with the task in
+//! `context.meta.task`, like scheduled jobs. A claimed task is hidden from
+//! other workers for the visibility timeout, so a task whose worker stopped
+//! runs again once it passes. A handler that throws is retried with
+//! doubling delays (`[javascript.tasks]`) until the task has used its
+//! attempts and is marked `failed`; handlers should therefore be safe to
+//! run more than once.
+//!
+//! A task may be postponed with `runAfter` (such as `"24h"`), `delayMs` or
+//! `runAt`, up to 30 days ahead. An `idempotencyKey` makes queueing run
+//! once: while a task of the script with the same key is kept (until
+//! `retention_hours` after it finished), `tasks.enqueue` returns that task
+//! marked `duplicate` instead of queueing another, so "only once per
+//! order" needs no bookkeeping of its own.
+//!
+//! Scripts follow their tasks with `tasks.get(id)`; administrators see the
+//! queue depth and failures of every script with the `taskQueueStats` and
+//! `scriptTasks` GraphQL queries.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{Notify, Semaphore};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::TaskQueueConfig;
+use crate::repository::{self, Repository as _};
+
+/// Lowest and highest priority a task may have
+pub const PRIORITY_RANGE: std::ops::RangeInclusive<i32> = -1000..=1000;
+
+/// Longest a task may be delayed
+const MAX_DELAY: chrono::Duration = chrono::Duration::days(30);
+
+/// How often workers look for due tasks when not woken
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often finished tasks past their retention are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Longest accepted idempotency key
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Most tasks `tasks.list` and the GraphQL view return
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+static SETTINGS: OnceLock<RwLock<TaskQueueConfig>> = OnceLock::new();
+
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the workers when a task is queued or finishes
+static WAKE: Notify = Notify::const_new();
+
+fn settings() -> &'static RwLock<TaskQueueConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the task queue configuration. Called once at server startup.
+pub fn configure(config: &TaskQueueConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> TaskQueueConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// State of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    /// Claimed by a worker whose visibility timeout has not passed
+    Running,
+    Completed,
+    /// Used all its attempts
+    Failed,
+}
+
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Pending => "pending",
+            TaskStatus::Running => "running",
+            TaskStatus::Completed => "completed",
+            TaskStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(TaskStatus::Pending),
+            "running" => Some(TaskStatus::Running),
+            "completed" => Some(TaskStatus::Completed),
+            "failed" => Some(TaskStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A queued task and how it went
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptTask {
+    pub id: Uuid,
+    pub script_uri: String,
+    pub handler_name: String,
+    pub payload: Value,
+    pub priority: i32,
+    pub status: TaskStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When the next attempt is due; meaningful while pending
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub idempotency_key: Option<String>,
+}
+
+/// Result of `tasks.enqueue`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnqueuedTask {
+    #[serde(flatten)]
+    pub task: ScriptTask,
+    /// The script already had a task with the idempotency key, which is
+    /// returned instead of a new one
+    pub duplicate: bool,
+}
+
+/// A task to queue
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewTask {
+    pub id: Uuid,
+    pub script_uri: String,
+    pub handler_name: String,
+    pub payload: Value,
+    pub priority: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub idempotency_key: Option<String>,
+}
+
+/// A task claimed by a worker for one attempt
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimedTask {
+    pub task: NewTask,
+    /// Including the attempt being made
+    pub attempts: i32,
+}
+
+/// What one attempt changes about a task
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskOutcome {
+    Completed,
+    Retry {
+        error: String,
+        run_at: DateTime<Utc>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// Queue depth of one script, for the GraphQL view
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskQueueStats {
+    pub script_uri: String,
+    /// Due or waiting for a later run, not running
+    pub pending: i64,
+    pub running: i64,
+    pub completed: i64,
+    pub failed: i64,
+    /// Oldest due task not yet running, showing how far workers lag
+    pub oldest_due_at: Option<DateTime<Utc>>,
+}
+
+/// Options of `tasks.enqueue`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct EnqueueOptions {
+    /// Higher runs first; 0 by default
+    pub priority: Option<i32>,
+    /// Run no earlier than this duration from now, such as "90s", "15m",
+    /// "24h" or "1d12h"
+    pub run_after: Option<String>,
+    /// Run no earlier than this many milliseconds from now
+    pub delay_ms: Option<u64>,
+    /// Run no earlier than this UTC ISO timestamp
+    pub run_at: Option<String>,
+    pub max_attempts: Option<u32>,
+    /// Queue only if the script has no task with this key yet
+    pub idempotency_key: Option<String>,
+}
+
+/// Filter of `tasks.list` and the GraphQL view
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListOptions {
+    pub status: Option<TaskStatus>,
+    pub script_uri: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Parse a `runAfter` duration: whole numbers with units `ms`, `s`, `m`,
+/// `h` or `d`, such as "500ms", "24h" or "1d12h"
+fn parse_run_after(value: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid runAfter '{}': use a duration such as '30s', '15m', '24h' or '1d12h'",
+            value
+        )
+    };
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let amount: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit_ms: u64 = match &rest[..unit_len] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        total = amount
+            .checked_mul(unit_ms)
+            .and_then(|ms| total.checked_add(ms))
+            .ok_or_else(invalid)?;
+    }
+    Ok(Duration::from_millis(total))
+}
+
+/// Queue a run of `handler_name` of `script_uri` with `payload` and wake
+/// the workers. With an idempotency key the script already used, the task
+/// queued with it is returned instead.
+pub fn enqueue(
+    script_uri: &str,
+    handler_name: &str,
+    payload: Value,
+    options: &EnqueueOptions,
+) -> Result<EnqueuedTask, String> {
+    let config = current_settings();
+    let handler_name = handler_name.trim();
+    if handler_name.is_empty() {
+        return Err("tasks.enqueue requires a non-empty handler name".to_string());
+    }
+    let payload_bytes = serde_json::to_vec(&payload)
+        .map_err(|e| format!("Failed to serialize payload: {}", e))?
+        .len();
+    if payload_bytes > config.max_payload_bytes {
+        return Err(format!(
+            "payload must not exceed {} bytes",
+            config.max_payload_bytes
+        ));
+    }
+    let priority = options.priority.unwrap_or(0);
+    if !PRIORITY_RANGE.contains(&priority) {
+        return Err(format!(
+            "priority must be between {} and {}",
+            PRIORITY_RANGE.start(),
+            PRIORITY_RANGE.end()
+        ));
+    }
+    let max_attempts = options.max_attempts.unwrap_or(config.max_attempts);
+    if max_attempts == 0 || max_attempts > config.max_attempts_limit {
+        return Err(format!(
+            "maxAttempts must be between 1 and {}",
+            config.max_attempts_limit
+        ));
+    }
+    let idempotency_key = match options.idempotency_key.as_deref().map(str::trim) {
+        Some("") => return Err("idempotencyKey must not be empty".to_string()),
+        Some(key) if key.len() > MAX_IDEMPOTENCY_KEY_LENGTH => {
+            return Err(format!(
+                "idempotencyKey must not exceed {} characters",
+                MAX_IDEMPOTENCY_KEY_LENGTH
+            ));
+        }
+        key => key.map(str::to_string),
+    };
+    let delays = [
+        options.run_at.is_some(),
+        options.run_after.is_some(),
+        options.delay_ms.is_some(),
+    ];
+    if delays.iter().filter(|set| **set).count() > 1 {
+        return Err("Use only one of runAt, runAfter and delayMs".to_string());
+    }
+    let delay_ms = match &options.run_after {
+        Some(run_after) => Some(parse_run_after(run_after)?.as_millis() as u64),
+        None => options.delay_ms,
+    };
+    let now = Utc::now();
+    let run_at = match (&options.run_at, delay_ms) {
+        (Some(run_at), _) => crate::scheduler::parse_utc_timestamp(run_at)
+            .map_err(|e| e.to_string())?
+            .max(now),
+        (None, Some(delay_ms)) => {
+            now + chrono::Duration::milliseconds(delay_ms.min(i64::MAX as u64) as i64)
+        }
+        (None, None) => now,
+    };
+    if run_at - now > MAX_DELAY {
+        return Err(format!(
+            "Tasks may be delayed by at most {} days",
+            MAX_DELAY.num_days()
+        ));
+    }
+
+    let task = NewTask {
+        id: Uuid::new_v4(),
+        script_uri: script_uri.to_string(),
+        handler_name: handler_name.to_string(),
+        payload,
+        priority,
+        max_attempts: max_attempts as i32,
+        run_at,
+        idempotency_key,
+    };
+    let queued = repository::enqueue_task(&task).map_err(|e| e.to_string())?;
+    if queued.duplicate {
+        debug!(
+            script_uri = %script_uri,
+            id = %queued.task.id,
+            "Task with the same idempotency key already queued"
+        );
+        return Ok(queued);
+    }
+    debug!(
+        script_uri = %script_uri,
+        id = %queued.task.id,
+        handler = %queued.task.handler_name,
+        "Queued task"
+    );
+    WAKE.notify_one();
+    Ok(queued)
+}
+
+/// A task of `script_uri`
+pub fn get(script_uri: &str, id: &str) -> Result<Option<ScriptTask>, String> {
+    let Ok(id) = Uuid::parse_str(id) else {
+        return Ok(None);
+    };
+    repository::get_task(id)
+        .map(|task| task.filter(|task| task.script_uri == script_uri))
+        .map_err(|e| e.to_string())
+}
+
+/// Tasks matching `options`, newest first
+pub fn list(options: &ListOptions) -> Result<Vec<ScriptTask>, String> {
+    let limit = options.limit.unwrap_or(100);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return Err(format!("limit must be between 1 and {}", MAX_LIST_LIMIT));
+    }
+    repository::list_tasks(options.status, options.script_uri.as_deref(), limit)
+        .map_err(|e| e.to_string())
+}
+
+/// Queue depth of every script with tasks
+pub fn stats() -> Result<Vec<TaskQueueStats>, String> {
+    repository::task_queue_stats().map_err(|e| e.to_string())
+}
+
+/// Delay before the retry that follows attempt number `attempts`
+fn retry_delay(config: &TaskQueueConfig, attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 32) as u32;
+    let delay = config
+        .retry_delay_ms
+        .saturating_mul(1u64 << doublings)
+        .min(config.max_retry_delay_ms);
+    Duration::from_millis(delay)
+}
+
+/// What an attempt's result means for the task
+fn outcome(
+    config: &TaskQueueConfig,
+    claimed: &ClaimedTask,
+    result: Result<(), String>,
+) -> TaskOutcome {
+    match result {
+        Ok(()) => TaskOutcome::Completed,
+        Err(error) if claimed.attempts >= claimed.task.max_attempts => {
+            TaskOutcome::Failed { error }
+        }
+        Err(error) => TaskOutcome::Retry {
+            error,
+            run_at: Utc::now()
+                + chrono::Duration::from_std(retry_delay(config, claimed.attempts))
+                    .unwrap_or_else(|_| chrono::Duration::hours(1)),
+        },
+    }
+}
+
+/// Run one claimed task and record the outcome
+async fn run_task(config: TaskQueueConfig, claimed: ClaimedTask) {
+    let task = &claimed.task;
+    // Claimed again after the worker of its last attempt stopped
+    let result = if claimed.attempts > task.max_attempts {
+        Err("Worker stopped during the last attempt".to_string())
+    } else {
+        let handler_claimed = claimed.clone();
+        tokio::task::spawn_blocking(move || {
+            crate::js_engine::execute_task_handler(
+                &handler_claimed.task.script_uri,
+                &handler_claimed.task.handler_name,
+                &handler_claimed,
+            )
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("handler panicked: {}", e)))
+    };
+    let outcome = outcome(&config, &claimed, result);
+
+    match &outcome {
+        TaskOutcome::Completed => debug!(
+            id = %task.id,
+            handler = %task.handler_name,
+            "Task completed"
+        ),
+        TaskOutcome::Retry { error, .. } => debug!(
+            id = %task.id,
+            handler = %task.handler_name,
+            attempt = claimed.attempts,
+            "Task attempt failed, will retry: {}",
+            error
+        ),
+        TaskOutcome::Failed { error } => {
+            warn!(
+                id = %task.id,
+                script = %task.script_uri,
+                handler = %task.handler_name,
+                attempts = claimed.attempts,
+                "Task failed: {}",
+                error
+            );
+            repository::insert_log_message_async(
+                &task.script_uri,
+                &format!(
+                    "task {} ({}) failed after {} attempts: {}",
+                    task.id, task.handler_name, claimed.attempts, error
+                ),
+                "ERROR",
+            )
+            .await;
+        }
+    }
+    if let Err(e) = repository::get_repository()
+        .finish_task_attempt(task.id, &outcome)
+        .await
+    {
+        warn!(id = %task.id, "Failed to record task attempt: {}", e);
+    }
+}
+
+/// Start the background task that runs queued tasks, at most
+/// `concurrency` at a time, and deletes finished ones past their
+/// retention. Only the first call starts a worker.
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let slots = Arc::new(Semaphore::new(current_settings().concurrency));
+        info!("Task queue worker started");
+        let mut last_purge: Option<tokio::time::Instant> = None;
+        loop {
+            let config = current_settings();
+            let repo = repository::get_repository();
+            let free = slots.available_permits();
+            if free > 0 {
+                let visibility = Duration::from_millis(config.visibility_timeout_ms);
+                match repo.claim_tasks(free as i64, visibility).await {
+                    Ok(claimed) => {
+                        for claimed in claimed {
+                            let Ok(slot) = slots.clone().try_acquire_owned() else {
+                                break;
+                            };
+                            let config = config.clone();
+                            tokio::spawn(async move {
+                                run_task(config, claimed).await;
+                                drop(slot);
+                                WAKE.notify_one();
+                            });
+                        }
+                    }
+                    Err(e) => warn!("Failed to claim tasks: {}", e),
+                }
+            }
+
+            if last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                last_purge = Some(tokio::time::Instant::now());
+                let before = Utc::now() - chrono::Duration::hours(config.retention_hours as i64);
+                match repo.purge_tasks(before).await {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Removed {} finished tasks", count),
+                    Err(e) => warn!("Failed to purge tasks: {}", e),
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = WAKE.notified() => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claimed(attempts: i32) -> ClaimedTask {
+        ClaimedTask {
+            task: NewTask {
+                id: Uuid::new_v4(),
+                script_uri: "https://example.com/reports".to_string(),
+                handler_name: "buildReport".to_string(),
+                payload: json!({ "reportId": 1 }),
+                priority: 0,
+                max_attempts: 3,
+                run_at: Utc::now(),
+                idempotency_key: None,
+            },
+            attempts,
+        }
+    }
+
+    #[test]
+    fn test_retries() {
+        let config = TaskQueueConfig {
+            retry_delay_ms: 1000,
+            max_retry_delay_ms: 5000,
+            ..TaskQueueConfig::default()
+        };
+        assert_eq!(retry_delay(&config, 1), Duration::from_secs(1));
+        assert_eq!(retry_delay(&config, 3), Duration::from_secs(4));
+        assert_eq!(retry_delay(&config, 30), Duration::from_secs(5));
+
+        assert_eq!(
+            outcome(&config, &claimed(1), Ok(())),
+            TaskOutcome::Completed
+        );
+        assert!(matches!(
+            outcome(&config, &claimed(2), Err("boom".to_string())),
+            TaskOutcome::Retry { ref error, .. } if error == "boom"
+        ));
+        assert_eq!(
+            outcome(&config, &claimed(3), Err("boom".to_string())),
+            TaskOutcome::Failed {
+                error: "boom".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_enqueue_validation() {
+        let uri = "https://example.com/reports";
+        let error = |options: Value| {
+            let options: EnqueueOptions = serde_json::from_value(options).unwrap();
+            enqueue(uri, "buildReport", Value::Null, &options).unwrap_err()
+        };
+
+        assert_eq!(
+            enqueue(uri, " ", Value::Null, &EnqueueOptions::default()).unwrap_err(),
+            "tasks.enqueue requires a non-empty handler name"
+        );
+        assert_eq!(
+            error(json!({ "priority": 1001 })),
+            "priority must be between -1000 and 1000"
+        );
+        assert_eq!(
+            error(json!({ "maxAttempts": 0 })),
+            "maxAttempts must be between 1 and 20"
+        );
+        assert_eq!(
+            error(json!({ "delayMs": 1000, "runAt": "2030-01-01T00:00:00Z" })),
+            "Use only one of runAt, runAfter and delayMs"
+        );
+        assert_eq!(
+            error(json!({ "runAfter": "1h", "delayMs": 1000 })),
+            "Use only one of runAt, runAfter and delayMs"
+        );
+        assert_eq!(
+            error(json!({ "runAfter": "31d" })),
+            "Tasks may be delayed by at most 30 days"
+        );
+        assert_eq!(
+            error(json!({ "idempotencyKey": " " })),
+            "idempotencyKey must not be empty"
+        );
+        assert_eq!(
+            error(json!({ "delayMs": 31u64 * 24 * 60 * 60 * 1000 })),
+            "Tasks may be delayed by at most 30 days"
+        );
+        assert!(serde_json::from_value::<EnqueueOptions>(json!({ "retries": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_parse_run_after() {
+        assert_eq!(parse_run_after("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_run_after("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_run_after("24h"), Ok(Duration::from_secs(24 * 3600)));
+        assert_eq!(
+            parse_run_after("1d12h30m"),
+            Ok(Duration::from_secs(36 * 3600 + 30 * 60))
+        );
+        for invalid in [
+            "",
+            "24",
+            "h",
+            "1.5h",
+            "-1h",
+            "1 h",
+            "2w",
+            "99999999999999999999d",
+        ] {
+            assert!(parse_run_after(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_task_status() {
+        for status in [
+            TaskStatus::Pending,
+            TaskStatus::Running,
+            TaskStatus::Completed,
+            TaskStatus::Failed,
+        ] {
+            assert_eq!(TaskStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(TaskStatus::parse("done"), None);
+    }
+}
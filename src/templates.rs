@@ -0,0 +1,150 @@
+//! Handlebars templates stored as script assets.
+//!
+//! `templates.render(name, data)` renders the calling script's asset `name`
+//! as a Handlebars template. `{{value}}` output is HTML-escaped and
+//! `{{{value}}}` inserts the value as is.
+//!
+//! Compiled templates are cached by script, asset and content hash, so
+//! repeated renders skip parsing and an edited asset is recompiled on its
+//! next render, whichever node it was edited on.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use handlebars::Handlebars;
+use tracing::debug;
+
+use crate::repository;
+
+/// Most compiled templates kept; the cache is emptied when it fills up
+const MAX_CACHED_TEMPLATES: usize = 500;
+
+/// Name a template is registered under in its own registry
+const TEMPLATE_NAME: &str = "template";
+
+struct CachedTemplate {
+    content_hash: String,
+    registry: Arc<Handlebars<'static>>,
+}
+
+/// (script URI, asset name) -> compiled template
+type TemplateCache = HashMap<(String, String), CachedTemplate>;
+
+static TEMPLATE_CACHE: OnceLock<Mutex<TemplateCache>> = OnceLock::new();
+
+fn cache() -> &'static Mutex<TemplateCache> {
+    TEMPLATE_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Render the asset `name` of the script at `script_uri` with `data`
+pub fn render(script_uri: &str, name: &str, data: &serde_json::Value) -> Result<String, String> {
+    let asset = repository::fetch_asset(script_uri, name)
+        .ok_or_else(|| format!("Template '{}' not found", name))?;
+    render_source(script_uri, name, &asset.content, data)
+}
+
+fn render_source(
+    script_uri: &str,
+    name: &str,
+    content: &[u8],
+    data: &serde_json::Value,
+) -> Result<String, String> {
+    compiled(script_uri, name, content)?
+        .render(TEMPLATE_NAME, data)
+        .map_err(|e| format!("Template '{}' rendering error: {}", name, e))
+}
+
+/// The compiled template for `content`, from the cache when it is unchanged
+fn compiled(
+    script_uri: &str,
+    name: &str,
+    content: &[u8],
+) -> Result<Arc<Handlebars<'static>>, String> {
+    let content_hash = crate::asset_registry::content_hash(content);
+    let key = (script_uri.to_string(), name.to_string());
+
+    if let Ok(guard) = cache().lock()
+        && let Some(entry) = guard.get(&key)
+        && entry.content_hash == content_hash
+    {
+        return Ok(Arc::clone(&entry.registry));
+    }
+
+    debug!(script_uri, template = name, "Compiling template");
+    let source = std::str::from_utf8(content)
+        .map_err(|_| format!("Template '{}' is not UTF-8 text", name))?;
+    let mut registry = Handlebars::new();
+    registry
+        .register_template_string(TEMPLATE_NAME, source)
+        .map_err(|e| format!("Template '{}' compilation error: {}", name, e))?;
+    let registry = Arc::new(registry);
+
+    if let Ok(mut guard) = cache().lock() {
+        if guard.len() >= MAX_CACHED_TEMPLATES && !guard.contains_key(&key) {
+            guard.clear();
+        }
+        guard.insert(
+            key,
+            CachedTemplate {
+                content_hash,
+                registry: Arc::clone(&registry),
+            },
+        );
+    }
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_escapes_values() {
+        let html = render_source(
+            "templates-test",
+            "page.hbs",
+            b"<h1>{{title}}</h1>{{{banner}}}{{#each items}}<li>{{this}}</li>{{/each}}",
+            &json!({
+                "title": "<script>x</script>",
+                "items": ["a & b"],
+                "banner": "<hr>"
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<h1>&lt;script&gt;x&lt;/script&gt;</h1><hr><li>a &amp; b</li>"
+        );
+    }
+
+    #[test]
+    fn test_compiled_templates_are_cached_by_content() {
+        let first = compiled("templates-test", "cached.hbs", b"Hi {{name}}").unwrap();
+        let again = compiled("templates-test", "cached.hbs", b"Hi {{name}}").unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        let edited = render_source(
+            "templates-test",
+            "cached.hbs",
+            b"Bye {{name}}",
+            &json!({"name": "Ada"}),
+        )
+        .unwrap();
+        assert_eq!(edited, "Bye Ada");
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let error = render_source("templates-test", "bad.hbs", b"{{#if}}", &json!({})).unwrap_err();
+        assert!(
+            error.starts_with("Template 'bad.hbs' compilation error"),
+            "{}",
+            error
+        );
+
+        let error =
+            render_source("templates-test", "bin.hbs", &[0xff, 0xfe], &json!({})).unwrap_err();
+        assert_eq!(error, "Template 'bin.hbs' is not UTF-8 text");
+    }
+}
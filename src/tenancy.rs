@@ -0,0 +1,384 @@
+//! Row-level tenancy for script data.
+//!
+//! When `tenancy.enabled` is set, each HTTP request handled by a script is
+//! attributed to a tenant derived from the request (its Host header or a
+//! claim of the signed-in user). While the handler runs, the tenant is the
+//! current thread's tenant: `sharedStorage` keys are stored under a
+//! per-tenant prefix and `db.query` runs with `app.tenant_id` set, which the
+//! row-level security policies added to every script table compare against.
+//! Code running without a tenant (init, scheduled jobs, requests no tenant
+//! could be derived for) sees only rows written without a tenant.
+//!
+//! Per-tenant limits on requests, execution time, stream connections and
+//! storage are configured under `tenancy.quotas`; see [`crate::tenant_quotas`].
+//!
+//! `tenancy.overrides` replaces the handler timeout, CORS origins, sign-in
+//! requirement and branding for single tenants or hosts. Overrides are looked
+//! up per request, by tenant ID first and then by host name.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{TenancyConfig, TenantOverrideConfig, TenantQuotaConfig};
+
+/// Postgres setting holding the tenant of the current db.query transaction
+pub const TENANT_SETTING: &str = "app.tenant_id";
+
+/// Longest tenant ID accepted (the longest DNS name)
+pub const MAX_TENANT_ID_LENGTH: usize = 253;
+
+/// Where the tenant of a request comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TenantSource {
+    /// Host header without the port, e.g. `acme.example.com`
+    #[default]
+    Host,
+    /// Domain of the signed-in user's email address
+    EmailDomain,
+    /// The signed-in user's ID
+    UserId,
+}
+
+static TENANCY: OnceLock<RwLock<TenancyConfig>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<TenancyConfig> {
+    TENANCY.get_or_init(Default::default)
+}
+
+/// Apply the tenancy configuration. Called once at server startup.
+pub fn configure(config: &TenancyConfig) {
+    let mut config = config.clone();
+    // Override keys are matched against normalized tenant IDs and host names
+    config.overrides = config
+        .overrides
+        .into_iter()
+        .map(|(key, overrides)| (key.trim().to_ascii_lowercase(), overrides))
+        .collect();
+    match settings().write() {
+        Ok(mut guard) => *guard = config,
+        Err(poisoned) => *poisoned.into_inner() = config,
+    }
+}
+
+/// Whether script data is scoped by tenant
+pub fn is_enabled() -> bool {
+    match settings().read() {
+        Ok(guard) => guard.enabled,
+        Err(poisoned) => poisoned.into_inner().enabled,
+    }
+}
+
+fn source() -> TenantSource {
+    match settings().read() {
+        Ok(guard) => guard.source,
+        Err(poisoned) => poisoned.into_inner().source,
+    }
+}
+
+/// Limits applied to each tenant
+pub fn quotas() -> TenantQuotaConfig {
+    match settings().read() {
+        Ok(guard) => guard.quotas.clone(),
+        Err(poisoned) => poisoned.into_inner().quotas.clone(),
+    }
+}
+
+/// Overrides for a request with the given tenant and Host header: the entry
+/// keyed by the tenant ID, else the one keyed by the host name
+pub fn resolve_overrides<'a>(
+    overrides: &'a HashMap<String, TenantOverrideConfig>,
+    tenant: Option<&str>,
+    host: Option<&str>,
+) -> Option<&'a TenantOverrideConfig> {
+    tenant.and_then(|tenant| overrides.get(tenant)).or_else(|| {
+        let host = crate::route_index::normalize_host(host?)?;
+        overrides.get(&host)
+    })
+}
+
+/// Overrides in effect for a request under the current configuration
+pub fn overrides_for(tenant: Option<&str>, host: Option<&str>) -> Option<TenantOverrideConfig> {
+    let lookup =
+        |config: &TenancyConfig| resolve_overrides(&config.overrides, tenant, host).cloned();
+    match settings().read() {
+        Ok(guard) => lookup(&guard),
+        Err(poisoned) => lookup(&poisoned.into_inner()),
+    }
+}
+
+/// The `request.tenant` object passed to handlers, or None when the request
+/// has neither a tenant nor overrides
+pub fn request_tenant_json(
+    tenant: Option<&str>,
+    overrides: Option<&TenantOverrideConfig>,
+) -> Option<serde_json::Value> {
+    if tenant.is_none() && overrides.is_none() {
+        return None;
+    }
+    let branding = overrides.map(|overrides| &overrides.branding);
+    Some(serde_json::json!({
+        "id": tenant,
+        "branding": {
+            "name": branding.and_then(|branding| branding.name.as_deref()),
+            "logoUrl": branding.and_then(|branding| branding.logo_url.as_deref()),
+            "primaryColor": branding.and_then(|branding| branding.primary_color.as_deref()),
+        },
+    }))
+}
+
+/// Whether `origin` may call script routes cross-origin under `allowed`
+pub fn cors_origin_allowed(allowed: &[String], origin: &str) -> bool {
+    allowed
+        .iter()
+        .any(|entry| entry == "*" || entry.eq_ignore_ascii_case(origin))
+}
+
+/// Normalize a tenant ID, or None when it is empty, too long or contains
+/// characters other than letters, digits and `.-_@:`. Tenant IDs are
+/// compared case-insensitively.
+pub fn normalize_tenant_id(raw: &str) -> Option<String> {
+    let tenant = raw.trim().to_ascii_lowercase();
+    let valid = !tenant.is_empty()
+        && tenant.len() <= MAX_TENANT_ID_LENGTH
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '@' | ':'));
+    valid.then_some(tenant)
+}
+
+/// Derive the tenant of a request with the given configuration. `host` is the
+/// Host header; `user_id` and `email` describe the signed-in user, if any.
+pub fn resolve_tenant(
+    source: TenantSource,
+    host: Option<&str>,
+    user_id: Option<&str>,
+    email: Option<&str>,
+) -> Option<String> {
+    match source {
+        TenantSource::Host => {
+            let host = host?;
+            // Strip the port; bracketed IPv6 literals are rejected below
+            let name = match host.rfind(':') {
+                Some(index) if !host[index..].contains(']') => &host[..index],
+                _ => host,
+            };
+            normalize_tenant_id(name)
+        }
+        TenantSource::EmailDomain => email
+            .and_then(|email| email.rsplit_once('@'))
+            .and_then(|(_, domain)| normalize_tenant_id(domain)),
+        TenantSource::UserId => user_id.and_then(normalize_tenant_id),
+    }
+}
+
+/// Tenant of a request under the current configuration, or None when
+/// tenancy is disabled or no tenant can be derived
+pub fn tenant_for_request(
+    host: Option<&str>,
+    user_id: Option<&str>,
+    email: Option<&str>,
+) -> Option<String> {
+    if !is_enabled() {
+        return None;
+    }
+    resolve_tenant(source(), host, user_id, email)
+}
+
+thread_local! {
+    static CURRENT_TENANT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous tenant when dropped
+#[derive(Debug)]
+pub struct TenantGuard {
+    previous: Option<String>,
+}
+
+impl Drop for TenantGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_TENANT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Make `tenant` the tenant of the current thread until the guard is dropped
+pub fn enter_tenant(tenant: Option<String>) -> TenantGuard {
+    CURRENT_TENANT.with(|current| TenantGuard {
+        previous: current.replace(tenant),
+    })
+}
+
+/// Tenant of the handler running on the current thread, if any
+pub fn current_tenant() -> Option<String> {
+    CURRENT_TENANT.with(|current| current.borrow().clone())
+}
+
+/// Prefix under which the current tenant's sharedStorage keys are stored, or
+/// None when tenancy is off. Code without a tenant gets the prefix of the
+/// empty tenant ID, so it cannot address keys of any real tenant either.
+pub fn storage_key_prefix() -> Option<String> {
+    if !is_enabled() {
+        return None;
+    }
+    Some(tenant_storage_prefix(&current_tenant_setting()))
+}
+
+/// Prefix of the sharedStorage keys of `tenant`
+pub fn tenant_storage_prefix(tenant: &str) -> String {
+    format!("tenant/{}/", tenant)
+}
+
+/// Tenant a stored sharedStorage key belongs to, or None for keys stored
+/// without tenancy or without a tenant
+pub fn tenant_of_storage_key(key: &str) -> Option<&str> {
+    let (tenant, _) = key.strip_prefix("tenant/")?.split_once('/')?;
+    (!tenant.is_empty()).then_some(tenant)
+}
+
+/// The sharedStorage key actually stored for `key`. Tenant IDs cannot
+/// contain `/`, so prefixes of different tenants never overlap.
+pub fn scoped_storage_key(key: &str) -> String {
+    match storage_key_prefix() {
+        Some(prefix) => format!("{}{}", prefix, key),
+        None => key.to_string(),
+    }
+}
+
+/// Value for `app.tenant_id` in db.query transactions; the empty string
+/// stands for "no tenant"
+pub fn current_tenant_setting() -> String {
+    current_tenant().unwrap_or_default()
+}
+
+/// Tenant db.query and db.migrate transactions are scoped to, or None when
+/// tenancy is off
+pub fn transaction_tenant() -> Option<String> {
+    is_enabled().then(current_tenant_setting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tenant_id() {
+        assert_eq!(
+            normalize_tenant_id(" Acme.Example.com "),
+            Some("acme.example.com".to_string())
+        );
+        assert_eq!(normalize_tenant_id("user:42"), Some("user:42".to_string()));
+        assert_eq!(normalize_tenant_id(""), None);
+        assert_eq!(normalize_tenant_id("a/b"), None);
+        assert_eq!(normalize_tenant_id("a b"), None);
+        assert_eq!(normalize_tenant_id(&"a".repeat(254)), None);
+    }
+
+    #[test]
+    fn test_resolve_tenant() {
+        assert_eq!(
+            resolve_tenant(
+                TenantSource::Host,
+                Some("Shop.Example.com:8443"),
+                None,
+                None
+            ),
+            Some("shop.example.com".to_string())
+        );
+        assert_eq!(
+            resolve_tenant(TenantSource::Host, Some("[::1]:8080"), None, None),
+            None
+        );
+        assert_eq!(
+            resolve_tenant(TenantSource::Host, None, Some("u1"), None),
+            None
+        );
+        assert_eq!(
+            resolve_tenant(
+                TenantSource::EmailDomain,
+                Some("shop.example.com"),
+                Some("u1"),
+                Some("ann@Customer.org")
+            ),
+            Some("customer.org".to_string())
+        );
+        assert_eq!(
+            resolve_tenant(TenantSource::EmailDomain, None, Some("u1"), None),
+            None
+        );
+        assert_eq!(
+            resolve_tenant(TenantSource::UserId, None, Some("u1"), None),
+            Some("u1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tenant_guard_restores_previous() {
+        assert_eq!(current_tenant(), None);
+        {
+            let _outer = enter_tenant(Some("a".to_string()));
+            {
+                let _inner = enter_tenant(Some("b".to_string()));
+                assert_eq!(current_tenant().as_deref(), Some("b"));
+            }
+            assert_eq!(current_tenant().as_deref(), Some("a"));
+            assert_eq!(current_tenant_setting(), "a");
+        }
+        assert_eq!(current_tenant(), None);
+        assert_eq!(current_tenant_setting(), "");
+    }
+
+    #[test]
+    fn test_resolve_overrides() {
+        let by_tenant = TenantOverrideConfig {
+            execution_timeout_ms: Some(500),
+            ..Default::default()
+        };
+        let by_host = TenantOverrideConfig {
+            require_authentication: true,
+            ..Default::default()
+        };
+        let overrides = HashMap::from([
+            ("customer.org".to_string(), by_tenant.clone()),
+            ("shop.example.com".to_string(), by_host.clone()),
+        ]);
+
+        assert_eq!(
+            resolve_overrides(&overrides, Some("customer.org"), Some("shop.example.com")),
+            Some(&by_tenant)
+        );
+        assert_eq!(
+            resolve_overrides(&overrides, Some("other.org"), Some("Shop.Example.com:443")),
+            Some(&by_host)
+        );
+        assert_eq!(
+            resolve_overrides(&overrides, None, Some("example.com")),
+            None
+        );
+        assert_eq!(resolve_overrides(&overrides, None, None), None);
+    }
+
+    #[test]
+    fn test_cors_origin_allowed() {
+        let allowed = vec!["https://app.example.com".to_string()];
+        assert!(cors_origin_allowed(&allowed, "https://APP.example.com"));
+        assert!(!cors_origin_allowed(&allowed, "https://evil.example.com"));
+        assert!(cors_origin_allowed(
+            &["*".to_string()],
+            "https://any.example"
+        ));
+        assert!(!cors_origin_allowed(&[], "https://app.example.com"));
+    }
+
+    #[test]
+    fn test_tenant_of_storage_key() {
+        let key = format!("{}counter", tenant_storage_prefix("acme.example.com"));
+        assert_eq!(tenant_of_storage_key(&key), Some("acme.example.com"));
+        assert_eq!(tenant_of_storage_key("tenant//counter"), None);
+        assert_eq!(tenant_of_storage_key("counter"), None);
+        assert_eq!(tenant_of_storage_key("tenant/acme"), None);
+    }
+}
@@ -0,0 +1,421 @@
+//! Per-tenant usage tracking and quotas.
+//!
+//! With tenancy enabled, every script handler request, the time its handler
+//! runs and every open stream connection are counted against the request's
+//! tenant. Requests and execution time are limited per one-minute window;
+//! when a tenant is over either limit, or at its stream connection limit,
+//! further requests are answered with HTTP 429 until the window rolls over
+//! or a connection closes. Storage is limited when sharedStorage items are
+//! written (see `repository::set_script_properties_item_with_ttl`).
+//!
+//! Counters live in memory and are per server process.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::TenantQuotaConfig;
+
+/// Length of the request and execution time windows
+pub const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+
+/// Tenants tracked at once; idle tenants are dropped first when full
+pub const MAX_TRACKED_TENANTS: usize = 10_000;
+
+/// A request rejected because its tenant is over a limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub tenant: String,
+    /// Which limit: "requests", "execution_time" or "stream_connections"
+    pub resource: &'static str,
+    pub limit: u64,
+    /// Seconds until the limit may allow the request again
+    pub retry_after_secs: u64,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.resource {
+            "requests" => "requests per minute",
+            "execution_time" => "ms of execution per minute",
+            _ => "concurrent connections",
+        };
+        write!(
+            f,
+            "Tenant '{}' is over its {} quota of {} {}",
+            self.tenant, self.resource, self.limit, unit
+        )
+    }
+}
+
+#[derive(Debug)]
+struct TenantCounters {
+    window_start: Instant,
+    window_requests: u64,
+    window_execution_ms: u64,
+    total_requests: u64,
+    total_execution_ms: u64,
+    throttled_requests: u64,
+    stream_connections: u64,
+}
+
+impl TenantCounters {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            window_requests: 0,
+            window_execution_ms: 0,
+            total_requests: 0,
+            total_execution_ms: 0,
+            throttled_requests: 0,
+            stream_connections: 0,
+        }
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        if now.duration_since(self.window_start) >= QUOTA_WINDOW {
+            self.window_start = now;
+            self.window_requests = 0;
+            self.window_execution_ms = 0;
+        }
+    }
+
+    fn retry_after_secs(&self, now: Instant) -> u64 {
+        let remaining = QUOTA_WINDOW.saturating_sub(now.duration_since(self.window_start));
+        remaining.as_secs().max(1)
+    }
+}
+
+/// Usage counters and quotas of tenants
+#[derive(Debug, Default)]
+pub struct TenantQuotas {
+    tenants: Mutex<HashMap<String, TenantCounters>>,
+}
+
+impl TenantQuotas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_counters<R>(
+        &self,
+        tenant: &str,
+        now: Instant,
+        f: impl FnOnce(&mut TenantCounters) -> R,
+    ) -> R {
+        let mut tenants = match self.tenants.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if !tenants.contains_key(tenant) && tenants.len() >= MAX_TRACKED_TENANTS {
+            evict_idle_tenant(&mut tenants);
+        }
+        let counters = tenants
+            .entry(tenant.to_string())
+            .or_insert_with(|| TenantCounters::new(now));
+        counters.roll_window(now);
+        f(counters)
+    }
+
+    /// Count a handler request of `tenant`, or reject it when the tenant is
+    /// over its request or execution time limit for the current window
+    pub fn check_request(
+        &self,
+        tenant: &str,
+        limits: &TenantQuotaConfig,
+        now: Instant,
+    ) -> Result<(), QuotaExceeded> {
+        self.with_counters(tenant, now, |counters| {
+            let exceeded = if limits.requests_per_minute > 0
+                && counters.window_requests >= limits.requests_per_minute
+            {
+                Some(("requests", limits.requests_per_minute))
+            } else if limits.execution_ms_per_minute > 0
+                && counters.window_execution_ms >= limits.execution_ms_per_minute
+            {
+                Some(("execution_time", limits.execution_ms_per_minute))
+            } else {
+                None
+            };
+            if let Some((resource, limit)) = exceeded {
+                counters.throttled_requests += 1;
+                return Err(QuotaExceeded {
+                    tenant: tenant.to_string(),
+                    resource,
+                    limit,
+                    retry_after_secs: counters.retry_after_secs(now),
+                });
+            }
+            counters.window_requests += 1;
+            counters.total_requests += 1;
+            Ok(())
+        })
+    }
+
+    /// Add the execution time of a handler that ran for `tenant`
+    pub fn record_execution(&self, tenant: &str, elapsed: Duration, now: Instant) {
+        let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        self.with_counters(tenant, now, |counters| {
+            counters.window_execution_ms = counters.window_execution_ms.saturating_add(elapsed_ms);
+            counters.total_execution_ms = counters.total_execution_ms.saturating_add(elapsed_ms);
+        });
+    }
+
+    /// Count an opened stream connection of `tenant`, or reject it when the
+    /// tenant already has its maximum number open
+    pub fn open_stream(
+        &self,
+        tenant: &str,
+        limits: &TenantQuotaConfig,
+        now: Instant,
+    ) -> Result<(), QuotaExceeded> {
+        self.with_counters(tenant, now, |counters| {
+            if limits.max_stream_connections > 0
+                && counters.stream_connections >= limits.max_stream_connections
+            {
+                counters.throttled_requests += 1;
+                return Err(QuotaExceeded {
+                    tenant: tenant.to_string(),
+                    resource: "stream_connections",
+                    limit: limits.max_stream_connections,
+                    retry_after_secs: QUOTA_WINDOW.as_secs(),
+                });
+            }
+            counters.stream_connections += 1;
+            Ok(())
+        })
+    }
+
+    /// Count a closed stream connection of `tenant`
+    pub fn close_stream(&self, tenant: &str, now: Instant) {
+        self.with_counters(tenant, now, |counters| {
+            counters.stream_connections = counters.stream_connections.saturating_sub(1);
+        });
+    }
+
+    /// Usage of every tracked tenant, or of `tenant` only, sorted by tenant
+    pub fn usage(&self, tenant: Option<&str>, now: Instant) -> Vec<TenantUsage> {
+        let mut tenants = match self.tenants.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut usage: Vec<TenantUsage> = tenants
+            .iter_mut()
+            .filter(|(id, _)| tenant.is_none_or(|tenant| tenant == id.as_str()))
+            .map(|(id, counters)| {
+                counters.roll_window(now);
+                TenantUsage {
+                    tenant: id.clone(),
+                    requests_this_minute: counters.window_requests,
+                    execution_ms_this_minute: counters.window_execution_ms,
+                    total_requests: counters.total_requests,
+                    total_execution_ms: counters.total_execution_ms,
+                    throttled_requests: counters.throttled_requests,
+                    stream_connections: counters.stream_connections,
+                    storage_bytes: 0,
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        usage
+    }
+}
+
+/// Drop the tenant that has been idle longest and has no open streams
+fn evict_idle_tenant(tenants: &mut HashMap<String, TenantCounters>) {
+    let oldest = tenants
+        .iter()
+        .filter(|(_, counters)| counters.stream_connections == 0)
+        .min_by_key(|(_, counters)| counters.window_start)
+        .map(|(tenant, _)| tenant.clone());
+    if let Some(tenant) = oldest {
+        tenants.remove(&tenant);
+    }
+}
+
+/// Usage of one tenant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantUsage {
+    pub tenant: String,
+    /// Handler requests in the current one-minute window
+    pub requests_this_minute: u64,
+    /// Handler execution time in the current one-minute window
+    pub execution_ms_this_minute: u64,
+    pub total_requests: u64,
+    pub total_execution_ms: u64,
+    /// Requests and stream connections rejected with 429
+    pub throttled_requests: u64,
+    pub stream_connections: u64,
+    /// Live sharedStorage bytes of the tenant across all scripts
+    pub storage_bytes: u64,
+}
+
+/// Configured per-tenant limits as reported by [`usage_report`]; 0 means
+/// unlimited
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantQuotaLimits {
+    pub requests_per_minute: u64,
+    pub execution_ms_per_minute: u64,
+    pub max_stream_connections: u64,
+    pub storage_bytes: u64,
+}
+
+impl From<TenantQuotaConfig> for TenantQuotaLimits {
+    fn from(config: TenantQuotaConfig) -> Self {
+        Self {
+            requests_per_minute: config.requests_per_minute,
+            execution_ms_per_minute: config.execution_ms_per_minute,
+            max_stream_connections: config.max_stream_connections,
+            storage_bytes: config.storage_bytes,
+        }
+    }
+}
+
+/// Result of [`usage_report`]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantUsageReport {
+    pub enabled: bool,
+    pub quotas: TenantQuotaLimits,
+    pub tenants: Vec<TenantUsage>,
+}
+
+static QUOTAS: OnceLock<TenantQuotas> = OnceLock::new();
+
+fn quotas() -> &'static TenantQuotas {
+    QUOTAS.get_or_init(TenantQuotas::new)
+}
+
+/// Count a handler request of `tenant` against its quotas
+pub fn check_request(tenant: &str) -> Result<(), QuotaExceeded> {
+    quotas().check_request(tenant, &crate::tenancy::quotas(), Instant::now())
+}
+
+/// Add the execution time of a handler that ran for `tenant`
+pub fn record_execution(tenant: &str, elapsed: Duration) {
+    quotas().record_execution(tenant, elapsed, Instant::now());
+}
+
+/// An open stream connection counted against a tenant; closes it when dropped
+#[derive(Debug)]
+pub struct StreamSlot {
+    tenant: String,
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        quotas().close_stream(&self.tenant, Instant::now());
+    }
+}
+
+/// Count a stream connection of `tenant` for as long as the returned slot
+/// lives
+pub fn open_stream(tenant: &str) -> Result<StreamSlot, QuotaExceeded> {
+    quotas().open_stream(tenant, &crate::tenancy::quotas(), Instant::now())?;
+    Ok(StreamSlot {
+        tenant: tenant.to_string(),
+    })
+}
+
+/// Usage of all tenants, or of `tenant` only, including their storage
+pub fn usage_report(tenant: Option<&str>) -> TenantUsageReport {
+    let mut tenants = quotas().usage(tenant, Instant::now());
+    match crate::repository::list_tenant_storage_bytes() {
+        Ok(mut storage) => {
+            for usage in &mut tenants {
+                usage.storage_bytes = storage.remove(&usage.tenant).unwrap_or(0);
+            }
+            // Tenants that only have stored data since the last restart
+            tenants.extend(
+                storage
+                    .into_iter()
+                    .filter(|(id, _)| tenant.is_none_or(|tenant| tenant == id.as_str()))
+                    .map(|(id, storage_bytes)| TenantUsage {
+                        tenant: id,
+                        requests_this_minute: 0,
+                        execution_ms_this_minute: 0,
+                        total_requests: 0,
+                        total_execution_ms: 0,
+                        throttled_requests: 0,
+                        stream_connections: 0,
+                        storage_bytes,
+                    }),
+            );
+            tenants.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        }
+        Err(e) => warn!("Failed to read tenant storage usage: {}", e),
+    }
+    TenantUsageReport {
+        enabled: crate::tenancy::is_enabled(),
+        quotas: crate::tenancy::quotas().into(),
+        tenants,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> TenantQuotaConfig {
+        TenantQuotaConfig {
+            requests_per_minute: 2,
+            execution_ms_per_minute: 100,
+            max_stream_connections: 1,
+            storage_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_request_quota_per_window() {
+        let quotas = TenantQuotas::new();
+        let start = Instant::now();
+        assert!(quotas.check_request("a", &limits(), start).is_ok());
+        assert!(quotas.check_request("a", &limits(), start).is_ok());
+        let err = quotas.check_request("a", &limits(), start).unwrap_err();
+        assert_eq!(err.resource, "requests");
+        assert_eq!(err.retry_after_secs, 60);
+        // Other tenants are unaffected
+        assert!(quotas.check_request("b", &limits(), start).is_ok());
+        // The next window starts over
+        let later = start + QUOTA_WINDOW;
+        assert!(quotas.check_request("a", &limits(), later).is_ok());
+
+        let usage = quotas.usage(Some("a"), later);
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].requests_this_minute, 1);
+        assert_eq!(usage[0].total_requests, 3);
+        assert_eq!(usage[0].throttled_requests, 1);
+    }
+
+    #[test]
+    fn test_execution_time_quota() {
+        let quotas = TenantQuotas::new();
+        let start = Instant::now();
+        assert!(quotas.check_request("a", &limits(), start).is_ok());
+        quotas.record_execution("a", Duration::from_millis(150), start);
+        let err = quotas.check_request("a", &limits(), start).unwrap_err();
+        assert_eq!(err.resource, "execution_time");
+        assert!(
+            quotas
+                .check_request("a", &TenantQuotaConfig::default(), start)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_stream_connection_quota() {
+        let quotas = TenantQuotas::new();
+        let now = Instant::now();
+        assert!(quotas.open_stream("a", &limits(), now).is_ok());
+        let err = quotas.open_stream("a", &limits(), now).unwrap_err();
+        assert_eq!(err.resource, "stream_connections");
+        quotas.close_stream("a", now);
+        assert!(quotas.open_stream("a", &limits(), now).is_ok());
+        assert_eq!(quotas.usage(None, now)[0].stream_connections, 1);
+    }
+}
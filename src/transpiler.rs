@@ -14,12 +14,22 @@ use tracing::{debug, error};
 /// Cached transpilation result
 #[derive(Clone, Debug)]
 struct CachedTranspilation {
-    /// Transpiled JavaScript code with inline source map
+    /// Transpiled JavaScript code
     code: String,
+    /// Source map (JSON) from the transpiled code back to the original source
+    source_map: Option<String>,
     /// SHA256 hash of the original source content
     content_hash: String,
 }
 
+/// JavaScript produced from a script
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transpiled {
+    pub code: String,
+    /// Source map (JSON) for transpiled scripts
+    pub source_map: Option<String>,
+}
+
 /// Global in-memory cache for transpiled scripts
 static TRANSPILED_CACHE: OnceLock<Mutex<HashMap<String, CachedTranspilation>>> = OnceLock::new();
 
@@ -29,7 +39,7 @@ fn needs_transpilation(uri: &str) -> bool {
 }
 
 /// Determine the syntax configuration based on file extension
-fn get_source_type(uri: &str) -> SourceType {
+pub(crate) fn get_source_type(uri: &str) -> SourceType {
     if uri.ends_with(".ts") {
         SourceType::default().with_typescript(true)
     } else if uri.ends_with(".tsx") {
@@ -50,14 +60,17 @@ fn calculate_content_hash(content: &str) -> String {
 }
 
 /// Get transpiled script from cache
-fn get_cached_transpilation(uri: &str, content_hash: &str) -> Option<String> {
+fn get_cached_transpilation(uri: &str, content_hash: &str) -> Option<Transpiled> {
     let cache = TRANSPILED_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
     if let Ok(guard) = cache.lock()
         && let Some(cached) = guard.get(uri)
     {
         if cached.content_hash == content_hash {
             debug!(uri = uri, "Transpilation cache hit");
-            return Some(cached.code.clone());
+            return Some(Transpiled {
+                code: cached.code.clone(),
+                source_map: cached.source_map.clone(),
+            });
         } else {
             debug!(uri = uri, "Transpilation cache miss (content changed)");
         }
@@ -66,10 +79,17 @@ fn get_cached_transpilation(uri: &str, content_hash: &str) -> Option<String> {
 }
 
 /// Store transpiled script in cache
-fn cache_transpilation(uri: &str, code: String, content_hash: String) {
+fn cache_transpilation(uri: &str, transpiled: &Transpiled, content_hash: String) {
     let cache = TRANSPILED_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
     if let Ok(mut guard) = cache.lock() {
-        guard.insert(uri.to_string(), CachedTranspilation { code, content_hash });
+        guard.insert(
+            uri.to_string(),
+            CachedTranspilation {
+                code: transpiled.code.clone(),
+                source_map: transpiled.source_map.clone(),
+                content_hash,
+            },
+        );
     }
 }
 
@@ -84,7 +104,7 @@ pub fn invalidate_transpilation_cache(uri: &str) {
 }
 
 /// Transpile TypeScript/JSX/TSX to JavaScript with source maps
-fn transpile(uri: &str, content: &str) -> AppResult<String> {
+fn transpile(uri: &str, content: &str) -> AppResult<Transpiled> {
     let start = std::time::Instant::now();
 
     // Create allocator for oxc
@@ -138,16 +158,14 @@ fn transpile(uri: &str, content: &str) -> AppResult<String> {
     Transformer::new(&allocator, std::path::Path::new(uri), &transform_options)
         .build_with_scoping(scoping, &mut program);
 
-    // Generate JavaScript code
+    // Generate JavaScript code and a source map back to the original source
     let printed = Codegen::new()
-        .with_options(CodegenOptions::default())
+        .with_options(CodegenOptions {
+            source_map_path: Some(std::path::PathBuf::from(uri)),
+            ..CodegenOptions::default()
+        })
         .build(&program);
 
-    let js_code = printed.code;
-
-    // For now, we don't include source maps (oxc API has changed)
-    // TODO: Add source map support when stable API is available
-
     let elapsed = start.elapsed();
     debug!(
         uri = uri,
@@ -155,33 +173,44 @@ fn transpile(uri: &str, content: &str) -> AppResult<String> {
         "Transpiled script"
     );
 
-    Ok(js_code)
+    Ok(Transpiled {
+        code: printed.code,
+        source_map: printed.map.map(|map| map.to_json_string()),
+    })
 }
 
 /// Transpile script if needed based on file extension
 ///
 /// - `.ts`, `.tsx`, `.jsx` files are transpiled to JavaScript
 /// - `.js` files are returned as-is
-/// - Returns transpiled code with inline source map
 pub fn transpile_if_needed(uri: &str, content: &str) -> AppResult<String> {
+    transpile_with_source_map(uri, content).map(|transpiled| transpiled.code)
+}
+
+/// Like [`transpile_if_needed`], also returning the source map of the
+/// transpiled code. JavaScript files are returned as-is without a map.
+pub fn transpile_with_source_map(uri: &str, content: &str) -> AppResult<Transpiled> {
     // Pass through JavaScript files without transpilation
     if !needs_transpilation(uri) {
-        return Ok(content.to_string());
+        return Ok(Transpiled {
+            code: content.to_string(),
+            source_map: None,
+        });
     }
 
     // Check cache
     let content_hash = calculate_content_hash(content);
-    if let Some(cached_code) = get_cached_transpilation(uri, &content_hash) {
-        return Ok(cached_code);
+    if let Some(cached) = get_cached_transpilation(uri, &content_hash) {
+        return Ok(cached);
     }
 
     // Transpile
-    let transpiled_code = transpile(uri, content)?;
+    let transpiled = transpile(uri, content)?;
 
     // Cache the result
-    cache_transpilation(uri, transpiled_code.clone(), content_hash);
+    cache_transpilation(uri, &transpiled, content_hash);
 
-    Ok(transpiled_code)
+    Ok(transpiled)
 }
 
 #[cfg(test)]
@@ -0,0 +1,822 @@
+//! Resumable uploads with the tus 1.0 protocol (<https://tus.io/protocols/resumable-upload>).
+//!
+//! Scripts enable an upload endpoint with `routeRegistry.registerUploadRoute`.
+//! Clients create an upload with a POST to the endpoint, send its bytes with
+//! PATCH requests to the returned upload URL and, after a dropped connection,
+//! resume from the offset a HEAD request reports. A completed upload is
+//! stored as an asset of the script, passed to a handler as
+//! `context.request.files[0]`, or both.
+//!
+//! Upload data is kept in the `tus` directory under the upload spool
+//! directory, next to a JSON file describing the upload, so an interrupted
+//! upload can be resumed after a server restart until it expires.
+//!
+//! Supported extensions: creation, termination and expiration.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, error, info, warn};
+
+use crate::parsers::{UploadLimits, UploadedFile};
+
+/// Protocol version implemented
+pub const TUS_VERSION: &str = "1.0.0";
+
+/// Protocol extensions implemented
+const TUS_EXTENSIONS: &str = "creation,termination,expiration";
+
+/// How long an upload may take to complete
+const UPLOAD_TTL: chrono::Duration = chrono::Duration::hours(24);
+
+/// Headers browsers may read from cross-origin tus responses
+const EXPOSED_HEADERS: &str = "Location, Tus-Resumable, Tus-Version, Tus-Extension, Tus-Max-Size, \
+                               Upload-Offset, Upload-Length, Upload-Metadata, Upload-Expires";
+
+/// Content type of PATCH request bodies
+const PATCH_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+/// An upload endpoint registered by a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadRoute {
+    pub script_uri: String,
+    /// Handler called with the completed upload
+    pub handler_name: Option<String>,
+    /// Completed uploads are stored as assets named with this prefix and
+    /// the upload's file name
+    pub asset_prefix: Option<String>,
+    /// Largest accepted upload; defaults to the server's upload request limit
+    pub max_size: Option<u64>,
+}
+
+/// Options of `routeRegistry.registerUploadRoute`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UploadRouteOptions {
+    /// Handler called with the completed upload
+    pub on_complete: Option<String>,
+    /// Asset name prefix completed uploads are stored under
+    pub asset: Option<String>,
+    pub max_size: Option<u64>,
+    pub host: Option<String>,
+}
+
+/// Where uploads are kept and how large they may be
+#[derive(Debug)]
+struct Settings {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(|| {
+        let limits = UploadLimits::from_config(&crate::config::RepositoryConfig::default());
+        settings_for(&limits)
+    })
+}
+
+fn settings_for(limits: &UploadLimits) -> Settings {
+    Settings {
+        dir: limits.spool_dir.join("tus"),
+        max_size: limits.max_request_bytes as u64,
+    }
+}
+
+/// Keep uploads next to the other spooled uploads and remove the ones that
+/// expired while the server was down. Only the first call has an effect.
+pub fn configure(limits: &UploadLimits) {
+    if SETTINGS.set(settings_for(limits)).is_err() {
+        return;
+    }
+    remove_expired_uploads();
+}
+
+/// Upload endpoints by registry key (see [`crate::route_index::host_scoped_path`])
+fn routes() -> &'static RwLock<HashMap<String, UploadRoute>> {
+    static ROUTES: OnceLock<RwLock<HashMap<String, UploadRoute>>> = OnceLock::new();
+    ROUTES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Register an upload endpoint, replacing an earlier registration of the key
+pub fn register_route(key: String, route: UploadRoute) {
+    debug!("Registered upload route {} for {}", key, route.script_uri);
+    routes()
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(key, route);
+}
+
+/// A request addressed to an upload endpoint
+#[derive(Debug, Clone)]
+pub struct UploadRequest {
+    /// Registry key of the endpoint
+    key: String,
+    /// Path of the endpoint; uploads live under it
+    base_path: String,
+    /// The upload addressed, for requests to an upload URL
+    upload_id: Option<String>,
+    route: UploadRoute,
+}
+
+/// The upload endpoint a request is addressed to: the endpoint itself or
+/// one of its uploads, `<endpoint>/<id>`
+pub fn match_request(host: Option<&str>, path: &str) -> Option<UploadRequest> {
+    let routes = routes()
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if routes.is_empty() {
+        return None;
+    }
+    let mut candidates = vec![(path, None)];
+    if let Some((base_path, upload_id)) = path.rsplit_once('/')
+        && !base_path.is_empty()
+    {
+        candidates.push((base_path, Some(upload_id)));
+    }
+    for (base_path, upload_id) in candidates {
+        for key in crate::route_index::request_path_keys(host, base_path) {
+            if let Some(route) = routes.get(&key) {
+                return Some(UploadRequest {
+                    key,
+                    base_path: base_path.to_string(),
+                    upload_id: upload_id.map(str::to_string),
+                    route: route.clone(),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Description of an upload, stored next to its data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadInfo {
+    /// Registry key of the endpoint the upload was created on
+    route: String,
+    length: u64,
+    /// Upload-Metadata header of the creation request, as sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<String>,
+    /// User who created the upload; only they may continue it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_id: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+fn data_path(id: &str) -> PathBuf {
+    settings().dir.join(id)
+}
+
+fn info_path(id: &str) -> PathBuf {
+    settings().dir.join(format!("{}.json", id))
+}
+
+/// Upload IDs are simple UUIDs, which also keeps them safe as file names
+fn valid_upload_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+async fn load_info(id: &str) -> Option<UploadInfo> {
+    let bytes = tokio::fs::read(info_path(id)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+async fn remove_upload(id: &str) {
+    for path in [data_path(id), info_path(id)] {
+        if let Err(e) = tokio::fs::remove_file(&path).await
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove upload file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Remove the data of expired uploads
+fn remove_expired_uploads() {
+    let Ok(entries) = std::fs::read_dir(&settings().dir) else {
+        return;
+    };
+    let now = Utc::now();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let expired = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<UploadInfo>(&bytes).ok())
+            .is_some_and(|info| info.expires_at <= now);
+        if expired {
+            let _ = std::fs::remove_file(path.with_extension(""));
+            let _ = std::fs::remove_file(&path);
+            debug!("Removed expired upload {}", path.display());
+        }
+    }
+}
+
+/// Uploads a PATCH request is currently writing to
+fn active_uploads() -> &'static Mutex<HashSet<String>> {
+    static ACTIVE: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    ACTIVE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Exclusive access to an upload, released when dropped
+struct UploadLock {
+    id: String,
+}
+
+impl UploadLock {
+    fn acquire(id: &str) -> Option<Self> {
+        let mut active = active_uploads()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        active
+            .insert(id.to_string())
+            .then(|| Self { id: id.to_string() })
+    }
+}
+
+impl Drop for UploadLock {
+    fn drop(&mut self) {
+        active_uploads()
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// Decode an Upload-Metadata header: comma-separated `key base64value`
+/// pairs, where the value may be left out
+pub fn parse_metadata(header: &str) -> Option<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    for pair in header.split(',') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, value)) => (key, value.trim()),
+            None => (pair, ""),
+        };
+        if key.is_empty() || !key.bytes().all(|b| b.is_ascii_graphic()) {
+            return None;
+        }
+        let value = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .ok()?;
+        metadata.insert(
+            key.to_string(),
+            String::from_utf8_lossy(&value).into_owned(),
+        );
+    }
+    Some(metadata)
+}
+
+/// HTTP date used by Upload-Expires
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Response with the headers every tus response carries
+fn tus_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header("Tus-Resumable", TUS_VERSION)
+        .header("Access-Control-Expose-Headers", EXPOSED_HEADERS)
+        .header("Cache-Control", "no-store")
+}
+
+fn tus_error(status: StatusCode, message: &str) -> Response {
+    tus_response(status)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(message.to_string()))
+        .unwrap_or_else(|_| status.into_response())
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Serve a request addressed to an upload endpoint. `tenant` and
+/// `tenant_info` are the request's tenant, for the completion handler.
+pub async fn handle_request(
+    req: Request<Body>,
+    upload: UploadRequest,
+    tenant: Option<String>,
+    tenant_info: Option<serde_json::Value>,
+    request_id: &str,
+) -> Response {
+    let max_size = upload.route.max_size.unwrap_or(settings().max_size);
+    if req.method() == Method::OPTIONS {
+        return tus_response(StatusCode::NO_CONTENT)
+            .header("Tus-Version", TUS_VERSION)
+            .header("Tus-Extension", TUS_EXTENSIONS)
+            .header("Tus-Max-Size", max_size.to_string())
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::NO_CONTENT.into_response());
+    }
+    if header_str(req.headers(), "Tus-Resumable") != Some(TUS_VERSION) {
+        return tus_response(StatusCode::PRECONDITION_FAILED)
+            .header("Tus-Version", TUS_VERSION)
+            .body(Body::from("Unsupported tus version"))
+            .unwrap_or_else(|_| StatusCode::PRECONDITION_FAILED.into_response());
+    }
+
+    let user_id = req
+        .extensions()
+        .get::<crate::auth::AuthUser>()
+        .map(|user| user.user_id.clone());
+    let Some(upload_id) = upload.upload_id.clone() else {
+        if req.method() != Method::POST {
+            return tus_error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+        }
+        return create_upload(
+            req,
+            upload,
+            max_size,
+            user_id,
+            tenant,
+            tenant_info,
+            request_id,
+        )
+        .await;
+    };
+
+    // Uploads of other endpoints and other users are not found
+    let info = if valid_upload_id(&upload_id) {
+        load_info(&upload_id).await
+    } else {
+        None
+    };
+    let Some(info) = info.filter(|info| {
+        info.route == upload.key && info.expires_at > Utc::now() && info.user_id == user_id
+    }) else {
+        return tus_error(StatusCode::NOT_FOUND, "Upload not found");
+    };
+
+    match *req.method() {
+        Method::HEAD => {
+            let offset = match tokio::fs::metadata(data_path(&upload_id)).await {
+                Ok(metadata) => metadata.len(),
+                Err(_) => return tus_error(StatusCode::NOT_FOUND, "Upload not found"),
+            };
+            let mut response = tus_response(StatusCode::OK)
+                .header("Upload-Offset", offset.to_string())
+                .header("Upload-Length", info.length.to_string())
+                .header("Upload-Expires", http_date(info.expires_at));
+            if let Some(metadata) = &info.metadata {
+                response = response.header("Upload-Metadata", metadata.as_str());
+            }
+            response
+                .body(Body::empty())
+                .unwrap_or_else(|_| StatusCode::OK.into_response())
+        }
+        Method::PATCH => {
+            append_to_upload(
+                req,
+                upload,
+                upload_id,
+                info,
+                tenant,
+                tenant_info,
+                request_id,
+            )
+            .await
+        }
+        Method::DELETE => {
+            let Some(_lock) = UploadLock::acquire(&upload_id) else {
+                return tus_error(StatusCode::LOCKED, "Upload is being written to");
+            };
+            remove_upload(&upload_id).await;
+            info!("[{}] Upload {} terminated", request_id, upload_id);
+            tus_response(StatusCode::NO_CONTENT)
+                .body(Body::empty())
+                .unwrap_or_else(|_| StatusCode::NO_CONTENT.into_response())
+        }
+        _ => tus_error(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+    }
+}
+
+/// POST to the endpoint: create an upload and answer with its URL
+async fn create_upload(
+    req: Request<Body>,
+    upload: UploadRequest,
+    max_size: u64,
+    user_id: Option<String>,
+    tenant: Option<String>,
+    tenant_info: Option<serde_json::Value>,
+    request_id: &str,
+) -> Response {
+    // The body of a creation request is not used
+    let (parts, _) = req.into_parts();
+    let headers = &parts.headers;
+    if headers.contains_key("Upload-Defer-Length") {
+        return tus_error(
+            StatusCode::BAD_REQUEST,
+            "Upload-Defer-Length is not supported",
+        );
+    }
+    let Some(length) = header_str(headers, "Upload-Length").and_then(|value| value.parse().ok())
+    else {
+        return tus_error(StatusCode::BAD_REQUEST, "Invalid Upload-Length");
+    };
+    if length > max_size {
+        return tus_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            &format!("Upload exceeds the maximum size of {} bytes", max_size),
+        );
+    }
+    let metadata = header_str(headers, "Upload-Metadata").map(str::to_string);
+    if metadata
+        .as_deref()
+        .is_some_and(|raw| parse_metadata(raw).is_none())
+    {
+        return tus_error(StatusCode::BAD_REQUEST, "Invalid Upload-Metadata");
+    }
+
+    remove_expired_uploads();
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let info = UploadInfo {
+        route: upload.key.clone(),
+        length,
+        metadata,
+        user_id,
+        expires_at: Utc::now() + UPLOAD_TTL,
+    };
+    let created = async {
+        tokio::fs::create_dir_all(&settings().dir).await?;
+        tokio::fs::File::create_new(data_path(&id)).await?;
+        let json = serde_json::to_vec(&info).map_err(std::io::Error::other)?;
+        tokio::fs::write(info_path(&id), json).await
+    }
+    .await;
+    if let Err(e) = created {
+        error!("[{}] Failed to create upload {}: {}", request_id, id, e);
+        remove_upload(&id).await;
+        return tus_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create upload");
+    }
+    info!(
+        "[{}] Created upload {} of {} bytes on {}",
+        request_id, id, length, upload.key
+    );
+
+    // An empty upload is complete as soon as it exists
+    if length == 0
+        && let Err(response) =
+            complete_upload(&parts, &upload, &id, &info, tenant, tenant_info, request_id).await
+    {
+        return response;
+    }
+
+    tus_response(StatusCode::CREATED)
+        .header("Location", format!("{}/{}", upload.base_path, id))
+        .header("Upload-Offset", "0")
+        .header("Upload-Expires", http_date(info.expires_at))
+        .body(Body::empty())
+        .unwrap_or_else(|_| StatusCode::CREATED.into_response())
+}
+
+/// PATCH to an upload: append the body at the offset the client names
+async fn append_to_upload(
+    req: Request<Body>,
+    upload: UploadRequest,
+    upload_id: String,
+    info: UploadInfo,
+    tenant: Option<String>,
+    tenant_info: Option<serde_json::Value>,
+    request_id: &str,
+) -> Response {
+    let content_type = header_str(req.headers(), "Content-Type");
+    if content_type != Some(PATCH_CONTENT_TYPE) {
+        return tus_error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/offset+octet-stream",
+        );
+    }
+    let Some(client_offset) =
+        header_str(req.headers(), "Upload-Offset").and_then(|value| value.parse::<u64>().ok())
+    else {
+        return tus_error(StatusCode::BAD_REQUEST, "Invalid Upload-Offset");
+    };
+    let Some(_lock) = UploadLock::acquire(&upload_id) else {
+        return tus_error(StatusCode::LOCKED, "Upload is being written to");
+    };
+
+    let path = data_path(&upload_id);
+    let mut file = match tokio::fs::OpenOptions::new().append(true).open(&path).await {
+        Ok(file) => file,
+        Err(_) => return tus_error(StatusCode::NOT_FOUND, "Upload not found"),
+    };
+    let mut offset = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            error!(
+                "[{}] Failed to read upload {}: {}",
+                request_id, upload_id, e
+            );
+            return tus_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read upload");
+        }
+    };
+    if client_offset != offset {
+        return tus_error(StatusCode::CONFLICT, "Upload-Offset does not match");
+    }
+
+    // Bytes received before the connection drops are kept, which is what
+    // lets the client resume
+    let (parts, body) = req.into_parts();
+    let mut stream = body.into_data_stream();
+    let mut outcome = Ok(());
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                debug!("[{}] Upload {} interrupted: {}", request_id, upload_id, e);
+                outcome = Err(tus_error(
+                    StatusCode::BAD_REQUEST,
+                    "Request body interrupted",
+                ));
+                break;
+            }
+        };
+        if chunk.len() as u64 > info.length - offset {
+            outcome = Err(tus_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Request body exceeds the Upload-Length",
+            ));
+            break;
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            error!(
+                "[{}] Failed to write upload {}: {}",
+                request_id, upload_id, e
+            );
+            outcome = Err(tus_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to store upload",
+            ));
+            break;
+        }
+        offset += chunk.len() as u64;
+    }
+    if let Err(e) = file.flush().await {
+        error!(
+            "[{}] Failed to write upload {}: {}",
+            request_id, upload_id, e
+        );
+    }
+    drop(file);
+    if let Err(response) = outcome {
+        return response;
+    }
+    debug!(
+        "[{}] Upload {} at {} of {} bytes",
+        request_id, upload_id, offset, info.length
+    );
+
+    if offset == info.length
+        && let Err(response) = complete_upload(
+            &parts,
+            &upload,
+            &upload_id,
+            &info,
+            tenant,
+            tenant_info,
+            request_id,
+        )
+        .await
+    {
+        return response;
+    }
+    tus_response(StatusCode::NO_CONTENT)
+        .header("Upload-Offset", offset.to_string())
+        .header("Upload-Expires", http_date(info.expires_at))
+        .body(Body::empty())
+        .unwrap_or_else(|_| StatusCode::NO_CONTENT.into_response())
+}
+
+/// Deliver a completed upload to its endpoint's asset prefix and handler,
+/// then remove it. Errors are answered with the returned response.
+async fn complete_upload(
+    req: &axum::http::request::Parts,
+    upload: &UploadRequest,
+    upload_id: &str,
+    info: &UploadInfo,
+    tenant: Option<String>,
+    tenant_info: Option<serde_json::Value>,
+    request_id: &str,
+) -> Result<(), Response> {
+    let metadata = info
+        .metadata
+        .as_deref()
+        .and_then(parse_metadata)
+        .unwrap_or_default();
+    let filename = metadata
+        .get("filename")
+        .or_else(|| metadata.get("name"))
+        .cloned();
+    let content_type = metadata
+        .get("filetype")
+        .or_else(|| metadata.get("type"))
+        .cloned();
+    let _ = tokio::fs::remove_file(info_path(upload_id)).await;
+    let file = match UploadedFile::from_file(
+        "upload".to_string(),
+        filename,
+        content_type,
+        data_path(upload_id),
+    ) {
+        Ok(file) => file,
+        Err(e) => {
+            error!(
+                "[{}] Failed to read upload {}: {}",
+                request_id, upload_id, e
+            );
+            return Err(tus_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read upload",
+            ));
+        }
+    };
+    info!(
+        "[{}] Upload {} complete ({} bytes, sha256 {})",
+        request_id, upload_id, file.size, file.sha256
+    );
+
+    let mut headers = HashMap::new();
+    for (name, value) in &req.headers {
+        if let Ok(value) = value.to_str() {
+            headers.insert(name.as_str().to_string(), value.to_string());
+        }
+    }
+    let auth_user = req.extensions.get::<crate::auth::AuthUser>().cloned();
+    let route = upload.route.clone();
+    let path = format!("{}/{}", upload.base_path, upload_id);
+    let method = req.method.to_string();
+    let upload_id = upload_id.to_string();
+    let request_id_for_worker = request_id.to_string();
+    let worker = move || -> Result<Option<crate::js_engine::JsHttpResponse>, String> {
+        let _log_context = crate::js_engine::enter_log_context(
+            crate::js_engine::LogContext::default().with_request_id(request_id_for_worker),
+        );
+        let _tenant = crate::tenancy::enter_tenant(tenant);
+
+        if let Some(prefix) = &route.asset_prefix {
+            let name = format!("{}{}", prefix, asset_file_name(&file, &upload_id));
+            let content = file
+                .read()
+                .map_err(|e| format!("Failed to read upload: {}", e))?
+                .into_owned();
+            let now = std::time::SystemTime::now();
+            crate::repository::upsert_asset(crate::repository::Asset {
+                uri: name.clone(),
+                name: Some(name.clone()),
+                mimetype: file
+                    .content_type
+                    .clone()
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                content,
+                created_at: now,
+                updated_at: now,
+                script_uri: route.script_uri.clone(),
+                headers: HashMap::new(),
+            })
+            .map_err(|e| format!("Failed to store upload as asset '{}': {}", name, e))?;
+        }
+
+        let Some(handler_name) = &route.handler_name else {
+            return Ok(None);
+        };
+        let auth_context = auth_user
+            .as_ref()
+            .map(crate::auth::AuthUser::js_auth_context)
+            .unwrap_or_else(crate::auth::JsAuthContext::anonymous);
+        let user_context = auth_user
+            .as_ref()
+            .map(crate::auth::AuthUser::user_context)
+            .unwrap_or_else(crate::security::UserContext::anonymous);
+        let params = crate::js_engine::RequestExecutionParams {
+            script_uri: route.script_uri.clone(),
+            handler_name: handler_name.clone(),
+            path,
+            method,
+            query_params: None,
+            form_data: Some(metadata),
+            raw_body: None,
+            headers,
+            user_context,
+            auth_context: Some(auth_context),
+            route_params: Some(HashMap::from([("id".to_string(), upload_id)])),
+            uploaded_files: Some(vec![file]),
+            timeout_ms: None,
+            tenant: tenant_info,
+        };
+        let response = crate::js_engine::execute_script_for_request_detailed(params)
+            .map_err(|failure| failure.summary)?;
+        Ok((response.status >= 400).then_some(response))
+    };
+
+    let limits = crate::js_engine::current_execution_limits();
+    let timed = tokio::time::timeout(
+        Duration::from_millis(limits.timeout_ms) + crate::debugger::pause_allowance(),
+        crate::worker_pool::run(&upload.route.script_uri, worker),
+    )
+    .await;
+    match timed {
+        Ok(Ok(Ok(None))) => Ok(()),
+        Ok(Ok(Ok(Some(rejected)))) => {
+            let mut response = crate::build_http_response_from_js(rejected);
+            response
+                .headers_mut()
+                .insert("Tus-Resumable", TUS_VERSION.parse().expect("valid header"));
+            Err(response)
+        }
+        Ok(Ok(Err(e))) => {
+            error!("[{}] Failed to deliver upload: {}", request_id, e);
+            Err(tus_error(StatusCode::INTERNAL_SERVER_ERROR, &e))
+        }
+        Ok(Err(e)) => {
+            error!("[{}] Failed to deliver upload: {}", request_id, e);
+            Err(tus_error(StatusCode::SERVICE_UNAVAILABLE, &e.to_string()))
+        }
+        Err(_) => Err(tus_error(
+            StatusCode::GATEWAY_TIMEOUT,
+            "Upload handler timed out",
+        )),
+    }
+}
+
+/// Last path segment of the upload's file name, or its ID when it has no
+/// usable name
+fn asset_file_name(file: &UploadedFile, upload_id: &str) -> String {
+    file.filename
+        .as_deref()
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or(upload_id)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metadata() {
+        let metadata =
+            parse_metadata("filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential")
+                .unwrap();
+        assert_eq!(
+            metadata.get("filename").map(String::as_str),
+            Some("world_domination_plan.pdf")
+        );
+        assert_eq!(
+            metadata.get("is_confidential").map(String::as_str),
+            Some("")
+        );
+        assert!(parse_metadata("").unwrap().is_empty());
+        assert!(parse_metadata("filename not-base64!").is_none());
+    }
+
+    #[test]
+    fn test_match_request() {
+        let route = UploadRoute {
+            script_uri: "https://example.com/uploads".to_string(),
+            handler_name: Some("done".to_string()),
+            asset_prefix: None,
+            max_size: None,
+        };
+        register_route("/tus-match-test/files".to_string(), route.clone());
+
+        let endpoint = match_request(None, "/tus-match-test/files").unwrap();
+        assert_eq!(endpoint.base_path, "/tus-match-test/files");
+        assert_eq!(endpoint.upload_id, None);
+        assert_eq!(endpoint.route, route);
+
+        let upload = match_request(Some("example.com"), "/tus-match-test/files/abc").unwrap();
+        assert_eq!(upload.key, "/tus-match-test/files");
+        assert_eq!(upload.upload_id.as_deref(), Some("abc"));
+
+        assert!(match_request(None, "/tus-match-test/files/abc/def").is_none());
+        assert!(match_request(None, "/tus-match-test").is_none());
+    }
+
+    #[test]
+    fn test_valid_upload_id() {
+        assert!(valid_upload_id(&uuid::Uuid::new_v4().simple().to_string()));
+        assert!(!valid_upload_id("../../etc/passwd"));
+        assert!(!valid_upload_id(""));
+    }
+}
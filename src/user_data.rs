@@ -0,0 +1,984 @@
+//! Export and erasure of the data kept about a user, for data subject access
+//! and erasure requests.
+//!
+//! [`export`] collects everything stored under a user ID: the account with
+//! its profile, session metadata, personal properties, the names (not the
+//! values) of personal secrets, push subscriptions, group memberships, script
+//! ownerships and collaborations, LLM and metered usage, account lockout and
+//! threat records, failed sign-ins and suspicious activity, idempotency keys
+//! with the responses stored for them, log entries whose
+//! structured data has `userId` set to the user and email log entries sent to
+//! the user's email address. Script tables (`database.createTable`) tag rows
+//! with a text `user_id` column; rows of those tables are exported too, as
+//! are rows of tables with a text `user_id` column in the scripts' private
+//! schemas (`db.query`, `db.migrate`). Private schema tables under tenant
+//! row-level security hide their rows unless the engine's database role
+//! bypasses it; such tables are listed under `skippedScriptSchemaTables` and
+//! counted in the `scriptSchemaTablesSkipped` summary entry instead.
+//!
+//! [`erase`] removes the same data in one transaction. `purge` deletes all of
+//! it. `anonymize` deletes personal rows but keeps usage, threat, log, email
+//! log and script table rows, replacing the user ID (or email address) with a
+//! pseudonym, so totals and history stay intact. Either way the account and
+//! its sessions are deleted. Scripts the user owned are kept.
+//!
+//! Every export and erasure is recorded in `user_data_requests` under the
+//! pseudonym, with the administrator who made it and the rows per section.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Row};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::db_schema_utils::{generate_script_schema_name, quote_identifier};
+use crate::error::{AppError, AppResult};
+
+/// Prefix of the pseudonyms replacing user IDs in anonymized rows
+pub const PSEUDONYM_PREFIX: &str = "deleted-";
+
+/// Most audit trail entries returned at once
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+/// Column script tables tag the owning user's rows with
+const SCRIPT_TABLE_USER_COLUMN: &str = "user_id";
+
+/// What erasure does to the rows of a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Retention {
+    /// Delete the rows in both modes
+    Personal,
+    /// Keep the rows under the pseudonym when anonymizing
+    Aggregate,
+}
+
+/// How the rows of a section name their user
+#[derive(Debug, Clone, Copy)]
+enum UserKey {
+    /// The column holds the user ID
+    Column(&'static str),
+    /// The column holds an idempotency scope with the user ID (see
+    /// [`crate::idempotency::request_scope`])
+    IdempotencyScope(&'static str),
+}
+
+impl UserKey {
+    /// SQL condition selecting the rows of the user bound as $1 with
+    /// [`UserKey::bind_value`]
+    fn condition(&self) -> String {
+        match self {
+            Self::Column(column) => format!("{} = $1", column),
+            Self::IdempotencyScope(column) => format!("strpos({}, $1) > 0", column),
+        }
+    }
+
+    fn bind_value(&self, user_id: &str) -> String {
+        match self {
+            Self::Column(_) => user_id.to_string(),
+            Self::IdempotencyScope(_) => crate::idempotency::user_scope_marker(user_id),
+        }
+    }
+}
+
+/// A table of rows keyed by the user
+struct Section {
+    /// Key of the rows in the export and the erasure summary
+    name: &'static str,
+    table: &'static str,
+    key: UserKey,
+    /// Columns exported; session tokens and secret values are left out
+    columns: &'static str,
+    retention: Retention,
+}
+
+const SECTIONS: &[Section] = &[
+    Section {
+        name: "sessions",
+        table: "sessions",
+        key: UserKey::Column("user_id"),
+        columns: "id, created_at, expires_at, last_accessed_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "oauthAuthorizationCodes",
+        table: "oauth_authorization_codes",
+        key: UserKey::Column("user_id"),
+        columns: "client_id, redirect_uri, scope, resource, used, expires_at, created_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "properties",
+        table: "user_properties",
+        key: UserKey::Column("user_id"),
+        columns: "script_uri, key, value, created_at, updated_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "secrets",
+        table: "user_secrets",
+        key: UserKey::Column("user_id"),
+        columns: "script_uri, key, created_at, updated_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "pushSubscriptions",
+        table: "push_subscriptions",
+        key: UserKey::Column("user_id"),
+        columns: "script_uri, endpoint, created_at, updated_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "groups",
+        table: "user_group_members",
+        key: UserKey::Column("user_id"),
+        columns: "group_name, created_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "scriptOwnerships",
+        table: "script_owners",
+        key: UserKey::Column("user_id"),
+        columns: "script_uri, created_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "scriptCollaborations",
+        table: "script_collaborators",
+        key: UserKey::Column("user_id"),
+        columns: "script_uri, created_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "accountLockout",
+        table: "account_lockouts",
+        key: UserKey::Column("user_id"),
+        columns: "failed_attempts, window_started_at, locked_until, updated_at",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "failedAuthAttempts",
+        table: "failed_auth_attempts",
+        key: UserKey::Column("identifier"),
+        columns: "type, attempt_time",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "suspiciousActivity",
+        table: "suspicious_activity",
+        key: UserKey::Column("identifier"),
+        columns: "activity_type, severity_score, details, timestamp",
+        retention: Retention::Personal,
+    },
+    Section {
+        name: "llmUsage",
+        table: "llm_usage",
+        key: UserKey::Column("user_id"),
+        columns: "month, script_uri, provider, model, calls, input_tokens, output_tokens, cost",
+        retention: Retention::Aggregate,
+    },
+    Section {
+        name: "usageRecords",
+        table: "usage_records",
+        key: UserKey::Column("user_id"),
+        columns: "metric, quantity, unit, tenant, script_uri, period_start, period_end",
+        retention: Retention::Aggregate,
+    },
+    Section {
+        name: "threatActions",
+        table: "threat_actions",
+        key: UserKey::Column("subject"),
+        columns: "policy, action, indicator, description, status, created_at, decided_by, decided_at",
+        retention: Retention::Aggregate,
+    },
+    Section {
+        name: "idempotencyKeys",
+        table: "idempotency_keys",
+        key: UserKey::IdempotencyScope("scope"),
+        columns: "scope, idempotency_key, status, content_type, headers, \
+                  encode(body, 'base64') AS body_base64, created_at, expires_at",
+        retention: Retention::Personal,
+    },
+];
+
+/// How [`erase`] treats rows that are kept for totals and history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErasureMode {
+    /// Replace the user ID with a pseudonym in usage, threat, log and script
+    /// table rows; delete everything else
+    Anonymize,
+    /// Delete everything
+    Purge,
+}
+
+impl ErasureMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "anonymize" => Ok(Self::Anonymize),
+            "purge" => Ok(Self::Purge),
+            other => Err(format!(
+                "Unknown erasure mode '{}', expected 'anonymize' or 'purge'",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Anonymize => "anonymize",
+            Self::Purge => "purge",
+        }
+    }
+}
+
+/// An entry of the audit trail
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDataRequest {
+    pub id: Uuid,
+    /// Pseudonym of the user
+    pub subject: String,
+    /// `export`, `anonymize` or `purge`
+    pub action: String,
+    pub requested_by: String,
+    /// Rows exported or erased per section
+    pub summary: BTreeMap<String, u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The pseudonym of a user: the same for every erasure of the user, and
+/// not reversible without guessing the user ID
+pub fn pseudonym(user_id: &str) -> String {
+    let digest = Sha256::digest(user_id.as_bytes());
+    format!("{}{}", PSEUDONYM_PREFIX, &hex::encode(digest)[..16])
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    error!("Database error handling user data: {}", e);
+    AppError::Database {
+        message: format!("Database error: {}", e),
+        source: None,
+    }
+}
+
+fn get_db_pool() -> AppResult<std::sync::Arc<crate::database::Database>> {
+    crate::repository::get_db_pool().ok_or_else(|| AppError::Internal {
+        message: "Database not initialized".to_string(),
+    })
+}
+
+fn validate_user_id(user_id: &str) -> AppResult<()> {
+    if user_id.trim().is_empty() {
+        return Err(AppError::Validation {
+            field: "user_id".to_string(),
+            reason: "User ID must not be empty".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Rows of `query` (taking the user ID as `$1`) as a JSON array
+async fn db_rows_json(conn: &mut PgConnection, query: &str, value: &str) -> AppResult<Value> {
+    let sql = format!(
+        "SELECT COALESCE(jsonb_agg(to_jsonb(t)), '[]'::jsonb) FROM ({}) t",
+        query
+    );
+    sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+        .bind(value)
+        .fetch_one(conn)
+        .await
+        .map_err(db_error)
+}
+
+/// The email address of an account, used to find email log entries
+async fn db_user_email(conn: &mut PgConnection, user_id: &str) -> AppResult<Option<String>> {
+    sqlx::query_scalar("SELECT email FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(conn)
+        .await
+        .map_err(db_error)
+}
+
+/// A script table with a `user_id` column
+struct TaggedTable {
+    script_uri: String,
+    logical_name: String,
+    physical_name: String,
+}
+
+fn convert_row_to_tagged_table(row: &PgRow) -> Result<TaggedTable, sqlx::Error> {
+    Ok(TaggedTable {
+        script_uri: row.try_get("script_uri")?,
+        logical_name: row.try_get("logical_table_name")?,
+        physical_name: row.try_get("physical_table_name")?,
+    })
+}
+
+async fn db_tagged_script_tables(conn: &mut PgConnection) -> AppResult<Vec<TaggedTable>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT st.script_uri, st.logical_table_name, st.physical_table_name
+        FROM script_tables st
+        JOIN information_schema.columns c
+          ON c.table_name = st.physical_table_name
+         AND c.table_schema = current_schema()
+         AND c.column_name = $1
+         AND c.data_type = 'text'
+        ORDER BY st.script_uri, st.logical_table_name
+        "#,
+    )
+    .bind(SCRIPT_TABLE_USER_COLUMN)
+    .fetch_all(conn)
+    .await
+    .map_err(db_error)?;
+    rows.iter()
+        .map(convert_row_to_tagged_table)
+        .collect::<Result<_, _>>()
+        .map_err(db_error)
+}
+
+/// A table with a `user_id` column in a script's private schema
+struct SchemaTable {
+    /// The script the schema belongs to; None when the script is gone
+    script_uri: Option<String>,
+    schema: String,
+    table: String,
+    /// Rows are under row-level security the engine's role does not bypass
+    hidden: bool,
+}
+
+impl SchemaTable {
+    fn qualified_name(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_identifier(&self.schema),
+            quote_identifier(&self.table)
+        )
+    }
+
+    fn to_json(&self) -> Value {
+        serde_json::json!({
+            "scriptUri": self.script_uri,
+            "schema": self.schema,
+            "table": self.table,
+        })
+    }
+}
+
+async fn db_tagged_schema_tables(conn: &mut PgConnection) -> AppResult<Vec<SchemaTable>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT c.table_schema, c.table_name,
+               cl.relrowsecurity AND NOT (
+                   SELECT rolsuper OR rolbypassrls FROM pg_roles WHERE rolname = current_user
+               ) AS hidden
+        FROM information_schema.columns c
+        JOIN pg_namespace n ON n.nspname = c.table_schema
+        JOIN pg_class cl ON cl.relnamespace = n.oid AND cl.relname = c.table_name
+        WHERE c.table_schema LIKE 'scriptdb\_%'
+          AND c.column_name = $1
+          AND c.data_type = 'text'
+          AND cl.relkind IN ('r', 'p')
+        ORDER BY c.table_schema, c.table_name
+        "#,
+    )
+    .bind(SCRIPT_TABLE_USER_COLUMN)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(db_error)?;
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Schema names are digests of the script URI
+    let uris: Vec<String> =
+        sqlx::query_scalar("SELECT uri FROM scripts UNION SELECT uri FROM script_trash")
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(db_error)?;
+    let scripts: BTreeMap<String, String> = uris
+        .into_iter()
+        .map(|uri| (generate_script_schema_name(&uri), uri))
+        .collect();
+
+    rows.iter()
+        .map(|row| {
+            let schema: String = row.try_get("table_schema")?;
+            Ok(SchemaTable {
+                script_uri: scripts.get(&schema).cloned(),
+                table: row.try_get("table_name")?,
+                hidden: row.try_get::<Option<bool>, _>("hidden")?.unwrap_or(false),
+                schema,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(db_error)
+}
+
+fn array_len(value: &Value) -> u64 {
+    value.as_array().map_or(0, |rows| rows.len() as u64)
+}
+
+async fn db_export(
+    conn: &mut PgConnection,
+    user_id: &str,
+) -> AppResult<(Value, BTreeMap<String, u64>)> {
+    let mut export = Map::new();
+    let mut summary = BTreeMap::new();
+    export.insert("userId".to_string(), Value::String(user_id.to_string()));
+    export.insert(
+        "exportedAt".to_string(),
+        Value::String(Utc::now().to_rfc3339()),
+    );
+
+    let account: Option<Value> = sqlx::query_scalar(
+        r#"
+        SELECT to_jsonb(u) - 'id'
+        FROM users u
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(db_error)?;
+    summary.insert("account".to_string(), u64::from(account.is_some()));
+    export.insert("account".to_string(), account.unwrap_or(Value::Null));
+
+    for section in SECTIONS {
+        let query = format!(
+            "SELECT {} FROM {} WHERE {}",
+            section.columns,
+            section.table,
+            section.key.condition()
+        );
+        let rows = db_rows_json(conn, &query, &section.key.bind_value(user_id)).await?;
+        summary.insert(section.name.to_string(), array_len(&rows));
+        export.insert(section.name.to_string(), rows);
+    }
+
+    let logs = db_rows_json(
+        conn,
+        "SELECT id, script_uri, log_level, message, data, created_at FROM logs \
+         WHERE data @> jsonb_build_object('userId', $1::text) ORDER BY created_at",
+        user_id,
+    )
+    .await?;
+    summary.insert("logs".to_string(), array_len(&logs));
+    export.insert("logs".to_string(), logs);
+
+    let email_log = match db_user_email(conn, user_id).await? {
+        Some(email) => {
+            db_rows_json(
+                conn,
+                "SELECT script_uri, subject, status, created_at FROM email_log \
+                 WHERE $1 = ANY(recipients) ORDER BY created_at",
+                &email,
+            )
+            .await?
+        }
+        None => Value::Array(Vec::new()),
+    };
+    summary.insert("emailLog".to_string(), array_len(&email_log));
+    export.insert("emailLog".to_string(), email_log);
+
+    let mut script_tables = Vec::new();
+    let mut script_table_rows = 0;
+    for table in db_tagged_script_tables(conn).await? {
+        let query = format!(
+            "SELECT * FROM {} WHERE {} = $1",
+            quote_identifier(&table.physical_name),
+            SCRIPT_TABLE_USER_COLUMN
+        );
+        let rows = db_rows_json(conn, &query, user_id).await?;
+        if array_len(&rows) == 0 {
+            continue;
+        }
+        script_table_rows += array_len(&rows);
+        script_tables.push(serde_json::json!({
+            "scriptUri": table.script_uri,
+            "table": table.logical_name,
+            "rows": rows,
+        }));
+    }
+    summary.insert("scriptTables".to_string(), script_table_rows);
+    export.insert("scriptTables".to_string(), Value::Array(script_tables));
+
+    let mut schema_tables = Vec::new();
+    let mut schema_table_rows = 0;
+    let mut skipped = Vec::new();
+    for table in db_tagged_schema_tables(conn).await? {
+        if table.hidden {
+            skipped.push(table.to_json());
+            continue;
+        }
+        let query = format!(
+            "SELECT * FROM {} WHERE {} = $1",
+            table.qualified_name(),
+            SCRIPT_TABLE_USER_COLUMN
+        );
+        let rows = db_rows_json(conn, &query, user_id).await?;
+        if array_len(&rows) == 0 {
+            continue;
+        }
+        schema_table_rows += array_len(&rows);
+        let mut entry = table.to_json();
+        entry["rows"] = rows;
+        schema_tables.push(entry);
+    }
+    summary.insert("scriptSchemaTables".to_string(), schema_table_rows);
+    summary.insert(
+        "scriptSchemaTablesSkipped".to_string(),
+        skipped.len() as u64,
+    );
+    export.insert(
+        "scriptSchemaTables".to_string(),
+        Value::Array(schema_tables),
+    );
+    export.insert(
+        "skippedScriptSchemaTables".to_string(),
+        Value::Array(skipped),
+    );
+
+    Ok((Value::Object(export), summary))
+}
+
+async fn db_erase(
+    conn: &mut PgConnection,
+    user_id: &str,
+    mode: ErasureMode,
+) -> AppResult<BTreeMap<String, u64>> {
+    let pseudonym = pseudonym(user_id);
+    let keep = mode == ErasureMode::Anonymize;
+    let mut summary = BTreeMap::new();
+
+    // Read before the account is deleted
+    let email = db_user_email(conn, user_id).await?;
+
+    for section in SECTIONS {
+        let result = if keep
+            && section.retention == Retention::Aggregate
+            && let UserKey::Column(column) = section.key
+        {
+            let sql = format!(
+                "UPDATE {} SET {column} = $2 WHERE {column} = $1",
+                section.table,
+                column = column
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(user_id)
+                .bind(&pseudonym)
+                .execute(&mut *conn)
+                .await
+        } else {
+            let sql = format!(
+                "DELETE FROM {} WHERE {}",
+                section.table,
+                section.key.condition()
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(section.key.bind_value(user_id))
+                .execute(&mut *conn)
+                .await
+        };
+        summary.insert(
+            section.name.to_string(),
+            result.map_err(db_error)?.rows_affected(),
+        );
+    }
+
+    let logs = if keep {
+        sqlx::query(
+            r#"
+            UPDATE logs SET data = jsonb_set(data, '{userId}', to_jsonb($2::text))
+            WHERE data @> jsonb_build_object('userId', $1::text)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&pseudonym)
+        .execute(&mut *conn)
+        .await
+    } else {
+        sqlx::query("DELETE FROM logs WHERE data @> jsonb_build_object('userId', $1::text)")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await
+    };
+    summary.insert("logs".to_string(), logs.map_err(db_error)?.rows_affected());
+
+    let mut email_log = 0;
+    if let Some(email) = email {
+        let result = if keep {
+            sqlx::query(
+                r#"
+                UPDATE email_log SET recipients = array_replace(recipients, $1, $2)
+                WHERE $1 = ANY(recipients)
+                "#,
+            )
+            .bind(&email)
+            .bind(&pseudonym)
+            .execute(&mut *conn)
+            .await
+        } else {
+            sqlx::query("DELETE FROM email_log WHERE $1 = ANY(recipients)")
+                .bind(&email)
+                .execute(&mut *conn)
+                .await
+        };
+        email_log = result.map_err(db_error)?.rows_affected();
+    }
+    summary.insert("emailLog".to_string(), email_log);
+
+    let mut script_table_rows = 0;
+    for table in db_tagged_script_tables(conn).await? {
+        let physical_name = quote_identifier(&table.physical_name);
+        let result = if keep {
+            let sql = format!(
+                "UPDATE {} SET {column} = $2 WHERE {column} = $1",
+                physical_name,
+                column = SCRIPT_TABLE_USER_COLUMN
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(user_id)
+                .bind(&pseudonym)
+                .execute(&mut *conn)
+                .await
+        } else {
+            let sql = format!(
+                "DELETE FROM {} WHERE {} = $1",
+                physical_name, SCRIPT_TABLE_USER_COLUMN
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await
+        };
+        script_table_rows += result.map_err(db_error)?.rows_affected();
+    }
+    summary.insert("scriptTables".to_string(), script_table_rows);
+
+    let mut schema_table_rows = 0;
+    let mut skipped = 0;
+    for table in db_tagged_schema_tables(conn).await? {
+        if table.hidden {
+            skipped += 1;
+            continue;
+        }
+        let result = if keep {
+            let sql = format!(
+                "UPDATE {} SET {column} = $2 WHERE {column} = $1",
+                table.qualified_name(),
+                column = SCRIPT_TABLE_USER_COLUMN
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(user_id)
+                .bind(&pseudonym)
+                .execute(&mut *conn)
+                .await
+        } else {
+            let sql = format!(
+                "DELETE FROM {} WHERE {} = $1",
+                table.qualified_name(),
+                SCRIPT_TABLE_USER_COLUMN
+            );
+            sqlx::query(sqlx::AssertSqlSafe(sql))
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await
+        };
+        schema_table_rows += result.map_err(db_error)?.rows_affected();
+    }
+    summary.insert("scriptSchemaTables".to_string(), schema_table_rows);
+    summary.insert("scriptSchemaTablesSkipped".to_string(), skipped);
+
+    let account = sqlx::query("DELETE FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    summary.insert("account".to_string(), account.rows_affected());
+
+    Ok(summary)
+}
+
+async fn db_record_request(
+    conn: &mut PgConnection,
+    user_id: &str,
+    action: &str,
+    requested_by: &str,
+    summary: BTreeMap<String, u64>,
+) -> AppResult<UserDataRequest> {
+    let request = UserDataRequest {
+        id: Uuid::new_v4(),
+        subject: pseudonym(user_id),
+        action: action.to_string(),
+        requested_by: requested_by.to_string(),
+        summary,
+        created_at: Utc::now(),
+    };
+    let summary_json = serde_json::to_value(&request.summary).map_err(|e| AppError::Internal {
+        message: format!("Failed to serialize summary: {}", e),
+    })?;
+    sqlx::query(
+        r#"
+        INSERT INTO user_data_requests (id, subject, action, requested_by, summary, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(request.id)
+    .bind(&request.subject)
+    .bind(&request.action)
+    .bind(&request.requested_by)
+    .bind(summary_json)
+    .bind(request.created_at)
+    .execute(conn)
+    .await
+    .map_err(db_error)?;
+    Ok(request)
+}
+
+fn convert_row_to_request(row: &PgRow) -> Result<UserDataRequest, sqlx::Error> {
+    let summary: Value = row.try_get("summary")?;
+    Ok(UserDataRequest {
+        id: row.try_get("id")?,
+        subject: row.try_get("subject")?,
+        action: row.try_get("action")?,
+        requested_by: row.try_get("requested_by")?,
+        summary: serde_json::from_value(summary).unwrap_or_default(),
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+async fn db_list_requests(
+    pool: &PgPool,
+    subject: Option<&str>,
+    limit: i64,
+) -> AppResult<Vec<UserDataRequest>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, subject, action, requested_by, summary, created_at
+        FROM user_data_requests
+        WHERE ($1::text IS NULL OR subject = $1)
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(subject)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(db_error)?;
+    rows.iter()
+        .map(convert_row_to_request)
+        .collect::<Result<_, _>>()
+        .map_err(db_error)
+}
+
+/// Export everything kept about `user_id` as one JSON document, recording
+/// the export in the audit trail
+pub fn export(user_id: &str, requested_by: &str) -> AppResult<Value> {
+    validate_user_id(user_id)?;
+    let db = get_db_pool()?;
+    let (export, request) = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            let mut conn = db.pool().acquire().await.map_err(db_error)?;
+            let (export, summary) = db_export(&mut conn, user_id).await?;
+            let request =
+                db_record_request(&mut conn, user_id, "export", requested_by, summary).await?;
+            Ok::<_, AppError>((export, request))
+        })
+    })?;
+    info!(
+        "Exported the data of user {} for {} (request {})",
+        request.subject, requested_by, request.id
+    );
+    Ok(export)
+}
+
+/// Erase everything kept about `user_id`, including the account, in one
+/// transaction with its audit trail entry
+pub fn erase(user_id: &str, mode: ErasureMode, requested_by: &str) -> AppResult<UserDataRequest> {
+    validate_user_id(user_id)?;
+    let db = get_db_pool()?;
+    let request = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            let mut tx = db.pool().begin().await.map_err(db_error)?;
+            let summary = db_erase(&mut tx, user_id, mode).await?;
+            let request =
+                db_record_request(&mut tx, user_id, mode.as_str(), requested_by, summary).await?;
+            tx.commit().await.map_err(db_error)?;
+            Ok::<_, AppError>(request)
+        })
+    })?;
+    crate::user_repository::forget_cached_user(user_id);
+    info!(
+        "Erased the data of user {} ({}) for {} (request {})",
+        request.subject, request.action, requested_by, request.id
+    );
+    Ok(request)
+}
+
+/// Audit trail entries, newest first; only those of `user_id` if given
+pub fn list_requests(user_id: Option<&str>, limit: i64) -> AppResult<Vec<UserDataRequest>> {
+    let db = get_db_pool()?;
+    let subject = user_id.map(pseudonym);
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_list_requests(
+            db.pool(),
+            subject.as_deref(),
+            limit.clamp(1, MAX_LIST_LIMIT),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{self, Repository, ScriptQueryOptions};
+    use std::sync::{Once, OnceLock};
+    use tokio::runtime::Runtime;
+
+    static DB_INIT: Once = Once::new();
+
+    fn get_runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| Runtime::new().expect("Failed to create Tokio runtime"))
+    }
+
+    fn setup_db() {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            return;
+        };
+        DB_INIT.call_once(|| {
+            get_runtime().block_on(async {
+                let pool = sqlx::PgPool::connect_lazy(&url).expect("Failed to create pool");
+                let db = std::sync::Arc::new(crate::database::Database::from_pool(pool.clone()));
+                let _ = crate::database::initialize_global_database(db);
+                let server_id = crate::notifications::generate_server_id();
+                let _ = crate::notifications::initialize_server_id(server_id.clone());
+                let repo = repository::PostgresRepository::new(pool, server_id);
+                let _ = repository::initialize_repository(repo);
+            });
+        });
+    }
+
+    fn should_skip_db_tests() -> bool {
+        std::env::var("DATABASE_URL").is_err()
+    }
+
+    #[test]
+    fn test_pseudonym() {
+        let name = pseudonym("user-123");
+        assert!(name.starts_with(PSEUDONYM_PREFIX));
+        assert_eq!(name.len(), PSEUDONYM_PREFIX.len() + 16);
+        assert!(!name.contains("user-123"));
+        assert_eq!(name, pseudonym("user-123"));
+        assert_ne!(name, pseudonym("user-124"));
+    }
+
+    #[test]
+    fn test_erasure_mode() {
+        for mode in [ErasureMode::Anonymize, ErasureMode::Purge] {
+            assert_eq!(ErasureMode::parse(mode.as_str()), Ok(mode));
+        }
+        assert!(ErasureMode::parse("delete").is_err());
+        assert!(ErasureMode::parse("").is_err());
+    }
+
+    #[test]
+    fn test_sections_are_unique() {
+        let mut names: Vec<&str> = SECTIONS.iter().map(|section| section.name).collect();
+        names.extend([
+            "account",
+            "logs",
+            "emailLog",
+            "scriptTables",
+            "scriptSchemaTables",
+            "scriptSchemaTablesSkipped",
+        ]);
+        let count = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), count);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_private_schema_rows_are_exported_and_erased() {
+        if should_skip_db_tests() {
+            return;
+        }
+        let rt = get_runtime();
+        let _guard = rt.enter();
+        setup_db();
+        let script_uri = "test://user-data-private-schema";
+        let user_id = "user-data-schema-user";
+        let options = ScriptQueryOptions::default();
+        repository::upsert_script(script_uri, "// db.query tables").expect("Should create script");
+        let query = |sql: &str, params: &[Value]| {
+            repository::run_script_query(script_uri, sql, params, &options)
+                .expect("Query should run")
+        };
+        query("DROP TABLE IF EXISTS notes", &[]);
+        query(
+            "CREATE TABLE notes (id SERIAL PRIMARY KEY, user_id TEXT, body TEXT)",
+            &[],
+        );
+        query(
+            "INSERT INTO notes (user_id, body) VALUES ($1, 'mine'), ('someone-else', 'theirs')",
+            &[Value::String(user_id.to_string())],
+        );
+        let repo = repository::get_repository();
+        let now = Utc::now();
+        let mine = crate::idempotency::request_scope("POST", "/orders", Some(user_id), None);
+        // A user ID that starts with this one must not match
+        let other = format!("{}-2", user_id);
+        let theirs = crate::idempotency::request_scope("POST", "/orders", Some(&other), None);
+        for scope in [&mine, &theirs] {
+            repo.claim_idempotency_key(
+                scope,
+                "key-1",
+                "hash",
+                now + chrono::Duration::hours(1),
+                now,
+            )
+            .await
+            .expect("Should claim idempotency key");
+        }
+
+        let exported = export(user_id, "test-admin").expect("Should export");
+        let keys = exported["idempotencyKeys"]
+            .as_array()
+            .expect("Export should list idempotency keys");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0]["scope"], mine.as_str());
+        let tables = exported["scriptSchemaTables"]
+            .as_array()
+            .expect("Export should list private schema tables");
+        let notes = tables
+            .iter()
+            .find(|table| table["scriptUri"] == script_uri && table["table"] == "notes")
+            .expect("Export should include the notes table");
+        assert_eq!(notes["rows"].as_array().map(Vec::len), Some(1));
+        assert_eq!(notes["rows"][0]["body"], "mine");
+
+        let request = erase(user_id, ErasureMode::Purge, "test-admin").expect("Should erase");
+        assert_eq!(request.summary.get("scriptSchemaTables"), Some(&1));
+        assert_eq!(request.summary.get("idempotencyKeys"), Some(&1));
+        let exported = export(&other, "test-admin").expect("Should export");
+        assert_eq!(
+            exported["idempotencyKeys"].as_array().map(Vec::len),
+            Some(1)
+        );
+        let remaining = query("SELECT user_id FROM notes", &[]);
+        assert_eq!(remaining.rows.len(), 1);
+        assert_eq!(remaining.rows[0]["user_id"], "someone-else");
+
+        query("DROP TABLE notes", &[]);
+        let _ = repo.release_idempotency_key(&theirs, "key-1").await;
+        let _ = repository::delete_script(script_uri);
+    }
+}
@@ -1,9 +1,11 @@
 use crate::error::{AppError, AppResult};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
-use std::sync::OnceLock;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use tracing::{debug, error, warn};
 
 /// Global bootstrap admin configuration
@@ -24,6 +26,48 @@ fn get_bootstrap_admins() -> &'static [String] {
     BOOTSTRAP_ADMINS.get().map(|v| v.as_slice()).unwrap_or(&[])
 }
 
+/// Global bootstrap role rules, including one per bootstrap editor
+static BOOTSTRAP_ROLES: OnceLock<Vec<crate::auth::BootstrapRoleRule>> = OnceLock::new();
+
+/// Set the roles given to new users by email pattern
+///
+/// This should be called once at application startup with the configured
+/// bootstrap editors and role rules.
+pub fn set_bootstrap_roles(rules: Vec<crate::auth::BootstrapRoleRule>) {
+    if BOOTSTRAP_ROLES.set(rules).is_err() {
+        warn!("Bootstrap roles already set, ignoring duplicate configuration");
+    }
+}
+
+/// Get the bootstrap role rules
+fn get_bootstrap_roles() -> &'static [crate::auth::BootstrapRoleRule] {
+    BOOTSTRAP_ROLES.get().map(|v| v.as_slice()).unwrap_or(&[])
+}
+
+/// The admin and editor flags a new user with `email` gets from the
+/// bootstrap admins and role rules
+fn bootstrap_role_flags(
+    email: &str,
+    bootstrap_admins: &[String],
+    bootstrap_roles: &[crate::auth::BootstrapRoleRule],
+) -> (bool, bool) {
+    let email_lower = email.to_lowercase();
+    let mut is_admin = bootstrap_admins
+        .iter()
+        .any(|admin_email| admin_email.to_lowercase() == email_lower);
+    let mut is_editor = false;
+    for rule in bootstrap_roles.iter().filter(|rule| rule.matches(email)) {
+        for role in &rule.roles {
+            match role {
+                UserRole::Administrator => is_admin = true,
+                UserRole::Editor => is_editor = true,
+                UserRole::Authenticated => {}
+            }
+        }
+    }
+    (is_admin, is_editor)
+}
+
 /// Defines the types of user repository errors that can occur
 #[derive(Debug, thiserror::Error)]
 pub enum UserRepositoryError {
@@ -476,22 +520,23 @@ pub async fn upsert_user(
     provider_name: String,
     provider_user_id: String,
 ) -> AppResult<String> {
-    let bootstrap_admins = get_bootstrap_admins();
     upsert_user_with_bootstrap(
         email,
         name,
         provider_name,
         provider_user_id,
-        bootstrap_admins,
+        get_bootstrap_admins(),
+        get_bootstrap_roles(),
     )
     .await
 }
 
 /// Upsert a user with bootstrap admin configuration
 ///
-/// This is the internal implementation that supports bootstrap admins.
-/// If the user's email matches one in the bootstrap_admins list, they automatically
-/// get the Administrator role on creation.
+/// This is the internal implementation that supports bootstrap admins and
+/// role rules. If the user's email matches one in the bootstrap_admins list,
+/// they automatically get the Administrator role on creation; matching role
+/// rules add their roles. Existing users keep their roles.
 ///
 /// # Arguments
 /// * `email` - User's email address
@@ -499,6 +544,7 @@ pub async fn upsert_user(
 /// * `provider_name` - OAuth provider name (e.g., "google", "github")
 /// * `provider_user_id` - Provider-specific user ID
 /// * `bootstrap_admins` - List of emails that should automatically get admin role
+/// * `bootstrap_roles` - Email patterns with the roles new users matching them get
 ///
 /// # Returns
 /// The user ID (either existing or newly created)
@@ -508,6 +554,7 @@ pub async fn upsert_user_with_bootstrap(
     provider_name: String,
     provider_user_id: String,
     bootstrap_admins: &[String],
+    bootstrap_roles: &[crate::auth::BootstrapRoleRule],
 ) -> AppResult<String> {
     // Validate inputs
     if email.trim().is_empty() {
@@ -531,19 +578,13 @@ pub async fn upsert_user_with_bootstrap(
         });
     }
 
-    // Check if this email is in the bootstrap admins list
-    let email_lower = email.to_lowercase();
-    let is_bootstrap_admin = bootstrap_admins
-        .iter()
-        .any(|admin_email| admin_email.to_lowercase() == email_lower);
-
-    let is_admin = is_bootstrap_admin;
-    let is_editor = false; // For now, only admins get editor role automatically
+    // Roles for the account if it is created now
+    let (is_admin, is_editor) = bootstrap_role_flags(&email, bootstrap_admins, bootstrap_roles);
 
-    if is_bootstrap_admin {
+    if is_admin || is_editor {
         debug!(
-            "User {} will be granted Administrator role (bootstrap admin)",
-            email
+            "User {} will be granted bootstrap roles on creation (admin: {}, editor: {})",
+            email, is_admin, is_editor
         );
     }
 
@@ -587,6 +628,15 @@ pub fn find_user_by_provider(
     })
 }
 
+/// Async variant of [`find_user_by_provider`] for callers already in async context
+pub async fn find_user_by_provider_async(
+    provider_name: &str,
+    provider_user_id: &str,
+) -> AppResult<Option<User>> {
+    let db = get_db_pool()?;
+    db_find_user_by_provider(db.pool(), provider_name, provider_user_id).await
+}
+
 /// Database-backed update user roles
 async fn db_update_user_roles(
     pool: &PgPool,
@@ -804,11 +854,896 @@ pub fn delete_user(user_id: &str) -> AppResult<bool> {
             .block_on(async { db_delete_user(db.pool(), user_id).await })
     })?;
     if deleted {
+        forget_cached_user(user_id);
         debug!("Deleted user: {}", user_id);
     }
     Ok(deleted)
 }
 
+/// Drop the cached profile and group memberships of a user deleted outside
+/// [`delete_user`]
+pub fn forget_cached_user(user_id: &str) {
+    profile_cache().remove(user_id);
+    group_membership_cache().remove(user_id);
+}
+
+/// Failed sign-in state of an account, kept by the account lockout policy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountLockout {
+    pub user_id: String,
+    /// Failed sign-ins in the current window
+    pub failed_attempts: u32,
+    /// When the first failed sign-in of the current window happened
+    pub window_started_at: chrono::DateTime<chrono::Utc>,
+    /// Set while the account is locked
+    pub locked_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn convert_row_to_account_lockout(row: &PgRow) -> Result<AccountLockout, sqlx::Error> {
+    Ok(AccountLockout {
+        user_id: row.try_get("user_id")?,
+        failed_attempts: row.try_get::<i32, _>("failed_attempts")?.max(0) as u32,
+        window_started_at: row.try_get("window_started_at")?,
+        locked_until: row.try_get("locked_until")?,
+    })
+}
+
+/// Get the lockout state of an account, if it has failed sign-ins
+pub async fn get_account_lockout(user_id: &str) -> AppResult<Option<AccountLockout>> {
+    let db = get_db_pool()?;
+    let row = sqlx::query(
+        r#"
+        SELECT user_id, failed_attempts, window_started_at, locked_until
+        FROM account_lockouts
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db.pool())
+    .await
+    .map_err(|e| {
+        error!("Database error getting account lockout: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    })?;
+
+    row.map(|row| convert_row_to_account_lockout(&row))
+        .transpose()
+        .map_err(|e| AppError::Database {
+            message: e.to_string(),
+            source: None,
+        })
+}
+
+/// Replace the lockout state of an account with `update(current)`. The row
+/// is locked while the update runs, so failures counted concurrently by
+/// other servers are not lost.
+pub async fn update_account_lockout<F>(user_id: &str, update: F) -> AppResult<AccountLockout>
+where
+    F: FnOnce(Option<AccountLockout>) -> AccountLockout,
+{
+    let db = get_db_pool()?;
+    let db_error = |e: sqlx::Error| {
+        error!("Database error updating account lockout: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+
+    let mut tx = db.pool().begin().await.map_err(db_error)?;
+    let current = sqlx::query(
+        r#"
+        SELECT user_id, failed_attempts, window_started_at, locked_until
+        FROM account_lockouts
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .map(|row| convert_row_to_account_lockout(&row))
+    .transpose()
+    .map_err(db_error)?;
+
+    let lockout = update(current);
+    sqlx::query(
+        r#"
+        INSERT INTO account_lockouts (user_id, failed_attempts, window_started_at, locked_until, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (user_id) DO UPDATE SET
+            failed_attempts = EXCLUDED.failed_attempts,
+            window_started_at = EXCLUDED.window_started_at,
+            locked_until = EXCLUDED.locked_until,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(user_id)
+    .bind(lockout.failed_attempts.min(i32::MAX as u32) as i32)
+    .bind(lockout.window_started_at)
+    .bind(lockout.locked_until)
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(lockout)
+}
+
+/// Async variant of [`clear_account_lockout`] for callers already in async context
+pub async fn clear_account_lockout_async(user_id: &str) -> AppResult<bool> {
+    let db = get_db_pool()?;
+    let result = sqlx::query("DELETE FROM account_lockouts WHERE user_id = $1")
+        .bind(user_id)
+        .execute(db.pool())
+        .await
+        .map_err(|e| {
+            error!("Database error clearing account lockout: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Forget the failed sign-ins of an account, unlocking it if it is locked.
+/// Returns whether the account had any.
+pub fn clear_account_lockout(user_id: &str) -> AppResult<bool> {
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(clear_account_lockout_async(user_id))
+    })
+}
+
+/// Largest serialized profile
+pub const MAX_PROFILE_BYTES: usize = 16 * 1024;
+
+/// How long a profile read from the database serves `request.auth.profile`.
+/// Updates refresh it on the instance that made them; other instances see
+/// them once their copy expires.
+const PROFILE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Cached profiles or group memberships beyond which expired ones are dropped
+const CACHE_PRUNE_SIZE: usize = 10_000;
+
+/// Compiled `auth.profile_schema`
+static PROFILE_SCHEMA: OnceLock<ProfileSchema> = OnceLock::new();
+
+static PROFILE_CACHE: OnceLock<Mutex<HashMap<String, (Instant, UserProfile)>>> = OnceLock::new();
+
+/// Custom profile fields of a user, kept in the `users.profile` JSONB column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserProfile {
+    pub user_id: String,
+    /// Fields described by the profile schema
+    pub fields: Map<String, Value>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// JSON Schema the profile fields must match (`auth.profile_schema`)
+#[derive(Debug)]
+pub struct ProfileSchema {
+    schema: Value,
+    validator: jsonschema::Validator,
+    /// Top-level properties marked `readOnly`, which only administrators change
+    read_only: Vec<String>,
+}
+
+impl ProfileSchema {
+    /// Compile a schema; it must describe an object
+    pub fn compile(schema: Value) -> Result<Self, String> {
+        if schema.get("type").and_then(Value::as_str) != Some("object") {
+            return Err("auth.profile_schema must have type \"object\"".to_string());
+        }
+        let validator = jsonschema::options()
+            .build(&schema)
+            .map_err(|e| format!("auth.profile_schema is not a valid JSON Schema: {}", e))?;
+        let read_only = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .map(|properties| {
+                properties
+                    .iter()
+                    .filter(|(_, property)| property.get("readOnly") == Some(&Value::Bool(true)))
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            schema,
+            validator,
+            read_only,
+        })
+    }
+
+    /// The schema as configured
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Apply `changes` to the `current` fields: fields set to null are
+    /// removed, others replaced. The result must match the schema, and only
+    /// administrators may change read-only fields.
+    pub fn apply(
+        &self,
+        current: &Map<String, Value>,
+        changes: Map<String, Value>,
+        is_admin: bool,
+    ) -> Result<Map<String, Value>, String> {
+        let mut fields = current.clone();
+        for (name, value) in changes {
+            if !is_admin
+                && self.read_only.contains(&name)
+                && current.get(&name).unwrap_or(&Value::Null) != &value
+            {
+                return Err(format!("Profile field '{}' is read-only", name));
+            }
+            if value.is_null() {
+                fields.remove(&name);
+            } else {
+                fields.insert(name, value);
+            }
+        }
+
+        let size = serde_json::to_vec(&fields)
+            .map(|json| json.len())
+            .unwrap_or_default();
+        if size > MAX_PROFILE_BYTES {
+            return Err(format!(
+                "Profile is too large ({} bytes, max {})",
+                size, MAX_PROFILE_BYTES
+            ));
+        }
+        let instance = Value::Object(fields.clone());
+        // Messages are masked so they do not echo submitted values
+        let violations: Vec<String> = self
+            .validator
+            .iter_errors(&instance)
+            .map(|error| {
+                let path = error.instance_path().as_str().to_string();
+                if path.is_empty() {
+                    error.masked().to_string()
+                } else {
+                    format!("{}: {}", path, error.masked())
+                }
+            })
+            .collect();
+        if !violations.is_empty() {
+            return Err(format!(
+                "Profile does not match the schema: {}",
+                violations.join("; ")
+            ));
+        }
+        Ok(fields)
+    }
+}
+
+/// Set the profile schema
+///
+/// This should be called once at application startup with `auth.profile_schema`.
+/// Without a schema profiles can be read but not changed.
+pub fn set_profile_schema(schema: Value) -> Result<(), String> {
+    let compiled = ProfileSchema::compile(schema)?;
+    if PROFILE_SCHEMA.set(compiled).is_err() {
+        warn!("Profile schema already set, ignoring duplicate configuration");
+    }
+    Ok(())
+}
+
+/// The profile schema, if one is configured
+pub fn profile_schema() -> Option<&'static ProfileSchema> {
+    PROFILE_SCHEMA.get()
+}
+
+fn profile_cache() -> MutexGuard<'static, HashMap<String, (Instant, UserProfile)>> {
+    PROFILE_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn cache_profile(profile: &UserProfile) {
+    let mut cache = profile_cache();
+    if cache.len() >= CACHE_PRUNE_SIZE {
+        cache.retain(|_, (cached_at, _)| cached_at.elapsed() < PROFILE_CACHE_TTL);
+    }
+    cache.insert(profile.user_id.clone(), (Instant::now(), profile.clone()));
+}
+
+/// Read the profile column of a users row
+fn profile_fields(row: &PgRow) -> AppResult<Map<String, Value>> {
+    let profile: Value = row.try_get("profile").map_err(|e| AppError::Database {
+        message: e.to_string(),
+        source: None,
+    })?;
+    match profile {
+        Value::Object(fields) => Ok(fields),
+        _ => Ok(Map::new()),
+    }
+}
+
+fn user_not_found(user_id: &str) -> AppError {
+    AppError::Validation {
+        field: "user_id".to_string(),
+        reason: format!("User not found: {}", user_id),
+    }
+}
+
+/// Database-backed get user profile
+async fn db_get_user_profile(pool: &PgPool, user_id: &str) -> AppResult<UserProfile> {
+    let row = sqlx::query("SELECT profile, updated_at FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| {
+            error!("Database error getting user profile: {}", e);
+            AppError::Database {
+                message: format!("Database error: {}", e),
+                source: None,
+            }
+        })?
+        .ok_or_else(|| user_not_found(user_id))?;
+
+    Ok(UserProfile {
+        user_id: user_id.to_string(),
+        fields: profile_fields(&row)?,
+        updated_at: row.try_get("updated_at").map_err(|e| AppError::Database {
+            message: e.to_string(),
+            source: None,
+        })?,
+    })
+}
+
+/// Database-backed update user profile. The row is locked while the changes
+/// are applied so concurrent updates of different fields don't undo each
+/// other.
+async fn db_update_user_profile(
+    pool: &PgPool,
+    schema: &ProfileSchema,
+    user_id: &str,
+    changes: Map<String, Value>,
+    is_admin: bool,
+) -> AppResult<UserProfile> {
+    let map_err = |e: sqlx::Error| {
+        error!("Database error updating user profile: {}", e);
+        AppError::Database {
+            message: format!("Database error: {}", e),
+            source: None,
+        }
+    };
+    let mut tx = pool.begin().await.map_err(map_err)?;
+    let row = sqlx::query("SELECT profile FROM users WHERE user_id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| user_not_found(user_id))?;
+    let fields = schema
+        .apply(&profile_fields(&row)?, changes, is_admin)
+        .map_err(|reason| AppError::Validation {
+            field: "profile".to_string(),
+            reason,
+        })?;
+
+    let now = chrono::Utc::now();
+    sqlx::query("UPDATE users SET profile = $1, updated_at = $2 WHERE user_id = $3")
+        .bind(Value::Object(fields.clone()))
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(map_err)?;
+    tx.commit().await.map_err(map_err)?;
+
+    debug!("Updated profile of user {}", user_id);
+    Ok(UserProfile {
+        user_id: user_id.to_string(),
+        fields,
+        updated_at: now,
+    })
+}
+
+/// Get a user's profile, from the profile cache when it is fresh
+pub async fn get_user_profile_async(user_id: &str) -> AppResult<UserProfile> {
+    if let Some((cached_at, profile)) = profile_cache().get(user_id)
+        && cached_at.elapsed() < PROFILE_CACHE_TTL
+    {
+        return Ok(profile.clone());
+    }
+    let db = get_db_pool()?;
+    let profile = db_get_user_profile(db.pool(), user_id).await?;
+    cache_profile(&profile);
+    Ok(profile)
+}
+
+/// Get a user's profile (for blocking contexts, e.g. JS host functions)
+pub fn get_user_profile(user_id: &str) -> AppResult<UserProfile> {
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(get_user_profile_async(user_id))
+    })
+}
+
+/// Change fields of a user's profile
+///
+/// Fields in `changes` set to null are removed and the others replaced; the
+/// resulting profile must match the profile schema. Only administrators
+/// (`is_admin`) may change fields the schema marks `readOnly`.
+pub async fn update_user_profile_async(
+    user_id: &str,
+    changes: Map<String, Value>,
+    is_admin: bool,
+) -> AppResult<UserProfile> {
+    let schema = profile_schema().ok_or_else(|| AppError::Validation {
+        field: "profile".to_string(),
+        reason: "No profile fields are configured (auth.profile_schema)".to_string(),
+    })?;
+    let db = get_db_pool()?;
+    let profile = db_update_user_profile(db.pool(), schema, user_id, changes, is_admin).await?;
+    cache_profile(&profile);
+    Ok(profile)
+}
+
+/// Blocking variant of [`update_user_profile_async`]
+pub fn update_user_profile(
+    user_id: &str,
+    changes: Map<String, Value>,
+    is_admin: bool,
+) -> AppResult<UserProfile> {
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current()
+            .block_on(update_user_profile_async(user_id, changes, is_admin))
+    })
+}
+
+/// Prefix of script collaborator entries that name a group instead of a
+/// user, e.g. `group:support`. Every member of the group collaborates.
+pub const GROUP_PRINCIPAL_PREFIX: &str = "group:";
+
+/// Longest group name
+pub const MAX_GROUP_NAME_LENGTH: usize = 64;
+
+/// How long the groups of a user read from the database are used for route
+/// checks and `request.auth.groups`. Membership changes refresh them on the
+/// instance that made them; other instances see them once their copy expires.
+const GROUP_MEMBERSHIP_CACHE_TTL: Duration = Duration::from_secs(30);
+
+static GROUP_MEMBERSHIP_CACHE: OnceLock<Mutex<HashMap<String, (Instant, Vec<String>)>>> =
+    OnceLock::new();
+
+/// A named group of users (a team)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserGroup {
+    pub name: String,
+    pub description: Option<String>,
+    /// Roles members get when they sign in, besides their own
+    pub roles: Vec<UserRole>,
+    /// User IDs of the members
+    pub members: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Check a group name: 1-64 lowercase letters, digits, `-`, `_` or `.`,
+/// starting with a letter or digit
+pub fn validate_group_name(name: &str) -> AppResult<()> {
+    let valid = name.len() <= MAX_GROUP_NAME_LENGTH
+        && name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_lowercase() || first.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Validation {
+            field: "name".to_string(),
+            reason: format!(
+                "Group name '{}' must be 1-{} lowercase letters, digits, '-', '_' or '.', starting with a letter or digit",
+                name, MAX_GROUP_NAME_LENGTH
+            ),
+        })
+    }
+}
+
+/// The group named by a script collaborator entry, if it names one
+pub fn group_principal(collaborator: &str) -> Option<&str> {
+    collaborator.strip_prefix(GROUP_PRINCIPAL_PREFIX)
+}
+
+fn group_membership_cache() -> MutexGuard<'static, HashMap<String, (Instant, Vec<String>)>> {
+    GROUP_MEMBERSHIP_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn group_not_found(name: &str) -> AppError {
+    AppError::Validation {
+        field: "name".to_string(),
+        reason: format!("Group not found: {}", name),
+    }
+}
+
+fn group_db_error(action: &str, e: sqlx::Error) -> AppError {
+    error!("Database error {}: {}", action, e);
+    AppError::Database {
+        message: format!("Database error: {}", e),
+        source: None,
+    }
+}
+
+/// Convert a database row into a UserGroup
+fn convert_row_to_group(row: &PgRow) -> AppResult<UserGroup> {
+    let map_err = |e: sqlx::Error| AppError::Database {
+        message: e.to_string(),
+        source: None,
+    };
+    let is_admin: bool = row.try_get("is_admin").map_err(map_err)?;
+    let is_editor: bool = row.try_get("is_editor").map_err(map_err)?;
+    let mut roles = Vec::new();
+    if is_editor {
+        roles.push(UserRole::Editor);
+    }
+    if is_admin {
+        roles.push(UserRole::Administrator);
+    }
+    Ok(UserGroup {
+        name: row.try_get("name").map_err(map_err)?,
+        description: row.try_get("description").map_err(map_err)?,
+        roles,
+        members: row.try_get("members").map_err(map_err)?,
+        created_at: row.try_get("created_at").map_err(map_err)?,
+        updated_at: row.try_get("updated_at").map_err(map_err)?,
+    })
+}
+
+/// Database-backed list groups, or the one group named `name`
+async fn db_list_groups(pool: &PgPool, name: Option<&str>) -> AppResult<Vec<UserGroup>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT g.name, g.description, g.is_admin, g.is_editor, g.created_at, g.updated_at,
+               COALESCE(array_agg(m.user_id ORDER BY m.user_id)
+                        FILTER (WHERE m.user_id IS NOT NULL), '{}') AS members
+        FROM user_groups g
+        LEFT JOIN user_group_members m ON m.group_name = g.name
+        WHERE $1::TEXT IS NULL OR g.name = $1
+        GROUP BY g.name
+        ORDER BY g.name
+        "#,
+    )
+    .bind(name)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| group_db_error("listing groups", e))?;
+
+    rows.iter().map(convert_row_to_group).collect()
+}
+
+/// Database-backed create group
+async fn db_create_group(
+    pool: &PgPool,
+    name: &str,
+    description: Option<&str>,
+    is_admin: bool,
+    is_editor: bool,
+) -> AppResult<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_groups (name, description, is_admin, is_editor)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name) DO NOTHING
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(is_admin)
+    .bind(is_editor)
+    .execute(pool)
+    .await
+    .map_err(|e| group_db_error("creating group", e))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed update group
+async fn db_update_group(
+    pool: &PgPool,
+    name: &str,
+    description: Option<&str>,
+    is_admin: bool,
+    is_editor: bool,
+) -> AppResult<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE user_groups
+        SET description = $2, is_admin = $3, is_editor = $4, updated_at = NOW()
+        WHERE name = $1
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(is_admin)
+    .bind(is_editor)
+    .execute(pool)
+    .await
+    .map_err(|e| group_db_error("updating group", e))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed delete group. The group's script collaborator entries go
+/// with it, so a new group of the same name does not inherit them.
+async fn db_delete_group(pool: &PgPool, name: &str) -> AppResult<bool> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| group_db_error("deleting group", e))?;
+    sqlx::query("DELETE FROM script_collaborators WHERE user_id = $1")
+        .bind(format!("{}{}", GROUP_PRINCIPAL_PREFIX, name))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| group_db_error("deleting group", e))?;
+    let result = sqlx::query("DELETE FROM user_groups WHERE name = $1")
+        .bind(name)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| group_db_error("deleting group", e))?;
+    tx.commit()
+        .await
+        .map_err(|e| group_db_error("deleting group", e))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed add group member. Returns whether the user was added,
+/// false when they were a member already.
+async fn db_add_group_member(pool: &PgPool, name: &str, user_id: &str) -> AppResult<bool> {
+    let row = sqlx::query(
+        r#"
+        WITH inserted AS (
+            INSERT INTO user_group_members (group_name, user_id)
+            SELECT g.name, u.user_id
+            FROM user_groups g, users u
+            WHERE g.name = $1 AND u.user_id = $2
+            ON CONFLICT DO NOTHING
+            RETURNING 1
+        )
+        SELECT EXISTS(SELECT 1 FROM user_groups WHERE name = $1) AS group_exists,
+               EXISTS(SELECT 1 FROM users WHERE user_id = $2) AS user_exists,
+               EXISTS(SELECT 1 FROM inserted) AS added
+        "#,
+    )
+    .bind(name)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| group_db_error("adding group member", e))?;
+
+    let flag = |column: &str| -> AppResult<bool> {
+        row.try_get(column).map_err(|e| AppError::Database {
+            message: e.to_string(),
+            source: None,
+        })
+    };
+    if !flag("group_exists")? {
+        return Err(group_not_found(name));
+    }
+    if !flag("user_exists")? {
+        return Err(user_not_found(user_id));
+    }
+    flag("added")
+}
+
+/// Database-backed remove group member
+async fn db_remove_group_member(pool: &PgPool, name: &str, user_id: &str) -> AppResult<bool> {
+    let result =
+        sqlx::query("DELETE FROM user_group_members WHERE group_name = $1 AND user_id = $2")
+            .bind(name)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map_err(|e| group_db_error("removing group member", e))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Database-backed get the names of the groups of a user
+async fn db_get_user_groups(pool: &PgPool, user_id: &str) -> AppResult<Vec<String>> {
+    sqlx::query_scalar(
+        "SELECT group_name FROM user_group_members WHERE user_id = $1 ORDER BY group_name",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| group_db_error("getting user groups", e))
+}
+
+/// Split roles into the (is_admin, is_editor) flags groups are stored with
+fn group_role_flags(roles: &[UserRole]) -> (bool, bool) {
+    (
+        roles.contains(&UserRole::Administrator),
+        roles.contains(&UserRole::Editor),
+    )
+}
+
+/// List all groups with their members (for admin purposes)
+pub fn list_groups() -> AppResult<Vec<UserGroup>> {
+    let db = get_db_pool()?;
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_list_groups(db.pool(), None))
+    })
+}
+
+/// Get a group with its members
+pub fn get_group(name: &str) -> AppResult<UserGroup> {
+    let db = get_db_pool()?;
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_list_groups(db.pool(), Some(name)))
+    })?
+    .pop()
+    .ok_or_else(|| group_not_found(name))
+}
+
+/// Create a group whose members get `roles` when they sign in
+pub fn create_group(
+    name: &str,
+    description: Option<&str>,
+    roles: &[UserRole],
+) -> AppResult<UserGroup> {
+    validate_group_name(name)?;
+    let (is_admin, is_editor) = group_role_flags(roles);
+    let db = get_db_pool()?;
+    let created = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_create_group(
+            db.pool(),
+            name,
+            description,
+            is_admin,
+            is_editor,
+        ))
+    })?;
+    if !created {
+        return Err(AppError::Validation {
+            field: "name".to_string(),
+            reason: format!("Group already exists: {}", name),
+        });
+    }
+    debug!("Created group: {}", name);
+    get_group(name)
+}
+
+/// Update a group
+///
+/// Replaces the group's description and roles. Members get changed roles
+/// on their next sign-in.
+pub fn update_group(
+    name: &str,
+    description: Option<&str>,
+    roles: &[UserRole],
+) -> AppResult<UserGroup> {
+    let (is_admin, is_editor) = group_role_flags(roles);
+    let db = get_db_pool()?;
+    let updated = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_update_group(
+            db.pool(),
+            name,
+            description,
+            is_admin,
+            is_editor,
+        ))
+    })?;
+    if !updated {
+        return Err(group_not_found(name));
+    }
+    debug!("Updated group: {}", name);
+    get_group(name)
+}
+
+/// Delete a group, its memberships and its script collaborations
+pub fn delete_group(name: &str) -> AppResult<bool> {
+    let db = get_db_pool()?;
+    let deleted = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_delete_group(db.pool(), name))
+    })?;
+    if deleted {
+        group_membership_cache().clear();
+        debug!("Deleted group: {}", name);
+    }
+    Ok(deleted)
+}
+
+/// Add a user to a group. Returns false when the user was a member already.
+pub fn add_group_member(name: &str, user_id: &str) -> AppResult<bool> {
+    let db = get_db_pool()?;
+    let added = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_add_group_member(db.pool(), name, user_id))
+    })?;
+    group_membership_cache().remove(user_id);
+    if added {
+        debug!("Added user {} to group {}", user_id, name);
+    }
+    Ok(added)
+}
+
+/// Remove a user from a group
+pub fn remove_group_member(name: &str, user_id: &str) -> AppResult<bool> {
+    let db = get_db_pool()?;
+    let removed = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(db_remove_group_member(db.pool(), name, user_id))
+    })?;
+    group_membership_cache().remove(user_id);
+    if removed {
+        debug!("Removed user {} from group {}", user_id, name);
+    }
+    Ok(removed)
+}
+
+/// Names of the groups a user is a member of, from the membership cache
+/// when it is fresh
+pub async fn get_user_groups_async(user_id: &str) -> AppResult<Vec<String>> {
+    if let Some((cached_at, groups)) = group_membership_cache().get(user_id)
+        && cached_at.elapsed() < GROUP_MEMBERSHIP_CACHE_TTL
+    {
+        return Ok(groups.clone());
+    }
+    let db = get_db_pool()?;
+    let groups = db_get_user_groups(db.pool(), user_id).await?;
+    let mut cache = group_membership_cache();
+    if cache.len() >= CACHE_PRUNE_SIZE {
+        cache.retain(|_, (cached_at, _)| cached_at.elapsed() < GROUP_MEMBERSHIP_CACHE_TTL);
+    }
+    cache.insert(user_id.to_string(), (Instant::now(), groups.clone()));
+    Ok(groups)
+}
+
+/// Names of the groups of a user (for blocking contexts, e.g. JS host functions)
+pub fn get_user_groups(user_id: &str) -> AppResult<Vec<String>> {
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(get_user_groups_async(user_id))
+    })
+}
+
+/// Roles a user gets from the groups they are a member of
+pub async fn get_group_roles_async(user_id: &str) -> AppResult<Vec<UserRole>> {
+    let db = get_db_pool()?;
+    let row = sqlx::query(
+        r#"
+        SELECT COALESCE(bool_or(g.is_admin), FALSE) AS is_admin,
+               COALESCE(bool_or(g.is_editor), FALSE) AS is_editor
+        FROM user_groups g
+        JOIN user_group_members m ON m.group_name = g.name
+        WHERE m.user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db.pool())
+    .await
+    .map_err(|e| group_db_error("getting group roles", e))?;
+
+    let map_err = |e: sqlx::Error| AppError::Database {
+        message: e.to_string(),
+        source: None,
+    };
+    let mut roles = Vec::new();
+    if row.try_get::<bool, _>("is_editor").map_err(map_err)? {
+        roles.push(UserRole::Editor);
+    }
+    if row.try_get::<bool, _>("is_admin").map_err(map_err)? {
+        roles.push(UserRole::Administrator);
+    }
+    Ok(roles)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1163,6 +2098,7 @@ mod tests {
                 "google".to_string(),
                 "google_admin".to_string(),
                 &bootstrap_admins,
+                &[],
             )
             .await
             .unwrap();
@@ -1179,6 +2115,7 @@ mod tests {
                 "google".to_string(),
                 "google_regular".to_string(),
                 &bootstrap_admins,
+                &[],
             )
             .await
             .unwrap();
@@ -1208,6 +2145,7 @@ mod tests {
                 "google".to_string(),
                 "google_admin_case".to_string(),
                 &bootstrap_admins,
+                &[],
             )
             .await
             .unwrap();
@@ -1217,4 +2155,117 @@ mod tests {
             assert!(admin_user.has_role(&UserRole::Administrator));
         });
     }
+
+    #[test]
+    fn test_bootstrap_role_flags() {
+        let admins = vec!["Admin@Example.com".to_string()];
+        let rules = vec![
+            crate::auth::BootstrapRoleRule {
+                pattern: "@example.com".to_string(),
+                roles: vec![UserRole::Editor],
+            },
+            crate::auth::BootstrapRoleRule {
+                pattern: "ops-*@example.org".to_string(),
+                roles: vec![UserRole::Editor, UserRole::Administrator],
+            },
+        ];
+
+        assert_eq!(
+            bootstrap_role_flags("admin@example.com", &admins, &rules),
+            (true, true)
+        );
+        assert_eq!(
+            bootstrap_role_flags("dev@example.com", &admins, &rules),
+            (false, true)
+        );
+        assert_eq!(
+            bootstrap_role_flags("ops-alice@example.org", &admins, &rules),
+            (true, true)
+        );
+        assert_eq!(
+            bootstrap_role_flags("alice@example.org", &admins, &rules),
+            (false, false)
+        );
+        assert_eq!(
+            bootstrap_role_flags("admin@example.com", &admins, &[]),
+            (true, false)
+        );
+    }
+
+    #[test]
+    fn test_profile_schema() {
+        assert!(ProfileSchema::compile(serde_json::json!({ "type": "string" })).is_err());
+
+        let schema = ProfileSchema::compile(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "locale": { "type": "string", "maxLength": 10 },
+                "newsletter": { "type": "boolean" },
+                "plan": { "type": "string", "readOnly": true }
+            },
+            "additionalProperties": false
+        }))
+        .unwrap();
+        let changes = |value: serde_json::Value| value.as_object().unwrap().clone();
+
+        let fields = schema
+            .apply(
+                &Map::new(),
+                changes(serde_json::json!({ "locale": "fi-FI", "newsletter": true })),
+                false,
+            )
+            .unwrap();
+        assert_eq!(fields.len(), 2);
+
+        // Null removes a field
+        let fields = schema
+            .apply(
+                &fields,
+                changes(serde_json::json!({ "newsletter": null })),
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            serde_json::Value::Object(fields.clone()),
+            serde_json::json!({ "locale": "fi-FI" })
+        );
+
+        assert!(
+            schema
+                .apply(&fields, changes(serde_json::json!({ "locale": 5 })), false)
+                .is_err()
+        );
+        assert!(
+            schema
+                .apply(&fields, changes(serde_json::json!({ "unknown": 1 })), false)
+                .is_err()
+        );
+
+        // Read-only fields are for administrators
+        let plan = changes(serde_json::json!({ "plan": "pro" }));
+        assert!(schema.apply(&fields, plan.clone(), false).is_err());
+        let fields = schema.apply(&fields, plan.clone(), true).unwrap();
+        assert!(schema.apply(&fields, plan, false).is_ok());
+    }
+
+    #[test]
+    fn test_validate_group_name() {
+        for name in ["support", "team-a", "eu_ops", "v2.beta", "7seas"] {
+            assert!(validate_group_name(name).is_ok(), "{}", name);
+        }
+        let too_long = "a".repeat(MAX_GROUP_NAME_LENGTH + 1);
+        for name in [
+            "",
+            "Support",
+            "-team",
+            "two words",
+            "group:x",
+            too_long.as_str(),
+        ] {
+            assert!(validate_group_name(name).is_err(), "{}", name);
+        }
+
+        assert_eq!(group_principal("group:support"), Some("support"));
+        assert_eq!(group_principal("user-123"), None);
+    }
 }
@@ -0,0 +1,550 @@
+//! Outgoing webhooks with retries (`webhooks.send`).
+//!
+//! A webhook sent with `fetch` is lost when its target is down. Instead,
+//! `webhooks.send(url, payload, options)` queues a delivery in the
+//! `webhook_deliveries` table and returns its ID at once. When the calling
+//! handler runs in a transaction, the delivery is queued in it, so a rolled
+//! back handler sends nothing.
+//!
+//! A background worker posts due deliveries with the same URL checks as
+//! `fetch`. A 2xx response marks a delivery `delivered`; anything else is
+//! retried with doubling delays (`[javascript.webhooks]`) until the delivery
+//! has used its attempts and is marked `failed`.
+//!
+//! Every request carries `X-Webhook-Id`, which stays the same across
+//! retries so receivers can drop duplicates, `X-Webhook-Timestamp` and
+//! `X-Webhook-Attempt`. With the `secret` option naming a script secret,
+//! `X-Webhook-Signature` carries `sha256=` and the hex HMAC-SHA256 of
+//! `{timestamp}.{body}` keyed with the secret.
+//!
+//! Scripts follow their deliveries with `webhooks.get(id)`; administrators
+//! see pending and failed deliveries of every script with the
+//! `webhookDeliveries` GraphQL query.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::WebhookConfig;
+use crate::repository::{self, Repository as _};
+
+/// Header names the engine sets on every delivery; scripts may not set them
+const RESERVED_HEADER_PREFIX: &str = "x-webhook-";
+
+/// Most deliveries one worker pass claims
+const CLAIM_BATCH_SIZE: i64 = 16;
+
+/// How often the worker looks for due deliveries when not woken
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often finished deliveries past their retention are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Most deliveries `webhooks.list` and the GraphQL view return
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+static SETTINGS: OnceLock<RwLock<WebhookConfig>> = OnceLock::new();
+
+static WORKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the worker when a delivery is queued
+static WAKE: Notify = Notify::const_new();
+
+fn settings() -> &'static RwLock<WebhookConfig> {
+    SETTINGS.get_or_init(Default::default)
+}
+
+/// Apply the webhook configuration. Called once at server startup.
+pub fn configure(config: &WebhookConfig) {
+    match settings().write() {
+        Ok(mut guard) => *guard = config.clone(),
+        Err(poisoned) => *poisoned.into_inner() = config.clone(),
+    }
+}
+
+fn current_settings() -> WebhookConfig {
+    match settings().read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// State of a delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    Delivered,
+    /// Used all its attempts
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(DeliveryStatus::Pending),
+            "delivered" => Some(DeliveryStatus::Delivered),
+            "failed" => Some(DeliveryStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A queued webhook and how its delivery went
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub script_uri: String,
+    pub url: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When the next attempt is due; meaningful while pending
+    pub next_attempt_at: DateTime<Utc>,
+    /// HTTP status of the last attempt, if it got a response
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery to queue
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewWebhook {
+    pub id: Uuid,
+    pub script_uri: String,
+    pub url: String,
+    pub payload: String,
+    pub content_type: String,
+    pub headers: HashMap<String, String>,
+    /// Name of the script secret the body is signed with
+    pub secret_name: Option<String>,
+    pub max_attempts: i32,
+}
+
+/// A delivery claimed by the worker for one attempt
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimedWebhook {
+    pub webhook: NewWebhook,
+    /// Including the attempt being made
+    pub attempts: i32,
+}
+
+/// What one attempt changes about a delivery
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttemptOutcome {
+    Delivered {
+        status_code: i32,
+    },
+    Retry {
+        status_code: Option<i32>,
+        error: String,
+        next_attempt_at: DateTime<Utc>,
+    },
+    Failed {
+        status_code: Option<i32>,
+        error: String,
+    },
+}
+
+/// Options of `webhooks.send`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct SendOptions {
+    /// Extra request headers; values may use `{{secret:name}}` like `fetch`
+    pub headers: HashMap<String, String>,
+    /// Name of the script secret to sign the body with
+    pub secret: Option<String>,
+    /// Defaults to `application/json` for objects and `text/plain` for
+    /// strings
+    pub content_type: Option<String>,
+    pub max_attempts: Option<u32>,
+}
+
+/// Filter of `webhooks.list` and the GraphQL view
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ListOptions {
+    pub status: Option<DeliveryStatus>,
+    pub script_uri: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Queue `payload` for delivery to `url` and wake the worker
+pub fn send(
+    script_uri: &str,
+    url: &str,
+    payload: String,
+    default_content_type: &str,
+    options: &SendOptions,
+) -> Result<WebhookDelivery, String> {
+    let config = current_settings();
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Webhook URLs must use http or https".to_string());
+    }
+    if payload.len() > config.max_payload_bytes {
+        return Err(format!(
+            "payload must not exceed {} bytes",
+            config.max_payload_bytes
+        ));
+    }
+    for (name, value) in &options.headers {
+        if name
+            .to_ascii_lowercase()
+            .starts_with(RESERVED_HEADER_PREFIX)
+            || name.eq_ignore_ascii_case("content-type")
+        {
+            return Err(format!("Header '{}' is set by the engine", name));
+        }
+        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}'", name))?;
+        reqwest::header::HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value of header '{}'", name))?;
+    }
+    let max_attempts = options.max_attempts.unwrap_or(config.max_attempts);
+    if max_attempts == 0 || max_attempts > config.max_attempts_limit {
+        return Err(format!(
+            "maxAttempts must be between 1 and {}",
+            config.max_attempts_limit
+        ));
+    }
+    if let Some(secret) = &options.secret
+        && repository::resolve_secret_db(script_uri, secret, None).is_none()
+    {
+        return Err(format!("Secret '{}' is not set", secret));
+    }
+
+    let webhook = NewWebhook {
+        id: Uuid::new_v4(),
+        script_uri: script_uri.to_string(),
+        url: parsed.to_string(),
+        payload,
+        content_type: options
+            .content_type
+            .clone()
+            .unwrap_or_else(|| default_content_type.to_string()),
+        headers: options.headers.clone(),
+        secret_name: options.secret.clone(),
+        max_attempts: max_attempts as i32,
+    };
+    let delivery = repository::enqueue_webhook(&webhook).map_err(|e| e.to_string())?;
+    debug!(
+        script_uri = %script_uri,
+        id = %delivery.id,
+        url = %delivery.url,
+        "Queued webhook delivery"
+    );
+    WAKE.notify_one();
+    Ok(delivery)
+}
+
+/// A delivery of `script_uri`
+pub fn get(script_uri: &str, id: &str) -> Result<Option<WebhookDelivery>, String> {
+    let Ok(id) = Uuid::parse_str(id) else {
+        return Ok(None);
+    };
+    repository::get_webhook_delivery(id)
+        .map(|delivery| delivery.filter(|delivery| delivery.script_uri == script_uri))
+        .map_err(|e| e.to_string())
+}
+
+/// Deliveries matching `options`, newest first
+pub fn list(options: &ListOptions) -> Result<Vec<WebhookDelivery>, String> {
+    let limit = options.limit.unwrap_or(100);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return Err(format!("limit must be between 1 and {}", MAX_LIST_LIMIT));
+    }
+    repository::list_webhook_deliveries(options.status, options.script_uri.as_deref(), limit)
+        .map_err(|e| e.to_string())
+}
+
+/// Delay before the retry that follows attempt number `attempts`
+fn retry_delay(config: &WebhookConfig, attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 32) as u32;
+    let delay = config
+        .retry_delay_ms
+        .saturating_mul(1u64 << doublings)
+        .min(config.max_retry_delay_ms);
+    Duration::from_millis(delay)
+}
+
+/// `X-Webhook-Signature` of `body` sent at `timestamp`
+fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Make one attempt; returns the response status, or the status (if any)
+/// and why the attempt failed
+fn attempt(claimed: &ClaimedWebhook, config: &WebhookConfig) -> Result<i32, (Option<i32>, String)> {
+    let webhook = &claimed.webhook;
+    let timestamp = Utc::now().timestamp();
+    let mut headers = webhook.headers.clone();
+    headers.insert("Content-Type".to_string(), webhook.content_type.clone());
+    headers.insert("X-Webhook-Id".to_string(), webhook.id.to_string());
+    headers.insert("X-Webhook-Timestamp".to_string(), timestamp.to_string());
+    headers.insert(
+        "X-Webhook-Attempt".to_string(),
+        claimed.attempts.to_string(),
+    );
+    if let Some(secret_name) = &webhook.secret_name {
+        let secret = repository::resolve_secret_db(&webhook.script_uri, secret_name, None)
+            .ok_or_else(|| (None, format!("Secret '{}' is not set", secret_name)))?;
+        headers.insert(
+            "X-Webhook-Signature".to_string(),
+            signature(&secret, timestamp, &webhook.payload),
+        );
+    }
+
+    let client = crate::http_client::HttpClient::new().map_err(|e| (None, e.to_string()))?;
+    let options = crate::http_client::FetchOptions {
+        method: "POST".to_string(),
+        headers: Some(headers),
+        body: Some(webhook.payload.clone()),
+        timeout_ms: Some(config.timeout_ms),
+    };
+    let response = client
+        .fetch(
+            webhook.url.clone(),
+            options,
+            Some(&webhook.script_uri),
+            None,
+        )
+        .map_err(|e| (None, e.to_string()))?;
+    let status = response.status as i32;
+    if response.ok {
+        Ok(status)
+    } else {
+        Err((Some(status), format!("HTTP {}", status)))
+    }
+}
+
+/// What an attempt's result means for the delivery
+fn outcome(
+    config: &WebhookConfig,
+    claimed: &ClaimedWebhook,
+    result: Result<i32, (Option<i32>, String)>,
+) -> AttemptOutcome {
+    match result {
+        Ok(status_code) => AttemptOutcome::Delivered { status_code },
+        Err((status_code, error)) if claimed.attempts >= claimed.webhook.max_attempts => {
+            AttemptOutcome::Failed { status_code, error }
+        }
+        Err((status_code, error)) => AttemptOutcome::Retry {
+            status_code,
+            error,
+            next_attempt_at: Utc::now()
+                + chrono::Duration::from_std(retry_delay(config, claimed.attempts))
+                    .unwrap_or_else(|_| chrono::Duration::hours(1)),
+        },
+    }
+}
+
+/// Attempt one claimed delivery and record the outcome
+async fn run_attempt(config: WebhookConfig, claimed: ClaimedWebhook) {
+    let attempt_config = config.clone();
+    let attempt_claimed = claimed.clone();
+    let result = tokio::task::spawn_blocking(move || attempt(&attempt_claimed, &attempt_config))
+        .await
+        .unwrap_or_else(|e| Err((None, format!("Delivery task failed: {}", e))));
+    let outcome = outcome(&config, &claimed, result);
+
+    let webhook = &claimed.webhook;
+    match &outcome {
+        AttemptOutcome::Delivered { status_code } => debug!(
+            id = %webhook.id,
+            url = %webhook.url,
+            status_code,
+            "Webhook delivered"
+        ),
+        AttemptOutcome::Retry { error, .. } => debug!(
+            id = %webhook.id,
+            url = %webhook.url,
+            attempt = claimed.attempts,
+            "Webhook attempt failed, will retry: {}",
+            error
+        ),
+        AttemptOutcome::Failed { error, .. } => {
+            warn!(
+                id = %webhook.id,
+                url = %webhook.url,
+                attempts = claimed.attempts,
+                "Webhook delivery failed: {}",
+                error
+            );
+            repository::insert_log_message_async(
+                &webhook.script_uri,
+                &format!(
+                    "webhook {} to {} failed after {} attempts: {}",
+                    webhook.id, webhook.url, claimed.attempts, error
+                ),
+                "ERROR",
+            )
+            .await;
+        }
+    }
+    if let Err(e) = repository::get_repository()
+        .finish_webhook_attempt(webhook.id, &outcome)
+        .await
+    {
+        warn!(id = %webhook.id, "Failed to record webhook attempt: {}", e);
+    }
+}
+
+/// Start the background task that delivers queued webhooks and deletes
+/// finished ones past their retention. Only the first call starts a worker.
+pub fn spawn_worker() {
+    if WORKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        info!("Webhook delivery worker started");
+        let mut last_purge: Option<tokio::time::Instant> = None;
+        loop {
+            let config = current_settings();
+            let repo = repository::get_repository();
+            // Attempts hold their claim for their timeout and a margin
+            let lock_for = Duration::from_millis(config.timeout_ms) + Duration::from_secs(30);
+            match repo.claim_webhooks(CLAIM_BATCH_SIZE, lock_for).await {
+                Ok(claimed) => {
+                    for claimed in claimed {
+                        tokio::spawn(run_attempt(config.clone(), claimed));
+                    }
+                }
+                Err(e) => warn!("Failed to claim webhook deliveries: {}", e),
+            }
+
+            if last_purge.is_none_or(|at| at.elapsed() >= PURGE_INTERVAL) {
+                last_purge = Some(tokio::time::Instant::now());
+                let before = Utc::now() - chrono::Duration::hours(config.retention_hours as i64);
+                match repo.purge_webhook_deliveries(before).await {
+                    Ok(0) => {}
+                    Ok(count) => debug!("Removed {} finished webhook deliveries", count),
+                    Err(e) => warn!("Failed to purge webhook deliveries: {}", e),
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = WAKE.notified() => {}
+            }
+        }
+    });
+}
+
+/// The body and default content type of a `webhooks.send` payload: strings
+/// are sent as they are, anything else as JSON
+pub fn payload_body(payload: &Value) -> Result<(String, &'static str), String> {
+    match payload {
+        Value::String(text) => Ok((text.clone(), "text/plain; charset=utf-8")),
+        Value::Null => Err("payload is required".to_string()),
+        other => serde_json::to_string(other)
+            .map(|json| (json, "application/json"))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claimed(attempts: i32) -> ClaimedWebhook {
+        ClaimedWebhook {
+            webhook: NewWebhook {
+                id: Uuid::new_v4(),
+                script_uri: "https://example.com/orders".to_string(),
+                url: "https://hooks.example.com/orders".to_string(),
+                payload: "{}".to_string(),
+                content_type: "application/json".to_string(),
+                headers: HashMap::new(),
+                secret_name: None,
+                max_attempts: 3,
+            },
+            attempts,
+        }
+    }
+
+    #[test]
+    fn test_signature() {
+        // Computed with: printf '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            signature("secret", 1_700_000_000, r#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+    }
+
+    #[test]
+    fn test_retries() {
+        let config = WebhookConfig {
+            retry_delay_ms: 1000,
+            max_retry_delay_ms: 5000,
+            ..WebhookConfig::default()
+        };
+        assert_eq!(retry_delay(&config, 1), Duration::from_secs(1));
+        assert_eq!(retry_delay(&config, 3), Duration::from_secs(4));
+        assert_eq!(retry_delay(&config, 30), Duration::from_secs(5));
+
+        assert_eq!(
+            outcome(&config, &claimed(1), Ok(204)),
+            AttemptOutcome::Delivered { status_code: 204 }
+        );
+        assert!(matches!(
+            outcome(
+                &config,
+                &claimed(2),
+                Err((Some(503), "HTTP 503".to_string()))
+            ),
+            AttemptOutcome::Retry {
+                status_code: Some(503),
+                ..
+            }
+        ));
+        assert_eq!(
+            outcome(&config, &claimed(3), Err((None, "refused".to_string()))),
+            AttemptOutcome::Failed {
+                status_code: None,
+                error: "refused".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_payload_body() {
+        assert_eq!(
+            payload_body(&json!({ "order": 1 })),
+            Ok((r#"{"order":1}"#.to_string(), "application/json"))
+        );
+        assert_eq!(
+            payload_body(&json!("ping")),
+            Ok(("ping".to_string(), "text/plain; charset=utf-8"))
+        );
+        assert!(payload_body(&Value::Null).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Pool of long-lived threads that execute scripts for HTTP requests.
+//!
+//! Script execution blocks, so it cannot run on the async runtime. Instead of
+//! a `spawn_blocking` thread per request, [`run`] queues the execution to one
+//! of a fixed set of worker threads. Each worker keeps its QuickJS runtimes
+//! between executions (see [`crate::runtime_pool`]), so a request only pays
+//! for a fresh context.
+//!
+//! Executions of the same script go to the same worker while it is idle, so
+//! they find its runtime and caches warm; when that worker is busy they go to
+//! the least loaded one. At most `javascript.max_concurrent_executions`
+//! executions are queued or running at once; more are rejected as
+//! [`WorkerPoolError::Saturated`] rather than queued without bound.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, mpsc};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{error, info};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Why an execution did not run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkerPoolError {
+    /// The queue is full
+    Saturated,
+    /// The execution panicked
+    Panicked,
+}
+
+impl fmt::Display for WorkerPoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Saturated => write!(f, "All script workers are busy"),
+            Self::Panicked => write!(f, "Script worker panicked"),
+        }
+    }
+}
+
+impl std::error::Error for WorkerPoolError {}
+
+/// Point-in-time view of the pool
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkerPoolMetrics {
+    pub threads: usize,
+    /// Most executions queued or running at once
+    pub capacity: usize,
+    /// Workers executing a script
+    pub busy: usize,
+    /// Executions waiting for a worker
+    pub queued: usize,
+    pub completed: u64,
+    /// Executions rejected because the queue was full
+    pub rejected: u64,
+    /// Executions that ran on their script's preferred worker
+    pub affinity_hits: u64,
+    pub affinity_misses: u64,
+    pub avg_queue_wait_ms: f64,
+    pub max_queue_wait_ms: f64,
+}
+
+thread_local! {
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is a pool worker
+pub fn is_worker_thread() -> bool {
+    IS_WORKER.with(Cell::get)
+}
+
+/// Counters shared by the pool and its queued jobs
+#[derive(Default)]
+struct Shared {
+    in_flight: AtomicUsize,
+    busy: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    affinity_hits: AtomicU64,
+    affinity_misses: AtomicU64,
+    waits: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+impl Shared {
+    fn record_wait(&self, wait: Duration) {
+        let micros = wait.as_micros().min(u64::MAX as u128) as u64;
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+}
+
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    /// Jobs queued or running on this worker
+    load: Arc<AtomicUsize>,
+}
+
+/// Releases an execution's place in the pool when its job finishes or is
+/// dropped unrun
+struct Slot {
+    shared: Arc<Shared>,
+    load: Arc<AtomicUsize>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.load.fetch_sub(1, Ordering::Relaxed);
+        self.shared.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Fixed set of worker threads executing queued jobs
+pub struct WorkerPool {
+    workers: Vec<Worker>,
+    capacity: usize,
+    shared: Arc<Shared>,
+}
+
+impl WorkerPool {
+    /// Start `threads` workers accepting at most `capacity` queued or running
+    /// jobs. Workers enter the current Tokio runtime, if any, so scripts can
+    /// reach async services.
+    pub fn new(threads: usize, capacity: usize) -> Self {
+        let threads = threads.max(1);
+        let handle = tokio::runtime::Handle::try_current().ok();
+        let workers = (0..threads)
+            .map(|index| {
+                let (jobs, receiver) = mpsc::channel::<Job>();
+                let handle = handle.clone();
+                std::thread::Builder::new()
+                    .name(format!("js-worker-{}", index))
+                    .spawn(move || {
+                        let _runtime = handle.as_ref().map(|handle| handle.enter());
+                        IS_WORKER.with(|worker| worker.set(true));
+                        while let Ok(job) = receiver.recv() {
+                            job();
+                        }
+                    })
+                    .expect("Failed to spawn script worker thread");
+                Worker {
+                    jobs,
+                    load: Arc::new(AtomicUsize::new(0)),
+                }
+            })
+            .collect();
+        Self {
+            workers,
+            capacity: capacity.max(1),
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    /// The worker for a job: the key's preferred worker when it is idle,
+    /// otherwise the least loaded one
+    fn pick(&self, affinity_key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        affinity_key.hash(&mut hasher);
+        let preferred = (hasher.finish() % self.workers.len() as u64) as usize;
+        if self.workers[preferred].load.load(Ordering::Relaxed) == 0 {
+            self.shared.affinity_hits.fetch_add(1, Ordering::Relaxed);
+            return preferred;
+        }
+        let least_loaded = (0..self.workers.len())
+            .min_by_key(|index| {
+                (
+                    self.workers[*index].load.load(Ordering::Relaxed),
+                    *index != preferred,
+                )
+            })
+            .unwrap_or(preferred);
+        if least_loaded == preferred {
+            self.shared.affinity_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.shared.affinity_misses.fetch_add(1, Ordering::Relaxed);
+        }
+        least_loaded
+    }
+
+    /// Run `job` on a worker and wait for its result. A job whose caller
+    /// stopped waiting before a worker picked it up is skipped.
+    pub async fn run<F, T>(&self, affinity_key: &str, job: F) -> Result<T, WorkerPoolError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        if self.shared.in_flight.fetch_add(1, Ordering::Relaxed) >= self.capacity {
+            self.shared.in_flight.fetch_sub(1, Ordering::Relaxed);
+            self.shared.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(WorkerPoolError::Saturated);
+        }
+        let worker = &self.workers[self.pick(affinity_key)];
+        worker.load.fetch_add(1, Ordering::Relaxed);
+        let slot = Slot {
+            shared: Arc::clone(&self.shared),
+            load: Arc::clone(&worker.load),
+        };
+
+        let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+        let queued_at = Instant::now();
+        let queued: Job = Box::new(move || {
+            let shared = Arc::clone(&slot.shared);
+            shared.record_wait(queued_at.elapsed());
+            if result_tx.is_closed() {
+                return;
+            }
+            shared.busy.fetch_add(1, Ordering::Relaxed);
+            let result = std::panic::catch_unwind(AssertUnwindSafe(job));
+            shared.busy.fetch_sub(1, Ordering::Relaxed);
+            shared.completed.fetch_add(1, Ordering::Relaxed);
+            drop(slot);
+            match result {
+                Ok(result) => {
+                    let _ = result_tx.send(result);
+                }
+                Err(_) => error!("Script worker job panicked"),
+            }
+        });
+        if worker.jobs.send(queued).is_err() {
+            return Err(WorkerPoolError::Panicked);
+        }
+        result_rx.await.map_err(|_| WorkerPoolError::Panicked)
+    }
+
+    /// Queue `job` on every worker, outside the capacity limit, to prepare
+    /// the workers for later executions
+    pub fn run_on_each(&self, job: fn()) {
+        for worker in &self.workers {
+            let queued: Job = Box::new(move || {
+                if std::panic::catch_unwind(job).is_err() {
+                    error!("Script worker preparation panicked");
+                }
+            });
+            let _ = worker.jobs.send(queued);
+        }
+    }
+
+    pub fn metrics(&self) -> WorkerPoolMetrics {
+        let in_flight = self.shared.in_flight.load(Ordering::Relaxed);
+        let busy = self.shared.busy.load(Ordering::Relaxed);
+        let waits = self.shared.waits.load(Ordering::Relaxed);
+        let total_wait_micros = self.shared.total_wait_micros.load(Ordering::Relaxed);
+        WorkerPoolMetrics {
+            threads: self.workers.len(),
+            capacity: self.capacity,
+            busy,
+            queued: in_flight.saturating_sub(busy),
+            completed: self.shared.completed.load(Ordering::Relaxed),
+            rejected: self.shared.rejected.load(Ordering::Relaxed),
+            affinity_hits: self.shared.affinity_hits.load(Ordering::Relaxed),
+            affinity_misses: self.shared.affinity_misses.load(Ordering::Relaxed),
+            avg_queue_wait_ms: if waits == 0 {
+                0.0
+            } else {
+                total_wait_micros as f64 / waits as f64 / 1000.0
+            },
+            max_queue_wait_ms: self.shared.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+static POOL: OnceLock<WorkerPool> = OnceLock::new();
+
+/// Default worker count: two per CPU core, since scripts spend much of their
+/// time blocked on database and HTTP calls
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(4, |cores| cores.get() * 2)
+}
+
+/// Start the request worker pool. Called once at startup; returns false if
+/// the pool was already started.
+pub fn configure(threads: usize, capacity: usize) -> bool {
+    let threads = if threads == 0 {
+        default_threads()
+    } else {
+        threads
+    };
+    let mut started = false;
+    POOL.get_or_init(|| {
+        started = true;
+        info!(threads, capacity, "Starting script worker pool");
+        WorkerPool::new(threads, capacity)
+    });
+    started
+}
+
+fn pool() -> &'static WorkerPool {
+    POOL.get_or_init(|| WorkerPool::new(default_threads(), 100))
+}
+
+/// Run `job` on the request worker pool, preferring the worker that last ran
+/// `affinity_key`
+pub async fn run<F, T>(affinity_key: &str, job: F) -> Result<T, WorkerPoolError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    pool().run(affinity_key, job).await
+}
+
+/// Run `job` once on every worker of the request worker pool
+pub fn run_on_each_worker(job: fn()) {
+    pool().run_on_each(job);
+}
+
+/// Statistics of the request worker pool
+pub fn metrics() -> WorkerPoolMetrics {
+    pool().metrics()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runs_jobs_on_workers() {
+        let pool = WorkerPool::new(2, 10);
+        let result = pool
+            .run("script-a", || (is_worker_thread(), 6 * 7))
+            .await
+            .unwrap();
+        assert_eq!(result, (true, 42));
+        assert!(!is_worker_thread());
+
+        assert_eq!(
+            pool.run("script-a", || panic!("boom")).await,
+            Err::<(), _>(WorkerPoolError::Panicked)
+        );
+        // The worker survives a panicking job
+        assert_eq!(pool.run("script-a", || 1).await, Ok(1));
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.completed, 3);
+        assert_eq!(metrics.busy + metrics.queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_saturated() {
+        let pool = Arc::new(WorkerPool::new(1, 1));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let blocked = {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move {
+                pool.run("script-a", move || release_rx.recv().is_ok())
+                    .await
+            })
+        };
+        while pool.metrics().busy == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(
+            pool.run("script-b", || ()).await,
+            Err(WorkerPoolError::Saturated)
+        );
+        assert_eq!(pool.metrics().rejected, 1);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(blocked.await.unwrap(), Ok(true));
+        assert_eq!(pool.run("script-b", || 2).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_prefers_idle_affinity_worker() {
+        let pool = WorkerPool::new(4, 10);
+        let first = pool
+            .run("script-a", || {
+                std::thread::current().name().map(str::to_string)
+            })
+            .await
+            .unwrap();
+        let second = pool
+            .run("script-a", || {
+                std::thread::current().name().map(str::to_string)
+            })
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(pool.metrics().affinity_hits, 2);
+    }
+}
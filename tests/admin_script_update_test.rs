@@ -90,6 +90,7 @@ mod admin_script_update_tests {
             test_uri,
             updated_content,
             admin_user.user_id.as_deref(),
+            false,
         );
 
         // This should succeed even though the script has no owner
@@ -175,6 +176,7 @@ mod admin_script_update_tests {
             test_uri,
             updated_content,
             regular_user.user_id.as_deref(),
+            false,
         );
 
         assert!(
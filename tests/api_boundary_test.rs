@@ -1179,6 +1179,7 @@ async fn test_route_registry_list_routes_includes_stream_and_asset_routes() {
         script_uri: "test://privileged-route-introspection".to_string(),
         created_at: std::time::SystemTime::now(),
         updated_at: std::time::SystemTime::now(),
+        headers: std::collections::HashMap::new(),
     })
     .expect("Failed to create asset for route introspection test");
 
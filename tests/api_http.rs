@@ -9,7 +9,7 @@
 
 mod common;
 
-use aiwebengine::repository;
+use aiwebengine::{asset_registry, repository};
 use common::{TestContext, should_skip_integration_tests, wait_for_server};
 
 // ============================================================================
@@ -312,6 +312,877 @@ async fn test_head_request_falls_back_to_get_with_empty_body() {
     context.cleanup().await.expect("Failed to cleanup");
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_options_answered_with_allowed_methods() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let _ = repository::upsert_script(
+        "https://example.com/method_test",
+        include_str!("../scripts/test_scripts/method_test.js"),
+    );
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let client = reqwest::Client::new();
+    let allowed = "DELETE, GET, HEAD, OPTIONS, POST, PUT";
+
+    let options_response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("http://127.0.0.1:{}/api/test", port),
+        )
+        .send()
+        .await
+        .expect("OPTIONS request failed");
+    assert_eq!(options_response.status(), 204);
+    assert_eq!(
+        options_response
+            .headers()
+            .get("allow")
+            .and_then(|value| value.to_str().ok()),
+        Some(allowed)
+    );
+
+    // 405 responses list the allowed methods too
+    let patch_response = client
+        .patch(format!("http://127.0.0.1:{}/api/test", port))
+        .send()
+        .await
+        .expect("PATCH request failed");
+    assert_eq!(patch_response.status(), 405);
+    assert_eq!(
+        patch_response
+            .headers()
+            .get("allow")
+            .and_then(|value| value.to_str().ok()),
+        Some(allowed)
+    );
+
+    let options_not_found = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("http://127.0.0.1:{}/api/nonexistent", port),
+        )
+        .send()
+        .await
+        .expect("OPTIONS request to nonexistent path failed");
+    assert_eq!(options_not_found.status(), 404);
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_asset_route_applies_custom_headers() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/asset_headers_test";
+    let _ = repository::upsert_script(script_uri, "function init() {}");
+    repository::set_script_privileged(script_uri, true).expect("Failed to set privileged");
+    repository::upsert_asset(repository::Asset {
+        uri: "headers-test.txt".to_string(),
+        mimetype: "text/plain".to_string(),
+        content: b"report".to_vec(),
+        name: Some("headers-test.txt".to_string()),
+        script_uri: script_uri.to_string(),
+        created_at: std::time::SystemTime::now(),
+        updated_at: std::time::SystemTime::now(),
+        headers: std::collections::HashMap::from([
+            (
+                "Content-Disposition".to_string(),
+                "attachment; filename=\"report.txt\"".to_string(),
+            ),
+            ("Cache-Control".to_string(), "no-cache".to_string()),
+        ]),
+    })
+    .expect("Failed to create asset");
+
+    // The route-level Cache-Control overrides the one stored with the asset
+    let script = r#"
+        function init(context) {
+          routeRegistry.registerAssetRoute("/headers-test.txt", "headers-test.txt", {
+            headers: { "Cache-Control": "public, max-age=600" },
+          });
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let response = reqwest::Client::new()
+        .get(format!("http://127.0.0.1:{}/headers-test.txt", port))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    assert_eq!(
+        header("content-disposition").as_deref(),
+        Some("attachment; filename=\"report.txt\"")
+    );
+    assert_eq!(
+        header("cache-control").as_deref(),
+        Some("public, max-age=600")
+    );
+    assert_eq!(
+        header("content-type").as_deref(),
+        Some("text/plain; charset=utf-8")
+    );
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_versioned_asset_route_serves_hashed_url() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/versioned_asset_test";
+    let _ = repository::upsert_script(script_uri, "function init() {}");
+    repository::set_script_privileged(script_uri, true).expect("Failed to set privileged");
+    repository::upsert_asset(repository::Asset {
+        uri: "versioned-app.js".to_string(),
+        mimetype: "application/javascript".to_string(),
+        content: b"console.log('v1');".to_vec(),
+        name: Some("versioned-app.js".to_string()),
+        script_uri: script_uri.to_string(),
+        created_at: std::time::SystemTime::now(),
+        updated_at: std::time::SystemTime::now(),
+        headers: std::collections::HashMap::new(),
+    })
+    .expect("Failed to create asset");
+
+    let script = r#"
+        function url(req) {
+          return { status: 200, body: routeRegistry.resolveAssetUrl("/static/versioned-app.js") };
+        }
+        function init(context) {
+          routeRegistry.registerAssetRoute("/static/versioned-app.js", "versioned-app.js", {
+            versioned: true,
+          });
+          routeRegistry.registerRoute("/versioned-app-url", "url", "GET");
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+    let client = reqwest::Client::new();
+
+    let hash = asset_registry::content_hash(b"console.log('v1');");
+    let hashed_url = format!("/static/versioned-app.{}.js", hash);
+    let resolved = client
+        .get(format!("http://127.0.0.1:{}/versioned-app-url", port))
+        .send()
+        .await
+        .expect("GET request failed")
+        .text()
+        .await
+        .expect("Failed to read body");
+    assert_eq!(resolved, hashed_url);
+
+    let response = client
+        .get(format!("http://127.0.0.1:{}{}", port, hashed_url))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("cache-control")
+            .and_then(|v| v.to_str().ok()),
+        Some(asset_registry::IMMUTABLE_CACHE_CONTROL)
+    );
+
+    let response = client
+        .get(format!("http://127.0.0.1:{}/static/versioned-app.js", port))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("cache-control")
+            .and_then(|v| v.to_str().ok()),
+        Some("no-cache")
+    );
+
+    // A hash that does not match the current content is not served
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/static/versioned-app.000000000000.js",
+            port
+        ))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 404);
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_image_asset_route_applies_query_transform() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let mut png = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(200, 100)
+        .write_to(&mut png, image::ImageFormat::Png)
+        .expect("Failed to encode PNG");
+    let script_uri = "https://example.com/image_transform_test";
+    let _ = repository::upsert_script(script_uri, "function init() {}");
+    repository::set_script_privileged(script_uri, true).expect("Failed to set privileged");
+    repository::upsert_asset(repository::Asset {
+        uri: "transform-test.png".to_string(),
+        mimetype: "image/png".to_string(),
+        content: png.into_inner(),
+        name: Some("transform-test.png".to_string()),
+        script_uri: script_uri.to_string(),
+        created_at: std::time::SystemTime::now(),
+        updated_at: std::time::SystemTime::now(),
+        headers: std::collections::HashMap::new(),
+    })
+    .expect("Failed to create asset");
+
+    let script = r#"
+        function init(context) {
+          routeRegistry.registerAssetRoute("/images/transform-test.png", "transform-test.png", {
+            imageTransforms: { widths: [50], formats: ["webp"] },
+          });
+          routeRegistry.registerAssetRoute("/images/original-test.png", "transform-test.png");
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/images/transform-test.png?w=50&format=webp",
+            port
+        ))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok()),
+        Some("image/webp")
+    );
+    let body = response.bytes().await.expect("Failed to read body");
+    let resized = image::load_from_memory(&body).expect("Response is not an image");
+    assert_eq!((resized.width(), resized.height()), (50, 25));
+
+    // Invalid parameters, and renditions the route doesn't list, are
+    // rejected; unrelated parameters are ignored
+    for query in ["w=0", "w=60", "format=avif", "w=50&h=10"] {
+        let response = client
+            .get(format!(
+                "http://127.0.0.1:{}/images/transform-test.png?{}",
+                port, query
+            ))
+            .send()
+            .await
+            .expect("GET request failed");
+        assert_eq!(response.status(), 400, "query {}", query);
+    }
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/images/transform-test.png?v=2",
+            port
+        ))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok()),
+        Some("image/png")
+    );
+
+    // Routes without imageTransforms serve the asset as stored
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/images/original-test.png?w=50&format=webp",
+            port
+        ))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    let body = response.bytes().await.expect("Failed to read body");
+    let original = image::load_from_memory(&body).expect("Response is not an image");
+    assert_eq!((original.width(), original.height()), (200, 100));
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_multipart_upload_spools_large_files() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/multipart_upload_test";
+    let script = r#"
+        function upload(context) {
+          const req = context.request;
+          return ResponseBuilder.json({
+            title: req.form.title,
+            body: req.body,
+            files: req.files.map((file) => ({
+              field: file.field,
+              size: file.size,
+              sha256: file.sha256,
+              path: file.path || null,
+              inline: typeof file.data === "string",
+              readLength: file.read().length
+            }))
+          });
+        }
+        function init(context) {
+          routeRegistry.registerRoute("/multipart-upload-test", "upload", "POST");
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    // Larger than the default 1 MB spool threshold
+    let large = vec![b'x'; 1536 * 1024];
+    let form = reqwest::multipart::Form::new()
+        .text("title", "Quarterly report")
+        .part(
+            "small",
+            reqwest::multipart::Part::bytes(b"hello".to_vec()).file_name("small.txt"),
+        )
+        .part(
+            "large",
+            reqwest::multipart::Part::bytes(large.clone()).file_name("large.bin"),
+        );
+    let response = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{}/multipart-upload-test", port))
+        .multipart(form)
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+
+    assert_eq!(body["title"], "Quarterly report");
+    assert!(body["body"].is_null());
+    let small = &body["files"][0];
+    assert_eq!(small["field"], "small");
+    assert_eq!(small["inline"], true);
+    assert!(small["path"].is_null());
+    assert_eq!(small["readLength"], 8);
+
+    let spooled = &body["files"][1];
+    assert_eq!(spooled["size"], large.len());
+    assert_eq!(spooled["inline"], false);
+    assert_eq!(spooled["readLength"], large.len().div_ceil(3) * 4);
+    assert_eq!(
+        spooled["sha256"],
+        hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&large))
+    );
+    // The spool file is removed once the request's worker lets go of it
+    let path = spooled["path"]
+        .as_str()
+        .expect("large file should be spooled");
+    let mut removed = false;
+    for _ in 0..50 {
+        if !std::path::Path::new(path).exists() {
+            removed = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(removed, "spool file {} should be removed", path);
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_route_upload_rules_and_file_methods() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/route_upload_rules_test";
+    let script = r#"
+        function avatar(context) {
+          const file = context.request.files[0];
+          const bytes = file.bytes();
+          return ResponseBuilder.json({
+            isBytes: bytes instanceof Uint8Array,
+            length: bytes.length,
+            first: bytes[0],
+            saved: file.saveAsAsset("avatars/" + file.filename)
+          });
+        }
+        function init(context) {
+          routeRegistry.registerRoute("/route-upload-rules-test", "avatar", "POST", {
+            uploads: { maxFileSize: 1024, maxFiles: 1, allowedTypes: ["image/*"] }
+          });
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let url = format!("http://127.0.0.1:{}/route-upload-rules-test", port);
+    let image = |name: &str, data: Vec<u8>, mime: &str| {
+        reqwest::multipart::Part::bytes(data)
+            .file_name(name.to_string())
+            .mime_str(mime)
+            .expect("valid mime type")
+    };
+    let client = reqwest::Client::new();
+
+    let form = reqwest::multipart::Form::new()
+        .part("avatar", image("a.png", vec![137, 80, 78, 71], "image/png"));
+    let response = client
+        .post(&url)
+        .multipart(form)
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(body["isBytes"], true);
+    assert_eq!(body["length"], 4);
+    assert_eq!(body["first"], 137);
+    let asset = repository::fetch_asset(script_uri, "avatars/a.png")
+        .unwrap_or_else(|| panic!("asset should be saved: {}", body["saved"]));
+    assert_eq!(asset.content, vec![137, 80, 78, 71]);
+    assert_eq!(asset.mimetype, "image/png");
+
+    // Rejected before the handler runs
+    let rejected = [
+        (
+            reqwest::multipart::Form::new().part("doc", image("a.pdf", vec![1], "application/pdf")),
+            415,
+        ),
+        (
+            reqwest::multipart::Form::new()
+                .part("avatar", image("big.png", vec![0; 2048], "image/png")),
+            413,
+        ),
+        (
+            reqwest::multipart::Form::new()
+                .part("a", image("a.png", vec![1], "image/png"))
+                .part("b", image("b.png", vec![2], "image/png")),
+            400,
+        ),
+    ];
+    for (form, status) in rejected {
+        let response = client
+            .post(&url)
+            .multipart(form)
+            .send()
+            .await
+            .expect("POST request failed");
+        assert_eq!(response.status(), status);
+    }
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_route_request_schema_validation() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/route_schema_test";
+    let script = r#"
+        function createTask(context) {
+          const req = context.request;
+          return ResponseBuilder.json({
+            id: req.params.id,
+            title: JSON.parse(req.body).title,
+            notify: req.query.notify
+          });
+        }
+        function init(context) {
+          routeRegistry.registerRoute("/route-schema-test/:id", "createTask", "POST", {
+            schema: {
+              params: { type: "object", properties: { id: { type: "integer" } } },
+              query: { type: "object", properties: { notify: { type: "boolean" } } },
+              body: {
+                type: "object",
+                required: ["title"],
+                properties: { title: { type: "string", minLength: 1 } },
+                additionalProperties: false
+              }
+            }
+          });
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}/route-schema-test", port);
+
+    let response = client
+        .post(format!("{}/7?notify=true", base))
+        .json(&serde_json::json!({ "title": "Write docs" }))
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(body["id"], "7");
+    assert_eq!(body["title"], "Write docs");
+    assert_eq!(body["notify"], "true");
+
+    // Rejected before the handler runs, with every violation listed
+    let response = client
+        .post(format!("{}/seven?notify=maybe", base))
+        .json(&serde_json::json!({ "title": "", "extra": 1 }))
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(response.status(), 400);
+    let body: serde_json::Value = response.json().await.expect("Response is not JSON");
+    assert_eq!(body["error"]["code"], "VALIDATION_ERROR");
+    let violations = body["error"]["context"]["violations"]
+        .as_array()
+        .expect("violations should be listed");
+    let mut found: Vec<String> = violations
+        .iter()
+        .map(|v| {
+            format!(
+                "{}:{}",
+                v["location"].as_str().unwrap(),
+                v["path"].as_str().unwrap()
+            )
+        })
+        .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        vec!["body:", "body:/title", "params:/id", "query:/notify"]
+    );
+
+    let response = client
+        .post(format!("{}/7", base))
+        .header("content-type", "application/json")
+        .body("{")
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(response.status(), 400);
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tus_resumable_upload() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/tus_upload_test";
+    let script = r#"
+        function uploaded(context) {
+          const file = context.request.files[0];
+          if (file.sha256 !== context.request.form.sha256) {
+            return { status: 422, body: "checksum mismatch", contentType: "text/plain" };
+          }
+          return { status: 200, body: "ok" };
+        }
+        function init(context) {
+          routeRegistry.registerUploadRoute("/tus-upload-test", {
+            onComplete: "uploaded",
+            asset: "tus/",
+            maxSize: 1024
+          });
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let base = format!("http://127.0.0.1:{}", port);
+    let endpoint = format!("{}/tus-upload-test", base);
+    let client = reqwest::Client::new();
+    let b64 =
+        |value: &str| base64::Engine::encode(&base64::engine::general_purpose::STANDARD, value);
+    let header = |response: &reqwest::Response, name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let options = client
+        .request(reqwest::Method::OPTIONS, &endpoint)
+        .send()
+        .await
+        .expect("OPTIONS request failed");
+    assert_eq!(options.status(), 204);
+    assert_eq!(header(&options, "tus-version").as_deref(), Some("1.0.0"));
+    assert_eq!(header(&options, "tus-max-size").as_deref(), Some("1024"));
+
+    let unversioned = client
+        .post(&endpoint)
+        .header("Upload-Length", "11")
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(unversioned.status(), 412);
+
+    let content = "hello world";
+    let sha256 = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(content));
+    let created = client
+        .post(&endpoint)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", content.len().to_string())
+        .header(
+            "Upload-Metadata",
+            format!(
+                "filename {},filetype {},sha256 {}",
+                b64("hello.txt"),
+                b64("text/plain"),
+                b64(&sha256)
+            ),
+        )
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(created.status(), 201);
+    let upload_url = format!(
+        "{}{}",
+        base,
+        header(&created, "location").expect("Location header")
+    );
+
+    let patch = |offset: usize, data: &'static str| {
+        client
+            .patch(&upload_url)
+            .header("Tus-Resumable", "1.0.0")
+            .header("Content-Type", "application/offset+octet-stream")
+            .header("Upload-Offset", offset.to_string())
+            .body(data)
+            .send()
+    };
+    let head = || {
+        client
+            .head(&upload_url)
+            .header("Tus-Resumable", "1.0.0")
+            .send()
+    };
+
+    let first = patch(0, "hello ").await.expect("PATCH request failed");
+    assert_eq!(first.status(), 204);
+    assert_eq!(header(&first, "upload-offset").as_deref(), Some("6"));
+
+    // Resuming reads the offset back
+    let status = head().await.expect("HEAD request failed");
+    assert_eq!(status.status(), 200);
+    assert_eq!(header(&status, "upload-offset").as_deref(), Some("6"));
+    assert_eq!(header(&status, "upload-length").as_deref(), Some("11"));
+
+    let stale = patch(0, "hello ").await.expect("PATCH request failed");
+    assert_eq!(stale.status(), 409);
+
+    let last = patch(6, "world").await.expect("PATCH request failed");
+    assert_eq!(last.status(), 204);
+    assert_eq!(header(&last, "upload-offset").as_deref(), Some("11"));
+
+    let asset = repository::fetch_asset(script_uri, "tus/hello.txt")
+        .expect("completed upload should be stored as an asset");
+    assert_eq!(asset.content, content.as_bytes());
+    assert_eq!(asset.mimetype, "text/plain");
+    assert_eq!(head().await.expect("HEAD request failed").status(), 404);
+
+    // The handler sees the file and can refuse it
+    let created = client
+        .post(&endpoint)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", "1")
+        .header("Upload-Metadata", format!("sha256 {}", b64("wrong")))
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(created.status(), 201);
+    let rejected = client
+        .patch(format!(
+            "{}{}",
+            base,
+            header(&created, "location").expect("Location header")
+        ))
+        .header("Tus-Resumable", "1.0.0")
+        .header("Content-Type", "application/offset+octet-stream")
+        .header("Upload-Offset", "0")
+        .body("x")
+        .send()
+        .await
+        .expect("PATCH request failed");
+    assert_eq!(rejected.status(), 422);
+    assert_eq!(rejected.text().await.unwrap(), "checksum mismatch");
+
+    let too_large = client
+        .post(&endpoint)
+        .header("Tus-Resumable", "1.0.0")
+        .header("Upload-Length", "2048")
+        .send()
+        .await
+        .expect("POST request failed");
+    assert_eq!(too_large.status(), 413);
+
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_structured_logs_carry_request_context() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    let context = TestContext::new();
+
+    let script_uri = "https://example.com/structured_log_test";
+    let _ = repository::clear_log_messages(script_uri);
+    let script = r#"
+        function placeOrder(req) {
+          console.info("Order placed", { orderId: "order-142", total: 12.5 });
+          console.log("plain", "message", 1);
+          return { status: 200, body: "ok" };
+        }
+        function init(context) {
+          routeRegistry.registerRoute("/structured-log-test", "placeOrder", "GET");
+          return { success: true };
+        }
+    "#;
+    let _ = repository::upsert_script(script_uri, script);
+
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let response = reqwest::Client::new()
+        .get(format!("http://127.0.0.1:{}/structured-log-test", port))
+        .send()
+        .await
+        .expect("GET request failed");
+    assert_eq!(response.status(), 200);
+    let request_id = response
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .expect("response should carry a request ID")
+        .to_string();
+
+    let page = repository::query_log_messages(&repository::LogQuery {
+        script_uri: Some(script_uri.to_string()),
+        request_id: Some(request_id.clone()),
+        ..Default::default()
+    })
+    .expect("Log query failed");
+    assert_eq!(page.entries.len(), 2);
+    let plain = &page.entries[0];
+    assert_eq!(plain.message, "plain message 1");
+    assert!(plain.data.is_none());
+
+    let order = &page.entries[1];
+    assert_eq!(order.message, "Order placed");
+    assert_eq!(order.level, "INFO");
+    assert_eq!(
+        order.data,
+        Some(serde_json::json!({ "orderId": "order-142", "total": 12.5 }))
+    );
+    let log_context = order.context.as_ref().expect("context should be captured");
+    assert_eq!(log_context["requestId"], request_id.as_str());
+    assert_eq!(log_context["handler"], "placeOrder");
+    assert_eq!(log_context["kind"], "httpRoute");
+    assert_eq!(log_context["path"], "/structured-log-test");
+
+    // Structured field filters use JSONB containment
+    let page = repository::query_log_messages(&repository::LogQuery {
+        script_uri: Some(script_uri.to_string()),
+        data: Some(serde_json::json!({ "orderId": "order-142" })),
+        ..Default::default()
+    })
+    .expect("Log query failed");
+    assert_eq!(page.entries.len(), 1);
+
+    let _ = repository::clear_log_messages(script_uri);
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_head_request_on_asset_route_strips_body() {
     if should_skip_integration_tests() {
@@ -330,6 +1201,7 @@ async fn test_head_request_on_asset_route_strips_body() {
         script_uri: script_uri.to_string(),
         created_at: std::time::SystemTime::now(),
         updated_at: std::time::SystemTime::now(),
+        headers: std::collections::HashMap::new(),
     })
     .expect("Failed to create asset");
 
@@ -45,6 +45,7 @@ fn test_asset(script_uri: &str, uri: &str, mimetype: &str, content: &[u8]) -> re
         created_at: now,
         updated_at: now,
         script_uri: script_uri.to_string(),
+        headers: HashMap::new(),
     }
 }
 
@@ -256,6 +257,8 @@ async fn imported_asset_module_executes_in_request_path() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     })
     .expect("request execution should succeed");
 
@@ -308,6 +311,8 @@ async fn imported_asset_root_module_executes_in_request_path() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     })
     .expect("request execution should succeed");
 
@@ -372,6 +377,8 @@ async fn imported_multiline_asset_root_module_executes_in_request_path() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     })
     .expect("request execution should succeed");
 
@@ -455,6 +462,8 @@ async fn imported_typescript_asset_module_with_type_exports_executes() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     })
     .expect("request execution should succeed");
 
@@ -633,6 +642,8 @@ async fn nested_asset_relative_import_chain_executes_in_request_path() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     })
     .expect("request execution should succeed");
 
@@ -3,7 +3,7 @@
 //! This module contains tests for core repository operations including:
 //! - Script lifecycle (create, read, update, delete)
 //! - Asset management
-//! - Log message storage and pruning
+//! - Log message storage, querying and pruning
 //! - GraphQL subscription schema configuration
 
 mod common;
@@ -169,6 +169,84 @@ async fn test_prune_keeps_latest_20_logs() {
     context.cleanup().await.expect("Failed to cleanup");
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_query_logs_filters_and_paginates() {
+    if std::env::var("DATABASE_URL").is_err() {
+        return;
+    }
+    let context = TestContext::new();
+    let port = context
+        .start_server()
+        .await
+        .expect("Server failed to start");
+    wait_for_server(port, 20).await.expect("Server not ready");
+
+    let test_uri = "test_query_logs_filters_and_paginates";
+    let _ = repository::clear_log_messages(test_uri);
+
+    for i in 0..5 {
+        repository::insert_log_message(test_uri, &format!("query-info-{}", i), "INFO");
+        repository::insert_log_message(test_uri, &format!("query-error-{}", i), "ERROR");
+    }
+    repository::insert_log_message(test_uri, "100% done_now", "INFO");
+
+    // Level filter with cursor pagination, newest first
+    let mut query = repository::LogQuery {
+        script_uri: Some(test_uri.to_string()),
+        levels: vec!["error".to_string()],
+        limit: Some(2),
+        ..Default::default()
+    };
+    let mut messages = Vec::new();
+    loop {
+        let page = repository::query_log_messages(&query).expect("Log query failed");
+        assert!(page.entries.len() <= 2);
+        messages.extend(page.entries.iter().map(|e| e.message.clone()));
+        match page.next_cursor {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+    assert_eq!(
+        messages,
+        (0..5)
+            .rev()
+            .map(|i| format!("query-error-{}", i))
+            .collect::<Vec<_>>()
+    );
+
+    // Text search matches LIKE wildcards literally
+    let page = repository::query_log_messages(&repository::LogQuery {
+        script_uri: Some(test_uri.to_string()),
+        search: Some("0% DONE_".to_string()),
+        ..Default::default()
+    })
+    .expect("Log query failed");
+    assert_eq!(page.entries.len(), 1);
+    assert_eq!(page.entries[0].message, "100% done_now");
+    assert!(page.next_cursor.is_none());
+
+    // Time range excluding everything
+    let page = repository::query_log_messages(&repository::LogQuery {
+        script_uri: Some(test_uri.to_string()),
+        until: Some(chrono::Utc::now() - chrono::Duration::hours(1)),
+        ..Default::default()
+    })
+    .expect("Log query failed");
+    assert!(page.entries.is_empty());
+
+    assert!(
+        repository::query_log_messages(&repository::LogQuery {
+            cursor: Some("not-a-cursor".to_string()),
+            ..Default::default()
+        })
+        .is_err()
+    );
+
+    let _ = repository::clear_log_messages(test_uri);
+    context.cleanup().await.expect("Failed to cleanup");
+}
+
 // ============================================================================
 // Asset Repository Tests
 // ============================================================================
@@ -208,6 +286,7 @@ async fn test_asset_management() {
         created_at: now,
         updated_at: now,
         script_uri: "https://example.com/core".to_string(),
+        headers: std::collections::HashMap::new(),
     };
     let _ = repository::upsert_asset(test_asset);
 
@@ -10,6 +10,7 @@
 //! - Stream name validation
 //! - Header injection prevention
 //! - Secure global context execution
+//! - Capability manifests gating outbound globals
 
 /// Security integration tests
 ///
@@ -18,9 +19,10 @@
 /// - Input validation blocks dangerous patterns
 /// - Rate limiting prevents abuse
 /// - Security bypasses are impossible
+use aiwebengine::security::script_manifests::ScriptCapability;
 use aiwebengine::security::{
-    Capability, InputValidator, RateLimitKey, RateLimiter, SecureOperations, UpsertScriptRequest,
-    UserContext,
+    Capability, GlobalSecurityConfig, InputValidator, RateLimitKey, RateLimiter,
+    SecureGlobalContext, SecureOperations, UpsertScriptRequest, UserContext,
 };
 use base64::{Engine, engine::general_purpose};
 use std::collections::HashMap;
@@ -725,6 +727,8 @@ async fn test_secure_request_execution() {
         route_params: None,
         auth_context: None,
         uploaded_files: None,
+        timeout_ms: None,
+        tenant: None,
     };
     let request_result = execute_script_for_request_secure(request_params);
 
@@ -824,3 +828,112 @@ async fn test_asset_upsert_sets_script_uri() {
     assert_eq!(asset.mimetype, "text/plain");
     assert_eq!(String::from_utf8(asset.content).unwrap(), "test content");
 }
+
+/// Names of the globals wired into a context set up with `config`
+fn defined_globals(config: GlobalSecurityConfig, names: &[&str]) -> Vec<String> {
+    let context = SecureGlobalContext::new_with_config(
+        UserContext::admin("manifest_admin".to_string()),
+        config,
+    );
+    let runtime = rquickjs::Runtime::new().expect("Should create runtime");
+    let ctx = rquickjs::Context::full(&runtime).expect("Should create context");
+    ctx.with(|ctx| {
+        context
+            .setup_secure_functions(&ctx, "https://example.com/manifest", None)
+            .expect("Should set up globals");
+        names
+            .iter()
+            .filter(|name| {
+                ctx.eval::<String, _>(format!("typeof {}", name))
+                    .is_ok_and(|kind| kind != "undefined")
+            })
+            .map(|name| name.to_string())
+            .collect()
+    })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unapproved_script_lacks_outbound_globals() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    setup_env().await;
+    let outbound = [
+        "fetch",
+        "fetchAsync",
+        "McpClient",
+        "email",
+        "notify",
+        "llm",
+        "embeddings",
+        "moderation",
+        "tasks",
+    ];
+
+    // A script without an approved manifest gets none of them
+    let mut config = GlobalSecurityConfig::default();
+    config.restrict_to(&[]);
+    assert_eq!(defined_globals(config, &outbound), Vec::<String>::new());
+
+    // Each is wired only with its own capability
+    let mut config = GlobalSecurityConfig::default();
+    config.restrict_to(&[ScriptCapability::Email, ScriptCapability::Llm]);
+    assert_eq!(
+        defined_globals(config, &outbound),
+        vec!["email", "llm", "embeddings", "moderation"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mcp_client_requires_fetch_and_secrets() {
+    if should_skip_integration_tests() {
+        return;
+    }
+    setup_env().await;
+
+    // With fetch but without secrets McpClient exists, but no call may name
+    // a secret
+    let mut config = GlobalSecurityConfig::default();
+    config.restrict_to(&[ScriptCapability::Fetch]);
+    assert_eq!(
+        defined_globals(config.clone(), &["McpClient"]),
+        vec!["McpClient"]
+    );
+
+    let context = SecureGlobalContext::new_with_config(
+        UserContext::admin("manifest_admin".to_string()),
+        config,
+    );
+    let runtime = rquickjs::Runtime::new().expect("Should create runtime");
+    let ctx = rquickjs::Context::full(&runtime).expect("Should create context");
+    ctx.with(|ctx| {
+        context
+            .setup_secure_functions(&ctx, "https://example.com/manifest", None)
+            .expect("Should set up globals");
+        let outcome: String = ctx
+            .eval(
+                r#"
+                const client = JSON.stringify({
+                    serverUrl: "https://mcp.example.com",
+                    secretIdentifier: "github_token",
+                });
+                [
+                    () => McpClient.constructor("https://mcp.example.com", "github_token"),
+                    () => McpClient._listTools(client),
+                    () => McpClient._callTool(client, "search", "{}"),
+                ].map((call) => {
+                    try {
+                        call();
+                        return "allowed";
+                    } catch (e) {
+                        return String(e.message).includes("secrets capability")
+                            ? "denied"
+                            : "failed: " + e.message;
+                    }
+                }).join(",")
+                "#,
+            )
+            .expect("Should run script");
+        assert_eq!(outcome, "denied,denied,denied");
+    });
+}